@@ -1,4 +1,4 @@
-use std::{str::FromStr, time::Duration};
+use std::{process::ExitStatus, str::FromStr, time::Duration};
 
 use alumet::{
     agent::{
@@ -9,34 +9,81 @@ use alumet::{
         watch,
     },
     pipeline,
-    plugin::PluginMetadata,
+    plugin::{PluginMetadata, rust::InvalidConfigField},
     static_plugins,
 };
-use alumet_agent::{exec_hints, init_logger};
+use alumet_agent::{control, exec_hints, health, init_logger, logging, word_distance};
+#[cfg(unix)]
+use alumet_agent::{reload, systemd};
 use anyhow::Context;
 use clap::{Args, FromArgMatches};
-use cli::{ConfigArgs, ConfigCommand, PluginsArgs, PluginsCommand};
+use cli::{ConfigArgs, ConfigCommand, ControlArgs, ControlCommand, PluginsArgs, PluginsCommand, RegenArgs};
 use config::GeneralConfig;
 
 const BINARY: &str = env!("CARGO_BIN_NAME");
 
+/// Counts the allocations of each thread, for the resource usage accounting (see `GeneralConfig::resource_usage`).
+#[cfg(feature = "allocation-tracking")]
+#[global_allocator]
+static ALLOCATOR: pipeline::usage::TrackingAllocator = pipeline::usage::TrackingAllocator;
+
+/// Prefix of the environment variables that override the config, ex. `ALUMET__PLUGINS__CSV__OUTPUT_PATH`.
+const ENV_OVERRIDE_PREFIX: &str = "ALUMET__";
+
 /// Loads the available plugins.
 fn load_plugins_metadata() -> Vec<PluginMetadata> {
     // plugins that work on every target
+    #[cfg_attr(
+        not(any(target_os = "linux", target_os = "macos", target_os = "windows")),
+        allow(unused_mut)
+    )]
     let mut plugins = static_plugins![
         plugin_csv::CsvPlugin,
         plugin_prometheus_exporter::PrometheusPlugin,
+        plugin_prometheus_remote_write::RemoteWritePlugin,
+        plugin_graphite::GraphitePlugin,
         plugin_influxdb::InfluxDbPlugin,
+        plugin_jsonl::JsonLinesPlugin,
         plugin_mongodb::MongoDbPlugin,
+        plugin_mqtt::MqttPlugin,
+        plugin_redis::RedisPlugin,
         plugin_relay::client::RelayClientPlugin,
         plugin_relay::server::RelayServerPlugin,
+        plugin_s3::S3Plugin,
+        plugin_splunk::SplunkPlugin,
+        plugin_sqlite::SqlitePlugin,
+        plugin_statsd::StatsdPlugin,
+        plugin_zabbix::ZabbixPlugin,
+        plugin_amqp::AmqpPlugin,
+        plugin_websocket::WebSocketPlugin,
+        plugin_rrd::RrdPlugin,
+        plugin_gcp_monitoring::GcpMonitoringPlugin,
+        plugin_azure_monitor::AzureMonitorPlugin,
+        plugin_cloudwatch::CloudWatchPlugin,
+        plugin_datadog::DatadogPlugin,
+        plugin_victoriametrics::VictoriaMetricsPlugin,
+        plugin_rollup::RollupPlugin,
+        plugin_dbus::DbusPlugin,
         plugin_opentelemetry::OpenTelemetryPlugin,
+        plugin_parquet::ParquetPlugin,
+        plugin_parquet::ipc::ArrowIpcPlugin,
         plugin_aggregation::AggregationPlugin,
         plugin_energy_attribution::EnergyAttributionPlugin,
         plugin_energy_estimation_tdp::EnergyEstimationTdpPlugin,
         plugin_elasticsearch::ElasticSearchPlugin,
+        plugin_grpc_control::GrpcControlPlugin,
+        plugin_http_control::HttpControlPlugin,
         plugin_kwollect_input::KwollectPluginInput,
         plugin_kwollect_output::KwollectPlugin,
+        plugin_tui::TuiPlugin,
+        plugin_pdu::PduPlugin,
+        plugin_otlp_receiver::OtlpReceiverPlugin,
+        plugin_statsd_input::StatsdInputPlugin,
+        plugin_collectd::CollectdPlugin,
+        plugin_replay::ReplayPlugin,
+        plugin_influxdb_input::InfluxDbInputPlugin,
+        plugin_carbon_intensity::CarbonIntensityPlugin,
+        plugin_weather::WeatherPlugin,
     ];
 
     // plugins that only work on Linux
@@ -55,9 +102,33 @@ fn load_plugins_metadata() -> Vec<PluginMetadata> {
             plugin_nvidia_nvml::NvmlPlugin,
             plugin_process_to_cgroup_bridge::ProcessToCgroupBridgePlugin,
             plugin_nvidia_jetson::JetsonPlugin,
+            plugin_amd_gpu::AmdGpuPlugin,
+            plugin_intel_gpu::IntelGpuPlugin,
+            plugin_ipmi::IpmiPlugin,
+            plugin_modbus::ModbusPlugin,
+            plugin_ebpf::EbpfPlugin,
+            plugin_docker::DockerPlugin,
+            plugin_libvirt::LibvirtPlugin,
+            plugin_smart::SmartPlugin,
+            plugin_power_supply::PowerSupplyPlugin,
+            plugin_systemd::SystemdPlugin,
+            plugin_fpga::FpgaPlugin,
+            plugin_i2c_power::I2cPowerPlugin,
         ]);
     }
 
+    // plugins that only work on macOS
+    #[cfg(target_os = "macos")]
+    plugins.extend(static_plugins![plugin_powermetrics::PowermetricsPlugin]);
+
+    // plugins that only work on Windows
+    #[cfg(target_os = "windows")]
+    plugins.extend(static_plugins![plugin_windows_perf::WindowsPerfPlugin]);
+
+    // optional plugins
+    #[cfg(feature = "wasm")]
+    plugins.extend(static_plugins![plugin_wasm::WasmPlugin]);
+
     plugins
 }
 
@@ -86,6 +157,9 @@ fn main() -> anyhow::Result<()> {
     // Parse CLI arguments and handle some special flags like --version and --help.
     let matches = cmd.get_matches();
     let mut args = cli::Cli::from_arg_matches(&matches).map_err(|e| e.exit()).unwrap();
+    if let Some(cli::Command::ExecPhases(exec_args)) = &args.command {
+        exec::check_phases(&exec_args.phases).context("invalid phases")?;
+    }
 
     // Special flags like --help will exit. In other cases, we continue.
     print_welcome();
@@ -97,7 +171,7 @@ fn main() -> anyhow::Result<()> {
     }
 
     // Run CLI commands that run before the config is loaded.
-    if run_command_no_config(&args, &plugins)? {
+    if run_command_no_config(&args, &mut plugins)? {
         return Ok(());
     }
 
@@ -106,14 +180,21 @@ fn main() -> anyhow::Result<()> {
     let default_config_provider: Box<dyn DefaultConfigProvider> = if args.common.no_default_config {
         Box::new(NoDefaultConfigProvider)
     } else {
-        Box::new(AutoDefaultConfigProvider::new(&plugins, config::GeneralConfig::default))
+        Box::new(
+            AutoDefaultConfigProvider::new(&plugins, config::GeneralConfig::default)
+                .with_general_comments(config::GENERAL_CONFIG_COMMENTS.iter().copied()),
+        )
     };
     let mut config = agent::config::Loader::parse_file(&args.common.config)
         .or_default_boxed(default_config_provider, true)
         .substitute_env_variables(true)
-        .with_override(config_override)
+        .with_profiles(&args.common.profile)
+        .with_env_overrides(ENV_OVERRIDE_PREFIX)
+        .with_override(config_override.clone())
         .load()
         .context("could not load config file")?;
+    #[cfg(unix)]
+    let loaded_config = config.clone();
 
     // Extract the config of each plugin.
     // If not set by CLI args, use the config to determine which plugins are enabled.
@@ -128,6 +209,12 @@ fn main() -> anyhow::Result<()> {
     // Extract non-plugin config.
     let config = config.try_into::<GeneralConfig>().context("invalid general config")?;
 
+    // Apply the log levels and format of the config.
+    let log_targets = plugins
+        .metadata(PluginFilter::Any)
+        .map(|p| (p.name.as_str(), p.log_target.as_deref()));
+    logging::configure(&config.log, log_targets).context("invalid log config")?;
+
     // Run CLI commands that only require the config and run before the pipeline starts.
     if run_command_no_measurement(&args, &config, &plugins).context("command failed")? {
         return Ok(());
@@ -135,12 +222,90 @@ fn main() -> anyhow::Result<()> {
 
     // begin the creation of the pipeline (we have some settings to apply to it)
     let mut pipeline = pipeline::Builder::new();
-    apply_pipeline_settings(&args, &config, &mut pipeline);
+    apply_pipeline_settings(&args, &config, &mut pipeline).context("invalid general config")?;
 
     // start Alumet with the pipeline and plugins
-    let agent = agent::Builder::from_pipeline(plugins, pipeline)
+    let mut agent_builder = agent::Builder::from_pipeline(plugins, pipeline);
+    if let Some(policy) = config.capability_policy {
+        agent_builder = agent_builder.capability_policy(policy.into());
+    }
+    if let Some(state_directory) = &config.state_directory {
+        agent_builder = agent_builder.state_directory(state_directory);
+    }
+    if matches!(args.command, Some(cli::Command::Validate)) {
+        let report = agent_builder
+            .dry_run()
+            .map_err(|e| with_config_location(e, &args.common.config))
+            .context("validation failed")?;
+        print_dry_run_report(&report);
+        return Ok(());
+    }
+
+    // serve the health endpoint during the startup, to report that the agent is starting
+    let health = match &config.health {
+        Some(health_config) => {
+            let (state, addr) =
+                health::start_server(&health_config.address).context("could not start the health server")?;
+            log::info!("Health endpoint available at http://{addr}/health");
+            Some(state)
+        }
+        None => None,
+    };
+
+    let agent = agent_builder
         .build_and_start()
+        .map_err(|e| with_config_location(e, &args.common.config))
         .context("startup failure")?;
+    if let Some(health) = health {
+        health.set_running(agent.pipeline.failures(), agent.pipeline.control_handle());
+    }
+
+    // reload the config file on SIGHUP
+    #[cfg(unix)]
+    {
+        let config_file = args.common.config.clone();
+        let profiles = args.common.profile.clone();
+        let load_config = move || {
+            agent::config::Loader::parse_file(&config_file)
+                .substitute_env_variables(true)
+                .with_profiles(&profiles)
+                .with_env_overrides(ENV_OVERRIDE_PREFIX)
+                .with_override(config_override.clone())
+                .load()
+                .context("could not load config file")
+        };
+        let running_plugins = agent.initialized_plugins.iter().map(|p| p.name().to_owned()).collect();
+        let reload_task = reload::reload_on_sighup(
+            loaded_config,
+            load_config,
+            args.common.plugins.is_none(),
+            agent.pipeline.control_handle(),
+            running_plugins,
+        );
+        agent.pipeline.async_runtime().spawn(async move {
+            if let Err(e) = reload_task.await {
+                log::error!("Hot reload of the configuration is not available: {e:#}");
+            }
+        });
+    }
+
+    // tell systemd that we are ready, and keep its watchdog happy
+    #[cfg(unix)]
+    if systemd::is_enabled() {
+        let rt = agent.pipeline.async_runtime();
+        rt.spawn(systemd::shutdown_on_sigterm(agent.pipeline.control_handle()));
+        rt.spawn(systemd::notify_on_shutdown(agent.pipeline.control_handle()));
+        if let Some(interval) = systemd::watchdog_interval() {
+            log::info!(
+                "systemd watchdog enabled, interval: {}",
+                humantime::format_duration(interval)
+            );
+            rt.spawn(systemd::watchdog(interval));
+        }
+        if let Err(e) = systemd::notify("READY=1") {
+            log::warn!("Failed to notify systemd: {e}");
+        }
+    }
 
     // run the provided command, the default is Run
     match args.command.take().unwrap_or(cli::Command::Run) {
@@ -151,19 +316,12 @@ fn main() -> anyhow::Result<()> {
         cli::Command::Exec(exec_args) => {
             let timeout = Duration::from_secs(5);
             let res = exec::exec_process(agent, exec_args.program, exec_args.args, timeout);
-            if let Err(err @ exec::ExecError::ProcessSpawn(program, e)) = &res {
-                match e.kind() {
-                    std::io::ErrorKind::NotFound => {
-                        panic!("{}", exec_hints::handle_not_found(program.clone(), Vec::new()));
-                    }
-                    std::io::ErrorKind::PermissionDenied => {
-                        panic!("{}", exec_hints::handle_permission_denied(program.clone()));
-                    }
-                    _ => {
-                        panic!("{}", err);
-                    }
-                }
-            }
+            handle_exec_result(res);
+        }
+        cli::Command::ExecPhases(exec_args) => {
+            let timeout = Duration::from_secs(5);
+            let res = exec::exec_phases(agent, exec_args.phases, timeout);
+            handle_exec_result(res);
         }
         cli::Command::Watch(process) => {
             let shutdown_timeout = Duration::from_secs(5);
@@ -184,6 +342,103 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// If the error has been caused by an invalid field in the config file, adds the location of the field to the error.
+fn with_config_location(error: anyhow::Error, config_file: &str) -> anyhow::Error {
+    let position = error.downcast_ref::<InvalidConfigField>().and_then(|field| {
+        let document = std::fs::read_to_string(config_file).ok()?;
+        agent::config::find_value_position(&document, &field.path)
+    });
+    match position {
+        Some((line, column)) => error.context(format!("invalid config at {config_file}:{line}:{column}")),
+        None => error,
+    }
+}
+
+/// Prints the result of the `validate` command.
+fn print_dry_run_report(report: &agent::builder::DryRunReport) {
+    println!("The configuration is valid.\n");
+    println!("Enabled plugins:");
+    for (name, version) in &report.plugins {
+        println!("- {name} v{version}");
+    }
+    println!("\nDisabled plugins:");
+    for name in &report.disabled_plugins {
+        println!("- {name}");
+    }
+    println!("\nMetrics:");
+    for m in &report.metrics {
+        println!("- {}: {} ({})", m.name, m.value_type, m.unit);
+    }
+    println!("\nSources:");
+    for s in &report.sources {
+        println!("- {s}");
+    }
+    println!("\nTransforms:");
+    for t in &report.transforms {
+        println!("- {t}");
+    }
+    println!("\nOutputs:");
+    for o in &report.outputs {
+        println!("- {o}");
+    }
+}
+
+/// Exits like the executed child, or panics with a helpful message if it could not be spawned.
+///
+/// If the child could not be awaited, or if the agent could not shut down properly,
+/// logs the error and exits with a non-zero code.
+fn handle_exec_result(res: Result<ExitStatus, exec::ExecError>) {
+    match res {
+        Ok(status) => {
+            // exit like the child, so that scripts can check whether the command has succeeded
+            if !status.success() {
+                std::process::exit(exec::exit_code(status));
+            }
+        }
+        Err(ref err @ exec::ExecError::ProcessSpawn(ref program, ref e)) => match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                panic!("{}", exec_hints::handle_not_found(program.clone(), Vec::new()));
+            }
+            std::io::ErrorKind::PermissionDenied => {
+                panic!("{}", exec_hints::handle_permission_denied(program.clone()));
+            }
+            _ => {
+                panic!("{}", err);
+            }
+        },
+        Err(err) => {
+            log::error!("{:#}", anyhow::Error::new(err));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Checks that every name refers to a plugin of the set.
+///
+/// The error suggests the closest plugin name, if there is one.
+fn check_plugin_names(plugins: &PluginSet, names: &[String]) -> anyhow::Result<()> {
+    for name in names {
+        if plugins.get_plugin(name).is_some() {
+            continue;
+        }
+        let closest = plugins
+            .metadata(PluginFilter::Any)
+            .map(|p| {
+                (
+                    word_distance::distance_with_adjacent_transposition(name, &p.name),
+                    &p.name,
+                )
+            })
+            .filter(|(distance, _)| *distance < 3)
+            .min();
+        return match closest {
+            Some((_, suggestion)) => Err(anyhow::anyhow!("unknown plugin '{name}', did you mean '{suggestion}'?")),
+            None => Err(anyhow::anyhow!("unknown plugin '{name}'")),
+        };
+    }
+    Ok(())
+}
+
 /// Prints a short welcome message.
 fn print_welcome() {
     // It is useful to have the precise version of the agent in the logs.
@@ -199,21 +454,49 @@ fn print_welcome() {
 /// If selected by the CLI user, runs a command that does not need the config file.
 ///
 /// Returns `true` if a command was run (in which case you probably should stop here).
-fn run_command_no_config(args: &cli::Cli, plugins: &PluginSet) -> anyhow::Result<bool> {
+fn run_command_no_config(args: &cli::Cli, plugins: &mut PluginSet) -> anyhow::Result<bool> {
     use cli::Command;
 
     match args.command {
         Some(Command::Config(ConfigArgs {
-            command: ConfigCommand::Regen,
+            command: ConfigCommand::Regen(RegenArgs {
+                plugins: ref selected_plugins,
+            }),
         })) => {
-            // (re)generate the default config
+            // (re)generate the default config, with the selected plugins only
             let file = &args.common.config;
-            let provider = AutoDefaultConfigProvider::new(plugins, config::GeneralConfig::default);
+            if let Some(selected) = selected_plugins {
+                check_plugin_names(plugins, selected)?;
+                plugins.enable_only(selected);
+            }
+            let provider = AutoDefaultConfigProvider::new(plugins, config::GeneralConfig::default)
+                .with_general_comments(config::GENERAL_CONFIG_COMMENTS.iter().copied());
             let new_config = provider.default_config_string()?;
             std::fs::write(file, new_config)?;
             log::info!("Default configuration file written to: {file}");
             Ok(true)
         }
+        Some(Command::Control(ControlArgs {
+            ref socket,
+            ref command,
+        })) => {
+            // send a command to a running agent
+            let line = match command {
+                ControlCommand::TriggerSource { matcher } => {
+                    format!("control {} trigger-now", control::source_pattern(matcher))
+                }
+                ControlCommand::SetInterval { matcher, interval } => control::set_interval_command(matcher, *interval),
+                ControlCommand::Pause { pattern } => format!("control {pattern} pause"),
+                ControlCommand::Resume { pattern } => format!("control {pattern} resume"),
+                ControlCommand::List { pattern } => format!("list {pattern}"),
+                ControlCommand::Shutdown => String::from("shutdown"),
+            };
+            let output = control::send_command(socket, &line).context("control command failed")?;
+            for l in output {
+                println!("{l}");
+            }
+            Ok(true)
+        }
         Some(Command::Plugins(PluginsArgs {
             status: false,
             command: PluginsCommand::List,
@@ -258,7 +541,11 @@ fn run_command_no_measurement(args: &cli::Cli, _config: &GeneralConfig, plugins:
 }
 
 /// Setup the measurement pipeline according to CLI args and config file.
-fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &mut pipeline::Builder) {
+fn apply_pipeline_settings(
+    args: &cli::Cli,
+    config: &GeneralConfig,
+    pipeline: &mut pipeline::Builder,
+) -> anyhow::Result<()> {
     // config file
     if let Some(max_update_interval) = config.max_update_interval {
         pipeline.trigger_constraints_mut().max_update_interval = max_update_interval.into_inner();
@@ -266,6 +553,21 @@ fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &m
     if let Some(source_channel_size) = config.source_channel_size {
         *pipeline.source_channel_size() = source_channel_size;
     }
+    for (metric, rule) in &config.sampling {
+        pipeline.sampling_rules_mut().set(metric, rule.to_owned().into());
+    }
+    if let Some(usage) = &config.resource_usage {
+        let settings = pipeline.resource_usage_mut();
+        settings.enabled = true;
+        settings.self_metrics_interval = usage.poll_interval.map(|i| i.into_inner());
+    }
+    for (primary, failover) in &config.failover {
+        let rule = failover
+            .to_rule()
+            .with_context(|| format!("invalid failover of {primary}"))?;
+        let primary = config::parse_output_name(primary).context("invalid failover")?;
+        pipeline.failover_rules_mut().set(primary, rule);
+    }
 
     // cli arguments
     if let Some(max_update_interval) = args.common.max_update_interval {
@@ -274,10 +576,20 @@ fn apply_pipeline_settings(args: &cli::Cli, config: &GeneralConfig, pipeline: &m
     if let Some(source_channel_size) = args.common.source_channel_size {
         *pipeline.source_channel_size() = source_channel_size;
     }
-    if matches!(args.command, Some(cli::Command::Exec(_))) {
-        // the "exec" command requires event-based source trigger
+    if matches!(args.command, Some(cli::Command::Exec(_) | cli::Command::ExecPhases(_))) {
+        // the "exec" commands require event-based source trigger
         pipeline.trigger_constraints_mut().allow_manual_trigger = true;
     }
+    if matches!(args.command, Some(cli::Command::ExecPhases(_))) {
+        // tag the measurements with the phase during which they have been taken
+        let transform = exec::PhaseTransform::new();
+        pipeline.add_transform_builder(
+            pipeline::naming::PluginName(String::from("alumet")),
+            "phases",
+            Box::new(move |_| Ok(Box::new(transform))),
+        )?;
+    }
+    Ok(())
 }
 
 /// Parses the config overrides provided on the command line, and merges them into a single table.
@@ -359,8 +671,9 @@ fn agent_version() -> String {
 /// To apply "advanced" tweaks, we combine the "derive" and "builder" APIs of clap.
 /// See https://docs.rs/clap/latest/clap/_derive/index.html#mixing-builder-and-derive-apis
 mod cli {
+    use alumet::agent::exec;
     use clap::{Args, Parser, Subcommand};
-    use std::time::Duration;
+    use std::{path::PathBuf, time::Duration};
 
     // NOTE: the doc comment attached to `Cli` is used by clap as the description of
     // the application. It is displayed at the start of the help message.
@@ -385,6 +698,11 @@ mod cli {
         /// Execute a command and observe its process.
         Exec(ExecArgs),
 
+        /// Execute several commands, one after the other, and observe each of them as a distinct phase.
+        ///
+        /// Example: `exec-phases --phase 'warmup=./bench --warmup' --phase 'benchmark=./bench'`
+        ExecPhases(ExecPhasesArgs),
+
         /// Watch a PID and observe it until its end
         Watch(Process),
 
@@ -393,6 +711,18 @@ mod cli {
 
         /// Get plugins information.
         Plugins(PluginsArgs),
+
+        /// Control a running agent through its control socket.
+        ///
+        /// The running agent must have the `socket-control` plugin enabled.
+        Control(ControlArgs),
+
+        /// Check the configuration and the plugins, without measuring anything.
+        ///
+        /// The plugins are initialized and started, then the elements that they would
+        /// have added to the measurement pipeline are listed. No source is triggered.
+        #[command(alias = "dry-run")]
+        Validate,
     }
 
     /// CLI arguments for the `exec` command.
@@ -402,10 +732,33 @@ mod cli {
         pub program: String,
 
         /// Arguments to the program.
-        #[arg(trailing_var_arg = true)]
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         pub args: Vec<String>,
     }
 
+    /// CLI arguments for the `exec-phases` command.
+    #[derive(Args)]
+    pub struct ExecPhasesArgs {
+        /// A phase, of the form `name=program arg1 arg2...`.
+        ///
+        /// The arguments are separated by whitespace and cannot be quoted: use a script for complex commands.
+        /// The phases are run in the order in which they are given, and their names must be unique.
+        /// If a phase fails, the next ones are skipped, except the phase named `teardown`, which always runs.
+        #[arg(long = "phase", required = true, value_parser = parse_phase)]
+        pub phases: Vec<exec::Phase>,
+    }
+
+    fn parse_phase(s: &str) -> Result<exec::Phase, String> {
+        let (name, command) = s.split_once('=').ok_or_else(|| String::from("expected name=command"))?;
+        let mut words = command.split_whitespace().map(String::from);
+        let program = words.next().ok_or_else(|| format!("empty command for phase {name}"))?;
+        Ok(exec::Phase {
+            name: name.trim().to_owned(),
+            program,
+            args: words.collect(),
+        })
+    }
+
     /// CLI arguments for the `watch` command.
     #[derive(Args)]
     pub struct Process {
@@ -424,7 +777,16 @@ mod cli {
         /// Regenerate the configuration file and stop.
         ///
         /// If the file exists, it will be overwritten.
-        Regen,
+        Regen(RegenArgs),
+    }
+
+    #[derive(Args)]
+    pub struct RegenArgs {
+        /// Only include these plugins, separated by commas, ex. `rapl,csv`.
+        ///
+        /// By default, every enabled plugin is included.
+        #[arg(long, value_delimiter = ',')]
+        pub plugins: Option<Vec<String>>,
     }
 
     #[derive(Args)]
@@ -446,6 +808,64 @@ mod cli {
         List,
     }
 
+    #[derive(Args)]
+    pub struct ControlArgs {
+        /// Path to the control socket of the running agent.
+        #[arg(long, env = "ALUMET_CONTROL_SOCKET", default_value = "alumet-control.sock")]
+        pub socket: PathBuf,
+
+        #[command(subcommand)]
+        pub command: ControlCommand,
+    }
+
+    /// Commands for `control`.
+    ///
+    /// Patterns have the form `kind/plugin/element`, for instance `sources/rapl/*`.
+    /// Matchers are restricted to sources and have the form `plugin/source`, for instance `rapl/*`.
+    #[derive(Subcommand)]
+    pub enum ControlCommand {
+        /// Poll the matching sources now.
+        ///
+        /// The sources must accept manual triggers.
+        TriggerSource {
+            /// Sources to trigger, ex. `rapl/*`.
+            matcher: String,
+        },
+
+        /// Change the time between two measurements of the matching sources.
+        SetInterval {
+            /// Sources to reconfigure, ex. `rapl/*`.
+            matcher: String,
+            /// New interval, ex. `500ms`.
+            #[arg(value_parser = humantime_serde::re::humantime::parse_duration)]
+            interval: Duration,
+        },
+
+        /// Pause the matching elements.
+        Pause {
+            /// Elements to pause, ex. `sources/procfs/*`.
+            #[arg(default_value = "sources")]
+            pattern: String,
+        },
+
+        /// Resume the matching elements.
+        Resume {
+            /// Elements to resume, ex. `sources/procfs/*`.
+            #[arg(default_value = "sources")]
+            pattern: String,
+        },
+
+        /// List the matching elements.
+        List {
+            /// Elements to list, ex. `outputs`.
+            #[arg(default_value = "*")]
+            pattern: String,
+        },
+
+        /// Stop the agent.
+        Shutdown,
+    }
+
     /// Common CLI arguments.
     ///
     /// # Example and tip
@@ -481,6 +901,13 @@ mod cli {
         #[arg(long)]
         pub config_override: Option<Vec<String>>,
 
+        /// Config profiles to apply, separated by commas, ex. `g5k-production`.
+        ///
+        /// A profile is defined in the config file, in the `profiles.<name>` table,
+        /// and overrides the rest of the config.
+        #[arg(long, env = "ALUMET_PROFILE", value_delimiter = ',')]
+        pub profile: Vec<String>,
+
         /// List of plugins to enable, separated by commas, ex. `csv,rapl`.
         ///
         /// All the other plugins will be disabled.
@@ -529,15 +956,250 @@ mod cli {
 /// and to write the default configuration to the TOML config file,
 /// therefore the structs derive [`serde::Deserialize`] and [`serde::Serialize`].
 mod config {
-    use std::time::Duration;
+    use std::{collections::BTreeMap, num::NonZeroU64, path::PathBuf, time::Duration};
 
+    use alumet::pipeline::failover::FailoverRule;
+    use alumet::pipeline::naming::OutputName;
+    use alumet::pipeline::sampling::SamplingRule;
+    use alumet::plugin::capability::CapabilityPolicy;
+    use alumet_agent::{health::HealthConfig, logging::LogConfig};
     use serde::{Deserialize, Serialize};
 
+    /// Comments of the general options, written in the generated config file.
+    pub const GENERAL_CONFIG_COMMENTS: &[(&str, &str)] = &[
+        (
+            "max_update_interval",
+            "Maximum delay before the changes of the pipeline are applied.",
+        ),
+        (
+            "source_channel_size",
+            "Number of measurement buffers that can wait between the sources and the transforms.",
+        ),
+        (
+            "sampling",
+            "Sampling rules, by metric name, ex. `rapl_consumed_energy = { keep_one_in = 10 }`.",
+        ),
+        (
+            "resource_usage",
+            "Measures the CPU time, memory and I/O used by the elements of each plugin.",
+        ),
+        (
+            "capability_policy",
+            "Restricts the capabilities that the plugins can require.",
+        ),
+        (
+            "log",
+            "Logging of the agent. The level of a plugin can be set with its name, ex. `csv = \"debug\"`.",
+        ),
+        (
+            "log.level",
+            "Minimum level of the messages: off, error, warn, info, debug or trace.",
+        ),
+        (
+            "log.format",
+            "Format of the messages: text (human-readable) or json (one JSON object per line).",
+        ),
+        ("health", "HTTP server for liveness and readiness probes."),
+        (
+            "state_directory",
+            "Directory where the plugins store their persistent state.",
+        ),
+        ("failover", "Failover of outputs, by primary output (`plugin/output`)."),
+    ];
+
     /// General config options, which are not specific to a particular plugin.
     #[derive(Deserialize, Serialize, Default)]
     pub struct GeneralConfig {
         // TODO move these to an "advanced" table
         pub max_update_interval: Option<humantime_serde::Serde<Duration>>,
         pub source_channel_size: Option<usize>,
+
+        /// Sampling rules, by metric name.
+        ///
+        /// Example: `sampling.rapl_consumed_energy = { keep_one_in = 10 }`
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub sampling: BTreeMap<String, SamplingConfig>,
+
+        /// If set, measures the CPU time, memory and I/O used by the elements of each plugin.
+        /// With a `poll_interval`, the usage is also measured with the `alumet_element_*` metrics.
+        ///
+        /// Example: `resource_usage = { poll_interval = "10s" }`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub resource_usage: Option<ResourceUsageConfig>,
+
+        /// Restricts the capabilities that the plugins can require.
+        /// If a plugin requires more than what is allowed, the agent refuses to start.
+        ///
+        /// Example: `capability_policy = { network = false, allowed_paths = ["/sys", "/proc"] }`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub capability_policy: Option<CapabilityPolicyConfig>,
+
+        /// Log level and format.
+        ///
+        /// Example: `log = { level = "info", format = "json", kwollect-input = "debug" }`
+        #[serde(default)]
+        pub log: LogConfig,
+
+        /// Optional HTTP server for liveness and readiness probes.
+        ///
+        /// Example: `health = { address = "0.0.0.0:8081" }`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub health: Option<HealthConfig>,
+
+        /// Directory where the plugins store their persistent state, ex. `/var/lib/alumet`.
+        ///
+        /// Defaults to `alumet-state`, relative to the working directory.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub state_directory: Option<PathBuf>,
+
+        /// Failover of outputs, by primary output (`plugin/output`).
+        /// When the primary output fails, the measurements are written to the secondary output,
+        /// and written to the primary output once it works again.
+        ///
+        /// Example: `failover."influxdb/out" = { secondary = "csv/out", retry_interval = "30s" }`
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub failover: BTreeMap<String, FailoverConfig>,
+    }
+
+    /// Resource usage accounting of the pipeline elements.
+    #[derive(Deserialize, Serialize, Clone, Default)]
+    #[serde(deny_unknown_fields)]
+    pub struct ResourceUsageConfig {
+        /// Interval between two measurements of the usage. No measurement if not set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub poll_interval: Option<humantime_serde::Serde<Duration>>,
+    }
+
+    /// Capabilities that the plugins are allowed to require.
+    ///
+    /// Everything that is not explicitly denied is allowed.
+    #[derive(Deserialize, Serialize, Clone)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct CapabilityPolicyConfig {
+        pub network: bool,
+        pub msr: bool,
+        pub perf_events: bool,
+        pub process_spawn: bool,
+        pub dbus: bool,
+        /// If set, plugins can only access these paths (and their content).
+        pub allowed_paths: Option<Vec<PathBuf>>,
+    }
+
+    impl Default for CapabilityPolicyConfig {
+        fn default() -> Self {
+            CapabilityPolicy::allow_all().into()
+        }
+    }
+
+    impl From<CapabilityPolicy> for CapabilityPolicyConfig {
+        fn from(value: CapabilityPolicy) -> Self {
+            Self {
+                network: value.network,
+                msr: value.msr,
+                perf_events: value.perf_events,
+                process_spawn: value.process_spawn,
+                dbus: value.dbus,
+                allowed_paths: value.allowed_paths,
+            }
+        }
+    }
+
+    impl From<CapabilityPolicyConfig> for CapabilityPolicy {
+        fn from(value: CapabilityPolicyConfig) -> Self {
+            Self {
+                network: value.network,
+                msr: value.msr,
+                perf_events: value.perf_events,
+                process_spawn: value.process_spawn,
+                dbus: value.dbus,
+                allowed_paths: value.allowed_paths,
+            }
+        }
+    }
+
+    /// Failover of a primary output.
+    #[derive(Deserialize, Serialize, Clone)]
+    #[serde(deny_unknown_fields)]
+    pub struct FailoverConfig {
+        /// Output that receives the measurements when the primary output fails, ex. `csv/out`.
+        pub secondary: String,
+        /// Minimum delay between two attempts to write to the primary output.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub retry_interval: Option<humantime_serde::Serde<Duration>>,
+        /// Maximum number of measurement points kept in memory until the primary output works again.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub max_gap_points: Option<usize>,
+    }
+
+    impl FailoverConfig {
+        pub fn to_rule(&self) -> anyhow::Result<FailoverRule> {
+            let mut rule = FailoverRule::new(parse_output_name(&self.secondary)?);
+            if let Some(interval) = self.retry_interval {
+                rule.retry_interval = interval.into_inner();
+            }
+            if let Some(max) = self.max_gap_points {
+                rule.max_gap_points = max;
+            }
+            Ok(rule)
+        }
+    }
+
+    /// Parses an output name of the form `plugin/output`.
+    pub fn parse_output_name(name: &str) -> anyhow::Result<OutputName> {
+        match name.split_once('/') {
+            Some((plugin, output)) if !plugin.is_empty() && !output.is_empty() => {
+                Ok(OutputName::from_str(plugin, output))
+            }
+            _ => Err(anyhow::anyhow!(
+                "invalid output name '{name}', expected 'plugin/output', ex. 'csv/out'"
+            )),
+        }
+    }
+
+    /// Reduces the number of measurement points of a metric.
+    #[derive(Deserialize, Serialize, Clone)]
+    #[serde(rename_all = "snake_case", deny_unknown_fields)]
+    pub enum SamplingConfig {
+        /// Keeps one point out of N, for each series.
+        KeepOneIn(NonZeroU64),
+        /// Keeps at most N points per second, for each series.
+        MaxPointsPerSecond(PointsPerSecond),
+    }
+
+    /// A strictly positive rate, checked when the config is loaded.
+    #[derive(Deserialize, Serialize, Clone, Copy)]
+    #[serde(try_from = "f64", into = "f64")]
+    pub struct PointsPerSecond(f64);
+
+    impl TryFrom<f64> for PointsPerSecond {
+        type Error = String;
+
+        fn try_from(rate: f64) -> Result<Self, Self::Error> {
+            // also rejects NaN
+            if rate > 0.0 {
+                Ok(Self(rate))
+            } else {
+                Err(format!(
+                    "the number of points per second must be strictly positive, got {rate}"
+                ))
+            }
+        }
+    }
+
+    impl From<PointsPerSecond> for f64 {
+        fn from(value: PointsPerSecond) -> Self {
+            value.0
+        }
+    }
+
+    impl From<SamplingConfig> for SamplingRule {
+        fn from(value: SamplingConfig) -> Self {
+            match value {
+                SamplingConfig::KeepOneIn(n) => SamplingRule::KeepOneIn(n),
+                SamplingConfig::MaxPointsPerSecond(rate) => SamplingRule::MaxRate {
+                    points_per_second: rate.into(),
+                },
+            }
+        }
     }
 }
@@ -0,0 +1,151 @@
+//! Client for the control socket of a running agent.
+//!
+//! The socket is provided by the `socket-control` plugin, which must be enabled in the running agent.
+//! The protocol is line-based: the client sends one command per line, and the agent replies with
+//! zero or more lines of output, followed by `ok` or `error: <message>`.
+
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+};
+use std::{path::Path, time::Duration};
+
+#[cfg(unix)]
+use anyhow::Context;
+use anyhow::anyhow;
+
+/// How long to wait for the agent to reply.
+#[cfg(unix)]
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends a command to the control socket and returns the lines of output.
+///
+/// Returns an error if the agent cannot be reached or if the command fails.
+#[cfg(unix)]
+pub fn send_command(socket_path: &Path, command: &str) -> anyhow::Result<Vec<String>> {
+    let mut stream = UnixStream::connect(socket_path).with_context(|| {
+        format!(
+            "could not connect to the control socket {}, is the agent running with the socket-control plugin enabled?",
+            socket_path.display()
+        )
+    })?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    stream.write_all(format!("{command}\n").as_bytes())?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let mut output = Vec::new();
+    loop {
+        let mut line = String::new();
+        let n = reader
+            .read_line(&mut line)
+            .context("could not read the response of the agent")?;
+        if n == 0 {
+            return Err(anyhow!("the agent closed the connection without replying"));
+        }
+        let line = line.trim_end();
+        if line == "ok" {
+            return Ok(output);
+        }
+        if let Some(msg) = line.strip_prefix("error: ") {
+            return Err(anyhow!("{msg}"));
+        }
+        output.push(line.to_owned());
+    }
+}
+
+/// Sends a command to the control socket and returns the lines of output.
+///
+/// The control socket is a Unix socket, this always returns an error on other platforms.
+#[cfg(not(unix))]
+pub fn send_command(_socket_path: &Path, _command: &str) -> anyhow::Result<Vec<String>> {
+    Err(anyhow!("the control socket is not supported on this platform"))
+}
+
+/// Turns a source matcher `plugin/source` into a pattern that only matches sources.
+///
+/// The matcher can use wildcards, for instance `rapl/*`.
+/// If there is no slash, the matcher is a plugin name and applies to all its sources.
+pub fn source_pattern(matcher: &str) -> String {
+    if matcher.contains('/') {
+        format!("sources/{matcher}")
+    } else {
+        format!("sources/{matcher}/*")
+    }
+}
+
+/// Returns the command that changes the interval between two measurements of the matching sources.
+///
+/// The interval is given in milliseconds: the agent splits the commands on whitespace, therefore
+/// it does not accept a formatted duration such as `1m 30s`.
+pub fn set_interval_command(matcher: &str, interval: Duration) -> String {
+    format!(
+        "control {} set-period {}ms",
+        source_pattern(matcher),
+        interval.as_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{set_interval_command, source_pattern};
+
+    #[test]
+    fn source_patterns() {
+        assert_eq!(source_pattern("rapl/in"), "sources/rapl/in");
+        assert_eq!(source_pattern("procfs/*"), "sources/procfs/*");
+        assert_eq!(source_pattern("rapl"), "sources/rapl/*");
+        assert_eq!(source_pattern("*"), "sources/*/*");
+    }
+
+    #[test]
+    fn set_interval_is_accepted_by_the_agent() {
+        for interval in [
+            Duration::from_millis(500),
+            Duration::from_millis(1500),
+            Duration::from_secs(90),
+        ] {
+            let command = set_interval_command("rapl", interval);
+            if let Err(e) = plugin_socket_control::command::parse(&command) {
+                panic!("command {command:?} should be accepted: {e}");
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn client() {
+        use super::send_command;
+        use std::{
+            io::{BufRead, BufReader, Write},
+            os::unix::net::UnixListener,
+        };
+
+        let tmp = tempfile::tempdir().unwrap();
+        let socket_path = tmp.path().join("control.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        // fake agent
+        let server = std::thread::spawn(move || {
+            for response in ["sources/a/b\nsources/a/c\nok\n", "error: invalid command\n"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut line = String::new();
+                BufReader::new(&stream).read_line(&mut line).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let output = send_command(&socket_path, "list sources").unwrap();
+        assert_eq!(output, vec!["sources/a/b", "sources/a/c"]);
+        let err = send_command(&socket_path, "bad").unwrap_err();
+        assert_eq!(err.to_string(), "invalid command");
+        server.join().unwrap();
+
+        // no agent
+        drop(tmp);
+        assert!(send_command(&socket_path, "list").is_err());
+    }
+}
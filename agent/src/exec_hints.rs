@@ -1,9 +1,20 @@
+#[cfg(unix)]
 use std::{
-    fs::{self, File, Metadata},
+    fs::{File, Metadata},
     os::unix::fs::PermissionsExt,
+};
+use std::{
+    fs,
     path::{Path, PathBuf},
 };
 
+#[cfg(not(unix))]
+pub fn handle_permission_denied(external_command: String) -> String {
+    log::error!("permission denied when trying to execute '{}'", external_command);
+    format!("Error happened about file's permission {}", external_command)
+}
+
+#[cfg(unix)]
 pub fn handle_permission_denied(external_command: String) -> String {
     let file_open_result = File::open(external_command.clone());
     let file_correctly_opened = if let Err(_err) = file_open_result {
@@ -135,10 +146,12 @@ pub fn handle_not_found(external_command: String, args: Vec<String>) -> String {
     "Sorry but the file was not found".to_string()
 }
 
+#[cfg(unix)]
 fn check_missing_permissions(current_permissions: u32, required_permissions: u32) -> u32 {
     required_permissions & !current_permissions
 }
 
+#[cfg(unix)]
 fn find_a_parent_with_perm_issue(path: String) -> anyhow::Result<std::path::PathBuf, String> {
     // Current parent can change if a parent of the parent don't have the correct rights
     let mut current_parent = match std::path::Path::new(&path).parent() {
@@ -166,7 +179,7 @@ fn find_a_parent_with_perm_issue(path: String) -> anyhow::Result<std::path::Path
     Err("Unable to retrieve a parent for your file".to_string())
 }
 
-#[cfg(test)]
+#[cfg(all(test, unix))]
 mod tests {
     use std::{fs, path::PathBuf};
 
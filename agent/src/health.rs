@@ -0,0 +1,285 @@
+//! Health endpoint, for the liveness and readiness probes of Kubernetes and other monitoring systems.
+//!
+//! The server is a tiny HTTP/1.1 server that runs on its own thread, so that it can answer while
+//! the plugins are starting. It provides the following routes:
+//! - `GET /health/live`: `200 OK` as long as the agent is alive;
+//! - `GET /health/ready` (or `/health`): `200 OK` if the agent is running and no pipeline element
+//!   has failed, `503 Service Unavailable` otherwise.
+//!
+//! Every response contains a JSON report of the state of the agent, for instance:
+//! ```json
+//! {"status":"degraded","failed_elements":[{"element":"sources/rapl/in","error":"..."}]}
+//! ```
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alumet::pipeline::{control::AnonymousControlHandle, error::ElementFailures};
+use serde::{Deserialize, Serialize};
+
+/// Maximum time to wait for a client to send its whole request.
+///
+/// The server handles one connection at a time, so a slow client must not be able to block it.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum size of a request, headers included.
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// The `health` table of the config.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Address and port on which the health server listens, ex. `0.0.0.0:8081`.
+    pub address: String,
+}
+
+/// State of the agent, as reported by the health server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// The plugins and the pipeline are starting.
+    Starting,
+    /// Everything is running.
+    Running,
+    /// The pipeline is running, but some elements have failed.
+    Degraded,
+    /// The pipeline is shutting down.
+    ShuttingDown,
+}
+
+/// Report returned by the health server.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub status: Status,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_elements: Vec<FailedElement>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedElement {
+    pub element: String,
+    pub error: String,
+}
+
+enum Phase {
+    Starting,
+    Running {
+        failures: ElementFailures,
+        control: AnonymousControlHandle,
+    },
+}
+
+/// Shared state of the agent, updated by the main thread and read by the server.
+#[derive(Clone)]
+pub struct HealthState(Arc<Mutex<Phase>>);
+
+impl HealthState {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(Phase::Starting)))
+    }
+
+    /// Marks the pipeline as started.
+    ///
+    /// From now on, the status is computed from the failures of the elements and from the
+    /// shutdown state of the pipeline.
+    pub fn set_running(&self, failures: ElementFailures, control: AnonymousControlHandle) {
+        *self.0.lock().unwrap() = Phase::Running { failures, control };
+    }
+
+    /// Returns the current report.
+    pub fn report(&self) -> HealthReport {
+        match &*self.0.lock().unwrap() {
+            Phase::Starting => HealthReport {
+                status: Status::Starting,
+                failed_elements: Vec::new(),
+            },
+            Phase::Running { failures, control } => {
+                let failed_elements: Vec<FailedElement> = failures
+                    .list()
+                    .into_iter()
+                    .map(|f| FailedElement {
+                        element: f.element.to_string(),
+                        error: f.error,
+                    })
+                    .collect();
+                let status = if control.is_shutting_down() {
+                    Status::ShuttingDown
+                } else if failed_elements.is_empty() {
+                    Status::Running
+                } else {
+                    Status::Degraded
+                };
+                HealthReport {
+                    status,
+                    failed_elements,
+                }
+            }
+        }
+    }
+}
+
+/// Starts the health server on a background thread.
+///
+/// The server stops when the process exits.
+pub fn start_server(address: &str) -> io::Result<(HealthState, SocketAddr)> {
+    let listener = TcpListener::bind(address)?;
+    let addr = listener.local_addr()?;
+    let state = HealthState::new();
+    let server_state = state.clone();
+    std::thread::Builder::new()
+        .name(String::from("health-server"))
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let res = stream.and_then(|s| handle_connection(s, &server_state));
+                if let Err(e) = res {
+                    log::debug!("health server: connection error: {e}");
+                }
+            }
+        })?;
+    Ok((state, addr))
+}
+
+/// Reads one request and responds to it.
+fn handle_connection(mut stream: TcpStream, state: &HealthState) -> io::Result<()> {
+    let request = read_request(&mut stream)?;
+    let request = String::from_utf8_lossy(&request);
+    let request_line = request.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    let (status, body) = respond(method, path, state);
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Reads the request line and the headers, within [`READ_TIMEOUT`] and [`MAX_REQUEST_SIZE`].
+fn read_request(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let deadline = Instant::now() + READ_TIMEOUT;
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    // stop at the end of the headers, we don't need them
+    while !has_end_of_headers(&request) {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "request not received in time"));
+        }
+        stream.set_read_timeout(Some(remaining))?;
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+        if request.len() > MAX_REQUEST_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large"));
+        }
+    }
+    Ok(request)
+}
+
+fn has_end_of_headers(request: &[u8]) -> bool {
+    request.windows(4).any(|w| w == b"\r\n\r\n") || request.windows(2).any(|w| w == b"\n\n")
+}
+
+/// Returns the HTTP status line and the body of the response.
+fn respond(method: &str, path: &str, state: &HealthState) -> (&'static str, String) {
+    // ignore the query string, if any
+    let path = path.split('?').next().unwrap_or_default();
+    let live = match path {
+        "/health/live" => true,
+        "/health/ready" | "/health" => false,
+        _ => return ("404 Not Found", String::from(r#"{"error":"not found"}"#)),
+    };
+    if method != "GET" {
+        return (
+            "405 Method Not Allowed",
+            String::from(r#"{"error":"method not allowed"}"#),
+        );
+    }
+    let report = state.report();
+    let status = if live || report.status == Status::Running {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+    let body = serde_json::to_string(&report).expect("the report should be serializable");
+    (status, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+
+    use alumet::pipeline;
+
+    use super::{Status, start_server};
+
+    fn get(addr: SocketAddr, path: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn starting() {
+        let (_state, addr) = start_server("127.0.0.1:0").unwrap();
+        let (status, body) = get(addr, "/health/ready");
+        assert_eq!(status, 503);
+        assert_eq!(body["status"], "starting");
+        assert!(body.get("failed_elements").is_none());
+
+        let (status, _) = get(addr, "/health/live");
+        assert_eq!(status, 200);
+        let (status, _) = get(addr, "/metrics");
+        assert_eq!(status, 404);
+    }
+
+    #[test]
+    fn running() {
+        let (state, addr) = start_server("127.0.0.1:0").unwrap();
+        let pipeline = pipeline::Builder::new().build().unwrap();
+        state.set_running(pipeline.failures(), pipeline.control_handle());
+
+        let (status, body) = get(addr, "/health?verbose");
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "running");
+
+        pipeline.control_handle().shutdown();
+        assert_eq!(state.report().status, Status::ShuttingDown);
+        let (status, _) = get(addr, "/health/ready");
+        assert_eq!(status, 503);
+        let (status, _) = get(addr, "/health/live");
+        assert_eq!(status, 200);
+        assert!(pipeline.wait_for_shutdown(None).is_ok());
+    }
+
+    #[test]
+    fn request_too_large() {
+        let (_state, addr) = start_server("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let request = format!(
+            "GET /health/live HTTP/1.1\r\nx-large: {}\r\n\r\n",
+            "a".repeat(16 * 1024)
+        );
+        // the server closes the connection without responding, the write may fail or not
+        let _ = stream.write_all(request.as_bytes());
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        assert!(response.is_empty());
+
+        // the server still answers to the other clients
+        let (status, _) = get(addr, "/health/live");
+        assert_eq!(status, 200);
+    }
+}
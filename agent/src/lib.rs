@@ -1,8 +1,12 @@
 use std::path::PathBuf;
 
-use env_logger::Env;
-
+pub mod control;
 pub mod exec_hints;
+pub mod health;
+pub mod logging;
+pub mod reload;
+#[cfg(unix)]
+pub mod systemd;
 pub mod word_distance;
 
 /// Returns the absolute path of the currently running executable.
@@ -34,7 +38,7 @@ pub fn relative_exe_path() -> std::io::Result<PathBuf> {
 
 /// Initializes the global logger.
 ///
-/// Call this first!
+/// Call this first! The log config can then be applied with [`logging::configure`].
 ///
 /// # Example
 ///
@@ -48,5 +52,5 @@ pub fn relative_exe_path() -> std::io::Result<PathBuf> {
 /// }
 /// ```
 pub fn init_logger() {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    logging::init();
 }
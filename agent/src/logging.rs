@@ -0,0 +1,364 @@
+//! Logging of the agent.
+//!
+//! The logger is installed by [`init`] when the agent starts, with the level given by `RUST_LOG`
+//! (or `info` by default). Once the configuration has been loaded, [`configure`] applies the
+//! `[log]` table of the config file:
+//!
+//! ```toml
+//! [log]
+//! level = "info"   # default level
+//! format = "json"  # "text" or "json"
+//! kwollect-input = "debug"  # level of one plugin
+//! ```
+//!
+//! The level of a plugin applies to the log messages emitted by its crate: plugins that are defined
+//! in the same crate, like `relay-client` and `relay-server`, share their level.
+//! `RUST_LOG` still takes precedence over the config, to debug without editing the file.
+//!
+//! In the JSON format, every message is written on its own line, with the fields `timestamp`, `level`,
+//! `target`, `message`, `plugin` (if the message comes from a plugin) and the key-values of the record,
+//! for instance the `element` of the pipeline that has failed.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    sync::{OnceLock, RwLock},
+    time::SystemTime,
+};
+
+use anyhow::anyhow;
+use env_filter::{Builder as FilterBuilder, Filter};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Environment variable that sets the log levels.
+const LEVEL_ENV: &str = "RUST_LOG";
+
+/// Environment variable that sets the format of the logs before the config is loaded.
+const FORMAT_ENV: &str = "ALUMET_LOG_FORMAT";
+
+static LOGGER: OnceLock<AgentLogger> = OnceLock::new();
+
+/// The `[log]` table of the config.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct LogConfig {
+    /// Level of the messages that don't belong to a plugin with its own level.
+    #[serde(default)]
+    pub level: LogLevel,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Level of each plugin, by plugin name.
+    #[serde(flatten)]
+    pub plugins: BTreeMap<String, LogLevel>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable messages.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Installs the global logger.
+///
+/// # Panics
+/// Panics if a logger has already been installed.
+pub fn init() {
+    let format = match std::env::var(FORMAT_ENV).as_deref() {
+        Ok("json") => LogFormat::Json,
+        _ => LogFormat::Text,
+    };
+    let state = LoggerState {
+        filter: build_filter(LevelFilter::Info, &[], std::env::var(LEVEL_ENV).ok().as_deref()),
+        format,
+        plugins: Vec::new(),
+    };
+    let logger = LOGGER.get_or_init(|| AgentLogger {
+        text: env_logger::Builder::new().filter_level(LevelFilter::Trace).build(),
+        state: RwLock::new(state),
+    });
+    log::set_logger(logger).expect("the logger should be installed only once");
+    log::set_max_level(logger.state.read().unwrap().filter.filter());
+}
+
+/// Applies the log config.
+///
+/// `plugins` gives the name and the log target of each plugin, see [`PluginMetadata::log_target`](alumet::plugin::PluginMetadata::log_target).
+/// Fails if the config sets the level of an unknown plugin, or of a plugin without log target.
+pub fn configure<'a>(
+    config: &LogConfig,
+    plugins: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+) -> anyhow::Result<()> {
+    let plugins: BTreeMap<&str, Option<&str>> = plugins.into_iter().collect();
+
+    let mut directives = Vec::with_capacity(config.plugins.len());
+    for (plugin, level) in &config.plugins {
+        match plugins.get(plugin.as_str()) {
+            Some(Some(target)) => directives.push((*target, LevelFilter::from(*level))),
+            Some(None) => return Err(anyhow!("cannot set the log level of plugin {plugin}: its log target is unknown")),
+            None => return Err(anyhow!("cannot set the log level of plugin {plugin}: no such plugin")),
+        }
+    }
+    let filter = build_filter(
+        config.level.into(),
+        &directives,
+        std::env::var(LEVEL_ENV).ok().as_deref(),
+    );
+
+    // Attribute the messages to the plugins, except when several plugins share the same target.
+    let mut targets: Vec<(String, String)> = Vec::new();
+    let mut shared = Vec::new();
+    for (name, target) in plugins {
+        let Some(target) = target else { continue };
+        if targets.iter().any(|(t, _)| t == target) {
+            shared.push(target);
+        } else {
+            targets.push((target.to_owned(), name.to_owned()));
+        }
+    }
+    targets.retain(|(t, _)| !shared.contains(&t.as_str()));
+    // Sort by decreasing length, so that the most specific target matches first.
+    targets.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then(a.cmp(b)));
+
+    let Some(logger) = LOGGER.get() else {
+        return Err(anyhow!("the logger has not been initialized"));
+    };
+    let mut state = logger.state.write().unwrap();
+    log::set_max_level(filter.filter());
+    state.filter = filter;
+    state.format = config.format;
+    state.plugins = targets;
+    Ok(())
+}
+
+/// Builds the log filter: the config first, then the directives of `RUST_LOG` on top of it.
+fn build_filter(default: LevelFilter, plugins: &[(&str, LevelFilter)], env: Option<&str>) -> Filter {
+    let mut builder = FilterBuilder::new();
+    builder.filter_level(default);
+    for (target, level) in plugins {
+        builder.filter_module(target, *level);
+    }
+    if let Some(env) = env {
+        builder.parse(env);
+    }
+    builder.build()
+}
+
+struct AgentLogger {
+    /// Formats the messages in the text format.
+    text: env_logger::Logger,
+    state: RwLock<LoggerState>,
+}
+
+struct LoggerState {
+    filter: Filter,
+    format: LogFormat,
+    /// Log target and name of each plugin, the longest targets first.
+    plugins: Vec<(String, String)>,
+}
+
+impl LoggerState {
+    /// Returns the name of the plugin that has emitted a message with the given target.
+    fn plugin_of(&self, target: &str) -> Option<&str> {
+        self.plugins
+            .iter()
+            .find(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map(|(_, plugin)| plugin.as_str())
+    }
+}
+
+impl Log for AgentLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.state.read().unwrap().filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let state = self.state.read().unwrap();
+        if !state.filter.matches(record) {
+            return;
+        }
+        match state.format {
+            LogFormat::Text => self.text.log(record),
+            LogFormat::Json => {
+                let line = json_line(record, state.plugin_of(record.target()), SystemTime::now());
+                let _ = writeln!(std::io::stderr().lock(), "{line}");
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.text.flush();
+    }
+}
+
+/// Formats a log record as a JSON object.
+fn json_line(record: &Record, plugin: Option<&str>, now: SystemTime) -> String {
+    let mut fields = Map::new();
+    fields.insert(
+        String::from("timestamp"),
+        Value::String(humantime::format_rfc3339_micros(now).to_string()),
+    );
+    fields.insert(String::from("level"), Value::String(record.level().to_string()));
+    fields.insert(String::from("target"), Value::String(record.target().to_owned()));
+    if let Some(plugin) = plugin {
+        fields.insert(String::from("plugin"), Value::String(plugin.to_owned()));
+    }
+    let _ = record.key_values().visit(&mut KeyValues(&mut fields));
+    fields.insert(String::from("message"), Value::String(record.args().to_string()));
+    Value::Object(fields).to_string()
+}
+
+/// Collects the key-values of a log record into a JSON object.
+struct KeyValues<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for KeyValues<'_> {
+    fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = if let Some(b) = value.to_bool() {
+            Value::from(b)
+        } else if let Some(n) = value.to_u64() {
+            Value::from(n)
+        } else if let Some(n) = value.to_i64() {
+            Value::from(n)
+        } else if let Some(f) = value.to_f64() {
+            Value::from(f)
+        } else {
+            Value::String(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use log::{Level, LevelFilter, Metadata, Record, kv::Value};
+    use serde_json::json;
+
+    use super::{LogConfig, LogFormat, LogLevel, LoggerState, build_filter, json_line};
+
+    #[test]
+    fn parse_config() {
+        let config: LogConfig = toml::from_str(
+            r#"
+            format = "json"
+            kwollect-input = "debug"
+            relay-client = "off"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.level, LogLevel::Info);
+        assert_eq!(config.format, LogFormat::Json);
+        assert_eq!(config.plugins.len(), 2);
+        assert_eq!(config.plugins["kwollect-input"], LogLevel::Debug);
+        assert_eq!(config.plugins["relay-client"], LogLevel::Off);
+
+        assert!(toml::from_str::<LogConfig>("format = \"xml\"").is_err());
+        assert!(toml::from_str::<LogConfig>("csv = \"verbose\"").is_err());
+    }
+
+    #[test]
+    fn filter_per_plugin() {
+        let filter = build_filter(
+            LevelFilter::Warn,
+            &[("plugin_kwollect_input", LevelFilter::Debug), ("plugin_csv", LevelFilter::Off)],
+            None,
+        );
+        let enabled = |level, target| filter.enabled(&Metadata::builder().level(level).target(target).build());
+        assert!(enabled(Level::Debug, "plugin_kwollect_input::source"));
+        assert!(!enabled(Level::Trace, "plugin_kwollect_input::source"));
+        assert!(!enabled(Level::Error, "plugin_csv::output"));
+        assert!(enabled(Level::Warn, "alumet::agent"));
+        assert!(!enabled(Level::Info, "alumet::agent"));
+        assert_eq!(filter.filter(), LevelFilter::Debug);
+
+        // RUST_LOG takes precedence
+        let filter = build_filter(
+            LevelFilter::Warn,
+            &[("plugin_csv", LevelFilter::Off)],
+            Some("info,plugin_csv=trace"),
+        );
+        let enabled = |level, target| filter.enabled(&Metadata::builder().level(level).target(target).build());
+        assert!(enabled(Level::Trace, "plugin_csv::output"));
+        assert!(enabled(Level::Info, "alumet::agent"));
+    }
+
+    #[test]
+    fn find_plugin() {
+        let state = LoggerState {
+            filter: build_filter(LevelFilter::Info, &[], None),
+            format: LogFormat::Json,
+            plugins: vec![
+                (String::from("plugin_relay"), String::from("relay-client")),
+                (String::from("plugin_csv"), String::from("csv")),
+            ],
+        };
+        assert_eq!(state.plugin_of("plugin_csv"), Some("csv"));
+        assert_eq!(state.plugin_of("plugin_csv::output"), Some("csv"));
+        assert_eq!(state.plugin_of("plugin_csv2"), None);
+        assert_eq!(state.plugin_of("alumet::pipeline"), None);
+    }
+
+    #[test]
+    fn json_format() {
+        let kvs = [
+            ("element", Value::from("output/csv/out")),
+            ("attempt", Value::from(2_u64)),
+        ];
+        let record = Record::builder()
+            .level(Level::Warn)
+            .target("plugin_csv::output")
+            .args(format_args!("something \"bad\" happened"))
+            .key_values(&kvs)
+            .build();
+        let line = json_line(&record, Some("csv"), UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            parsed,
+            json!({
+                "timestamp": "2023-11-14T22:13:20.000000Z",
+                "level": "WARN",
+                "target": "plugin_csv::output",
+                "plugin": "csv",
+                "element": "output/csv/out",
+                "attempt": 2,
+                "message": "something \"bad\" happened",
+            })
+        );
+        assert!(!line.contains('\n'));
+    }
+}
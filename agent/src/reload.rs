@@ -0,0 +1,299 @@
+//! Hot reload of the configuration.
+//!
+//! When the agent receives `SIGHUP`, it reads its configuration file again and applies the changes
+//! that do not require a restart. The pipeline keeps running: no measurement is lost.
+//!
+//! The following changes are applied:
+//! - `poll_interval` of a plugin: the sources of the plugin that are polled at regular intervals use the
+//!   new interval. The other settings of their trigger, such as the flush and update intervals, are kept;
+//! - status of a plugin (`enabled = true/false`, or removal of its section): the sources, transforms
+//!   and outputs of the plugin are enabled or disabled. A plugin that was not running when the agent
+//!   started cannot be enabled this way.
+//!
+//! The other changes are ignored, with a warning. They will be applied on the next restart.
+//! This includes the output filters (for instance, the list of metrics that an output plugin exports)
+//! and, more generally, every other option of the plugins, because the plugins read them only once,
+//! when they start.
+
+use std::{collections::BTreeSet, time::Duration};
+
+use alumet::{
+    agent::config::extract_plugins_config,
+    pipeline::{
+        control::{AnonymousControlHandle, request},
+        matching::{OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern},
+    },
+};
+
+/// Maximum time to wait for the pipeline to apply a change.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A difference between two versions of the configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The `poll_interval` of a plugin has changed.
+    SetPollInterval { plugin: String, interval: Duration },
+    /// A plugin has been enabled.
+    EnablePlugin(String),
+    /// A plugin has been disabled.
+    DisablePlugin(String),
+    /// A setting has changed, but it cannot be applied without restarting the agent.
+    NeedsRestart(String),
+}
+
+/// Computes the changes between the `old` and the `new` configuration.
+///
+/// If `track_status` is false, the status of the plugins is ignored (for instance, because it
+/// has been set on the command line).
+pub fn diff(old: &toml::Table, new: &toml::Table, track_status: bool) -> anyhow::Result<Vec<Change>> {
+    let mut old = old.clone();
+    let mut new = new.clone();
+    let old_plugins = extract_plugins_config(&mut old)?;
+    let new_plugins = extract_plugins_config(&mut new)?;
+    let mut changes = Vec::new();
+
+    // general options
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        if old.get(key) != new.get(key) {
+            changes.push(Change::NeedsRestart(key.to_owned()));
+        }
+    }
+
+    // plugins
+    let disabled = (false, toml::Table::new());
+    let plugins: BTreeSet<&String> = old_plugins.keys().chain(new_plugins.keys()).collect();
+    for plugin in plugins {
+        let (old_enabled, old_config) = old_plugins.get(plugin).unwrap_or(&disabled);
+        let (new_enabled, new_config) = new_plugins.get(plugin).unwrap_or(&disabled);
+        if track_status && old_enabled != new_enabled {
+            if *new_enabled {
+                changes.push(Change::EnablePlugin(plugin.to_owned()));
+            } else {
+                changes.push(Change::DisablePlugin(plugin.to_owned()));
+                continue; // the other settings don't matter
+            }
+        }
+
+        let keys: BTreeSet<&String> = old_config.keys().chain(new_config.keys()).collect();
+        for key in keys {
+            let new_value = new_config.get(key);
+            if old_config.get(key) == new_value {
+                continue;
+            }
+            let interval = new_value
+                .and_then(|v| v.as_str())
+                .and_then(|v| humantime::parse_duration(v).ok())
+                .filter(|d| !d.is_zero());
+            match (key.as_str(), interval) {
+                ("poll_interval", Some(interval)) => changes.push(Change::SetPollInterval {
+                    plugin: plugin.to_owned(),
+                    interval,
+                }),
+                _ => changes.push(Change::NeedsRestart(format!("plugins.{plugin}.{key}"))),
+            }
+        }
+    }
+    Ok(changes)
+}
+
+/// Applies the changes to the running pipeline.
+///
+/// `running_plugins` contains the name of the plugins that have been started.
+/// Errors are logged, and do not prevent the other changes from being applied.
+///
+/// Returns `false` if a change could not be applied. The changes that require a restart
+/// are not counted as failures.
+pub async fn apply(changes: Vec<Change>, handle: &AnonymousControlHandle, running_plugins: &BTreeSet<String>) -> bool {
+    let mut all_applied = true;
+    for change in changes {
+        let plugin = match &change {
+            Change::NeedsRestart(setting) => {
+                log::warn!("Setting {setting} has changed, restart the agent to apply it.");
+                continue;
+            }
+            Change::SetPollInterval { plugin, .. } | Change::EnablePlugin(plugin) | Change::DisablePlugin(plugin) => {
+                plugin.to_owned()
+            }
+        };
+        if !running_plugins.contains(&plugin) {
+            log::warn!("Plugin {plugin} was not running when the agent started, restart the agent to apply {change:?}");
+            continue;
+        }
+        if let Err(e) = apply_one(&change, plugin, handle).await {
+            log::error!("Failed to apply {change:?}: {e:#}");
+            all_applied = false;
+        } else {
+            log::info!("Applied {change:?}");
+        }
+    }
+    all_applied
+}
+
+async fn apply_one(change: &Change, plugin: String, handle: &AnonymousControlHandle) -> anyhow::Result<()> {
+    let sources = SourceNamePattern::new(StringPattern::Exact(plugin.clone()), StringPattern::Any);
+    let transforms = TransformNamePattern::new(StringPattern::Exact(plugin.clone()), StringPattern::Any);
+    let outputs = OutputNamePattern::new(StringPattern::Exact(plugin), StringPattern::Any);
+    match change {
+        Change::SetPollInterval { interval, .. } => {
+            handle
+                .send_wait(request::source(sources).set_poll_interval(*interval), REQUEST_TIMEOUT)
+                .await?;
+        }
+        Change::EnablePlugin(_) => {
+            handle
+                .send_wait(request::source(sources).enable(), REQUEST_TIMEOUT)
+                .await?;
+            handle
+                .send_wait(request::transform(transforms).enable(), REQUEST_TIMEOUT)
+                .await?;
+            handle
+                .send_wait(request::output(outputs).enable(), REQUEST_TIMEOUT)
+                .await?;
+        }
+        Change::DisablePlugin(_) => {
+            // disable the sources first, so that the outputs receive the last measurements
+            handle
+                .send_wait(request::source(sources).disable(), REQUEST_TIMEOUT)
+                .await?;
+            handle
+                .send_wait(request::transform(transforms).disable(), REQUEST_TIMEOUT)
+                .await?;
+            handle
+                .send_wait(request::output(outputs).disable(), REQUEST_TIMEOUT)
+                .await?;
+        }
+        Change::NeedsRestart(_) => unreachable!("NeedsRestart is handled by the caller"),
+    }
+    Ok(())
+}
+
+/// Reloads the configuration every time the agent receives `SIGHUP`.
+///
+/// `load_config` is called to read the new configuration, which is compared to the previous one.
+/// The new configuration replaces the previous one only if all its changes have been applied,
+/// so that the failed changes are retried on the next reload.
+/// This function never returns, unless an error occurs when listening to the signal.
+#[cfg(unix)]
+pub async fn reload_on_sighup(
+    mut config: toml::Table,
+    load_config: impl Fn() -> anyhow::Result<toml::Table>,
+    track_status: bool,
+    handle: AnonymousControlHandle,
+    running_plugins: BTreeSet<String>,
+) -> anyhow::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut sighup = signal(SignalKind::hangup())?;
+    while sighup.recv().await.is_some() {
+        log::info!("SIGHUP received, reloading the configuration...");
+        let new_config = match load_config() {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("Could not reload the configuration, the previous one is kept. {e:#}");
+                continue;
+            }
+        };
+        match diff(&config, &new_config, track_status) {
+            Ok(changes) if changes.is_empty() => log::info!("The configuration has not changed."),
+            Ok(changes) => {
+                if !apply(changes, &handle, &running_plugins).await {
+                    log::error!("Some changes could not be applied, they will be retried on the next reload.");
+                    continue;
+                }
+            }
+            Err(e) => {
+                log::error!("Invalid configuration, the previous one is kept. {e:#}");
+                continue;
+            }
+        }
+        config = new_config;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Change, diff};
+
+    #[test]
+    fn no_change() {
+        let config = toml::toml! {
+            max_update_interval = "1s"
+            [plugins.rapl]
+            poll_interval = "1s"
+        };
+        assert_eq!(diff(&config, &config, true).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn changes() {
+        let old = toml::toml! {
+            max_update_interval = "1s"
+            [plugins.rapl]
+            poll_interval = "1s"
+            perf_mode = true
+            [plugins.procfs]
+            [plugins.csv]
+            enabled = false
+        };
+        let new = toml::toml! {
+            max_update_interval = "2s"
+            [plugins.rapl]
+            poll_interval = "500ms"
+            perf_mode = false
+            [plugins.csv]
+            output_path = "out.csv"
+        };
+        let changes = diff(&old, &new, true).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::NeedsRestart(String::from("max_update_interval")),
+                Change::EnablePlugin(String::from("csv")),
+                Change::NeedsRestart(String::from("plugins.csv.output_path")),
+                Change::DisablePlugin(String::from("procfs")),
+                Change::NeedsRestart(String::from("plugins.rapl.perf_mode")),
+                Change::SetPollInterval {
+                    plugin: String::from("rapl"),
+                    interval: Duration::from_millis(500)
+                },
+            ]
+        );
+
+        // without status tracking
+        let changes = diff(&old, &new, false).unwrap();
+        assert!(
+            !changes
+                .iter()
+                .any(|c| matches!(c, Change::EnablePlugin(_) | Change::DisablePlugin(_)))
+        );
+    }
+
+    #[test]
+    fn invalid_interval() {
+        let old = toml::toml! {
+            [plugins.rapl]
+            poll_interval = "1s"
+        };
+        let new = toml::toml! {
+            [plugins.rapl]
+            poll_interval = "not a duration"
+        };
+        assert_eq!(
+            diff(&old, &new, true).unwrap(),
+            vec![Change::NeedsRestart(String::from("plugins.rapl.poll_interval"))]
+        );
+
+        let new = toml::toml! {
+            [plugins.rapl]
+            poll_interval = "0s"
+        };
+        assert_eq!(
+            diff(&old, &new, true).unwrap(),
+            vec![Change::NeedsRestart(String::from("plugins.rapl.poll_interval"))]
+        );
+    }
+}
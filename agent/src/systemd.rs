@@ -0,0 +1,155 @@
+//! Integration with systemd: readiness notification and watchdog.
+//!
+//! When the agent runs as a systemd service of type `notify`, systemd sets the `NOTIFY_SOCKET`
+//! environment variable. The agent uses it to tell systemd when it is ready (`READY=1`), when it
+//! stops (`STOPPING=1`) and, if `WatchdogSec` is set in the unit, that it is still alive (`WATCHDOG=1`).
+//!
+//! Outside of systemd, these functions do nothing.
+//!
+//! See the documentation of [`sd_notify`](https://www.freedesktop.org/software/systemd/man/latest/sd_notify.html).
+
+use std::{
+    ffi::OsStr,
+    io,
+    os::unix::{ffi::OsStrExt, net::UnixDatagram},
+    time::Duration,
+};
+
+use alumet::pipeline::control::AnonymousControlHandle;
+
+const NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+const WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+const WATCHDOG_PID: &str = "WATCHDOG_PID";
+
+/// Returns `true` if the agent has been started by systemd with the notification socket.
+pub fn is_enabled() -> bool {
+    std::env::var_os(NOTIFY_SOCKET).is_some()
+}
+
+/// Sends a state change to systemd, such as `READY=1`.
+///
+/// Returns `false` if the agent has not been started by systemd (there is no notification socket).
+pub fn notify(state: &str) -> io::Result<bool> {
+    match std::env::var_os(NOTIFY_SOCKET) {
+        Some(socket) => notify_to(&socket, state).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Sends a state change to the given notification socket.
+///
+/// Socket names that begin with `@` are abstract sockets (only supported on Linux).
+fn notify_to(socket: &OsStr, state: &str) -> io::Result<()> {
+    let datagram = UnixDatagram::unbound()?;
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            let addr = SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract notification sockets are only supported on Linux",
+            ));
+        }
+        None => {
+            datagram.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the interval of the watchdog that systemd expects, if it is enabled for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = std::env::var(WATCHDOG_USEC).ok();
+    let pid = std::env::var(WATCHDOG_PID).ok();
+    parse_watchdog(usec.as_deref(), pid.as_deref(), std::process::id())
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, current_pid: u32) -> Option<Duration> {
+    // If WATCHDOG_PID is set, the watchdog is only enabled for this pid (and not for our children).
+    if let Some(pid) = pid
+        && pid.parse::<u32>().ok()? != current_pid
+    {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Sends `WATCHDOG=1` twice per `interval`, as recommended by systemd.
+///
+/// This function never returns. Run it on the async runtime of the pipeline: if the runtime is stuck,
+/// the keep-alives stop and systemd restarts the agent (depending on the unit configuration).
+pub async fn watchdog(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval / 2);
+    loop {
+        ticker.tick().await;
+        if let Err(e) = notify("WATCHDOG=1") {
+            log::warn!("Failed to send the watchdog keep-alive to systemd: {e}");
+        }
+    }
+}
+
+/// Shuts the pipeline down when the agent receives `SIGTERM`, which is how systemd stops services.
+///
+/// This function returns after the first `SIGTERM`.
+pub async fn shutdown_on_sigterm(handle: AnonymousControlHandle) -> io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    if sigterm.recv().await.is_some() {
+        log::info!("SIGTERM received, shutting down...");
+        handle.shutdown();
+    }
+    Ok(())
+}
+
+/// Sends `STOPPING=1` to systemd when the pipeline starts to shut down, whatever the reason:
+/// `SIGTERM`, `Ctrl+C`, a request on the control socket, the end of the executed command, etc.
+pub async fn notify_on_shutdown(handle: AnonymousControlHandle) {
+    handle.shutdown_requested().await;
+    if let Err(e) = notify("STOPPING=1") {
+        log::warn!("Failed to notify systemd: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixDatagram, time::Duration};
+
+    use super::{notify_to, parse_watchdog};
+
+    #[test]
+    fn notify() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("notify.sock");
+        let receiver = UnixDatagram::bind(&path).unwrap();
+
+        notify_to(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+
+    #[test]
+    fn watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("10000000"), None, 42),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            parse_watchdog(Some("500000"), Some("42"), 42),
+            Some(Duration::from_millis(500))
+        );
+        // not for us
+        assert_eq!(parse_watchdog(Some("500000"), Some("43"), 42), None);
+        // disabled or invalid
+        assert_eq!(parse_watchdog(None, None, 42), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(Some("abc"), None, 42), None);
+    }
+}
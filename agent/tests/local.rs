@@ -5,7 +5,7 @@ use anyhow::Context;
 
 use common::{
     empty_temp_dir,
-    run::{run_agent, run_agent_tee},
+    run::{command_run_agent, run_agent, run_agent_tee},
     tests,
 };
 use indoc::indoc;
@@ -52,11 +52,64 @@ fn regen_config_with_plugins() -> anyhow::Result<()> {
     let config_content = std::fs::read_to_string(conf)?;
     let expected = indoc! { r#"
         [plugins.rapl]
+        # Interval between two RAPL measurements.
         poll_interval = "1s"
+        # Interval between two flushes of the measurements to the pipeline.
         flush_interval = "5s"
+        # Set to true to disable perf_events and always use the powercap sysfs.
         no_perf_events = false
+        # Set to true to never read the RAPL MSRs, which are the last resort
+        # when perf_events and powercap cannot be used.
+        no_msr = false
+
+        # Energy counters of AMD Zen processors, used when RAPL is not available and to measure the CCDs.
+        [plugins.rapl.amd]
+        # Interfaces to use, in order of preference: hwmon (amd_energy, zenergy and zenpower drivers)
+        # and msr. If empty, the AMD counters are not used.
+        interfaces = [
+            "hwmon",
+            "msr",
+        ]
+        # Measure the energy of the cores of each CCD, in addition to RAPL.
+        per_ccd = true
     "# };
-    assert_eq!(config_content, expected);
+    // the section is preceded by comments that describe the plugin, and the options by comments that explain them
+    assert!(config_content.starts_with("# Configuration of the Alumet agent."));
+    assert!(config_content.contains("\n# Plugin rapl v"));
+    assert!(
+        config_content.ends_with(expected),
+        "unexpected config:\n{config_content}"
+    );
+    Ok(())
+}
+
+#[test]
+fn regen_config_selected_plugins() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    let conf_path_str = conf.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "regen", "--plugins", "rapl,csv"],
+        tmp_dir.path(),
+    )?;
+    assert!(output.status.success(), "command should succeed");
+
+    let config_content = std::fs::read_to_string(&conf)?;
+    let config: toml::Table = toml::from_str(&config_content)?;
+    let plugins: Vec<&String> = config["plugins"].as_table().unwrap().keys().collect();
+    assert_eq!(plugins, vec!["csv", "rapl"]);
+    assert!(config_content.contains("# Plugin csv v"));
+    assert!(config_content.contains("# Minimum level of the messages"));
+
+    // unknown plugins are rejected
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &["--config", conf_path_str, "config", "regen", "--plugins", "rapll"],
+        tmp_dir.path(),
+    )?;
+    assert!(!output.status.success(), "command should fail");
+    assert!(String::from_utf8(output.stderr)?.contains("did you mean 'rapl'?"));
     Ok(())
 }
 
@@ -121,6 +174,136 @@ fn plugin_enabled_with_missing_config_should_use_default() -> anyhow::Result<()>
     Ok(())
 }
 
+#[test]
+fn exec_exit_code_is_propagated() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    std::fs::write(&conf, "")?;
+
+    let conf_path_str = conf.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &[
+            "--plugins",
+            "csv",
+            "--config",
+            conf_path_str,
+            "exec",
+            "sh",
+            "-c",
+            "exit 3",
+        ],
+        tmp_dir.path(),
+    )?;
+    assert_eq!(output.status.code(), Some(3), "the agent should exit like the child");
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn systemd_stopping_without_sigterm() -> anyhow::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    std::fs::write(&conf, "")?;
+    let socket_path = tmp_dir.path().join("notify.sock");
+    let socket = UnixDatagram::bind(&socket_path)?;
+
+    // the agent stops at the end of the command, without SIGTERM
+    let conf_path_str = conf.to_str().unwrap();
+    let status = command_run_agent(
+        AGENT_BIN,
+        &["--plugins", "csv", "--config", conf_path_str, "exec", "true"],
+    )?
+    .current_dir(tmp_dir.path())
+    .env("NOTIFY_SOCKET", &socket_path)
+    .status()?;
+    assert!(status.success());
+
+    socket.set_nonblocking(true)?;
+    let mut states = Vec::new();
+    let mut buf = [0u8; 64];
+    while let Ok(n) = socket.recv(&mut buf) {
+        states.push(String::from_utf8_lossy(&buf[..n]).into_owned());
+    }
+    assert_eq!(states, vec!["READY=1", "STOPPING=1"]);
+    Ok(())
+}
+
+#[test]
+fn exec_phases() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    std::fs::write(&conf, "")?;
+    let marker = tmp_dir.path().join("teardown");
+
+    let conf_path_str = conf.to_str().unwrap();
+    let teardown = format!("teardown=touch {}", marker.to_str().unwrap());
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &[
+            "--plugins",
+            "csv",
+            "--config",
+            conf_path_str,
+            "exec-phases",
+            "--phase",
+            "warmup=true",
+            "--phase",
+            "benchmark=false",
+            "--phase",
+            &teardown,
+        ],
+        tmp_dir.path(),
+    )?;
+    assert_eq!(
+        output.status.code(),
+        Some(1),
+        "the agent should exit like the failed phase"
+    );
+    assert!(
+        marker.exists(),
+        "the teardown phase should run even if a previous phase has failed"
+    );
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("Starting phase warmup"));
+    assert!(stderr.contains("Starting phase benchmark"));
+    assert!(stderr.contains("Starting phase teardown"));
+    Ok(())
+}
+
+#[test]
+fn exec_phases_duplicate_names() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    std::fs::write(&conf, "")?;
+
+    let conf_path_str = conf.to_str().unwrap();
+    let output = run_agent_tee(
+        AGENT_BIN,
+        &[
+            "--plugins",
+            "csv",
+            "--config",
+            conf_path_str,
+            "exec-phases",
+            "--phase",
+            "benchmark=true",
+            "--phase",
+            "benchmark=false",
+        ],
+        tmp_dir.path(),
+    )?;
+    assert!(!output.status.success(), "the agent should refuse duplicate phases");
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("the phase benchmark appears more than once"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
 #[test]
 fn plugin_enabled_with_bad_config_should_fail() -> anyhow::Result<()> {
     let tmp_dir = tempfile::tempdir()?;
@@ -140,3 +323,29 @@ fn plugin_enabled_with_bad_config_should_fail() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn invalid_sampling_rate_should_fail() -> anyhow::Result<()> {
+    let tmp_dir = tempfile::tempdir()?;
+    let conf = tmp_dir.path().join("config.toml");
+    let conf_path_str = conf.to_str().unwrap();
+    for rate in ["0.0", "-1.0", "nan"] {
+        std::fs::write(
+            &conf,
+            format!("sampling.cpu_time_delta = {{ max_points_per_second = {rate} }}"),
+        )?;
+        let output = run_agent_tee(
+            AGENT_BIN,
+            &["--plugins", "csv", "--config", conf_path_str, "exec", "sleep", "0"],
+            tmp_dir.path(),
+        )?;
+        assert!(!output.status.success(), "command should fail with rate {rate}");
+
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(
+            stderr.contains("must be strictly positive"),
+            "unexpected error: {stderr}"
+        );
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
 //! Integration tests for the relay mode, client and server together.
+// The tests stop the agents with SIGTERM.
+#![cfg(unix)]
+
 pub mod common;
 
 use anyhow::{Context, anyhow};
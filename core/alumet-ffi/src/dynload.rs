@@ -246,6 +246,11 @@ pub fn load_cdylib(file: &Path) -> Result<PluginMetadata, LoadError> {
             }),
             None => Box::new(|| Ok(None)),
         },
+        // Dynamic plugins cannot declare their capabilities yet, they are refused by restrictive policies.
+        capabilities: Box::new(|_| Ok(None)),
+        config_comments: Vec::new(),
+        // the log messages of a dynamic plugin don't have a predictable target
+        log_target: None,
     };
 
     Ok(initializable_info)
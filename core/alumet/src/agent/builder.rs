@@ -1,13 +1,23 @@
 //! Builder for Alumet agents.
 
-use std::{collections::HashMap, ops::DerefMut, time::Duration};
+use std::{
+    collections::HashMap,
+    ops::DerefMut,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::{Context, anyhow};
 use thiserror::Error;
 
 use crate::agent::plugin::PluginInfo;
+use crate::metrics::Metric;
 use crate::pipeline::error::PipelineError;
+use crate::pipeline::naming::{OutputName, SourceName, TransformName};
+use crate::plugin::capability::CapabilityPolicy;
 use crate::plugin::phases::PreStartAction;
+use crate::plugin::rust::InvalidConfigField;
+use crate::plugin::state::DEFAULT_STATE_ROOT;
 use crate::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable, Plugin};
 use crate::{
     pipeline::{self, naming::PluginName},
@@ -34,6 +44,12 @@ pub struct Builder {
 
     /// Functions called during the agent startup.
     callbacks: Callbacks,
+
+    /// Restricts the capabilities of the plugins, if set.
+    capability_policy: Option<CapabilityPolicy>,
+
+    /// Parent of the state directories of the plugins.
+    state_root: PathBuf,
 }
 
 struct Callbacks {
@@ -59,6 +75,20 @@ pub enum AgentShutdownError {
     TimeoutExpired,
 }
 
+/// What a [dry run](Builder::dry_run) of the agent would have registered.
+#[derive(Debug)]
+pub struct DryRunReport {
+    /// Name and version of the plugins that have been started.
+    pub plugins: Vec<(String, String)>,
+    /// Name of the disabled plugins.
+    pub disabled_plugins: Vec<String>,
+    /// Metrics, in the order of registration.
+    pub metrics: Vec<Metric>,
+    pub sources: Vec<SourceName>,
+    pub transforms: Vec<TransformName>,
+    pub outputs: Vec<OutputName>,
+}
+
 #[derive(Debug, Error)]
 #[error("{} errors while shutting the agent down", errors.len())]
 pub struct ShutdownError {
@@ -96,9 +126,31 @@ impl Builder {
             plugins,
             pipeline_builder,
             callbacks: Callbacks::default(),
+            capability_policy: None,
+            state_root: PathBuf::from(DEFAULT_STATE_ROOT),
         }
     }
 
+    /// Sets the capability policy of the agent.
+    ///
+    /// Before initializing the plugins, the agent checks the capabilities that they require
+    /// against the policy. If a plugin requires a capability that is not allowed, the startup fails.
+    ///
+    /// By default, there is no policy: every capability is allowed.
+    pub fn capability_policy(mut self, policy: CapabilityPolicy) -> Self {
+        self.capability_policy = Some(policy);
+        self
+    }
+
+    /// Sets the directory that contains the state directories of the plugins.
+    ///
+    /// Each plugin gets its own subdirectory, see [`AlumetPluginStart::state_dir`].
+    /// By default, the state is stored in [`DEFAULT_STATE_ROOT`], relative to the working directory.
+    pub fn state_directory(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_root = path.into();
+        self
+    }
+
     /// Sets a function to run after the plugins have been initialized.
     ///
     /// There can be only one callback. If this function is called more than once,
@@ -137,88 +189,6 @@ impl Builder {
 
     /// Builds and starts the underlying measurement pipeline and the enabled plugins.
     pub fn build_and_start(self) -> anyhow::Result<RunningAgent> {
-        /// Initializes one plugin.
-        ///
-        /// Returns the initialized plugin, or an error.
-        fn init_plugin(p: PluginInfo) -> anyhow::Result<Box<dyn Plugin>> {
-            let name = p.metadata.name;
-            let version = p.metadata.version;
-            let config = match p.config {
-                Some(config) => Some(ConfigTable(config)),
-                None => {
-                    // no config has been provided for this plugin, use its default config
-                    (p.metadata.default_config)()
-                        .with_context(|| format!("failed to generate default config of plugin {name} v{version}"))?
-                }
-            };
-            let config = config.unwrap_or_default();
-            log::debug!("Initializing plugin {name} v{version} with config {config:?}...");
-
-            // call init
-            let initialized = (p.metadata.init)(config)
-                .with_context(|| format!("plugin failed to initialize: {} v{}", name, version))?;
-
-            // check that the plugin corresponds to its metadata
-            if (initialized.name(), initialized.version()) != (&name, &version) {
-                return Err(anyhow!(
-                    "invalid plugin: metadata is '{name}' v{version} but the plugin's methods return '{name}' v{version}"
-                ));
-            }
-            Ok(initialized)
-        }
-
-        /// Starts a plugin, i.e. calls [`Plugin::start`] with the right context.
-        fn start_plugin(
-            p: &mut dyn Plugin,
-            pipeline_builder: &mut pipeline::Builder,
-            pre_start_actions: &mut Vec<(PluginName, Box<dyn PreStartAction>)>,
-            post_start_actions: &mut Vec<(PluginName, Box<dyn PostStartAction>)>,
-        ) -> anyhow::Result<()> {
-            let name = p.name().to_owned();
-            let version = p.version().to_owned();
-            log::debug!("Starting plugin {name} v{version}...");
-
-            let mut ctx = AlumetPluginStart {
-                current_plugin: PluginName(name.clone()),
-                pipeline_builder,
-                pre_start_actions,
-                post_start_actions,
-            };
-            p.start(&mut ctx)
-                .with_context(|| format!("plugin failed to start: {name} v{version}"))
-        }
-
-        /// Executes the pre-pipeline-start phase of a plugin, i.e. calls [`Plugin::pre_pipeline_start`] with the right context.
-        fn pre_pipeline_start(
-            p: &mut dyn Plugin,
-            pipeline_builder: &mut pipeline::Builder,
-            actions: &mut HashMap<PluginName, Vec<Box<dyn PreStartAction>>>,
-        ) -> anyhow::Result<()> {
-            let name = p.name().to_owned();
-            let version = p.version().to_owned();
-            log::debug!("Running pre-pipeline-start hook for plugin {name} v{version}...");
-
-            // Prepare the context.
-            let pname = PluginName(name.clone());
-            let mut ctx = AlumetPreStart {
-                current_plugin: pname.clone(),
-                pipeline_builder,
-            };
-
-            // Call pre_pipeline_start.
-            p.pre_pipeline_start(&mut ctx)
-                .with_context(|| format!("plugin pre_pipeline_start failed: {} v{}", p.name(), p.version()))?;
-
-            // Run the additional actions registered by the plugin, if any.
-            if let Some(actions) = actions.remove(&pname) {
-                for f in actions {
-                    (f)(&mut ctx)
-                        .with_context(|| format!("plugin post-pipeline-start action failed: {name} v{version}"))?;
-                }
-            }
-            Ok(())
-        }
-
         /// Executes the post-pipeline-start phase of a plugin, i.e. calls [`Plugin::post_pipeline_start`] with the right context.
         ///
         /// Plugins can also register post-pipeline-start actions in the form of closures, we run these too.
@@ -252,26 +222,18 @@ impl Builder {
             Ok(())
         }
 
-        /// Groups all pre or post-start actions by plugin.
-        fn group_plugin_actions<BoxedAction>(
-            post_start_actions: Vec<(PluginName, BoxedAction)>,
-            n_plugins: usize,
-        ) -> HashMap<PluginName, Vec<BoxedAction>> {
-            let mut res = HashMap::with_capacity(n_plugins);
-            for (plugin, action) in post_start_actions {
-                let plugin_actions: &mut Vec<_> = res.entry(plugin).or_default();
-                plugin_actions.push(action);
-            }
-            res
-        }
-
         // Find which plugins are enabled.
         log::info!("Initializing the plugins...");
         let (enabled_plugins, disabled_plugins): (Vec<PluginInfo>, Vec<PluginInfo>) = self.plugins.into_partition();
 
+        // Check the capabilities before running any code of the plugins.
+        let enabled_plugins = configure_plugins(enabled_plugins, self.capability_policy.as_ref())?;
+
         // Initialize the plugins that are enabled.
-        let initialized_plugins: anyhow::Result<Vec<Box<dyn Plugin>>> =
-            enabled_plugins.into_iter().map(init_plugin).collect();
+        let initialized_plugins: anyhow::Result<Vec<Box<dyn Plugin>>> = enabled_plugins
+            .into_iter()
+            .map(|(p, config)| init_plugin(p, config))
+            .collect();
         let mut initialized_plugins = initialized_plugins?;
         let n_plugins = initialized_plugins.len();
         match n_plugins {
@@ -297,6 +259,7 @@ impl Builder {
                 &mut pipeline_builder,
                 &mut pre_start_actions,
                 &mut post_start_actions,
+                &self.state_root,
             )?;
         }
         print_stats(&mut pipeline_builder, &initialized_plugins, &disabled_plugins);
@@ -332,6 +295,75 @@ impl Builder {
         Ok(agent)
     }
 
+    /// Initializes and starts the enabled plugins, runs their pre-pipeline-start hooks and checks the
+    /// measurement pipeline, then stops the plugins without building the pipeline.
+    ///
+    /// This allows to check the configuration and the plugins without triggering any source.
+    /// Note that plugins may still perform some I/O in their `init` and `start` methods.
+    ///
+    /// Returns a report of what would have been registered in the pipeline.
+    pub fn dry_run(self) -> anyhow::Result<DryRunReport> {
+        let (enabled_plugins, disabled_plugins): (Vec<PluginInfo>, Vec<PluginInfo>) = self.plugins.into_partition();
+        let enabled_plugins = configure_plugins(enabled_plugins, self.capability_policy.as_ref())?;
+
+        log::info!("Initializing the plugins...");
+        let initialized_plugins: anyhow::Result<Vec<Box<dyn Plugin>>> = enabled_plugins
+            .into_iter()
+            .map(|(p, config)| init_plugin(p, config))
+            .collect();
+        let mut initialized_plugins = initialized_plugins?;
+
+        log::info!("Starting the plugins...");
+        let mut pipeline_builder = self.pipeline_builder;
+        let mut pre_start_actions = Vec::new();
+        let mut post_start_actions = Vec::new();
+        for plugin in initialized_plugins.iter_mut() {
+            start_plugin(
+                plugin.deref_mut(),
+                &mut pipeline_builder,
+                &mut pre_start_actions,
+                &mut post_start_actions,
+                &self.state_root,
+            )?;
+        }
+
+        // Run the pre-pipeline-start hooks and check the pipeline, like `build_and_start` does before starting it.
+        let mut pre_actions_per_plugin = group_plugin_actions(pre_start_actions, initialized_plugins.len());
+        for plugin in initialized_plugins.iter_mut() {
+            pre_pipeline_start(plugin.deref_mut(), &mut pipeline_builder, &mut pre_actions_per_plugin)?;
+        }
+        pipeline_builder.check().context("invalid pipeline")?;
+
+        // Gather the report.
+        let inspector = pipeline_builder.inspect();
+        let mut metrics: Vec<_> = inspector.metrics().iter().collect();
+        metrics.sort_by_key(|(id, _)| id.0);
+        let report = DryRunReport {
+            plugins: initialized_plugins
+                .iter()
+                .map(|p| (p.name().to_owned(), p.version().to_owned()))
+                .collect(),
+            disabled_plugins: disabled_plugins.into_iter().map(|p| p.metadata.name).collect(),
+            metrics: metrics.into_iter().map(|(_, m)| m.to_owned()).collect(),
+            sources: inspector.sources(),
+            transforms: inspector.transforms(),
+            outputs: inspector.outputs(),
+        };
+
+        // Drop the pipeline elements before stopping the plugins, like in a normal shutdown.
+        drop(pre_actions_per_plugin);
+        drop(post_start_actions);
+        drop(pipeline_builder);
+        log::info!("Stopping the plugins...");
+        for mut plugin in initialized_plugins {
+            let name = plugin.name().to_owned();
+            plugin
+                .stop()
+                .with_context(|| format!("plugin failed to stop: {name}"))?;
+        }
+        Ok(report)
+    }
+
     /// Applies test expectations to this builder.
     #[cfg(feature = "test")]
     pub fn with_expectations<E: TestExpectations>(self, expectations: E) -> Self {
@@ -344,6 +376,165 @@ impl Builder {
     }
 }
 
+/// Returns the config of each plugin, after having checked the capabilities that the plugin requires
+/// with this config.
+fn configure_plugins(
+    plugins: Vec<PluginInfo>,
+    policy: Option<&CapabilityPolicy>,
+) -> anyhow::Result<Vec<(PluginInfo, ConfigTable)>> {
+    plugins
+        .into_iter()
+        .map(|p| {
+            let config = plugin_config(&p)?;
+            check_capabilities(&p, &config, policy)?;
+            Ok((p, config))
+        })
+        .collect()
+}
+
+/// Returns the config of a plugin: the config that has been provided, or the default config of the plugin.
+fn plugin_config(p: &PluginInfo) -> anyhow::Result<ConfigTable> {
+    let name = &p.metadata.name;
+    let version = &p.metadata.version;
+    let config = match &p.config {
+        Some(config) => Some(ConfigTable(config.clone())),
+        None => {
+            // no config has been provided for this plugin, use its default config
+            (p.metadata.default_config)()
+                .with_context(|| format!("failed to generate default config of plugin {name} v{version}"))?
+        }
+    };
+    Ok(config.unwrap_or_default())
+}
+
+/// Logs the capabilities required by a plugin with the given config and checks them against the policy.
+fn check_capabilities(p: &PluginInfo, config: &ConfigTable, policy: Option<&CapabilityPolicy>) -> anyhow::Result<()> {
+    let name = &p.metadata.name;
+    let capabilities = (p.metadata.capabilities)(config)
+        .with_context(|| format!("failed to determine the capabilities of plugin {name}"))?;
+    let Some(capabilities) = capabilities else {
+        log::info!("Plugin {name} does not declare the capabilities that it requires.");
+        if policy.is_some_and(|policy| !policy.is_unrestricted()) {
+            return Err(anyhow!(
+                "plugin {name} does not declare its capabilities, which is not allowed by the policy"
+            ));
+        }
+        return Ok(());
+    };
+    if !capabilities.is_empty() {
+        let list = capabilities
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        log::info!("Plugin {name} requires the following capabilities: {list}");
+    }
+    if let Some(policy) = policy {
+        let violations = policy.violations(&capabilities);
+        if !violations.is_empty() {
+            let list = violations.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", ");
+            return Err(anyhow!(
+                "plugin {name} requires capabilities that are not allowed by the policy: {list}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Initializes one plugin.
+///
+/// Returns the initialized plugin, or an error.
+fn init_plugin(p: PluginInfo, config: ConfigTable) -> anyhow::Result<Box<dyn Plugin>> {
+    let name = p.metadata.name;
+    let version = p.metadata.version;
+    log::debug!("Initializing plugin {name} v{version} with config {config:?}...");
+
+    // call init
+    let initialized = (p.metadata.init)(config)
+        .map_err(|mut e| {
+            // make the path of the invalid field (if any) absolute, to help the user to find it
+            if let Some(field) = e.downcast_mut::<InvalidConfigField>() {
+                field.path.splice(0..0, [String::from("plugins"), name.clone()]);
+            }
+            e
+        })
+        .with_context(|| format!("plugin failed to initialize: {} v{}", name, version))?;
+
+    // check that the plugin corresponds to its metadata
+    if (initialized.name(), initialized.version()) != (&name, &version) {
+        return Err(anyhow!(
+            "invalid plugin: metadata is '{name}' v{version} but the plugin's methods return '{name}' v{version}"
+        ));
+    }
+    Ok(initialized)
+}
+
+/// Starts a plugin, i.e. calls [`Plugin::start`] with the right context.
+fn start_plugin(
+    p: &mut dyn Plugin,
+    pipeline_builder: &mut pipeline::Builder,
+    pre_start_actions: &mut Vec<(PluginName, Box<dyn PreStartAction>)>,
+    post_start_actions: &mut Vec<(PluginName, Box<dyn PostStartAction>)>,
+    state_root: &Path,
+) -> anyhow::Result<()> {
+    let name = p.name().to_owned();
+    let version = p.version().to_owned();
+    log::debug!("Starting plugin {name} v{version}...");
+
+    let mut ctx = AlumetPluginStart {
+        current_plugin: PluginName(name.clone()),
+        pipeline_builder,
+        pre_start_actions,
+        post_start_actions,
+        state_root,
+    };
+    p.start(&mut ctx)
+        .with_context(|| format!("plugin failed to start: {name} v{version}"))
+}
+
+/// Executes the pre-pipeline-start phase of a plugin, i.e. calls [`Plugin::pre_pipeline_start`] with the right context.
+fn pre_pipeline_start(
+    p: &mut dyn Plugin,
+    pipeline_builder: &mut pipeline::Builder,
+    actions: &mut HashMap<PluginName, Vec<Box<dyn PreStartAction>>>,
+) -> anyhow::Result<()> {
+    let name = p.name().to_owned();
+    let version = p.version().to_owned();
+    log::debug!("Running pre-pipeline-start hook for plugin {name} v{version}...");
+
+    // Prepare the context.
+    let pname = PluginName(name.clone());
+    let mut ctx = AlumetPreStart {
+        current_plugin: pname.clone(),
+        pipeline_builder,
+    };
+
+    // Call pre_pipeline_start.
+    p.pre_pipeline_start(&mut ctx)
+        .with_context(|| format!("plugin pre_pipeline_start failed: {} v{}", p.name(), p.version()))?;
+
+    // Run the additional actions registered by the plugin, if any.
+    if let Some(actions) = actions.remove(&pname) {
+        for f in actions {
+            (f)(&mut ctx).with_context(|| format!("plugin post-pipeline-start action failed: {name} v{version}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Groups all pre or post-start actions by plugin.
+fn group_plugin_actions<BoxedAction>(
+    post_start_actions: Vec<(PluginName, BoxedAction)>,
+    n_plugins: usize,
+) -> HashMap<PluginName, Vec<BoxedAction>> {
+    let mut res = HashMap::with_capacity(n_plugins);
+    for (plugin, action) in post_start_actions {
+        let plugin_actions: &mut Vec<_> = res.entry(plugin).or_default();
+        plugin_actions.push(action);
+    }
+    res
+}
+
 impl RunningAgent {
     /// Waits until the measurement pipeline stops, then stops the plugins.
     ///
@@ -42,6 +42,28 @@
 //!
 //! // TODO use the config
 //! ```
+//!
+//! # Layers
+//!
+//! The final configuration is built by merging several layers, in this order:
+//! 1. the configuration file (or the default configuration);
+//! 2. the selected [profiles](Loader::with_profiles), defined in the `profiles` table of the file;
+//! 3. the [environment variables](Loader::with_env_overrides), such as `ALUMET__PLUGINS__CSV__OUTPUT_PATH`;
+//! 4. the [overrides](Loader::with_override), usually provided on the command line.
+//!
+//! Each layer overrides the values of the previous ones.
+//!
+//! ```toml
+//! [plugins.kwollect-input]
+//! site = "nancy"
+//!
+//! # With the profile `lyon`, the site is "lyon".
+//! [profiles.lyon.plugins.kwollect-input]
+//! site = "lyon"
+//! ```
+//!
+//! Finally, the [secrets](super::secrets) referenced in the configuration, such as `${file:/run/secrets/token}`,
+//! are resolved.
 use std::collections::BTreeMap;
 use std::io;
 use std::path::PathBuf;
@@ -52,7 +74,8 @@ use anyhow::anyhow;
 use serde::Serialize;
 
 use super::plugin::{PluginFilter, PluginSet};
-use crate::plugin::PluginMetadata;
+use super::secrets::resolve_secrets;
+use crate::plugin::{ConfigTable, PluginMetadata};
 use error::*;
 
 /// Loads the agent configuration from a TOML file.
@@ -67,6 +90,10 @@ pub struct Loader<'d> {
     overrides: Option<toml::Table>,
     /// Should environment variable substitution be applied before deserializing?
     substitute_env: bool,
+    /// Profiles to apply, in order.
+    profiles: Vec<String>,
+    /// Prefix of the environment variables that override the config, if enabled.
+    env_prefix: Option<String>,
 }
 
 /// Generates default configurations.
@@ -96,6 +123,8 @@ pub trait DefaultConfigProvider {
 pub struct AutoDefaultConfigProvider<'p, A: Serialize, F: Fn() -> A> {
     plugins: &'p PluginSet,
     default_general_options: F,
+    /// Comments of the general options, by option path.
+    general_comments: Vec<(String, String)>,
 }
 
 /// When asked to generate a default configuration, fails with an error.
@@ -110,6 +139,8 @@ impl<'d> Loader<'d> {
             save_default: false,
             overrides: None,
             substitute_env: false,
+            profiles: Vec::new(),
+            env_prefix: None,
         }
     }
 
@@ -158,6 +189,26 @@ impl<'d> Loader<'d> {
         self
     }
 
+    /// Applies the given profiles, in order.
+    ///
+    /// A profile is a table `profiles.<name>` of the configuration file, which is [merged](merge_override)
+    /// into the configuration. Loading fails if a profile does not exist.
+    ///
+    /// The `profiles` table is always removed from the loaded configuration.
+    pub fn with_profiles<S: Into<String>>(mut self, profiles: impl IntoIterator<Item = S>) -> Self {
+        self.profiles.extend(profiles.into_iter().map(Into::into));
+        self
+    }
+
+    /// Overrides the content of the configuration with the environment variables that start with `prefix`.
+    ///
+    /// See [`env_overrides`] for the syntax of the variables.
+    /// The environment variables are applied after the profiles, but before the [overrides](Self::with_override).
+    pub fn with_env_overrides(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
     /// Loads the configuration with the provided settings.
     pub fn load(mut self) -> Result<toml::Table, LoadError> {
         self.load_impl().map_err(|e| LoadError {
@@ -170,9 +221,15 @@ impl<'d> Loader<'d> {
         let config_content = self.read_config_or_default()?;
         let config_content = substitute_env(&config_content)?;
         let mut parsed_config = toml::Table::from_str(&config_content)?;
+        apply_profiles(&mut parsed_config, &self.profiles)?;
+        if let Some(prefix) = &self.env_prefix {
+            let vars = std::env::vars_os().filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)));
+            merge_override(&mut parsed_config, env_overrides(prefix, vars));
+        }
         if let Some(overrides) = self.overrides.take() {
             merge_override(&mut parsed_config, overrides);
         }
+        resolve_secrets(&mut parsed_config)?;
         Ok(parsed_config)
     }
 
@@ -218,8 +275,21 @@ impl<'p, A: Serialize, F: Fn() -> A> AutoDefaultConfigProvider<'p, A, F> {
         Self {
             plugins,
             default_general_options,
+            general_comments: Vec::new(),
         }
     }
+
+    /// Sets the comments that explain the general options in the generated configuration.
+    ///
+    /// Like [`AlumetPlugin::config_comments`](crate::plugin::rust::AlumetPlugin::config_comments),
+    /// each comment is associated with the path of an option, for instance `log.level`.
+    pub fn with_general_comments<'c>(mut self, comments: impl IntoIterator<Item = (&'c str, &'c str)>) -> Self {
+        self.general_comments = comments
+            .into_iter()
+            .map(|(path, comment)| (path.to_owned(), comment.to_owned()))
+            .collect();
+        self
+    }
 }
 
 impl<'p, A: Serialize, F: Fn() -> A> DefaultConfigProvider for AutoDefaultConfigProvider<'p, A, F> {
@@ -232,8 +302,109 @@ impl<'p, A: Serialize, F: Fn() -> A> DefaultConfigProvider for AutoDefaultConfig
         config.insert(String::from("plugins"), toml::Value::Table(plugins_table));
         Ok(config)
     }
+
+    /// Generates a commented configuration: the general options come first, followed by
+    /// one section per enabled plugin, preceded by the name, version and capabilities of the plugin.
+    /// The options are preceded by their comments, if any (see [`Self::with_general_comments`]).
+    fn default_config_string(&self) -> anyhow::Result<String> {
+        let general = toml::Table::try_from((self.default_general_options)())?;
+        let mut res = String::from(DEFAULT_CONFIG_HEADER);
+        if !general.is_empty() {
+            res.push('\n');
+            write_commented(&mut res, &toml::to_string_pretty(&general)?, "", &self.general_comments);
+        }
+        for plugin in self.plugins.metadata(PluginFilter::Enabled) {
+            let Some(plugin_config) = generate_plugin_configs([plugin])?.remove(&plugin.name) else {
+                continue; // the plugin has no config
+            };
+            res.push_str(&format!("\n# Plugin {} v{}\n", plugin.name, plugin.version));
+            let capabilities =
+                (plugin.capabilities)(&ConfigTable(plugin_config.as_table().cloned().unwrap_or_default()))?;
+            match &capabilities {
+                Some(capabilities) if !capabilities.is_empty() => {
+                    let capabilities: Vec<String> = capabilities.iter().map(|c| c.to_string()).collect();
+                    res.push_str(&format!("# Requires: {}\n", capabilities.join(", ")));
+                }
+                Some(_) => (),
+                None => res.push_str("# Requires: undeclared capabilities\n"),
+            }
+            let section = toml::Table::from_iter([(
+                String::from("plugins"),
+                toml::Value::Table(toml::Table::from_iter([(plugin.name.clone(), plugin_config)])),
+            )]);
+            let prefix = format!("plugins.{}", plugin.name);
+            write_commented(
+                &mut res,
+                &toml::to_string_pretty(&section)?,
+                &prefix,
+                &plugin.config_comments,
+            );
+        }
+        Ok(res)
+    }
+}
+
+/// Writes the TOML document `toml` to `out`, with the comments above the options.
+///
+/// The paths of the `comments` are relative to the table `prefix` (empty for the root table).
+/// The document is expected to be formatted by [`toml::to_string_pretty`]: one table header or
+/// one `key = value` per line, the values that span several lines being indented.
+fn write_commented(out: &mut String, toml: &str, prefix: &str, comments: &[(String, String)]) {
+    fn relative<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+        if prefix.is_empty() {
+            return Some(path);
+        }
+        match path.strip_prefix(prefix)? {
+            "" => Some(""),
+            rest => rest.strip_prefix('.'),
+        }
+    }
+
+    let mut table = String::new(); // path of the current table, relative to the prefix
+    let mut in_multiline_string = false;
+    for line in toml.lines() {
+        let path = if in_multiline_string || line.starts_with([' ', ']', '#']) {
+            None
+        } else if line.starts_with('[') {
+            let header = line.trim_matches(['[', ']']);
+            table = relative(header, prefix).unwrap_or(header).to_owned();
+            Some(table.clone())
+        } else if let Some((key, _)) = line.split_once(" = ") {
+            let key = key.trim_matches('"');
+            Some(if table.is_empty() {
+                key.to_owned()
+            } else {
+                format!("{table}.{key}")
+            })
+        } else {
+            None
+        };
+        if line.matches("\"\"\"").count() % 2 == 1 || line.matches("'''").count() % 2 == 1 {
+            in_multiline_string = !in_multiline_string;
+        }
+
+        let comment = path
+            .filter(|p| !p.is_empty())
+            .and_then(|p| comments.iter().find(|(path, _)| *path == p));
+        if let Some((_, comment)) = comment {
+            for comment_line in comment.lines() {
+                out.push_str(format!("# {comment_line}").trim_end());
+                out.push('\n');
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
 }
 
+/// Comment at the beginning of the configuration generated by [`AutoDefaultConfigProvider`].
+const DEFAULT_CONFIG_HEADER: &str = "\
+# Configuration of the Alumet agent.
+#
+# Each [plugins.<name>] section configures one plugin.
+# To disable a plugin, remove its section or set `enabled = false` in it.
+";
+
 impl DefaultConfigProvider for NoDefaultConfigProvider {
     fn default_config(&self) -> anyhow::Result<toml::Table> {
         Err(anyhow!("no default config available"))
@@ -246,6 +417,8 @@ impl DefaultConfigProvider for NoDefaultConfigProvider {
 /// The pattern can be escaped to prevent its replacement: `\${NOT_A_VAR}`.
 /// If a variable does not exist or is invalid, returns an error.
 ///
+/// The [secret references](super::secrets) of the form `${provider:name}` are not replaced by this function.
+///
 pub fn substitute_env(mut input: &'_ str) -> Result<Cow<'_, str>, InvalidSubstitutionError> {
     // Look for the first substitution.
     let first = input.find("${");
@@ -277,6 +450,11 @@ pub fn substitute_env(mut input: &'_ str) -> Result<Cow<'_, str>, InvalidSubstit
                     // unclosed substitution: "${substitution never ends..."
                     return Err(InvalidSubstitutionError::WrongSyntax);
                 }
+                Some(end) if input[2..end].contains(':') => {
+                    // secret reference: "${provider:name}", resolved later
+                    res.push_str(&input[..=end]);
+                    next_start = end + 1;
+                }
                 Some(end) => {
                     // correct substitution syntax: "${VAR_NAME}"
                     let env_var_name = &input[2..end];
@@ -521,10 +699,22 @@ pub mod error {
         #[error("env var substitution failed")]
         Substitution(#[from] InvalidSubstitutionError),
 
+        /// A profile has been selected but it is not defined in the config.
+        #[error("profile {0} does not exist in the config")]
+        UnknownProfile(String),
+
+        /// A value of the config has the wrong type.
+        #[error("invalid config structure")]
+        BadType(#[from] BadTypeError),
+
         /// The config file was read but could not be parsed to a valid TOML structure
         /// (after environment variable substitution).
         #[error("invalid TOML config")]
         InvalidToml(#[from] toml::de::Error),
+
+        /// A secret referenced in the config could not be resolved.
+        #[error("secret resolution failed")]
+        Secret(#[from] crate::agent::secrets::SecretError),
     }
 
     /// Environment variable substitution failed.
@@ -574,6 +764,305 @@ pub mod error {
     }
 }
 
+/// Removes the `profiles` table from the config and merges the selected profiles into the config.
+fn apply_profiles(config: &mut toml::Table, selected: &[String]) -> Result<(), LoadErrorCause> {
+    let mut profiles = match config.remove("profiles") {
+        Some(toml::Value::Table(t)) => t,
+        Some(bad) => return Err(BadTypeError::new(String::from("profiles"), "table", bad).into()),
+        None => toml::Table::new(),
+    };
+    for name in selected {
+        match profiles.remove(name) {
+            Some(toml::Value::Table(profile)) => merge_override(config, profile),
+            Some(bad) => return Err(BadTypeError::new(format!("profiles.{name}"), "table", bad).into()),
+            None => return Err(LoadErrorCause::UnknownProfile(name.to_owned())),
+        }
+    }
+    Ok(())
+}
+
+/// Builds a configuration table from environment variables.
+///
+/// Only the variables that start with `prefix` are used. The rest of the name of the variable
+/// is the path to the config value, with `__` (two underscores) as a separator, in any case.
+/// To refer to a plugin, use `PLUGINS` (or `PLUGIN`) followed by the name of the plugin,
+/// where `-` is replaced by `_`.
+///
+/// The value is parsed as a TOML value if possible (for instance `123`, `true` or `["a", "b"]`),
+/// otherwise it is used as a string.
+///
+/// # Example
+/// ```
+/// use alumet::agent::config::env_overrides;
+///
+/// let vars = vec![
+///     (String::from("ALUMET__PLUGIN__KWOLLECT_INPUT__SITE"), String::from("lyon")),
+///     (String::from("ALUMET__MAX_UPDATE_INTERVAL"), String::from("2s")),
+///     (String::from("ALUMET__PLUGINS__CSV__FORCE_FLUSH"), String::from("false")),
+///     (String::from("PATH"), String::from("/usr/bin")),
+/// ];
+/// let config = env_overrides("ALUMET__", vars);
+/// assert_eq!(config, toml::toml! {
+///     max_update_interval = "2s"
+///     [plugins.kwollect-input]
+///     site = "lyon"
+///     [plugins.csv]
+///     force_flush = false
+/// });
+/// ```
+pub fn env_overrides(prefix: &str, vars: impl IntoIterator<Item = (String, String)>) -> toml::Table {
+    let mut res = toml::Table::new();
+    for (name, value) in vars {
+        let Some(path) = name.strip_prefix(prefix) else {
+            continue;
+        };
+        let mut keys: Vec<String> = path.split("__").map(|k| k.to_lowercase()).collect();
+        if keys.iter().any(|k| k.is_empty()) {
+            log::warn!("Ignoring environment variable {name}: invalid config path.");
+            continue;
+        }
+        if keys[0] == "plugin" || keys[0] == "plugins" {
+            keys[0] = String::from("plugins");
+            if let Some(plugin) = keys.get_mut(1) {
+                *plugin = plugin.replace('_', "-");
+            }
+        }
+
+        // parse the value
+        let value = toml::Table::from_str(&format!("v = {value}"))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .unwrap_or(toml::Value::String(value));
+
+        // build the nested tables
+        let mut layer = toml::Table::new();
+        let last = keys.pop().unwrap();
+        layer.insert(last, value);
+        for key in keys.into_iter().rev() {
+            let mut parent = toml::Table::new();
+            parent.insert(key, toml::Value::Table(layer));
+            layer = parent;
+        }
+        merge_override(&mut res, layer);
+    }
+    res
+}
+
+/// Finds the position of a value in a TOML document.
+///
+/// `path` is a list of keys (for tables) or indices (for arrays) that leads to the value,
+/// for instance the [`path`](crate::plugin::rust::InvalidConfigField::path) of an invalid field.
+/// If the value does not exist, the position of its closest parent is returned.
+///
+/// Returns the line and column of the value, both starting at 1,
+/// or `None` if the document cannot be parsed.
+pub fn find_value_position(document: &str, path: &[String]) -> Option<(usize, usize)> {
+    use toml::de::{DeTable, DeValue};
+
+    let root = DeTable::parse(document).ok()?;
+    let mut span = root.span();
+    let mut table = Some(root.get_ref());
+    let mut array: Option<&[_]> = None;
+    for segment in path {
+        let value = match (table, array) {
+            (Some(t), _) => t.get(segment.as_str()),
+            (None, Some(a)) => segment.parse::<usize>().ok().and_then(|i| a.get(i)),
+            (None, None) => None,
+        };
+        let Some(value) = value else {
+            break;
+        };
+        span = value.span();
+        (table, array) = match value.get_ref() {
+            DeValue::Table(t) => (Some(t), None),
+            DeValue::Array(a) => (None, Some(a.as_ref())),
+            _ => (None, None),
+        };
+    }
+
+    // convert the byte offset to a line and a column
+    let before = &document[..span.start];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    Some((line, column))
+}
+
+#[cfg(test)]
+mod tests_layers {
+    use std::io::Write;
+
+    use super::{Loader, apply_profiles, env_overrides};
+
+    #[test]
+    fn profiles() {
+        let mut config = toml::toml! {
+            site = "nancy"
+            [plugins.csv]
+            output_path = "a.csv"
+            [profiles.lyon]
+            site = "lyon"
+            [profiles.ci.plugins.csv]
+            output_path = "ci.csv"
+        };
+        apply_profiles(&mut config, &[String::from("lyon"), String::from("ci")]).unwrap();
+        assert_eq!(
+            config,
+            toml::toml! {
+                site = "lyon"
+                [plugins.csv]
+                output_path = "ci.csv"
+            }
+        );
+
+        let mut config = toml::toml! {
+            [profiles.lyon]
+            site = "lyon"
+        };
+        assert!(apply_profiles(&mut config, &[String::from("rennes")]).is_err());
+    }
+
+    #[test]
+    fn env() {
+        let vars = vec![
+            (
+                String::from("ALUMET__PLUGIN__PROCFS__KERNEL__POLL_INTERVAL"),
+                String::from("1s"),
+            ),
+            (
+                String::from("ALUMET__PLUGIN__KWOLLECT_INPUT__METRICS"),
+                String::from("[\"a\", \"b\"]"),
+            ),
+            (String::from("ALUMET__SOURCE_CHANNEL_SIZE"), String::from("128")),
+            (String::from("ALUMET__"), String::from("ignored")),
+            (String::from("ALUMET__PLUGIN____X"), String::from("ignored")),
+            (String::from("ALUMET_CONFIG"), String::from("ignored")),
+        ];
+        assert_eq!(
+            env_overrides("ALUMET__", vars),
+            toml::toml! {
+                source_channel_size = 128
+                [plugins.procfs.kernel]
+                poll_interval = "1s"
+                [plugins.kwollect-input]
+                metrics = ["a", "b"]
+            }
+        );
+    }
+
+    #[test]
+    fn load_with_layers() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "{}",
+            indoc::indoc! {r#"
+                a = 1
+                b = 1
+                c = 1
+                [profiles.p]
+                a = 2
+                b = 2
+                c = 2
+            "#}
+        )
+        .unwrap();
+        let config = Loader::parse_file(file.path())
+            .with_profiles(["p"])
+            .with_override(toml::toml! { c = 3 })
+            .load()
+            .unwrap();
+        assert_eq!(config, toml::toml! { a = 2 b = 2 c = 3 });
+    }
+}
+
+#[cfg(test)]
+mod tests_find_value_position {
+    use super::find_value_position;
+
+    fn path(p: &[&str]) -> Vec<String> {
+        p.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn find() {
+        let document = indoc::indoc! {r#"
+            global = 1
+
+            [plugins.kwollect-input]
+            url = "https://example.com"
+            metrics = "bmc_node_power_watt"
+
+            [[plugins.csv.columns]]
+            name = "a"
+            [[plugins.csv.columns]]
+            name = "b"
+        "#};
+        assert_eq!(find_value_position(document, &path(&["global"])), Some((1, 10)));
+        assert_eq!(
+            find_value_position(document, &path(&["plugins", "kwollect-input", "metrics"])),
+            Some((5, 11))
+        );
+        assert_eq!(
+            find_value_position(document, &path(&["plugins", "csv", "columns", "1", "name"])),
+            Some((10, 8))
+        );
+        // missing field: position of the parent table
+        let parent = find_value_position(document, &path(&["plugins", "kwollect-input"]));
+        assert!(parent.is_some());
+        assert_eq!(
+            find_value_position(document, &path(&["plugins", "kwollect-input", "missing"])),
+            parent
+        );
+        // invalid document
+        assert_eq!(find_value_position("[", &path(&["a"])), None);
+    }
+}
+
+#[cfg(test)]
+mod tests_write_commented {
+    use super::write_commented;
+
+    #[test]
+    fn nested_tables() {
+        let toml = "\
+enabled = true
+paths = [
+    \"a\",
+    \"b\",
+]
+
+[plugins.p.amd]
+per_ccd = true
+";
+        let comments = [
+            (String::from("paths"), String::from("Paths to read.")),
+            (String::from("amd"), String::from("Options of AMD CPUs.")),
+            (
+                String::from("amd.per_ccd"),
+                String::from("One measurement per CCD.\n\nSlower."),
+            ),
+        ];
+        let mut out = String::new();
+        write_commented(&mut out, toml, "plugins.p", &comments);
+        let expected = "\
+enabled = true
+# Paths to read.
+paths = [
+    \"a\",
+    \"b\",
+]
+
+# Options of AMD CPUs.
+[plugins.p.amd]
+# One measurement per CCD.
+#
+# Slower.
+per_ccd = true
+";
+        assert_eq!(out, expected);
+    }
+}
+
 #[cfg(test)]
 mod tests_substitute_env {
     use std::borrow::Cow;
@@ -701,6 +1190,16 @@ mod tests_substitute_env {
         assert_eq!(expected, substitute_env(&input).unwrap());
     }
 
+    #[test]
+    fn secret_reference() {
+        let input = "password = '${file:/run/secrets/pass}'";
+        assert_eq!(Cow::Borrowed(input), substitute_env(input).unwrap());
+
+        let input = format!("user = '{SUBSTITUTION}'\npassword = '${{env:PASSWORD}}'");
+        let expected = format!("user = '{ENV_VAR_VALUE}'\npassword = '${{env:PASSWORD}}'");
+        assert_eq!(expected, substitute_env(&input).unwrap());
+    }
+
     #[test]
     fn unclosed() {
         let input = "${";
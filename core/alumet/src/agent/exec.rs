@@ -1,23 +1,30 @@
 //! Spawning child processes and watching them.
 
 use std::{
+    collections::HashSet,
     process::{Command, ExitStatus},
-    time::Duration,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use anyhow::Context;
 
 use crate::{
-    pipeline::{MeasurementPipeline, control::request, naming::matching::SourceNamePattern},
-    plugin::event::EndConsumerMeasurement,
-    plugin::event::StartConsumerMeasurement,
+    measurement::{MeasurementBuffer, Timestamp},
+    pipeline::{
+        MeasurementPipeline, Transform,
+        control::request,
+        elements::{error::TransformError, transform::TransformContext},
+        naming::matching::SourceNamePattern,
+    },
+    plugin::event::{self, EndConsumerMeasurement, PhaseChange, StartConsumerMeasurement},
     resources::ResourceConsumer,
 };
 
 use super::{RunningAgent, builder::ShutdownError};
 use thiserror::Error;
 
-/// Error that can occur in [`exec_process`].
+/// Error that can occur in [`exec_process`] and [`exec_phases`].
 #[derive(Error, Debug)]
 pub enum ExecError {
     /// The process could not be spawned.
@@ -31,42 +38,240 @@ pub enum ExecError {
     Shutdown(#[source] ShutdownError),
 }
 
+/// Name of the phase that runs even if a previous phase has failed, to clean up after the experiment.
+pub const TEARDOWN_PHASE: &str = "teardown";
+
+/// A command that is measured as one phase of an experiment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phase {
+    /// Name of the phase, such as `warmup` or `benchmark`.
+    pub name: String,
+    /// The program to run.
+    pub program: String,
+    /// Arguments to the program.
+    pub args: Vec<String>,
+}
+
 /// Spawns a process that runs `program args` and stops the measurement agent when it exits.
 ///
 /// The measurement sources are triggered before the process spawns and after it exits.
+/// The [`EndConsumerMeasurement`] event contains the exit code of the process and the following metadata:
+/// - `program`: the program that has been executed;
+/// - `command_line`: the program and its arguments, separated by spaces;
+/// - `exit_status`: a description of the exit status, such as `exit status: 0` or `signal: 9 (SIGKILL)`;
+/// - `duration_secs`: the time between the spawn of the process and its exit, in seconds.
 ///
 /// After the process exits, the pipeline must stop within `shutdown_timeout`, or an error is returned.
+/// On success, returns the exit status of the process.
 pub fn exec_process(
     agent: RunningAgent,
     program: String,
     args: Vec<String>,
     shutdown_timeout: Duration,
-) -> Result<(), ExecError> {
+) -> Result<ExitStatus, ExecError> {
+    let exit_status = measure_command(&agent.pipeline, None, program, args)?;
+    log::info!("Alumet will now stop.");
+
+    // Stop the pipeline
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(shutdown_timeout).map_err(ExecError::Shutdown)?;
+    Ok(exit_status)
+}
+
+/// Checks that the phases can be run by [`exec_phases`]: their names must be unique and not empty.
+pub fn check_phases(phases: &[Phase]) -> anyhow::Result<()> {
+    let mut names = HashSet::new();
+    for phase in phases {
+        anyhow::ensure!(!phase.name.is_empty(), "the name of a phase cannot be empty");
+        anyhow::ensure!(
+            names.insert(phase.name.as_str()),
+            "the phase {} appears more than once",
+            phase.name
+        );
+    }
+    Ok(())
+}
+
+/// Runs the phases one after the other, then stops the measurement agent.
+///
+/// Each phase is measured like the command of [`exec_process`], and its [`EndConsumerMeasurement`] event
+/// contains an additional `phase` metadata. A [`PhaseChange`] event is published at the beginning of each phase,
+/// and after the last one. Use a [`PhaseTransform`] to add the `phase` attribute to the measurements.
+///
+/// If a phase fails, the next phases are skipped, except the [`TEARDOWN_PHASE`], which always runs.
+/// Returns the result of the first phase that has failed, or the exit status of the last phase.
+pub fn exec_phases(
+    agent: RunningAgent,
+    phases: Vec<Phase>,
+    shutdown_timeout: Duration,
+) -> Result<ExitStatus, ExecError> {
+    let mut exit_status = ExitStatus::default();
+    let mut failure = None;
+    for phase in phases {
+        if failure.is_some() && phase.name != TEARDOWN_PHASE {
+            log::info!("Skipping phase {}", phase.name);
+            continue;
+        }
+        log::info!("Starting phase {}", phase.name);
+        event::phase_change().publish(PhaseChange {
+            phase: Some(phase.name.clone()),
+            timestamp: Timestamp::now(),
+        });
+        match measure_command(&agent.pipeline, Some(phase.name.clone()), phase.program, phase.args) {
+            Ok(status) if status.success() => exit_status = status,
+            res => {
+                if failure.is_none() {
+                    log::warn!(
+                        "Phase {} has failed, the next phases will be skipped (except {TEARDOWN_PHASE}).",
+                        phase.name
+                    );
+                    failure = Some(res);
+                }
+            }
+        }
+    }
+    event::phase_change().publish(PhaseChange {
+        phase: None,
+        timestamp: Timestamp::now(),
+    });
+    log::info!("Alumet will now stop.");
+
+    // Stop the pipeline
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(shutdown_timeout).map_err(ExecError::Shutdown)?;
+    failure.unwrap_or(Ok(exit_status))
+}
+
+/// A transform that adds the `phase` attribute to the measurements taken during a phase of [`exec_phases`].
+///
+/// The phase of a measurement is determined by its timestamp, according to the [`PhaseChange`] events.
+/// The measurements that already have a `phase` attribute are left untouched.
+pub struct PhaseTransform {
+    timeline: Arc<Mutex<PhaseTimeline>>,
+}
+
+/// The beginning of each phase, in chronological order. `None` marks the end of the last phase.
+type PhaseTimeline = Vec<(Timestamp, Option<String>)>;
+
+impl PhaseTransform {
+    /// Creates a new transform that follows the [`PhaseChange`] events, starting with the last one published.
+    pub fn new() -> Self {
+        let timeline = Arc::new(Mutex::new(Vec::new()));
+        let events = timeline.clone();
+        event::phase_change().subscribe(move |evt| {
+            events.lock().unwrap().push((evt.timestamp, evt.phase));
+            Ok(())
+        });
+        Self { timeline }
+    }
+}
+
+impl Default for PhaseTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transform for PhaseTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        let timeline = self.timeline.lock().unwrap();
+        if timeline.is_empty() {
+            return Ok(());
+        }
+        for m in measurements.iter_mut() {
+            // the last phase that has started before the measurement
+            let i = timeline.partition_point(|(start, _)| *start <= m.timestamp);
+            let phase = match i.checked_sub(1) {
+                Some(i) => &timeline[i].1,
+                None => continue,
+            };
+            if let Some(phase) = phase
+                && m.attributes_keys().all(|k| k != "phase")
+            {
+                m.add_attr("phase", phase.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Measures one command: triggers the sources, spawns the process, waits for it to exit,
+/// triggers the sources again and publishes an [`EndConsumerMeasurement`] event.
+fn measure_command(
+    pipeline: &MeasurementPipeline,
+    phase: Option<String>,
+    program: String,
+    args: Vec<String>,
+) -> Result<ExitStatus, ExecError> {
     // At least one measurement.
-    if let Err(e) = trigger_measurement_now(&agent.pipeline) {
+    if let Err(e) = trigger_measurement_now(pipeline) {
         log::error!("Could not trigger a first measurement before the child spawn: {e}");
     }
 
     // Spawn the process and wait for it to exit.
-    let exit_status = exec_child(program, args)?;
-    log::info!("Child process exited with status {exit_status}, Alumet will now stop.");
+    let command_line = command_line(&program, &args);
+    let start = Instant::now();
+    let (pid, exit_status) = exec_child(program.clone(), args)?;
+    let duration = start.elapsed();
+    log::info!("Child process exited with status {exit_status} after {duration:.3?}.");
 
     // One last measurement.
-    if let Err(e) = trigger_measurement_now(&agent.pipeline) {
+    if let Err(e) = trigger_measurement_now(pipeline) {
         log::error!("Could not trigger one last measurement after the child exit: {e}");
     }
 
     // Publish an event to perform a measurement at the end of the experiment
     log::info!("Publishing EndConsumerMeasurement event");
-    crate::plugin::event::end_consumer_measurement().publish(EndConsumerMeasurement);
+    let mut event = EndConsumerMeasurement::new(vec![ResourceConsumer::Process { pid }])
+        .with_metadata("program", program)
+        .with_metadata("command_line", command_line)
+        .with_metadata("exit_status", exit_status.to_string())
+        .with_metadata("duration_secs", duration.as_secs_f64());
+    if let Some(code) = exit_status.code() {
+        event = event.with_exit_code(code);
+    }
+    if let Some(phase) = phase {
+        event = event.with_metadata("phase", phase);
+    }
+    event::end_consumer_measurement().publish(event);
+    Ok(exit_status)
+}
 
-    // Stop the pipeline
-    agent.pipeline.control_handle().shutdown();
-    agent.wait_for_shutdown(shutdown_timeout).map_err(ExecError::Shutdown)
+/// Returns the code that a shell would report for a process that exited with the given status.
+///
+/// If the process has been killed by a signal, the code is `128 + signal`, like in POSIX shells.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
+
+/// Joins the program and its arguments, quoting the arguments that contain spaces.
+fn command_line(program: &str, args: &[String]) -> String {
+    let mut res = String::from(program);
+    for arg in args {
+        res.push(' ');
+        if arg.is_empty() || arg.contains(char::is_whitespace) {
+            res.push_str(&format!("{arg:?}"));
+        } else {
+            res.push_str(arg);
+        }
+    }
+    res
 }
 
 /// Spawns a child process and waits for it to exit.
-fn exec_child(external_command: String, args: Vec<String>) -> Result<ExitStatus, ExecError> {
+///
+/// Returns the pid of the process and its exit status.
+fn exec_child(external_command: String, args: Vec<String>) -> Result<(u32, ExitStatus), ExecError> {
     // Spawn the process.
     let mut p = Command::new(external_command.clone())
         .args(args)
@@ -76,12 +281,11 @@ fn exec_child(external_command: String, args: Vec<String>) -> Result<ExitStatus,
     // Notify the plugins that there is a process to observe.
     let pid = p.id();
     log::info!("Child process '{external_command}' spawned with pid {pid}.");
-    crate::plugin::event::start_consumer_measurement()
-        .publish(StartConsumerMeasurement(vec![ResourceConsumer::Process { pid }]));
+    event::start_consumer_measurement().publish(StartConsumerMeasurement(vec![ResourceConsumer::Process { pid }]));
 
     // Wait for the process to terminate.
     let status = p.wait().map_err(|e| ExecError::ProcessWait(pid, e))?;
-    Ok(status)
+    Ok((pid, status))
 }
 
 const TRIGGER_TIMEOUT: Duration = Duration::from_secs(1);
@@ -98,3 +302,99 @@ fn trigger_measurement_now(pipeline: &MeasurementPipeline) -> anyhow::Result<()>
         .block_on(send_task)
         .context("failed to send TriggerMessage")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{Phase, PhaseTransform, check_phases, command_line};
+    use crate::{
+        measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::{RawMetricId, registry::MetricRegistry},
+        pipeline::{Transform, elements::transform::TransformContext},
+        plugin::event::{self, PhaseChange},
+        resources::{Resource, ResourceConsumer},
+    };
+
+    #[test]
+    fn test_check_phases() {
+        let phase = |name: &str| Phase {
+            name: name.to_owned(),
+            program: String::from("true"),
+            args: Vec::new(),
+        };
+        assert!(check_phases(&[phase("warmup"), phase("benchmark"), phase("teardown")]).is_ok());
+        assert!(check_phases(&[phase("warmup"), phase("benchmark"), phase("warmup")]).is_err());
+        assert!(check_phases(&[phase("")]).is_err());
+    }
+
+    #[test]
+    fn test_phase_transform() {
+        let at = |secs: u64| Timestamp::from(UNIX_EPOCH + Duration::from_secs(secs));
+        let point = |secs: u64| {
+            MeasurementPoint::new_untyped(
+                at(secs),
+                RawMetricId::from_u64(0),
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                WrappedMeasurementValue::U64(1),
+            )
+        };
+
+        let mut transform = PhaseTransform::new();
+        // ignore the events of the other tests
+        transform.timeline.lock().unwrap().clear();
+        for (secs, phase) in [(10, Some("warmup")), (20, Some("benchmark")), (30, None)] {
+            event::phase_change().publish(PhaseChange {
+                phase: phase.map(String::from),
+                timestamp: at(secs),
+            });
+        }
+
+        let mut buffer = MeasurementBuffer::from(vec![
+            point(5),
+            point(10),
+            point(25),
+            point(25).with_attr("phase", "other"),
+            point(35),
+        ]);
+        let ctx = TransformContext {
+            metrics: &MetricRegistry::new(),
+        };
+        transform.apply(&mut buffer, &ctx).unwrap();
+        let phases: Vec<Vec<AttributeValue>> = buffer
+            .iter()
+            .map(|m| m.attributes().map(|(_, v)| v.clone()).collect())
+            .collect();
+        assert_eq!(
+            phases,
+            vec![
+                vec![],
+                vec![AttributeValue::String(String::from("warmup"))],
+                vec![AttributeValue::String(String::from("benchmark"))],
+                vec![AttributeValue::Str("other")],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_line() {
+        let args = vec![String::from("-c"), String::from("sleep 1"), String::new()];
+        assert_eq!(command_line("sh", &args), r#"sh -c "sleep 1" """#);
+        assert_eq!(command_line("true", &[]), "true");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exit_code() {
+        use super::exit_code;
+        use std::process::Command;
+
+        let status = Command::new("sh").args(["-c", "exit 3"]).status().unwrap();
+        assert_eq!(exit_code(status), 3);
+
+        let status = Command::new("sh").args(["-c", "kill -9 $$"]).status().unwrap();
+        assert_eq!(exit_code(status), 128 + 9);
+    }
+}
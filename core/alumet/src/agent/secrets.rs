@@ -0,0 +1,308 @@
+//! Resolution of the secrets referenced in the configuration.
+//!
+//! Credentials should not be written in clear text in the configuration file.
+//! Instead, any string of the configuration can refer to a secret, with the syntax `${provider:reference}`:
+//! - `${env:VAR}` is replaced by the value of the environment variable `VAR`;
+//! - `${file:/run/secrets/x}` is replaced by the content of the file `/run/secrets/x`, without its
+//!   trailing newline (this works well with the secrets of Docker and Kubernetes);
+//! - `${keyring:name}` is replaced by the content of the key `name` of the Linux kernel keyring
+//!   (a key of type `user`, see `keyctl(1)`).
+//!
+//! The [`Loader`](super::config::Loader) resolves the secrets after merging all the layers of the configuration,
+//! before the configuration is deserialized. Thus, the plugins get the actual values of the secrets,
+//! and they handle credentials in a consistent way without doing anything special.
+//!
+//! # Example
+//! ```toml
+//! [plugins.kwollect-output]
+//! login = "alice"
+//! password = "${file:/run/secrets/kwollect_password}"
+//!
+//! [plugins.influxdb]
+//! token = "${env:INFLUX_TOKEN}"
+//! ```
+
+use std::{borrow::Cow, env::VarError, io};
+
+use thiserror::Error;
+
+/// Replaces the secret references that appear in the strings of `config` by the values of the secrets.
+///
+/// See the [module documentation](self).
+pub fn resolve_secrets(config: &mut toml::Table) -> Result<(), SecretError> {
+    for (key, value) in config.iter_mut() {
+        resolve_value(value, key)?;
+    }
+    Ok(())
+}
+
+fn resolve_value(value: &mut toml::Value, path: &str) -> Result<(), SecretError> {
+    match value {
+        toml::Value::String(s) => {
+            if let Cow::Owned(resolved) = resolve_str(s).map_err(|e| e.at(path))? {
+                *s = resolved;
+            }
+        }
+        toml::Value::Array(array) => {
+            for (i, v) in array.iter_mut().enumerate() {
+                resolve_value(v, &format!("{path}[{i}]"))?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, v) in table.iter_mut() {
+                resolve_value(v, &format!("{path}.{key}"))?;
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Replaces the secret references that appear in `input` by the values of the secrets.
+///
+/// A pattern `${...}` that does not contain `:` is not a secret reference, it is left untouched.
+pub fn resolve_str(input: &str) -> Result<Cow<'_, str>, SecretError> {
+    let mut res = String::new();
+    let mut rest = input;
+    let mut resolved_any = false;
+    while let Some(begin) = rest.find("${") {
+        let Some(len) = rest[begin..].find('}') else {
+            break;
+        };
+        let reference = &rest[begin + 2..begin + len];
+        let Some((provider, name)) = reference.split_once(':') else {
+            // not a secret: keep it
+            res.push_str(&rest[..begin + len + 1]);
+            rest = &rest[begin + len + 1..];
+            continue;
+        };
+        let secret = read_secret(provider, name).map_err(|cause| SecretError {
+            reference: reference.to_owned(),
+            path: None,
+            cause,
+        })?;
+        res.push_str(&rest[..begin]);
+        res.push_str(&secret);
+        rest = &rest[begin + len + 1..];
+        resolved_any = true;
+    }
+    if !resolved_any {
+        return Ok(Cow::Borrowed(input));
+    }
+    res.push_str(rest);
+    Ok(Cow::Owned(res))
+}
+
+fn read_secret(provider: &str, name: &str) -> Result<String, SecretErrorCause> {
+    match provider {
+        "env" => match std::env::var(name) {
+            Ok(value) => Ok(value),
+            Err(VarError::NotPresent) => Err(SecretErrorCause::MissingEnv),
+            Err(VarError::NotUnicode(_)) => Err(SecretErrorCause::NotUnicode),
+        },
+        "file" => {
+            let content = std::fs::read_to_string(name).map_err(SecretErrorCause::File)?;
+            Ok(trim_newline(content))
+        }
+        "keyring" => {
+            let content = keyring::read_user_key(name).map_err(SecretErrorCause::Keyring)?;
+            let content = String::from_utf8(content).map_err(|_| SecretErrorCause::NotUnicode)?;
+            Ok(trim_newline(content))
+        }
+        _ => Err(SecretErrorCause::UnknownProvider(provider.to_owned())),
+    }
+}
+
+/// Removes the trailing newline, which is usually added by the tools that write the secrets.
+fn trim_newline(mut s: String) -> String {
+    let len = s.trim_end_matches(['\n', '\r']).len();
+    s.truncate(len);
+    s
+}
+
+#[cfg(target_os = "linux")]
+mod keyring {
+    use std::{ffi::CString, io};
+
+    /// Finds a key of type `user` in the keyrings of the process, and reads its content.
+    pub fn read_user_key(description: &str) -> io::Result<Vec<u8>> {
+        let key_type = c"user";
+        let description = CString::new(description).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: the strings are valid and nul-terminated, no callout info, no destination keyring
+        let id = unsafe {
+            libc::syscall(
+                libc::SYS_request_key,
+                key_type.as_ptr(),
+                description.as_ptr(),
+                std::ptr::null::<libc::c_char>(),
+                0,
+            )
+        };
+        if id < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // KEYCTL_READ returns the size of the key, which can be larger than the buffer.
+        let mut buf = vec![0u8; 256];
+        loop {
+            // SAFETY: the buffer is valid for buf.len() bytes
+            let len = unsafe { libc::syscall(libc::SYS_keyctl, libc::KEYCTL_READ, id, buf.as_mut_ptr(), buf.len()) };
+            if len < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let len = len as usize;
+            if len <= buf.len() {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+            buf.resize(len, 0);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod keyring {
+    use std::io;
+
+    pub fn read_user_key(_description: &str) -> io::Result<Vec<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the keyring is only supported on Linux",
+        ))
+    }
+}
+
+/// A secret referenced in the configuration could not be resolved.
+#[derive(Error, Debug)]
+#[error("could not resolve the secret ${{{reference}}}{}", .path.as_ref().map(|p| format!(" in {p}")).unwrap_or_default())]
+pub struct SecretError {
+    /// The reference, without `${` and `}`, for instance `env:INFLUX_TOKEN`.
+    pub reference: String,
+    /// Path of the config value that contains the reference, if known.
+    pub path: Option<String>,
+    #[source]
+    cause: SecretErrorCause,
+}
+
+impl SecretError {
+    fn at(mut self, path: &str) -> Self {
+        self.path = Some(path.to_owned());
+        self
+    }
+}
+
+#[derive(Error, Debug)]
+enum SecretErrorCause {
+    #[error("unknown secret provider '{0}', expected env, file or keyring")]
+    UnknownProvider(String),
+    #[error("the environment variable does not exist")]
+    MissingEnv,
+    #[error("the secret is not valid UTF-8")]
+    NotUnicode,
+    #[error("could not read the file")]
+    File(#[source] io::Error),
+    #[error("could not read the key from the keyring")]
+    Keyring(#[source] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::io::Write;
+
+    use super::{resolve_secrets, resolve_str};
+
+    // This environment variable exist both at compile time and runtime.
+    const ENV_VAR_VALUE: &str = env!("CARGO_PKG_NAME");
+
+    #[test]
+    fn no_secret() {
+        for input in ["", "abcd", "${NOT_A_SECRET}", "$ {env:X}", "${env:unclosed"] {
+            assert_eq!(resolve_str(input).unwrap(), Cow::Borrowed(input));
+        }
+    }
+
+    #[test]
+    fn env() {
+        assert_eq!(resolve_str("${env:CARGO_PKG_NAME}").unwrap(), ENV_VAR_VALUE);
+        assert_eq!(
+            resolve_str("a ${env:CARGO_PKG_NAME} and ${b} and ${env:CARGO_PKG_NAME}").unwrap(),
+            format!("a {ENV_VAR_VALUE} and ${{b}} and {ENV_VAR_VALUE}")
+        );
+        let err = resolve_str("${env:ALUMET_TEST_DOES_NOT_EXIST}").unwrap_err();
+        assert_eq!(err.reference, "env:ALUMET_TEST_DOES_NOT_EXIST");
+    }
+
+    #[test]
+    fn file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "s3cr3t").unwrap();
+        let input = format!("${{file:{}}}", file.path().display());
+        assert_eq!(resolve_str(&input).unwrap(), "s3cr3t");
+
+        resolve_str("${file:/this/file/does/not/exist}").unwrap_err();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn keyring() {
+        use std::ffi::CString;
+
+        let description = CString::new(format!("alumet-test-{}", std::process::id())).unwrap();
+        let payload = b"k3y";
+        // SAFETY: valid strings and buffer, KEY_SPEC_PROCESS_KEYRING is a special keyring id
+        let id = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                c"user".as_ptr(),
+                description.as_ptr(),
+                payload.as_ptr(),
+                payload.len(),
+                libc::KEY_SPEC_PROCESS_KEYRING,
+            )
+        };
+        if id < 0 {
+            // some sandboxes forbid the keyring syscalls
+            eprintln!(
+                "keyring unavailable, skipping test: {}",
+                std::io::Error::last_os_error()
+            );
+            return;
+        }
+        let input = format!("${{keyring:{}}}", description.to_str().unwrap());
+        assert_eq!(resolve_str(&input).unwrap(), "k3y");
+        resolve_str("${keyring:alumet-test-this-key-does-not-exist}").unwrap_err();
+    }
+
+    #[test]
+    fn unknown_provider() {
+        let err = resolve_str("${vault:secret/data}").unwrap_err();
+        assert!(err.to_string().contains("vault:secret/data"));
+    }
+
+    #[test]
+    fn table() {
+        let mut config: toml::Table = toml::from_str(
+            r#"
+            user = "${env:CARGO_PKG_NAME}"
+            port = 1234
+            [plugins.a]
+            list = ["x", "${env:CARGO_PKG_NAME}"]
+            password = "${env:ALUMET_TEST_DOES_NOT_EXIST}"
+            "#,
+        )
+        .unwrap();
+        let err = resolve_secrets(&mut config).unwrap_err();
+        assert_eq!(err.path.as_deref(), Some("plugins.a.password"));
+        assert!(!err.to_string().contains(ENV_VAR_VALUE));
+
+        config["plugins"]["a"]
+            .as_table_mut()
+            .unwrap()
+            .insert(String::from("password"), toml::Value::from("p@ss"));
+        resolve_secrets(&mut config).unwrap();
+        assert_eq!(config["user"].as_str(), Some(ENV_VAR_VALUE));
+        assert_eq!(config["plugins"]["a"]["list"][1].as_str(), Some(ENV_VAR_VALUE));
+        assert_eq!(config["plugins"]["a"]["password"].as_str(), Some("p@ss"));
+    }
+}
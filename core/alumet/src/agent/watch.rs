@@ -1,6 +1,6 @@
 //! Watch processes through it's pid
 use anyhow::Context;
-use std::{fs, io, path::PathBuf, ptr, time::Duration};
+use std::{io, time::Duration};
 use thiserror::Error;
 
 use crate::pipeline::{MeasurementPipeline, control::request, matching::SourceNamePattern};
@@ -57,7 +57,18 @@ pub fn watch_process(agent: RunningAgent, pid: u32, shutdown_timeout: Duration)
     agent.wait_for_shutdown(shutdown_timeout).map_err(WatchError::Shutdown)
 }
 
-/// Spawns a child process and waits for it to exit.
+/// Waits for a process to exit.
+#[cfg(not(target_os = "linux"))]
+fn wait_child(pid: i32) -> Result<(), WatchError> {
+    let e = io::Error::new(
+        io::ErrorKind::Unsupported,
+        "watching a process is only supported on Linux",
+    );
+    Err(WatchError::ProcessWait(pid, e))
+}
+
+/// Waits for a process to exit.
+#[cfg(target_os = "linux")]
 fn wait_child(pid: i32) -> Result<(), WatchError> {
     match unsafe { nc::pidfd_open(pid, 0) } {
         Ok(pidfd) => wait_child_syscall(pid, pidfd),
@@ -72,7 +83,10 @@ fn wait_child(pid: i32) -> Result<(), WatchError> {
 }
 
 // Wait for process exit using sysfs file
+#[cfg(target_os = "linux")]
 fn wait_child_sysfs(pid: i32) -> Result<(), WatchError> {
+    use std::{fs, path::PathBuf};
+
     let path = PathBuf::from(format!("/proc/{pid}/"));
     loop {
         if !fs::metadata(&path).is_ok() {
@@ -85,11 +99,14 @@ fn wait_child_sysfs(pid: i32) -> Result<(), WatchError> {
 }
 
 // Wait for process exit using a syscall
+#[cfg(target_os = "linux")]
 fn wait_child_syscall(pid: i32, pidfd: i32) -> Result<(), WatchError> {
+    use std::ptr;
+
     loop {
         // Attempt to read from the file descriptor
         let mut timeout = libc::timeval {
-            tv_sec: Duration::from_secs(5).as_secs() as i64,
+            tv_sec: Duration::from_secs(5).as_secs() as libc::time_t,
             tv_usec: 0,
         };
         let mut read_fds = unsafe { std::mem::zeroed::<libc::fd_set>() }; // Initialize fd_set
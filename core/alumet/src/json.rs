@@ -0,0 +1,73 @@
+//! Conversion of the measurements to JSON, for the outputs that send JSON documents.
+//!
+//! This module is only available with the `json` feature.
+//!
+//! # Example
+//! ```
+//! use alumet::json;
+//! use alumet::measurement::AttributeValue;
+//!
+//! assert_eq!(json::attribute_value(&AttributeValue::U64(42)), serde_json::json!(42));
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde_json::{Value, json};
+
+use crate::measurement::{AttributeValue, MeasurementPoint};
+
+/// Converts an attribute to a JSON value, preserving its type.
+pub fn attribute_value(value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::F64(v) => json!(v),
+        AttributeValue::U64(v) => json!(v),
+        AttributeValue::Bool(v) => json!(v),
+        AttributeValue::Str(v) => json!(v),
+        AttributeValue::String(v) => json!(v),
+        AttributeValue::ListU64(v) => json!(v),
+    }
+}
+
+/// Returns the attributes of a measurement as JSON values, sorted by key.
+///
+/// The attributes are collected in a `BTreeMap`: a `serde_json::Map` is not sorted when the
+/// `preserve_order` feature of `serde_json` is enabled by another crate of the build.
+pub fn attributes(m: &MeasurementPoint) -> BTreeMap<&str, Value> {
+    m.attributes()
+        .map(|(key, value)| (key, attribute_value(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        measurement::{AttributeValue, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::attributes;
+
+    #[test]
+    fn sorted_attributes() {
+        let m = MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            RawMetricId::from_u64(0),
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::U64(1),
+        )
+        .with_attr("kind", "user")
+        .with_attr("cpu", 2_u64)
+        .with_attr("cores", AttributeValue::ListU64(vec![0, 1]))
+        .with_attr("ratio", 0.5)
+        .with_attr("idle", false);
+        let json = serde_json::to_string(&attributes(&m)).unwrap();
+        assert_eq!(
+            json,
+            json!({"cores": [0, 1], "cpu": 2, "idle": false, "kind": "user", "ratio": 0.5}).to_string()
+        );
+    }
+}
@@ -32,11 +32,14 @@
 #![doc(html_logo_url = "https://alumet.dev/img/alumet-logo-color.svg")]
 
 pub mod agent;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod measurement;
 pub mod metrics;
 pub mod pipeline;
 pub mod plugin;
 pub mod resources;
+pub mod template;
 pub mod timeseries;
 pub mod units;
 
@@ -466,6 +466,12 @@ impl MeasurementBuffer {
         self.points.clear();
     }
 
+    /// Retains only the measurements specified by the predicate.
+    /// See [`Vec::retain`].
+    pub fn retain<F: FnMut(&MeasurementPoint) -> bool>(&mut self, f: F) {
+        self.points.retain(f);
+    }
+
     /// Creates an iterator on the buffer's content.
     pub fn iter(&self) -> impl Iterator<Item = &MeasurementPoint> {
         self.points.iter()
@@ -5,6 +5,7 @@
 //! - a description
 //! - a type of measured value
 //! - a measurement unit
+//! - optional tags, such as [`tag::ENERGY`]
 //!
 //! This information is stored in the [`Metric`] struct.
 //!
@@ -64,6 +65,39 @@ pub struct Metric {
     pub value_type: WrappedMeasurementType,
     /// Unit that applies to all the measurements of this metric.
     pub unit: PrefixedUnit,
+    /// Categories of the metric, for instance `energy` or `thermal`.
+    ///
+    /// Tags allow generic plugins to find the metrics they are interested in,
+    /// with [`MetricRegistry::by_tag`]. See [`tag`] for a list of common tags.
+    pub tags: Vec<String>,
+}
+
+impl Metric {
+    /// Returns `true` if the metric has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+}
+
+/// Common metric tags.
+///
+/// Plugins are free to use other tags, but using these ones when applicable
+/// makes it easier for other plugins to find the metrics.
+pub mod tag {
+    /// Energy consumption, for instance in joules.
+    pub const ENERGY: &str = "energy";
+    /// Electrical power, for instance in watts.
+    pub const POWER: &str = "power";
+    /// Temperature, fan speed, and other thermal information.
+    pub const THERMAL: &str = "thermal";
+    /// Performance counters, usage, frequency...
+    pub const PERFORMANCE: &str = "performance";
+    /// Memory usage.
+    pub const MEMORY: &str = "memory";
+    /// Network traffic.
+    pub const NETWORK: &str = "network";
+    /// Storage usage and I/O.
+    pub const STORAGE: &str = "storage";
 }
 
 /// Trait for both typed and untyped metric ids.
@@ -40,3 +40,30 @@ impl std::fmt::Display for MetricTypeError {
         )
     }
 }
+
+/// Error which can occur when looking up a metric by name, with an expected type.
+#[derive(Debug)]
+pub enum MetricLookupError {
+    /// There is no metric with this name in the registry.
+    NotFound(String),
+    /// The metric exists but its measurement type is not the expected one.
+    Type(String, MetricTypeError),
+}
+
+impl std::error::Error for MetricLookupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MetricLookupError::NotFound(_) => None,
+            MetricLookupError::Type(_, e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for MetricLookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricLookupError::NotFound(name) => write!(f, "metric not found: {name}"),
+            MetricLookupError::Type(name, _) => write!(f, "metric {name} does not have the expected type"),
+        }
+    }
+}
@@ -2,10 +2,12 @@
 
 use std::collections::HashMap;
 
+use crate::measurement::MeasurementType;
+
 use super::{
-    def::{Metric, MetricId, RawMetricId},
+    def::{Metric, MetricId, RawMetricId, TypedMetricId},
     duplicate::{self, DuplicateCriteria, DuplicateReaction},
-    error::MetricCreationError,
+    error::{MetricCreationError, MetricLookupError},
 };
 
 /// A registry of metrics.
@@ -38,6 +40,45 @@ impl MetricRegistry {
             .and_then(|id| self.metrics_by_id.get(id).map(|m| (*id, m)))
     }
 
+    /// Finds the metric that has the given name, and checks that its measurement type is `T`.
+    ///
+    /// This allows a plugin to use a metric that has been registered by another plugin.
+    ///
+    /// # Example
+    /// ```
+    /// use alumet::metrics::{TypedMetricId, error::MetricLookupError, registry::MetricRegistry};
+    ///
+    /// fn energy_metric(metrics: &MetricRegistry) -> Result<TypedMetricId<f64>, MetricLookupError> {
+    ///     metrics.by_name_typed("rapl_consumed_energy")
+    /// }
+    /// ```
+    pub fn by_name_typed<T: MeasurementType>(&self, name: &str) -> Result<TypedMetricId<T>, MetricLookupError> {
+        let (id, _) = self
+            .by_name(name)
+            .ok_or_else(|| MetricLookupError::NotFound(name.to_owned()))?;
+        TypedMetricId::try_from(id, self).map_err(|e| MetricLookupError::Type(name.to_owned(), e))
+    }
+
+    /// Finds the metrics that have the given tag.
+    ///
+    /// The order of the metrics is unspecified.
+    ///
+    /// # Example
+    /// ```
+    /// use alumet::metrics::def::tag;
+    /// use alumet::metrics::registry::MetricRegistry;
+    ///
+    /// fn power_metrics(metrics: &MetricRegistry) -> Vec<String> {
+    ///     metrics.by_tag(tag::POWER).map(|(_id, m)| m.name.clone()).collect()
+    /// }
+    /// ```
+    pub fn by_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = (RawMetricId, &'a Metric)> + 'a {
+        self.metrics_by_id
+            .iter()
+            .filter(move |(_, m)| m.has_tag(tag))
+            .map(|(id, m)| (*id, m))
+    }
+
     /// The number of metrics in the registry.
     pub fn len(&self) -> usize {
         self.metrics_by_id.len()
@@ -271,6 +312,7 @@ mod tests {
                     description: "...".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
             )
@@ -282,6 +324,7 @@ mod tests {
                     description: "abcd".to_owned(),
                     value_type: WrappedMeasurementType::F64,
                     unit: Unit::Volt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
             )
@@ -300,6 +343,7 @@ mod tests {
                     description: "".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
                 DuplicateReaction::Error,
@@ -312,6 +356,7 @@ mod tests {
                     description: "".to_owned(),
                     value_type: WrappedMeasurementType::F64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
                 DuplicateReaction::Error,
@@ -324,6 +369,7 @@ mod tests {
                     description: "".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Second.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Incompatible,
                 DuplicateReaction::Rename {
@@ -365,6 +411,7 @@ mod tests {
                     description: "...".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
                 "suffix",
@@ -380,6 +427,7 @@ mod tests {
                     description: "...".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Different,
                 "suffix",
@@ -396,6 +444,7 @@ mod tests {
                     description: "...".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
                 "suffix",
@@ -413,6 +462,7 @@ mod tests {
                     description: "abcd".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Incompatible,
                 "suffix",
@@ -430,6 +480,7 @@ mod tests {
                     description: "...".to_owned(),
                     value_type: WrappedMeasurementType::F64,
                     unit: Unit::Watt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Incompatible,
                 "suffix",
@@ -446,6 +497,7 @@ mod tests {
                     description: "...".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Byte.into(), // Byte instead of Watt
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Incompatible,
                 "suffix",
@@ -459,6 +511,7 @@ mod tests {
                     description: "xyz".to_owned(),
                     value_type: WrappedMeasurementType::U64, // U64 instead of F64
                     unit: Unit::Volt.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
                 "suffix",
@@ -484,6 +537,7 @@ mod tests {
                     description: "not the same".to_owned(),
                     value_type: WrappedMeasurementType::U64,
                     unit: Unit::Second.into(),
+                    tags: Vec::new(),
                 },
                 DuplicateCriteria::Strict,
                 "suffix",
@@ -500,4 +554,35 @@ mod tests {
             assert_ne!(id4, id1);
         }
     }
+
+    #[test]
+    fn by_tag() {
+        let mut metrics = MetricRegistry::new();
+        let mut register = |name: &str, tags: &[&str]| {
+            metrics
+                .register_no_duplicate(
+                    Metric {
+                        name: name.to_owned(),
+                        description: "".to_owned(),
+                        value_type: WrappedMeasurementType::F64,
+                        unit: Unit::Watt.into(),
+                        tags: tags.iter().map(|t| t.to_string()).collect(),
+                    },
+                    DuplicateCriteria::Strict,
+                )
+                .unwrap()
+        };
+        let a = register("a", &["power", "gpu"]);
+        let b = register("b", &["power"]);
+        let _c = register("c", &[]);
+
+        let mut power: Vec<_> = metrics.by_tag("power").map(|(id, _)| id).collect();
+        power.sort_by_key(|id| id.as_u64());
+        assert_eq!(power, vec![a, b]);
+
+        let gpu: Vec<_> = metrics.by_tag("gpu").map(|(id, m)| (id, m.name.as_str())).collect();
+        assert_eq!(gpu, vec![(a, "a")]);
+
+        assert_eq!(metrics.by_tag("thermal").count(), 0);
+    }
 }
@@ -1,4 +1,5 @@
 //! Construction of measurement pipelines.
+use std::collections::HashSet;
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, anyhow};
@@ -25,11 +26,15 @@ use super::elements::output::builder::OutputBuilder;
 use super::elements::source::builder::SourceBuilder;
 use super::elements::source::trigger::TriggerConstraints;
 use super::elements::transform::builder::TransformBuilder;
-use super::error::PipelineError;
+use super::error::{ElementFailures, PipelineError};
+use super::failover::FailoverRules;
 use super::naming::{
     OutputName, PluginName, SourceName, TransformName,
     namespace::{DuplicateNameError, Namespace2},
 };
+use super::sampling::SamplingRules;
+use super::shutdown::{self, ShutdownHook};
+use super::usage::{self, ResourceUsageSettings, UsageTracker};
 use super::{
     control::key::{OutputKey, SourceKey, TransformKey},
     control::{AnonymousControlHandle, PipelineControl},
@@ -44,6 +49,9 @@ pub struct MeasurementPipeline {
     metrics: (MetricSender, MetricReader),
     pipeline_control_task: JoinHandle<Result<(), PipelineError>>,
     metrics_control_task: JoinHandle<()>,
+    failures: ElementFailures,
+    usage: UsageTracker,
+    shutdown_hooks: Vec<ShutdownHook>,
 }
 
 /// A Builder for [`MeasurementPipeline`].
@@ -67,14 +75,26 @@ pub struct Builder {
     /// How many `MeasurementBuffer` can be stored in the channel that sources write to.
     source_channel_size: usize,
 
+    /// Sampling rules to apply to the measurements, right after the sources.
+    sampling_rules: SamplingRules,
+
+    /// Failover rules to apply to the outputs.
+    failover_rules: FailoverRules,
+
     /// Enables or disables the "simplified pipeline" optimization.
     /// Set this to `false` if you plan to add more outputs at runtime, while there is only one output at the beginning.
     allow_simplified_pipeline: bool,
 
+    /// Accounting of the resources used by the pipeline elements.
+    resource_usage: ResourceUsageSettings,
+
     /// Metrics
     pub(crate) metrics: MetricRegistry,
     metric_listeners: Namespace2<Box<dyn MetricListenerBuilder>>,
 
+    /// Async functions to run when the pipeline shuts down.
+    shutdown_hooks: Vec<ShutdownHook>,
+
     // tokio::Runtime settings.
     threads_normal: Option<usize>,
     threads_high_priority: Option<usize>,
@@ -97,9 +117,13 @@ impl Builder {
             default_transforms_order: Vec::new(),
             trigger_constraints: TriggerConstraints::default(),
             source_channel_size: DEFAULT_CHAN_BUF_SIZE,
+            sampling_rules: SamplingRules::new(),
+            failover_rules: FailoverRules::new(),
             allow_simplified_pipeline: true,
+            resource_usage: ResourceUsageSettings::default(),
             metrics: MetricRegistry::new(),
             metric_listeners: Namespace2::new(),
+            shutdown_hooks: Vec::new(),
             threads_normal: None, // default to the number of cores
             threads_high_priority: None,
         }
@@ -120,6 +144,30 @@ impl Builder {
         &mut self.source_channel_size
     }
 
+    /// Returns a mutable reference to the sampling rules, which allow to reduce the number
+    /// of measurement points of some metrics, right after the sources.
+    ///
+    /// See the [`sampling`](super::sampling) module.
+    pub fn sampling_rules_mut(&mut self) -> &mut SamplingRules {
+        &mut self.sampling_rules
+    }
+
+    /// Returns a mutable reference to the failover rules, which allow to write the measurements
+    /// to a secondary output when a primary output fails.
+    ///
+    /// See the [`failover`](super::failover) module.
+    pub fn failover_rules_mut(&mut self) -> &mut FailoverRules {
+        &mut self.failover_rules
+    }
+
+    /// Returns a mutable reference to the settings of the resource usage accounting,
+    /// which measures the resources used by each pipeline element.
+    ///
+    /// See the [`usage`](super::usage) module.
+    pub fn resource_usage_mut(&mut self) -> &mut ResourceUsageSettings {
+        &mut self.resource_usage
+    }
+
     pub fn allow_simplified_pipeline(&mut self) -> &mut bool {
         &mut self.allow_simplified_pipeline
     }
@@ -178,6 +226,13 @@ impl Builder {
         }
     }
 
+    /// Registers an async function to run when the pipeline shuts down.
+    ///
+    /// See the [`shutdown`](super::shutdown) module.
+    pub fn add_shutdown_hook(&mut self, hook: ShutdownHook) {
+        self.shutdown_hooks.push(hook);
+    }
+
     /// Sets the number of non-high-priority threads to use.
     ///
     /// # Default
@@ -229,6 +284,50 @@ impl Builder {
         });
     }
 
+    /// Removes a source builder, in order to build the source outside of a pipeline.
+    ///
+    /// The builder is only removed if the source is of the expected kind.
+    /// Returns an error otherwise, and `Ok(None)` if there is no such source.
+    #[cfg(feature = "test")]
+    pub(crate) fn take_source(&mut self, name: &SourceName, autonomous: bool) -> Result<Option<SourceBuilder>, ()> {
+        match self.sources.get(name.plugin(), name.source()) {
+            Some(SourceBuilder::Autonomous(_)) if !autonomous => Err(()),
+            Some(SourceBuilder::Managed(_)) if autonomous => Err(()),
+            _ => Ok(self.sources.remove(name.plugin(), name.source())),
+        }
+    }
+
+    /// Removes a transform builder, in order to build the transform outside of a pipeline.
+    #[cfg(feature = "test")]
+    pub(crate) fn take_transform(&mut self, name: &TransformName) -> Option<Box<dyn TransformBuilder>> {
+        self.default_transforms_order.retain(|n| n != name);
+        self.transforms.remove(name.plugin(), name.transform())
+    }
+
+    /// Removes an output builder, in order to build the output outside of a pipeline.
+    #[cfg(feature = "test")]
+    pub(crate) fn take_output(&mut self, name: &OutputName) -> Option<OutputBuilder> {
+        self.outputs.remove(name.plugin(), name.output())
+    }
+
+    /// Removes all the metric listener builders.
+    #[cfg(feature = "test")]
+    pub(crate) fn take_metric_listeners(&mut self) -> Namespace2<Box<dyn MetricListenerBuilder>> {
+        std::mem::replace(&mut self.metric_listeners, Namespace2::new())
+    }
+
+    /// Checks the registered elements and the settings of the pipeline, without building it.
+    ///
+    /// This covers the failover rules and the order of the transforms, which are
+    /// otherwise only checked by [`build`](Self::build).
+    pub fn check(&self) -> anyhow::Result<()> {
+        self.failover_rules
+            .check(&self.outputs)
+            .context("output creation failed")?;
+        let order = self.transforms_order.as_ref().unwrap_or(&self.default_transforms_order);
+        check_transforms_order(&self.transforms, order)
+    }
+
     /// Builds the measurement pipeline.
     ///
     /// The new pipeline is immediately started.
@@ -256,24 +355,16 @@ impl Builder {
             mut transforms: Namespace2<Box<dyn TransformBuilder>>,
             order: Vec<TransformName>,
         ) -> anyhow::Result<Vec<(TransformName, Box<dyn TransformBuilder>)>> {
+            check_transforms_order(&transforms, &order)?;
             let res = order
                 .into_iter()
                 .map(|name| {
-                    transforms
+                    let builder = transforms
                         .remove(name.plugin(), name.transform())
-                        .ok_or_else(|| anyhow!("an order was specified for a transform that does not exist: {name}"))
-                        .map(|builder| (name, builder))
+                        .expect("the order has been checked");
+                    (name, builder)
                 })
-                .collect::<anyhow::Result<Vec<_>>>()?;
-
-            if !transforms.is_empty() {
-                let names = transforms
-                    .flat_keys()
-                    .map(|(plugin, trans)| format!("{plugin}/{trans}"))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                return Err(anyhow!("missing order for these transforms: {names}"));
-            }
+                .collect();
             Ok(res)
         }
 
@@ -303,6 +394,13 @@ impl Builder {
         // Token to shutdown the remaining parts of the pipeline, after the elements have been stopped.
         let pipeline_shutdown_finalize = CancellationToken::new();
 
+        // Resource usage accounting, with its optional self-metrics source.
+        let usage = UsageTracker::new(self.resource_usage.enabled);
+        if let (true, Some(interval)) = (usage.is_enabled(), self.resource_usage.self_metrics_interval) {
+            usage::add_self_metrics_source(&mut self.metrics, &mut self.sources, usage.clone(), interval)
+                .context("could not add the resource usage source")?;
+        }
+
         // --- Metric registry (one for the entire pipeline) ---
         // Note: We can modify it without sending a message thanks to MetricAccess::write().
         let mut registry_control = MetricRegistryControl::new(self.metrics);
@@ -326,6 +424,11 @@ impl Builder {
         let mut output_control;
         let transform_control;
 
+        // Each primary output and its secondary output become a single output.
+        self.failover_rules
+            .apply(&mut self.outputs)
+            .context("output creation failed")?;
+
         if self.outputs.is_empty() {
             log::warn!(
                 "No output has been registered. A dummy output will be added to make the pipeline work, but you probably want to add a true output."
@@ -333,14 +436,16 @@ impl Builder {
             add_dummy_output(&mut self.outputs);
         }
 
-        if self.outputs.total_count() == 1 && self.transforms.is_empty() && self.allow_simplified_pipeline {
+        // The sampling rules are applied by the transform task, which does not exist in the simplified pipeline.
+        let simplify = self.allow_simplified_pipeline && self.sampling_rules.is_empty();
+        if self.outputs.total_count() == 1 && self.transforms.is_empty() && simplify {
             // OPTIMIZATION: there is only one output and no transform,
             // we can connect the inputs directly to the output.
             log::info!("Only one output and no transform, using a simplified and optimized measurement pipeline.");
 
             // Outputs
             let out_rx_provider = channel::ReceiverProvider::from(in_rx);
-            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone());
+            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone(), usage.clone());
             output_control
                 .blocking_create_outputs(self.outputs)
                 .context("output creation failed")?;
@@ -353,7 +458,7 @@ impl Builder {
 
             // Outputs
             let out_rx_provider = channel::ReceiverProvider::from(out_tx.clone());
-            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone());
+            output_control = OutputControl::new(out_rx_provider, rt_handle.clone(), metrics_r.clone(), usage.clone());
             output_control
                 .blocking_create_outputs(self.outputs)
                 .context("output creation failed")?;
@@ -361,8 +466,15 @@ impl Builder {
             // Transforms
             let order = self.transforms_order.unwrap_or(self.default_transforms_order);
             let transforms = take_transforms_in_order(self.transforms, order)?;
-            transform_control =
-                TransformControl::with_transforms(transforms, metrics_r.clone(), in_rx, out_tx, rt_handle)?;
+            transform_control = TransformControl::with_transforms(
+                transforms,
+                self.sampling_rules,
+                metrics_r.clone(),
+                in_rx,
+                out_tx,
+                rt_handle,
+                &usage,
+            )?;
         };
 
         // Sources, last in order not to loose any measurement if they start measuring right away.
@@ -373,13 +485,21 @@ impl Builder {
             rt_handle.clone(),
             rt_priority.as_ref().unwrap_or(&rt_normal).handle().clone(),
             (metrics_r.clone(), metrics_tx.clone()),
+            usage.clone(),
         );
         source_control
             .blocking_create_sources(self.sources)
             .context("source creation failed")?;
 
         // Pipeline control
-        let control = PipelineControl::new(source_control, transform_control, output_control);
+        let failures = ElementFailures::default();
+        let control = PipelineControl::new(
+            source_control,
+            transform_control,
+            output_control,
+            failures.clone(),
+            usage.clone(),
+        );
         let (control_handle, control_join) = control.start(pipeline_shutdown, pipeline_shutdown_finalize, rt_handle);
 
         // Done!
@@ -390,6 +510,9 @@ impl Builder {
             metrics: (metrics_tx, metrics_r),
             pipeline_control_task: control_join,
             metrics_control_task: metrics_join,
+            failures,
+            usage,
+            shutdown_hooks: self.shutdown_hooks,
         })
     }
 
@@ -426,6 +549,34 @@ impl Builder {
     }
 }
 
+/// Checks that `order` contains every transform of `transforms` exactly once.
+fn check_transforms_order(
+    transforms: &Namespace2<Box<dyn TransformBuilder>>,
+    order: &[TransformName],
+) -> anyhow::Result<()> {
+    let mut ordered = HashSet::with_capacity(order.len());
+    for name in order {
+        if transforms.get(name.plugin(), name.transform()).is_none() {
+            return Err(anyhow!(
+                "an order was specified for a transform that does not exist: {name}"
+            ));
+        }
+        if !ordered.insert(name) {
+            return Err(anyhow!("the transform {name} appears more than once in the order"));
+        }
+    }
+    if ordered.len() != transforms.total_count() {
+        let names = transforms
+            .flat_keys()
+            .filter(|(plugin, trans)| !ordered.contains(&TransformName::new(plugin.to_string(), trans.to_string())))
+            .map(|(plugin, trans)| format!("{plugin}/{trans}"))
+            .collect::<Vec<String>>()
+            .join(", ");
+        return Err(anyhow!("missing order for these transforms: {names}"));
+    }
+    Ok(())
+}
+
 /// Statistics about the current state of the builder.
 pub struct BuilderStats {
     /// Number of registered source builders.
@@ -552,6 +703,18 @@ impl MeasurementPipeline {
         self.metrics.0.clone()
     }
 
+    /// Returns the list of the elements that have failed, which is updated while the pipeline runs.
+    pub fn failures(&self) -> ElementFailures {
+        self.failures.clone()
+    }
+
+    /// Returns the resources used by the pipeline elements, which are updated while the pipeline runs.
+    ///
+    /// The accounting must be enabled with [`Builder::resource_usage_mut`].
+    pub fn resource_usage(&self) -> UsageTracker {
+        self.usage.clone()
+    }
+
     /// Returns a handle to the non-high-priority tokio async runtime.
     ///
     /// This handle can be used to start asynchronous tasks that will be cancelled when
@@ -566,12 +729,19 @@ impl MeasurementPipeline {
     /// This is a blocking function, it should not be called from within an async runtime.
     pub fn wait_for_shutdown(self, timeout: Option<Duration>) -> Result<(), ShutdownError> {
         log::debug!("pipeline::wait_for_shutdown");
+        let rt_handle = self.rt_normal.handle().clone();
         let shutdown_task = async {
-            let pipeline_result = self
+            let mut pipeline_result = self
                 .pipeline_control_task
                 .await
                 .context("pipeline_control_task failed to execute to completion")?;
 
+            if !self.shutdown_hooks.is_empty() {
+                log::trace!("pipeline_control_task has ended, running the shutdown hooks");
+                let hooks_result = shutdown::run_hooks(self.shutdown_hooks, &rt_handle).await;
+                pipeline_result = pipeline_result.and(hooks_result);
+            }
+
             log::trace!("pipeline_control_task has ended, waiting for metrics_control_task");
             self.metrics_control_task
                 .await
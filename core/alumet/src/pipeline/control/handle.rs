@@ -74,6 +74,16 @@ impl AnonymousControlHandle {
         self.shutdown_token.cancel();
     }
 
+    /// Returns true if the pipeline has been requested to shut down.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown_token.is_cancelled()
+    }
+
+    /// Waits until the pipeline is requested to shut down, by [`shutdown`](Self::shutdown) or by `Ctrl+C`.
+    pub async fn shutdown_requested(&self) {
+        self.shutdown_token.cancelled().await
+    }
+
     /// Sends a control request to the pipeline, without waiting for a response.
     ///
     /// # Errors
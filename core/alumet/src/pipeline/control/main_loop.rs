@@ -1,6 +1,7 @@
 //! On-the-fly modification of the pipeline.
 use crate::pipeline::control::messages::RequestMessage;
-use crate::pipeline::error::PipelineError;
+use crate::pipeline::error::{ElementFailures, PipelineError};
+use crate::pipeline::usage::UsageTracker;
 
 use crate::pipeline::elements::{output, source, transform};
 
@@ -18,6 +19,10 @@ pub(crate) struct PipelineControl {
     sources: source::control::SourceControl,
     transforms: transform::control::TransformControl,
     outputs: output::control::OutputControl,
+    /// The elements that have failed, shared with the observers of the pipeline.
+    failures: ElementFailures,
+    /// The resources used by the elements, shared with the observers of the pipeline.
+    usage: UsageTracker,
 }
 
 impl PipelineControl {
@@ -25,11 +30,15 @@ impl PipelineControl {
         sources: source::control::SourceControl,
         transforms: transform::control::TransformControl,
         outputs: output::control::OutputControl,
+        failures: ElementFailures,
+        usage: UsageTracker,
     ) -> Self {
         Self {
             sources,
             transforms,
             outputs,
+            failures,
+            usage,
         }
     }
 
@@ -102,6 +111,9 @@ impl PipelineControl {
                 };
                 send_response(result, response_tx)
             }
+            messages::ControlRequest::ResourceUsage(RequestMessage { response_tx, body }) => {
+                send_response(Ok(self.usage.elements(&body)), response_tx)
+            }
         }
     }
 
@@ -126,11 +138,13 @@ impl PipelineControl {
             res: Result<Result<(), PipelineError>, tokio::task::JoinError>,
             kind: &'static str,
             result: &mut Result<(), PipelineError>,
+            failures: &ElementFailures,
         ) {
             match res {
                 Ok(Ok(())) => log::debug!("One {kind} task finished without error."),
                 Ok(Err(e_normal)) => {
                     log::error!("One {kind} task finished with error: {e_normal}");
+                    failures.record(&e_normal);
                     *result = Err(e_normal);
                 }
                 Err(e) if e.is_cancelled() => {
@@ -147,6 +161,7 @@ impl PipelineControl {
         // Keep track of the most recent error, so we can propagate it to the agent.
         // It is particularily useful in tests, to assert that no error occurred.
         let mut last_error: Result<(), PipelineError> = Result::Ok(());
+        let failures = self.failures.clone();
 
         loop {
             tokio::select! {
@@ -196,13 +211,13 @@ impl PipelineControl {
                 // - loop, sources JoinSet not empty => branch enabled
 
                 res = self.sources.join_next_task(), if self.sources.has_task() => {
-                    task_finished(res, "source", &mut last_error);
+                    task_finished(res, "source", &mut last_error, &failures);
                 },
                 res = self.transforms.join_next_task(), if self.transforms.has_task() => {
-                    task_finished(res, "transform", &mut last_error);
+                    task_finished(res, "transform", &mut last_error, &failures);
                 }
                 res = self.outputs.join_next_task(), if self.outputs.has_task() => {
-                    task_finished(res, "output", &mut last_error);
+                    task_finished(res, "output", &mut last_error, &failures);
                 }
             }
         }
@@ -211,17 +226,17 @@ impl PipelineControl {
         // Stop the elements, waiting for each step of the pipeline to finish before stopping the next one.
         log::trace!("waiting for sources to finish");
         self.sources
-            .shutdown(|res| task_finished(res, "source", &mut last_error))
+            .shutdown(|res| task_finished(res, "source", &mut last_error, &failures))
             .await;
 
         log::trace!("waiting for transforms to finish");
         self.transforms
-            .shutdown(|res| task_finished(res, "transform", &mut last_error))
+            .shutdown(|res| task_finished(res, "transform", &mut last_error, &failures))
             .await;
 
         log::trace!("waiting for outputs to finish");
         self.outputs
-            .shutdown(|res| task_finished(res, "output", &mut last_error))
+            .shutdown(|res| task_finished(res, "output", &mut last_error, &failures))
             .await;
 
         // Finalize the shutdown sequence by cancelling the remaining things.
@@ -5,6 +5,7 @@ use crate::pipeline::{
     error::PipelineError,
     matching::ElementNamePattern,
     naming::ElementName,
+    usage::ResourceUsage,
 };
 
 pub type Receiver = mpsc::Receiver<ControlRequest>;
@@ -14,6 +15,7 @@ pub type Sender = mpsc::Sender<ControlRequest>;
 pub enum ControlRequest {
     NoResult(RequestMessage<EmptyResponseBody, ()>),
     Introspect(RequestMessage<IntrospectionBody, IntrospectionResponse>),
+    ResourceUsage(RequestMessage<ElementNamePattern, ResourceUsageResponse>),
 }
 
 pub type ResponseSender<R> = oneshot::Sender<Result<R, PipelineError>>;
@@ -43,3 +45,5 @@ pub enum IntrospectionBody {
 }
 
 pub type IntrospectionResponse = Vec<ElementName>;
+
+pub type ResourceUsageResponse = Vec<(ElementName, ResourceUsage)>;
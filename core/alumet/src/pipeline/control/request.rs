@@ -26,7 +26,7 @@ pub mod source;
 mod transform;
 
 pub use create::{CreationRequest, MultiCreationRequestBuilder, SingleCreationRequestBuilder, create_many, create_one};
-pub use introspect::{ElementListFilter, IntrospectionRequest, list_elements};
+pub use introspect::{ElementListFilter, IntrospectionRequest, ResourceUsageRequest, list_elements, resource_usage};
 pub use output::{OutputRequest, OutputRequestBuilder, RemainingDataStrategy, output};
 pub use source::{SourceRequest, SourceRequestBuilder, source};
 use tokio::sync::oneshot;
@@ -13,11 +13,24 @@ pub fn list_elements(filter: ElementListFilter) -> IntrospectionRequest {
     IntrospectionRequest { list_filter: filter }
 }
 
+/// Creates a request that returns the resources used by the elements that match the given filter.
+///
+/// The response is empty if the resource usage accounting is disabled,
+/// see [`usage`](crate::pipeline::usage).
+pub fn resource_usage(filter: ElementListFilter) -> ResourceUsageRequest {
+    ResourceUsageRequest { list_filter: filter }
+}
+
 #[derive(Debug)]
 pub struct IntrospectionRequest {
     list_filter: ElementListFilter,
 }
 
+#[derive(Debug)]
+pub struct ResourceUsageRequest {
+    list_filter: ElementListFilter,
+}
+
 #[derive(Debug)]
 pub struct ElementListFilter {
     pub(crate) pattern: ElementNamePattern,
@@ -89,3 +102,24 @@ impl AnonymousControlRequest for IntrospectionRequest {
         (req, DirectResponseReceiver(rx))
     }
 }
+
+impl AnonymousControlRequest for ResourceUsageRequest {
+    type OkResponse = messages::ResourceUsageResponse;
+    type Receiver = DirectResponseReceiver<Self::OkResponse>;
+
+    fn serialize(self) -> messages::ControlRequest {
+        messages::ControlRequest::ResourceUsage(messages::RequestMessage {
+            response_tx: None,
+            body: self.list_filter.pattern,
+        })
+    }
+
+    fn serialize_with_response(self) -> (messages::ControlRequest, Self::Receiver) {
+        let (tx, rx) = oneshot::channel();
+        let req = messages::ControlRequest::ResourceUsage(messages::RequestMessage {
+            response_tx: Some(tx),
+            body: self.list_filter.pattern,
+        });
+        (req, DirectResponseReceiver(rx))
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use tokio::sync::oneshot;
 
 use crate::pipeline::{
@@ -36,6 +38,20 @@ impl SourceRequestBuilder {
         }
     }
 
+    /// Changes the poll interval of the sources that are triggered at regular intervals.
+    ///
+    /// Unlike [`set_trigger`](Self::set_trigger), this keeps the other settings of the triggers,
+    /// such as the flush and update intervals. The sources that are not triggered at regular intervals
+    /// are left untouched.
+    pub fn set_poll_interval(self, poll_interval: Duration) -> SourceRequest {
+        SourceRequest {
+            msg: ControlMessage::Configure(ConfigureMessage {
+                matcher: self.matcher,
+                command: ConfigureCommand::SetPollInterval(poll_interval),
+            }),
+        }
+    }
+
     pub fn trigger_now(self) -> SourceRequest {
         SourceRequest {
             msg: ControlMessage::TriggerManually(crate::pipeline::elements::source::control::TriggerMessage {
@@ -13,6 +13,7 @@ use tokio::{
 use crate::pipeline::elements::output::{AsyncOutputStream, run::run_async_output};
 use crate::pipeline::matching::OutputNamePattern;
 use crate::pipeline::naming::{OutputName, namespace::Namespace2};
+use crate::pipeline::usage::{Measured, UsageTracker};
 use crate::pipeline::util::{
     channel,
     stream::{ControlledStream, SharedStreamState, StreamState},
@@ -107,10 +108,18 @@ struct TaskManager {
     rt_normal: runtime::Handle,
 
     metrics: MetricReader,
+
+    /// Resource usage of the outputs.
+    usage: UsageTracker,
 }
 
 impl OutputControl {
-    pub fn new(rx_provider: channel::ReceiverProvider, rt_normal: runtime::Handle, metrics: MetricReader) -> Self {
+    pub fn new(
+        rx_provider: channel::ReceiverProvider,
+        rt_normal: runtime::Handle,
+        metrics: MetricReader,
+        usage: UsageTracker,
+    ) -> Self {
         Self {
             tasks: TaskManager {
                 spawned_tasks: JoinSet::new(),
@@ -118,6 +127,7 @@ impl OutputControl {
                 rx_provider,
                 rt_normal,
                 metrics: metrics.clone(),
+                usage,
             },
             metrics,
         }
@@ -255,16 +265,17 @@ impl TaskManager {
 
         // Put the output in a Mutex to overcome the lack of tokio::spawn_scoped.
         let guarded_output = Arc::new(Mutex::new(output));
+        let usage = self.usage.register(name.clone());
 
         // Spawn the task on the runtime.
         match rx {
             // Specialize on the kind of receiver at compile-time (for performance).
             channel::ReceiverEnum::Broadcast(rx) => {
-                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config);
+                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config, usage);
                 self.spawned_tasks.spawn_on(task, &self.rt_normal);
             }
             channel::ReceiverEnum::Single(rx) => {
-                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config);
+                let task = run_blocking_output(name, guarded_output, rx, metrics, shared_config, usage);
                 self.spawned_tasks.spawn_on(task, &self.rt_normal);
             }
         }
@@ -308,6 +319,7 @@ impl TaskManager {
         self.controllers.push((name.clone(), control));
 
         // Spawn the output
+        let output = Measured::new(output, self.usage.register(name.clone()));
         let task = run_async_output(name, output);
         self.spawned_tasks.spawn_on(task, &self.rt_normal);
         Ok(())
@@ -9,25 +9,30 @@ use crate::{
     pipeline::{
         error::PipelineError,
         naming::OutputName,
+        usage::{self, Measured, UsageCounter},
         util::channel::{self, RecvError},
     },
 };
 
 use super::{BoxedAsyncOutput, Output, OutputContext, control, error::WriteError};
 
-pub async fn run_async_output(name: OutputName, output: BoxedAsyncOutput) -> Result<(), PipelineError> {
+pub(crate) async fn run_async_output(
+    name: OutputName,
+    output: Measured<BoxedAsyncOutput>,
+) -> Result<(), PipelineError> {
     output.await.map_err(|e| {
-        log::error!("Error when asynchronously writing to {name} (will stop running): {e:?}");
+        log::error!(element:% = name; "Error when asynchronously writing to {name} (will stop running): {e:?}");
         PipelineError::for_element(name, e)
     })
 }
 
-pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
+pub(crate) async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
     name: OutputName,
     guarded_output: Arc<Mutex<Box<dyn Output>>>,
     mut rx: Rx,
     metrics_reader: MetricReader,
     config: Arc<control::SharedOutputConfig>,
+    usage: Option<Arc<UsageCounter>>,
 ) -> Result<(), PipelineError> {
     /// If `measurements` is an `Ok`, build an [`OutputContext`] and call `output.write(&measurements, &ctx)`.
     /// Otherwise, handle the error.
@@ -35,6 +40,7 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
         name: &OutputName,
         output: Arc<Mutex<Box<dyn Output>>>,
         metrics_r: MetricReader,
+        usage: Option<Arc<UsageCounter>>,
         maybe_measurements: Result<MeasurementBuffer, channel::RecvError>,
     ) -> anyhow::Result<ControlFlow<()>> {
         match maybe_measurements {
@@ -44,23 +50,24 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                     let ctx = OutputContext {
                         metrics: &metrics_r.blocking_read(),
                     };
-                    output.lock().unwrap().write(&measurements, &ctx)
+                    let mut output = output.lock().unwrap();
+                    usage::measure(usage.as_deref(), || output.write(&measurements, &ctx))
                 })
                 .await?;
                 match res {
                     Ok(()) => Ok(ControlFlow::Continue(())),
                     Err(WriteError::CanRetry(e)) => {
-                        log::error!("Non-fatal error when writing to {name} (will retry): {e:#}");
+                        log::error!(element:% = name; "Non-fatal error when writing to {name} (will retry): {e:#}");
                         Ok(ControlFlow::Continue(()))
                     }
                     Err(WriteError::Fatal(e)) => {
-                        log::error!("Fatal error when writing to {name} (will stop running): {e:?}");
+                        log::error!(element:% = name; "Fatal error when writing to {name} (will stop running): {e:?}");
                         Err(e.context(format!("fatal error when writing to {name}")))
                     }
                 }
             }
             Err(channel::RecvError::Lagged(n)) => {
-                log::warn!("Output {name} is too slow, it lost the oldest {n} messages.");
+                log::warn!(element:% = name; "Output {name} is too slow, it lost the oldest {n} messages.");
                 Ok(ControlFlow::Continue(()))
             }
             Err(channel::RecvError::Closed) => {
@@ -100,7 +107,7 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                 }
             },
             measurements = rx.recv(), if receive => {
-                let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), measurements)
+                let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), usage.clone(), measurements)
                     .await
                     .map_err(|e| PipelineError::for_element(name.clone(), e))?;
                 if res.is_break() {
@@ -125,9 +132,15 @@ pub async fn run_blocking_output<Rx: channel::MeasurementReceiver>(
                     Err(RecvError::Lagged(n)) => format!("Err(Lagged({n}))"),
                 }
             );
-            let res = write_measurements(&name, guarded_output.clone(), metrics_reader.clone(), received)
-                .await
-                .map_err(|e| PipelineError::for_element(name.clone(), e))?;
+            let res = write_measurements(
+                &name,
+                guarded_output.clone(),
+                metrics_reader.clone(),
+                usage.clone(),
+                received,
+            )
+            .await
+            .map_err(|e| PipelineError::for_element(name.clone(), e))?;
             if res.is_break() {
                 break;
             }
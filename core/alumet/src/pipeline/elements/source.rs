@@ -1,5 +1,6 @@
 //! Implementation and control of source tasks.
 
+pub mod blocking;
 pub mod builder;
 pub mod control;
 pub mod error;
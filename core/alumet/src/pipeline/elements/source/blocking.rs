@@ -0,0 +1,79 @@
+//! Values that must not be dropped in an async context.
+
+use std::ops::{Deref, DerefMut};
+
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// Wraps a value that must not be dropped in an async context.
+///
+/// Sources are polled by the async tasks of the pipeline. Some values, such as the blocking
+/// client of `reqwest`, stop a runtime of their own when dropped, which panics in an async context.
+/// `BlockingDrop` drops its value with [`tokio::task::block_in_place`] when it is needed.
+///
+/// # Example
+/// ```
+/// use alumet::pipeline::elements::source::blocking::BlockingDrop;
+///
+/// struct HttpClient {
+///     client: BlockingDrop<Vec<u8>>, // typically, reqwest::blocking::Client
+/// }
+///
+/// let http = HttpClient { client: BlockingDrop::new(Vec::new()) };
+/// assert!(http.client.is_empty());
+/// ```
+pub struct BlockingDrop<T> {
+    /// Always `Some`, except during the drop.
+    value: Option<T>,
+}
+
+impl<T> BlockingDrop<T> {
+    pub fn new(value: T) -> Self {
+        Self { value: Some(value) }
+    }
+}
+
+impl<T> Deref for BlockingDrop<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().expect("the value should exist until the drop")
+    }
+}
+
+impl<T> DerefMut for BlockingDrop<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().expect("the value should exist until the drop")
+    }
+}
+
+impl<T> Drop for BlockingDrop<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            // block_in_place is only available on the multi-threaded runtime, which the pipeline uses.
+            let in_async_context =
+                Handle::try_current().is_ok_and(|h| h.runtime_flavor() == RuntimeFlavor::MultiThread);
+            if in_async_context {
+                tokio::task::block_in_place(move || drop(value));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockingDrop;
+
+    #[test]
+    fn drop_in_async_context() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+        // dropping a runtime in an async context panics, unless it is done with block_in_place
+        let inner = BlockingDrop::new(tokio::runtime::Runtime::new().unwrap());
+        rt.block_on(rt.spawn(async move { drop(inner) })).unwrap();
+
+        // outside of the runtime, the value is dropped normally
+        drop(BlockingDrop::new(tokio::runtime::Runtime::new().unwrap()));
+    }
+}
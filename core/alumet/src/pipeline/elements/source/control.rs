@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::time::Duration;
 
 use anyhow::Context;
 use num_enum::{FromPrimitive, IntoPrimitive};
@@ -15,6 +16,7 @@ use crate::pipeline::error::PipelineError;
 use crate::pipeline::matching::{ElementNamePattern, SourceNamePattern};
 use crate::pipeline::naming::{ElementKind, ElementName};
 use crate::pipeline::naming::{SourceName, namespace::Namespace2};
+use crate::pipeline::usage::{Measured, UsageTracker};
 
 use super::builder;
 use super::trigger::{Trigger, TriggerConstraints, TriggerSpec};
@@ -70,6 +72,9 @@ pub enum ConfigureCommand {
     Resume,
     Stop,
     SetTrigger(TriggerSpec),
+    /// Changes the poll interval of the sources that are triggered at regular intervals,
+    /// and keeps the other settings of their trigger. The other sources are left untouched.
+    SetPollInterval(Duration),
 }
 
 pub(super) enum Reconfiguration {
@@ -121,6 +126,9 @@ struct TaskManager {
 
     /// Handle of the "priority" async runtime. Used for creating new sources.
     rt_priority: runtime::Handle,
+
+    /// Resource usage of the sources.
+    usage: UsageTracker,
 }
 
 impl SourceControl {
@@ -131,6 +139,7 @@ impl SourceControl {
         rt_normal: runtime::Handle,
         rt_priority: runtime::Handle,
         metrics: (MetricReader, MetricSender),
+        usage: UsageTracker,
     ) -> Self {
         Self {
             tasks: TaskManager {
@@ -141,6 +150,7 @@ impl SourceControl {
                 in_tx,
                 rt_normal,
                 rt_priority,
+                usage,
             },
             metrics,
         }
@@ -296,17 +306,19 @@ impl TaskManager {
                 // Some triggers need to be built with an executor available, therefore we use `Handle::enter()`.
                 let trigger = {
                     let _guard = runtime.enter();
-                    Trigger::new(source.trigger_spec).context("error in Trigger::new")?
+                    Trigger::new(source.trigger_spec.clone()).context("error in Trigger::new")?
                 };
                 log::trace!("new trigger created from the spec");
 
                 // Create a controller to control the async task.
-                let (controller, config) = super::task_controller::new_managed(trigger, source.initial_state);
+                let (controller, config) =
+                    super::task_controller::new_managed(source.trigger_spec, trigger, source.initial_state);
                 self.controllers.push((name.clone(), controller));
                 log::trace!("new controller initialized");
 
                 // Create the future (async task).
-                let source_task = run_managed(name, source.source, self.in_tx.clone(), config);
+                let usage = self.usage.register(name.clone());
+                let source_task = run_managed(name, source.source, self.in_tx.clone(), config, usage);
                 log::trace!("source task created");
 
                 // Spawn the future (execute the async task on the thread pool)
@@ -318,6 +330,7 @@ impl TaskManager {
                 let source = build(ctx, token.clone(), tx).context("autonomous source creation failed")?;
                 log::trace!("New autonomous source: {}", name);
 
+                let source = Measured::new(source, self.usage.register(name.clone()));
                 let source_task = run_autonomous(name.clone(), source);
                 let controller = super::task_controller::new_autonomous(token);
                 self.controllers.push((name, controller));
@@ -340,6 +353,10 @@ impl TaskManager {
                 spec.constrain(&self.trigger_constraints);
                 Reconfiguration::SetTrigger(spec)
             }
+            ConfigureCommand::SetPollInterval(interval) => {
+                self.set_poll_interval(&msg.matcher, interval);
+                return;
+            }
         };
 
         for (name, source_controller) in &mut self.controllers {
@@ -349,6 +366,24 @@ impl TaskManager {
         }
     }
 
+    fn set_poll_interval(&mut self, matcher: &SourceMatcher, interval: Duration) {
+        for (name, source_controller) in &mut self.controllers {
+            if !matcher.matches(name) {
+                continue;
+            }
+            // Only the sources that are triggered at regular intervals have a poll interval.
+            let Some(mut spec) = source_controller
+                .trigger_spec()
+                .and_then(|spec| spec.with_poll_interval(interval))
+            else {
+                log::debug!("Source {name} is not triggered at regular intervals, its trigger is kept.");
+                continue;
+            };
+            spec.constrain(&self.trigger_constraints);
+            source_controller.reconfigure(&Reconfiguration::SetTrigger(spec));
+        }
+    }
+
     fn trigger_manually(&mut self, msg: TriggerMessage) {
         let mut matches = 0;
         for (name, source_controller) in &mut self.controllers {
@@ -7,6 +7,7 @@ use tokio::sync::mpsc::error::TrySendError;
 use crate::measurement::{MeasurementBuffer, Timestamp};
 use crate::pipeline::error::PipelineError;
 use crate::pipeline::naming::SourceName;
+use crate::pipeline::usage::{self, Measured, UsageCounter};
 
 use super::control::TaskState;
 use super::error::PollError;
@@ -18,6 +19,7 @@ pub(crate) async fn run_managed(
     mut source: Box<dyn Source>,
     tx: mpsc::Sender<MeasurementBuffer>,
     config: Arc<super::task_controller::SharedSourceConfig>,
+    usage: Option<Arc<UsageCounter>>,
 ) -> Result<(), PipelineError> {
     /// Flushes the measurement and returns a new buffer.
     fn flush(buffer: MeasurementBuffer, tx: &mpsc::Sender<MeasurementBuffer>, name: &SourceName) -> MeasurementBuffer {
@@ -92,7 +94,7 @@ pub(crate) async fn run_managed(
                 }
             }
             TaskState::Stop => {
-                log::warn!("Source {source_name} has been started in Stop state and will stop immediately.");
+                log::warn!(element:% = source_name; "Source {source_name} has been started in Stop state and will stop immediately.");
                 return Ok(());
             }
         }
@@ -114,17 +116,20 @@ pub(crate) async fn run_managed(
             TriggerReason::Triggered => {
                 // poll the source
                 let timestamp = Timestamp::now();
-                match source.poll(&mut buffer.as_accumulator(), timestamp) {
+                let res = usage::measure(usage.as_deref(), || {
+                    source.poll(&mut buffer.as_accumulator(), timestamp)
+                });
+                match res {
                     Ok(()) => (),
                     Err(PollError::NormalStop) => {
                         log::info!("Source {source_name} stopped itself.");
                         break 'run; // stop polling
                     }
                     Err(PollError::CanRetry(e)) => {
-                        log::error!("Non-fatal error when polling {source_name} (will retry): {e:#}");
+                        log::error!(element:% = source_name; "Non-fatal error when polling {source_name} (will retry): {e:#}");
                     }
                     Err(PollError::Fatal(e)) => {
-                        log::error!("Fatal error when polling {source_name} (will stop running): {e:?}");
+                        log::error!(element:% = source_name; "Fatal error when polling {source_name} (will stop running): {e:?}");
                         return Err(PipelineError::for_element(source_name, e));
                     }
                 };
@@ -179,14 +184,17 @@ pub(crate) async fn run_managed(
     Ok(())
 }
 
-pub async fn run_autonomous(source_name: SourceName, source: AutonomousSource) -> Result<(), PipelineError> {
+pub(crate) async fn run_autonomous(
+    source_name: SourceName,
+    source: Measured<AutonomousSource>,
+) -> Result<(), PipelineError> {
     match source.await {
         Ok(_) => {
             log::debug!("{source_name} stops.");
             Ok(())
         }
         Err(e) => {
-            log::error!("Error in autonomous source {source_name} (will stop running): {e:?}");
+            log::error!(element:% = source_name; "Error in autonomous source {source_name} (will stop running): {e:?}");
             Err(PipelineError::for_element(source_name, e))
         }
     }
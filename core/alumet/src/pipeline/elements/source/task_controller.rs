@@ -7,14 +7,14 @@ use tokio::sync::Notify;
 use tokio_util::sync::CancellationToken;
 
 use super::control::{Reconfiguration, TaskState};
-use super::trigger::{ManualTrigger, Trigger};
+use super::trigger::{ManualTrigger, Trigger, TriggerSpec};
 
 /// A controller for a single source.
 pub enum SingleSourceController {
-    /// Dynamic configuration of a managed source + manual trigger.
+    /// Dynamic configuration of a managed source + manual trigger, and the spec of its current trigger.
     ///
     /// This is more flexible than the token of autonomous sources.
-    Managed(Arc<SharedSourceConfig>),
+    Managed(Arc<SharedSourceConfig>, TriggerSpec),
 
     /// When cancelled, shuts the autonomous source down.
     ///
@@ -33,6 +33,7 @@ pub struct SharedSourceConfig {
 }
 
 pub fn new_managed(
+    initial_spec: TriggerSpec,
     initial_trigger: Trigger,
     initial_state: TaskState,
 ) -> (SingleSourceController, Arc<SharedSourceConfig>) {
@@ -43,7 +44,7 @@ pub fn new_managed(
         new_trigger: Mutex::new(Some(initial_trigger)),
         manual_trigger,
     });
-    (SingleSourceController::Managed(config.clone()), config)
+    (SingleSourceController::Managed(config.clone(), initial_spec), config)
 }
 
 pub fn new_autonomous(shutdown_token: CancellationToken) -> SingleSourceController {
@@ -53,7 +54,7 @@ pub fn new_autonomous(shutdown_token: CancellationToken) -> SingleSourceControll
 impl SingleSourceController {
     pub fn reconfigure(&mut self, command: &Reconfiguration) {
         match self {
-            SingleSourceController::Managed(shared) => {
+            SingleSourceController::Managed(shared, spec) => {
                 match &command {
                     Reconfiguration::SetState(new_state) => {
                         // TODO use a bit to signal that there's a new trigger?
@@ -62,6 +63,7 @@ impl SingleSourceController {
                     Reconfiguration::SetTrigger(new_spec) => {
                         let trigger = Trigger::new(new_spec.to_owned()).unwrap();
                         *shared.new_trigger.lock().unwrap() = Some(trigger);
+                        *spec = new_spec.to_owned();
                     }
                 }
                 shared.change_notifier.notify_one();
@@ -75,9 +77,17 @@ impl SingleSourceController {
         }
     }
 
+    /// Returns the spec of the current trigger, or `None` if the source is autonomous.
+    pub fn trigger_spec(&self) -> Option<&TriggerSpec> {
+        match self {
+            SingleSourceController::Managed(_, spec) => Some(spec),
+            SingleSourceController::Autonomous(_) => None,
+        }
+    }
+
     pub fn trigger_now(&mut self) {
         match self {
-            SingleSourceController::Managed(shared) => {
+            SingleSourceController::Managed(shared, _) => {
                 if let Some(t) = &shared.manual_trigger {
                     t.trigger_now();
                 }
@@ -111,6 +111,37 @@ impl TriggerSpec {
         builder::time_interval(poll_interval)
     }
 
+    /// Returns the interval between two polls, if the source is triggered at regular intervals.
+    pub fn poll_interval(&self) -> Option<time::Duration> {
+        match self.mechanism {
+            TriggerMechanismSpec::TimeInterval(_, poll_interval) => Some(poll_interval),
+            _ => None,
+        }
+    }
+
+    /// Returns a copy of this spec that polls the source at another interval,
+    /// or `None` if the source is not triggered at regular intervals.
+    ///
+    /// The other settings are kept. In particular, the flush and update intervals do not change:
+    /// the number of polls between two flushes (and between two updates) is adjusted to the new interval.
+    pub fn with_poll_interval(&self, poll_interval: time::Duration) -> Option<TriggerSpec> {
+        let TriggerMechanismSpec::TimeInterval(_, old_interval) = self.mechanism else {
+            return None;
+        };
+        let rounds = |old_rounds: usize| {
+            let interval = old_interval.as_nanos() * old_rounds as u128;
+            ((interval / poll_interval.as_nanos().max(1)) as usize).max(1)
+        };
+        let mut spec = self.clone();
+        spec.mechanism = TriggerMechanismSpec::TimeInterval(time::Instant::now(), poll_interval);
+        spec.loop_params.flush_rounds = rounds(self.loop_params.flush_rounds);
+        if !self.interruptible {
+            // An interruptible trigger updates the command on each poll, and on each interruption.
+            spec.loop_params.update_rounds = rounds(self.loop_params.update_rounds);
+        }
+        Some(spec)
+    }
+
     /// Adjusts the trigger specification to respect the given constraints.
     ///
     /// # Constraints
@@ -275,8 +306,10 @@ enum TriggerMechanism {
     #[cfg(target_os = "linux")]
     Timerfd(tokio_timerfd::Interval),
 
-    /// A trigger based on [`tokio::time::sleep`].
-    #[allow(dead_code)]
+    /// A trigger based on [`tokio::time::sleep_until`], used on the platforms that do not support timerfd.
+    ///
+    /// Contains the next deadline and the period.
+    #[cfg_attr(target_os = "linux", allow(dead_code))]
     Sleep(tokio::time::Instant, tokio::time::Duration),
 
     /// A "manual" trigger based on [`tokio::sync::Notify`].
@@ -314,19 +347,23 @@ impl TryFrom<TriggerMechanismSpec> for TriggerMechanism {
 
 impl TriggerMechanism {
     pub async fn next(&mut self) -> Result<(), std::io::Error> {
-        use tokio_stream::StreamExt;
-
         match self {
             #[cfg(target_os = "linux")]
             TriggerMechanism::Timerfd(interval) => {
+                use tokio_stream::StreamExt;
+
                 interval.next().await.unwrap()?;
                 Ok(())
             }
-            TriggerMechanism::Sleep(start, period) => {
-                let start = *start;
+            TriggerMechanism::Sleep(next, period) => {
+                tokio::time::sleep_until(*next).await;
+                // Compute the next deadline from the previous one, not from the current time,
+                // so that the trigger does not drift. Skip the ticks that have been missed, if any.
                 let now = tokio::time::Instant::now();
-                let deadline = if start > now { start } else { now + *period };
-                tokio::time::sleep_until(deadline).await;
+                *next += *period;
+                if *next <= now {
+                    *next = now + *period;
+                }
                 Ok(())
             }
             TriggerMechanism::Future(f) => f().await,
@@ -455,4 +492,29 @@ mod tests {
         assert_eq!(trigger.loop_params.flush_rounds, 5);
         assert_eq!(trigger.loop_params.update_rounds, 1);
     }
+
+    #[test]
+    fn change_poll_interval() {
+        let trigger = builder::time_interval(Duration::from_secs(1))
+            .flush_interval(Duration::from_secs(6))
+            .update_interval(Duration::from_secs(2))
+            .build()
+            .unwrap();
+
+        // the flush and update intervals are kept
+        let faster = trigger.with_poll_interval(Duration::from_millis(500)).unwrap();
+        assert_eq!(faster.poll_interval(), Some(Duration::from_millis(500)));
+        assert_eq!(faster.loop_params.flush_rounds, 12);
+        assert_eq!(faster.loop_params.update_rounds, 4);
+        assert!(!faster.interruptible);
+
+        let slower = trigger.with_poll_interval(Duration::from_secs(3)).unwrap();
+        assert_eq!(slower.poll_interval(), Some(Duration::from_secs(3)));
+        assert_eq!(slower.loop_params.flush_rounds, 2);
+        assert_eq!(slower.loop_params.update_rounds, 1); // max(1)
+
+        // not triggered at regular intervals: nothing to change
+        let manual = builder::manual().build().unwrap();
+        assert!(manual.with_poll_interval(Duration::from_secs(1)).is_none());
+    }
 }
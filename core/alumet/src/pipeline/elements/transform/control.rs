@@ -14,10 +14,11 @@ use crate::pipeline::control::matching::TransformMatcher;
 use crate::pipeline::error::PipelineError;
 use crate::pipeline::matching::ElementNamePattern;
 use crate::pipeline::naming::{ElementKind, ElementName, TransformName};
+use crate::pipeline::sampling::{Decimator, SamplingRules};
+use crate::pipeline::usage::UsageTracker;
 
-use super::Transform;
 use super::builder::{BuildContext, TransformBuilder};
-use super::run::run_all_in_order;
+use super::run::{NamedTransform, run_all_in_order};
 
 /// Controls the transforms of a measurement pipeline.
 ///
@@ -47,10 +48,12 @@ impl TransformControl {
 
     pub fn with_transforms(
         transforms: Vec<(TransformName, Box<dyn TransformBuilder>)>,
+        sampling_rules: SamplingRules,
         metrics: MetricReader,
         rx: mpsc::Receiver<MeasurementBuffer>,
         tx: broadcast::Sender<MeasurementBuffer>,
         rt_normal: &runtime::Handle,
+        usage: &UsageTracker,
     ) -> anyhow::Result<Self> {
         let metrics_r = metrics.blocking_read();
         let mut built = Vec::with_capacity(transforms.len());
@@ -59,9 +62,19 @@ impl TransformControl {
             let transform = builder(&mut ctx)
                 .context("transform creation failed")
                 .inspect_err(|e| log::error!("Failed to build transform {full_name}: {e:#}"))?;
-            built.push((full_name, transform));
+            let counter = usage.register(full_name.clone());
+            built.push((full_name, transform, counter));
         }
-        let tasks = TaskManager::spawn(built, metrics.clone(), rx, tx, rt_normal);
+        let decimator = if sampling_rules.is_empty() {
+            None
+        } else {
+            log::info!(
+                "{} sampling rule(s) will be applied after the sources.",
+                sampling_rules.len()
+            );
+            Some(Decimator::new(sampling_rules))
+        };
+        let tasks = TaskManager::spawn(built, decimator, metrics.clone(), rx, tx, rt_normal);
         Ok(Self { tasks })
     }
 
@@ -109,7 +122,8 @@ impl TransformControl {
 
 impl TaskManager {
     pub fn spawn(
-        transforms: Vec<(TransformName, Box<dyn Transform>)>,
+        transforms: Vec<NamedTransform>,
+        decimator: Option<Decimator>,
         metrics_r: MetricReader,
         rx: mpsc::Receiver<MeasurementBuffer>,
         tx: broadcast::Sender<MeasurementBuffer>,
@@ -118,7 +132,7 @@ impl TaskManager {
         let mut active_bitset: u64 = 0;
         let mut names_by_bitset_position = Vec::with_capacity(transforms.len());
 
-        for (i, (name, _)) in transforms.iter().enumerate() {
+        for (i, (name, _, _)) in transforms.iter().enumerate() {
             active_bitset |= 1 << i;
             names_by_bitset_position.push(name.clone());
         }
@@ -126,7 +140,7 @@ impl TaskManager {
         // Start the transforms task.
         let mut set = JoinSet::new();
         let active_bitset = Arc::new(AtomicU64::new(active_bitset));
-        let task = run_all_in_order(transforms, rx, tx, active_bitset.clone(), metrics_r);
+        let task = run_all_in_order(transforms, decimator, rx, tx, active_bitset.clone(), metrics_r);
         set.spawn_on(task, rt_normal);
         Self {
             spawned_tasks: set,
@@ -11,13 +11,22 @@ use tokio::sync::{broadcast, mpsc};
 use crate::{
     measurement::MeasurementBuffer,
     metrics::online::MetricReader,
-    pipeline::{error::PipelineError, naming::TransformName},
+    pipeline::{
+        error::PipelineError,
+        naming::TransformName,
+        sampling::Decimator,
+        usage::{self, UsageCounter},
+    },
 };
 
 use super::{Transform, TransformContext, error::TransformError};
 
-pub async fn run_all_in_order(
-    mut transforms: Vec<(TransformName, Box<dyn Transform>)>,
+/// A transform with its name and the counter that measures its resource usage, if enabled.
+pub(crate) type NamedTransform = (TransformName, Box<dyn Transform>, Option<Arc<UsageCounter>>);
+
+pub(crate) async fn run_all_in_order(
+    mut transforms: Vec<NamedTransform>,
+    mut decimator: Option<Decimator>,
     mut rx: mpsc::Receiver<MeasurementBuffer>,
     tx: broadcast::Sender<MeasurementBuffer>,
     active_flags: Arc<AtomicU64>,
@@ -27,7 +36,7 @@ pub async fn run_all_in_order(
         "Running transforms: {}",
         transforms
             .iter()
-            .map(|(name, _)| name.to_string())
+            .map(|(name, _, _)| name.to_string())
             .collect::<Vec<_>>()
             .join(", ")
     );
@@ -46,17 +55,25 @@ pub async fn run_all_in_order(
             let metrics = &metrics_reader.read().await;
             let ctx = TransformContext { metrics };
 
+            // Apply the sampling rules before any transform.
+            if let Some(decimator) = &mut decimator {
+                decimator.apply(&mut measurements, metrics);
+                if measurements.is_empty() {
+                    continue;
+                }
+            }
+
             // Run the enabled transforms. If one of them fails, the ability to continue running depends on the error type.
-            for (i, (name, t)) in &mut transforms.iter_mut().enumerate() {
+            for (i, (name, t, usage)) in &mut transforms.iter_mut().enumerate() {
                 let t_flag = 1 << i;
                 if current_flags & t_flag != 0 {
-                    match t.apply(&mut measurements, &ctx) {
+                    match usage::measure(usage.as_deref(), || t.apply(&mut measurements, &ctx)) {
                         Ok(()) => (),
                         Err(TransformError::UnexpectedInput(e)) => {
-                            log::error!("Transform {name} received unexpected measurements: {e:#}");
+                            log::error!(element:% = name; "Transform {name} received unexpected measurements: {e:#}");
                         }
                         Err(TransformError::Fatal(e)) => {
-                            log::error!("Fatal error in transform {name} (this breaks the transform task!): {e:?}");
+                            log::error!(element:% = name; "Fatal error in transform {name} (this breaks the transform task!): {e:?}");
                             return Err(PipelineError::for_element(name.to_owned(), e));
                         }
                     }
@@ -76,14 +93,14 @@ pub async fn run_all_in_order(
     let metrics = &metrics_reader.read().await;
     let ctx = TransformContext { metrics };
     let mut err = Ok(());
-    for (name, trans) in transforms.iter_mut() {
+    for (name, trans, _) in transforms.iter_mut() {
         match trans.finish(&ctx) {
             Ok(()) => (),
             Err(TransformError::UnexpectedInput(e)) => {
-                log::error!("Transform {name} received unexpected measurements during finish: {e:#}");
+                log::error!(element:% = name; "Transform {name} received unexpected measurements during finish: {e:#}");
             }
             Err(TransformError::Fatal(e)) => {
-                log::error!("Fatal error in transform {name} during finish: {e:?}");
+                log::error!(element:% = name; "Fatal error in transform {name} during finish: {e:?}");
                 err = Err(PipelineError::for_element(name.to_owned(), e));
             }
         }
@@ -1,4 +1,7 @@
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    sync::{Arc, Mutex},
+};
 
 use super::naming::{ElementName, PluginName};
 
@@ -87,6 +90,44 @@ impl PipelineError {
     }
 }
 
+/// A pipeline element that has stopped because of an error.
+#[derive(Debug, Clone)]
+pub struct ElementFailure {
+    pub element: ElementName,
+    /// The error, with its causes.
+    pub error: String,
+}
+
+/// The elements of a running pipeline that have failed.
+///
+/// Obtain it with [`MeasurementPipeline::failures`](super::MeasurementPipeline::failures).
+/// All the clones share the same list, which is updated by the pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct ElementFailures(Arc<Mutex<Vec<ElementFailure>>>);
+
+impl ElementFailures {
+    /// Returns the failures that have occurred so far, in order.
+    pub fn list(&self) -> Vec<ElementFailure> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Returns true if no element has failed.
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+
+    /// Records the error, if it comes from a pipeline element.
+    pub(crate) fn record(&self, error: &PipelineError) {
+        if let Some(element) = error.element() {
+            let failure = ElementFailure {
+                element: element.clone(),
+                error: format!("{:#}", error.0),
+            };
+            self.0.lock().unwrap().push(failure);
+        }
+    }
+}
+
 impl From<anyhow::Error> for PipelineError {
     fn from(value: anyhow::Error) -> Self {
         Self(value.context("error in pipeline"))
@@ -116,7 +157,7 @@ mod tests {
         naming::{ElementKind, ElementName},
     };
 
-    use super::PipelineError;
+    use super::{ElementFailures, PipelineError};
 
     #[test]
     fn check_types() {
@@ -156,4 +197,22 @@ mod tests {
         println!("wrapped error source: {:#}", wrapped.0.source().unwrap());
         assert_eq!(wrapped.element(), Some(&name));
     }
+
+    #[test]
+    fn failures() {
+        let name = ElementName {
+            kind: ElementKind::Output,
+            plugin: String::from("plugin"),
+            element: String::from("out"),
+        };
+        let failures = ElementFailures::default();
+        failures.record(&PipelineError::internal(anyhow!("not an element")));
+        assert!(failures.is_empty());
+
+        failures.record(&PipelineError::for_element(name.clone(), anyhow!("disk full")));
+        let list = failures.clone().list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].element, name);
+        assert_eq!(list[0].error, "error in outputs/plugin/out: disk full");
+    }
 }
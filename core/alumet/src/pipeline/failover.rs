@@ -0,0 +1,515 @@
+//! Failover of outputs.
+//!
+//! A failover rule pairs a _primary_ output with a _secondary_ output, such as a local file.
+//! The measurements are normally written to the primary output only. When it fails with a
+//! [`WriteError::CanRetry`], the measurements are written to the secondary output instead, and
+//! kept in memory. From time to time, the pipeline tries to write them to the primary output again:
+//! once it succeeds, the gap is filled and the secondary output stops receiving measurements.
+//!
+//! If the primary output fails with a [`WriteError::Fatal`], the secondary output replaces it for good.
+//!
+//! The rules are applied when the pipeline is built. The secondary output is only fed by its primary
+//! output: it does not receive the measurements directly. Only blocking outputs support failover.
+//!
+//! # Example
+//! ```
+//! use std::time::Duration;
+//! use alumet::pipeline;
+//! use alumet::pipeline::failover::FailoverRule;
+//! use alumet::pipeline::naming::OutputName;
+//!
+//! let mut pipeline = pipeline::Builder::new();
+//! let rules = pipeline.failover_rules_mut();
+//!
+//! // When influxdb is unavailable, write to a CSV file.
+//! let mut rule = FailoverRule::new(OutputName::from_str("csv", "out"));
+//! rule.retry_interval = Duration::from_secs(10);
+//! rules.set(OutputName::from_str("influxdb", "out"), rule);
+//! ```
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+
+use crate::measurement::MeasurementBuffer;
+
+use super::elements::output::builder::{BlockingOutputBuildContext, BlockingOutputBuilder, OutputBuilder};
+use super::elements::output::{Output, OutputContext, WriteError};
+use super::naming::{OutputName, namespace::Namespace2};
+
+/// Configures the failover of a primary output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailoverRule {
+    /// The output that receives the measurements when the primary output fails.
+    pub secondary: OutputName,
+    /// Minimum delay between two attempts to write to the primary output, after a failure.
+    pub retry_interval: Duration,
+    /// Maximum number of measurement points kept in memory while the primary output fails.
+    ///
+    /// When this limit is exceeded, the oldest measurements are not written to the primary output
+    /// (but they have been written to the secondary output).
+    pub max_gap_points: usize,
+}
+
+impl FailoverRule {
+    /// Creates a rule with the default retry interval (30 seconds) and the default gap size (100 000 points).
+    pub fn new(secondary: OutputName) -> Self {
+        Self {
+            secondary,
+            retry_interval: Duration::from_secs(30),
+            max_gap_points: 100_000,
+        }
+    }
+}
+
+/// A set of failover rules, indexed by primary output.
+#[derive(Debug, Clone, Default)]
+pub struct FailoverRules {
+    by_primary: HashMap<OutputName, FailoverRule>,
+}
+
+impl FailoverRules {
+    /// Creates an empty set of rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the failover rule of a primary output, replacing the previous one (if any).
+    pub fn set(&mut self, primary: OutputName, rule: FailoverRule) {
+        self.by_primary.insert(primary, rule);
+    }
+
+    /// Returns the failover rule of a primary output, if any.
+    pub fn get(&self, primary: &OutputName) -> Option<&FailoverRule> {
+        self.by_primary.get(primary)
+    }
+
+    /// Returns `true` if there is no rule.
+    pub fn is_empty(&self) -> bool {
+        self.by_primary.is_empty()
+    }
+
+    /// The number of rules.
+    pub fn len(&self) -> usize {
+        self.by_primary.len()
+    }
+
+    /// Checks that the rules are valid, and that they refer to existing blocking outputs.
+    pub(crate) fn check(&self, outputs: &Namespace2<OutputBuilder>) -> anyhow::Result<()> {
+        let mut secondaries = HashSet::new();
+        for (primary, rule) in &self.by_primary {
+            if rule.secondary == *primary {
+                return Err(anyhow!(
+                    "invalid failover of {primary}: an output cannot be its own secondary"
+                ));
+            }
+            if self.by_primary.contains_key(&rule.secondary) {
+                return Err(anyhow!(
+                    "invalid failover of {primary}: {} has its own secondary, failovers cannot be chained",
+                    rule.secondary
+                ));
+            }
+            if !secondaries.insert(&rule.secondary) {
+                return Err(anyhow!(
+                    "invalid failover of {primary}: {} is already the secondary of another output",
+                    rule.secondary
+                ));
+            }
+            check_blocking(outputs, primary)?;
+            check_blocking(outputs, &rule.secondary)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the builders of each primary output and of its secondary output by a single builder,
+    /// which builds a [`FailoverOutput`].
+    pub(crate) fn apply(self, outputs: &mut Namespace2<OutputBuilder>) -> anyhow::Result<()> {
+        self.check(outputs)?;
+        for (primary, rule) in self.by_primary {
+            let primary_builder = take_blocking(outputs, &primary)?;
+            let secondary_builder = take_blocking(outputs, &rule.secondary)?;
+            let name = primary.clone();
+            let builder = move |ctx: &mut dyn BlockingOutputBuildContext| {
+                let primary_output = primary_builder(ctx)?;
+                let secondary_output = secondary_builder(ctx)?;
+                let output = FailoverOutput::new(name, primary_output, secondary_output, rule);
+                Ok(Box::new(output) as Box<dyn Output>)
+            };
+            outputs.add(
+                primary.plugin().to_owned(),
+                primary.output().to_owned(),
+                OutputBuilder::Blocking(Box::new(builder)),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that `name` refers to a blocking output.
+fn check_blocking(outputs: &Namespace2<OutputBuilder>, name: &OutputName) -> anyhow::Result<()> {
+    match outputs.get(name.plugin(), name.output()) {
+        Some(OutputBuilder::Blocking(_)) => Ok(()),
+        Some(OutputBuilder::Async(_)) => Err(anyhow!(
+            "invalid failover: {name} is an async output, only blocking outputs support failover"
+        )),
+        None => Err(anyhow!("invalid failover: there is no output {name}")),
+    }
+}
+
+/// Removes the builder of a blocking output.
+fn take_blocking(
+    outputs: &mut Namespace2<OutputBuilder>,
+    name: &OutputName,
+) -> anyhow::Result<Box<dyn BlockingOutputBuilder>> {
+    check_blocking(outputs, name)?;
+    match outputs.remove(name.plugin(), name.output()) {
+        Some(OutputBuilder::Blocking(builder)) => Ok(builder),
+        _ => unreachable!("the output has been checked"),
+    }
+}
+
+/// An output that writes to a secondary output when its primary output fails.
+pub(crate) struct FailoverOutput {
+    name: OutputName,
+    /// The primary output, or `None` if it has failed with a fatal error.
+    primary: Option<Box<dyn Output>>,
+    secondary: Box<dyn Output>,
+    rule: FailoverRule,
+    /// Measurements that have not been written to the primary output yet, oldest first.
+    ///
+    /// The output is failed over if and only if the gap is not empty.
+    gap: VecDeque<MeasurementBuffer>,
+    /// Number of measurement points in the gap.
+    gap_points: usize,
+    /// When to try to write to the primary output again.
+    next_retry: Instant,
+}
+
+impl FailoverOutput {
+    pub fn new(name: OutputName, primary: Box<dyn Output>, secondary: Box<dyn Output>, rule: FailoverRule) -> Self {
+        Self {
+            name,
+            primary: Some(primary),
+            secondary,
+            rule,
+            gap: VecDeque::new(),
+            gap_points: 0,
+            next_retry: Instant::now(),
+        }
+    }
+
+    /// Adds measurements to the gap, and discards the oldest ones if the gap is too large.
+    fn push_gap(&mut self, measurements: &MeasurementBuffer) {
+        self.gap_points += measurements.len();
+        self.gap.push_back(measurements.clone());
+        let mut discarded = 0;
+        while self.gap_points > self.rule.max_gap_points && self.gap.len() > 1 {
+            let oldest = self.gap.pop_front().unwrap();
+            self.gap_points -= oldest.len();
+            discarded += oldest.len();
+        }
+        if discarded > 0 {
+            log::warn!(
+                "The gap of {} is too large, {discarded} measurements will only be in {}.",
+                self.name,
+                self.rule.secondary
+            );
+        }
+    }
+
+    /// Writes the gap to the primary output, until it is empty or the primary output fails.
+    fn sync(&mut self, ctx: &OutputContext) {
+        let Some(primary) = &mut self.primary else {
+            return;
+        };
+        let n_buffers = self.gap.len();
+        while let Some(measurements) = self.gap.front() {
+            match primary.write(measurements, ctx) {
+                Ok(()) => {
+                    self.gap_points -= measurements.len();
+                    self.gap.pop_front();
+                }
+                Err(WriteError::CanRetry(e)) => {
+                    log::debug!("{} is still failing: {e:#}", self.name);
+                    self.next_retry = Instant::now() + self.rule.retry_interval;
+                    return;
+                }
+                Err(WriteError::Fatal(e)) => {
+                    self.fail_permanently(e);
+                    return;
+                }
+            }
+        }
+        log::info!(
+            "{} works again, {n_buffers} buffers of measurements have been synced.",
+            self.name
+        );
+    }
+
+    fn fail_permanently(&mut self, e: anyhow::Error) {
+        log::error!(
+            "Fatal error when writing to {}, {} replaces it from now on: {e:?}",
+            self.name,
+            self.rule.secondary
+        );
+        self.primary = None;
+        self.gap.clear();
+        self.gap_points = 0;
+    }
+}
+
+impl Output for FailoverOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let Some(primary) = &mut self.primary else {
+            return self.secondary.write(measurements, ctx);
+        };
+        if self.gap.is_empty() {
+            match primary.write(measurements, ctx) {
+                Ok(()) => return Ok(()),
+                Err(WriteError::CanRetry(e)) => {
+                    log::warn!(
+                        "Error when writing to {}, failing over to {}: {e:#}",
+                        self.name,
+                        self.rule.secondary
+                    );
+                    self.next_retry = Instant::now() + self.rule.retry_interval;
+                }
+                Err(WriteError::Fatal(e)) => {
+                    self.fail_permanently(e);
+                    return self.secondary.write(measurements, ctx);
+                }
+            }
+        }
+
+        // Failed over: the measurements will be written to the primary output later.
+        self.push_gap(measurements);
+        if Instant::now() >= self.next_retry {
+            self.sync(ctx);
+            if self.gap.is_empty() {
+                // the new measurements have been written to the primary output
+                return Ok(());
+            }
+        }
+        self.secondary.write(measurements, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+
+    use crate::measurement::{MeasurementBuffer, MeasurementPoint, Timestamp};
+    use crate::metrics::def::RawMetricId;
+    use crate::metrics::registry::MetricRegistry;
+    use crate::pipeline::elements::output::builder::{BlockingOutputBuildContext, OutputBuilder};
+    use crate::pipeline::elements::output::{Output, OutputContext, WriteError};
+    use crate::pipeline::naming::{OutputName, namespace::Namespace2};
+    use crate::resources::{Resource, ResourceConsumer};
+
+    use super::{FailoverOutput, FailoverRule, FailoverRules};
+
+    /// How the mock output reacts to the next writes.
+    #[derive(Clone, Copy, PartialEq)]
+    enum Behavior {
+        Accept,
+        Retry,
+        Fatal,
+    }
+
+    /// An output that records the value of the first point of each buffer.
+    struct MockOutput {
+        behavior: Arc<Mutex<Behavior>>,
+        written: Arc<Mutex<Vec<u64>>>,
+    }
+
+    /// The mock output, with handles to change its behavior and to read what it wrote.
+    type Mock = (MockOutput, Arc<Mutex<Behavior>>, Arc<Mutex<Vec<u64>>>);
+
+    impl Output for MockOutput {
+        fn write(&mut self, measurements: &MeasurementBuffer, _ctx: &OutputContext) -> Result<(), WriteError> {
+            match *self.behavior.lock().unwrap() {
+                Behavior::Accept => {
+                    let value = measurements.iter().next().unwrap().value.as_u64();
+                    self.written.lock().unwrap().push(value);
+                    Ok(())
+                }
+                Behavior::Retry => Err(WriteError::CanRetry(anyhow!("unavailable"))),
+                Behavior::Fatal => Err(WriteError::Fatal(anyhow!("broken"))),
+            }
+        }
+    }
+
+    fn mock() -> Mock {
+        let behavior = Arc::new(Mutex::new(Behavior::Accept));
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let output = MockOutput {
+            behavior: behavior.clone(),
+            written: written.clone(),
+        };
+        (output, behavior, written)
+    }
+
+    fn buffer(value: u64, len: usize) -> MeasurementBuffer {
+        let mut buf = MeasurementBuffer::new();
+        for _ in 0..len {
+            buf.push(MeasurementPoint::new_untyped(
+                Timestamp::now(),
+                RawMetricId::from_u64(0),
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                crate::measurement::WrappedMeasurementValue::U64(value),
+            ));
+        }
+        buf
+    }
+
+    fn rule(max_gap_points: usize) -> FailoverRule {
+        FailoverRule {
+            secondary: OutputName::from_str("file", "out"),
+            retry_interval: Duration::ZERO,
+            max_gap_points,
+        }
+    }
+
+    #[test]
+    fn failover_and_sync() {
+        let (primary, primary_behavior, primary_written) = mock();
+        let (secondary, _, secondary_written) = mock();
+        let name = OutputName::from_str("db", "out");
+        let mut output = FailoverOutput::new(name, Box::new(primary), Box::new(secondary), rule(100));
+        let metrics = MetricRegistry::new();
+        let ctx = OutputContext { metrics: &metrics };
+
+        output.write(&buffer(1, 1), &ctx).unwrap();
+        *primary_behavior.lock().unwrap() = Behavior::Retry;
+        output.write(&buffer(2, 1), &ctx).unwrap();
+        output.write(&buffer(3, 1), &ctx).unwrap();
+        *primary_behavior.lock().unwrap() = Behavior::Accept;
+        output.write(&buffer(4, 1), &ctx).unwrap();
+        output.write(&buffer(5, 1), &ctx).unwrap();
+
+        // the gap has been filled, in order
+        assert_eq!(*primary_written.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(*secondary_written.lock().unwrap(), vec![2, 3]);
+    }
+
+    #[test]
+    fn retry_interval() {
+        let (primary, primary_behavior, primary_written) = mock();
+        let (secondary, _, secondary_written) = mock();
+        let name = OutputName::from_str("db", "out");
+        let rule = FailoverRule {
+            retry_interval: Duration::from_secs(3600),
+            ..rule(100)
+        };
+        let mut output = FailoverOutput::new(name, Box::new(primary), Box::new(secondary), rule);
+        let metrics = MetricRegistry::new();
+        let ctx = OutputContext { metrics: &metrics };
+
+        *primary_behavior.lock().unwrap() = Behavior::Retry;
+        output.write(&buffer(1, 1), &ctx).unwrap();
+        *primary_behavior.lock().unwrap() = Behavior::Accept;
+        output.write(&buffer(2, 1), &ctx).unwrap();
+
+        // the primary output is not tried again before the end of the interval
+        assert!(primary_written.lock().unwrap().is_empty());
+        assert_eq!(*secondary_written.lock().unwrap(), vec![1, 2]);
+        assert_eq!(output.gap.len(), 2);
+    }
+
+    #[test]
+    fn max_gap() {
+        let (primary, primary_behavior, primary_written) = mock();
+        let (secondary, _, secondary_written) = mock();
+        let name = OutputName::from_str("db", "out");
+        let mut output = FailoverOutput::new(name, Box::new(primary), Box::new(secondary), rule(5));
+        let metrics = MetricRegistry::new();
+        let ctx = OutputContext { metrics: &metrics };
+
+        *primary_behavior.lock().unwrap() = Behavior::Retry;
+        for i in 1..=4 {
+            output.write(&buffer(i, 2), &ctx).unwrap();
+        }
+        assert_eq!(output.gap_points, 4);
+        *primary_behavior.lock().unwrap() = Behavior::Accept;
+        output.write(&buffer(5, 2), &ctx).unwrap();
+
+        // the oldest buffers have been dropped from the gap, but not from the secondary output
+        assert_eq!(*primary_written.lock().unwrap(), vec![4, 5]);
+        assert_eq!(*secondary_written.lock().unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn fatal_error() {
+        let (primary, primary_behavior, primary_written) = mock();
+        let (secondary, _, secondary_written) = mock();
+        let name = OutputName::from_str("db", "out");
+        let mut output = FailoverOutput::new(name, Box::new(primary), Box::new(secondary), rule(100));
+        let metrics = MetricRegistry::new();
+        let ctx = OutputContext { metrics: &metrics };
+
+        *primary_behavior.lock().unwrap() = Behavior::Fatal;
+        output.write(&buffer(1, 1), &ctx).unwrap();
+        *primary_behavior.lock().unwrap() = Behavior::Accept;
+        output.write(&buffer(2, 1), &ctx).unwrap();
+
+        // the primary output is never used again
+        assert!(primary_written.lock().unwrap().is_empty());
+        assert_eq!(*secondary_written.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn apply_rules() {
+        fn outputs(names: &[(&str, &str)]) -> Namespace2<OutputBuilder> {
+            let mut outputs = Namespace2::new();
+            for (plugin, name) in names {
+                let builder = |_: &mut dyn BlockingOutputBuildContext| Ok(Box::new(mock().0) as Box<dyn Output>);
+                outputs
+                    .add(
+                        plugin.to_string(),
+                        name.to_string(),
+                        OutputBuilder::Blocking(Box::new(builder)),
+                    )
+                    .unwrap();
+            }
+            outputs
+        }
+        let db = OutputName::from_str("db", "out");
+        let file = OutputName::from_str("file", "out");
+
+        let mut rules = FailoverRules::new();
+        rules.set(db.clone(), rule(100));
+        let mut builders = outputs(&[("db", "out"), ("file", "out"), ("other", "out")]);
+        rules.apply(&mut builders).unwrap();
+        let mut names: Vec<_> = builders.flat_keys().cloned().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                (String::from("db"), String::from("out")),
+                (String::from("other"), String::from("out"))
+            ]
+        );
+
+        // missing secondary
+        let mut rules = FailoverRules::new();
+        rules.set(db.clone(), rule(100));
+        assert!(rules.apply(&mut outputs(&[("db", "out")])).is_err());
+
+        // chained failovers
+        let mut rules = FailoverRules::new();
+        rules.set(db.clone(), rule(100));
+        rules.set(file.clone(), FailoverRule::new(OutputName::from_str("other", "out")));
+        let mut builders = outputs(&[("db", "out"), ("file", "out"), ("other", "out")]);
+        assert!(rules.apply(&mut builders).is_err());
+
+        // self failover
+        let mut rules = FailoverRules::new();
+        rules.set(file.clone(), FailoverRule::new(file.clone()));
+        assert!(rules.apply(&mut outputs(&[("file", "out")])).is_err());
+    }
+}
@@ -14,7 +14,11 @@ pub mod builder;
 pub mod control;
 pub mod elements;
 pub mod error;
+pub mod failover;
 pub mod naming;
+pub mod sampling;
+pub mod shutdown;
+pub mod usage;
 pub(crate) mod util;
 
 pub use elements::output::Output;
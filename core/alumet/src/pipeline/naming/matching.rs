@@ -1,5 +1,6 @@
 //! Match pipeline elements by plugin, element kind, element name, etc.
 
+use regex::Regex;
 use thiserror::Error;
 
 use super::{ElementKind, ElementName, OutputName, SourceName, TransformName};
@@ -18,15 +19,27 @@ pub struct ElementNamePattern {
 
 /// A pattern that matches a name (String).
 ///
-/// Name patterns are a very simplified form of regular expression.
+/// Most name patterns are a very simplified form of regular expression.
+/// Use [`StringPattern::Regex`] when more flexibility is needed.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum StringPattern {
     Exact(String),
     StartWith(String),
     EndWith(String),
+    /// Matches the names that are entirely matched by a regular expression.
+    Regex(NameRegex),
     Any,
 }
 
+/// A regular expression that must match the whole name.
+///
+/// Two `NameRegex` are equal if they have been created from the same expression.
+#[derive(Debug, Clone)]
+pub struct NameRegex {
+    expr: String,
+    regex: Regex,
+}
+
 // Below are "restricted" patterns that only work on a specific element kind.
 // They can only be created if it can be proved that the kind to be matched is the right one.
 
@@ -49,16 +62,58 @@ pub struct IncompatibleKindError {
 }
 
 impl StringPattern {
+    /// Creates a pattern that matches the names that are entirely matched by the regular expression `expr`.
+    ///
+    /// # Example
+    /// ```
+    /// use alumet::pipeline::naming::matching::StringPattern;
+    ///
+    /// let pattern = StringPattern::regex("rapl|perf").unwrap();
+    /// assert!(pattern.matches("rapl"));
+    /// assert!(!pattern.matches("rapl-2"));
+    /// ```
+    pub fn regex(expr: &str) -> Result<Self, regex::Error> {
+        NameRegex::new(expr).map(StringPattern::Regex)
+    }
+
     pub fn matches(&self, name: &str) -> bool {
         match self {
             StringPattern::Exact(pat) => pat == name,
             StringPattern::StartWith(pat) => name.starts_with(pat),
             StringPattern::EndWith(pat) => name.ends_with(pat),
+            StringPattern::Regex(regex) => regex.matches(name),
             StringPattern::Any => true,
         }
     }
 }
 
+impl NameRegex {
+    pub fn new(expr: &str) -> Result<Self, regex::Error> {
+        let regex = Regex::new(&format!("^(?:{expr})$"))?;
+        Ok(Self {
+            expr: expr.to_owned(),
+            regex,
+        })
+    }
+
+    /// Returns the regular expression, as given to [`NameRegex::new`].
+    pub fn as_str(&self) -> &str {
+        &self.expr
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+impl PartialEq for NameRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+    }
+}
+
+impl Eq for NameRegex {}
+
 impl ElementNamePattern {
     /// Creates a "wildcard" pattern that matches everything.
     pub fn wildcard() -> Self {
@@ -127,6 +182,11 @@ impl SourceNamePattern {
         Self::new(StringPattern::Any, StringPattern::Any)
     }
 
+    /// Creates a pattern that matches all the sources of the given plugin.
+    pub fn plugin<S: Into<String>>(plugin: S) -> Self {
+        Self::new(StringPattern::Exact(plugin.into()), StringPattern::Any)
+    }
+
     pub fn matches(&self, name: &SourceName) -> bool {
         self.0.plugin.matches(&name.0.plugin) && self.0.element.matches(&name.0.element)
     }
@@ -150,6 +210,11 @@ impl TransformNamePattern {
         Self::new(StringPattern::Any, StringPattern::Any)
     }
 
+    /// Creates a pattern that matches all the transforms of the given plugin.
+    pub fn plugin<S: Into<String>>(plugin: S) -> Self {
+        Self::new(StringPattern::Exact(plugin.into()), StringPattern::Any)
+    }
+
     pub fn matches(&self, name: &TransformName) -> bool {
         self.0.plugin.matches(&name.0.plugin) && self.0.element.matches(&name.0.element)
     }
@@ -173,6 +238,11 @@ impl OutputNamePattern {
         Self::new(StringPattern::Any, StringPattern::Any)
     }
 
+    /// Creates a pattern that matches all the outputs of the given plugin.
+    pub fn plugin<S: Into<String>>(plugin: S) -> Self {
+        Self::new(StringPattern::Exact(plugin.into()), StringPattern::Any)
+    }
+
     pub fn matches(&self, name: &OutputName) -> bool {
         self.0.plugin.matches(&name.0.plugin) && self.0.element.matches(&name.0.element)
     }
@@ -3,7 +3,7 @@ use thiserror::Error;
 
 use crate::pipeline::naming::ElementKind;
 
-use super::matching::StringPattern;
+use super::matching::{OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern};
 
 /// Parses a string to an `ElementKind`.
 ///
@@ -29,6 +29,8 @@ pub enum NamePatternParseError {
     Asterisk,
     #[error("invalid pattern: the string is empty")]
     Empty,
+    #[error("invalid pattern: bad regular expression: {0}")]
+    Regex(String),
 }
 
 impl FromStr for StringPattern {
@@ -38,9 +40,14 @@ impl FromStr for StringPattern {
     ///
     /// The only special character in name patterns is `*`, which acts as a "wildcard".
     /// For instance, `a*` matches every name that begins with `a`.
+    ///
+    /// A pattern that starts with `~` is a regular expression, which must match the whole name.
+    /// For instance, `~rapl|perf` matches `rapl` and `perf`.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.is_empty() {
             Err(NamePatternParseError::Empty)
+        } else if let Some(expr) = s.strip_prefix('~') {
+            StringPattern::regex(expr).map_err(|e| NamePatternParseError::Regex(e.to_string()))
         } else if s == "*" {
             Ok(StringPattern::Any)
         } else if let Some(suffix) = s.strip_prefix('*') {
@@ -65,9 +72,76 @@ impl FromStr for StringPattern {
     }
 }
 
+/// Parses `plugin/element` into a pair of patterns.
+///
+/// The element can be omitted: `plugin` is the same as `plugin/*`.
+///
+/// The two parts are separated by the first `/` that is not escaped by a backslash,
+/// before the patterns are parsed. Therefore, a regular expression on the plugin can
+/// contain `\/` (which matches `/`), and the element pattern can contain any character.
+fn parse_plugin_and_element(s: &str) -> Result<(StringPattern, StringPattern), NamePatternParseError> {
+    match split_plugin_and_element(s) {
+        Some((plugin, element)) => Ok((StringPattern::from_str(plugin)?, StringPattern::from_str(element)?)),
+        None => Ok((StringPattern::from_str(s)?, StringPattern::Any)),
+    }
+}
+
+/// Splits `s` at the first unescaped `/`.
+fn split_plugin_and_element(s: &str) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '/' => return Some((&s[..i], &s[i + 1..])),
+            _ => (),
+        }
+    }
+    None
+}
+
+impl FromStr for SourceNamePattern {
+    type Err = NamePatternParseError;
+
+    /// Parses a `SourceNamePattern` of the form `plugin/source`, for instance `rapl/*`.
+    ///
+    /// Each part is parsed as a [`StringPattern`]. `plugin` alone matches all the sources of the plugin.
+    /// The parts are separated by the first `/`: in a regular expression on the plugin, write `\/` instead of `/`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (plugin, source) = parse_plugin_and_element(s)?;
+        Ok(SourceNamePattern::new(plugin, source))
+    }
+}
+
+impl FromStr for TransformNamePattern {
+    type Err = NamePatternParseError;
+
+    /// Parses a `TransformNamePattern` of the form `plugin/transform`.
+    ///
+    /// See [`SourceNamePattern::from_str`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (plugin, transform) = parse_plugin_and_element(s)?;
+        Ok(TransformNamePattern::new(plugin, transform))
+    }
+}
+
+impl FromStr for OutputNamePattern {
+    type Err = NamePatternParseError;
+
+    /// Parses an `OutputNamePattern` of the form `plugin/output`.
+    ///
+    /// See [`SourceNamePattern::from_str`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (plugin, output) = parse_plugin_and_element(s)?;
+        Ok(OutputNamePattern::new(plugin, output))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{NamePatternParseError, StringPattern};
+    use crate::pipeline::naming::SourceName;
+    use crate::pipeline::naming::matching::SourceNamePattern;
     use std::str::FromStr;
 
     #[test]
@@ -88,6 +162,47 @@ mod tests {
         assert_eq!(StringPattern::from_str("a*b"), Err(NamePatternParseError::Asterisk));
         assert_eq!(StringPattern::from_str("a*b*c"), Err(NamePatternParseError::Asterisk));
         assert_eq!(StringPattern::from_str(""), Err(NamePatternParseError::Empty));
+        assert_eq!(StringPattern::from_str("~a|b")?, StringPattern::regex("a|b")?);
+        assert!(matches!(
+            StringPattern::from_str("~a("),
+            Err(NamePatternParseError::Regex(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_source_pattern() -> anyhow::Result<()> {
+        let kwollect = SourceNamePattern::from_str("kwollect-input/*")?;
+        assert_eq!(kwollect, SourceNamePattern::plugin("kwollect-input"));
+        assert_eq!(SourceNamePattern::from_str("kwollect-input")?, kwollect);
+        assert!(kwollect.matches(&SourceName::new("kwollect-input".into(), "node-1".into())));
+        assert!(!kwollect.matches(&SourceName::new("rapl".into(), "node-1".into())));
+
+        let regex = SourceNamePattern::from_str("~rapl|perf/~cpu-[0-9]+")?;
+        assert!(regex.matches(&SourceName::new("perf".into(), "cpu-12".into())));
+        assert!(!regex.matches(&SourceName::new("perf".into(), "cpu-a".into())));
+        assert!(!regex.matches(&SourceName::new("procfs".into(), "cpu-1".into())));
+
+        assert_eq!(SourceNamePattern::from_str("a/"), Err(NamePatternParseError::Empty));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_source_pattern_with_slash() -> anyhow::Result<()> {
+        // the element pattern can contain slashes
+        let cgroup = SourceNamePattern::from_str("procfs/~/sys/fs/cgroup/.+")?;
+        assert!(cgroup.matches(&SourceName::new("procfs".into(), "/sys/fs/cgroup/user.slice".into())));
+        assert!(!cgroup.matches(&SourceName::new("procfs".into(), "cgroup".into())));
+
+        // in the plugin pattern, the slash must be escaped
+        let escaped = SourceNamePattern::from_str(r"~a\/b|c/src")?;
+        assert!(escaped.matches(&SourceName::new("a/b".into(), "src".into())));
+        assert!(escaped.matches(&SourceName::new("c".into(), "src".into())));
+        assert!(!escaped.matches(&SourceName::new("a".into(), "b|c/src".into())));
+        assert_eq!(
+            SourceNamePattern::from_str(r"~a\/b")?,
+            SourceNamePattern::new(StringPattern::regex(r"a\/b")?, StringPattern::Any)
+        );
         Ok(())
     }
 }
@@ -0,0 +1,373 @@
+//! Per-metric sampling (decimation) of the measurements.
+//!
+//! Some sources can produce a lot of measurement points, more than what the operator needs.
+//! Sampling rules allow to reduce the number of points of specific metrics, without modifying
+//! the plugins that produce them.
+//!
+//! The rules are applied by the pipeline right after the sources, before any transform or output.
+//! Each rule applies to one metric (identified by its name) and is evaluated separately for
+//! each _series_ of that metric, i.e. for each combination of (metric, resource, consumer).
+//!
+//! # Example
+//! ```
+//! use std::num::NonZeroU64;
+//! use alumet::pipeline;
+//! use alumet::pipeline::sampling::SamplingRule;
+//!
+//! let mut pipeline = pipeline::Builder::new();
+//! let rules = pipeline.sampling_rules_mut();
+//!
+//! // Keep only 1 point out of 10 for this metric.
+//! rules.set("rapl_consumed_energy", SamplingRule::KeepOneIn(NonZeroU64::new(10).unwrap()));
+//!
+//! // Keep at most 2 points per second, per series.
+//! rules.set("cpu_time_delta", SamplingRule::MaxRate { points_per_second: 2.0 });
+//! ```
+
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::time::Duration;
+
+use rustc_hash::FxHashMap;
+
+use crate::measurement::{MeasurementBuffer, Timestamp};
+use crate::metrics::def::RawMetricId;
+use crate::metrics::registry::MetricRegistry;
+use crate::resources::{Resource, ResourceConsumer};
+
+/// Controls how many points of a metric are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SamplingRule {
+    /// Keeps one point out of `n`, for each series.
+    ///
+    /// The first point of each series is always kept.
+    KeepOneIn(NonZeroU64),
+    /// Keeps at most `points_per_second` points per second, for each series.
+    ///
+    /// The rate is computed from the timestamps of the measurement points, not from the time at which they
+    /// are received by the pipeline.
+    MaxRate { points_per_second: f64 },
+}
+
+/// A set of sampling rules, indexed by metric name.
+#[derive(Debug, Clone, Default)]
+pub struct SamplingRules {
+    by_metric_name: HashMap<String, SamplingRule>,
+}
+
+/// Applies sampling rules to measurement buffers.
+///
+/// A `Decimator` is stateful: it remembers, for each series, when the last point has been kept.
+/// The state of a series that does not receive any point during [`SERIES_TTL`] buffers is eventually
+/// forgotten, as if the series had never been seen.
+pub(crate) struct Decimator {
+    rules: SamplingRules,
+    /// Rules that apply to each metric id, resolved from the metric names on first use.
+    resolved: FxHashMap<RawMetricId, Option<ResolvedRule>>,
+    /// State of each series.
+    series: FxHashMap<(RawMetricId, Resource, ResourceConsumer), Series>,
+    /// Number of buffers processed so far.
+    round: u64,
+}
+
+/// Number of buffers after which the state of a series that has not received any point can be removed.
+///
+/// The old series are removed every `SERIES_TTL` buffers, which bounds the cost of the cleanup.
+/// Without this, the state would grow forever when the series change over time, for instance
+/// when the consumers are processes that come and go.
+const SERIES_TTL: u64 = 64;
+
+#[derive(Debug, Clone, Copy)]
+enum ResolvedRule {
+    KeepOneIn(u64),
+    MinInterval(Duration),
+}
+
+struct Series {
+    state: SeriesState,
+    /// The round in which the series has received its last point.
+    last_seen: u64,
+}
+
+enum SeriesState {
+    /// Number of points seen since the last kept point.
+    Counter(u64),
+    /// Timestamp of the last kept point.
+    LastKept(Timestamp),
+}
+
+impl SamplingRule {
+    fn resolve(&self) -> ResolvedRule {
+        match self {
+            SamplingRule::KeepOneIn(n) => ResolvedRule::KeepOneIn(n.get()),
+            SamplingRule::MaxRate { points_per_second } => {
+                let interval = Duration::try_from_secs_f64(1.0 / points_per_second).unwrap_or(Duration::MAX);
+                ResolvedRule::MinInterval(interval)
+            }
+        }
+    }
+}
+
+impl SamplingRules {
+    /// Creates an empty set of rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the sampling rule of a metric, replacing the previous one (if any).
+    ///
+    /// # Panics
+    /// Panics if the rule is [`SamplingRule::MaxRate`] with a rate that is not strictly positive.
+    pub fn set(&mut self, metric_name: impl Into<String>, rule: SamplingRule) {
+        if let SamplingRule::MaxRate { points_per_second } = rule {
+            assert!(
+                points_per_second > 0.0,
+                "invalid sampling rule: points_per_second must be strictly positive, got {points_per_second}"
+            );
+        }
+        self.by_metric_name.insert(metric_name.into(), rule);
+    }
+
+    /// Returns the sampling rule of a metric, if any.
+    pub fn get(&self, metric_name: &str) -> Option<&SamplingRule> {
+        self.by_metric_name.get(metric_name)
+    }
+
+    /// Returns `true` if there is no rule.
+    pub fn is_empty(&self) -> bool {
+        self.by_metric_name.is_empty()
+    }
+
+    /// The number of rules.
+    pub fn len(&self) -> usize {
+        self.by_metric_name.len()
+    }
+}
+
+impl Decimator {
+    pub fn new(rules: SamplingRules) -> Self {
+        Self {
+            rules,
+            resolved: FxHashMap::default(),
+            series: FxHashMap::default(),
+            round: 0,
+        }
+    }
+
+    /// Removes the points that must be dropped according to the sampling rules.
+    pub fn apply(&mut self, buffer: &mut MeasurementBuffer, metrics: &MetricRegistry) {
+        self.round += 1;
+        let round = self.round;
+        let rules = &self.rules;
+        let resolved = &mut self.resolved;
+        let series = &mut self.series;
+        buffer.retain(|p| {
+            // Find the rule of the metric. Metrics can be registered at any time,
+            // so we resolve the names lazily, when we see a metric for the first time.
+            let rule = *resolved.entry(p.metric).or_insert_with(|| {
+                metrics
+                    .by_id(&p.metric)
+                    .and_then(|m| rules.get(&m.name))
+                    .map(SamplingRule::resolve)
+            });
+            let Some(rule) = rule else {
+                return true;
+            };
+
+            // Apply the rule to the series.
+            let key = (p.metric, p.resource.clone(), p.consumer.clone());
+            let state = series.get_mut(&key).map(|s| {
+                s.last_seen = round;
+                &mut s.state
+            });
+            match (rule, state) {
+                (ResolvedRule::KeepOneIn(n), Some(SeriesState::Counter(count))) => {
+                    *count += 1;
+                    if *count >= n {
+                        *count = 0;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                (ResolvedRule::MinInterval(interval), Some(SeriesState::LastKept(last))) => {
+                    let keep = match p.timestamp.duration_since(*last) {
+                        Ok(elapsed) => elapsed >= interval,
+                        Err(_) => false, // the point is older than the last kept point
+                    };
+                    if keep {
+                        *last = p.timestamp;
+                    }
+                    keep
+                }
+                (rule, _) => {
+                    // first point of the series: always keep it
+                    let state = match rule {
+                        ResolvedRule::KeepOneIn(_) => SeriesState::Counter(0),
+                        ResolvedRule::MinInterval(_) => SeriesState::LastKept(p.timestamp),
+                    };
+                    series.insert(
+                        key,
+                        Series {
+                            state,
+                            last_seen: round,
+                        },
+                    );
+                    true
+                }
+            }
+        });
+
+        // Forget the series that have disappeared.
+        if round.is_multiple_of(SERIES_TTL) {
+            series.retain(|_, s| round - s.last_seen < SERIES_TTL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+
+    use crate::measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType};
+    use crate::metrics::def::{Metric, RawMetricId};
+    use crate::metrics::duplicate::{DuplicateCriteria, DuplicateReaction};
+    use crate::metrics::registry::MetricRegistry;
+    use crate::resources::{Resource, ResourceConsumer};
+    use crate::units::Unit;
+
+    use super::{Decimator, SERIES_TTL, SamplingRule, SamplingRules};
+
+    fn registry() -> (MetricRegistry, RawMetricId, RawMetricId) {
+        let mut metrics = MetricRegistry::new();
+        let mut register = |name: &str| {
+            let m = Metric {
+                name: name.to_owned(),
+                description: String::new(),
+                value_type: WrappedMeasurementType::U64,
+                unit: Unit::Unity.into(),
+                tags: Vec::new(),
+            };
+            metrics
+                .register(m, DuplicateCriteria::Strict, DuplicateReaction::Error)
+                .unwrap()
+        };
+        let chatty = register("chatty");
+        let normal = register("normal");
+        (metrics, chatty, normal)
+    }
+
+    fn point(metric: RawMetricId, t: Timestamp, cpu: u32) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            t,
+            metric,
+            Resource::CpuCore { id: cpu },
+            ResourceConsumer::LocalMachine,
+            crate::measurement::WrappedMeasurementValue::U64(0),
+        )
+    }
+
+    #[test]
+    fn keep_one_in() {
+        let (metrics, chatty, normal) = registry();
+        let mut rules = SamplingRules::new();
+        rules.set("chatty", SamplingRule::KeepOneIn(NonZeroU64::new(3).unwrap()));
+        let mut decimator = Decimator::new(rules);
+
+        let t = Timestamp::now();
+        let mut buf = MeasurementBuffer::new();
+        for _ in 0..7 {
+            buf.push(point(chatty, t, 0));
+            buf.push(point(chatty, t, 1));
+            buf.push(point(normal, t, 0));
+        }
+        decimator.apply(&mut buf, &metrics);
+
+        // 7 points per series, keep the 1st, 4th and 7th
+        let count = |metric, cpu| {
+            buf.iter()
+                .filter(|p| p.metric == metric && p.resource == Resource::CpuCore { id: cpu })
+                .count()
+        };
+        assert_eq!(count(chatty, 0), 3);
+        assert_eq!(count(chatty, 1), 3);
+        assert_eq!(count(normal, 0), 7);
+
+        // the state is kept between two buffers
+        let mut buf = MeasurementBuffer::new();
+        buf.push(point(chatty, t, 0));
+        buf.push(point(chatty, t, 0));
+        buf.push(point(chatty, t, 0));
+        decimator.apply(&mut buf, &metrics);
+        assert_eq!(buf.len(), 1);
+    }
+
+    #[test]
+    fn max_rate() {
+        let (metrics, chatty, normal) = registry();
+        let mut rules = SamplingRules::new();
+        rules.set(
+            "chatty",
+            SamplingRule::MaxRate {
+                points_per_second: 10.0,
+            },
+        );
+        let mut decimator = Decimator::new(rules);
+
+        // one point every 25ms during 1s, but at most 10 points/s
+        let t0 = Timestamp::now();
+        let mut buf = MeasurementBuffer::new();
+        for i in 0..40 {
+            let t = t0 + Duration::from_millis(25 * i);
+            buf.push(point(chatty, t, 0));
+            buf.push(point(normal, t, 0));
+        }
+        decimator.apply(&mut buf, &metrics);
+        assert_eq!(buf.iter().filter(|p| p.metric == chatty).count(), 10);
+        assert_eq!(buf.iter().filter(|p| p.metric == normal).count(), 40);
+    }
+
+    #[test]
+    fn forget_old_series() {
+        let (metrics, chatty, _) = registry();
+        let mut rules = SamplingRules::new();
+        rules.set("chatty", SamplingRule::KeepOneIn(NonZeroU64::new(3).unwrap()));
+        let mut decimator = Decimator::new(rules);
+
+        // many series at first
+        let t = Timestamp::now();
+        let mut buf = MeasurementBuffer::new();
+        for cpu in 0..100 {
+            buf.push(point(chatty, t, cpu));
+        }
+        decimator.apply(&mut buf, &metrics);
+        assert_eq!(decimator.series.len(), 100);
+
+        // then only one series
+        for _ in 1..2 * SERIES_TTL {
+            let mut buf = MeasurementBuffer::new();
+            buf.push(point(chatty, t, 0));
+            decimator.apply(&mut buf, &metrics);
+        }
+        assert_eq!(
+            decimator.series.len(),
+            1,
+            "the state of the old series should be removed"
+        );
+
+        // an old series that comes back starts from scratch: its first point is kept
+        let mut buf = MeasurementBuffer::new();
+        buf.push(point(chatty, t, 1));
+        decimator.apply(&mut buf, &metrics);
+        assert_eq!(buf.len(), 1);
+        assert_eq!(decimator.series.len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_rate() {
+        let mut rules = SamplingRules::new();
+        rules.set("metric", SamplingRule::MaxRate { points_per_second: 0.0 });
+    }
+}
@@ -0,0 +1,74 @@
+//! Asynchronous hooks that run when the pipeline shuts down.
+//!
+//! [`Plugin::stop`](crate::plugin::Plugin::stop) is synchronous and is called after the
+//! async runtime of the pipeline has been torn down. Plugins that need to await some final
+//! operations (e.g. flushing a connection) can register a shutdown hook instead, with
+//! [`AlumetPluginStart::on_shutdown`](crate::plugin::AlumetPluginStart::on_shutdown).
+//!
+//! The hooks run concurrently, after all the sources, transforms and outputs have stopped,
+//! and before the runtime is torn down. Each hook has a deadline: if it does not complete in time,
+//! it is cancelled.
+
+use std::{future::Future, pin::Pin, time::Duration};
+
+use anyhow::anyhow;
+use tokio::{runtime, time::Instant};
+
+use super::{error::PipelineError, naming::PluginName};
+
+type BoxedHook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> + Send>;
+
+/// An asynchronous function to run when the pipeline shuts down.
+pub struct ShutdownHook {
+    plugin: PluginName,
+    deadline: Duration,
+    hook: BoxedHook,
+}
+
+impl ShutdownHook {
+    /// Creates a new hook for the given plugin.
+    ///
+    /// `hook` must complete before the `deadline`, otherwise it is cancelled.
+    pub fn new<F, Fut>(plugin: PluginName, deadline: Duration, hook: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        Self {
+            plugin,
+            deadline,
+            hook: Box::new(move || Box::pin(hook())),
+        }
+    }
+}
+
+/// Runs the hooks concurrently on the given runtime and waits for them to complete (or to be cancelled).
+///
+/// Returns the last error, if any.
+pub(crate) async fn run_hooks(hooks: Vec<ShutdownHook>, rt: &runtime::Handle) -> Result<(), PipelineError> {
+    let start = Instant::now();
+    let tasks: Vec<_> = hooks
+        .into_iter()
+        .map(|h| {
+            log::debug!("Running shutdown hook of plugin {}...", h.plugin.0);
+            let task = rt.spawn((h.hook)());
+            (h.plugin, h.deadline, task)
+        })
+        .collect();
+
+    let mut result = Ok(());
+    for (plugin, deadline, mut task) in tasks {
+        let error = match tokio::time::timeout_at(start + deadline, &mut task).await {
+            Ok(Ok(Ok(()))) => continue,
+            Ok(Ok(Err(e))) => e,
+            Ok(Err(e_panic)) => anyhow!("shutdown hook panicked: {e_panic}"),
+            Err(_) => {
+                task.abort();
+                anyhow!("shutdown hook did not complete within {deadline:?}, it has been cancelled")
+            }
+        };
+        log::error!("Error in shutdown hook of plugin {}: {error:?}", plugin.0);
+        result = Err(PipelineError::for_plugin(plugin, error));
+    }
+    result
+}
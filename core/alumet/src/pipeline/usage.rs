@@ -0,0 +1,508 @@
+//! Accounting of the resources used by the pipeline elements.
+//!
+//! When it is enabled with [`Builder::resource_usage_mut`](super::Builder::resource_usage_mut),
+//! the pipeline measures, for each source, transform and output:
+//! - the number of calls to the element (`poll`, `apply`, `write`, or `Future::poll` for async elements);
+//! - the CPU time spent in these calls (on Unix);
+//! - the number of bytes read and written by the thread during these calls, including
+//!   the network and the pipes (on Linux);
+//! - the number of bytes allocated during these calls, if [`TrackingAllocator`] is the global allocator.
+//!
+//! This allows operators to check that the overhead of the measurement agent stays within budget,
+//! and to find which plugin is responsible for it.
+//! The usage can be obtained with [`MeasurementPipeline::resource_usage`](super::MeasurementPipeline::resource_usage),
+//! with the [`request::resource_usage`](super::control::request::resource_usage) control request,
+//! or as measurements, if the self-metrics are enabled (see [`ResourceUsageSettings`]).
+//!
+//! Accounting is disabled by default, because it adds a few system calls around each call to an element.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use super::{
+    Source,
+    elements::{
+        error::PollError,
+        source::{
+            builder::{ManagedSource, SourceBuilder},
+            control::TaskState,
+            trigger::TriggerSpec,
+        },
+    },
+    matching::ElementNamePattern,
+    naming::{ElementName, namespace::Namespace2},
+};
+use crate::{
+    measurement::{
+        MeasurementAccumulator, MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue,
+    },
+    metrics::{
+        Metric, RawMetricId,
+        duplicate::{DuplicateCriteria, DuplicateReaction},
+        registry::MetricRegistry,
+    },
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+
+/// Resource usage settings of the pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceUsageSettings {
+    /// Enables the resource usage accounting.
+    pub enabled: bool,
+    /// If set (and if the accounting is enabled), adds a source that measures the usage
+    /// of each element at this interval, with the `alumet_element_*` metrics.
+    pub self_metrics_interval: Option<Duration>,
+}
+
+/// Resources used by a pipeline element (or by a group of elements) since the pipeline has started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Number of calls to the element.
+    pub calls: u64,
+    /// CPU time (user and system) spent in the element.
+    pub cpu_time: Duration,
+    /// Number of bytes allocated by the element.
+    pub allocated_bytes: u64,
+    /// Number of bytes read by the element.
+    pub read_bytes: u64,
+    /// Number of bytes written by the element.
+    pub written_bytes: u64,
+}
+
+impl std::ops::AddAssign for ResourceUsage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.calls += rhs.calls;
+        self.cpu_time += rhs.cpu_time;
+        self.allocated_bytes += rhs.allocated_bytes;
+        self.read_bytes += rhs.read_bytes;
+        self.written_bytes += rhs.written_bytes;
+    }
+}
+
+/// Keeps track of the resources used by the pipeline elements.
+///
+/// All the clones share the same data, which is updated by the pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct UsageTracker(Option<Arc<Mutex<TrackedElements>>>);
+
+type TrackedElements = Vec<(ElementName, Arc<UsageCounter>)>;
+
+impl UsageTracker {
+    pub(crate) fn new(enabled: bool) -> Self {
+        if enabled {
+            Self(Some(Arc::default()))
+        } else {
+            Self(None)
+        }
+    }
+
+    /// Returns true if the resource usage accounting is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Starts to track the usage of a new element.
+    ///
+    /// Returns `None` if the accounting is disabled.
+    pub(crate) fn register(&self, name: impl Into<ElementName>) -> Option<Arc<UsageCounter>> {
+        let elements = self.0.as_ref()?;
+        let counter = Arc::new(UsageCounter::default());
+        elements.lock().unwrap().push((name.into(), counter.clone()));
+        Some(counter)
+    }
+
+    /// Returns the usage of each element that matches the pattern.
+    pub fn elements(&self, pat: &ElementNamePattern) -> Vec<(ElementName, ResourceUsage)> {
+        match &self.0 {
+            Some(elements) => elements
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(name, _)| pat.matches(name))
+                .map(|(name, counter)| (name.to_owned(), counter.load()))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the total usage of the elements of each plugin, by plugin name.
+    pub fn by_plugin(&self) -> BTreeMap<String, ResourceUsage> {
+        let mut res: BTreeMap<String, ResourceUsage> = BTreeMap::new();
+        for (name, usage) in self.elements(&ElementNamePattern::wildcard()) {
+            *res.entry(name.plugin).or_default() += usage;
+        }
+        res
+    }
+}
+
+/// Usage counters of a single element.
+#[derive(Debug, Default)]
+pub(crate) struct UsageCounter {
+    calls: AtomicU64,
+    cpu_nanos: AtomicU64,
+    allocated_bytes: AtomicU64,
+    read_bytes: AtomicU64,
+    written_bytes: AtomicU64,
+}
+
+impl UsageCounter {
+    fn load(&self) -> ResourceUsage {
+        ResourceUsage {
+            calls: self.calls.load(Ordering::Relaxed),
+            cpu_time: Duration::from_nanos(self.cpu_nanos.load(Ordering::Relaxed)),
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+            read_bytes: self.read_bytes.load(Ordering::Relaxed),
+            written_bytes: self.written_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(&self, before: &ThreadSnapshot, after: &ThreadSnapshot) {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.cpu_nanos
+            .fetch_add(after.cpu_nanos.saturating_sub(before.cpu_nanos), Ordering::Relaxed);
+        self.allocated_bytes.fetch_add(
+            after.allocated_bytes.saturating_sub(before.allocated_bytes),
+            Ordering::Relaxed,
+        );
+        if let (Some(io_before), Some(io_after)) = (&before.io, &after.io) {
+            // The bytes read to obtain the first snapshot are included in the second one.
+            let read = io_after.read.saturating_sub(io_before.read + io_before.snapshot_len);
+            self.read_bytes.fetch_add(read, Ordering::Relaxed);
+            self.written_bytes
+                .fetch_add(io_after.written.saturating_sub(io_before.written), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Runs `f` and adds the resources that it uses to the counter (if there is one).
+///
+/// `f` must not move to another thread, which is the case of any synchronous function.
+pub(crate) fn measure<R>(counter: Option<&UsageCounter>, f: impl FnOnce() -> R) -> R {
+    match counter {
+        Some(counter) => {
+            let before = ThreadSnapshot::now();
+            let res = f();
+            let after = ThreadSnapshot::now();
+            counter.add(&before, &after);
+            res
+        }
+        None => f(),
+    }
+}
+
+/// A future that measures the resources used by each call to the inner future's `poll`.
+pub(crate) struct Measured<F> {
+    inner: F,
+    counter: Option<Arc<UsageCounter>>,
+}
+
+impl<F> Measured<F> {
+    pub(crate) fn new(inner: F, counter: Option<Arc<UsageCounter>>) -> Self {
+        Self { inner, counter }
+    }
+}
+
+impl<F: Future + Unpin> Future for Measured<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        measure(this.counter.as_deref(), || Pin::new(&mut this.inner).poll(cx))
+    }
+}
+
+/// Resource usage of the current thread, at a given time.
+struct ThreadSnapshot {
+    cpu_nanos: u64,
+    allocated_bytes: u64,
+    io: Option<IoSnapshot>,
+}
+
+struct IoSnapshot {
+    read: u64,
+    written: u64,
+    /// Number of bytes read to obtain this snapshot.
+    snapshot_len: u64,
+}
+
+impl ThreadSnapshot {
+    fn now() -> Self {
+        // The I/O counters are read first, so that the allocations made to read them
+        // (the first time, before the buffer is reused) are not counted.
+        let io = thread_io();
+        Self {
+            allocated_bytes: THREAD_ALLOCATED_BYTES.with(|c| c.get()),
+            cpu_nanos: thread_cpu_nanos(),
+            io,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn thread_cpu_nanos() -> u64 {
+    let mut t = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: clock_gettime only writes to the timespec, which is valid
+    let res = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut t) };
+    if res == 0 {
+        t.tv_sec as u64 * 1_000_000_000 + t.tv_nsec as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(not(unix))]
+fn thread_cpu_nanos() -> u64 {
+    0
+}
+
+#[cfg(target_os = "linux")]
+thread_local! {
+    /// Buffer for the content of `/proc/thread-self/io`, reused to avoid an allocation per snapshot.
+    static IO_BUFFER: std::cell::RefCell<Vec<u8>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+#[cfg(target_os = "linux")]
+fn thread_io() -> Option<IoSnapshot> {
+    use std::io::Read;
+
+    IO_BUFFER.with_borrow_mut(|buf| {
+        buf.clear();
+        std::fs::File::open("/proc/thread-self/io")
+            .ok()?
+            .read_to_end(buf)
+            .ok()?;
+        parse_io(std::str::from_utf8(buf).ok()?)
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn parse_io(content: &str) -> Option<IoSnapshot> {
+    let mut read = None;
+    let mut written = None;
+    for line in content.lines() {
+        if let Some(value) = line.strip_prefix("rchar:") {
+            read = value.trim().parse().ok();
+        } else if let Some(value) = line.strip_prefix("wchar:") {
+            written = value.trim().parse().ok();
+        }
+    }
+    Some(IoSnapshot {
+        read: read?,
+        written: written?,
+        snapshot_len: content.len() as u64,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_io() -> Option<IoSnapshot> {
+    None
+}
+
+thread_local! {
+    static THREAD_ALLOCATED_BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A global allocator that counts the bytes allocated by each thread, so that
+/// the allocations can be attributed to the pipeline elements.
+///
+/// It delegates to the [`System`] allocator. Install it in the agent binary:
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: alumet::pipeline::usage::TrackingAllocator = alumet::pipeline::usage::TrackingAllocator;
+/// ```
+/// Without it, [`ResourceUsage::allocated_bytes`] is always zero.
+pub struct TrackingAllocator;
+
+impl TrackingAllocator {
+    fn count(bytes: usize) {
+        // try_with: the thread-local may already have been destroyed
+        let _ = THREAD_ALLOCATED_BYTES.try_with(|c| c.set(c.get().wrapping_add(bytes as u64)));
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        Self::count(layout.size());
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // a reallocation is counted as a new allocation of the additional bytes
+        Self::count(new_size.saturating_sub(layout.size()));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// Name of the source that measures the resource usage, which is registered by the plugin `alumet`.
+pub const SELF_METRICS_SOURCE: &str = "resource_usage";
+
+/// Registers the `alumet_element_*` metrics and adds a source that measures the usage of each element.
+pub(crate) fn add_self_metrics_source(
+    metrics: &mut MetricRegistry,
+    sources: &mut Namespace2<SourceBuilder>,
+    tracker: UsageTracker,
+    poll_interval: Duration,
+) -> anyhow::Result<()> {
+    let mut create = |name: &str, unit: PrefixedUnit, description: &str| -> anyhow::Result<RawMetricId> {
+        let metric = Metric {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            value_type: WrappedMeasurementType::U64,
+            unit,
+            tags: Vec::new(),
+        };
+        let id = metrics.register(metric, DuplicateCriteria::Incompatible, DuplicateReaction::Error)?;
+        Ok(id)
+    };
+    let source = UsageSource {
+        tracker,
+        calls: create(
+            "alumet_element_calls",
+            Unit::Unity.into(),
+            "number of calls to a pipeline element since the start of the agent",
+        )?,
+        cpu_time: create(
+            "alumet_element_cpu_time",
+            PrefixedUnit::nano(Unit::Second),
+            "CPU time spent in a pipeline element since the start of the agent",
+        )?,
+        allocated_bytes: create(
+            "alumet_element_allocated_bytes",
+            Unit::Byte.into(),
+            "number of bytes allocated by a pipeline element since the start of the agent",
+        )?,
+        read_bytes: create(
+            "alumet_element_read_bytes",
+            Unit::Byte.into(),
+            "number of bytes read by a pipeline element since the start of the agent",
+        )?,
+        written_bytes: create(
+            "alumet_element_written_bytes",
+            Unit::Byte.into(),
+            "number of bytes written by a pipeline element since the start of the agent",
+        )?,
+    };
+    let builder = SourceBuilder::Managed(Box::new(move |_| {
+        Ok(ManagedSource {
+            trigger_spec: TriggerSpec::at_interval(poll_interval),
+            source: Box::new(source),
+            initial_state: TaskState::Run,
+        })
+    }));
+    sources.add(String::from("alumet"), String::from(SELF_METRICS_SOURCE), builder)?;
+    Ok(())
+}
+
+/// Measures the resource usage of each pipeline element.
+struct UsageSource {
+    tracker: UsageTracker,
+    calls: RawMetricId,
+    cpu_time: RawMetricId,
+    allocated_bytes: RawMetricId,
+    read_bytes: RawMetricId,
+    written_bytes: RawMetricId,
+}
+
+impl Source for UsageSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        for (name, usage) in self.tracker.elements(&ElementNamePattern::wildcard()) {
+            let consumer = ResourceConsumer::custom("alumet_plugin", name.plugin);
+            let values = [
+                (self.calls, usage.calls),
+                (self.cpu_time, usage.cpu_time.as_nanos() as u64),
+                (self.allocated_bytes, usage.allocated_bytes),
+                (self.read_bytes, usage.read_bytes),
+                (self.written_bytes, usage.written_bytes),
+            ];
+            for (metric, value) in values {
+                let point = MeasurementPoint::new_untyped(
+                    timestamp,
+                    metric,
+                    Resource::LocalMachine,
+                    consumer.clone(),
+                    WrappedMeasurementValue::U64(value),
+                )
+                .with_attr("element_kind", name.kind.to_string())
+                .with_attr("element", name.element.clone());
+                measurements.push(point);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, time::Duration};
+
+    use super::{UsageTracker, measure};
+    use crate::pipeline::{
+        matching::ElementNamePattern,
+        naming::{SourceName, TransformName},
+    };
+
+    #[test]
+    fn disabled() {
+        let tracker = UsageTracker::new(false);
+        assert!(tracker.register(SourceName::new("a".into(), "s".into())).is_none());
+        assert!(tracker.elements(&ElementNamePattern::wildcard()).is_empty());
+    }
+
+    #[test]
+    fn measure_calls() {
+        let tracker = UsageTracker::new(true);
+        let source = tracker.register(SourceName::new("a".into(), "s".into())).unwrap();
+        let transform = tracker.register(TransformName::new("a".into(), "t".into())).unwrap();
+
+        let res = measure(Some(&source), || {
+            // burn some CPU time
+            let t0 = std::time::Instant::now();
+            while t0.elapsed() < Duration::from_millis(20) {
+                std::hint::black_box(0);
+            }
+            42
+        });
+        assert_eq!(res, 42);
+        measure(Some(&transform), || {
+            let mut file = tempfile::tempfile().unwrap();
+            file.write_all(&[0; 1000]).unwrap();
+        });
+
+        let elements = tracker.elements(&ElementNamePattern::wildcard());
+        let source_usage = elements[0].1;
+        assert_eq!(source_usage.calls, 1);
+        #[cfg(unix)]
+        assert!(source_usage.cpu_time >= Duration::from_millis(10), "{source_usage:?}");
+        let transform_usage = elements[1].1;
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(transform_usage.written_bytes, 1000);
+            assert_eq!(transform_usage.read_bytes, 0);
+        }
+
+        let by_plugin = tracker.by_plugin();
+        assert_eq!(by_plugin.len(), 1);
+        assert_eq!(by_plugin["a"].calls, 2);
+    }
+}
@@ -0,0 +1,247 @@
+//! Capabilities required by plugins, and policies that restrict them.
+//!
+//! A plugin can declare the capabilities that it requires to work, such as a network access
+//! or an access to some files (see [`AlumetPlugin::capabilities`](super::rust::AlumetPlugin::capabilities)).
+//! The agent logs these capabilities at startup and, if a [`CapabilityPolicy`] is set,
+//! refuses to run the plugins that require more than what the policy allows.
+//! The capabilities are obtained with the configuration of the plugin, because some of them,
+//! such as the path of an output file, depend on it
+//! (see [`AlumetPlugin::capabilities_with_config`](super::rust::AlumetPlugin::capabilities_with_config)).
+//!
+//! A plugin that does not declare its capabilities, such as a dynamic plugin, could require anything:
+//! it is refused by every policy that restricts something.
+//!
+//! Declarations are informative: Alumet does not sandbox the plugins at the OS level.
+//! They allow the operator of a shared machine to review and restrict what the agent does.
+//!
+//! # Example
+//! ```
+//! use std::path::PathBuf;
+//! use alumet::plugin::capability::{Capability, CapabilityPolicy};
+//!
+//! let policy = CapabilityPolicy {
+//!     network: false,
+//!     allowed_paths: Some(vec![PathBuf::from("/sys")]),
+//!     ..CapabilityPolicy::allow_all()
+//! };
+//!
+//! let required = vec![
+//!     Capability::Filesystem(PathBuf::from("/sys/devices/virtual/powercap")),
+//!     Capability::Network,
+//! ];
+//! assert_eq!(policy.violations(&required), vec![&Capability::Network]);
+//! ```
+
+use std::fmt::Display;
+use std::path::{Component, Path, PathBuf};
+
+/// Something that a plugin needs in order to work.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Access to the network (as a client or as a server).
+    Network,
+    /// Access to a path of the filesystem (and its content, if it is a directory).
+    Filesystem(PathBuf),
+    /// Access to the model-specific registers of the CPU (usually through `/dev/cpu/*/msr`).
+    Msr,
+    /// Use of the `perf_event_open` system call.
+    PerfEvents,
+    /// Execution of external programs.
+    ProcessSpawn,
+    /// Access to a D-Bus message bus (the system bus, the session bus or a bus at a given address).
+    Dbus,
+}
+
+/// Restricts the capabilities that plugins are allowed to require.
+///
+/// The default policy allows everything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapabilityPolicy {
+    /// Allows [`Capability::Network`].
+    pub network: bool,
+    /// Allows [`Capability::Msr`].
+    pub msr: bool,
+    /// Allows [`Capability::PerfEvents`].
+    pub perf_events: bool,
+    /// Allows [`Capability::ProcessSpawn`].
+    pub process_spawn: bool,
+    /// Allows [`Capability::Dbus`].
+    pub dbus: bool,
+    /// Paths that can be accessed: a [`Capability::Filesystem`] is allowed if its path is in one
+    /// of these directories. `None` allows every path.
+    ///
+    /// The paths are compared after having been made absolute (relatively to the current directory)
+    /// and normalized, so that `..` and symbolic links cannot escape an allowed directory.
+    pub allowed_paths: Option<Vec<PathBuf>>,
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::Network => write!(f, "network"),
+            Capability::Filesystem(path) => write!(f, "filesystem:{}", path.display()),
+            Capability::Msr => write!(f, "msr"),
+            Capability::PerfEvents => write!(f, "perf_events"),
+            Capability::ProcessSpawn => write!(f, "process_spawn"),
+            Capability::Dbus => write!(f, "dbus"),
+        }
+    }
+}
+
+impl Default for CapabilityPolicy {
+    fn default() -> Self {
+        Self::allow_all()
+    }
+}
+
+impl CapabilityPolicy {
+    /// A policy that allows every capability.
+    pub fn allow_all() -> Self {
+        Self {
+            network: true,
+            msr: true,
+            perf_events: true,
+            process_spawn: true,
+            dbus: true,
+            allowed_paths: None,
+        }
+    }
+
+    /// A policy that denies every capability.
+    pub fn deny_all() -> Self {
+        Self {
+            network: false,
+            msr: false,
+            perf_events: false,
+            process_spawn: false,
+            dbus: false,
+            allowed_paths: Some(Vec::new()),
+        }
+    }
+
+    /// Returns `true` if the policy allows the given capability.
+    pub fn allows(&self, capability: &Capability) -> bool {
+        match capability {
+            Capability::Network => self.network,
+            Capability::Msr => self.msr,
+            Capability::PerfEvents => self.perf_events,
+            Capability::ProcessSpawn => self.process_spawn,
+            Capability::Dbus => self.dbus,
+            Capability::Filesystem(path) => match &self.allowed_paths {
+                None => true,
+                Some(allowed) => {
+                    let path = normalize(path);
+                    allowed.iter().any(|dir| path.starts_with(normalize(dir)))
+                }
+            },
+        }
+    }
+
+    /// Returns `true` if the policy allows every capability.
+    ///
+    /// Only such a policy allows the plugins that do not declare their capabilities.
+    pub fn is_unrestricted(&self) -> bool {
+        *self == Self::allow_all()
+    }
+
+    /// Returns the capabilities that are not allowed by the policy.
+    pub fn violations<'a>(&self, required: &'a [Capability]) -> Vec<&'a Capability> {
+        required.iter().filter(|c| !self.allows(c)).collect()
+    }
+}
+
+/// Returns the absolute form of `path`, without `.`, `..` and symbolic links.
+///
+/// Unlike [`Path::canonicalize`], this works for paths that do not exist yet, such as output files:
+/// the longest existing ancestor is canonicalized, and the rest is normalized lexically.
+fn normalize(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut existing = absolute.as_path();
+    let mut normalized = loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            break canonical;
+        }
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => break PathBuf::new(),
+        }
+    };
+    let rest = if normalized.as_os_str().is_empty() {
+        absolute.as_path()
+    } else {
+        absolute.strip_prefix(existing).unwrap_or(Path::new(""))
+    };
+    for component in rest.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Capability, CapabilityPolicy};
+
+    #[test]
+    fn policy() {
+        let required = vec![
+            Capability::Network,
+            Capability::PerfEvents,
+            Capability::Filesystem(PathBuf::from("/sys/devices/virtual/powercap")),
+            Capability::Filesystem(PathBuf::from("/etc/shadow")),
+        ];
+        assert!(CapabilityPolicy::allow_all().violations(&required).is_empty());
+        assert_eq!(CapabilityPolicy::deny_all().violations(&required).len(), 4);
+
+        let policy = CapabilityPolicy {
+            perf_events: false,
+            allowed_paths: Some(vec![PathBuf::from("/sys"), PathBuf::from("/proc")]),
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.violations(&required),
+            vec![
+                &Capability::PerfEvents,
+                &Capability::Filesystem(PathBuf::from("/etc/shadow"))
+            ]
+        );
+        // only whole components are compared
+        assert!(!policy.allows(&Capability::Filesystem(PathBuf::from("/system"))));
+        // the paths are normalized before being compared
+        assert!(!policy.allows(&Capability::Filesystem(PathBuf::from("/sys/../etc/shadow"))));
+        assert!(!policy.allows(&Capability::Filesystem(PathBuf::from("/sys/not-a-dir/../../etc"))));
+        assert!(policy.allows(&Capability::Filesystem(PathBuf::from("/proc/./self/../stat"))));
+
+        assert!(CapabilityPolicy::allow_all().is_unrestricted());
+        assert!(!policy.is_unrestricted());
+        assert!(!CapabilityPolicy::deny_all().is_unrestricted());
+    }
+
+    #[test]
+    fn relative_paths() {
+        let policy = CapabilityPolicy {
+            allowed_paths: Some(vec![std::env::current_dir().unwrap()]),
+            ..Default::default()
+        };
+        assert!(policy.allows(&Capability::Filesystem(PathBuf::from("alumet-output.csv"))));
+        assert!(policy.allows(&Capability::Filesystem(PathBuf::from("./out/data.csv"))));
+        assert!(!policy.allows(&Capability::Filesystem(PathBuf::from("../alumet-output.csv"))));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Capability::Network.to_string(), "network");
+        assert_eq!(Capability::Dbus.to_string(), "dbus");
+        assert_eq!(
+            Capability::Filesystem(PathBuf::from("/proc")).to_string(),
+            "filesystem:/proc"
+        );
+    }
+}
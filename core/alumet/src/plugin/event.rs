@@ -39,38 +39,63 @@
 //!     Ok(())
 //! });
 //! ```
+//!
+//! # Late subscribers
+//!
+//! When a listener subscribes, it immediately receives the last event that has been published before,
+//! so that the order of the `post_pipeline_start` hooks does not matter.
+//! To only receive the events that are published after the subscription, use [`EventBus::subscribe_without_replay`].
 
 use std::{
+    collections::BTreeMap,
     ops::Deref,
     sync::{Mutex, OnceLock},
 };
 
-use crate::resources::{Resource, ResourceConsumer};
+use crate::{
+    measurement::{AttributeValue, Timestamp},
+    resources::{Resource, ResourceConsumer},
+};
 
 /// Trait for constraining event types.
 pub trait Event: Clone {}
 
 /// An event bus.
+///
+/// # Replay
+/// The bus remembers the last event that has been published with [`publish`](Self::publish).
+/// When a listener subscribes with [`subscribe`](Self::subscribe), it immediately
+/// receives that event. This ensures that no event is missed because of the order in which the plugins
+/// subscribe and publish (for instance, during `post_pipeline_start`).
 pub struct EventBus<E: Event> {
-    /// The listeners, in a Mutex.
+    /// The listeners and the last event, in a Mutex.
     ///
     /// We use a Mutex here, not a RwLock, because we don't want to impose a Sync
     /// bound on the listener functions.
-    listeners: Mutex<Vec<Box<dyn Fn(E) -> anyhow::Result<()> + Send>>>,
+    inner: Mutex<EventBusInner<E>>,
+}
+
+struct EventBusInner<E> {
+    listeners: Vec<Box<dyn Fn(E) -> anyhow::Result<()> + Send>>,
+    /// The last published event, for the replay.
+    last: Option<E>,
 }
 
 impl<E: Event> Default for EventBus<E> {
     fn default() -> Self {
         Self {
-            listeners: Mutex::new(Vec::with_capacity(4)),
+            inner: Mutex::new(EventBusInner {
+                listeners: Vec::with_capacity(4),
+                last: None,
+            }),
         }
     }
 }
 
 impl<E: Event> EventBus<E> {
-    /// Subscribe to the event bus.
+    /// Subscribe to the event bus, and receive the last event that has already been published (if any).
     ///
-    /// `listener` will be called on future events.
+    /// `listener` is immediately called with the last event, then on future events.
     ///
     /// # Performance caveats
     ///
@@ -79,46 +104,62 @@ impl<E: Event> EventBus<E> {
     /// To execute large tasks in response to an event, consider sending a message
     /// to another thread (or async future) through a [`channel`](tokio::sync::mpsc::channel).
     pub fn subscribe<F: Fn(E) -> anyhow::Result<()> + Send + 'static>(&self, listener: F) {
-        let mut listeners = self.listeners.lock().unwrap();
-        listeners.push(Box::new(listener));
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(last) = &inner.last {
+            call_listener(&listener, last.clone());
+        }
+        inner.listeners.push(Box::new(listener));
+    }
+
+    /// Subscribe to the event bus, without receiving the events that have already been published.
+    ///
+    /// `listener` will only be called on future events.
+    /// See [`subscribe`](Self::subscribe).
+    pub fn subscribe_without_replay<F: Fn(E) -> anyhow::Result<()> + Send + 'static>(&self, listener: F) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.listeners.push(Box::new(listener));
     }
 
     /// Publish an event to the bus.
     ///
     /// All the `listeners` will be called with the event.
+    /// The event is kept, to be replayed to future listeners.
     pub fn publish(&self, event: E) {
-        for listener in self.listeners.lock().unwrap().deref() {
-            if let Err(e) = listener(event.clone()) {
-                log::error!("Error in event handler: {e:?}")
-            }
+        let mut inner = self.inner.lock().unwrap();
+        for listener in &inner.listeners {
+            call_listener(listener.deref(), event.clone());
         }
+        inner.last = Some(event);
     }
 
     /// If someone is listening for an event, create the event with the provided closure
     /// and publish it to the bus.
     ///
     /// All the `listeners` will be called with the event.
+    ///
+    /// Unlike [`publish`](Self::publish), `publish_lazy` does not keep the event:
+    /// it will not be replayed to future listeners.
     pub fn publish_lazy(&self, create_event: impl FnOnce() -> E) {
-        let listeners = self.listeners.lock().unwrap();
-        match &listeners[..] {
+        let inner = self.inner.lock().unwrap();
+        match &inner.listeners[..] {
             [] => (),
-            [listener] => {
-                if let Err(e) = listener(create_event()) {
-                    log::error!("Error in event handler: {e:?}")
-                }
-            }
+            [listener] => call_listener(listener.deref(), create_event()),
             listeners => {
                 let event = create_event();
                 for listener in listeners {
-                    if let Err(e) = listener(event.clone()) {
-                        log::error!("Error in event handler: {e:?}")
-                    }
+                    call_listener(listener.deref(), event.clone());
                 }
             }
         }
     }
 }
 
+fn call_listener<E>(listener: &(dyn Fn(E) -> anyhow::Result<()> + Send), event: E) {
+    if let Err(e) = listener(event) {
+        log::error!("Error in event handler: {e:?}")
+    }
+}
+
 // ====== Global events and buses ======
 
 /// Contains all the global event buses.
@@ -127,6 +168,8 @@ struct EventBuses {
     start_consumer_measurement: EventBus<StartConsumerMeasurement>,
     start_resource_measurement: EventBus<StartResourceMeasurement>,
     end_consumer_measurement: EventBus<EndConsumerMeasurement>,
+    phase_change: EventBus<PhaseChange>,
+    job_change: EventBus<JobChange>,
 }
 
 /// Global variable, initialized only once, containing the event buses.
@@ -153,6 +196,16 @@ pub fn end_consumer_measurement() -> &'static EventBus<EndConsumerMeasurement> {
         .end_consumer_measurement
 }
 
+/// Returns the global event bus for the event [`PhaseChange`].
+pub fn phase_change() -> &'static EventBus<PhaseChange> {
+    &GLOBAL_EVENT_BUSES.get_or_init(EventBuses::default).phase_change
+}
+
+/// Returns the global event bus for the event [`JobChange`].
+pub fn job_change() -> &'static EventBus<JobChange> {
+    &GLOBAL_EVENT_BUSES.get_or_init(EventBuses::default).job_change
+}
+
 /// Event occurring when new [resource consumers](ResourceConsumer) are detected
 /// and should be measured.
 #[derive(Clone)]
@@ -164,21 +217,170 @@ pub struct StartConsumerMeasurement(pub Vec<ResourceConsumer>);
 pub struct StartResourceMeasurement(pub Vec<Resource>);
 
 /// Event occurring when measurements should be performed at the end of the consumer experiment.
-#[derive(Clone)]
-pub struct EndConsumerMeasurement;
+///
+/// # Example
+/// ```no_run
+/// use alumet::plugin::event::{self, EndConsumerMeasurement};
+/// use alumet::resources::ResourceConsumer;
+///
+/// let event = EndConsumerMeasurement::new(vec![ResourceConsumer::Process { pid: 1234 }])
+///     .with_exit_code(0)
+///     .with_metadata("job_id", 42_u64);
+/// event::end_consumer_measurement().publish(event);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EndConsumerMeasurement {
+    /// The consumers that have been measured, if known.
+    pub consumers: Vec<ResourceConsumer>,
+    /// The exit code of the consumer, if it is a process that has exited normally.
+    pub exit_code: Option<i32>,
+    /// Additional information about the experiment, such as the name of the phase or the id of the job.
+    pub metadata: EventMetadata,
+}
+
+/// Event occurring when an experiment goes from one phase to the next one.
+///
+/// Phases are distinct parts of an experiment, such as `warmup`, `benchmark` and `teardown`.
+/// During a phase, the [`EndConsumerMeasurement`] events contain the name of the phase in their
+/// `phase` metadata.
+#[derive(Clone, Debug)]
+pub struct PhaseChange {
+    /// The phase that starts, or `None` if the last phase has ended.
+    pub phase: Option<String>,
+    /// When the phase has changed.
+    pub timestamp: Timestamp,
+}
+
+/// Event occurring when a job of a batch scheduler (such as OAR or Slurm) starts or ends on the node.
+///
+/// Plugins that query external data about the node can use it to bound their queries to the duration of the job.
+///
+/// # Example
+/// ```no_run
+/// use alumet::measurement::Timestamp;
+/// use alumet::plugin::event::{self, JobChange, JobState};
+///
+/// let event = JobChange::new("oar", 42, JobState::Started, Timestamp::now()).with_metadata("walltime", 3600_u64);
+/// event::job_change().publish(event);
+/// ```
+#[derive(Clone, Debug)]
+pub struct JobChange {
+    /// Name of the scheduler, such as `oar`.
+    pub scheduler: String,
+    /// Id of the job, given by the scheduler.
+    pub job_id: u64,
+    pub state: JobState,
+    /// When the job has started or ended.
+    pub timestamp: Timestamp,
+    /// Additional information about the job, such as its walltime.
+    pub metadata: EventMetadata,
+}
+
+/// State of a job in a [`JobChange`] event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    Started,
+    Ended,
+}
+
+/// Key-value data attached to an event.
+///
+/// The keys are sorted, which makes the iteration order deterministic.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventMetadata(BTreeMap<String, AttributeValue>);
+
+impl EndConsumerMeasurement {
+    /// Creates a new event about the given consumers, without any additional information.
+    pub fn new(consumers: Vec<ResourceConsumer>) -> Self {
+        Self {
+            consumers,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the exit code of the consumer.
+    pub fn with_exit_code(mut self, code: i32) -> Self {
+        self.exit_code = Some(code);
+        self
+    }
+
+    /// Adds some metadata to the event.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<AttributeValue>) -> Self {
+        self.metadata.insert(key, value);
+        self
+    }
+}
+
+impl JobChange {
+    /// Creates a new event about a job, without any additional information.
+    pub fn new(scheduler: impl Into<String>, job_id: u64, state: JobState, timestamp: Timestamp) -> Self {
+        Self {
+            scheduler: scheduler.into(),
+            job_id,
+            state,
+            timestamp,
+            metadata: EventMetadata::new(),
+        }
+    }
+
+    /// Adds some metadata to the event.
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<AttributeValue>) -> Self {
+        self.metadata.insert(key, value);
+        self
+    }
+}
+
+impl EventMetadata {
+    /// Creates an empty set of metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a key-value pair, replacing the previous value (if any).
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<AttributeValue>) {
+        self.0.insert(key.into(), value.into());
+    }
+
+    /// Returns the value associated with `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&AttributeValue> {
+        self.0.get(key)
+    }
+
+    /// Iterates on the key-value pairs, sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AttributeValue)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Returns `true` if there is no metadata.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The number of key-value pairs.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
 
 impl Event for StartConsumerMeasurement {}
 impl Event for StartResourceMeasurement {}
 impl Event for EndConsumerMeasurement {}
+impl Event for PhaseChange {}
+impl Event for JobChange {}
 
 #[cfg(test)]
 mod tests {
     use std::sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicU32, Ordering},
     };
 
-    use super::{Event, EventBus};
+    use crate::{
+        measurement::{AttributeValue, Timestamp},
+        resources::ResourceConsumer,
+    };
+
+    use super::{EndConsumerMeasurement, Event, EventBus, JobChange, JobState};
 
     #[derive(Clone)]
     struct TestEvent(u32);
@@ -203,10 +405,117 @@ mod tests {
             cloned_count.fetch_add(event.0, Ordering::SeqCst);
             Ok(())
         });
+        assert_eq!(
+            123,
+            event_count.load(Ordering::SeqCst),
+            "the last event should be replayed to the new listener"
+        );
 
         bus.publish(TestEvent(1));
-        assert_eq!(1, event_count.load(Ordering::SeqCst));
+        assert_eq!(124, event_count.load(Ordering::SeqCst));
         bus.publish(TestEvent(10));
-        assert_eq!(11, event_count.load(Ordering::SeqCst));
+        assert_eq!(134, event_count.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn replay() {
+        let bus: EventBus<TestEvent> = EventBus::default();
+        let replayed = Arc::new(AtomicU32::new(0));
+        let not_replayed = Arc::new(AtomicU32::new(0));
+
+        // publish before subscribing
+        bus.publish(TestEvent(1));
+        bus.publish(TestEvent(2));
+
+        let cloned = replayed.clone();
+        bus.subscribe(move |event| {
+            cloned.fetch_add(event.0, Ordering::SeqCst);
+            Ok(())
+        });
+        assert_eq!(
+            2,
+            replayed.load(Ordering::SeqCst),
+            "only the last event should be replayed"
+        );
+
+        let cloned = not_replayed.clone();
+        bus.subscribe_without_replay(move |event| {
+            cloned.fetch_add(event.0, Ordering::SeqCst);
+            Ok(())
+        });
+        assert_eq!(
+            0,
+            not_replayed.load(Ordering::SeqCst),
+            "events should not be replayed to this listener"
+        );
+
+        // both listeners receive the new events
+        bus.publish(TestEvent(10));
+        assert_eq!(12, replayed.load(Ordering::SeqCst));
+        assert_eq!(10, not_replayed.load(Ordering::SeqCst));
+
+        // lazy events are not replayed
+        let bus: EventBus<TestEvent> = EventBus::default();
+        bus.publish_lazy(|| TestEvent(100));
+        let cloned = replayed.clone();
+        bus.subscribe(move |event| {
+            cloned.fetch_add(event.0, Ordering::SeqCst);
+            Ok(())
+        });
+        assert_eq!(12, replayed.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn event_payload() {
+        let bus: EventBus<EndConsumerMeasurement> = EventBus::default();
+        let received = Arc::new(Mutex::new(None));
+        let cloned = received.clone();
+        bus.subscribe(move |event| {
+            *cloned.lock().unwrap() = Some(event);
+            Ok(())
+        });
+
+        let event = EndConsumerMeasurement::new(vec![ResourceConsumer::Process { pid: 42 }])
+            .with_exit_code(1)
+            .with_metadata("phase", "build")
+            .with_metadata("job_id", 123_u64);
+        bus.publish(event);
+
+        let event = received.lock().unwrap().take().expect("the event should be received");
+        assert_eq!(event.consumers, vec![ResourceConsumer::Process { pid: 42 }]);
+        assert_eq!(event.exit_code, Some(1));
+        let metadata: Vec<_> = event.metadata.iter().collect();
+        assert_eq!(
+            metadata,
+            vec![
+                ("job_id", &AttributeValue::U64(123)),
+                ("phase", &AttributeValue::Str("build"))
+            ]
+        );
+    }
+
+    #[test]
+    fn job_change() {
+        let bus: EventBus<JobChange> = EventBus::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let cloned = received.clone();
+        bus.subscribe(move |event| {
+            cloned.lock().unwrap().push(event);
+            Ok(())
+        });
+
+        let start = Timestamp::from_unix_timestamp(1718000000, 0);
+        bus.publish(JobChange::new("oar", 4242, JobState::Started, start).with_metadata("walltime", 3600_u64));
+        bus.publish(JobChange::new("oar", 4242, JobState::Ended, Timestamp::now()));
+
+        let events = received.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].scheduler, "oar");
+        assert_eq!(events[0].job_id, 4242);
+        assert_eq!(events[0].state, JobState::Started);
+        assert_eq!(events[0].timestamp, start);
+        assert_eq!(events[0].metadata.get("walltime"), Some(&AttributeValue::U64(3600)));
+        assert_eq!(events[1].state, JobState::Ended);
+        assert!(events[1].metadata.is_empty());
     }
 }
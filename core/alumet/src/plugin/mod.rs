@@ -24,7 +24,8 @@
 //! in `start`, since the pipeline is not fully constructed nor started at this point).
 //!
 //! 5. **Stop**: when the pipeline is stopped, the elements registered by the plugin are stopped and dropped.
-//! Then, [`stop`](Plugin::stop) is called.
+//!    Then, the async hooks registered with [`on_shutdown`](AlumetPluginStart::on_shutdown) run, with a deadline,
+//!    and the async runtime of the pipeline is torn down. Finally, [`stop`](Plugin::stop) is called.
 //!
 //! 6. **Drop**: like any Rust value, the plugin is dropped when it goes out of scope.
 //! To customize the destructor of your static plugin, implement the [`Drop`] trait on your plugin structure.
@@ -93,16 +94,22 @@
 //!
 use std::fmt::Debug;
 
+use self::capability::Capability;
 use self::rust::AlumetPlugin;
 
+pub mod capability;
 pub mod event;
 pub(crate) mod phases;
 pub mod rust;
+pub mod state;
 pub mod util;
 pub mod version;
 
 pub use phases::{AlumetPluginStart, AlumetPostStart, AlumetPreStart};
 
+/// Function that returns the capabilities required by a plugin with the given configuration.
+pub type CapabilitiesProvider = Box<dyn Fn(&ConfigTable) -> anyhow::Result<Option<Vec<Capability>>>>;
+
 /// Plugin metadata, and a function that allows to initialize the plugin.
 pub struct PluginMetadata {
     /// Name of the plugin, must be unique.
@@ -118,6 +125,20 @@ pub struct PluginMetadata {
     /// Alumet agent, in case it does not exist. In other cases, the default
     /// config returned by this function is not used, including when
     pub default_config: Box<dyn Fn() -> anyhow::Result<Option<ConfigTable>>>,
+    /// Function that returns the capabilities required by the plugin with the given configuration,
+    /// or None if the plugin does not declare them.
+    ///
+    /// See the [`capability`] module.
+    pub capabilities: CapabilitiesProvider,
+    /// Comments that explain the options of the default configuration, by option path.
+    ///
+    /// See [`rust::AlumetPlugin::config_comments`].
+    pub config_comments: Vec<(String, String)>,
+    /// Prefix of the targets of the log messages emitted by the plugin, or None if unknown.
+    ///
+    /// For static plugins, this is the name of the crate that defines the plugin, for instance `plugin_csv`.
+    /// It allows to set a different log level for each plugin.
+    pub log_target: Option<String>,
 }
 
 impl PluginMetadata {
@@ -128,6 +149,12 @@ impl PluginMetadata {
             version: P::version().to_owned(),
             init: Box::new(|conf| P::init(conf).map(|p| p as _)),
             default_config: Box::new(P::default_config),
+            capabilities: Box::new(P::capabilities_with_config),
+            config_comments: P::config_comments()
+                .into_iter()
+                .map(|(path, comment)| (path.to_owned(), comment.to_owned()))
+                .collect(),
+            log_target: std::any::type_name::<P>().split("::").next().map(str::to_owned),
         }
     }
 }
@@ -137,6 +164,7 @@ impl Debug for PluginMetadata {
         f.debug_struct("PluginMetadata")
             .field("name", &self.name)
             .field("version", &self.version)
+            .field("log_target", &self.log_target)
             .finish()
     }
 }
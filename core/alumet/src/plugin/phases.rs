@@ -1,11 +1,14 @@
 //! Phases of the plugins lifecycle.
 use std::future::Future;
+use std::io;
 use std::marker::PhantomData;
+use std::path::Path;
+use std::time::Duration;
 
 use crate::measurement::{MeasurementType, WrappedMeasurementType};
 use crate::metrics::def::{Metric, RawMetricId, TypedMetricId};
 use crate::metrics::duplicate::{DuplicateCriteria, DuplicateReaction};
-use crate::metrics::error::MetricCreationError;
+use crate::metrics::error::{MetricCreationError, MetricLookupError};
 use crate::metrics::online::listener::{MetricListener, MetricListenerBuilder};
 use crate::metrics::online::{MetricReader, MetricSender};
 use crate::metrics::registry::MetricRegistry;
@@ -15,7 +18,9 @@ use crate::pipeline::elements::source::control::TaskState;
 use crate::pipeline::elements::source::trigger::TriggerSpec;
 use crate::pipeline::elements::{output, source, transform};
 use crate::pipeline::naming::{PluginName, namespace::DuplicateNameError};
+use crate::pipeline::shutdown::ShutdownHook;
 use crate::pipeline::{self, Output, Source, Transform};
+use crate::plugin::state::StateDir;
 use crate::units::PrefixedUnit;
 
 /// Structure passed to plugins for the start-up phase.
@@ -30,6 +35,7 @@ pub struct AlumetPluginStart<'a> {
     pub(crate) pipeline_builder: &'a mut pipeline::Builder,
     pub(crate) pre_start_actions: &'a mut Vec<(PluginName, Box<dyn PreStartAction>)>,
     pub(crate) post_start_actions: &'a mut Vec<(PluginName, Box<dyn PostStartAction>)>,
+    pub(crate) state_root: &'a Path,
 }
 
 pub trait PostStartAction: FnOnce(&mut AlumetPostStart) -> anyhow::Result<()> {}
@@ -44,6 +50,15 @@ impl<'a> AlumetPluginStart<'a> {
         self.current_plugin.clone()
     }
 
+    /// Returns the state directory of the plugin.
+    ///
+    /// The directory is reserved to the plugin and persists between the runs of the agent.
+    /// Use it to store watermarks, spool files, caches, etc.
+    /// See the [`state`](super::state) module.
+    pub fn state_dir(&self) -> io::Result<StateDir> {
+        StateDir::open(self.state_root.join(&self.current_plugin.0))
+    }
+
     /// Creates a new metric with a measurement type `T` (checked at compile time).
     /// Fails if a metric with the same name already exists.
     ///
@@ -74,6 +89,49 @@ impl<'a> AlumetPluginStart<'a> {
             description: description.into(),
             value_type: T::wrapped_type(),
             unit: unit.into(),
+            tags: Vec::new(),
+        };
+        let untyped_id =
+            self.pipeline_builder
+                .metrics
+                .register(m, DuplicateCriteria::Incompatible, DuplicateReaction::Error)?;
+        Ok(TypedMetricId(untyped_id, PhantomData))
+    }
+
+    /// Creates a new metric with a measurement type `T` (checked at compile time) and some tags.
+    /// Fails if a metric with the same name already exists.
+    ///
+    /// Tags allow other plugins to find the metric by category, see [`MetricRegistry::by_tag`](crate::metrics::registry::MetricRegistry::by_tag).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use alumet::units::Unit;
+    /// use alumet::metrics::{TypedMetricId, def::tag};
+    /// # use alumet::plugin::AlumetPluginStart;
+    ///
+    /// # fn f() -> anyhow::Result<()> {
+    /// # let alumet: &AlumetPluginStart = todo!();
+    /// let gpu_energy: TypedMetricId<f64> = alumet.create_metric_with_tags(
+    ///     "gpu_energy_consumption",
+    ///     Unit::Joule,
+    ///     "energy consumed by the GPU since the previous measurement",
+    ///     &[tag::ENERGY],
+    /// )?;
+    /// # }
+    /// ```
+    pub fn create_metric_with_tags<T: MeasurementType>(
+        &mut self,
+        name: impl Into<String>,
+        unit: impl Into<PrefixedUnit>,
+        description: impl Into<String>,
+        tags: &[&str],
+    ) -> Result<TypedMetricId<T>, MetricCreationError> {
+        let m = Metric {
+            name: name.into(),
+            description: description.into(),
+            value_type: T::wrapped_type(),
+            unit: unit.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
         };
         let untyped_id =
             self.pipeline_builder
@@ -100,6 +158,7 @@ impl<'a> AlumetPluginStart<'a> {
             description: description.to_owned(),
             value_type,
             unit: unit.into(),
+            tags: Vec::new(),
         };
         self.pipeline_builder
             .metrics
@@ -363,6 +422,33 @@ impl<'a> AlumetPluginStart<'a> {
         let plugin = self.current_plugin_name();
         self.pre_start_actions.push((plugin, Box::new(action)));
     }
+
+    /// Registers an async function that will run when the pipeline shuts down,
+    /// after the sources, transforms and outputs have stopped.
+    ///
+    /// Unlike [`Plugin::stop`](crate::plugin::Plugin::stop), the hook runs on the async runtime
+    /// of the pipeline, which allows to await some final operations, such as flushing a connection.
+    /// If the hook does not complete before the `deadline`, it is cancelled.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use alumet::plugin::AlumetPluginStart;
+    /// # use std::time::Duration;
+    /// # let alumet: &mut AlumetPluginStart = todo!();
+    /// alumet.on_shutdown(Duration::from_secs(5), || async {
+    ///     // e.g. send the remaining data to a remote server
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn on_shutdown<F, Fut>(&mut self, deadline: Duration, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let plugin = self.current_plugin_name();
+        self.pipeline_builder
+            .add_shutdown_hook(ShutdownHook::new(plugin, deadline, hook));
+    }
 }
 
 /// Structure passed to plugins for the pre start-up phase.
@@ -435,6 +521,23 @@ impl<'a> AlumetPostStart<'a> {
         self.pipeline.metrics_reader()
     }
 
+    /// Finds a metric by its name, and checks that its measurement type is `T`.
+    ///
+    /// This allows a plugin to use the metrics registered by other plugins during their
+    /// start-up phase, whatever the order of the plugins.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use alumet::plugin::AlumetPostStart;
+    /// # let alumet: &AlumetPostStart = todo!();
+    /// // In post_pipeline_start(&mut self, alumet: &mut AlumetPostStart)
+    /// let energy = alumet.find_metric::<f64>("rapl_consumed_energy")?;
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn find_metric<T: MeasurementType>(&self, name: &str) -> Result<TypedMetricId<T>, MetricLookupError> {
+        self.pipeline.metrics_reader().blocking_read().by_name_typed(name)
+    }
+
     /// Returns a handle to the main asynchronous runtime used by the pipeline.
     pub fn async_runtime(&self) -> tokio::runtime::Handle {
         self.pipeline.async_runtime().clone()
@@ -6,7 +6,7 @@ use anyhow::{Context, anyhow};
 
 use crate::plugin::{AlumetPluginStart, Plugin};
 
-use super::{AlumetPostStart, ConfigTable, phases::AlumetPreStart};
+use super::{AlumetPostStart, ConfigTable, capability::Capability, phases::AlumetPreStart};
 
 /// Trait for Alumet plugins written in Rust.
 ///
@@ -53,6 +53,36 @@ pub trait AlumetPlugin {
     /// ```
     fn default_config() -> anyhow::Result<Option<ConfigTable>>;
 
+    /// Returns the capabilities required by the plugin, for instance a network access.
+    ///
+    /// They are checked against the capability policy of the agent, before the plugin is initialized.
+    /// A plugin that requires nothing must return `Some(Vec::new())`: by default, the capabilities
+    /// are not declared, and the plugin is refused by every policy that restricts something.
+    /// See the [`capability`](super::capability) module.
+    fn capabilities() -> Option<Vec<Capability>> {
+        None // not declared by default
+    }
+
+    /// Returns the capabilities required by the plugin with the given configuration.
+    ///
+    /// The agent calls this method with the configuration that it will pass to [`init`](Self::init).
+    /// By default, it returns [`capabilities`](Self::capabilities). Override it if the capabilities
+    /// depend on the configuration, for instance if the plugin writes to a configurable path.
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let _ = config;
+        Ok(Self::capabilities())
+    }
+
+    /// Returns the comments that explain the options of the default configuration.
+    ///
+    /// Each comment is associated with the path of an option in the config of the plugin, for instance
+    /// `poll_interval`, or `rotation.compression` for an option of the `rotation` table. The path of a
+    /// table, such as `rotation`, explains the whole table. When the agent generates its configuration
+    /// file, it writes the comments above the options.
+    fn config_comments() -> Vec<(&'static str, &'static str)> {
+        Vec::new()
+    }
+
     /// Starts the plugin, allowing it to register metrics, sources and outputs.
     ///
     /// # Plugin restart
@@ -113,9 +143,13 @@ impl<P: AlumetPlugin> Plugin for P {
     }
 }
 
+/// Deserializes a configuration.
+///
+/// If the configuration is invalid, the returned error contains an [`InvalidConfigField`],
+/// which indicates what field is invalid and why.
 pub fn deserialize_config<'de, T: serde::de::Deserialize<'de>>(config: ConfigTable) -> anyhow::Result<T> {
-    toml::Value::Table(config.0)
-        .try_into::<T>()
+    serde_path_to_error::deserialize(toml::Value::Table(config.0))
+        .map_err(InvalidConfigField::from)
         .with_context(|| format!("error when deserializing ConfigTable to {}", std::any::type_name::<T>()))
         .context(InvalidConfig)
 }
@@ -136,6 +170,21 @@ pub fn serialize_config<T: serde::ser::Serialize>(config: T) -> anyhow::Result<C
     res.context(InvalidConfig)
 }
 
+/// Returns the value of an optional config option, or else the value of the environment variable `var`.
+///
+/// This is useful for credentials, which are often given by the environment.
+///
+/// # Example
+/// ```
+/// use alumet::plugin::rust::config_or_env;
+///
+/// let token = Some(String::from("abcd"));
+/// assert_eq!(config_or_env(&token, "MY_TOKEN"), Some(String::from("abcd")));
+/// ```
+pub fn config_or_env(value: &Option<String>, var: &str) -> Option<String> {
+    value.clone().or_else(|| std::env::var(var).ok())
+}
+
 /// Signals an invalid configuration.
 ///
 /// Use this singleton with [`anyhow::Context`] to signal that
@@ -153,3 +202,154 @@ impl std::fmt::Display for InvalidConfig {
         write!(f, "invalid configuration")
     }
 }
+
+/// An invalid field in a configuration.
+///
+/// To find the invalid field in an error returned by [`deserialize_config`],
+/// use [`anyhow::Error::downcast_ref`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidConfigField {
+    /// Path to the invalid field, from the root of the configuration.
+    ///
+    /// The elements of the arrays are represented by their index.
+    pub path: Vec<String>,
+    /// What's wrong with the field.
+    pub message: String,
+}
+
+impl InvalidConfigField {
+    /// Returns the path to the field as a string, for instance `"metrics[0].name"`.
+    pub fn path_string(&self) -> String {
+        let mut res = String::new();
+        for segment in &self.path {
+            if segment.parse::<usize>().is_ok() {
+                res.push_str(&format!("[{segment}]"));
+            } else {
+                if !res.is_empty() {
+                    res.push('.');
+                }
+                res.push_str(segment);
+            }
+        }
+        res
+    }
+}
+
+impl From<serde_path_to_error::Error<toml::de::Error>> for InvalidConfigField {
+    fn from(value: serde_path_to_error::Error<toml::de::Error>) -> Self {
+        use serde_path_to_error::Segment;
+
+        let path = value
+            .path()
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Seq { index } => Some(index.to_string()),
+                Segment::Map { key } => Some(key.to_owned()),
+                Segment::Enum { variant } => Some(variant.to_owned()),
+                Segment::Unknown => None,
+            })
+            .collect();
+
+        // Turn "invalid type: string \"a\", expected a sequence" into "must be a sequence, got string \"a\""
+        let message = value.inner().message().trim();
+        let message = match message
+            .strip_prefix("invalid type: ")
+            .and_then(|m| m.split_once(", expected "))
+        {
+            Some((actual, expected)) => format!("must be {expected}, got {actual}"),
+            None => message.to_owned(),
+        };
+        Self { path, message }
+    }
+}
+
+impl std::error::Error for InvalidConfigField {}
+impl std::fmt::Display for InvalidConfigField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else if self.message.starts_with("must be") {
+            write!(f, "field `{}` {}", self.path_string(), self.message)
+        } else {
+            write!(f, "field `{}`: {}", self.path_string(), self.message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::{ConfigTable, InvalidConfigField, deserialize_config};
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    #[serde(deny_unknown_fields)]
+    struct Config {
+        url: String,
+        metrics: Vec<String>,
+        #[serde(default)]
+        nodes: Vec<Node>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Node {
+        name: String,
+    }
+
+    fn invalid_field(config: toml::Table) -> InvalidConfigField {
+        let err = deserialize_config::<Config>(ConfigTable(config)).unwrap_err();
+        err.downcast_ref::<InvalidConfigField>()
+            .expect("the error should contain the invalid field")
+            .to_owned()
+    }
+
+    #[test]
+    fn invalid_type() {
+        let field = invalid_field(toml::toml! {
+            url = "https://example.com"
+            metrics = "bmc_node_power_watt"
+        });
+        assert_eq!(field.path, vec!["metrics"]);
+        assert_eq!(
+            field.to_string(),
+            "field `metrics` must be a sequence, got string \"bmc_node_power_watt\""
+        );
+    }
+
+    #[test]
+    fn nested() {
+        let field = invalid_field(toml::toml! {
+            url = "https://example.com"
+            metrics = []
+            nodes = [{ name = "a" }, { name = 2 }]
+        });
+        assert_eq!(field.path, vec!["nodes", "1", "name"]);
+        assert_eq!(field.path_string(), "nodes[1].name");
+        assert_eq!(
+            field.to_string(),
+            "field `nodes[1].name` must be a string, got integer `2`"
+        );
+    }
+
+    #[test]
+    fn missing_or_unknown() {
+        let field = invalid_field(toml::toml! {
+            metrics = []
+        });
+        assert!(field.path.is_empty());
+        assert_eq!(field.to_string(), "missing field `url`");
+
+        let field = invalid_field(toml::toml! {
+            url = "https://example.com"
+            metrics = []
+            typo = 1
+        });
+        assert_eq!(field.path, vec!["typo"]);
+        assert!(
+            field.to_string().starts_with("field `typo`: unknown field `typo`"),
+            "{field}"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Persistent state of the plugins.
+//!
+//! Some plugins need to remember things between two runs of the agent: the timestamp of the
+//! last fetched measurement (a "watermark"), the data that could not be sent yet (a "spool"),
+//! a cache, etc. Instead of inventing its own file handling, a plugin can obtain a [`StateDir`],
+//! a directory that belongs to the plugin alone, with [`AlumetPluginStart::state_dir`](super::AlumetPluginStart::state_dir).
+//!
+//! # Crash safety
+//! The files of a `StateDir` are written atomically: a reader sees either the old content or
+//! the new content of the file, never a mix of both, even if the agent crashes (or the machine
+//! loses power) in the middle of a write. To achieve this, the new content is written to a
+//! temporary file, which is synced to the disk and then renamed to replace the old file.
+//!
+//! # Example
+//! ```no_run
+//! use alumet::plugin::AlumetPluginStart;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Watermark {
+//!     last_timestamp: u64,
+//! }
+//!
+//! # fn f(alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+//! let state = alumet.state_dir()?;
+//! let watermark: Option<Watermark> = state.load("watermark.toml")?;
+//! // ...
+//! state.save("watermark.toml", &Watermark { last_timestamp: 1234 })?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Default root of the state directories, relative to the working directory of the agent.
+pub const DEFAULT_STATE_ROOT: &str = "alumet-state";
+
+/// Suffix of the temporary files used during atomic writes.
+const TMP_SUFFIX: &str = ".tmp";
+
+/// A directory where a plugin can store its state.
+///
+/// See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct StateDir {
+    path: PathBuf,
+}
+
+impl StateDir {
+    /// Opens a state directory.
+    ///
+    /// The directory is created on the first write, if it does not exist.
+    /// The temporary files that were left by an interrupted write are deleted.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let entries = match fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self { path }),
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') && name.ends_with(TMP_SUFFIX) {
+                log::debug!("Removing incomplete state file {:?}", entry.path());
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(Self { path })
+    }
+
+    /// Returns the path of the directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the file `name` in this directory.
+    ///
+    /// Fails if `name` is not a valid file name. Names that begin with a dot are reserved.
+    pub fn file_path(&self, name: &str) -> io::Result<PathBuf> {
+        let valid = !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']);
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid state file name {name:?}"),
+            ));
+        }
+        Ok(self.path.join(name))
+    }
+
+    /// Atomically replaces the content of the file `name`.
+    pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        let path = self.file_path(name)?;
+        let tmp_path = self.path.join(format!(".{name}{TMP_SUFFIX}"));
+        fs::create_dir_all(&self.path)?;
+        write_atomic(&path, &tmp_path, contents.as_ref())
+    }
+
+    /// Reads the content of the file `name`, or returns `None` if it does not exist.
+    pub fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let path = self.file_path(name)?;
+        match fs::read(path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads the content of the file `name` as a string, or returns `None` if it does not exist.
+    pub fn read_to_string(&self, name: &str) -> io::Result<Option<String>> {
+        let path = self.file_path(name)?;
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Deletes the file `name`. Returns `true` if it existed.
+    pub fn remove(&self, name: &str) -> io::Result<bool> {
+        let path = self.file_path(name)?;
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Serializes `value` to TOML and atomically writes it to the file `name`.
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> anyhow::Result<()> {
+        let content = toml::to_string(value).with_context(|| format!("failed to serialize state {name:?}"))?;
+        self.write(name, content)
+            .with_context(|| format!("failed to write state {name:?}"))
+    }
+
+    /// Reads the file `name` and deserializes it from TOML, or returns `None` if it does not exist.
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> anyhow::Result<Option<T>> {
+        let Some(content) = self
+            .read_to_string(name)
+            .with_context(|| format!("failed to read state {name:?}"))?
+        else {
+            return Ok(None);
+        };
+        let value = toml::from_str(&content).with_context(|| format!("invalid state {name:?}"))?;
+        Ok(Some(value))
+    }
+}
+
+/// Writes `contents` to `tmp_path`, syncs it and renames it to `path`.
+fn write_atomic(path: &Path, tmp_path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(tmp_path, path)?;
+
+    // Sync the directory, so that the rename itself is persisted.
+    // This is not possible (nor needed) on Windows.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::StateDir;
+
+    #[test]
+    fn write_read_remove() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::open(tmp.path().join("plugin")).unwrap();
+        assert!(!state.path().exists());
+        assert_eq!(state.read("spool").unwrap(), None);
+        assert!(!state.remove("spool").unwrap());
+
+        state.write("spool", "first").unwrap();
+        assert_eq!(state.read_to_string("spool").unwrap().as_deref(), Some("first"));
+        state.write("spool", b"second").unwrap();
+        assert_eq!(state.read("spool").unwrap(), Some(b"second".to_vec()));
+
+        // no temporary file is left behind
+        let files: Vec<_> = fs::read_dir(state.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec!["spool"]);
+
+        assert!(state.remove("spool").unwrap());
+        assert!(!state.remove("spool").unwrap());
+        assert_eq!(state.read("spool").unwrap(), None);
+    }
+
+    #[test]
+    fn save_load() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Watermark {
+            timestamp: u64,
+            source: String,
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::open(tmp.path()).unwrap();
+        assert_eq!(state.load::<Watermark>("watermark.toml").unwrap(), None);
+
+        let watermark = Watermark {
+            timestamp: 1234,
+            source: String::from("kwollect"),
+        };
+        state.save("watermark.toml", &watermark).unwrap();
+        assert_eq!(state.load("watermark.toml").unwrap(), Some(watermark));
+
+        state.write("watermark.toml", "not toml").unwrap();
+        state.load::<Watermark>("watermark.toml").unwrap_err();
+    }
+
+    #[test]
+    fn interrupted_write() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("watermark"), "old").unwrap();
+        fs::write(tmp.path().join(".watermark.tmp"), "incomplete").unwrap();
+
+        let state = StateDir::open(tmp.path()).unwrap();
+        assert!(!tmp.path().join(".watermark.tmp").exists());
+        assert_eq!(state.read_to_string("watermark").unwrap().as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn invalid_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = StateDir::open(tmp.path()).unwrap();
+        for name in ["", ".hidden", "..", "../escape", "a/b", "a\\b"] {
+            state.write(name, "x").unwrap_err();
+        }
+    }
+}
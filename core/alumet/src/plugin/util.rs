@@ -1,5 +1,9 @@
 //! Utilities for implementing plugins.
 
+use std::num::ParseIntError;
+
+use anyhow::{Context, anyhow};
+
 /// Computes the difference between each successive measurement.
 ///
 /// # Correction of overflows
@@ -87,6 +91,27 @@ impl From<CounterDiffUpdate> for Option<u64> {
     }
 }
 
+/// Returns the ids of the online CPUs, read from `/sys/devices/system/cpu/online`.
+pub fn online_cpus() -> anyhow::Result<Vec<u32>> {
+    let path = "/sys/devices/system/cpu/online";
+    let list = std::fs::read_to_string(path).with_context(|| format!("could not read {path}"))?;
+    parse_cpu_list(&list).with_context(|| format!("invalid content in {path}: {list}"))
+}
+
+/// Parses a list of CPUs in the format of the kernel, such as `0-3,8,10-11`.
+pub fn parse_cpu_list(list: &str) -> anyhow::Result<Vec<u32>> {
+    let mut res = Vec::new();
+    for item in list.trim_end().split(',') {
+        let bounds: Vec<u32> = item.split('-').map(str::parse).collect::<Result<_, ParseIntError>>()?;
+        match *bounds.as_slice() {
+            [start, end] => res.extend(start..=end),
+            [n] => res.push(n),
+            _ => return Err(anyhow!("invalid cpu range: {item}")),
+        }
+    }
+    Ok(res)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,4 +163,13 @@ mod tests {
         let mut counter = CounterDiff::with_max_value(255);
         let _ = counter.update(256);
     }
+
+    #[test]
+    fn cpu_list() {
+        assert_eq!(parse_cpu_list("0\n").unwrap(), vec![0]);
+        assert_eq!(parse_cpu_list("0-3\n").unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(parse_cpu_list("0-1,4,6-7").unwrap(), vec![0, 1, 4, 6, 7]);
+        assert!(parse_cpu_list("0-1-2").is_err());
+        assert!(parse_cpu_list("").is_err());
+    }
 }
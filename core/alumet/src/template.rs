@@ -0,0 +1,263 @@
+//! Templates with placeholders, such as `alumet/{hostname}/{metric}`.
+//!
+//! Several plugins build strings from a template given in their configuration: MQTT topics,
+//! Graphite paths, object keys, etc. A [`Template`] is parsed once, when the plugin starts,
+//! then rendered for each measurement (or file, or anything else).
+//!
+//! The templates about measurements use the placeholders of [`MeasurementPlaceholder`],
+//! plus `{hostname}`, which is resolved when the template is parsed.
+//!
+//! # Example
+//! ```
+//! use alumet::template::MeasurementTemplate;
+//!
+//! let template = MeasurementTemplate::parse_measurement("alumet/{hostname}/{metric}", "node-1").unwrap();
+//! assert_eq!(template.literal(), "alumet/node-1/");
+//! ```
+
+use thiserror::Error;
+
+use crate::{measurement::MeasurementPoint, metrics::Metric};
+
+/// A parsed template, made of text and of placeholders of type `P`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template<P> {
+    parts: Vec<Part<P>>,
+}
+
+/// A part of a [`Template`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Part<P> {
+    /// Text that is copied as is.
+    Text(String),
+    /// Placeholder that is replaced by a value when the template is rendered.
+    Placeholder(P),
+}
+
+/// Error that can occur when parsing a template.
+#[derive(Debug, Error, PartialEq)]
+pub enum TemplateError {
+    #[error("unclosed placeholder")]
+    Unclosed,
+    #[error("unknown placeholder {{{0}}}")]
+    UnknownPlaceholder(String),
+}
+
+impl<P> Template<P> {
+    /// Parses a template.
+    ///
+    /// For each placeholder `{name}`, `placeholder(name)` returns the corresponding part,
+    /// or `None` if the placeholder is unknown. It can return a [`Part::Text`] to replace
+    /// the placeholders whose value is known in advance.
+    pub fn parse(template: &str, mut placeholder: impl FnMut(&str) -> Option<Part<P>>) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or(TemplateError::Unclosed)? + start;
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+            let name = &rest[start + 1..end];
+            let part = placeholder(name).ok_or_else(|| TemplateError::UnknownPlaceholder(name.to_owned()))?;
+            parts.push(part);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Returns the parts of the template.
+    pub fn parts(&self) -> &[Part<P>] {
+        &self.parts
+    }
+
+    /// Returns true if the template contains nothing, not even a placeholder.
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    /// Returns the text of the template, without the placeholders.
+    ///
+    /// This is useful to check that the template does not contain forbidden characters.
+    pub fn literal(&self) -> String {
+        self.parts
+            .iter()
+            .filter_map(|p| match p {
+                Part::Text(t) => Some(t.as_str()),
+                Part::Placeholder(_) => None,
+            })
+            .collect()
+    }
+
+    /// Renders the template: `value` appends the value of each placeholder to the result.
+    pub fn render(&self, mut value: impl FnMut(&P, &mut String)) -> String {
+        let mut res = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(t) => res.push_str(t),
+                Part::Placeholder(p) => value(p, &mut res),
+            }
+        }
+        res
+    }
+}
+
+/// A placeholder about a measurement point.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeasurementPlaceholder {
+    /// `{metric}`: the name of the metric.
+    Metric,
+    /// `{resource_kind}`: the kind of the resource, ex. `cpu_package`.
+    ResourceKind,
+    /// `{resource_id}`: the id of the resource, ex. `0`.
+    ResourceId,
+    /// `{consumer_kind}`: the kind of the consumer, ex. `cgroup`.
+    ConsumerKind,
+    /// `{consumer_id}`: the id of the consumer, ex. `/system.slice`.
+    ConsumerId,
+    /// `{attr.<key>}`: the value of an attribute, or nothing if the measurement does not have it.
+    Attribute(String),
+}
+
+impl MeasurementPlaceholder {
+    /// Returns the placeholder that has the given name, if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "metric" => Some(Self::Metric),
+            "resource_kind" => Some(Self::ResourceKind),
+            "resource_id" => Some(Self::ResourceId),
+            "consumer_kind" => Some(Self::ConsumerKind),
+            "consumer_id" => Some(Self::ConsumerId),
+            _ => match name.strip_prefix("attr.") {
+                Some(key) if !key.is_empty() => Some(Self::Attribute(key.to_owned())),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the value of the placeholder for the measurement `m`, whose metric is `metric`.
+    pub fn value(&self, m: &MeasurementPoint, metric: &Metric) -> String {
+        match self {
+            Self::Metric => metric.name.clone(),
+            Self::ResourceKind => m.resource.kind().to_owned(),
+            Self::ResourceId => m.resource.id_display().to_string(),
+            Self::ConsumerKind => m.consumer.kind().to_owned(),
+            Self::ConsumerId => m.consumer.id_display().to_string(),
+            Self::Attribute(key) => m
+                .attributes()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A template about measurements.
+pub type MeasurementTemplate = Template<MeasurementPlaceholder>;
+
+impl MeasurementTemplate {
+    /// Parses a template about measurements.
+    ///
+    /// `{hostname}` is replaced by `hostname` right away, since it does not change.
+    /// The other placeholders are the ones of [`MeasurementPlaceholder`].
+    pub fn parse_measurement(template: &str, hostname: &str) -> Result<Self, TemplateError> {
+        Self::parse(template, |name| match name {
+            "hostname" => Some(Part::Text(hostname.to_owned())),
+            _ => MeasurementPlaceholder::from_name(name).map(Part::Placeholder),
+        })
+    }
+
+    /// Renders the template for the measurement `m`, whose metric is `metric`.
+    ///
+    /// The values of the placeholders are passed to `sanitize`, which typically replaces the characters
+    /// that have a special meaning in the rendered string.
+    pub fn render_measurement(
+        &self,
+        m: &MeasurementPoint,
+        metric: &Metric,
+        sanitize: impl Fn(&str) -> String,
+    ) -> String {
+        self.render(|p, res| res.push_str(&sanitize(&p.value(m, metric))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use crate::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue},
+        metrics::{Metric, RawMetricId},
+        resources::{Resource, ResourceConsumer},
+        units::{PrefixedUnit, Unit},
+    };
+
+    use super::{MeasurementPlaceholder, MeasurementTemplate, Part, Template, TemplateError};
+
+    #[test]
+    fn parse() {
+        let template = Template::parse("a{x}b{y}", |name| match name {
+            "x" => Some(Part::Placeholder(1)),
+            "y" => Some(Part::Text(String::from("Y"))),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(
+            template.parts(),
+            &[
+                Part::Text(String::from("a")),
+                Part::Placeholder(1),
+                Part::Text(String::from("b")),
+                Part::Text(String::from("Y")),
+            ]
+        );
+        assert_eq!(template.literal(), "abY");
+        assert_eq!(template.render(|p, res| res.push_str(&p.to_string())), "a1bY");
+
+        let empty = Template::<()>::parse("", |_| None).unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        let unclosed = Template::<()>::parse("a{x", |_| Some(Part::Placeholder(())));
+        assert_eq!(unclosed, Err(TemplateError::Unclosed));
+        let unknown = MeasurementTemplate::parse_measurement("a/{host}", "node");
+        assert_eq!(unknown, Err(TemplateError::UnknownPlaceholder(String::from("host"))));
+        assert_eq!(unknown.unwrap_err().to_string(), "unknown placeholder {host}");
+        assert!(MeasurementPlaceholder::from_name("attr.").is_none());
+    }
+
+    #[test]
+    fn measurement() {
+        let metric = Metric {
+            name: String::from("rapl_consumed_energy"),
+            description: String::new(),
+            value_type: WrappedMeasurementType::F64,
+            unit: PrefixedUnit::from(Unit::Joule),
+            tags: Vec::new(),
+        };
+        let m = MeasurementPoint::new_untyped(
+            Timestamp::from(SystemTime::UNIX_EPOCH),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 1 },
+            ResourceConsumer::ControlGroup {
+                path: "/system.slice".into(),
+            },
+            WrappedMeasurementValue::F64(1.0),
+        )
+        .with_attr("domain", "package");
+
+        let template = MeasurementTemplate::parse_measurement(
+            "{hostname}/{metric}/{resource_kind}/{resource_id}/{consumer_kind}/{consumer_id}/{attr.domain}{attr.missing}",
+            "node-1",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render_measurement(&m, &metric, |v| v.replace('/', "_")),
+            "node-1/rapl_consumed_energy/cpu_package/1/cgroup/_system.slice/package"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt::Display,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue},
+    metrics::{RawMetricId, def::MetricId},
+    plugin::rust::AlumetPlugin,
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+
+use super::PluginHarness;
+
+/// Number of distinct values taken by each dimension of the synthetic measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cardinality {
+    /// Number of distinct resources (CPU cores).
+    pub resources: u32,
+    /// Number of distinct consumers (processes).
+    pub consumers: u32,
+    /// Number of attributes attached to each point.
+    pub attributes: usize,
+}
+
+impl Default for Cardinality {
+    fn default() -> Self {
+        Self {
+            resources: 8,
+            consumers: 16,
+            attributes: 2,
+        }
+    }
+}
+
+/// Generates synthetic measurement buffers, to benchmark sources, transforms and outputs.
+///
+/// The points cycle through the metrics first, then the resources, then the consumers.
+/// The generated buffers only depend on the requested size: two calls with the same size
+/// return the same measurements.
+///
+/// # Example
+/// ```no_run
+/// use alumet::test::{PluginHarness, bench::{Cardinality, SyntheticMeasurements}};
+/// # use alumet::plugin::rust::AlumetPlugin;
+/// # struct MyPlugin;
+/// # impl AlumetPlugin for MyPlugin {
+/// #     fn name() -> &'static str { "my-plugin" }
+/// #     fn version() -> &'static str { "0.1.0" }
+/// #     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> { Ok(None) }
+/// #     fn init(_: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> { todo!() }
+/// #     fn start(&mut self, _: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> { todo!() }
+/// #     fn stop(&mut self) -> anyhow::Result<()> { todo!() }
+/// # }
+/// # fn main() -> anyhow::Result<()> {
+/// let mut harness = PluginHarness::<MyPlugin>::start_with_default_config()?;
+/// let synthetic = SyntheticMeasurements::register(&harness, 4, Cardinality::default())?;
+/// let mut output = harness.output("out")?;
+///
+/// let buf = synthetic.generate(10_000);
+/// output.write(&buf).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub struct SyntheticMeasurements {
+    metrics: Vec<(RawMetricId, WrappedMeasurementType)>,
+    cardinality: Cardinality,
+    attribute_keys: Vec<String>,
+}
+
+impl SyntheticMeasurements {
+    /// Timestamp of the first point: 2023-11-14T22:13:20Z.
+    pub const START: Duration = Duration::from_secs(1_700_000_000);
+
+    /// Registers `metrics` new metrics of type `f64`, named `synthetic_0`, `synthetic_1`, etc.
+    pub fn register<P: AlumetPlugin>(
+        harness: &PluginHarness<P>,
+        metrics: usize,
+        cardinality: Cardinality,
+    ) -> anyhow::Result<Self> {
+        let metrics = (0..metrics)
+            .map(|i| {
+                let id = harness.create_metric::<f64>(format!("synthetic_{i}"), Unit::Unity, "synthetic metric")?;
+                Ok((id.untyped_id(), WrappedMeasurementType::F64))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self::new(metrics, cardinality))
+    }
+
+    /// Uses existing metrics, for instance the ones that a transform expects as input.
+    ///
+    /// Fails if one of the metrics does not exist.
+    pub fn for_metrics<P: AlumetPlugin>(
+        harness: &PluginHarness<P>,
+        names: &[&str],
+        cardinality: Cardinality,
+    ) -> anyhow::Result<Self> {
+        let registry = harness.metrics();
+        let metrics = names
+            .iter()
+            .map(|name| match registry.by_name(name) {
+                Some((id, metric)) => Ok((id, metric.value_type.clone())),
+                None => Err(anyhow!("metric {name:?} does not exist")),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self::new(metrics, cardinality))
+    }
+
+    fn new(metrics: Vec<(RawMetricId, WrappedMeasurementType)>, cardinality: Cardinality) -> Self {
+        assert!(!metrics.is_empty(), "at least one metric is required");
+        assert!(
+            cardinality.resources > 0 && cardinality.consumers > 0,
+            "there must be at least one resource and one consumer"
+        );
+        let attribute_keys = (0..cardinality.attributes).map(|k| format!("attr_{k}")).collect();
+        Self {
+            metrics,
+            cardinality,
+            attribute_keys,
+        }
+    }
+
+    /// Generates a buffer of `points` measurements.
+    ///
+    /// The timestamp advances by one millisecond after each full cycle of metrics.
+    pub fn generate(&self, points: usize) -> MeasurementBuffer {
+        let n_metrics = self.metrics.len();
+        let n_resources = self.cardinality.resources as usize;
+        let n_consumers = self.cardinality.consumers as usize;
+        let t0 = Timestamp::from(UNIX_EPOCH + Self::START);
+
+        let mut buf = MeasurementBuffer::with_capacity(points);
+        for i in 0..points {
+            let (metric, value_type) = &self.metrics[i % n_metrics];
+            let cycle = i / n_metrics;
+            let timestamp = t0 + Duration::from_millis(cycle as u64);
+            let resource = Resource::CpuCore {
+                id: (cycle % n_resources) as u32,
+            };
+            let consumer = ResourceConsumer::Process {
+                pid: 1000 + ((cycle / n_resources) % n_consumers) as u32,
+            };
+            let value = match value_type {
+                WrappedMeasurementType::F64 => WrappedMeasurementValue::F64(i as f64 * 0.5),
+                WrappedMeasurementType::U64 => WrappedMeasurementValue::U64(i as u64),
+            };
+            let mut point = MeasurementPoint::new_untyped(timestamp, *metric, resource, consumer, value);
+            for (k, key) in self.attribute_keys.iter().enumerate() {
+                point.add_attr(key.clone(), (i % (k + 2)) as u64);
+            }
+            buf.push(point);
+        }
+        buf
+    }
+}
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that counts the allocations, to report them in benchmarks.
+///
+/// It delegates to the [`System`] allocator. Install it in the benchmark binary:
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOC: alumet::test::bench::CountingAllocator = alumet::test::bench::CountingAllocator;
+/// ```
+/// Without it, [`count_allocations`] always reports zero allocations.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // a reallocation is counted as a new allocation of the additional bytes
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size.saturating_sub(layout.size()) as u64, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+/// Allocations made while running some code, see [`count_allocations`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocationStats {
+    /// Number of allocations, including reallocations.
+    pub allocations: u64,
+    pub deallocations: u64,
+    /// Number of bytes allocated.
+    pub bytes: u64,
+}
+
+impl AllocationStats {
+    fn now() -> Self {
+        Self {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+            bytes: ALLOCATED_BYTES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Divides the stats by the number of iterations.
+    pub fn per_iteration(&self, iterations: u64) -> Self {
+        let iterations = iterations.max(1);
+        Self {
+            allocations: self.allocations / iterations,
+            deallocations: self.deallocations / iterations,
+            bytes: self.bytes / iterations,
+        }
+    }
+}
+
+impl Display for AllocationStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} allocations ({} bytes), {} deallocations",
+            self.allocations, self.bytes, self.deallocations
+        )
+    }
+}
+
+/// Runs `f` and counts the allocations that happen meanwhile.
+///
+/// Requires [`CountingAllocator`] to be the global allocator. The counters are global:
+/// the allocations of the other threads (for instance the tasks of an async output) are included.
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, AllocationStats) {
+    let before = AllocationStats::now();
+    let res = f();
+    let after = AllocationStats::now();
+    let stats = AllocationStats {
+        allocations: after.allocations - before.allocations,
+        deallocations: after.deallocations - before.deallocations,
+        bytes: after.bytes - before.bytes,
+    };
+    (res, stats)
+}
+
+/// Runs `f` several times and prints the average allocations per call on the standard error.
+///
+/// Call it next to the timing benchmark of the same function, so that the report covers both
+/// the time and the memory allocations of the hot path.
+pub fn report_allocations(name: &str, iterations: u64, mut f: impl FnMut()) {
+    // warm up, so that lazy initializations are not counted
+    f();
+    let (_, stats) = count_allocations(|| {
+        for _ in 0..iterations {
+            f();
+        }
+    });
+    eprintln!("{name}: {} per iteration", stats.per_iteration(iterations));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AllocationStats, Cardinality, SyntheticMeasurements};
+    use crate::{
+        measurement::{WrappedMeasurementType, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    #[test]
+    fn generate() {
+        let metrics = vec![
+            (RawMetricId(0), WrappedMeasurementType::F64),
+            (RawMetricId(1), WrappedMeasurementType::U64),
+        ];
+        let cardinality = Cardinality {
+            resources: 2,
+            consumers: 3,
+            attributes: 1,
+        };
+        let synthetic = SyntheticMeasurements::new(metrics, cardinality);
+        let buf = synthetic.generate(12);
+        assert_eq!(buf.len(), 12);
+
+        let points: Vec<_> = buf.iter().collect();
+        assert_eq!(points[0].metric, RawMetricId(0));
+        assert_eq!(points[1].metric, RawMetricId(1));
+        assert_eq!(points[1].value, WrappedMeasurementValue::U64(1));
+        assert_eq!(points[2].resource, Resource::CpuCore { id: 1 });
+        assert_eq!(points[4].consumer, ResourceConsumer::Process { pid: 1001 });
+        assert_eq!(points[11].consumer, ResourceConsumer::Process { pid: 1002 });
+        assert_eq!(points[3].attributes().count(), 1);
+        assert_eq!(points[0].timestamp, points[1].timestamp);
+        assert_ne!(points[1].timestamp, points[2].timestamp);
+
+        // deterministic
+        let again = synthetic.generate(12);
+        assert!(buf.iter().zip(again.iter()).all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn per_iteration() {
+        let stats = AllocationStats {
+            allocations: 10,
+            deallocations: 8,
+            bytes: 1000,
+        };
+        let expected = AllocationStats {
+            allocations: 2,
+            deallocations: 1,
+            bytes: 200,
+        };
+        assert_eq!(stats.per_iteration(5), expected);
+        assert_eq!(stats.per_iteration(0), stats);
+    }
+}
@@ -0,0 +1,50 @@
+//! Sample data for the unit tests of plugins.
+//!
+//! Templates and converters usually need a [`MeasurementPoint`] and the
+//! [`Metric`] it refers to. These functions build both with fixed values,
+//! so that the expected output of a test does not depend on the clock.
+//!
+//! # Example
+//! ```
+//! use alumet::resources::ResourceConsumer;
+//! use alumet::test::fixture;
+//!
+//! let metric = fixture::metric();
+//! let point = fixture::point(ResourceConsumer::LocalMachine).with_attr("domain", "package");
+//! assert_eq!(metric.name, "rapl_consumed_energy");
+//! assert_eq!(point.attributes_len(), 1);
+//! ```
+
+use std::time::SystemTime;
+
+use crate::{
+    measurement::{MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue},
+    metrics::{Metric, RawMetricId},
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+
+/// Returns the metric `rapl_consumed_energy`, an energy in joules of type `F64`.
+pub fn metric() -> Metric {
+    Metric {
+        name: String::from("rapl_consumed_energy"),
+        description: String::new(),
+        value_type: WrappedMeasurementType::F64,
+        unit: PrefixedUnit::from(Unit::Joule),
+        tags: Vec::new(),
+    }
+}
+
+/// Returns a measurement of the [`metric`] with the value `1.0`, taken at the
+/// Unix epoch on the CPU package 1, for the given `consumer`.
+///
+/// The point has no attributes: add them with [`MeasurementPoint::with_attr`].
+pub fn point(consumer: ResourceConsumer) -> MeasurementPoint {
+    MeasurementPoint::new_untyped(
+        Timestamp::from(SystemTime::UNIX_EPOCH),
+        RawMetricId::from_u64(0),
+        Resource::CpuPackage { id: 1 },
+        consumer,
+        WrappedMeasurementValue::F64(1.0),
+    )
+}
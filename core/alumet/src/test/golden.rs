@@ -0,0 +1,458 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    plugin::rust::{AlumetPlugin, serialize_config},
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+
+use super::PluginHarness;
+
+/// Environment variable that, when set to `1`, makes [`assert_golden`] update the golden files
+/// instead of comparing them.
+pub const UPDATE_GOLDEN_ENV: &str = "ALUMET_UPDATE_GOLDEN";
+
+/// Compares the output of a test with the content of a golden file.
+///
+/// If the environment variable [`ALUMET_UPDATE_GOLDEN`](UPDATE_GOLDEN_ENV) is set to `1`, the file is
+/// (re)written with `actual` instead, and the assertion always succeeds. Review the changes with git.
+///
+/// Relative paths are resolved from the current directory, which is the directory of the package
+/// when running `cargo test`.
+///
+/// # Panics
+/// Panics if the file does not exist, or if its content is different from `actual`.
+pub fn assert_golden(path: impl AsRef<Path>, actual: impl AsRef<str>) {
+    let path = path.as_ref();
+    let actual = actual.as_ref();
+    if std::env::var(UPDATE_GOLDEN_ENV).is_ok_and(|v| v == "1") {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(path, actual).unwrap_or_else(|e| panic!("failed to write {}: {e}", path.display()));
+        return;
+    }
+    let expected = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => panic!(
+            "failed to read golden file {}: {e}\nRun the test with {UPDATE_GOLDEN_ENV}=1 to create it.",
+            path.display()
+        ),
+    };
+    assert!(
+        expected == actual,
+        "the output does not match the golden file {}\n--- expected\n{expected}\n--- actual\n{actual}\n---\nRun the test with {UPDATE_GOLDEN_ENV}=1 to update the file.",
+        path.display()
+    );
+}
+
+/// Compares a JSON document with the content of a golden file, regardless of the order of the keys.
+///
+/// The order of the keys of a parsed JSON object depends on the `preserve_order` feature of `serde_json`,
+/// which Cargo enables for every crate of the build as soon as one of them needs it. Therefore, the
+/// golden file is compared with `actual` as a [`serde_json::Value`], not as a string.
+///
+/// Like [`assert_golden`], the file is (re)written if [`ALUMET_UPDATE_GOLDEN`](UPDATE_GOLDEN_ENV) is set to `1`.
+///
+/// # Panics
+/// Panics if `actual` or the file is not valid JSON, if the file does not exist, or if the documents are different.
+pub fn assert_golden_json(path: impl AsRef<Path>, actual: impl AsRef<[u8]>) {
+    let path = path.as_ref();
+    let actual: serde_json::Value = serde_json::from_slice(actual.as_ref()).expect("the output should be valid JSON");
+    let actual_pretty = serde_json::to_string_pretty(&actual).unwrap() + "\n";
+    if std::env::var(UPDATE_GOLDEN_ENV).is_ok_and(|v| v == "1") {
+        assert_golden(path, actual_pretty);
+        return;
+    }
+    let expected = match std::fs::read(path) {
+        Ok(content) => content,
+        Err(e) => panic!(
+            "failed to read golden file {}: {e}\nRun the test with {UPDATE_GOLDEN_ENV}=1 to create it.",
+            path.display()
+        ),
+    };
+    let expected: serde_json::Value =
+        serde_json::from_slice(&expected).unwrap_or_else(|e| panic!("invalid golden file {}: {e}", path.display()));
+    assert!(
+        expected == actual,
+        "the output does not match the golden file {}\n--- expected\n{}\n--- actual\n{actual_pretty}\n---\nRun the test with {UPDATE_GOLDEN_ENV}=1 to update the file.",
+        path.display(),
+        serde_json::to_string_pretty(&expected).unwrap(),
+    );
+}
+
+/// A deterministic set of measurements, to snapshot-test the format of an output.
+///
+/// The measurements cover both value types, several resources and consumers, and attributes.
+/// Their timestamps are fixed, so that the output is the same on every run.
+pub struct SampleMeasurements {
+    /// Metric `sample_energy`, in joules.
+    pub energy: TypedMetricId<f64>,
+    /// Metric `sample_count`, without unit.
+    pub count: TypedMetricId<u64>,
+}
+
+impl SampleMeasurements {
+    /// Timestamp of the first measurement: 2023-11-14T22:13:20Z.
+    pub const START: Duration = Duration::from_secs(1_700_000_000);
+
+    /// Starts the plugin `P` with the given config, and registers the metrics of the sample.
+    ///
+    /// This is how the tests of an output usually begin.
+    pub fn start<P: AlumetPlugin>(config: impl Serialize) -> anyhow::Result<(PluginHarness<P>, Self)> {
+        let harness = PluginHarness::<P>::start(serialize_config(config)?)?;
+        let sample = Self::register(&harness)?;
+        Ok((harness, sample))
+    }
+
+    /// Registers the metrics of the sample in the registry of the harness.
+    pub fn register<P: AlumetPlugin>(harness: &PluginHarness<P>) -> anyhow::Result<Self> {
+        Ok(Self {
+            energy: harness.create_metric("sample_energy", Unit::Joule, "energy consumed by the resource")?,
+            count: harness.create_metric("sample_count", Unit::Unity, "number of events")?,
+        })
+    }
+
+    /// Returns the measurements of the sample.
+    ///
+    /// Each call returns exactly the same measurements.
+    pub fn measurements(&self) -> MeasurementBuffer {
+        let t0 = Timestamp::from(UNIX_EPOCH + Self::START);
+        let t1 = t0 + Duration::from_millis(500);
+        let t2 = t0 + Duration::from_secs(1);
+        MeasurementBuffer::from(vec![
+            MeasurementPoint::new(
+                t0,
+                self.energy,
+                Resource::CpuPackage { id: 0 },
+                ResourceConsumer::LocalMachine,
+                12.5,
+            ),
+            MeasurementPoint::new(
+                t0,
+                self.energy,
+                Resource::Dram { pkg_id: 0 },
+                ResourceConsumer::LocalMachine,
+                3.25,
+            ),
+            MeasurementPoint::new(
+                t1,
+                self.count,
+                Resource::LocalMachine,
+                ResourceConsumer::Process { pid: 1234 },
+                42,
+            )
+            .with_attr("state", "running")
+            .with_attr("cpu", 3_u64),
+            MeasurementPoint::new(
+                t2,
+                self.energy,
+                Resource::Gpu {
+                    bus_id: "0000:01:00.0".into(),
+                },
+                ResourceConsumer::LocalMachine,
+                150.0,
+            )
+            .with_attr("model", "test-gpu"),
+            MeasurementPoint::new(
+                t2,
+                self.count,
+                Resource::CpuCore { id: 3 },
+                ResourceConsumer::ControlGroup {
+                    path: "/system.slice/test.service".into(),
+                },
+                7,
+            )
+            .with_attr("throttled", true),
+        ])
+    }
+}
+
+/// An HTTP request received by [`HttpCapture`].
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub method: String,
+    /// Path and query of the request, for instance `/api/v1/write?db=alumet`.
+    pub path: String,
+    /// Headers of the request, with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl CapturedRequest {
+    /// Returns the value of a header, if present.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers.iter().find(|(k, _)| *k == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the body as a string.
+    ///
+    /// # Panics
+    /// Panics if the body is not valid UTF-8.
+    pub fn body_str(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("the body should be valid UTF-8")
+    }
+}
+
+/// A minimal HTTP server that records the requests it receives.
+///
+/// Point the output under test to [`url`](Self::url), write some measurements, then inspect the
+/// [`requests`](Self::requests) that the output has sent. Every request receives the same response,
+/// chosen at creation (by default, an empty response with the status `200 OK`). The server stops when the `HttpCapture` is dropped.
+///
+/// Only HTTP/1.1 without TLS is supported, with a `Content-Length` or a chunked body.
+pub struct HttpCapture {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<CapturedRequest>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpCapture {
+    /// Starts a server that responds `200 OK` to every request.
+    pub fn start() -> io::Result<Self> {
+        Self::with_status(200)
+    }
+
+    /// Starts a server that responds with the given status code to every request.
+    pub fn with_status(status: u16) -> io::Result<Self> {
+        Self::with_response(status, Vec::new())
+    }
+
+    /// Starts a server that responds with the given status code and body to every request.
+    pub fn with_response(status: u16, body: impl Into<Vec<u8>>) -> io::Result<Self> {
+        let body: Arc<[u8]> = body.into().into();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let requests = requests.clone();
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let requests = requests.clone();
+                    let body = body.clone();
+                    std::thread::spawn(move || {
+                        if let Err(e) = serve_connection(stream, status, &body, &requests) {
+                            log::warn!("HttpCapture: connection error: {e}");
+                        }
+                    });
+                }
+            })
+        };
+        Ok(Self {
+            addr,
+            requests,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the base URL of the server, for instance `http://127.0.0.1:45678`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Returns the requests received so far.
+    pub fn requests(&self) -> Vec<CapturedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns and forgets the requests received so far.
+    pub fn take_requests(&self) -> Vec<CapturedRequest> {
+        std::mem::take(&mut self.requests.lock().unwrap())
+    }
+}
+
+impl Drop for HttpCapture {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // wake up the listener
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Reads the requests of a connection until it is closed.
+fn serve_connection(
+    stream: TcpStream,
+    status: u16,
+    body: &[u8],
+    requests: &Mutex<Vec<CapturedRequest>>,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    while let Some(request) = read_request(&mut reader)? {
+        // record the request before responding, so that it is visible as soon as the client gets the response
+        requests.lock().unwrap().push(request);
+        let reason = if (200..300).contains(&status) { "OK" } else { "Error" };
+        write!(
+            writer,
+            "HTTP/1.1 {status} {reason}\r\ncontent-length: {}\r\n\r\n",
+            body.len()
+        )?;
+        writer.write_all(body)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Reads one HTTP request, or returns `None` if the connection has been closed.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<CapturedRequest>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid request line"));
+    };
+    let (method, path) = (method.to_owned(), path.to_owned());
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_owned()));
+        }
+    }
+
+    let header = |name: &str| headers.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    let body = if header("transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+        read_chunked(reader)?
+    } else {
+        let len = header("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+        body
+    };
+    Ok(Some(CapturedRequest {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.trim_end().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))?;
+        if size == 0 {
+            // skip the trailers
+            loop {
+                line.clear();
+                reader.read_line(&mut line)?;
+                if line.trim_end().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor, Read, Write};
+    use std::net::TcpStream;
+
+    use super::{HttpCapture, assert_golden_json, read_request};
+
+    #[test]
+    fn parse_requests() {
+        let data = "POST /write?db=x HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello\
+                    PUT /chunked HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;ext=1\r\nde\r\n0\r\n\r\n";
+        let mut reader = BufReader::new(Cursor::new(data));
+
+        let req = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/write?db=x");
+        assert_eq!(req.header("HOST"), Some("localhost"));
+        assert_eq!(req.body_str(), "hello");
+
+        let req = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(req.method, "PUT");
+        assert_eq!(req.body_str(), "abcde");
+
+        assert!(read_request(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn capture() {
+        let server = HttpCapture::with_status(204).unwrap();
+        let mut stream = TcpStream::connect(server.url().trim_start_matches("http://")).unwrap();
+        stream
+            .write_all(b"POST /a HTTP/1.1\r\nContent-Length: 2\r\n\r\nokPOST /b HTTP/1.1\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert_eq!(response.matches("HTTP/1.1 204").count(), 2);
+
+        let requests = server.take_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!((requests[0].path.as_str(), requests[0].body_str()), ("/a", "ok"));
+        assert_eq!(requests[1].path, "/b");
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn golden_json_ignores_key_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.json");
+        std::fs::write(&path, "{\"b\": 1, \"a\": {\"y\": [1, 2], \"x\": null}}").unwrap();
+        assert_golden_json(&path, r#"{"a":{"x":null,"y":[1,2]},"b":1}"#);
+
+        let res = std::panic::catch_unwind(|| assert_golden_json(&path, r#"{"a":{"x":null,"y":[2,1]},"b":1}"#));
+        assert!(res.is_err(), "the order of the arrays matters");
+    }
+
+    #[test]
+    fn response_body() {
+        let server = HttpCapture::with_response(400, r#"{"error":"x"}"#).unwrap();
+        let mut stream = TcpStream::connect(server.url().trim_start_matches("http://")).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.ends_with("content-length: 13\r\n\r\n{\"error\":\"x\"}"));
+    }
+}
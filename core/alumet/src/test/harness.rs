@@ -0,0 +1,575 @@
+use std::{
+    marker::PhantomData,
+    ops::DerefMut,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use anyhow::{Context, anyhow};
+use futures::StreamExt;
+use tokio::{runtime::Runtime, sync::mpsc, task::JoinHandle};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    measurement::{MeasurementBuffer, MeasurementPoint, MeasurementType, Timestamp, WrappedMeasurementValue},
+    metrics::{
+        Metric, RawMetricId, TypedMetricId,
+        duplicate::DuplicateReaction,
+        online::{MetricReader, MetricRegistryControl, MetricSender},
+        registry::MetricRegistry,
+    },
+    pipeline::{
+        self, Output, Source, Transform,
+        elements::{
+            error::{PollError, TransformError, WriteError},
+            output::{
+                AsyncOutputStream, OutputContext,
+                builder::{AsyncOutputBuildContext, BlockingOutputBuildContext, OutputBuilder},
+            },
+            source::{
+                builder::{AutonomousSourceBuildContext, ManagedSourceBuildContext, SourceBuilder},
+                trigger::TriggerSpec,
+            },
+            transform::{TransformContext, builder::TransformBuildContext},
+        },
+        naming::{OutputName, PluginName, SourceName, TransformName},
+    },
+    plugin::{AlumetPluginStart, AlumetPreStart, ConfigTable, Plugin, rust::AlumetPlugin},
+    units::PrefixedUnit,
+};
+
+/// Initial value of the virtual clock of the sources.
+const VIRTUAL_EPOCH: Duration = Duration::from_secs(1_700_000_000);
+
+/// Runs a plugin outside of any measurement pipeline, and gives a direct access to its sources,
+/// transforms and outputs.
+///
+/// Unlike [`RuntimeExpectations`](super::RuntimeExpectations), the harness does not start an agent:
+/// the plugin is initialized and started, then its elements are built on demand and called directly
+/// by the test, with the inputs that it chooses. Managed sources are polled with a virtual clock.
+///
+/// The `on_pipeline_start` actions and [`post_pipeline_start`](Plugin::post_pipeline_start) are not
+/// called, since there is no pipeline. Use [`RuntimeExpectations`](super::RuntimeExpectations) to test them.
+///
+/// The harness blocks on its own async runtime: use it in a normal `#[test]`, not in a `#[tokio::test]`.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use alumet::measurement::WrappedMeasurementValue;
+/// use alumet::plugin::rust::AlumetPlugin;
+/// use alumet::test::PluginHarness;
+///
+/// # struct MyPlugin;
+/// # impl AlumetPlugin for MyPlugin {
+/// #     fn name() -> &'static str { "my-plugin" }
+/// #     fn version() -> &'static str { "0.1.0" }
+/// #     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> { Ok(None) }
+/// #     fn init(_: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> { todo!() }
+/// #     fn start(&mut self, _: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> { todo!() }
+/// #     fn stop(&mut self) -> anyhow::Result<()> { todo!() }
+/// # }
+/// # fn main() -> anyhow::Result<()> {
+/// let mut harness = PluginHarness::<MyPlugin>::start_with_default_config()?;
+///
+/// // poll the source every second (according to its trigger), for 5 virtual seconds
+/// let mut source = harness.source("counter")?;
+/// let measurements = source.run_for(Duration::from_secs(5))?;
+///
+/// // check the measurements
+/// let values = harness.values(&measurements, "coffee_counter");
+/// assert_eq!(values.len(), 5);
+/// assert_eq!(values[0], WrappedMeasurementValue::U64(1));
+///
+/// harness.stop()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PluginHarness<P: AlumetPlugin> {
+    // The runtime is dropped first, so that its tasks don't see the other fields disappear.
+    runtime: Runtime,
+    plugin: Box<P>,
+    plugin_name: PluginName,
+    pipeline_builder: pipeline::Builder,
+    metrics_reader: MetricReader,
+    metrics_sender: MetricSender,
+    shutdown: CancellationToken,
+    state_root: StateRoot,
+}
+
+/// Parent of the state directory of the plugin, deleted with the harness if it is temporary.
+struct StateRoot {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl Drop for StateRoot {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+impl<P: AlumetPlugin> PluginHarness<P> {
+    /// Initializes and starts the plugin with its default configuration.
+    pub fn start_with_default_config() -> anyhow::Result<Self> {
+        let config = P::default_config()?.unwrap_or_else(|| ConfigTable(toml::Table::new()));
+        Self::start(config)
+    }
+
+    /// Initializes and starts the plugin with the given configuration.
+    ///
+    /// The pre-pipeline-start phase is also executed.
+    /// The [state directory](crate::plugin::state) of the plugin is temporary, it is deleted when the harness is dropped.
+    pub fn start(config: ConfigTable) -> anyhow::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("alumet-harness-{}-{n}", std::process::id()));
+        Self::start_impl(config, StateRoot { path, temporary: true })
+    }
+
+    /// Initializes and starts the plugin with the given configuration, and stores the
+    /// [state](crate::plugin::state) of the plugin in `state_root`.
+    ///
+    /// Use this to check what the plugin does with the state left by a previous run.
+    pub fn start_with_state(config: ConfigTable, state_root: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let state_root = StateRoot {
+            path: state_root.into(),
+            temporary: false,
+        };
+        Self::start_impl(config, state_root)
+    }
+
+    fn start_impl(config: ConfigTable, state_root: StateRoot) -> anyhow::Result<Self> {
+        let mut plugin = P::init(config).context("plugin init failed")?;
+        let plugin_name = PluginName(P::name().to_owned());
+
+        let mut pipeline_builder = pipeline::Builder::new();
+        let mut pre_start_actions = Vec::new();
+        let mut post_start_actions = Vec::new();
+        let mut ctx = AlumetPluginStart {
+            current_plugin: plugin_name.clone(),
+            pipeline_builder: &mut pipeline_builder,
+            pre_start_actions: &mut pre_start_actions,
+            post_start_actions: &mut post_start_actions,
+            state_root: &state_root.path,
+        };
+        Plugin::start(plugin.deref_mut(), &mut ctx).context("plugin start failed")?;
+
+        let mut ctx = AlumetPreStart {
+            current_plugin: plugin_name.clone(),
+            pipeline_builder: &mut pipeline_builder,
+        };
+        for (_, action) in pre_start_actions {
+            action(&mut ctx).context("plugin pre-pipeline-start action failed")?;
+        }
+        Plugin::pre_pipeline_start(plugin.deref_mut(), &mut ctx).context("plugin pre_pipeline_start failed")?;
+
+        // Start the metric registry, for the elements that register metrics at runtime.
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let shutdown = CancellationToken::new();
+        let mut registry_control = MetricRegistryControl::new(pipeline_builder.metrics.clone());
+        registry_control.create_listeners(pipeline_builder.take_metric_listeners(), runtime.handle())?;
+        let (metrics_sender, metrics_access, _) = registry_control.start(shutdown.clone(), runtime.handle());
+
+        Ok(Self {
+            runtime,
+            plugin,
+            plugin_name,
+            pipeline_builder,
+            metrics_reader: metrics_access.into_read_only(),
+            metrics_sender,
+            shutdown,
+            state_root,
+        })
+    }
+
+    /// Stops the plugin.
+    pub fn stop(mut self) -> anyhow::Result<()> {
+        self.shutdown.cancel();
+        self.plugin.stop()
+    }
+
+    /// Returns the parent of the state directory of the plugin.
+    pub fn state_root(&self) -> &Path {
+        &self.state_root.path
+    }
+
+    /// Returns the plugin under test.
+    pub fn plugin(&mut self) -> &mut P {
+        &mut self.plugin
+    }
+
+    /// Returns the current state of the metric registry.
+    pub fn metrics(&self) -> MetricRegistry {
+        self.metrics_reader.blocking_read().clone()
+    }
+
+    /// Registers a new metric, for instance to give measurements to an output.
+    pub fn create_metric<T: MeasurementType>(
+        &self,
+        name: impl Into<String>,
+        unit: impl Into<PrefixedUnit>,
+        description: impl Into<String>,
+    ) -> anyhow::Result<TypedMetricId<T>> {
+        let metric = Metric {
+            name: name.into(),
+            description: description.into(),
+            value_type: T::wrapped_type(),
+            unit: unit.into(),
+            tags: Vec::new(),
+        };
+        let mut res = self
+            .runtime
+            .block_on(
+                self.metrics_sender
+                    .create_metrics(vec![metric], DuplicateReaction::Error),
+            )
+            .map_err(|e| anyhow!("failed to register the metric: {e}"))?;
+        let id = res.pop().expect("there should be one result per metric")?;
+        Ok(TypedMetricId(id, PhantomData))
+    }
+
+    /// Returns the id of a metric.
+    ///
+    /// # Panics
+    /// Panics if there is no metric with this name.
+    pub fn metric_id(&self, name: &str) -> RawMetricId {
+        match self.metrics_reader.blocking_read().by_name(name) {
+            Some((id, _)) => id,
+            None => panic!("metric {name:?} does not exist"),
+        }
+    }
+
+    /// Returns the points of `measurements` that belong to the metric `name`.
+    ///
+    /// # Panics
+    /// Panics if there is no metric with this name.
+    pub fn points<'a>(&self, measurements: &'a MeasurementBuffer, name: &str) -> Vec<&'a MeasurementPoint> {
+        let id = self.metric_id(name);
+        measurements.iter().filter(|p| p.metric == id).collect()
+    }
+
+    /// Returns the values of the points of `measurements` that belong to the metric `name`.
+    ///
+    /// # Panics
+    /// Panics if there is no metric with this name.
+    pub fn values(&self, measurements: &MeasurementBuffer, name: &str) -> Vec<WrappedMeasurementValue> {
+        self.points(measurements, name)
+            .into_iter()
+            .map(|p| p.value.clone())
+            .collect()
+    }
+
+    /// Builds the source `name` of the plugin.
+    ///
+    /// Each source can only be built once.
+    pub fn source(&mut self, name: &str) -> anyhow::Result<TestSource> {
+        let full_name = SourceName::new(self.plugin_name.0.clone(), name.to_owned());
+        let builder = self
+            .pipeline_builder
+            .take_source(&full_name, false)
+            .map_err(|_| anyhow!("{full_name} is an autonomous source, use autonomous_source() instead"))?;
+        let Some(SourceBuilder::Managed(builder)) = builder else {
+            return Err(anyhow!("source {full_name} not found (or already built)"));
+        };
+        let registry = self.metrics_reader.blocking_read();
+        let source = builder(&mut self.context(&registry)).with_context(|| format!("failed to build {full_name}"))?;
+        Ok(TestSource {
+            source: source.source,
+            trigger: source.trigger_spec,
+            now: Timestamp::from(UNIX_EPOCH + VIRTUAL_EPOCH),
+        })
+    }
+
+    /// Builds and starts the autonomous source `name` of the plugin.
+    pub fn autonomous_source(&mut self, name: &str) -> anyhow::Result<TestAutonomousSource> {
+        let full_name = SourceName::new(self.plugin_name.0.clone(), name.to_owned());
+        let builder = self
+            .pipeline_builder
+            .take_source(&full_name, true)
+            .map_err(|_| anyhow!("{full_name} is a managed source, use source() instead"))?;
+        let Some(SourceBuilder::Autonomous(builder)) = builder else {
+            return Err(anyhow!("source {full_name} not found (or already built)"));
+        };
+        let registry = self.metrics_reader.blocking_read();
+        let (tx, rx) = mpsc::channel(64);
+        let shutdown = self.shutdown.child_token();
+        let source = builder(&mut self.context(&registry), shutdown.clone(), tx)
+            .with_context(|| format!("failed to build {full_name}"))?;
+        let task = self.runtime.spawn(source);
+        Ok(TestAutonomousSource {
+            rx,
+            shutdown,
+            task,
+            runtime: self.runtime.handle().clone(),
+        })
+    }
+
+    /// Builds the transform `name` of the plugin.
+    pub fn transform(&mut self, name: &str) -> anyhow::Result<TestTransform> {
+        let full_name = TransformName::new(self.plugin_name.0.clone(), name.to_owned());
+        let builder = self
+            .pipeline_builder
+            .take_transform(&full_name)
+            .ok_or_else(|| anyhow!("transform {full_name} not found (or already built)"))?;
+        let registry = self.metrics_reader.blocking_read();
+        let transform =
+            builder(&mut self.context(&registry)).with_context(|| format!("failed to build {full_name}"))?;
+        Ok(TestTransform {
+            transform,
+            metrics: self.metrics_reader.clone(),
+        })
+    }
+
+    /// Builds the output `name` of the plugin.
+    ///
+    /// Async outputs are started on the runtime of the harness.
+    pub fn output(&mut self, name: &str) -> anyhow::Result<TestOutput> {
+        let full_name = OutputName::new(self.plugin_name.0.clone(), name.to_owned());
+        let builder = self
+            .pipeline_builder
+            .take_output(&full_name)
+            .ok_or_else(|| anyhow!("output {full_name} not found (or already built)"))?;
+        let registry = self.metrics_reader.blocking_read();
+        let mut ctx = self.context(&registry);
+        let inner = match builder {
+            OutputBuilder::Blocking(builder) => {
+                let output = builder(&mut ctx).with_context(|| format!("failed to build {full_name}"))?;
+                TestOutputInner::Blocking {
+                    output,
+                    metrics: self.metrics_reader.clone(),
+                }
+            }
+            OutputBuilder::Async(builder) => {
+                let (tx, rx) = mpsc::channel(64);
+                let stream = AsyncOutputStream(Box::pin(ReceiverStream::new(rx).map(Ok)));
+                let output = builder(&mut ctx, stream).with_context(|| format!("failed to build {full_name}"))?;
+                TestOutputInner::Async {
+                    tx,
+                    task: self.runtime.spawn(output),
+                    runtime: self.runtime.handle().clone(),
+                }
+            }
+        };
+        Ok(TestOutput { inner })
+    }
+
+    fn context<'a>(&'a self, registry: &'a MetricRegistry) -> HarnessContext<'a> {
+        HarnessContext {
+            registry,
+            reader: &self.metrics_reader,
+            sender: &self.metrics_sender,
+            runtime: self.runtime.handle(),
+        }
+    }
+}
+
+/// A managed source under test, polled with a virtual clock.
+pub struct TestSource {
+    source: Box<dyn Source>,
+    trigger: TriggerSpec,
+    now: Timestamp,
+}
+
+impl TestSource {
+    /// Returns the trigger that the plugin has chosen for this source.
+    pub fn trigger(&self) -> &TriggerSpec {
+        &self.trigger
+    }
+
+    /// Returns the current time of the virtual clock.
+    pub fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    /// Moves the virtual clock forward, without polling the source.
+    pub fn advance(&mut self, duration: Duration) {
+        self.now = self.now + duration;
+    }
+
+    /// Polls the source once, at the current time of the virtual clock.
+    pub fn poll(&mut self) -> Result<MeasurementBuffer, PollError> {
+        let mut buf = MeasurementBuffer::new();
+        self.source.poll(&mut buf.as_accumulator(), self.now)?;
+        Ok(buf)
+    }
+
+    /// Moves the virtual clock forward by `duration`, and polls the source at each tick of its trigger.
+    ///
+    /// Fails if the source is not triggered at regular intervals.
+    pub fn run_for(&mut self, duration: Duration) -> anyhow::Result<MeasurementBuffer> {
+        let interval = self
+            .trigger
+            .poll_interval()
+            .filter(|i| !i.is_zero())
+            .ok_or_else(|| anyhow!("the source is not triggered at regular intervals"))?;
+        let end = self.now + duration;
+        let mut buf = MeasurementBuffer::new();
+        while self.now + interval <= end {
+            self.advance(interval);
+            let mut polled = self
+                .poll()
+                .map_err(|e| anyhow!("poll failed at {:?}: {e:?}", self.now))?;
+            buf.merge(&mut polled);
+        }
+        self.now = end;
+        Ok(buf)
+    }
+}
+
+/// An autonomous source under test, running on the runtime of the harness.
+pub struct TestAutonomousSource {
+    rx: mpsc::Receiver<MeasurementBuffer>,
+    shutdown: CancellationToken,
+    task: JoinHandle<anyhow::Result<()>>,
+    runtime: tokio::runtime::Handle,
+}
+
+impl TestAutonomousSource {
+    /// Waits for the next measurements sent by the source.
+    ///
+    /// Returns `None` if the source has stopped or if nothing is received before the timeout.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<MeasurementBuffer> {
+        // the timer must be created inside the runtime
+        self.runtime
+            .block_on(async { tokio::time::timeout(timeout, self.rx.recv()).await })
+            .ok()
+            .flatten()
+    }
+
+    /// Stops the source and waits for it to finish.
+    pub fn stop(self) -> anyhow::Result<()> {
+        self.shutdown.cancel();
+        self.runtime.block_on(self.task)?
+    }
+}
+
+/// A transform under test.
+pub struct TestTransform {
+    transform: Box<dyn Transform>,
+    metrics: MetricReader,
+}
+
+impl TestTransform {
+    /// Applies the transform to the measurements and returns the result.
+    pub fn apply(&mut self, mut measurements: MeasurementBuffer) -> Result<MeasurementBuffer, TransformError> {
+        let registry = self.metrics.blocking_read();
+        let ctx = TransformContext { metrics: &registry };
+        self.transform.apply(&mut measurements, &ctx)?;
+        Ok(measurements)
+    }
+
+    /// Calls [`Transform::finish`], like the pipeline does when it stops.
+    pub fn finish(&mut self) -> Result<(), TransformError> {
+        let registry = self.metrics.blocking_read();
+        let ctx = TransformContext { metrics: &registry };
+        self.transform.finish(&ctx)
+    }
+}
+
+/// An output under test.
+pub struct TestOutput {
+    inner: TestOutputInner,
+}
+
+enum TestOutputInner {
+    Blocking {
+        output: Box<dyn Output>,
+        metrics: MetricReader,
+    },
+    Async {
+        tx: mpsc::Sender<MeasurementBuffer>,
+        task: JoinHandle<anyhow::Result<()>>,
+        runtime: tokio::runtime::Handle,
+    },
+}
+
+impl TestOutput {
+    /// Gives some measurements to the output.
+    ///
+    /// A blocking output writes them before this function returns.
+    /// An async output receives them in the background: call [`finish`](Self::finish) to wait for it.
+    pub fn write(&mut self, measurements: &MeasurementBuffer) -> Result<(), WriteError> {
+        match &mut self.inner {
+            TestOutputInner::Blocking { output, metrics } => {
+                let registry = metrics.blocking_read();
+                output.write(measurements, &OutputContext { metrics: &registry })
+            }
+            TestOutputInner::Async { tx, .. } => tx
+                .blocking_send(measurements.clone())
+                .map_err(|_| WriteError::Fatal(anyhow!("the async output has stopped"))),
+        }
+    }
+
+    /// Closes the input of the output, and waits for it to finish.
+    pub fn finish(self) -> anyhow::Result<()> {
+        match self.inner {
+            TestOutputInner::Blocking { .. } => Ok(()),
+            TestOutputInner::Async { tx, task, runtime } => {
+                drop(tx);
+                runtime.block_on(task)?
+            }
+        }
+    }
+}
+
+/// Context given to the builders of the elements.
+struct HarnessContext<'a> {
+    registry: &'a MetricRegistry,
+    reader: &'a MetricReader,
+    sender: &'a MetricSender,
+    runtime: &'a tokio::runtime::Handle,
+}
+
+impl ManagedSourceBuildContext for HarnessContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.registry.by_name(name)
+    }
+}
+
+impl AutonomousSourceBuildContext for HarnessContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.registry.by_name(name)
+    }
+
+    fn metrics_reader(&self) -> MetricReader {
+        self.reader.clone()
+    }
+
+    fn metrics_sender(&self) -> MetricSender {
+        self.sender.clone()
+    }
+}
+
+impl TransformBuildContext for HarnessContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.registry.by_name(name)
+    }
+
+    fn metrics(&self) -> &MetricRegistry {
+        self.registry
+    }
+}
+
+impl BlockingOutputBuildContext for HarnessContext<'_> {
+    fn metric_by_name(&self, name: &str) -> Option<(RawMetricId, &Metric)> {
+        self.registry.by_name(name)
+    }
+}
+
+impl AsyncOutputBuildContext for HarnessContext<'_> {
+    fn async_runtime(&self) -> &tokio::runtime::Handle {
+        self.runtime
+    }
+
+    fn metrics_reader(&self) -> MetricReader {
+        self.reader.clone()
+    }
+}
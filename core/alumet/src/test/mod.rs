@@ -28,11 +28,24 @@
 //! alumet = {version = "version", features = ["test"]}
 //! ```
 
+/// Benchmarks of pipeline elements with synthetic measurements.
+pub mod bench;
+
+/// Sample metrics and measurement points for unit tests.
+pub mod fixture;
+
+/// Snapshot tests of the data written by outputs.
+pub mod golden;
+
+/// Tests of individual pipeline elements, without a measurement pipeline.
+pub mod harness;
+
 /// Tests performed while the measurement pipeline is running.
 pub mod runtime;
 
 /// Tests performed at startup.
 pub mod startup;
 
+pub use harness::PluginHarness;
 pub use runtime::RuntimeExpectations;
 pub use startup::StartupExpectations;
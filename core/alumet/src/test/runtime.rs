@@ -599,6 +599,7 @@ impl RuntimeExpectations {
             description: "".into(),
             value_type: T::wrapped_type(),
             unit: unit.into(),
+            tags: Vec::new(),
         };
         self.metrics_to_create.push(m);
         self
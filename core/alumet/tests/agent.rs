@@ -1,4 +1,5 @@
 use std::{
+    path::PathBuf,
     sync::{Arc, atomic::Ordering},
     time::Duration,
 };
@@ -7,15 +8,17 @@ use alumet::{
     agent::{
         self,
         config::{AutoDefaultConfigProvider, DefaultConfigProvider},
-        plugin::PluginSet,
+        plugin::{PluginInfo, PluginSet},
     },
+    pipeline::{self, naming::TransformName},
     plugin::{
         AlumetPluginStart, ConfigTable, PluginMetadata,
-        rust::{AlumetPlugin, serialize_config},
+        capability::{Capability, CapabilityPolicy},
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
     static_plugins,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 mod common;
 use common::test_plugin::{AtomicState, MeasurementCounters, State, TestPlugin};
@@ -30,12 +33,95 @@ fn static_plugin_macro() {
     assert_eq!(1, single.len());
     assert_eq!("name", single[0].name);
     assert_eq!("version", single[0].version);
+    let capabilities = (single[0].capabilities)(&ConfigTable::default()).unwrap();
+    assert_eq!(Some(vec![Capability::Network]), capabilities);
 
     // Accept single identifiers and qualified paths.
     let multiple = static_plugins![MyPlugin, self::MyPlugin];
     assert_eq!(2, multiple.len());
 }
 
+#[test]
+fn capability_policy() {
+    let plugins = PluginSet::from(static_plugins![MyPlugin]);
+    let policy = CapabilityPolicy {
+        network: false,
+        ..CapabilityPolicy::allow_all()
+    };
+    // The plugin must be refused before its initialization (which would panic).
+    let res = agent::Builder::new(plugins).capability_policy(policy).build_and_start();
+    let err = res.err().expect("the plugin should be refused");
+    assert!(err.to_string().contains("not allowed by the policy: network"), "{err}");
+}
+
+#[test]
+fn capability_policy_config() {
+    let plugins = |path: &str| {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<FilePlugin>(),
+            enabled: true,
+            config: Some(toml! { path = path }),
+        });
+        plugins
+    };
+    let policy = || CapabilityPolicy {
+        allowed_paths: Some(vec![PathBuf::from("/tmp")]),
+        ..CapabilityPolicy::allow_all()
+    };
+
+    // The path of the config is checked, not the path of the default config.
+    let res = agent::Builder::new(plugins("/etc/passwd"))
+        .capability_policy(policy())
+        .build_and_start();
+    let err = res.err().expect("the plugin should be refused");
+    assert!(err.to_string().contains("filesystem:/etc/passwd"), "{err}");
+
+    let res = agent::Builder::new(plugins("/tmp/../etc/passwd"))
+        .capability_policy(policy())
+        .build_and_start();
+    let err = res.err().expect("the plugin should be refused");
+    assert!(err.to_string().contains("not allowed by the policy"), "{err}");
+
+    let res = agent::Builder::new(plugins("/tmp/alumet-test.out"))
+        .capability_policy(policy())
+        .build_and_start();
+    let err = res.err().expect("the plugin init fails");
+    assert!(format!("{err:#}").contains("init called"), "{err:#}");
+}
+
+#[test]
+fn capability_policy_undeclared() {
+    let plugins = || {
+        vec![PluginMetadata {
+            name: "undeclared".to_owned(),
+            version: "0.0.1".to_owned(),
+            init: Box::new(|_| Err(anyhow::anyhow!("init called"))),
+            default_config: Box::new(|| Ok(None)),
+            capabilities: Box::new(|_| Ok(None)),
+            config_comments: Vec::new(),
+            log_target: None,
+        }]
+    };
+    // A plugin that does not declare its capabilities could require anything.
+    let policy = CapabilityPolicy {
+        msr: false,
+        ..CapabilityPolicy::allow_all()
+    };
+    let res = agent::Builder::new(PluginSet::from(plugins()))
+        .capability_policy(policy)
+        .build_and_start();
+    let err = res.err().expect("the plugin should be refused");
+    assert!(err.to_string().contains("does not declare its capabilities"), "{err}");
+
+    // Everything is allowed, including undeclared capabilities.
+    let res = agent::Builder::new(PluginSet::from(plugins()))
+        .capability_policy(CapabilityPolicy::allow_all())
+        .build_and_start();
+    let err = res.err().expect("the plugin init fails");
+    assert!(format!("{err:#}").contains("init called"), "{err:#}");
+}
+
 #[test]
 fn default_config_no_plugin() {
     let plugins = PluginSet::from(Vec::new()); // empty set
@@ -67,6 +153,29 @@ fn default_config_1_plugin() {
     assert_eq!(config, expected);
 }
 
+#[test]
+fn default_config_string_comments() {
+    let plugins = PluginSet::from(static_plugins![MyPlugin]);
+    let config = AutoDefaultConfigProvider::new(&plugins, MyAgentConfig::default)
+        .with_general_comments([("global_setting", "A setting that applies to everything.")])
+        .default_config_string()
+        .unwrap();
+    let expected = "\
+# A setting that applies to everything.
+global_setting = \"default\"
+
+# Plugin name vversion
+# Requires: network
+[plugins.name]
+# Items of the list.
+# They are kept in order.
+list = [\"default-item\"]
+# Number of things.
+count = 42
+";
+    assert!(config.ends_with(expected), "unexpected config:\n{config}");
+}
+
 #[test]
 fn test_plugin_lifecycle() {
     // Create two TestPlugins with a different name.
@@ -85,12 +194,18 @@ fn test_plugin_lifecycle() {
             version: "0.0.1".to_owned(),
             init: Box::new(move |_| Ok(TestPlugin::init("plugin1", 98, state1_meta, c1_meta))),
             default_config: Box::new(|| Ok(None)),
+            capabilities: Box::new(|_| Ok(None)),
+            config_comments: Vec::new(),
+            log_target: None,
         },
         PluginMetadata {
             name: "plugin2".to_owned(),
             version: "0.0.1".to_owned(),
             init: Box::new(move |_| Ok(TestPlugin::init("plugin2", 1000, state2_meta, c2_meta))),
             default_config: Box::new(|| Ok(None)),
+            capabilities: Box::new(|_| Ok(None)),
+            config_comments: Vec::new(),
+            log_target: None,
         },
     ];
     let plugins = PluginSet::from(plugins);
@@ -205,6 +320,59 @@ fn test_plugin_lifecycle() {
     assert_eq!(transform2_out, output2_written);
 }
 
+#[test]
+fn dry_run() {
+    let state = Arc::new(AtomicState::new(State::PreInit));
+    let counters = MeasurementCounters::default();
+    let state_meta = state.clone();
+    let plugins = vec![PluginMetadata {
+        name: "plugin1".to_owned(),
+        version: "0.0.1".to_owned(),
+        init: Box::new(move |_| Ok(TestPlugin::init("plugin1", 98, state_meta, counters))),
+        default_config: Box::new(|| Ok(None)),
+        capabilities: Box::new(|_| Ok(None)),
+        config_comments: Vec::new(),
+        log_target: None,
+    }];
+    let report = agent::Builder::new(PluginSet::from(plugins)).dry_run().unwrap();
+
+    // the plugin has been started then stopped, without any measurement
+    assert_eq!(state.get(), State::Stopped);
+    assert_eq!(report.plugins, vec![("plugin1".to_owned(), "0.0.1".to_owned())]);
+    assert!(report.disabled_plugins.is_empty());
+    assert_eq!(report.metrics.len(), 2);
+    assert_eq!(report.sources.len(), 1);
+    assert_eq!(report.transforms.len(), 1);
+    assert_eq!(report.outputs.len(), 1);
+}
+
+#[test]
+fn dry_run_checks_the_pipeline() {
+    let state = Arc::new(AtomicState::new(State::PreInit));
+    let counters = MeasurementCounters::default();
+    let state_meta = state.clone();
+    let plugins = vec![PluginMetadata {
+        name: "plugin1".to_owned(),
+        version: "0.0.1".to_owned(),
+        init: Box::new(move |_| Ok(TestPlugin::init("plugin1", 98, state_meta, counters))),
+        default_config: Box::new(|| Ok(None)),
+        capabilities: Box::new(|_| Ok(None)),
+        config_comments: Vec::new(),
+        log_target: None,
+    }];
+    let mut pipeline = pipeline::Builder::new();
+    pipeline.transforms_order(vec![TransformName::from_str("plugin1", "missing")]);
+    let err = agent::Builder::from_pipeline(PluginSet::from(plugins), pipeline)
+        .dry_run()
+        .expect_err("the transforms order is invalid");
+    assert!(
+        format!("{err:#}").contains("transform that does not exist: transforms/plugin1/missing"),
+        "unexpected error: {err:#}"
+    );
+    // the pre-pipeline-start hook of the plugin has run before the check
+    assert_eq!(state.get(), State::PrePipelineStart);
+}
+
 /// Sorts a vector of strings and returns it.
 fn sorted<A: AsRef<str> + Ord>(mut strings: Vec<A>) -> Vec<A> {
     strings.sort();
@@ -221,6 +389,17 @@ impl AlumetPlugin for MyPlugin {
         "version"
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn config_comments() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("list", "Items of the list.\nThey are kept in order."),
+            ("count", "Number of things."),
+        ]
+    }
+
     fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
         todo!()
     }
@@ -239,6 +418,47 @@ impl AlumetPlugin for MyPlugin {
     }
 }
 
+/// Requires an access to the path of its config.
+struct FilePlugin;
+impl AlumetPlugin for FilePlugin {
+    fn name() -> &'static str {
+        "file"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: FilePluginConfig = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(config.path)]))
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Err(anyhow::anyhow!("init called"))
+    }
+
+    fn start(&mut self, _alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        todo!()
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        todo!()
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(FilePluginConfig {
+            path: PathBuf::from("/tmp/default.out"),
+        })?;
+        Ok(Some(config))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FilePluginConfig {
+    path: PathBuf,
+}
+
 #[derive(Serialize)]
 struct MyPluginConfig {
     list: Vec<String>,
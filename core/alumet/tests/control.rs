@@ -1,4 +1,11 @@
-use std::{collections::HashSet, time::Duration};
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
 use alumet::{
     agent::{self, plugin::PluginSet},
@@ -8,8 +15,8 @@ use alumet::{
             handle::SendWaitError,
             request::{self, ElementListFilter},
         },
-        elements::source::trigger::TriggerSpec,
-        naming::{ElementKind, ElementName, PluginName},
+        elements::source::trigger::{self, TriggerSpec},
+        naming::{ElementKind, ElementName, PluginName, matching::SourceNamePattern},
     },
     plugin::rust::AlumetPlugin,
     static_plugins,
@@ -219,6 +226,52 @@ fn list_filter() {
     );
 }
 
+#[test]
+fn trigger_sources_in_bulk() {
+    let agent = agent::Builder::new(PluginSet::new()).build_and_start().unwrap();
+    let handle = agent.pipeline.control_handle();
+    let rt = current_thread_runtime();
+
+    // create some sources that are only polled when manually triggered
+    let mut polls = Vec::new();
+    for (plugin, source) in [("kwollect", "node-1"), ("kwollect", "node-2"), ("rapl", "cpu-0")] {
+        let count = Arc::new(AtomicUsize::new(0));
+        let request = request::create_one().add_source(
+            source,
+            Box::new(CountingSource(count.clone())),
+            trigger::builder::manual().build().unwrap(),
+        );
+        rt.block_on(
+            handle
+                .clone()
+                .with_plugin(PluginName(plugin.to_owned()))
+                .send_wait(request, TIMEOUT),
+        )
+        .expect("creation request failed");
+        polls.push(count);
+    }
+    let counts = || polls.iter().map(|c| c.load(Ordering::Relaxed)).collect::<Vec<_>>();
+
+    // trigger all the sources of a plugin
+    rt.block_on(handle.send_wait(
+        request::source(SourceNamePattern::plugin("kwollect")).trigger_now(),
+        TIMEOUT,
+    ))
+    .unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(counts(), vec![1, 1, 0]);
+
+    // trigger the sources that match a regex
+    let pattern: SourceNamePattern = "*/~node-2|cpu-[0-9]+".parse().unwrap();
+    rt.block_on(handle.send_wait(request::source(pattern).trigger_now(), TIMEOUT))
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(counts(), vec![1, 2, 1]);
+
+    handle.shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
 fn current_thread_runtime() -> tokio::runtime::Runtime {
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -227,6 +280,7 @@ fn current_thread_runtime() -> tokio::runtime::Runtime {
 }
 
 struct DummySource;
+struct CountingSource(Arc<AtomicUsize>);
 struct DummyTransform;
 struct DummyOutput;
 struct TestPlugin;
@@ -241,6 +295,17 @@ impl Source for DummySource {
     }
 }
 
+impl Source for CountingSource {
+    fn poll(
+        &mut self,
+        _measurements: &mut alumet::measurement::MeasurementAccumulator,
+        _timestamp: alumet::measurement::Timestamp,
+    ) -> Result<(), alumet::pipeline::elements::error::PollError> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
 impl Transform for DummyTransform {
     fn apply(
         &mut self,
@@ -0,0 +1,115 @@
+use std::{sync::Mutex, time::Duration};
+
+use alumet::{
+    agent::{self, plugin::PluginSet},
+    metrics::error::MetricLookupError,
+    plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable, rust::AlumetPlugin},
+    static_plugins,
+    units::Unit,
+};
+
+/// Registers some metrics.
+struct Producer;
+
+/// Looks up the metrics of the producer in `post_pipeline_start`.
+struct Consumer;
+
+/// Results of the lookups done by the consumer.
+static RESULTS: Mutex<Vec<Result<(), String>>> = Mutex::new(Vec::new());
+
+impl AlumetPlugin for Producer {
+    fn name() -> &'static str {
+        "producer"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.create_metric::<f64>("energy", Unit::Joule, "energy")?;
+        alumet.create_metric::<u64>("count", Unit::Unity, "number of events")?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl AlumetPlugin for Consumer {
+    fn name() -> &'static str {
+        "consumer"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn start(&mut self, _alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn post_pipeline_start(&mut self, alumet: &mut AlumetPostStart) -> anyhow::Result<()> {
+        let mut results = RESULTS.lock().unwrap();
+        results.push(
+            alumet
+                .find_metric::<f64>("energy")
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        );
+        results.push(
+            alumet
+                .find_metric::<u64>("count")
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        );
+        results.push(match alumet.find_metric::<u64>("energy") {
+            Err(MetricLookupError::Type(name, _)) if name == "energy" => Ok(()),
+            other => Err(format!("unexpected result for the wrong type: {other:?}")),
+        });
+        results.push(match alumet.find_metric::<f64>("unknown") {
+            Err(MetricLookupError::NotFound(name)) if name == "unknown" => Ok(()),
+            other => Err(format!("unexpected result for an unknown metric: {other:?}")),
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn find_metric_of_other_plugin() {
+    let plugins = PluginSet::from(static_plugins![Producer, Consumer]);
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("agent should start");
+    agent.pipeline.control_handle().shutdown();
+    agent
+        .wait_for_shutdown(Duration::from_secs(1))
+        .expect("agent should stop");
+
+    let results = RESULTS.lock().unwrap();
+    assert_eq!(results.len(), 4, "the consumer should have looked up 4 metrics");
+    for res in results.iter() {
+        res.as_ref().unwrap();
+    }
+}
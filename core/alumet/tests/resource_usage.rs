@@ -0,0 +1,126 @@
+use std::{sync::Mutex, time::Duration};
+
+use alumet::{
+    agent::{self, plugin::PluginSet},
+    measurement::{MeasurementAccumulator, MeasurementBuffer, Timestamp},
+    pipeline::{
+        self, Output, Source,
+        control::request::{self, ElementListFilter},
+        elements::{error::PollError, output::OutputContext, output::error::WriteError, source::trigger::TriggerSpec},
+        naming::{ElementKind, ElementName},
+    },
+    plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin},
+    resources::ResourceConsumer,
+    static_plugins,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The plugins (in `alumet_plugin` consumers) that appear in the self-metrics.
+static SELF_METRICS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Busy;
+struct AllocatingSource;
+struct CollectingOutput;
+
+impl AlumetPlugin for Busy {
+    fn name() -> &'static str {
+        "busy"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let trigger = TriggerSpec::at_interval(Duration::from_millis(10));
+        alumet.add_source("allocating", Box::new(AllocatingSource), trigger)?;
+        alumet.add_blocking_output("collecting", Box::new(CollectingOutput))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl Source for AllocatingSource {
+    fn poll(&mut self, _measurements: &mut MeasurementAccumulator, _timestamp: Timestamp) -> Result<(), PollError> {
+        let v = vec![0u8; 4096];
+        std::hint::black_box(v);
+        Ok(())
+    }
+}
+
+impl Output for CollectingOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, _ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut plugins = SELF_METRICS.lock().unwrap();
+        for m in measurements {
+            if let ResourceConsumer::Custom { kind, id } = &m.consumer
+                && kind == "alumet_plugin"
+            {
+                plugins.push(id.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn resource_usage() {
+    let mut pipeline = pipeline::Builder::new();
+    let settings = pipeline.resource_usage_mut();
+    settings.enabled = true;
+    settings.self_metrics_interval = Some(Duration::from_millis(50));
+
+    let plugins = PluginSet::from(static_plugins![Busy]);
+    let agent = agent::Builder::from_pipeline(plugins, pipeline)
+        .build_and_start()
+        .expect("agent should start");
+    std::thread::sleep(Duration::from_millis(300));
+
+    // usage of each plugin, directly from the pipeline
+    let by_plugin = agent.pipeline.resource_usage().by_plugin();
+    let busy = by_plugin.get("busy").expect("the plugin should be tracked");
+    assert!(busy.calls > 2, "unexpected usage {busy:?}");
+    let alumet = by_plugin
+        .get("alumet")
+        .expect("the self-metrics source should be tracked");
+    assert!(alumet.calls > 0, "unexpected usage {alumet:?}");
+
+    // usage of the source, with a control request
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let handle = agent.pipeline.control_handle();
+    let request = request::resource_usage(ElementListFilter::kind(ElementKind::Source).plugin("busy"));
+    let usage = rt
+        .block_on(handle.send_wait(request, TIMEOUT))
+        .expect("usage request failed");
+    match &usage[..] {
+        [(name, usage)] => {
+            assert_eq!(name, &ElementName::from_str(ElementKind::Source, "busy", "allocating"));
+            assert!(usage.calls > 2, "unexpected usage {usage:?}");
+        }
+        _ => panic!("unexpected response {usage:?}"),
+    }
+
+    // self-metrics, written to the output
+    let plugins = SELF_METRICS.lock().unwrap().clone();
+    assert!(
+        plugins.iter().any(|p| p == "busy"),
+        "unexpected self-metrics {plugins:?}"
+    );
+
+    agent.pipeline.control_handle().shutdown();
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
@@ -0,0 +1,110 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use alumet::{
+    agent::{self, builder::AgentShutdownError, plugin::PluginSet},
+    plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin},
+    static_plugins,
+};
+use anyhow::anyhow;
+
+/// Set by the shutdown hook of `Flush`.
+static FLUSHED: AtomicBool = AtomicBool::new(false);
+/// Set by `Flush::stop` if the hook has run before it.
+static FLUSHED_BEFORE_STOP: AtomicBool = AtomicBool::new(false);
+
+/// Awaits a "final flush" in its shutdown hook.
+struct Flush;
+
+/// Registers a hook that never completes in time.
+struct Slow;
+
+impl AlumetPlugin for Flush {
+    fn name() -> &'static str {
+        "flush"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.on_shutdown(Duration::from_secs(1), || async {
+            // the async runtime is still available
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            FLUSHED.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        FLUSHED_BEFORE_STOP.store(FLUSHED.load(Ordering::SeqCst), Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl AlumetPlugin for Slow {
+    fn name() -> &'static str {
+        "slow"
+    }
+
+    fn version() -> &'static str {
+        "0.0.1"
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.on_shutdown(Duration::from_millis(100), || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Err(anyhow!("should have been cancelled"))
+        });
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn shutdown_hooks() {
+    let plugins = PluginSet::from(static_plugins![Flush, Slow]);
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("agent should start");
+    agent.pipeline.control_handle().shutdown();
+    let errors = agent
+        .wait_for_shutdown(Duration::from_secs(2))
+        .expect_err("the slow hook should fail")
+        .errors;
+
+    assert!(FLUSHED.load(Ordering::SeqCst), "the hook should have run");
+    assert!(
+        FLUSHED_BEFORE_STOP.load(Ordering::SeqCst),
+        "the hook should have run before stop()"
+    );
+    match &errors[..] {
+        [AgentShutdownError::Pipeline(e)] => {
+            assert_eq!(e.to_string(), "error in action requested by plugin slow");
+        }
+        _ => panic!("unexpected errors: {errors:?}"),
+    }
+}
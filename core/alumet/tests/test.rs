@@ -1,2 +1,4 @@
 #[cfg(feature = "test")]
+mod test_harness;
+#[cfg(feature = "test")]
 mod test_module;
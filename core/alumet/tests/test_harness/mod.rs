@@ -0,0 +1,173 @@
+//! This file contains tests for the plugin harness.
+
+use std::time::Duration;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::TypedMetricId,
+    pipeline::{
+        Output, Source, Transform,
+        elements::{
+            error::{PollError, TransformError, WriteError},
+            output::OutputContext,
+            source::trigger::TriggerSpec,
+            transform::TransformContext,
+        },
+    },
+    plugin::{ConfigTable, rust::AlumetPlugin},
+    resources::{Resource, ResourceConsumer},
+    test::PluginHarness,
+    units::Unit,
+};
+use std::sync::{Arc, Mutex};
+
+struct TeaPlugin {
+    written: Arc<Mutex<Vec<u64>>>,
+}
+
+struct TeaSource {
+    metric: TypedMetricId<u64>,
+    count: u64,
+}
+
+struct TeaTransform;
+
+struct TeaOutput {
+    written: Arc<Mutex<Vec<u64>>>,
+}
+
+impl AlumetPlugin for TeaPlugin {
+    fn name() -> &'static str {
+        "tea"
+    }
+
+    fn version() -> &'static str {
+        "0.1.0"
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(TeaPlugin {
+            written: Arc::new(Mutex::new(Vec::new())),
+        }))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let metric = alumet.create_metric::<u64>("cups", Unit::Unity, "number of cups of tea")?;
+        alumet.add_source(
+            "tea_source",
+            Box::new(TeaSource { metric, count: 0 }),
+            TriggerSpec::at_interval(Duration::from_secs(2)),
+        )?;
+        alumet.add_transform("tea_transform", Box::new(TeaTransform))?;
+        alumet.add_blocking_output(
+            "tea_output",
+            Box::new(TeaOutput {
+                written: self.written.clone(),
+            }),
+        )?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl Source for TeaSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        self.count += 1;
+        measurements.push(MeasurementPoint::new(
+            t,
+            self.metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            self.count,
+        ));
+        Ok(())
+    }
+}
+
+impl Transform for TeaTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
+        for m in measurements.iter_mut() {
+            if let WrappedMeasurementValue::U64(v) = m.value {
+                m.value = WrappedMeasurementValue::U64(v * 10);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Output for TeaOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, _ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut written = self.written.lock().unwrap();
+        for m in measurements.iter() {
+            if let WrappedMeasurementValue::U64(v) = m.value {
+                written.push(v);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn source_virtual_time() -> anyhow::Result<()> {
+    let mut harness = PluginHarness::<TeaPlugin>::start_with_default_config()?;
+    let mut source = harness.source("tea_source")?;
+    assert_eq!(source.trigger().poll_interval(), Some(Duration::from_secs(2)));
+
+    let start = source.now();
+    let measurements = source.run_for(Duration::from_secs(7))?;
+    let points = harness.points(&measurements, "cups");
+    assert_eq!(points.len(), 3);
+    assert_eq!(points[0].timestamp, start + Duration::from_secs(2));
+    assert_eq!(points[2].timestamp, start + Duration::from_secs(6));
+    assert_eq!(source.now(), start + Duration::from_secs(7));
+
+    // the clock continues where it stopped
+    source.advance(Duration::from_secs(1));
+    let measurements = source.poll().unwrap();
+    let points = harness.points(&measurements, "cups");
+    assert_eq!(points.len(), 1);
+    assert_eq!(points[0].timestamp, start + Duration::from_secs(8));
+    assert_eq!(points[0].value, WrappedMeasurementValue::U64(4));
+
+    harness.stop()
+}
+
+#[test]
+fn transform_and_output() -> anyhow::Result<()> {
+    let mut harness = PluginHarness::<TeaPlugin>::start_with_default_config()?;
+    let mut source = harness.source("tea_source")?;
+    let mut transform = harness.transform("tea_transform")?;
+    let mut output = harness.output("tea_output")?;
+
+    let measurements = source.run_for(Duration::from_secs(4))?;
+    let measurements = transform.apply(measurements).unwrap();
+    assert_eq!(
+        harness.values(&measurements, "cups"),
+        vec![WrappedMeasurementValue::U64(10), WrappedMeasurementValue::U64(20)]
+    );
+
+    output.write(&measurements).unwrap();
+    output.finish()?;
+    assert_eq!(*harness.plugin().written.lock().unwrap(), vec![10, 20]);
+    harness.stop()
+}
+
+#[test]
+fn missing_elements() -> anyhow::Result<()> {
+    let mut harness = PluginHarness::<TeaPlugin>::start_with_default_config()?;
+    assert!(harness.source("coffee_source").is_err());
+    assert!(harness.autonomous_source("tea_source").is_err());
+    harness.source("tea_source")?;
+
+    // each element can only be built once
+    harness.output("tea_output")?;
+    assert!(harness.output("tea_output").is_err());
+    harness.stop()
+}
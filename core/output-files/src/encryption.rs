@@ -0,0 +1,111 @@
+//! Encryption of the output files with [age](https://age-encryption.org).
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use age::{Encryptor, stream::StreamWriter};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+pub use age::x25519::Recipient;
+
+/// Encryption of the output files. The files are not encrypted by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct EncryptionConfig {
+    /// Public keys of the recipients, such as `age1...`. The files are encrypted if there is at least one recipient.
+    pub recipients: Vec<String>,
+}
+
+impl EncryptionConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.recipients.is_empty()
+    }
+
+    /// Parses the public keys of the recipients.
+    pub fn parse_recipients(&self) -> anyhow::Result<Vec<Recipient>> {
+        self.recipients
+            .iter()
+            .map(|r| Recipient::from_str(r).map_err(|e| anyhow!("invalid age recipient {r:?}: {e}")))
+            .collect()
+    }
+}
+
+/// An output file, which is encrypted if there is at least one recipient.
+pub struct FileWriter {
+    /// `None` once the file has been finished.
+    inner: Option<Inner>,
+    /// Number of bytes written to the file, before encryption.
+    written: u64,
+}
+
+enum Inner {
+    Plain(File),
+    Encrypted(StreamWriter<File>),
+}
+
+impl FileWriter {
+    pub fn create(path: &Path, recipients: &[Recipient]) -> io::Result<Self> {
+        let file = File::create(path)?;
+        let inner = if recipients.is_empty() {
+            Inner::Plain(file)
+        } else {
+            let recipients = recipients.iter().map(|r| r as &dyn age::Recipient);
+            let encryptor = Encryptor::with_recipients(recipients).map_err(io::Error::other)?;
+            Inner::Encrypted(encryptor.wrap_output(file)?)
+        };
+        Ok(Self {
+            inner: Some(inner),
+            written: 0,
+        })
+    }
+
+    /// Number of bytes written to the file, before encryption.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Flushes the file and, if it is encrypted, writes its last chunk. Without it, the file cannot be decrypted.
+    ///
+    /// The writer cannot be used after that.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.inner.take() {
+            Some(Inner::Plain(mut file)) => file.flush(),
+            Some(Inner::Encrypted(writer)) => writer.finish()?.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match &mut self.inner {
+            Some(Inner::Plain(file)) => file.write(buf)?,
+            Some(Inner::Encrypted(writer)) => writer.write(buf)?,
+            None => return Err(io::Error::other("the file has been finished")),
+        };
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    /// Flushes the file. When it is encrypted, the data is only written by chunks of 64 KiB.
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Some(Inner::Plain(file)) => file.flush(),
+            Some(Inner::Encrypted(writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::error!("Failed to finish the output file: {e}");
+        }
+    }
+}
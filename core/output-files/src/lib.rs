@@ -0,0 +1,4 @@
+//! Output files of the plugins that write measurements to files, such as `csv` and `jsonl`.
+
+pub mod encryption;
+pub mod rotation;
@@ -0,0 +1,325 @@
+//! Rotation, compression and retention of the output files.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// Compression level of zstd, from 1 (fastest) to 22 (smallest output).
+const ZSTD_LEVEL: i32 = 3;
+
+/// When and how to rotate the output file. Rotation is disabled by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct RotationConfig {
+    /// Rotate the file when it is bigger than this size, in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// Rotate the file when it has been written for this duration.
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<Duration>,
+    /// Compression of the rotated files.
+    pub compression: Compression,
+    /// Maximum number of rotated files to keep. The oldest files are deleted first.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+    /// Delete the rotated files that are older than this duration.
+    #[serde(with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub retention: Option<Duration>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    /// Compresses the file at `path`, then deletes it.
+    fn compress_file(&self, path: &Path) -> io::Result<PathBuf> {
+        let Some(ext) = self.extension() else {
+            return Ok(path.to_owned());
+        };
+        let compressed_path = with_suffix(path, ext);
+
+        let mut input = BufReader::new(File::open(path)?);
+        let output = BufWriter::new(File::create(&compressed_path)?);
+        match self {
+            Compression::None => unreachable!(),
+            Compression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+                io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?.flush()?;
+            }
+            Compression::Zstd => {
+                let mut encoder = zstd::Encoder::new(output, ZSTD_LEVEL)?;
+                io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?.flush()?;
+            }
+        }
+        fs::remove_file(path)?;
+        Ok(compressed_path)
+    }
+}
+
+impl RotationConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.max_size.is_some() || self.max_age.is_some()
+    }
+
+    /// Returns `true` if a file of `size` bytes, opened at `opened_at`, must be rotated.
+    pub fn must_rotate(&self, size: u64, opened_at: SystemTime) -> bool {
+        self.max_size.is_some_and(|max| size >= max)
+            || self
+                .max_age
+                .is_some_and(|max| opened_at.elapsed().unwrap_or_default() >= max)
+    }
+}
+
+/// Rotates the output files.
+///
+/// The compression and the retention policy are applied in the background,
+/// so that a big file does not block the output.
+pub struct Rotator {
+    config: RotationConfig,
+    output_path: PathBuf,
+    /// Thread that compresses the last rotated file.
+    background: Option<JoinHandle<()>>,
+}
+
+impl Rotator {
+    pub fn new(config: RotationConfig, output_path: PathBuf) -> Self {
+        Self {
+            config,
+            output_path,
+            background: None,
+        }
+    }
+
+    pub fn config(&self) -> &RotationConfig {
+        &self.config
+    }
+
+    /// Renames the output file, which must be closed, and compresses it.
+    pub fn rotate(&mut self) -> anyhow::Result<()> {
+        let rotated = rotated_path(&self.output_path, OffsetDateTime::now_utc());
+        fs::rename(&self.output_path, &rotated)
+            .with_context(|| format!("failed to rename {:?} to {rotated:?}", self.output_path))?;
+        log::debug!("Rotated {:?} to {rotated:?}", self.output_path);
+
+        // Only one file is compressed at a time.
+        self.wait_background();
+        let config = self.config.clone();
+        let output_path = self.output_path.clone();
+        let background = std::thread::Builder::new()
+            .name(String::from("output-rotation"))
+            .spawn(move || {
+                if let Err(e) = config.compression.compress_file(&rotated) {
+                    log::error!("Failed to compress {rotated:?}: {e}");
+                }
+                if let Err(e) = apply_retention(&config, &output_path) {
+                    log::error!("Failed to delete the old output files: {e:#}");
+                }
+            })
+            .context("failed to spawn the rotation thread")?;
+        self.background = Some(background);
+        Ok(())
+    }
+
+    fn wait_background(&mut self) {
+        if let Some(handle) = self.background.take()
+            && handle.join().is_err()
+        {
+            log::error!("The rotation thread panicked.");
+        }
+    }
+}
+
+impl Drop for Rotator {
+    fn drop(&mut self) {
+        self.wait_background();
+    }
+}
+
+/// Returns the path of a rotated file: `dir/name.csv` becomes `dir/name-YYYYMMDDTHHMMSS.csv`.
+///
+/// If the file already exists, a counter is appended to the date.
+fn rotated_path(output_path: &Path, now: OffsetDateTime) -> PathBuf {
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = output_path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let date = format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    let mut path = output_path.with_file_name(format!("{stem}-{date}{ext}"));
+    let mut i = 1;
+    while path.exists() || with_suffix(&path, "gz").exists() || with_suffix(&path, "zst").exists() {
+        path = output_path.with_file_name(format!("{stem}-{date}-{i}{ext}"));
+        i += 1;
+    }
+    path
+}
+
+/// Appends an extension to a path: `name.csv` becomes `name.csv.{ext}`.
+fn with_suffix(path: &Path, ext: &str) -> PathBuf {
+    let mut res = path.as_os_str().to_owned();
+    res.push(".");
+    res.push(ext);
+    PathBuf::from(res)
+}
+
+/// Returns the rotated files of `output_path`, from the oldest to the newest.
+fn rotated_files(output_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = match output_path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{stem}-");
+    let ext = output_path.extension().map(|e| e.to_string_lossy().into_owned());
+
+    let mut res = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let name = name
+            .strip_suffix(".gz")
+            .or_else(|| name.strip_suffix(".zst"))
+            .unwrap_or(&name);
+        let Some(rest) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        let rest = match &ext {
+            Some(ext) => rest.strip_suffix(&format!(".{ext}")),
+            None => Some(rest),
+        };
+        // The name contains a date, and sometimes a counter, see `rotated_path`.
+        let Some((date, counter)) = rest.and_then(|r| parse_rotation_suffix(r)) else {
+            continue;
+        };
+        res.push(((date.to_owned(), counter), path));
+    }
+    res.sort();
+    Ok(res.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Parses `YYYYMMDDTHHMMSS` or `YYYYMMDDTHHMMSS-N`.
+fn parse_rotation_suffix(s: &str) -> Option<(&str, u32)> {
+    let (date, counter) = match s.split_once('-') {
+        Some((date, counter)) => (date, counter.parse().ok()?),
+        None => (s, 0),
+    };
+    let valid = date.len() == 15
+        && date
+            .char_indices()
+            .all(|(i, c)| if i == 8 { c == 'T' } else { c.is_ascii_digit() });
+    valid.then_some((date, counter))
+}
+
+/// Deletes the rotated files that exceed `max_files`, or that are older than `retention`.
+fn apply_retention(config: &RotationConfig, output_path: &Path) -> anyhow::Result<()> {
+    if config.max_files.is_none() && config.retention.is_none() {
+        return Ok(());
+    }
+    let files = rotated_files(output_path)?;
+    let excess = config.max_files.map(|max| files.len().saturating_sub(max)).unwrap_or(0);
+    for (i, path) in files.iter().enumerate() {
+        let expired = config.retention.is_some_and(|retention| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| t.elapsed().unwrap_or_default() >= retention)
+        });
+        if i < excess || expired {
+            fs::remove_file(path).with_context(|| format!("failed to delete {path:?}"))?;
+            log::debug!("Deleted old output file {path:?}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use time::OffsetDateTime;
+
+    use super::{RotationConfig, apply_retention, rotated_files, rotated_path};
+
+    #[test]
+    fn rotated_names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let output = tmp.path().join("alumet-output.csv");
+        let now = OffsetDateTime::from_unix_timestamp(1_735_787_045).unwrap(); // 2025-01-02T03:04:05Z
+
+        let first = rotated_path(&output, now);
+        assert_eq!(first, tmp.path().join("alumet-output-20250102T030405.csv"));
+        fs::write(first.with_extension("csv.gz"), "").unwrap();
+        let second = rotated_path(&output, now);
+        assert_eq!(second, tmp.path().join("alumet-output-20250102T030405-1.csv"));
+    }
+
+    #[test]
+    fn retention() {
+        let tmp = tempfile::tempdir().unwrap();
+        let output = tmp.path().join("alumet-output.csv");
+        let files = [
+            "alumet-output-20250101T000000.csv.gz",
+            "alumet-output-20250102T000000.csv.zst",
+            "alumet-output-20250103T000000.csv",
+            "alumet-output-20250103T000000-1.csv",
+        ];
+        let others = [
+            "alumet-output.csv",
+            "alumet-output-old.csv",
+            "other-20250101T000000.csv",
+        ];
+        for f in files.iter().chain(&others) {
+            fs::write(tmp.path().join(f), "").unwrap();
+        }
+        let names = |paths: Vec<std::path::PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(names(rotated_files(&output).unwrap()), files);
+
+        let config = RotationConfig {
+            max_files: Some(2),
+            ..Default::default()
+        };
+        apply_retention(&config, &output).unwrap();
+        assert_eq!(names(rotated_files(&output).unwrap()), files[2..]);
+        for f in others {
+            assert!(Path::new(&tmp.path().join(f)).exists());
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Naming rules of Prometheus.
+//!
+//! Prometheus only accepts metric names that match `[a-zA-Z_:][a-zA-Z0-9_:]*` and label names
+//! that match `[a-zA-Z_][a-zA-Z0-9_]*`. Moreover, the label names that start with `__` are reserved
+//! for internal use. The functions of this crate turn arbitrary Alumet names into valid Prometheus names.
+//!
+//! # Example
+//! ```
+//! use alumet_prometheus::{sanitize_label_name, sanitize_metric_name};
+//!
+//! assert_eq!(sanitize_metric_name("rapl.energy:total"), "rapl_energy:total");
+//! assert_eq!(sanitize_label_name("__name__"), "_name__");
+//! ```
+
+/// Replaces the invalid characters of a metric name by `_`.
+pub fn sanitize_metric_name(name: &str) -> String {
+    sanitize(name, |c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Replaces the invalid characters of a label name by `_`, and removes the reserved `__` prefix.
+pub fn sanitize_label_name(name: &str) -> String {
+    let name = sanitize(name, |c| c.is_ascii_alphanumeric() || c == '_');
+    // label names that start with __ are reserved for internal use
+    match name.strip_prefix("__") {
+        Some(rest) => format!("_{}", rest.trim_start_matches('_')),
+        None => name,
+    }
+}
+
+fn sanitize(name: &str, valid: impl Fn(char) -> bool) -> String {
+    let mut res: String = name.chars().map(|c| if valid(c) { c } else { '_' }).collect();
+    if res.is_empty() || res.starts_with(|c: char| c.is_ascii_digit()) {
+        res.insert(0, '_');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn sanitization() {
+        assert_eq!(sanitize_metric_name("rapl_consumed_energy"), "rapl_consumed_energy");
+        assert_eq!(sanitize_metric_name("cpu.time:user"), "cpu_time:user");
+        assert_eq!(sanitize_metric_name("2xx-requests"), "_2xx_requests");
+        assert_eq!(sanitize_label_name("domain"), "domain");
+        assert_eq!(sanitize_label_name("cpu:state"), "cpu_state");
+        assert_eq!(sanitize_label_name("0-domain"), "_0_domain");
+        assert_eq!(sanitize_label_name("__name__"), "_name__");
+        assert_eq!(sanitize_label_name(""), "_");
+    }
+}
@@ -11,6 +11,7 @@ use alumet::{
     metrics::{Metric, RawMetricId, duplicate::DuplicateReaction, online::MetricSender},
     plugin::{
         ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
 };
@@ -39,6 +40,10 @@ impl AlumetPlugin for AggregationPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(Vec::new())
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
@@ -78,6 +83,7 @@ impl AlumetPlugin for AggregationPlugin {
                 unit: metric.unit.clone(),
                 description: metric.description.clone(),
                 value_type: metric.value_type.clone(),
+                tags: metric.tags.clone(),
             };
 
             self.metrics_list.push(new_metric);
@@ -0,0 +1,272 @@
+//! Detection of the AMD GPUs and of the sysfs files of the `amdgpu` driver.
+//!
+//! These are the files that `rocm-smi` reads, hence ROCm does not need to be installed.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+pub const DRM_PATH: &str = "/sys/class/drm";
+
+/// PCI vendor id of AMD.
+const AMD_VENDOR_ID: &str = "0x1002";
+
+/// An AMD GPU and the files that give access to its measurements.
+///
+/// A missing file means that the device does not support the feature, or that it cannot be read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AmdGpu {
+    /// PCI bus ID of the device, such as `0000:03:00.0`.
+    pub bus_id: String,
+    /// Average (or, on recent GPUs, instantaneous) power in µW.
+    pub power: Option<PathBuf>,
+    /// Energy counter in µJ.
+    pub energy: Option<PathBuf>,
+    /// Temperature sensors in m°C, with their labels.
+    pub temperatures: Vec<(String, PathBuf)>,
+    /// VRAM in use, in bytes.
+    pub vram_used: Option<PathBuf>,
+    /// Percentage of time the GPU was busy.
+    pub gpu_busy: Option<PathBuf>,
+    /// Percentage of time the memory controller was busy.
+    pub memory_busy: Option<PathBuf>,
+}
+
+impl AmdGpu {
+    pub fn has_any(&self) -> bool {
+        self.power.is_some()
+            || self.energy.is_some()
+            || !self.temperatures.is_empty()
+            || self.vram_used.is_some()
+            || self.gpu_busy.is_some()
+            || self.memory_busy.is_some()
+    }
+
+    /// The available features, separated by commas.
+    pub fn features(&self) -> String {
+        let mut available = Vec::new();
+        if self.power.is_some() {
+            available.push("power");
+        }
+        if self.energy.is_some() {
+            available.push("energy");
+        }
+        if !self.temperatures.is_empty() {
+            available.push("temperature");
+        }
+        if self.vram_used.is_some() {
+            available.push("vram_used");
+        }
+        if self.gpu_busy.is_some() {
+            available.push("gpu_busy");
+        }
+        if self.memory_busy.is_some() {
+            available.push("memory_busy");
+        }
+        available.join(", ")
+    }
+}
+
+/// Detects the AMD GPUs, sorted by card number.
+///
+/// Returns the name of each card, such as `card1`, and the result of its inspection.
+pub fn detect(drm_dir: &Path) -> anyhow::Result<Vec<(String, anyhow::Result<AmdGpu>)>> {
+    let mut cards = Vec::new();
+    let entries = fs::read_dir(drm_dir).with_context(|| format!("failed to list {drm_dir:?}"))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // skip the connectors (card0-DP-1) and the render nodes (renderD128)
+        let Some(number) = name.strip_prefix("card").and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        cards.push((number, name, entry.path().join("device")));
+    }
+    cards.sort();
+
+    let mut gpus = Vec::new();
+    for (_, name, device) in cards {
+        match fs::read_to_string(device.join("vendor")) {
+            Ok(vendor) if vendor.trim() == AMD_VENDOR_ID => gpus.push((name, inspect(&device))),
+            Ok(_) => (),
+            Err(e) => gpus.push((
+                name,
+                Err(e).with_context(|| format!("failed to read the vendor of {device:?}")),
+            )),
+        }
+    }
+    Ok(gpus)
+}
+
+/// Finds the files of an AMD GPU.
+fn inspect(device: &Path) -> anyhow::Result<AmdGpu> {
+    let uevent = device.join("uevent");
+    let content = fs::read_to_string(&uevent).with_context(|| format!("failed to read {uevent:?}"))?;
+    let bus_id = content
+        .lines()
+        .find_map(|line| line.strip_prefix("PCI_SLOT_NAME="))
+        .with_context(|| format!("no PCI_SLOT_NAME in {uevent:?}"))?
+        .to_owned();
+
+    let hwmon = find_hwmon(device)?;
+    let in_hwmon = |name: &str| hwmon.as_ref().map(|dir| dir.join(name)).filter(|p| is_readable(p));
+    let power = in_hwmon("power1_average").or_else(|| in_hwmon("power1_input"));
+    let energy = in_hwmon("energy1_input");
+    let temperatures = match &hwmon {
+        Some(dir) => find_temperatures(dir)?,
+        None => Vec::new(),
+    };
+
+    let in_device = |name: &str| Some(device.join(name)).filter(|p| is_readable(p));
+    Ok(AmdGpu {
+        bus_id,
+        power,
+        energy,
+        temperatures,
+        vram_used: in_device("mem_info_vram_used"),
+        gpu_busy: in_device("gpu_busy_percent"),
+        memory_busy: in_device("mem_busy_percent"),
+    })
+}
+
+/// Returns the hwmon directory of the device, if any.
+fn find_hwmon(device: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let dir = device.join("hwmon");
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut entries = fs::read_dir(&dir)
+        .with_context(|| format!("failed to list {dir:?}"))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    Ok(entries.into_iter().find(|p| p.is_dir()))
+}
+
+/// Returns the readable temperature sensors, sorted by number, with their labels (`edge`, `junction`, `mem`).
+fn find_temperatures(hwmon: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut sensors = Vec::new();
+    for entry in fs::read_dir(hwmon).with_context(|| format!("failed to list {hwmon:?}"))? {
+        let path = entry?.path();
+        let Some(number) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("temp"))
+            .and_then(|n| n.strip_suffix("_input"))
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if !is_readable(&path) {
+            continue;
+        }
+        let label = fs::read_to_string(hwmon.join(format!("temp{number}_label")))
+            .map(|l| l.trim().to_owned())
+            .unwrap_or_else(|_| format!("temp{number}"));
+        sensors.push((number, label, path));
+    }
+    sensors.sort();
+    Ok(sensors.into_iter().map(|(_, label, path)| (label, path)).collect())
+}
+
+/// Returns true if the file contains an integer.
+///
+/// The driver returns an error when reading the files of the unsupported features.
+fn is_readable(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|s| s.trim().parse::<u64>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::{AmdGpu, detect};
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn detection() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let drm = tmp.path();
+
+        // a recent AMD GPU, with an instantaneous power only
+        let card1 = drm.join("card1/device");
+        write(&card1.join("vendor"), "0x1002\n");
+        write(&card1.join("uevent"), "DRIVER=amdgpu\nPCI_SLOT_NAME=0000:03:00.0\n");
+        write(&card1.join("hwmon/hwmon4/power1_input"), "35000000\n");
+        write(&card1.join("hwmon/hwmon4/temp1_input"), "41000\n");
+        write(&card1.join("hwmon/hwmon4/temp1_label"), "edge\n");
+        write(&card1.join("hwmon/hwmon4/temp2_input"), "45000\n");
+        write(&card1.join("hwmon/hwmon4/temp2_label"), "junction\n");
+        write(&card1.join("hwmon/hwmon4/temp10_input"), "50000\n");
+        write(&card1.join("mem_info_vram_used"), "536870912\n");
+        write(&card1.join("gpu_busy_percent"), "12\n");
+        // not supported by the device
+        write(&card1.join("mem_busy_percent"), "");
+
+        // a connector, an NVIDIA GPU and a render node
+        fs::create_dir_all(drm.join("card1-DP-1"))?;
+        write(&drm.join("card0/device/vendor"), "0x10de\n");
+        fs::create_dir_all(drm.join("renderD128"))?;
+
+        // an AMD GPU without uevent
+        write(&drm.join("card2/device/vendor"), "0x1002\n");
+
+        let gpus = detect(drm)?;
+        assert_eq!(gpus.len(), 2);
+        let (name, gpu) = &gpus[0];
+        assert_eq!(name, "card1");
+        let hwmon = card1.join("hwmon/hwmon4");
+        assert_eq!(
+            gpu.as_ref().unwrap(),
+            &AmdGpu {
+                bus_id: String::from("0000:03:00.0"),
+                power: Some(hwmon.join("power1_input")),
+                energy: None,
+                temperatures: vec![
+                    (String::from("edge"), hwmon.join("temp1_input")),
+                    (String::from("junction"), hwmon.join("temp2_input")),
+                    (String::from("temp10"), hwmon.join("temp10_input")),
+                ],
+                vram_used: Some(card1.join("mem_info_vram_used")),
+                gpu_busy: Some(card1.join("gpu_busy_percent")),
+                memory_busy: None,
+            }
+        );
+        assert_eq!(
+            gpu.as_ref().unwrap().features(),
+            "power, temperature, vram_used, gpu_busy"
+        );
+
+        let (name, gpu) = &gpus[1];
+        assert_eq!(name, "card2");
+        let err = gpu.as_ref().unwrap_err();
+        assert!(format!("{err:#}").contains("uevent"), "{err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn average_power() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let device = tmp.path().join("card0/device");
+        write(&device.join("vendor"), "0x1002\n");
+        write(&device.join("uevent"), "PCI_SLOT_NAME=0000:0b:00.0\n");
+        write(&device.join("hwmon/hwmon2/power1_average"), "20000000\n");
+        write(&device.join("hwmon/hwmon2/power1_input"), "21000000\n");
+
+        let gpus = detect(tmp.path())?;
+        let gpu = gpus[0].1.as_ref().unwrap();
+        assert_eq!(gpu.power, Some(device.join("hwmon/hwmon2/power1_average")));
+        assert!(gpu.has_any());
+        Ok(())
+    }
+}
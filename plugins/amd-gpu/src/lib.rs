@@ -0,0 +1,310 @@
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+mod device;
+mod metrics;
+mod source;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct AmdGpuPlugin {
+    config: Config,
+}
+
+impl AmdGpuPlugin {
+    #[cfg(not(test))]
+    fn drm_path(&self) -> PathBuf {
+        PathBuf::from(device::DRM_PATH)
+    }
+
+    #[cfg(test)]
+    fn drm_path(&self) -> PathBuf {
+        self.config.drm_test_path.clone()
+    }
+}
+
+impl AlumetPlugin for AmdGpuPlugin {
+    fn name() -> &'static str {
+        "amdgpu"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from(device::DRM_PATH)),
+            // the cards of the DRM class are links to their device, and its hwmon directory
+            Capability::Filesystem(PathBuf::from("/sys/devices")),
+        ])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(AmdGpuPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let gpus = device::detect(&self.drm_path()).context("failed to detect the GPUs")?;
+        if gpus.is_empty() {
+            return Err(anyhow!("No AMD GPU found. Is the amdgpu driver loaded?"));
+        }
+        let n_found = gpus.len();
+
+        let mut working = Vec::with_capacity(n_found);
+        for (card, gpu) in gpus {
+            match gpu {
+                Ok(gpu) if gpu.has_any() => {
+                    log::info!(
+                        "Found AMD GPU {} ({card}) with features: {}",
+                        gpu.bus_id,
+                        gpu.features()
+                    );
+                    working.push(gpu);
+                }
+                Ok(gpu) => {
+                    log::warn!(
+                        "Skipping AMD GPU {} ({card}) because it supports no useful feature.",
+                        gpu.bus_id
+                    );
+                }
+                Err(e) => {
+                    if self.config.skip_failed_devices {
+                        log::warn!("Skipping AMD GPU {card} because of error:\n{e:?}");
+                    } else {
+                        // don't skip, fail immediately
+                        return Err(e.context(format!("failed to inspect AMD GPU {card}")));
+                    }
+                }
+            }
+        }
+        if working.is_empty() {
+            return Err(anyhow!(
+                "{n_found} AMD GPUs found but none of them is working (see previous warnings)."
+            ));
+        }
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        for gpu in working {
+            let source_name = format!("device_{}", gpu.bus_id);
+            let source = source::AmdGpuSource::open(gpu, metrics.clone())?;
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&source_name, Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// On startup, the plugin inspects the GPU devices and detect their features.
+    /// If `skip_failed_devices = true`, inspection failures will be logged and the plugin will continue.
+    /// If `skip_failed_devices = false`, the first failure will make the plugin's startup fail.
+    skip_failed_devices: bool,
+
+    #[cfg(test)]
+    drm_test_path: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1), // 1Hz
+            flush_interval: Duration::from_secs(5),
+            skip_failed_devices: true,
+            #[cfg(test)]
+            drm_test_path: PathBuf::from(device::DRM_PATH),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::{PrefixedUnit, Unit},
+    };
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<AmdGpuPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    #[test]
+    fn plugin_with_sysfs() {
+        let tmp = tempdir().unwrap();
+        let device = tmp.path().join("card0/device");
+        write(&device.join("vendor"), "0x1002\n");
+        write(&device.join("uevent"), "PCI_SLOT_NAME=0000:03:00.0\n");
+        write(&device.join("hwmon/hwmon3/power1_average"), "42500000\n");
+        write(&device.join("hwmon/hwmon3/energy1_input"), "1000000\n");
+        write(&device.join("hwmon/hwmon3/temp1_input"), "51000\n");
+        write(&device.join("hwmon/hwmon3/temp1_label"), "edge\n");
+        write(&device.join("mem_info_vram_used"), "1073741824\n");
+        write(&device.join("gpu_busy_percent"), "87\n");
+        write(&device.join("mem_busy_percent"), "20\n");
+
+        let config = Config {
+            drm_test_path: tmp.path().to_owned(),
+            ..Config::default()
+        };
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<f64>("amdgpu_energy_consumption", PrefixedUnit::milli(Unit::Joule))
+            .expect_metric::<u64>("amdgpu_instant_power", PrefixedUnit::milli(Unit::Watt))
+            .expect_metric::<u64>("amdgpu_temperature_gpu", Unit::DegreeCelsius)
+            .expect_metric::<u64>("amdgpu_gpu_utilization", Unit::Percent)
+            .expect_metric::<u64>("amdgpu_memory_utilization", Unit::Percent)
+            .expect_metric::<u64>("amdgpu_memory_used", Unit::Byte)
+            .expect_source("amdgpu", "device_0000:03:00.0");
+
+        let energy_file = device.join("hwmon/hwmon3/energy1_input");
+        let runtime = RuntimeExpectations::new()
+            .test_source(
+                SourceName::from_str("amdgpu", "device_0000:03:00.0"),
+                || (),
+                |ctx| {
+                    // no energy on the first measurement
+                    let mut values: Vec<_> = ctx.measurements().iter().map(|m| m.value.clone()).collect();
+                    values.sort_by_key(|v| format!("{v:?}"));
+                    assert_eq!(
+                        values,
+                        vec![
+                            WrappedMeasurementValue::U64(1073741824),
+                            WrappedMeasurementValue::U64(20),
+                            WrappedMeasurementValue::U64(42500),
+                            WrappedMeasurementValue::U64(51),
+                            WrappedMeasurementValue::U64(87),
+                        ]
+                    );
+                    let temperature = ctx
+                        .measurements()
+                        .iter()
+                        .find(|m| m.value == WrappedMeasurementValue::U64(51))
+                        .unwrap();
+                    assert_eq!(temperature.resource.id_display().to_string(), "0000:03:00.0");
+                    assert_eq!(temperature.attributes().next().unwrap().1.to_string(), "edge");
+                },
+            )
+            .test_source(
+                SourceName::from_str("amdgpu", "device_0000:03:00.0"),
+                move || write(&energy_file, "3500000\n"),
+                |ctx| {
+                    let energy: Vec<_> = ctx
+                        .measurements()
+                        .iter()
+                        .filter(|m| matches!(m.value, WrappedMeasurementValue::F64(_)))
+                        .map(|m| m.value.clone())
+                        .collect();
+                    assert_eq!(energy, vec![WrappedMeasurementValue::F64(2500.0)]);
+                },
+            );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn no_amd_gpu() {
+        let tmp = tempdir().unwrap();
+        write(&tmp.path().join("card0/device/vendor"), "0x10de\n");
+        let config = Config {
+            drm_test_path: tmp.path().to_owned(),
+            ..Config::default()
+        };
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+
+    #[test]
+    fn failed_device() {
+        let tmp = tempdir().unwrap();
+        write(&tmp.path().join("card0/device/vendor"), "0x1002\n");
+        let device = tmp.path().join("card1/device");
+        write(&device.join("vendor"), "0x1002\n");
+        write(&device.join("uevent"), "PCI_SLOT_NAME=0000:0b:00.0\n");
+        write(&device.join("gpu_busy_percent"), "0\n");
+
+        // the GPU without uevent is skipped
+        let config = Config {
+            drm_test_path: tmp.path().to_owned(),
+            ..Config::default()
+        };
+        let startup = StartupExpectations::new().expect_source("amdgpu", "device_0000:0b:00.0");
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .build_and_start()
+            .expect("agent should start");
+        agent.pipeline.control_handle().shutdown();
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+
+        // or makes the startup fail
+        let config = Config {
+            skip_failed_devices: false,
+            ..config
+        };
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+}
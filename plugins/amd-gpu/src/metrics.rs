@@ -0,0 +1,57 @@
+use alumet::{
+    metrics::{TypedMetricId, def::tag, error::MetricCreationError},
+    plugin::AlumetPluginStart,
+    units::{PrefixedUnit, Unit},
+};
+
+/// Contains the ids of the measured metrics.
+///
+/// The names and units are the same as the ones of the NVML plugin, with the prefix `amdgpu_` instead of `nvml_`.
+#[derive(Clone)]
+pub struct Metrics {
+    /// Energy consumed by the GPU since the previous measurement, in mJ.
+    pub energy_consumption: TypedMetricId<f64>,
+    /// Power of the GPU, in mW.
+    pub instant_power: TypedMetricId<u64>,
+    /// GPU temperature in °C.
+    pub temperature_gpu: TypedMetricId<u64>,
+    /// GPU rate utilization in percentage.
+    pub gpu_utilization: TypedMetricId<u64>,
+    /// Memory controller utilization in percentage.
+    pub memory_utilization: TypedMetricId<u64>,
+    /// VRAM in use, in bytes.
+    pub memory_used: TypedMetricId<u64>,
+}
+
+impl Metrics {
+    /// Creates new Alumet metrics for AMD GPU measurements and stores their ids in a `Metrics` structure.
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            energy_consumption: alumet.create_metric_with_tags(
+                "amdgpu_energy_consumption",
+                PrefixedUnit::milli(Unit::Joule),
+                "Energy consumption by the GPU since the previous measurement",
+                &[tag::ENERGY],
+            )?,
+            instant_power: alumet.create_metric_with_tags(
+                "amdgpu_instant_power",
+                PrefixedUnit::milli(Unit::Watt),
+                "Power of the GPU at the time of the measurement",
+                &[tag::POWER],
+            )?,
+            temperature_gpu: alumet.create_metric_with_tags(
+                "amdgpu_temperature_gpu",
+                Unit::DegreeCelsius,
+                "Instantaneous temperature of the GPU at the time of the measurement",
+                &[tag::THERMAL],
+            )?,
+            gpu_utilization: alumet.create_metric("amdgpu_gpu_utilization", Unit::Percent, "GPU rate utilization")?,
+            memory_utilization: alumet.create_metric(
+                "amdgpu_memory_utilization",
+                Unit::Percent,
+                "Utilization of the GPU memory controller",
+            )?,
+            memory_used: alumet.create_metric("amdgpu_memory_used", Unit::Byte, "VRAM used on the GPU")?,
+        })
+    }
+}
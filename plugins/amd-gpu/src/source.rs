@@ -0,0 +1,202 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{Read, Seek},
+    path::Path,
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::elements::error::PollError,
+    plugin::util::{CounterDiff, CounterDiffUpdate},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{device::AmdGpu, metrics::Metrics};
+
+/// Measurement source that reads the sysfs files of an AMD GPU.
+pub struct AmdGpuSource {
+    metrics: Metrics,
+    resource: Resource,
+    files: Files,
+    /// Internal state to compute the difference between two increments of the energy counter.
+    energy_counter: CounterDiff,
+    /// Energy computed from the power, when the device has no energy counter.
+    integrator: PowerIntegrator,
+    buf: Vec<u8>,
+}
+
+/// The opened files of the device.
+struct Files {
+    power: Option<File>,
+    energy: Option<File>,
+    temperatures: Vec<(String, File)>,
+    vram_used: Option<File>,
+    gpu_busy: Option<File>,
+    memory_busy: Option<File>,
+}
+
+/// Computes the energy consumed between two measurements of the power, with the trapezoidal rule.
+#[derive(Default)]
+struct PowerIntegrator {
+    /// Previous power in mW, and its timestamp.
+    last: Option<(f64, Timestamp)>,
+}
+
+impl PowerIntegrator {
+    /// Returns the energy consumed since the previous measurement in mJ, or `None` on the first call.
+    fn update(&mut self, power: f64, timestamp: Timestamp) -> Option<f64> {
+        let energy = self.last.and_then(|(last, t)| {
+            let elapsed = timestamp.duration_since(t).ok()?;
+            Some((power + last) / 2.0 * elapsed.as_secs_f64())
+        });
+        self.last = Some((power, timestamp));
+        energy
+    }
+}
+
+impl AmdGpuSource {
+    pub fn open(gpu: AmdGpu, metrics: Metrics) -> anyhow::Result<AmdGpuSource> {
+        fn open(path: Option<&Path>) -> anyhow::Result<Option<File>> {
+            path.map(|p| File::open(p).with_context(|| format!("failed to open {p:?}")))
+                .transpose()
+        }
+        let mut temperatures = Vec::with_capacity(gpu.temperatures.len());
+        for (label, path) in &gpu.temperatures {
+            let file = File::open(path).with_context(|| format!("failed to open {path:?}"))?;
+            temperatures.push((label.clone(), file));
+        }
+        let files = Files {
+            power: open(gpu.power.as_deref())?,
+            energy: open(gpu.energy.as_deref())?,
+            temperatures,
+            vram_used: open(gpu.vram_used.as_deref())?,
+            gpu_busy: open(gpu.gpu_busy.as_deref())?,
+            memory_busy: open(gpu.memory_busy.as_deref())?,
+        };
+        Ok(AmdGpuSource {
+            metrics,
+            resource: Resource::Gpu {
+                bus_id: Cow::Owned(gpu.bus_id),
+            },
+            files,
+            energy_counter: CounterDiff::with_max_value(u64::MAX),
+            integrator: PowerIntegrator::default(),
+            buf: Vec::with_capacity(32),
+        })
+    }
+}
+
+/// Reads a file that contains an integer.
+fn read_integer(file: &mut File, buf: &mut Vec<u8>) -> anyhow::Result<u64> {
+    buf.clear();
+    file.rewind().with_context(|| format!("failed to rewind {file:?}"))?;
+    file.read_to_end(buf)
+        .with_context(|| format!("failed to read {file:?}"))?;
+    let content = std::str::from_utf8(buf)?;
+    content
+        .trim_end()
+        .parse()
+        .with_context(|| format!("failed to parse {file:?}: '{content}'"))
+}
+
+impl alumet::pipeline::Source for AmdGpuSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        // no consumer, we just monitor the device here
+        let consumer = ResourceConsumer::LocalMachine;
+
+        // Get the power in mW, the driver gives µW
+        let power = match &mut self.files.power {
+            Some(file) => Some(read_integer(file, &mut self.buf)? as f64 / 1000.0),
+            None => None,
+        };
+        if let Some(milli_watts) = power {
+            measurements.push(MeasurementPoint::new(
+                timestamp,
+                self.metrics.instant_power,
+                self.resource.clone(),
+                consumer.clone(),
+                milli_watts as u64,
+            ));
+        }
+
+        // Get the energy in mJ from the counter in µJ, or from the power
+        let energy = match (&mut self.files.energy, power) {
+            (Some(file), _) => match self.energy_counter.update(read_integer(file, &mut self.buf)?) {
+                CounterDiffUpdate::FirstTime => None,
+                CounterDiffUpdate::Difference(diff) | CounterDiffUpdate::CorrectedDifference(diff) => {
+                    Some(diff as f64 / 1000.0)
+                }
+            },
+            (None, Some(milli_watts)) => self.integrator.update(milli_watts, timestamp),
+            (None, None) => None,
+        };
+        if let Some(milli_joules) = energy {
+            measurements.push(MeasurementPoint::new(
+                timestamp,
+                self.metrics.energy_consumption,
+                self.resource.clone(),
+                consumer.clone(),
+                milli_joules,
+            ));
+        }
+
+        // Get the temperatures in °C, the driver gives m°C
+        for (label, file) in &mut self.files.temperatures {
+            let celsius = read_integer(file, &mut self.buf)? / 1000;
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.temperature_gpu,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    celsius,
+                )
+                .with_attr("sensor", label.clone()),
+            );
+        }
+
+        let gauges = [
+            (&mut self.files.vram_used, self.metrics.memory_used),
+            (&mut self.files.gpu_busy, self.metrics.gpu_utilization),
+            (&mut self.files.memory_busy, self.metrics.memory_utilization),
+        ];
+        for (file, metric) in gauges {
+            if let Some(file) = file {
+                let value = read_integer(file, &mut self.buf)?;
+                measurements.push(MeasurementPoint::new(
+                    timestamp,
+                    metric,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    value,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::measurement::Timestamp;
+
+    use super::PowerIntegrator;
+
+    fn at(millis: u64) -> Timestamp {
+        Timestamp::from(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn power_integration() {
+        let mut integrator = PowerIntegrator::default();
+        assert_eq!(integrator.update(10_000.0, at(0)), None);
+        assert_eq!(integrator.update(30_000.0, at(500)), Some(10_000.0));
+        assert_eq!(integrator.update(30_000.0, at(2500)), Some(60_000.0));
+        // a measurement in the past gives no energy
+        assert_eq!(integrator.update(30_000.0, at(2000)), None);
+    }
+}
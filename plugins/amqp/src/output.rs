@@ -0,0 +1,193 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::{
+        Output,
+        elements::{
+            error::WriteError,
+            output::{OutputContext, error::WriteRetry},
+        },
+    },
+};
+use anyhow::{Context, anyhow};
+use lapin::{
+    BasicProperties, Channel, Connection, ConnectionProperties, ExchangeKind,
+    options::{BasicPublishOptions, ConfirmSelectOptions, ExchangeDeclareOptions},
+    publisher_confirm::{Confirmation, PublisherConfirm},
+    types::FieldTable,
+    uri::AMQPUri,
+};
+
+use crate::{
+    payload::{self, CONTENT_TYPE, Record},
+    routing::RoutingKeyTemplate,
+};
+
+/// Settings of the messages and of the exchange.
+pub struct Settings {
+    pub uri: AMQPUri,
+    /// Name of the client, visible in the management interface of the broker.
+    pub connection_name: String,
+    pub exchange: String,
+    /// The exchange to declare when connecting, if any.
+    pub declare_exchange: Option<ExchangeKind>,
+    pub routing_key: RoutingKeyTemplate,
+    pub persistent: bool,
+    pub publisher_confirms: bool,
+    /// Maximum number of measurements per message.
+    pub max_batch_size: usize,
+    pub timeout: Duration,
+}
+
+pub struct AmqpOutput {
+    /// Runs the futures of the client, which does its I/O on its own thread, and the timeouts.
+    runtime: tokio::runtime::Runtime,
+    publisher: Publisher,
+}
+
+struct Publisher {
+    settings: Settings,
+    /// `None` if the connection has not been established yet, or has been lost.
+    session: Option<Session>,
+}
+
+struct Session {
+    // Keep the connection open as long as the channel is used.
+    connection: Connection,
+    channel: Channel,
+}
+
+impl AmqpOutput {
+    pub fn new(settings: Settings) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build()?;
+        Ok(Self {
+            runtime,
+            publisher: Publisher {
+                settings,
+                session: None,
+            },
+        })
+    }
+}
+
+impl Publisher {
+    async fn connect(&self) -> anyhow::Result<Session> {
+        let s = &self.settings;
+        let properties = ConnectionProperties::default().with_connection_name(s.connection_name.clone().into());
+        let connection = tokio::time::timeout(s.timeout, Connection::connect_uri(s.uri.clone(), properties))
+            .await
+            .map_err(|_| anyhow!("connection timeout"))?
+            .context("failed to connect to the AMQP broker")?;
+        let channel = connection.create_channel().await.context("failed to open a channel")?;
+        if s.publisher_confirms {
+            channel
+                .confirm_select(ConfirmSelectOptions::default())
+                .await
+                .context("failed to enable the publisher confirms")?;
+        }
+        if let Some(kind) = &s.declare_exchange {
+            let options = ExchangeDeclareOptions {
+                durable: true,
+                ..Default::default()
+            };
+            channel
+                .exchange_declare(&s.exchange, kind.clone(), options, FieldTable::default())
+                .await
+                .with_context(|| format!("failed to declare the exchange {:?}", s.exchange))?;
+        }
+        log::debug!(
+            "Connected to the AMQP broker at {}:{}",
+            s.uri.authority.host,
+            s.uri.authority.port
+        );
+        Ok(Session { connection, channel })
+    }
+
+    /// Publishes the messages, and waits for their confirmation if enabled.
+    async fn publish(&mut self, messages: &[(String, Vec<u8>)]) -> anyhow::Result<()> {
+        let session = match &self.session {
+            Some(session) if session.connection.status().connected() && session.channel.status().connected() => session,
+            _ => {
+                self.session = None;
+                self.session.insert(self.connect().await?)
+            }
+        };
+
+        let s = &self.settings;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut properties = BasicProperties::default()
+            .with_content_type(CONTENT_TYPE.into())
+            .with_app_id("alumet".into())
+            .with_timestamp(timestamp);
+        if s.persistent {
+            properties = properties.with_delivery_mode(2);
+        }
+
+        let mut confirms: Vec<PublisherConfirm> = Vec::with_capacity(messages.len());
+        for (routing_key, body) in messages {
+            let confirm = session
+                .channel
+                .basic_publish(
+                    &s.exchange,
+                    routing_key,
+                    BasicPublishOptions::default(),
+                    body,
+                    properties.clone(),
+                )
+                .await
+                .context("failed to publish a message")?;
+            confirms.push(confirm);
+        }
+        for confirm in confirms {
+            let confirmation = tokio::time::timeout(s.timeout, confirm)
+                .await
+                .map_err(|_| anyhow!("the broker did not confirm the messages in time"))?
+                .context("failed to receive the confirmation of a message")?;
+            if let Confirmation::Nack(_) = confirmation {
+                return Err(anyhow!("the broker refused a message"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Output for AmqpOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        // Group the measurements by routing key, in the order of their first appearance.
+        let mut groups: Vec<(String, Vec<Record>)> = Vec::new();
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            let routing_key = self.publisher.settings.routing_key.render(m, metric);
+            let record = Record::new(m, metric);
+            match groups.iter_mut().find(|(key, _)| *key == routing_key) {
+                Some((_, records)) => records.push(record),
+                None => groups.push((routing_key, vec![record])),
+            }
+        }
+        let messages: Vec<(String, Vec<u8>)> = groups
+            .iter()
+            .flat_map(|(key, records)| {
+                records
+                    .chunks(self.publisher.settings.max_batch_size)
+                    .map(|batch| (key.clone(), payload::encode(batch)))
+            })
+            .collect();
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let res = self.runtime.block_on(self.publisher.publish(&messages));
+        if res.is_err() {
+            // Reconnect on the next write.
+            self.publisher.session = None;
+        }
+        res.retry_write()
+    }
+}
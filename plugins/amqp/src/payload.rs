@@ -0,0 +1,55 @@
+//! Content of the messages: a JSON array of measurements.
+
+use std::{collections::BTreeMap, time::SystemTime};
+
+use alumet::{
+    json,
+    measurement::{MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+/// Content type of the messages.
+pub const CONTENT_TYPE: &str = "application/json";
+
+/// A measurement in a message.
+///
+/// The fields are serialized in this order, and the attributes are sorted by key,
+/// whatever the features of `serde_json`.
+#[derive(Serialize)]
+pub struct Record<'a> {
+    metric: &'a str,
+    timestamp: String,
+    value: Value,
+    unit: String,
+    resource_kind: &'a str,
+    resource_id: String,
+    consumer_kind: &'a str,
+    consumer_id: String,
+    attributes: BTreeMap<&'a str, Value>,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(m: &'a MeasurementPoint, metric: &'a Metric) -> Self {
+        Self {
+            metric: &metric.name,
+            timestamp: humantime::format_rfc3339_nanos(SystemTime::from(m.timestamp)).to_string(),
+            value: match m.value {
+                WrappedMeasurementValue::F64(v) => json!(v),
+                WrappedMeasurementValue::U64(v) => json!(v),
+            },
+            unit: metric.unit.unique_name(),
+            resource_kind: m.resource.kind(),
+            resource_id: m.resource.id_display().to_string(),
+            consumer_kind: m.consumer.kind(),
+            consumer_id: m.consumer.id_display().to_string(),
+            attributes: json::attributes(m),
+        }
+    }
+}
+
+/// Returns the body of a message that contains `records`.
+pub fn encode(records: &[Record]) -> Vec<u8> {
+    serde_json::to_vec(records).expect("a record should always be serializable")
+}
@@ -0,0 +1,91 @@
+//! Templates of the routing keys, such as `alumet.{hostname}.{metric}`.
+
+use alumet::{measurement::MeasurementPoint, metrics::Metric, template::MeasurementTemplate};
+use anyhow::Context;
+
+/// Maximum length of a routing key, in bytes.
+const MAX_ROUTING_KEY_LEN: usize = 255;
+
+/// A parsed routing key template.
+#[derive(Debug, PartialEq)]
+pub struct RoutingKeyTemplate(MeasurementTemplate);
+
+impl RoutingKeyTemplate {
+    /// Parses a template.
+    ///
+    /// The placeholders are replaced by the corresponding values for each measurement.
+    /// The hostname is resolved here, since it does not change.
+    pub fn parse(template: &str, hostname: &str) -> anyhow::Result<Self> {
+        let parsed = MeasurementTemplate::parse_measurement(template, &sanitize(hostname))
+            .with_context(|| format!("invalid routing key template {template:?}"))?;
+        Ok(Self(parsed))
+    }
+
+    /// Returns the routing key of a measurement.
+    ///
+    /// Routing keys longer than 255 bytes are truncated.
+    pub fn render(&self, m: &MeasurementPoint, metric: &Metric) -> String {
+        let mut res = self.0.render_measurement(m, metric, sanitize);
+        if res.len() > MAX_ROUTING_KEY_LEN {
+            let mut end = MAX_ROUTING_KEY_LEN;
+            while !res.is_char_boundary(end) {
+                end -= 1;
+            }
+            res.truncate(end);
+        }
+        res
+    }
+}
+
+/// Replaces the characters that have a special meaning in the bindings of topic exchanges.
+///
+/// A value must not create new words (`.`) or wildcards (`*`, `#`) in the routing key.
+fn sanitize(value: &str) -> String {
+    value.replace(['.', '*', '#'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        resources::ResourceConsumer,
+        test::fixture::{metric, point},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::RoutingKeyTemplate;
+
+    #[test]
+    fn render() {
+        let template = RoutingKeyTemplate::parse("alumet.{hostname}", "node-1.example.com").unwrap();
+        assert_eq!(
+            template.render(&point(ResourceConsumer::LocalMachine), &metric()),
+            "alumet.node-1_example_com"
+        );
+
+        let template = RoutingKeyTemplate::parse(
+            "{hostname}.{metric}.{resource_kind}.{resource_id}.{consumer_kind}.{consumer_id}",
+            "node-1",
+        )
+        .unwrap();
+        let consumer = ResourceConsumer::ControlGroup {
+            path: "/system.slice/test.service".into(),
+        };
+        assert_eq!(
+            template.render(&point(consumer), &metric()),
+            "node-1.rapl_consumed_energy.cpu_package.1.cgroup./system_slice/test_service"
+        );
+    }
+
+    #[test]
+    fn truncate() {
+        let template = RoutingKeyTemplate::parse("{hostname}", &"é".repeat(200)).unwrap();
+        let key = template.render(&point(ResourceConsumer::LocalMachine), &metric());
+        assert_eq!(key.len(), 254);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(RoutingKeyTemplate::parse("alumet.{host}", "node").is_err());
+        assert!(RoutingKeyTemplate::parse("alumet.{metric", "node").is_err());
+    }
+}
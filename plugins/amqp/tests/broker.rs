@@ -0,0 +1,225 @@
+//! A minimal AMQP 0-9-1 broker that records the published messages.
+//!
+//! It implements just enough of the protocol for the plugin: the handshake, channels,
+//! exchange declarations, publisher confirms and publications.
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use amq_protocol::{
+    frame::{AMQPFrame, WriteContext, gen_frame, parse_frame},
+    protocol::{AMQPClass, basic, channel, confirm, connection, exchange},
+    types::FieldTable,
+};
+
+/// A message received by the broker.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub exchange: String,
+    pub routing_key: String,
+    pub content_type: Option<String>,
+    pub delivery_mode: Option<u8>,
+    pub body: Vec<u8>,
+}
+
+/// An exchange declared by the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declared {
+    pub name: String,
+    pub kind: String,
+    pub durable: bool,
+}
+
+#[derive(Default)]
+struct State {
+    messages: Vec<Message>,
+    declared: Vec<Declared>,
+}
+
+pub struct FakeBroker {
+    port: u16,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeBroker {
+    /// Starts a broker that acknowledges the messages, or rejects them if `nack` is true.
+    pub fn start(nack: bool) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let state = Arc::new(Mutex::new(State::default()));
+        let state2 = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let state = state2.clone();
+                thread::spawn(move || serve(stream, nack, &state));
+            }
+        });
+        Ok(Self { port, state })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn messages(&self) -> Vec<Message> {
+        self.state.lock().unwrap().messages.clone()
+    }
+
+    pub fn declared(&self) -> Vec<Declared> {
+        self.state.lock().unwrap().declared.clone()
+    }
+}
+
+/// A publication whose content has not been fully received yet.
+struct Pending {
+    channel: u16,
+    message: Message,
+    size: u64,
+}
+
+fn serve(mut stream: TcpStream, nack: bool, state: &Mutex<State>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let mut pending: Option<Pending> = None;
+    let mut delivery_tag = 0;
+    let mut confirm_mode = false;
+    loop {
+        let n = match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        buf.extend_from_slice(&chunk[..n]);
+        // Handle every complete frame, the rest is kept for the next read.
+        while let Ok((rest, frame)) = parse_frame(buf.as_slice()) {
+            let consumed = buf.len() - rest.len();
+            buf.drain(..consumed);
+
+            let mut reply = |frame: AMQPFrame| send(&mut stream, &frame);
+            match frame {
+                AMQPFrame::ProtocolHeader(_) => reply(AMQPFrame::Method(
+                    0,
+                    AMQPClass::Connection(connection::AMQPMethod::Start(connection::Start {
+                        version_major: 0,
+                        version_minor: 9,
+                        server_properties: FieldTable::default(),
+                        mechanisms: "PLAIN".into(),
+                        locales: "en_US".into(),
+                    })),
+                )),
+                AMQPFrame::Method(0, AMQPClass::Connection(method)) => match method {
+                    connection::AMQPMethod::StartOk(_) => reply(AMQPFrame::Method(
+                        0,
+                        AMQPClass::Connection(connection::AMQPMethod::Tune(connection::Tune {
+                            channel_max: 2047,
+                            frame_max: 131072,
+                            heartbeat: 0,
+                        })),
+                    )),
+                    connection::AMQPMethod::Open(_) => reply(AMQPFrame::Method(
+                        0,
+                        AMQPClass::Connection(connection::AMQPMethod::OpenOk(connection::OpenOk {})),
+                    )),
+                    connection::AMQPMethod::Close(_) => {
+                        reply(AMQPFrame::Method(
+                            0,
+                            AMQPClass::Connection(connection::AMQPMethod::CloseOk(connection::CloseOk {})),
+                        ));
+                        return;
+                    }
+                    _ => (),
+                },
+                AMQPFrame::Method(ch, AMQPClass::Channel(method)) => match method {
+                    channel::AMQPMethod::Open(_) => reply(AMQPFrame::Method(
+                        ch,
+                        AMQPClass::Channel(channel::AMQPMethod::OpenOk(channel::OpenOk {})),
+                    )),
+                    channel::AMQPMethod::Close(_) => reply(AMQPFrame::Method(
+                        ch,
+                        AMQPClass::Channel(channel::AMQPMethod::CloseOk(channel::CloseOk {})),
+                    )),
+                    _ => (),
+                },
+                AMQPFrame::Method(ch, AMQPClass::Confirm(confirm::AMQPMethod::Select(_))) => {
+                    confirm_mode = true;
+                    reply(AMQPFrame::Method(
+                        ch,
+                        AMQPClass::Confirm(confirm::AMQPMethod::SelectOk(confirm::SelectOk {})),
+                    ))
+                }
+                AMQPFrame::Method(ch, AMQPClass::Exchange(exchange::AMQPMethod::Declare(declare))) => {
+                    state.lock().unwrap().declared.push(Declared {
+                        name: declare.exchange.to_string(),
+                        kind: declare.kind.to_string(),
+                        durable: declare.durable,
+                    });
+                    if !declare.nowait {
+                        reply(AMQPFrame::Method(
+                            ch,
+                            AMQPClass::Exchange(exchange::AMQPMethod::DeclareOk(exchange::DeclareOk {})),
+                        ));
+                    }
+                }
+                AMQPFrame::Method(ch, AMQPClass::Basic(basic::AMQPMethod::Publish(publish))) => {
+                    pending = Some(Pending {
+                        channel: ch,
+                        message: Message {
+                            exchange: publish.exchange.to_string(),
+                            routing_key: publish.routing_key.to_string(),
+                            content_type: None,
+                            delivery_mode: None,
+                            body: Vec::new(),
+                        },
+                        size: 0,
+                    });
+                }
+                AMQPFrame::Header(_, _, header) => {
+                    let p = pending.as_mut().expect("content header without publish");
+                    p.size = header.body_size;
+                    p.message.content_type = header.properties.content_type().as_ref().map(|t| t.to_string());
+                    p.message.delivery_mode = *header.properties.delivery_mode();
+                }
+                AMQPFrame::Body(_, data) => {
+                    pending
+                        .as_mut()
+                        .expect("content body without publish")
+                        .message
+                        .body
+                        .extend(data);
+                }
+                _ => (),
+            }
+
+            // Complete the publication once the whole body has been received.
+            if let Some(p) = pending.take_if(|p| p.size > 0 && p.message.body.len() as u64 == p.size) {
+                state.lock().unwrap().messages.push(p.message);
+                if !confirm_mode {
+                    continue;
+                }
+                delivery_tag += 1;
+                let method = if nack {
+                    basic::AMQPMethod::Nack(basic::Nack {
+                        delivery_tag,
+                        multiple: false,
+                        requeue: false,
+                    })
+                } else {
+                    basic::AMQPMethod::Ack(basic::Ack {
+                        delivery_tag,
+                        multiple: false,
+                    })
+                };
+                send(&mut stream, &AMQPFrame::Method(p.channel, AMQPClass::Basic(method)));
+            }
+        }
+    }
+}
+
+fn send(stream: &mut TcpStream, frame: &AMQPFrame) {
+    let ctx = gen_frame(frame)(WriteContext::from(Vec::new())).expect("the frame should be serializable");
+    stream.write_all(&ctx.write).unwrap();
+}
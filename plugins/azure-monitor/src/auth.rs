@@ -0,0 +1,164 @@
+//! Access tokens of Microsoft Entra ID (formerly Azure Active Directory).
+//!
+//! The tokens are obtained with the secret of an application (client credentials),
+//! or from the managed identity of the machine, through the Instance Metadata Service (IMDS).
+//! See <https://learn.microsoft.com/en-us/entra/identity-platform/v2-oauth2-client-creds-grant-flow>
+//! and <https://learn.microsoft.com/en-us/entra/identity/managed-identities-azure-resources/how-to-use-vm-token>.
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, anyhow};
+use reqwest::blocking::{Client, Response};
+use serde::Deserialize;
+
+/// The tokens are renewed a bit before their expiration.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// How to obtain the tokens.
+pub enum Credentials {
+    /// An application registered in Entra ID, and one of its secrets.
+    ClientSecret {
+        /// URL of the identity platform, such as `https://login.microsoftonline.com`.
+        authority: String,
+        tenant_id: String,
+        client_id: String,
+        client_secret: String,
+    },
+    /// The managed identity of the machine.
+    ManagedIdentity {
+        /// URL of the IMDS, such as `http://169.254.169.254`.
+        imds_endpoint: String,
+        /// Client id of a user-assigned identity, if the machine has several identities.
+        client_id: Option<String>,
+    },
+}
+
+/// Provides valid tokens for a resource, and renews them when needed.
+pub struct TokenProvider {
+    credentials: Credentials,
+    /// The resource that accepts the tokens, such as `https://monitoring.azure.com/`.
+    resource: &'static str,
+    /// The current token and its expiration.
+    token: Option<(String, Instant)>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: ExpiresIn,
+}
+
+/// The IMDS returns the lifetime of the token as a string, the identity platform as a number.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExpiresIn {
+    Number(u64),
+    String(String),
+}
+
+impl TokenProvider {
+    pub fn new(credentials: Credentials, resource: &'static str) -> Self {
+        Self {
+            credentials,
+            resource,
+            token: None,
+        }
+    }
+
+    /// Returns a valid token, and requests a new one if needed.
+    pub fn token(&mut self, client: &Client) -> anyhow::Result<&str> {
+        let now = Instant::now();
+        let valid = matches!(&self.token, Some((_, expiry)) if *expiry > now + EXPIRY_MARGIN);
+        if !valid {
+            let response = self.request(client)?;
+            let expires_in = match response.expires_in {
+                ExpiresIn::Number(n) => n,
+                ExpiresIn::String(s) => s.parse().context("invalid expires_in in the token response")?,
+            };
+            self.token = Some((response.access_token, now + Duration::from_secs(expires_in)));
+        }
+        Ok(&self.token.as_ref().unwrap().0)
+    }
+
+    /// Forgets the current token, for instance because the API rejected it.
+    pub fn invalidate(&mut self) {
+        self.token = None;
+    }
+
+    fn request(&self, client: &Client) -> anyhow::Result<TokenResponse> {
+        let res = match &self.credentials {
+            Credentials::ClientSecret {
+                authority,
+                tenant_id,
+                client_id,
+                client_secret,
+            } => {
+                let url = format!("{}/{tenant_id}/oauth2/v2.0/token", authority.trim_end_matches('/'));
+                let scope = format!("{}.default", self.resource);
+                client
+                    .post(&url)
+                    .form(&[
+                        ("grant_type", "client_credentials"),
+                        ("client_id", client_id),
+                        ("client_secret", client_secret),
+                        ("scope", &scope),
+                    ])
+                    .send()
+                    .with_context(|| format!("failed to request an access token from {url}"))?
+            }
+            Credentials::ManagedIdentity {
+                imds_endpoint,
+                client_id,
+            } => {
+                let mut query = vec![("api-version", "2018-02-01"), ("resource", self.resource)];
+                if let Some(client_id) = client_id {
+                    query.push(("client_id", client_id));
+                }
+                client
+                    .get(format!(
+                        "{}/metadata/identity/oauth2/token",
+                        imds_endpoint.trim_end_matches('/')
+                    ))
+                    .query(&query)
+                    .header("Metadata", "true")
+                    .send()
+                    .with_context(|| format!("failed to request an access token from the IMDS at {imds_endpoint}"))?
+            }
+        };
+        let body = read_success(res).context("failed to obtain an access token")?;
+        serde_json::from_slice(&body).context("invalid access token response")
+    }
+}
+
+/// Location and identifier of the virtual machine, from the IMDS.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceMetadata {
+    pub location: String,
+    pub resource_id: String,
+}
+
+/// Queries the IMDS for the location and the identifier of the virtual machine.
+pub fn instance_metadata(client: &Client, imds_endpoint: &str) -> anyhow::Result<InstanceMetadata> {
+    let res = client
+        .get(format!(
+            "{}/metadata/instance/compute",
+            imds_endpoint.trim_end_matches('/')
+        ))
+        .query(&[("api-version", "2021-02-01")])
+        .header("Metadata", "true")
+        .send()
+        .with_context(|| format!("failed to query the IMDS at {imds_endpoint}"))?;
+    let body = read_success(res).context("failed to query the IMDS")?;
+    serde_json::from_slice(&body).context("invalid response of the IMDS")
+}
+
+/// Returns the body of the response, or an error if its status is not a success.
+fn read_success(res: Response) -> anyhow::Result<Vec<u8>> {
+    let status = res.status();
+    let body = res.bytes().context("failed to read the response")?;
+    if !status.is_success() {
+        return Err(anyhow!("{status}: {}", String::from_utf8_lossy(&body)));
+    }
+    Ok(body.to_vec())
+}
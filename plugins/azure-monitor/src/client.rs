@@ -0,0 +1,54 @@
+use alumet::pipeline::elements::{error::WriteError, output::error::WriteRetry};
+use anyhow::{Context, anyhow};
+use reqwest::{StatusCode, blocking::Client};
+
+use crate::auth::TokenProvider;
+
+/// Sends authenticated requests to the APIs of Azure Monitor.
+pub struct AzureClient {
+    http: Client,
+    tokens: TokenProvider,
+}
+
+impl AzureClient {
+    pub fn new(http: Client, tokens: TokenProvider) -> Self {
+        Self { http, tokens }
+    }
+
+    /// Posts a JSON body to `url`.
+    ///
+    /// Returns the status and the body of the response if the request has been rejected,
+    /// or an error if it can be retried later.
+    pub fn post(&mut self, url: &str, body: Vec<u8>) -> Result<Option<(StatusCode, String)>, WriteError> {
+        let token = self
+            .tokens
+            .token(&self.http)
+            .context("failed to authenticate to Microsoft Entra ID")
+            .retry_write()?;
+        let res = self
+            .http
+            .post(url)
+            .bearer_auth(token)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .context("failed to send the request to Azure Monitor")
+            .retry_write()?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(None);
+        }
+        let body = res.text().unwrap_or_default();
+        if status == StatusCode::UNAUTHORIZED {
+            // The token has been revoked or has expired earlier than expected.
+            self.tokens.invalidate();
+            return Err(WriteError::CanRetry(anyhow!("Azure Monitor error {status}: {body}")));
+        }
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            // The API is busy or unavailable, try again later.
+            return Err(WriteError::CanRetry(anyhow!("Azure Monitor error {status}: {body}")));
+        }
+        Ok(Some((status, body)))
+    }
+}
@@ -0,0 +1,251 @@
+mod auth;
+mod client;
+mod logs;
+mod metrics;
+
+use std::time::Duration;
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{config_or_env, deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::{Context, anyhow};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Credentials, TokenProvider};
+use crate::client::AzureClient;
+use crate::logs::LogsOutput;
+use crate::metrics::MetricsOutput;
+
+pub struct AzureMonitorPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for AzureMonitorPlugin {
+    fn name() -> &'static str {
+        "azure-monitor"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(config.batch_size > 0, "batch_size must be greater than zero");
+        if config.destination == Destination::Logs {
+            anyhow::ensure!(
+                !config.logs.endpoint.is_empty() && !config.logs.rule_id.is_empty(),
+                "the endpoint and the rule_id of the data collection rule are required to send logs"
+            );
+        }
+        Ok(Box::new(AzureMonitorPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let http = Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .context("failed to create the HTTP client")?;
+        let credentials = credentials(&config.auth)?;
+
+        match config.destination {
+            Destination::Metrics => {
+                let (region, resource_id) = match (&config.metrics.region, &config.metrics.resource_id) {
+                    (Some(region), Some(resource_id)) => (region.clone(), resource_id.clone()),
+                    (region, resource_id) => {
+                        // Send the metrics of the virtual machine on which Alumet runs.
+                        let instance = auth::instance_metadata(&http, &config.auth.imds_endpoint).context(
+                            "No region or resource_id specified in the config, and unable to retrieve them from the IMDS.",
+                        )?;
+                        (
+                            region.clone().unwrap_or(instance.location),
+                            resource_id.clone().unwrap_or(instance.resource_id),
+                        )
+                    }
+                };
+                let endpoint = match &config.metrics.endpoint {
+                    Some(endpoint) => endpoint.trim_end_matches('/').to_owned(),
+                    None => format!("https://{region}.monitoring.azure.com"),
+                };
+                let client = AzureClient::new(http, TokenProvider::new(credentials, metrics::RESOURCE));
+                let output = MetricsOutput::new(
+                    client,
+                    format!("{endpoint}/{}/metrics", resource_id.trim_matches('/')),
+                    config.metrics.namespace.clone(),
+                    hostname,
+                    config.batch_size,
+                );
+                alumet.add_blocking_output("out", Box::new(output))?;
+            }
+            Destination::Logs => {
+                let logs = &config.logs;
+                let client = AzureClient::new(http, TokenProvider::new(credentials, logs::RESOURCE));
+                let url = format!(
+                    "{}/dataCollectionRules/{}/streams/{}?api-version=2023-01-01",
+                    logs.endpoint.trim_end_matches('/'),
+                    logs.rule_id,
+                    logs.stream
+                );
+                let output = LogsOutput::new(client, url, hostname, config.batch_size);
+                alumet.add_blocking_output("out", Box::new(output))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Uses the secret of an application if there is one, like the `EnvironmentCredential` of the Azure SDKs,
+/// or the managed identity of the machine.
+fn credentials(auth: &AuthConfig) -> anyhow::Result<Credentials> {
+    let client_id = config_or_env(&auth.client_id, "AZURE_CLIENT_ID");
+    match config_or_env(&auth.client_secret, "AZURE_CLIENT_SECRET") {
+        Some(client_secret) => Ok(Credentials::ClientSecret {
+            authority: auth.authority.clone(),
+            tenant_id: config_or_env(&auth.tenant_id, "AZURE_TENANT_ID")
+                .ok_or_else(|| anyhow!("missing tenant_id, required with a client_secret"))?,
+            client_id: client_id.ok_or_else(|| anyhow!("missing client_id, required with a client_secret"))?,
+            client_secret,
+        }),
+        None => Ok(Credentials::ManagedIdentity {
+            imds_endpoint: auth.imds_endpoint.clone(),
+            client_id,
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Send the measurements as custom metrics of an Azure resource (`"metrics"`),
+    /// or as rows of a Log Analytics table (`"logs"`).
+    pub destination: Destination,
+    /// Value of the `hostname` dimension or column. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Maximum number of series (metrics) or rows (logs) per request.
+    pub batch_size: usize,
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub logs: LogsConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Destination {
+    Metrics,
+    Logs,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Application registered in Entra ID, and one of its secrets. Default to the environment variables
+    /// `AZURE_TENANT_ID`, `AZURE_CLIENT_ID` and `AZURE_CLIENT_SECRET`.
+    ///
+    /// Without a secret, the managed identity of the machine is used,
+    /// and `client_id` selects one of its user-assigned identities.
+    pub tenant_id: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// URL of the Microsoft identity platform.
+    pub authority: String,
+    /// URL of the Azure Instance Metadata Service.
+    pub imds_endpoint: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Region of the resource, such as `westeurope`. Defaults to the region of the virtual machine.
+    pub region: Option<String>,
+    /// Identifier of the resource to which the metrics are attached. Defaults to the virtual machine.
+    pub resource_id: Option<String>,
+    /// Namespace of the custom metrics.
+    pub namespace: String,
+    /// URL of the custom metrics API. Defaults to the endpoint of the region.
+    pub endpoint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogsConfig {
+    /// Logs ingestion endpoint of the data collection endpoint (or of the data collection rule).
+    pub endpoint: String,
+    /// Immutable identifier of the data collection rule, such as `dcr-00000000000000000000000000000000`.
+    pub rule_id: String,
+    /// Name of the stream declared in the data collection rule.
+    pub stream: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            destination: Destination::Metrics,
+            hostname: None,
+            batch_size: 500,
+            timeout: Duration::from_secs(30),
+            auth: AuthConfig::default(),
+            metrics: MetricsConfig::default(),
+            logs: LogsConfig::default(),
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            tenant_id: None,
+            client_id: None,
+            client_secret: None,
+            authority: String::from("https://login.microsoftonline.com"),
+            imds_endpoint: String::from("http://169.254.169.254"),
+        }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            region: None,
+            resource_id: None,
+            namespace: String::from("Alumet"),
+            endpoint: None,
+        }
+    }
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            rule_id: String::new(),
+            stream: String::from("Custom-Alumet_CL"),
+        }
+    }
+}
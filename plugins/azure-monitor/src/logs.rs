@@ -0,0 +1,98 @@
+//! Output to a Log Analytics workspace, through the Logs Ingestion API.
+//!
+//! See <https://learn.microsoft.com/en-us/azure/azure-monitor/logs/logs-ingestion-api-overview>.
+
+use std::{collections::BTreeMap, time::SystemTime};
+
+use alumet::{
+    json,
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use anyhow::Context;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::client::AzureClient;
+
+/// The resource that accepts the tokens of the Logs Ingestion API.
+pub const RESOURCE: &str = "https://monitor.azure.com/";
+
+pub struct LogsOutput {
+    client: AzureClient,
+    /// URL of the stream, such as
+    /// `https://my-dce.westeurope-1.ingest.monitor.azure.com/dataCollectionRules/dcr-.../streams/Custom-Alumet_CL?api-version=2023-01-01`.
+    url: String,
+    hostname: String,
+    /// Maximum number of records per request.
+    batch_size: usize,
+}
+
+/// A row of the table. The columns follow the naming conventions of Log Analytics.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Record<'a> {
+    time_generated: String,
+    metric: &'a str,
+    value: Value,
+    unit: String,
+    hostname: &'a str,
+    resource_kind: &'a str,
+    resource_id: String,
+    consumer_kind: &'a str,
+    consumer_id: String,
+    attributes: BTreeMap<&'a str, Value>,
+}
+
+impl<'a> Record<'a> {
+    fn new(m: &'a MeasurementPoint, metric: &'a Metric, hostname: &'a str) -> Self {
+        Self {
+            time_generated: humantime::format_rfc3339_nanos(SystemTime::from(m.timestamp)).to_string(),
+            metric: &metric.name,
+            value: match m.value {
+                WrappedMeasurementValue::F64(v) => json!(v),
+                WrappedMeasurementValue::U64(v) => json!(v),
+            },
+            unit: metric.unit.unique_name(),
+            hostname,
+            resource_kind: m.resource.kind(),
+            resource_id: m.resource.id_display().to_string(),
+            consumer_kind: m.consumer.kind(),
+            consumer_id: m.consumer.id_display().to_string(),
+            attributes: json::attributes(m),
+        }
+    }
+}
+
+impl LogsOutput {
+    pub fn new(client: AzureClient, url: String, hostname: String, batch_size: usize) -> Self {
+        Self {
+            client,
+            url,
+            hostname,
+            batch_size,
+        }
+    }
+}
+
+impl alumet::pipeline::Output for LogsOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut records = Vec::with_capacity(measurements.len());
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            records.push(Record::new(m, metric, &self.hostname));
+        }
+        for batch in records.chunks(self.batch_size) {
+            let body = serde_json::to_vec(batch).context("failed to serialize the records")?;
+            if let Some((status, body)) = self.client.post(&self.url, body)? {
+                // Invalid data, unknown stream, missing permissions... sending the records again would not help.
+                log::error!("Azure Monitor rejected the records with status {status}: {body}");
+            }
+        }
+        Ok(())
+    }
+}
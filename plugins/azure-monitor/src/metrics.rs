@@ -0,0 +1,155 @@
+//! Output to the custom metrics of Azure Monitor.
+//!
+//! See <https://learn.microsoft.com/en-us/azure/azure-monitor/essentials/metrics-store-custom-rest-api>.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::client::AzureClient;
+
+/// The resource that accepts the tokens of the custom metrics API.
+pub const RESOURCE: &str = "https://monitoring.azure.com/";
+
+/// The dimensions of every metric.
+const DIMENSIONS: [&str; 5] = [
+    "hostname",
+    "resource_kind",
+    "resource_id",
+    "consumer_kind",
+    "consumer_id",
+];
+
+pub struct MetricsOutput {
+    client: AzureClient,
+    /// URL of the metrics of the Azure resource, such as
+    /// `https://westeurope.monitoring.azure.com/subscriptions/.../virtualMachines/vm-1/metrics`.
+    url: String,
+    namespace: String,
+    hostname: String,
+    /// Maximum number of series per request.
+    batch_size: usize,
+}
+
+#[derive(Serialize)]
+struct CustomMetric<'a> {
+    time: String,
+    data: Data<'a>,
+}
+
+#[derive(Serialize)]
+struct Data<'a> {
+    #[serde(rename = "baseData")]
+    base_data: BaseData<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BaseData<'a> {
+    metric: &'a str,
+    namespace: &'a str,
+    dim_names: [&'static str; 5],
+    series: &'a [Series],
+}
+
+/// The values of a series during one minute, pre-aggregated as required by the API.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Series {
+    dim_values: [String; 5],
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
+
+impl Series {
+    fn add(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+impl MetricsOutput {
+    pub fn new(client: AzureClient, url: String, namespace: String, hostname: String, batch_size: usize) -> Self {
+        Self {
+            client,
+            url,
+            namespace,
+            hostname,
+            batch_size,
+        }
+    }
+}
+
+impl alumet::pipeline::Output for MetricsOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        // The API stores the metrics with a resolution of one minute, and each request contains
+        // the values of a single metric at a single time: group the points by metric and by minute.
+        let mut groups: BTreeMap<(&str, u64), BTreeMap<[String; 5], Series>> = BTreeMap::new();
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
+            };
+            let (secs, _) = m.timestamp.to_unix_timestamp();
+            let dim_values = [
+                self.hostname.clone(),
+                m.resource.kind().to_owned(),
+                m.resource.id_display().to_string(),
+                m.consumer.kind().to_owned(),
+                m.consumer.id_display().to_string(),
+            ];
+            groups
+                .entry((&metric.name, secs - secs % 60))
+                .or_default()
+                .entry(dim_values)
+                .and_modify(|s| s.add(value))
+                .or_insert_with_key(|dim_values| Series {
+                    dim_values: dim_values.clone(),
+                    min: value,
+                    max: value,
+                    sum: value,
+                    count: 1,
+                });
+        }
+
+        for ((metric, minute), series) in groups {
+            let series: Vec<Series> = series.into_values().collect();
+            let time = UNIX_EPOCH + Duration::from_secs(minute);
+            for batch in series.chunks(self.batch_size) {
+                let body = CustomMetric {
+                    time: humantime::format_rfc3339_seconds(time).to_string(),
+                    data: Data {
+                        base_data: BaseData {
+                            metric,
+                            namespace: &self.namespace,
+                            dim_names: DIMENSIONS,
+                            series: batch,
+                        },
+                    },
+                };
+                let body = serde_json::to_vec(&body).context("failed to serialize the custom metric")?;
+                if let Some((status, body)) = self.client.post(&self.url, body)? {
+                    // Invalid data, missing permissions... sending the metric again would not help.
+                    log::error!("Azure Monitor rejected the metric {metric} with status {status}: {body}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
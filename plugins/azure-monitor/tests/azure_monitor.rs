@@ -0,0 +1,274 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use alumet::{
+    measurement::{MeasurementPoint, Timestamp},
+    pipeline::elements::error::WriteError,
+    plugin::rust::serialize_config,
+    resources::{Resource, ResourceConsumer},
+    test::{
+        PluginHarness,
+        golden::{CapturedRequest, HttpCapture, SampleMeasurements, assert_golden},
+    },
+};
+use mockito::{Matcher, Mock, Server};
+use plugin_azure_monitor::{AuthConfig, AzureMonitorPlugin, Config, Destination, LogsConfig, MetricsConfig};
+use pretty_assertions::assert_eq;
+
+const RESOURCE_ID: &str = "/subscriptions/sub-1/resourceGroups/rg-1/providers/Microsoft.Compute/virtualMachines/vm-1";
+
+/// Authenticates with the secret of an application, on the mock server.
+fn auth(server: &Server) -> AuthConfig {
+    AuthConfig {
+        tenant_id: Some(String::from("tenant-1")),
+        client_id: Some(String::from("client-1")),
+        client_secret: Some(String::from("secret-1")),
+        authority: server.url(),
+        ..AuthConfig::default()
+    }
+}
+
+fn metrics_config(server: &Server, api: &HttpCapture) -> Config {
+    Config {
+        destination: Destination::Metrics,
+        hostname: Some(String::from("node-1")),
+        auth: auth(server),
+        metrics: MetricsConfig {
+            region: Some(String::from("westeurope")),
+            resource_id: Some(String::from(RESOURCE_ID)),
+            endpoint: Some(api.url()),
+            ..MetricsConfig::default()
+        },
+        ..Config::default()
+    }
+}
+
+fn logs_config(server: &Server, api: &HttpCapture) -> Config {
+    Config {
+        destination: Destination::Logs,
+        hostname: Some(String::from("node-1")),
+        auth: auth(server),
+        logs: LogsConfig {
+            endpoint: api.url(),
+            rule_id: String::from("dcr-1"),
+            ..LogsConfig::default()
+        },
+        ..Config::default()
+    }
+}
+
+/// Mocks the token endpoint of the identity platform, for the given resource.
+fn mock_token(server: &mut Server, resource: &str) -> Mock {
+    server
+        .mock("POST", "/tenant-1/oauth2/v2.0/token")
+        .match_body(Matcher::AllOf(vec![
+            Matcher::UrlEncoded(String::from("grant_type"), String::from("client_credentials")),
+            Matcher::UrlEncoded(String::from("client_id"), String::from("client-1")),
+            Matcher::UrlEncoded(String::from("client_secret"), String::from("secret-1")),
+            Matcher::UrlEncoded(String::from("scope"), format!("{resource}.default")),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"token_type":"Bearer","expires_in":3599,"access_token":"test-token"}"#)
+        .create()
+}
+
+/// Returns the requests sent to the API, after checking that they are sent to `path` with the token.
+fn sent_to(api: &HttpCapture, path: &str) -> Vec<CapturedRequest> {
+    let requests = api.requests();
+    for request in &requests {
+        assert_eq!((request.method.as_str(), request.path.as_str()), ("POST", path));
+        assert_eq!(request.header("authorization"), Some("Bearer test-token"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+    }
+    requests
+}
+
+/// Puts each request body on its own line, to make the golden files readable.
+fn lines(requests: &[CapturedRequest], separator: &str) -> String {
+    requests
+        .iter()
+        .map(|r| {
+            r.body_str()
+                .replace(&format!("}},{separator}"), &format!("}},\n{separator}"))
+                + "\n"
+        })
+        .collect()
+}
+
+#[test]
+fn metrics() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let token = mock_token(&mut server, "https://monitoring.azure.com/");
+    let api = HttpCapture::start()?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<AzureMonitorPlugin>(metrics_config(&server, &api))?;
+    let mut output = harness.output("out")?;
+    // two points of the same series in the same minute: they are aggregated
+    let mut buffer = sample.measurements();
+    let t = Timestamp::from(UNIX_EPOCH + SampleMeasurements::START + Duration::from_secs(30));
+    buffer.push(MeasurementPoint::new(
+        t,
+        sample.energy,
+        Resource::CpuPackage { id: 0 },
+        ResourceConsumer::LocalMachine,
+        7.5,
+    ));
+    output.write(&buffer).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    // the token is reused by the requests
+    token.assert();
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/metrics.json"),
+        lines(&sent_to(&api, &format!("{RESOURCE_ID}/metrics")), r#"{"dimValues""#),
+    );
+    Ok(())
+}
+
+#[test]
+fn managed_identity() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let imds = server
+        .mock("GET", "/metadata/instance/compute")
+        .match_query(Matcher::UrlEncoded(
+            String::from("api-version"),
+            String::from("2021-02-01"),
+        ))
+        .match_header("metadata", "true")
+        .with_body(format!(
+            r#"{{"location":"westeurope","resourceId":"{RESOURCE_ID}","vmSize":"Standard_D2s_v3"}}"#
+        ))
+        .create();
+    let token = server
+        .mock("GET", "/metadata/identity/oauth2/token")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded(String::from("resource"), String::from("https://monitoring.azure.com/")),
+            Matcher::UrlEncoded(String::from("client_id"), String::from("identity-1")),
+        ]))
+        .match_header("metadata", "true")
+        .with_body(r#"{"access_token":"test-token","expires_in":"3599","token_type":"Bearer"}"#)
+        .create();
+    let api = HttpCapture::start()?;
+
+    let config = Config {
+        auth: AuthConfig {
+            client_id: Some(String::from("identity-1")),
+            imds_endpoint: server.url(),
+            ..AuthConfig::default()
+        },
+        metrics: MetricsConfig {
+            endpoint: Some(api.url()),
+            ..MetricsConfig::default()
+        },
+        ..metrics_config(&server, &api)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<AzureMonitorPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    imds.assert();
+    token.assert();
+    // one request per metric
+    assert_eq!(sent_to(&api, &format!("{RESOURCE_ID}/metrics")).len(), 2);
+    Ok(())
+}
+
+#[test]
+fn logs() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    mock_token(&mut server, "https://monitor.azure.com/");
+    let api = HttpCapture::with_status(204)?;
+
+    let config = Config {
+        batch_size: 2,
+        ..logs_config(&server, &api)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<AzureMonitorPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let requests = sent_to(
+        &api,
+        "/dataCollectionRules/dcr-1/streams/Custom-Alumet_CL?api-version=2023-01-01",
+    );
+    assert_eq!(requests.len(), 3);
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/logs.json"),
+        lines(&requests, r#"{"TimeGenerated""#),
+    );
+    Ok(())
+}
+
+#[test]
+fn server_error() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    mock_token(&mut server, "https://monitoring.azure.com/");
+    let api = HttpCapture::with_status(503)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<AzureMonitorPlugin>(metrics_config(&server, &api))?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn rejected() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    mock_token(&mut server, "https://monitor.azure.com/");
+    let api = HttpCapture::with_status(400)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<AzureMonitorPlugin>(logs_config(&server, &api))?;
+    let mut output = harness.output("out")?;
+    // the records are dropped
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    assert_eq!(api.requests().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn token_refused() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    server
+        .mock("POST", "/tenant-1/oauth2/v2.0/token")
+        .with_status(401)
+        .with_body(r#"{"error":"invalid_client"}"#)
+        .create();
+    let api = HttpCapture::start()?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<AzureMonitorPlugin>(metrics_config(&server, &api))?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    output.finish()?;
+    harness.stop()?;
+
+    assert!(api.requests().is_empty());
+    Ok(())
+}
+
+#[test]
+fn invalid_config() -> anyhow::Result<()> {
+    let config = Config {
+        destination: Destination::Logs,
+        ..Config::default()
+    };
+    assert!(PluginHarness::<AzureMonitorPlugin>::start(serialize_config(config)?).is_err());
+
+    let config = Config {
+        batch_size: 0,
+        ..Config::default()
+    };
+    assert!(PluginHarness::<AzureMonitorPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
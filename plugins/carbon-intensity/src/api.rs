@@ -0,0 +1,45 @@
+//! Carbon intensity of the grid, common to the different providers.
+
+use std::time::Duration;
+
+use alumet::pipeline::elements::source::blocking::BlockingDrop;
+
+use anyhow::{Context, anyhow};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+
+/// API of a provider, which gives the carbon intensity of a zone.
+pub trait IntensityApi: Send {
+    /// Fetches the latest carbon intensity of the zone, in gCO2eq/kWh.
+    fn fetch(&mut self) -> anyhow::Result<f64>;
+}
+
+/// An HTTP client for the API of a provider.
+pub struct HttpClient {
+    client: BlockingDrop<Client>,
+    url: String,
+}
+
+impl HttpClient {
+    pub fn new(url: &str, timeout: Duration) -> anyhow::Result<Self> {
+        let client = Client::builder().timeout(timeout).build()?;
+        Ok(Self {
+            client: BlockingDrop::new(client),
+            url: url.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.client.get(format!("{}{path}", self.url))
+    }
+}
+
+/// Deserializes the body of a response, or returns an error that contains the message of the provider.
+pub fn json<T: DeserializeOwned>(res: Response) -> anyhow::Result<T> {
+    let status = res.status();
+    if !status.is_success() {
+        let body = res.text().unwrap_or_default();
+        return Err(anyhow!("the provider returned {status}: {}", body.trim()));
+    }
+    res.json().context("invalid response")
+}
@@ -0,0 +1,89 @@
+//! Real-time éCO2mix data of RTE, for France, through the open data portal of the French grid operators.
+//!
+//! See <https://odre.opendatasoft.com/explore/dataset/eco2mix-national-tr/>.
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::api::{HttpClient, IntensityApi, json};
+
+pub const DEFAULT_URL: &str = "https://odre.opendatasoft.com";
+
+pub struct Eco2mix {
+    client: HttpClient,
+    /// Optional key, which raises the quotas of the portal.
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Records {
+    results: Vec<Record>,
+}
+
+#[derive(Deserialize)]
+struct Record {
+    /// Estimated CO2 emissions of the electricity production, in gCO2/kWh.
+    taux_co2: Option<f64>,
+}
+
+impl Eco2mix {
+    pub fn new(client: HttpClient, api_key: Option<String>) -> Self {
+        Self { client, api_key }
+    }
+}
+
+impl IntensityApi for Eco2mix {
+    fn fetch(&mut self) -> anyhow::Result<f64> {
+        // The dataset has a record per quarter hour, but the last ones have no CO2 rate yet.
+        let mut request = self
+            .client
+            .get("/api/explore/v2.1/catalog/datasets/eco2mix-national-tr/records")
+            .query(&[
+                ("select", "date_heure,taux_co2"),
+                ("where", "taux_co2 is not null"),
+                ("order_by", "date_heure desc"),
+                ("limit", "1"),
+            ]);
+        if let Some(key) = &self.api_key {
+            request = request.header(reqwest::header::AUTHORIZATION, format!("Apikey {key}"));
+        }
+        let records: Records = json(request.send()?)?;
+        records
+            .results
+            .first()
+            .and_then(|r| r.taux_co2)
+            .ok_or_else(|| anyhow!("no CO2 rate in the éCO2mix data"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn latest() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/api/explore/v2.1/catalog/datasets/eco2mix-national-tr/records")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("order_by".into(), "date_heure desc".into()),
+                Matcher::UrlEncoded("limit".into(), "1".into()),
+            ]))
+            .with_body(
+                json!({
+                    "total_count": 35040,
+                    "results": [{ "date_heure": "2025-01-01T10:15:00+00:00", "taux_co2": 27 }]
+                })
+                .to_string(),
+            )
+            .create();
+        let client = HttpClient::new(&server.url(), Duration::from_secs(5)).unwrap();
+        let mut api = Eco2mix::new(client, None);
+        assert_eq!(api.fetch().unwrap(), 27.0);
+    }
+}
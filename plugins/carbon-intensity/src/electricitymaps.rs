@@ -0,0 +1,78 @@
+//! API of Electricity Maps.
+//!
+//! See <https://portal.electricitymaps.com/developer-hub/api/reference#latest-carbon-intensity>.
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::api::{HttpClient, IntensityApi, json};
+
+pub const DEFAULT_URL: &str = "https://api.electricitymap.org";
+
+pub struct ElectricityMaps {
+    client: HttpClient,
+    zone: String,
+    api_key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LatestIntensity {
+    /// In gCO2eq/kWh, `null` when the zone has no data.
+    carbon_intensity: Option<f64>,
+}
+
+impl ElectricityMaps {
+    pub fn new(client: HttpClient, zone: String, api_key: String) -> Self {
+        Self { client, zone, api_key }
+    }
+}
+
+impl IntensityApi for ElectricityMaps {
+    fn fetch(&mut self) -> anyhow::Result<f64> {
+        let res = self
+            .client
+            .get("/v3/carbon-intensity/latest")
+            .query(&[("zone", &self.zone)])
+            .header("auth-token", &self.api_key)
+            .send()?;
+        let latest: LatestIntensity = json(res)?;
+        latest
+            .carbon_intensity
+            .ok_or_else(|| anyhow!("no carbon intensity available for zone {}", self.zone))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn latest() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/v3/carbon-intensity/latest")
+            .match_query(Matcher::UrlEncoded("zone".into(), "DE".into()))
+            .match_header("auth-token", "secret")
+            .with_body(
+                json!({
+                    "zone": "DE",
+                    "carbonIntensity": 302,
+                    "datetime": "2025-01-01T10:00:00.000Z",
+                    "updatedAt": "2025-01-01T09:48:12.533Z",
+                    "emissionFactorType": "lifecycle",
+                    "isEstimated": true
+                })
+                .to_string(),
+            )
+            .create();
+        let client = HttpClient::new(&server.url(), Duration::from_secs(5)).unwrap();
+        let mut api = ElectricityMaps::new(client, String::from("DE"), String::from("secret"));
+        assert_eq!(api.fetch().unwrap(), 302.0);
+    }
+}
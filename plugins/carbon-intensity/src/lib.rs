@@ -0,0 +1,296 @@
+use std::time::Duration;
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, config_or_env, deserialize_config, serialize_config},
+    },
+    units::Unit,
+};
+
+use api::{HttpClient, IntensityApi};
+use source::{CacheSettings, IntensitySource};
+
+mod api;
+mod eco2mix;
+mod electricitymaps;
+mod source;
+mod watttime;
+
+/// Name of the metric, which the transforms that compute carbon emissions look for.
+pub const METRIC_NAME: &str = "grid_carbon_intensity";
+
+pub struct CarbonIntensityPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for CarbonIntensityPlugin {
+    fn name() -> &'static str {
+        "carbon-intensity"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.provider == Provider::Eco2mix && config.zone != "FR" {
+            return Err(anyhow!(
+                "éCO2mix only covers France, please set the zone to \"FR\" or use another provider"
+            ));
+        }
+        Ok(Box::new(CarbonIntensityPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let url = |default: &str| config.url.as_deref().unwrap_or(default).to_owned();
+        // The provider is contacted on the first poll, so that an unreachable provider does not prevent the agent from starting.
+        let api: Box<dyn IntensityApi> = match config.provider {
+            Provider::ElectricityMaps => {
+                let api_key = config_or_env(&config.api_key, "ELECTRICITYMAPS_API_KEY").ok_or_else(|| {
+                    anyhow!("No api_key specified in the config, and ELECTRICITYMAPS_API_KEY is not set.")
+                })?;
+                let client = self.http_client(url(electricitymaps::DEFAULT_URL))?;
+                Box::new(electricitymaps::ElectricityMaps::new(
+                    client,
+                    config.zone.clone(),
+                    api_key,
+                ))
+            }
+            Provider::WattTime => {
+                let username = config_or_env(&config.username, "WATTTIME_USERNAME")
+                    .ok_or_else(|| anyhow!("No username specified in the config, and WATTTIME_USERNAME is not set."))?;
+                let password = config_or_env(&config.password, "WATTTIME_PASSWORD")
+                    .ok_or_else(|| anyhow!("No password specified in the config, and WATTTIME_PASSWORD is not set."))?;
+                let client = self.http_client(url(watttime::DEFAULT_URL))?;
+                Box::new(watttime::WattTime::new(client, config.zone.clone(), username, password))
+            }
+            Provider::Eco2mix => {
+                let client = self.http_client(url(eco2mix::DEFAULT_URL))?;
+                Box::new(eco2mix::Eco2mix::new(client, config.api_key.clone()))
+            }
+        };
+
+        let unit = Unit::Custom {
+            unique_name: String::from("gCO2eq/kWh"),
+            display_name: String::from("gCO₂eq/kWh"),
+        };
+        let metric =
+            alumet.create_metric::<f64>(METRIC_NAME, unit, "Carbon intensity of the electricity of the grid")?;
+        let cache = CacheSettings {
+            refresh_interval: config.refresh_interval,
+            max_age: config.max_age,
+        };
+        let source = IntensitySource::new(api, config.provider.name(), config.zone.clone(), metric, cache);
+        let trigger = TriggerSpec::builder(config.poll_interval)
+            .flush_interval(config.flush_interval)
+            .build()?;
+        alumet.add_source("grid", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl CarbonIntensityPlugin {
+    fn http_client(&self, url: String) -> anyhow::Result<HttpClient> {
+        HttpClient::new(&url, self.config.timeout).context("failed to create the HTTP client")
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub provider: Provider,
+    /// Zone of the grid, whose format depends on the provider: `FR` (Electricity Maps, éCO2mix), `CAISO_NORTH` (WattTime).
+    pub zone: String,
+    /// Key of the Electricity Maps API (defaults to the environment variable `ELECTRICITYMAPS_API_KEY`),
+    /// or optional key of the open data portal of éCO2mix.
+    pub api_key: Option<String>,
+    /// WattTime account. Defaults to the environment variable `WATTTIME_USERNAME`.
+    pub username: Option<String>,
+    /// Password of the WattTime account. Defaults to the environment variable `WATTTIME_PASSWORD`.
+    pub password: Option<String>,
+    /// URL of the API. Defaults to the public API of the provider.
+    pub url: Option<String>,
+
+    /// Initial interval between two measurements of the intensity.
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+
+    /// Interval between two requests to the provider. In between, the last value is measured again.
+    #[serde(with = "humantime_serde")]
+    pub refresh_interval: Duration,
+
+    /// How long the last value can be measured when the provider cannot be reached.
+    #[serde(with = "humantime_serde")]
+    pub max_age: Duration,
+
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    ElectricityMaps,
+    WattTime,
+    Eco2mix,
+}
+
+impl Provider {
+    fn name(self) -> &'static str {
+        match self {
+            Provider::ElectricityMaps => "electricitymaps",
+            Provider::WattTime => "watttime",
+            Provider::Eco2mix => "eco2mix",
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            provider: Provider::ElectricityMaps,
+            zone: String::from("FR"),
+            api_key: None,
+            username: None,
+            password: None,
+            url: None,
+            poll_interval: Duration::from_secs(60),
+            flush_interval: Duration::from_secs(60),
+            refresh_interval: Duration::from_secs(15 * 60),
+            max_age: Duration::from_secs(2 * 3600),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        measurement::{AttributeValue, WrappedMeasurementValue},
+        pipeline::elements::error::PollError,
+        test::PluginHarness,
+    };
+    use mockito::{Matcher, Server};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn config(url: String, refresh_interval: Duration, max_age: Duration) -> Config {
+        Config {
+            api_key: Some(String::from("secret")),
+            url: Some(url),
+            refresh_interval,
+            max_age,
+            ..Config::default()
+        }
+    }
+
+    fn mock_latest(server: &mut Server, status: usize, intensity: u32) -> mockito::Mock {
+        server
+            .mock("GET", "/v3/carbon-intensity/latest")
+            .match_query(Matcher::UrlEncoded("zone".into(), "FR".into()))
+            .with_status(status)
+            .with_body(format!(r#"{{"zone":"FR","carbonIntensity":{intensity}}}"#))
+            .create()
+    }
+
+    #[test]
+    fn values_are_cached() -> anyhow::Result<()> {
+        let mut server = Server::new();
+        let mock = mock_latest(&mut server, 200, 35).expect(1);
+        let config = config(server.url(), Duration::from_secs(3600), Duration::from_secs(3600));
+        let mut harness = PluginHarness::<CarbonIntensityPlugin>::start(serialize_config(config)?)?;
+        let metric = harness.metrics().by_name(METRIC_NAME).unwrap().1.clone();
+        assert_eq!(metric.unit.unique_name(), "gCO2eq/kWh");
+
+        let mut source = harness.source("grid")?;
+        for _ in 0..2 {
+            let measurements = source.poll().unwrap();
+            assert_eq!(
+                harness.values(&measurements, METRIC_NAME),
+                vec![WrappedMeasurementValue::F64(35.0)]
+            );
+            let point = harness.points(&measurements, METRIC_NAME)[0];
+            assert_eq!(point.resource.id_display().to_string(), "FR");
+            let attributes: Vec<_> = point.attributes().map(|(k, v)| (k.to_owned(), v.clone())).collect();
+            assert_eq!(
+                attributes,
+                vec![(String::from("provider"), AttributeValue::Str("electricitymaps"))]
+            );
+        }
+        mock.assert();
+        harness.stop()?;
+        Ok(())
+    }
+
+    #[test]
+    fn provider_failure() -> anyhow::Result<()> {
+        let mut server = Server::new();
+        let ok = mock_latest(&mut server, 200, 35);
+        // always refresh, use the last value for one hour
+        let config = config(server.url(), Duration::ZERO, Duration::from_secs(3600));
+        let mut harness = PluginHarness::<CarbonIntensityPlugin>::start(serialize_config(config)?)?;
+        let mut source = harness.source("grid")?;
+        source.poll().unwrap();
+        ok.remove();
+
+        mock_latest(&mut server, 503, 0);
+        let measurements = source.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, METRIC_NAME),
+            vec![WrappedMeasurementValue::F64(35.0)]
+        );
+        harness.stop()?;
+        Ok(())
+    }
+
+    #[test]
+    fn no_value_yet() -> anyhow::Result<()> {
+        let mut server = Server::new();
+        mock_latest(&mut server, 503, 0);
+        let config = config(server.url(), Duration::ZERO, Duration::from_secs(3600));
+        let mut harness = PluginHarness::<CarbonIntensityPlugin>::start(serialize_config(config)?)?;
+        let mut source = harness.source("grid")?;
+        assert!(matches!(source.poll(), Err(PollError::CanRetry(_))));
+        harness.stop()?;
+        Ok(())
+    }
+
+    #[test]
+    fn eco2mix_is_french() {
+        let config = Config {
+            provider: Provider::Eco2mix,
+            zone: String::from("DE"),
+            ..Config::default()
+        };
+        let error = CarbonIntensityPlugin::init(serialize_config(config).unwrap())
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("only covers France"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::api::IntensityApi;
+
+/// Measurement source that gives the carbon intensity of a zone.
+///
+/// The provider is not queried at every poll: its APIs are rate-limited, and the intensity of
+/// a grid changes slowly. The last value is measured again until `refresh_interval` has elapsed.
+pub struct IntensitySource {
+    api: Box<dyn IntensityApi>,
+    provider: &'static str,
+    resource: Resource,
+    metric: TypedMetricId<f64>,
+    refresh_interval: Duration,
+    /// How long the last value can be used when the provider fails.
+    max_age: Duration,
+    /// Last value fetched, with the moment it has been fetched.
+    cache: Option<(f64, Instant)>,
+}
+
+/// Settings of the cache of [`IntensitySource`].
+pub struct CacheSettings {
+    pub refresh_interval: Duration,
+    pub max_age: Duration,
+}
+
+impl IntensitySource {
+    pub fn new(
+        api: Box<dyn IntensityApi>,
+        provider: &'static str,
+        zone: String,
+        metric: TypedMetricId<f64>,
+        cache: CacheSettings,
+    ) -> Self {
+        Self {
+            api,
+            provider,
+            resource: Resource::custom("grid_zone", zone),
+            metric,
+            refresh_interval: cache.refresh_interval,
+            max_age: cache.max_age,
+            cache: None,
+        }
+    }
+
+    fn intensity(&mut self) -> anyhow::Result<f64> {
+        if let Some((value, fetched)) = self.cache
+            && fetched.elapsed() < self.refresh_interval
+        {
+            return Ok(value);
+        }
+        // The HTTP requests are blocking.
+        match tokio::task::block_in_place(|| self.api.fetch()) {
+            Ok(value) => {
+                self.cache = Some((value, Instant::now()));
+                Ok(value)
+            }
+            Err(e) => match self.cache {
+                Some((value, fetched)) if fetched.elapsed() < self.max_age => {
+                    log::warn!(
+                        "Failed to fetch the carbon intensity from {}, using the value of {}s ago: {e:#}",
+                        self.provider,
+                        fetched.elapsed().as_secs()
+                    );
+                    Ok(value)
+                }
+                _ => Err(e),
+            },
+        }
+    }
+}
+
+impl Source for IntensitySource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // The provider can be unreachable for a while (the next poll may work).
+        let intensity = self
+            .intensity()
+            .with_context(|| format!("failed to fetch the carbon intensity from {}", self.provider))
+            .map_err(PollError::CanRetry)?;
+        let point = MeasurementPoint::new(
+            t,
+            self.metric,
+            self.resource.clone(),
+            ResourceConsumer::LocalMachine,
+            intensity,
+        )
+        .with_attr("provider", self.provider);
+        measurements.push(point);
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+//! Scraping of the summary API of the kubelet, which reports the usage of the pods and of their containers.
+
+use std::{collections::HashMap, time::Duration};
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::token::Token;
+use api::{NetworkStats, Summary};
+
+/// Configuration of the kubelet source.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KubeletConfig {
+    /// Scrape the kubelet of the node, to measure the pods and their containers.
+    pub enabled: bool,
+    /// URL of the kubelet, usually `https://<node ip>:10250`.
+    pub url: String,
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+}
+
+impl Default for KubeletConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: String::from("https://127.0.0.1:10250"),
+            poll_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Kubelet client, authenticated with the token of the ServiceAccount.
+pub struct KubeletClient {
+    client: reqwest::blocking::Client,
+    auth_token: Token,
+    summary_route: String,
+}
+
+/// Encoding/decoding of the kubelet responses.
+/// Fields that we don't need are not included, serde will skip them.
+mod api {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Summary {
+        pub node: NodeStats,
+        #[serde(default)]
+        pub pods: Vec<PodStats>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NodeStats {
+        pub node_name: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PodStats {
+        pub pod_ref: PodReference,
+        #[serde(default)]
+        pub containers: Vec<ContainerStats>,
+        pub cpu: Option<CpuStats>,
+        pub memory: Option<MemoryStats>,
+        pub network: Option<NetworkStats>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct PodReference {
+        pub name: String,
+        pub namespace: String,
+        pub uid: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct ContainerStats {
+        pub name: String,
+        pub cpu: Option<CpuStats>,
+        pub memory: Option<MemoryStats>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CpuStats {
+        /// Average usage over the last sampling window of the kubelet, in billionths of core.
+        pub usage_nano_cores: Option<u64>,
+        /// Cumulative CPU time, in nanoseconds.
+        pub usage_core_nano_seconds: Option<u64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct MemoryStats {
+        pub working_set_bytes: Option<u64>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NetworkStats {
+        /// Cumulative bytes received, on the default interface.
+        pub rx_bytes: Option<u64>,
+        /// Cumulative bytes sent, on the default interface.
+        pub tx_bytes: Option<u64>,
+    }
+}
+
+impl KubeletClient {
+    pub fn new(kubelet_url: &str, auth_token: Token) -> anyhow::Result<Self> {
+        // the kubelet usually has a self-signed certificate
+        let client = reqwest::blocking::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .context("failed to build http client")?;
+
+        let summary_route = format!("{kubelet_url}/stats/summary");
+
+        Ok(Self {
+            client,
+            auth_token,
+            summary_route,
+        })
+    }
+
+    fn summary(&self) -> anyhow::Result<Summary> {
+        let token = self.auth_token.get_value().context("failed to get auth token")?;
+        let response = self
+            .client
+            .get(&self.summary_route)
+            .bearer_auth(token)
+            .send()
+            .context("failed to send http request")?
+            .error_for_status()
+            .context("the kubelet refused the request, can the ServiceAccount get nodes/stats?")?;
+        response.json().context("failed to parse json response")
+    }
+}
+
+pub struct KubeletMetrics {
+    cpu_time_delta: TypedMetricId<u64>,
+    cpu_percent: TypedMetricId<f64>,
+    memory_working_set: TypedMetricId<u64>,
+    network_bytes_delta: TypedMetricId<u64>,
+}
+
+impl KubeletMetrics {
+    pub fn create(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            cpu_time_delta: alumet.create_metric(
+                "kubelet_cpu_time_delta",
+                PrefixedUnit::nano(Unit::Second),
+                "time spent on the CPU since the previous measurement, according to the kubelet",
+            )?,
+            cpu_percent: alumet.create_metric(
+                "kubelet_cpu_percent",
+                Unit::Percent,
+                "CPU usage averaged by the kubelet (1 core used fully = 100%)",
+            )?,
+            memory_working_set: alumet.create_metric(
+                "kubelet_memory_working_set",
+                Unit::Byte,
+                "memory that cannot be evicted, according to the kubelet",
+            )?,
+            network_bytes_delta: alumet.create_metric(
+                "kubelet_network_bytes_delta",
+                Unit::Byte,
+                "bytes received or sent by the pod since the previous measurement",
+            )?,
+        })
+    }
+}
+
+/// Measurement source that scrapes the summary API of the kubelet.
+pub struct KubeletSource {
+    client: KubeletClient,
+    metrics: KubeletMetrics,
+    /// Cumulative counters of the pods and containers, at the previous measurement.
+    previous: HashMap<String, Counters>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    cpu_time: Option<u64>,
+    rx_bytes: Option<u64>,
+    tx_bytes: Option<u64>,
+}
+
+impl KubeletSource {
+    pub fn new(client: KubeletClient, metrics: KubeletMetrics) -> Self {
+        Self {
+            client,
+            metrics,
+            previous: HashMap::new(),
+        }
+    }
+}
+
+impl Source for KubeletSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // the kubelet can be restarted, the next poll may work
+        let summary = self
+            .client
+            .summary()
+            .context("failed to get the stats summary of the kubelet")
+            .map_err(PollError::CanRetry)?;
+
+        let node = AttributeValue::String(summary.node.node_name);
+        let mut current = HashMap::with_capacity(self.previous.len());
+        for pod in summary.pods {
+            let pod_ref = pod.pod_ref;
+            let attrs = vec![
+                ("uid".to_string(), AttributeValue::String(pod_ref.uid.clone())),
+                ("namespace".to_string(), AttributeValue::String(pod_ref.namespace)),
+                ("pod".to_string(), AttributeValue::String(pod_ref.name)),
+                ("node".to_string(), node.clone()),
+            ];
+
+            for container in pod.containers {
+                let id = format!("{}/{}", pod_ref.uid, container.name);
+                let mut attrs = attrs.clone();
+                attrs.push(("container".to_string(), AttributeValue::String(container.name)));
+                let usage = Usage {
+                    consumer: ResourceConsumer::custom("container", id.clone()),
+                    attrs,
+                    cpu: container.cpu,
+                    memory: container.memory,
+                    network: None,
+                };
+                let counters = self.push_usage(measurements, t, usage, self.previous.get(&id));
+                current.insert(id, counters);
+            }
+
+            let usage = Usage {
+                consumer: ResourceConsumer::custom("pod", pod_ref.uid.clone()),
+                attrs,
+                cpu: pod.cpu,
+                memory: pod.memory,
+                network: pod.network,
+            };
+            let counters = self.push_usage(measurements, t, usage, self.previous.get(&pod_ref.uid));
+            current.insert(pod_ref.uid, counters);
+        }
+        // forget the pods and containers that have been deleted
+        self.previous = current;
+        Ok(())
+    }
+}
+
+/// Usage of a pod or of a container.
+struct Usage {
+    consumer: ResourceConsumer,
+    attrs: Vec<(String, AttributeValue)>,
+    cpu: Option<api::CpuStats>,
+    memory: Option<api::MemoryStats>,
+    network: Option<NetworkStats>,
+}
+
+impl KubeletSource {
+    /// Pushes the measurements of a pod or container, and returns its current counters.
+    fn push_usage(
+        &self,
+        measurements: &mut MeasurementAccumulator,
+        t: Timestamp,
+        usage: Usage,
+        previous: Option<&Counters>,
+    ) -> Counters {
+        let Usage {
+            consumer,
+            attrs,
+            cpu,
+            memory,
+            network,
+        } = usage;
+        let point = |metric, value| {
+            MeasurementPoint::new(t, metric, Resource::LocalMachine, consumer.clone(), value).with_attr_slice(&attrs)
+        };
+
+        let counters = Counters {
+            cpu_time: cpu.as_ref().and_then(|c| c.usage_core_nano_seconds),
+            rx_bytes: network.as_ref().and_then(|n| n.rx_bytes),
+            tx_bytes: network.as_ref().and_then(|n| n.tx_bytes),
+        };
+        // The counters are reset when a container restarts, skip the deltas in this case.
+        let delta = |current: Option<u64>, previous: Option<u64>| current?.checked_sub(previous?);
+        let previous = previous.copied().unwrap_or_default();
+
+        if let Some(cpu_time) = delta(counters.cpu_time, previous.cpu_time) {
+            measurements.push(point(self.metrics.cpu_time_delta, cpu_time));
+        }
+        if let Some(nano_cores) = cpu.and_then(|c| c.usage_nano_cores) {
+            let percent = nano_cores as f64 / 10_000_000.0;
+            measurements.push(
+                MeasurementPoint::new(
+                    t,
+                    self.metrics.cpu_percent,
+                    Resource::LocalMachine,
+                    consumer.clone(),
+                    percent,
+                )
+                .with_attr_slice(&attrs),
+            );
+        }
+        if let Some(working_set) = memory.and_then(|m| m.working_set_bytes) {
+            measurements.push(point(self.metrics.memory_working_set, working_set));
+        }
+        for (kind, current, previous) in [
+            ("rx", counters.rx_bytes, previous.rx_bytes),
+            ("tx", counters.tx_bytes, previous.tx_bytes),
+        ] {
+            if let Some(bytes) = delta(current, previous) {
+                measurements.push(point(self.metrics.network_bytes_delta, bytes).with_attr("kind", kind));
+            }
+        }
+        counters
+    }
+}
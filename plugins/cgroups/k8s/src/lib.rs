@@ -1,13 +1,17 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use alumet::{
     pipeline::elements::source::trigger::TriggerSpec,
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::{
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
 };
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    kubelet::{KubeletClient, KubeletConfig, KubeletMetrics, KubeletSource},
     pods::{ApiClient, AutoNodePodRegistry},
     token::{Token, TokenRetrievalConfig},
 };
@@ -17,6 +21,7 @@ use util_cgroups_plugins::{
     metrics::Metrics,
 };
 
+mod kubelet;
 mod pods;
 mod source;
 mod token;
@@ -36,6 +41,19 @@ impl AlumetPlugin for K8sPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from("/sys/fs/cgroup")),
+            // mount points of the cgroup filesystems
+            Capability::Filesystem(PathBuf::from("/proc/mounts")),
+            // the API server of Kubernetes, and the kubelet scraped by the source `kubelet`
+            Capability::Network,
+            Capability::Filesystem(PathBuf::from(token::DEFAULT_SECRET_TOKEN_PATH)),
+            // `kubectl`, to create a token
+            Capability::ProcessSpawn,
+        ])
+    }
+
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
         let config = deserialize_config(config)?;
         Ok(Box::new(Self {
@@ -56,7 +74,7 @@ impl AlumetPlugin for K8sPlugin {
         // prepare K8S link and test it
         let node = self.config.k8s_node_name();
         let api_token = Token::new(self.config.token_retrieval.clone().into());
-        let api_client = ApiClient::new(&self.config.k8s_api_url, api_token)
+        let api_client = ApiClient::new(&self.config.k8s_api_url, api_token.clone())
             .context("failed to create http client for communicating with the K8S API")?;
         let mut pod_registry = AutoNodePodRegistry::new(node, api_client);
         pod_registry
@@ -64,6 +82,15 @@ impl AlumetPlugin for K8sPlugin {
             .context("failed to list pods with the K8S API, are the url and token correct?")?;
         log::info!("List of pods refreshed.");
 
+        // scrape the kubelet, if enabled, to get the usage of the containers
+        if self.config.kubelet.enabled {
+            let client = KubeletClient::new(&self.config.kubelet.url, api_token)
+                .context("failed to create http client for communicating with the kubelet")?;
+            let source = KubeletSource::new(client, KubeletMetrics::create(alumet)?);
+            let trigger = TriggerSpec::at_interval(self.config.kubelet.poll_interval);
+            alumet.add_source("kubelet", Box::new(source), trigger)?;
+        }
+
         // store the state for later, because we cannot set up everything now
         let starting_state = StartingState {
             metrics,
@@ -100,7 +127,7 @@ impl AlumetPlugin for K8sPlugin {
     }
 
     fn stop(&mut self) -> anyhow::Result<()> {
-        drop(self.reactor.take().unwrap());
+        drop(self.reactor.take());
         Ok(())
     }
 }
@@ -122,6 +149,10 @@ pub struct Config {
 
     #[serde(with = "humantime_serde")]
     pub poll_interval: Duration,
+
+    /// Scraping of the summary API of the kubelet.
+    #[serde(default)]
+    pub kubelet: KubeletConfig,
 }
 
 #[cfg_attr(tarpaulin, ignore)]
@@ -137,6 +168,7 @@ impl Default for Config {
             k8s_api_url: default_k8s_api_url(),
             token_retrieval: TokenRetrievalConfig::Simple(token::SimpleRetrievalMethod::Auto),
             poll_interval: Duration::from_secs(5),
+            kubelet: KubeletConfig::default(),
         }
     }
 }
@@ -10,7 +10,7 @@ use base64::{DecodeError, Engine};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-const DEFAULT_SECRET_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
+pub(crate) const DEFAULT_SECRET_TOKEN_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/token";
 const DEFAULT_SERVICE_ACCOUNT: &str = "alumet-reader";
 const DEFAULT_NAMESPACE: &str = "alumet";
 
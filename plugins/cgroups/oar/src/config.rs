@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
@@ -8,6 +8,20 @@ pub struct Config {
     #[serde(with = "humantime_serde")]
     pub(crate) poll_interval: Duration,
     pub(crate) jobs_only: bool,
+    /// Adds the walltime and the start time of the jobs to the measurements, by asking `oarstat`.
+    #[serde(default = "default_true")]
+    pub(crate) job_info: bool,
+    /// Path to the `oarstat` executable.
+    #[serde(default = "default_oarstat")]
+    pub(crate) oarstat: PathBuf,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_oarstat() -> PathBuf {
+    PathBuf::from("oarstat")
 }
 
 impl Default for Config {
@@ -17,6 +31,8 @@ impl Default for Config {
             oar_version: OarVersion::Oar3,
             poll_interval: Duration::from_secs(1),
             jobs_only: true,
+            job_info: true,
+            oarstat: default_oarstat(),
         }
     }
 }
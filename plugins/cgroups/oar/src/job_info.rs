@@ -0,0 +1,147 @@
+use std::{path::PathBuf, process::Command};
+
+use alumet::measurement::AttributeValue;
+use anyhow::{Context, anyhow};
+use serde_json::Value;
+
+/// Information about a job that is not in the path of its cgroup.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobInfo {
+    /// Maximum duration of the job, in seconds.
+    pub walltime: Option<u64>,
+    /// When the job has started, as a unix timestamp in seconds.
+    pub start_time: Option<u64>,
+}
+
+impl JobInfo {
+    /// Returns the attributes `oar_walltime` and `oar_start_time`, when they are known.
+    pub fn attributes(&self) -> Vec<(String, AttributeValue)> {
+        [("oar_walltime", self.walltime), ("oar_start_time", self.start_time)]
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|v| (String::from(key), AttributeValue::U64(v))))
+            .collect()
+    }
+}
+
+/// Gets information about the jobs with `oarstat`, which asks the OAR server.
+#[derive(Clone)]
+pub struct Oarstat {
+    /// Path to the `oarstat` executable.
+    path: PathBuf,
+}
+
+impl Oarstat {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn job_info(&self, job_id: u64) -> anyhow::Result<JobInfo> {
+        let output = Command::new(&self.path)
+            .args(["-f", "-J", "-j", &job_id.to_string()])
+            .output()
+            .with_context(|| format!("failed to run {}", self.path.display()))?;
+        if !output.status.success() {
+            let error_message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            return Err(anyhow!("oarstat failed with {}", output.status).context(error_message));
+        }
+        parse_job(&String::from_utf8_lossy(&output.stdout), job_id)
+    }
+}
+
+/// Parses the output of `oarstat -f -J -j <job_id>`, which is a JSON object indexed by the job id.
+///
+/// Example: `{"4242": {"Job_Id": 4242, "owner": "alice", "walltime": 3600, "startTime": 1718000000, ...}}`
+///
+/// OAR 2 uses `H:M:S` walltimes, and some versions name the start time `start_time`.
+fn parse_job(output: &str, job_id: u64) -> anyhow::Result<JobInfo> {
+    let jobs: Value = serde_json::from_str(output).context("invalid JSON output")?;
+    let job = jobs
+        .get(job_id.to_string())
+        .with_context(|| format!("job {job_id} not found in the output of oarstat"))?;
+    let walltime = match job.get("walltime") {
+        Some(Value::Number(n)) => n.as_u64(),
+        Some(Value::String(s)) => Some(parse_walltime(s)?),
+        _ => None,
+    };
+    let start_time = job
+        .get("startTime")
+        .or_else(|| job.get("start_time"))
+        .and_then(Value::as_u64)
+        // a job that has not started yet has a start time of 0
+        .filter(|t| *t > 0);
+    Ok(JobInfo { walltime, start_time })
+}
+
+/// Parses a walltime in the format `H:M:S`, `H:M` or `H`, into a number of seconds.
+fn parse_walltime(walltime: &str) -> anyhow::Result<u64> {
+    let mut seconds = 0;
+    let mut parts = 0;
+    for part in walltime.split(':') {
+        let n: u64 = part
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid walltime {walltime:?}"))?;
+        seconds = seconds * 60 + n;
+        parts += 1;
+    }
+    if parts > 3 {
+        return Err(anyhow!("invalid walltime {walltime:?}"));
+    }
+    // `H:M` means hours and minutes, not minutes and seconds
+    Ok(seconds * 60_u64.pow(3 - parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_oarstat_output() {
+        let output = r#"{
+            "4242": {
+                "Job_Id": 4242, "name": "train", "owner": "alice", "state": "Running",
+                "walltime": 7200, "startTime": 1718000000, "submissionTime": 1717999990,
+                "assigned_network_address": ["taurus-3.lyon.grid5000.fr"]
+            }
+        }"#;
+        let info = parse_job(output, 4242).unwrap();
+        assert_eq!(
+            info,
+            JobInfo {
+                walltime: Some(7200),
+                start_time: Some(1718000000),
+            }
+        );
+        assert_eq!(
+            info.attributes(),
+            vec![
+                (String::from("oar_walltime"), AttributeValue::U64(7200)),
+                (String::from("oar_start_time"), AttributeValue::U64(1718000000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_oar2_output() {
+        let output = r#"{"12": {"Job_Id": "12", "walltime": "1:30:0", "start_time": 0}}"#;
+        assert_eq!(
+            parse_job(output, 12).unwrap(),
+            JobInfo {
+                walltime: Some(5400),
+                start_time: None,
+            }
+        );
+        assert!(parse_job(output, 13).is_err());
+    }
+
+    #[test]
+    fn walltime() {
+        assert_eq!(parse_walltime("2").unwrap(), 7200);
+        assert_eq!(parse_walltime("0:30").unwrap(), 1800);
+        assert_eq!(parse_walltime("01:00:05").unwrap(), 3605);
+        assert!(parse_walltime("1:00:00:00").is_err());
+        assert!(parse_walltime("1h").is_err());
+    }
+}
@@ -1,20 +1,27 @@
 use std::sync::{Arc, Mutex};
 
+use alumet::{
+    measurement::Timestamp,
+    plugin::event::{self, JobChange, JobState},
+};
 use anyhow::Context;
-use rustc_hash::FxHashSet;
+use rustc_hash::FxHashMap;
 
 use crate::{
     attr::{JOB_REGEX_OAR2, JOB_REGEX_OAR3, find_jobid_in_attrs},
     config::OarVersion,
+    job_info::JobInfo,
 };
 use util_cgroups_plugins::{cgroup_events::CgroupRemovalCallback, regex::RegexAttributesExtrator};
 
-/// Tracks the jobs that are currently running on the node.
+/// Tracks the jobs that are currently running on the node, with their information.
+///
+/// When a job starts or ends, the tracker publishes a [`JobChange`] event.
 ///
 /// `JobTracker` is `Clone`, `Send` and `Sync`: you can clone it and pass it around freely.
 #[derive(Clone)]
 pub struct JobTracker {
-    jobs: Arc<Mutex<FxHashSet<u64>>>,
+    jobs: Arc<Mutex<FxHashMap<u64, JobInfo>>>,
 }
 
 /// Removes jobs from the [`JobTracker`] when the corresponding cgroup is deleted.
@@ -28,37 +35,72 @@ impl JobTracker {
     /// Creates a new, empty job tracker.
     pub fn new() -> Self {
         Self {
-            jobs: Arc::new(Mutex::new(FxHashSet::default())),
+            jobs: Arc::new(Mutex::new(FxHashMap::default())),
         }
     }
 
-    pub fn add(&mut self, job_id: u64) {
-        self.jobs.lock().unwrap().insert(job_id);
+    pub fn add(&mut self, job_id: u64, info: JobInfo) {
+        let previous = self.jobs.lock().unwrap().insert(job_id, info.clone());
+        if previous.is_none() {
+            // publish outside of the lock, the listeners may use the tracker
+            let timestamp = match info.start_time {
+                Some(t) => Timestamp::from_unix_timestamp(t, 0),
+                None => Timestamp::now(),
+            };
+            publish_job_change(job_id, JobState::Started, timestamp, &info);
+        }
     }
 
-    pub fn add_multiple(&mut self, job_ids: impl Iterator<Item = u64>) {
-        self.jobs.lock().unwrap().extend(job_ids);
+    /// Returns the information about a job, if it is known.
+    pub fn get(&self, job_id: u64) -> Option<JobInfo> {
+        self.jobs.lock().unwrap().get(&job_id).cloned()
     }
 
     pub fn remove(&mut self, job_id: u64) {
-        self.jobs.lock().unwrap().remove(&job_id);
+        self.remove_multiple(std::iter::once(job_id));
     }
 
     pub fn remove_multiple(&mut self, job_ids: impl Iterator<Item = u64>) {
-        let mut j = self.jobs.lock().unwrap();
-        for job in job_ids {
-            j.remove(&job);
+        let removed: Vec<(u64, JobInfo)> = {
+            let mut j = self.jobs.lock().unwrap();
+            job_ids
+                .filter_map(|job| j.remove(&job).map(|info| (job, info)))
+                .collect()
+        };
+        let timestamp = Timestamp::now();
+        for (job_id, info) in removed {
+            publish_job_change(job_id, JobState::Ended, timestamp, &info);
         }
     }
 
     pub fn known_jobs_sorted(&self) -> Vec<u64> {
         let mut v: Vec<u64> = {
             let v = self.jobs.lock().unwrap();
-            v.iter().cloned().collect()
+            v.keys().cloned().collect()
         };
         v.sort();
         v
     }
+
+    /// Returns the only job that is running on the node, if there is exactly one.
+    pub fn single_job(&self) -> Option<(u64, JobInfo)> {
+        let jobs = self.jobs.lock().unwrap();
+        match jobs.len() {
+            1 => jobs.iter().next().map(|(id, info)| (*id, info.clone())),
+            _ => None,
+        }
+    }
+}
+
+fn publish_job_change(job_id: u64, state: JobState, timestamp: Timestamp, info: &JobInfo) {
+    let mut event = JobChange::new("oar", job_id, state, timestamp);
+    if let Some(walltime) = info.walltime {
+        event.metadata.insert("walltime", walltime);
+    }
+    if let Some(start_time) = info.start_time {
+        event.metadata.insert("start_time", start_time);
+    }
+    event::job_change().publish(event);
 }
 
 impl JobCleaner {
@@ -103,4 +145,56 @@ mod tests {
         // this compiles only if JobTracker is Send and Sync
         f::<JobTracker>();
     }
+
+    #[test]
+    fn job_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_cloned = events.clone();
+        // the bus is global, ignore the events of the other tests
+        event::job_change().subscribe(move |e| {
+            if e.job_id >= 900_000 {
+                events_cloned
+                    .lock()
+                    .unwrap()
+                    .push((e.job_id, e.state, e.timestamp, e.metadata));
+            }
+            Ok(())
+        });
+
+        let mut tracker = JobTracker::new();
+        let info = JobInfo {
+            walltime: Some(3600),
+            start_time: Some(1718000000),
+        };
+        tracker.add(900_001, info.clone());
+        tracker.add(900_001, info.clone()); // already known: no event
+        tracker.add(900_002, JobInfo::default());
+        assert_eq!(tracker.known_jobs_sorted(), vec![900_001, 900_002]);
+        assert_eq!(tracker.single_job(), None);
+
+        tracker.remove(900_002);
+        tracker.remove(900_003); // unknown: no event
+        assert_eq!(tracker.single_job(), Some((900_001, info)));
+        tracker.remove_multiple([900_001].into_iter());
+        assert!(tracker.known_jobs_sorted().is_empty());
+
+        let events = events.lock().unwrap();
+        let summary: Vec<_> = events.iter().map(|(id, state, _, _)| (*id, *state)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (900_001, JobState::Started),
+                (900_002, JobState::Started),
+                (900_002, JobState::Ended),
+                (900_001, JobState::Ended),
+            ]
+        );
+        let (_, _, start, metadata) = &events[0];
+        assert_eq!(start, &Timestamp::from_unix_timestamp(1718000000, 0));
+        assert_eq!(
+            metadata.get("walltime"),
+            Some(&alumet::measurement::AttributeValue::U64(3600))
+        );
+        assert!(events[1].3.is_empty());
+    }
 }
@@ -1,5 +1,8 @@
+use std::path::PathBuf;
+
 use alumet::plugin::{
     AlumetPluginStart, AlumetPostStart, ConfigTable,
+    capability::Capability,
     rust::{AlumetPlugin, deserialize_config, serialize_config},
 };
 use anyhow::Context;
@@ -14,6 +17,7 @@ use util_cgroups_plugins::{
 };
 
 mod attr;
+mod job_info;
 mod job_tracker;
 mod source;
 mod transform;
@@ -48,6 +52,16 @@ impl AlumetPlugin for OarPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from("/sys/fs/cgroup")),
+            // mount points of the cgroup filesystems
+            Capability::Filesystem(PathBuf::from("/proc/mounts")),
+            // `id`, to find the user of a job, and `oarstat`, to get the information of the jobs
+            Capability::ProcessSpawn,
+        ])
+    }
+
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config: config::Config = deserialize_config(config)?;
         Ok(Box::new(Self::new(config)))
@@ -71,7 +85,8 @@ impl AlumetPlugin for OarPlugin {
         };
         self.starting_state = Some(starting_state);
 
-        // Add a transform that adds the list of job ids to every point that does not have the attribute "job_id".
+        // Add a transform that adds the list of job ids to every point that does not have the attribute "job_id",
+        // and the id and walltime of the job to the points that belong to a single job.
         let transform = JobInfoAttacher::new(tracker);
         alumet.add_transform("oar_job_info_attacher", Box::new(transform))?;
         Ok(())
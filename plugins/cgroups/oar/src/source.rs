@@ -7,6 +7,7 @@ use util_cgroups::Cgroup;
 use crate::{
     attr::{JOB_REGEX_OAR2, JOB_REGEX_OAR3, find_jobid_in_attrs, find_userid_in_attrs},
     config::OarVersion,
+    job_info::{JobInfo, Oarstat},
     job_tracker::JobTracker,
 };
 use util_cgroups_plugins::{
@@ -21,18 +22,22 @@ pub struct JobSourceSetup {
     username_from_userid: bool,
     trigger: TriggerSpec,
     tracker: JobTracker,
+    /// Gives the walltime and the start time of the jobs, if enabled.
+    oarstat: Option<Oarstat>,
     jobs_only: bool,
 }
 
 impl JobSourceSetup {
     pub fn new(config: super::config::Config, tracker: JobTracker) -> anyhow::Result<Self> {
         let trigger = TriggerSpec::at_interval(config.poll_interval);
+        let oarstat = config.job_info.then(|| Oarstat::new(config.oarstat));
         match config.oar_version {
             OarVersion::Oar2 => Ok(Self {
                 extractor: RegexAttributesExtrator::new(JOB_REGEX_OAR2)?,
                 username_from_userid: false,
                 trigger,
                 tracker,
+                oarstat,
                 jobs_only: config.jobs_only,
             }),
             OarVersion::Oar3 => Ok(Self {
@@ -40,10 +45,27 @@ impl JobSourceSetup {
                 username_from_userid: true,
                 trigger,
                 tracker,
+                oarstat,
                 jobs_only: config.jobs_only,
             }),
         }
     }
+
+    /// Returns the information about a job, and asks OAR the first time.
+    fn job_info(&mut self, job_id: u64) -> JobInfo {
+        if let Some(info) = self.tracker.get(job_id) {
+            return info;
+        }
+        let info = match &self.oarstat {
+            Some(oarstat) => oarstat.job_info(job_id).unwrap_or_else(|e| {
+                log::warn!("Could not get the walltime of OAR job {job_id}: {e:#}");
+                JobInfo::default()
+            }),
+            None => JobInfo::default(),
+        };
+        self.tracker.add(job_id, info.clone());
+        info
+    }
 }
 
 impl CgroupSetupCallback for JobSourceSetup {
@@ -66,9 +88,9 @@ impl CgroupSetupCallback for JobSourceSetup {
                 attrs.push((String::from("user"), AttributeValue::String(user)));
             }
 
-            // add to job tracker
+            // add the walltime and the start time, and track the job
             let job_id = find_jobid_in_attrs(&attrs).expect("job_id should be set");
-            self.tracker.add(job_id);
+            attrs.extend(self.job_info(job_id).attributes());
 
             // give a nice name
             name = format!(
@@ -111,11 +133,50 @@ fn username_from_id(id: u64) -> anyhow::Result<String> {
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::config::Config;
 
     // #[test]
     // fn test_username_from_id() {
     //     let username = username_from_id(1000).unwrap();
     //     println!("{username}");
     // }
+
+    #[test]
+    fn job_info_is_asked_once() {
+        // fake oarstat, which is called with `-f -J -j <id>`
+        let dir = tempfile::tempdir().unwrap();
+        let oarstat = dir.path().join("oarstat");
+        fs::write(
+            &oarstat,
+            "#!/bin/sh\nprintf '{\"%s\": {\"walltime\": 3600, \"startTime\": 1718000000}}' \"$4\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&oarstat, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = Config {
+            oarstat: oarstat.clone(),
+            ..Default::default()
+        };
+        let tracker = JobTracker::new();
+        let mut setup = JobSourceSetup::new(config, tracker.clone()).unwrap();
+        let expected = JobInfo {
+            walltime: Some(3600),
+            start_time: Some(1718000000),
+        };
+        assert_eq!(setup.job_info(12345), expected);
+        assert_eq!(tracker.known_jobs_sorted(), vec![12345]);
+
+        // the next calls use the information of the tracker
+        fs::remove_file(&oarstat).unwrap();
+        assert_eq!(setup.job_info(12345), expected);
+
+        // oarstat is not available: the job is tracked without information
+        assert_eq!(setup.job_info(678), JobInfo::default());
+        assert_eq!(tracker.known_jobs_sorted(), vec![678, 12345]);
+    }
 }
@@ -10,7 +10,8 @@ use alumet::{
 
 use crate::job_tracker::JobTracker;
 
-/// Add the list of current jobs to every measurement that is not job-specific.
+/// Add the list of current jobs to every measurement that is not job-specific,
+/// and the id and walltime of the job to every measurement that can be attributed to a single job.
 /// This is used to relate the measurements to the jobs, for searching, making dashboards, etc.
 pub struct JobInfoAttacher {
     tracker: JobTracker,
@@ -26,12 +27,29 @@ impl Transform for JobInfoAttacher {
     fn apply(&mut self, measurements: &mut MeasurementBuffer, _ctx: &TransformContext) -> Result<(), TransformError> {
         // lazily initialized
         let current_job_list = LazyCell::new(|| self.tracker.known_jobs_sorted().into_iter().collect::<Vec<_>>());
+        let single_job = LazyCell::new(|| self.tracker.single_job());
         for m in measurements.iter_mut() {
-            if !m.attributes_keys().any(|k| k == "job_id") {
+            let job = if m.attributes_keys().any(|k| k == "job_id") {
+                // The measurement is about a job, its id is a number if the job has been measured by this plugin.
+                m.attributes().find_map(|(k, v)| match (k, v) {
+                    ("job_id", AttributeValue::U64(id)) => Some((*id, self.tracker.get(*id).unwrap_or_default())),
+                    _ => None,
+                })
+            } else {
                 // This measurement is not job-specific, attach the list of running jobs.
                 // See issue #209.
                 let jobs_attr = current_job_list.clone();
                 m.add_attr("involved_jobs", AttributeValue::ListU64(jobs_attr));
+                // If only one job is running, the measurement is about the node that it has reserved.
+                single_job.clone()
+            };
+            if let Some((id, info)) = job {
+                m.add_attr("oar_job_id", AttributeValue::U64(id));
+                for (key, value) in info.attributes() {
+                    if !m.attributes_keys().any(|k| k == key) {
+                        m.add_attr(key, value);
+                    }
+                }
             }
         }
         Ok(())
@@ -5,11 +5,15 @@ use alumet::{
         self,
         plugin::{PluginInfo, PluginSet},
     },
+    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
     pipeline::{
         control::request::{self, ElementListFilter},
-        naming::ElementKind,
+        naming::{ElementKind, TransformName},
     },
     plugin::PluginMetadata,
+    resources::{Resource, ResourceConsumer},
+    test::RuntimeExpectations,
+    units::Unit,
 };
 use anyhow::Context;
 use plugin_oar::OarPlugin;
@@ -185,3 +189,62 @@ fn test_oar2() -> anyhow::Result<()> {
     agent.wait_for_shutdown(TIMEOUT)?;
     Ok(())
 }
+
+#[test]
+#[serial]
+fn test_job_attributes() -> anyhow::Result<()> {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<OarPlugin>(),
+        enabled: true,
+        config: toml::from_str(
+            r#"
+                oar_version = "oar3"
+                poll_interval = "500ms"
+                jobs_only = true
+                job_info = false
+            "#,
+        )
+        .unwrap(),
+    });
+
+    let run_expect = RuntimeExpectations::new()
+        .create_metric::<f64>("rapl_consumed_energy", Unit::Joule)
+        .test_transform(
+            TransformName::from_str("oar", "oar_job_info_attacher"),
+            |ctx| {
+                let metric = ctx.metrics().by_name("rapl_consumed_energy").unwrap().0;
+                let point = MeasurementPoint::new_untyped(
+                    Timestamp::now(),
+                    metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    WrappedMeasurementValue::F64(12.5),
+                );
+                let mut buf = MeasurementBuffer::new();
+                buf.push(point.clone());
+                buf.push(point.with_attr("job_id", 123456_u64));
+                buf
+            },
+            |ctx| {
+                let m = ctx.measurements().to_vec();
+                assert_eq!(m.len(), 2);
+                // no job is running: the point that is not about a job cannot be attributed
+                assert!(m[0].attributes_keys().any(|k| k == "involved_jobs"));
+                assert!(!m[0].attributes_keys().any(|k| k == "oar_job_id"));
+                // the point of the job is tagged with its id
+                assert!(!m[1].attributes_keys().any(|k| k == "involved_jobs"));
+                let job_id = m[1]
+                    .attributes()
+                    .find(|(k, _)| *k == "oar_job_id")
+                    .map(|(_, v)| v.clone());
+                assert_eq!(job_id, Some(AttributeValue::U64(123456)));
+            },
+        );
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(run_expect)
+        .build_and_start()?;
+    agent.wait_for_shutdown(TIMEOUT)?;
+    Ok(())
+}
@@ -1,8 +1,11 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use alumet::{
     pipeline::elements::source::trigger::TriggerSpec,
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::{
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
 };
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
@@ -34,6 +37,14 @@ impl AlumetPlugin for RawCgroupPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from("/sys/fs/cgroup")),
+            // mount points of the cgroup filesystems
+            Capability::Filesystem(PathBuf::from("/proc/mounts")),
+        ])
+    }
+
     fn init(config: alumet::plugin::ConfigTable) -> anyhow::Result<Box<Self>> {
         let config = deserialize_config(config)?;
         Ok(Box::new(Self {
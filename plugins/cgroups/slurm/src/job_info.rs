@@ -0,0 +1,108 @@
+use std::{path::PathBuf, process::Command};
+
+use alumet::measurement::AttributeValue;
+use anyhow::{Context, anyhow};
+
+/// Information about a job that is not in the path of its cgroup.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobInfo {
+    /// Name of the user that submitted the job.
+    pub user: Option<String>,
+    /// Account that the resources of the job are charged to.
+    pub account: Option<String>,
+}
+
+impl JobInfo {
+    /// Returns the attributes `user` and `account`, when they are known.
+    pub fn attributes(&self) -> Vec<(String, AttributeValue)> {
+        [("user", &self.user), ("account", &self.account)]
+            .into_iter()
+            .filter_map(|(key, value)| {
+                value
+                    .as_ref()
+                    .map(|v| (String::from(key), AttributeValue::String(v.clone())))
+            })
+            .collect()
+    }
+}
+
+/// Gets information about the jobs with `scontrol show job`, which asks the Slurm controller.
+#[derive(Clone)]
+pub struct Scontrol {
+    /// Path to the `scontrol` executable.
+    path: PathBuf,
+}
+
+impl Scontrol {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn job_info(&self, job_id: u64) -> anyhow::Result<JobInfo> {
+        let output = Command::new(&self.path)
+            .args(["--oneliner", "show", "job", &job_id.to_string()])
+            .output()
+            .with_context(|| format!("failed to run {}", self.path.display()))?;
+        if !output.status.success() {
+            let error_message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            return Err(anyhow!("scontrol failed with {}", output.status).context(error_message));
+        }
+        Ok(parse_job(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Parses the output of `scontrol --oneliner show job`, which is a list of `Key=Value`.
+///
+/// Example: `JobId=10707 JobName=train UserId=alice(1000) GroupId=alice(1000) ... Account=research QOS=normal ...`
+fn parse_job(output: &str) -> JobInfo {
+    let mut info = JobInfo::default();
+    for (key, value) in output.split_whitespace().filter_map(|field| field.split_once('=')) {
+        match key {
+            // the name of the user is followed by its id, like `alice(1000)`
+            "UserId" => info.user = Some(value.split('(').next().unwrap_or(value).to_owned()),
+            "Account" if value != "(null)" => info.account = Some(value.to_owned()),
+            _ => (),
+        }
+    }
+    info.user = info.user.filter(|u| !u.is_empty());
+    info.account = info.account.filter(|a| !a.is_empty());
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_scontrol_output() {
+        let output = "JobId=10707 JobName=train model UserId=alice(1000) GroupId=alice(1000) MCS_label=N/A \
+                      Priority=4294901758 Nice=0 Account=research QOS=normal JobState=RUNNING Reason=None \
+                      Command=/home/alice/train.sh WorkDir=/home/alice\n";
+        assert_eq!(
+            parse_job(output),
+            JobInfo {
+                user: Some(String::from("alice")),
+                account: Some(String::from("research")),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_scontrol_output_without_account() {
+        let output = "JobId=42 JobName=bash UserId=bob(1001) GroupId=bob(1001) Account=(null) QOS=normal\n";
+        let info = parse_job(output);
+        assert_eq!(
+            info,
+            JobInfo {
+                user: Some(String::from("bob")),
+                account: None,
+            }
+        );
+        assert_eq!(
+            info.attributes(),
+            vec![(String::from("user"), AttributeValue::String(String::from("bob")))]
+        );
+    }
+}
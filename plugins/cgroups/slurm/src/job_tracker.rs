@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use rustc_hash::FxHashMap;
+use util_cgroups::CgroupVersion;
+
+use crate::{
+    attr::{JOB_REGEX_SLURM1, JOB_REGEX_SLURM2, JOB_STEP_REGEX, find_jobid_in_attrs},
+    job_info::JobInfo,
+};
+use util_cgroups_plugins::{cgroup_events::CgroupRemovalCallback, regex::RegexAttributesExtrator};
+
+/// Tracks the jobs that are currently running on the node, with their information.
+///
+/// `JobTracker` is `Clone`, `Send` and `Sync`: you can clone it and pass it around freely.
+#[derive(Clone)]
+pub struct JobTracker {
+    jobs: Arc<Mutex<FxHashMap<u64, JobInfo>>>,
+}
+
+/// Removes jobs from the [`JobTracker`] when the cgroup of the job is deleted.
+#[derive(Clone)]
+pub struct JobCleaner {
+    tracker: JobTracker,
+    extractor_v1: RegexAttributesExtrator,
+    extractor_v2: RegexAttributesExtrator,
+    step_extractor: RegexAttributesExtrator,
+}
+
+impl JobTracker {
+    /// Creates a new, empty job tracker.
+    pub fn new() -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(FxHashMap::default())),
+        }
+    }
+
+    pub fn add(&mut self, job_id: u64, info: JobInfo) {
+        self.jobs.lock().unwrap().insert(job_id, info);
+    }
+
+    /// Returns the information about a job, if it is known.
+    pub fn get(&self, job_id: u64) -> Option<JobInfo> {
+        self.jobs.lock().unwrap().get(&job_id).cloned()
+    }
+
+    pub fn remove_multiple(&mut self, job_ids: impl Iterator<Item = u64>) {
+        let mut j = self.jobs.lock().unwrap();
+        for job in job_ids {
+            j.remove(&job);
+        }
+    }
+
+    pub fn known_jobs_sorted(&self) -> Vec<u64> {
+        let mut v: Vec<u64> = {
+            let v = self.jobs.lock().unwrap();
+            v.keys().cloned().collect()
+        };
+        v.sort();
+        v
+    }
+}
+
+impl JobCleaner {
+    pub fn new(tracker: &JobTracker) -> anyhow::Result<Self> {
+        Ok(Self {
+            tracker: tracker.clone(),
+            extractor_v1: RegexAttributesExtrator::new(JOB_REGEX_SLURM1)?,
+            extractor_v2: RegexAttributesExtrator::new(JOB_REGEX_SLURM2)?,
+            step_extractor: RegexAttributesExtrator::new(JOB_STEP_REGEX)?,
+        })
+    }
+}
+
+impl CgroupRemovalCallback for JobCleaner {
+    fn on_cgroups_removed(&mut self, cgroups: Vec<util_cgroups::Cgroup>) -> anyhow::Result<()> {
+        let mut job_ids = Vec::new();
+        for cgroup in cgroups {
+            let extractor = match cgroup.hierarchy().version() {
+                CgroupVersion::V1 => &mut self.extractor_v1,
+                CgroupVersion::V2 => &mut self.extractor_v2,
+            };
+            // If the regex matches, the cgroup corresponds to a job or to one of its steps, and it should have a job id.
+            let attrs = extractor.extract(cgroup.canonical_path()).context("bad regex")?;
+            if attrs.is_empty() {
+                continue;
+            }
+            // The job keeps running when one of its steps ends.
+            let step_attrs = self
+                .step_extractor
+                .extract(cgroup.canonical_path())
+                .context("bad regex")?;
+            if step_attrs.is_empty() {
+                let job_id = find_jobid_in_attrs(&attrs).context("if the regex matches, job_id should be set")?;
+                job_ids.push(job_id);
+            }
+        }
+        self.tracker.remove_multiple(job_ids.into_iter());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assert_send_sync() {
+        fn f<T: Send + Sync>() {}
+        // this compiles only if JobTracker is Send and Sync
+        f::<JobTracker>();
+    }
+
+    #[test]
+    fn known_jobs() {
+        let mut tracker = JobTracker::new();
+        tracker.add(12, JobInfo::default());
+        tracker.add(
+            5,
+            JobInfo {
+                user: Some(String::from("alice")),
+                account: None,
+            },
+        );
+        assert_eq!(tracker.known_jobs_sorted(), vec![5, 12]);
+        assert_eq!(tracker.get(5).unwrap().user.as_deref(), Some("alice"));
+
+        tracker.remove_multiple([12].into_iter());
+        assert_eq!(tracker.known_jobs_sorted(), vec![5]);
+        assert_eq!(tracker.get(12), None);
+    }
+}
@@ -1,19 +1,27 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use alumet::plugin::{
     AlumetPluginStart, AlumetPostStart, ConfigTable,
+    capability::Capability,
     rust::{AlumetPlugin, deserialize_config, serialize_config},
 };
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    job_tracker::{JobCleaner, JobTracker},
+    transform::JobInfoAttacher,
+};
 use util_cgroups_plugins::{
-    cgroup_events::{CgroupReactor, NoCallback, ReactorCallbacks, ReactorConfig},
+    cgroup_events::{CgroupReactor, ReactorCallbacks, ReactorConfig},
     metrics::Metrics,
 };
 
 mod attr;
+mod job_info;
+mod job_tracker;
 mod source;
+mod transform;
 
 /// Gathers metrics for slurm jobs.
 ///
@@ -45,6 +53,16 @@ impl AlumetPlugin for SlurmPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from("/sys/fs/cgroup")),
+            // mount points of the cgroup filesystems
+            Capability::Filesystem(PathBuf::from("/proc/mounts")),
+            // `scontrol`, to get the information of the jobs
+            Capability::ProcessSpawn,
+        ])
+    }
+
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config: Config = deserialize_config(config)?;
         Ok(Box::new(Self::new(config)))
@@ -56,8 +74,15 @@ impl AlumetPlugin for SlurmPlugin {
     }
 
     fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let tracker = JobTracker::new();
         let config = self.config.take().unwrap();
 
+        if config.involved_jobs {
+            // Add a transform that adds the list of job ids to every point that does not have the attribute "job_id".
+            let transform = JobInfoAttacher::new(tracker.clone());
+            alumet.add_transform("slurm_job_info_attacher", Box::new(transform))?;
+        }
+
         // Prepare for cgroup detection.
         let starting_state = StartingState {
             metrics: Metrics::create(alumet)?,
@@ -65,7 +90,8 @@ impl AlumetPlugin for SlurmPlugin {
                 add_source_in_pause_state: config.add_source_in_pause_state,
                 ..Default::default()
             },
-            source_setup: source::JobSourceSetup::new(config)?,
+            job_cleaner: JobCleaner::new(&tracker)?,
+            source_setup: source::JobSourceSetup::new(config, tracker)?,
         };
         self.starting_state = Some(starting_state);
         Ok(())
@@ -78,7 +104,7 @@ impl AlumetPlugin for SlurmPlugin {
             s.metrics,
             ReactorCallbacks {
                 probe_setup: s.source_setup,
-                on_removal: NoCallback,
+                on_removal: s.job_cleaner,
             },
             alumet.pipeline_control(),
         )
@@ -115,6 +141,25 @@ pub struct Config {
     /// !! It's essentially needed for advanced Alumet setup with a control plugin that manage the state of sources.
     #[serde(default)]
     pub add_source_in_pause_state: bool,
+
+    /// Adds the attributes `user` and `account` to the measurements of the jobs, by asking `scontrol`.
+    #[serde(default = "default_true")]
+    pub job_info: bool,
+    /// Path to the `scontrol` executable.
+    #[serde(default = "default_scontrol")]
+    pub scontrol: PathBuf,
+    /// Adds the list of the running jobs, as an attribute `involved_jobs`, to the measurements that
+    /// are not about a job (for instance the measurements of the other plugins).
+    #[serde(default = "default_true")]
+    pub involved_jobs: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_scontrol() -> PathBuf {
+    PathBuf::from("scontrol")
 }
 
 impl Default for Config {
@@ -125,6 +170,9 @@ impl Default for Config {
             cgroupv1_refresh_interval: None,
             jobs_only: true,
             add_source_in_pause_state: false,
+            job_info: true,
+            scontrol: default_scontrol(),
+            involved_jobs: true,
         }
     }
 }
@@ -133,4 +181,5 @@ pub struct StartingState {
     metrics: Metrics,
     reactor_config: ReactorConfig,
     source_setup: source::JobSourceSetup,
+    job_cleaner: JobCleaner,
 }
@@ -1,7 +1,11 @@
 use alumet::pipeline::elements::source::trigger::TriggerSpec;
 use util_cgroups::Cgroup;
 
-use crate::attr::{JOB_REGEX_SLURM1, JOB_REGEX_SLURM2, JOB_STEP_REGEX, find_jobid_in_attrs};
+use crate::{
+    attr::{JOB_REGEX_SLURM1, JOB_REGEX_SLURM2, JOB_STEP_REGEX, find_jobid_in_attrs},
+    job_info::{JobInfo, Scontrol},
+    job_tracker::JobTracker,
+};
 use util_cgroups_plugins::{
     cgroup_events::{CgroupSetupCallback, ProbeSetup, SourceSettings},
     metrics::{AugmentedMetrics, Metrics},
@@ -14,21 +18,45 @@ pub struct JobSourceSetup {
     extractor_v2: RegexAttributesExtrator,
     step_extractor: RegexAttributesExtrator,
     trigger: TriggerSpec,
+    tracker: JobTracker,
+    /// Gives the user and the account of the jobs, if enabled.
+    scontrol: Option<Scontrol>,
     jobs_only: bool,
 }
 
 impl JobSourceSetup {
-    pub fn new(config: super::Config) -> anyhow::Result<Self> {
+    pub fn new(config: super::Config, tracker: JobTracker) -> anyhow::Result<Self> {
         let trigger = TriggerSpec::at_interval(config.poll_interval);
+        let scontrol = config.job_info.then(|| Scontrol::new(config.scontrol));
 
         Ok(Self {
             extractor_v1: RegexAttributesExtrator::new(JOB_REGEX_SLURM1)?,
             extractor_v2: RegexAttributesExtrator::new(JOB_REGEX_SLURM2)?,
             step_extractor: RegexAttributesExtrator::new(JOB_STEP_REGEX)?,
             trigger,
+            tracker,
+            scontrol,
             jobs_only: config.jobs_only,
         })
     }
+
+    /// Returns the information about a job, and asks Slurm the first time.
+    ///
+    /// The job and its steps have different cgroups: the information is shared by their sources.
+    fn job_info(&mut self, job_id: u64) -> JobInfo {
+        if let Some(info) = self.tracker.get(job_id) {
+            return info;
+        }
+        let info = match &self.scontrol {
+            Some(scontrol) => scontrol.job_info(job_id).unwrap_or_else(|e| {
+                log::warn!("Could not get the user and the account of Slurm job {job_id}: {e:#}");
+                JobInfo::default()
+            }),
+            None => JobInfo::default(),
+        };
+        self.tracker.add(job_id, info.clone());
+        info
+    }
 }
 
 impl CgroupSetupCallback for JobSourceSetup {
@@ -56,6 +84,9 @@ impl CgroupSetupCallback for JobSourceSetup {
             self.step_extractor
                 .extract_into(cgroup.canonical_path(), &mut attrs)
                 .expect("bad regex: it should only match if the input can be parsed into the specified types");
+
+            // add the user and the account, and track the job
+            attrs.extend(self.job_info(job_id).attributes());
         } else {
             // not a job, just a cgroup (for ex. a systemd service)
             if self.jobs_only {
@@ -73,3 +104,48 @@ impl CgroupSetupCallback for JobSourceSetup {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, time::Duration};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn job_info_is_asked_once() {
+        // fake scontrol, which is called with `--oneliner show job <id>`
+        let dir = tempfile::tempdir().unwrap();
+        let scontrol = dir.path().join("scontrol");
+        fs::write(
+            &scontrol,
+            "#!/bin/sh\necho \"JobId=$4 JobName=train UserId=alice(1000) GroupId=alice(1000) Account=research QOS=normal\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&scontrol, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = Config {
+            poll_interval: Duration::from_secs(1),
+            scontrol: scontrol.clone(),
+            ..Default::default()
+        };
+        let tracker = JobTracker::new();
+        let mut setup = JobSourceSetup::new(config, tracker.clone()).unwrap();
+        let expected = JobInfo {
+            user: Some(String::from("alice")),
+            account: Some(String::from("research")),
+        };
+        assert_eq!(setup.job_info(12345), expected);
+        assert_eq!(tracker.known_jobs_sorted(), vec![12345]);
+
+        // the steps of the job use the information of the tracker
+        fs::remove_file(&scontrol).unwrap();
+        assert_eq!(setup.job_info(12345), expected);
+
+        // scontrol is not available: the job is tracked without information
+        assert_eq!(setup.job_info(678), JobInfo::default());
+        assert_eq!(tracker.known_jobs_sorted(), vec![678, 12345]);
+    }
+}
@@ -3,13 +3,13 @@ use alumet::{
         self,
         plugin::{PluginInfo, PluginSet},
     },
-    measurement::WrappedMeasurementValue,
-    pipeline::naming::SourceName,
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    pipeline::naming::{SourceName, TransformName},
     plugin::{
         PluginMetadata,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
-    resources::ResourceConsumer,
+    resources::{Resource, ResourceConsumer},
     test::{RuntimeExpectations, StartupExpectations},
     units::{PrefixedUnit, Unit},
 };
@@ -94,7 +94,8 @@ fn test_correct_run_with_no_jobs() {
         .expect_metric::<u64>("cgroup_memory_kernel_stack", Unit::Byte.clone())
         .expect_metric::<u64>("cgroup_memory_pagetables", Unit::Byte.clone())
         .expect_metric::<u64>("memory_usage", Unit::Byte.clone())
-        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second));
+        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second))
+        .expect_transform("slurm", "slurm_job_info_attacher");
 
     let agent = agent::Builder::new(plugins)
         .with_expectations(startup_expectations)
@@ -130,7 +131,8 @@ fn test_correct_run_with_one_job() {
         .expect_metric::<u64>("cgroup_memory_kernel_stack", Unit::Byte.clone())
         .expect_metric::<u64>("cgroup_memory_pagetables", Unit::Byte.clone())
         .expect_metric::<u64>("memory_usage", Unit::Byte.clone())
-        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second));
+        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second))
+        .expect_transform("slurm", "slurm_job_info_attacher");
 
     let pah_src1 = root.clone();
     let pah_src2 = root.clone();
@@ -219,7 +221,8 @@ fn test_correct_run_with_two_jobs() {
         .expect_metric::<u64>("cgroup_memory_kernel_stack", Unit::Byte.clone())
         .expect_metric::<u64>("cgroup_memory_pagetables", Unit::Byte.clone())
         .expect_metric::<u64>("memory_usage", Unit::Byte.clone())
-        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second));
+        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second))
+        .expect_transform("slurm", "slurm_job_info_attacher");
 
     let path_src11 = root.clone();
     let path_src12 = root.clone();
@@ -362,7 +365,8 @@ fn test_correct_run_with_one_job_coming_later() {
         .expect_metric::<u64>("cgroup_memory_kernel_stack", Unit::Byte.clone())
         .expect_metric::<u64>("cgroup_memory_pagetables", Unit::Byte.clone())
         .expect_metric::<u64>("memory_usage", Unit::Byte.clone())
-        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second));
+        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second))
+        .expect_transform("slurm", "slurm_job_info_attacher");
 
     let path_slurmstepd = root.clone();
     let path_job = root.clone();
@@ -402,6 +406,60 @@ fn test_correct_run_with_one_job_coming_later() {
     agent.wait_for_shutdown(TIMEOUT).unwrap();
 }
 
+#[test]
+fn test_involved_jobs() {
+    // Creation of file hierarchy
+    let tmp_root = tempdir().unwrap();
+    let root = tmp_root.path().to_path_buf();
+    cgroupv2::create_cgroupv2_tree_slurm_job(&root).unwrap();
+
+    let mut plugins = PluginSet::new();
+    let config = Config {
+        poll_interval: Duration::from_secs(1),
+        job_info: false,
+        ..Default::default()
+    };
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<SlurmPlugin>(),
+        enabled: true,
+        config: Some(config_to_toml_table(&config)),
+    });
+
+    let run_expect = RuntimeExpectations::new()
+        .create_metric::<f64>("rapl_consumed_energy", Unit::Joule)
+        .test_transform(
+            TransformName::from_str("slurm", "slurm_job_info_attacher"),
+            |ctx| {
+                let metric = ctx.metrics().by_name("rapl_consumed_energy").unwrap().0;
+                let point = MeasurementPoint::new_untyped(
+                    Timestamp::now(),
+                    metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    WrappedMeasurementValue::F64(12.5),
+                );
+                let mut buf = MeasurementBuffer::new();
+                buf.push(point.clone());
+                buf.push(point.with_attr("job_id", 12345_u64));
+                buf
+            },
+            |ctx| {
+                let m = ctx.measurements().to_vec();
+                assert_eq!(m.len(), 2);
+                // only the point that is not about a job gets the list of the running jobs
+                assert!(m[0].attributes_keys().any(|k| k == "involved_jobs"));
+                assert!(!m[1].attributes_keys().any(|k| k == "involved_jobs"));
+            },
+        );
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(run_expect)
+        .build_and_start()
+        .unwrap();
+
+    agent.wait_for_shutdown(TIMEOUT).unwrap();
+}
+
 #[test]
 fn test_cgroupv1_two_jobs() {
     let _ = env_logger::try_init_from_env(env_logger::Env::default());
@@ -425,7 +483,8 @@ fn test_cgroupv1_two_jobs() {
 
     let startup_expectations = StartupExpectations::new()
         .expect_metric::<u64>("memory_usage", Unit::Byte.clone())
-        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second));
+        .expect_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second))
+        .expect_transform("slurm", "slurm_job_info_attacher");
 
     let run_expect = RuntimeExpectations::new()
         .test_source(
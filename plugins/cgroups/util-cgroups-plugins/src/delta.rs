@@ -16,3 +16,22 @@ impl Default for CpuDeltaCounters {
         }
     }
 }
+
+/// CounterDiff for the I/O counters of a device.
+pub struct IoDeltaCounters {
+    pub rbytes: CounterDiff,
+    pub wbytes: CounterDiff,
+    pub rios: CounterDiff,
+    pub wios: CounterDiff,
+}
+
+impl Default for IoDeltaCounters {
+    fn default() -> Self {
+        Self {
+            rbytes: CounterDiff::with_max_value(u64::MAX),
+            wbytes: CounterDiff::with_max_value(u64::MAX),
+            rios: CounterDiff::with_max_value(u64::MAX),
+            wios: CounterDiff::with_max_value(u64::MAX),
+        }
+    }
+}
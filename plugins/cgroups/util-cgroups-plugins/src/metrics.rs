@@ -22,6 +22,10 @@ pub struct Metrics {
     pub memory_kernel_stack: TypedMetricId<u64>,
     /// Memory used to manage correspondence between virtual and physical addresses.
     pub memory_pagetables: TypedMetricId<u64>,
+    /// Bytes read or written by the cgroup since last measurement.
+    pub io_bytes_delta: TypedMetricId<u64>,
+    /// Number of read or write operations of the cgroup since last measurement.
+    pub io_operations_delta: TypedMetricId<u64>,
 }
 
 /// Used by probes to configure how cgroup measurements will be mapped to Alumet measurement points.
@@ -61,6 +65,10 @@ pub struct AugmentedMetrics {
     pub memory_kernel_stack: AugmentedMetric<u64>,
     /// Memory used to manage correspondence between virtual and physical addresses.
     pub memory_pagetables: AugmentedMetric<u64>,
+    /// Bytes read or written by the cgroup since last measurement.
+    pub io_bytes_delta: AugmentedMetric<u64>,
+    /// Number of read or write operations of the cgroup since last measurement.
+    pub io_operations_delta: AugmentedMetric<u64>,
 
     /// Common attributes, added to the points of all metrics.
     pub common_attrs: Vec<(String, AttributeValue)>,
@@ -104,6 +112,16 @@ impl Metrics {
             Unit::Byte,
             "Amount of memory allocated for page tables (which map virtual addresses to physical addresses).",
         )?;
+        let io_bytes_delta = alumet.create_metric::<u64>(
+            "cgroup_io_bytes_delta",
+            Unit::Byte,
+            "Bytes read or written by the cgroup on a block device since the previous measurement",
+        )?;
+        let io_operations_delta = alumet.create_metric::<u64>(
+            "cgroup_io_operations_delta",
+            Unit::Unity,
+            "Number of read or write operations of the cgroup on a block device since the previous measurement",
+        )?;
         Ok(Self {
             cpu_time_delta,
             cpu_percent,
@@ -112,6 +130,8 @@ impl Metrics {
             memory_file,
             memory_kernel_stack,
             memory_pagetables,
+            io_bytes_delta,
+            io_operations_delta,
         })
     }
 }
@@ -126,6 +146,8 @@ impl AugmentedMetrics {
             memory_file: AugmentedMetric::simple(metrics.memory_file),
             memory_kernel_stack: AugmentedMetric::simple(metrics.memory_kernel_stack),
             memory_pagetables: AugmentedMetric::simple(metrics.memory_pagetables),
+            io_bytes_delta: AugmentedMetric::simple(metrics.io_bytes_delta),
+            io_operations_delta: AugmentedMetric::simple(metrics.io_operations_delta),
             common_attrs: Vec::new(),
         }
     }
@@ -152,6 +174,8 @@ impl AugmentedMetrics {
             memory_file: AugmentedMetric::simple(metrics.memory_file),
             memory_kernel_stack: AugmentedMetric::simple(metrics.memory_kernel_stack),
             memory_pagetables: AugmentedMetric::simple(metrics.memory_pagetables),
+            io_bytes_delta: AugmentedMetric::simple(metrics.io_bytes_delta),
+            io_operations_delta: AugmentedMetric::simple(metrics.io_operations_delta),
             common_attrs,
         }
     }
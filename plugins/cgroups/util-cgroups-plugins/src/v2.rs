@@ -3,18 +3,24 @@ use alumet::{
     pipeline::{Source, elements::error::PollError},
     resources::{Resource, ResourceConsumer},
 };
+use rustc_hash::FxHashMap;
 use util_cgroups::{
     Cgroup,
     measure::v2::{V2Collector, cpu::CpuStatCollectorSettings, memory::MemoryStatCollectorSettings},
 };
 
 use super::{
-    delta::CpuDeltaCounters, metrics::AugmentedMetric, metrics::AugmentedMetrics, self_stop::analyze_io_result,
+    delta::{CpuDeltaCounters, IoDeltaCounters},
+    metrics::AugmentedMetric,
+    metrics::AugmentedMetrics,
+    self_stop::analyze_io_result,
 };
 
 pub struct CgroupV2Probe {
     consumer: ResourceConsumer,
     delta_counters: CpuDeltaCounters,
+    /// Counters of each block device, by device number.
+    io_delta_counters: FxHashMap<String, IoDeltaCounters>,
     metrics: AugmentedMetrics,
     collector: V2Collector,
     io_buf: Vec<u8>,
@@ -36,6 +42,7 @@ impl CgroupV2Probe {
         Ok(Self {
             consumer,
             delta_counters: Default::default(),
+            io_delta_counters: Default::default(),
             metrics,
             collector,
             io_buf,
@@ -154,6 +161,47 @@ impl Source for CgroupV2Probe {
                 measurements.push(self.new_point(&self.metrics.memory_pagetables, t, &resource, value));
             }
         }
+
+        // I/O statistics
+        if let Some(io_stat) = data.io_stat {
+            // forget the devices that have disappeared
+            self.io_delta_counters
+                .retain(|device, _| io_stat.iter().any(|stats| &stats.device == device));
+            for stats in io_stat {
+                let counters = self.io_delta_counters.entry(stats.device.clone()).or_default();
+                let deltas = [
+                    (
+                        &self.metrics.io_bytes_delta,
+                        "read",
+                        counters.rbytes.update(stats.rbytes).difference(),
+                    ),
+                    (
+                        &self.metrics.io_bytes_delta,
+                        "write",
+                        counters.wbytes.update(stats.wbytes).difference(),
+                    ),
+                    (
+                        &self.metrics.io_operations_delta,
+                        "read",
+                        counters.rios.update(stats.rios).difference(),
+                    ),
+                    (
+                        &self.metrics.io_operations_delta,
+                        "write",
+                        counters.wios.update(stats.wios).difference(),
+                    ),
+                ];
+                for (metric, kind, value) in deltas {
+                    if let Some(value) = value {
+                        measurements.push(
+                            self.new_point(metric, t, &resource, value)
+                                .with_attr("kind", kind)
+                                .with_attr("device", stats.device.clone()),
+                        );
+                    }
+                }
+            }
+        }
         Ok(())
     }
 }
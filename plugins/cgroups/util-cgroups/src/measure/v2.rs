@@ -6,6 +6,9 @@ pub mod cpu;
 /// Memory statistics for cgroup v2.
 pub mod memory;
 
+/// I/O statistics for cgroup v2.
+pub mod io;
+
 /// Small zero-cost wrapper around line index.
 mod line_index;
 
@@ -36,6 +39,7 @@ mod common {
 
     use super::{
         cpu::{CpuStatCollector, CpuStats},
+        io::{IoStatCollector, IoStats},
         memory::{MemoryCurrentCollector, MemoryStatCollector, MemoryStats},
     };
 
@@ -44,12 +48,15 @@ mod common {
         memory_current: Option<MemoryCurrentCollector>,
         memory_stat: Option<MemoryStatCollector>,
         cpu_stat: Option<CpuStatCollector>,
+        io_stat: Option<IoStatCollector>,
     }
 
     pub struct V2Stats {
         pub memory_current: Option<u64>,
         pub memory_stat: Option<MemoryStats>,
         pub cpu_stat: Option<CpuStats>,
+        /// One entry per device.
+        pub io_stat: Option<Vec<IoStats>>,
     }
 
     impl V2Collector {
@@ -71,6 +78,7 @@ mod common {
             let memory_current_file = cgroup_path.join("memory.current");
             let memory_stat_file = cgroup_path.join("memory.stat");
             let cpu_stat_file = cgroup_path.join("cpu.stat");
+            let io_stat_file = cgroup_path.join("io.stat");
 
             let prepare_memory_current = || -> anyhow::Result<Option<MemoryCurrentCollector>> {
                 match MemoryCurrentCollector::new(&memory_current_file) {
@@ -117,12 +125,28 @@ mod common {
                 }
             };
 
+            let prepare_io_stat = || -> anyhow::Result<Option<IoStatCollector>> {
+                match IoStatCollector::new(&io_stat_file) {
+                    Ok(res) => Ok(Some(res)),
+                    Err(e) if e.kind() == ErrorKind::NotFound => {
+                        // the file does not exist (the io controller is not enabled), ignore
+                        log::warn!(
+                            "{} does not exist, some metrics will not be available",
+                            io_stat_file.display()
+                        );
+                        Ok(None)
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            };
+
             let error_msg = || format!("collector creation failed for cgroup {}", cgroup.unique_name());
 
             Ok(Self {
                 memory_current: prepare_memory_current().with_context(error_msg)?,
                 memory_stat: prepare_memory_stat(io_buf).with_context(error_msg)?,
                 cpu_stat: prepare_cpu_stat(io_buf).with_context(error_msg)?,
+                io_stat: prepare_io_stat().with_context(error_msg)?,
             })
         }
 
@@ -133,11 +157,13 @@ mod common {
             let memory_current = self.memory_current.as_mut().map(|c| c.measure(io_buf)).transpose()?;
             let memory_stat = self.memory_stat.as_mut().map(|c| c.measure(io_buf)).transpose()?;
             let cpu_stat = self.cpu_stat.as_mut().map(|c| c.measure(io_buf)).transpose()?;
+            let io_stat = self.io_stat.as_mut().map(|c| c.measure(io_buf)).transpose()?;
 
             Ok(V2Stats {
                 memory_current,
                 memory_stat,
                 cpu_stat,
+                io_stat,
             })
         }
     }
@@ -0,0 +1,112 @@
+use std::{fs::File, io, path::Path};
+
+use crate::measure::parse::read_fully;
+
+/// Collects measurements from `io.stat`.
+pub struct IoStatCollector {
+    file: File,
+}
+
+/// Represents the I/O counters of one device, extracted from the `io.stat` file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IoStats {
+    /// Number of the device, in the `MAJ:MIN` format.
+    pub device: String,
+    /// Bytes read.
+    pub rbytes: u64,
+    /// Bytes written.
+    pub wbytes: u64,
+    /// Number of read operations.
+    pub rios: u64,
+    /// Number of write operations.
+    pub wios: u64,
+    // could be extended to manage the discard counters
+}
+
+impl IoStatCollector {
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Collects measurements from the underlying "file", using `io_buf` as an intermediary I/O buffer.
+    ///
+    /// Returns the counters of each device, in the order of the file.
+    /// The devices that have not been used by the cgroup are absent.
+    pub fn measure(&mut self, io_buf: &mut Vec<u8>) -> io::Result<Vec<IoStats>> {
+        read_fully(&mut self.file, io_buf)?;
+        // SAFETY: the content is generated by the kernel and is always valid ASCII (hence valid UTF-8)
+        let content = unsafe { std::str::from_utf8_unchecked(io_buf) };
+        parse_io_stat(content)
+    }
+}
+
+/// Parses the content of `io.stat`.
+///
+/// # Input format
+/// ```text
+/// 8:16 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0
+/// 8:0 rbytes=90430464 wbytes=299008000 rios=8950 wios=1252 dbytes=50331648 dios=3021
+/// ```
+fn parse_io_stat(content: &str) -> io::Result<Vec<IoStats>> {
+    let mut res = Vec::new();
+    for line in content.lines() {
+        let mut fields = line.split_ascii_whitespace();
+        let Some(device) = fields.next() else {
+            continue; // empty line
+        };
+        let mut stats = IoStats {
+            device: device.to_owned(),
+            ..Default::default()
+        };
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+            let counter = match key {
+                "rbytes" => &mut stats.rbytes,
+                "wbytes" => &mut stats.wbytes,
+                "rios" => &mut stats.rios,
+                "wios" => &mut stats.wios,
+                _ => continue,
+            };
+            *counter = value.parse().map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        }
+        res.push(stats);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IoStats, parse_io_stat};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse() {
+        let content = "8:16 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0\n\
+                       253:0 rbytes=90430464 wbytes=299008000 rios=8950 wios=1252\n";
+        assert_eq!(
+            parse_io_stat(content).unwrap(),
+            vec![
+                IoStats {
+                    device: String::from("8:16"),
+                    rbytes: 1459200,
+                    wbytes: 314773504,
+                    rios: 192,
+                    wios: 353,
+                },
+                IoStats {
+                    device: String::from("253:0"),
+                    rbytes: 90430464,
+                    wbytes: 299008000,
+                    rios: 8950,
+                    wios: 1252,
+                },
+            ]
+        );
+        assert_eq!(parse_io_stat("").unwrap(), vec![]);
+        assert!(parse_io_stat("8:0 rbytes=abc").is_err());
+        assert!(parse_io_stat("8:0 rbytes").is_err());
+    }
+}
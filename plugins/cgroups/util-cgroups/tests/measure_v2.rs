@@ -34,6 +34,11 @@ pub fn test_new_and_measure() -> anyhow::Result<()> {
     let data_mem_cur = "852";
     let mut file3 = File::create(file_path)?;
     file3.write_all(data_mem_cur.as_bytes())?;
+    // file 4
+    let file_path = root.path().join("io.stat");
+    let data_io = "8:0 rbytes=4096 wbytes=8192 rios=1 wios=2 dbytes=0 dios=0\n";
+    let mut file4 = File::create(file_path)?;
+    file4.write_all(data_io.as_bytes())?;
 
     let hierarchy = CgroupHierarchy::manually_unchecked(root.path(), CgroupVersion::V2, vec!["cpu", "memory"]);
     let cgroup = Cgroup::from_fs_path(&hierarchy, root.path().to_path_buf());
@@ -51,9 +56,11 @@ pub fn test_new_and_measure() -> anyhow::Result<()> {
     assert!(v2stat.cpu_stat.is_some());
     assert!(v2stat.memory_stat.is_some());
     assert!(v2stat.memory_current.is_some());
+    assert!(v2stat.io_stat.is_some());
     let cpu_stat = v2stat.cpu_stat.unwrap();
     let mem_stat = v2stat.memory_stat.unwrap();
     let mem_cur = v2stat.memory_current.unwrap();
+    let io_stat = v2stat.io_stat.unwrap();
 
     assert_eq!(cpu_stat.system.unwrap_or(0), 456);
     assert_eq!(cpu_stat.user.unwrap_or(0), 123);
@@ -66,6 +73,13 @@ pub fn test_new_and_measure() -> anyhow::Result<()> {
 
     assert_eq!(mem_cur, 852);
 
+    assert_eq!(io_stat.len(), 1);
+    assert_eq!(io_stat[0].device, "8:0");
+    assert_eq!(io_stat[0].rbytes, 4096);
+    assert_eq!(io_stat[0].wbytes, 8192);
+    assert_eq!(io_stat[0].rios, 1);
+    assert_eq!(io_stat[0].wios, 2);
+
     Ok(())
 }
 
@@ -139,6 +153,7 @@ pub fn test_new_files_dont_exist() -> anyhow::Result<()> {
     assert!(v2stat.cpu_stat.is_none());
     assert!(v2stat.memory_stat.is_none());
     assert!(v2stat.memory_current.is_none());
+    assert!(v2stat.io_stat.is_none());
     Ok(())
 }
 
@@ -0,0 +1,105 @@
+//! Credentials of the requests: static keys, or the IAM role of the EC2 instance.
+//!
+//! The credentials of the role are obtained from the instance metadata service, with IMDSv2.
+//! See <https://docs.aws.amazon.com/AWSEC2/latest/UserGuide/instance-metadata-security-credentials.html>.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, anyhow};
+use reqwest::blocking::{Client, Response};
+use serde::Deserialize;
+
+use alumet_sigv4::Credentials;
+
+/// Lifetime of the session tokens of IMDSv2.
+const IMDS_TOKEN_TTL: &str = "21600";
+/// The credentials of the role are renewed a bit before their expiration.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(300);
+
+pub enum CredentialsProvider {
+    Static(Credentials),
+    /// The IAM role attached to the EC2 instance.
+    InstanceProfile {
+        /// URL of the instance metadata service, such as `http://169.254.169.254`.
+        imds_endpoint: String,
+        /// The current credentials and their expiration.
+        current: Option<(Credentials, SystemTime)>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: String,
+    expiration: String,
+}
+
+impl CredentialsProvider {
+    /// Returns valid credentials, and renews them when needed.
+    pub fn credentials(&mut self, client: &Client) -> anyhow::Result<&Credentials> {
+        match self {
+            CredentialsProvider::Static(credentials) => Ok(credentials),
+            CredentialsProvider::InstanceProfile { imds_endpoint, current } => {
+                let valid = matches!(current, Some((_, expiration)) if *expiration > SystemTime::now() + EXPIRY_MARGIN);
+                if !valid {
+                    *current = Some(role_credentials(client, imds_endpoint)?);
+                }
+                Ok(&current.as_ref().unwrap().0)
+            }
+        }
+    }
+
+    /// Forgets the current credentials, for instance because they have expired earlier than expected.
+    pub fn invalidate(&mut self) {
+        if let CredentialsProvider::InstanceProfile { current, .. } = self {
+            *current = None;
+        }
+    }
+}
+
+/// Queries the instance metadata service, for instance `placement/region`.
+pub fn query_metadata(client: &Client, imds_endpoint: &str, path: &str) -> anyhow::Result<String> {
+    let imds_endpoint = imds_endpoint.trim_end_matches('/');
+    let res = client
+        .put(format!("{imds_endpoint}/latest/api/token"))
+        .header("X-aws-ec2-metadata-token-ttl-seconds", IMDS_TOKEN_TTL)
+        .send();
+    let token = text(res).context("failed to obtain a session token from the instance metadata service")?;
+    let res = client
+        .get(format!("{imds_endpoint}/latest/meta-data/{path}"))
+        .header("X-aws-ec2-metadata-token", token)
+        .send();
+    text(res).with_context(|| format!("failed to query {path} from the instance metadata service"))
+}
+
+fn role_credentials(client: &Client, imds_endpoint: &str) -> anyhow::Result<(Credentials, SystemTime)> {
+    let roles = query_metadata(client, imds_endpoint, "iam/security-credentials/")?;
+    let role = roles
+        .lines()
+        .next()
+        .filter(|r| !r.is_empty())
+        .ok_or_else(|| anyhow!("no IAM role is attached to the instance"))?;
+    let body = query_metadata(client, imds_endpoint, &format!("iam/security-credentials/{role}"))?;
+    let role: RoleCredentials = serde_json::from_str(&body).context("invalid credentials of the IAM role")?;
+    let expiration = humantime::parse_rfc3339(&role.expiration)
+        .with_context(|| format!("invalid expiration of the credentials: {}", role.expiration))?;
+    let credentials = Credentials {
+        access_key_id: role.access_key_id,
+        secret_access_key: role.secret_access_key,
+        session_token: Some(role.token),
+    };
+    Ok((credentials, expiration))
+}
+
+/// Returns the body of the response, or an error if its status is not a success.
+fn text(res: reqwest::Result<Response>) -> anyhow::Result<String> {
+    let res = res?;
+    let status = res.status();
+    let body = res.text().context("failed to read the response")?;
+    if !status.is_success() {
+        return Err(anyhow!("{status}: {body}"));
+    }
+    Ok(body)
+}
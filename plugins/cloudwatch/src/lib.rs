@@ -0,0 +1,188 @@
+mod credentials;
+mod metric_data;
+mod output;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{config_or_env, deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::{Context, anyhow};
+use reqwest::{Url, blocking::Client};
+use serde::{Deserialize, Serialize};
+
+use crate::credentials::CredentialsProvider;
+use crate::metric_data::{Encoder, MAX_DIMENSIONS};
+use crate::output::CloudWatchOutput;
+use alumet_sigv4::Credentials;
+
+pub use crate::metric_data::Field;
+
+/// Maximum number of metric data per `PutMetricData` request, imposed by CloudWatch.
+const MAX_BATCH_SIZE: usize = 1000;
+
+pub struct CloudWatchPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for CloudWatchPlugin {
+    fn name() -> &'static str {
+        "cloudwatch"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(
+            (1..=MAX_BATCH_SIZE).contains(&config.batch_size),
+            "batch_size must be between 1 and {MAX_BATCH_SIZE}"
+        );
+        anyhow::ensure!(
+            config.storage_resolution == 1 || config.storage_resolution == 60,
+            "storage_resolution must be 1 (high resolution) or 60 (standard resolution)"
+        );
+        let n_dimensions = config.dimensions.len() + config.attributes.len() + config.static_dimensions.len();
+        anyhow::ensure!(
+            n_dimensions <= MAX_DIMENSIONS,
+            "too many dimensions: {n_dimensions} (dimensions, attributes and static_dimensions), CloudWatch accepts at most {MAX_DIMENSIONS}"
+        );
+        Ok(Box::new(CloudWatchPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let http = Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .context("failed to create the HTTP client")?;
+
+        let region = config_or_env(&config.region, "AWS_REGION").or_else(|| std::env::var("AWS_DEFAULT_REGION").ok());
+        let region = match region {
+            Some(region) => region,
+            // Send the metrics to the region of the EC2 instance on which Alumet runs.
+            None => credentials::query_metadata(&http, &config.imds_endpoint, "placement/region").context(
+                "No region specified in the config, and unable to retrieve it from the instance metadata service.",
+            )?,
+        };
+        let endpoint = match &config.endpoint {
+            Some(endpoint) => endpoint.to_owned(),
+            None => format!("https://monitoring.{region}.amazonaws.com"),
+        };
+        let url = Url::parse(&endpoint).with_context(|| format!("invalid endpoint {endpoint:?}"))?;
+
+        let credentials = match (
+            config_or_env(&config.access_key_id, "AWS_ACCESS_KEY_ID"),
+            config_or_env(&config.secret_access_key, "AWS_SECRET_ACCESS_KEY"),
+        ) {
+            (Some(access_key_id), Some(secret_access_key)) => CredentialsProvider::Static(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: config_or_env(&config.session_token, "AWS_SESSION_TOKEN"),
+            }),
+            // Without keys, use the IAM role of the EC2 instance.
+            (None, None) => CredentialsProvider::InstanceProfile {
+                imds_endpoint: config.imds_endpoint.clone(),
+                current: None,
+            },
+            _ => return Err(anyhow!("access_key_id and secret_access_key must be given together")),
+        };
+
+        let encoder = Encoder {
+            namespace: config.namespace.clone(),
+            hostname,
+            fields: config.dimensions.clone(),
+            attributes: config.attributes.clone(),
+            static_dimensions: config.static_dimensions.clone(),
+            storage_resolution: config.storage_resolution,
+        };
+        let output = CloudWatchOutput::new(http, url, region, credentials, encoder, config.batch_size)?;
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Namespace of the metrics.
+    pub namespace: String,
+    /// Region of CloudWatch, such as `eu-west-3`. Defaults to the environment variables `AWS_REGION`
+    /// and `AWS_DEFAULT_REGION`, then to the region of the EC2 instance.
+    pub region: Option<String>,
+    /// URL of the CloudWatch API. Defaults to the endpoint of the region.
+    pub endpoint: Option<String>,
+    /// Credentials. Default to the environment variables `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+    ///
+    /// Without keys, the IAM role of the EC2 instance (instance profile) is used.
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub session_token: Option<String>,
+    /// URL of the EC2 instance metadata service.
+    pub imds_endpoint: String,
+    /// Value of the `hostname` dimension. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Properties of the measurements that become dimensions: `hostname`, `resource_kind`,
+    /// `resource_id`, `consumer_kind` and `consumer_id`.
+    pub dimensions: Vec<Field>,
+    /// Attributes of the measurements that become dimensions, when the measurements have them.
+    pub attributes: Vec<String>,
+    /// Dimensions added to every metric, for instance `InstanceId = "i-0123456789abcdef0"`
+    /// to match the metrics of EC2 in alarms and dashboards.
+    pub static_dimensions: BTreeMap<String, String>,
+    /// Storage resolution of the metrics, in seconds: 60 (standard) or 1 (high resolution).
+    pub storage_resolution: u32,
+    /// Maximum number of metric data per request (at most 1000).
+    pub batch_size: usize,
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            namespace: String::from("Alumet"),
+            region: None,
+            endpoint: None,
+            access_key_id: None,
+            secret_access_key: None,
+            session_token: None,
+            imds_endpoint: String::from("http://169.254.169.254"),
+            hostname: None,
+            dimensions: vec![
+                Field::ResourceKind,
+                Field::ResourceId,
+                Field::ConsumerKind,
+                Field::ConsumerId,
+            ],
+            attributes: Vec::new(),
+            static_dimensions: BTreeMap::new(),
+            storage_resolution: 60,
+            batch_size: MAX_BATCH_SIZE,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
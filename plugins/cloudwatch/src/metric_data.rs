@@ -0,0 +1,162 @@
+//! Encoding of the measurements as the parameters of `PutMetricData`, in the Query API.
+//!
+//! See <https://docs.aws.amazon.com/AmazonCloudWatch/latest/APIReference/API_PutMetricData.html>.
+
+use std::{collections::BTreeMap, fmt::Write, time::SystemTime};
+
+use alumet::{
+    measurement::{MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+    units::{PrefixedUnit, Unit, UnitPrefix},
+};
+use serde::{Deserialize, Serialize};
+
+use alumet_sigv4::uri_encode;
+
+/// Maximum number of dimensions of a metric, imposed by CloudWatch.
+pub const MAX_DIMENSIONS: usize = 30;
+
+/// A property of the measurements that can be used as a dimension.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Hostname,
+    ResourceKind,
+    ResourceId,
+    ConsumerKind,
+    ConsumerId,
+}
+
+impl Field {
+    fn name(&self) -> &'static str {
+        match self {
+            Field::Hostname => "hostname",
+            Field::ResourceKind => "resource_kind",
+            Field::ResourceId => "resource_id",
+            Field::ConsumerKind => "consumer_kind",
+            Field::ConsumerId => "consumer_id",
+        }
+    }
+}
+
+/// Converts the measurements into the parameters of the requests.
+pub struct Encoder {
+    pub namespace: String,
+    pub hostname: String,
+    /// The properties of the measurements that become dimensions.
+    pub fields: Vec<Field>,
+    /// The attributes that become dimensions, when the measurements have them.
+    pub attributes: Vec<String>,
+    /// Dimensions added to every metric, such as `InstanceId`.
+    pub static_dimensions: BTreeMap<String, String>,
+    /// Storage resolution of the metrics, in seconds: 60 (standard) or 1 (high resolution).
+    pub storage_resolution: u32,
+}
+
+impl Encoder {
+    /// Returns the beginning of the body of a request, before the metric data.
+    pub fn header(&self) -> String {
+        format!(
+            "Action=PutMetricData&Version=2010-08-01&Namespace={}",
+            uri_encode(&self.namespace, false)
+        )
+    }
+
+    /// Returns the parameters of the `index`-th metric datum of a request (starting at 1),
+    /// each preceded by `&`, or `None` if the value cannot be stored by CloudWatch.
+    pub fn datum(&self, index: usize, m: &MeasurementPoint, metric: &Metric) -> Option<String> {
+        let value = match m.value {
+            WrappedMeasurementValue::F64(v) => v,
+            WrappedMeasurementValue::U64(v) => v as f64,
+        };
+        // CloudWatch rejects NaN and infinite values.
+        if !value.is_finite() {
+            return None;
+        }
+
+        let prefix = format!("&MetricData.member.{index}.");
+        let mut res = String::new();
+        let mut param = |name: &str, value: &str| {
+            let _ = write!(res, "{prefix}{name}={}", uri_encode(value, false));
+        };
+        param("MetricName", &metric.name);
+        let mut n = 0;
+        for (name, value) in self.dimensions(m) {
+            // The values of the dimensions cannot be empty.
+            if value.is_empty() {
+                continue;
+            }
+            n += 1;
+            param(&format!("Dimensions.member.{n}.Name"), name);
+            param(&format!("Dimensions.member.{n}.Value"), &value);
+        }
+        let timestamp = humantime::format_rfc3339_millis(SystemTime::from(m.timestamp)).to_string();
+        param("Timestamp", &timestamp);
+        param("Value", &value.to_string());
+        param("Unit", unit(&metric.unit));
+        if self.storage_resolution != 60 {
+            param("StorageResolution", &self.storage_resolution.to_string());
+        }
+        Some(res)
+    }
+
+    fn dimensions<'a>(&'a self, m: &'a MeasurementPoint) -> Vec<(&'a str, String)> {
+        let mut dims: Vec<(&str, String)> = self
+            .fields
+            .iter()
+            .map(|f| {
+                let value = match f {
+                    Field::Hostname => self.hostname.clone(),
+                    Field::ResourceKind => m.resource.kind().to_owned(),
+                    Field::ResourceId => m.resource.id_display().to_string(),
+                    Field::ConsumerKind => m.consumer.kind().to_owned(),
+                    Field::ConsumerId => m.consumer.id_display().to_string(),
+                };
+                (f.name(), value)
+            })
+            .collect();
+        for key in &self.attributes {
+            if let Some((_, value)) = m.attributes().find(|(k, _)| k == key) {
+                dims.push((key, value.to_string()));
+            }
+        }
+        dims.extend(self.static_dimensions.iter().map(|(k, v)| (k.as_str(), v.clone())));
+        dims
+    }
+}
+
+/// Returns the CloudWatch unit that corresponds to an Alumet unit, or `"None"` if there is none.
+fn unit(unit: &PrefixedUnit) -> &'static str {
+    match (&unit.base_unit, &unit.prefix) {
+        (Unit::Second, UnitPrefix::Plain) => "Seconds",
+        (Unit::Second, UnitPrefix::Milli) => "Milliseconds",
+        (Unit::Second, UnitPrefix::Micro) => "Microseconds",
+        (Unit::Byte, UnitPrefix::Plain) => "Bytes",
+        (Unit::Byte, UnitPrefix::Kilo) => "Kilobytes",
+        (Unit::Byte, UnitPrefix::Mega) => "Megabytes",
+        (Unit::Byte, UnitPrefix::Giga) => "Gigabytes",
+        (Unit::Percent, UnitPrefix::Plain) => "Percent",
+        (Unit::Unity, UnitPrefix::Plain) => "Count",
+        _ => "None",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::units::{PrefixedUnit, Unit, UnitPrefix};
+    use pretty_assertions::assert_eq;
+
+    use super::unit;
+
+    #[test]
+    fn units() {
+        assert_eq!(unit(&PrefixedUnit::from(Unit::Second)), "Seconds");
+        let ms = PrefixedUnit {
+            base_unit: Unit::Second,
+            prefix: UnitPrefix::Milli,
+        };
+        assert_eq!(unit(&ms), "Milliseconds");
+        assert_eq!(unit(&PrefixedUnit::from(Unit::Unity)), "Count");
+        assert_eq!(unit(&PrefixedUnit::from(Unit::Joule)), "None");
+    }
+}
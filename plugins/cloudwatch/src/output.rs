@@ -0,0 +1,141 @@
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::elements::{error::WriteError, output::OutputContext, output::error::WriteRetry},
+};
+use alumet_sigv4 as sigv4;
+use anyhow::{Context, anyhow};
+use reqwest::{StatusCode, Url, blocking::Client};
+use time::OffsetDateTime;
+
+use crate::{credentials::CredentialsProvider, metric_data::Encoder};
+
+/// Maximum size of the body of a `PutMetricData` request, imposed by CloudWatch.
+const MAX_BODY_SIZE: usize = 1_000_000;
+
+const CONTENT_TYPE: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+pub struct CloudWatchOutput {
+    http: Client,
+    /// URL of the CloudWatch API, such as `https://monitoring.eu-west-3.amazonaws.com`.
+    url: Url,
+    /// Value of the `host` header, signed with the request.
+    host: String,
+    region: String,
+    credentials: CredentialsProvider,
+    encoder: Encoder,
+    /// Maximum number of metric data per request.
+    batch_size: usize,
+}
+
+impl CloudWatchOutput {
+    pub fn new(
+        http: Client,
+        url: Url,
+        region: String,
+        credentials: CredentialsProvider,
+        encoder: Encoder,
+        batch_size: usize,
+    ) -> anyhow::Result<Self> {
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => return Err(anyhow!("invalid endpoint {url}: missing host")),
+        };
+        Ok(Self {
+            http,
+            url,
+            host,
+            region,
+            credentials,
+            encoder,
+            batch_size,
+        })
+    }
+
+    /// Sends a `PutMetricData` request.
+    fn put_metric_data(&mut self, body: String) -> Result<(), WriteError> {
+        let credentials = self
+            .credentials
+            .credentials(&self.http)
+            .context("failed to obtain the credentials of the IAM role")
+            .retry_write()?;
+        let payload_sha256 = sigv4::sha256_hex(body.as_bytes());
+        let request = sigv4::Request {
+            method: "POST",
+            host: &self.host,
+            path: self.url.path(),
+            query: &[],
+            headers: &[("Content-Type", CONTENT_TYPE)],
+            payload_sha256: &payload_sha256,
+        };
+        let headers = sigv4::sign(
+            &request,
+            credentials,
+            &self.region,
+            "monitoring",
+            OffsetDateTime::now_utc(),
+        );
+
+        let mut request = self.http.post(self.url.clone()).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let res = request
+            .send()
+            .context("failed to send the request to CloudWatch")
+            .retry_write()?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = res.text().unwrap_or_default();
+        if status == StatusCode::FORBIDDEN && body.contains("ExpiredToken") {
+            // The credentials of the role have expired earlier than expected.
+            self.credentials.invalidate();
+            return Err(WriteError::CanRetry(anyhow!("CloudWatch error {status}: {body}")));
+        }
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS || body.contains("Throttling") {
+            // The API is busy or unavailable, try again later.
+            return Err(WriteError::CanRetry(anyhow!("CloudWatch error {status}: {body}")));
+        }
+        // Invalid data, missing permissions... sending the metrics again would not help.
+        log::error!("CloudWatch rejected the metric data with status {status}: {body}");
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Output for CloudWatchOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let header = self.encoder.header();
+        let mut body = header.clone();
+        let mut count = 0;
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            let Some(datum) = self.encoder.datum(count + 1, m, metric) else {
+                log::debug!("skipping the non-finite value {:?} of {}", m.value, metric.name);
+                continue;
+            };
+            if count > 0 && body.len() + datum.len() > MAX_BODY_SIZE {
+                // Send the previous data, and number this datum again as the first of the next request.
+                self.put_metric_data(std::mem::replace(&mut body, header.clone()))?;
+                count = 0;
+                body.push_str(&self.encoder.datum(1, m, metric).unwrap());
+            } else {
+                body.push_str(&datum);
+            }
+            count += 1;
+            if count == self.batch_size {
+                self.put_metric_data(std::mem::replace(&mut body, header.clone()))?;
+                count = 0;
+            }
+        }
+        if count > 0 {
+            self.put_metric_data(body)?;
+        }
+        Ok(())
+    }
+}
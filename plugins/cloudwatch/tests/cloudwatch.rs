@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use alumet::{
+    pipeline::elements::error::WriteError,
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{CapturedRequest, HttpCapture, SampleMeasurements, assert_golden},
+    },
+};
+use mockito::Server;
+use plugin_cloudwatch::{CloudWatchPlugin, Config, Field};
+use pretty_assertions::assert_eq;
+
+fn config(server: &HttpCapture) -> Config {
+    Config {
+        region: Some(String::from("eu-west-3")),
+        endpoint: Some(server.url()),
+        access_key_id: Some(String::from("AKIDEXAMPLE")),
+        secret_access_key: Some(String::from("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY")),
+        hostname: Some(String::from("node-1")),
+        ..Config::default()
+    }
+}
+
+/// Puts each parameter on its own line, to make the golden files readable.
+fn lines(requests: &[CapturedRequest]) -> String {
+    requests
+        .iter()
+        .map(|r| r.body_str().replace('&', "\n&") + "\n\n")
+        .collect()
+}
+
+#[test]
+fn put_metric_data() -> anyhow::Result<()> {
+    let server = HttpCapture::start()?;
+
+    let config = Config {
+        dimensions: vec![Field::Hostname, Field::ResourceKind, Field::ResourceId],
+        attributes: vec![String::from("state")],
+        static_dimensions: BTreeMap::from([(String::from("InstanceId"), String::from("i-0123456789abcdef0"))]),
+        storage_resolution: 1,
+        ..config(&server)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<CloudWatchPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!((requests[0].method.as_str(), requests[0].path.as_str()), ("POST", "/"));
+    assert_eq!(
+        requests[0].header("content-type"),
+        Some("application/x-www-form-urlencoded; charset=utf-8")
+    );
+    let authorization = requests[0].header("authorization").unwrap_or_default();
+    assert!(
+        authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/")
+            && authorization
+                .contains("/eu-west-3/monitoring/aws4_request, SignedHeaders=content-type;host;x-amz-date, "),
+        "unexpected authorization: {authorization}"
+    );
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/put_metric_data.txt"),
+        lines(&requests),
+    );
+    Ok(())
+}
+
+#[test]
+fn batching() -> anyhow::Result<()> {
+    let server = HttpCapture::start()?;
+
+    let config = Config {
+        batch_size: 2,
+        ..config(&server)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<CloudWatchPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    // 5 points, at most 2 per request
+    let requests = server.requests();
+    let bodies: Vec<&str> = requests.iter().map(|r| r.body_str()).collect();
+    let counts: Vec<usize> = bodies.iter().map(|b| b.matches(".MetricName=").count()).collect();
+    assert_eq!(counts, vec![2, 2, 1]);
+    // the data are numbered from 1 in each request
+    assert!(bodies.iter().all(|b| b.contains("&MetricData.member.1.MetricName=")));
+    Ok(())
+}
+
+#[test]
+fn instance_profile() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let token = server
+        .mock("PUT", "/latest/api/token")
+        .match_header("x-aws-ec2-metadata-token-ttl-seconds", "21600")
+        .with_body("imds-token")
+        .expect_at_least(1)
+        .create();
+    let region = server
+        .mock("GET", "/latest/meta-data/placement/region")
+        .match_header("x-aws-ec2-metadata-token", "imds-token")
+        .with_body("eu-west-3")
+        .create();
+    let roles = server
+        .mock("GET", "/latest/meta-data/iam/security-credentials/")
+        .match_header("x-aws-ec2-metadata-token", "imds-token")
+        .with_body("alumet-role\n")
+        .create();
+    let role = server
+        .mock("GET", "/latest/meta-data/iam/security-credentials/alumet-role")
+        .match_header("x-aws-ec2-metadata-token", "imds-token")
+        .with_body(
+            r#"{
+                "Code": "Success",
+                "LastUpdated": "2023-11-14T21:00:00Z",
+                "Type": "AWS-HMAC",
+                "AccessKeyId": "ASIAEXAMPLE",
+                "SecretAccessKey": "secret",
+                "Token": "session-token",
+                "Expiration": "2100-01-01T00:00:00Z"
+            }"#,
+        )
+        .create();
+    let metrics = HttpCapture::start()?;
+
+    let config = Config {
+        region: None,
+        access_key_id: None,
+        secret_access_key: None,
+        imds_endpoint: server.url(),
+        ..config(&metrics)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<CloudWatchPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    // the credentials are reused by the next requests
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    token.assert();
+    region.assert();
+    roles.assert();
+    role.assert();
+    let requests = metrics.requests();
+    assert_eq!(requests.len(), 2);
+    for request in requests {
+        assert_eq!(request.header("x-amz-security-token"), Some("session-token"));
+        let authorization = request.header("authorization").unwrap_or_default();
+        assert!(
+            authorization.starts_with("AWS4-HMAC-SHA256 Credential=ASIAEXAMPLE/")
+                && authorization.contains("/eu-west-3/monitoring/"),
+            "unexpected authorization: {authorization}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn throttling() -> anyhow::Result<()> {
+    let response = "<ErrorResponse><Error><Type>Sender</Type><Code>Throttling</Code><Message>Rate exceeded</Message></Error></ErrorResponse>";
+    let server = HttpCapture::with_response(400, response)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<CloudWatchPlugin>(config(&server))?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn rejected() -> anyhow::Result<()> {
+    let response =
+        "<ErrorResponse><Error><Type>Sender</Type><Code>InvalidParameterValue</Code></Error></ErrorResponse>";
+    let server = HttpCapture::with_response(400, response)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<CloudWatchPlugin>(config(&server))?;
+    let mut output = harness.output("out")?;
+    // invalid data are dropped instead of being sent again
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+    assert_eq!(server.requests().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn invalid_config() -> anyhow::Result<()> {
+    let server = HttpCapture::start()?;
+    let too_many = Config {
+        attributes: (0..27).map(|i| format!("attr{i}")).collect(),
+        ..config(&server)
+    };
+    let bad_resolution = Config {
+        storage_resolution: 10,
+        ..config(&server)
+    };
+    let bad_batch = Config {
+        batch_size: 1001,
+        ..config(&server)
+    };
+    for config in [too_many, bad_resolution, bad_batch] {
+        assert!(PluginHarness::<CloudWatchPlugin>::start(serialize_config(config)?).is_err());
+    }
+    Ok(())
+}
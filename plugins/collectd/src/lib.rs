@@ -0,0 +1,248 @@
+mod protocol;
+mod source;
+mod types_db;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+pub use crate::protocol::SecurityLevel;
+use crate::{protocol::Decoder, source::CollectdSource, types_db::TypesDb};
+
+pub struct CollectdPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for CollectdPlugin {
+    fn name() -> &'static str {
+        "collectd"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.security_level != SecurityLevel::None && config.users.is_empty() && config.auth_file.is_none() {
+            return Err(anyhow!(
+                "security_level {:?} requires some users, please set users or auth_file in the configuration.",
+                config.security_level
+            ));
+        }
+        Ok(Box::new(CollectdPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let mut users = self.config.users.clone();
+        if let Some(auth_file) = &self.config.auth_file {
+            users.extend(read_auth_file(auth_file)?);
+        }
+        let decoder = Decoder {
+            security_level: self.config.security_level,
+            users,
+        };
+
+        let mut types_db = TypesDb::default();
+        for path in &self.config.types_db {
+            match TypesDb::load(path) {
+                Ok(db) => types_db.extend(db),
+                // the names of the data sources are replaced by their index
+                Err(e) => log::warn!("{e:#}"),
+            }
+        }
+
+        // Bind the socket right now (fail fast).
+        let address = &self.config.address;
+        let socket =
+            std::net::UdpSocket::bind(address.as_str()).with_context(|| format!("failed to listen on {address}"))?;
+        socket.set_nonblocking(true)?;
+        log::info!("Listening for collectd on {}", socket.local_addr()?);
+
+        let flush_interval = self.config.flush_interval;
+        alumet.add_autonomous_source_builder("udp", move |ctx, cancel_token, out_tx| {
+            let metrics_tx = ctx.metrics_sender();
+            let source = Box::pin(async move {
+                let socket = tokio::net::UdpSocket::from_std(socket)?;
+                let source = CollectdSource::new(socket, flush_interval, decoder, types_db, metrics_tx, out_tx);
+                source.run(cancel_token).await
+            });
+            Ok(source)
+        })?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads an authentication file of collectd, which contains lines like `user: password`.
+fn read_auth_file(path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let content = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let users = content
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(user, password)| (user.trim().to_owned(), password.trim().to_owned()))
+        .filter(|(user, _)| !user.is_empty())
+        .collect();
+    Ok(users)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the UDP socket.
+    pub address: String,
+    /// Interval between two sendings of the received measurements to the pipeline.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+    /// Minimum security of the accepted values: `"none"`, `"sign"` or `"encrypt"`.
+    pub security_level: SecurityLevel,
+    /// Password of each user, to check the signatures and decrypt the packets.
+    pub users: BTreeMap<String, String>,
+    /// Authentication file of collectd (`AuthFile`), which contains lines like `user: password`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_file: Option<PathBuf>,
+    /// Files that define the data sources of the collectd types (`TypesDB`), to name them.
+    pub types_db: Vec<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: String::from("127.0.0.1:25826"),
+            flush_interval: Duration::from_secs(1),
+            security_level: SecurityLevel::None,
+            users: BTreeMap::new(),
+            auth_file: None,
+            types_db: vec![PathBuf::from("/usr/share/collectd/types.db")],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+
+    use alumet::{
+        measurement::{AttributeValue, WrappedMeasurementValue},
+        test::PluginHarness,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::protocol::{Value, tests::Encoder};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn free_address() -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn encrypted_packets() -> anyhow::Result<()> {
+        let tmp = std::env::temp_dir().join(format!("alumet-collectd-{}", std::process::id()));
+        fs::create_dir_all(&tmp)?;
+        let types_db = tmp.join("types.db");
+        fs::write(&types_db, "if_octets rx:DERIVE:0:U, tx:DERIVE:0:U\n")?;
+        let auth_file = tmp.join("passwd");
+        fs::write(&auth_file, "alice: secret\n")?;
+
+        let address = free_address();
+        let config = Config {
+            address: address.clone(),
+            flush_interval: Duration::from_millis(100),
+            security_level: SecurityLevel::Encrypt,
+            auth_file: Some(auth_file),
+            types_db: vec![types_db, tmp.join("missing.db")],
+            ..Config::default()
+        };
+        let mut harness = PluginHarness::<CollectdPlugin>::start(serialize_config(config)?)?;
+        let mut source = harness.autonomous_source("udp")?;
+
+        let client = UdpSocket::bind("127.0.0.1:0")?;
+        let mut packet = Encoder(Vec::new());
+        let mut send = |rx: i64, tx: i64, temperature: f64| {
+            packet.0.clear();
+            packet
+                .value_list(
+                    "node-1",
+                    0,
+                    "interface",
+                    "if_octets",
+                    &[Value::Derive(rx), Value::Derive(tx)],
+                )
+                .value_list("node-1", 0, "sensors", "temperature", &[Value::Gauge(temperature)]);
+            client.send_to(&packet.encrypted("alice", "secret"), &address).unwrap();
+            // unencrypted packets are ignored
+            client.send_to(&packet.0, &address).unwrap();
+        };
+
+        send(100, 50, 40.0);
+        let buffer = source.recv_timeout(TIMEOUT).expect("measurements should be received");
+        assert_eq!(
+            harness.values(&buffer, "collectd_sensors_temperature"),
+            vec![WrappedMeasurementValue::F64(40.0)]
+        );
+        let point = harness.points(&buffer, "collectd_sensors_temperature")[0];
+        let attributes: Vec<_> = point.attributes().map(|(k, v)| (k.to_owned(), v.clone())).collect();
+        assert_eq!(
+            attributes,
+            vec![(String::from("host"), AttributeValue::String(String::from("node-1")))]
+        );
+
+        // the derives are reported as increases, from the second value
+        send(160, 75, 41.0);
+        let buffer = source.recv_timeout(TIMEOUT).expect("measurements should be received");
+        let octets: Vec<_> = harness
+            .points(&buffer, "collectd_interface_if_octets")
+            .into_iter()
+            .map(|p| {
+                let ds = p.attributes().find(|(k, _)| *k == "ds").unwrap().1;
+                (ds.to_string(), p.value.clone())
+            })
+            .collect();
+        assert_eq!(
+            octets,
+            vec![
+                (String::from("rx"), WrappedMeasurementValue::F64(60.0)),
+                (String::from("tx"), WrappedMeasurementValue::F64(25.0)),
+            ]
+        );
+
+        source.stop()?;
+        harness.stop()?;
+        fs::remove_dir_all(&tmp)?;
+        Ok(())
+    }
+
+    #[test]
+    fn users_are_required() {
+        let config = Config {
+            security_level: SecurityLevel::Sign,
+            ..Config::default()
+        };
+        assert!(CollectdPlugin::init(serialize_config(config).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,513 @@
+//! Decoding of the binary network protocol of collectd.
+//!
+//! A packet is a sequence of parts, each with a type, a length and a payload. The parts that identify
+//! a value list (host, plugin, type, time...) set a state, which applies to the following `VALUES` parts.
+//!
+//! The packets can be signed with HMAC-SHA-256, or encrypted with AES-256 in OFB mode. Both use the
+//! password of a user: the signed or encrypted data is decoded like a new packet, with a new state.
+//! See <https://github.com/collectd/collectd/wiki/Binary-protocol>.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use aes::{
+    Aes256, Block,
+    cipher::{BlockEncrypt, KeyInit},
+};
+use alumet::measurement::Timestamp;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const TYPE_HOST: u16 = 0x0000;
+const TYPE_TIME: u16 = 0x0001;
+const TYPE_TIME_HR: u16 = 0x0008;
+const TYPE_PLUGIN: u16 = 0x0002;
+const TYPE_PLUGIN_INSTANCE: u16 = 0x0003;
+const TYPE_TYPE: u16 = 0x0004;
+const TYPE_TYPE_INSTANCE: u16 = 0x0005;
+const TYPE_VALUES: u16 = 0x0006;
+const TYPE_SIGN_SHA256: u16 = 0x0200;
+const TYPE_ENCR_AES256: u16 = 0x0210;
+
+const DS_COUNTER: u8 = 0;
+const DS_GAUGE: u8 = 1;
+const DS_DERIVE: u8 = 2;
+const DS_ABSOLUTE: u8 = 3;
+
+const PART_HEADER_LEN: usize = 4;
+const SHA256_LEN: usize = 32;
+const SHA1_LEN: usize = 20;
+const IV_LEN: usize = 16;
+
+/// Minimum security of the accepted values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecurityLevel {
+    /// Accept all the values. The signatures are checked when the user is known.
+    #[default]
+    None,
+    /// Only accept the values that are signed or encrypted.
+    Sign,
+    /// Only accept the values that are encrypted.
+    Encrypt,
+}
+
+/// A set of values sent by a collectd plugin, identified by `host/plugin-plugin_instance/type-type_instance`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ValueList {
+    pub host: String,
+    pub time: Option<Timestamp>,
+    pub plugin: String,
+    pub plugin_instance: String,
+    pub type_: String,
+    pub type_instance: String,
+    pub values: Vec<Value>,
+}
+
+/// A value of a data source, which has a type in collectd.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    /// A counter that only increases, and may wrap around.
+    Counter(u64),
+    Gauge(f64),
+    /// A counter that may decrease.
+    Derive(i64),
+    /// A counter that is reset when it is read.
+    Absolute(u64),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The packet is truncated or a part is malformed.
+    Malformed(&'static str),
+    UnknownUser(String),
+    InvalidSignature(String),
+    /// The checksum of the decrypted data is wrong, the password is probably wrong.
+    DecryptionFailed(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Malformed(reason) => write!(f, "malformed packet: {reason}"),
+            Error::UnknownUser(user) => write!(f, "unknown user {user:?}"),
+            Error::InvalidSignature(user) => write!(f, "invalid signature of user {user:?}"),
+            Error::DecryptionFailed(user) => write!(f, "failed to decrypt the data of user {user:?}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Decodes the packets of collectd.
+pub struct Decoder {
+    pub security_level: SecurityLevel,
+    /// Password of each user.
+    pub users: BTreeMap<String, String>,
+}
+
+impl Decoder {
+    /// Decodes a packet, and returns its value lists that have the required security level.
+    pub fn decode(&self, packet: &[u8]) -> Result<Vec<ValueList>, Error> {
+        let mut value_lists = Vec::new();
+        self.decode_parts(packet, SecurityLevel::None, &mut value_lists)?;
+        Ok(value_lists)
+    }
+
+    fn decode_parts(&self, mut data: &[u8], level: SecurityLevel, out: &mut Vec<ValueList>) -> Result<(), Error> {
+        let mut state = ValueList::default();
+        while !data.is_empty() {
+            if data.len() < PART_HEADER_LEN {
+                return Err(Error::Malformed("truncated part header"));
+            }
+            let part_type = u16::from_be_bytes([data[0], data[1]]);
+            let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+            if len < PART_HEADER_LEN || len > data.len() {
+                return Err(Error::Malformed("invalid part length"));
+            }
+            let payload = &data[PART_HEADER_LEN..len];
+            let rest = &data[len..];
+            match part_type {
+                TYPE_HOST => state.host = string(payload)?,
+                TYPE_TIME => state.time = timestamp(u64_part(payload)?, 1)?,
+                TYPE_TIME_HR => state.time = timestamp(u64_part(payload)?, 1 << 30)?,
+                TYPE_PLUGIN => state.plugin = string(payload)?,
+                TYPE_PLUGIN_INSTANCE => state.plugin_instance = string(payload)?,
+                TYPE_TYPE => state.type_ = string(payload)?,
+                TYPE_TYPE_INSTANCE => state.type_instance = string(payload)?,
+                TYPE_VALUES => {
+                    let values = values(payload)?;
+                    if level >= self.security_level {
+                        out.push(ValueList {
+                            values,
+                            ..state.clone()
+                        });
+                    }
+                }
+                TYPE_SIGN_SHA256 => {
+                    // the signature covers the rest of the packet, which is decoded as a new packet
+                    return self.verify_signature(payload, rest, level, out);
+                }
+                TYPE_ENCR_AES256 => {
+                    let plaintext = self.decrypt(payload)?;
+                    self.decode_parts(&plaintext, SecurityLevel::Encrypt, out)?;
+                }
+                // notifications, intervals and unknown parts
+                _ => (),
+            }
+            data = rest;
+        }
+        Ok(())
+    }
+
+    fn verify_signature(
+        &self,
+        payload: &[u8],
+        signed: &[u8],
+        level: SecurityLevel,
+        out: &mut Vec<ValueList>,
+    ) -> Result<(), Error> {
+        if payload.len() < SHA256_LEN {
+            return Err(Error::Malformed("truncated signature"));
+        }
+        let (hash, username) = payload.split_at(SHA256_LEN);
+        let username = String::from_utf8_lossy(username).into_owned();
+        let Some(password) = self.users.get(&username) else {
+            if self.security_level == SecurityLevel::None {
+                // the signature cannot be checked, but it is not required
+                return self.decode_parts(signed, level, out);
+            }
+            return Err(Error::UnknownUser(username));
+        };
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(password.as_bytes()).expect("HMAC accepts keys of any size");
+        mac.update(username.as_bytes());
+        mac.update(signed);
+        if mac.verify_slice(hash).is_err() {
+            return Err(Error::InvalidSignature(username));
+        }
+        self.decode_parts(signed, level.max(SecurityLevel::Sign), out)
+    }
+
+    /// Decrypts the payload of an encrypted part, and returns the parts that it contains.
+    fn decrypt(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        if payload.len() < 2 {
+            return Err(Error::Malformed("truncated encrypted part"));
+        }
+        let username_len = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+        let payload = &payload[2..];
+        if payload.len() < username_len + IV_LEN + SHA1_LEN {
+            return Err(Error::Malformed("truncated encrypted part"));
+        }
+        let (username, payload) = payload.split_at(username_len);
+        let (iv, encrypted) = payload.split_at(IV_LEN);
+        let username = String::from_utf8_lossy(username).into_owned();
+        let password = self
+            .users
+            .get(&username)
+            .ok_or_else(|| Error::UnknownUser(username.clone()))?;
+
+        let mut data = encrypted.to_vec();
+        aes256_ofb(
+            &Sha256::digest(password.as_bytes()).into(),
+            iv.try_into().unwrap(),
+            &mut data,
+        );
+        let plaintext = data.split_off(SHA1_LEN);
+        if Sha1::digest(&plaintext).as_slice() != data.as_slice() {
+            return Err(Error::DecryptionFailed(username));
+        }
+        Ok(plaintext)
+    }
+}
+
+/// Encrypts or decrypts `data` with AES-256 in OFB mode.
+pub(crate) fn aes256_ofb(key: &[u8; 32], iv: &[u8; IV_LEN], data: &mut [u8]) {
+    let cipher = Aes256::new(key.into());
+    let mut keystream = Block::clone_from_slice(iv);
+    for chunk in data.chunks_mut(IV_LEN) {
+        cipher.encrypt_block(&mut keystream);
+        for (byte, key) in chunk.iter_mut().zip(keystream.iter()) {
+            *byte ^= key;
+        }
+    }
+}
+
+/// Decodes a null-terminated string.
+fn string(payload: &[u8]) -> Result<String, Error> {
+    match payload.split_last() {
+        Some((0, s)) => Ok(String::from_utf8_lossy(s).into_owned()),
+        _ => Err(Error::Malformed("string is not null-terminated")),
+    }
+}
+
+fn u64_part(payload: &[u8]) -> Result<u64, Error> {
+    let bytes = payload
+        .try_into()
+        .map_err(|_| Error::Malformed("invalid numeric part"))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Converts a time in `1/resolution` seconds to a timestamp. A null time means "now".
+fn timestamp(time: u64, resolution: u64) -> Result<Option<Timestamp>, Error> {
+    if time == 0 {
+        return Ok(None);
+    }
+    let secs = time / resolution;
+    let nanos = (time % resolution) * 1_000_000_000 / resolution;
+    let time = UNIX_EPOCH
+        .checked_add(Duration::new(secs, nanos as u32))
+        .ok_or(Error::Malformed("time out of range"))?;
+    Ok(Some(Timestamp::from(time)))
+}
+
+fn values(payload: &[u8]) -> Result<Vec<Value>, Error> {
+    if payload.len() < 2 {
+        return Err(Error::Malformed("truncated values"));
+    }
+    let count = u16::from_be_bytes([payload[0], payload[1]]) as usize;
+    let payload = &payload[2..];
+    if payload.len() != count * 9 {
+        return Err(Error::Malformed("invalid number of values"));
+    }
+    let (types, values) = payload.split_at(count);
+    types
+        .iter()
+        .zip(values.chunks_exact(8))
+        .map(|(ds_type, bytes)| {
+            let bytes: [u8; 8] = bytes.try_into().unwrap();
+            match *ds_type {
+                DS_COUNTER => Ok(Value::Counter(u64::from_be_bytes(bytes))),
+                // gauges are in the byte order of x86, unlike the other values
+                DS_GAUGE => Ok(Value::Gauge(f64::from_le_bytes(bytes))),
+                DS_DERIVE => Ok(Value::Derive(i64::from_be_bytes(bytes))),
+                DS_ABSOLUTE => Ok(Value::Absolute(u64::from_be_bytes(bytes))),
+                _ => Err(Error::Malformed("unknown data source type")),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Encodes collectd packets, like the network plugin of collectd.
+    pub struct Encoder(pub Vec<u8>);
+
+    impl Encoder {
+        fn part(&mut self, part_type: u16, payload: &[u8]) -> &mut Self {
+            self.0.extend((part_type).to_be_bytes());
+            self.0.extend(((payload.len() + PART_HEADER_LEN) as u16).to_be_bytes());
+            self.0.extend(payload);
+            self
+        }
+
+        fn string(&mut self, part_type: u16, s: &str) -> &mut Self {
+            let mut payload = s.as_bytes().to_vec();
+            payload.push(0);
+            self.part(part_type, &payload)
+        }
+
+        /// Adds the parts of a value list.
+        pub fn value_list(
+            &mut self,
+            host: &str,
+            time_hr: u64,
+            plugin: &str,
+            type_: &str,
+            values: &[Value],
+        ) -> &mut Self {
+            self.string(TYPE_HOST, host)
+                .part(TYPE_TIME_HR, &time_hr.to_be_bytes())
+                .string(TYPE_PLUGIN, plugin)
+                .string(TYPE_TYPE, type_);
+            let mut payload = (values.len() as u16).to_be_bytes().to_vec();
+            for v in values {
+                payload.push(match v {
+                    Value::Counter(_) => DS_COUNTER,
+                    Value::Gauge(_) => DS_GAUGE,
+                    Value::Derive(_) => DS_DERIVE,
+                    Value::Absolute(_) => DS_ABSOLUTE,
+                });
+            }
+            for v in values {
+                payload.extend(match *v {
+                    Value::Counter(c) | Value::Absolute(c) => c.to_be_bytes(),
+                    Value::Gauge(g) => g.to_le_bytes(),
+                    Value::Derive(d) => d.to_be_bytes(),
+                });
+            }
+            self.part(TYPE_VALUES, &payload)
+        }
+
+        /// Returns the packet, signed.
+        pub fn signed(&self, username: &str, password: &str) -> Vec<u8> {
+            let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(password.as_bytes()).unwrap();
+            mac.update(username.as_bytes());
+            mac.update(&self.0);
+            let mut payload = mac.finalize().into_bytes().to_vec();
+            payload.extend(username.as_bytes());
+            let mut signed = Encoder(Vec::new());
+            signed.part(TYPE_SIGN_SHA256, &payload);
+            signed.0.extend(&self.0);
+            signed.0
+        }
+
+        /// Returns the packet, encrypted.
+        pub fn encrypted(&self, username: &str, password: &str) -> Vec<u8> {
+            let iv = [7; IV_LEN];
+            let mut data = Sha1::digest(&self.0).to_vec();
+            data.extend(&self.0);
+            aes256_ofb(&Sha256::digest(password.as_bytes()).into(), &iv, &mut data);
+            let mut payload = (username.len() as u16).to_be_bytes().to_vec();
+            payload.extend(username.as_bytes());
+            payload.extend(iv);
+            payload.extend(data);
+            let mut encrypted = Encoder(Vec::new());
+            encrypted.part(TYPE_ENCR_AES256, &payload);
+            encrypted.0
+        }
+    }
+
+    fn packet() -> Encoder {
+        let mut packet = Encoder(Vec::new());
+        packet
+            .value_list(
+                "node-1",
+                1_700_000_000 << 30,
+                "load",
+                "load",
+                &[Value::Gauge(0.5), Value::Gauge(0.25), Value::Gauge(0.125)],
+            )
+            .string(TYPE_PLUGIN_INSTANCE, "eth0")
+            .value_list(
+                "node-1",
+                (1_700_000_000 << 30) + (1 << 29),
+                "interface",
+                "if_octets",
+                &[Value::Derive(1000), Value::Derive(2000)],
+            );
+        packet
+    }
+
+    fn decoder(security_level: SecurityLevel) -> Decoder {
+        Decoder {
+            security_level,
+            users: BTreeMap::from([(String::from("alice"), String::from("secret"))]),
+        }
+    }
+
+    #[test]
+    fn plain() {
+        let value_lists = decoder(SecurityLevel::None).decode(&packet().0).unwrap();
+        assert_eq!(
+            value_lists,
+            vec![
+                ValueList {
+                    host: String::from("node-1"),
+                    time: Some(Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_700_000_000))),
+                    plugin: String::from("load"),
+                    plugin_instance: String::new(),
+                    type_: String::from("load"),
+                    type_instance: String::new(),
+                    values: vec![Value::Gauge(0.5), Value::Gauge(0.25), Value::Gauge(0.125)],
+                },
+                ValueList {
+                    host: String::from("node-1"),
+                    time: Some(Timestamp::from(UNIX_EPOCH + Duration::from_millis(1_700_000_000_500))),
+                    plugin: String::from("interface"),
+                    plugin_instance: String::from("eth0"),
+                    type_: String::from("if_octets"),
+                    type_instance: String::new(),
+                    values: vec![Value::Derive(1000), Value::Derive(2000)],
+                },
+            ]
+        );
+        // unsigned values are rejected if a signature is required
+        assert_eq!(decoder(SecurityLevel::Sign).decode(&packet().0).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn signed() {
+        let expected = decoder(SecurityLevel::None).decode(&packet().0).unwrap();
+        let signed = packet().signed("alice", "secret");
+        assert_eq!(decoder(SecurityLevel::Sign).decode(&signed).unwrap(), expected);
+        assert_eq!(decoder(SecurityLevel::Encrypt).decode(&signed).unwrap(), vec![]);
+
+        let forged = packet().signed("alice", "wrong");
+        assert_eq!(
+            decoder(SecurityLevel::None).decode(&forged),
+            Err(Error::InvalidSignature(String::from("alice")))
+        );
+        // the signatures of the unknown users are only required at the sign level
+        let unknown = packet().signed("bob", "secret");
+        assert_eq!(decoder(SecurityLevel::None).decode(&unknown).unwrap(), expected);
+        assert_eq!(
+            decoder(SecurityLevel::Sign).decode(&unknown),
+            Err(Error::UnknownUser(String::from("bob")))
+        );
+    }
+
+    #[test]
+    fn encrypted() {
+        let expected = decoder(SecurityLevel::None).decode(&packet().0).unwrap();
+        let encrypted = packet().encrypted("alice", "secret");
+        assert_eq!(decoder(SecurityLevel::Encrypt).decode(&encrypted).unwrap(), expected);
+
+        let wrong_password = packet().encrypted("alice", "wrong");
+        assert_eq!(
+            decoder(SecurityLevel::None).decode(&wrong_password),
+            Err(Error::DecryptionFailed(String::from("alice")))
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let mut packet = packet().0;
+        packet.truncate(packet.len() - 3);
+        assert_eq!(
+            decoder(SecurityLevel::None).decode(&packet),
+            Err(Error::Malformed("invalid part length"))
+        );
+        assert_eq!(
+            decoder(SecurityLevel::None).decode(&[0, 0, 0, 5, b'a']),
+            Err(Error::Malformed("string is not null-terminated"))
+        );
+        let mut packet = Encoder(Vec::new());
+        packet.part(TYPE_TIME, &u64::MAX.to_be_bytes());
+        assert_eq!(
+            decoder(SecurityLevel::None).decode(&packet.0),
+            Err(Error::Malformed("time out of range"))
+        );
+    }
+
+    #[test]
+    fn ofb() {
+        // F.4.5 of NIST SP 800-38A
+        let key: [u8; 32] = hex("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4")
+            .try_into()
+            .unwrap();
+        let iv: [u8; 16] = hex("000102030405060708090a0b0c0d0e0f").try_into().unwrap();
+        let mut data = hex("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+        aes256_ofb(&key, &iv, &mut data);
+        assert_eq!(
+            data,
+            hex("dc7e84bfda79164b7ecd8486985d38604febdc6740d20b3ac88f6ad82a4fb08d")
+        );
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,225 @@
+use std::{collections::HashMap, time::Duration};
+
+use alumet::{
+    measurement::{
+        AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue,
+    },
+    metrics::{Metric, RawMetricId, duplicate::DuplicateReaction, online::MetricSender},
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+use anyhow::anyhow;
+use tokio::{net::UdpSocket, sync::mpsc};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    protocol::{Decoder, Value, ValueList},
+    types_db::TypesDb,
+};
+
+/// Maximum size of a UDP datagram.
+const MAX_PACKET_SIZE: usize = 65535;
+
+/// Receives the packets of collectd and sends their measurements to the pipeline at regular intervals.
+pub struct CollectdSource {
+    socket: UdpSocket,
+    flush_interval: Duration,
+    decoder: Decoder,
+    types_db: TypesDb,
+    metrics_tx: MetricSender,
+    out_tx: mpsc::Sender<MeasurementBuffer>,
+    /// Id of each metric, or `None` if it could not be registered.
+    metric_ids: HashMap<String, Option<RawMetricId>>,
+    /// Previous value of the counters, by series.
+    counters: HashMap<String, Value>,
+    buffer: MeasurementBuffer,
+}
+
+impl CollectdSource {
+    pub fn new(
+        socket: UdpSocket,
+        flush_interval: Duration,
+        decoder: Decoder,
+        types_db: TypesDb,
+        metrics_tx: MetricSender,
+        out_tx: mpsc::Sender<MeasurementBuffer>,
+    ) -> Self {
+        Self {
+            socket,
+            flush_interval,
+            decoder,
+            types_db,
+            metrics_tx,
+            out_tx,
+            metric_ids: HashMap::new(),
+            counters: HashMap::new(),
+            buffer: MeasurementBuffer::new(),
+        }
+    }
+
+    pub async fn run(mut self, cancel_token: CancellationToken) -> anyhow::Result<()> {
+        let mut packet = vec![0; MAX_PACKET_SIZE];
+        let mut flush = tokio::time::interval(self.flush_interval);
+        loop {
+            tokio::select! {
+                biased;
+                _ = cancel_token.cancelled() => {
+                    break;
+                }
+                _ = flush.tick() => {
+                    self.flush().await?;
+                }
+                received = self.socket.recv_from(&mut packet) => {
+                    match received {
+                        Ok((n, sender)) => match self.decoder.decode(&packet[..n]) {
+                            Ok(value_lists) => {
+                                for vl in value_lists {
+                                    self.push(vl).await?;
+                                }
+                            }
+                            Err(e) => log::warn!("Dropping a collectd packet from {sender}: {e}"),
+                        },
+                        Err(e) => log::error!("Failed to receive a collectd packet: {e}"),
+                    }
+                }
+            }
+        }
+        self.flush().await
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.out_tx.send(buffer).await?;
+        }
+        Ok(())
+    }
+
+    async fn push(&mut self, vl: ValueList) -> anyhow::Result<()> {
+        let name = format!("collectd_{}_{}", vl.plugin, vl.type_);
+        let Some(metric) = self.metric_id(&name).await? else {
+            return Ok(());
+        };
+        let timestamp = vl.time.unwrap_or_else(Timestamp::now);
+
+        let mut attributes = vec![(String::from("host"), AttributeValue::String(vl.host.clone()))];
+        if !vl.plugin_instance.is_empty() {
+            attributes.push((
+                String::from("plugin_instance"),
+                AttributeValue::String(vl.plugin_instance.clone()),
+            ));
+        }
+        if !vl.type_instance.is_empty() {
+            attributes.push((
+                String::from("type_instance"),
+                AttributeValue::String(vl.type_instance.clone()),
+            ));
+        }
+
+        for (i, value) in vl.values.iter().enumerate() {
+            let mut attributes = attributes.clone();
+            if vl.values.len() > 1 {
+                let ds = self.types_db.source_name(&vl.type_, i);
+                attributes.push((String::from("ds"), AttributeValue::String(ds)));
+            }
+            let value = match *value {
+                Value::Gauge(v) if v.is_nan() => continue,
+                Value::Gauge(v) => v,
+                Value::Absolute(v) => v as f64,
+                Value::Counter(_) | Value::Derive(_) => {
+                    let key = format!("{name}\0{attributes:?}");
+                    match delta(self.counters.insert(key, *value), *value) {
+                        Some(delta) => delta,
+                        None => continue,
+                    }
+                }
+            };
+            let point = MeasurementPoint::new_untyped(
+                timestamp,
+                metric,
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                WrappedMeasurementValue::F64(value),
+            )
+            .with_attr_vec(attributes);
+            self.buffer.push(point);
+        }
+        Ok(())
+    }
+
+    /// Returns the id of a metric, registered on its first use.
+    async fn metric_id(&mut self, name: &str) -> anyhow::Result<Option<RawMetricId>> {
+        if let Some(id) = self.metric_ids.get(name) {
+            return Ok(*id);
+        }
+        let metric = Metric {
+            name: name.to_owned(),
+            description: String::from("metric received from collectd"),
+            value_type: WrappedMeasurementType::F64,
+            unit: Unit::Unity.into(),
+            tags: Vec::new(),
+        };
+        let result = self
+            .metrics_tx
+            .create_metrics(vec![metric], DuplicateReaction::Error)
+            .await
+            .map_err(|e| anyhow!("create_metrics returned an error: {e:?}"))?
+            .pop()
+            .expect("there should be one result per metric");
+        let id = match result {
+            Ok(id) => Some(id),
+            Err(e) => {
+                log::error!("The collectd metric {name} cannot be registered, its values will be ignored: {e}");
+                None
+            }
+        };
+        self.metric_ids.insert(name.to_owned(), id);
+        Ok(id)
+    }
+}
+
+/// Returns the increase of a counter since its previous value.
+///
+/// Returns `None` for the first value, and when a derive has decreased (it has been reset).
+/// Like collectd, a counter that decreases is assumed to have wrapped around.
+fn delta(previous: Option<Value>, value: Value) -> Option<f64> {
+    match (previous?, value) {
+        (Value::Counter(previous), Value::Counter(value)) => {
+            let delta = if value >= previous {
+                value - previous
+            } else if previous <= u32::MAX as u64 {
+                u32::MAX as u64 - previous + value + 1
+            } else {
+                value.wrapping_sub(previous)
+            };
+            Some(delta as f64)
+        }
+        (Value::Derive(previous), Value::Derive(value)) if value >= previous => {
+            Some((value as f64) - (previous as f64))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_delta() {
+        assert_eq!(delta(None, Value::Counter(10)), None);
+        assert_eq!(delta(Some(Value::Counter(10)), Value::Counter(15)), Some(5.0));
+        // 32-bit wrap around
+        assert_eq!(
+            delta(Some(Value::Counter(u32::MAX as u64 - 1)), Value::Counter(3)),
+            Some(5.0)
+        );
+        // 64-bit wrap around
+        assert_eq!(delta(Some(Value::Counter(u64::MAX - 1)), Value::Counter(3)), Some(5.0));
+
+        assert_eq!(delta(Some(Value::Derive(-5)), Value::Derive(5)), Some(10.0));
+        assert_eq!(delta(Some(Value::Derive(5)), Value::Derive(1)), None);
+        // the type of the data source has changed
+        assert_eq!(delta(Some(Value::Derive(5)), Value::Counter(6)), None);
+    }
+}
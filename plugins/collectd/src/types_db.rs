@@ -0,0 +1,73 @@
+//! Names of the data sources of the collectd types, from `types.db`.
+//!
+//! Each line of `types.db` defines a type and its data sources, like:
+//! `if_octets rx:DERIVE:0:U, tx:DERIVE:0:U`.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::Context;
+
+/// Names of the data sources of each type.
+#[derive(Debug, Default, PartialEq)]
+pub struct TypesDb(HashMap<String, Vec<String>>);
+
+impl TypesDb {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut types = HashMap::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, sources)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let sources = sources
+                .split(',')
+                .filter_map(|ds| ds.trim().split(':').next())
+                .filter(|ds| !ds.is_empty())
+                .map(str::to_owned)
+                .collect();
+            types.insert(name.to_owned(), sources);
+        }
+        Self(types)
+    }
+
+    pub fn extend(&mut self, other: TypesDb) {
+        self.0.extend(other.0);
+    }
+
+    /// Returns the name of a data source, or its index if the type is unknown.
+    pub fn source_name(&self, type_: &str, index: usize) -> String {
+        self.0
+            .get(type_)
+            .and_then(|sources| sources.get(index))
+            .cloned()
+            .unwrap_or_else(|| index.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let db = TypesDb::parse(
+            "# comment\n\
+             load\t\t\tshortterm:GAUGE:0:5000, midterm:GAUGE:0:5000, longterm:GAUGE:0:5000\n\
+             \n\
+             if_octets               rx:DERIVE:0:U, tx:DERIVE:0:U\n",
+        );
+        assert_eq!(db.source_name("load", 1), "midterm");
+        assert_eq!(db.source_name("if_octets", 0), "rx");
+        assert_eq!(db.source_name("if_octets", 2), "2");
+        assert_eq!(db.source_name("unknown", 0), "0");
+    }
+}
@@ -0,0 +1,53 @@
+use std::hint::black_box;
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        bench::{Cardinality, CountingAllocator, SyntheticMeasurements, report_allocations},
+    },
+};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use plugin_csv::{Config, CsvPlugin};
+
+#[global_allocator]
+static ALLOC: CountingAllocator = CountingAllocator;
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+fn bench_write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("csv_write");
+    for attributes in [0, 8] {
+        // Write to /dev/null: we measure the formatting, not the disk, and the file would grow a lot.
+        let config = Config {
+            output_path: "/dev/null".into(),
+            force_flush: false,
+            ..Config::default()
+        };
+        let mut harness = PluginHarness::<CsvPlugin>::start(serialize_config(config).unwrap()).unwrap();
+        let cardinality = Cardinality {
+            attributes,
+            ..Cardinality::default()
+        };
+        let synthetic = SyntheticMeasurements::register(&harness, 4, cardinality).unwrap();
+        let mut output = harness.output("out").unwrap();
+
+        for size in SIZES {
+            let buf = synthetic.generate(size);
+            let label = format!("{attributes}_attributes");
+            report_allocations(&format!("csv_write/{label}/{size}"), 100, || {
+                output.write(&buf).unwrap()
+            });
+            group.throughput(Throughput::Elements(size as u64));
+            group.bench_with_input(BenchmarkId::new(label, size), &buf, |b, buf| {
+                b.iter(|| output.write(black_box(buf)).unwrap())
+            });
+        }
+        output.finish().unwrap();
+        harness.stop().unwrap();
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_write);
+criterion_main!(benches);
@@ -0,0 +1,135 @@
+//! Choice of the columns of the CSV file, and format of their values.
+
+use std::{collections::HashSet, time::SystemTime};
+
+use alumet::{
+    json,
+    measurement::{MeasurementPoint, Timestamp},
+};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// A column of the CSV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Metric,
+    Timestamp,
+    Value,
+    /// The unit of the metric, as configured by `use_unit_display_name`.
+    Unit,
+    ResourceKind,
+    ResourceId,
+    ConsumerKind,
+    ConsumerId,
+    /// The attributes of the measurements, formatted according to [`AttributesFormat`].
+    Attributes,
+}
+
+/// The columns of the CSV file, in the order of the original format.
+pub fn default_columns() -> Vec<Column> {
+    vec![
+        Column::Metric,
+        Column::Timestamp,
+        Column::Value,
+        Column::ResourceKind,
+        Column::ResourceId,
+        Column::ConsumerKind,
+        Column::ConsumerId,
+        Column::Attributes,
+    ]
+}
+
+/// How the attributes are flattened.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributesFormat {
+    /// One column per attribute key, plus a column `__late_attributes` for the keys that
+    /// appear after the header has been written.
+    #[default]
+    Columns,
+    /// A single column `attributes` that contains a JSON object.
+    Json,
+}
+
+/// Format of the `timestamp` column.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampFormat {
+    /// RFC 3339 in UTC, e.g. `2025-01-01T12:00:00.5Z`.
+    #[default]
+    Rfc3339,
+    /// Seconds since the Unix epoch, with a fractional part if needed, e.g. `1735732800.5`.
+    UnixSeconds,
+    /// Milliseconds since the Unix epoch.
+    UnixMillis,
+    /// Nanoseconds since the Unix epoch.
+    UnixNanos,
+}
+
+impl TimestampFormat {
+    pub fn format(&self, t: Timestamp) -> anyhow::Result<String> {
+        let (secs, nanos) = t.to_unix_timestamp();
+        let res = match self {
+            TimestampFormat::Rfc3339 => OffsetDateTime::from(SystemTime::from(t)).format(&Rfc3339)?,
+            TimestampFormat::UnixSeconds if nanos == 0 => secs.to_string(),
+            TimestampFormat::UnixSeconds => {
+                let fraction = format!("{nanos:09}");
+                format!("{secs}.{}", fraction.trim_end_matches('0'))
+            }
+            TimestampFormat::UnixMillis => (u128::from(secs) * 1_000 + u128::from(nanos / 1_000_000)).to_string(),
+            TimestampFormat::UnixNanos => (u128::from(secs) * 1_000_000_000 + u128::from(nanos)).to_string(),
+        };
+        Ok(res)
+    }
+}
+
+/// Checks that the list of columns is usable.
+pub fn check_columns(columns: &[Column]) -> anyhow::Result<()> {
+    if columns.is_empty() {
+        return Err(anyhow!("at least one column is required"));
+    }
+    let mut seen = HashSet::new();
+    for c in columns {
+        if !seen.insert(c) {
+            return Err(anyhow!("column {c:?} is present more than once"));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the attributes of a measurement as a JSON object, sorted by key.
+pub fn attributes_json(m: &MeasurementPoint) -> String {
+    serde_json::to_string(&json::attributes(m)).expect("the attributes should always be serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::measurement::Timestamp;
+    use pretty_assertions::assert_eq;
+
+    use super::{Column, TimestampFormat, check_columns};
+
+    #[test]
+    fn timestamps() {
+        let t = Timestamp::from(UNIX_EPOCH + Duration::new(1_700_000_000, 500_000_000));
+        let format = |f: TimestampFormat| f.format(t).unwrap();
+        assert_eq!(format(TimestampFormat::Rfc3339), "2023-11-14T22:13:20.5Z");
+        assert_eq!(format(TimestampFormat::UnixSeconds), "1700000000.5");
+        assert_eq!(format(TimestampFormat::UnixMillis), "1700000000500");
+        assert_eq!(format(TimestampFormat::UnixNanos), "1700000000500000000");
+
+        let t = Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        assert_eq!(TimestampFormat::UnixSeconds.format(t).unwrap(), "1700000000");
+    }
+
+    #[test]
+    fn columns() {
+        assert!(check_columns(&[Column::Metric, Column::Value]).is_ok());
+        assert!(check_columns(&[]).is_err());
+        assert!(check_columns(&[Column::Value, Column::Metric, Column::Value]).is_err());
+    }
+}
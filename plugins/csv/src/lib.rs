@@ -1,4 +1,5 @@
 mod csv;
+mod layout;
 mod output;
 // TODO mod input
 
@@ -6,9 +7,15 @@ use std::path::PathBuf;
 
 use alumet::plugin::{
     ConfigTable,
+    capability::Capability,
     rust::{AlumetPlugin, deserialize_config, serialize_config},
 };
-use output::CsvOutput;
+pub use alumet_output_files::{
+    encryption::EncryptionConfig,
+    rotation::{Compression, RotationConfig},
+};
+pub use layout::{AttributesFormat, Column, TimestampFormat};
+use output::{CsvOutput, Layout};
 use serde::{Deserialize, Serialize};
 
 pub struct CsvPlugin {
@@ -24,23 +31,87 @@ impl AlumetPlugin for CsvPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(config.output_path)]))
+    }
+
+    fn config_comments() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("output_path", "Absolute or relative path to the output file."),
+            ("force_flush", "Flush the file after each write."),
+            (
+                "append_unit_to_metric_name",
+                "Append the unit to the metric name, ex. `rapl_consumed_energy_J`.",
+            ),
+            (
+                "use_unit_display_name",
+                "Use the display name of the units (`°C`) instead of their unique name (`Cel`).",
+            ),
+            ("csv_delimiter", "Delimiter of the columns."),
+            (
+                "columns",
+                "Columns of the file, in order. Available columns: metric, timestamp, value, unit,\n\
+                 resource_kind, resource_id, consumer_kind, consumer_id and attributes.",
+            ),
+            (
+                "attributes_format",
+                "How the attributes are written: `columns` (one column per key) or `json`.",
+            ),
+            (
+                "timestamp_format",
+                "Format of the timestamps: rfc3339, unix_seconds, unix_millis or unix_nanos.",
+            ),
+            (
+                "rotation",
+                "Rotation of the output file, disabled by default.\n\
+                 Set `max_size` (in bytes) or `max_age` (ex. \"1h\") to enable it, and `max_files` or\n\
+                 `retention` (ex. \"7d\") to delete the old files.",
+            ),
+            (
+                "rotation.compression",
+                "Compression of the rotated files: none, gzip or zstd.",
+            ),
+            ("encryption", "Encryption of the output files with age."),
+            (
+                "encryption.recipients",
+                "Public keys of the recipients, ex. `age1...`. No encryption if empty.",
+            ),
+        ]
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
 
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config: Config = deserialize_config(config)?;
+        layout::check_columns(&config.columns)?;
+        config.encryption.parse_recipients()?;
+        if config.encryption.is_enabled() && config.rotation.compression != Compression::None {
+            return Err(anyhow::anyhow!(
+                "encrypted files cannot be compressed, remove the compression of the rotated files"
+            ));
+        }
         Ok(Box::new(CsvPlugin { config }))
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let layout = Layout {
+            columns: self.config.columns.clone(),
+            attributes_format: self.config.attributes_format,
+            timestamp_format: self.config.timestamp_format,
+            append_unit_to_metric_name: self.config.append_unit_to_metric_name,
+            use_unit_display_name: self.config.use_unit_display_name,
+        };
         let output = Box::new(CsvOutput::new(
             &self.config.output_path,
             self.config.force_flush,
-            self.config.append_unit_to_metric_name,
-            self.config.use_unit_display_name,
             self.config.csv_delimiter,
             self.config.csv_escaped_quote.take().unwrap_or(String::from("\"\"")),
+            layout,
+            self.config.rotation.clone(),
+            self.config.encryption.parse_recipients()?,
         )?);
         alumet.add_blocking_output("out", output)?;
         Ok(())
@@ -65,6 +136,21 @@ pub struct Config {
     /// The CSV delimiter, such as `;`
     pub csv_delimiter: char,
     pub csv_escaped_quote: Option<String>,
+    /// The columns of the file, in order.
+    #[serde(default = "layout::default_columns")]
+    pub columns: Vec<Column>,
+    /// How the attributes are written: one column per key, or a JSON object.
+    #[serde(default)]
+    pub attributes_format: AttributesFormat,
+    /// Format of the timestamps.
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+    /// Rotation of the output file, disabled by default.
+    #[serde(default)]
+    pub rotation: RotationConfig,
+    /// Encryption of the output files, disabled by default.
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
 }
 
 impl Default for Config {
@@ -76,6 +162,11 @@ impl Default for Config {
             append_unit_to_metric_name: true,
             csv_delimiter: ';',
             csv_escaped_quote: None,
+            columns: layout::default_columns(),
+            attributes_format: AttributesFormat::default(),
+            timestamp_format: TimestampFormat::default(),
+            rotation: RotationConfig::default(),
+            encryption: EncryptionConfig::default(),
         }
     }
 }
@@ -1,61 +1,142 @@
 use std::{
     collections::HashSet,
-    fs::File,
+    fmt,
     io::{self, BufWriter, Write},
-    path::Path,
+    path::{Path, PathBuf},
     time::SystemTime,
 };
 
-use alumet::measurement::WrappedMeasurementValue;
 use alumet::{
-    measurement::MeasurementBuffer,
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
+use alumet_output_files::{
+    encryption::{FileWriter, Recipient},
+    rotation::{RotationConfig, Rotator},
+};
 use anyhow::Context;
-use time::OffsetDateTime;
-use time::format_description::well_known::Rfc3339;
 
-use crate::csv::CsvHelper;
+use crate::{
+    csv::CsvHelper,
+    layout::{AttributesFormat, Column, TimestampFormat, attributes_json},
+};
 
 pub struct CsvOutput {
-    /// The attributes that we have written to the header.
+    /// The attributes that we have written to the header, sorted by key.
     /// None if the header has not been written yet.
-    attributes_in_header: Option<HashSet<String>>,
+    attributes_in_header: Option<Vec<String>>,
 
     /// parameter: do we flush after each write(measurements)?
     force_flush: bool,
 
-    /// parameter: do we append the unit to the metric name?
-    append_unit_to_metric_name: bool,
-    use_unit_display_name: bool,
+    /// parameter: columns and format of the values
+    layout: Layout,
 
     /// File writer
-    writer: BufWriter<File>,
+    writer: BufWriter<FileWriter>,
+    output_path: PathBuf,
+    /// Recipients of the encrypted files, empty if the files are not encrypted.
+    recipients: Vec<Recipient>,
+    opened_at: SystemTime,
+    rotator: Rotator,
 
     /// CSV utility
     csv_helper: CsvHelper,
 }
 
+/// Columns of the CSV file and format of their values.
+pub struct Layout {
+    pub columns: Vec<Column>,
+    pub attributes_format: AttributesFormat,
+    pub timestamp_format: TimestampFormat,
+    /// Do we append the unit to the metric name?
+    pub append_unit_to_metric_name: bool,
+    pub use_unit_display_name: bool,
+}
+
 impl CsvOutput {
     pub fn new(
         output_file: impl AsRef<Path>,
         force_flush: bool,
-        append_unit_to_metric_name: bool,
-        use_unit_display_name: bool,
         delimiter: char,
         escaped_quote: String,
+        layout: Layout,
+        rotation: RotationConfig,
+        recipients: Vec<Recipient>,
     ) -> io::Result<Self> {
-        let writer = BufWriter::new(File::create(output_file)?);
+        let output_path = output_file.as_ref().to_owned();
+        let writer = BufWriter::new(FileWriter::create(&output_path, &recipients)?);
         let helper = CsvHelper::new(delimiter, escaped_quote);
         Ok(Self {
             attributes_in_header: None,
             force_flush,
-            append_unit_to_metric_name,
-            use_unit_display_name,
+            layout,
             writer,
+            rotator: Rotator::new(rotation, output_path.clone()),
+            output_path,
+            recipients,
+            opened_at: SystemTime::now(),
             csv_helper: helper,
         })
     }
+
+    /// Moves the current file aside and starts a new file, with a new header.
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        // The file must be complete before it is compressed.
+        self.writer.get_mut().finish()?;
+        // On Unix, the open file can be renamed. It is closed when the writer is replaced.
+        self.rotator.rotate()?;
+        self.writer = BufWriter::new(
+            FileWriter::create(&self.output_path, &self.recipients)
+                .with_context(|| format!("failed to create {:?}", self.output_path))?,
+        );
+        self.opened_at = SystemTime::now();
+        self.attributes_in_header = None;
+        Ok(())
+    }
+
+    /// Returns `true` if the attributes are written in one column per key.
+    fn attributes_in_columns(&self) -> bool {
+        self.layout.attributes_format == AttributesFormat::Columns && self.layout.columns.contains(&Column::Attributes)
+    }
+
+    fn write_header(&mut self, measurements: &MeasurementBuffer) -> io::Result<()> {
+        // Collect the attributes that are present in the measurements.
+        // Then, sort the keys to ensure a consistent order between calls to `CsvOutput::write`.
+        let mut attr_keys: Vec<String> = if self.attributes_in_columns() {
+            collect_attribute_keys(measurements).into_iter().collect()
+        } else {
+            Vec::new()
+        };
+        attr_keys.sort();
+
+        // Build the CSV header
+        let mut header = Vec::with_capacity(8 + attr_keys.len());
+        for column in &self.layout.columns {
+            match column {
+                Column::Metric => header.push("metric"),
+                Column::Timestamp => header.push("timestamp"),
+                Column::Value => header.push("value"),
+                Column::Unit => header.push("unit"),
+                Column::ResourceKind => header.push("resource_kind"),
+                Column::ResourceId => header.push("resource_id"),
+                Column::ConsumerKind => header.push("consumer_kind"),
+                Column::ConsumerId => header.push("consumer_id"),
+                Column::Attributes => match self.layout.attributes_format {
+                    AttributesFormat::Columns => {
+                        header.extend(attr_keys.iter().map(|k| k.as_str()));
+                        header.push("__late_attributes");
+                    }
+                    AttributesFormat::Json => header.push("attributes"),
+                },
+            }
+        }
+        self.csv_helper.writeln(&mut self.writer, header)?;
+
+        self.attributes_in_header = Some(attr_keys);
+        Ok(())
+    }
 }
 
 fn collect_attribute_keys(buf: &MeasurementBuffer) -> HashSet<String> {
@@ -66,113 +147,91 @@ fn collect_attribute_keys(buf: &MeasurementBuffer) -> HashSet<String> {
     res
 }
 
+/// Pushes the attributes of `m` in the columns of the header, and the other attributes in `__late_attributes`.
+fn push_attribute_columns(record: &mut Vec<String>, m: &MeasurementPoint, header_attrs: &[String]) -> fmt::Result {
+    // Write the known attributes in the same order as the header, leaving the missing ones empty.
+    for key in header_attrs {
+        let value = m.attributes().find(|(k, _)| k == key).map(|(_, v)| v.to_string());
+        record.push(value.unwrap_or_default());
+    }
+
+    // Add the unknown attributes to the column `__late_attributes`, sorted by key.
+    let mut late_attrs_sorted = m
+        .attributes()
+        .filter(|(k, _)| header_attrs.binary_search_by(|h| h.as_str().cmp(k)).is_err())
+        .collect::<Vec<_>>();
+    late_attrs_sorted.sort_by_key(|(k, _)| *k);
+    let mut late_attrs: String = String::new();
+    for (key, value) in late_attrs_sorted {
+        use std::fmt::Write;
+
+        if !late_attrs.is_empty() {
+            late_attrs.push_str(", ");
+        }
+        write!(
+            late_attrs,
+            "{}={}",
+            escape_late_attribute(key),
+            escape_late_attribute(&value.to_string())
+        )?;
+    }
+
+    // Push the late attributes as one value
+    record.push(late_attrs);
+    Ok(())
+}
+
 impl alumet::pipeline::Output for CsvOutput {
     fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
         if self.attributes_in_header.is_none() && !measurements.is_empty() {
-            // Collect the attributes that are present in the measurements.
-            // Then, sort the keys to ensure a consistent order between calls to `CsvOutput::write`.
-            let attr_keys = collect_attribute_keys(measurements);
-            let mut attr_keys_sorted: Vec<&str> = attr_keys.iter().map(|s| s.as_str()).collect::<Vec<_>>();
-            attr_keys_sorted.sort();
-
-            // Build the CSV header
-            let mut header = Vec::with_capacity(8 + attr_keys_sorted.len());
-            header.extend(&[
-                "metric",
-                "timestamp",
-                "value",
-                "resource_kind",
-                "resource_id",
-                "consumer_kind",
-                "consumer_id",
-            ]);
-            header.extend(attr_keys_sorted);
-            header.push("__late_attributes");
-
-            self.csv_helper.writeln(&mut self.writer, header)?;
-
-            self.attributes_in_header = Some(attr_keys);
+            self.write_header(measurements)?;
         }
 
+        let layout = &self.layout;
         for m in measurements.iter() {
             // get the full definition of the metric
             let full_metric = ctx
                 .metrics
                 .by_id(&m.metric)
                 .with_context(|| format!("Unknown metric {:?}", m.metric))?;
-
-            // extract the metric name, appending its unit if configured so
-            let metric_name = if self.append_unit_to_metric_name {
-                let unit_string = if self.use_unit_display_name {
-                    full_metric.unit.display_name()
-                } else {
-                    full_metric.unit.unique_name()
-                };
-                if unit_string.is_empty() {
-                    full_metric.name.to_owned()
-                } else {
-                    format!("{}_{}", full_metric.name, unit_string)
-                }
+            let unit_string = if layout.use_unit_display_name {
+                full_metric.unit.display_name()
             } else {
-                full_metric.name.clone()
+                full_metric.unit.unique_name()
             };
 
-            // convert every field to string
-            let datetime: OffsetDateTime = SystemTime::from(m.timestamp).into();
-            let datetime: String = datetime.format(&Rfc3339)?;
-            let value = match m.value {
-                WrappedMeasurementValue::F64(x) => x.to_string(),
-                WrappedMeasurementValue::U64(x) => x.to_string(),
-            };
-            let resource_kind = m.resource.kind().to_owned();
-            let resource_id = m.resource.id_display().to_string();
-            let consumer_kind = m.consumer.kind().to_owned();
-            let consumer_id = m.consumer.id_display().to_string();
-
-            // Start to build the record
-            let mut record = vec![
-                metric_name,
-                datetime,
-                value,
-                resource_kind,
-                resource_id,
-                consumer_kind,
-                consumer_id,
-            ];
-
-            // Sort the attributes by key
-            let mut attr_sorted = m.attributes().collect::<Vec<_>>();
-            attr_sorted.sort_by_key(|(k, _)| *k);
-
-            // Handle known as well as new attributes.
-            let mut late_attrs: String = String::new();
-            let mut known_attrs = 0;
-            for (key, value) in attr_sorted {
-                if self.attributes_in_header.as_ref().unwrap().contains(key) {
-                    // known attribute, write in the same order as the header (thanks to the sort)
-                    record.push(value.to_string());
-                    known_attrs += 1;
-                } else {
-                    // unknown attribute, add to the column `__late_attributes`
-                    use std::fmt::Write;
-
-                    if !late_attrs.is_empty() {
-                        late_attrs.push_str(", ");
+            // convert every field to string, in the order of the columns
+            let mut record = Vec::with_capacity(layout.columns.len());
+            for column in &layout.columns {
+                match column {
+                    Column::Metric => {
+                        // extract the metric name, appending its unit if configured so
+                        let metric_name = if layout.append_unit_to_metric_name && !unit_string.is_empty() {
+                            format!("{}_{}", full_metric.name, unit_string)
+                        } else {
+                            full_metric.name.clone()
+                        };
+                        record.push(metric_name);
                     }
-                    write!(
-                        late_attrs,
-                        "{}={}",
-                        escape_late_attribute(key),
-                        escape_late_attribute(&value.to_string())
-                    )?;
+                    Column::Timestamp => record.push(layout.timestamp_format.format(m.timestamp)?),
+                    Column::Value => record.push(match m.value {
+                        WrappedMeasurementValue::F64(x) => x.to_string(),
+                        WrappedMeasurementValue::U64(x) => x.to_string(),
+                    }),
+                    Column::Unit => record.push(unit_string.clone()),
+                    Column::ResourceKind => record.push(m.resource.kind().to_owned()),
+                    Column::ResourceId => record.push(m.resource.id_display().to_string()),
+                    Column::ConsumerKind => record.push(m.consumer.kind().to_owned()),
+                    Column::ConsumerId => record.push(m.consumer.id_display().to_string()),
+                    Column::Attributes => match layout.attributes_format {
+                        AttributesFormat::Columns => {
+                            let header_attrs = self.attributes_in_header.as_ref().unwrap();
+                            push_attribute_columns(&mut record, m, header_attrs)?;
+                        }
+                        AttributesFormat::Json => record.push(attributes_json(m)),
+                    },
                 }
             }
-            // Add missing attributes as empty values
-            let missing_attributes = self.attributes_in_header.as_ref().unwrap().len() - known_attrs;
-            record.extend(vec![String::from(""); missing_attributes]);
-
-            // Push the late attributes as one value
-            record.push(late_attrs);
 
             // Write the record
             self.csv_helper.writeln(&mut self.writer, record)?;
@@ -181,6 +240,12 @@ impl alumet::pipeline::Output for CsvOutput {
             log::trace!("flushing BufWriter");
             self.writer.flush()?;
         }
+        if self.rotator.config().is_enabled() {
+            let size = self.writer.get_ref().written() + self.writer.buffer().len() as u64;
+            if self.rotator.config().must_rotate(size, self.opened_at) {
+                self.rotate()?;
+            }
+        }
         Ok(())
     }
 }
@@ -0,0 +1,114 @@
+use std::{fs, io::Read, iter};
+
+use age::x25519::Identity;
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{PluginHarness, golden::SampleMeasurements},
+};
+use plugin_csv::{Compression, Config, CsvPlugin, EncryptionConfig, RotationConfig};
+use pretty_assertions::assert_eq;
+
+fn decrypt(path: &std::path::Path, identity: &Identity) -> anyhow::Result<String> {
+    let encrypted = fs::read(path)?;
+    let decryptor = age::Decryptor::new(&encrypted[..])?;
+    let mut reader = decryptor.decrypt(iter::once(identity as &dyn age::Identity))?;
+    let mut res = String::new();
+    reader.read_to_string(&mut res)?;
+    Ok(res)
+}
+
+fn encryption(identity: &Identity) -> EncryptionConfig {
+    EncryptionConfig {
+        recipients: vec![identity.to_public().to_string()],
+    }
+}
+
+#[test]
+fn encrypted_file() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.csv.age");
+    let identity = Identity::generate();
+    let config = Config {
+        output_path: output_path.clone(),
+        encryption: encryption(&identity),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<CsvPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let expected = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.csv"))?;
+    assert!(!fs::read(&output_path)?.windows(6).any(|w| w == b"metric"));
+    assert_eq!(decrypt(&output_path, &identity)?, expected);
+
+    // only the recipient can decrypt the file
+    assert!(decrypt(&output_path, &Identity::generate()).is_err());
+    Ok(())
+}
+
+#[test]
+fn encrypted_rotation() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.csv");
+    let identity = Identity::generate();
+    let config = Config {
+        output_path: output_path.clone(),
+        rotation: RotationConfig {
+            // rotate after each write
+            max_size: Some(1),
+            ..Default::default()
+        },
+        encryption: encryption(&identity),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<CsvPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    for _ in 0..2 {
+        output.write(&sample.measurements()).unwrap();
+    }
+    output.finish()?;
+    harness.stop()?;
+
+    // each rotated file is complete, and the current file is empty but valid
+    let expected = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.csv"))?;
+    let mut files: Vec<_> = fs::read_dir(tmp.path())?.map(|e| e.unwrap().path()).collect();
+    files.sort();
+    assert_eq!(files.len(), 3, "unexpected files {files:?}");
+    assert_eq!(files[2], output_path);
+    assert_eq!(decrypt(&files[0], &identity)?, expected);
+    assert_eq!(decrypt(&files[1], &identity)?, expected);
+    assert_eq!(decrypt(&files[2], &identity)?, "");
+    Ok(())
+}
+
+#[test]
+fn invalid_encryption() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config {
+        output_path: tmp.path().join("alumet-output.csv"),
+        encryption: EncryptionConfig {
+            recipients: vec![String::from("age1notakey")],
+        },
+        ..Config::default()
+    };
+    assert!(PluginHarness::<CsvPlugin>::start(serialize_config(config).unwrap()).is_err());
+
+    // compressing encrypted files is useless
+    let config = Config {
+        output_path: tmp.path().join("alumet-output.csv"),
+        rotation: RotationConfig {
+            max_size: Some(1000),
+            compression: Compression::Zstd,
+            ..Default::default()
+        },
+        encryption: encryption(&Identity::generate()),
+        ..Config::default()
+    };
+    assert!(PluginHarness::<CsvPlugin>::start(serialize_config(config).unwrap()).is_err());
+}
@@ -0,0 +1,64 @@
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_csv::{AttributesFormat, Column, Config, CsvPlugin, TimestampFormat};
+
+#[test]
+fn csv_format() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.csv");
+    let config = Config {
+        output_path: output_path.clone(),
+        force_flush: true,
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<CsvPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+
+    let content = std::fs::read_to_string(&output_path)?;
+    assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.csv"), content);
+    harness.stop()
+}
+
+#[test]
+fn csv_custom_layout() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.csv");
+    let config = Config {
+        output_path: output_path.clone(),
+        append_unit_to_metric_name: false,
+        csv_delimiter: ',',
+        columns: vec![
+            Column::Timestamp,
+            Column::Metric,
+            Column::Value,
+            Column::Unit,
+            Column::ConsumerId,
+            Column::Attributes,
+        ],
+        attributes_format: AttributesFormat::Json,
+        timestamp_format: TimestampFormat::UnixMillis,
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<CsvPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+
+    let content = std::fs::read_to_string(&output_path)?;
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/custom_layout.csv"),
+        content,
+    );
+    harness.stop()
+}
@@ -0,0 +1,57 @@
+use std::{fs, io::Read};
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{PluginHarness, golden::SampleMeasurements},
+};
+use plugin_csv::{Compression, Config, CsvPlugin, RotationConfig};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn rotate_compress_and_delete() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.csv");
+    let config = Config {
+        output_path: output_path.clone(),
+        rotation: RotationConfig {
+            // rotate after each write
+            max_size: Some(1),
+            compression: Compression::Gzip,
+            max_files: Some(2),
+            ..Default::default()
+        },
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<CsvPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    for _ in 0..3 {
+        output.write(&sample.measurements()).unwrap();
+    }
+    output.finish()?;
+    harness.stop()?;
+
+    let mut files: Vec<String> = fs::read_dir(tmp.path())?
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    // the current file is empty, the oldest rotated file has been deleted
+    assert_eq!(files.len(), 3, "unexpected files {files:?}");
+    assert_eq!(files[2], "alumet-output.csv");
+    assert_eq!(fs::read_to_string(&output_path)?, "");
+    assert!(
+        files[..2]
+            .iter()
+            .all(|f| f.starts_with("alumet-output-") && f.ends_with(".csv.gz"))
+    );
+
+    // each rotated file is a complete CSV file, with a header
+    let expected = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.csv"))?;
+    for f in &files[..2] {
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(fs::File::open(tmp.path().join(f))?).read_to_string(&mut content)?;
+        assert_eq!(content, expected);
+    }
+    Ok(())
+}
@@ -0,0 +1,200 @@
+//! Output to the metrics API of Datadog.
+//!
+//! See <https://docs.datadoghq.com/api/latest/metrics/#submit-metrics>.
+
+use std::time::{Duration, Instant};
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::elements::{
+        error::WriteError,
+        output::{OutputContext, error::WriteRetry},
+    },
+    units::{PrefixedUnit, Unit, UnitPrefix},
+};
+use anyhow::{Context, anyhow};
+use reqwest::{StatusCode, blocking::Client};
+use serde::Serialize;
+
+use crate::{mapping::Mapping, rate_limit::RateLimiter};
+
+/// How long to wait after a `429 Too Many Requests` without the `X-RateLimit-Reset` header.
+const DEFAULT_RESET: Duration = Duration::from_secs(10);
+
+pub struct ApiOutput {
+    client: Client,
+    /// URL of the series endpoint, such as `https://api.datadoghq.com/api/v2/series`.
+    url: String,
+    api_key: String,
+    /// The host of the series.
+    hostname: String,
+    mapping: Mapping,
+    /// Maximum number of series per request.
+    batch_size: usize,
+    limiter: Option<RateLimiter>,
+    /// Set when Datadog has rate limited the requests: nothing is sent until then.
+    paused_until: Option<Instant>,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    series: &'a [Series],
+}
+
+#[derive(Serialize)]
+struct Series {
+    metric: String,
+    /// 1 for a count, 3 for a gauge.
+    #[serde(rename = "type")]
+    kind: u8,
+    points: [Point; 1],
+    resources: [HostResource; 1],
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Point {
+    timestamp: u64,
+    value: f64,
+}
+
+#[derive(Serialize)]
+struct HostResource {
+    name: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+impl ApiOutput {
+    pub fn new(
+        client: Client,
+        url: String,
+        api_key: String,
+        hostname: String,
+        mapping: Mapping,
+        batch_size: usize,
+        limiter: Option<RateLimiter>,
+    ) -> Self {
+        Self {
+            client,
+            url,
+            api_key,
+            hostname,
+            mapping,
+            batch_size,
+            limiter,
+            paused_until: None,
+        }
+    }
+
+    /// Sends a batch of series.
+    fn send(&mut self, series: &[Series]) -> Result<(), WriteError> {
+        if let Some(limiter) = &mut self.limiter {
+            limiter.wait();
+        }
+        let body = serde_json::to_vec(&Payload { series }).context("failed to serialize the series")?;
+        let res = self
+            .client
+            .post(&self.url)
+            .header("DD-API-KEY", &self.api_key)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .context("failed to send the series to Datadog")
+            .retry_write()?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            // Stop sending until the end of the rate limit period.
+            let reset = res
+                .headers()
+                .get("X-RateLimit-Reset")
+                .and_then(|v| v.to_str().ok()?.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_RESET);
+            self.paused_until = Some(Instant::now() + reset);
+            return Err(WriteError::CanRetry(anyhow!(
+                "Datadog rate limited the requests, pausing for {reset:?}"
+            )));
+        }
+        let body = res.text().unwrap_or_default();
+        if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+            // The API is busy or unavailable, try again later.
+            return Err(WriteError::CanRetry(anyhow!("Datadog error {status}: {body}")));
+        }
+        // Invalid API key, invalid data... sending the series again would not help.
+        log::error!("Datadog rejected the series with status {status}: {body}");
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Output for ApiOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        if let Some(until) = self.paused_until {
+            if Instant::now() < until {
+                return Err(WriteError::CanRetry(anyhow!(
+                    "Datadog rate limited the requests, the measurements will be sent later"
+                )));
+            }
+            self.paused_until = None;
+        }
+
+        let mut series = Vec::with_capacity(measurements.len().min(self.batch_size));
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
+            };
+            let (timestamp, _) = m.timestamp.to_unix_timestamp();
+            series.push(Series {
+                metric: self.mapping.metric_name(metric),
+                kind: if self.mapping.is_counter(metric) { 1 } else { 3 },
+                points: [Point { timestamp, value }],
+                resources: [HostResource {
+                    name: self.hostname.clone(),
+                    kind: "host",
+                }],
+                tags: self.mapping.tags(m),
+                unit: unit(&metric.unit),
+            });
+            if series.len() == self.batch_size {
+                self.send(&series)?;
+                series.clear();
+            }
+        }
+        if !series.is_empty() {
+            self.send(&series)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the Datadog unit that corresponds to an Alumet unit, if there is one.
+///
+/// See <https://docs.datadoghq.com/metrics/units/#unit-list>.
+fn unit(unit: &PrefixedUnit) -> Option<&'static str> {
+    let unit = match (&unit.base_unit, &unit.prefix) {
+        (Unit::Second, UnitPrefix::Plain) => "second",
+        (Unit::Second, UnitPrefix::Milli) => "millisecond",
+        (Unit::Second, UnitPrefix::Micro) => "microsecond",
+        (Unit::Second, UnitPrefix::Nano) => "nanosecond",
+        (Unit::Byte, UnitPrefix::Plain) => "byte",
+        (Unit::Joule, UnitPrefix::Plain) => "joule",
+        (Unit::Watt, UnitPrefix::Plain) => "watt",
+        (Unit::Volt, UnitPrefix::Plain) => "volt",
+        (Unit::Ampere, UnitPrefix::Plain) => "ampere",
+        (Unit::Hertz, UnitPrefix::Plain) => "hertz",
+        (Unit::Percent, UnitPrefix::Plain) => "percent",
+        _ => return None,
+    };
+    Some(unit)
+}
@@ -0,0 +1,100 @@
+//! Output to DogStatsD, the StatsD server of the Datadog agent.
+//!
+//! See <https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/>.
+
+use std::{io, net::UdpSocket, os::unix::net::UnixDatagram};
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::elements::{
+        error::WriteError,
+        output::{OutputContext, error::WriteRetry},
+    },
+};
+use anyhow::Context;
+
+use crate::{mapping::Mapping, rate_limit::RateLimiter};
+
+/// Socket connected to the agent.
+pub enum Transport {
+    Udp(UdpSocket),
+    /// Unix domain socket, recommended in containers.
+    Unix(UnixDatagram),
+}
+
+impl Transport {
+    fn send(&self, packet: &[u8]) -> io::Result<usize> {
+        match self {
+            Transport::Udp(socket) => socket.send(packet),
+            Transport::Unix(socket) => socket.send(packet),
+        }
+    }
+}
+
+pub struct DogstatsdOutput {
+    transport: Transport,
+    mapping: Mapping,
+    /// Maximum size of a packet. The lines are grouped in packets of this size.
+    max_packet_size: usize,
+    limiter: Option<RateLimiter>,
+}
+
+impl DogstatsdOutput {
+    pub fn new(transport: Transport, mapping: Mapping, max_packet_size: usize, limiter: Option<RateLimiter>) -> Self {
+        Self {
+            transport,
+            mapping,
+            max_packet_size,
+            limiter,
+        }
+    }
+
+    fn send(&mut self, packet: &str) -> anyhow::Result<()> {
+        if let Some(limiter) = &mut self.limiter {
+            limiter.wait();
+        }
+        self.transport
+            .send(packet.as_bytes())
+            .context("failed to send the measurements to DogStatsD")?;
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Output for DogstatsdOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut packet = String::with_capacity(self.max_packet_size);
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) => v.to_string(),
+                WrappedMeasurementValue::U64(v) => v.to_string(),
+            };
+            let kind = if self.mapping.is_counter(metric) { "c" } else { "g" };
+            let mut line = format!("{}:{value}|{kind}", self.mapping.metric_name(metric));
+            let tags = self.mapping.tags(m);
+            if !tags.is_empty() {
+                line.push_str("|#");
+                // The tags cannot contain commas: they are normalized.
+                line.push_str(&tags.join(","));
+            }
+
+            // Send the packet when the next line does not fit in it.
+            // A line that is bigger than max_packet_size is sent alone.
+            if !packet.is_empty() && packet.len() + 1 + line.len() > self.max_packet_size {
+                self.send(&packet).retry_write()?;
+                packet.clear();
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(&line);
+        }
+        if !packet.is_empty() {
+            self.send(&packet).retry_write()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+mod api;
+mod dogstatsd;
+mod mapping;
+mod rate_limit;
+
+use std::{
+    collections::BTreeMap,
+    net::{ToSocketAddrs, UdpSocket},
+    os::unix::net::UnixDatagram,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::{Context, anyhow};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::api::ApiOutput;
+use crate::dogstatsd::{DogstatsdOutput, Transport};
+use crate::mapping::Mapping;
+use crate::rate_limit::RateLimiter;
+
+pub struct DatadogPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for DatadogPlugin {
+    fn name() -> &'static str {
+        "datadog"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        match config.destination {
+            Destination::Api => {
+                anyhow::ensure!(config.api.batch_size > 0, "batch_size must be greater than zero");
+                anyhow::ensure!(
+                    config.api.max_requests_per_second >= 0.0,
+                    "max_requests_per_second cannot be negative"
+                );
+            }
+            Destination::Dogstatsd => {
+                anyhow::ensure!(
+                    config.dogstatsd.max_packet_size > 0,
+                    "max_packet_size must be greater than zero"
+                );
+                anyhow::ensure!(
+                    config.dogstatsd.max_packets_per_second >= 0.0,
+                    "max_packets_per_second cannot be negative"
+                );
+            }
+        }
+        Ok(Box::new(DatadogPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let mapping = Mapping::new(
+            config.prefix.clone(),
+            config.counters.iter().cloned().collect(),
+            config.tags.rename.clone(),
+            &config.tags.extra,
+        );
+
+        match config.destination {
+            Destination::Api => {
+                let api = &config.api;
+                let api_key = api
+                    .api_key
+                    .clone()
+                    .or_else(|| std::env::var("DD_API_KEY").ok())
+                    .ok_or_else(|| anyhow!("No api_key specified in the config, and DD_API_KEY is not set."))?;
+                let hostname = match &config.hostname {
+                    Some(hostname) => hostname.to_owned(),
+                    None => hostname::get()
+                        .context(
+                            "No hostname specified in the config, and unable to retrieve the hostname of the machine.",
+                        )?
+                        .to_string_lossy()
+                        .to_string(),
+                };
+                let endpoint = match &api.endpoint {
+                    Some(endpoint) => endpoint.trim_end_matches('/').to_owned(),
+                    None => format!("https://api.{}", api.site),
+                };
+                let client = Client::builder()
+                    .timeout(api.timeout)
+                    .build()
+                    .context("failed to create the HTTP client")?;
+                let output = ApiOutput::new(
+                    client,
+                    format!("{endpoint}/api/v2/series"),
+                    api_key,
+                    hostname,
+                    mapping,
+                    api.batch_size,
+                    rate_limiter(api.max_requests_per_second),
+                );
+                alumet.add_blocking_output("out", Box::new(output))?;
+            }
+            Destination::Dogstatsd => {
+                let dogstatsd = &config.dogstatsd;
+                let transport = match &dogstatsd.socket_path {
+                    Some(path) => {
+                        let socket = UnixDatagram::unbound().context("failed to create the Unix socket")?;
+                        socket
+                            .connect(path)
+                            .with_context(|| format!("failed to connect to DogStatsD at {path:?}"))?;
+                        Transport::Unix(socket)
+                    }
+                    None => {
+                        // The address is resolved once: the UDP socket is connected to it.
+                        let address = (dogstatsd.host.as_str(), dogstatsd.port)
+                            .to_socket_addrs()
+                            .with_context(|| format!("failed to resolve the address of DogStatsD {}", dogstatsd.host))?
+                            .next()
+                            .ok_or_else(|| anyhow!("no address found for DogStatsD {}", dogstatsd.host))?;
+                        let bind_address = if address.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+                        let socket = UdpSocket::bind(bind_address).context("failed to create the UDP socket")?;
+                        socket.connect(address)?;
+                        Transport::Udp(socket)
+                    }
+                };
+                let output = DogstatsdOutput::new(
+                    transport,
+                    mapping,
+                    dogstatsd.max_packet_size,
+                    rate_limiter(dogstatsd.max_packets_per_second),
+                );
+                alumet.add_blocking_output("out", Box::new(output))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// A rate of zero disables the rate limiting.
+fn rate_limiter(rate: f64) -> Option<RateLimiter> {
+    (rate > 0.0).then(|| RateLimiter::new(rate, Instant::now()))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Send the measurements to the API of Datadog (`"api"`), or to the local Datadog agent (`"dogstatsd"`).
+    pub destination: Destination,
+    /// Prepended to the name of the metrics.
+    pub prefix: String,
+    /// Metrics sent as counts, such as the energy consumed since the last measurement.
+    /// The other metrics are sent as gauges.
+    pub counters: Vec<String>,
+    /// Host of the series sent to the API. Defaults to the hostname.
+    /// With DogStatsD, the agent sets the host itself.
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub tags: TagsConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub dogstatsd: DogstatsdConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Destination {
+    Api,
+    Dogstatsd,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TagsConfig {
+    /// New names of the tags `resource_kind`, `resource_id`, `consumer_kind`, `consumer_id`
+    /// and of the attributes. An empty name removes the tag.
+    pub rename: BTreeMap<String, String>,
+    /// Tags added to every measurement, such as `env:prod`.
+    pub extra: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfig {
+    /// API key. Defaults to the environment variable `DD_API_KEY`.
+    pub api_key: Option<String>,
+    /// Datadog site, such as `datadoghq.com` or `datadoghq.eu`.
+    pub site: String,
+    /// URL of the API. Defaults to the API of the site.
+    pub endpoint: Option<String>,
+    /// Maximum number of series per request.
+    pub batch_size: usize,
+    /// Maximum number of requests per second, 0 to disable the limit.
+    pub max_requests_per_second: f64,
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DogstatsdConfig {
+    /// Address of the agent.
+    pub host: String,
+    pub port: u16,
+    /// Unix domain socket of the agent, used instead of UDP if set.
+    pub socket_path: Option<PathBuf>,
+    /// Maximum size of a packet, in bytes.
+    pub max_packet_size: usize,
+    /// Maximum number of packets per second, 0 to disable the limit.
+    pub max_packets_per_second: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            destination: Destination::Api,
+            prefix: String::from("alumet."),
+            counters: Vec::new(),
+            hostname: None,
+            tags: TagsConfig::default(),
+            api: ApiConfig::default(),
+            dogstatsd: DogstatsdConfig::default(),
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            site: String::from("datadoghq.com"),
+            endpoint: None,
+            batch_size: 500,
+            max_requests_per_second: 5.0,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Default for DogstatsdConfig {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: 8125,
+            socket_path: None,
+            max_packet_size: 1432,
+            max_packets_per_second: 1000.0,
+        }
+    }
+}
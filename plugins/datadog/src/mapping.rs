@@ -0,0 +1,139 @@
+//! Conversion of the metrics into Datadog metric names and types,
+//! and of the resources, consumers and attributes of the measurements into Datadog tags.
+//!
+//! See <https://docs.datadoghq.com/getting_started/tagging/#define-tags>.
+
+use std::collections::{BTreeMap, HashSet};
+
+use alumet::{measurement::MeasurementPoint, metrics::Metric};
+
+/// Maximum length of a tag or of a metric name.
+const MAX_LENGTH: usize = 200;
+
+/// Builds the names, the types and the tags of the measurements.
+pub struct Mapping {
+    /// Prepended to the name of the metrics, e.g. `alumet.`.
+    prefix: String,
+    /// Metrics sent as counts, the other metrics are sent as gauges.
+    counters: HashSet<String>,
+    /// New names of the tags, such as `resource_kind = "alumet_resource"`. An empty name removes the tag.
+    rename: BTreeMap<String, String>,
+    /// Tags added to every measurement, already normalized.
+    extra: Vec<String>,
+}
+
+impl Mapping {
+    pub fn new(prefix: String, counters: HashSet<String>, rename: BTreeMap<String, String>, extra: &[String]) -> Self {
+        Self {
+            prefix,
+            counters,
+            rename,
+            extra: extra.iter().map(|t| normalize_tag(t)).collect(),
+        }
+    }
+
+    /// Returns the Datadog name of a metric.
+    pub fn metric_name(&self, metric: &Metric) -> String {
+        normalize_metric_name(&format!("{}{}", self.prefix, metric.name))
+    }
+
+    /// Is the metric sent as a count, instead of a gauge?
+    pub fn is_counter(&self, metric: &Metric) -> bool {
+        self.counters.contains(&metric.name)
+    }
+
+    /// Returns the tags of a measurement, as `key:value` strings, without the empty values.
+    pub fn tags(&self, m: &MeasurementPoint) -> Vec<String> {
+        let fields = [
+            ("resource_kind", m.resource.kind().to_owned()),
+            ("resource_id", m.resource.id_display().to_string()),
+            ("consumer_kind", m.consumer.kind().to_owned()),
+            ("consumer_id", m.consumer.id_display().to_string()),
+        ];
+        let attributes = m.attributes().map(|(k, v)| (k, v.to_string()));
+        let mut tags = Vec::new();
+        for (key, value) in fields.into_iter().chain(attributes) {
+            let key = self.rename.get(key).map(String::as_str).unwrap_or(key);
+            if key.is_empty() || value.is_empty() {
+                continue;
+            }
+            tags.push(normalize_tag(&format!("{key}:{value}")));
+        }
+        tags.extend(self.extra.iter().cloned());
+        tags
+    }
+}
+
+/// Converts a tag to the format of Datadog: lowercase, with only alphanumeric characters,
+/// underscores, minuses, colons, periods and slashes.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '_' | '-' | ':' | '.' | '/' => c,
+            _ => '_',
+        })
+        .take(MAX_LENGTH)
+        .collect()
+}
+
+/// Converts a metric name to the format of Datadog: only ASCII alphanumeric characters, underscores and periods.
+fn normalize_metric_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' => c,
+            _ => '_',
+        })
+        .take(MAX_LENGTH)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashSet},
+        time::SystemTime,
+    };
+
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{Mapping, normalize_metric_name, normalize_tag};
+
+    #[test]
+    fn normalization() {
+        assert_eq!(normalize_tag("Domain:Package 0"), "domain:package_0");
+        assert_eq!(normalize_tag("path:/system.slice/a@b"), "path:/system.slice/a_b");
+        assert_eq!(normalize_metric_name("alumet.rapl-energy"), "alumet.rapl_energy");
+    }
+
+    #[test]
+    fn mapping() {
+        let rename = BTreeMap::from([
+            (String::from("resource_kind"), String::from("alumet_resource")),
+            (String::from("consumer_kind"), String::new()),
+        ]);
+        let mapping = Mapping::new(String::new(), HashSet::new(), rename, &[String::from("env:Prod")]);
+        let m = MeasurementPoint::new_untyped(
+            Timestamp::from(SystemTime::UNIX_EPOCH),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::F64(12.5),
+        )
+        .with_attr("domain", "package");
+        assert_eq!(
+            mapping.tags(&m),
+            vec![
+                "alumet_resource:cpu_package",
+                "resource_id:0",
+                "domain:package",
+                "env:prod"
+            ]
+        );
+    }
+}
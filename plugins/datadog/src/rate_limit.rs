@@ -0,0 +1,71 @@
+//! Client-side rate limiting of the requests or packets, with a token bucket.
+
+use std::time::{Duration, Instant};
+
+/// Allows `rate` actions per second on average, and bursts of up to one second of actions.
+pub struct RateLimiter {
+    rate: f64,
+    capacity: f64,
+    /// Available tokens. A negative value is a debt, paid back by waiting.
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, now: Instant) -> Self {
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last: now,
+        }
+    }
+
+    /// Takes a token, and returns how long to wait before performing the action.
+    pub fn acquire(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity) - 1.0;
+        self.last = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    /// Takes a token, and sleeps until the action is allowed.
+    pub fn wait(&mut self) {
+        let delay = self.acquire(Instant::now());
+        if !delay.is_zero() {
+            log::debug!("rate limit reached, waiting {delay:?}");
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use pretty_assertions::assert_eq;
+
+    use super::RateLimiter;
+
+    #[test]
+    fn token_bucket() {
+        let t0 = Instant::now();
+        let mut limiter = RateLimiter::new(2.0, t0);
+        // burst of 2 actions
+        assert_eq!(limiter.acquire(t0), Duration::ZERO);
+        assert_eq!(limiter.acquire(t0), Duration::ZERO);
+        // then 2 actions per second
+        assert_eq!(limiter.acquire(t0), Duration::from_millis(500));
+        assert_eq!(limiter.acquire(t0), Duration::from_secs(1));
+        // the debt is paid back over time
+        let t1 = t0 + Duration::from_millis(2500);
+        assert_eq!(limiter.acquire(t1), Duration::ZERO);
+        assert_eq!(limiter.acquire(t1), Duration::ZERO);
+        assert_eq!(limiter.acquire(t1), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, net::UdpSocket, time::Duration};
+
+use alumet::{
+    pipeline::elements::error::WriteError,
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{HttpCapture, SampleMeasurements, assert_golden, assert_golden_json},
+    },
+};
+use mockito::Server;
+use plugin_datadog::{ApiConfig, Config, DatadogPlugin, Destination, DogstatsdConfig, TagsConfig};
+use pretty_assertions::assert_eq;
+
+fn api_config(endpoint: String) -> Config {
+    Config {
+        destination: Destination::Api,
+        counters: vec![String::from("sample_energy")],
+        hostname: Some(String::from("node-1")),
+        api: ApiConfig {
+            api_key: Some(String::from("test-key")),
+            endpoint: Some(endpoint),
+            ..ApiConfig::default()
+        },
+        ..Config::default()
+    }
+}
+
+#[test]
+fn api() -> anyhow::Result<()> {
+    let server = HttpCapture::with_response(202, r#"{"errors":[]}"#)?;
+
+    let config = Config {
+        tags: TagsConfig {
+            rename: BTreeMap::from([
+                (String::from("resource_kind"), String::from("alumet_resource")),
+                (String::from("cpu"), String::new()),
+            ]),
+            extra: vec![String::from("env:test")],
+        },
+        ..api_config(server.url())
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<DatadogPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        (requests[0].method.as_str(), requests[0].path.as_str()),
+        ("POST", "/api/v2/series")
+    );
+    assert_eq!(requests[0].header("dd-api-key"), Some("test-key"));
+    assert_eq!(requests[0].header("content-type"), Some("application/json"));
+    assert_golden_json(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/series.json"),
+        &requests[0].body,
+    );
+    Ok(())
+}
+
+#[test]
+fn batching() -> anyhow::Result<()> {
+    let server = HttpCapture::with_response(202, r#"{"errors":[]}"#)?;
+
+    let config = Config {
+        api: ApiConfig {
+            batch_size: 2,
+            ..api_config(server.url()).api
+        },
+        ..api_config(server.url())
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<DatadogPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    // 5 points, at most 2 per request
+    let counts: Vec<usize> = server
+        .requests()
+        .iter()
+        .map(|r| r.body_str().matches(r#""metric":"#).count())
+        .collect();
+    assert_eq!(counts, vec![2, 2, 1]);
+    Ok(())
+}
+
+#[test]
+fn rate_limited() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/api/v2/series")
+        .with_status(429)
+        .with_header("X-RateLimit-Reset", "60")
+        .create();
+
+    let (mut harness, sample) = SampleMeasurements::start::<DatadogPlugin>(api_config(server.url()))?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    // nothing is sent until the end of the period
+    let res = output.write(&sample.measurements());
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    output.finish()?;
+    harness.stop()?;
+    mock.expect(1).assert();
+    Ok(())
+}
+
+#[test]
+fn rejected() -> anyhow::Result<()> {
+    let server = HttpCapture::with_status(403)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<DatadogPlugin>(api_config(server.url()))?;
+    let mut output = harness.output("out")?;
+    // an invalid API key is not fixed by sending the series again
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+    assert_eq!(server.requests().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn dogstatsd() -> anyhow::Result<()> {
+    let server = UdpSocket::bind("127.0.0.1:0")?;
+    let config = Config {
+        destination: Destination::Dogstatsd,
+        counters: vec![String::from("sample_energy")],
+        tags: TagsConfig {
+            rename: BTreeMap::from([(String::from("consumer_kind"), String::new())]),
+            extra: vec![String::from("service:alumet")],
+        },
+        dogstatsd: DogstatsdConfig {
+            host: String::from("127.0.0.1"),
+            port: server.local_addr()?.port(),
+            ..DogstatsdConfig::default()
+        },
+        ..Config::default()
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<DatadogPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    server.set_read_timeout(Some(Duration::from_millis(200)))?;
+    let mut buf = [0; 2048];
+    let n = server.recv(&mut buf)?;
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/dogstatsd.txt"),
+        format!("{}\n", String::from_utf8(buf[..n].to_vec())?),
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_config() {
+    let endpoint = String::from("http://127.0.0.1:1");
+    let no_batch = Config {
+        api: ApiConfig {
+            batch_size: 0,
+            ..api_config(endpoint.clone()).api
+        },
+        ..api_config(endpoint.clone())
+    };
+    let negative_rate = Config {
+        api: ApiConfig {
+            max_requests_per_second: -1.0,
+            ..api_config(endpoint.clone()).api
+        },
+        ..api_config(endpoint)
+    };
+    for config in [no_batch, negative_rate] {
+        assert!(PluginHarness::<DatadogPlugin>::start(serialize_config(config).unwrap()).is_err());
+    }
+}
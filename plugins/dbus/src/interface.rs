@@ -0,0 +1,50 @@
+//! The D-Bus interface `org.alumet.Measurements1`.
+
+use zbus::{interface, object_server::SignalEmitter};
+
+use crate::state::{SeriesSummary, Totals};
+
+/// Path of the object that implements the interface.
+pub const OBJECT_PATH: &str = "/org/alumet/Measurements";
+
+/// Live power and energy consumption, exposed as D-Bus properties.
+#[derive(Default)]
+pub struct Measurements {
+    power: f64,
+    energy: f64,
+    series: Vec<SeriesSummary>,
+}
+
+impl Measurements {
+    /// Copies the current values of the totals, which the clients get when they read the properties.
+    pub fn update(&mut self, totals: &Totals) {
+        self.power = totals.power();
+        self.energy = totals.energy();
+        self.series = totals.series();
+    }
+}
+
+#[interface(name = "org.alumet.Measurements1")]
+impl Measurements {
+    /// Total power, in watts.
+    #[zbus(property)]
+    fn power(&self) -> f64 {
+        self.power
+    }
+
+    /// Total energy consumed since the start of the agent, in joules.
+    #[zbus(property)]
+    fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// Every series: metric, resource kind, resource id, consumer kind, consumer id, power and energy.
+    #[zbus(property)]
+    fn series(&self) -> Vec<SeriesSummary> {
+        self.series.clone()
+    }
+
+    /// Emitted when new measurements have been received, at most once per `signal_interval`.
+    #[zbus(signal)]
+    pub async fn updated(emitter: &SignalEmitter<'_>, power: f64, energy: f64) -> zbus::Result<()>;
+}
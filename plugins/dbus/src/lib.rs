@@ -0,0 +1,102 @@
+pub mod interface;
+mod output;
+pub mod state;
+
+use std::time::Duration;
+
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, capability::Capability, rust::AlumetPlugin};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use zbus::{blocking::connection::Builder, fdo::RequestNameFlags, names::WellKnownName};
+
+use crate::interface::{Measurements, OBJECT_PATH};
+use crate::output::DbusOutput;
+
+pub struct DbusPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for DbusPlugin {
+    fn name() -> &'static str {
+        "dbus"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        // the measurements are published on the bus
+        Some(vec![Capability::Dbus])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        WellKnownName::try_from(config.name.as_str())
+            .with_context(|| format!("invalid D-Bus name {:?}", config.name))?;
+        Ok(Box::new(DbusPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+
+        // Connect now to report errors early.
+        let builder = match config.bus.as_str() {
+            "session" => Builder::session(),
+            "system" => Builder::system(),
+            address => Builder::address(address),
+        }
+        .with_context(|| format!("invalid D-Bus bus {:?}", config.bus))?;
+        let connection = builder
+            .serve_at(OBJECT_PATH, Measurements::default())?
+            .build()
+            .with_context(|| format!("failed to connect to the {} bus", config.bus))?;
+        // Fail if another agent owns the name, instead of waiting for it in a queue.
+        connection
+            .request_name_with_flags(config.name.as_str(), RequestNameFlags::DoNotQueue.into())
+            .with_context(|| format!("failed to request the name {}", config.name))?;
+        log::info!("Measurements exposed on D-Bus as {} {OBJECT_PATH}", config.name);
+
+        let metrics = config.metrics.iter().cloned().collect();
+        let output = DbusOutput::new(connection, metrics, config.signal_interval);
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The bus to connect to: `session`, `system` or the address of a bus, such as `unix:path=/run/user/1000/bus`.
+    pub bus: String,
+    /// Well-known name to own on the bus.
+    pub name: String,
+    /// The metrics to expose. If empty, every metric in watts, joules or watt-hours is exposed.
+    ///
+    /// The total power and energy are sums over the exposed series:
+    /// choose metrics that do not overlap, such as the energy of the CPU packages and of the GPUs.
+    pub metrics: Vec<String>,
+    /// Minimum time between two `Updated` signals. The properties are always up to date.
+    #[serde(with = "humantime_serde")]
+    pub signal_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bus: String::from("session"),
+            name: String::from("org.alumet.Alumet"),
+            metrics: Vec::new(),
+            signal_interval: Duration::from_secs(1),
+        }
+    }
+}
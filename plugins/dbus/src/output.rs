@@ -0,0 +1,94 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use anyhow::Context;
+use async_io::block_on;
+use zbus::blocking::Connection;
+
+use crate::{
+    interface::{Measurements, OBJECT_PATH},
+    state::{Quantity, Totals},
+};
+
+/// Updates the D-Bus properties with the measurements, and notifies the clients.
+pub struct DbusOutput {
+    connection: Connection,
+    /// The metrics to expose. If empty, every power and energy metric is exposed.
+    metrics: HashSet<String>,
+    totals: Totals,
+    signal_interval: Duration,
+    /// When the last signals were emitted.
+    last_signal: Option<Instant>,
+}
+
+impl DbusOutput {
+    pub fn new(connection: Connection, metrics: HashSet<String>, signal_interval: Duration) -> Self {
+        Self {
+            connection,
+            metrics,
+            totals: Totals::default(),
+            signal_interval,
+            last_signal: None,
+        }
+    }
+
+    /// Copies the totals to the D-Bus object and, if the last signals are old enough, emits new ones.
+    fn publish(&mut self) -> zbus::Result<()> {
+        let iface = self
+            .connection
+            .object_server()
+            .interface::<_, Measurements>(OBJECT_PATH)?;
+        iface.get_mut().update(&self.totals);
+
+        if self.last_signal.is_some_and(|t| t.elapsed() < self.signal_interval) {
+            return Ok(());
+        }
+        self.last_signal = Some(Instant::now());
+        let emitter = iface.signal_emitter();
+        let measurements = iface.get();
+        block_on(async {
+            measurements.power_changed(emitter).await?;
+            measurements.energy_changed(emitter).await?;
+            measurements.series_changed(emitter).await?;
+            Measurements::updated(emitter, self.totals.power(), self.totals.energy()).await
+        })
+    }
+}
+
+impl alumet::pipeline::Output for DbusOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut changed = false;
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            if !self.metrics.is_empty() && !self.metrics.contains(&metric.name) {
+                continue;
+            }
+            // The other metrics cannot be summed into a power or an energy.
+            let Some(quantity) = Quantity::of(&metric.unit) else {
+                continue;
+            };
+            let key = (
+                metric.name.clone(),
+                m.resource.kind().to_owned(),
+                m.resource.id_display().to_string(),
+                m.consumer.kind().to_owned(),
+                m.consumer.id_display().to_string(),
+            );
+            self.totals.add(key, quantity, m.timestamp, m.value.as_f64());
+            changed = true;
+        }
+        if changed {
+            self.publish().context("failed to publish the measurements on D-Bus")?;
+        }
+        Ok(())
+    }
+}
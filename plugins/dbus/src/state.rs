@@ -0,0 +1,211 @@
+//! Power and energy of the series, computed from the measurements.
+
+use std::collections::BTreeMap;
+
+use alumet::{
+    measurement::Timestamp,
+    units::{PrefixedUnit, Unit, UnitPrefix},
+};
+
+/// Identifies a series of measurements: metric, resource kind, resource id, consumer kind and consumer id.
+pub type SeriesKey = (String, String, String, String, String);
+
+/// A series as exposed on the bus: its key, followed by its power in watts and its energy in joules.
+pub type SeriesSummary = (String, String, String, String, String, f64, f64);
+
+/// What a metric measures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quantity {
+    /// A power, with the factor that converts the values to watts.
+    Power(f64),
+    /// An energy consumed since the previous measurement, with the factor that converts the values to joules.
+    Energy(f64),
+}
+
+impl Quantity {
+    /// Returns the quantity measured in this unit, or `None` if it is neither a power nor an energy.
+    pub fn of(unit: &PrefixedUnit) -> Option<Self> {
+        let factor = match unit.prefix {
+            UnitPrefix::Nano => 1e-9,
+            UnitPrefix::Micro => 1e-6,
+            UnitPrefix::Milli => 1e-3,
+            UnitPrefix::Plain => 1.0,
+            UnitPrefix::Kilo => 1e3,
+            UnitPrefix::Mega => 1e6,
+            UnitPrefix::Giga => 1e9,
+        };
+        match unit.base_unit {
+            Unit::Watt => Some(Quantity::Power(factor)),
+            Unit::Joule => Some(Quantity::Energy(factor)),
+            Unit::WattHour => Some(Quantity::Energy(factor * 3600.0)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    /// Last known power, in watts.
+    power: f64,
+    /// Energy consumed since the start of the agent, in joules.
+    energy: f64,
+    /// Timestamp of the last measurement.
+    last: Option<Timestamp>,
+}
+
+/// Power and energy of every series.
+#[derive(Debug, Default)]
+pub struct Totals {
+    series: BTreeMap<SeriesKey, Series>,
+}
+
+impl Totals {
+    /// Takes a measurement of a series into account.
+    ///
+    /// The power of an energy metric is the energy divided by the time elapsed since the previous
+    /// measurement of the series, and the energy of a power metric is the power multiplied by this time.
+    /// Nothing can be computed for the first measurement of a series, except for its energy.
+    pub fn add(&mut self, key: SeriesKey, quantity: Quantity, timestamp: Timestamp, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        let series = self.series.entry(key).or_default();
+        let elapsed = series
+            .last
+            .and_then(|last| timestamp.duration_since(last).ok())
+            .map(|d| d.as_secs_f64())
+            .filter(|secs| *secs > 0.0);
+        match quantity {
+            Quantity::Power(factor) => {
+                let power = value * factor;
+                if let Some(secs) = elapsed {
+                    series.energy += power * secs;
+                }
+                series.power = power;
+            }
+            Quantity::Energy(factor) => {
+                let energy = value * factor;
+                if let Some(secs) = elapsed {
+                    series.power = energy / secs;
+                }
+                series.energy += energy;
+            }
+        }
+        // A late measurement must not shorten the next interval.
+        if series.last.is_none_or(|last| timestamp > last) {
+            series.last = Some(timestamp);
+        }
+    }
+
+    /// Sum of the power of the series, in watts.
+    pub fn power(&self) -> f64 {
+        self.series.values().map(|s| s.power).sum()
+    }
+
+    /// Sum of the energy of the series, in joules.
+    pub fn energy(&self) -> f64 {
+        self.series.values().map(|s| s.energy).sum()
+    }
+
+    /// Returns every series, sorted by key.
+    pub fn series(&self) -> Vec<SeriesSummary> {
+        self.series
+            .iter()
+            .map(
+                |((metric, resource_kind, resource_id, consumer_kind, consumer_id), s)| {
+                    (
+                        metric.clone(),
+                        resource_kind.clone(),
+                        resource_id.clone(),
+                        consumer_kind.clone(),
+                        consumer_id.clone(),
+                        s.power,
+                        s.energy,
+                    )
+                },
+            )
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::{
+        measurement::Timestamp,
+        units::{PrefixedUnit, Unit},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{Quantity, SeriesKey, Totals};
+
+    fn key(metric: &str) -> SeriesKey {
+        (
+            metric.to_owned(),
+            String::from("cpu_package"),
+            String::from("0"),
+            String::from("local_machine"),
+            String::new(),
+        )
+    }
+
+    fn at(millis: u64) -> Timestamp {
+        Timestamp::from(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn quantities() {
+        assert_eq!(
+            Quantity::of(&PrefixedUnit::from(Unit::Watt)),
+            Some(Quantity::Power(1.0))
+        );
+        assert_eq!(
+            Quantity::of(&PrefixedUnit::milli(Unit::Joule)),
+            Some(Quantity::Energy(1e-3))
+        );
+        assert_eq!(
+            Quantity::of(&PrefixedUnit::from(Unit::WattHour)),
+            Some(Quantity::Energy(3600.0))
+        );
+        assert_eq!(Quantity::of(&PrefixedUnit::from(Unit::Second)), None);
+    }
+
+    #[test]
+    fn power_from_energy() {
+        let mut totals = Totals::default();
+        let energy = Quantity::Energy(1e-3);
+        totals.add(key("rapl"), energy, at(0), 5000.0);
+        // the power is unknown until the second measurement
+        assert_eq!((totals.power(), totals.energy()), (0.0, 5.0));
+        totals.add(key("rapl"), energy, at(500), 10_000.0);
+        assert_eq!((totals.power(), totals.energy()), (20.0, 15.0));
+        // a late measurement is counted, but does not change the interval
+        totals.add(key("rapl"), energy, at(250), 1000.0);
+        totals.add(key("rapl"), energy, at(1000), 5000.0);
+        assert_eq!((totals.power(), totals.energy()), (10.0, 21.0));
+    }
+
+    #[test]
+    fn energy_from_power() {
+        let mut totals = Totals::default();
+        let power = Quantity::Power(1.0);
+        totals.add(key("gpu"), power, at(0), 100.0);
+        totals.add(key("gpu"), power, at(2000), 50.0);
+        totals.add(key("gpu"), power, at(2000), f64::NAN);
+        assert_eq!((totals.power(), totals.energy()), (50.0, 100.0));
+    }
+
+    #[test]
+    fn sum_of_series() {
+        let mut totals = Totals::default();
+        totals.add(key("rapl"), Quantity::Energy(1.0), at(0), 3.0);
+        totals.add(key("rapl"), Quantity::Energy(1.0), at(1000), 3.0);
+        totals.add(key("gpu"), Quantity::Power(1.0), at(0), 40.0);
+        assert_eq!((totals.power(), totals.energy()), (43.0, 6.0));
+        let series = totals.series();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].0, "gpu");
+        assert_eq!((series[1].5, series[1].6), (3.0, 6.0));
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    plugin::rust::serialize_config,
+    resources::{Resource, ResourceConsumer},
+    test::{PluginHarness, golden::SampleMeasurements},
+};
+use plugin_dbus::{Config, DbusPlugin, interface::OBJECT_PATH, state::SeriesSummary};
+use pretty_assertions::assert_eq;
+use zbus::{
+    blocking::{Proxy, proxy},
+    proxy::CacheProperties,
+};
+
+const INTERFACE: &str = "org.alumet.Measurements1";
+
+/// A private session bus, stopped on drop.
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    /// Starts a bus, or returns `None` if `dbus-daemon` is not installed.
+    fn start() -> anyhow::Result<Option<Self>> {
+        let daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut daemon = match daemon {
+            Ok(daemon) => daemon,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("dbus-daemon is not installed, the test is skipped");
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address)?;
+        Ok(Some(Self {
+            daemon,
+            address: address.trim().to_owned(),
+        }))
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            bus: self.address.clone(),
+            ..Config::default()
+        }
+    }
+
+    fn proxy(&self) -> anyhow::Result<Proxy<'static>> {
+        let connection = zbus::blocking::connection::Builder::address(self.address.as_str())?.build()?;
+        // Without cache, the properties are read from the bus every time.
+        Ok(proxy::Builder::new(&connection)
+            .destination("org.alumet.Alumet")?
+            .path(OBJECT_PATH)?
+            .interface(INTERFACE)?
+            .cache_properties(CacheProperties::No)
+            .build()?)
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+fn at(secs: u64) -> Timestamp {
+    Timestamp::from(UNIX_EPOCH + SampleMeasurements::START + Duration::from_secs(secs))
+}
+
+#[test]
+fn properties() -> anyhow::Result<()> {
+    let Some(bus) = Bus::start()? else { return Ok(()) };
+    let mut harness = PluginHarness::<DbusPlugin>::start(serialize_config(bus.config())?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("out")?;
+    let proxy = bus.proxy()?;
+
+    // only the energy is exposed, and its power is unknown until the second measurement
+    output.write(&sample.measurements()).unwrap();
+    assert_eq!(proxy.get_property::<f64>("Energy")?, 165.75);
+    assert_eq!(proxy.get_property::<f64>("Power")?, 0.0);
+
+    output
+        .write(&MeasurementBuffer::from(vec![MeasurementPoint::new(
+            at(2),
+            sample.energy,
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            20.0,
+        )]))
+        .unwrap();
+    assert_eq!(proxy.get_property::<f64>("Energy")?, 185.75);
+    assert_eq!(proxy.get_property::<f64>("Power")?, 10.0);
+    let series: Vec<SeriesSummary> = proxy.get_property("Series")?;
+    let series: Vec<_> = series.iter().map(|s| (s.1.as_str(), s.2.as_str(), s.5, s.6)).collect();
+    assert_eq!(
+        series,
+        vec![
+            ("cpu_package", "0", 10.0, 32.5),
+            ("dram", "0", 0.0, 3.25),
+            ("gpu", "0000:01:00.0", 0.0, 150.0),
+        ]
+    );
+
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn updated_signal() -> anyhow::Result<()> {
+    let Some(bus) = Bus::start()? else { return Ok(()) };
+    let config = Config {
+        metrics: vec![String::from("sample_energy")],
+        signal_interval: Duration::from_secs(3600),
+        ..bus.config()
+    };
+    let mut harness = PluginHarness::<DbusPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("out")?;
+    let proxy = bus.proxy()?;
+    let mut signals = proxy.receive_signal("Updated")?;
+
+    output.write(&sample.measurements()).unwrap();
+    let (power, energy): (f64, f64) = signals.next().unwrap().body().deserialize()?;
+    assert_eq!((power, energy), (0.0, 165.75));
+
+    // no new signal before the end of the interval, but the properties are up to date
+    output.write(&sample.measurements()).unwrap();
+    assert_eq!(proxy.get_property::<f64>("Energy")?, 331.5);
+
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn name_already_taken() -> anyhow::Result<()> {
+    let Some(bus) = Bus::start()? else { return Ok(()) };
+    let _owner = zbus::blocking::connection::Builder::address(bus.address.as_str())?
+        .name("org.alumet.Alumet")?
+        .build()?;
+    let err = PluginHarness::<DbusPlugin>::start(serialize_config(bus.config())?)
+        .err()
+        .expect("the name should already be taken");
+    assert!(
+        format!("{err:#}").contains("failed to request the name org.alumet.Alumet"),
+        "{err:#}"
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_name() -> anyhow::Result<()> {
+    let config = Config {
+        name: String::from("not a name"),
+        ..Config::default()
+    };
+    let err = PluginHarness::<DbusPlugin>::start(serialize_config(config)?)
+        .err()
+        .expect("the name should be rejected");
+    assert!(format!("{err:#}").contains("invalid D-Bus name"), "{err:#}");
+    Ok(())
+}
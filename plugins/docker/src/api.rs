@@ -0,0 +1,227 @@
+//! Minimal client of the Docker Engine API, over the unix socket of the daemon.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, de::DeserializeOwned};
+
+pub struct DockerClient {
+    socket: PathBuf,
+    timeout: Duration,
+}
+
+/// Error returned when the daemon answers with an unexpected status code.
+#[derive(Debug, thiserror::Error)]
+#[error("the Docker daemon returned {status}: {message}")]
+pub struct StatusError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl DockerClient {
+    pub fn new(socket: PathBuf, timeout: Duration) -> Self {
+        Self { socket, timeout }
+    }
+
+    /// Lists the running containers.
+    pub fn containers(&self) -> anyhow::Result<Vec<ContainerSummary>> {
+        self.get("/containers/json")
+    }
+
+    /// Gets the current statistics of a container.
+    pub fn stats(&self, id: &str) -> anyhow::Result<ContainerStats> {
+        // one-shot: don't wait for a second sample to fill `precpu_stats`, we compute the deltas ourselves
+        self.get(&format!("/containers/{id}/stats?stream=false&one-shot=true"))
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> anyhow::Result<T> {
+        let mut stream = UnixStream::connect(&self.socket)
+            .with_context(|| format!("could not connect to the Docker daemon at {:?}", self.socket))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n\r\n"
+        )?;
+
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .with_context(|| format!("could not read the response to GET {path}"))?;
+        let (status, body) = parse_response(&response).with_context(|| format!("invalid response to GET {path}"))?;
+        if status != 200 {
+            let message = serde_json::from_slice::<ErrorMessage>(&body)
+                .map(|e| e.message)
+                .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned());
+            return Err(StatusError { status, message }.into());
+        }
+        serde_json::from_slice(&body).with_context(|| format!("could not parse the response to GET {path}"))
+    }
+}
+
+/// Parses an HTTP/1.1 response, returns its status code and its body.
+fn parse_response(response: &[u8]) -> anyhow::Result<(u16, Vec<u8>)> {
+    let end_of_head = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .context("incomplete HTTP header")?;
+    let head = std::str::from_utf8(&response[..end_of_head]).context("HTTP header is not valid utf-8")?;
+    let body = &response[end_of_head + 4..];
+
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let status = status_line
+        .split(' ')
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .with_context(|| format!("invalid HTTP status line: {status_line}"))?;
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.eq_ignore_ascii_case("transfer-encoding") && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+    let body = if chunked { decode_chunked(body)? } else { body.to_vec() };
+    Ok((status, body))
+}
+
+fn decode_chunked(mut data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut body = Vec::with_capacity(data.len());
+    loop {
+        let end_of_size = data
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .context("incomplete chunk size")?;
+        let size = std::str::from_utf8(&data[..end_of_size])?;
+        // ignore the chunk extensions, if any
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).with_context(|| format!("invalid chunk size: {size}"))?;
+        if size == 0 {
+            return Ok(body);
+        }
+        let chunk = data
+            .get(end_of_size + 2..end_of_size + 2 + size)
+            .ok_or_else(|| anyhow!(io::Error::from(io::ErrorKind::UnexpectedEof)))?;
+        body.extend_from_slice(chunk);
+        data = data.get(end_of_size + 2 + size + 2..).unwrap_or_default();
+    }
+}
+
+#[derive(Deserialize)]
+struct ErrorMessage {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ContainerSummary {
+    pub id: String,
+    /// Names of the container, with a leading `/`.
+    pub names: Vec<String>,
+    pub image: String,
+}
+
+impl ContainerSummary {
+    /// Returns the main name of the container, without the leading `/`.
+    pub fn name(&self) -> &str {
+        self.names
+            .first()
+            .map(|n| n.trim_start_matches('/'))
+            .unwrap_or(&self.id)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContainerStats {
+    pub cpu_stats: CpuStats,
+    pub memory_stats: MemoryStats,
+    /// Network counters, per interface. Absent if the container uses the network of the host.
+    #[serde(default)]
+    pub networks: HashMap<String, NetworkStats>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CpuStats {
+    pub cpu_usage: CpuUsage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CpuUsage {
+    /// Total CPU time, in nanoseconds.
+    pub total_usage: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemoryStats {
+    pub usage: Option<u64>,
+    #[serde(default)]
+    pub stats: HashMap<String, u64>,
+}
+
+impl MemoryStats {
+    /// Returns the memory used by the container, without the inactive page cache, like `docker stats`.
+    pub fn used(&self) -> Option<u64> {
+        let usage = self.usage?;
+        // cgroup v1: total_inactive_file, cgroup v2: inactive_file
+        let cache = self
+            .stats
+            .get("total_inactive_file")
+            .or_else(|| self.stats.get("inactive_file"))
+            .copied()
+            .unwrap_or(0);
+        Some(usage.saturating_sub(cache))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NetworkStats {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response() {
+        let (status, body) =
+            parse_response(b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n[]")
+                .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, b"[]");
+
+        let chunked = b"HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n{\"me\r\n1b;ext=1\r\nssage\":\"No such container\"}\r\n0\r\n\r\n";
+        let (status, body) = parse_response(chunked).unwrap();
+        assert_eq!(status, 404);
+        assert_eq!(body, br#"{"message":"No such container"}"#);
+
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nabc").is_err());
+    }
+
+    #[test]
+    fn stats() {
+        let stats = r#"{
+            "read": "2025-03-04T10:12:05.276427358Z",
+            "cpu_stats": {"cpu_usage": {"total_usage": 1230000000}, "system_cpu_usage": 98700000000, "online_cpus": 4},
+            "memory_stats": {"usage": 52428800, "stats": {"inactive_file": 10485760, "file": 20971520}, "limit": 8331689984},
+            "networks": {"eth0": {"rx_bytes": 5000, "rx_packets": 40, "tx_bytes": 1200, "tx_packets": 12}}
+        }"#;
+        let stats: ContainerStats = serde_json::from_str(stats).unwrap();
+        assert_eq!(stats.cpu_stats.cpu_usage.total_usage, 1230000000);
+        assert_eq!(stats.memory_stats.used(), Some(41943040));
+        assert_eq!(stats.networks["eth0"].rx_bytes, 5000);
+
+        // network_mode: host
+        let stats = r#"{"cpu_stats": {"cpu_usage": {"total_usage": 5}}, "memory_stats": {}}"#;
+        let stats: ContainerStats = serde_json::from_str(stats).unwrap();
+        assert_eq!(stats.memory_stats.used(), None);
+        assert!(stats.networks.is_empty());
+    }
+}
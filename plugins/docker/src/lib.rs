@@ -0,0 +1,256 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use api::DockerClient;
+
+mod api;
+mod metrics;
+mod source;
+
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+pub struct DockerPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for DockerPlugin {
+    fn name() -> &'static str {
+        "docker"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Filesystem(PathBuf::from(DEFAULT_SOCKET))])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(DockerPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let client = DockerClient::new(self.config.socket.clone(), self.config.timeout);
+        let containers = client
+            .containers()
+            .context("failed to list the containers, is the Docker daemon running?")?;
+        log::info!("Found {} running containers.", containers.len());
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::DockerSource::new(client, metrics);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("containers", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// Unix socket of the Docker daemon.
+    socket: PathBuf,
+
+    /// Maximum time to wait for each response of the Docker daemon.
+    #[serde(with = "humantime_serde")]
+    timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            flush_interval: Duration::from_secs(15),
+            socket: PathBuf::from(DEFAULT_SOCKET),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixListener,
+        path::Path,
+        sync::{
+            Arc,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::{PrefixedUnit, Unit},
+    };
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    const CONTAINERS: &str = r#"[
+        {"Id": "8dfafdbc3a40", "Names": ["/web"], "Image": "nginx:1.27", "State": "running"}
+    ]"#;
+
+    /// Starts a fake Docker daemon whose only container uses one second of CPU time,
+    /// receives 1000 bytes and sends 100 bytes between two requests.
+    fn fake_daemon(socket: &Path) {
+        let listener = UnixListener::bind(socket).unwrap();
+        let requests = Arc::new(AtomicU64::new(0));
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream).read_line(&mut request_line).unwrap();
+                let (status, body) = if request_line.starts_with("GET /containers/json ") {
+                    ("200 OK", CONTAINERS.to_owned())
+                } else if request_line.starts_with("GET /containers/8dfafdbc3a40/stats?") {
+                    let n = requests.fetch_add(1, Ordering::Relaxed) + 1;
+                    let stats = format!(
+                        r#"{{"cpu_stats": {{"cpu_usage": {{"total_usage": {}}}}},
+                        "memory_stats": {{"usage": 5000, "stats": {{"inactive_file": 1000}}}},
+                        "networks": {{"eth0": {{"rx_bytes": {}, "tx_bytes": {}}}}}}}"#,
+                        n * 1_000_000_000,
+                        n * 1000,
+                        n * 100
+                    );
+                    ("200 OK", stats)
+                } else {
+                    ("404 Not Found", String::from(r#"{"message":"page not found"}"#))
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+    }
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<DockerPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    #[test]
+    fn container_stats() {
+        let tmp = tempdir().unwrap();
+        let socket = tmp.path().join("docker.sock");
+        fake_daemon(&socket);
+        let config = Config {
+            poll_interval: Duration::from_millis(100),
+            socket,
+            ..Config::default()
+        };
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<u64>("docker_cpu_time_delta", PrefixedUnit::nano(Unit::Second))
+            .expect_metric::<f64>("docker_cpu_percent", Unit::Percent)
+            .expect_metric::<u64>("docker_memory_usage", Unit::Byte)
+            .expect_metric::<u64>("docker_network_bytes_delta", Unit::Byte)
+            .expect_source("docker", "containers");
+
+        let source = SourceName::from_str("docker", "containers");
+        let runtime = RuntimeExpectations::new()
+            .test_source(
+                source.clone(),
+                || (),
+                |ctx| {
+                    // first measurement: no delta yet
+                    let m = ctx.measurements();
+                    let values: Vec<_> = m.iter().map(|m| m.value.clone()).collect();
+                    assert_eq!(values, vec![WrappedMeasurementValue::U64(4000)]);
+                    let point = m.iter().next().unwrap();
+                    assert_eq!(point.consumer.kind(), "container");
+                    assert_eq!(point.consumer.id_display().to_string(), "8dfafdbc3a40");
+                    let attrs: Vec<_> = point.attributes().map(|(k, v)| (k, v.to_string())).collect();
+                    assert_eq!(
+                        attrs,
+                        vec![
+                            ("container_name", String::from("web")),
+                            ("container_image", String::from("nginx:1.27"))
+                        ]
+                    );
+                },
+            )
+            .test_source(
+                source,
+                || (),
+                |ctx| {
+                    let m = ctx.measurements();
+                    let values: Vec<_> = m
+                        .iter()
+                        .filter_map(|m| match m.value {
+                            WrappedMeasurementValue::U64(v) => Some(v),
+                            WrappedMeasurementValue::F64(_) => None,
+                        })
+                        .collect();
+                    assert_eq!(values, vec![4000, 1_000_000_000, 1000, 100]);
+                    assert_eq!(m.len(), 5);
+                },
+            );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn no_daemon() {
+        let tmp = tempdir().unwrap();
+        let config = Config {
+            socket: tmp.path().join("docker.sock"),
+            ..Config::default()
+        };
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+}
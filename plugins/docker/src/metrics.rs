@@ -0,0 +1,43 @@
+use alumet::{
+    metrics::TypedMetricId,
+    plugin::AlumetPluginStart,
+    units::{PrefixedUnit, Unit},
+};
+
+pub struct Metrics {
+    /// CPU time since the previous measurement.
+    pub cpu_time_delta: TypedMetricId<u64>,
+    /// CPU time divided by the time elapsed since the previous measurement.
+    pub cpu_percent: TypedMetricId<f64>,
+    /// Memory used, without the inactive page cache.
+    pub memory_usage: TypedMetricId<u64>,
+    /// Network traffic since the previous measurement.
+    pub network_bytes_delta: TypedMetricId<u64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            cpu_time_delta: alumet.create_metric(
+                "docker_cpu_time_delta",
+                PrefixedUnit::nano(Unit::Second),
+                "CPU time used by the container since the previous measurement",
+            )?,
+            cpu_percent: alumet.create_metric(
+                "docker_cpu_percent",
+                Unit::Percent,
+                "Part of the CPU used by the container since the previous measurement (1 core fully used = 100%)",
+            )?,
+            memory_usage: alumet.create_metric(
+                "docker_memory_usage",
+                Unit::Byte,
+                "Memory used by the container, without the inactive page cache",
+            )?,
+            network_bytes_delta: alumet.create_metric(
+                "docker_network_bytes_delta",
+                Unit::Byte,
+                "Bytes received or sent by the container since the previous measurement",
+            )?,
+        })
+    }
+}
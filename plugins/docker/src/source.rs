@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{
+    api::{ContainerStats, DockerClient, StatusError},
+    metrics::Metrics,
+};
+
+/// Measurement source that gets the statistics of the running containers from the Docker daemon.
+pub struct DockerSource {
+    client: DockerClient,
+    metrics: Metrics,
+    /// Counters of the containers, at the previous measurement.
+    previous: HashMap<String, ContainerCounters>,
+}
+
+struct ContainerCounters {
+    timestamp: Timestamp,
+    cpu_time: u64,
+    /// Received and sent bytes, per interface.
+    networks: HashMap<String, (u64, u64)>,
+}
+
+impl DockerSource {
+    pub fn new(client: DockerClient, metrics: Metrics) -> Self {
+        Self {
+            client,
+            metrics,
+            previous: HashMap::new(),
+        }
+    }
+}
+
+impl Source for DockerSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // The daemon can be restarted, the next poll may work.
+        let containers = self
+            .client
+            .containers()
+            .context("could not list the containers")
+            .map_err(PollError::CanRetry)?;
+
+        let mut current = HashMap::with_capacity(containers.len());
+        for container in containers {
+            let stats = match self.client.stats(&container.id) {
+                Ok(stats) => stats,
+                Err(e) if e.downcast_ref::<StatusError>().is_some_and(|e| e.status == 404) => {
+                    log::debug!("Container {} has stopped before its measurement.", container.id);
+                    continue;
+                }
+                Err(e) => {
+                    return Err(PollError::CanRetry(
+                        e.context(format!("could not get the stats of container {}", container.id)),
+                    ));
+                }
+            };
+
+            let consumer = ResourceConsumer::custom("container", container.id.clone());
+            let attrs = vec![
+                ("container_name", AttributeValue::String(container.name().to_owned())),
+                ("container_image", AttributeValue::String(container.image.clone())),
+            ];
+            let point = |metric, value| {
+                MeasurementPoint::new(t, metric, Resource::LocalMachine, consumer.clone(), value)
+                    .with_attr_vec(attrs.clone())
+            };
+
+            if let Some(memory) = stats.memory_stats.used() {
+                measurements.push(point(self.metrics.memory_usage, memory));
+            }
+
+            let counters = ContainerCounters::new(t, &stats);
+            if let Some(previous) = self.previous.get(&container.id) {
+                if let Some(cpu_time) = counters.cpu_time.checked_sub(previous.cpu_time) {
+                    measurements.push(point(self.metrics.cpu_time_delta, cpu_time));
+                    let elapsed = t.duration_since(previous.timestamp)?.as_nanos() as f64;
+                    if elapsed > 0.0 {
+                        let percent = cpu_time as f64 / elapsed * 100.0;
+                        measurements.push(
+                            MeasurementPoint::new(
+                                t,
+                                self.metrics.cpu_percent,
+                                Resource::LocalMachine,
+                                consumer.clone(),
+                                percent,
+                            )
+                            .with_attr_vec(attrs.clone()),
+                        );
+                    }
+                } else {
+                    log::debug!("The CPU time of container {} has been reset.", container.id);
+                }
+
+                for (interface, (rx, tx)) in &counters.networks {
+                    let Some((prev_rx, prev_tx)) = previous.networks.get(interface) else {
+                        continue;
+                    };
+                    for (kind, value, prev) in [("rx", rx, prev_rx), ("tx", tx, prev_tx)] {
+                        if let Some(delta) = value.checked_sub(*prev) {
+                            measurements.push(
+                                point(self.metrics.network_bytes_delta, delta)
+                                    .with_attr("kind", kind)
+                                    .with_attr("interface", interface.clone()),
+                            );
+                        }
+                    }
+                }
+            }
+            current.insert(container.id, counters);
+        }
+        // forget the containers that have stopped
+        self.previous = current;
+        Ok(())
+    }
+}
+
+impl ContainerCounters {
+    fn new(timestamp: Timestamp, stats: &ContainerStats) -> Self {
+        Self {
+            timestamp,
+            cpu_time: stats.cpu_stats.cpu_usage.total_usage,
+            networks: stats
+                .networks
+                .iter()
+                .map(|(name, net)| (name.clone(), (net.rx_bytes, net.tx_bytes)))
+                .collect(),
+        }
+    }
+}
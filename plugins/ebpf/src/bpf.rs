@@ -0,0 +1,462 @@
+//! Minimal wrappers around the `bpf` system call.
+//!
+//! Only the commands needed by the plugin are supported: creating maps, reading and deleting
+//! their elements, and loading programs. See `include/uapi/linux/bpf.h` in the Linux sources.
+
+use std::{
+    ffi::CStr,
+    io,
+    mem::size_of,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use anyhow::anyhow;
+
+// commands
+const BPF_MAP_CREATE: libc::c_long = 0;
+const BPF_MAP_LOOKUP_ELEM: libc::c_long = 1;
+#[cfg(test)]
+const BPF_MAP_UPDATE_ELEM: libc::c_long = 2;
+const BPF_MAP_DELETE_ELEM: libc::c_long = 3;
+const BPF_MAP_GET_NEXT_KEY: libc::c_long = 4;
+const BPF_PROG_LOAD: libc::c_long = 5;
+
+/// Flag of BPF_MAP_UPDATE_ELEM: only create a new element.
+pub const BPF_NOEXIST: u64 = 1;
+
+/// Type of a BPF map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum MapType {
+    Hash = 1,
+    PercpuArray = 6,
+}
+
+/// Type of a BPF program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ProgramType {
+    Tracepoint = 5,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct MapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+    inner_map_fd: u32,
+    numa_node: u32,
+    map_name: [u8; 16],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct MapElemAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    /// `value` or `next_key`
+    value: u64,
+    flags: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+}
+
+/// Calls `bpf(cmd, attr, sizeof(attr))`.
+fn bpf<A>(cmd: libc::c_long, attr: &mut A) -> io::Result<libc::c_long> {
+    // SAFETY: attr is a repr(C) struct that matches the beginning of `union bpf_attr` for the command,
+    // the kernel zero-extends it to the full size of the union.
+    let res = unsafe { libc::syscall(libc::SYS_bpf, cmd, attr as *mut A, size_of::<A>() as libc::c_uint) };
+    if res < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(res)
+    }
+}
+
+/// Copies `name` into a fixed-size buffer, truncated to 15 bytes to keep a terminating null byte.
+fn object_name(name: &str) -> [u8; 16] {
+    let mut buf = [0u8; 16];
+    let len = name.len().min(15);
+    buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+    buf
+}
+
+/// A BPF map, with keys of type `K` and values of type `V`.
+///
+/// The map is destroyed when it is dropped, unless a loaded program still uses it.
+pub struct Map<K, V> {
+    fd: OwnedFd,
+    _types: std::marker::PhantomData<(K, V)>,
+}
+
+impl<K: Copy + Default, V: Copy + Default> Map<K, V> {
+    /// Creates a new map.
+    ///
+    /// For per-cpu maps, `V` is the type of the value of each cpu.
+    pub fn create(map_type: MapType, name: &str, max_entries: u32) -> io::Result<Self> {
+        let mut attr = MapCreateAttr {
+            map_type: map_type as u32,
+            key_size: size_of::<K>() as u32,
+            value_size: size_of::<V>() as u32,
+            max_entries,
+            map_name: object_name(name),
+            ..Default::default()
+        };
+        let fd = bpf(BPF_MAP_CREATE, &mut attr)?;
+        // SAFETY: the kernel has returned a new file descriptor
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+        Ok(Self {
+            fd,
+            _types: std::marker::PhantomData,
+        })
+    }
+
+    /// Returns the file descriptor of the map, to be referenced by a program.
+    pub fn raw_fd(&self) -> i32 {
+        self.fd.as_raw_fd()
+    }
+
+    /// Returns the value associated to `key`, or `None` if there is no such element.
+    ///
+    /// This does not work with per-cpu maps, because they contain one value per cpu.
+    pub fn lookup(&self, key: &K) -> io::Result<Option<V>> {
+        let mut value = V::default();
+        let mut attr = MapElemAttr {
+            map_fd: self.raw_fd() as u32,
+            key: key as *const K as u64,
+            value: &mut value as *mut V as u64,
+            ..Default::default()
+        };
+        match bpf(BPF_MAP_LOOKUP_ELEM, &mut attr) {
+            Ok(_) => Ok(Some(value)),
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sets the value associated to `key`, with the given `flags` (0 or `BPF_NOEXIST`).
+    ///
+    /// Only the tests need to fill the maps from the userspace.
+    #[cfg(test)]
+    pub fn update(&self, key: &K, value: &V, flags: u64) -> io::Result<()> {
+        let mut attr = MapElemAttr {
+            map_fd: self.raw_fd() as u32,
+            key: key as *const K as u64,
+            value: value as *const V as u64,
+            flags,
+            ..Default::default()
+        };
+        bpf(BPF_MAP_UPDATE_ELEM, &mut attr)?;
+        Ok(())
+    }
+
+    /// Deletes the element associated to `key`, if it exists.
+    pub fn delete(&self, key: &K) -> io::Result<()> {
+        let mut attr = MapElemAttr {
+            map_fd: self.raw_fd() as u32,
+            key: key as *const K as u64,
+            ..Default::default()
+        };
+        match bpf(BPF_MAP_DELETE_ELEM, &mut attr) {
+            Ok(_) => Ok(()),
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns all the elements of the map.
+    ///
+    /// The map can be modified by a program during the iteration: an element that is
+    /// added or deleted at the same time may be missed.
+    pub fn entries(&self) -> io::Result<Vec<(K, V)>> {
+        let mut res = Vec::new();
+        let mut key: Option<K> = None;
+        loop {
+            let mut next_key = K::default();
+            let mut attr = MapElemAttr {
+                map_fd: self.raw_fd() as u32,
+                // a null key returns the first key
+                key: key.as_ref().map(|k| k as *const K as u64).unwrap_or(0),
+                value: &mut next_key as *mut K as u64,
+                ..Default::default()
+            };
+            match bpf(BPF_MAP_GET_NEXT_KEY, &mut attr) {
+                Ok(_) => (),
+                Err(e) if e.raw_os_error() == Some(libc::ENOENT) => break, // no more keys
+                Err(e) => return Err(e),
+            }
+            if let Some(value) = self.lookup(&next_key)? {
+                res.push((next_key, value));
+            }
+            key = Some(next_key);
+        }
+        Ok(res)
+    }
+}
+
+/// A BPF program, loaded in the kernel.
+pub struct Program {
+    fd: OwnedFd,
+}
+
+impl Program {
+    /// Loads a program, which must pass the verifier of the kernel.
+    ///
+    /// If the program is rejected, the error contains the log of the verifier.
+    pub fn load(prog_type: ProgramType, name: &str, insns: &[Insn]) -> anyhow::Result<Self> {
+        let license = c"GPL";
+        let mut log = vec![0u8; 64 * 1024];
+        let mut attr = ProgLoadAttr {
+            prog_type: prog_type as u32,
+            insn_cnt: insns.len() as u32,
+            insns: insns.as_ptr() as u64,
+            license: license.as_ptr() as u64,
+            log_level: 1,
+            log_size: log.len() as u32,
+            log_buf: log.as_mut_ptr() as u64,
+            prog_name: object_name(name),
+            ..Default::default()
+        };
+        match bpf(BPF_PROG_LOAD, &mut attr) {
+            Ok(fd) => {
+                // SAFETY: the kernel has returned a new file descriptor
+                let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+                Ok(Self { fd })
+            }
+            Err(e) => {
+                let log = CStr::from_bytes_until_nul(&log)
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                if log.is_empty() {
+                    Err(anyhow!(e).context(format!("failed to load the BPF program {name}")))
+                } else {
+                    Err(anyhow!(e).context(format!("the BPF program {name} was rejected by the verifier:\n{log}")))
+                }
+            }
+        }
+    }
+
+    pub fn raw_fd(&self) -> i32 {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// A BPF instruction.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Insn {
+    code: u8,
+    /// Destination register in the low nibble, source register in the high nibble.
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+/// A BPF register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+#[allow(dead_code)] // not all the registers are used by our program
+pub enum Reg {
+    R0 = 0,
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+    R8,
+    R9,
+    /// Read-only frame pointer.
+    R10,
+}
+
+/// Size of a memory access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Size {
+    W = 0x00,
+    DW = 0x18,
+}
+
+/// Helper functions that can be called by the programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum Helper {
+    MapLookupElem = 1,
+    MapUpdateElem = 2,
+    KtimeGetNs = 5,
+    GetCurrentPidTgid = 14,
+    GetCurrentCgroupId = 80,
+}
+
+// instruction classes
+const BPF_LD: u8 = 0x00;
+const BPF_LDX: u8 = 0x01;
+const BPF_ST: u8 = 0x02;
+const BPF_STX: u8 = 0x03;
+const BPF_JMP: u8 = 0x05;
+const BPF_ALU64: u8 = 0x07;
+// modes
+const BPF_IMM: u8 = 0x00;
+const BPF_MEM: u8 = 0x60;
+const BPF_ATOMIC: u8 = 0xc0;
+// sources
+const BPF_K: u8 = 0x00;
+const BPF_X: u8 = 0x08;
+// operations
+const BPF_ADD: u8 = 0x00;
+const BPF_SUB: u8 = 0x10;
+const BPF_RSH: u8 = 0x70;
+const BPF_MOV: u8 = 0xb0;
+const BPF_JEQ: u8 = 0x10;
+const BPF_CALL: u8 = 0x80;
+const BPF_EXIT: u8 = 0x90;
+/// In `ld_imm64`, indicates that the immediate value is the file descriptor of a map.
+const BPF_PSEUDO_MAP_FD: Reg = Reg::R1;
+
+impl Insn {
+    const fn new(code: u8, dst: Reg, src: Reg, off: i16, imm: i32) -> Self {
+        Self {
+            code,
+            regs: (src as u8) << 4 | dst as u8,
+            off,
+            imm,
+        }
+    }
+
+    /// `dst = src`
+    pub const fn mov64(dst: Reg, src: Reg) -> Self {
+        Self::new(BPF_ALU64 | BPF_MOV | BPF_X, dst, src, 0, 0)
+    }
+
+    /// `dst = imm`
+    pub const fn mov64_imm(dst: Reg, imm: i32) -> Self {
+        Self::new(BPF_ALU64 | BPF_MOV | BPF_K, dst, Reg::R0, 0, imm)
+    }
+
+    /// `dst += imm`
+    pub const fn add64_imm(dst: Reg, imm: i32) -> Self {
+        Self::new(BPF_ALU64 | BPF_ADD | BPF_K, dst, Reg::R0, 0, imm)
+    }
+
+    /// `dst -= src`
+    pub const fn sub64(dst: Reg, src: Reg) -> Self {
+        Self::new(BPF_ALU64 | BPF_SUB | BPF_X, dst, src, 0, 0)
+    }
+
+    /// `dst >>= imm`
+    pub const fn rsh64_imm(dst: Reg, imm: i32) -> Self {
+        Self::new(BPF_ALU64 | BPF_RSH | BPF_K, dst, Reg::R0, 0, imm)
+    }
+
+    /// `dst = *(size *)(src + off)`
+    pub const fn load(size: Size, dst: Reg, src: Reg, off: i16) -> Self {
+        Self::new(BPF_LDX | BPF_MEM | size as u8, dst, src, off, 0)
+    }
+
+    /// `*(size *)(dst + off) = src`
+    pub const fn store(size: Size, dst: Reg, off: i16, src: Reg) -> Self {
+        Self::new(BPF_STX | BPF_MEM | size as u8, dst, src, off, 0)
+    }
+
+    /// `*(size *)(dst + off) = imm`
+    pub const fn store_imm(size: Size, dst: Reg, off: i16, imm: i32) -> Self {
+        Self::new(BPF_ST | BPF_MEM | size as u8, dst, Reg::R0, off, imm)
+    }
+
+    /// `lock *(u64 *)(dst + off) += src`
+    pub const fn atomic_add64(dst: Reg, off: i16, src: Reg) -> Self {
+        Self::new(BPF_STX | BPF_ATOMIC | Size::DW as u8, dst, src, off, BPF_ADD as i32)
+    }
+
+    /// `dst = map`, using two instructions.
+    pub const fn load_map_fd(dst: Reg, map_fd: i32) -> [Self; 2] {
+        [
+            Self::new(BPF_LD | BPF_IMM | Size::DW as u8, dst, BPF_PSEUDO_MAP_FD, 0, map_fd),
+            Self::new(0, Reg::R0, Reg::R0, 0, 0),
+        ]
+    }
+
+    /// `if dst == imm goto pc + 1 + off`
+    pub const fn jeq_imm(dst: Reg, imm: i32, off: i16) -> Self {
+        Self::new(BPF_JMP | BPF_JEQ | BPF_K, dst, Reg::R0, off, imm)
+    }
+
+    /// Returns the same instruction with another offset, to patch the target of a jump.
+    pub const fn with_offset(self, off: i16) -> Self {
+        Self { off, ..self }
+    }
+
+    /// `r0 = helper(r1, r2, r3, r4, r5)`
+    pub const fn call(helper: Helper) -> Self {
+        Self::new(BPF_JMP | BPF_CALL, Reg::R0, Reg::R0, 0, helper as i32)
+    }
+
+    /// `return r0`
+    pub const fn exit() -> Self {
+        Self::new(BPF_JMP | BPF_EXIT, Reg::R0, Reg::R0, 0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Insn, Reg, Size};
+
+    #[test]
+    fn encoding() {
+        // compare with the output of `llvm-objdump -d` on programs compiled by clang
+        let bytes = |insn: Insn| -> [u8; 8] {
+            let mut res = [insn.code, insn.regs, 0, 0, 0, 0, 0, 0];
+            res[2..4].copy_from_slice(&insn.off.to_le_bytes());
+            res[4..].copy_from_slice(&insn.imm.to_le_bytes());
+            res
+        };
+        assert_eq!(bytes(Insn::mov64(Reg::R6, Reg::R1)), [0xbf, 0x16, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            bytes(Insn::add64_imm(Reg::R2, -4)),
+            [0x07, 0x02, 0, 0, 0xfc, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            bytes(Insn::load(Size::DW, Reg::R1, Reg::R0, 0)),
+            [0x79, 0x01, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            bytes(Insn::store(Size::W, Reg::R10, -16, Reg::R0)),
+            [0x63, 0x0a, 0xf0, 0xff, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            bytes(Insn::store_imm(Size::W, Reg::R10, -4, 0)),
+            [0x62, 0x0a, 0xfc, 0xff, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            bytes(Insn::atomic_add64(Reg::R0, 0, Reg::R7)),
+            [0xdb, 0x70, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(bytes(Insn::load_map_fd(Reg::R1, 4)[0]), [0x18, 0x11, 0, 0, 4, 0, 0, 0]);
+        assert_eq!(bytes(Insn::jeq_imm(Reg::R0, 0, 3)), [0x15, 0x00, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes(Insn::rsh64_imm(Reg::R0, 32)), [0x77, 0x00, 0, 0, 32, 0, 0, 0]);
+        assert_eq!(
+            bytes(Insn::call(super::Helper::KtimeGetNs)),
+            [0x85, 0, 0, 0, 5, 0, 0, 0]
+        );
+        assert_eq!(bytes(Insn::exit()), [0x95, 0, 0, 0, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Resolution of the cgroup ids returned by `bpf_get_current_cgroup_id`.
+
+use std::{
+    collections::HashMap,
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+/// Finds where the unified hierarchy (cgroup v2) is mounted.
+pub fn find_cgroup2_mount() -> anyhow::Result<Option<PathBuf>> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").context("could not read the mount points")?;
+    Ok(parse_cgroup2_mount(&mountinfo))
+}
+
+fn parse_cgroup2_mount(mountinfo: &str) -> Option<PathBuf> {
+    // 36 25 0:31 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:9 - cgroup2 cgroup2 rw
+    mountinfo.lines().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        let fs_type = fs.split(' ').next()?;
+        let mount_point = mount.split(' ').nth(4)?;
+        (fs_type == "cgroup2").then(|| PathBuf::from(mount_point))
+    })
+}
+
+/// Maps the ids of the cgroups to their path.
+///
+/// The id of a cgroup v2 is the inode number of its directory.
+pub struct CgroupResolver {
+    root: PathBuf,
+    paths: HashMap<u64, String>,
+}
+
+impl CgroupResolver {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            paths: HashMap::new(),
+        }
+    }
+
+    /// Returns the path of the cgroup, relative to the root of the hierarchy, such as `/system.slice/cron.service`.
+    pub fn path(&self, id: u64) -> Option<&str> {
+        self.paths.get(&id).map(String::as_str)
+    }
+
+    /// Scans the hierarchy again, to find the cgroups that have been created since the last scan.
+    pub fn refresh(&mut self) -> io::Result<()> {
+        self.paths.clear();
+        let root_id = std::fs::metadata(&self.root)?.ino();
+        self.paths.insert(root_id, String::from("/"));
+        let root = self.root.clone();
+        self.scan(&root, "")
+    }
+
+    fn scan(&mut self, dir: &Path, prefix: &str) -> io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let metadata = match entry.metadata() {
+                Ok(m) => m,
+                // the cgroup has been removed in the meantime
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if !metadata.is_dir() {
+                continue;
+            }
+            let path = format!("{prefix}/{}", entry.file_name().to_string_lossy());
+            match self.scan(&entry.path(), &path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+            self.paths.insert(metadata.ino(), path);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::fs::MetadataExt, path::PathBuf};
+
+    use super::{CgroupResolver, parse_cgroup2_mount};
+
+    #[test]
+    fn mount_point() {
+        let mountinfo = "24 29 0:22 / /sys rw,nosuid,nodev,noexec,relatime shared:7 - sysfs sysfs rw
+35 24 0:30 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:9 - cgroup2 cgroup2 rw,nsdelegate
+";
+        assert_eq!(parse_cgroup2_mount(mountinfo), Some(PathBuf::from("/sys/fs/cgroup")));
+
+        let hybrid = "33 24 0:28 / /sys/fs/cgroup/unified rw,nosuid shared:10 - cgroup2 cgroup2 rw
+34 24 0:29 / /sys/fs/cgroup/cpu rw,nosuid shared:11 - cgroup cgroup rw,cpu
+";
+        assert_eq!(
+            parse_cgroup2_mount(hybrid),
+            Some(PathBuf::from("/sys/fs/cgroup/unified"))
+        );
+        assert_eq!(
+            parse_cgroup2_mount("34 24 0:29 / /sys/fs/cgroup/cpu rw - cgroup cgroup rw,cpu"),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve() {
+        let root = tempfile::tempdir().unwrap();
+        let service = root.path().join("system.slice/cron.service");
+        std::fs::create_dir_all(&service).unwrap();
+        std::fs::write(service.join("cgroup.procs"), "").unwrap();
+        let id = |p: &std::path::Path| std::fs::metadata(p).unwrap().ino();
+
+        let mut resolver = CgroupResolver::new(root.path().to_owned());
+        assert_eq!(resolver.path(id(&service)), None);
+        resolver.refresh().unwrap();
+        assert_eq!(resolver.path(id(root.path())), Some("/"));
+        assert_eq!(
+            resolver.path(id(&root.path().join("system.slice"))),
+            Some("/system.slice")
+        );
+        assert_eq!(resolver.path(id(&service)), Some("/system.slice/cron.service"));
+        assert_eq!(resolver.path(id(&service.join("cgroup.procs"))), None);
+    }
+}
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use cgroup::CgroupResolver;
+use program::SchedSwitchProgram;
+use tracepoint::AttachedTracepoint;
+
+mod bpf;
+mod cgroup;
+mod metrics;
+mod program;
+mod source;
+mod tracepoint;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct EbpfPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for EbpfPlugin {
+    fn name() -> &'static str {
+        "ebpf"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::PerfEvents])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(config.max_tasks > 0, "max_tasks must be greater than zero");
+        Ok(Box::new(EbpfPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        if !self.config.per_process && !self.config.per_cgroup {
+            log::warn!("Both per_process and per_cgroup are disabled, nothing to measure.");
+            return Ok(());
+        }
+
+        let cgroups = if self.config.per_cgroup {
+            match cgroup::find_cgroup2_mount()? {
+                Some(root) => {
+                    let mut resolver = CgroupResolver::new(root);
+                    resolver.refresh().context("could not scan the cgroup hierarchy")?;
+                    Some(resolver)
+                }
+                None => {
+                    log::warn!("No cgroup v2 hierarchy is mounted, the CPU time will not be measured per cgroup.");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let program = SchedSwitchProgram::load(self.config.max_tasks)
+            .context("could not load the BPF program, does the agent have the capabilities CAP_BPF and CAP_PERFMON?")?;
+        let id = tracepoint::tracepoint_id("sched", "sched_switch")?;
+        let attached = AttachedTracepoint::attach(&program.program, id)?;
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::SchedSource::new(program, attached, metrics, self.config.per_process, cgroups);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("sched_switch", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// Report the CPU time of each process.
+    per_process: bool,
+
+    /// Report the CPU time of each cgroup (v2).
+    per_cgroup: bool,
+
+    /// Maximum number of (process, cgroup) pairs that can be tracked between two measurements.
+    ///
+    /// The CPU time of the processes that do not fit in the BPF map is not measured.
+    max_tasks: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(5),
+            per_process: true,
+            per_cgroup: true,
+            max_tasks: 16384,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use alumet::{
+    metrics::TypedMetricId,
+    plugin::AlumetPluginStart,
+    units::{PrefixedUnit, Unit},
+};
+
+#[derive(Clone)]
+pub struct Metrics {
+    /// CPU time since the previous measurement.
+    pub cpu_time_delta: TypedMetricId<u64>,
+    /// CPU time divided by the time elapsed since the previous measurement.
+    pub cpu_percent: TypedMetricId<f64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            cpu_time_delta: alumet.create_metric(
+                "ebpf_cpu_time_delta",
+                PrefixedUnit::nano(Unit::Second),
+                "Time spent on the CPU since the previous measurement, accounted at each context switch",
+            )?,
+            cpu_percent: alumet.create_metric(
+                "ebpf_cpu_percent",
+                Unit::Percent,
+                "Part of the CPU used since the previous measurement (1 core fully used = 100%)",
+            )?,
+        })
+    }
+}
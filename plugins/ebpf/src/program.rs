@@ -0,0 +1,170 @@
+//! BPF program that accounts the CPU time of the processes on `sched:sched_switch`.
+//!
+//! At each context switch, the program adds the time elapsed since the previous switch
+//! on the same CPU to the task that is being switched out (which is still the current task),
+//! in a hash map indexed by process (thread group) and cgroup.
+
+use std::io;
+
+use crate::bpf::{BPF_NOEXIST, Helper, Insn, Map, MapType, Program, ProgramType, Reg, Size};
+
+/// Key of the map of CPU times.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskKey {
+    /// Id of the cgroup v2 of the process, which is the inode number of its directory.
+    pub cgroup_id: u64,
+    /// Process id (id of the thread group).
+    pub pid: u32,
+    _pad: u32,
+}
+
+impl TaskKey {
+    #[cfg(test)]
+    pub fn new(cgroup_id: u64, pid: u32) -> Self {
+        Self {
+            cgroup_id,
+            pid,
+            _pad: 0,
+        }
+    }
+}
+
+/// The maps and the program, loaded in the kernel.
+pub struct SchedSwitchProgram {
+    /// CPU time, in nanoseconds, since the program has been attached.
+    pub cpu_times: Map<TaskKey, u64>,
+    /// Timestamp of the last context switch, per CPU.
+    _last_switch: Map<u32, u64>,
+    pub program: Program,
+}
+
+impl SchedSwitchProgram {
+    /// Creates the maps and loads the program.
+    ///
+    /// `max_entries` is the maximum number of (process, cgroup) pairs that can be tracked at the same time.
+    pub fn load(max_entries: u32) -> anyhow::Result<Self> {
+        let cpu_times = Map::create(MapType::Hash, "alumet_cpu_time", max_entries)?;
+        let last_switch = Map::create(MapType::PercpuArray, "alumet_switch", 1)?;
+        let insns = instructions(last_switch.raw_fd(), cpu_times.raw_fd());
+        let program = Program::load(ProgramType::Tracepoint, "alumet_sched", &insns)?;
+        Ok(Self {
+            cpu_times,
+            _last_switch: last_switch,
+            program,
+        })
+    }
+
+    /// Reads the CPU time of each (process, cgroup) pair.
+    pub fn cpu_times(&self) -> io::Result<Vec<(TaskKey, u64)>> {
+        self.cpu_times.entries()
+    }
+}
+
+fn instructions(last_switch_fd: i32, cpu_times_fd: i32) -> Vec<Insn> {
+    use Reg::*;
+
+    // stack layout
+    const ZERO: i16 = -4; // u32 key of last_switch
+    const KEY: i16 = -24; // TaskKey
+    const VALUE: i16 = -32; // u64 value of cpu_times
+
+    let mut insns = Vec::with_capacity(40);
+    // r7 = now
+    insns.push(Insn::call(Helper::KtimeGetNs));
+    insns.push(Insn::mov64(R7, R0));
+
+    // r0 = &last_switch[cpu]
+    insns.push(Insn::store_imm(Size::W, R10, ZERO, 0));
+    insns.extend(Insn::load_map_fd(R1, last_switch_fd));
+    insns.push(Insn::mov64(R2, R10));
+    insns.push(Insn::add64_imm(R2, ZERO.into()));
+    insns.push(Insn::call(Helper::MapLookupElem));
+    let exit_if_null = insns.len();
+    insns.push(Insn::jeq_imm(R0, 0, 0)); // patched below
+
+    // r7 = now - last_switch[cpu]; last_switch[cpu] = now
+    insns.push(Insn::load(Size::DW, R1, R0, 0));
+    insns.push(Insn::store(Size::DW, R0, 0, R7));
+    let exit_if_first = insns.len();
+    insns.push(Insn::jeq_imm(R1, 0, 0)); // first switch on this cpu, patched below
+    insns.push(Insn::sub64(R7, R1));
+
+    // key.pid = current tgid, skip the idle task
+    insns.push(Insn::call(Helper::GetCurrentPidTgid));
+    insns.push(Insn::rsh64_imm(R0, 32));
+    let exit_if_idle = insns.len();
+    insns.push(Insn::jeq_imm(R0, 0, 0)); // patched below
+    insns.push(Insn::store(Size::W, R10, KEY + 8, R0));
+    insns.push(Insn::store_imm(Size::W, R10, KEY + 12, 0));
+
+    // key.cgroup_id = current cgroup
+    insns.push(Insn::call(Helper::GetCurrentCgroupId));
+    insns.push(Insn::store(Size::DW, R10, KEY, R0));
+
+    // r0 = &cpu_times[key]
+    insns.extend(Insn::load_map_fd(R1, cpu_times_fd));
+    insns.push(Insn::mov64(R2, R10));
+    insns.push(Insn::add64_imm(R2, KEY.into()));
+    insns.push(Insn::call(Helper::MapLookupElem));
+    let insert_if_null = insns.len();
+    insns.push(Insn::jeq_imm(R0, 0, 0)); // patched below
+
+    // cpu_times[key] += r7
+    insns.push(Insn::atomic_add64(R0, 0, R7));
+    let exit = insns.len();
+    insns.push(Insn::mov64_imm(R0, 0));
+    insns.push(Insn::exit());
+
+    // cpu_times[key] = r7, if no other cpu has inserted the key in the meantime (it's very unlikely, ignore)
+    let insert = insns.len();
+    insns.push(Insn::store(Size::DW, R10, VALUE, R7));
+    insns.extend(Insn::load_map_fd(R1, cpu_times_fd));
+    insns.push(Insn::mov64(R2, R10));
+    insns.push(Insn::add64_imm(R2, KEY.into()));
+    insns.push(Insn::mov64(R3, R10));
+    insns.push(Insn::add64_imm(R3, VALUE.into()));
+    insns.push(Insn::mov64_imm(R4, BPF_NOEXIST as i32));
+    insns.push(Insn::call(Helper::MapUpdateElem));
+    insns.push(Insn::mov64_imm(R0, 0));
+    insns.push(Insn::exit());
+
+    // patch the jumps, relative to the next instruction
+    for (jump, target) in [
+        (exit_if_null, exit),
+        (exit_if_first, exit),
+        (exit_if_idle, exit),
+        (insert_if_null, insert),
+    ] {
+        insns[jump] = insns[jump].with_offset((target - jump - 1) as i16);
+    }
+    insns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SchedSwitchProgram, TaskKey};
+    use crate::bpf::BPF_NOEXIST;
+
+    #[test]
+    #[ignore = "requires CAP_BPF"]
+    fn load_program() {
+        // the verifier accepts the program
+        let program = SchedSwitchProgram::load(16).unwrap();
+        assert!(program.cpu_times().unwrap().is_empty());
+
+        // the map works as expected
+        let map = &program.cpu_times;
+        map.update(&TaskKey::new(1, 100), &5000, BPF_NOEXIST).unwrap();
+        map.update(&TaskKey::new(2, 101), &7000, BPF_NOEXIST).unwrap();
+        let mut entries = program.cpu_times().unwrap();
+        entries.sort_by_key(|(k, _)| k.pid);
+        assert_eq!(
+            entries,
+            vec![(TaskKey::new(1, 100), 5000), (TaskKey::new(2, 101), 7000)]
+        );
+        map.delete(&TaskKey::new(1, 100)).unwrap();
+        assert_eq!(map.lookup(&TaskKey::new(1, 100)).unwrap(), None);
+        assert_eq!(map.lookup(&TaskKey::new(2, 101)).unwrap(), Some(7000));
+    }
+}
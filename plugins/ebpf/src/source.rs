@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{
+    cgroup::CgroupResolver,
+    metrics::Metrics,
+    program::{SchedSwitchProgram, TaskKey},
+    tracepoint::AttachedTracepoint,
+};
+
+/// Measurement source that reads the CPU time accounted by the BPF program.
+pub struct SchedSource {
+    program: SchedSwitchProgram,
+    _attached: AttachedTracepoint,
+    metrics: Metrics,
+    tracker: CpuTimeTracker,
+    per_process: bool,
+    /// Set if the CPU time must be reported per cgroup.
+    cgroups: Option<CgroupResolver>,
+    last_timestamp: Timestamp,
+}
+
+impl SchedSource {
+    /// Creates a new source. The program must have been attached just before.
+    pub fn new(
+        program: SchedSwitchProgram,
+        attached: AttachedTracepoint,
+        metrics: Metrics,
+        per_process: bool,
+        cgroups: Option<CgroupResolver>,
+    ) -> Self {
+        Self {
+            program,
+            _attached: attached,
+            metrics,
+            tracker: CpuTimeTracker::default(),
+            per_process,
+            cgroups,
+            last_timestamp: Timestamp::now(),
+        }
+    }
+}
+
+impl Source for SchedSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        let elapsed = t.duration_since(self.last_timestamp)?.as_nanos() as f64;
+        self.last_timestamp = t;
+
+        let entries = self.program.cpu_times().context("could not read the BPF map")?;
+        let (deltas, idle) = self.tracker.update(&entries);
+
+        // Remove the tasks that have not run, to make room for the new ones.
+        for key in idle {
+            self.program
+                .cpu_times
+                .delete(&key)
+                .context("could not delete an element of the BPF map")?;
+        }
+
+        let mut push = |consumer: ResourceConsumer, cpu_time: u64| {
+            measurements.push(MeasurementPoint::new(
+                t,
+                self.metrics.cpu_time_delta,
+                Resource::LocalMachine,
+                consumer.clone(),
+                cpu_time,
+            ));
+            if elapsed > 0.0 {
+                measurements.push(MeasurementPoint::new(
+                    t,
+                    self.metrics.cpu_percent,
+                    Resource::LocalMachine,
+                    consumer,
+                    cpu_time as f64 / elapsed * 100.0,
+                ));
+            }
+        };
+
+        if self.per_process {
+            let mut per_pid: HashMap<u32, u64> = HashMap::new();
+            for (key, delta) in &deltas {
+                *per_pid.entry(key.pid).or_default() += delta;
+            }
+            for (pid, cpu_time) in per_pid {
+                push(ResourceConsumer::Process { pid }, cpu_time);
+            }
+        }
+
+        if let Some(cgroups) = &mut self.cgroups {
+            let mut per_cgroup: HashMap<u64, u64> = HashMap::new();
+            for (key, delta) in &deltas {
+                *per_cgroup.entry(key.cgroup_id).or_default() += delta;
+            }
+            // find the cgroups that have been created since the last scan
+            if per_cgroup.keys().any(|id| cgroups.path(*id).is_none()) {
+                cgroups
+                    .refresh()
+                    .context("could not scan the cgroup hierarchy")
+                    .map_err(PollError::CanRetry)?;
+            }
+            for (id, cpu_time) in per_cgroup {
+                match cgroups.path(id) {
+                    Some(path) => push(
+                        ResourceConsumer::ControlGroup {
+                            path: path.to_owned().into(),
+                        },
+                        cpu_time,
+                    ),
+                    None => log::debug!("Unknown cgroup {id}, it has probably been removed."),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Computes the CPU time of each task since the previous measurement.
+#[derive(Default)]
+struct CpuTimeTracker {
+    previous: HashMap<TaskKey, u64>,
+}
+
+impl CpuTimeTracker {
+    /// Updates the tracker with the content of the BPF map.
+    ///
+    /// Returns the CPU time of the tasks that have run since the previous update,
+    /// and the tasks that have not run, which can be removed from the map.
+    ///
+    /// The removal is not atomic: the BPF program can add some time to a task between
+    /// the read and the removal, and this time is lost. It only happens when a task
+    /// wakes up after having slept for a whole poll interval.
+    fn update(&mut self, entries: &[(TaskKey, u64)]) -> (Vec<(TaskKey, u64)>, Vec<TaskKey>) {
+        let mut deltas = Vec::with_capacity(entries.len());
+        let mut idle = Vec::new();
+        let mut current = HashMap::with_capacity(entries.len());
+        for (key, value) in entries {
+            match self.previous.get(key) {
+                Some(prev) if prev == value => idle.push(*key),
+                // the element has been created after the previous update, the time is counted from 0
+                prev => {
+                    deltas.push((*key, value.saturating_sub(prev.copied().unwrap_or(0))));
+                    current.insert(*key, *value);
+                }
+            }
+        }
+        self.previous = current;
+        (deltas, idle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CpuTimeTracker;
+    use crate::program::TaskKey;
+
+    #[test]
+    fn tracker() {
+        let a = TaskKey::new(1, 10);
+        let b = TaskKey::new(2, 20);
+        let mut tracker = CpuTimeTracker::default();
+
+        let (deltas, idle) = tracker.update(&[(a, 100), (b, 50)]);
+        assert_eq!(deltas, vec![(a, 100), (b, 50)]);
+        assert!(idle.is_empty());
+
+        // b has not run
+        let (deltas, idle) = tracker.update(&[(a, 250), (b, 50)]);
+        assert_eq!(deltas, vec![(a, 150)]);
+        assert_eq!(idle, vec![b]);
+
+        // b has been removed, then it runs again
+        let (deltas, idle) = tracker.update(&[(a, 250), (b, 30)]);
+        assert_eq!(deltas, vec![(b, 30)]);
+        assert_eq!(idle, vec![a]);
+
+        let (deltas, idle) = tracker.update(&[(b, 40)]);
+        assert_eq!(deltas, vec![(b, 10)]);
+        assert!(idle.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+//! Attachment of a BPF program to a kernel tracepoint, with `perf_event_open`.
+
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::{Path, PathBuf},
+};
+
+use alumet::plugin::util::online_cpus;
+use anyhow::Context;
+use perf_event_open_sys::{bindings, ioctls, perf_event_open};
+
+use crate::bpf::Program;
+
+/// The usual mount points of the tracefs.
+const TRACEFS_PATHS: [&str; 2] = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+
+/// Finds the id of the tracepoint `category:name`, such as `sched:sched_switch`.
+pub fn tracepoint_id(category: &str, name: &str) -> anyhow::Result<u64> {
+    let tracefs = TRACEFS_PATHS
+        .iter()
+        .map(Path::new)
+        .find(|p| p.join("events").is_dir())
+        .with_context(|| format!("tracefs not found (tried {TRACEFS_PATHS:?}), is it mounted?"))?;
+    let path: PathBuf = tracefs.join("events").join(category).join(name).join("id");
+    let id = std::fs::read_to_string(&path).with_context(|| format!("could not read {path:?}"))?;
+    id.trim()
+        .parse()
+        .with_context(|| format!("invalid tracepoint id in {path:?}: {id}"))
+}
+
+/// A program attached to a tracepoint on every online CPU.
+///
+/// The program is detached when this value is dropped.
+pub struct AttachedTracepoint {
+    _events: Vec<OwnedFd>,
+}
+
+impl AttachedTracepoint {
+    pub fn attach(program: &Program, tracepoint_id: u64) -> anyhow::Result<Self> {
+        let mut events = Vec::new();
+        for cpu in online_cpus()? {
+            let mut attr = bindings::perf_event_attr::default();
+            attr.type_ = bindings::PERF_TYPE_TRACEPOINT;
+            attr.size = std::mem::size_of::<bindings::perf_event_attr>() as u32;
+            attr.config = tracepoint_id;
+            attr.__bindgen_anon_1.sample_period = 1;
+            attr.__bindgen_anon_2.wakeup_events = 1;
+
+            // SAFETY: attr is properly initialized
+            let fd = unsafe {
+                perf_event_open(
+                    &mut attr,
+                    -1,
+                    cpu as i32,
+                    -1,
+                    bindings::PERF_FLAG_FD_CLOEXEC as libc::c_ulong,
+                )
+            };
+            if fd < 0 {
+                return Err(io::Error::last_os_error()).with_context(|| {
+                    format!("perf_event_open failed for the tracepoint {tracepoint_id} on cpu {cpu}")
+                });
+            }
+            // SAFETY: the kernel has returned a new file descriptor
+            let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+            // SAFETY: fd is a perf event and the program is loaded
+            if unsafe { ioctls::SET_BPF(fd.as_raw_fd(), program.raw_fd() as u32) } < 0 {
+                return Err(io::Error::last_os_error()).context("could not attach the BPF program");
+            }
+            if unsafe { ioctls::ENABLE(fd.as_raw_fd(), 0) } < 0 {
+                return Err(io::Error::last_os_error()).context("could not enable the tracepoint");
+            }
+            events.push(fd);
+        }
+        Ok(Self { _events: events })
+    }
+}
@@ -2,4 +2,5 @@ mod client;
 mod de;
 mod ser;
 
-pub use client::{ApiAuthentication, Client, ConnectionSettings, DataSettings};
+pub use client::{ApiAuthentication, Client, ConnectionSettings, DataSettings, RetrySettings};
+pub use ser::IndexNaming;
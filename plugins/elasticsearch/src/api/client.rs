@@ -1,19 +1,29 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
-use alumet::{measurement::MeasurementBuffer, pipeline::elements::output::OutputContext};
+use alumet::{measurement::MeasurementBuffer, metrics::RawMetricId, pipeline::elements::output::OutputContext};
 use anyhow::Context;
 use reqwest::{
-    Url,
+    StatusCode, Url,
     header::{HeaderMap, HeaderValue, InvalidHeaderValue},
 };
 
-use super::ser::{CreateIndexTemplate, IndexTemplate, Serializer};
+use super::{
+    de::BulkResponse,
+    ser::{CreateIndexTemplate, IndexNaming, IndexTemplate, Serializer},
+};
 
 // OpenSearch/ElasticSearch API client.
 pub struct Client {
     serializer: Serializer,
     client: reqwest::blocking::Client,
     server_url: Url,
+    ilm_policy: Option<String>,
+    retry: RetrySettings,
+    /// Metrics whose index template has been created.
+    templated_metrics: HashSet<RawMetricId>,
 }
 
 pub struct ConnectionSettings {
@@ -25,6 +35,16 @@ pub struct ConnectionSettings {
 pub struct DataSettings {
     pub index_prefix: String,
     pub metric_unit_as_index_suffix: bool,
+    pub index_naming: IndexNaming,
+    /// Name of the ILM policy applied to the new indices.
+    pub ilm_policy: Option<String>,
+}
+
+/// Retries of the bulk requests that are rejected because the cluster is overloaded (status 429).
+pub struct RetrySettings {
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after each attempt.
+    pub initial_delay: Duration,
 }
 
 #[derive(Debug, PartialEq)]
@@ -44,7 +64,7 @@ pub fn user_agent_string() -> String {
 }
 
 impl Client {
-    pub fn new(conn: ConnectionSettings, data: DataSettings) -> anyhow::Result<Self> {
+    pub fn new(conn: ConnectionSettings, data: DataSettings, retry: RetrySettings) -> anyhow::Result<Self> {
         let mut builder = reqwest::blocking::ClientBuilder::new();
         if conn.allow_insecure {
             builder = builder
@@ -66,14 +86,27 @@ impl Client {
             serializer: Serializer {
                 index_prefix: data.index_prefix,
                 metric_unit_as_index_suffix: data.metric_unit_as_index_suffix,
+                index_naming: data.index_naming,
             },
+            ilm_policy: data.ilm_policy,
+            retry,
+            templated_metrics: HashSet::new(),
         })
     }
 
+    /// Settings of the new indices.
+    fn index_settings(&self) -> Option<serde_json::Value> {
+        self.ilm_policy
+            .as_ref()
+            .map(|policy| serde_json::json!({ "index.lifecycle.name": policy }))
+    }
+
+    /// Creates the template that applies to all the indices of Alumet.
     pub fn create_index_template(&self) -> anyhow::Result<()> {
         const TEMPLATE_NAME: &str = "alumet_index_template";
 
         let template = IndexTemplate {
+            settings: self.index_settings(),
             mappings: self.serializer.common_index_mappings(),
         };
         let index_pattern = format!("{}-*", self.serializer.index_prefix);
@@ -84,11 +117,49 @@ impl Client {
             version: 3,
             meta: HashMap::from_iter([("origin".to_string(), "Alumet measurements".to_string())]),
         };
+        self.put_index_template(TEMPLATE_NAME, &create)
+    }
 
+    /// Creates the templates of the indices of the metrics that have not been seen yet.
+    ///
+    /// The templates are generated from the metric registry, so that the value of each metric
+    /// is mapped to the right type. They only apply when the indices are named after the metrics.
+    fn create_metric_templates(&mut self, m: &MeasurementBuffer, ctx: &OutputContext) -> anyhow::Result<()> {
+        if self.serializer.index_naming != IndexNaming::Metric {
+            return Ok(());
+        }
+        for point in m {
+            if self.templated_metrics.contains(&point.metric) {
+                continue;
+            }
+            let metric = ctx
+                .metrics
+                .by_id(&point.metric)
+                .with_context(|| format!("unknown metric {:?}", point.metric))?;
+            let index = self.serializer.index_name(point, metric);
+            let create = CreateIndexTemplate {
+                index_patterns: vec![index.clone()],
+                template: IndexTemplate {
+                    settings: self.index_settings(),
+                    mappings: self.serializer.metric_index_mappings(metric),
+                },
+                // higher than the common template, which is replaced by this one
+                priority: 90,
+                version: 3,
+                meta: HashMap::from_iter([("origin".to_string(), "Alumet measurements".to_string())]),
+            };
+            self.put_index_template(&format!("alumet_metric_{index}"), &create)
+                .with_context(|| format!("failed to create the index template of metric {}", metric.name))?;
+            self.templated_metrics.insert(point.metric);
+        }
+        Ok(())
+    }
+
+    fn put_index_template(&self, name: &str, template: &CreateIndexTemplate) -> anyhow::Result<()> {
         // Create the template (or update it if it exists).
         // Note: server_url is turned into a string with a trailing '/'.
-        let url = format!("{}_index_template/{TEMPLATE_NAME}", self.server_url);
-        let request = self.client.put(url).json(&create);
+        let url = format!("{}_index_template/{name}", self.server_url);
+        let request = self.client.put(url).json(template);
         log::trace!("sending {request:?}");
 
         let response = request.send().context("could not send request")?;
@@ -98,14 +169,44 @@ impl Client {
         Ok(())
     }
 
-    pub fn bulk_insert_measurements(&self, m: &MeasurementBuffer, ctx: &OutputContext) -> anyhow::Result<()> {
-        let url = self.server_url.join("_bulk").unwrap();
+    pub fn bulk_insert_measurements(&mut self, m: &MeasurementBuffer, ctx: &OutputContext) -> anyhow::Result<()> {
+        self.create_metric_templates(m, ctx)?;
 
-        let body = self
+        let mut docs = self
             .serializer
-            .body_bulk_create_docs(m, ctx)
+            .bulk_create_docs(m, ctx)
             .context("measurements serialization failed")?;
 
+        // Send the documents, and send again the ones that are rejected because the cluster is overloaded.
+        let mut delay = self.retry.initial_delay;
+        let mut attempt = 0;
+        loop {
+            let rejected = self.bulk_request(docs)?;
+            if rejected.is_empty() {
+                return Ok(());
+            }
+            if attempt >= self.retry.max_retries {
+                return Err(anyhow::anyhow!(
+                    "{} documents rejected with status 429 after {attempt} retries",
+                    rejected.len()
+                ));
+            }
+            log::warn!(
+                "{} documents rejected because the cluster is overloaded, retrying in {delay:?}",
+                rejected.len()
+            );
+            std::thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+            docs = rejected;
+        }
+    }
+
+    /// Sends a bulk request, and returns the documents that must be sent again.
+    fn bulk_request(&self, docs: Vec<String>) -> anyhow::Result<Vec<String>> {
+        let url = self.server_url.join("_bulk").unwrap();
+        let body = docs.concat();
+
         log::trace!("serialized measurements:\n{body}");
         let request = self.client.put(url).body(body).header(
             reqwest::header::CONTENT_TYPE,
@@ -115,11 +216,44 @@ impl Client {
         let response = request.send().context("could not send request")?;
 
         log::trace!("got response {response:?}");
-        Self::handle_response(response)?;
-        Ok(())
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            // the whole request has been rejected
+            return Ok(docs);
+        }
+        let response = Self::handle_response(response)?;
+
+        // Some documents may have been rejected individually.
+        // Ignore the responses that we cannot parse: the documents have been accepted.
+        let Ok(response) = response.json::<BulkResponse>() else {
+            return Ok(Vec::new());
+        };
+        if !response.errors {
+            return Ok(Vec::new());
+        }
+        let mut rejected = Vec::new();
+        let mut failed = 0;
+        let mut first_error = None;
+        for (doc, item) in docs.into_iter().zip(response.items) {
+            let Some(result) = item.into_values().next() else {
+                continue;
+            };
+            if result.status == StatusCode::TOO_MANY_REQUESTS.as_u16() {
+                rejected.push(doc);
+            } else if result.error.is_some() {
+                failed += 1;
+                first_error = first_error.or(result.error);
+            }
+        }
+        if failed > 0 {
+            log::error!(
+                "{failed} documents could not be indexed, first error: {}",
+                first_error.unwrap_or_default()
+            );
+        }
+        Ok(rejected)
     }
 
-    fn handle_response(response: reqwest::blocking::Response) -> anyhow::Result<()> {
+    fn handle_response(response: reqwest::blocking::Response) -> anyhow::Result<reqwest::blocking::Response> {
         let status = response.status();
         if status.is_client_error() || status.is_server_error() {
             let status_msg = format!("{} {}", status.as_str(), status.canonical_reason().unwrap_or_default());
@@ -128,7 +262,7 @@ impl Client {
                 .with_context(|| format!("failed to decode response body for error {status:?}"))?;
             Err(anyhow::anyhow!("server responded with error: {status_msg}\n{body}"))
         } else {
-            Ok(())
+            Ok(response)
         }
     }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::Deserialize;
 
 /// A response to a "create" query with the following filter (set as a query parameter):
@@ -76,3 +78,20 @@ pub struct ErrorDetails {
     pub index_uuid: String,
     pub shard: String,
 }
+
+/// A response to a bulk request.
+///
+/// Only the fields that we need are parsed, see [`FilteredBulkCreateResponse`]
+/// for an example of the full response.
+#[derive(Debug, Deserialize)]
+pub struct BulkResponse {
+    pub errors: bool,
+    #[serde(default)]
+    pub items: Vec<HashMap<String, BulkItem>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkItem {
+    pub status: u16,
+    pub error: Option<serde_json::Value>,
+}
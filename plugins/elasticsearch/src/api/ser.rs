@@ -1,78 +1,143 @@
 //! Implementation of a small subset of the REST API of OpenSearch/ElasticSearch.
 
 use alumet::{
-    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+    measurement::{
+        AttributeValue, MeasurementBuffer, MeasurementPoint, WrappedMeasurementType, WrappedMeasurementValue,
+    },
+    metrics::Metric,
     pipeline::elements::output::OutputContext,
 };
 
-use serde::{Serialize, ser::Error, ser::SerializeMap};
+use serde::{Deserialize, Serialize, ser::Error, ser::SerializeMap};
 use serde_json::json;
 use std::{collections::HashMap, time::SystemTime};
 use time::{UtcDateTime, format_description::well_known::Rfc3339};
 
+/// How the indices are named.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexNaming {
+    /// One index per metric: `{index_prefix}-{metric_name}`, or `{index_prefix}-{metric_name}-{metric_unit_unique_name}`.
+    #[default]
+    Metric,
+    /// One index per day for all the metrics: `{index_prefix}-metrics-YYYY.MM.dd`.
+    ///
+    /// The name of the metric and its unit are stored in each document.
+    /// Old indices can easily be deleted, for instance by an ILM policy.
+    Daily,
+}
+
 /// OpenSearch/ElasticSearch serializer helper.
 pub struct Serializer {
-    /// Each index will be named like `"{index_prefix}-{metric_name}"`
+    /// Each index will be named like `"{index_prefix}-{metric_name}"`, see [`IndexNaming`].
     pub index_prefix: String,
 
     pub metric_unit_as_index_suffix: bool,
+
+    pub index_naming: IndexNaming,
 }
 
 impl Serializer {
     /// Generates the mappings for an index.
     pub fn common_index_mappings(&self) -> serde_json::Value {
+        let mut properties = DocMeasurement::properties_definitions();
+        if self.index_naming == IndexNaming::Daily {
+            // All the metrics share the same index, hence the same type for the value.
+            properties.insert(String::from("metric"), json!({ "type": "keyword" }));
+            properties.insert(String::from("unit"), json!({ "type": "keyword" }));
+            properties.insert(String::from("value"), json!({ "type": "double" }));
+        }
+        json!({
+            "properties": properties
+        })
+    }
+
+    /// Generates the mappings for the index of a metric, with the right type for the value.
+    ///
+    /// The unit and the description of the metric are stored in the metadata of the mappings.
+    pub fn metric_index_mappings(&self, metric: &Metric) -> serde_json::Value {
+        let value_type = match metric.value_type {
+            WrappedMeasurementType::F64 => "double",
+            WrappedMeasurementType::U64 => "unsigned_long",
+        };
+        let mut properties = DocMeasurement::properties_definitions();
+        properties.insert(String::from("value"), json!({ "type": value_type }));
         json!({
-            "properties": DocMeasurement::properties_definitions()
+            "_meta": {
+                "metric": metric.name,
+                "unit": metric.unit.unique_name(),
+                "description": metric.description,
+            },
+            "properties": properties
         })
     }
 
-    /// Generates the body of a bulk document creation request.
+    /// Returns the name of the index of a measurement.
+    pub fn index_name(&self, m: &MeasurementPoint, metric: &Metric) -> String {
+        let mut buf = String::from(&self.index_prefix);
+        buf.push('-');
+        match self.index_naming {
+            IndexNaming::Metric => {
+                // {prefix}-{metric} or {prefix}-{metric}-{suffix}
+                buf.push_str(&metric.name);
+                if self.metric_unit_as_index_suffix {
+                    let index_suffix = metric.unit.unique_name();
+                    buf.push('-');
+                    buf.push_str(&index_suffix);
+                };
+            }
+            IndexNaming::Daily => {
+                // {prefix}-metrics-YYYY.MM.dd
+                let date = UtcDateTime::from(SystemTime::from(m.timestamp));
+                buf.push_str(&format!(
+                    "metrics-{:04}.{:02}.{:02}",
+                    date.year(),
+                    u8::from(date.month()),
+                    date.day()
+                ));
+            }
+        }
+        buf
+    }
+
+    /// Generates the (action, document) pairs of a bulk document creation request, one per measurement.
     ///
     /// Each measurement point is created as a separate document with a `@timestamp` field.
     /// The remaining fields depend on the client settings.
-    pub fn body_bulk_create_docs(
+    /// Each element of the returned list ends with a newline, the body of the request is their concatenation.
+    pub fn bulk_create_docs(
         &self,
         measurement_points: &MeasurementBuffer,
         ctx: &OutputContext,
-    ) -> std::io::Result<String> {
-        // The bulk body is made of (action, document) pairs.
-        // Each element is serialized as a json object and separated by a newline at the end.
-        let mut bytes = Vec::new();
+    ) -> anyhow::Result<Vec<String>> {
+        let mut res = Vec::with_capacity(measurement_points.len());
         for m in measurement_points {
-            // TODO provide m.metric.fetch_definition(ctx) ?
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .ok_or_else(|| anyhow::anyhow!("unknown metric {:?}", m.metric))?;
 
-            self.serialize_bulk_action(&mut bytes, m, ctx)?;
+            // The action and the document are serialized as json objects, each followed by a newline.
+            let mut bytes = Vec::new();
+            self.serialize_bulk_action(&mut bytes, m, metric)?;
             bytes.push(b'\n');
 
-            self.serialize_bulk_document(&mut bytes, m, ctx)?;
+            self.serialize_bulk_document(&mut bytes, m, metric)?;
             bytes.push(b'\n');
-        }
 
-        // SAFETY: serde_json outputs valid utf8 chars
-        Ok(unsafe { String::from_utf8_unchecked(bytes) })
+            // serde_json outputs valid utf8 chars
+            res.push(String::from_utf8(bytes)?);
+        }
+        Ok(res)
     }
 
     fn serialize_bulk_action(
         &self,
         buf: &mut Vec<u8>,
         m: &MeasurementPoint,
-        ctx: &OutputContext,
+        metric: &Metric,
     ) -> serde_json::Result<()> {
-        let index = {
-            // {prefix}-{metric} or {prefix}-{metric}-{suffix}
-            let metric = ctx.metrics.by_id(&m.metric).unwrap();
-            let metric_name = metric.name.to_owned();
-            let index_prefix = &self.index_prefix;
-            let mut buf = String::from(index_prefix);
-            buf.push('-');
-            buf.push_str(&metric_name);
-            if self.metric_unit_as_index_suffix {
-                let index_suffix = metric.unit.unique_name();
-                buf.push('-');
-                buf.push_str(&index_suffix);
-            };
-            buf
-        };
+        let index = self.index_name(m, metric);
         let action = BulkAction::Create { index };
         serde_json::to_writer(buf, &action)
     }
@@ -81,9 +146,12 @@ impl Serializer {
         &self,
         buf: &mut Vec<u8>,
         measurement: &MeasurementPoint,
-        _ctx: &OutputContext,
+        metric: &Metric,
     ) -> serde_json::Result<()> {
-        let doc = DocMeasurement { measurement };
+        let doc = DocMeasurement {
+            measurement,
+            metric: (self.index_naming == IndexNaming::Daily).then_some(metric),
+        };
         serde_json::to_writer(buf, &doc)
     }
 }
@@ -100,6 +168,8 @@ enum BulkAction {
 /// A structure that allows a custom serialization of MeasurementPoints.
 struct DocMeasurement<'a> {
     measurement: &'a MeasurementPoint,
+    /// If set, the name and unit of the metric are added to the document.
+    metric: Option<&'a Metric>,
 }
 
 impl Serialize for DocMeasurement<'_> {
@@ -113,6 +183,12 @@ impl Serialize for DocMeasurement<'_> {
         let datetime = datetime.format(&Rfc3339).map_err(S::Error::custom)?;
         map.serialize_entry("@timestamp", &datetime)?;
 
+        // metric, when several metrics share the same index
+        if let Some(metric) = self.metric {
+            map.serialize_entry("metric", &metric.name)?;
+            map.serialize_entry("unit", &metric.unit.unique_name())?;
+        }
+
         // resource and consumer
         map.serialize_entry("resource_kind", self.measurement.resource.kind())?;
         // TODO there should be a nicer way to get a &str from a resource id without allocating a string
@@ -134,12 +210,11 @@ impl Serialize for DocMeasurement<'_> {
             "consumer_kind",
             "consumer_id",
         ];
+        const RESERVED_METRIC_KEYS: [&str; 2] = ["metric", "unit"];
         for (key, attr) in self.measurement.attributes() {
-            let key = if RESERVED_KEYS.contains(&key) {
-                &format!("__{key}")
-            } else {
-                key
-            };
+            let reserved =
+                RESERVED_KEYS.contains(&key) || (self.metric.is_some() && RESERVED_METRIC_KEYS.contains(&key));
+            let key = if reserved { &format!("__{key}") } else { key };
             match attr {
                 AttributeValue::F64(v) => map.serialize_entry(key, v)?,
                 AttributeValue::U64(v) => map.serialize_entry(key, v)?,
@@ -188,6 +263,8 @@ pub struct CreateIndexTemplate {
 
 #[derive(Debug, Serialize)]
 pub struct IndexTemplate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<serde_json::Value>,
     pub mappings: serde_json::Value,
 }
 
@@ -210,6 +287,7 @@ mod tests {
     #[test]
     fn test_serialize() {
         let doc = DocMeasurement {
+            metric: None,
             measurement: &MeasurementPoint::new_untyped(
                 timestamp_from_rfc3339("2025-06-06T12:03:18Z"),
                 RawMetricId::from_u64(1),
@@ -2,10 +2,14 @@ use alumet::{
     measurement::MeasurementBuffer,
     pipeline::{
         Output,
-        elements::{error::WriteError, output::OutputContext},
+        elements::{
+            error::WriteError,
+            output::{OutputContext, error::WriteRetry},
+        },
     },
     plugin::{
         AlumetPluginStart, ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
 };
@@ -26,6 +30,10 @@ impl AlumetPlugin for ElasticSearchPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config = deserialize_config(config)?;
         Ok(Box::new(ElasticSearchPlugin { config }))
@@ -56,6 +64,12 @@ impl AlumetPlugin for ElasticSearchPlugin {
             api::DataSettings {
                 index_prefix: config.index_prefix,
                 metric_unit_as_index_suffix: config.metric_unit_as_index_suffix,
+                index_naming: config.index_naming,
+                ilm_policy: config.ilm_policy,
+            },
+            api::RetrySettings {
+                max_retries: config.max_retries,
+                initial_delay: config.retry_delay,
             },
         )
         .context("failed to initialize api client")?;
@@ -88,20 +102,23 @@ impl Output for ElasticSearchOutput {
         if !measurements.is_empty() {
             self.client
                 .bulk_insert_measurements(measurements, ctx)
-                .context("failed to send measurements")?;
+                .context("failed to send measurements")
+                .retry_write()?;
         }
         Ok(())
     }
 }
 
 pub mod config {
-    use std::path::PathBuf;
+    use std::{path::PathBuf, time::Duration};
 
     use anyhow::Context;
     use serde::{Deserialize, Serialize};
 
     use crate::api;
 
+    pub use crate::api::IndexNaming;
+
     #[derive(Debug, Serialize, Deserialize)]
     pub struct Config {
         /// The url of the database instance.
@@ -115,6 +132,26 @@ pub mod config {
         pub index_prefix: String,
         /// Controls the use of an optional suffix for each index (format `{index_prefix}-{metric_name}-{metric_unit_unique_name}`).
         pub metric_unit_as_index_suffix: bool,
+        /// `"metric"` for one index per metric, `"daily"` for one index per day (format `{index_prefix}-metrics-YYYY.MM.dd`).
+        #[serde(default)]
+        pub index_naming: IndexNaming,
+        /// Name of an existing ILM policy to apply to the new indices (ElasticSearch only).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub ilm_policy: Option<String>,
+        /// How many times the documents are sent again when the cluster is overloaded (status 429).
+        #[serde(default = "default_max_retries")]
+        pub max_retries: u32,
+        /// Delay before the first retry, doubled after each attempt.
+        #[serde(default = "default_retry_delay", with = "humantime_serde")]
+        pub retry_delay: Duration,
+    }
+
+    fn default_max_retries() -> u32 {
+        5
+    }
+
+    fn default_retry_delay() -> Duration {
+        Duration::from_millis(500)
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -137,6 +174,10 @@ pub mod config {
                 allow_insecure: false,
                 index_prefix: String::from("alumet"),
                 metric_unit_as_index_suffix: false,
+                index_naming: IndexNaming::Metric,
+                ilm_policy: None,
+                max_retries: default_max_retries(),
+                retry_delay: default_retry_delay(),
             }
         }
     }
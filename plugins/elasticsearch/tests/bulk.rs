@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{PluginHarness, golden::SampleMeasurements},
+};
+use mockito::{Matcher, Server};
+use plugin_elasticsearch::{
+    ElasticSearchPlugin,
+    plugin::config::{Config, IndexNaming},
+};
+
+#[test]
+fn daily_indices_with_ilm_policy() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let template = server
+        .mock("PUT", "/_index_template/alumet_index_template")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "index_patterns": ["alumet-*"],
+            "template": {
+                "settings": { "index.lifecycle.name": "alumet-30d" },
+                "mappings": { "properties": { "metric": { "type": "keyword" }, "value": { "type": "double" } } }
+            }
+        })))
+        .with_status(200)
+        .create();
+    // the documents of the sample span two seconds of the same day
+    let bulk = server
+        .mock("PUT", "/_bulk")
+        .match_request(|req| {
+            let body = req.utf8_lossy_body().unwrap();
+            let lines: Vec<&str> = body.lines().collect();
+            lines.len() == 10
+                && lines
+                    .iter()
+                    .step_by(2)
+                    .all(|l| *l == r#"{"create":{"_index":"alumet-metrics-2023.11.14"}}"#)
+                && lines[1].contains(r#""metric":"sample_energy","unit":"J""#)
+        })
+        .with_status(200)
+        .with_body(r#"{"errors":false,"items":[]}"#)
+        .create();
+
+    let config = Config {
+        server_url: server.url(),
+        index_naming: IndexNaming::Daily,
+        ilm_policy: Some(String::from("alumet-30d")),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<ElasticSearchPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("api")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    template.assert();
+    bulk.assert();
+    Ok(())
+}
+
+#[test]
+fn retry_rejected_documents() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let _template = server.mock("PUT", Matcher::Any).with_status(200).create();
+
+    // The mocks are served in order: the whole request is rejected,
+    // then only the first document, which is finally accepted.
+    let overloaded = server.mock("PUT", "/_bulk").with_status(429).expect(1).create();
+    let partial = server
+        .mock("PUT", "/_bulk")
+        .match_request(|req| req.utf8_lossy_body().unwrap().lines().count() == 10)
+        .with_status(200)
+        .with_body(
+            r#"{"errors":true,"items":[
+                {"create":{"status":429,"error":{"type":"es_rejected_execution_exception"}}},
+                {"create":{"status":201}},
+                {"create":{"status":201}},
+                {"create":{"status":201}},
+                {"create":{"status":201}}
+            ]}"#,
+        )
+        .expect(1)
+        .create();
+    let retried = server
+        .mock("PUT", "/_bulk")
+        .match_request(|req| {
+            let body = req.utf8_lossy_body().unwrap();
+            body.lines().count() == 2 && body.contains(r#""resource_kind":"cpu_package""#)
+        })
+        .with_status(200)
+        .with_body(r#"{"errors":false,"items":[{"create":{"status":201}}]}"#)
+        .create();
+
+    let config = Config {
+        server_url: server.url(),
+        retry_delay: Duration::from_millis(10),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<ElasticSearchPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("api")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    overloaded.assert();
+    partial.assert();
+    retried.assert();
+    Ok(())
+}
@@ -57,6 +57,7 @@ fn simple_point(metric: RawMetricId, value: WrappedMeasurementValue) -> Measurem
 struct SharedMocks {
     server: mockito::Server,
     create_index_template: Mock,
+    create_metric_templates: Mock,
     current_mock: Option<Mock>,
 }
 
@@ -89,6 +90,16 @@ fn elastic_output() {
         .with_status(200)
         .create();
 
+    // Mock api requests to create the templates of the metrics (expected once per metric)
+    let create_metric_templates: Mock = server
+        .mock(
+            "PUT",
+            mockito::Matcher::Regex(String::from("^/_index_template/alumet_metric_alumet-test_metric_")),
+        )
+        .with_status(200)
+        .expect(2)
+        .create();
+
     // Debug mock
     let _ = server
         .mock("PUT", "/_bulk")
@@ -117,6 +128,7 @@ fn elastic_output() {
     let mocks = Arc::new(Mutex::new(SharedMocks {
         server,
         create_index_template,
+        create_metric_templates,
         current_mock: None, // will be setup in each RuntimeExpectations scenario
     }));
 
@@ -211,6 +223,7 @@ fn elastic_output() {
                 move || {
                     let mocks = mocks.lock().unwrap();
                     mocks.create_index_template.assert();
+                    mocks.create_metric_templates.assert();
                     mocks.current_mock.as_ref().unwrap().assert();
                 }
             },
@@ -1,6 +1,7 @@
 use alumet::{
     plugin::{
         AlumetPluginStart, ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config},
     },
     units::Unit,
@@ -24,6 +25,10 @@ impl AlumetPlugin for EnergyAttributionPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(Vec::new())
+    }
+
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config = deserialize_config(config)?;
         Ok(Box::new(Self { config: Some(config) }))
@@ -0,0 +1,54 @@
+use alumet::{
+    test::{
+        PluginHarness,
+        bench::{Cardinality, CountingAllocator, SyntheticMeasurements, report_allocations},
+    },
+    units::{PrefixedUnit, Unit},
+};
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use plugin_energy_estimation_tdp::EnergyEstimationTdpPlugin;
+
+#[global_allocator]
+static ALLOC: CountingAllocator = CountingAllocator;
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+fn bench_apply(c: &mut Criterion) {
+    let mut harness = PluginHarness::<EnergyEstimationTdpPlugin>::start_with_default_config().unwrap();
+    // the input metric of the transform, usually registered by the procfs plugin
+    harness
+        .create_metric::<u64>("cpu_time_delta", PrefixedUnit::nano(Unit::Second), "CPU usage")
+        .unwrap();
+    let mut transform = harness.transform("transform").unwrap();
+
+    let mut group = c.benchmark_group("tdp_apply");
+    for consumers in [1, 100] {
+        let cardinality = Cardinality {
+            consumers,
+            ..Cardinality::default()
+        };
+        let synthetic = SyntheticMeasurements::for_metrics(&harness, &["cpu_time_delta"], cardinality).unwrap();
+        for size in SIZES {
+            let buf = synthetic.generate(size);
+            let label = format!("{consumers}_consumers");
+            // includes the copy of the input
+            report_allocations(&format!("tdp_apply/{label}/{size}"), 100, || {
+                transform.apply(buf.clone()).unwrap();
+            });
+            group.throughput(Throughput::Elements(size as u64));
+            group.bench_with_input(BenchmarkId::new(label, size), &buf, |b, buf| {
+                // the transform modifies its input: give it a fresh copy each time, outside of the measurement
+                b.iter_batched(
+                    || buf.clone(),
+                    |buf| transform.apply(buf).unwrap(),
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+    harness.stop().unwrap();
+}
+
+criterion_group!(benches, bench_apply);
+criterion_main!(benches);
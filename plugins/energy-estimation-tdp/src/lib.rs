@@ -2,6 +2,7 @@ use alumet::{
     metrics::{RawMetricId, TypedMetricId},
     plugin::{
         ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
     units::Unit,
@@ -40,6 +41,10 @@ impl AlumetPlugin for EnergyEstimationTdpPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(Vec::new())
+    }
+
     // We use the default config by default and on initialization.
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
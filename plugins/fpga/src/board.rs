@@ -0,0 +1,60 @@
+/// An FPGA board, whose sensors are read through the runtime of its vendor.
+pub trait Board: Send {
+    /// PCI address of the board, such as `0000:3b:00.0`.
+    fn bus_id(&self) -> &str;
+
+    /// Reads the sensors of the board.
+    fn read(&mut self) -> anyhow::Result<Readings>;
+}
+
+/// Values of the sensors of a board.
+#[derive(Debug, Default, PartialEq)]
+pub struct Readings {
+    /// Power sensors, in Watts.
+    pub power: Vec<Sensor>,
+    /// Temperature sensors, in degrees Celsius.
+    pub temperature: Vec<Sensor>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Sensor {
+    /// Name of the sensor, such as `board` or `FPGA Core Temperature`.
+    pub label: String,
+    pub value: f64,
+}
+
+impl Sensor {
+    pub fn new(label: impl Into<String>, value: f64) -> Self {
+        Self {
+            label: label.into(),
+            value,
+        }
+    }
+}
+
+/// Returns true if `s` looks like a PCI address, such as `0000:3b:00.0`.
+pub fn is_pci_address(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() == 12
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b':',
+            10 => *b == b'.',
+            11 => (b'0'..=b'7').contains(b),
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pci_address() {
+        assert!(is_pci_address("0000:3b:00.0"));
+        assert!(is_pci_address("0000:65:00.1"));
+        assert!(!is_pci_address("0000:3b:00"));
+        assert!(!is_pci_address("0000:3b:00.8"));
+        assert!(!is_pci_address("pci0000:00"));
+        assert!(!is_pci_address("dfl-fme.0"));
+    }
+}
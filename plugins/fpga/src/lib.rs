@@ -0,0 +1,272 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use board::Board;
+
+mod board;
+mod metrics;
+mod opae;
+mod source;
+mod xrt;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct FpgaPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for FpgaPlugin {
+    fn name() -> &'static str {
+        "fpga"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            // hwmon devices of the OPAE boards
+            Capability::Filesystem(PathBuf::from("/sys")),
+            // `xbutil`, which measures the XRT boards
+            Capability::ProcessSpawn,
+        ])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(FpgaPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let mut boards: Vec<Box<dyn Board>> = Vec::new();
+        for runtime in &self.config.runtimes {
+            match runtime {
+                Runtime::Xrt => {
+                    let found = xrt::explore(&self.config.sysfs, &self.config.xbutil)
+                        .context("could not find the boards of XRT")?;
+                    boards.extend(found.into_iter().map(|b| Box::new(b) as Box<dyn Board>));
+                }
+                Runtime::Opae => {
+                    let found = opae::explore(&self.config.sysfs).context("could not find the boards of OPAE")?;
+                    boards.extend(found.into_iter().map(|b| Box::new(b) as Box<dyn Board>));
+                }
+            }
+        }
+        if boards.is_empty() {
+            return Err(anyhow!(
+                "No FPGA board found, is the driver of the board (xocl or dfl) loaded?"
+            ));
+        }
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        for mut board in boards {
+            // Read the sensors now to report errors early, such as a missing xbutil.
+            let readings = board
+                .read()
+                .with_context(|| format!("failed to read the sensors of FPGA board {}", board.bus_id()))?;
+            log::info!(
+                "Found FPGA board {} with {} power and {} temperature sensors.",
+                board.bus_id(),
+                readings.power.len(),
+                readings.temperature.len()
+            );
+
+            let source_name = format!("board_{}", board.bus_id());
+            let source = source::FpgaSource::new(board, metrics.clone());
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&source_name, Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+
+    /// The runtimes whose boards are measured.
+    pub runtimes: Vec<Runtime>,
+
+    /// Path to the `xbutil` executable of XRT.
+    pub xbutil: PathBuf,
+
+    /// Mount point of sysfs.
+    pub sysfs: PathBuf,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Runtime {
+    /// Xilinx Runtime, for the AMD/Xilinx Alveo boards.
+    Xrt,
+    /// Open Programmable Acceleration Engine, for the Intel boards.
+    Opae,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            flush_interval: Duration::from_secs(15),
+            runtimes: vec![Runtime::Xrt, Runtime::Opae],
+            xbutil: PathBuf::from("xbutil"),
+            sysfs: PathBuf::from("/sys"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    };
+
+    use alumet::{measurement::WrappedMeasurementValue, test::PluginHarness};
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::opae::tests::fake_hwmon;
+
+    /// A fake xbutil that writes a report with one thermal sensor and the power of the board.
+    fn fake_xbutil(dir: &Path) -> PathBuf {
+        let path = dir.join("xbutil");
+        let script = r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+    case "$1" in
+        --output) output="$2"; shift ;;
+    esac
+    shift
+done
+cat > "$output" <<EOF
+{"devices": [{"device_id": "0000:65:00.1",
+    "electrical": {"power_consumption_watts": "23.5"},
+    "thermals": [{"location_id": "fpga0", "description": "FPGA", "is_present": "true", "temp_C": "45"}]}]}
+EOF
+"#;
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn xrt_and_opae_boards() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let sysfs = tmp.path().join("sys");
+        fs::create_dir_all(sysfs.join("bus/pci/drivers/xocl/0000:65:00.1"))?;
+        fake_hwmon(
+            &sysfs,
+            "hwmon4",
+            "0000:3b:00.0",
+            &[
+                ("name", "n6000bmc-hwmon\n"),
+                ("power1_input", "61250000\n"),
+                ("power1_label", "Board Power\n"),
+                ("temp1_input", "52500\n"),
+                ("temp1_label", "FPGA Core Temperature\n"),
+            ],
+        );
+        let config = Config {
+            xbutil: fake_xbutil(tmp.path()),
+            sysfs,
+            ..Config::default()
+        };
+        let mut harness = PluginHarness::<FpgaPlugin>::start(serialize_config(config)?)?;
+
+        let measurements = harness.source("board_0000:65:00.1")?.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, "fpga_power"),
+            vec![WrappedMeasurementValue::F64(23.5)]
+        );
+        let point = harness.points(&measurements, "fpga_temperature")[0];
+        assert_eq!(point.value, WrappedMeasurementValue::F64(45.0));
+        assert_eq!(point.resource.kind(), "fpga");
+        assert_eq!(point.resource.id_display().to_string(), "0000:65:00.1");
+        let attrs: Vec<_> = point.attributes().map(|(k, v)| (k, v.to_string())).collect();
+        assert_eq!(attrs, vec![("sensor", String::from("FPGA"))]);
+
+        let mut opae_source = harness.source("board_0000:3b:00.0")?;
+        let measurements = opae_source.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, "fpga_power"),
+            vec![WrappedMeasurementValue::F64(61.25)]
+        );
+        assert_eq!(
+            harness.values(&measurements, "fpga_temperature"),
+            vec![WrappedMeasurementValue::F64(52.5)]
+        );
+
+        // the board has been removed
+        fs::remove_dir_all(tmp.path().join("sys/class/hwmon/hwmon4"))?;
+        assert!(matches!(
+            opae_source.poll(),
+            Err(alumet::pipeline::elements::error::PollError::CanRetry(_))
+        ));
+
+        harness.stop()?;
+        Ok(())
+    }
+
+    #[test]
+    fn no_board() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let config = Config {
+            sysfs: tmp.path().to_owned(),
+            ..Config::default()
+        };
+        let err = PluginHarness::<FpgaPlugin>::start(serialize_config(config)?)
+            .err()
+            .expect("the plugin should not start without board");
+        assert!(format!("{err:#}").contains("No FPGA board found"), "{err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn missing_xbutil() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        fs::create_dir_all(tmp.path().join("bus/pci/drivers/xocl/0000:65:00.1"))?;
+        let config = Config {
+            runtimes: vec![Runtime::Xrt],
+            xbutil: tmp.path().join("xbutil"),
+            sysfs: tmp.path().to_owned(),
+            ..Config::default()
+        };
+        let err = PluginHarness::<FpgaPlugin>::start(serialize_config(config)?)
+            .err()
+            .expect("the plugin should not start without xbutil");
+        assert!(format!("{err:#}").contains("failed to run"), "{err:#}");
+        Ok(())
+    }
+}
@@ -0,0 +1,22 @@
+use alumet::{metrics::TypedMetricId, plugin::AlumetPluginStart, units::Unit};
+
+#[derive(Clone)]
+pub struct Metrics {
+    /// Power measured by a sensor of the board.
+    pub power: TypedMetricId<f64>,
+    /// Temperature measured by a sensor of the board.
+    pub temperature: TypedMetricId<f64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            power: alumet.create_metric("fpga_power", Unit::Watt, "Power consumed by the FPGA board or its chip")?,
+            temperature: alumet.create_metric(
+                "fpga_temperature",
+                Unit::DegreeCelsius,
+                "Temperature of a sensor of the FPGA board",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,188 @@
+//! Intel FPGA boards, supported by OPAE, whose sensors are exposed by the DFL drivers of Linux as hwmon devices.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::board::{Board, Readings, Sensor, is_pci_address};
+
+/// An Intel FPGA board, with the hwmon devices of its FPGA Management Engine (FME) and of its BMC.
+#[derive(Debug, PartialEq)]
+pub struct OpaeBoard {
+    bus_id: String,
+    hwmons: Vec<PathBuf>,
+}
+
+/// Finds the Intel FPGA boards in `<sysfs>/class/hwmon`.
+///
+/// ## Expected file layout
+///
+/// ```txt
+/// /sys/class/hwmon/
+/// |− hwmon3
+///     |− name                  (dfl_fme_power)
+///     |− power1_input          (microWatts)
+///     |− device -> ../../../devices/pci0000:3a/0000:3a:00.0/0000:3b:00.0/dfl-fme.0
+/// |− hwmon4
+///     |− name                  (n6000bmc-hwmon)
+///     |− temp1_input           (milli-degrees Celsius)
+///     |− temp1_label
+///     |− device -> ...
+/// ```
+pub fn explore(sysfs: &Path) -> anyhow::Result<Vec<OpaeBoard>> {
+    let root = sysfs.join("class/hwmon");
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let mut boards: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for entry in fs::read_dir(&root).with_context(|| format!("failed to read dir {root:?}"))? {
+        let path = entry?.path();
+        let Ok(name) = fs::read_to_string(path.join("name")) else {
+            continue;
+        };
+        if !is_fpga_hwmon(name.trim()) {
+            continue;
+        }
+        // the PCI device of the board is an ancestor of the hwmon device
+        let device = fs::canonicalize(path.join("device")).with_context(|| format!("failed to resolve {path:?}"))?;
+        let bus_id = device
+            .ancestors()
+            .filter_map(|p| p.file_name()?.to_str())
+            .find(|name| is_pci_address(name));
+        match bus_id {
+            Some(bus_id) => boards.entry(bus_id.to_owned()).or_default().push(path),
+            None => log::warn!("{path:?} looks like an FPGA sensor but its PCI device is unknown, it will be ignored."),
+        }
+    }
+    Ok(boards
+        .into_iter()
+        .map(|(bus_id, mut hwmons)| {
+            hwmons.sort();
+            OpaeBoard { bus_id, hwmons }
+        })
+        .collect())
+}
+
+/// Returns true if the hwmon device is registered by a DFL driver: the FME (`dfl_fme_power` and `dfl_fme_thermal`)
+/// or the BMC of the board (`n3000bmc-hwmon`, `d5005bmc-hwmon`, `n6000bmc-hwmon`, etc.).
+fn is_fpga_hwmon(name: &str) -> bool {
+    name.starts_with("dfl_fme_") || name.ends_with("bmc-hwmon")
+}
+
+impl Board for OpaeBoard {
+    fn bus_id(&self) -> &str {
+        &self.bus_id
+    }
+
+    fn read(&mut self) -> anyhow::Result<Readings> {
+        let mut readings = Readings::default();
+        for hwmon in &self.hwmons {
+            read_hwmon(hwmon, &mut readings).with_context(|| format!("failed to read the sensors of {hwmon:?}"))?;
+        }
+        Ok(readings)
+    }
+}
+
+fn read_hwmon(hwmon: &Path, readings: &mut Readings) -> anyhow::Result<()> {
+    let name = fs::read_to_string(hwmon.join("name"))?;
+    let mut inputs: Vec<_> = fs::read_dir(hwmon)?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|file| file.strip_suffix("_input").map(str::to_owned))
+        .collect();
+    inputs.sort();
+    for channel in inputs {
+        let (sensors, scale) = if channel.starts_with("power") {
+            (&mut readings.power, 1e-6)
+        } else if channel.starts_with("temp") {
+            (&mut readings.temperature, 1e-3)
+        } else {
+            continue;
+        };
+        let input = hwmon.join(format!("{channel}_input"));
+        let content = fs::read_to_string(&input).with_context(|| format!("failed to read {input:?}"))?;
+        let value: i64 = content
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid content {content:?} in {input:?}"))?;
+        let label = match fs::read_to_string(hwmon.join(format!("{channel}_label"))) {
+            Ok(label) => label.trim().to_owned(),
+            Err(_) => format!("{}_{channel}", name.trim()),
+        };
+        sensors.push(Sensor::new(label, value as f64 * scale));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::os::unix::fs::symlink;
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// Creates a hwmon device in the fake sysfs, with the given files, for the given PCI device.
+    pub fn fake_hwmon(sysfs: &Path, hwmon: &str, pci_device: &str, files: &[(&str, &str)]) {
+        let device = sysfs.join("devices/pci0000:3a").join(pci_device).join("dfl-fme.0");
+        fs::create_dir_all(&device).unwrap();
+        let path = sysfs.join("class/hwmon").join(hwmon);
+        fs::create_dir_all(&path).unwrap();
+        symlink(&device, path.join("device")).unwrap();
+        for (file, content) in files {
+            fs::write(path.join(file), content).unwrap();
+        }
+    }
+
+    #[test]
+    fn boards() {
+        let tmp = tempdir().unwrap();
+        let sysfs = tmp.path();
+        fake_hwmon(
+            sysfs,
+            "hwmon2",
+            "0000:3b:00.0",
+            &[("name", "dfl_fme_power\n"), ("power1_input", "45500000\n")],
+        );
+        fake_hwmon(
+            sysfs,
+            "hwmon3",
+            "0000:3b:00.0",
+            &[
+                ("name", "n6000bmc-hwmon\n"),
+                ("temp1_input", "52500\n"),
+                ("temp1_label", "FPGA Core Temperature\n"),
+                ("in0_input", "12000\n"),
+            ],
+        );
+        // not an FPGA
+        fake_hwmon(
+            sysfs,
+            "hwmon0",
+            "0000:00:01.0",
+            &[("name", "coretemp\n"), ("temp1_input", "40000\n")],
+        );
+
+        let mut boards = explore(sysfs).unwrap();
+        assert_eq!(boards.len(), 1);
+        let board = &mut boards[0];
+        assert_eq!(board.bus_id(), "0000:3b:00.0");
+        assert_eq!(
+            board.read().unwrap(),
+            Readings {
+                power: vec![Sensor::new("dfl_fme_power_power1", 45.5)],
+                temperature: vec![Sensor::new("FPGA Core Temperature", 52.5)],
+            }
+        );
+    }
+
+    #[test]
+    fn no_hwmon() {
+        let tmp = tempdir().unwrap();
+        assert_eq!(explore(tmp.path()).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,62 @@
+use std::borrow::Cow;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{board::Board, metrics::Metrics};
+
+/// Measurement source that reads the sensors of an FPGA board.
+pub struct FpgaSource {
+    board: Box<dyn Board>,
+    metrics: Metrics,
+    resource: Resource,
+}
+
+impl FpgaSource {
+    pub fn new(board: Box<dyn Board>, metrics: Metrics) -> Self {
+        let resource = Resource::Custom {
+            kind: Cow::Borrowed("fpga"),
+            id: Cow::Owned(board.bus_id().to_owned()),
+        };
+        Self {
+            board,
+            metrics,
+            resource,
+        }
+    }
+}
+
+impl Source for FpgaSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // The board can be reset or reprogrammed, the next poll may work.
+        let readings = self
+            .board
+            .read()
+            .with_context(|| format!("could not read the sensors of FPGA board {}", self.board.bus_id()))
+            .map_err(PollError::CanRetry)?;
+
+        let sensors = [
+            (self.metrics.power, readings.power),
+            (self.metrics.temperature, readings.temperature),
+        ];
+        for (metric, values) in sensors {
+            for sensor in values {
+                measurements.push(
+                    MeasurementPoint::new(
+                        t,
+                        metric,
+                        self.resource.clone(),
+                        ResourceConsumer::LocalMachine,
+                        sensor.value,
+                    )
+                    .with_attr("sensor", sensor.label),
+                );
+            }
+        }
+        Ok(())
+    }
+}
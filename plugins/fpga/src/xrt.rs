@@ -0,0 +1,164 @@
+//! AMD/Xilinx FPGA boards (Alveo), whose sensors are read with `xbutil`, the command-line tool of XRT.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, anyhow};
+use serde_json::Value;
+
+use crate::board::{Board, Readings, Sensor, is_pci_address};
+
+/// An Alveo board, managed by the `xocl` driver of XRT.
+#[derive(Debug, PartialEq)]
+pub struct XrtBoard {
+    bus_id: String,
+    /// Path to the `xbutil` executable.
+    xbutil: PathBuf,
+}
+
+/// Finds the boards that are bound to the `xocl` driver, in `<sysfs>/bus/pci/drivers/xocl`.
+pub fn explore(sysfs: &Path, xbutil: &Path) -> anyhow::Result<Vec<XrtBoard>> {
+    let driver = sysfs.join("bus/pci/drivers/xocl");
+    if !driver.exists() {
+        return Ok(Vec::new());
+    }
+    let mut boards: Vec<_> = fs::read_dir(&driver)
+        .with_context(|| format!("failed to read dir {driver:?}"))?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| is_pci_address(name))
+        .map(|bus_id| XrtBoard {
+            bus_id,
+            xbutil: xbutil.to_owned(),
+        })
+        .collect();
+    boards.sort_by(|a, b| a.bus_id.cmp(&b.bus_id));
+    Ok(boards)
+}
+
+impl Board for XrtBoard {
+    fn bus_id(&self) -> &str {
+        &self.bus_id
+    }
+
+    fn read(&mut self) -> anyhow::Result<Readings> {
+        // xbutil only writes its JSON reports to files
+        let report = std::env::temp_dir().join(format!(
+            "alumet-xbutil-{}-{}.json",
+            std::process::id(),
+            self.bus_id.replace([':', '.'], "_")
+        ));
+        let output = Command::new(&self.xbutil)
+            .args(["examine", "--device", &self.bus_id, "--report", "electrical", "thermal"])
+            .args(["--format", "JSON", "--force", "--output"])
+            .arg(&report)
+            .output()
+            .with_context(|| format!("failed to run {}", self.xbutil.display()))?;
+        if !output.status.success() {
+            let error_message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            return Err(anyhow!("xbutil failed with {}", output.status).context(error_message));
+        }
+        let content = fs::read_to_string(&report).with_context(|| format!("failed to read {report:?}"));
+        let _ = fs::remove_file(&report);
+        parse_report(&content?)
+    }
+}
+
+/// Parses the JSON report of `xbutil examine --report electrical thermal`.
+///
+/// Example: `{"devices": [{"device_id": "0000:65:00.1", "electrical": {"power_consumption_watts": "23.5", ...},
+/// "thermals": [{"location_id": "fpga0", "description": "FPGA", "is_present": "true", "temp_C": "45"}, ...]}]}`
+///
+/// XRT writes the numbers and the booleans as strings.
+fn parse_report(report: &str) -> anyhow::Result<Readings> {
+    let report: Value = serde_json::from_str(report).context("invalid JSON report")?;
+    let device = report
+        .get("devices")
+        .and_then(|d| d.get(0))
+        .context("no device in the report of xbutil")?;
+    let mut readings = Readings::default();
+    if let Some(power) = device.pointer("/electrical/power_consumption_watts").and_then(number) {
+        readings.power.push(Sensor::new("board", power));
+    }
+    for thermal in device.get("thermals").and_then(Value::as_array).into_iter().flatten() {
+        let present = thermal.get("is_present").is_none_or(|p| p == "true" || p == true);
+        let label = thermal
+            .get("description")
+            .or_else(|| thermal.get("location_id"))
+            .and_then(Value::as_str);
+        if let (true, Some(label), Some(temperature)) = (present, label, thermal.get("temp_C").and_then(number)) {
+            readings.temperature.push(Sensor::new(label, temperature));
+        }
+    }
+    Ok(readings)
+}
+
+/// Reads a number, that may be written as a string.
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse_xbutil_report() {
+        let report = r#"{
+            "schema_version": {"schema": "JSON", "creation_date": "Tue Jun 10 09:12:01 2025 GMT"},
+            "devices": [{
+                "interface_type": "pcie",
+                "device_id": "0000:65:00.1",
+                "electrical": {
+                    "power_rails": [{"id": "12v_pex", "description": "12 Volts PCI Express",
+                        "voltage": {"volts": "12.100", "is_present": "true"},
+                        "current": {"amps": "1.520", "is_present": "true"}}],
+                    "power_consumption_max_watts": "225.000000",
+                    "power_consumption_watts": "23.543",
+                    "power_consumption_warning": "false"
+                },
+                "thermals": [
+                    {"location_id": "pcb_top_front", "description": "PCB Top Front", "is_present": "true", "temp_C": "38"},
+                    {"location_id": "fpga0", "description": "FPGA", "is_present": "true", "temp_C": "45"},
+                    {"location_id": "vccint", "description": "Int Vcc", "is_present": "false", "temp_C": "0"}
+                ]
+            }]
+        }"#;
+        assert_eq!(
+            parse_report(report).unwrap(),
+            Readings {
+                power: vec![Sensor::new("board", 23.543)],
+                temperature: vec![Sensor::new("PCB Top Front", 38.0), Sensor::new("FPGA", 45.0)],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_empty_report() {
+        assert!(parse_report(r#"{"devices": []}"#).is_err());
+        assert_eq!(
+            parse_report(r#"{"devices": [{"device_id": "0000:65:00.1"}]}"#).unwrap(),
+            Readings::default()
+        );
+    }
+
+    #[test]
+    fn xocl_boards() {
+        let tmp = tempfile::tempdir().unwrap();
+        let driver = tmp.path().join("bus/pci/drivers/xocl");
+        for entry in ["0000:65:00.1", "0000:17:00.1", "bind", "module"] {
+            fs::create_dir_all(driver.join(entry)).unwrap();
+        }
+        let boards = explore(tmp.path(), Path::new("xbutil")).unwrap();
+        let ids: Vec<_> = boards.iter().map(|b| b.bus_id()).collect();
+        assert_eq!(ids, vec!["0000:17:00.1", "0000:65:00.1"]);
+    }
+}
@@ -0,0 +1,191 @@
+//! Types of the Cloud Monitoring API v3.
+//!
+//! See <https://cloud.google.com/monitoring/api/ref_v3/rest/v3/projects.timeSeries/create>
+//! and <https://cloud.google.com/monitoring/api/ref_v3/rest/v3/projects.metricDescriptors/create>.
+
+use std::collections::BTreeMap;
+
+use alumet::{
+    measurement::WrappedMeasurementType,
+    units::{PrefixedUnit, Unit, UnitPrefix},
+};
+use serde::Serialize;
+
+/// The labels of every time series, in addition to the labels of the monitored resource.
+pub const LABELS: [(&str, &str); 5] = [
+    ("hostname", "Name of the host that produced the measurement"),
+    ("resource_kind", "Kind of the measured resource"),
+    ("resource_id", "Identifier of the measured resource"),
+    ("consumer_kind", "Kind of the resource consumer"),
+    ("consumer_id", "Identifier of the resource consumer"),
+];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricDescriptor<'a> {
+    #[serde(rename = "type")]
+    pub metric_type: &'a str,
+    pub metric_kind: &'static str,
+    pub value_type: &'static str,
+    pub unit: String,
+    pub description: &'a str,
+    pub display_name: &'a str,
+    pub labels: Vec<LabelDescriptor>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelDescriptor {
+    pub key: &'static str,
+    pub value_type: &'static str,
+    pub description: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTimeSeriesRequest<'a> {
+    pub time_series: Vec<TimeSeries<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSeries<'a> {
+    pub metric: MetricLabels<'a>,
+    pub resource: &'a MonitoredResource,
+    pub metric_kind: &'static str,
+    pub value_type: &'static str,
+    /// Exactly one point per request and per time series.
+    pub points: [Point; 1],
+}
+
+#[derive(Serialize)]
+pub struct MetricLabels<'a> {
+    #[serde(rename = "type")]
+    pub metric_type: &'a str,
+    pub labels: BTreeMap<&'static str, &'a str>,
+}
+
+#[derive(Serialize)]
+pub struct MonitoredResource {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub labels: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+pub struct Point {
+    pub interval: Interval,
+    pub value: TypedValue,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interval {
+    /// The time of a gauge, in RFC 3339 format.
+    pub end_time: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TypedValue {
+    DoubleValue(f64),
+    /// The 64-bit integers are encoded as strings in the JSON API.
+    Int64Value(String),
+}
+
+/// Returns the `valueType` of a metric.
+pub fn value_type(t: &WrappedMeasurementType) -> &'static str {
+    match t {
+        WrappedMeasurementType::F64 => "DOUBLE",
+        WrappedMeasurementType::U64 => "INT64",
+    }
+}
+
+/// Returns the type of a metric, such as `custom.googleapis.com/alumet/rapl_consumed_energy`.
+///
+/// The metric types can only contain letters, digits, `_` and `/`.
+pub fn metric_type(prefix: &str, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}/{name}", prefix.trim_end_matches('/'))
+}
+
+/// Converts a unit into the subset of UCUM that Cloud Monitoring understands.
+///
+/// The units that it cannot scale are kept as annotations, such as `{J}`, which are displayed as is.
+/// The annotations can only contain printable ASCII characters.
+pub fn unit(unit: &PrefixedUnit) -> String {
+    let prefix = match unit.prefix {
+        UnitPrefix::Nano => "n",
+        UnitPrefix::Micro => "u",
+        UnitPrefix::Milli => "m",
+        UnitPrefix::Plain => "",
+        UnitPrefix::Kilo => "k",
+        UnitPrefix::Mega => "M",
+        UnitPrefix::Giga => "G",
+    };
+    let symbol = format!("{prefix}{}", unit.base_unit.unique_name());
+    match (&unit.base_unit, &unit.prefix) {
+        (Unit::Second, _) | (Unit::Byte, _) => symbol,
+        (Unit::Unity, UnitPrefix::Plain) => String::from("1"),
+        (Unit::Percent, UnitPrefix::Plain) => String::from("%"),
+        _ => {
+            let annotation: String = symbol
+                .chars()
+                .filter(|c| c.is_ascii_graphic() && *c != '{' && *c != '}')
+                .collect();
+            format!("{{{annotation}}}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::units::{PrefixedUnit, Unit, UnitPrefix};
+    use pretty_assertions::assert_eq;
+
+    use super::{metric_type, unit};
+
+    #[test]
+    fn units() {
+        assert_eq!(unit(&PrefixedUnit::from(Unit::Second)), "s");
+        assert_eq!(
+            unit(&PrefixedUnit {
+                base_unit: Unit::Second,
+                prefix: UnitPrefix::Micro
+            }),
+            "us"
+        );
+        assert_eq!(
+            unit(&PrefixedUnit {
+                base_unit: Unit::Byte,
+                prefix: UnitPrefix::Kilo
+            }),
+            "kBy"
+        );
+        assert_eq!(unit(&PrefixedUnit::from(Unit::Unity)), "1");
+        assert_eq!(unit(&PrefixedUnit::from(Unit::Percent)), "%");
+        assert_eq!(
+            unit(&PrefixedUnit {
+                base_unit: Unit::Joule,
+                prefix: UnitPrefix::Milli
+            }),
+            "{mJ}"
+        );
+        assert_eq!(unit(&PrefixedUnit::from(Unit::DegreeCelsius)), "{Cel}");
+    }
+
+    #[test]
+    fn metric_types() {
+        assert_eq!(
+            metric_type("custom.googleapis.com/alumet/", "rapl_consumed_energy"),
+            "custom.googleapis.com/alumet/rapl_consumed_energy"
+        );
+        assert_eq!(
+            metric_type("custom.googleapis.com/alumet", "cpu.time-delta"),
+            "custom.googleapis.com/alumet/cpu_time_delta"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! OAuth 2.0 access tokens for the Google Cloud APIs.
+//!
+//! The tokens are obtained with the key of a service account (signed JWT),
+//! or from the metadata server of the instance when Alumet runs on Google Cloud.
+//! See <https://developers.google.com/identity/protocols/oauth2/service-account#httprest>.
+
+use std::{
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, anyhow};
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use reqwest::blocking::Client;
+use ring::{
+    rand::SystemRandom,
+    signature::{RSA_PKCS1_SHA256, RsaKeyPair},
+};
+use serde::{Deserialize, Serialize};
+
+/// Scope required to write time series and to create metric descriptors.
+const SCOPE: &str = "https://www.googleapis.com/auth/monitoring.write";
+/// Lifetime of the signed assertions (the maximum allowed by Google).
+const ASSERTION_LIFETIME: Duration = Duration::from_secs(3600);
+/// The tokens are renewed a bit before their expiration.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// The JSON key of a service account, as downloaded from the console.
+#[derive(Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub private_key_id: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    String::from("https://oauth2.googleapis.com/token")
+}
+
+impl ServiceAccountKey {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read(path)
+            .with_context(|| format!("failed to read the service account key {}", path.display()))?;
+        serde_json::from_slice(&content).with_context(|| format!("invalid service account key {}", path.display()))
+    }
+}
+
+/// How to obtain the tokens.
+pub enum Credentials {
+    ServiceAccount {
+        email: String,
+        key_id: Option<String>,
+        token_uri: String,
+        key: Box<RsaKeyPair>,
+    },
+    /// The default service account of the instance, through the metadata server.
+    Metadata { host: String },
+}
+
+impl Credentials {
+    pub fn service_account(key: &ServiceAccountKey) -> anyhow::Result<Self> {
+        let pem = pem::parse(&key.private_key).context("invalid private key in the service account key")?;
+        let rsa = RsaKeyPair::from_pkcs8(pem.contents())
+            .map_err(|e| anyhow!("invalid private key in the service account key: {e}"))?;
+        Ok(Credentials::ServiceAccount {
+            email: key.client_email.clone(),
+            key_id: key.private_key_id.clone(),
+            token_uri: key.token_uri.clone(),
+            key: Box::new(rsa),
+        })
+    }
+}
+
+/// Returns the host of the metadata server, which can be overridden by `GCE_METADATA_HOST`
+/// like in the Google Cloud client libraries.
+pub fn metadata_host() -> String {
+    std::env::var("GCE_METADATA_HOST").unwrap_or_else(|_| String::from("metadata.google.internal"))
+}
+
+/// Queries the metadata server, for instance `project/project-id`.
+pub fn query_metadata(client: &Client, host: &str, path: &str) -> anyhow::Result<String> {
+    let res = client
+        .get(format!("http://{host}/computeMetadata/v1/{path}"))
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .with_context(|| format!("failed to query the metadata server at {host}"))?;
+    let status = res.status();
+    let body = res.text().unwrap_or_default();
+    if !status.is_success() {
+        return Err(anyhow!("the metadata server returned {status}: {body}"));
+    }
+    Ok(body)
+}
+
+/// Provides valid tokens, and renews them when needed.
+pub struct TokenProvider {
+    credentials: Credentials,
+    /// The current token and its expiration.
+    token: Option<(String, Instant)>,
+}
+
+#[derive(Serialize)]
+struct Header<'a> {
+    alg: &'static str,
+    typ: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kid: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'static str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+impl TokenProvider {
+    pub fn new(credentials: Credentials) -> Self {
+        Self {
+            credentials,
+            token: None,
+        }
+    }
+
+    /// Returns a valid token, and requests a new one if needed.
+    pub fn token(&mut self, client: &Client) -> anyhow::Result<&str> {
+        let now = Instant::now();
+        let valid = matches!(&self.token, Some((_, expiry)) if *expiry > now + EXPIRY_MARGIN);
+        if !valid {
+            let response = self.request(client)?;
+            let expiry = now + Duration::from_secs(response.expires_in);
+            self.token = Some((response.access_token, expiry));
+        }
+        Ok(&self.token.as_ref().unwrap().0)
+    }
+
+    /// Forgets the current token, for instance because the API rejected it.
+    pub fn invalidate(&mut self) {
+        self.token = None;
+    }
+
+    fn request(&self, client: &Client) -> anyhow::Result<TokenResponse> {
+        let res = match &self.credentials {
+            Credentials::ServiceAccount {
+                email,
+                key_id,
+                token_uri,
+                key,
+            } => {
+                let iat = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                let claims = Claims {
+                    iss: email,
+                    scope: SCOPE,
+                    aud: token_uri,
+                    iat,
+                    exp: iat + ASSERTION_LIFETIME.as_secs(),
+                };
+                let assertion = sign_jwt(key, key_id.as_deref(), &claims)?;
+                client
+                    .post(token_uri)
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(format!(
+                        "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer&assertion={assertion}"
+                    ))
+                    .send()
+                    .with_context(|| format!("failed to request an access token from {token_uri}"))?
+            }
+            Credentials::Metadata { host } => client
+                .get(format!(
+                    "http://{host}/computeMetadata/v1/instance/service-accounts/default/token"
+                ))
+                .header("Metadata-Flavor", "Google")
+                .send()
+                .with_context(|| format!("failed to request an access token from the metadata server at {host}"))?,
+        };
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().unwrap_or_default();
+            return Err(anyhow!("failed to obtain an access token: {status}: {body}"));
+        }
+        let body = res.bytes().context("failed to read the access token response")?;
+        serde_json::from_slice(&body).context("invalid access token response")
+    }
+}
+
+/// Returns a JWT signed with RS256.
+fn sign_jwt(key: &RsaKeyPair, key_id: Option<&str>, claims: &Claims) -> anyhow::Result<String> {
+    let header = Header {
+        alg: "RS256",
+        typ: "JWT",
+        kid: key_id,
+    };
+    let mut jwt = BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?);
+    jwt.push('.');
+    jwt.push_str(&BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?));
+
+    let mut signature = vec![0; key.public().modulus_len()];
+    key.sign(&RSA_PKCS1_SHA256, &SystemRandom::new(), jwt.as_bytes(), &mut signature)
+        .map_err(|_| anyhow!("failed to sign the token request"))?;
+    jwt.push('.');
+    jwt.push_str(&BASE64_URL_SAFE_NO_PAD.encode(signature));
+    Ok(jwt)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+    use pretty_assertions::assert_eq;
+    use ring::signature::{KeyPair, RSA_PKCS1_2048_8192_SHA256, RsaKeyPair, UnparsedPublicKey};
+
+    use super::{Claims, sign_jwt};
+
+    const KEY: &str = include_str!("../tests/test-key.pem");
+
+    #[test]
+    fn jwt() {
+        let key = RsaKeyPair::from_pkcs8(pem::parse(KEY).unwrap().contents()).unwrap();
+        let claims = Claims {
+            iss: "alumet@project.iam.gserviceaccount.com",
+            scope: super::SCOPE,
+            aud: "https://oauth2.googleapis.com/token",
+            iat: 1_700_000_000,
+            exp: 1_700_003_600,
+        };
+        let jwt = sign_jwt(&key, Some("key-1"), &claims).unwrap();
+
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let header = BASE64_URL_SAFE_NO_PAD.decode(parts[0]).unwrap();
+        assert_eq!(
+            String::from_utf8(header).unwrap(),
+            r#"{"alg":"RS256","typ":"JWT","kid":"key-1"}"#
+        );
+        let claims = BASE64_URL_SAFE_NO_PAD.decode(parts[1]).unwrap();
+        assert_eq!(
+            String::from_utf8(claims).unwrap(),
+            r#"{"iss":"alumet@project.iam.gserviceaccount.com","scope":"https://www.googleapis.com/auth/monitoring.write","aud":"https://oauth2.googleapis.com/token","iat":1700000000,"exp":1700003600}"#
+        );
+
+        let signature = BASE64_URL_SAFE_NO_PAD.decode(parts[2]).unwrap();
+        let public_key = UnparsedPublicKey::new(&RSA_PKCS1_2048_8192_SHA256, key.public_key().as_ref());
+        let signed = format!("{}.{}", parts[0], parts[1]);
+        public_key.verify(signed.as_bytes(), &signature).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+mod api;
+mod auth;
+mod output;
+
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::Context;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::api::MonitoredResource;
+use crate::auth::{Credentials, ServiceAccountKey, TokenProvider};
+use crate::output::GcpMonitoringOutput;
+
+/// Cloud Monitoring rejects the points that are written more often than this, for a given time series.
+const MIN_SAMPLING_PERIOD: Duration = Duration::from_secs(5);
+
+pub struct GcpMonitoringPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for GcpMonitoringPlugin {
+    fn name() -> &'static str {
+        "gcp-monitoring"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(
+            config.min_interval >= MIN_SAMPLING_PERIOD,
+            "min_interval must be at least {}, the minimum sampling period of Cloud Monitoring",
+            humantime::format_duration(MIN_SAMPLING_PERIOD)
+        );
+        anyhow::ensure!(!config.metric_prefix.is_empty(), "metric_prefix must not be empty");
+        Ok(Box::new(GcpMonitoringPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .context("failed to create the HTTP client")?;
+
+        // Use the key of a service account if there is one, like the Google Cloud client libraries.
+        let key_path = config
+            .credentials
+            .clone()
+            .or_else(|| std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from));
+        let (credentials, key_project) = match key_path {
+            Some(path) => {
+                let key = ServiceAccountKey::load(&path)?;
+                (Credentials::service_account(&key)?, key.project_id)
+            }
+            None => {
+                let host = auth::metadata_host();
+                log::info!("No service account key provided, using the credentials of the instance.");
+                (Credentials::Metadata { host }, None)
+            }
+        };
+        let project_id = match config.project_id.clone().or(key_project) {
+            Some(project_id) => project_id,
+            None => auth::query_metadata(&client, &auth::metadata_host(), "project/project-id").context(
+                "No project_id specified in the config, and unable to retrieve it from the metadata server.",
+            )?,
+        };
+
+        let mut labels = config.resource_labels.clone();
+        if config.resource_type == "global" {
+            labels
+                .entry(String::from("project_id"))
+                .or_insert_with(|| project_id.clone());
+        }
+        let resource = MonitoredResource {
+            resource_type: config.resource_type.clone(),
+            labels,
+        };
+        let output = GcpMonitoringOutput::new(
+            client,
+            format!("{}/v3/projects/{project_id}", config.endpoint.trim_end_matches('/')),
+            TokenProvider::new(credentials),
+            resource,
+            hostname,
+            config.metric_prefix.clone(),
+            config.min_interval,
+        );
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Google Cloud project in which the metrics are written.
+    /// Defaults to the project of the service account key, or to the project of the instance.
+    pub project_id: Option<String>,
+    /// Path to the JSON key of a service account.
+    /// Defaults to `GOOGLE_APPLICATION_CREDENTIALS`, or to the service account of the instance.
+    pub credentials: Option<PathBuf>,
+    /// Prefix of the metric types, followed by `/` and the name of the metric.
+    pub metric_prefix: String,
+    /// Type of the monitored resource, such as `global`, `gce_instance` or `k8s_node`.
+    pub resource_type: String,
+    /// Labels of the monitored resource. For `global`, `project_id` is added automatically.
+    pub resource_labels: BTreeMap<String, String>,
+    /// Value of the `hostname` label. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Minimum time between two points of a time series, at least 5 seconds.
+    #[serde(with = "humantime_serde")]
+    pub min_interval: Duration,
+    /// URL of the Cloud Monitoring API.
+    pub endpoint: String,
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            project_id: None,
+            credentials: None,
+            metric_prefix: String::from("custom.googleapis.com/alumet"),
+            resource_type: String::from("global"),
+            resource_labels: BTreeMap::new(),
+            hostname: None,
+            min_interval: Duration::from_secs(10),
+            endpoint: String::from("https://monitoring.googleapis.com"),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
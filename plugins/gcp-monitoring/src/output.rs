@@ -0,0 +1,247 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, SystemTime},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::{Metric, RawMetricId},
+    pipeline::elements::{
+        error::WriteError,
+        output::{OutputContext, error::WriteRetry},
+    },
+};
+use anyhow::{Context, anyhow};
+use reqwest::{StatusCode, blocking::Client};
+
+use crate::{
+    api::{self, CreateTimeSeriesRequest, Interval, MetricLabels, MonitoredResource, Point, TimeSeries, TypedValue},
+    auth::TokenProvider,
+};
+
+/// Maximum number of time series per request, imposed by the API.
+const MAX_SERIES_PER_REQUEST: usize = 200;
+
+pub struct GcpMonitoringOutput {
+    client: Client,
+    /// URL of the project, such as `https://monitoring.googleapis.com/v3/projects/my-project`.
+    project_url: String,
+    tokens: TokenProvider,
+    resource: MonitoredResource,
+    hostname: String,
+    metric_prefix: String,
+    /// Minimum time between two points of the same time series.
+    min_interval: Duration,
+    /// The type of the metrics whose descriptor has already been created (or refused).
+    descriptors: HashMap<RawMetricId, String>,
+    /// Time of the last point sent for each time series.
+    last_sent: HashMap<SeriesKey, Timestamp>,
+}
+
+/// Identifies a time series: the metric type and the labels.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct SeriesKey {
+    metric_type: String,
+    resource_kind: String,
+    resource_id: String,
+    consumer_kind: String,
+    consumer_id: String,
+}
+
+impl GcpMonitoringOutput {
+    pub fn new(
+        client: Client,
+        project_url: String,
+        tokens: TokenProvider,
+        resource: MonitoredResource,
+        hostname: String,
+        metric_prefix: String,
+        min_interval: Duration,
+    ) -> Self {
+        Self {
+            client,
+            project_url,
+            tokens,
+            resource,
+            hostname,
+            metric_prefix,
+            min_interval,
+            descriptors: HashMap::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// Creates the descriptor of a metric, if it has not been done yet, and returns the type of the metric.
+    ///
+    /// If the API refuses the descriptor, the points are written anyway:
+    /// Cloud Monitoring creates a descriptor automatically, without the unit and the description.
+    fn ensure_descriptor(&mut self, id: RawMetricId, metric: &Metric) -> Result<String, WriteError> {
+        if let Some(metric_type) = self.descriptors.get(&id) {
+            return Ok(metric_type.clone());
+        }
+        let metric_type = api::metric_type(&self.metric_prefix, &metric.name);
+        let descriptor = api::MetricDescriptor {
+            metric_type: &metric_type,
+            metric_kind: "GAUGE",
+            value_type: api::value_type(&metric.value_type),
+            unit: api::unit(&metric.unit),
+            description: &metric.description,
+            display_name: &metric.name,
+            labels: api::LABELS
+                .iter()
+                .map(|(key, description)| api::LabelDescriptor {
+                    key,
+                    value_type: "STRING",
+                    description,
+                })
+                .collect(),
+        };
+        let body = serde_json::to_vec(&descriptor).context("failed to serialize the metric descriptor")?;
+        let url = format!("{}/metricDescriptors", self.project_url);
+        match self.post(&url, body)? {
+            None => log::debug!("Metric descriptor {metric_type} created"),
+            Some((status, body)) => {
+                log::warn!("Cloud Monitoring refused the descriptor of {metric_type} with status {status}: {body}")
+            }
+        }
+        self.descriptors.insert(id, metric_type.clone());
+        Ok(metric_type)
+    }
+
+    /// Sends a request to the API.
+    ///
+    /// Returns the status and the body of the response if the request has been rejected,
+    /// or an error if it can be retried later.
+    fn post(&mut self, url: &str, body: Vec<u8>) -> Result<Option<(StatusCode, String)>, WriteError> {
+        let token = self
+            .tokens
+            .token(&self.client)
+            .context("failed to authenticate to Google Cloud")
+            .retry_write()?;
+        let res = self
+            .client
+            .post(url)
+            .bearer_auth(token)
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .context("failed to send the request to Cloud Monitoring")
+            .retry_write()?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(None);
+        }
+        let body = res.text().unwrap_or_default();
+        if status == StatusCode::UNAUTHORIZED {
+            // The token has been revoked or has expired earlier than expected.
+            self.tokens.invalidate();
+            return Err(WriteError::CanRetry(anyhow!("Cloud Monitoring error {status}: {body}")));
+        }
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            // The API is busy or unavailable, try again later.
+            return Err(WriteError::CanRetry(anyhow!("Cloud Monitoring error {status}: {body}")));
+        }
+        Ok(Some((status, body)))
+    }
+
+    /// Returns true if the point is too close to the previous point of its time series.
+    ///
+    /// Cloud Monitoring rejects the points that are older than the last point of their time series,
+    /// and the points that are written more often than every 5 seconds.
+    fn too_early(&self, key: &SeriesKey, t: Timestamp) -> bool {
+        match self.last_sent.get(key) {
+            Some(last) => match SystemTime::from(t).duration_since(SystemTime::from(*last)) {
+                Ok(elapsed) => elapsed < self.min_interval,
+                Err(_) => true,
+            },
+            None => false,
+        }
+    }
+}
+
+impl alumet::pipeline::Output for GcpMonitoringOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        // Only the last point of each time series is kept: a request can contain a single point per series.
+        let mut latest: BTreeMap<SeriesKey, &MeasurementPoint> = BTreeMap::new();
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            let key = SeriesKey {
+                metric_type: self.ensure_descriptor(m.metric, metric)?,
+                resource_kind: m.resource.kind().to_owned(),
+                resource_id: m.resource.id_display().to_string(),
+                consumer_kind: m.consumer.kind().to_owned(),
+                consumer_id: m.consumer.id_display().to_string(),
+            };
+            if self.too_early(&key, m.timestamp) {
+                continue;
+            }
+            match latest.get(&key) {
+                Some(previous) if previous.timestamp > m.timestamp => (),
+                _ => {
+                    latest.insert(key, m);
+                }
+            }
+        }
+
+        let latest: Vec<_> = latest.into_iter().collect();
+        for batch in latest.chunks(MAX_SERIES_PER_REQUEST) {
+            let time_series = batch
+                .iter()
+                .map(|(key, m)| time_series(key, m, &self.resource, &self.hostname))
+                .collect();
+            let request = CreateTimeSeriesRequest { time_series };
+            let body = serde_json::to_vec(&request).context("failed to serialize the time series")?;
+            let url = format!("{}/timeSeries", self.project_url);
+            if let Some((status, body)) = self.post(&url, body)? {
+                // Invalid data, missing permissions... sending the points again would not help.
+                log::error!("Cloud Monitoring rejected the time series with status {status}: {body}");
+            }
+            for (key, m) in batch {
+                self.last_sent.insert(key.clone(), m.timestamp);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn time_series<'a>(
+    key: &'a SeriesKey,
+    m: &MeasurementPoint,
+    resource: &'a MonitoredResource,
+    hostname: &'a str,
+) -> TimeSeries<'a> {
+    let labels = BTreeMap::from([
+        ("hostname", hostname),
+        ("resource_kind", key.resource_kind.as_str()),
+        ("resource_id", key.resource_id.as_str()),
+        ("consumer_kind", key.consumer_kind.as_str()),
+        ("consumer_id", key.consumer_id.as_str()),
+    ]);
+    let (value_type, value) = match m.value {
+        WrappedMeasurementValue::F64(v) => ("DOUBLE", TypedValue::DoubleValue(v)),
+        // INT64 is signed, the (unlikely) larger values are saturated.
+        WrappedMeasurementValue::U64(v) => (
+            "INT64",
+            TypedValue::Int64Value(i64::try_from(v).unwrap_or(i64::MAX).to_string()),
+        ),
+    };
+    TimeSeries {
+        metric: MetricLabels {
+            metric_type: &key.metric_type,
+            labels,
+        },
+        resource,
+        metric_kind: "GAUGE",
+        value_type,
+        points: [Point {
+            interval: Interval {
+                end_time: humantime::format_rfc3339_nanos(SystemTime::from(m.timestamp)).to_string(),
+            },
+            value,
+        }],
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    pipeline::elements::error::WriteError,
+    plugin::rust::serialize_config,
+    resources::{Resource, ResourceConsumer},
+    test::{
+        PluginHarness,
+        golden::{CapturedRequest, HttpCapture, SampleMeasurements, assert_golden},
+    },
+};
+use mockito::{Matcher, Mock, Server};
+use plugin_gcp_monitoring::{Config, GcpMonitoringPlugin};
+use pretty_assertions::assert_eq;
+
+const TOKEN_RESPONSE: &str = r#"{"access_token":"test-token","expires_in":3600,"token_type":"Bearer"}"#;
+
+/// Writes a service account key that uses the token endpoint of the mock server.
+fn write_key(dir: &Path, token_server: &Server) -> anyhow::Result<std::path::PathBuf> {
+    let key = serde_json::json!({
+        "type": "service_account",
+        "project_id": "test-project",
+        "private_key_id": "key-1",
+        "private_key": include_str!("test-key.pem"),
+        "client_email": "alumet@test-project.iam.gserviceaccount.com",
+        "token_uri": format!("{}/token", token_server.url()),
+    });
+    let path = dir.join("key.json");
+    std::fs::write(&path, serde_json::to_vec(&key)?)?;
+    Ok(path)
+}
+
+fn config(key: &Path, api: &HttpCapture) -> Config {
+    Config {
+        credentials: Some(key.to_owned()),
+        hostname: Some(String::from("node-1")),
+        endpoint: api.url(),
+        ..Config::default()
+    }
+}
+
+fn mock_token(server: &mut Server) -> Mock {
+    server
+        .mock("POST", "/token")
+        .match_header("content-type", "application/x-www-form-urlencoded")
+        .match_body(Matcher::Regex(String::from(
+            "^grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer&assertion=[A-Za-z0-9_.-]+$",
+        )))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(TOKEN_RESPONSE)
+        .create()
+}
+
+/// Returns the requests sent to an endpoint of the API, after checking their authentication.
+fn sent_to(api: &HttpCapture, path: &str) -> Vec<CapturedRequest> {
+    let requests: Vec<CapturedRequest> = api.requests().into_iter().filter(|r| r.path == path).collect();
+    for request in &requests {
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.header("authorization"), Some("Bearer test-token"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+    }
+    requests
+}
+
+/// The energy of the first CPU package, at `START + secs`.
+fn energy(sample: &SampleMeasurements, secs: u64, value: f64) -> MeasurementBuffer {
+    let t = Timestamp::from(UNIX_EPOCH + SampleMeasurements::START + Duration::from_secs(secs));
+    MeasurementBuffer::from(vec![MeasurementPoint::new(
+        t,
+        sample.energy,
+        Resource::CpuPackage { id: 0 },
+        ResourceConsumer::LocalMachine,
+        value,
+    )])
+}
+
+/// Puts each JSON object of the array in a request on its own line, to make the golden files readable.
+fn split(requests: &[CapturedRequest]) -> String {
+    requests
+        .iter()
+        .map(|r| r.body_str().replace("},{\"metric\"", "},\n{\"metric\"") + "\n")
+        .collect()
+}
+
+#[test]
+fn write() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir()?;
+    let key = write_key(dir.path(), &server)?;
+    let token = mock_token(&mut server).expect(1);
+    let api = HttpCapture::start()?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<GcpMonitoringPlugin>(config(&key, &api))?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    // already sent
+    output.write(&sample.measurements()).unwrap();
+    // too early: less than 10 seconds after the previous point
+    output.write(&energy(&sample, 5, 1.0)).unwrap();
+    output.write(&energy(&sample, 11, 2.0)).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    token.assert();
+    let descriptors = sent_to(&api, "/v3/projects/test-project/metricDescriptors");
+    assert_eq!(descriptors.len(), 2);
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/descriptors.json"),
+        split(&descriptors),
+    );
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/time_series.json"),
+        split(&sent_to(&api, "/v3/projects/test-project/timeSeries")),
+    );
+    Ok(())
+}
+
+#[test]
+fn resource() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir()?;
+    let key = write_key(dir.path(), &server)?;
+    mock_token(&mut server);
+    let api = HttpCapture::start()?;
+
+    let config = Config {
+        project_id: Some(String::from("other-project")),
+        resource_type: String::from("gce_instance"),
+        resource_labels: [("instance_id", "1234"), ("zone", "europe-west1-b")]
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect(),
+        ..config(&key, &api)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<GcpMonitoringPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&energy(&sample, 0, 1.0)).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let series = sent_to(&api, "/v3/projects/other-project/timeSeries");
+    assert_eq!(series.len(), 1);
+    let body = series[0].body_str();
+    assert!(
+        body.contains(r#""resource":{"type":"gce_instance","labels":{"instance_id":"1234","zone":"europe-west1-b"}}"#),
+        "{body}"
+    );
+    Ok(())
+}
+
+#[test]
+fn server_error() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir()?;
+    let key = write_key(dir.path(), &server)?;
+    mock_token(&mut server);
+    let api = HttpCapture::with_status(503)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<GcpMonitoringPlugin>(config(&key, &api))?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&energy(&sample, 0, 1.0));
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    // neither the descriptor nor the point have been sent, they can be sent again
+    let res = output.write(&energy(&sample, 0, 1.0));
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    output.finish()?;
+    harness.stop()?;
+
+    assert_eq!(sent_to(&api, "/v3/projects/test-project/metricDescriptors").len(), 2);
+    Ok(())
+}
+
+#[test]
+fn rejected() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir()?;
+    let key = write_key(dir.path(), &server)?;
+    mock_token(&mut server);
+    // the points are written even if the descriptors are refused
+    let api = HttpCapture::with_status(400)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<GcpMonitoringPlugin>(config(&key, &api))?;
+    let mut output = harness.output("out")?;
+    output.write(&energy(&sample, 0, 1.0)).unwrap();
+    output.write(&energy(&sample, 20, 2.0)).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    assert_eq!(sent_to(&api, "/v3/projects/test-project/metricDescriptors").len(), 1);
+    assert_eq!(sent_to(&api, "/v3/projects/test-project/timeSeries").len(), 2);
+    Ok(())
+}
+
+#[test]
+fn token_refused() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let dir = tempfile::tempdir()?;
+    let key = write_key(dir.path(), &server)?;
+    server
+        .mock("POST", "/token")
+        .with_status(400)
+        .with_body(r#"{"error":"invalid_grant"}"#)
+        .create();
+    let api = HttpCapture::start()?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<GcpMonitoringPlugin>(config(&key, &api))?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&energy(&sample, 0, 1.0));
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    output.finish()?;
+    harness.stop()?;
+
+    assert!(api.requests().is_empty());
+    Ok(())
+}
+
+#[test]
+fn invalid_config() -> anyhow::Result<()> {
+    let config = Config {
+        min_interval: Duration::from_secs(1),
+        ..Config::default()
+    };
+    assert!(PluginHarness::<GcpMonitoringPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
@@ -10,6 +10,7 @@ use alumet::{
     pipeline::elements::source::trigger::TriggerSpec,
     plugin::{
         AlumetPluginStart, ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
     units::{PrefixedUnit, Unit},
@@ -30,6 +31,11 @@ impl AlumetPlugin for GraceHopperPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(PathBuf::from(config.root_path))]))
+    }
+
     fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
         let config = deserialize_config(config)?;
         Ok(Box::new(GraceHopperPlugin { config }))
@@ -0,0 +1,117 @@
+mod output;
+mod path;
+mod protocol;
+
+use std::time::Duration;
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::output::GraphiteOutput;
+use crate::path::PathTemplate;
+pub use crate::protocol::Protocol;
+
+pub struct GraphitePlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for GraphitePlugin {
+    fn name() -> &'static str {
+        "graphite"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.batch_size == 0 {
+            return Err(anyhow!("batch_size must be greater than zero"));
+        }
+        Ok(Box::new(GraphitePlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let path = PathTemplate::parse(&config.path, &hostname)?;
+        let port = config.port.unwrap_or_else(|| config.protocol.default_port());
+
+        let output = GraphiteOutput::new(
+            format!("{}:{port}", config.host),
+            config.timeout,
+            config.protocol,
+            path,
+            config.attributes_as_tags,
+            config.batch_size,
+        );
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the Carbon receiver.
+    pub host: String,
+    /// Defaults to 2003 for the plaintext protocol, and 2004 for the pickle protocol.
+    pub port: Option<u16>,
+    /// Protocol: `"plaintext"` or `"pickle"`.
+    pub protocol: Protocol,
+    /// Name of the machine in the paths. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Template of the path of each measurement.
+    ///
+    /// See [`MeasurementPlaceholder`](alumet::template::MeasurementPlaceholder) for the available placeholders,
+    /// in addition to `{hostname}`.
+    pub path: String,
+    /// Append the attributes to the paths, as Graphite tags (requires Graphite 1.1 or later).
+    pub attributes_as_tags: bool,
+    /// Maximum number of datapoints in a pickle message.
+    pub batch_size: usize,
+    /// Timeout of the connection to Carbon, and of each write.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: None,
+            protocol: Protocol::Plaintext,
+            hostname: None,
+            path: String::from(
+                "alumet.{hostname}.{metric}.{resource_kind}.{resource_id}.{consumer_kind}.{consumer_id}",
+            ),
+            attributes_as_tags: false,
+            batch_size: 500,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
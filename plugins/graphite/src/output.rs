@@ -0,0 +1,122 @@
+use std::{
+    io::Write,
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::{
+        Output,
+        elements::{
+            error::WriteError,
+            output::{OutputContext, error::WriteRetry},
+        },
+    },
+};
+use anyhow::{Context, anyhow};
+
+use crate::{
+    path::{PathTemplate, append_tags},
+    protocol::{Datapoint, Protocol, encode_pickle, encode_plaintext},
+};
+
+pub struct GraphiteOutput {
+    /// Address of the Carbon receiver, such as `localhost:2003`.
+    address: String,
+    timeout: Duration,
+    /// `None` if the connection has not been established yet, or has been lost.
+    stream: Option<TcpStream>,
+    protocol: Protocol,
+    path: PathTemplate,
+    attributes_as_tags: bool,
+    batch_size: usize,
+}
+
+impl GraphiteOutput {
+    pub fn new(
+        address: String,
+        timeout: Duration,
+        protocol: Protocol,
+        path: PathTemplate,
+        attributes_as_tags: bool,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            address,
+            timeout,
+            stream: None,
+            protocol,
+            path,
+            attributes_as_tags,
+            batch_size,
+        }
+    }
+
+    fn connect(&self) -> anyhow::Result<TcpStream> {
+        let addr = self
+            .address
+            .to_socket_addrs()
+            .with_context(|| format!("failed to resolve {}", self.address))?
+            .next()
+            .ok_or_else(|| anyhow!("no address found for {}", self.address))?;
+        let stream = TcpStream::connect_timeout(&addr, self.timeout)
+            .with_context(|| format!("failed to connect to Carbon at {}", self.address))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_nodelay(true)?;
+        log::debug!("Connected to Carbon at {}", self.address);
+        Ok(stream)
+    }
+
+    /// Sends the data, and closes the connection if it fails, so that the next write reconnects.
+    fn send(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => self.stream.insert(self.connect()?),
+        };
+        if let Err(e) = stream.write_all(data) {
+            self.stream = None;
+            return Err(e).with_context(|| format!("failed to send the datapoints to Carbon at {}", self.address));
+        }
+        Ok(())
+    }
+}
+
+impl Output for GraphiteOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut datapoints = Vec::with_capacity(measurements.len());
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            let mut path = self.path.render(m, metric);
+            if self.attributes_as_tags {
+                append_tags(&mut path, m);
+            }
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
+            };
+            datapoints.push(Datapoint {
+                path,
+                value,
+                timestamp: m.timestamp.to_unix_timestamp().0,
+            });
+        }
+
+        let mut data = Vec::new();
+        match self.protocol {
+            Protocol::Plaintext => encode_plaintext(&mut data, &datapoints),
+            // Carbon limits the size of the pickle messages, hence the batches.
+            Protocol::Pickle => {
+                for batch in datapoints.chunks(self.batch_size) {
+                    encode_pickle(&mut data, batch);
+                }
+            }
+        }
+        // Carbon does not acknowledge the datapoints: the measurements are sent again if the connection fails.
+        self.send(&data).retry_write()?;
+        Ok(())
+    }
+}
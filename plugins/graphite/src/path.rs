@@ -0,0 +1,136 @@
+//! Path templates, such as `alumet.{hostname}.{metric}`.
+
+use alumet::{measurement::MeasurementPoint, metrics::Metric, template::MeasurementTemplate};
+use anyhow::{Context, anyhow};
+
+/// A parsed path template.
+#[derive(Debug, PartialEq)]
+pub struct PathTemplate(MeasurementTemplate);
+
+impl PathTemplate {
+    /// Parses a template.
+    ///
+    /// The placeholders are replaced by the corresponding values for each measurement.
+    /// The hostname is resolved here, since it does not change.
+    pub fn parse(template: &str, hostname: &str) -> anyhow::Result<Self> {
+        let parsed = MeasurementTemplate::parse_measurement(template, &sanitize(hostname))
+            .with_context(|| format!("invalid path template {template:?}"))?;
+        if parsed.literal().contains(|c: char| c.is_whitespace() || c == ';') {
+            return Err(anyhow!(
+                "whitespace and semicolons are not allowed in path template {template:?}"
+            ));
+        }
+        if parsed.is_empty() {
+            return Err(anyhow!("the path template is empty"));
+        }
+        Ok(Self(parsed))
+    }
+
+    /// Returns the Graphite path of a measurement.
+    ///
+    /// The empty values remove their node from the path: with the template `{resource_kind}.{resource_id}`,
+    /// the path of a measurement about the local machine is `local_machine`, not `local_machine.`.
+    pub fn render(&self, m: &MeasurementPoint, metric: &Metric) -> String {
+        self.0
+            .render_measurement(m, metric, sanitize)
+            .split('.')
+            .filter(|node| !node.is_empty())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// Replaces the characters that have a special meaning in Graphite paths.
+///
+/// A value must not create new nodes (`.`), and must not contain whitespace, which separates
+/// the fields of the plaintext protocol, or `;`, which starts the tags.
+pub fn sanitize(value: &str) -> String {
+    value.replace(|c: char| c == '.' || c == '/' || c == ';' || c.is_whitespace(), "_")
+}
+
+/// Appends the attributes of a measurement to its path, as Graphite tags: `path;key1=value1;key2=value2`.
+///
+/// The tags are sorted by key, and the characters that are not allowed in tags are replaced by `_`.
+pub fn append_tags(path: &mut String, m: &MeasurementPoint) {
+    let mut tags: Vec<(String, String)> = m
+        .attributes()
+        .map(|(key, value)| (sanitize_tag(key), sanitize_tag(&value.to_string())))
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    tags.sort();
+    for (key, value) in tags {
+        path.push(';');
+        path.push_str(&key);
+        path.push('=');
+        path.push_str(&value);
+    }
+}
+
+fn sanitize_tag(value: &str) -> String {
+    value.replace(
+        |c: char| matches!(c, ';' | '!' | '^' | '=' | '~') || c.is_whitespace(),
+        "_",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        measurement::MeasurementPoint,
+        resources::ResourceConsumer,
+        test::fixture::{self, metric},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{PathTemplate, append_tags};
+
+    fn point(consumer: ResourceConsumer) -> MeasurementPoint {
+        fixture::point(consumer).with_attr("domain", "package")
+    }
+
+    #[test]
+    fn render() {
+        let template = PathTemplate::parse("alumet.{hostname}.{metric}.{attr.domain}", "node-1.example.com").unwrap();
+        assert_eq!(
+            template.render(&point(ResourceConsumer::LocalMachine), &metric()),
+            "alumet.node-1_example_com.rapl_consumed_energy.package"
+        );
+
+        let template = PathTemplate::parse(
+            "{resource_kind}.{resource_id}.{consumer_kind}.{consumer_id}.{attr.missing}",
+            "node",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&point(ResourceConsumer::LocalMachine), &metric()),
+            "cpu_package.1.local_machine"
+        );
+        let consumer = ResourceConsumer::ControlGroup {
+            path: "/system.slice/test.service".into(),
+        };
+        assert_eq!(
+            template.render(&point(consumer), &metric()),
+            "cpu_package.1.cgroup._system_slice_test_service"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(PathTemplate::parse("alumet.{host}", "node").is_err());
+        assert!(PathTemplate::parse("alumet.{metric", "node").is_err());
+        assert!(PathTemplate::parse("alumet.{attr.}", "node").is_err());
+        assert!(PathTemplate::parse("alumet {metric}", "node").is_err());
+        assert!(PathTemplate::parse("alumet;tag=x", "node").is_err());
+        assert!(PathTemplate::parse("", "node").is_err());
+    }
+
+    #[test]
+    fn tags() {
+        let m = point(ResourceConsumer::LocalMachine)
+            .with_attr("kind", "a=b c")
+            .with_attr("cpu", 3_u64);
+        let mut path = String::from("alumet.energy");
+        append_tags(&mut path, &m);
+        assert_eq!(path, "alumet.energy;cpu=3;domain=package;kind=a_b_c");
+    }
+}
@@ -0,0 +1,129 @@
+//! Encoding of the datapoints in the plaintext and pickle protocols of Carbon.
+//!
+//! See <https://graphite.readthedocs.io/en/latest/feeding-carbon.html>.
+
+use serde::{Deserialize, Serialize};
+
+/// Protocol used to send the datapoints to Carbon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// One line per datapoint: `path value timestamp`.
+    #[default]
+    Plaintext,
+    /// Batches of datapoints, serialized with the pickle format of Python, which is more efficient.
+    Pickle,
+}
+
+impl Protocol {
+    /// Returns the port of the Carbon receiver that handles this protocol, by default.
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Protocol::Plaintext => 2003,
+            Protocol::Pickle => 2004,
+        }
+    }
+}
+
+/// A value at a given time, in seconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Datapoint {
+    pub path: String,
+    pub value: f64,
+    pub timestamp: u64,
+}
+
+/// Appends the datapoints to `buf`, in the plaintext protocol.
+pub fn encode_plaintext(buf: &mut Vec<u8>, datapoints: &[Datapoint]) {
+    use std::io::Write;
+
+    for d in datapoints {
+        writeln!(buf, "{} {} {}", d.path, d.value, d.timestamp).expect("writing to a Vec should not fail");
+    }
+}
+
+// Opcodes of the pickle format, see the module `pickletools` of Python.
+const PROTO: u8 = 0x80;
+const EMPTY_LIST: u8 = b']';
+const MARK: u8 = b'(';
+const BINUNICODE: u8 = b'X';
+const BINFLOAT: u8 = b'G';
+const TUPLE2: u8 = 0x86;
+const APPENDS: u8 = b'e';
+const STOP: u8 = b'.';
+
+/// Appends the datapoints to `buf`, in the pickle protocol.
+///
+/// The message is a list of `(path, (timestamp, value))` tuples, prefixed by its length
+/// (4 bytes, big-endian). Carbon converts the timestamps and the values to floats, therefore
+/// both are encoded as floats.
+pub fn encode_pickle(buf: &mut Vec<u8>, datapoints: &[Datapoint]) {
+    let len_pos = buf.len();
+    buf.extend_from_slice(&[0; 4]);
+
+    buf.extend_from_slice(&[PROTO, 2, EMPTY_LIST, MARK]);
+    for d in datapoints {
+        buf.push(BINUNICODE);
+        buf.extend_from_slice(&(d.path.len() as u32).to_le_bytes());
+        buf.extend_from_slice(d.path.as_bytes());
+        buf.push(BINFLOAT);
+        buf.extend_from_slice(&(d.timestamp as f64).to_be_bytes());
+        buf.push(BINFLOAT);
+        buf.extend_from_slice(&d.value.to_be_bytes());
+        buf.extend_from_slice(&[TUPLE2, TUPLE2]);
+    }
+    buf.extend_from_slice(&[APPENDS, STOP]);
+
+    let len = (buf.len() - len_pos - 4) as u32;
+    buf[len_pos..len_pos + 4].copy_from_slice(&len.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{Datapoint, encode_pickle, encode_plaintext};
+
+    fn datapoints() -> Vec<Datapoint> {
+        vec![
+            Datapoint {
+                path: String::from("a.b"),
+                value: 12.5,
+                timestamp: 1_700_000_000,
+            },
+            Datapoint {
+                path: String::from("c"),
+                value: 42.0,
+                timestamp: 1_700_000_001,
+            },
+        ]
+    }
+
+    #[test]
+    fn plaintext() {
+        let mut buf = Vec::new();
+        encode_plaintext(&mut buf, &datapoints());
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "a.b 12.5 1700000000\nc 42 1700000001\n"
+        );
+    }
+
+    #[test]
+    fn pickle() {
+        let mut buf = Vec::new();
+        encode_pickle(&mut buf, &datapoints());
+        // pickle.dumps([("a.b", (1700000000.0, 12.5)), ("c", (1700000001.0, 42.0))], protocol=2),
+        // without the MEMOIZE opcodes that Python adds
+        let mut expected = b"\x80\x02](".to_vec();
+        expected.extend_from_slice(b"X\x03\x00\x00\x00a.b");
+        expected.extend_from_slice(b"GA\xd9\x54\xfc\x40\x00\x00\x00");
+        expected.extend_from_slice(b"G@)\x00\x00\x00\x00\x00\x00\x86\x86");
+        expected.extend_from_slice(b"X\x01\x00\x00\x00c");
+        expected.extend_from_slice(b"GA\xd9\x54\xfc\x40\x40\x00\x00");
+        expected.extend_from_slice(b"G@E\x00\x00\x00\x00\x00\x00\x86\x86");
+        expected.extend_from_slice(b"e.");
+        assert_eq!(&buf[..4], &(expected.len() as u32).to_be_bytes());
+        assert_eq!(&buf[4..], &expected[..]);
+    }
+}
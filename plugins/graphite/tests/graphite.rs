@@ -0,0 +1,112 @@
+use std::{
+    io::Read,
+    net::TcpListener,
+    thread::{self, JoinHandle},
+};
+
+use alumet::{
+    pipeline::elements::error::WriteError,
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_graphite::{Config, GraphitePlugin, Protocol};
+use pretty_assertions::assert_eq;
+
+/// Accepts one connection and returns everything that has been received on it.
+fn fake_carbon(listener: TcpListener) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        received
+    })
+}
+
+fn config(port: u16) -> Config {
+    Config {
+        host: String::from("127.0.0.1"),
+        port: Some(port),
+        hostname: Some(String::from("node-1")),
+        ..Config::default()
+    }
+}
+
+/// Writes the sample measurements with the given config.
+fn write_sample(config: Config) -> anyhow::Result<()> {
+    let mut harness = PluginHarness::<GraphitePlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()
+}
+
+#[test]
+fn plaintext() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let config = Config {
+        attributes_as_tags: true,
+        ..config(listener.local_addr()?.port())
+    };
+    let carbon = fake_carbon(listener);
+    write_sample(config)?;
+
+    let received = String::from_utf8(carbon.join().unwrap())?;
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/plaintext.txt"),
+        received,
+    );
+    Ok(())
+}
+
+#[test]
+fn pickle_batches() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let config = Config {
+        protocol: Protocol::Pickle,
+        path: String::from("{metric}.{resource_kind}"),
+        batch_size: 2,
+        ..config(listener.local_addr()?.port())
+    };
+    let carbon = fake_carbon(listener);
+    write_sample(config)?;
+
+    // 5 measurements in batches of 2: 3 messages
+    let received = carbon.join().unwrap();
+    let mut rest = &received[..];
+    let mut n_messages = 0;
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into()?) as usize;
+        let message = &rest[4..4 + len];
+        assert!(message.starts_with(b"\x80\x02]("));
+        assert!(message.ends_with(b"e."));
+        rest = &rest[4 + len..];
+        n_messages += 1;
+    }
+    assert_eq!(n_messages, 3);
+    Ok(())
+}
+
+#[test]
+fn retry_when_carbon_is_down() -> anyhow::Result<()> {
+    // Find a free port, then close it.
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let mut harness = PluginHarness::<GraphitePlugin>::start(serialize_config(config(port))?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    assert!(matches!(res, Err(WriteError::CanRetry(_))), "unexpected result {res:?}");
+
+    // Once Carbon is up, the output connects again.
+    let carbon = fake_carbon(TcpListener::bind(("127.0.0.1", port))?);
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+    let received = String::from_utf8(carbon.join().unwrap())?;
+    assert_eq!(received.lines().count(), 5);
+    Ok(())
+}
@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Use a vendored protoc, so that building the plugin does not require protobuf to be installed.
+    let protoc = protoc_bin_vendored::protoc_bin_path()?;
+    // SAFETY: build scripts are single-threaded.
+    unsafe { std::env::set_var("PROTOC", protoc) };
+
+    tonic_build::configure().compile_protos(&["proto/alumet_control.proto"], &["proto"])?;
+    Ok(())
+}
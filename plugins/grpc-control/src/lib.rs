@@ -0,0 +1,141 @@
+//! gRPC control of the Alumet agent, with live streaming of the measurements.
+//!
+//! See `proto/alumet_control.proto` for the definition of the service.
+
+mod output;
+mod service;
+
+/// Code generated from the protobuf definitions.
+pub mod proto {
+    tonic::include_proto!("alumet.control.v1");
+}
+
+use std::{net::SocketAddr, time::Duration};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use tokio::{runtime::Runtime, sync::broadcast};
+use tokio_util::sync::CancellationToken;
+
+use output::{BroadcastOutput, SharedBatch};
+use proto::alumet_control_server::AlumetControlServer;
+use service::ControlService;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address and port on which the gRPC server listens.
+    pub address: String,
+    /// Maximum number of measurement batches that can be waiting to be sent to a slow client.
+    ///
+    /// If a client is too slow, it will miss some measurements.
+    pub stream_buffer_size: usize,
+}
+
+pub struct GrpcControlPlugin {
+    config: Config,
+    measurements_tx: Option<broadcast::Sender<SharedBatch>>,
+    server: Option<GrpcServer>,
+}
+
+struct GrpcServer {
+    rt: Runtime,
+    cancel_token: CancellationToken,
+}
+
+impl AlumetPlugin for GrpcControlPlugin {
+    fn name() -> &'static str {
+        "grpc-control"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        // a broadcast channel cannot be empty
+        anyhow::ensure!(
+            config.stream_buffer_size > 0,
+            "stream_buffer_size must be greater than zero"
+        );
+        Ok(Box::new(GrpcControlPlugin {
+            config,
+            measurements_tx: None,
+            server: None,
+        }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        // The output forwards the measurements to the gRPC clients that have subscribed to them.
+        let (tx, _) = broadcast::channel(self.config.stream_buffer_size);
+        alumet.add_blocking_output("stream", Box::new(BroadcastOutput::new(tx.clone())))?;
+        self.measurements_tx = Some(tx);
+        Ok(())
+    }
+
+    fn post_pipeline_start(&mut self, alumet: &mut AlumetPostStart) -> anyhow::Result<()> {
+        let addr: SocketAddr = self
+            .config
+            .address
+            .parse()
+            .with_context(|| format!("invalid address {}", self.config.address))?;
+        let measurements_tx = self.measurements_tx.take().unwrap();
+        let service = ControlService::new(alumet.pipeline_control().anonymous(), measurements_tx);
+
+        // Run the server on its own runtime, so that it can be stopped independently of the pipeline.
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("grpc-control")
+            .enable_all()
+            .build()?;
+        let cancel_token = CancellationToken::new();
+        let shutdown = cancel_token.clone().cancelled_owned();
+        let listener = rt
+            .block_on(tokio::net::TcpListener::bind(addr))
+            .with_context(|| format!("could not bind to {addr}"))?;
+        rt.spawn(async move {
+            let incoming = tokio_stream::wrappers::TcpListenerStream::new(listener);
+            let res = tonic::transport::Server::builder()
+                .add_service(AlumetControlServer::new(service))
+                .serve_with_incoming_shutdown(incoming, shutdown)
+                .await;
+            if let Err(e) = res {
+                log::error!("gRPC server failed: {e:#}");
+            }
+        });
+        log::info!("gRPC control server listening on {addr}");
+        self.server = Some(GrpcServer { rt, cancel_token });
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        if let Some(server) = self.server.take() {
+            server.cancel_token.cancel();
+            server.rt.shutdown_timeout(Duration::from_secs(1));
+            log::info!("gRPC control server stopped.");
+        }
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: String::from("127.0.0.1:50051"),
+            stream_buffer_size: 64,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+    metrics::registry::MetricRegistry,
+    pipeline::elements::{
+        error::WriteError,
+        output::{Output, OutputContext},
+    },
+};
+use tokio::sync::broadcast;
+
+use crate::proto;
+
+/// A batch of measurements, shared by all the gRPC clients.
+pub type SharedBatch = Arc<proto::MeasurementBatch>;
+
+/// Output that broadcasts the measurements to the gRPC clients.
+pub struct BroadcastOutput {
+    tx: broadcast::Sender<SharedBatch>,
+}
+
+impl BroadcastOutput {
+    pub fn new(tx: broadcast::Sender<SharedBatch>) -> Self {
+        Self { tx }
+    }
+}
+
+impl Output for BroadcastOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        if self.tx.receiver_count() == 0 {
+            // nobody is listening, don't bother converting the measurements
+            return Ok(());
+        }
+        let batch = convert_buffer(measurements, ctx.metrics);
+        // An error here means that all the clients have disconnected in the meantime, which is fine.
+        let _ = self.tx.send(Arc::new(batch));
+        Ok(())
+    }
+}
+
+fn convert_buffer(measurements: &MeasurementBuffer, metrics: &MetricRegistry) -> proto::MeasurementBatch {
+    let measurements = measurements
+        .iter()
+        .filter_map(|p| {
+            let metric = metrics.by_id(&p.metric)?;
+            Some(convert_point(p, metric.name.clone()))
+        })
+        .collect();
+    proto::MeasurementBatch { measurements }
+}
+
+fn convert_point(p: &MeasurementPoint, metric: String) -> proto::Measurement {
+    let (timestamp_secs, timestamp_nanos) = p.timestamp.to_unix_timestamp();
+    let value = match p.value {
+        WrappedMeasurementValue::F64(v) => proto::measurement::Value::F64(v),
+        WrappedMeasurementValue::U64(v) => proto::measurement::Value::U64(v),
+    };
+    let attributes: HashMap<String, String> = p.attributes().map(|(k, v)| (k.to_owned(), v.to_string())).collect();
+    proto::Measurement {
+        metric,
+        timestamp_secs,
+        timestamp_nanos,
+        value: Some(value),
+        resource_kind: p.resource.kind().to_owned(),
+        resource_id: p.resource.id_string().unwrap_or_default(),
+        consumer_kind: p.consumer.kind().to_owned(),
+        consumer_id: p.consumer.id_string().unwrap_or_default(),
+        attributes,
+    }
+}
+
+/// Only keeps the measurements of the given metrics.
+///
+/// If `metrics` is empty, the batch is returned unchanged.
+pub fn filter_batch(batch: &proto::MeasurementBatch, metrics: &[String]) -> proto::MeasurementBatch {
+    if metrics.is_empty() {
+        return batch.clone();
+    }
+    let measurements = batch
+        .measurements
+        .iter()
+        .filter(|m| metrics.contains(&m.metric))
+        .cloned()
+        .collect();
+    proto::MeasurementBatch { measurements }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::{convert_point, filter_batch};
+    use crate::proto;
+
+    #[test]
+    fn convert() {
+        let point = MeasurementPoint::new_untyped(
+            Timestamp::from_unix_timestamp(1700000000, 42),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 1 },
+            ResourceConsumer::Process { pid: 123 },
+            WrappedMeasurementValue::F64(12.5),
+        )
+        .with_attr("domain", "package");
+        let m = convert_point(&point, String::from("energy"));
+        assert_eq!(m.metric, "energy");
+        assert_eq!((m.timestamp_secs, m.timestamp_nanos), (1700000000, 42));
+        assert_eq!(m.value, Some(proto::measurement::Value::F64(12.5)));
+        assert_eq!((m.resource_kind.as_str(), m.resource_id.as_str()), ("cpu_package", "1"));
+        assert_eq!((m.consumer_kind.as_str(), m.consumer_id.as_str()), ("process", "123"));
+        assert_eq!(m.attributes.get("domain").map(String::as_str), Some("package"));
+    }
+
+    #[test]
+    fn filter() {
+        let measurement = |metric: &str| proto::Measurement {
+            metric: metric.to_owned(),
+            ..Default::default()
+        };
+        let batch = proto::MeasurementBatch {
+            measurements: vec![measurement("a"), measurement("b"), measurement("a")],
+        };
+        assert_eq!(filter_batch(&batch, &[]), batch);
+        let filtered = filter_batch(&batch, &[String::from("a")]);
+        assert_eq!(filtered.measurements, vec![measurement("a"), measurement("a")]);
+    }
+}
@@ -0,0 +1,210 @@
+use std::{pin::Pin, str::FromStr, time::Duration};
+
+use alumet::pipeline::{
+    control::{
+        AnonymousControlHandle,
+        handle::SendWaitError,
+        request::{self, ElementListFilter},
+    },
+    elements::source::trigger::TriggerSpec,
+    matching::{OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern},
+    naming::{ElementKind, ElementName, matching::ElementNamePattern},
+};
+use tokio::sync::broadcast;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream, wrappers::errors::BroadcastStreamRecvError};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    output::{SharedBatch, filter_batch},
+    proto::{self, alumet_control_server::AlumetControl},
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Implementation of the gRPC service.
+pub struct ControlService {
+    handle: AnonymousControlHandle,
+    measurements_tx: broadcast::Sender<SharedBatch>,
+}
+
+impl ControlService {
+    pub fn new(handle: AnonymousControlHandle, measurements_tx: broadcast::Sender<SharedBatch>) -> Self {
+        Self {
+            handle,
+            measurements_tx,
+        }
+    }
+}
+
+type MeasurementStream = Pin<Box<dyn Stream<Item = Result<proto::MeasurementBatch, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl AlumetControl for ControlService {
+    async fn list_elements(
+        &self,
+        request: Request<proto::ListElementsRequest>,
+    ) -> Result<Response<proto::ListElementsResponse>, Status> {
+        let pattern = convert_pattern(request.into_inner().pattern)?;
+        let filter = match pattern.kind {
+            Some(kind) => ElementListFilter::kind(kind),
+            None => ElementListFilter::kind_any(),
+        };
+        let filter = filter.plugin_pat(pattern.plugin).name_pat(pattern.element);
+        let mut elements = self
+            .handle
+            .send_wait(request::list_elements(filter), REQUEST_TIMEOUT)
+            .await
+            .map_err(convert_error)?;
+        elements.sort_by_cached_key(|e| e.to_string());
+        let elements = elements.into_iter().map(convert_element).collect();
+        Ok(Response::new(proto::ListElementsResponse { elements }))
+    }
+
+    async fn control(
+        &self,
+        request: Request<proto::ControlRequest>,
+    ) -> Result<Response<proto::ControlResponse>, Status> {
+        let request = request.into_inner();
+        let action = proto::ControlAction::try_from(request.action)
+            .map_err(|_| Status::invalid_argument(format!("unknown action {}", request.action)))?;
+        let pattern = convert_pattern(request.pattern)?;
+        let kinds = match pattern.kind {
+            Some(kind) => vec![kind],
+            None => vec![ElementKind::Source, ElementKind::Transform, ElementKind::Output],
+        };
+        for kind in kinds {
+            let pat = ElementNamePattern {
+                kind: Some(kind),
+                ..pattern.clone()
+            };
+            let req: request::any::AnyAnonymousControlRequest = match (action, kind) {
+                (proto::ControlAction::Pause, ElementKind::Source) => source(pat).disable().into(),
+                (proto::ControlAction::Pause, ElementKind::Transform) => transform(pat).disable().into(),
+                (proto::ControlAction::Pause, ElementKind::Output) => output(pat).disable().into(),
+                (proto::ControlAction::Resume, ElementKind::Source) => source(pat).enable().into(),
+                (proto::ControlAction::Resume, ElementKind::Transform) => transform(pat).enable().into(),
+                (proto::ControlAction::Resume, ElementKind::Output) => output(pat).enable().into(),
+                (proto::ControlAction::Stop, ElementKind::Source) => source(pat).stop().into(),
+                (proto::ControlAction::Stop, ElementKind::Output) => {
+                    output(pat).stop(request::RemainingDataStrategy::Write).into()
+                }
+                (proto::ControlAction::TriggerNow, ElementKind::Source) => source(pat).trigger_now().into(),
+                (proto::ControlAction::SetPeriod, ElementKind::Source) => {
+                    if request.period_ms == 0 {
+                        return Err(Status::invalid_argument("period_ms must be greater than zero"));
+                    }
+                    let spec = TriggerSpec::at_interval(Duration::from_millis(request.period_ms));
+                    source(pat).set_trigger(spec).into()
+                }
+                (proto::ControlAction::Unspecified, _) => {
+                    return Err(Status::invalid_argument("missing action"));
+                }
+                _ if pattern.kind.is_none() => continue, // the action does not apply to every kind of element
+                (action, kind) => {
+                    return Err(Status::invalid_argument(format!(
+                        "action {} cannot be applied to {kind}s",
+                        action.as_str_name()
+                    )));
+                }
+            };
+            self.handle
+                .send_wait(req, REQUEST_TIMEOUT)
+                .await
+                .map_err(convert_error)?;
+        }
+        Ok(Response::new(proto::ControlResponse {}))
+    }
+
+    async fn shutdown(
+        &self,
+        _request: Request<proto::ShutdownRequest>,
+    ) -> Result<Response<proto::ShutdownResponse>, Status> {
+        log::info!("Shutdown requested via gRPC.");
+        self.handle.shutdown();
+        Ok(Response::new(proto::ShutdownResponse {}))
+    }
+
+    type StreamMeasurementsStream = MeasurementStream;
+
+    async fn stream_measurements(
+        &self,
+        request: Request<proto::StreamMeasurementsRequest>,
+    ) -> Result<Response<Self::StreamMeasurementsStream>, Status> {
+        let metrics = request.into_inner().metrics;
+        let stream = BroadcastStream::new(self.measurements_tx.subscribe()).filter_map(move |res| match res {
+            Ok(batch) => {
+                let batch = filter_batch(&batch, &metrics);
+                (!batch.measurements.is_empty()).then_some(Ok(batch))
+            }
+            Err(BroadcastStreamRecvError::Lagged(n)) => {
+                log::warn!("gRPC client is too slow, {n} batches of measurements have been skipped");
+                None
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+fn source(pat: ElementNamePattern) -> request::SourceRequestBuilder {
+    request::source(SourceNamePattern::try_from(pat).unwrap())
+}
+
+fn transform(pat: ElementNamePattern) -> request::TransformRequestBuilder {
+    request::transform(TransformNamePattern::try_from(pat).unwrap())
+}
+
+fn output(pat: ElementNamePattern) -> request::OutputRequestBuilder {
+    request::output(OutputNamePattern::try_from(pat).unwrap())
+}
+
+#[allow(clippy::result_large_err)] // Status is the error type of tonic
+fn convert_pattern(pattern: Option<proto::ElementPattern>) -> Result<ElementNamePattern, Status> {
+    #[allow(clippy::result_large_err)]
+    fn string_pattern(s: &str) -> Result<StringPattern, Status> {
+        if s.is_empty() {
+            Ok(StringPattern::Any)
+        } else {
+            StringPattern::from_str(s).map_err(|e| Status::invalid_argument(format!("bad pattern '{s}': {e}")))
+        }
+    }
+
+    let pattern = pattern.unwrap_or_default();
+    let kind = match proto::ElementKind::try_from(pattern.kind) {
+        Ok(proto::ElementKind::Any) => None,
+        Ok(proto::ElementKind::Source) => Some(ElementKind::Source),
+        Ok(proto::ElementKind::Transform) => Some(ElementKind::Transform),
+        Ok(proto::ElementKind::Output) => Some(ElementKind::Output),
+        Err(_) => {
+            return Err(Status::invalid_argument(format!(
+                "unknown element kind {}",
+                pattern.kind
+            )));
+        }
+    };
+    Ok(ElementNamePattern {
+        kind,
+        plugin: string_pattern(&pattern.plugin)?,
+        element: string_pattern(&pattern.element)?,
+    })
+}
+
+fn convert_element(name: ElementName) -> proto::Element {
+    let kind = match name.kind {
+        ElementKind::Source => proto::ElementKind::Source,
+        ElementKind::Transform => proto::ElementKind::Transform,
+        ElementKind::Output => proto::ElementKind::Output,
+    };
+    proto::Element {
+        kind: kind.into(),
+        plugin: name.plugin,
+        name: name.element,
+    }
+}
+
+fn convert_error(e: SendWaitError) -> Status {
+    match e {
+        SendWaitError::NotAvailable => Status::unavailable(e.to_string()),
+        SendWaitError::Timeout => Status::deadline_exceeded(e.to_string()),
+        e => Status::internal(format!("{e:#}")),
+    }
+}
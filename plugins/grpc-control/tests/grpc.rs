@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{
+        Source,
+        elements::{error::PollError, source::trigger::TriggerSpec},
+    },
+    plugin::{AlumetPluginStart, ConfigTable, PluginMetadata, rust::AlumetPlugin, rust::serialize_config},
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+use plugin_grpc_control::{
+    Config, GrpcControlPlugin,
+    proto::{self, alumet_control_client::AlumetControlClient},
+};
+
+const ADDRESS: &str = "127.0.0.1:50151";
+
+#[test]
+fn invalid_config() {
+    let config = serialize_config(Config {
+        stream_buffer_size: 0,
+        ..Config::default()
+    })
+    .unwrap();
+    assert!(GrpcControlPlugin::init(config).is_err());
+}
+
+#[test]
+fn control_and_stream() {
+    let _ = env_logger::try_init();
+
+    let plugin_config = serialize_config(Config {
+        address: ADDRESS.to_owned(),
+        stream_buffer_size: 16,
+    })
+    .unwrap()
+    .0;
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<GrpcControlPlugin>(),
+        enabled: true,
+        config: Some(plugin_config),
+    });
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<CounterPlugin>(),
+        enabled: true,
+        config: None,
+    });
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("alumet should start");
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let mut client = AlumetControlClient::connect(format!("http://{ADDRESS}"))
+            .await
+            .expect("I should be able to connect to the gRPC server");
+
+        // list the sources
+        let sources = client
+            .list_elements(proto::ListElementsRequest {
+                pattern: Some(proto::ElementPattern {
+                    kind: proto::ElementKind::Source.into(),
+                    ..Default::default()
+                }),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .elements;
+        assert_eq!(
+            sources,
+            vec![proto::Element {
+                kind: proto::ElementKind::Source.into(),
+                plugin: String::from("counter"),
+                name: String::from("count"),
+            }]
+        );
+
+        // receive some measurements
+        let mut stream = client
+            .stream_measurements(proto::StreamMeasurementsRequest {
+                metrics: vec![String::from("count")],
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let batch = tokio::time::timeout(Duration::from_secs(2), stream.message())
+            .await
+            .expect("measurements should arrive in time")
+            .unwrap()
+            .expect("the stream should not end");
+        assert!(!batch.measurements.is_empty());
+        assert!(batch.measurements.iter().all(|m| m.metric == "count"));
+
+        // invalid control: transforms cannot be triggered
+        let err = client
+            .control(proto::ControlRequest {
+                pattern: Some(proto::ElementPattern {
+                    kind: proto::ElementKind::Transform.into(),
+                    ..Default::default()
+                }),
+                action: proto::ControlAction::TriggerNow.into(),
+                period_ms: 0,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        // valid control
+        client
+            .control(proto::ControlRequest {
+                pattern: Some(proto::ElementPattern {
+                    kind: proto::ElementKind::Any.into(),
+                    plugin: String::from("counter"),
+                    element: String::from("*"),
+                }),
+                action: proto::ControlAction::Pause.into(),
+                period_ms: 0,
+            })
+            .await
+            .expect("control should work");
+
+        client.shutdown(proto::ShutdownRequest {}).await.unwrap();
+    });
+
+    agent
+        .wait_for_shutdown(Duration::from_secs(2))
+        .expect("alumet should stop");
+}
+
+struct CounterPlugin;
+
+struct CounterSource {
+    metric: TypedMetricId<u64>,
+    count: u64,
+}
+
+impl AlumetPlugin for CounterPlugin {
+    fn name() -> &'static str {
+        "counter"
+    }
+
+    fn version() -> &'static str {
+        "0.1.0"
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(Self))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let metric = alumet.create_metric("count", Unit::Unity, "a counter")?;
+        let source = CounterSource { metric, count: 0 };
+        alumet.add_source(
+            "count",
+            Box::new(source),
+            TriggerSpec::at_interval(Duration::from_millis(10)),
+        )?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl Source for CounterSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        self.count += 1;
+        measurements.push(MeasurementPoint::new(
+            timestamp,
+            self.metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            self.count,
+        ));
+        Ok(())
+    }
+}
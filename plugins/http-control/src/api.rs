@@ -0,0 +1,292 @@
+//! Routes of the REST API.
+//!
+//! - `GET /elements`: lists the elements of the pipeline, optionally filtered by kind (`?kind=sources`)
+//! - `POST /sources/{plugin}/{source}/trigger`: triggers the matching sources now
+//! - `POST /pipeline/pause`: pauses all the sources, so that no new measurement is produced
+//! - `POST /pipeline/resume`: resumes all the sources
+
+use std::{str::FromStr, time::Duration};
+
+use alumet::pipeline::{
+    control::{
+        AnonymousControlHandle,
+        handle::SendWaitError,
+        request::{self, ElementListFilter},
+    },
+    matching::{SourceNamePattern, StringPattern},
+    naming::{ElementKind, ElementName, parsing::parse_kind},
+};
+use hyper::{Body, Method, Request, Response, StatusCode, header};
+use serde::Serialize;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Shared state of the API.
+pub struct ApiState {
+    handle: AnonymousControlHandle,
+    token: String,
+}
+
+impl ApiState {
+    pub fn new(handle: AnonymousControlHandle, token: String) -> Self {
+        Self { handle, token }
+    }
+}
+
+/// A request to the API, extracted from the HTTP request.
+#[derive(Debug, PartialEq)]
+enum Route {
+    ListElements(Option<ElementKind>),
+    TriggerSources(SourceNamePattern),
+    PausePipeline,
+    ResumePipeline,
+}
+
+/// An error that is returned to the client.
+#[derive(Debug, PartialEq)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ElementInfo {
+    kind: String,
+    plugin: String,
+    name: String,
+}
+
+/// Handles an HTTP request.
+pub async fn handle(req: Request<Body>, state: &ApiState) -> Response<Body> {
+    let res = match check_token(&req, &state.token) {
+        Ok(()) => match parse_route(req.method(), req.uri().path(), req.uri().query()) {
+            Ok(route) => run(route, &state.handle).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(res) => res,
+        Err(e) => {
+            log::debug!("{} {} failed: {}", req.method(), req.uri(), e.message);
+            json_response(e.status, &serde_json::json!({ "error": e.message }))
+        }
+    }
+}
+
+async fn run(route: Route, handle: &AnonymousControlHandle) -> Result<Response<Body>, ApiError> {
+    match route {
+        Route::ListElements(kind) => {
+            let filter = match kind {
+                Some(kind) => ElementListFilter::kind(kind),
+                None => ElementListFilter::kind_any(),
+            };
+            let mut elements = handle
+                .send_wait(request::list_elements(filter), REQUEST_TIMEOUT)
+                .await
+                .map_err(ApiError::from)?;
+            elements.sort_by_cached_key(|e| e.to_string());
+            let elements: Vec<ElementInfo> = elements.into_iter().map(ElementInfo::from).collect();
+            Ok(json_response(StatusCode::OK, &elements))
+        }
+        Route::TriggerSources(pat) => {
+            let req = request::source(pat).trigger_now();
+            handle.send_wait(req, REQUEST_TIMEOUT).await.map_err(ApiError::from)?;
+            Ok(empty_response())
+        }
+        Route::PausePipeline => {
+            let req = request::source(SourceNamePattern::wildcard()).disable();
+            handle.send_wait(req, REQUEST_TIMEOUT).await.map_err(ApiError::from)?;
+            Ok(empty_response())
+        }
+        Route::ResumePipeline => {
+            let req = request::source(SourceNamePattern::wildcard()).enable();
+            handle.send_wait(req, REQUEST_TIMEOUT).await.map_err(ApiError::from)?;
+            Ok(empty_response())
+        }
+    }
+}
+
+fn check_token(req: &Request<Body>, token: &str) -> Result<(), ApiError> {
+    if token.is_empty() {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "the REST API is disabled because no token has been configured",
+        ));
+    }
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match provided {
+        Some(provided) if constant_time_eq(provided.as_bytes(), token.as_bytes()) => Ok(()),
+        Some(_) => Err(ApiError::new(StatusCode::UNAUTHORIZED, "invalid token")),
+        None => Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "missing token, use the header 'Authorization: Bearer <token>'",
+        )),
+    }
+}
+
+/// Compares two byte strings in a time that does not depend on their content.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn parse_route(method: &Method, path: &str, query: Option<&str>) -> Result<Route, ApiError> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        (&Method::GET, ["elements"]) => {
+            let kind = match query.and_then(|q| q.split('&').find_map(|kv| kv.strip_prefix("kind="))) {
+                Some(kind) => parse_kind(kind)
+                    .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, format!("invalid element kind '{kind}'")))?,
+                None => None,
+            };
+            Ok(Route::ListElements(kind))
+        }
+        (&Method::POST, ["sources", plugin, source, "trigger"]) => {
+            let plugin = parse_pattern(plugin)?;
+            let source = parse_pattern(source)?;
+            Ok(Route::TriggerSources(SourceNamePattern::new(plugin, source)))
+        }
+        (&Method::POST, ["pipeline", "pause"]) => Ok(Route::PausePipeline),
+        (&Method::POST, ["pipeline", "resume"]) => Ok(Route::ResumePipeline),
+        (_, ["elements"] | ["sources", _, _, "trigger"] | ["pipeline", "pause" | "resume"]) => {
+            Err(ApiError::new(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"))
+        }
+        _ => Err(ApiError::new(StatusCode::NOT_FOUND, format!("not found: {path}"))),
+    }
+}
+
+fn parse_pattern(s: &str) -> Result<StringPattern, ApiError> {
+    StringPattern::from_str(s)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("invalid pattern '{s}': {e}")))
+}
+
+fn json_response<T: Serialize + ?Sized>(status: StatusCode, body: &T) -> Response<Body> {
+    let body = serde_json::to_string(body).expect("serialization to json should not fail");
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn empty_response() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .unwrap()
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<SendWaitError> for ApiError {
+    fn from(e: SendWaitError) -> Self {
+        let status = match e {
+            SendWaitError::NotAvailable => StatusCode::SERVICE_UNAVAILABLE,
+            SendWaitError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError::new(status, format!("{e:#}"))
+    }
+}
+
+impl From<ElementName> for ElementInfo {
+    fn from(name: ElementName) -> Self {
+        Self {
+            kind: name.kind.to_string(),
+            plugin: name.plugin,
+            name: name.element,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::pipeline::{
+        matching::{SourceNamePattern, StringPattern},
+        naming::ElementKind,
+    };
+    use hyper::{Body, Method, Request, StatusCode};
+
+    use super::{Route, check_token, parse_route};
+
+    #[test]
+    fn routes() {
+        assert_eq!(
+            parse_route(&Method::GET, "/elements", None),
+            Ok(Route::ListElements(None))
+        );
+        assert_eq!(
+            parse_route(&Method::GET, "/elements", Some("kind=sources")),
+            Ok(Route::ListElements(Some(ElementKind::Source)))
+        );
+        assert_eq!(
+            parse_route(&Method::POST, "/sources/rapl/in/trigger", None),
+            Ok(Route::TriggerSources(SourceNamePattern::exact("rapl", "in")))
+        );
+        assert_eq!(
+            parse_route(&Method::POST, "/sources/procfs/*/trigger", None),
+            Ok(Route::TriggerSources(SourceNamePattern::new(
+                StringPattern::Exact(String::from("procfs")),
+                StringPattern::Any
+            )))
+        );
+        assert_eq!(
+            parse_route(&Method::POST, "/pipeline/pause", None),
+            Ok(Route::PausePipeline)
+        );
+        assert_eq!(
+            parse_route(&Method::POST, "/pipeline/resume/", None),
+            Ok(Route::ResumePipeline)
+        );
+    }
+
+    #[test]
+    fn bad_routes() {
+        let status = |method, path, query| parse_route(method, path, query).unwrap_err().status;
+        assert_eq!(status(&Method::GET, "/nothing", None), StatusCode::NOT_FOUND);
+        assert_eq!(status(&Method::POST, "/elements", None), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            status(&Method::GET, "/pipeline/pause", None),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            status(&Method::GET, "/elements", Some("kind=nope")),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(&Method::POST, "/sources/a*b/x/trigger", None),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn token() {
+        let req = |auth: Option<&str>| {
+            let mut builder = Request::builder().uri("/elements");
+            if let Some(auth) = auth {
+                builder = builder.header("Authorization", auth);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+        assert!(check_token(&req(Some("Bearer secret")), "secret").is_ok());
+        assert!(check_token(&req(Some("Bearer wrong")), "secret").is_err());
+        assert!(check_token(&req(Some("secret")), "secret").is_err());
+        assert!(check_token(&req(None), "secret").is_err());
+        // no token configured: everything is rejected
+        assert!(check_token(&req(Some("Bearer ")), "").is_err());
+    }
+}
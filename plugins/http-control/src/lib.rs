@@ -0,0 +1,131 @@
+//! REST API to control the Alumet pipeline.
+
+mod api;
+
+use std::{net::SocketAddr, sync::Arc, thread::JoinHandle};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable};
+use anyhow::Context;
+use hyper::{
+    Server,
+    service::{make_service_fn, service_fn},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use api::ApiState;
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address and port on which the HTTP server listens.
+    pub address: String,
+    /// Token that the clients must provide in the `Authorization` header: `Authorization: Bearer <token>`.
+    ///
+    /// If the token is empty, every request is rejected.
+    pub token: String,
+}
+
+pub struct HttpControlPlugin {
+    config: Config,
+    server: Option<(oneshot::Sender<()>, JoinHandle<()>)>,
+}
+
+impl AlumetPlugin for HttpControlPlugin {
+    fn name() -> &'static str {
+        "http-control"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(HttpControlPlugin { config, server: None }))
+    }
+
+    fn start(&mut self, _alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn post_pipeline_start(&mut self, alumet: &mut AlumetPostStart) -> anyhow::Result<()> {
+        let addr: SocketAddr = self
+            .config
+            .address
+            .parse()
+            .with_context(|| format!("invalid address {}", self.config.address))?;
+        if self.config.token.is_empty() {
+            log::warn!("No token has been configured for the REST API: all the requests will be rejected.");
+        }
+        let state = Arc::new(ApiState::new(
+            alumet.pipeline_control().anonymous(),
+            self.config.token.clone(),
+        ));
+
+        // Bind now to report errors early.
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let builder = {
+            let _guard = rt.enter();
+            Server::try_bind(&addr).with_context(|| format!("could not bind to {addr}"))?
+        };
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name(String::from("http-control"))
+            .spawn(move || {
+                rt.block_on(async move {
+                    let make_svc = make_service_fn(move |_conn| {
+                        let state = state.clone();
+                        async move {
+                            Ok::<_, hyper::Error>(service_fn(move |req| {
+                                let state = state.clone();
+                                async move { Ok::<_, hyper::Error>(api::handle(req, &state).await) }
+                            }))
+                        }
+                    });
+                    let server = builder.serve(make_svc).with_graceful_shutdown(async {
+                        shutdown_rx.await.ok();
+                    });
+                    if let Err(e) = server.await {
+                        log::error!("REST API server error: {e}");
+                    }
+                });
+            })?;
+        log::info!("REST API available on http://{addr}");
+        self.server = Some((shutdown_tx, thread));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        if let Some((shutdown_tx, thread)) = self.server.take() {
+            // The server may have already stopped on its own, ignore the error.
+            let _ = shutdown_tx.send(());
+            thread
+                .join()
+                .map_err(|_| anyhow::anyhow!("the REST API thread panicked"))?;
+            log::info!("REST API server stopped.");
+        }
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: String::from("127.0.0.1:8585"),
+            token: String::new(),
+        }
+    }
+}
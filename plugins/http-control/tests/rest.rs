@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    plugin::{PluginMetadata, rust::serialize_config},
+};
+use hyper::{Body, Client, Method, Request, StatusCode};
+use plugin_http_control::{Config, HttpControlPlugin};
+
+const ADDRESS: &str = "127.0.0.1:50152";
+const TOKEN: &str = "test-token";
+
+#[test]
+fn rest_api() {
+    let _ = env_logger::try_init();
+
+    let plugin_config = serialize_config(Config {
+        address: ADDRESS.to_owned(),
+        token: TOKEN.to_owned(),
+    })
+    .unwrap()
+    .0;
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<HttpControlPlugin>(),
+        enabled: true,
+        config: Some(plugin_config),
+    });
+    let agent = agent::Builder::new(plugins)
+        .build_and_start()
+        .expect("alumet should start");
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let client = Client::new();
+        let request = |method: Method, path: &str, token: Option<&str>| {
+            let mut builder = Request::builder().method(method).uri(format!("http://{ADDRESS}{path}"));
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {token}"));
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        // no token
+        let res = client.request(request(Method::GET, "/elements", None)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        // list the elements
+        let res = client
+            .request(request(Method::GET, "/elements", Some(TOKEN)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([{"kind": "output", "plugin": "alumet", "name": "dummy"}])
+        );
+
+        // pause and resume
+        for path in ["/pipeline/pause", "/pipeline/resume", "/sources/*/*/trigger"] {
+            let res = client.request(request(Method::POST, path, Some(TOKEN))).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NO_CONTENT, "POST {path} failed");
+        }
+    });
+
+    agent.pipeline.control_handle().shutdown();
+    agent
+        .wait_for_shutdown(Duration::from_secs(2))
+        .expect("alumet should stop");
+}
@@ -0,0 +1,62 @@
+//! Direct access to the registers of a chip, through the i2c device files of Linux (`/dev/i2c-<bus>`).
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use anyhow::Context;
+
+use crate::ina::{Backend, Chip};
+
+/// Selects the address of the device that the next reads and writes talk to (`I2C_SLAVE` in `linux/i2c-dev.h`).
+const I2C_SLAVE: u16 = 0x0703;
+
+nix::ioctl_write_int_bad!(i2c_set_slave, I2C_SLAVE);
+
+/// Reads the registers of a chip on an i2c bus.
+///
+/// This requires the `i2c-dev` kernel module, and the permission to open the device file
+/// (usually granted to the group `i2c`).
+pub struct I2cBackend {
+    chip: Chip,
+    file: File,
+}
+
+impl I2cBackend {
+    pub fn open(dev: &Path, chip: Chip, bus: u32, address: u16) -> anyhow::Result<Self> {
+        let path = dev.join(format!("i2c-{bus}"));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("failed to open {path:?}, is the i2c-dev module loaded?"))?;
+        // SAFETY: I2C_SLAVE takes the address as an integer argument, and the file descriptor is valid.
+        unsafe { i2c_set_slave(file.as_raw_fd(), address as i32) }
+            .with_context(|| format!("failed to select the device {address:#04x} on {path:?}"))?;
+        Ok(Self { chip, file })
+    }
+
+    /// Reads a 16-bit register, which the INA chips send in big-endian order.
+    fn read_register(&mut self, register: u8) -> anyhow::Result<u16> {
+        self.file
+            .write_all(&[register])
+            .with_context(|| format!("failed to select register {register:#04x}"))?;
+        let mut buf = [0u8; 2];
+        self.file
+            .read_exact(&mut buf)
+            .with_context(|| format!("failed to read register {register:#04x}"))?;
+        Ok(u16::from_be_bytes(buf))
+    }
+}
+
+impl Backend for I2cBackend {
+    fn read_channel(&mut self, channel: u8) -> anyhow::Result<(f64, f64)> {
+        let (shunt, bus) = self.chip.registers(channel);
+        let shunt = self.read_register(shunt)?;
+        let bus = self.read_register(bus)?;
+        Ok((self.chip.bus_voltage(bus), self.chip.shunt_voltage(shunt)))
+    }
+}
@@ -0,0 +1,268 @@
+//! Texas Instruments INA219, INA226 and INA3221 shunt monitors.
+//!
+//! The chips measure the voltage of the bus and the voltage across a shunt resistor, for each of their channels.
+//! The current is computed from the shunt voltage with the calibration of the channel, so that the
+//! measurements are consistent whether the chip is read through its Linux driver or directly on the i2c bus.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::rail::{RailReading, Sensor};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Chip {
+    /// Single channel, 26V, 12-bit.
+    Ina219,
+    /// Single channel, 36V, 16-bit.
+    Ina226,
+    /// Three channels, 26V, 13-bit.
+    Ina3221,
+}
+
+impl Chip {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chip::Ina219 => "ina219",
+            Chip::Ina226 => "ina226",
+            Chip::Ina3221 => "ina3221",
+        }
+    }
+
+    /// Number of channels of the chip, numbered from 1.
+    pub fn channels(&self) -> u8 {
+        match self {
+            Chip::Ina3221 => 3,
+            _ => 1,
+        }
+    }
+
+    /// Returns the registers that hold the shunt voltage and the bus voltage of the channel.
+    pub fn registers(&self, channel: u8) -> (u8, u8) {
+        match self {
+            Chip::Ina219 | Chip::Ina226 => (0x01, 0x02),
+            Chip::Ina3221 => {
+                let shunt = 0x01 + 2 * (channel - 1);
+                (shunt, shunt + 1)
+            }
+        }
+    }
+
+    /// Converts the content of the shunt voltage register to Volts.
+    pub fn shunt_voltage(&self, raw: u16) -> f64 {
+        let raw = raw as i16;
+        match self {
+            Chip::Ina219 => raw as f64 * 10e-6,
+            Chip::Ina226 => raw as f64 * 2.5e-6,
+            // bits 15-3, in two's complement
+            Chip::Ina3221 => (raw >> 3) as f64 * 40e-6,
+        }
+    }
+
+    /// Converts the content of the bus voltage register to Volts.
+    pub fn bus_voltage(&self, raw: u16) -> f64 {
+        match self {
+            // bits 15-3, the lower bits are flags
+            Chip::Ina219 => (raw >> 3) as f64 * 4e-3,
+            Chip::Ina226 => raw as f64 * 1.25e-3,
+            Chip::Ina3221 => ((raw as i16) >> 3) as f64 * 8e-3,
+        }
+    }
+
+    /// Returns the hwmon files of the channel: bus voltage (mV), current (mA) and shunt resistor (µΩ).
+    fn hwmon_files(&self, channel: u8) -> [String; 3] {
+        match self {
+            Chip::Ina219 | Chip::Ina226 => [
+                String::from("in1_input"),
+                String::from("curr1_input"),
+                String::from("shunt_resistor"),
+            ],
+            Chip::Ina3221 => [
+                format!("in{channel}_input"),
+                format!("curr{channel}_input"),
+                format!("shunt{channel}_resistor"),
+            ],
+        }
+    }
+}
+
+/// A way to read the bus voltage and the shunt voltage of the channels of a chip.
+pub trait Backend: Send {
+    /// Returns the bus voltage and the shunt voltage of the channel, in Volts.
+    fn read_channel(&mut self, channel: u8) -> anyhow::Result<(f64, f64)>;
+}
+
+/// A channel of a chip, with its calibration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub number: u8,
+    pub label: String,
+    /// Resistance of the shunt in Ohms.
+    pub shunt_resistor: f64,
+    /// Correction factor applied to the current.
+    pub gain: f64,
+}
+
+/// An INA chip and the channels to measure.
+pub struct InaSensor {
+    backend: Box<dyn Backend>,
+    channels: Vec<Channel>,
+}
+
+impl InaSensor {
+    pub fn new(backend: Box<dyn Backend>, channels: Vec<Channel>) -> Self {
+        Self { backend, channels }
+    }
+}
+
+impl Sensor for InaSensor {
+    fn read(&mut self) -> anyhow::Result<Vec<RailReading>> {
+        let mut readings = Vec::with_capacity(self.channels.len());
+        for channel in &self.channels {
+            let (bus, shunt) = self
+                .backend
+                .read_channel(channel.number)
+                .with_context(|| format!("failed to read channel {}", channel.number))?;
+            readings.push(RailReading {
+                rail: channel.label.clone(),
+                voltage: Some(bus),
+                current: Some(shunt / channel.shunt_resistor * channel.gain),
+            });
+        }
+        Ok(readings)
+    }
+}
+
+/// Reads a chip through the hwmon device of its Linux driver (`ina2xx` or `ina3221`).
+pub struct HwmonBackend {
+    chip: Chip,
+    hwmon: PathBuf,
+}
+
+impl HwmonBackend {
+    /// Finds the hwmon device of the chip at `address` on i2c bus `bus`, if a driver is bound to it.
+    ///
+    /// ## Expected file layout
+    ///
+    /// ```txt
+    /// /sys/bus/i2c/devices/1-0040/
+    /// |− hwmon/hwmon2
+    ///     |− in1_input            (milliVolts, bus voltage)
+    ///     |− curr1_input          (milliAmperes)
+    ///     |− shunt_resistor       (microOhms, shunt{n}_resistor for the INA3221)
+    /// ```
+    pub fn find(sysfs: &Path, chip: Chip, bus: u32, address: u16) -> anyhow::Result<Option<Self>> {
+        let dir = sysfs.join(format!("bus/i2c/devices/{bus}-{address:04x}/hwmon"));
+        if !dir.exists() {
+            return Ok(None);
+        }
+        let hwmon = fs::read_dir(&dir)
+            .with_context(|| format!("failed to read dir {dir:?}"))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("hwmon"))
+            });
+        Ok(hwmon.map(|hwmon| Self { chip, hwmon }))
+    }
+
+    fn read_value(&self, file: &str) -> anyhow::Result<f64> {
+        let path = self.hwmon.join(file);
+        let content = fs::read_to_string(&path).with_context(|| format!("failed to read {path:?}"))?;
+        let value: i64 = content
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid content {content:?} in {path:?}"))?;
+        Ok(value as f64)
+    }
+}
+
+impl Backend for HwmonBackend {
+    fn read_channel(&mut self, channel: u8) -> anyhow::Result<(f64, f64)> {
+        let [bus, current, shunt_resistor] = self.chip.hwmon_files(channel);
+        let bus = self.read_value(&bus)? * 1e-3;
+        let current = self.read_value(&current)? * 1e-3;
+        let shunt_resistor = self.read_value(&shunt_resistor)? * 1e-6;
+        if shunt_resistor <= 0.0 {
+            return Err(anyhow!("invalid shunt resistor in {:?}", self.hwmon));
+        }
+        // The driver computes the current with its own shunt resistor, which may differ from the configured one.
+        Ok((bus, current * shunt_resistor))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// Creates the hwmon device of an i2c chip in the fake sysfs, with the given files.
+    pub fn fake_hwmon(sysfs: &Path, device: &str, files: &[(&str, &str)]) {
+        let path = sysfs.join("bus/i2c/devices").join(device).join("hwmon/hwmon1");
+        fs::create_dir_all(&path).unwrap();
+        for (file, content) in files {
+            fs::write(path.join(file), content).unwrap();
+        }
+    }
+
+    #[test]
+    fn registers() {
+        assert_eq!(Chip::Ina219.registers(1), (0x01, 0x02));
+        assert_eq!(Chip::Ina3221.registers(1), (0x01, 0x02));
+        assert_eq!(Chip::Ina3221.registers(3), (0x05, 0x06));
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn conversions() {
+        // examples of the datasheets
+        assert_close(Chip::Ina219.shunt_voltage(0x0FA0), 0.04);
+        assert_close(Chip::Ina219.shunt_voltage(0xF060), -0.04);
+        assert_close(Chip::Ina219.bus_voltage(0x5D98), 11.98);
+        assert_close(Chip::Ina226.shunt_voltage(0x7FFF), 0.0819175);
+        assert_close(Chip::Ina226.bus_voltage(0x2580), 12.0);
+        assert_close(Chip::Ina3221.shunt_voltage(0x0FA0), 0.02);
+        assert_close(Chip::Ina3221.shunt_voltage(0xFFF8), -40e-6);
+        assert_close(Chip::Ina3221.bus_voltage(0x2EE0), 12.0);
+    }
+
+    #[test]
+    fn hwmon() {
+        let tmp = tempdir().unwrap();
+        let sysfs = tmp.path();
+        fake_hwmon(
+            sysfs,
+            "1-0041",
+            &[
+                ("in2_input", "5040\n"),
+                ("curr2_input", "500\n"),
+                ("shunt2_resistor", "10000\n"),
+            ],
+        );
+        assert!(HwmonBackend::find(sysfs, Chip::Ina3221, 1, 0x40).unwrap().is_none());
+        let backend = HwmonBackend::find(sysfs, Chip::Ina3221, 1, 0x41).unwrap().unwrap();
+        let channel = Channel {
+            number: 2,
+            label: String::from("VDD_IN"),
+            shunt_resistor: 0.02,
+            gain: 1.0,
+        };
+        let mut sensor = InaSensor::new(Box::new(backend), vec![channel]);
+        let readings = sensor.read().unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].rail, "VDD_IN");
+        assert_close(readings[0].voltage.unwrap(), 5.04);
+        assert_close(readings[0].current.unwrap(), 0.25);
+    }
+}
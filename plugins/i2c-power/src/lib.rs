@@ -0,0 +1,429 @@
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use ina::{Backend, Channel, Chip, HwmonBackend, InaSensor};
+use rail::Sensor;
+
+mod i2c;
+mod ina;
+mod metrics;
+mod pmic;
+mod rail;
+mod source;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct I2cPowerPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for I2cPowerPlugin {
+    fn name() -> &'static str {
+        "i2c-power"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            // hwmon devices of the chips bound to their Linux driver
+            Capability::Filesystem(PathBuf::from("/sys")),
+            // i2c device files, to read the chips directly
+            Capability::Filesystem(PathBuf::from("/dev")),
+            // `vcgencmd`, which measures the PMIC
+            Capability::ProcessSpawn,
+        ])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(I2cPowerPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let mut sensors: Vec<(String, &'static str, Box<dyn Sensor>)> = Vec::new();
+        let mut names = HashSet::new();
+        for device in &self.config.devices {
+            if !names.insert(device.name.as_str()) {
+                return Err(anyhow!("duplicate device name {:?}", device.name));
+            }
+            let sensor = self
+                .open_device(device)
+                .with_context(|| format!("invalid device {:?}", device.name))?;
+            sensors.push((device.name.clone(), device.chip.name(), Box::new(sensor)));
+        }
+        if let Some(pmic) = &self.config.pmic {
+            sensors.push((String::from("pmic"), "pmic", Box::new(pmic::Pmic::new(&pmic.vcgencmd))));
+        }
+        if sensors.is_empty() {
+            return Err(anyhow!(
+                "No device to measure, add a device or enable the pmic in the configuration."
+            ));
+        }
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        for (name, chip, mut sensor) in sensors {
+            // Read the rails now to report errors early, such as a wrong address or a missing vcgencmd.
+            let readings = sensor
+                .read()
+                .with_context(|| format!("failed to read the power rails of {name}"))?;
+            log::info!("Found {chip} {name} with {} power rails.", readings.len());
+
+            let source_name = format!("device_{name}");
+            let source = source::RailSource::new(name, chip, sensor, metrics.clone());
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&source_name, Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl I2cPowerPlugin {
+    fn open_device(&self, device: &DeviceConfig) -> anyhow::Result<InaSensor> {
+        let chip = device.chip;
+        let channels: Vec<Channel> = if device.channels.is_empty() {
+            (1..=chip.channels())
+                .map(|number| ChannelConfig::new(number).to_channel())
+                .collect()
+        } else {
+            device.channels.iter().map(ChannelConfig::to_channel).collect()
+        };
+        let mut labels = HashSet::new();
+        for channel in &channels {
+            if channel.number == 0 || channel.number > chip.channels() {
+                return Err(anyhow!(
+                    "invalid channel {}, the {} has {} channel(s)",
+                    channel.number,
+                    chip.name(),
+                    chip.channels()
+                ));
+            }
+            if channel.shunt_resistor <= 0.0 {
+                return Err(anyhow!("invalid shunt resistor of channel {}", channel.number));
+            }
+            if !labels.insert(channel.label.as_str()) {
+                return Err(anyhow!("duplicate channel label {:?}", channel.label));
+            }
+        }
+
+        let hwmon = match device.access {
+            Access::Auto | Access::Hwmon => HwmonBackend::find(&self.config.sysfs, chip, device.bus, device.address)?,
+            Access::I2c => None,
+        };
+        let backend: Box<dyn Backend> = match (hwmon, device.access) {
+            (Some(hwmon), _) => Box::new(hwmon),
+            (None, Access::Hwmon) => {
+                return Err(anyhow!(
+                    "no hwmon device for {:#04x} on i2c bus {}, is the driver of the {} loaded?",
+                    device.address,
+                    device.bus,
+                    chip.name()
+                ));
+            }
+            (None, _) => Box::new(i2c::I2cBackend::open(
+                &self.config.dev,
+                chip,
+                device.bus,
+                device.address,
+            )?),
+        };
+        Ok(InaSensor::new(backend, channels))
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+
+    /// Mount point of sysfs.
+    pub sysfs: PathBuf,
+
+    /// Directory of the i2c device files.
+    pub dev: PathBuf,
+
+    /// The INA chips to measure.
+    pub devices: Vec<DeviceConfig>,
+
+    /// Measures the PMIC of the Raspberry Pi 5, if set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pmic: Option<PmicConfig>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// Name of the device, used in the resource of the measurements.
+    pub name: String,
+    pub chip: Chip,
+    /// Number of the i2c bus, as in `/dev/i2c-<bus>`.
+    pub bus: u32,
+    /// Address of the chip on the bus.
+    pub address: u16,
+    /// How to read the chip.
+    #[serde(default)]
+    pub access: Access,
+    /// The channels to measure, all the channels of the chip with the default calibration if empty.
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    /// Through the Linux driver of the chip if it is loaded, directly on the i2c bus otherwise.
+    #[default]
+    Auto,
+    /// Through the hwmon device of the Linux driver of the chip.
+    Hwmon,
+    /// Directly on the i2c bus, with `/dev/i2c-<bus>`.
+    I2c,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    /// Number of the channel, from 1.
+    pub channel: u8,
+    /// Name of the power rail, `channel_<n>` if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Resistance of the shunt in Ohms.
+    #[serde(default = "default_shunt_resistor")]
+    pub shunt_resistor: f64,
+    /// Correction factor applied to the current, to calibrate the channel against a reference meter.
+    #[serde(default = "default_gain")]
+    pub gain: f64,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PmicConfig {
+    /// Path to the `vcgencmd` executable of the Raspberry Pi firmware.
+    pub vcgencmd: PathBuf,
+}
+
+fn default_shunt_resistor() -> f64 {
+    0.1
+}
+
+fn default_gain() -> f64 {
+    1.0
+}
+
+impl ChannelConfig {
+    fn new(channel: u8) -> Self {
+        Self {
+            channel,
+            label: None,
+            shunt_resistor: default_shunt_resistor(),
+            gain: default_gain(),
+        }
+    }
+
+    fn to_channel(&self) -> Channel {
+        Channel {
+            number: self.channel,
+            label: self
+                .label
+                .clone()
+                .unwrap_or_else(|| format!("channel_{}", self.channel)),
+            shunt_resistor: self.shunt_resistor,
+            gain: self.gain,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(5),
+            sysfs: PathBuf::from("/sys"),
+            dev: PathBuf::from("/dev"),
+            // the usual INA219 breakout board, with a 0.1 Ohm shunt
+            devices: vec![DeviceConfig {
+                name: String::from("ina219"),
+                chip: Chip::Ina219,
+                bus: 1,
+                address: 0x40,
+                access: Access::Auto,
+                channels: vec![ChannelConfig::new(1)],
+            }],
+            pmic: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use alumet::{measurement::WrappedMeasurementValue, test::PluginHarness};
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::ina::tests::fake_hwmon;
+
+    /// A fake vcgencmd that prints the telemetry of one rail of the PMIC.
+    fn fake_vcgencmd(dir: &Path) -> PathBuf {
+        let path = dir.join("vcgencmd");
+        let script = r#"#!/bin/sh
+[ "$1" = "pmic_read_adc" ] || exit 1
+echo "   VDD_CORE_A current(7)=2.00000000A"
+echo "   VDD_CORE_V volt(15)=0.75000000V"
+"#;
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn ina3221(channels: Vec<ChannelConfig>) -> DeviceConfig {
+        DeviceConfig {
+            name: String::from("board"),
+            chip: Chip::Ina3221,
+            bus: 1,
+            address: 0x40,
+            access: Access::Auto,
+            channels,
+        }
+    }
+
+    #[test]
+    fn ina_and_pmic() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let sysfs = tmp.path().join("sys");
+        // 5V, 0.5A with the 10 mOhm shunt of the driver
+        fake_hwmon(
+            &sysfs,
+            "1-0040",
+            &[
+                ("in1_input", "5000\n"),
+                ("curr1_input", "500\n"),
+                ("shunt1_resistor", "10000\n"),
+            ],
+        );
+        let config = Config {
+            sysfs,
+            devices: vec![ina3221(vec![ChannelConfig {
+                channel: 1,
+                label: Some(String::from("VDD_IN")),
+                // the real shunt is 20 mOhm, and the channel reads 10% too low
+                shunt_resistor: 0.02,
+                gain: 1.1,
+            }])],
+            pmic: Some(PmicConfig {
+                vcgencmd: fake_vcgencmd(tmp.path()),
+            }),
+            ..Config::default()
+        };
+        let mut harness = PluginHarness::<I2cPowerPlugin>::start(serialize_config(config)?)?;
+
+        let mut source = harness.source("device_board")?;
+        let measurements = source.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, "rail_voltage"),
+            vec![WrappedMeasurementValue::F64(5.0)]
+        );
+        let point = harness.points(&measurements, "rail_current")[0];
+        let WrappedMeasurementValue::F64(current) = point.value else {
+            panic!("unexpected value {:?}", point.value)
+        };
+        assert!((current - 0.275).abs() < 1e-9, "{current}");
+        assert_eq!(point.resource.kind(), "power_rail");
+        assert_eq!(point.resource.id_display().to_string(), "board/VDD_IN");
+        let attrs: Vec<_> = point.attributes().map(|(k, v)| (k, v.to_string())).collect();
+        assert_eq!(attrs, vec![("chip", String::from("ina3221"))]);
+        assert!(harness.values(&measurements, "rail_energy_consumption").is_empty());
+
+        let mut pmic = harness.source("device_pmic")?;
+        let measurements = pmic.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, "rail_power"),
+            vec![WrappedMeasurementValue::F64(1.5)]
+        );
+        pmic.advance(Duration::from_secs(2));
+        let measurements = pmic.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, "rail_energy_consumption"),
+            vec![WrappedMeasurementValue::F64(3.0)]
+        );
+
+        // the chip has been unbound from its driver
+        fs::remove_dir_all(tmp.path().join("sys/bus/i2c/devices/1-0040"))?;
+        assert!(matches!(
+            source.poll(),
+            Err(alumet::pipeline::elements::error::PollError::CanRetry(_))
+        ));
+
+        harness.stop()?;
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_channel() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        fake_hwmon(tmp.path(), "1-0040", &[]);
+        let config = Config {
+            sysfs: tmp.path().to_owned(),
+            devices: vec![ina3221(vec![ChannelConfig::new(4)])],
+            ..Config::default()
+        };
+        let err = PluginHarness::<I2cPowerPlugin>::start(serialize_config(config)?)
+            .err()
+            .expect("the plugin should not start with an invalid channel");
+        assert!(format!("{err:#}").contains("invalid channel 4"), "{err:#}");
+        Ok(())
+    }
+
+    #[test]
+    fn no_i2c_device() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let config = Config {
+            sysfs: tmp.path().to_owned(),
+            dev: tmp.path().to_owned(),
+            ..Config::default()
+        };
+        let err = PluginHarness::<I2cPowerPlugin>::start(serialize_config(config)?)
+            .err()
+            .expect("the plugin should not start without i2c device");
+        assert!(format!("{err:#}").contains("is the i2c-dev module loaded"), "{err:#}");
+        Ok(())
+    }
+}
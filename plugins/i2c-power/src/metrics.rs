@@ -0,0 +1,28 @@
+use alumet::{metrics::TypedMetricId, plugin::AlumetPluginStart, units::Unit};
+
+#[derive(Clone)]
+pub struct Metrics {
+    /// Voltage of a power rail.
+    pub voltage: TypedMetricId<f64>,
+    /// Current drawn from a power rail.
+    pub current: TypedMetricId<f64>,
+    /// Power drawn from a power rail.
+    pub power: TypedMetricId<f64>,
+    /// Energy drawn from a power rail since the previous measurement.
+    pub energy: TypedMetricId<f64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            voltage: alumet.create_metric("rail_voltage", Unit::Volt, "Voltage of a power rail")?,
+            current: alumet.create_metric("rail_current", Unit::Ampere, "Current drawn from a power rail")?,
+            power: alumet.create_metric("rail_power", Unit::Watt, "Power drawn from a power rail")?,
+            energy: alumet.create_metric(
+                "rail_energy_consumption",
+                Unit::Joule,
+                "Energy drawn from a power rail since the previous measurement",
+            )?,
+        })
+    }
+}
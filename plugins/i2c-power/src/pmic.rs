@@ -0,0 +1,126 @@
+//! Telemetry of the power management IC of the Raspberry Pi 5, read with `vcgencmd pmic_read_adc`.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::rail::{RailReading, Sensor};
+
+/// The PMIC of a Raspberry Pi, read with the `vcgencmd` tool of the firmware.
+pub struct Pmic {
+    vcgencmd: PathBuf,
+}
+
+impl Pmic {
+    pub fn new(vcgencmd: &Path) -> Self {
+        Self {
+            vcgencmd: vcgencmd.to_owned(),
+        }
+    }
+}
+
+impl Sensor for Pmic {
+    fn read(&mut self) -> anyhow::Result<Vec<RailReading>> {
+        let output = Command::new(&self.vcgencmd)
+            .arg("pmic_read_adc")
+            .output()
+            .with_context(|| format!("failed to run {:?}", self.vcgencmd))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{:?} failed with {}: {}",
+                self.vcgencmd,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let stdout = String::from_utf8(output.stdout).context("invalid output of vcgencmd")?;
+        parse_adc(&stdout)
+    }
+}
+
+/// Parses the output of `vcgencmd pmic_read_adc`.
+///
+/// Each line is a current (`_A` suffix, in Amperes) or a voltage (`_V` suffix, in Volts) of a rail:
+///
+/// ```txt
+///    3V7_WL_SW_A current(0)=0.00390372A
+///      3V3_SYS_A current(1)=0.05562232A
+///    3V7_WL_SW_V volt(8)=3.71170400V
+///      EXT5V_V volt(24)=5.14486000V
+/// ```
+///
+/// The rails are sorted by name.
+pub fn parse_adc(output: &str) -> anyhow::Result<Vec<RailReading>> {
+    let mut rails: BTreeMap<&str, RailReading> = BTreeMap::new();
+    for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let invalid = || anyhow!("invalid line {line:?} in the output of vcgencmd");
+        let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+        let name = name.split_whitespace().next().ok_or_else(invalid)?;
+        let value: f64 = value.trim_end_matches(['A', 'V']).parse().with_context(invalid)?;
+        let (rail, is_current) = if let Some(rail) = name.strip_suffix("_A") {
+            (rail, true)
+        } else if let Some(rail) = name.strip_suffix("_V") {
+            (rail, false)
+        } else {
+            return Err(invalid());
+        };
+        let reading = rails.entry(rail).or_insert_with(|| RailReading {
+            rail: rail.to_owned(),
+            voltage: None,
+            current: None,
+        });
+        if is_current {
+            reading.current = Some(value);
+        } else {
+            reading.voltage = Some(value);
+        }
+    }
+    Ok(rails.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let output = "     3V7_WL_SW_A current(0)=0.00390372A
+       3V3_SYS_A current(1)=0.05562232A
+     3V7_WL_SW_V volt(8)=3.71170400V
+       3V3_SYS_V volt(9)=3.31096000V
+         EXT5V_V volt(24)=5.14486000V
+";
+        assert_eq!(
+            parse_adc(output).unwrap(),
+            vec![
+                RailReading {
+                    rail: String::from("3V3_SYS"),
+                    voltage: Some(3.31096),
+                    current: Some(0.05562232),
+                },
+                RailReading {
+                    rail: String::from("3V7_WL_SW"),
+                    voltage: Some(3.711704),
+                    current: Some(0.00390372),
+                },
+                RailReading {
+                    rail: String::from("EXT5V"),
+                    voltage: Some(5.14486),
+                    current: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse_adc("error=1 error_msg=\"Command not registered\"").is_err());
+        assert!(parse_adc("VDD_CORE_A current(7)=abcA").is_err());
+    }
+}
@@ -0,0 +1,23 @@
+/// A sensor that measures one or several power rails.
+pub trait Sensor: Send {
+    /// Reads the voltage and the current of each rail.
+    fn read(&mut self) -> anyhow::Result<Vec<RailReading>>;
+}
+
+/// Voltage and current of a power rail.
+#[derive(Debug, PartialEq)]
+pub struct RailReading {
+    /// Name of the rail, such as `5V` or `VDD_CORE`.
+    pub rail: String,
+    /// Voltage in Volts.
+    pub voltage: Option<f64>,
+    /// Current in Amperes.
+    pub current: Option<f64>,
+}
+
+impl RailReading {
+    /// Returns the power of the rail in Watts, if its voltage and current are known.
+    pub fn power(&self) -> Option<f64> {
+        Some(self.voltage? * self.current?)
+    }
+}
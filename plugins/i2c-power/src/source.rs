@@ -0,0 +1,75 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{metrics::Metrics, rail::Sensor};
+
+/// Measurement source that reads the power rails of a sensor.
+pub struct RailSource {
+    device: String,
+    chip: &'static str,
+    sensor: Box<dyn Sensor>,
+    metrics: Metrics,
+    /// Last power of each rail, to compute the energy.
+    prev_power: HashMap<String, (Timestamp, f64)>,
+}
+
+impl RailSource {
+    pub fn new(device: String, chip: &'static str, sensor: Box<dyn Sensor>, metrics: Metrics) -> Self {
+        Self {
+            device,
+            chip,
+            sensor,
+            metrics,
+            prev_power: HashMap::new(),
+        }
+    }
+}
+
+impl Source for RailSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // The i2c bus can be busy or the transfer can fail because of noise, the next poll may work.
+        let readings = self
+            .sensor
+            .read()
+            .with_context(|| format!("could not read the power rails of {}", self.device))
+            .map_err(PollError::CanRetry)?;
+
+        for reading in readings {
+            let resource = Resource::Custom {
+                kind: Cow::Borrowed("power_rail"),
+                id: Cow::Owned(format!("{}/{}", self.device, reading.rail)),
+            };
+            let point = |metric: TypedMetricId<f64>, value: f64| {
+                MeasurementPoint::new(t, metric, resource.clone(), ResourceConsumer::LocalMachine, value)
+                    .with_attr("chip", self.chip)
+            };
+            if let Some(voltage) = reading.voltage {
+                measurements.push(point(self.metrics.voltage, voltage));
+            }
+            if let Some(current) = reading.current {
+                measurements.push(point(self.metrics.current, current));
+            }
+            if let Some(power) = reading.power() {
+                measurements.push(point(self.metrics.power, power));
+                if let Some((prev_t, prev_power)) = self.prev_power.insert(reading.rail, (t, power)) {
+                    let energy = compute_energy(prev_t, prev_power, t, power).map_err(PollError::CanRetry)?;
+                    measurements.push(point(self.metrics.energy, energy));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Computes the energy, in Joules, consumed between two power measurements, with a discrete integral.
+fn compute_energy(prev_t: Timestamp, prev_power: f64, t: Timestamp, power: f64) -> anyhow::Result<f64> {
+    let elapsed = t.duration_since(prev_t)?.as_secs_f64();
+    Ok((prev_power + power) / 2.0 * elapsed)
+}
@@ -0,0 +1,124 @@
+//! InfluxDB query APIs.
+
+use std::time::Duration;
+
+use alumet::pipeline::elements::source::blocking::BlockingDrop;
+
+use anyhow::{Context, anyhow};
+use reqwest::{
+    blocking::{Client as HttpClient, Response},
+    header,
+};
+use serde_json::json;
+
+/// Client for the query APIs of InfluxDB.
+pub struct Client {
+    client: BlockingDrop<HttpClient>,
+    host: String,
+    /// String of the form `Token <api_token>`.
+    token_header: String,
+}
+
+impl Client {
+    pub fn new(host: &str, token: &str, timeout: Duration) -> anyhow::Result<Self> {
+        let client = HttpClient::builder().timeout(timeout).build()?;
+        Ok(Self {
+            client: BlockingDrop::new(client),
+            host: host.trim_end_matches('/').to_owned(),
+            token_header: format!("Token {token}"),
+        })
+    }
+
+    /// Runs a Flux query with the v2 API, and returns the result as annotated CSV.
+    pub fn flux(&self, org: &str, query: &str) -> anyhow::Result<String> {
+        let body = json!({
+            "query": query,
+            "type": "flux",
+            "dialect": { "header": true, "annotations": [] },
+        });
+        let res = self
+            .client
+            .post(format!("{}/api/v2/query", self.host))
+            .query(&[("org", org)])
+            .header(header::AUTHORIZATION, &self.token_header)
+            .header(header::ACCEPT, "application/csv")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()?;
+        text_or_error(res)
+    }
+
+    /// Runs an InfluxQL query with the v1 compatibility API, and returns the result as JSON.
+    ///
+    /// The timestamps of the result are in nanoseconds.
+    pub fn influxql(&self, database: &str, query: &str) -> anyhow::Result<String> {
+        let res = self
+            .client
+            .get(format!("{}/query", self.host))
+            .query(&[("db", database), ("q", query), ("epoch", "ns")])
+            .header(header::AUTHORIZATION, &self.token_header)
+            .header(header::ACCEPT, "application/json")
+            .send()?;
+        text_or_error(res)
+    }
+}
+
+/// Returns the body of a response, or an error that contains the message of InfluxDB.
+fn text_or_error(res: Response) -> anyhow::Result<String> {
+    let status = res.status();
+    let body = res.text().context("failed to get a response from the server")?;
+    if status.is_success() {
+        Ok(body)
+    } else {
+        Err(anyhow!("InfluxDB returned {status}: {}", body.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{Matcher, Server};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn flux() {
+        let mut server = Server::new();
+        let mock = server
+            .mock("POST", "/api/v2/query")
+            .match_query(Matcher::UrlEncoded("org".into(), "facility".into()))
+            .match_header("authorization", "Token secret")
+            .match_body(Matcher::PartialJson(
+                json!({ "query": "from(bucket: \"meters\")", "type": "flux" }),
+            ))
+            .with_body(",result,table,_time,_value\r\n")
+            .create();
+        let client = Client::new(&server.url(), "secret", Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            client.flux("facility", "from(bucket: \"meters\")").unwrap(),
+            ",result,table,_time,_value\r\n"
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn influxql_error() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/query")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("db".into(), "meters".into()),
+                Matcher::UrlEncoded("q".into(), "SELECT".into()),
+                Matcher::UrlEncoded("epoch".into(), "ns".into()),
+            ]))
+            .with_status(400)
+            .with_body(r#"{"error":"error parsing query: found EOF, expected identifier"}"#)
+            .create();
+        let client = Client::new(&server.url(), "secret", Duration::from_secs(5)).unwrap();
+        let error = client.influxql("meters", "SELECT").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"InfluxDB returned 400 Bad Request: {"error":"error parsing query: found EOF, expected identifier"}"#
+        );
+    }
+}
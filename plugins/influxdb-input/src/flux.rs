@@ -0,0 +1,184 @@
+//! Parsing of the results of the Flux queries, in annotated CSV.
+//!
+//! The result contains one or several blocks separated by an empty line. Each block starts with a
+//! header, which gives the columns of the block, like `,result,table,_start,_stop,_time,_value,_field,_measurement,host`.
+//! See <https://docs.influxdata.com/influxdb/v2/reference/syntax/annotated-csv/>.
+
+use anyhow::{Context, anyhow};
+
+use crate::source::Row;
+
+/// Columns that give information about the query, and not about the series.
+const IGNORED_COLUMNS: [&str; 5] = ["", "result", "table", "_start", "_stop"];
+
+pub fn parse(body: &str) -> anyhow::Result<Vec<Row>> {
+    let mut rows = Vec::new();
+    let mut header: Option<Vec<String>> = None;
+    for record in records(body) {
+        if record.iter().all(|field| field.is_empty()) {
+            // end of a block
+            header = None;
+            continue;
+        }
+        let Some(columns) = &header else {
+            if record.iter().any(|c| c == "error") {
+                // the next line contains the error
+                header = Some(record);
+                continue;
+            }
+            if !record.iter().any(|c| c == "_time") || !record.iter().any(|c| c == "_value") {
+                return Err(anyhow!(
+                    "the result has no _time or no _value column, please keep these columns in the query"
+                ));
+            }
+            header = Some(record);
+            continue;
+        };
+        if let Some(i) = columns.iter().position(|c| c == "error") {
+            return Err(anyhow!(
+                "the query failed: {}",
+                record.get(i).map_or("", |e| e.as_str())
+            ));
+        }
+        if let Some(row) = parse_row(columns, record)? {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
+
+/// Converts a record to a row, or returns `None` if its value is not a number.
+fn parse_row(columns: &[String], record: Vec<String>) -> anyhow::Result<Option<Row>> {
+    let mut timestamp = None;
+    let mut value = None;
+    let mut attributes = Vec::new();
+    for (column, field) in columns.iter().zip(record) {
+        match column.as_str() {
+            "_time" => {
+                let t = humantime::parse_rfc3339(&field).with_context(|| format!("invalid time {field:?}"))?;
+                timestamp = Some(t.into());
+            }
+            "_value" => match field.parse::<f64>() {
+                Ok(v) => value = Some(v),
+                // string and boolean values
+                Err(_) => return Ok(None),
+            },
+            c if IGNORED_COLUMNS.contains(&c) || field.is_empty() => (),
+            "_measurement" => attributes.push((String::from("measurement"), field)),
+            "_field" => attributes.push((String::from("field"), field)),
+            c => attributes.push((c.to_owned(), field)),
+        }
+    }
+    match (timestamp, value) {
+        (Some(timestamp), Some(value)) => Ok(Some(Row {
+            timestamp,
+            value,
+            attributes,
+        })),
+        _ => Ok(None),
+    }
+}
+
+/// Splits a CSV text into records of unescaped fields.
+///
+/// The empty lines are kept as records with one empty field.
+fn records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => (),
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::measurement::Timestamp;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn attrs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn several_blocks() {
+        let body = ",result,table,_start,_stop,_time,_value,_field,_measurement,meter\r\n\
+                    ,_result,0,2025-01-01T00:00:00Z,2025-01-01T01:00:00Z,2025-01-01T00:00:00Z,1520.5,power,facility,\"main, north\"\r\n\
+                    ,_result,0,2025-01-01T00:00:00Z,2025-01-01T01:00:00Z,2025-01-01T00:00:10.5Z,1530,power,facility,\"main, north\"\r\n\
+                    \r\n\
+                    ,result,table,_start,_stop,_time,_value,_field,_measurement\r\n\
+                    ,_result,1,2025-01-01T00:00:00Z,2025-01-01T01:00:00Z,2025-01-01T00:00:00Z,ok,status,facility\r\n\
+                    ,_result,2,2025-01-01T00:00:00Z,2025-01-01T01:00:00Z,2025-01-01T00:00:00Z,21.5,temperature,facility\r\n\
+                    \r\n";
+        let t0 = Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_735_689_600));
+        assert_eq!(
+            parse(body).unwrap(),
+            vec![
+                Row {
+                    timestamp: t0,
+                    value: 1520.5,
+                    attributes: attrs(&[
+                        ("field", "power"),
+                        ("measurement", "facility"),
+                        ("meter", "main, north")
+                    ]),
+                },
+                Row {
+                    timestamp: Timestamp::from(UNIX_EPOCH + Duration::from_millis(1_735_689_610_500)),
+                    value: 1530.0,
+                    attributes: attrs(&[
+                        ("field", "power"),
+                        ("measurement", "facility"),
+                        ("meter", "main, north")
+                    ]),
+                },
+                // the string value "ok" is ignored
+                Row {
+                    timestamp: t0,
+                    value: 21.5,
+                    attributes: attrs(&[("field", "temperature"), ("measurement", "facility")]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_result() {
+        assert_eq!(parse("").unwrap(), vec![]);
+        assert_eq!(parse("\r\n").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = parse(",error,reference\r\n,\"type error: \"\"x\"\" undefined\",\r\n").unwrap_err();
+        assert_eq!(error.to_string(), r#"the query failed: type error: "x" undefined"#);
+
+        assert!(parse(",result,table,_time,host\r\n,_result,0,2025-01-01T00:00:00Z,a\r\n").is_err());
+        assert!(parse(",result,table,_time,_value\r\n,_result,0,yesterday,1\r\n").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Parsing of the results of the InfluxQL queries, in JSON.
+//!
+//! Each series of the result has a name (the measurement), tags, columns and rows of values, like
+//! `{"name":"facility","tags":{"meter":"main"},"columns":["time","power"],"values":[[1735689600000000000,1520.5]]}`.
+
+use std::collections::BTreeMap;
+
+use alumet::measurement::Timestamp;
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::source::Row;
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    results: Vec<StatementResult>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StatementResult {
+    #[serde(default)]
+    series: Vec<Series>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Series {
+    name: String,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    columns: Vec<String>,
+    #[serde(default)]
+    values: Vec<Vec<Value>>,
+}
+
+pub fn parse(body: &str) -> anyhow::Result<Vec<Row>> {
+    let response: Response = serde_json::from_str(body).context("invalid response")?;
+    if let Some(error) = response.error {
+        return Err(anyhow!("the query failed: {error}"));
+    }
+    let mut rows = Vec::new();
+    for result in response.results {
+        if let Some(error) = result.error {
+            return Err(anyhow!("the query failed: {error}"));
+        }
+        for series in result.series {
+            parse_series(series, &mut rows)?;
+        }
+    }
+    Ok(rows)
+}
+
+/// Converts the values of a series to rows: one row per number.
+///
+/// The columns that contain strings or booleans, such as the tags selected with `SELECT *`,
+/// are attributes of the rows. If the series has several numerical columns, the name of the
+/// column is given by the attribute `field`.
+fn parse_series(series: Series, rows: &mut Vec<Row>) -> anyhow::Result<()> {
+    let time = series
+        .columns
+        .iter()
+        .position(|c| c == "time")
+        .ok_or_else(|| anyhow!("the series {} has no time column", series.name))?;
+    let numerical: Vec<bool> = (0..series.columns.len())
+        .map(|i| i != time && series.values.iter().any(|v| v.get(i).is_some_and(Value::is_number)))
+        .collect();
+    let several_fields = numerical.iter().filter(|n| **n).count() > 1;
+
+    let mut attributes = vec![(String::from("measurement"), series.name.clone())];
+    attributes.extend(series.tags);
+    for values in series.values {
+        let nanos = values
+            .get(time)
+            .and_then(Value::as_u64)
+            .ok_or_else(|| anyhow!("invalid time in series {}", series.name))?;
+        let timestamp = Timestamp::from_unix_timestamp(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32);
+
+        let mut row_attributes = attributes.clone();
+        for (i, value) in values.iter().enumerate() {
+            let text = match value {
+                Value::String(s) => s.clone(),
+                Value::Bool(b) => b.to_string(),
+                _ => continue,
+            };
+            if !numerical[i] {
+                row_attributes.push((series.columns[i].clone(), text));
+            }
+        }
+        for (i, value) in values.iter().enumerate() {
+            let Some(value) = value.as_f64().filter(|_| numerical[i]) else {
+                continue;
+            };
+            let mut attributes = row_attributes.clone();
+            if several_fields {
+                attributes.push((String::from("field"), series.columns[i].clone()));
+            }
+            rows.push(Row {
+                timestamp,
+                value,
+                attributes,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn attrs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn series() {
+        let body = r#"{"results":[{"statement_id":0,"series":[
+            {"name":"facility","tags":{"meter":"main"},"columns":["time","power","site"],"values":[
+                [1735689600000000000,1520.5,"north"],
+                [1735689610500000000,1530,"north"]
+            ]},
+            {"name":"cooling","columns":["time","power","flow","state"],"values":[
+                [1735689600000000000,300,null,"on"],
+                [1735689600000000000,null,2.5,"off"]
+            ]}
+        ]}]}"#;
+        let t0 = Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_735_689_600));
+        assert_eq!(
+            parse(body).unwrap(),
+            vec![
+                Row {
+                    timestamp: t0,
+                    value: 1520.5,
+                    attributes: attrs(&[("measurement", "facility"), ("meter", "main"), ("site", "north")]),
+                },
+                Row {
+                    timestamp: Timestamp::from(UNIX_EPOCH + Duration::from_millis(1_735_689_610_500)),
+                    value: 1530.0,
+                    attributes: attrs(&[("measurement", "facility"), ("meter", "main"), ("site", "north")]),
+                },
+                Row {
+                    timestamp: t0,
+                    value: 300.0,
+                    attributes: attrs(&[("measurement", "cooling"), ("state", "on"), ("field", "power")]),
+                },
+                Row {
+                    timestamp: t0,
+                    value: 2.5,
+                    attributes: attrs(&[("measurement", "cooling"), ("state", "off"), ("field", "flow")]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_result() {
+        assert_eq!(parse(r#"{"results":[{"statement_id":0}]}"#).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = parse(r#"{"results":[{"statement_id":0,"error":"database not found: meters"}]}"#).unwrap_err();
+        assert_eq!(error.to_string(), "the query failed: database not found: meters");
+        assert!(parse(r#"{"error":"unauthorized"}"#).is_err());
+        assert!(parse("<html>").is_err());
+    }
+}
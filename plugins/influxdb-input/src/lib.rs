@@ -0,0 +1,160 @@
+use std::{collections::HashSet, str::FromStr, time::Duration};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    units::PrefixedUnit,
+};
+
+use client::Client;
+use source::QuerySource;
+
+mod client;
+mod flux;
+mod influxql;
+mod source;
+
+pub struct InfluxDbInputPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for InfluxDbInputPlugin {
+    fn name() -> &'static str {
+        "influxdb-input"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(InfluxDbInputPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        if self.config.queries.is_empty() {
+            return Err(anyhow!("No query configured, please add some to the configuration."));
+        }
+
+        let mut metrics = HashSet::new();
+        for query in &self.config.queries {
+            if !metrics.insert(&query.metric) {
+                return Err(anyhow!(
+                    "metric {} is given by several queries, please use one metric per query",
+                    query.metric
+                ));
+            }
+            let unit = PrefixedUnit::from_str(&query.unit)
+                .with_context(|| format!("invalid unit of the query of {}", query.metric))?;
+            let metric = alumet.create_metric::<f64>(&query.metric, unit, &query.description)?;
+
+            // InfluxDB is contacted on the first poll, so that an unreachable server does not prevent the agent from starting.
+            let client = Client::new(&self.config.host, &self.config.token, self.config.timeout)
+                .context("failed to create the HTTP client")?;
+            let target = match query.language {
+                Language::Flux => self.config.org.clone(),
+                Language::Influxql => self.config.database.clone(),
+            };
+            let source = QuerySource::new(client, query.language, target, query.query.clone(), metric);
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&format!("query_{}", query.metric), Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the host where InfluxDB is running
+    pub host: String,
+    /// Token to read the database
+    pub token: String,
+    /// Organisation of the Flux queries
+    pub org: String,
+    /// Database of the InfluxQL queries (with InfluxDB v2: a bucket mapped to a database)
+    pub database: String,
+
+    /// Initial interval between two executions of the queries.
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+
+    /// How long to wait for the result of a query.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+
+    pub queries: Vec<Query>,
+}
+
+/// A query, whose results are the measurements of a metric.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Query {
+    /// Name of the Alumet metric.
+    pub metric: String,
+    /// Unit of the values, such as `W` or `kW.h`.
+    pub unit: String,
+    #[serde(default)]
+    pub description: String,
+    pub language: Language,
+    pub query: String,
+}
+
+/// Language of a query.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// Flux, with the `/api/v2/query` API of InfluxDB v2.
+    Flux,
+    /// InfluxQL, with the `/query` API of InfluxDB v1, and its compatibility API in InfluxDB v2 and v3.
+    Influxql,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: String::from("http://localhost:8086"),
+            token: String::from("FILL ME"),
+            org: String::from("FILL ME"),
+            database: String::from("FILL ME"),
+            poll_interval: Duration::from_secs(60),
+            flush_interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+            queries: vec![Query {
+                metric: String::from("facility_power"),
+                unit: String::from("W"),
+                description: String::from("Power measured by the meters of the facility"),
+                language: Language::Flux,
+                query: String::from(
+                    r#"from(bucket: "facility") |> range(start: -5m) |> filter(fn: (r) => r._measurement == "power")"#,
+                ),
+            }],
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{Language, client::Client, flux, influxql};
+
+/// A point of a series returned by InfluxDB.
+#[derive(Debug, PartialEq)]
+pub struct Row {
+    pub timestamp: Timestamp,
+    pub value: f64,
+    /// Measurement, field and tags of the series.
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Measurement source that runs a query at regular intervals.
+pub struct QuerySource {
+    client: Client,
+    language: Language,
+    /// Organization (Flux) or database (InfluxQL) of the query.
+    target: String,
+    query: String,
+    metric: TypedMetricId<f64>,
+    /// Timestamp of the last point of each series that has been measured, by series key.
+    last_points: HashMap<String, Timestamp>,
+}
+
+impl QuerySource {
+    pub fn new(client: Client, language: Language, target: String, query: String, metric: TypedMetricId<f64>) -> Self {
+        Self {
+            client,
+            language,
+            target,
+            query,
+            metric,
+            last_points: HashMap::new(),
+        }
+    }
+
+    fn run_query(&self) -> anyhow::Result<Vec<Row>> {
+        match self.language {
+            Language::Flux => flux::parse(&self.client.flux(&self.target, &self.query)?),
+            Language::Influxql => influxql::parse(&self.client.influxql(&self.target, &self.query)?),
+        }
+    }
+}
+
+impl Source for QuerySource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, _t: Timestamp) -> Result<(), PollError> {
+        // The HTTP requests are blocking, and InfluxDB can be unreachable for a while (the next poll may work).
+        let rows = tokio::task::block_in_place(|| self.run_query())
+            .context("failed to query InfluxDB")
+            .map_err(PollError::CanRetry)?;
+
+        // The time ranges of two successive queries usually overlap: only the new points are measured.
+        let mut new_last_points: HashMap<String, Timestamp> = HashMap::new();
+        for row in rows {
+            let key = format!("{:?}", row.attributes);
+            if let Some(last) = self.last_points.get(&key)
+                && row.timestamp <= *last
+            {
+                continue;
+            }
+            let last = new_last_points.entry(key).or_insert(row.timestamp);
+            *last = (*last).max(row.timestamp);
+            let attributes = row
+                .attributes
+                .into_iter()
+                .map(|(key, value)| (key, AttributeValue::String(value)))
+                .collect();
+            let point = MeasurementPoint::new(
+                row.timestamp,
+                self.metric,
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                row.value,
+            )
+            .with_attr_vec(attributes);
+            measurements.push(point);
+        }
+        self.last_points.extend(new_last_points);
+        Ok(())
+    }
+}
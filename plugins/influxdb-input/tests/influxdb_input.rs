@@ -0,0 +1,93 @@
+use alumet::{
+    measurement::{AttributeValue, WrappedMeasurementValue},
+    pipeline::elements::error::PollError,
+    plugin::rust::serialize_config,
+    test::PluginHarness,
+};
+use mockito::{Matcher, Server};
+use plugin_influxdb_input::{Config, InfluxDbInputPlugin, Language, Query};
+use pretty_assertions::assert_eq;
+
+const QUERY: &str = "SELECT power FROM facility WHERE time > now() - 5m GROUP BY meter";
+
+fn config(host: String) -> Config {
+    Config {
+        host,
+        token: String::from("secret"),
+        database: String::from("meters"),
+        queries: vec![Query {
+            metric: String::from("facility_power"),
+            unit: String::from("kW"),
+            description: String::new(),
+            language: Language::Influxql,
+            query: String::from(QUERY),
+        }],
+        ..Config::default()
+    }
+}
+
+fn mock_query(server: &mut Server, values: &str) -> mockito::Mock {
+    server
+        .mock("GET", "/query")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("db".into(), "meters".into()),
+            Matcher::UrlEncoded("q".into(), QUERY.into()),
+        ]))
+        .match_header("authorization", "Token secret")
+        .with_body(format!(
+            r#"{{"results":[{{"statement_id":0,"series":[{{"name":"facility","tags":{{"meter":"main"}},"columns":["time","power"],"values":{values}}}]}}]}}"#
+        ))
+        .create()
+}
+
+#[test]
+fn only_new_points_are_measured() -> anyhow::Result<()> {
+    let mut server = Server::new();
+    let mut harness = PluginHarness::<InfluxDbInputPlugin>::start(serialize_config(config(server.url()))?)?;
+    let metric = harness.metrics().by_name("facility_power").unwrap().1.clone();
+    assert_eq!(metric.unit.unique_name(), "kiloW");
+    let mut source = harness.source("query_facility_power")?;
+
+    let first = mock_query(&mut server, "[[1735689600000000000,1.5],[1735689660000000000,1.6]]");
+    let measurements = source.poll().unwrap();
+    first.assert();
+    first.remove();
+    assert_eq!(
+        harness.values(&measurements, "facility_power"),
+        vec![WrappedMeasurementValue::F64(1.5), WrappedMeasurementValue::F64(1.6)]
+    );
+    let point = harness.points(&measurements, "facility_power")[0];
+    let attributes: Vec<_> = point.attributes().map(|(k, v)| (k.to_owned(), v.clone())).collect();
+    assert_eq!(
+        attributes,
+        vec![
+            (
+                String::from("measurement"),
+                AttributeValue::String(String::from("facility"))
+            ),
+            (String::from("meter"), AttributeValue::String(String::from("main"))),
+        ]
+    );
+
+    // the time range of the second query overlaps the first one
+    mock_query(&mut server, "[[1735689660000000000,1.6],[1735689720000000000,1.7]]");
+    let measurements = source.poll().unwrap();
+    assert_eq!(
+        harness.values(&measurements, "facility_power"),
+        vec![WrappedMeasurementValue::F64(1.7)]
+    );
+
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn unreachable_server() -> anyhow::Result<()> {
+    // nothing listens on port 9 (discard)
+    let mut harness =
+        PluginHarness::<InfluxDbInputPlugin>::start(serialize_config(config(String::from("http://127.0.0.1:9")))?)?;
+    let mut source = harness.source("query_facility_power")?;
+    assert!(matches!(source.poll(), Err(PollError::CanRetry(_))));
+    harness.stop()?;
+    Ok(())
+}
@@ -1,42 +1,125 @@
-//! InfluxDB2 API.
+//! InfluxDB2 and InfluxDB3 write APIs.
 
 use alumet::measurement::Timestamp;
 use anyhow::Context;
 use reqwest::{Url, header};
+use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
     fmt::Write,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-/// Client for InfluxDB v2.
+/// Version of the InfluxDB write API.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiVersion {
+    /// `/api/v2/write`, with an organization and a bucket.
+    #[default]
+    V2,
+    /// `/api/v3/write_lp`, with a database (the bucket in the configuration). The organization is ignored.
+    V3,
+}
+
+/// Precision of the timestamps in the line protocol.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    Ns,
+    Us,
+    Ms,
+    S,
+}
+
+impl Precision {
+    /// Name of the precision in the query parameters of the API.
+    fn query_param(&self, api: ApiVersion) -> &'static str {
+        match (api, self) {
+            (ApiVersion::V2, Precision::Ns) => "ns",
+            (ApiVersion::V2, Precision::Us) => "us",
+            (ApiVersion::V2, Precision::Ms) => "ms",
+            (ApiVersion::V2, Precision::S) => "s",
+            (ApiVersion::V3, Precision::Ns) => "nanosecond",
+            (ApiVersion::V3, Precision::Us) => "microsecond",
+            (ApiVersion::V3, Precision::Ms) => "millisecond",
+            (ApiVersion::V3, Precision::S) => "second",
+        }
+    }
+
+    /// Number of nanoseconds in one unit of this precision.
+    fn nanos(&self) -> u128 {
+        match self {
+            Precision::Ns => 1,
+            Precision::Us => 1_000,
+            Precision::Ms => 1_000_000,
+            Precision::S => 1_000_000_000,
+        }
+    }
+}
+
+/// Client for InfluxDB v2 or v3.
 pub struct Client {
     client: reqwest::Client,
-    /// String of the form `<host>/api/v2/write`.
+    host: String,
+    api: ApiVersion,
+    precision: Precision,
+    /// String of the form `<host>/api/v2/write` or `<host>/api/v3/write_lp`.
     write_url: String,
-    /// String of the form `Token <api_token>`.
+    /// String of the form `Token <api_token>` (v2) or `Bearer <api_token>` (v3).
     token_header: String,
+    token: String,
 }
 
 impl Client {
     pub fn new(host: String, token: String) -> Self {
         let write_url = format!("{host}/api/v2/write");
-        let token = format!("Token {token}");
+        let token_header = format!("Token {token}");
         Self {
             client: reqwest::Client::new(),
+            host,
+            api: ApiVersion::V2,
+            precision: Precision::Ns,
             write_url,
-            token_header: token,
+            token_header,
+            token,
+        }
+    }
+
+    /// Uses the given version of the write API.
+    pub fn api_version(mut self, api: ApiVersion) -> Self {
+        self.api = api;
+        match api {
+            ApiVersion::V2 => {
+                self.write_url = format!("{}/api/v2/write", self.host);
+                self.token_header = format!("Token {}", self.token);
+            }
+            ApiVersion::V3 => {
+                self.write_url = format!("{}/api/v3/write_lp", self.host);
+                self.token_header = format!("Bearer {}", self.token);
+            }
         }
+        self
+    }
+
+    /// Sets the precision of the timestamps, which must match the precision of the data.
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
     }
 
     /// Writes measurements to InfluxDB, in the given organization and bucket.
+    ///
+    /// With the v3 API, `bucket` is the database and `org` is ignored.
     pub async fn write(&self, org: &str, bucket: &str, data: LineProtocolData) -> anyhow::Result<()> {
-        // TODO optimize: https://docs.influxdata.com/influxdb/v2/write-data/best-practices/optimize-writes
-        let precision = "ns";
-        let url = Url::parse_with_params(
-            &self.write_url,
-            &[("org", org), ("bucket", bucket), ("precision", precision)],
-        )?;
+        let precision = self.precision.query_param(self.api);
+        let url = match self.api {
+            ApiVersion::V2 => Url::parse_with_params(
+                &self.write_url,
+                &[("org", org), ("bucket", bucket), ("precision", precision)],
+            )?,
+            ApiVersion::V3 => Url::parse_with_params(&self.write_url, &[("db", bucket), ("precision", precision)])?,
+        };
         let res = self
             .client
             .post(url)
@@ -61,8 +144,20 @@ impl Client {
     ///
     /// Returns `Ok(())` if all goes well.
     pub async fn test_write(&self, org: &str, bucket: &str) -> anyhow::Result<()> {
-        // send empty data
-        self.write(org, bucket, LineProtocolData(String::new())).await
+        match self.api {
+            // send empty data
+            ApiVersion::V2 => self.write(org, bucket, LineProtocolData(String::new())).await,
+            // InfluxDB3 rejects empty data, check that the server is reachable and that the token is valid
+            ApiVersion::V3 => {
+                self.client
+                    .get(format!("{}/health", self.host))
+                    .header(header::AUTHORIZATION, &self.token_header)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+        }
     }
 }
 
@@ -78,6 +173,7 @@ impl LineProtocolData {
 pub struct LineProtocolBuilder {
     buf: String,
     after_first_field: bool,
+    precision: Precision,
 }
 
 #[allow(unused)]
@@ -86,6 +182,7 @@ impl LineProtocolBuilder {
         Self {
             buf: String::new(),
             after_first_field: false,
+            precision: Precision::Ns,
         }
     }
 
@@ -94,9 +191,16 @@ impl LineProtocolBuilder {
         Self {
             buf: String::with_capacity(capacity),
             after_first_field: false,
+            precision: Precision::Ns,
         }
     }
 
+    /// Sets the precision of the timestamps.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
     /// Writes the measurement to the current line.
     ///
     /// Must be called first in a line. Required.
@@ -178,7 +282,7 @@ impl LineProtocolBuilder {
         self.field(key, if value { "T" } else { "F" })
     }
 
-    /// Writes a tag to the current line.
+    /// Writes the timestamp of the current line, truncated to the precision of the builder.
     ///
     /// Must be called after `field`. Required.
     pub fn timestamp(&mut self, timestamp: Timestamp) -> &mut Self {
@@ -186,7 +290,7 @@ impl LineProtocolBuilder {
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_nanos();
-        write!(self.buf, " {nanoseconds}").unwrap();
+        write!(self.buf, " {}", nanoseconds / self.precision.nanos()).unwrap();
         self
     }
 
@@ -224,7 +328,7 @@ mod tests {
     use mockito::{Matcher, Mock, Server, ServerGuard};
     use std::time::{Duration, UNIX_EPOCH};
 
-    use super::{Client, LineProtocolBuilder, LineProtocolData};
+    use super::{ApiVersion, Client, LineProtocolBuilder, LineProtocolData, Precision};
     use crate::influxdb2::escape_string;
     use alumet::measurement::Timestamp;
 
@@ -343,6 +447,45 @@ measurement_without_tags fieldKey="fieldValue",bool=T,float=123,int=-123i,uint=1
         test_write_line.assert();
     }
 
+    #[test]
+    fn precision() {
+        let ts = Timestamp::from(UNIX_EPOCH + Duration::from_nanos(1556813561098765432));
+        let mut builder = LineProtocolBuilder::new().with_precision(Precision::Ms);
+        builder.measurement("m").field_uint("value", 1).timestamp(ts);
+        assert_eq!(builder.build().0, "m value=1u 1556813561098");
+
+        let mut builder = LineProtocolBuilder::new().with_precision(Precision::S);
+        builder.measurement("m").field_uint("value", 1).timestamp(ts);
+        assert_eq!(builder.build().0, "m value=1u 1556813561");
+    }
+
+    #[tokio::test]
+    async fn write_v3() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/v3/write_lp")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("db".into(), "somedb".into()),
+                Matcher::UrlEncoded("precision".into(), "millisecond".into()),
+            ]))
+            .match_header("authorization", "Bearer sometoken")
+            .match_body("m value=1u 1556813561098")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let influx_client = Client::new(server.url(), String::from("sometoken"))
+            .api_version(ApiVersion::V3)
+            .precision(Precision::Ms);
+        let mut builder = LineProtocolBuilder::new().with_precision(Precision::Ms);
+        builder
+            .measurement("m")
+            .field_uint("value", 1)
+            .timestamp(Timestamp::from(UNIX_EPOCH + Duration::from_nanos(1556813561098765432)));
+        influx_client.write("", "somedb", builder.build()).await.unwrap();
+        mock.assert();
+    }
+
     #[test]
     fn verify_client() {
         let url = "http://127.0.0.1:8086";
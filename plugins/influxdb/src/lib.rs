@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use alumet::{
-    measurement::{AttributeValue, MeasurementBuffer, WrappedMeasurementValue},
+    measurement::{AttributeValue, MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
     pipeline::{
         Output,
         elements::{
@@ -9,12 +9,17 @@ use alumet::{
             output::{OutputContext, error::WriteRetry},
         },
     },
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::{
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
 };
 use anyhow::Context;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use crate::influxdb2::LineProtocolData;
+pub use crate::influxdb2::{ApiVersion, Precision};
 
 mod influxdb2;
 
@@ -31,6 +36,10 @@ impl AlumetPlugin for InfluxDbPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
@@ -42,9 +51,12 @@ impl AlumetPlugin for InfluxDbPlugin {
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
         let config = self.config.take().unwrap();
+        anyhow::ensure!(config.batch_size > 0, "batch_size must be greater than zero");
 
         // Connect to InfluxDB to detect configuration errors early.
-        let influx_client = influxdb2::Client::new(config.host.clone(), config.token.clone());
+        let influx_client = influxdb2::Client::new(config.host.clone(), config.token.clone())
+            .api_version(config.api_version)
+            .precision(config.precision);
         let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
         log::info!("Testing connection to InfluxDB...");
         rt.block_on(influx_client.test_write(&config.org, &config.bucket))
@@ -63,6 +75,8 @@ impl AlumetPlugin for InfluxDbPlugin {
                 client: influx_client,
                 org: config.org,
                 bucket: config.bucket,
+                precision: config.precision,
+                batch_size: config.batch_size,
                 attributes_as: config.attributes_as,
                 attributes_as_tags: config.attributes_as_tags.unwrap_or_default(),
                 attributes_as_fields: config.attributes_as_fields.unwrap_or_default(),
@@ -80,6 +94,9 @@ struct InfluxDbOutput {
     client: influxdb2::Client,
     org: String,
     bucket: String,
+    precision: Precision,
+    /// Maximum number of lines per write request.
+    batch_size: usize,
     attributes_as: AttributeAs,
     attributes_as_tags: HashSet<String>,
     attributes_as_fields: HashSet<String>,
@@ -93,10 +110,35 @@ impl Output for InfluxDbOutput {
             return Ok(());
         }
 
-        // Build the data to send to InfluxDB.
-        let mut builder = LineProtocolData::builder();
+        // Split the measurements in batches, each batch is sent in its own request.
+        for batch in &measurements.iter().chunks(self.batch_size) {
+            let data = self.build_batch(batch, ctx)?;
+            log::debug!("Line protocol data: {data:?}");
+
+            // Do the writing on the tokio Runtime.
+            let handle = tokio::runtime::Handle::current();
+            handle
+                .block_on(self.client.write(&self.org, &self.bucket, data))
+                .context("failed to write measurements to InfluxDB")
+                .retry_write()?;
+        }
+        Ok(())
+    }
+}
+
+impl InfluxDbOutput {
+    /// Builds the line protocol data for a batch of measurements.
+    fn build_batch<'a>(
+        &self,
+        measurements: impl Iterator<Item = &'a MeasurementPoint>,
+        ctx: &OutputContext,
+    ) -> anyhow::Result<LineProtocolData> {
+        let mut builder = LineProtocolData::builder().with_precision(self.precision);
         for m in measurements {
-            let metric = ctx.metrics.by_id(&m.metric).unwrap();
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
             builder.measurement(&metric.name);
 
             // Resources and consumers are translated to tags.
@@ -155,16 +197,7 @@ impl Output for InfluxDbOutput {
             // And the timestamp comes last.
             builder.timestamp(m.timestamp);
         }
-        let data = builder.build();
-        log::debug!("Line protocol data: {data:?}");
-
-        // Do the writing on the tokio Runtime.
-        let handle = tokio::runtime::Handle::current();
-        handle
-            .block_on(self.client.write(&self.org, &self.bucket, data))
-            .context("failed to write measurements to InfluxDB")
-            .retry_write()?;
-        Ok(())
+        Ok(builder.build())
     }
 }
 
@@ -215,8 +248,17 @@ pub struct Config {
     pub token: String,
     /// Organisation where to write data
     pub org: String,
-    /// Bucket where to write data   
+    /// Bucket where to write data (with InfluxDB v3: the database)
     pub bucket: String,
+    /// Version of the InfluxDB API: `"v2"` or `"v3"`.
+    #[serde(default)]
+    pub api_version: ApiVersion,
+    /// Precision of the timestamps: `"ns"`, `"us"`, `"ms"` or `"s"`.
+    #[serde(default)]
+    pub precision: Precision,
+    /// Maximum number of lines sent in one write request.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
     /// By default, serialize all Alumet attributes as fields. This can be either `"field"` or `"tag".
     pub attributes_as: AttributeAs,
     /// Always serialize the given list of attributes as InfluxDB tags
@@ -237,6 +279,11 @@ pub enum AttributeAs {
     Field,
 }
 
+fn default_batch_size() -> usize {
+    // recommended by https://docs.influxdata.com/influxdb/v2/write-data/best-practices/optimize-writes
+    5000
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -244,6 +291,9 @@ impl Default for Config {
             token: String::from("FILL ME"),
             org: String::from("FILL ME"),
             bucket: String::from("FILL ME"),
+            api_version: ApiVersion::V2,
+            precision: Precision::Ns,
+            batch_size: default_batch_size(),
             attributes_as: AttributeAs::Field,
             attributes_as_tags: None,
             attributes_as_fields: None,
@@ -6,7 +6,7 @@ mod tests {
 
     use mockito::{Matcher, Mock, Server, ServerGuard};
 
-    use plugin_influxdb::{AttributeAs, Config, InfluxDbPlugin};
+    use plugin_influxdb::{ApiVersion, AttributeAs, Config, InfluxDbPlugin, Precision};
 
     use crate::fakeplugin::TestsPlugin;
 
@@ -70,6 +70,9 @@ mod tests {
             token: String::from(token),
             org: String::from(org),
             bucket: String::from(bucket),
+            api_version: ApiVersion::V2,
+            precision: Precision::Ns,
+            batch_size: 5000,
             attributes_as: AttributeAs::Field,
             attributes_as_tags: None,
             attributes_as_fields: None,
@@ -113,6 +116,95 @@ mod tests {
 
         agent.wait_for_shutdown(Duration::from_secs(2)).unwrap();
     }
+
+    #[test]
+    fn write_batches_v3() {
+        let mut server = Server::new();
+        let token = "sometoken";
+        let db = "somedb";
+
+        let health_mock = server
+            .mock("GET", "/health")
+            .match_header("authorization", format!("Bearer {token}").as_str())
+            .with_status(200)
+            .create();
+        let mock_batch = |server: &mut ServerGuard, value: u64| {
+            server
+                .mock("POST", "/api/v3/write_lp")
+                .match_query(Matcher::AllOf(vec![
+                    Matcher::UrlEncoded("db".into(), db.into()),
+                    Matcher::UrlEncoded("precision".into(), "second".into()),
+                ]))
+                .match_header("authorization", format!("Bearer {token}").as_str())
+                .match_body(
+                    format!(
+                        "dumb,resource_kind=local_machine,resource_consumer_kind=local_machine value={value}u 818254800"
+                    )
+                    .as_str(),
+                )
+                .with_status(204)
+                .expect(1)
+                .create()
+        };
+        let first_batch = mock_batch(&mut server, 10);
+        let second_batch = mock_batch(&mut server, 20);
+
+        let mut plugins = PluginSet::new();
+        let source_config = Config {
+            host: server.url(),
+            token: String::from(token),
+            org: String::new(),
+            bucket: String::from(db),
+            api_version: ApiVersion::V3,
+            precision: Precision::S,
+            batch_size: 1,
+            attributes_as: AttributeAs::Field,
+            attributes_as_tags: None,
+            attributes_as_fields: None,
+        };
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<InfluxDbPlugin>(),
+            enabled: true,
+            config: Some(config_to_toml_table(&source_config)),
+        });
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<TestsPlugin>(),
+            enabled: true,
+            config: None,
+        });
+
+        let make_input = move |ctx: &mut OutputCheckInputContext| -> MeasurementBuffer {
+            let metric = ctx.metrics().by_name("dumb").expect("metric should exist").0;
+            let timestamp = Timestamp::from(UNIX_EPOCH + Duration::from_millis(818254800123));
+            let mut m = MeasurementBuffer::new();
+            for value in [10, 20] {
+                m.push(MeasurementPoint::new_untyped(
+                    timestamp,
+                    metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    WrappedMeasurementValue::U64(value),
+                ));
+            }
+            m
+        };
+        let check_output = move || {
+            health_mock.assert();
+            first_batch.assert();
+            second_batch.assert();
+        };
+
+        let runtime_expectations =
+            RuntimeExpectations::new().test_output(OutputName::from_str("influxdb", "out"), make_input, check_output);
+
+        let agent = agent::Builder::new(plugins)
+            .with_expectations(runtime_expectations)
+            .build_and_start()
+            .unwrap();
+
+        agent.wait_for_shutdown(Duration::from_secs(2)).unwrap();
+    }
+
     fn config_to_toml_table(config: &Config) -> toml::Table {
         toml::Value::try_from(config).unwrap().as_table().unwrap().clone()
     }
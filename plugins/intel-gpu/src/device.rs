@@ -0,0 +1,327 @@
+//! Detection of the Intel GPUs and of the sysfs files of the `i915` and `xe` drivers.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+pub const DRM_PATH: &str = "/sys/class/drm";
+
+/// PCI vendor id of Intel.
+const INTEL_VENDOR_ID: &str = "0x8086";
+
+/// The kernel driver of an Intel GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Driver {
+    I915,
+    Xe,
+}
+
+impl fmt::Display for Driver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Driver::I915 => "i915",
+            Driver::Xe => "xe",
+        })
+    }
+}
+
+/// An Intel GPU and the files that give access to its measurements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntelGpu {
+    /// PCI bus ID of the device, such as `0000:03:00.0`.
+    pub bus_id: String,
+    pub driver: Driver,
+    /// Energy counters in µJ, with their domain: `card` and `pkg` with `xe`, `gpu` with `i915`.
+    pub energy: Vec<(String, PathBuf)>,
+    /// Actual frequencies in MHz, with the name of their GT (graphics technology unit, such as `gt0`).
+    pub frequencies: Vec<(String, PathBuf)>,
+}
+
+impl IntelGpu {
+    pub fn has_any(&self) -> bool {
+        !self.energy.is_empty() || !self.frequencies.is_empty()
+    }
+
+    /// The available features, separated by commas.
+    pub fn features(&self) -> String {
+        let energy = self.energy.iter().map(|(domain, _)| format!("energy({domain})"));
+        let frequencies = self.frequencies.iter().map(|(gt, _)| format!("frequency({gt})"));
+        energy.chain(frequencies).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// Detects the Intel GPUs, sorted by card number.
+///
+/// Returns the name of each card, such as `card1`, and the result of its inspection.
+pub fn detect(drm_dir: &Path) -> anyhow::Result<Vec<(String, anyhow::Result<IntelGpu>)>> {
+    let mut cards = Vec::new();
+    let entries = fs::read_dir(drm_dir).with_context(|| format!("failed to list {drm_dir:?}"))?;
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // skip the connectors (card0-eDP-1) and the render nodes (renderD128)
+        let Some(number) = name.strip_prefix("card").and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        cards.push((number, name, entry.path()));
+    }
+    cards.sort();
+
+    let mut gpus = Vec::new();
+    for (_, name, card) in cards {
+        let vendor = card.join("device/vendor");
+        match fs::read_to_string(&vendor) {
+            Ok(id) if id.trim() == INTEL_VENDOR_ID => {
+                if let Some(gpu) = inspect(&card).transpose() {
+                    gpus.push((name, gpu));
+                }
+            }
+            Ok(_) => (),
+            Err(e) => gpus.push((name, Err(e).with_context(|| format!("failed to read {vendor:?}")))),
+        }
+    }
+    Ok(gpus)
+}
+
+/// Finds the files of an Intel GPU, or returns `None` if its driver is neither `i915` nor `xe`.
+fn inspect(card: &Path) -> anyhow::Result<Option<IntelGpu>> {
+    let device = card.join("device");
+    let uevent = device.join("uevent");
+    let content = fs::read_to_string(&uevent).with_context(|| format!("failed to read {uevent:?}"))?;
+    let field = |name: &str| {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+    };
+    let driver = match field("DRIVER") {
+        Some("i915") => Driver::I915,
+        Some("xe") => Driver::Xe,
+        _ => return Ok(None),
+    };
+    let bus_id = field("PCI_SLOT_NAME")
+        .with_context(|| format!("no PCI_SLOT_NAME in {uevent:?}"))?
+        .to_owned();
+
+    let energy = match find_hwmon(&device, driver)? {
+        Some(hwmon) => find_energy_counters(&hwmon)?,
+        None => Vec::new(),
+    };
+    let frequencies = match driver {
+        Driver::I915 => i915_frequencies(card)?,
+        Driver::Xe => xe_frequencies(&device)?,
+    };
+    Ok(Some(IntelGpu {
+        bus_id,
+        driver,
+        energy,
+        frequencies,
+    }))
+}
+
+/// Lists the subdirectories of `dir` whose names start with `prefix`, sorted by name.
+fn subdirs(dir: &Path, prefix: &str) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to list {dir:?}"))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(prefix) && entry.path().is_dir() {
+            found.push((name, entry.path()));
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Returns the hwmon directory of the driver, which only exists on discrete GPUs.
+fn find_hwmon(device: &Path, driver: Driver) -> anyhow::Result<Option<PathBuf>> {
+    let driver = driver.to_string();
+    let hwmon = subdirs(&device.join("hwmon"), "hwmon")?
+        .into_iter()
+        .map(|(_, path)| path)
+        .find(|path| fs::read_to_string(path.join("name")).is_ok_and(|name| name.trim() == driver));
+    Ok(hwmon)
+}
+
+/// Returns the readable energy counters, sorted by number, with their domains.
+fn find_energy_counters(hwmon: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut counters = Vec::new();
+    for entry in fs::read_dir(hwmon).with_context(|| format!("failed to list {hwmon:?}"))? {
+        let path = entry?.path();
+        let Some(number) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("energy"))
+            .and_then(|n| n.strip_suffix("_input"))
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if !is_readable(&path) {
+            continue;
+        }
+        // i915 exposes the energy of the GPU, without label
+        let domain = fs::read_to_string(hwmon.join(format!("energy{number}_label")))
+            .map(|l| l.trim().to_owned())
+            .unwrap_or_else(|_| String::from("gpu"));
+        counters.push((number, domain, path));
+    }
+    counters.sort();
+    Ok(counters.into_iter().map(|(_, domain, path)| (domain, path)).collect())
+}
+
+/// Returns the frequency files of `i915`: one per GT on recent kernels, or one for the whole GPU.
+fn i915_frequencies(card: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut frequencies: Vec<_> = subdirs(&card.join("gt"), "gt")?
+        .into_iter()
+        .map(|(gt, dir)| (gt, dir.join("rps_act_freq_mhz")))
+        .filter(|(_, path)| is_readable(path))
+        .collect();
+    if frequencies.is_empty() {
+        let path = card.join("gt_act_freq_mhz");
+        if is_readable(&path) {
+            frequencies.push((String::from("gt0"), path));
+        }
+    }
+    Ok(frequencies)
+}
+
+/// Returns the frequency files of `xe`: one per GT of each tile.
+fn xe_frequencies(device: &Path) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut frequencies = Vec::new();
+    for (_, tile) in subdirs(device, "tile")? {
+        for (gt, dir) in subdirs(&tile, "gt")? {
+            let path = dir.join("freq0/act_freq");
+            if is_readable(&path) {
+                frequencies.push((gt, path));
+            }
+        }
+    }
+    Ok(frequencies)
+}
+
+/// Returns true if the file contains an integer.
+fn is_readable(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|s| s.trim().parse::<u64>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::{Driver, IntelGpu, detect};
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn detection() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let drm = tmp.path();
+
+        // an integrated GPU of a laptop: no hwmon, frequency of the whole GPU
+        let card0 = drm.join("card0");
+        write(&card0.join("device/vendor"), "0x8086\n");
+        write(
+            &card0.join("device/uevent"),
+            "DRIVER=i915\nPCI_SLOT_NAME=0000:00:02.0\n",
+        );
+        write(&card0.join("gt_act_freq_mhz"), "350\n");
+        fs::create_dir_all(drm.join("card0-eDP-1"))?;
+
+        // a discrete GPU with xe: two energy counters and two GTs
+        let card1 = drm.join("card1");
+        let hwmon = card1.join("device/hwmon/hwmon5");
+        write(&card1.join("device/vendor"), "0x8086\n");
+        write(&card1.join("device/uevent"), "DRIVER=xe\nPCI_SLOT_NAME=0000:03:00.0\n");
+        write(&card1.join("device/hwmon/hwmon4/name"), "nvme\n");
+        write(&hwmon.join("name"), "xe\n");
+        write(&hwmon.join("energy1_input"), "123456\n");
+        write(&hwmon.join("energy1_label"), "card\n");
+        write(&hwmon.join("energy2_input"), "100000\n");
+        write(&hwmon.join("energy2_label"), "pkg\n");
+        write(&card1.join("device/tile0/gt0/freq0/act_freq"), "2400\n");
+        write(&card1.join("device/tile0/gt1/freq0/act_freq"), "0\n");
+
+        // an AMD GPU, and an Intel device handled by another driver
+        write(&drm.join("card2/device/vendor"), "0x1002\n");
+        write(&drm.join("card3/device/vendor"), "0x8086\n");
+        write(&drm.join("card3/device/uevent"), "DRIVER=simpledrm\n");
+
+        let gpus = detect(drm)?;
+        let gpus: Vec<_> = gpus.into_iter().map(|(name, gpu)| (name, gpu.unwrap())).collect();
+        assert_eq!(
+            gpus,
+            vec![
+                (
+                    String::from("card0"),
+                    IntelGpu {
+                        bus_id: String::from("0000:00:02.0"),
+                        driver: Driver::I915,
+                        energy: vec![],
+                        frequencies: vec![(String::from("gt0"), card0.join("gt_act_freq_mhz"))],
+                    }
+                ),
+                (
+                    String::from("card1"),
+                    IntelGpu {
+                        bus_id: String::from("0000:03:00.0"),
+                        driver: Driver::Xe,
+                        energy: vec![
+                            (String::from("card"), hwmon.join("energy1_input")),
+                            (String::from("pkg"), hwmon.join("energy2_input")),
+                        ],
+                        frequencies: vec![
+                            (String::from("gt0"), card1.join("device/tile0/gt0/freq0/act_freq")),
+                            (String::from("gt1"), card1.join("device/tile0/gt1/freq0/act_freq")),
+                        ],
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            gpus[1].1.features(),
+            "energy(card), energy(pkg), frequency(gt0), frequency(gt1)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn i915_discrete_gpu() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let card = tmp.path().join("card1");
+        write(&card.join("device/vendor"), "0x8086\n");
+        write(&card.join("device/uevent"), "DRIVER=i915\nPCI_SLOT_NAME=0000:03:00.0\n");
+        write(&card.join("device/hwmon/hwmon2/name"), "i915\n");
+        write(&card.join("device/hwmon/hwmon2/energy1_input"), "9000\n");
+        write(&card.join("gt/gt0/rps_act_freq_mhz"), "1800\n");
+        // the legacy file is ignored when the GTs are listed
+        write(&card.join("gt_act_freq_mhz"), "1800\n");
+
+        let gpus = detect(tmp.path())?;
+        let gpu = gpus[0].1.as_ref().unwrap();
+        assert_eq!(gpu.features(), "energy(gpu), frequency(gt0)");
+        assert_eq!(gpu.frequencies[0].1, card.join("gt/gt0/rps_act_freq_mhz"));
+        Ok(())
+    }
+
+    #[test]
+    fn missing_uevent() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        write(&tmp.path().join("card0/device/vendor"), "0x8086\n");
+        let gpus = detect(tmp.path())?;
+        let err = gpus[0].1.as_ref().unwrap_err();
+        assert!(format!("{err:#}").contains("uevent"), "{err:#}");
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, time::Duration};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+mod device;
+mod metrics;
+mod source;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct IntelGpuPlugin {
+    config: Config,
+}
+
+impl IntelGpuPlugin {
+    #[cfg(not(test))]
+    fn drm_path(&self) -> PathBuf {
+        PathBuf::from(device::DRM_PATH)
+    }
+
+    #[cfg(test)]
+    fn drm_path(&self) -> PathBuf {
+        self.config.drm_test_path.clone()
+    }
+}
+
+impl AlumetPlugin for IntelGpuPlugin {
+    fn name() -> &'static str {
+        "intel-gpu"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from(device::DRM_PATH)),
+            // the cards of the DRM class are links to their device, and its hwmon directory
+            Capability::Filesystem(PathBuf::from("/sys/devices")),
+        ])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(IntelGpuPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let gpus = device::detect(&self.drm_path()).context("failed to detect the GPUs")?;
+        if gpus.is_empty() {
+            return Err(anyhow!("No Intel GPU found. Is the i915 or xe driver loaded?"));
+        }
+        let n_found = gpus.len();
+
+        let mut working = Vec::with_capacity(n_found);
+        for (card, gpu) in gpus {
+            match gpu {
+                Ok(gpu) if gpu.has_any() => {
+                    log::info!(
+                        "Found Intel GPU {} ({card}, {}) with features: {}",
+                        gpu.bus_id,
+                        gpu.driver,
+                        gpu.features()
+                    );
+                    working.push(gpu);
+                }
+                Ok(gpu) => {
+                    log::warn!(
+                        "Skipping Intel GPU {} ({card}) because it supports no useful feature.",
+                        gpu.bus_id
+                    );
+                }
+                Err(e) => {
+                    if self.config.skip_failed_devices {
+                        log::warn!("Skipping Intel GPU {card} because of error:\n{e:?}");
+                    } else {
+                        // don't skip, fail immediately
+                        return Err(e.context(format!("failed to inspect Intel GPU {card}")));
+                    }
+                }
+            }
+        }
+        if working.is_empty() {
+            return Err(anyhow!(
+                "{n_found} Intel GPUs found but none of them is working (see previous warnings)."
+            ));
+        }
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        for gpu in working {
+            let source_name = format!("device_{}", gpu.bus_id);
+            let source = source::IntelGpuSource::open(gpu, metrics.clone())?;
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&source_name, Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// On startup, the plugin inspects the GPU devices and detect their features.
+    /// If `skip_failed_devices = true`, inspection failures will be logged and the plugin will continue.
+    /// If `skip_failed_devices = false`, the first failure will make the plugin's startup fail.
+    skip_failed_devices: bool,
+
+    #[cfg(test)]
+    drm_test_path: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1), // 1Hz
+            flush_interval: Duration::from_secs(5),
+            skip_failed_devices: true,
+            #[cfg(test)]
+            drm_test_path: PathBuf::from(device::DRM_PATH),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, time::Duration};
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::{PrefixedUnit, Unit},
+    };
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<IntelGpuPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    #[test]
+    fn plugin_with_sysfs() {
+        let tmp = tempdir().unwrap();
+        let device = tmp.path().join("card1/device");
+        let hwmon = device.join("hwmon/hwmon2");
+        write(&device.join("vendor"), "0x8086\n");
+        write(&device.join("uevent"), "DRIVER=xe\nPCI_SLOT_NAME=0000:03:00.0\n");
+        write(&hwmon.join("name"), "xe\n");
+        write(&hwmon.join("energy1_input"), "1000000\n");
+        write(&hwmon.join("energy1_label"), "card\n");
+        write(&device.join("tile0/gt0/freq0/act_freq"), "2100\n");
+
+        let config = Config {
+            drm_test_path: tmp.path().to_owned(),
+            ..Config::default()
+        };
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<f64>("intel_gpu_energy_consumption", PrefixedUnit::milli(Unit::Joule))
+            .expect_metric::<f64>("intel_gpu_power", PrefixedUnit::milli(Unit::Watt))
+            .expect_metric::<u64>("intel_gpu_frequency", PrefixedUnit::mega(Unit::Hertz))
+            .expect_source("intel-gpu", "device_0000:03:00.0");
+
+        let energy_file = hwmon.join("energy1_input");
+        let runtime = RuntimeExpectations::new()
+            .test_source(
+                SourceName::from_str("intel-gpu", "device_0000:03:00.0"),
+                || (),
+                |ctx| {
+                    // no energy nor power on the first measurement
+                    let m = ctx.measurements();
+                    assert_eq!(m.len(), 1);
+                    let frequency = m.iter().next().unwrap();
+                    assert_eq!(frequency.value, WrappedMeasurementValue::U64(2100));
+                    assert_eq!(frequency.resource.id_display().to_string(), "0000:03:00.0");
+                    assert_eq!(frequency.attributes().next().unwrap().1.to_string(), "gt0");
+                },
+            )
+            .test_source(
+                SourceName::from_str("intel-gpu", "device_0000:03:00.0"),
+                move || write(&energy_file, "3500000\n"),
+                |ctx| {
+                    let energy = ctx
+                        .measurements()
+                        .iter()
+                        .find(|m| m.value == WrappedMeasurementValue::F64(2500.0))
+                        .expect("the energy should be measured");
+                    assert_eq!(energy.attributes().next().unwrap().1.to_string(), "card");
+                    let n_power = ctx
+                        .measurements()
+                        .iter()
+                        .filter(|m| matches!(m.value, WrappedMeasurementValue::F64(p) if p > 0.0 && p != 2500.0))
+                        .count();
+                    assert_eq!(n_power, 1);
+                },
+            );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn no_intel_gpu() {
+        let tmp = tempdir().unwrap();
+        write(&tmp.path().join("card0/device/vendor"), "0x10de\n");
+        let config = Config {
+            drm_test_path: tmp.path().to_owned(),
+            ..Config::default()
+        };
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+
+    #[test]
+    fn failed_device() {
+        let tmp = tempdir().unwrap();
+        write(&tmp.path().join("card0/device/vendor"), "0x8086\n");
+        let card = tmp.path().join("card1");
+        write(&card.join("device/vendor"), "0x8086\n");
+        write(&card.join("device/uevent"), "DRIVER=i915\nPCI_SLOT_NAME=0000:00:02.0\n");
+        write(&card.join("gt_act_freq_mhz"), "300\n");
+
+        // the GPU without uevent is skipped
+        let config = Config {
+            drm_test_path: tmp.path().to_owned(),
+            ..Config::default()
+        };
+        let startup = StartupExpectations::new().expect_source("intel-gpu", "device_0000:00:02.0");
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .build_and_start()
+            .expect("agent should start");
+        agent.pipeline.control_handle().shutdown();
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+
+        // or makes the startup fail
+        let config = Config {
+            skip_failed_devices: false,
+            ..config
+        };
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+}
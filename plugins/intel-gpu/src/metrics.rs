@@ -0,0 +1,41 @@
+use alumet::{
+    metrics::{TypedMetricId, def::tag, error::MetricCreationError},
+    plugin::AlumetPluginStart,
+    units::{PrefixedUnit, Unit},
+};
+
+/// Contains the ids of the measured metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    /// Energy consumed since the previous measurement, in mJ.
+    pub energy_consumption: TypedMetricId<f64>,
+    /// Average power since the previous measurement, in mW.
+    pub power: TypedMetricId<f64>,
+    /// Actual frequency of a GT, in MHz.
+    pub frequency: TypedMetricId<u64>,
+}
+
+impl Metrics {
+    /// Creates new Alumet metrics for Intel GPU measurements and stores their ids in a `Metrics` structure.
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            energy_consumption: alumet.create_metric_with_tags(
+                "intel_gpu_energy_consumption",
+                PrefixedUnit::milli(Unit::Joule),
+                "Energy consumption by the GPU since the previous measurement",
+                &[tag::ENERGY],
+            )?,
+            power: alumet.create_metric_with_tags(
+                "intel_gpu_power",
+                PrefixedUnit::milli(Unit::Watt),
+                "Average power of the GPU since the previous measurement",
+                &[tag::POWER],
+            )?,
+            frequency: alumet.create_metric(
+                "intel_gpu_frequency",
+                PrefixedUnit::mega(Unit::Hertz),
+                "Actual frequency of the GPU",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{Read, Seek},
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::elements::error::PollError,
+    plugin::util::{CounterDiff, CounterDiffUpdate},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{device::IntelGpu, metrics::Metrics};
+
+/// Measurement source that reads the sysfs files of an Intel GPU.
+pub struct IntelGpuSource {
+    metrics: Metrics,
+    resource: Resource,
+    energy: Vec<EnergyCounter>,
+    /// Frequency files, with the name of their GT.
+    frequencies: Vec<(String, File)>,
+    /// Timestamp of the previous measurement, to compute the power.
+    last_timestamp: Option<Timestamp>,
+    buf: Vec<u8>,
+}
+
+/// An energy counter of the hwmon interface, in µJ.
+struct EnergyCounter {
+    domain: String,
+    file: File,
+    diff: CounterDiff,
+}
+
+impl IntelGpuSource {
+    pub fn open(gpu: IntelGpu, metrics: Metrics) -> anyhow::Result<IntelGpuSource> {
+        let mut energy = Vec::with_capacity(gpu.energy.len());
+        for (domain, path) in gpu.energy {
+            let file = File::open(&path).with_context(|| format!("failed to open {path:?}"))?;
+            energy.push(EnergyCounter {
+                domain,
+                file,
+                diff: CounterDiff::with_max_value(u64::MAX),
+            });
+        }
+        let mut frequencies = Vec::with_capacity(gpu.frequencies.len());
+        for (gt, path) in gpu.frequencies {
+            let file = File::open(&path).with_context(|| format!("failed to open {path:?}"))?;
+            frequencies.push((gt, file));
+        }
+        Ok(IntelGpuSource {
+            metrics,
+            resource: Resource::Gpu {
+                bus_id: Cow::Owned(gpu.bus_id),
+            },
+            energy,
+            frequencies,
+            last_timestamp: None,
+            buf: Vec::with_capacity(32),
+        })
+    }
+}
+
+/// Reads a file that contains an integer.
+fn read_integer(file: &mut File, buf: &mut Vec<u8>) -> anyhow::Result<u64> {
+    buf.clear();
+    file.rewind().with_context(|| format!("failed to rewind {file:?}"))?;
+    file.read_to_end(buf)
+        .with_context(|| format!("failed to read {file:?}"))?;
+    let content = std::str::from_utf8(buf)?;
+    content
+        .trim_end()
+        .parse()
+        .with_context(|| format!("failed to parse {file:?}: '{content}'"))
+}
+
+impl alumet::pipeline::Source for IntelGpuSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        // no consumer, we just monitor the device here
+        let consumer = ResourceConsumer::LocalMachine;
+
+        // The power is the energy consumed since the previous measurement, divided by the elapsed time.
+        let elapsed = self
+            .last_timestamp
+            .and_then(|last| timestamp.duration_since(last).ok())
+            .map(|d| d.as_secs_f64())
+            .filter(|secs| *secs > 0.0);
+        self.last_timestamp = Some(timestamp);
+
+        for counter in &mut self.energy {
+            let value = read_integer(&mut counter.file, &mut self.buf)?;
+            let micro_joules = match counter.diff.update(value) {
+                CounterDiffUpdate::FirstTime => continue,
+                CounterDiffUpdate::Difference(diff) => diff,
+                CounterDiffUpdate::CorrectedDifference(diff) => {
+                    log::debug!(
+                        "Overflow on the {} energy counter of {:?}",
+                        counter.domain,
+                        self.resource
+                    );
+                    diff
+                }
+            };
+            let milli_joules = micro_joules as f64 / 1000.0;
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.energy_consumption,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    milli_joules,
+                )
+                .with_attr("domain", counter.domain.clone()),
+            );
+            if let Some(secs) = elapsed {
+                measurements.push(
+                    MeasurementPoint::new(
+                        timestamp,
+                        self.metrics.power,
+                        self.resource.clone(),
+                        consumer.clone(),
+                        milli_joules / secs,
+                    )
+                    .with_attr("domain", counter.domain.clone()),
+                );
+            }
+        }
+
+        for (gt, file) in &mut self.frequencies {
+            let mega_hertz = read_integer(file, &mut self.buf)?;
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.frequency,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    mega_hertz,
+                )
+                .with_attr("gt", gt.clone()),
+            );
+        }
+        Ok(())
+    }
+}
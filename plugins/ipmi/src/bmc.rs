@@ -0,0 +1,313 @@
+//! Access to the Baseboard Management Controller (BMC).
+//!
+//! The measurements are obtained with raw IPMI requests, sent either through the local device
+//! of the `ipmi_devintf` kernel module, or through `ipmitool` for a remote BMC.
+
+use std::{
+    ffi::{c_int, c_long, c_short, c_uchar, c_uint, c_ushort},
+    fmt,
+    fs::{File, OpenOptions},
+    os::fd::{AsFd, AsRawFd},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, anyhow};
+use nix::poll::{PollFd, PollFlags, PollTimeout};
+
+pub const DEVICE_PATH: &str = "/dev/ipmi0";
+
+/// Network function of the sensor requests.
+pub const NETFN_SENSOR: u8 = 0x04;
+/// Network function of the storage requests, which give access to the Sensor Data Records.
+pub const NETFN_STORAGE: u8 = 0x0a;
+/// Network function of the DCMI requests (group extension).
+pub const NETFN_DCMI: u8 = 0x2c;
+
+/// A BMC that answers raw IPMI requests.
+pub trait Bmc: Send {
+    /// Sends a request and returns the data of the response, without its completion code.
+    ///
+    /// If the BMC does not complete the request, the error contains a [`CompletionCode`].
+    fn request(&mut self, netfn: u8, cmd: u8, data: &[u8]) -> anyhow::Result<Vec<u8>>;
+}
+
+/// Error returned by the BMC when a request does not complete normally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompletionCode(pub u8);
+
+impl CompletionCode {
+    /// The reservation of the SDR repository has been cancelled.
+    pub const RESERVATION_CANCELLED: CompletionCode = CompletionCode(0xc5);
+    /// The requested record or sensor is not present.
+    pub const NOT_PRESENT: CompletionCode = CompletionCode(0xcb);
+
+    /// Returns true if `err` has been caused by this completion code.
+    pub fn is(self, err: &anyhow::Error) -> bool {
+        err.downcast_ref::<CompletionCode>() == Some(&self)
+    }
+}
+
+impl fmt::Display for CompletionCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self.0 {
+            0xc0 => "node busy",
+            0xc1 => "invalid command",
+            0xc3 => "timeout",
+            0xc5 => "reservation cancelled",
+            0xc9 => "parameter out of range",
+            0xca => "cannot return the number of requested bytes",
+            0xcb => "requested data not present",
+            0xcc => "invalid data field",
+            0xd4 => "insufficient privilege level",
+            0xd5 => "command not supported in present state",
+            0xd6 => "command disabled",
+            _ => "unspecified error",
+        };
+        write!(
+            f,
+            "the BMC returned the completion code {:#04x} ({description})",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for CompletionCode {}
+
+// The structures of `linux/ipmi.h`, used with the ioctls of the IPMI device.
+
+const IPMI_IOC_MAGIC: u8 = b'i';
+const IPMI_SYSTEM_INTERFACE_ADDR_TYPE: c_int = 0x0c;
+const IPMI_BMC_CHANNEL: c_short = 0xf;
+const IPMI_RESPONSE_RECV_TYPE: c_int = 1;
+const IPMI_MAX_ADDR_SIZE: usize = 32;
+const IPMI_MAX_MSG_LENGTH: usize = 272;
+
+#[repr(C)]
+struct IpmiSystemInterfaceAddr {
+    addr_type: c_int,
+    channel: c_short,
+    lun: c_uchar,
+}
+
+#[repr(C)]
+struct IpmiAddr {
+    addr_type: c_int,
+    channel: c_short,
+    data: [c_uchar; IPMI_MAX_ADDR_SIZE],
+}
+
+#[repr(C)]
+struct IpmiMsg {
+    netfn: c_uchar,
+    cmd: c_uchar,
+    data_len: c_ushort,
+    data: *mut c_uchar,
+}
+
+#[repr(C)]
+struct IpmiReq {
+    addr: *mut c_uchar,
+    addr_len: c_uint,
+    msgid: c_long,
+    msg: IpmiMsg,
+}
+
+#[repr(C)]
+struct IpmiRecv {
+    recv_type: c_int,
+    addr: *mut c_uchar,
+    addr_len: c_uint,
+    msgid: c_long,
+    msg: IpmiMsg,
+}
+
+nix::ioctl_readwrite!(ipmi_receive_msg_trunc, IPMI_IOC_MAGIC, 11, IpmiRecv);
+nix::ioctl_read!(ipmi_send_command, IPMI_IOC_MAGIC, 13, IpmiReq);
+
+/// The local BMC, reached through the IPMI device of the kernel.
+pub struct DeviceBmc {
+    path: PathBuf,
+    file: File,
+    msgid: c_long,
+}
+
+impl DeviceBmc {
+    /// How long to wait for a response of the BMC.
+    const TIMEOUT_MS: u16 = 5000;
+
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("failed to open {path:?}, is the ipmi_devintf module loaded?"))?;
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            msgid: 0,
+        })
+    }
+
+    fn send(&mut self, netfn: u8, cmd: u8, data: &[u8]) -> anyhow::Result<()> {
+        let mut addr = IpmiSystemInterfaceAddr {
+            addr_type: IPMI_SYSTEM_INTERFACE_ADDR_TYPE,
+            channel: IPMI_BMC_CHANNEL,
+            lun: 0,
+        };
+        let mut data = data.to_vec();
+        let mut req = IpmiReq {
+            addr: (&mut addr as *mut IpmiSystemInterfaceAddr).cast(),
+            addr_len: size_of::<IpmiSystemInterfaceAddr>() as c_uint,
+            msgid: self.msgid,
+            msg: IpmiMsg {
+                netfn,
+                cmd,
+                data_len: data.len() as c_ushort,
+                data: data.as_mut_ptr(),
+            },
+        };
+        // SAFETY: the pointers of `req` are valid until the end of the call, which copies the data
+        unsafe { ipmi_send_command(self.file.as_raw_fd(), &mut req) }
+            .with_context(|| format!("failed to send a request to {:?}", self.path))?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> anyhow::Result<Vec<u8>> {
+        loop {
+            let mut fds = [PollFd::new(self.file.as_fd(), PollFlags::POLLIN)];
+            let ready = nix::poll::poll(&mut fds, PollTimeout::from(Self::TIMEOUT_MS))
+                .with_context(|| format!("failed to wait for {:?}", self.path))?;
+            if ready == 0 {
+                return Err(anyhow!("no response from the BMC after {}ms", Self::TIMEOUT_MS));
+            }
+
+            let mut addr = IpmiAddr {
+                addr_type: 0,
+                channel: 0,
+                data: [0; IPMI_MAX_ADDR_SIZE],
+            };
+            let mut data = vec![0u8; IPMI_MAX_MSG_LENGTH];
+            let mut recv = IpmiRecv {
+                recv_type: 0,
+                addr: (&mut addr as *mut IpmiAddr).cast(),
+                addr_len: size_of::<IpmiAddr>() as c_uint,
+                msgid: 0,
+                msg: IpmiMsg {
+                    netfn: 0,
+                    cmd: 0,
+                    data_len: data.len() as c_ushort,
+                    data: data.as_mut_ptr(),
+                },
+            };
+            // SAFETY: the pointers of `recv` are valid and the buffers are large enough for any message
+            unsafe { ipmi_receive_msg_trunc(self.file.as_raw_fd(), &mut recv) }
+                .with_context(|| format!("failed to receive a response from {:?}", self.path))?;
+
+            // ignore the events and the responses to the requests that timed out
+            if recv.recv_type == IPMI_RESPONSE_RECV_TYPE && recv.msgid == self.msgid {
+                data.truncate(recv.msg.data_len as usize);
+                return Ok(data);
+            }
+        }
+    }
+}
+
+impl Bmc for DeviceBmc {
+    fn request(&mut self, netfn: u8, cmd: u8, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.msgid = self.msgid.wrapping_add(1);
+        self.send(netfn, cmd, data)?;
+        let response = self.receive()?;
+        match response.split_first() {
+            Some((0, data)) => Ok(data.to_vec()),
+            Some((code, _)) => Err(CompletionCode(*code).into()),
+            None => Err(anyhow!("empty response from the BMC")),
+        }
+    }
+}
+
+/// A remote BMC, reached with `ipmitool -I lanplus`.
+pub struct LanplusBmc {
+    ipmitool: PathBuf,
+    host: String,
+    user: String,
+    password: String,
+}
+
+impl LanplusBmc {
+    pub fn new(ipmitool: PathBuf, host: String, user: String, password: String) -> Self {
+        Self {
+            ipmitool,
+            host,
+            user,
+            password,
+        }
+    }
+}
+
+impl Bmc for LanplusBmc {
+    fn request(&mut self, netfn: u8, cmd: u8, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let output = Command::new(&self.ipmitool)
+            .args(["-I", "lanplus", "-H", &self.host, "-U", &self.user, "-E", "raw"])
+            .args([netfn, cmd].iter().chain(data).map(|b| format!("{b:#04x}")))
+            // -E reads the password from the environment, which is not visible in the list of processes
+            .env("IPMI_PASSWORD", &self.password)
+            .output()
+            .with_context(|| format!("failed to execute {}", self.ipmitool.display()))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if output.status.success() {
+            parse_raw_output(&stdout)
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match parse_completion_code(&stderr) {
+                Some(code) => Err(code.into()),
+                None => Err(anyhow!("ipmitool failed ({}): {}", output.status, stderr.trim())),
+            }
+        }
+    }
+}
+
+/// Parses the response of `ipmitool raw`, which prints the data in hexadecimal, such as ` dc 64 00`.
+fn parse_raw_output(stdout: &str) -> anyhow::Result<Vec<u8>> {
+    stdout
+        .split_whitespace()
+        .map(|byte| u8::from_str_radix(byte, 16).with_context(|| format!("invalid output of ipmitool: {stdout:?}")))
+        .collect()
+}
+
+/// Finds the completion code in the error message of `ipmitool raw`,
+/// such as `Unable to send RAW command (channel=0x0 netfn=0x2c lun=0x0 cmd=0x2 rsp=0xc1): Invalid command`.
+fn parse_completion_code(stderr: &str) -> Option<CompletionCode> {
+    let code = stderr.split("rsp=0x").nth(1)?.get(..2)?;
+    u8::from_str_radix(code, 16).ok().map(CompletionCode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompletionCode, parse_completion_code, parse_raw_output};
+
+    #[test]
+    fn ipmitool_output() {
+        let stdout = " dc 64 00 0a 00\n 2c 01 5a 00\n";
+        assert_eq!(
+            parse_raw_output(stdout).unwrap(),
+            vec![0xdc, 0x64, 0x00, 0x0a, 0x00, 0x2c, 0x01, 0x5a, 0x00]
+        );
+        assert_eq!(parse_raw_output("").unwrap(), Vec::<u8>::new());
+        assert!(parse_raw_output("Error: no response").is_err());
+    }
+
+    #[test]
+    fn ipmitool_completion_code() {
+        let stderr = "Unable to send RAW command (channel=0x0 netfn=0x2c lun=0x0 cmd=0x2 rsp=0xc1): Invalid command\n";
+        assert_eq!(parse_completion_code(stderr), Some(CompletionCode(0xc1)));
+        assert_eq!(
+            parse_completion_code("Error: Unable to establish IPMI v2 / RMCP+ session"),
+            None
+        );
+
+        let err = anyhow::Error::from(CompletionCode(0xcb)).context("failed to read the SDR");
+        assert!(CompletionCode::NOT_PRESENT.is(&err));
+        assert!(!CompletionCode::RESERVATION_CANCELLED.is(&err));
+    }
+}
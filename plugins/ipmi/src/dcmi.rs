@@ -0,0 +1,72 @@
+//! Power readings of the Data Center Manageability Interface (DCMI).
+
+use anyhow::anyhow;
+
+use crate::bmc::{Bmc, NETFN_DCMI};
+
+/// Identifies the DCMI group extension in the requests and responses.
+const DCMI_GROUP_ID: u8 = 0xdc;
+const CMD_GET_POWER_READING: u8 = 0x02;
+/// Mode of the power reading: power statistics of the whole system.
+const SYSTEM_POWER_STATISTICS: u8 = 0x01;
+
+/// The power of the node, in Watts, measured by the BMC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerReading {
+    /// Current power.
+    pub current: u16,
+    /// Average power over the statistics period of the BMC.
+    pub average: u16,
+    /// False if the BMC is not measuring the power, in which case the readings are meaningless.
+    pub active: bool,
+}
+
+/// Gets the power of the node.
+pub fn power_reading(bmc: &mut dyn Bmc) -> anyhow::Result<PowerReading> {
+    let data = bmc.request(
+        NETFN_DCMI,
+        CMD_GET_POWER_READING,
+        &[DCMI_GROUP_ID, SYSTEM_POWER_STATISTICS, 0x00, 0x00],
+    )?;
+    parse_power_reading(&data)
+}
+
+/// Parses the response to "Get Power Reading": group id, current, minimum, maximum and average power,
+/// timestamp, statistics period and power measurement state.
+fn parse_power_reading(data: &[u8]) -> anyhow::Result<PowerReading> {
+    if data.len() < 18 || data[0] != DCMI_GROUP_ID {
+        return Err(anyhow!("invalid DCMI power reading: {data:02x?}"));
+    }
+    let word = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    Ok(PowerReading {
+        current: word(1),
+        average: word(7),
+        active: data[17] & 0x40 != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PowerReading, parse_power_reading};
+
+    #[test]
+    fn power_reading() {
+        let data = [
+            0xdc, 0x2c, 0x01, 0x5a, 0x00, 0xf4, 0x01, 0x18, 0x01, 0x10, 0x2f, 0x6a, 0x65, 0xe8, 0x03, 0x00, 0x00, 0x40,
+        ];
+        assert_eq!(
+            parse_power_reading(&data).unwrap(),
+            PowerReading {
+                current: 300,
+                average: 280,
+                active: true,
+            }
+        );
+
+        let mut inactive = data;
+        inactive[17] = 0;
+        assert!(!parse_power_reading(&inactive).unwrap().active);
+
+        assert!(parse_power_reading(&data[..10]).is_err());
+    }
+}
@@ -0,0 +1,484 @@
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, path::PathBuf, time::Duration};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    resources::Resource,
+};
+
+use bmc::{Bmc, DeviceBmc, LanplusBmc};
+use sdr::SensorKind;
+
+mod bmc;
+mod dcmi;
+mod metrics;
+mod sdr;
+mod source;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct IpmiPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for IpmiPlugin {
+    fn name() -> &'static str {
+        "ipmi"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from(bmc::DEVICE_PATH)),
+            // `ipmitool -I lanplus`, which queries a remote BMC
+            Capability::ProcessSpawn,
+            Capability::Network,
+        ])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(IpmiPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let (mut bmc, resource) = self.open_bmc()?;
+
+        let dcmi = match dcmi::power_reading(bmc.as_mut()) {
+            Ok(_) => true,
+            Err(e) => {
+                log::warn!("The BMC does not provide DCMI power readings: {e:#}");
+                false
+            }
+        };
+
+        let sensors = sdr::sensors(bmc.as_mut());
+        let psu_sensors = match &sensors {
+            Ok(sensors) => self.select_psu_sensors(sensors),
+            Err(e) => {
+                log::warn!("Could not list the power sensors of the BMC: {e:#}");
+                Vec::new()
+            }
+        };
+
+        if !dcmi && psu_sensors.is_empty() {
+            let msg = "The BMC provides no DCMI power reading and no PSU input power sensor (see previous warnings).";
+            if self.config.sensors.is_none() {
+                return Err(anyhow!(msg));
+            }
+            log::warn!("{msg}");
+        } else {
+            let names: Vec<_> = psu_sensors.iter().map(|s| s.name.as_str()).collect();
+            log::info!("Measuring the node with DCMI: {dcmi}, PSU input power sensors: {names:?}");
+
+            let metrics = metrics::Metrics::new(alumet)?;
+            let source = source::IpmiSource::new(bmc, metrics, resource.clone(), dcmi, psu_sensors);
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source("bmc", Box::new(source), trigger)?;
+        }
+
+        if let Some(inventory) = &self.config.sensors {
+            let sensors: Vec<_> = sensors
+                .context("failed to list the sensors of the BMC")?
+                .into_iter()
+                .filter(|s| inventory.kinds.contains(&s.kind))
+                .collect();
+            if sensors.is_empty() {
+                return Err(anyhow!(
+                    "The SDR repository of the BMC contains no sensor of the kinds {:?}.",
+                    inventory.kinds
+                ));
+            }
+            log::info!("Measuring {} sensors of the BMC.", sensors.len());
+
+            // The sources are polled in parallel, each one has its own connection to the BMC.
+            let (bmc, _) = self.open_bmc()?;
+            let metrics = metrics::SensorMetrics::new(alumet)?;
+            let source = source::SensorSource::new(bmc, metrics, resource, sensors);
+            let trigger = TriggerSpec::builder(inventory.poll_interval)
+                .flush_interval(inventory.flush_interval)
+                .build()?;
+            alumet.add_source("sensors", Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl IpmiPlugin {
+    /// Connects to the BMC, and returns it with the resource that it measures.
+    fn open_bmc(&self) -> anyhow::Result<(Box<dyn Bmc>, Resource)> {
+        match &self.config.lanplus {
+            Some(remote) => {
+                let bmc = LanplusBmc::new(
+                    remote.ipmitool.clone(),
+                    remote.host.clone(),
+                    remote.user.clone(),
+                    remote.password.clone(),
+                );
+                let resource = Resource::Custom {
+                    kind: Cow::Borrowed("ipmi_host"),
+                    id: Cow::Owned(remote.host.clone()),
+                };
+                Ok((Box::new(bmc), resource))
+            }
+            None => Ok((Box::new(DeviceBmc::open(&self.config.device)?), Resource::LocalMachine)),
+        }
+    }
+
+    /// Keeps the power sensors that measure the input power of the PSUs.
+    fn select_psu_sensors(&self, sensors: &[sdr::Sensor]) -> Vec<sdr::Sensor> {
+        let power_sensors = sensors.iter().filter(|s| s.kind == SensorKind::Power);
+        let wanted = &self.config.psu_sensors;
+        if wanted.is_empty() {
+            return power_sensors.filter(|s| sdr::is_psu_input(&s.name)).cloned().collect();
+        }
+        for name in wanted {
+            if !power_sensors.clone().any(|s| &s.name == name) {
+                log::warn!("Power sensor {name:?} not found in the SDR repository of the BMC.");
+            }
+        }
+        power_sensors.filter(|s| wanted.contains(&s.name)).cloned().collect()
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    ///
+    /// Most BMCs update their readings every second or less often.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// IPMI device of the local BMC, used when `lanplus` is not set.
+    device: PathBuf,
+
+    /// Names of the sensors that measure the input power of the power supply units (PSU).
+    /// If empty, the power sensors whose names look like `PS1 Input Power` or `PSU1_PIN` are used.
+    psu_sensors: Vec<String>,
+
+    /// Remote BMC to query with `ipmitool -I lanplus`, instead of the local device.
+    lanplus: Option<Lanplus>,
+
+    /// Inventory of the sensors of the BMC (temperatures, voltages, fans, etc.), disabled if not set.
+    sensors: Option<Sensors>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Lanplus {
+    /// Address of the BMC.
+    host: String,
+    user: String,
+    password: String,
+    /// Path to the `ipmitool` executable.
+    #[serde(default = "default_ipmitool")]
+    ipmitool: PathBuf,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Sensors {
+    /// Initial interval between two readings of the sensors.
+    #[serde(with = "humantime_serde", default = "default_sensors_poll_interval")]
+    poll_interval: Duration,
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde", default = "default_sensors_flush_interval")]
+    flush_interval: Duration,
+    /// Kinds of the sensors to read.
+    #[serde(default = "default_sensor_kinds")]
+    kinds: Vec<SensorKind>,
+}
+
+fn default_ipmitool() -> PathBuf {
+    PathBuf::from("ipmitool")
+}
+
+fn default_sensors_poll_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+fn default_sensors_flush_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_sensor_kinds() -> Vec<SensorKind> {
+    SensorKind::ALL.to_vec()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            flush_interval: Duration::from_secs(15),
+            device: PathBuf::from(bmc::DEVICE_PATH),
+            psu_sensors: Vec::new(),
+            lanplus: None,
+            sensors: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path, time::Duration};
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::Unit,
+    };
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A fake ipmitool whose BMC supports DCMI but has an empty SDR repository.
+    const FAKE_IPMITOOL: &str = r#"#!/bin/sh
+if [ "$IPMI_PASSWORD" != "secret" ]; then
+    echo "Error: Unable to establish IPMI v2 / RMCP+ session" >&2
+    exit 1
+fi
+case "$*" in
+    *"raw 0x2c 0x02 0xdc 0x01 0x00 0x00")
+        echo " dc 2c 01 5a 00 f4 01 18 01 10 2f 6a 65 e8 03 00"
+        echo " 00 40"
+        ;;
+    *)
+        echo "Unable to send RAW command (channel=0x0 netfn=0xa lun=0x0 cmd=0x23 rsp=0xcb): Requested data not present" >&2
+        exit 1
+        ;;
+esac
+"#;
+
+    fn fake_ipmitool(dir: &Path) -> PathBuf {
+        let path = dir.join("ipmitool");
+        fs::write(&path, FAKE_IPMITOOL).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// A fake ipmitool whose BMC supports DCMI and has an SDR repository with the given records.
+    fn fake_ipmitool_with_sdr(dir: &Path, records: &[Vec<u8>], readings: &[(u8, &str)]) -> PathBuf {
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ");
+        let mut script = String::from("#!/bin/sh\n");
+        for (i, record) in records.iter().enumerate() {
+            script.push_str(&format!("RECORD_{i}=\"{}\"\n", hex(record)));
+        }
+        for (number, reading) in readings {
+            script.push_str(&format!("READING_{number}=\"{reading}\"\n"));
+        }
+        script.push_str(&format!("COUNT={}\n", records.len()));
+        script.push_str(
+            r#"shift 8 # -I lanplus -H <host> -U <user> -E raw
+case "$1 $2" in
+    "0x2c 0x02")
+        echo " dc 2c 01 5a 00 f4 01 18 01 10 2f 6a 65 e8 03 00 00 40"
+        ;;
+    "0x0a 0x22")
+        echo " 01 00"
+        ;;
+    "0x0a 0x23")
+        id=$(($5))
+        eval record=\$RECORD_$id
+        if [ $((id + 1)) -lt $COUNT ]; then next="0$((id + 1)) 00"; else next="ff ff"; fi
+        echo " $next $(echo $record | cut -d' ' -f$(($7 + 1))-$(($7 + $8)))"
+        ;;
+    "0x04 0x2d")
+        eval echo \$READING_$(($3))
+        ;;
+esac
+"#,
+        );
+        let path = dir.join("ipmitool");
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    /// Builds a sensor record: a full record for the analog sensors, a compact one for the others.
+    fn sensor_record(name: &str, number: u8, sensor_type: u8, unit: Option<u8>) -> Vec<u8> {
+        let name_offset = if unit.is_some() { 47 } else { 31 };
+        let mut record = vec![0u8; name_offset + 1];
+        record[2] = 0x51; // SDR version
+        record[3] = if unit.is_some() { 0x01 } else { 0x02 };
+        record[4] = (name_offset - 4 + name.len()) as u8;
+        record[5] = 0x20;
+        record[7] = number;
+        record[12] = sensor_type;
+        match unit {
+            Some(unit) => {
+                record[13] = 0x01; // threshold sensor
+                record[21] = unit;
+                record[24] = 1; // M
+            }
+            None => record[13] = 0x6f,
+        }
+        record[name_offset] = 0xc0 | name.len() as u8;
+        record.extend(name.as_bytes());
+        record
+    }
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<IpmiPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    fn lanplus_config(ipmitool: PathBuf, password: &str) -> Config {
+        Config {
+            poll_interval: Duration::from_millis(100),
+            lanplus: Some(Lanplus {
+                host: String::from("node-1-bmc"),
+                user: String::from("admin"),
+                password: String::from(password),
+                ipmitool,
+            }),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn dcmi_with_lanplus() {
+        let tmp = tempdir().unwrap();
+        let config = lanplus_config(fake_ipmitool(tmp.path()), "secret");
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<u64>("ipmi_dcmi_power", Unit::Watt)
+            .expect_metric::<u64>("ipmi_dcmi_average_power", Unit::Watt)
+            .expect_metric::<f64>("ipmi_psu_input_power", Unit::Watt)
+            .expect_source("ipmi", "bmc");
+
+        let runtime = RuntimeExpectations::new().test_source(
+            SourceName::from_str("ipmi", "bmc"),
+            || (),
+            |ctx| {
+                let m = ctx.measurements();
+                let values: Vec<_> = m.iter().map(|m| m.value.clone()).collect();
+                assert_eq!(
+                    values,
+                    vec![WrappedMeasurementValue::U64(300), WrappedMeasurementValue::U64(280)]
+                );
+                let resource = &m.iter().next().unwrap().resource;
+                assert_eq!(resource.kind(), "ipmi_host");
+                assert_eq!(resource.id_display().to_string(), "node-1-bmc");
+            },
+        );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn sensor_inventory() {
+        let tmp = tempdir().unwrap();
+        let records = [
+            sensor_record("Inlet Temp", 0x01, 0x01, Some(1)),
+            sensor_record("FAN1", 0x02, 0x04, Some(18)),
+            sensor_record("PS1 Status", 0x05, 0x08, None),
+        ];
+        let readings = [(1, " 1b c0"), (2, " 00 e0"), (5, " 00 c0 01 80")];
+        let mut config = lanplus_config(fake_ipmitool_with_sdr(tmp.path(), &records, &readings), "secret");
+        config.sensors = Some(Sensors {
+            poll_interval: Duration::from_millis(100),
+            flush_interval: Duration::from_millis(100),
+            kinds: default_sensor_kinds(),
+        });
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<f64>("ipmi_sensor_temperature", Unit::DegreeCelsius)
+            .expect_metric::<u64>("ipmi_psu_status", Unit::Unity)
+            .expect_source("ipmi", "bmc")
+            .expect_source("ipmi", "sensors");
+
+        let runtime = RuntimeExpectations::new().test_source(
+            SourceName::from_str("ipmi", "sensors"),
+            || (),
+            |ctx| {
+                let m = ctx.measurements();
+                let values: Vec<_> = m
+                    .iter()
+                    .map(|p| {
+                        let sensor = p.attributes().find(|(k, _)| *k == "sensor").unwrap().1.to_string();
+                        (sensor, p.value.clone())
+                    })
+                    .collect();
+                // the fan is not available
+                assert_eq!(
+                    values,
+                    vec![
+                        (String::from("Inlet Temp"), WrappedMeasurementValue::F64(27.0)),
+                        (String::from("PS1 Status"), WrappedMeasurementValue::U64(1)),
+                    ]
+                );
+            },
+        );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn unreachable_bmc() {
+        let tmp = tempdir().unwrap();
+        let config = lanplus_config(fake_ipmitool(tmp.path()), "wrong");
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+
+    #[test]
+    fn missing_device() {
+        let tmp = tempdir().unwrap();
+        let config = Config {
+            device: tmp.path().join("ipmi0"),
+            ..Config::default()
+        };
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+}
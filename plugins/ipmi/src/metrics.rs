@@ -0,0 +1,108 @@
+use alumet::{
+    metrics::{TypedMetricId, def::tag, error::MetricCreationError},
+    plugin::AlumetPluginStart,
+    units::Unit,
+};
+
+use crate::sdr::SensorKind;
+
+/// Contains the ids of the measured metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    /// Current power of the node, in W.
+    pub dcmi_power: TypedMetricId<u64>,
+    /// Average power of the node over the statistics period of the BMC, in W.
+    pub dcmi_average_power: TypedMetricId<u64>,
+    /// Input power of a power supply unit, in W.
+    pub psu_input_power: TypedMetricId<f64>,
+}
+
+impl Metrics {
+    /// Creates new Alumet metrics for IPMI measurements and stores their ids in a `Metrics` structure.
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            dcmi_power: alumet.create_metric_with_tags(
+                "ipmi_dcmi_power",
+                Unit::Watt,
+                "Power of the whole node, measured by the BMC",
+                &[tag::POWER],
+            )?,
+            dcmi_average_power: alumet.create_metric_with_tags(
+                "ipmi_dcmi_average_power",
+                Unit::Watt,
+                "Average power of the whole node over the statistics period of the BMC",
+                &[tag::POWER],
+            )?,
+            psu_input_power: alumet.create_metric_with_tags(
+                "ipmi_psu_input_power",
+                Unit::Watt,
+                "Input power of a power supply unit",
+                &[tag::POWER],
+            )?,
+        })
+    }
+}
+
+/// Contains the ids of the metrics of the sensor inventory.
+#[derive(Clone)]
+pub struct SensorMetrics {
+    /// Temperature, in °C.
+    pub temperature: TypedMetricId<f64>,
+    /// Voltage, in V.
+    pub voltage: TypedMetricId<f64>,
+    /// Current, in A.
+    pub current: TypedMetricId<f64>,
+    /// Power, in W.
+    pub power: TypedMetricId<f64>,
+    /// Speed of a fan, in RPM.
+    pub fan_speed: TypedMetricId<f64>,
+    /// States of a power supply unit, one bit per state.
+    pub psu_status: TypedMetricId<u64>,
+}
+
+impl SensorMetrics {
+    /// Creates the metrics of the sensor inventory.
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        let rpm = Unit::Custom {
+            unique_name: String::from("{rpm}"),
+            display_name: String::from("RPM"),
+        };
+        Ok(Self {
+            temperature: alumet.create_metric(
+                "ipmi_sensor_temperature",
+                Unit::DegreeCelsius,
+                "Temperature measured by a sensor of the BMC",
+            )?,
+            voltage: alumet.create_metric("ipmi_sensor_voltage", Unit::Volt, "Voltage measured by a sensor of the BMC")?,
+            current: alumet.create_metric(
+                "ipmi_sensor_current",
+                Unit::Ampere,
+                "Current measured by a sensor of the BMC",
+            )?,
+            power: alumet.create_metric_with_tags(
+                "ipmi_sensor_power",
+                Unit::Watt,
+                "Power measured by a sensor of the BMC",
+                &[tag::POWER],
+            )?,
+            fan_speed: alumet.create_metric("ipmi_sensor_fan_speed", rpm, "Rotation speed of a fan")?,
+            psu_status: alumet.create_metric(
+                "ipmi_psu_status",
+                Unit::Unity,
+                "States of a power supply unit, as a bit field (bit 0: presence detected, bit 1: failure detected, etc.)",
+            )?,
+        })
+    }
+
+    /// Returns the metric of an analog sensor, or `None` for the discrete sensors.
+    pub fn analog(&self, kind: SensorKind) -> Option<TypedMetricId<f64>> {
+        match kind {
+            SensorKind::Temperature => Some(self.temperature),
+            SensorKind::Voltage => Some(self.voltage),
+            SensorKind::Current => Some(self.current),
+            SensorKind::Power => Some(self.power),
+            SensorKind::FanSpeed => Some(self.fan_speed),
+            SensorKind::PsuStatus => None,
+        }
+    }
+}
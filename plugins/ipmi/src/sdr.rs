@@ -0,0 +1,535 @@
+//! Discovery and reading of the sensors, described by the Sensor Data Records (SDR) of the BMC.
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::bmc::{Bmc, CompletionCode, NETFN_SENSOR, NETFN_STORAGE};
+
+const CMD_RESERVE_SDR_REPOSITORY: u8 = 0x22;
+const CMD_GET_SDR: u8 = 0x23;
+const CMD_GET_SENSOR_READING: u8 = 0x2d;
+
+/// Id of the last record of the repository.
+const LAST_RECORD_ID: u16 = 0xffff;
+/// Length of the header of a record.
+const HEADER_LENGTH: u8 = 5;
+/// Maximum number of bytes to read at once: some BMCs do not support larger reads.
+const MAX_READ_LENGTH: u8 = 16;
+
+/// Record type of a full sensor record, the only one that describes the conversion of the readings.
+const FULL_SENSOR_RECORD: u8 = 0x01;
+/// Record type of a compact sensor record, which describes discrete sensors.
+const COMPACT_SENSOR_RECORD: u8 = 0x02;
+/// Slave address of the BMC, which owns the sensors that can be read without bridging.
+const BMC_SLAVE_ADDRESS: u8 = 0x20;
+/// Sensor type of the power supplies.
+const SENSOR_TYPE_POWER_SUPPLY: u8 = 0x08;
+/// Event/reading type of the sensors whose states are specific to their sensor type.
+const READING_TYPE_SENSOR_SPECIFIC: u8 = 0x6f;
+
+// Unit codes of the analog readings.
+const UNIT_DEGREES_C: u8 = 1;
+const UNIT_VOLTS: u8 = 4;
+const UNIT_AMPS: u8 = 5;
+const UNIT_WATTS: u8 = 6;
+const UNIT_RPM: u8 = 18;
+
+/// What a sensor measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorKind {
+    /// Temperature, in °C.
+    Temperature,
+    /// Voltage, in V.
+    Voltage,
+    /// Current, in A.
+    Current,
+    /// Power, in W.
+    Power,
+    /// Speed of a fan, in RPM.
+    FanSpeed,
+    /// States of a power supply unit, such as "presence detected" or "failure detected".
+    PsuStatus,
+}
+
+impl SensorKind {
+    pub const ALL: [SensorKind; 6] = [
+        SensorKind::Temperature,
+        SensorKind::Voltage,
+        SensorKind::Current,
+        SensorKind::Power,
+        SensorKind::FanSpeed,
+        SensorKind::PsuStatus,
+    ];
+}
+
+/// A sensor of the BMC.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sensor {
+    pub name: String,
+    pub kind: SensorKind,
+    number: u8,
+    /// Conversion of the raw readings, for the analog sensors.
+    conversion: Option<Conversion>,
+}
+
+/// A reading of a sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reading {
+    /// Value of an analog sensor, converted to the unit of its kind.
+    Value(f64),
+    /// States of a discrete sensor, one bit per state.
+    States(u16),
+}
+
+/// Parameters of the linear conversion `y = (M * x + B * 10^Bexp) * 10^Rexp`
+/// of the raw readings to the unit of the sensor.
+#[derive(Debug, Clone, PartialEq)]
+struct Conversion {
+    format: AnalogFormat,
+    m: i16,
+    b: i16,
+    b_exp: i8,
+    r_exp: i8,
+}
+
+/// Numeric format of the raw readings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnalogFormat {
+    Unsigned,
+    OnesComplement,
+    TwosComplement,
+}
+
+impl Conversion {
+    fn apply(&self, raw: u8) -> f64 {
+        let x = match self.format {
+            AnalogFormat::Unsigned => f64::from(raw),
+            AnalogFormat::OnesComplement if raw & 0x80 != 0 => -f64::from(!raw),
+            AnalogFormat::OnesComplement => f64::from(raw),
+            AnalogFormat::TwosComplement => f64::from(raw as i8),
+        };
+        let m = f64::from(self.m);
+        let b = f64::from(self.b) * 10f64.powi(self.b_exp.into());
+        (m * x + b) * 10f64.powi(self.r_exp.into())
+    }
+}
+
+impl Sensor {
+    /// Reads the sensor.
+    ///
+    /// Returns `None` if the reading is unavailable, for instance because the power supply is not plugged in.
+    pub fn read(&self, bmc: &mut dyn Bmc) -> anyhow::Result<Option<Reading>> {
+        let data = bmc
+            .request(NETFN_SENSOR, CMD_GET_SENSOR_READING, &[self.number])
+            .with_context(|| format!("failed to read sensor {}", self.name))?;
+        let [raw, flags, ..] = data[..] else {
+            return Err(anyhow!("invalid reading of sensor {}: {data:02x?}", self.name));
+        };
+        let scanning_enabled = flags & 0x40 != 0;
+        let unavailable = flags & 0x20 != 0;
+        if !scanning_enabled || unavailable {
+            return Ok(None);
+        }
+        let reading = match &self.conversion {
+            Some(conversion) => Reading::Value(conversion.apply(raw)),
+            None => {
+                // the states 0 to 7, then 8 to 14 (optional)
+                let low = data.get(2).copied().unwrap_or(0);
+                let high = data.get(3).copied().unwrap_or(0) & 0x7f;
+                Reading::States(u16::from_le_bytes([low, high]))
+            }
+        };
+        Ok(Some(reading))
+    }
+}
+
+/// Lists the sensors of the BMC that can be measured, in the order of the repository.
+pub fn sensors(bmc: &mut dyn Bmc) -> anyhow::Result<Vec<Sensor>> {
+    Ok(read_records(bmc)?
+        .iter()
+        .filter_map(|record| parse_sensor(record))
+        .collect())
+}
+
+/// Reads all the records of the SDR repository.
+fn read_records(bmc: &mut dyn Bmc) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut reservation = reserve(bmc)?;
+    let mut records = Vec::new();
+    let mut id = 0;
+    loop {
+        let mut attempts = 0;
+        let (next, record) = loop {
+            match read_record(bmc, reservation, id) {
+                Ok(res) => break res,
+                // the reservation is cancelled when the BMC updates the repository, which is rare
+                Err(e) if CompletionCode::RESERVATION_CANCELLED.is(&e) && attempts < 3 => {
+                    attempts += 1;
+                    reservation = reserve(bmc)?;
+                }
+                // the repository is empty
+                Err(e) if CompletionCode::NOT_PRESENT.is(&e) && id == 0 => return Ok(records),
+                Err(e) => return Err(e),
+            }
+        };
+        records.push(record);
+        if next == LAST_RECORD_ID || next == id {
+            return Ok(records);
+        }
+        id = next;
+    }
+}
+
+fn reserve(bmc: &mut dyn Bmc) -> anyhow::Result<[u8; 2]> {
+    let data = bmc
+        .request(NETFN_STORAGE, CMD_RESERVE_SDR_REPOSITORY, &[])
+        .context("failed to reserve the SDR repository")?;
+    match data[..] {
+        [lsb, msb, ..] => Ok([lsb, msb]),
+        _ => Err(anyhow!("invalid SDR reservation: {data:02x?}")),
+    }
+}
+
+/// Reads a record, and returns it with the id of the next one.
+fn read_record(bmc: &mut dyn Bmc, reservation: [u8; 2], id: u16) -> anyhow::Result<(u16, Vec<u8>)> {
+    let (next, mut record) = get_sdr(bmc, reservation, id, 0, HEADER_LENGTH)?;
+    let length = usize::from(HEADER_LENGTH) + usize::from(record[4]);
+    while record.len() < length {
+        let remaining = (length - record.len()).min(usize::from(MAX_READ_LENGTH)) as u8;
+        let (_, chunk) = get_sdr(bmc, reservation, id, record.len() as u8, remaining)?;
+        if chunk.is_empty() {
+            return Err(anyhow!("truncated SDR record {id:#06x}"));
+        }
+        record.extend(chunk);
+    }
+    Ok((next, record))
+}
+
+fn get_sdr(bmc: &mut dyn Bmc, reservation: [u8; 2], id: u16, offset: u8, len: u8) -> anyhow::Result<(u16, Vec<u8>)> {
+    let [id_lsb, id_msb] = id.to_le_bytes();
+    let data = bmc
+        .request(
+            NETFN_STORAGE,
+            CMD_GET_SDR,
+            &[reservation[0], reservation[1], id_lsb, id_msb, offset, len],
+        )
+        .with_context(|| format!("failed to read SDR record {id:#06x}"))?;
+    if data.len() < 2 + usize::from(len.min(HEADER_LENGTH)) {
+        return Err(anyhow!("invalid SDR record {id:#06x}: {data:02x?}"));
+    }
+    let next = u16::from_le_bytes([data[0], data[1]]);
+    Ok((next, data[2..].to_vec()))
+}
+
+/// Parses a sensor record, if it describes a sensor of the BMC whose kind is known.
+///
+/// The analog sensors must have linear readings, and the discrete sensors must be power supplies.
+fn parse_sensor(record: &[u8]) -> Option<Sensor> {
+    // offset of the length of the name of the sensor, which is the last field
+    let name_offset = match record.get(3) {
+        Some(&FULL_SENSOR_RECORD) => 47,
+        Some(&COMPACT_SENSOR_RECORD) => 31,
+        _ => return None,
+    };
+    if record.len() <= name_offset {
+        return None;
+    }
+    let (owner, lun, number) = (record[5], record[6] & 0x03, record[7]);
+    if owner != BMC_SLAVE_ADDRESS || lun != 0 {
+        return None;
+    }
+    let (sensor_type, reading_type) = (record[12], record[13]);
+    let (kind, conversion) = if sensor_type == SENSOR_TYPE_POWER_SUPPLY && reading_type == READING_TYPE_SENSOR_SPECIFIC
+    {
+        (SensorKind::PsuStatus, None)
+    } else if record[3] == FULL_SENSOR_RECORD {
+        let (kind, conversion) = parse_analog(record)?;
+        (kind, Some(conversion))
+    } else {
+        return None;
+    };
+    let name_length = usize::from(record[name_offset] & 0x1f);
+    let name = &record[name_offset + 1..];
+    let name = name.get(..name_length).unwrap_or(name);
+    let name = String::from_utf8_lossy(name).trim_end_matches('\0').trim().to_owned();
+    Some(Sensor {
+        name,
+        kind,
+        number,
+        conversion,
+    })
+}
+
+/// Parses the unit and the conversion of the readings of a full sensor record.
+fn parse_analog(record: &[u8]) -> Option<(SensorKind, Conversion)> {
+    let format = match record[20] >> 6 {
+        0 => AnalogFormat::Unsigned,
+        1 => AnalogFormat::OnesComplement,
+        2 => AnalogFormat::TwosComplement,
+        _ => return None, // no numeric reading
+    };
+    let percentage = record[20] & 0x01 != 0;
+    let linear = record[23] & 0x7f == 0;
+    if percentage || !linear {
+        return None;
+    }
+    let kind = match record[21] {
+        UNIT_DEGREES_C => SensorKind::Temperature,
+        UNIT_VOLTS => SensorKind::Voltage,
+        UNIT_AMPS => SensorKind::Current,
+        UNIT_WATTS => SensorKind::Power,
+        UNIT_RPM => SensorKind::FanSpeed,
+        _ => return None,
+    };
+    let conversion = Conversion {
+        format,
+        m: signed10(record[24], record[25]),
+        b: signed10(record[26], record[27]),
+        r_exp: signed4(record[29] >> 4),
+        b_exp: signed4(record[29]),
+    };
+    Some((kind, conversion))
+}
+
+/// Decodes a 10-bits two's complement number, made of 8 low bits and of the 2 high bits of `high`.
+fn signed10(low: u8, high: u8) -> i16 {
+    let value = u16::from(low) | (u16::from(high >> 6) << 8);
+    ((value << 6) as i16) >> 6
+}
+
+/// Decodes a 4-bits two's complement number.
+fn signed4(value: u8) -> i8 {
+    ((value << 4) as i8) >> 4
+}
+
+/// Returns true if the name of the sensor looks like the input power of a power supply unit,
+/// such as `PS1 Input Power` or `PSU2_PIN`.
+pub fn is_psu_input(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.starts_with("ps") && (name.contains("input") || name.contains("pin"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::anyhow;
+    use pretty_assertions::assert_eq;
+
+    use super::{AnalogFormat, Conversion, Reading, SensorKind, is_psu_input, sensors, signed4, signed10};
+    use crate::bmc::{Bmc, CompletionCode};
+
+    /// A BMC with an SDR repository, sensor readings and an optional DCMI power reading.
+    #[derive(Default)]
+    struct FakeBmc {
+        records: Vec<Vec<u8>>,
+        readings: HashMap<u8, Vec<u8>>,
+        dcmi: Option<Vec<u8>>,
+        /// Number of Get SDR requests before the cancellation of the reservation.
+        cancel_after: Option<usize>,
+        reservation: u8,
+    }
+
+    impl Bmc for FakeBmc {
+        fn request(&mut self, netfn: u8, cmd: u8, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+            match (netfn, cmd) {
+                (0x0a, 0x22) => {
+                    self.reservation += 1;
+                    Ok(vec![self.reservation, 0])
+                }
+                (0x0a, 0x23) => {
+                    if let Some(n) = &mut self.cancel_after {
+                        if *n == 0 {
+                            self.cancel_after = None;
+                            self.reservation += 1;
+                        } else {
+                            *n -= 1;
+                        }
+                    }
+                    let offset = usize::from(data[4]);
+                    if offset != 0 && data[0] != self.reservation {
+                        return Err(CompletionCode::RESERVATION_CANCELLED.into());
+                    }
+                    let id = usize::from(u16::from_le_bytes([data[2], data[3]]));
+                    let record = self.records.get(id).ok_or(CompletionCode::NOT_PRESENT)?;
+                    let next = if id + 1 == self.records.len() {
+                        0xffff
+                    } else {
+                        id as u16 + 1
+                    };
+                    let end = (offset + usize::from(data[5])).min(record.len());
+                    let mut response = next.to_le_bytes().to_vec();
+                    response.extend(&record[offset..end]);
+                    Ok(response)
+                }
+                (0x04, 0x2d) => self
+                    .readings
+                    .get(&data[0])
+                    .cloned()
+                    .ok_or_else(|| CompletionCode::NOT_PRESENT.into()),
+                (0x2c, 0x02) => self.dcmi.clone().ok_or_else(|| CompletionCode(0xc1).into()),
+                _ => Err(anyhow!("unexpected request {netfn:#04x} {cmd:#04x}")),
+            }
+        }
+    }
+
+    /// Builds a full sensor record.
+    fn full_sensor_record(name: &str, number: u8, unit: u8, m: u8, r_exp: i8) -> Vec<u8> {
+        let mut record = vec![0u8; 48];
+        record[2] = 0x51; // SDR version
+        record[3] = 0x01;
+        record[4] = (43 + name.len()) as u8;
+        record[5] = 0x20;
+        record[7] = number;
+        record[21] = unit;
+        record[24] = m;
+        record[29] = (r_exp as u8) << 4;
+        record[47] = 0xc0 | name.len() as u8;
+        record.extend(name.as_bytes());
+        record
+    }
+
+    /// Builds a compact sensor record of a power supply.
+    fn psu_status_record(name: &str, number: u8) -> Vec<u8> {
+        let mut record = vec![0u8; 32];
+        record[2] = 0x51; // SDR version
+        record[3] = 0x02;
+        record[4] = (27 + name.len()) as u8;
+        record[5] = 0x20;
+        record[7] = number;
+        record[12] = 0x08;
+        record[13] = 0x6f;
+        record[31] = 0xc0 | name.len() as u8;
+        record.extend(name.as_bytes());
+        record
+    }
+
+    #[test]
+    fn discovery() {
+        let mut bmc = FakeBmc {
+            records: vec![
+                full_sensor_record("PS1 Input Power", 0x60, 6, 10, 0),
+                // a temperature
+                full_sensor_record("Inlet Temp", 0x01, 1, 1, 0),
+                // a compact sensor record
+                vec![0, 0, 0x51, 0x02, 3, 0, 0, 0],
+                full_sensor_record("Pwr Consumption", 0x77, 6, 14, 0),
+            ],
+            cancel_after: Some(5),
+            ..Default::default()
+        };
+        let sensors = sensors(&mut bmc).unwrap();
+        let names: Vec<_> = sensors.iter().map(|s| (s.name.as_str(), s.kind)).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("PS1 Input Power", SensorKind::Power),
+                ("Inlet Temp", SensorKind::Temperature),
+                ("Pwr Consumption", SensorKind::Power)
+            ]
+        );
+
+        bmc.readings.insert(0x60, vec![12, 0xc0]);
+        bmc.readings.insert(0x77, vec![25, 0xe0]);
+        assert_eq!(sensors[0].read(&mut bmc).unwrap(), Some(Reading::Value(120.0)));
+        // reading unavailable
+        assert_eq!(sensors[2].read(&mut bmc).unwrap(), None);
+    }
+
+    #[test]
+    fn empty_repository() {
+        let mut bmc = FakeBmc::default();
+        assert_eq!(sensors(&mut bmc).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn inventory() {
+        let mut bmc = FakeBmc {
+            records: vec![
+                full_sensor_record("CPU1 Temp", 0x01, 1, 1, 0),
+                full_sensor_record("12V", 0x02, 4, 6, -2),
+                full_sensor_record("FAN1", 0x03, 18, 12, 1),
+                // unit unknown to the plugin: CFM
+                full_sensor_record("Airflow", 0x04, 17, 1, 0),
+                psu_status_record("PS2 Status", 0x05),
+                // a discrete sensor that is not a power supply
+                vec![
+                    0, 0, 0x51, 0x02, 27, 0x20, 0, 0x06, 0, 0, 0, 0, 0x07, 0x6f, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0xc0,
+                ],
+            ],
+            ..Default::default()
+        };
+        let sensors = sensors(&mut bmc).unwrap();
+        let kinds: Vec<_> = sensors.iter().map(|s| (s.name.as_str(), s.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("CPU1 Temp", SensorKind::Temperature),
+                ("12V", SensorKind::Voltage),
+                ("FAN1", SensorKind::FanSpeed),
+                ("PS2 Status", SensorKind::PsuStatus),
+            ]
+        );
+
+        bmc.readings.insert(0x01, vec![42, 0xc0]);
+        bmc.readings.insert(0x02, vec![200, 0xc0]);
+        bmc.readings.insert(0x03, vec![75, 0xc0]);
+        let values: Vec<_> = sensors[..3].iter().map(|s| s.read(&mut bmc).unwrap()).collect();
+        assert_eq!(
+            values,
+            vec![
+                Some(Reading::Value(42.0)),
+                Some(Reading::Value(12.0)),
+                Some(Reading::Value(9000.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn psu_states() {
+        let mut bmc = FakeBmc {
+            records: vec![psu_status_record("PS1 Status", 0x05)],
+            ..Default::default()
+        };
+        let sensors = sensors(&mut bmc).unwrap();
+        let psu = &sensors[0];
+        assert_eq!(psu.kind, SensorKind::PsuStatus);
+
+        // presence detected and failure detected
+        bmc.readings.insert(0x05, vec![0, 0xc0, 0x03, 0x80]);
+        assert_eq!(psu.read(&mut bmc).unwrap(), Some(Reading::States(0x03)));
+        // the second byte of states is optional
+        bmc.readings.insert(0x05, vec![0, 0xc0, 0x01]);
+        assert_eq!(psu.read(&mut bmc).unwrap(), Some(Reading::States(0x01)));
+        // the power supply is not plugged in
+        bmc.readings.insert(0x05, vec![0, 0xe0, 0x00]);
+        assert_eq!(psu.read(&mut bmc).unwrap(), None);
+    }
+
+    #[test]
+    fn conversion() {
+        let conversion = Conversion {
+            format: AnalogFormat::TwosComplement,
+            m: 2,
+            b: -5,
+            b_exp: 1,
+            r_exp: -1,
+        };
+        assert_eq!(conversion.apply(100), 15.0);
+        assert_eq!(conversion.apply(0xfe), -5.4);
+
+        assert_eq!(signed10(0xff, 0xc0), -1);
+        assert_eq!(signed10(0x02, 0x40), 258);
+        assert_eq!(signed4(0x0f), -1);
+        assert_eq!(signed4(0x07), 7);
+    }
+
+    #[test]
+    fn psu_names() {
+        assert!(is_psu_input("PS1 Input Power"));
+        assert!(is_psu_input("PSU2_PIN"));
+        assert!(!is_psu_input("PSU2_POUT"));
+        assert!(!is_psu_input("Pwr Consumption"));
+    }
+}
@@ -0,0 +1,137 @@
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::elements::error::PollError,
+    resources::{Resource, ResourceConsumer},
+};
+
+use crate::{
+    bmc::{Bmc, CompletionCode},
+    dcmi,
+    metrics::{Metrics, SensorMetrics},
+    sdr::{Reading, Sensor},
+};
+
+/// Measurement source that queries the BMC of a node.
+pub struct IpmiSource {
+    bmc: Box<dyn Bmc>,
+    metrics: Metrics,
+    /// The node measured by the BMC.
+    resource: Resource,
+    /// True if the BMC supports the DCMI power readings.
+    dcmi: bool,
+    /// Input power sensors of the power supply units.
+    psu_sensors: Vec<Sensor>,
+}
+
+impl IpmiSource {
+    pub fn new(bmc: Box<dyn Bmc>, metrics: Metrics, resource: Resource, dcmi: bool, psu_sensors: Vec<Sensor>) -> Self {
+        Self {
+            bmc,
+            metrics,
+            resource,
+            dcmi,
+            psu_sensors,
+        }
+    }
+}
+
+impl alumet::pipeline::Source for IpmiSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        // no consumer, we just monitor the node here
+        let consumer = ResourceConsumer::LocalMachine;
+
+        // The BMC can be busy or unreachable for a while, the next poll may work.
+        if self.dcmi {
+            let reading = dcmi::power_reading(self.bmc.as_mut()).map_err(PollError::CanRetry)?;
+            if reading.active {
+                measurements.push(MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.dcmi_power,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    u64::from(reading.current),
+                ));
+                measurements.push(MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.dcmi_average_power,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    u64::from(reading.average),
+                ));
+            } else {
+                log::debug!("The BMC is not measuring the power, skipping the DCMI power reading.");
+            }
+        }
+
+        for sensor in &self.psu_sensors {
+            let Some(Reading::Value(watts)) = sensor.read(self.bmc.as_mut()).map_err(PollError::CanRetry)? else {
+                continue;
+            };
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.psu_input_power,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    watts,
+                )
+                .with_attr("sensor", sensor.name.clone()),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Measurement source that reads all the sensors of the inventory, for the health of the hardware.
+pub struct SensorSource {
+    bmc: Box<dyn Bmc>,
+    metrics: SensorMetrics,
+    resource: Resource,
+    sensors: Vec<Sensor>,
+}
+
+impl SensorSource {
+    pub fn new(bmc: Box<dyn Bmc>, metrics: SensorMetrics, resource: Resource, sensors: Vec<Sensor>) -> Self {
+        Self {
+            bmc,
+            metrics,
+            resource,
+            sensors,
+        }
+    }
+}
+
+impl alumet::pipeline::Source for SensorSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        let consumer = ResourceConsumer::LocalMachine;
+
+        for sensor in &self.sensors {
+            let reading = match sensor.read(self.bmc.as_mut()) {
+                Ok(Some(reading)) => reading,
+                Ok(None) => continue,
+                // The BMC refuses to read this sensor, but the other ones may work.
+                Err(e) if e.downcast_ref::<CompletionCode>().is_some() => {
+                    log::debug!("Skipping sensor {}: {e:#}", sensor.name);
+                    continue;
+                }
+                // The BMC is busy or unreachable, the next poll may work.
+                Err(e) => return Err(PollError::CanRetry(e)),
+            };
+            let point = match (reading, self.metrics.analog(sensor.kind)) {
+                (Reading::Value(value), Some(metric)) => {
+                    MeasurementPoint::new(timestamp, metric, self.resource.clone(), consumer.clone(), value)
+                }
+                (Reading::States(states), None) => MeasurementPoint::new(
+                    timestamp,
+                    self.metrics.psu_status,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    u64::from(states),
+                ),
+                _ => unreachable!("the analog sensors have values and the discrete sensors have states"),
+            };
+            measurements.push(point.with_attr("sensor", sensor.name.clone()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+mod output;
+mod record;
+
+use std::path::PathBuf;
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+pub use alumet_output_files::{
+    encryption::EncryptionConfig,
+    rotation::{Compression, RotationConfig},
+};
+use anyhow::anyhow;
+use output::JsonLinesOutput;
+use serde::{Deserialize, Serialize};
+
+/// Value of `output_path` that writes to the standard output.
+pub const STDOUT_PATH: &str = "-";
+
+pub struct JsonLinesPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for JsonLinesPlugin {
+    fn name() -> &'static str {
+        "jsonl"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(config.output_path)]))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.output_path.as_os_str() == STDOUT_PATH && config.rotation.is_enabled() {
+            return Err(anyhow!(
+                "the standard output cannot be rotated, remove the rotation settings"
+            ));
+        }
+        config.encryption.parse_recipients()?;
+        if config.output_path.as_os_str() == STDOUT_PATH && config.encryption.is_enabled() {
+            return Err(anyhow!(
+                "the standard output cannot be encrypted, remove the encryption settings"
+            ));
+        }
+        if config.encryption.is_enabled() && config.rotation.compression != Compression::None {
+            return Err(anyhow!(
+                "encrypted files cannot be compressed, remove the compression of the rotated files"
+            ));
+        }
+        if config.buffer_size == 0 {
+            return Err(anyhow!("buffer_size must be greater than zero"));
+        }
+        Ok(Box::new(JsonLinesPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let output = Box::new(JsonLinesOutput::new(
+            &self.config.output_path,
+            self.config.buffer_size,
+            self.config.force_flush,
+            self.config.rotation.clone(),
+            self.config.encryption.parse_recipients()?,
+        )?);
+        alumet.add_blocking_output("out", output)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Absolute or relative path to the output file, or `-` for the standard output.
+    pub output_path: PathBuf,
+    /// Do we flush after each write (measurements)?
+    pub force_flush: bool,
+    /// Size of the write buffer, in bytes.
+    pub buffer_size: usize,
+    /// Rotation of the output file, disabled by default.
+    #[serde(default)]
+    pub rotation: RotationConfig,
+    /// Encryption of the output files, disabled by default.
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_path: PathBuf::from("alumet-output.jsonl"),
+            force_flush: true,
+            buffer_size: 64 * 1024,
+            rotation: RotationConfig::default(),
+            encryption: EncryptionConfig::default(),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    io::{self, BufWriter, Stdout, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use alumet_output_files::{
+    encryption::{FileWriter, Recipient},
+    rotation::{RotationConfig, Rotator},
+};
+use anyhow::Context;
+
+use crate::{STDOUT_PATH, record::write_record};
+
+/// Writes one JSON object per line.
+pub struct JsonLinesOutput {
+    sink: Sink,
+    /// parameter: do we flush after each write(measurements)?
+    force_flush: bool,
+    buffer_size: usize,
+    rotator: Rotator,
+    /// Recipients of the encrypted files, empty if the files are not encrypted.
+    recipients: Vec<Recipient>,
+    /// Buffer reused for each line.
+    line: Vec<u8>,
+}
+
+enum Sink {
+    Stdout(BufWriter<Stdout>),
+    File {
+        writer: BufWriter<FileWriter>,
+        path: PathBuf,
+        opened_at: SystemTime,
+        /// Number of bytes written to the file, including the ones that are still buffered.
+        size: u64,
+    },
+}
+
+impl JsonLinesOutput {
+    pub fn new(
+        output_path: impl AsRef<Path>,
+        buffer_size: usize,
+        force_flush: bool,
+        rotation: RotationConfig,
+        recipients: Vec<Recipient>,
+    ) -> anyhow::Result<Self> {
+        let output_path = output_path.as_ref();
+        let sink = if output_path.as_os_str() == STDOUT_PATH {
+            Sink::Stdout(BufWriter::with_capacity(buffer_size, io::stdout()))
+        } else {
+            open_file(output_path.to_owned(), buffer_size, &recipients)?
+        };
+        let rotator = Rotator::new(rotation, output_path.to_owned());
+        Ok(Self {
+            sink,
+            force_flush,
+            buffer_size,
+            rotator,
+            recipients,
+            line: Vec::with_capacity(256),
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.sink {
+            Sink::Stdout(w) => w,
+            Sink::File { writer, .. } => writer,
+        }
+    }
+
+    /// Moves the current file aside and starts a new one.
+    fn rotate_if_needed(&mut self) -> anyhow::Result<()> {
+        let Sink::File {
+            writer,
+            path,
+            opened_at,
+            size,
+        } = &mut self.sink
+        else {
+            return Ok(());
+        };
+        if !self.rotator.config().must_rotate(*size, *opened_at) {
+            return Ok(());
+        }
+        writer.flush()?;
+        // An encrypted file must be complete before it is rotated.
+        writer.get_mut().finish()?;
+        // On Unix, the open file can be renamed. It is closed when the sink is replaced.
+        self.rotator.rotate()?;
+        self.sink = open_file(path.clone(), self.buffer_size, &self.recipients)?;
+        Ok(())
+    }
+}
+
+fn open_file(path: PathBuf, buffer_size: usize, recipients: &[Recipient]) -> anyhow::Result<Sink> {
+    let file = FileWriter::create(&path, recipients).with_context(|| format!("failed to create {path:?}"))?;
+    Ok(Sink::File {
+        writer: BufWriter::with_capacity(buffer_size, file),
+        path,
+        opened_at: SystemTime::now(),
+        size: 0,
+    })
+}
+
+impl alumet::pipeline::Output for JsonLinesOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut written = 0;
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            let mut line = std::mem::take(&mut self.line);
+            line.clear();
+            write_record(&mut line, m, metric);
+            self.writer().write_all(&line)?;
+            written += line.len() as u64;
+            self.line = line;
+        }
+        if self.force_flush {
+            log::trace!("flushing BufWriter");
+            self.writer().flush()?;
+        }
+        if let Sink::File { size, .. } = &mut self.sink {
+            *size += written;
+        }
+        self.rotate_if_needed()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+//! Conversion of a measurement point to a JSON object.
+
+use std::{collections::BTreeMap, time::SystemTime};
+
+use alumet::{
+    json,
+    measurement::{MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+/// One line of the output.
+///
+/// The fields are serialized in this order, and the attributes are sorted by key,
+/// whatever the features of `serde_json`.
+#[derive(Serialize)]
+struct Record<'a> {
+    metric: &'a str,
+    timestamp: String,
+    value: Value,
+    unit: String,
+    resource_kind: &'a str,
+    resource_id: String,
+    consumer_kind: &'a str,
+    consumer_id: String,
+    attributes: BTreeMap<&'a str, Value>,
+}
+
+/// Writes the JSON object of `m` to `buf`, followed by a newline.
+pub fn write_record(buf: &mut Vec<u8>, m: &MeasurementPoint, metric: &Metric) {
+    let record = Record {
+        metric: &metric.name,
+        timestamp: humantime::format_rfc3339_nanos(SystemTime::from(m.timestamp)).to_string(),
+        value: match m.value {
+            WrappedMeasurementValue::F64(v) => json!(v),
+            WrappedMeasurementValue::U64(v) => json!(v),
+        },
+        unit: metric.unit.unique_name(),
+        resource_kind: m.resource.kind(),
+        resource_id: m.resource.id_display().to_string(),
+        consumer_kind: m.consumer.kind(),
+        consumer_id: m.consumer.id_display().to_string(),
+        attributes: json::attributes(m),
+    };
+    serde_json::to_writer(&mut *buf, &record).expect("a record should always be serializable");
+    buf.push(b'\n');
+}
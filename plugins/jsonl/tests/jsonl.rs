@@ -0,0 +1,141 @@
+use std::{fs, io::Read, iter};
+
+use age::x25519::Identity;
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_jsonl::{Config, EncryptionConfig, JsonLinesPlugin, RotationConfig};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn jsonl_format() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.jsonl");
+    let config = Config {
+        output_path: output_path.clone(),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<JsonLinesPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+
+    let content = fs::read_to_string(&output_path)?;
+    for line in content.lines() {
+        serde_json::from_str::<serde_json::Value>(line)?;
+    }
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.jsonl"),
+        content,
+    );
+    harness.stop()
+}
+
+#[test]
+fn rotate_and_delete() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.jsonl");
+    let config = Config {
+        output_path: output_path.clone(),
+        force_flush: false,
+        rotation: RotationConfig {
+            // rotate after each write
+            max_size: Some(1),
+            max_files: Some(2),
+            ..Default::default()
+        },
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<JsonLinesPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    for _ in 0..3 {
+        output.write(&sample.measurements()).unwrap();
+    }
+    output.finish()?;
+    harness.stop()?;
+
+    let mut files: Vec<String> = fs::read_dir(tmp.path())?
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    // the current file is empty, the oldest rotated file has been deleted
+    assert_eq!(files.len(), 3, "unexpected files {files:?}");
+    assert_eq!(files[2], "alumet-output.jsonl");
+    assert_eq!(fs::read_to_string(&output_path)?, "");
+
+    let expected = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.jsonl"))?;
+    for f in &files[..2] {
+        assert!(
+            f.starts_with("alumet-output-") && f.ends_with(".jsonl"),
+            "unexpected file {f}"
+        );
+        assert_eq!(fs::read_to_string(tmp.path().join(f))?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn stdout_cannot_be_rotated() -> anyhow::Result<()> {
+    let config = Config {
+        output_path: plugin_jsonl::STDOUT_PATH.into(),
+        rotation: RotationConfig {
+            max_size: Some(1024),
+            ..Default::default()
+        },
+        ..Config::default()
+    };
+    assert!(PluginHarness::<JsonLinesPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
+
+#[test]
+fn encrypted_file() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let output_path = tmp.path().join("alumet-output.jsonl.age");
+    let identity = Identity::generate();
+    let config = Config {
+        output_path: output_path.clone(),
+        encryption: EncryptionConfig {
+            recipients: vec![identity.to_public().to_string()],
+        },
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<JsonLinesPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let encrypted = fs::read(&output_path)?;
+    let decryptor = age::Decryptor::new(&encrypted[..])?;
+    let mut content = String::new();
+    decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))?
+        .read_to_string(&mut content)?;
+    let expected = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.jsonl"))?;
+    assert_eq!(content, expected);
+    Ok(())
+}
+
+#[test]
+fn stdout_cannot_be_encrypted() -> anyhow::Result<()> {
+    let config = Config {
+        output_path: plugin_jsonl::STDOUT_PATH.into(),
+        encryption: EncryptionConfig {
+            recipients: vec![Identity::generate().to_public().to_string()],
+        },
+        ..Config::default()
+    };
+    assert!(PluginHarness::<JsonLinesPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
@@ -1,6 +1,7 @@
 // This file contains the main implementation of the Kwollect input plugin for Alumet.
 
 use alumet::{
+    measurement::AttributeValue,
     metrics::TypedMetricId,
     pipeline::{
         control::{matching::SourceMatcher, request},
@@ -9,6 +10,7 @@ use alumet::{
     },
     plugin::{
         AlumetPluginStart, AlumetPostStart, ConfigTable,
+        capability::Capability,
         event::{self},
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
@@ -16,6 +18,7 @@ use alumet::{
 };
 use anyhow::Context;
 use chrono::{DateTime, FixedOffset, Utc};
+use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::time::{Duration, SystemTime};
@@ -30,9 +33,14 @@ mod source;
 
 use crate::source::KwollectSource;
 
+/// How long the plugin waits for its HTTP connections to be closed when Alumet stops.
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
 /// Structure for Kwollect implementation
 pub struct KwollectPluginInput {
     config: Arc<Mutex<ParsedConfig>>,
+    /// HTTP client shared by the sources, taken by the shutdown hook to close its connections.
+    client: Arc<Mutex<Option<Client>>>,
 }
 
 /// Implementation of input Kwollect plugin as an Alumet plugin
@@ -45,6 +53,24 @@ impl AlumetPlugin for KwollectPluginInput {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn config_comments() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("site", "Grid'5000 site of the node, ex. `lyon`."),
+            ("hostname", "Hostname of the node, ex. `taurus-7`."),
+            ("metrics", "Kwollect metrics to collect, ex. `wattmetre_power_watt`."),
+            ("login", "Your Grid'5000 username."),
+            ("password", "Your Grid'5000 password."),
+            (
+                "utc_offset",
+                "Offset of the timezone of the site, in hours (2 in summer and 1 in winter in France).",
+            ),
+        ]
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
@@ -62,6 +88,7 @@ impl AlumetPlugin for KwollectPluginInput {
         };
         Ok(Box::new(KwollectPluginInput {
             config: Arc::new(Mutex::new(parsed_config)),
+            client: Arc::new(Mutex::new(None)),
         }))
     }
 
@@ -104,6 +131,17 @@ impl AlumetPlugin for KwollectPluginInput {
         }
 
         config.metric_ids = metric_ids;
+
+        // One client for all the requests, so that the connections to the Grid'5000 API are reused.
+        *self.client.lock().unwrap() = Some(Client::new());
+        let client = self.client.clone();
+        alumet.on_shutdown(SHUTDOWN_DEADLINE, move || async move {
+            // The sources have been dropped, this is the last reference to the client.
+            // Dropping it waits for its background thread, which must not block an async worker.
+            let client = client.lock().unwrap().take();
+            tokio::task::spawn_blocking(move || drop(client)).await?;
+            Ok(())
+        });
         Ok(())
     }
 
@@ -121,6 +159,7 @@ impl AlumetPlugin for KwollectPluginInput {
         let control_handle = alumet.pipeline_control();
         let config_cloned = self.config.clone();
         let async_runtime = alumet.async_runtime().clone();
+        let client = self.client.clone();
 
         let start_alumet: OffsetDateTime = SystemTime::now().into();
         let system_time: SystemTime = convert_to_system_time(start_alumet);
@@ -130,9 +169,24 @@ impl AlumetPlugin for KwollectPluginInput {
         } else {
             FixedOffset::east_opt(0).unwrap() // fallback : UTC
         };
-        let start_paris = start_utc.with_timezone(&paris_offset);
-        event::end_consumer_measurement().subscribe(move |_evt| {
-            log::debug!("End consumer measurement event received");
+        let start_paris = Arc::new(Mutex::new(start_utc.with_timezone(&paris_offset)));
+
+        // When the experiment has several phases, fetch the data of each phase separately.
+        let phase_start = start_paris.clone();
+        event::phase_change().subscribe(move |evt| {
+            if evt.phase.is_some() {
+                let start_utc = convert_to_utc(evt.timestamp.into());
+                *phase_start.lock().unwrap() = start_utc.with_timezone(&paris_offset);
+            }
+            Ok(())
+        });
+
+        event::end_consumer_measurement().subscribe(move |evt| {
+            log::debug!("End consumer measurement event received: {evt:?}");
+            let Some(client) = client.lock().unwrap().clone() else {
+                log::warn!("Alumet is stopping, the Kwollect data of this measurement will not be fetched.");
+                return Ok(());
+            };
             let config = config_cloned.lock().unwrap();
             let pipeline_control = control_handle.clone();
             let end_alumet: OffsetDateTime = SystemTime::now().into();
@@ -149,17 +203,32 @@ impl AlumetPlugin for KwollectPluginInput {
                 utc_offset: config.utc_offset,
             };
 
-            let url = build_kwollect_url(&config_for_url, &start_paris, &end_paris);
+            let url = build_kwollect_url(&config_for_url, &start_paris.lock().unwrap(), &end_paris);
             log::info!("API request should be triggered with URL: {url}");
 
-            let source = KwollectSource::new(config_for_url, config.metric_ids.clone(), url)
-                .expect("Failed to create KwollectSource");
+            // Tag the fetched data with the metadata of the experiment.
+            let mut attributes: Vec<(String, AttributeValue)> =
+                evt.metadata.iter().map(|(k, v)| (k.to_owned(), v.clone())).collect();
+            if let Some(code) = evt.exit_code {
+                // Exit codes are unsigned on every platform (0-255 on Unix, a DWORD on Windows), only
+                // their Rust type is signed: keep the bits, so that the attribute always has the same type.
+                attributes.push((String::from("exit_code"), AttributeValue::U64(code as u32 as u64)));
+            }
+
+            let source = KwollectSource::new(client, config_for_url, config.metric_ids.clone(), url)
+                .expect("Failed to create KwollectSource")
+                .with_attributes(attributes);
 
             let mut builder = ManualTriggerBuilder::new();
             let trigger_spec = builder.build().expect("Failed to build trigger");
             log::debug!("Creating request...");
 
-            let request = request::create_one().add_source("kwollect_event_source", Box::new(source), trigger_spec);
+            // one source per phase, because the names of the sources must be unique
+            let source_name = match evt.metadata.get("phase") {
+                Some(phase) => format!("kwollect_event_source_{phase}"),
+                None => String::from("kwollect_event_source"),
+            };
+            let request = request::create_one().add_source(&source_name, Box::new(source), trigger_spec);
 
             // The pipeline will wait for the response of the source
             async_runtime
@@ -176,8 +245,7 @@ impl AlumetPlugin for KwollectPluginInput {
 
                     if result.is_ok() {
                         log::debug!("Triggering Kwollect Source now");
-                        let source_name =
-                            SourceName::new("kwollect-input".to_string(), "kwollect_event_source".to_string());
+                        let source_name = SourceName::new("kwollect-input".to_string(), source_name);
                         let source_matcher = SourceMatcher::Name(source_name.into());
                         let trigger_now_request =
                             alumet::pipeline::control::request::source(source_matcher).trigger_now();
@@ -282,8 +350,7 @@ fn build_kwollect_url(config: &Config, start: &DateTime<FixedOffset>, end: &Date
 }
 
 /// Performs an asynchronous HTTP GET request with basic authentication to the provided URL and returns the parsed JSON response.
-fn fetch_data(url: &str, config: &Config) -> Result<Value, anyhow::Error> {
-    let client = reqwest::blocking::Client::new();
+fn fetch_data(client: &Client, url: &str, config: &Config) -> Result<Value, anyhow::Error> {
     let response = client
         .get(url)
         .basic_auth(&config.login, Some(&config.password))
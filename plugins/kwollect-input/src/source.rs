@@ -10,18 +10,39 @@ use alumet::{
     resources::{Resource, ResourceConsumer},
 };
 use chrono::DateTime;
+use reqwest::blocking::Client;
 use std::borrow::Cow::{Borrowed, Owned};
 use std::time::SystemTime;
 
 pub struct KwollectSource {
+    pub client: Client,
     pub config: Config,
     pub metric: Vec<TypedMetricId<f64>>,
     pub url: String,
+    /// Attributes to add to every measurement point, for instance the metadata of the experiment.
+    pub attributes: Vec<(String, AttributeValue)>,
 }
 
 impl KwollectSource {
-    pub fn new(config: Config, metric: Vec<TypedMetricId<f64>>, url: String) -> anyhow::Result<KwollectSource> {
-        Ok(KwollectSource { config, metric, url })
+    pub fn new(
+        client: Client,
+        config: Config,
+        metric: Vec<TypedMetricId<f64>>,
+        url: String,
+    ) -> anyhow::Result<KwollectSource> {
+        Ok(KwollectSource {
+            client,
+            config,
+            metric,
+            url,
+            attributes: Vec::new(),
+        })
+    }
+
+    /// Adds the given attributes to every measurement point produced by the source.
+    pub fn with_attributes(mut self, attributes: Vec<(String, AttributeValue)>) -> Self {
+        self.attributes = attributes;
+        self
     }
 }
 
@@ -65,7 +86,7 @@ impl Source for KwollectSource {
         }
 
         // Retrieve the URL stored in KwollectPluginInput
-        let data = fetch_data(&self.url, &self.config)
+        let data = fetch_data(&self.client, &self.url, &self.config)
             .map_err(|e| PollError::Fatal(anyhow::anyhow!("Failed to fetch data: {}", e)))?;
         log::debug!("Full API response: {data:?}");
 
@@ -76,6 +97,7 @@ impl Source for KwollectSource {
             for &metric in &self.metric {
                 match create_measurement_point(&measure, metric) {
                     Ok(mp) => {
+                        let mp = mp.with_attr_slice(&self.attributes);
                         log::debug!("Created measurement point: {mp:?}");
                         measurements.push(mp);
                     }
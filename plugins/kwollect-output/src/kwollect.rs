@@ -1,10 +1,10 @@
 use alumet::measurement::{AttributeValue, WrappedMeasurementValue};
 use serde::{Serialize, ser::SerializeMap};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 pub struct Measure {
     pub device_id: String,
-    pub labels: HashMap<String, AttributeValue>,
+    pub labels: BTreeMap<String, AttributeValue>,
     pub metric_id: String,
     pub timestamp: f64,
     pub value: WrappedMeasurementValue,
@@ -25,7 +25,7 @@ impl Serialize for Measure {
             WrappedMeasurementValue::U64(v) => map.serialize_entry("value", &v)?,
         };
 
-        struct LabelsSerializer<'a>(&'a HashMap<String, AttributeValue>);
+        struct LabelsSerializer<'a>(&'a BTreeMap<String, AttributeValue>);
 
         impl Serialize for LabelsSerializer<'_> {
             fn serialize<T>(&self, serializer: T) -> Result<T::Ok, T::Error>
@@ -54,7 +54,7 @@ impl Serialize for Measure {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
 
     use alumet::measurement::{AttributeValue, WrappedMeasurementValue};
     use serde_json::Value;
@@ -65,7 +65,7 @@ mod tests {
     fn test_serialize_impl() {
         let entry = Measure {
             device_id: String::from("Iorek"),
-            labels: HashMap::new(),
+            labels: BTreeMap::new(),
             metric_id: String::from("Byrnison"),
             timestamp: 1750930866.0,
             value: WrappedMeasurementValue::F64(19.0),
@@ -118,7 +118,7 @@ mod tests {
 
     #[test]
     fn test_format_to_json_uint() {
-        let mut label = HashMap::new();
+        let mut label = BTreeMap::new();
         label.insert("William".to_string(), AttributeValue::String("Kirjava".to_string()));
         label.insert("Lyra".to_string(), AttributeValue::String("Pantalaimon".to_string()));
         label.insert("Alethiometer".to_string(), AttributeValue::U64(6));
@@ -1,11 +1,19 @@
 mod kwollect;
 mod output;
+mod spool;
 
+use alumet::plugin::capability::Capability;
 use alumet::plugin::rust::{deserialize_config, serialize_config};
 use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::output::KwollectOutput;
+use crate::spool::Spool;
+
+/// How long the plugin tries to send the spooled batches when Alumet stops.
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(10);
 
 pub struct KwollectPlugin {
     config: Config,
@@ -20,6 +28,10 @@ impl AlumetPlugin for KwollectPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
@@ -30,15 +42,26 @@ impl AlumetPlugin for KwollectPlugin {
     }
 
     fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
-        let output = Box::new(KwollectOutput::new(
+        let mut output = KwollectOutput::new(
             self.config.url.to_owned(),
             self.config.hostname.clone(),
             self.config.login.clone(),
             self.config.password.clone(),
             self.config.append_unit_to_metric_name,
             self.config.use_unit_display_name,
-        )?);
-        alumet.add_blocking_output("kwollect-output", output)?;
+        )?;
+        if self.config.max_spooled_batches > 0 {
+            let spool = Spool::open(alumet.state_dir()?, self.config.max_spooled_batches)?;
+            let spool = Arc::new(Mutex::new(spool));
+            output = output.with_spool(spool.clone());
+
+            // Try to send the spooled batches one last time. If it fails, they stay on the disk.
+            let sender = output.sender();
+            alumet.on_shutdown(SHUTDOWN_DEADLINE, move || async move {
+                tokio::task::spawn_blocking(move || sender.send_spooled(&mut spool.lock().unwrap())).await?
+            });
+        }
+        alumet.add_blocking_output("kwollect-output", Box::new(output))?;
 
         Ok(())
     }
@@ -56,6 +79,15 @@ pub struct Config {
     pub hostname: Option<String>,
     pub append_unit_to_metric_name: bool,
     pub use_unit_display_name: bool,
+    /// Maximum number of batches of measurements to keep on disk when Kwollect cannot be reached.
+    /// They are sent when Kwollect is available again, even after a restart of the agent.
+    /// Set to 0 to disable the spool.
+    #[serde(default = "default_max_spooled_batches")]
+    pub max_spooled_batches: usize,
+}
+
+fn default_max_spooled_batches() -> usize {
+    1000
 }
 
 fn default_client_name_and_site() -> (String, String) {
@@ -83,6 +115,7 @@ impl Default for Config {
             password: None,
             append_unit_to_metric_name: true,
             use_unit_display_name: true,
+            max_spooled_batches: default_max_spooled_batches(),
         }
     }
 }
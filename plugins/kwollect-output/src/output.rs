@@ -1,21 +1,33 @@
-use std::collections::HashMap;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
 
 use alumet::{
     measurement::{AttributeValue, MeasurementBuffer},
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
-use anyhow::Context;
+use anyhow::{Context, anyhow};
 use reqwest::{StatusCode, blocking::Client};
+use serde_json::Value;
 
-use crate::kwollect::Measure;
+use crate::{kwollect::Measure, spool::Spool};
 
 pub struct KwollectOutput {
-    client: Client,
-    url: String,
+    sender: Sender,
     node: Option<String>,
-    auth: Option<(String, String)>,
     append_unit_to_metric_name: bool,
     use_unit_display_name: bool,
+    /// Batches that could not be sent yet, if spooling is enabled.
+    spool: Option<Arc<Mutex<Spool>>>,
+}
+
+/// Sends batches of measurements to the Kwollect API.
+#[derive(Clone)]
+pub struct Sender {
+    client: Client,
+    url: String,
+    auth: Option<(String, String)>,
 }
 
 impl KwollectOutput {
@@ -27,25 +39,70 @@ impl KwollectOutput {
         append_unit_to_metric_name: bool,
         use_unit_display_name: bool,
     ) -> anyhow::Result<Self> {
-        if let (Some(user), Some(pass)) = (login, password) {
-            Ok(Self {
+        let auth = match (login, password) {
+            (Some(user), Some(pass)) => Some((user, pass)),
+            _ => None,
+        };
+        Ok(Self {
+            sender: Sender {
                 client: Client::builder().danger_accept_invalid_certs(true).build()?,
                 url,
-                node,
-                auth: Some((user, pass)),
-                append_unit_to_metric_name,
-                use_unit_display_name,
-            })
-        } else {
-            Ok(Self {
-                client: Client::builder().danger_accept_invalid_certs(true).build()?,
-                url,
-                node,
-                auth: None,
-                append_unit_to_metric_name,
-                use_unit_display_name,
-            })
+                auth,
+            },
+            node,
+            append_unit_to_metric_name,
+            use_unit_display_name,
+            spool: None,
+        })
+    }
+
+    /// Keeps the batches that cannot be sent in a spool, and sends them later.
+    pub fn with_spool(mut self, spool: Arc<Mutex<Spool>>) -> Self {
+        self.spool = Some(spool);
+        self
+    }
+
+    /// Returns a sender that uses the same connection settings as the output.
+    pub fn sender(&self) -> Sender {
+        self.sender.clone()
+    }
+}
+
+impl Sender {
+    /// Sends a batch of measurements to Kwollect.
+    ///
+    /// Returns an error if the batch should be sent again later.
+    fn send(&self, batch: &[Value]) -> anyhow::Result<()> {
+        let mut request_builder = self.client.post(&self.url);
+        if let Some((user, pass)) = &self.auth {
+            request_builder = request_builder.basic_auth(user, Some(pass));
+        }
+        let res = request_builder.json(batch).send()?;
+
+        let status = res.status();
+        if status.is_server_error() {
+            let body = res.text().unwrap_or_default();
+            return Err(anyhow!("server error {status}: {body}"));
+        }
+        if status != StatusCode::OK {
+            // the batch has been rejected, sending it again would not help
+            let body = res.text()?;
+            log::error!("response from remote: {}", body)
+        }
+        Ok(())
+    }
+
+    /// Sends the spooled batches, and persists the ones that could not be sent.
+    pub fn send_spooled(&self, spool: &mut Spool) -> anyhow::Result<()> {
+        // Send the oldest batches first, and stop at the first failure to preserve the order.
+        while let Some(batch) = spool.front() {
+            if let Err(e) = self.send(batch) {
+                log::warn!("Failed to send the measurements to Kwollect, they will be sent later: {e:#}");
+                break;
+            }
+            spool.pop_front();
         }
+        spool.persist().context("failed to persist the spool")
     }
 }
 
@@ -74,7 +131,7 @@ impl alumet::pipeline::Output for KwollectOutput {
             };
             let ts_tmp = measure.timestamp.to_unix_timestamp();
             let ts = ts_tmp.0 as f64 + ts_tmp.1 as f64 / 1_000_000_000.0;
-            let mut json_map: HashMap<String, AttributeValue> = HashMap::new();
+            let mut json_map: BTreeMap<String, AttributeValue> = BTreeMap::new();
             // Add ressource_kind, ressource_id, consumer_kind and consumer_id
             json_map.insert(
                 "ressource_kind".to_string(),
@@ -107,17 +164,13 @@ impl alumet::pipeline::Output for KwollectOutput {
             json_list.push(serialized);
         }
 
-        let mut request_builder = self.client.post(&self.url);
-        if let Some((user, pass)) = &self.auth {
-            request_builder = request_builder.basic_auth(user, Some(pass));
-        }
-        let res = request_builder.json(&json_list).send().unwrap();
-
-        if res.status() != StatusCode::OK {
-            let body = res.text().unwrap();
-            log::error!("response from remote: {}", body)
-        }
+        let Some(spool) = &self.spool else {
+            return self.sender.send(&json_list).map_err(WriteError::CanRetry);
+        };
 
+        let mut spool = spool.lock().unwrap();
+        spool.push(json_list);
+        self.sender.send_spooled(&mut spool)?;
         Ok(())
     }
 }
@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+
+use alumet::plugin::state::StateDir;
+use anyhow::Context;
+use serde_json::Value;
+
+/// Name of the spool file in the state directory of the plugin.
+const SPOOL_FILE: &str = "spool.json";
+
+/// Batches of measurements that have not been sent to Kwollect yet.
+///
+/// The spool is persisted in the state directory of the plugin, so that
+/// the batches survive a restart of the agent.
+pub struct Spool {
+    state: StateDir,
+    batches: VecDeque<Vec<Value>>,
+    max_batches: usize,
+    /// Is there a spool file on the disk?
+    persisted: bool,
+}
+
+impl Spool {
+    /// Opens the spool, and loads the batches left by the previous run, if any.
+    pub fn open(state: StateDir, max_batches: usize) -> anyhow::Result<Self> {
+        let batches: VecDeque<Vec<Value>> = match state.read(SPOOL_FILE)? {
+            Some(content) => serde_json::from_slice(&content).context("invalid spool file")?,
+            None => VecDeque::new(),
+        };
+        if !batches.is_empty() {
+            log::info!(
+                "{} unsent batches found in the spool, they will be sent first",
+                batches.len()
+            );
+        }
+        Ok(Self {
+            persisted: !batches.is_empty(),
+            state,
+            batches,
+            max_batches,
+        })
+    }
+
+    pub fn push(&mut self, batch: Vec<Value>) {
+        self.batches.push_back(batch);
+        if self.batches.len() > self.max_batches {
+            log::warn!(
+                "Kwollect spool is full ({} batches), dropping the oldest batch",
+                self.max_batches
+            );
+            self.batches.pop_front();
+        }
+    }
+
+    pub fn front(&self) -> Option<&Vec<Value>> {
+        self.batches.front()
+    }
+
+    pub fn pop_front(&mut self) {
+        self.batches.pop_front();
+    }
+
+    /// Writes the content of the spool to the disk.
+    pub fn persist(&mut self) -> anyhow::Result<()> {
+        if self.batches.is_empty() {
+            if self.persisted {
+                self.state.remove(SPOOL_FILE)?;
+                self.persisted = false;
+            }
+        } else {
+            let content = serde_json::to_vec(&self.batches)?;
+            self.state.write(SPOOL_FILE, content)?;
+            self.persisted = true;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{HttpCapture, SampleMeasurements, assert_golden_json},
+    },
+};
+use plugin_kwollect_output::{Config, KwollectPlugin};
+
+#[test]
+fn kwollect_format() -> anyhow::Result<()> {
+    let server = HttpCapture::start()?;
+    let config = Config {
+        url: format!("{}/metrics", server.url()),
+        hostname: Some(String::from("node-1")),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<KwollectPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("kwollect-output")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        (requests[0].method.as_str(), requests[0].path.as_str()),
+        ("POST", "/metrics")
+    );
+    assert_eq!(requests[0].header("content-type"), Some("application/json"));
+
+    assert_golden_json(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.json"),
+        &requests[0].body,
+    );
+    harness.stop()
+}
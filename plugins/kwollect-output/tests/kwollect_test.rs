@@ -48,6 +48,7 @@ fn test_with_no_auth() {
     config.login = None;
     config.password = None;
     config.hostname = Some("DHARMA".to_string());
+    config.max_spooled_batches = 0;
 
     plugins.add_plugin(PluginInfo {
         metadata: PluginMetadata::from_static::<KwollectPlugin>(),
@@ -107,6 +108,7 @@ fn test_with_auth() {
         hostname: Some("DHARMA".to_string()),
         append_unit_to_metric_name: true,
         use_unit_display_name: false,
+        max_spooled_batches: 0,
     };
 
     plugins.add_plugin(PluginInfo {
@@ -161,3 +163,60 @@ fn test_default_config() {
 fn config_to_toml_table(config: &Config) -> toml::Table {
     toml::Value::try_from(config).unwrap().as_table().unwrap().clone()
 }
+
+#[test]
+fn spool_sent_on_shutdown() {
+    let state_root = tempfile::tempdir().unwrap();
+    let spool_file = state_root.path().join("kwollect-output/spool.json");
+
+    // Kwollect is unavailable for the first write, then available again.
+    let mut server = Server::new();
+    let unavailable = server.mock("POST", "/").with_status(503).expect(1).create();
+    let available = server.mock("POST", "/").with_status(200).expect(1).create();
+
+    let mut plugins = PluginSet::new();
+    let config = Config {
+        url: server.url(),
+        hostname: Some("DHARMA".to_string()),
+        ..Config::default()
+    };
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<KwollectPlugin>(),
+        enabled: true,
+        config: Some(config_to_toml_table(&config)),
+    });
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<TestsPlugin>(),
+        enabled: true,
+        config: None,
+    });
+    let make_input = |ctx: &mut OutputCheckInputContext| -> MeasurementBuffer {
+        let metric = ctx.metrics().by_name("example_counter").expect("metric should exist").0;
+        let mut m = MeasurementBuffer::new();
+        m.push(MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            metric,
+            Resource::LocalMachine,
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::U64(10),
+        ));
+        m
+    };
+    let runtime_expectations = RuntimeExpectations::new().test_output(
+        OutputName::from_str("kwollect-output", "kwollect-output"),
+        make_input,
+        || (),
+    );
+
+    let agent = agent::Builder::new(plugins)
+        .state_directory(state_root.path())
+        .with_expectations(runtime_expectations)
+        .build_and_start()
+        .unwrap();
+    agent.wait_for_shutdown(Duration::from_secs(5)).unwrap();
+
+    // The batch has been spooled, then sent by the shutdown hook.
+    unavailable.assert();
+    available.assert();
+    assert!(!spool_file.exists());
+}
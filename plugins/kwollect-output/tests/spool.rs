@@ -0,0 +1,68 @@
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{HttpCapture, SampleMeasurements},
+    },
+};
+use plugin_kwollect_output::{Config, KwollectPlugin};
+
+fn config(url: String) -> Config {
+    Config {
+        url: format!("{url}/metrics"),
+        hostname: Some(String::from("node-1")),
+        ..Config::default()
+    }
+}
+
+#[test]
+fn spool_survives_restart() -> anyhow::Result<()> {
+    let state_root = tempfile::tempdir()?;
+    let spool_file = state_root.path().join("kwollect-output/spool.json");
+
+    // Kwollect is unavailable: the measurements are kept in the spool.
+    let unavailable = HttpCapture::with_status(503)?;
+    let mut harness = PluginHarness::<KwollectPlugin>::start_with_state(
+        serialize_config(config(unavailable.url()))?,
+        state_root.path(),
+    )?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("kwollect-output")?;
+    output.write(&sample.measurements()).unwrap();
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+    assert_eq!(unavailable.requests().len(), 2);
+    assert!(spool_file.exists());
+
+    // After a restart, the spooled batches are sent before the new ones, and the spool is deleted.
+    let available = HttpCapture::start()?;
+    let mut harness = PluginHarness::<KwollectPlugin>::start_with_state(
+        serialize_config(config(available.url()))?,
+        state_root.path(),
+    )?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("kwollect-output")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+    assert_eq!(available.requests().len(), 3);
+    assert!(!spool_file.exists());
+    Ok(())
+}
+
+#[test]
+fn spool_disabled() -> anyhow::Result<()> {
+    let unavailable = HttpCapture::with_status(503)?;
+    let config = Config {
+        max_spooled_batches: 0,
+        ..config(unavailable.url())
+    };
+    let mut harness = PluginHarness::<KwollectPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("kwollect-output")?;
+    output.write(&sample.measurements()).unwrap_err();
+    output.finish()?;
+    assert!(!harness.state_root().exists());
+    harness.stop()
+}
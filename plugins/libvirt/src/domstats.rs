@@ -0,0 +1,228 @@
+//! Statistics of the running domains, obtained with `virsh domstats`.
+
+use std::{collections::BTreeMap, path::PathBuf, process::Command};
+
+use anyhow::{Context, anyhow};
+
+/// Runs `virsh domstats` on a libvirt connection.
+pub struct Virsh {
+    pub virsh: PathBuf,
+    pub uri: String,
+}
+
+/// Statistics of a domain (virtual machine).
+#[derive(Debug, Default, PartialEq)]
+pub struct DomainStats {
+    pub name: String,
+    /// Total CPU time of the domain (vCPUs and emulator), in nanoseconds.
+    pub cpu_time: Option<u64>,
+    /// CPU time of the vCPUs, in nanoseconds.
+    pub vcpu_time: Option<u64>,
+    /// Resident memory of the domain on the host, in bytes.
+    pub memory_rss: Option<u64>,
+    /// Bytes read and written, per disk.
+    pub blocks: Vec<IoCounters>,
+    /// Bytes received and sent, per network interface.
+    pub interfaces: Vec<IoCounters>,
+}
+
+/// Cumulative I/O counters of a device.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IoCounters {
+    pub name: String,
+    /// Bytes read (disk) or received (network).
+    pub read: u64,
+    /// Bytes written (disk) or sent (network).
+    pub write: u64,
+}
+
+impl Virsh {
+    /// Gets the statistics of the running domains.
+    pub fn domstats(&self) -> anyhow::Result<Vec<DomainStats>> {
+        let output = Command::new(&self.virsh)
+            .args(["-c", &self.uri, "domstats", "--list-running", "--raw"])
+            .args(["--cpu-total", "--vcpu", "--balloon", "--block", "--interface"])
+            .output()
+            .with_context(|| format!("failed to execute {}", self.virsh.display()))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("virsh failed ({}): {}", output.status, stderr.trim()));
+        }
+        parse_domstats(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Parses the output of `virsh domstats --raw`, which looks like:
+///
+/// ```text
+/// Domain: 'vm1'
+///   cpu.time=4123456789
+///   balloon.rss=1048576
+///   vcpu.0.time=3000000000
+///   block.count=1
+///   block.0.name=vda
+///   block.0.rd.bytes=1000
+/// ```
+fn parse_domstats(output: &str) -> anyhow::Result<Vec<DomainStats>> {
+    let mut domains = Vec::new();
+    let mut current: Option<DomainParser> = None;
+    for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(name) = line.strip_prefix("Domain: ") {
+            domains.extend(current.take().map(DomainParser::finish));
+            let name = name.trim_matches(|c| c == '\'' || c == '"');
+            current = Some(DomainParser::new(name));
+            continue;
+        }
+        let domain = current
+            .as_mut()
+            .with_context(|| format!("unexpected line before the first domain: {line}"))?;
+        let (key, value) = line
+            .split_once('=')
+            .with_context(|| format!("invalid line in the output of virsh: {line}"))?;
+        domain
+            .parse_field(key, value)
+            .with_context(|| format!("invalid value: {line}"))?;
+    }
+    domains.extend(current.map(DomainParser::finish));
+    Ok(domains)
+}
+
+struct DomainParser {
+    stats: DomainStats,
+    blocks: BTreeMap<usize, IoCounters>,
+    interfaces: BTreeMap<usize, IoCounters>,
+}
+
+impl DomainParser {
+    fn new(name: &str) -> Self {
+        Self {
+            stats: DomainStats {
+                name: name.to_owned(),
+                ..Default::default()
+            },
+            blocks: BTreeMap::new(),
+            interfaces: BTreeMap::new(),
+        }
+    }
+
+    fn parse_field(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        let mut parts = key.split('.');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("cpu"), Some("time"), None) => self.stats.cpu_time = Some(value.parse()?),
+            (Some("balloon"), Some("rss"), None) => self.stats.memory_rss = Some(value.parse::<u64>()? * 1024),
+            (Some("vcpu"), Some(n), Some("time")) if n.parse::<usize>().is_ok() => {
+                *self.stats.vcpu_time.get_or_insert(0) += value.parse::<u64>()?;
+            }
+            (Some(kind @ ("block" | "net")), Some(n), Some(field)) => {
+                let Ok(n) = n.parse::<usize>() else {
+                    return Ok(());
+                };
+                let devices = if kind == "block" {
+                    &mut self.blocks
+                } else {
+                    &mut self.interfaces
+                };
+                let device = devices.entry(n).or_default();
+                match (field, parts.next()) {
+                    ("name", None) => device.name = value.to_owned(),
+                    ("rd" | "rx", Some("bytes")) => device.read = value.parse()?,
+                    ("wr" | "tx", Some("bytes")) => device.write = value.parse()?,
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> DomainStats {
+        DomainStats {
+            blocks: self.blocks.into_values().collect(),
+            interfaces: self.interfaces.into_values().collect(),
+            ..self.stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let output = "Domain: 'web'
+  state.state=1
+  state.reason=1
+  cpu.time=4123456789
+  cpu.user=1000000000
+  cpu.system=2000000000
+  balloon.current=2097152
+  balloon.maximum=2097152
+  balloon.rss=1048576
+  vcpu.current=2
+  vcpu.maximum=2
+  vcpu.0.state=1
+  vcpu.0.time=3000000000
+  vcpu.0.wait=0
+  vcpu.1.state=1
+  vcpu.1.time=500000000
+  net.count=1
+  net.0.name=vnet0
+  net.0.rx.bytes=5000
+  net.0.rx.pkts=40
+  net.0.tx.bytes=1200
+  net.0.tx.pkts=12
+  block.count=2
+  block.0.name=vda
+  block.0.path=/var/lib/libvirt/images/web.qcow2
+  block.0.rd.reqs=10
+  block.0.rd.bytes=40960
+  block.0.wr.bytes=8192
+  block.1.name=sda
+  block.1.rd.bytes=512
+
+Domain: 'db'
+  state.state=1
+  cpu.time=10
+";
+        let domains = parse_domstats(output).unwrap();
+        assert_eq!(
+            domains,
+            vec![
+                DomainStats {
+                    name: String::from("web"),
+                    cpu_time: Some(4123456789),
+                    vcpu_time: Some(3500000000),
+                    memory_rss: Some(1073741824),
+                    blocks: vec![
+                        IoCounters {
+                            name: String::from("vda"),
+                            read: 40960,
+                            write: 8192
+                        },
+                        IoCounters {
+                            name: String::from("sda"),
+                            read: 512,
+                            write: 0
+                        },
+                    ],
+                    interfaces: vec![IoCounters {
+                        name: String::from("vnet0"),
+                        read: 5000,
+                        write: 1200
+                    }],
+                },
+                DomainStats {
+                    name: String::from("db"),
+                    cpu_time: Some(10),
+                    ..Default::default()
+                }
+            ]
+        );
+        assert_eq!(parse_domstats("").unwrap(), vec![]);
+        assert!(parse_domstats("  cpu.time=10").is_err());
+        assert!(parse_domstats("Domain: 'web'\n  cpu.time=abc").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use domstats::Virsh;
+
+mod domstats;
+mod metrics;
+mod source;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct LibvirtPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for LibvirtPlugin {
+    fn name() -> &'static str {
+        "libvirt"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::ProcessSpawn])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(LibvirtPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let virsh = Virsh {
+            virsh: self.config.virsh.clone(),
+            uri: self.config.uri.clone(),
+        };
+        let domains = virsh
+            .domstats()
+            .with_context(|| format!("failed to get the statistics of the domains of {}", self.config.uri))?;
+        log::info!("Found {} running domains on {}.", domains.len(), self.config.uri);
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::LibvirtSource::new(virsh, metrics);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("domains", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// URI of the libvirt connection.
+    uri: String,
+
+    /// Path to the `virsh` executable.
+    virsh: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            flush_interval: Duration::from_secs(15),
+            uri: String::from("qemu:///system"),
+            virsh: PathBuf::from("virsh"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::{PrefixedUnit, Unit},
+    };
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A fake virsh with one domain, which uses one second of CPU time and reads 4096 bytes
+    /// between two calls. The number of calls is counted in the file `calls`, next to the script.
+    const FAKE_VIRSH: &str = r#"#!/bin/sh
+if [ "$2" != "qemu:///system" ]; then
+    echo "error: failed to connect to the hypervisor" >&2
+    exit 1
+fi
+dir=$(dirname "$0")
+n=$(( $(cat "$dir/calls" 2>/dev/null || echo 0) + 1 ))
+echo $n > "$dir/calls"
+echo "Domain: 'web'"
+echo "  cpu.time=$(( n * 1000000000 ))"
+echo "  balloon.rss=1024"
+echo "  vcpu.0.time=$(( n * 800000000 ))"
+echo "  block.count=1"
+echo "  block.0.name=vda"
+echo "  block.0.rd.bytes=$(( n * 4096 ))"
+echo "  block.0.wr.bytes=0"
+"#;
+
+    fn fake_virsh(dir: &Path) -> PathBuf {
+        let path = dir.join("virsh");
+        fs::write(&path, FAKE_VIRSH).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<LibvirtPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    #[test]
+    fn domain_stats() {
+        let tmp = tempdir().unwrap();
+        let config = Config {
+            poll_interval: Duration::from_millis(100),
+            virsh: fake_virsh(tmp.path()),
+            ..Config::default()
+        };
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<u64>("libvirt_cpu_time_delta", PrefixedUnit::nano(Unit::Second))
+            .expect_metric::<f64>("libvirt_cpu_percent", Unit::Percent)
+            .expect_metric::<u64>("libvirt_memory_rss", Unit::Byte)
+            .expect_metric::<u64>("libvirt_block_bytes_delta", Unit::Byte)
+            .expect_metric::<u64>("libvirt_network_bytes_delta", Unit::Byte)
+            .expect_source("libvirt", "domains");
+
+        let source = SourceName::from_str("libvirt", "domains");
+        let runtime = RuntimeExpectations::new()
+            .test_source(
+                source.clone(),
+                || (),
+                |ctx| {
+                    // first measurement: no delta yet
+                    let m = ctx.measurements();
+                    let values: Vec<_> = m.iter().map(|m| m.value.clone()).collect();
+                    assert_eq!(values, vec![WrappedMeasurementValue::U64(1024 * 1024)]);
+                    let consumer = &m.iter().next().unwrap().consumer;
+                    assert_eq!(consumer.kind(), "vm");
+                    assert_eq!(consumer.id_display().to_string(), "web");
+                },
+            )
+            .test_source(
+                source,
+                || (),
+                |ctx| {
+                    let m = ctx.measurements();
+                    let values: Vec<_> = m
+                        .iter()
+                        .filter_map(|m| match m.value {
+                            WrappedMeasurementValue::U64(v) => Some(v),
+                            WrappedMeasurementValue::F64(_) => None,
+                        })
+                        .collect();
+                    assert_eq!(values, vec![1024 * 1024, 1_000_000_000, 800_000_000, 4096, 0]);
+                    assert_eq!(m.len(), 6);
+                },
+            );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn unreachable_hypervisor() {
+        let tmp = tempdir().unwrap();
+        let config = Config {
+            uri: String::from("qemu+ssh://nowhere/system"),
+            virsh: fake_virsh(tmp.path()),
+            ..Config::default()
+        };
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+}
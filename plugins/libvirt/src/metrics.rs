@@ -0,0 +1,50 @@
+use alumet::{
+    metrics::TypedMetricId,
+    plugin::AlumetPluginStart,
+    units::{PrefixedUnit, Unit},
+};
+
+pub struct Metrics {
+    /// CPU time since the previous measurement.
+    pub cpu_time_delta: TypedMetricId<u64>,
+    /// Total CPU time divided by the time elapsed since the previous measurement.
+    pub cpu_percent: TypedMetricId<f64>,
+    /// Resident memory on the host.
+    pub memory_rss: TypedMetricId<u64>,
+    /// Disk I/O since the previous measurement.
+    pub block_bytes_delta: TypedMetricId<u64>,
+    /// Network traffic since the previous measurement.
+    pub network_bytes_delta: TypedMetricId<u64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            cpu_time_delta: alumet.create_metric(
+                "libvirt_cpu_time_delta",
+                PrefixedUnit::nano(Unit::Second),
+                "CPU time used by the virtual machine since the previous measurement",
+            )?,
+            cpu_percent: alumet.create_metric(
+                "libvirt_cpu_percent",
+                Unit::Percent,
+                "Part of the CPU used by the virtual machine since the previous measurement (1 core fully used = 100%)",
+            )?,
+            memory_rss: alumet.create_metric(
+                "libvirt_memory_rss",
+                Unit::Byte,
+                "Resident memory of the virtual machine on the host",
+            )?,
+            block_bytes_delta: alumet.create_metric(
+                "libvirt_block_bytes_delta",
+                Unit::Byte,
+                "Bytes read or written on a disk of the virtual machine since the previous measurement",
+            )?,
+            network_bytes_delta: alumet.create_metric(
+                "libvirt_network_bytes_delta",
+                Unit::Byte,
+                "Bytes received or sent by the virtual machine since the previous measurement",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{
+    domstats::{DomainStats, IoCounters, Virsh},
+    metrics::Metrics,
+};
+
+/// Measurement source that gets the statistics of the running virtual machines from libvirt.
+pub struct LibvirtSource {
+    virsh: Virsh,
+    metrics: Metrics,
+    /// Statistics of the domains at the previous measurement.
+    previous: HashMap<String, (Timestamp, DomainStats)>,
+}
+
+impl LibvirtSource {
+    pub fn new(virsh: Virsh, metrics: Metrics) -> Self {
+        Self {
+            virsh,
+            metrics,
+            previous: HashMap::new(),
+        }
+    }
+}
+
+impl Source for LibvirtSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // libvirtd can be restarted, the next poll may work
+        let domains = self
+            .virsh
+            .domstats()
+            .context("failed to get the statistics of the domains")
+            .map_err(PollError::CanRetry)?;
+
+        let mut current = HashMap::with_capacity(domains.len());
+        for domain in domains {
+            let consumer = ResourceConsumer::custom("vm", domain.name.clone());
+            let point = |metric: TypedMetricId<u64>, value: u64| {
+                MeasurementPoint::new(t, metric, Resource::LocalMachine, consumer.clone(), value)
+            };
+
+            if let Some(rss) = domain.memory_rss {
+                measurements.push(point(self.metrics.memory_rss, rss));
+            }
+
+            if let Some((prev_t, prev)) = self.previous.get(&domain.name) {
+                // The counters are reset when the domain is restarted, skip the deltas in this case.
+                let delta = |current: Option<u64>, previous: Option<u64>| current?.checked_sub(previous?);
+                if let Some(cpu_time) = delta(domain.cpu_time, prev.cpu_time) {
+                    measurements.push(point(self.metrics.cpu_time_delta, cpu_time).with_attr("kind", "total"));
+                    let elapsed = t.duration_since(*prev_t)?.as_nanos() as f64;
+                    if elapsed > 0.0 {
+                        measurements.push(MeasurementPoint::new(
+                            t,
+                            self.metrics.cpu_percent,
+                            Resource::LocalMachine,
+                            consumer.clone(),
+                            cpu_time as f64 / elapsed * 100.0,
+                        ));
+                    }
+                }
+                if let Some(vcpu_time) = delta(domain.vcpu_time, prev.vcpu_time) {
+                    measurements.push(point(self.metrics.cpu_time_delta, vcpu_time).with_attr("kind", "vcpu"));
+                }
+
+                let io_deltas = [
+                    (
+                        self.metrics.block_bytes_delta,
+                        "device",
+                        ("read", "write"),
+                        &domain.blocks,
+                        &prev.blocks,
+                    ),
+                    (
+                        self.metrics.network_bytes_delta,
+                        "interface",
+                        ("rx", "tx"),
+                        &domain.interfaces,
+                        &prev.interfaces,
+                    ),
+                ];
+                for (metric, device_attr, (read_kind, write_kind), devices, prev_devices) in io_deltas {
+                    for (device, prev) in matching_devices(devices, prev_devices) {
+                        for (kind, value, prev) in [
+                            (read_kind, device.read, prev.read),
+                            (write_kind, device.write, prev.write),
+                        ] {
+                            if let Some(bytes) = value.checked_sub(prev) {
+                                measurements.push(
+                                    point(metric, bytes)
+                                        .with_attr("kind", kind)
+                                        .with_attr(device_attr, device.name.clone()),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            current.insert(domain.name.clone(), (t, domain));
+        }
+        // forget the domains that have been stopped
+        self.previous = current;
+        Ok(())
+    }
+}
+
+/// Pairs the devices with their previous counters, by name.
+fn matching_devices<'a>(
+    devices: &'a [IoCounters],
+    previous: &'a [IoCounters],
+) -> impl Iterator<Item = (&'a IoCounters, &'a IoCounters)> {
+    devices
+        .iter()
+        .filter_map(|d| previous.iter().find(|p| p.name == d.name).map(|p| (d, p)))
+}
@@ -0,0 +1,392 @@
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alumet::{
+    metrics::TypedMetricId,
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    units::PrefixedUnit,
+};
+
+use protocol::Table;
+use register::{DataType, Endianness, Register};
+use source::{MeasuredRegister, ModbusSource};
+use transport::{Connection, Endpoint, SerialSettings};
+
+mod protocol;
+mod register;
+mod source;
+mod transport;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct ModbusPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for ModbusPlugin {
+    fn name() -> &'static str {
+        "modbus"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            // Modbus TCP
+            Capability::Network,
+            // the serial ports of Modbus RTU, such as `/dev/ttyUSB0`
+            Capability::Filesystem(PathBuf::from("/dev")),
+        ])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(ModbusPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        if self.config.meters.is_empty() {
+            return Err(anyhow!("No meter configured, please add some to the configuration."));
+        }
+
+        // The same metric can be measured by several meters, with the same unit.
+        let mut metrics: HashMap<String, (PrefixedUnit, TypedMetricId<f64>)> = HashMap::new();
+        // The meters of a serial line or of a TCP gateway share the same connection.
+        let mut connections: HashMap<String, (Endpoint, Arc<Mutex<Connection>>)> = HashMap::new();
+
+        for meter in &self.config.meters {
+            let mut registers = Vec::with_capacity(meter.registers.len());
+            for register in &meter.registers {
+                let unit = PrefixedUnit::from_str(&register.unit)
+                    .with_context(|| format!("invalid unit of {} in meter {}", register.metric, meter.name))?;
+                let metric = match metrics.get(&register.metric) {
+                    Some((existing, _)) if existing != &unit => {
+                        return Err(anyhow!(
+                            "metric {} is measured in {existing} and in {unit}, please use the same unit everywhere",
+                            register.metric
+                        ));
+                    }
+                    Some((_, id)) => *id,
+                    None => {
+                        let id = alumet.create_metric(&register.metric, unit.clone(), &register.description)?;
+                        metrics.insert(register.metric.clone(), (unit, id));
+                        id
+                    }
+                };
+                registers.push(MeasuredRegister::new(register.clone(), metric));
+            }
+
+            let endpoint = meter.endpoint()?;
+            let (shared, connection) = connections.entry(endpoint.key()).or_insert_with(|| {
+                let connection = Connection::new(endpoint.clone(), self.config.timeout);
+                (endpoint.clone(), Arc::new(Mutex::new(connection)))
+            });
+            if *shared != endpoint {
+                return Err(anyhow!(
+                    "meter {} uses the serial port of another meter, but with different settings",
+                    meter.name
+                ));
+            }
+
+            let source = ModbusSource::new(connection.clone(), meter.name.clone(), meter.unit_id, registers);
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&format!("meter_{}", meter.name), Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// How long to wait for the response of a meter.
+    #[serde(with = "humantime_serde")]
+    timeout: Duration,
+
+    meters: Vec<Meter>,
+}
+
+/// A meter, that is a Modbus server with registers to read.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Meter {
+    /// Name of the meter, used as the id of the resource of its measurements.
+    name: String,
+
+    /// Address of the meter on the serial line, or unit identifier behind a TCP gateway.
+    unit_id: u8,
+
+    /// Address of the Modbus TCP server, such as `192.168.1.50:502`.
+    /// Exactly one of `tcp` and `rtu` must be set.
+    tcp: Option<String>,
+
+    /// Serial line of the Modbus RTU server.
+    rtu: Option<SerialSettings>,
+
+    registers: Vec<Register>,
+}
+
+impl Meter {
+    fn endpoint(&self) -> anyhow::Result<Endpoint> {
+        match (&self.tcp, &self.rtu) {
+            (Some(address), None) => Ok(Endpoint::Tcp(address.clone())),
+            (None, Some(serial)) => {
+                transport::baud_rate(serial.baud_rate).with_context(|| format!("invalid meter {}", self.name))?;
+                if !matches!(serial.stop_bits, 1 | 2) {
+                    return Err(anyhow!("invalid meter {}: stop_bits must be 1 or 2", self.name));
+                }
+                Ok(Endpoint::Rtu(serial.clone()))
+            }
+            _ => Err(anyhow!(
+                "invalid meter {}: exactly one of tcp and rtu must be set",
+                self.name
+            )),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // An example of meter, with two registers of the Eastron SDM630
+        let registers = vec![
+            Register {
+                metric: String::from("modbus_power"),
+                unit: String::from("W"),
+                description: String::from("Total power measured by the meter"),
+                address: 0x0034,
+                table: Table::Input,
+                data_type: DataType::F32,
+                word_order: Endianness::Big,
+                byte_order: Endianness::Big,
+                scale: 1.0,
+                counter: false,
+                attributes: BTreeMap::new(),
+            },
+            Register {
+                metric: String::from("modbus_energy"),
+                unit: String::from("J"),
+                description: String::from("Energy consumed since the previous measurement"),
+                address: 0x0156,
+                table: Table::Input,
+                data_type: DataType::F32,
+                word_order: Endianness::Big,
+                byte_order: Endianness::Big,
+                scale: 3_600_000.0, // kWh to J
+                counter: true,
+                attributes: BTreeMap::new(),
+            },
+        ];
+        Self {
+            poll_interval: Duration::from_secs(1), // 1Hz
+            flush_interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(1),
+            meters: vec![Meter {
+                name: String::from("wattmeter"),
+                unit_id: 1,
+                tcp: Some(String::from("127.0.0.1:502")),
+                rtu: None,
+                registers,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::Unit,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    type Registers = Arc<Mutex<HashMap<(u8, u16), u16>>>;
+
+    /// Starts a Modbus TCP server that serves the input (4) and holding (3) registers,
+    /// and returns its address.
+    fn fake_server(registers: Registers) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let registers = registers.clone();
+                thread::spawn(move || {
+                    let mut request = [0u8; 12];
+                    while stream.read_exact(&mut request).is_ok() {
+                        let function = request[7];
+                        let address = u16::from_be_bytes([request[8], request[9]]);
+                        let count = u16::from_be_bytes([request[10], request[11]]);
+                        let registers = registers.lock().unwrap();
+                        let values: Option<Vec<u16>> = (address..address + count)
+                            .map(|a| registers.get(&(function, a)).copied())
+                            .collect();
+                        let pdu = match values {
+                            Some(values) => {
+                                let mut pdu = vec![function, 2 * count as u8];
+                                pdu.extend(values.iter().flat_map(|v| v.to_be_bytes()));
+                                pdu
+                            }
+                            None => vec![function | 0x80, 0x02],
+                        };
+                        let mut response = request[..4].to_vec();
+                        response.extend((pdu.len() as u16 + 1).to_be_bytes());
+                        response.push(request[6]);
+                        response.extend(pdu);
+                        stream.write_all(&response).unwrap();
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<ModbusPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    #[test]
+    fn tcp_meter() {
+        let registers = Registers::default();
+        {
+            let mut registers = registers.lock().unwrap();
+            // power: 230.5 W
+            registers.insert((4, 0x0034), 0x4366);
+            registers.insert((4, 0x0035), 0x8000);
+            // energy: 1.000 kWh
+            registers.insert((4, 0x0156), 0x3f80);
+            registers.insert((4, 0x0157), 0x0000);
+        }
+        let mut config = Config::default();
+        config.meters[0].tcp = Some(fake_server(registers.clone()));
+        config.meters[0].registers[0]
+            .attributes
+            .insert(String::from("phase"), String::from("total"));
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<f64>("modbus_power", Unit::Watt)
+            .expect_metric::<f64>("modbus_energy", Unit::Joule)
+            .expect_source("modbus", "meter_wattmeter");
+
+        let runtime = RuntimeExpectations::new()
+            .test_source(
+                SourceName::from_str("modbus", "meter_wattmeter"),
+                || (),
+                |ctx| {
+                    // no energy on the first measurement
+                    let m = ctx.measurements();
+                    assert_eq!(m.len(), 1);
+                    let power = m.iter().next().unwrap();
+                    assert_eq!(power.value, WrappedMeasurementValue::F64(230.5));
+                    assert_eq!(power.resource.kind(), "modbus_meter");
+                    assert_eq!(power.resource.id_display().to_string(), "wattmeter");
+                    let (key, value) = power.attributes().next().unwrap();
+                    assert_eq!((key, value.to_string()), ("phase", String::from("total")));
+                },
+            )
+            .test_source(
+                SourceName::from_str("modbus", "meter_wattmeter"),
+                move || {
+                    // energy: 1.5 kWh
+                    registers.lock().unwrap().insert((4, 0x0156), 0x3fc0);
+                },
+                |ctx| {
+                    let values: Vec<_> = ctx.measurements().iter().map(|m| m.value.clone()).collect();
+                    assert_eq!(
+                        values,
+                        vec![
+                            WrappedMeasurementValue::F64(230.5),
+                            WrappedMeasurementValue::F64(1_800_000.0)
+                        ]
+                    );
+                },
+            );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn invalid_meters() {
+        // no connection
+        let mut config = Config::default();
+        config.meters[0].tcp = None;
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+
+        // unknown unit
+        let mut config = Config::default();
+        config.meters[0].registers[0].unit = String::from("horsepower");
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+
+        // same metric with two units
+        let mut config = Config::default();
+        config.meters[0].registers[1].metric = String::from("modbus_power");
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+}
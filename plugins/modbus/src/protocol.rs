@@ -0,0 +1,222 @@
+//! Encoding and decoding of the Modbus frames that read registers.
+//!
+//! See the "MODBUS Application Protocol Specification" and the "MODBUS over Serial Line Specification".
+
+use std::{fmt, io::Read};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// The two kinds of 16-bit registers that contain the measurements.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Table {
+    /// Read-write registers, read with the function code 3.
+    #[default]
+    Holding,
+    /// Read-only registers, read with the function code 4.
+    Input,
+}
+
+impl Table {
+    fn function_code(self) -> u8 {
+        match self {
+            Table::Holding => 0x03,
+            Table::Input => 0x04,
+        }
+    }
+}
+
+/// Error returned by a Modbus server that cannot complete the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exception(pub u8);
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self.0 {
+            0x01 => "illegal function",
+            0x02 => "illegal data address",
+            0x03 => "illegal data value",
+            0x04 => "server device failure",
+            0x06 => "server device busy",
+            0x0a => "gateway path unavailable",
+            0x0b => "gateway target device failed to respond",
+            _ => "unknown exception",
+        };
+        write!(
+            f,
+            "the Modbus server returned the exception {:#04x} ({description})",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for Exception {}
+
+/// Builds the Protocol Data Unit (PDU) that reads `count` registers, starting at `address`.
+pub fn read_request(table: Table, address: u16, count: u16) -> Vec<u8> {
+    let mut pdu = vec![table.function_code()];
+    pdu.extend(address.to_be_bytes());
+    pdu.extend(count.to_be_bytes());
+    pdu
+}
+
+/// Parses the PDU of the response to [`read_request`].
+pub fn parse_read_response(pdu: &[u8], table: Table, count: u16) -> anyhow::Result<Vec<u16>> {
+    let function = table.function_code();
+    match pdu {
+        [f, code] if *f == function | 0x80 => Err(Exception(*code).into()),
+        [f, n, data @ ..]
+            if *f == function && usize::from(*n) == data.len() && data.len() == 2 * usize::from(count) =>
+        {
+            Ok(data.chunks_exact(2).map(|w| u16::from_be_bytes([w[0], w[1]])).collect())
+        }
+        _ => Err(anyhow!("invalid Modbus response: {pdu:02x?}")),
+    }
+}
+
+/// Builds a Modbus TCP frame: the MBAP header followed by the PDU.
+pub fn tcp_frame(transaction: u16, unit_id: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(7 + pdu.len());
+    frame.extend(transaction.to_be_bytes());
+    frame.extend([0, 0]); // protocol identifier of Modbus
+    frame.extend((pdu.len() as u16 + 1).to_be_bytes());
+    frame.push(unit_id);
+    frame.extend(pdu);
+    frame
+}
+
+/// Reads a Modbus TCP frame and returns its PDU.
+pub fn read_tcp_frame(stream: &mut impl Read, transaction: u16, unit_id: u8) -> anyhow::Result<Vec<u8>> {
+    let mut header = [0u8; 7];
+    stream.read_exact(&mut header).context("failed to read the response")?;
+    let length = usize::from(u16::from_be_bytes([header[4], header[5]]));
+    if length < 2 {
+        return Err(anyhow!("invalid Modbus TCP header: {header:02x?}"));
+    }
+    let mut pdu = vec![0u8; length - 1];
+    stream.read_exact(&mut pdu).context("failed to read the response")?;
+
+    let received = u16::from_be_bytes([header[0], header[1]]);
+    if received != transaction || header[6] != unit_id {
+        return Err(anyhow!(
+            "unexpected response: transaction {received} from unit {}, expected {transaction} from unit {unit_id}",
+            header[6]
+        ));
+    }
+    Ok(pdu)
+}
+
+/// Builds a Modbus RTU frame: the address of the server, the PDU and the CRC.
+pub fn rtu_frame(unit_id: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(3 + pdu.len());
+    frame.push(unit_id);
+    frame.extend(pdu);
+    frame.extend(crc16(&frame).to_le_bytes());
+    frame
+}
+
+/// Reads a Modbus RTU frame that responds to a read request, and returns its PDU.
+pub fn read_rtu_frame(port: &mut impl Read, unit_id: u8) -> anyhow::Result<Vec<u8>> {
+    let mut frame = vec![0u8; 3];
+    port.read_exact(&mut frame).context("failed to read the response")?;
+    // after the address and the function code: the exception code, or the number of bytes of data
+    let remaining = if frame[1] & 0x80 != 0 {
+        2
+    } else {
+        usize::from(frame[2]) + 2
+    };
+    let start = frame.len();
+    frame.resize(start + remaining, 0);
+    port.read_exact(&mut frame[start..])
+        .context("failed to read the response")?;
+
+    let (content, crc) = frame.split_at(frame.len() - 2);
+    if crc16(content).to_le_bytes() != crc {
+        return Err(anyhow!("invalid CRC in Modbus RTU frame: {frame:02x?}"));
+    }
+    if content[0] != unit_id {
+        return Err(anyhow!(
+            "unexpected response from unit {}, expected {unit_id}",
+            content[0]
+        ));
+    }
+    Ok(content[1..].to_vec())
+}
+
+/// Computes the CRC-16 of a Modbus RTU frame.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffff;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xa001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn requests() {
+        let pdu = read_request(Table::Input, 0x0034, 2);
+        assert_eq!(pdu, vec![0x04, 0x00, 0x34, 0x00, 0x02]);
+        assert_eq!(
+            tcp_frame(7, 1, &pdu),
+            vec![0x00, 0x07, 0x00, 0x00, 0x00, 0x06, 0x01, 0x04, 0x00, 0x34, 0x00, 0x02]
+        );
+        // example of the specification
+        let pdu = read_request(Table::Holding, 0, 10);
+        assert_eq!(rtu_frame(1, &pdu), vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x0a, 0xc5, 0xcd]);
+    }
+
+    #[test]
+    fn responses() {
+        let pdu = [0x03, 0x04, 0x43, 0x48, 0x00, 0x01];
+        assert_eq!(
+            parse_read_response(&pdu, Table::Holding, 2).unwrap(),
+            vec![0x4348, 0x0001]
+        );
+        // wrong number of registers
+        assert!(parse_read_response(&pdu, Table::Holding, 1).is_err());
+        // wrong function
+        assert!(parse_read_response(&pdu, Table::Input, 2).is_err());
+
+        let err = parse_read_response(&[0x84, 0x02], Table::Input, 2).unwrap_err();
+        assert_eq!(err.downcast_ref::<Exception>(), Some(&Exception(0x02)));
+    }
+
+    #[test]
+    fn tcp_response() {
+        let frame = [0x00, 0x07, 0x00, 0x00, 0x00, 0x05, 0x01, 0x04, 0x02, 0x00, 0x2a];
+        assert_eq!(
+            read_tcp_frame(&mut &frame[..], 7, 1).unwrap(),
+            vec![0x04, 0x02, 0x00, 0x2a]
+        );
+        assert!(read_tcp_frame(&mut &frame[..], 8, 1).is_err());
+        assert!(read_tcp_frame(&mut &frame[..5], 7, 1).is_err());
+    }
+
+    #[test]
+    fn rtu_response() {
+        let frame = rtu_frame(3, &[0x04, 0x02, 0x00, 0x2a]);
+        assert_eq!(
+            read_rtu_frame(&mut &frame[..], 3).unwrap(),
+            vec![0x04, 0x02, 0x00, 0x2a]
+        );
+        assert!(read_rtu_frame(&mut &frame[..], 4).is_err());
+
+        let exception = rtu_frame(3, &[0x84, 0x02]);
+        assert_eq!(read_rtu_frame(&mut &exception[..], 3).unwrap(), vec![0x84, 0x02]);
+
+        let mut corrupted = frame.clone();
+        corrupted[4] ^= 0xff;
+        let err = read_rtu_frame(&mut &corrupted[..], 3).unwrap_err();
+        assert!(err.to_string().contains("CRC"), "{err}");
+    }
+}
@@ -0,0 +1,156 @@
+//! Description of the registers of a meter, and decoding of their values.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::Table;
+
+/// A value of a meter, stored in one or several consecutive registers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Register {
+    /// Name of the Alumet metric.
+    pub metric: String,
+    /// Unit of the value, after scaling, such as `W` or `kW.h`.
+    pub unit: String,
+    #[serde(default)]
+    pub description: String,
+    /// Address of the first register.
+    pub address: u16,
+    #[serde(default)]
+    pub table: Table,
+    #[serde(rename = "type", default)]
+    pub data_type: DataType,
+    /// Order of the registers of a value that spans several registers.
+    #[serde(default)]
+    pub word_order: Endianness,
+    /// Order of the two bytes of each register.
+    #[serde(default)]
+    pub byte_order: Endianness,
+    /// Factor applied to the raw value.
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    /// If true, the register is a counter (such as the total energy) and the difference
+    /// between two measurements is reported instead of the value.
+    #[serde(default)]
+    pub counter: bool,
+    /// Attributes of the measurements, such as the phase of the power.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataType {
+    #[default]
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endianness {
+    /// Most significant first, the default of Modbus.
+    #[default]
+    Big,
+    Little,
+}
+
+impl DataType {
+    /// Number of 16-bit registers used by a value.
+    pub fn register_count(self) -> u16 {
+        match self {
+            DataType::U16 | DataType::I16 => 1,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+            DataType::U64 | DataType::I64 | DataType::F64 => 4,
+        }
+    }
+}
+
+impl Register {
+    /// Decodes the value of the registers, and scales it.
+    pub fn decode(&self, words: &[u16]) -> f64 {
+        let mut bytes = Vec::with_capacity(2 * words.len());
+        let ordered: Box<dyn Iterator<Item = &u16>> = match self.word_order {
+            Endianness::Big => Box::new(words.iter()),
+            Endianness::Little => Box::new(words.iter().rev()),
+        };
+        for word in ordered {
+            match self.byte_order {
+                Endianness::Big => bytes.extend(word.to_be_bytes()),
+                Endianness::Little => bytes.extend(word.to_le_bytes()),
+            }
+        }
+        // the bytes are now ordered from the most significant one
+        let value = match self.data_type {
+            DataType::U16 => f64::from(u16::from_be_bytes(bytes[..2].try_into().unwrap())),
+            DataType::I16 => f64::from(i16::from_be_bytes(bytes[..2].try_into().unwrap())),
+            DataType::U32 => f64::from(u32::from_be_bytes(bytes[..4].try_into().unwrap())),
+            DataType::I32 => f64::from(i32::from_be_bytes(bytes[..4].try_into().unwrap())),
+            DataType::U64 => u64::from_be_bytes(bytes[..8].try_into().unwrap()) as f64,
+            DataType::I64 => i64::from_be_bytes(bytes[..8].try_into().unwrap()) as f64,
+            DataType::F32 => f64::from(f32::from_be_bytes(bytes[..4].try_into().unwrap())),
+            DataType::F64 => f64::from_be_bytes(bytes[..8].try_into().unwrap()),
+        };
+        value * self.scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::protocol::Table;
+
+    use super::{DataType, Endianness, Register};
+
+    fn register(data_type: DataType, word_order: Endianness, byte_order: Endianness, scale: f64) -> Register {
+        Register {
+            metric: String::from("power"),
+            unit: String::from("W"),
+            description: String::new(),
+            address: 0,
+            table: Table::Input,
+            data_type,
+            word_order,
+            byte_order,
+            scale,
+            counter: false,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn decoding() {
+        use DataType::*;
+        use Endianness::*;
+
+        assert_eq!(register(U16, Big, Big, 0.1).decode(&[2305]), 230.5);
+        assert_eq!(register(I16, Big, Big, 1.0).decode(&[0xfff6]), -10.0);
+        assert_eq!(register(I16, Big, Little, 1.0).decode(&[0xf6ff]), -10.0);
+        assert_eq!(register(U32, Big, Big, 1.0).decode(&[0x0001, 0x0002]), 65538.0);
+        assert_eq!(register(U32, Little, Big, 1.0).decode(&[0x0002, 0x0001]), 65538.0);
+        assert_eq!(register(I32, Big, Big, 0.001).decode(&[0xffff, 0xfc18]), -1.0);
+        // 230.5 = 0x43668000
+        assert_eq!(register(F32, Big, Big, 1.0).decode(&[0x4366, 0x8000]), 230.5);
+        assert_eq!(register(F32, Little, Little, 1.0).decode(&[0x0080, 0x6643]), 230.5);
+        assert_eq!(register(U64, Big, Big, 1.0).decode(&[0, 0, 1, 0]), 65536.0);
+        assert_eq!(
+            register(I64, Big, Big, 1.0).decode(&[0xffff, 0xffff, 0xffff, 0xfffe]),
+            -2.0
+        );
+        // 1.5 = 0x3ff8000000000000
+        assert_eq!(register(F64, Big, Big, 2.0).decode(&[0x3ff8, 0, 0, 0]), 3.0);
+    }
+}
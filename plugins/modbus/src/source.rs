@@ -0,0 +1,113 @@
+use std::sync::{Arc, Mutex};
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::elements::error::PollError,
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+
+use crate::{register::Register, transport::Connection};
+
+/// Measurement source that reads the registers of a meter.
+pub struct ModbusSource {
+    /// Connection to the meter, shared with the other meters of the same bus or gateway.
+    connection: Arc<Mutex<Connection>>,
+    unit_id: u8,
+    resource: Resource,
+    registers: Vec<MeasuredRegister>,
+}
+
+/// A register and the metric of its measurements.
+pub struct MeasuredRegister {
+    register: Register,
+    metric: TypedMetricId<f64>,
+    attributes: Vec<(String, AttributeValue)>,
+    /// Previous value of a counter.
+    last: Option<f64>,
+}
+
+impl MeasuredRegister {
+    pub fn new(register: Register, metric: TypedMetricId<f64>) -> Self {
+        let attributes = register
+            .attributes
+            .iter()
+            .map(|(k, v)| (k.clone(), AttributeValue::String(v.clone())))
+            .collect();
+        Self {
+            register,
+            metric,
+            attributes,
+            last: None,
+        }
+    }
+}
+
+impl ModbusSource {
+    pub fn new(
+        connection: Arc<Mutex<Connection>>,
+        meter: String,
+        unit_id: u8,
+        registers: Vec<MeasuredRegister>,
+    ) -> Self {
+        Self {
+            connection,
+            unit_id,
+            resource: Resource::Custom {
+                kind: "modbus_meter".into(),
+                id: meter.into(),
+            },
+            registers,
+        }
+    }
+}
+
+impl alumet::pipeline::Source for ModbusSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        // no consumer, we just monitor the meter here
+        let consumer = ResourceConsumer::LocalMachine;
+
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|_| anyhow!("the connection to the meter is poisoned"))?;
+        for measured in &mut self.registers {
+            let register = &measured.register;
+            // The meter can be unreachable for a while, the next poll may work.
+            let words = connection
+                .read_registers(
+                    self.unit_id,
+                    register.table,
+                    register.address,
+                    register.data_type.register_count(),
+                )
+                .with_context(|| format!("failed to read the register of {}", register.metric))
+                .map_err(PollError::CanRetry)?;
+            let mut value = register.decode(&words);
+
+            if register.counter {
+                let previous = measured.last.replace(value);
+                match previous {
+                    Some(previous) if value >= previous => value -= previous,
+                    Some(_) => {
+                        log::debug!("The counter of {} has been reset.", register.metric);
+                        continue;
+                    }
+                    None => continue,
+                }
+            }
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    measured.metric,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    value,
+                )
+                .with_attr_vec(measured.attributes.clone()),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! Connections to the Modbus servers, over TCP or over a serial line (RTU).
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use nix::sys::termios::{self, BaudRate, ControlFlags, FlushArg, SetArg, SpecialCharacterIndices};
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::{self, Table};
+
+/// Settings of a serial line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SerialSettings {
+    /// Path to the serial port, such as `/dev/ttyUSB0`.
+    pub port: PathBuf,
+    pub baud_rate: u32,
+    pub parity: Parity,
+    /// 1 or 2.
+    pub stop_bits: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Where to find a Modbus server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    /// Address of a Modbus TCP server or gateway, such as `192.168.1.50:502`.
+    Tcp(String),
+    /// Serial line of Modbus RTU servers.
+    Rtu(SerialSettings),
+}
+
+impl Endpoint {
+    /// Identifies the connection, which is shared by all the servers of the endpoint.
+    pub fn key(&self) -> String {
+        match self {
+            Endpoint::Tcp(address) => format!("tcp:{address}"),
+            Endpoint::Rtu(serial) => format!("rtu:{}", serial.port.display()),
+        }
+    }
+}
+
+enum Stream {
+    Tcp(TcpStream),
+    Serial(File),
+}
+
+/// A connection to an endpoint, opened on the first request and reopened after an I/O error.
+pub struct Connection {
+    endpoint: Endpoint,
+    timeout: Duration,
+    stream: Option<Stream>,
+    transaction: u16,
+}
+
+impl Connection {
+    pub fn new(endpoint: Endpoint, timeout: Duration) -> Self {
+        Self {
+            endpoint,
+            timeout,
+            stream: None,
+            transaction: 0,
+        }
+    }
+
+    /// Reads `count` registers of the server `unit_id`.
+    pub fn read_registers(&mut self, unit_id: u8, table: Table, address: u16, count: u16) -> anyhow::Result<Vec<u16>> {
+        let pdu = protocol::read_request(table, address, count);
+        let response = self.exchange(unit_id, &pdu);
+        if let Err(e) = &response {
+            log::debug!("Closing the connection to {} after error: {e:#}", self.endpoint.key());
+            self.stream = None;
+        }
+        protocol::parse_read_response(&response?, table, count)
+            .with_context(|| format!("failed to read {count} {table:?} registers at {address} of unit {unit_id}"))
+    }
+
+    /// Sends a request and returns the PDU of the response.
+    fn exchange(&mut self, unit_id: u8, pdu: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => self.stream.insert(open(&self.endpoint, self.timeout)?),
+        };
+        match stream {
+            Stream::Tcp(tcp) => {
+                self.transaction = self.transaction.wrapping_add(1);
+                tcp.write_all(&protocol::tcp_frame(self.transaction, unit_id, pdu))
+                    .context("failed to send the request")?;
+                protocol::read_tcp_frame(tcp, self.transaction, unit_id)
+            }
+            Stream::Serial(port) => {
+                // discard the remains of a previous response that timed out
+                termios::tcflush(&*port, FlushArg::TCIFLUSH)?;
+                port.write_all(&protocol::rtu_frame(unit_id, pdu))
+                    .context("failed to send the request")?;
+                protocol::read_rtu_frame(&mut TimeoutReader(port), unit_id)
+            }
+        }
+    }
+}
+
+fn open(endpoint: &Endpoint, timeout: Duration) -> anyhow::Result<Stream> {
+    match endpoint {
+        Endpoint::Tcp(address) => {
+            let addr = address
+                .to_socket_addrs()
+                .with_context(|| format!("invalid address {address}"))?
+                .next()
+                .with_context(|| format!("no IP address for {address}"))?;
+            let stream = TcpStream::connect_timeout(&addr, timeout)
+                .with_context(|| format!("failed to connect to {address}"))?;
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            stream.set_nodelay(true)?;
+            Ok(Stream::Tcp(stream))
+        }
+        Endpoint::Rtu(serial) => Ok(Stream::Serial(open_serial(serial, timeout)?)),
+    }
+}
+
+/// Opens a serial port in raw mode, with the given settings and 8 data bits.
+fn open_serial(serial: &SerialSettings, timeout: Duration) -> anyhow::Result<File> {
+    let path = &serial.port;
+    let port = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(nix::libc::O_NOCTTY)
+        .open(path)
+        .with_context(|| format!("failed to open {path:?}"))?;
+
+    let mut settings = termios::tcgetattr(&port).with_context(|| format!("{path:?} is not a serial port"))?;
+    termios::cfmakeraw(&mut settings);
+    termios::cfsetspeed(&mut settings, baud_rate(serial.baud_rate)?)?;
+    let flags = &mut settings.control_flags;
+    flags.insert(ControlFlags::CLOCAL | ControlFlags::CREAD | ControlFlags::CS8);
+    flags.remove(ControlFlags::PARENB | ControlFlags::PARODD | ControlFlags::CSTOPB);
+    match serial.parity {
+        Parity::None => (),
+        Parity::Even => flags.insert(ControlFlags::PARENB),
+        Parity::Odd => flags.insert(ControlFlags::PARENB | ControlFlags::PARODD),
+    }
+    match serial.stop_bits {
+        1 => (),
+        2 => flags.insert(ControlFlags::CSTOPB),
+        n => return Err(anyhow!("invalid number of stop bits: {n}, expected 1 or 2")),
+    }
+    // a read returns when at least one byte is available, or when the timeout expires (in tenths of second)
+    let tenths = (timeout.as_millis() / 100).clamp(1, u8::MAX.into()) as u8;
+    settings.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+    settings.control_chars[SpecialCharacterIndices::VTIME as usize] = tenths;
+    termios::tcsetattr(&port, SetArg::TCSANOW, &settings)
+        .with_context(|| format!("failed to configure the serial port {path:?}"))?;
+    Ok(port)
+}
+
+pub fn baud_rate(rate: u32) -> anyhow::Result<BaudRate> {
+    Ok(match rate {
+        1200 => BaudRate::B1200,
+        2400 => BaudRate::B2400,
+        4800 => BaudRate::B4800,
+        9600 => BaudRate::B9600,
+        19200 => BaudRate::B19200,
+        38400 => BaudRate::B38400,
+        57600 => BaudRate::B57600,
+        115200 => BaudRate::B115200,
+        _ => return Err(anyhow!("unsupported baud rate {rate}")),
+    })
+}
+
+/// Turns the end of file, returned by the serial port when the timeout expires, into a timeout error.
+struct TimeoutReader<'a>(&'a mut File);
+
+impl Read for TimeoutReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.read(buf)? {
+            0 if !buf.is_empty() => Err(std::io::ErrorKind::TimedOut.into()),
+            n => Ok(n),
+        }
+    }
+}
@@ -4,7 +4,10 @@ use alumet::{
         Output,
         elements::{error::WriteError, output::OutputContext},
     },
-    plugin::rust::{AlumetPlugin, deserialize_config, serialize_config},
+    plugin::{
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
 };
 
 use mongodb::{
@@ -29,6 +32,10 @@ impl AlumetPlugin for MongoDbPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
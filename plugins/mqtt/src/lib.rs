@@ -0,0 +1,148 @@
+mod output;
+mod payload;
+mod tls;
+mod topic;
+
+use std::{path::PathBuf, time::Duration};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::Context;
+use rumqttc::{MqttOptions, Transport};
+use serde::{Deserialize, Serialize};
+
+use crate::output::MqttOutput;
+pub use crate::payload::{BINARY_PAYLOAD_LEN, PayloadFormat};
+use crate::topic::TopicTemplate;
+
+pub struct MqttPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for MqttPlugin {
+    fn name() -> &'static str {
+        "mqtt"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(MqttPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let topic = TopicTemplate::parse(&config.topic, &hostname)?;
+        let qos =
+            rumqttc::qos(config.qos).with_context(|| format!("invalid qos {}, expected 0, 1 or 2", config.qos))?;
+
+        let client_id = config.client_id.clone().unwrap_or_else(|| format!("alumet-{hostname}"));
+        let mut options = MqttOptions::new(client_id, &config.host, config.port);
+        options.set_keep_alive(config.keep_alive);
+        if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+            options.set_credentials(user, pass);
+        }
+        if config.tls.enabled {
+            let tls = tls::client_configuration(&config.tls).context("invalid TLS configuration")?;
+            options.set_transport(Transport::tls_with_config(tls));
+        }
+
+        let output = MqttOutput::new(
+            options,
+            config.queue_capacity,
+            topic,
+            qos,
+            config.retain,
+            config.payload_format,
+        )?;
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the MQTT broker.
+    pub host: String,
+    pub port: u16,
+    /// Identifier of the client, which must be unique on the broker. Defaults to `alumet-<hostname>`.
+    pub client_id: Option<String>,
+    /// Name of the machine in the topics. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Template of the topic of each measurement.
+    ///
+    /// See [`MeasurementPlaceholder`](alumet::template::MeasurementPlaceholder) for the available placeholders,
+    /// in addition to `{hostname}`.
+    pub topic: String,
+    /// Quality of service: 0 (at most once), 1 (at least once) or 2 (exactly once).
+    pub qos: u8,
+    /// Whether the broker keeps the last message of each topic for new subscribers.
+    pub retain: bool,
+    /// Format of the messages: `"json"` or `"binary"`.
+    pub payload_format: PayloadFormat,
+    /// Login and password, both optional.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(with = "humantime_serde")]
+    pub keep_alive: Duration,
+    /// Maximum number of messages waiting to be sent.
+    /// When the broker is unreachable for too long, the new measurements are dropped.
+    pub queue_capacity: usize,
+    #[serde(default)]
+    pub tls: TlsConfig,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM file of the certificate authorities to trust. Defaults to the certificates of the system.
+    pub ca_cert: Option<PathBuf>,
+    /// PEM files of the certificate and private key of the client, for mutual TLS.
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: 1883,
+            client_id: None,
+            hostname: None,
+            topic: String::from("alumet/{hostname}/{metric}"),
+            qos: 0,
+            retain: false,
+            payload_format: PayloadFormat::Json,
+            username: None,
+            password: None,
+            keep_alive: Duration::from_secs(30),
+            queue_capacity: 1000,
+            tls: TlsConfig::default(),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use anyhow::{Context, anyhow};
+use rumqttc::{Client, ClientError, Connection, Event, MqttOptions, Outgoing, Packet, QoS};
+
+use crate::{payload::PayloadFormat, topic::TopicTemplate};
+
+/// Delay before reconnecting to the broker after a connection error.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+pub struct MqttOutput {
+    client: Client,
+    /// Thread that drives the connection to the broker, see [`run_connection`].
+    connection: Option<JoinHandle<()>>,
+    stopping: Arc<AtomicBool>,
+    topic: TopicTemplate,
+    qos: QoS,
+    retain: bool,
+    payload: PayloadFormat,
+}
+
+impl MqttOutput {
+    /// Creates the output and starts connecting to the broker in the background.
+    ///
+    /// At most `queue_capacity` messages wait to be sent to the broker.
+    pub fn new(
+        options: MqttOptions,
+        queue_capacity: usize,
+        topic: TopicTemplate,
+        qos: QoS,
+        retain: bool,
+        payload: PayloadFormat,
+    ) -> anyhow::Result<Self> {
+        let (client, connection) = Client::new(options, queue_capacity);
+        let stopping = Arc::new(AtomicBool::new(false));
+        let stopping_clone = stopping.clone();
+        let connection = std::thread::Builder::new()
+            .name(String::from("mqtt-connection"))
+            .spawn(move || run_connection(connection, stopping_clone))
+            .context("failed to spawn the MQTT connection thread")?;
+        Ok(Self {
+            client,
+            connection: Some(connection),
+            stopping,
+            topic,
+            qos,
+            retain,
+            payload,
+        })
+    }
+}
+
+/// Polls the connection until the client disconnects.
+///
+/// The event loop of rumqttc sends the queued messages, and reconnects to the broker when needed.
+fn run_connection(mut connection: Connection, stopping: Arc<AtomicBool>) {
+    for event in connection.iter() {
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => log::info!("Connected to the MQTT broker."),
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+            Ok(_) => (),
+            Err(_) if stopping.load(Ordering::Relaxed) => break,
+            Err(e) => {
+                log::warn!("MQTT connection error, reconnecting in {RECONNECT_DELAY:?}: {e}");
+                std::thread::sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+}
+
+impl alumet::pipeline::Output for MqttOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut dropped = 0;
+        for m in measurements {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            let topic = self.topic.render(m, metric);
+            let payload = self.payload.encode(m, metric);
+            match self.client.try_publish(topic, self.qos, self.retain, payload) {
+                Ok(()) => (),
+                // the queue is full, probably because the broker is unreachable
+                Err(ClientError::TryRequest(_)) => dropped += 1,
+                Err(e) => return Err(WriteError::Fatal(e.into())),
+            }
+        }
+        if dropped > 0 {
+            return Err(WriteError::CanRetry(anyhow!(
+                "{dropped} measurements have been dropped because the queue of MQTT messages is full"
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MqttOutput {
+    fn drop(&mut self) {
+        // Send the queued messages, then disconnect.
+        // If the broker is unreachable, the connection thread stops at the next error.
+        self.stopping.store(true, Ordering::Relaxed);
+        if let Err(e) = self.client.disconnect() {
+            log::debug!("MQTT connection already closed: {e}");
+        }
+        if let Some(connection) = self.connection.take()
+            && connection.join().is_err()
+        {
+            log::error!("The MQTT connection thread panicked.");
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Encoding of the measurements in the payload of the MQTT messages.
+
+use std::{collections::BTreeMap, time::SystemTime};
+
+use alumet::{
+    json,
+    measurement::{MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// Format of the payload, one measurement per message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadFormat {
+    /// A JSON object with the value, the timestamp, the resource, the consumer and the attributes.
+    #[default]
+    Json,
+    /// A compact binary record with the timestamp and the value, see [`encode_binary`].
+    Binary,
+}
+
+impl PayloadFormat {
+    pub fn encode(&self, m: &MeasurementPoint, metric: &Metric) -> Vec<u8> {
+        match self {
+            PayloadFormat::Json => encode_json(m, metric),
+            PayloadFormat::Binary => encode_binary(m).to_vec(),
+        }
+    }
+}
+
+/// The JSON payload of a measurement.
+///
+/// The fields are serialized in this order, and the attributes are sorted by key, whatever the features of `serde_json`.
+#[derive(Serialize)]
+struct JsonPayload<'a> {
+    metric: &'a str,
+    timestamp: String,
+    value: Value,
+    unit: String,
+    resource_kind: &'a str,
+    resource_id: String,
+    consumer_kind: &'a str,
+    consumer_id: String,
+    attributes: BTreeMap<&'a str, Value>,
+}
+
+fn encode_json(m: &MeasurementPoint, metric: &Metric) -> Vec<u8> {
+    let value = match m.value {
+        WrappedMeasurementValue::F64(v) => json!(v),
+        WrappedMeasurementValue::U64(v) => json!(v),
+    };
+    let payload = JsonPayload {
+        metric: &metric.name,
+        timestamp: humantime::format_rfc3339_nanos(SystemTime::from(m.timestamp)).to_string(),
+        value,
+        unit: metric.unit.unique_name(),
+        resource_kind: m.resource.kind(),
+        resource_id: m.resource.id_display().to_string(),
+        consumer_kind: m.consumer.kind(),
+        consumer_id: m.consumer.id_display().to_string(),
+        attributes: json::attributes(m),
+    };
+    serde_json::to_vec(&payload).expect("the payload should always be serializable")
+}
+
+/// Size of a binary payload, in bytes.
+pub const BINARY_PAYLOAD_LEN: usize = 21;
+
+/// Encodes the timestamp and the value of a measurement in 21 bytes, in big-endian order:
+///
+/// | bytes   | content                                                  |
+/// |---------|----------------------------------------------------------|
+/// | 0..8    | seconds since the Unix epoch (u64)                       |
+/// | 8..12   | nanoseconds in the second (u32)                          |
+/// | 12      | type of the value: 0 for u64, 1 for f64                  |
+/// | 13..21  | value (u64, or the bits of the f64)                      |
+///
+/// The rest of the measurement (metric, resource, consumer) is identified by the topic.
+pub fn encode_binary(m: &MeasurementPoint) -> [u8; BINARY_PAYLOAD_LEN] {
+    let (secs, nanos) = m.timestamp.to_unix_timestamp();
+    let (kind, bits) = match m.value {
+        WrappedMeasurementValue::U64(v) => (0u8, v),
+        WrappedMeasurementValue::F64(v) => (1u8, v.to_bits()),
+    };
+    let mut res = [0u8; BINARY_PAYLOAD_LEN];
+    res[0..8].copy_from_slice(&secs.to_be_bytes());
+    res[8..12].copy_from_slice(&nanos.to_be_bytes());
+    res[12] = kind;
+    res[13..21].copy_from_slice(&bits.to_be_bytes());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::encode_binary;
+
+    #[test]
+    fn binary() {
+        let timestamp = Timestamp::from(UNIX_EPOCH + Duration::new(1_700_000_000, 5));
+        let point = |value| {
+            MeasurementPoint::new_untyped(
+                timestamp,
+                RawMetricId::from_u64(0),
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                value,
+            )
+        };
+
+        let bytes = encode_binary(&point(WrappedMeasurementValue::U64(42)));
+        assert_eq!(u64::from_be_bytes(bytes[0..8].try_into().unwrap()), 1_700_000_000);
+        assert_eq!(u32::from_be_bytes(bytes[8..12].try_into().unwrap()), 5);
+        assert_eq!(bytes[12], 0);
+        assert_eq!(u64::from_be_bytes(bytes[13..21].try_into().unwrap()), 42);
+
+        let bytes = encode_binary(&point(WrappedMeasurementValue::F64(12.5)));
+        assert_eq!(bytes[12], 1);
+        assert_eq!(f64::from_be_bytes(bytes[13..21].try_into().unwrap()), 12.5);
+    }
+}
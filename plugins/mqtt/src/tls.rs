@@ -0,0 +1,63 @@
+//! TLS configuration of the connection to the broker.
+
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+
+use anyhow::{Context, anyhow};
+use rumqttc::TlsConfiguration;
+use rustls::{ClientConfig, RootCertStore, pki_types::CertificateDer, pki_types::PrivateKeyDer};
+
+use crate::TlsConfig;
+
+/// Builds the TLS configuration of the client.
+///
+/// Without `ca_cert`, the certificate authorities of the system are trusted.
+pub fn client_configuration(tls: &TlsConfig) -> anyhow::Result<TlsConfiguration> {
+    let mut roots = RootCertStore::empty();
+    match &tls.ca_cert {
+        Some(path) => {
+            for cert in read_certs(path)? {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("invalid CA certificate in {}", path.display()))?;
+            }
+        }
+        None => {
+            let native = rustls_native_certs::load_native_certs();
+            for e in native.errors {
+                log::warn!("Failed to load a certificate of the system: {e}");
+            }
+            roots.add_parsable_certificates(native.certs);
+        }
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    let config = match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(read_certs(cert)?, read_key(key)?)
+            .context("invalid client certificate or key")?,
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err(anyhow!("client_cert and client_key must be set together")),
+    };
+    Ok(TlsConfiguration::Rustls(Arc::new(config)))
+}
+
+fn read_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to read the certificates in {}", path.display()))?;
+    if certs.is_empty() {
+        return Err(anyhow!("no certificate found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("failed to read the private key in {}", path.display()))?
+        .ok_or_else(|| anyhow!("no private key found in {}", path.display()))
+}
@@ -0,0 +1,80 @@
+//! Topic templates, such as `alumet/{hostname}/{metric}`.
+
+use alumet::{measurement::MeasurementPoint, metrics::Metric, template::MeasurementTemplate};
+use anyhow::{Context, anyhow};
+
+/// A parsed topic template.
+#[derive(Debug, PartialEq)]
+pub struct TopicTemplate(MeasurementTemplate);
+
+impl TopicTemplate {
+    /// Parses a template.
+    ///
+    /// The placeholders are replaced by the corresponding values for each measurement.
+    /// The hostname is resolved here, since it does not change.
+    pub fn parse(template: &str, hostname: &str) -> anyhow::Result<Self> {
+        let parsed = MeasurementTemplate::parse_measurement(template, &sanitize(hostname))
+            .with_context(|| format!("invalid topic template {template:?}"))?;
+        if parsed.literal().contains(['+', '#']) {
+            return Err(anyhow!("wildcards are not allowed in topic template {template:?}"));
+        }
+        if parsed.is_empty() {
+            return Err(anyhow!("the topic template is empty"));
+        }
+        Ok(Self(parsed))
+    }
+
+    /// Returns the topic of a measurement.
+    pub fn render(&self, m: &MeasurementPoint, metric: &Metric) -> String {
+        self.0.render_measurement(m, metric, sanitize)
+    }
+}
+
+/// Replaces the characters that have a special meaning in MQTT topics.
+///
+/// A value must not create new levels (`/`) or wildcards (`+`, `#`) in the topic.
+fn sanitize(value: &str) -> String {
+    value.replace(['/', '+', '#'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        resources::ResourceConsumer,
+        test::fixture::{metric, point},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::TopicTemplate;
+
+    #[test]
+    fn render() {
+        let template = TopicTemplate::parse("alumet/{hostname}/{metric}", "node-1").unwrap();
+        assert_eq!(
+            template.render(&point(ResourceConsumer::LocalMachine), &metric()),
+            "alumet/node-1/rapl_consumed_energy"
+        );
+
+        let template = TopicTemplate::parse(
+            "{hostname}/{resource_kind}/{resource_id}/{consumer_kind}/{consumer_id}",
+            "a/b",
+        )
+        .unwrap();
+        let consumer = ResourceConsumer::ControlGroup {
+            path: "/system.slice/test.service".into(),
+        };
+        assert_eq!(
+            template.render(&point(consumer), &metric()),
+            "a_b/cpu_package/1/cgroup/_system.slice_test.service"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(TopicTemplate::parse("alumet/{host}", "node").is_err());
+        assert!(TopicTemplate::parse("alumet/{metric", "node").is_err());
+        assert!(TopicTemplate::parse("alumet/+/{metric}", "node").is_err());
+        assert!(TopicTemplate::parse("alumet/#", "node").is_err());
+        assert!(TopicTemplate::parse("", "node").is_err());
+    }
+}
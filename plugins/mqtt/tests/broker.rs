@@ -0,0 +1,73 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread::JoinHandle,
+};
+
+use bytes::BytesMut;
+use rumqttc::{ConnAck, ConnectReturnCode, Packet, PubAck, Publish, QoS, mqttbytes::Error};
+
+const MAX_PACKET_SIZE: usize = 1 << 20;
+
+/// A minimal MQTT broker that accepts one client and records the messages that it publishes.
+pub struct FakeBroker {
+    port: u16,
+    thread: JoinHandle<Vec<Publish>>,
+}
+
+impl FakeBroker {
+    pub fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let thread = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("the client should connect");
+            handle_client(stream)
+        });
+        Ok(Self { port, thread })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Waits for the client to disconnect, and returns the messages that it has published.
+    pub fn published(self) -> Vec<Publish> {
+        self.thread.join().expect("the broker should not panic")
+    }
+}
+
+fn handle_client(mut stream: TcpStream) -> Vec<Publish> {
+    let mut published = Vec::new();
+    let mut buf = BytesMut::new();
+    loop {
+        let packet = match Packet::read(&mut buf, MAX_PACKET_SIZE) {
+            Ok(packet) => packet,
+            Err(Error::InsufficientBytes(_)) => {
+                let mut chunk = [0u8; 4096];
+                let n = stream.read(&mut chunk).unwrap();
+                if n == 0 {
+                    return published;
+                }
+                buf.extend_from_slice(&chunk[..n]);
+                continue;
+            }
+            Err(e) => panic!("invalid packet: {e:?}"),
+        };
+        let response = match packet {
+            Packet::Connect(_) => Some(Packet::ConnAck(ConnAck::new(ConnectReturnCode::Success, false))),
+            Packet::Publish(publish) => {
+                let ack = (publish.qos == QoS::AtLeastOnce).then(|| Packet::PubAck(PubAck::new(publish.pkid)));
+                published.push(publish);
+                ack
+            }
+            Packet::PingReq => Some(Packet::PingResp),
+            Packet::Disconnect => return published,
+            _ => None,
+        };
+        if let Some(response) = response {
+            let mut out = BytesMut::new();
+            response.write(&mut out, MAX_PACKET_SIZE).unwrap();
+            stream.write_all(&out).unwrap();
+        }
+    }
+}
@@ -0,0 +1,102 @@
+mod broker;
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_mqtt::{BINARY_PAYLOAD_LEN, Config, MqttPlugin, PayloadFormat};
+use pretty_assertions::assert_eq;
+use rumqttc::QoS;
+
+use crate::broker::FakeBroker;
+
+fn config(broker: &FakeBroker) -> Config {
+    Config {
+        host: String::from("127.0.0.1"),
+        port: broker.port(),
+        hostname: Some(String::from("node-1")),
+        ..Config::default()
+    }
+}
+
+#[test]
+fn publish_json() -> anyhow::Result<()> {
+    let broker = FakeBroker::start()?;
+    let mut harness = PluginHarness::<MqttPlugin>::start(serialize_config(config(&broker))?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let mut actual = String::new();
+    for message in broker.published() {
+        assert_eq!(message.qos, QoS::AtMostOnce);
+        assert!(!message.retain);
+        let payload = std::str::from_utf8(&message.payload)?;
+        actual.push_str(&format!("{} {payload}\n", message.topic));
+    }
+    assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.txt"), actual);
+    Ok(())
+}
+
+#[test]
+fn publish_binary_with_qos() -> anyhow::Result<()> {
+    let broker = FakeBroker::start()?;
+    let config = Config {
+        topic: String::from("energy/{resource_kind}/{resource_id}"),
+        qos: 1,
+        retain: true,
+        payload_format: PayloadFormat::Binary,
+        ..config(&broker)
+    };
+    let mut harness = PluginHarness::<MqttPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let messages = broker.published();
+    let topics: Vec<&str> = messages.iter().map(|m| m.topic.as_str()).collect();
+    assert_eq!(
+        topics,
+        vec![
+            "energy/cpu_package/0",
+            "energy/dram/0",
+            "energy/local_machine/",
+            "energy/gpu/0000:01:00.0",
+            "energy/cpu_core/3",
+        ]
+    );
+    for m in &messages {
+        assert_eq!(m.qos, QoS::AtLeastOnce);
+        assert!(m.retain);
+        assert_eq!(m.payload.len(), BINARY_PAYLOAD_LEN);
+    }
+    // first measurement: 12.5 J at SampleMeasurements::START
+    let first = &messages[0].payload;
+    assert_eq!(
+        u64::from_be_bytes(first[0..8].try_into()?),
+        SampleMeasurements::START.as_secs()
+    );
+    assert_eq!(first[12], 1);
+    assert_eq!(f64::from_be_bytes(first[13..21].try_into()?), 12.5);
+    Ok(())
+}
+
+#[test]
+fn invalid_config() -> anyhow::Result<()> {
+    let broker = FakeBroker::start()?;
+    let config = Config {
+        qos: 3,
+        ..config(&broker)
+    };
+    assert!(PluginHarness::<MqttPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
@@ -3,12 +3,13 @@ mod source;
 
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use alumet::{
     pipeline::elements::source::trigger::TriggerSpec,
     plugin::{
         ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
 };
@@ -44,6 +45,10 @@ impl AlumetPlugin for JetsonPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Filesystem(PathBuf::from("/sys/bus/i2c/drivers"))])
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         let config = serialize_config(Config::default())?;
         Ok(Some(config))
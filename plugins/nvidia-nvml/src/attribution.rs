@@ -0,0 +1,81 @@
+//! Attribution of the GPU power and energy to the consumers that use the GPU.
+
+use std::collections::BTreeMap;
+
+/// Power and energy of a GPU (or of a part of it) during the last measurement interval.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Consumption {
+    /// Instantaneous power in milliWatts.
+    pub power: Option<f64>,
+    /// Energy consumed since the previous measurement in milliJoules.
+    pub energy: Option<f64>,
+}
+
+impl Consumption {
+    pub fn is_empty(&self) -> bool {
+        self.power.is_none() && self.energy.is_none()
+    }
+
+    /// Returns a fraction of this consumption.
+    pub fn scaled(&self, fraction: f64) -> Self {
+        Self {
+            power: self.power.map(|p| p * fraction),
+            energy: self.energy.map(|e| e * fraction),
+        }
+    }
+}
+
+/// Computes the share of each consumer, proportionally to its weight.
+///
+/// The weights of the same consumer are added together, because NVML can return several samples per process.
+/// Returns nothing if the sum of the weights is zero: the consumption cannot be attributed.
+pub fn shares<K: Ord>(weights: impl IntoIterator<Item = (K, f64)>) -> Vec<(K, f64)> {
+    let mut by_consumer = BTreeMap::new();
+    for (consumer, weight) in weights {
+        *by_consumer.entry(consumer).or_insert(0.0) += weight;
+    }
+    let total: f64 = by_consumer.values().sum();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+    by_consumer.into_iter().map(|(k, w)| (k, w / total)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn proportional_shares() {
+        assert_eq!(
+            shares([(1234, 60.0), (42, 20.0), (1234, 20.0)]),
+            vec![(42, 0.2), (1234, 0.8)]
+        );
+        assert_eq!(shares([("a", 1.0), ("b", 1.0)]), vec![("a", 0.5), ("b", 0.5)]);
+    }
+
+    #[test]
+    fn idle_gpu() {
+        assert_eq!(shares([(1234, 0.0), (42, 0.0)]), vec![]);
+        assert_eq!(shares(Vec::<(u32, f64)>::new()), vec![]);
+    }
+
+    #[test]
+    fn scaled_consumption() {
+        let consumption = Consumption {
+            power: Some(200_000.0),
+            energy: None,
+        };
+        assert!(!consumption.is_empty());
+        assert_eq!(
+            consumption.scaled(0.25),
+            Consumption {
+                power: Some(50_000.0),
+                energy: None
+            }
+        );
+        assert!(Consumption::default().is_empty());
+    }
+}
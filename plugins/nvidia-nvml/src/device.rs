@@ -1,9 +1,12 @@
 use anyhow::Context;
 use nvml_wrapper::{Device, Nvml, error::NvmlError};
-use nvml_wrapper_sys::bindings::nvmlDevice_t;
+use nvml_wrapper_sys::bindings::{NvmlLib, nvmlDevice_t};
 use std::sync::Arc;
 
-use super::features::OptionalFeatures;
+use super::{
+    features::OptionalFeatures,
+    nvml_ext::{MigInstance, mig_instances},
+};
 
 /// Detected NVML devices.
 pub struct NvmlDevices {
@@ -21,6 +24,8 @@ pub struct ManagedDevice {
     pub features: OptionalFeatures,
     /// PCI bus ID of the device.
     pub bus_id: String,
+    /// The MIG instances of the device, if MIG is enabled.
+    pub mig_instances: Vec<MigInstance>,
 }
 
 /// Statistics about the device detection.
@@ -39,6 +44,15 @@ impl NvmlDevices {
             "NVML initialization failed, please check your driver (do you have a dekstop/server NVidia GPU?)",
         )?);
 
+        // nvml_wrapper gives no access to its library, open it again to call the functions that it lacks.
+        let raw_lib = match unsafe { NvmlLib::new("libnvidia-ml.so") } {
+            Ok(lib) => Some(lib),
+            Err(e) => {
+                log::warn!("Failed to load the NVML library for the MIG functions, MIG instances will be ignored: {e}");
+                None
+            }
+        };
+
         let count = nvml.device_count().context("could not get device count")?;
         let mut devices = Vec::with_capacity(count as usize);
         for i in 0..count {
@@ -55,11 +69,21 @@ impl NvmlDevices {
                         let bus_id = pci_info
                             .with_context(|| format!("failed to get the bus ID of device {i}"))?
                             .bus_id;
+                        let mig_instances = match &raw_lib {
+                            Some(raw_lib) => mig_instances(raw_lib, handle).unwrap_or_else(|e| {
+                                log::warn!(
+                                    "Failed to list the MIG instances of GPU device {i}, they will be ignored: {e}"
+                                );
+                                Vec::new()
+                            }),
+                            None => Vec::new(),
+                        };
                         let d = ManagedDevice {
                             lib,
                             handle,
                             features,
                             bus_id,
+                            mig_instances,
                         };
                         Some(d)
                     } else {
@@ -141,6 +165,7 @@ mod tests {
             handle,
             features: OptionalFeatures::detect_on(&device).expect("Detect features"),
             bus_id,
+            mig_instances: Vec::new(),
         };
 
         let wrapped_device = managed_device.as_wrapper();
@@ -1,4 +1,8 @@
-use nvml_wrapper::{Device, enum_wrappers::device::TemperatureSensor, error::NvmlError};
+use nvml_wrapper::{
+    Device,
+    enum_wrappers::device::{Clock, TemperatureSensor},
+    error::NvmlError,
+};
 use std::fmt::Display;
 
 use crate::nvml_ext::DeviceExt;
@@ -20,6 +24,8 @@ pub struct OptionalFeatures {
     pub instant_power: bool,
     /// GPU temperature.
     pub temperature_gpu: bool,
+    /// Current frequencies of the graphics, SM and memory clocks.
+    pub clocks: bool,
     /// GPU rate utilization.
     pub major_utilization: bool,
     /// GPU video decoding property.
@@ -32,6 +38,8 @@ pub struct OptionalFeatures {
     pub running_compute_processes: AvailableVersion,
     /// Relevant currently running graphical processes data.
     pub running_graphics_processes: AvailableVersion,
+    /// Per-process accounting, which must be enabled by the administrator.
+    pub accounting: bool,
 }
 
 impl OptionalFeatures {
@@ -41,12 +49,14 @@ impl OptionalFeatures {
             total_energy_consumption: is_supported(device.total_energy_consumption())?,
             instant_power: is_supported(device.power_usage())?,
             temperature_gpu: is_supported(device.temperature(TemperatureSensor::Gpu))?,
+            clocks: is_supported(device.clock_info(Clock::SM))?,
             major_utilization: is_supported(device.utilization_rates())?,
             decoder_utilization: is_supported(device.decoder_utilization())?,
             encoder_utilization: is_supported(device.encoder_utilization())?,
             process_utilization_stats: is_supported(device.fixed_process_utilization_stats(0))?,
             running_compute_processes: check_running_compute_processes(device)?,
             running_graphics_processes: check_running_graphics_processes(device)?,
+            accounting: check_accounting(device)?,
         })
     }
 
@@ -61,6 +71,7 @@ impl OptionalFeatures {
             || self.decoder_utilization
             || self.encoder_utilization
             || self.temperature_gpu
+            || self.clocks
             || self.running_compute_processes != AvailableVersion::None
             || self.running_graphics_processes != AvailableVersion::None
     }
@@ -90,6 +101,9 @@ impl Display for OptionalFeatures {
         if self.temperature_gpu {
             available.push("temperature_gpu");
         }
+        if self.clocks {
+            available.push("clocks");
+        }
         match self.running_compute_processes {
             AvailableVersion::Latest => available.push("running_compute_processes(latest)"),
             AvailableVersion::V2 => available.push("running_compute_processes(v2)"),
@@ -100,6 +114,9 @@ impl Display for OptionalFeatures {
             AvailableVersion::V2 => available.push("running_graphics_processes(v2)"),
             AvailableVersion::None => (),
         };
+        if self.accounting {
+            available.push("accounting");
+        }
         write!(f, "{}", available.join(", "))
     }
 }
@@ -128,6 +145,14 @@ fn check_running_graphics_processes(device: &Device) -> Result<AvailableVersion,
     }
 }
 
+/// Checks whether the per-process accounting is enabled on this NVML device.
+fn check_accounting(device: &Device) -> Result<bool, NvmlError> {
+    match device.is_accounting_enabled() {
+        Err(NvmlError::NotSupported) => Ok(false),
+        res => res,
+    }
+}
+
 /// Checks if a feature is supported by the available GPU by inspecting the return type of an NVML function.
 ///
 /// # Example
@@ -160,12 +185,14 @@ mod tests {
             encoder_utilization: true,
             process_utilization_stats: true,
             temperature_gpu: true,
+            clocks: true,
             running_compute_processes: AvailableVersion::Latest,
             running_graphics_processes: AvailableVersion::Latest,
+            accounting: true,
         };
         assert_eq!(
             format!("{}", features),
-            "total_energy_consumption, instant_power, major_utilization, decoder_utilization, encoder_utilization, process_utilization_stats, temperature_gpu, running_compute_processes(latest), running_graphics_processes(latest)"
+            "total_energy_consumption, instant_power, major_utilization, decoder_utilization, encoder_utilization, process_utilization_stats, temperature_gpu, clocks, running_compute_processes(latest), running_graphics_processes(latest), accounting"
         );
     }
 
@@ -181,8 +208,10 @@ mod tests {
             encoder_utilization: true,
             process_utilization_stats: false,
             temperature_gpu: true,
+            clocks: false,
             running_compute_processes: AvailableVersion::None,
             running_graphics_processes: AvailableVersion::None,
+            accounting: false,
         };
         assert_eq!(
             format!("{}", features),
@@ -202,8 +231,10 @@ mod tests {
             encoder_utilization: false,
             process_utilization_stats: false,
             temperature_gpu: false,
+            clocks: false,
             running_compute_processes: AvailableVersion::V2,
             running_graphics_processes: AvailableVersion::V2,
+            accounting: false,
         };
         assert_eq!(
             format!("{}", features),
@@ -233,8 +264,10 @@ mod tests {
             encoder_utilization: false,
             process_utilization_stats: false,
             temperature_gpu: false,
+            clocks: false,
             running_compute_processes: AvailableVersion::None,
             running_graphics_processes: AvailableVersion::None,
+            accounting: false,
         };
         assert!(!features.has_any());
     }
@@ -1,15 +1,17 @@
 use anyhow::{Context, anyhow};
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use alumet::{
     pipeline::elements::source::trigger::TriggerSpec,
     plugin::{
         ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
 };
 
+mod attribution;
 mod device;
 mod features;
 mod metrics;
@@ -29,6 +31,11 @@ impl AlumetPlugin for NvmlPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        // the device files of the GPUs, opened by NVML
+        Some(vec![Capability::Filesystem(PathBuf::from("/dev"))])
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         let config = serialize_config(Config::default())?;
         Ok(Some(config))
@@ -66,6 +73,13 @@ impl AlumetPlugin for NvmlPlugin {
                     device_name,
                     device.features
                 );
+                if !device.mig_instances.is_empty() {
+                    log::info!(
+                        "NVML device {} is split into {} MIG instances.",
+                        device.bus_id,
+                        device.mig_instances.len()
+                    );
+                }
             }
         }
         let metrics = metrics::Metrics::new(alumet)?;
@@ -73,7 +87,7 @@ impl AlumetPlugin for NvmlPlugin {
         for maybe_device in nvml.devices {
             if let Some(device) = maybe_device {
                 let source_name = format!("device_{}", device.bus_id);
-                let source = probe::NvmlSource::new(device, metrics.clone())?;
+                let source = probe::NvmlSource::new(device, metrics.clone(), self.config.attribution)?;
                 let trigger = TriggerSpec::builder(self.config.poll_interval)
                     .flush_interval(self.config.flush_interval)
                     .build()?;
@@ -104,6 +118,10 @@ struct Config {
     /// If `skip_failed_devices = true`, the first failure will make the plugin's startup fail.
     #[serde(default = "default_true")]
     skip_failed_devices: bool,
+
+    /// Split the power and energy of each GPU between the processes that use it, or between its MIG instances.
+    #[serde(default = "default_true")]
+    attribution: bool,
 }
 
 fn default_true() -> bool {
@@ -116,6 +134,7 @@ impl Default for Config {
             poll_interval: Duration::from_secs(1), // 1Hz
             flush_interval: Duration::from_secs(5),
             skip_failed_devices: true,
+            attribution: true,
         }
     }
 }
@@ -1,5 +1,5 @@
 use alumet::{
-    metrics::{TypedMetricId, error::MetricCreationError},
+    metrics::{TypedMetricId, def::tag, error::MetricCreationError},
     plugin::AlumetPluginStart,
     units::{PrefixedUnit, Unit},
 };
@@ -13,6 +13,12 @@ pub struct Metrics {
     pub instant_power: TypedMetricId<u64>,
     /// GPU temperature in °C
     pub temperature_gpu: TypedMetricId<u64>,
+    /// Frequency of the graphics clock in MHz.
+    pub graphics_clock: TypedMetricId<u64>,
+    /// Frequency of the streaming multiprocessors clock in MHz.
+    pub sm_clock: TypedMetricId<u64>,
+    /// Frequency of the memory clock in MHz.
+    pub memory_clock: TypedMetricId<u64>,
     /// GPU rate utilization in percentage
     pub major_utilization_gpu: TypedMetricId<u64>,
     /// GPU memory utilization in percentage
@@ -31,26 +37,50 @@ pub struct Metrics {
     pub running_compute_processes: TypedMetricId<u64>,
     /// Relevant currently running graphical processes data in percentage.
     pub running_graphics_processes: TypedMetricId<u64>,
+    /// GPU memory used by a process in bytes.
+    pub process_memory_used: TypedMetricId<u64>,
+    /// Part of the GPU power attributed to a process or to a MIG instance in mW.
+    pub attributed_power: TypedMetricId<f64>,
+    /// Part of the GPU energy attributed to a process or to a MIG instance in mJ.
+    pub attributed_energy: TypedMetricId<f64>,
 }
 
 impl Metrics {
     /// Creates new Alumet metrics for NVML measurements and stores their ids in a `Metrics` structure.
     pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
         Ok(Self {
-            total_energy_consumption: alumet.create_metric(
+            total_energy_consumption: alumet.create_metric_with_tags(
                 "nvml_energy_consumption",
                 PrefixedUnit::milli(Unit::Joule),
                 "Energy consumption by the GPU (including memory) since the previous measurement",
+                &[tag::ENERGY],
             )?,
-            instant_power: alumet.create_metric(
+            instant_power: alumet.create_metric_with_tags(
                 "nvml_instant_power",
                 PrefixedUnit::milli(Unit::Watt),
                 "Instantaneous power of the GPU at the time of the measurement",
+                &[tag::POWER],
             )?,
-            temperature_gpu: alumet.create_metric(
+            temperature_gpu: alumet.create_metric_with_tags(
                 "nvml_temperature_gpu",
                 Unit::DegreeCelsius,
                 "Instantaneous temperature of the GPU at the time of the measurement",
+                &[tag::THERMAL],
+            )?,
+            graphics_clock: alumet.create_metric(
+                "nvml_graphics_clock",
+                PrefixedUnit::mega(Unit::Hertz),
+                "Current frequency of the graphics clock",
+            )?,
+            sm_clock: alumet.create_metric(
+                "nvml_sm_clock",
+                PrefixedUnit::mega(Unit::Hertz),
+                "Current frequency of the streaming multiprocessors clock",
+            )?,
+            memory_clock: alumet.create_metric(
+                "nvml_memory_clock",
+                PrefixedUnit::mega(Unit::Hertz),
+                "Current frequency of the memory clock",
             )?,
             major_utilization_gpu: alumet.create_metric(
                 "nvml_gpu_utilization",
@@ -99,6 +129,23 @@ impl Metrics {
                 Unit::Percent,
                 "Utilization of the GPU streaming multiprocessors by the process",
             )?,
+            process_memory_used: alumet.create_metric(
+                "nvml_process_memory_used",
+                Unit::Byte,
+                "GPU memory used by the process",
+            )?,
+
+            // attribution of the device consumption
+            attributed_power: alumet.create_metric(
+                "nvml_attributed_power",
+                PrefixedUnit::milli(Unit::Watt),
+                "Part of the GPU power attributed to the process or to the MIG instance",
+            )?,
+            attributed_energy: alumet.create_metric(
+                "nvml_attributed_energy",
+                PrefixedUnit::milli(Unit::Joule),
+                "Part of the GPU energy attributed to the process or to the MIG instance since the previous measurement",
+            )?,
         })
     }
 }
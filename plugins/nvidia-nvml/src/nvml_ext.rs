@@ -1,6 +1,11 @@
-//! "Extends" the nvml_wrapper crate to fix some functions.
+//! "Extends" the nvml_wrapper crate to fix some functions and to add the missing ones.
 
-use nvml_wrapper::{Device, error::NvmlError, struct_wrappers::device::ProcessUtilizationSample};
+use nvml_wrapper::{
+    Device,
+    error::{NvmlError, nvml_sym, nvml_try},
+    struct_wrappers::device::ProcessUtilizationSample,
+};
+use nvml_wrapper_sys::bindings::{NVML_DEVICE_MIG_ENABLE, NvmlLib, nvmlDevice_t, nvmlDeviceAttributes_t};
 
 /// Extension trait for NVML `Device`.
 pub trait DeviceExt {
@@ -8,6 +13,8 @@ pub trait DeviceExt {
         &self,
         last_seen_timestamp: u64,
     ) -> Result<Vec<ProcessUtilizationSample>, NvmlError>;
+
+    fn running_processes_accounting_utilization(&self) -> Result<Vec<(u32, f64)>, NvmlError>;
 }
 
 impl DeviceExt for Device<'_> {
@@ -27,4 +34,78 @@ impl DeviceExt for Device<'_> {
             res => res,
         }
     }
+
+    /// Gets the GPU utilization of the running processes, over their lifetime, from the accounting of NVML.
+    ///
+    /// The accounting mode must be enabled (`nvidia-smi --accounting-mode=1`).
+    fn running_processes_accounting_utilization(&self) -> Result<Vec<(u32, f64)>, NvmlError> {
+        let mut utilization = Vec::new();
+        for pid in self.accounting_pids()? {
+            match self.accounting_stats_for(pid) {
+                Ok(stats) if stats.is_running => {
+                    if let Some(gpu) = stats.gpu_utilization {
+                        utilization.push((pid, gpu as f64));
+                    }
+                }
+                // the stats of a terminated process may have been overwritten by a new process
+                Ok(_) | Err(NvmlError::NotFound) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(utilization)
+    }
+}
+
+/// A MIG (Multi-Instance GPU) device, that is a slice of a physical GPU.
+pub struct MigInstance {
+    /// A pointer to the MIG device, as returned by NVML.
+    pub handle: nvmlDevice_t,
+    /// Id of the GPU instance, unique on its physical GPU.
+    pub gpu_instance_id: u32,
+    /// Number of streaming multiprocessors in the slice.
+    pub multiprocessor_count: u32,
+}
+
+/// Lists the MIG instances of a device. The list is empty if MIG is not enabled.
+///
+/// `nvml_wrapper` has no MIG functions, hence the use of the raw library.
+pub fn mig_instances(lib: &NvmlLib, device: nvmlDevice_t) -> Result<Vec<MigInstance>, NvmlError> {
+    unsafe {
+        let get_mig_mode = nvml_sym(lib.nvmlDeviceGetMigMode.as_ref())?;
+        let (mut current_mode, mut pending_mode) = (0, 0);
+        match nvml_try(get_mig_mode(device, &mut current_mode, &mut pending_mode)) {
+            Err(NvmlError::NotSupported) => return Ok(vec![]),
+            res => res?,
+        }
+        if current_mode != NVML_DEVICE_MIG_ENABLE {
+            return Ok(vec![]);
+        }
+
+        let get_max_count = nvml_sym(lib.nvmlDeviceGetMaxMigDeviceCount.as_ref())?;
+        let get_by_index = nvml_sym(lib.nvmlDeviceGetMigDeviceHandleByIndex.as_ref())?;
+        let get_instance_id = nvml_sym(lib.nvmlDeviceGetGpuInstanceId.as_ref())?;
+        let get_attributes = nvml_sym(lib.nvmlDeviceGetAttributes_v2.as_ref())?;
+
+        let mut max_count = 0;
+        nvml_try(get_max_count(device, &mut max_count))?;
+        let mut instances = Vec::new();
+        for i in 0..max_count {
+            let mut handle: nvmlDevice_t = std::ptr::null_mut();
+            match nvml_try(get_by_index(device, i, &mut handle)) {
+                // not every index is used
+                Err(NvmlError::NotFound) => continue,
+                res => res?,
+            }
+            let mut gpu_instance_id = 0;
+            nvml_try(get_instance_id(handle, &mut gpu_instance_id))?;
+            let mut attributes: nvmlDeviceAttributes_t = std::mem::zeroed();
+            nvml_try(get_attributes(handle, &mut attributes))?;
+            instances.push(MigInstance {
+                handle,
+                gpu_instance_id,
+                multiprocessor_count: attributes.multiprocessorCount,
+            });
+        }
+        Ok(instances)
+    }
 }
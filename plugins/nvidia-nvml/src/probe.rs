@@ -1,5 +1,10 @@
 use anyhow::Context;
-use nvml_wrapper::{enum_wrappers::device::TemperatureSensor, error::NvmlError};
+use nvml_wrapper::{
+    Device,
+    enum_wrappers::device::{Clock, TemperatureSensor},
+    enums::device::UsedGpuMemory,
+    error::NvmlError,
+};
 use std::time::SystemTime;
 
 use alumet::{
@@ -9,7 +14,12 @@ use alumet::{
     resources::{Resource, ResourceConsumer},
 };
 
-use crate::{features::AvailableVersion, metrics::Metrics, nvml_ext::DeviceExt};
+use crate::{
+    attribution::{Consumption, shares},
+    features::AvailableVersion,
+    metrics::Metrics,
+    nvml_ext::DeviceExt,
+};
 
 use super::device::ManagedDevice;
 
@@ -23,6 +33,8 @@ pub struct NvmlSource {
     metrics: Metrics,
     /// Alumet resource ID.
     resource: Resource,
+    /// Split the power and energy of the device between its processes or MIG instances?
+    attribution: bool,
 
     /// Last poll timestamp
     last_poll_timestamp: Option<Timestamp>,
@@ -33,13 +45,14 @@ pub struct NvmlSource {
 unsafe impl Send for NvmlSource {}
 
 impl NvmlSource {
-    pub fn new(device: ManagedDevice, metrics: Metrics) -> Result<NvmlSource, NvmlError> {
+    pub fn new(device: ManagedDevice, metrics: Metrics, attribution: bool) -> Result<NvmlSource, NvmlError> {
         let bus_id = std::borrow::Cow::Owned(device.bus_id.clone());
         Ok(NvmlSource {
             energy_counter: CounterDiff::with_max_value(u64::MAX),
             device,
             metrics,
             resource: Resource::Gpu { bus_id },
+            attribution,
             last_poll_timestamp: None,
         })
     }
@@ -52,6 +65,8 @@ impl alumet::pipeline::Source for NvmlSource {
 
         // no consumer, we just monitor the device here
         let consumer = ResourceConsumer::LocalMachine;
+        // what can be attributed to the processes or MIG instances
+        let mut consumption = Consumption::default();
 
         if features.total_energy_consumption {
             // the difference in milliJoules
@@ -60,6 +75,7 @@ impl alumet::pipeline::Source for NvmlSource {
                 .update(device.total_energy_consumption()?)
                 .difference();
             if let Some(milli_joules) = diff {
+                consumption.energy = Some(milli_joules as f64);
                 // if meaningful (we need at least two measurements), push
                 measurements.push(MeasurementPoint::new(
                     timestamp,
@@ -73,12 +89,14 @@ impl alumet::pipeline::Source for NvmlSource {
 
         // Get power consumption in milliWatts
         if features.instant_power {
+            let milli_watts = device.power_usage()?;
+            consumption.power = Some(milli_watts as f64);
             measurements.push(MeasurementPoint::new(
                 timestamp,
                 self.metrics.instant_power,
                 self.resource.clone(),
                 consumer.clone(),
-                device.power_usage()? as u64,
+                milli_watts as u64,
             ))
         }
 
@@ -93,6 +111,23 @@ impl alumet::pipeline::Source for NvmlSource {
             ));
         }
 
+        // Get the current frequencies of the clocks in MHz
+        if features.clocks {
+            for (clock, metric) in [
+                (Clock::Graphics, self.metrics.graphics_clock),
+                (Clock::SM, self.metrics.sm_clock),
+                (Clock::Memory, self.metrics.memory_clock),
+            ] {
+                measurements.push(MeasurementPoint::new(
+                    timestamp,
+                    metric,
+                    self.resource.clone(),
+                    consumer.clone(),
+                    device.clock_info(clock)? as u64,
+                ));
+            }
+        }
+
         // Get the current utilization rates memory for this device major subsystems in percentage
         if features.major_utilization {
             let u = device.utilization_rates()?;
@@ -150,48 +185,72 @@ impl alumet::pipeline::Source for NvmlSource {
             ));
         }
 
-        let n_compute_processes = match features.running_compute_processes {
-            AvailableVersion::Latest => Some(device.running_compute_processes_count()?),
-            AvailableVersion::V2 => Some(device.running_compute_processes_count_v2()?),
+        let compute_processes = match features.running_compute_processes {
+            AvailableVersion::Latest => Some(device.running_compute_processes()?),
+            AvailableVersion::V2 => Some(device.running_compute_processes_v2()?),
             AvailableVersion::None => None,
         };
-        if let Some(n) = n_compute_processes {
+        if let Some(processes) = &compute_processes {
             measurements.push(MeasurementPoint::new(
                 timestamp,
                 self.metrics.running_compute_processes,
                 self.resource.clone(),
                 consumer.clone(),
-                n as u64,
+                processes.len() as u64,
             ));
         }
 
-        let n_graphic_processes = match features.running_graphics_processes {
-            AvailableVersion::Latest => Some(device.running_graphics_processes_count()?),
-            AvailableVersion::V2 => Some(device.running_graphics_processes_count_v2()?),
+        let graphics_processes = match features.running_graphics_processes {
+            AvailableVersion::Latest => Some(device.running_graphics_processes()?),
+            AvailableVersion::V2 => Some(device.running_graphics_processes_v2()?),
             AvailableVersion::None => None,
         };
-        if let Some(n) = n_graphic_processes {
+        if let Some(processes) = &graphics_processes {
             measurements.push(MeasurementPoint::new(
                 timestamp,
                 self.metrics.running_graphics_processes,
                 self.resource.clone(),
                 consumer.clone(),
-                n as u64,
+                processes.len() as u64,
             ));
         }
 
+        // Memory used by each running process, once per type of process (a process can do both compute and graphics)
+        let processes = compute_processes
+            .iter()
+            .flatten()
+            .map(|p| ("compute", p))
+            .chain(graphics_processes.iter().flatten().map(|p| ("graphics", p)));
+        for (process_type, process) in processes {
+            if let UsedGpuMemory::Used(bytes) = process.used_gpu_memory {
+                measurements.push(
+                    MeasurementPoint::new(
+                        timestamp,
+                        self.metrics.process_memory_used,
+                        self.resource.clone(),
+                        ResourceConsumer::Process { pid: process.pid },
+                        bytes,
+                    )
+                    .with_attr("process_type", process_type),
+                );
+            }
+        }
+
         // Collection of the device processes-scoped measurements
+        let mut sm_utilization = Vec::new();
         if features.process_utilization_stats {
             if let Some(last_poll_timestamp) = self.last_poll_timestamp {
+                // NVML timestamps the samples in microseconds
                 let unix_ts = last_poll_timestamp
                     .duration_since(SystemTime::UNIX_EPOCH.into())?
-                    .as_secs();
+                    .as_micros() as u64;
 
                 let processes_samples = device
                     .fixed_process_utilization_stats(unix_ts)
                     .context("process_utilization_stats failed")?;
 
                 for process_sample in processes_samples {
+                    sm_utilization.push((process_sample.pid, process_sample.sm_util as f64));
                     let consumer = ResourceConsumer::Process {
                         pid: process_sample.pid,
                     };
@@ -231,6 +290,95 @@ impl alumet::pipeline::Source for NvmlSource {
             self.last_poll_timestamp = Some(timestamp);
         }
 
+        if self.attribution && !consumption.is_empty() {
+            if self.device.mig_instances.is_empty() {
+                // The utilization of the last interval is better than the one of the whole life of the processes.
+                let utilization = if features.process_utilization_stats {
+                    sm_utilization
+                } else if features.accounting {
+                    device.running_processes_accounting_utilization()?
+                } else {
+                    Vec::new()
+                };
+                for (pid, share) in shares(utilization) {
+                    let consumer = ResourceConsumer::Process { pid };
+                    self.push_attributed(measurements, timestamp, consumer, consumption.scaled(share), None);
+                }
+            } else {
+                self.attribute_to_mig_instances(measurements, timestamp, consumption)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl NvmlSource {
+    /// Splits the consumption of the device between its MIG instances, proportionally to their number of
+    /// streaming multiprocessors, then splits the part of each instance evenly between its compute processes.
+    ///
+    /// NVML does not measure the utilization of the MIG instances by their processes.
+    fn attribute_to_mig_instances(
+        &self,
+        measurements: &mut MeasurementAccumulator,
+        timestamp: Timestamp,
+        consumption: Consumption,
+    ) -> Result<(), NvmlError> {
+        let instances = &self.device.mig_instances;
+        let multiprocessors = instances
+            .iter()
+            .map(|mig| (mig.gpu_instance_id, mig.multiprocessor_count as f64));
+        for (gpu_instance_id, share) in shares(multiprocessors) {
+            let Some(mig) = instances.iter().find(|mig| mig.gpu_instance_id == gpu_instance_id) else {
+                continue;
+            };
+            let instance_consumption = consumption.scaled(share);
+            let consumer = ResourceConsumer::custom("mig_instance", gpu_instance_id.to_string());
+            self.push_attributed(measurements, timestamp, consumer, instance_consumption, None);
+
+            let mig_device = unsafe { Device::new(mig.handle, &self.device.lib) };
+            let processes = match self.device.features.running_compute_processes {
+                AvailableVersion::Latest => mig_device.running_compute_processes()?,
+                AvailableVersion::V2 => mig_device.running_compute_processes_v2()?,
+                AvailableVersion::None => Vec::new(),
+            };
+            for (pid, process_share) in shares(processes.iter().map(|p| (p.pid, 1.0))) {
+                let consumer = ResourceConsumer::Process { pid };
+                let process_consumption = instance_consumption.scaled(process_share);
+                self.push_attributed(
+                    measurements,
+                    timestamp,
+                    consumer,
+                    process_consumption,
+                    Some(gpu_instance_id),
+                );
+            }
+        }
         Ok(())
     }
+
+    /// Pushes the power and energy attributed to a consumer of the device.
+    fn push_attributed(
+        &self,
+        measurements: &mut MeasurementAccumulator,
+        timestamp: Timestamp,
+        consumer: ResourceConsumer,
+        consumption: Consumption,
+        mig_instance: Option<u32>,
+    ) {
+        let values = [
+            (self.metrics.attributed_power, consumption.power),
+            (self.metrics.attributed_energy, consumption.energy),
+        ];
+        for (metric, value) in values {
+            if let Some(value) = value {
+                let mut point =
+                    MeasurementPoint::new(timestamp, metric, self.resource.clone(), consumer.clone(), value);
+                if let Some(id) = mig_instance {
+                    point = point.with_attr("mig_instance", id as u64);
+                }
+                measurements.push(point);
+            }
+        }
+    }
 }
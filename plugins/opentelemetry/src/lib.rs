@@ -1,8 +1,14 @@
 mod output;
 
+pub use output::ExportProtocol;
+
+use alumet::plugin::capability::Capability;
 use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
-use output::OpenTelemetryOutput;
+use anyhow::Context;
+use opentelemetry::KeyValue;
+use output::{ExportSettings, OpenTelemetryOutput};
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
 
 pub struct OpenTelemetryPlugin {
     config: Config,
@@ -17,6 +23,10 @@ impl AlumetPlugin for OpenTelemetryPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
@@ -27,16 +37,39 @@ impl AlumetPlugin for OpenTelemetryPlugin {
     }
 
     fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+        let export = ExportSettings {
+            protocol: self.config.protocol,
+            collector_host: self.config.collector_host.clone(),
+            headers: self.config.headers.clone().into_iter().collect(),
+            timeout: self.config.timeout,
+            push_interval: Duration::from_secs(self.config.push_interval_seconds),
+            resource_attributes: self
+                .config
+                .resource_attributes
+                .iter()
+                .map(|(k, v)| KeyValue::new(k.to_owned(), v.to_owned()))
+                .collect(),
+        };
         // Create a new OpenTelemetryOutput instance
         let otel_output = Box::new(OpenTelemetryOutput::new(
             self.config.use_unit_display_name,
             self.config.add_attributes_to_labels,
             self.config.prefix.clone(),
             self.config.suffix.clone(),
-            self.config.collector_host.clone(),
-            self.config.push_interval_seconds,
+            export,
         )?);
-        alumet.add_blocking_output("out", otel_output.clone())?;
+        let provider = otel_output.provider();
+        alumet.add_blocking_output("out", otel_output)?;
+
+        // Export the measurements aggregated since the last push, and close the connection to the collector.
+        alumet.on_shutdown(self.config.timeout, move || async move {
+            let Some(provider) = provider.get().cloned() else {
+                return Ok(()); // nothing has been written
+            };
+            tokio::task::spawn_blocking(move || provider.shutdown())
+                .await?
+                .context("failed to shut down the OTLP exporter")
+        });
         Ok(())
     }
 
@@ -47,24 +80,44 @@ impl AlumetPlugin for OpenTelemetryPlugin {
 
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
-struct Config {
-    collector_host: String,
-    prefix: String,
-    suffix: String,
-    use_unit_display_name: bool,
-    add_attributes_to_labels: bool,
-    push_interval_seconds: u64,
+pub struct Config {
+    /// `grpc` or `http`.
+    #[serde(default)]
+    pub protocol: ExportProtocol,
+    pub collector_host: String,
+    pub prefix: String,
+    pub suffix: String,
+    pub use_unit_display_name: bool,
+    pub add_attributes_to_labels: bool,
+    /// The measurements are aggregated and sent in one batch at this interval.
+    pub push_interval_seconds: u64,
+    #[serde(default = "default_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Additional headers (or gRPC metadata), ex. for authentication.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Attributes of the OpenTelemetry resource, ex. `host.name`. `service.name` defaults to `alumet`.
+    #[serde(default)]
+    pub resource_attributes: BTreeMap<String, String>,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(10)
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            protocol: ExportProtocol::Grpc,
             collector_host: String::from("http://localhost:4317"),
             prefix: String::from(""),
             suffix: String::from("_alumet"),
             use_unit_display_name: true,
             add_attributes_to_labels: true,
             push_interval_seconds: 15,
+            timeout: default_timeout(),
+            headers: BTreeMap::new(),
+            resource_attributes: BTreeMap::new(),
         }
     }
 }
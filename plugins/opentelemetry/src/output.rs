@@ -1,24 +1,58 @@
 use alumet::{
-    measurement::{MeasurementBuffer, WrappedMeasurementValue},
-    metrics::Metric,
+    measurement::{AttributeValue, MeasurementBuffer, WrappedMeasurementValue},
+    metrics::{Metric, RawMetricId},
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
 use anyhow::Context;
-use opentelemetry::{InstrumentationScope, KeyValue, global};
-use opentelemetry_otlp::{MetricExporter, WithExportConfig};
+use opentelemetry::{
+    InstrumentationScope, KeyValue, Value,
+    metrics::{Gauge, Meter, MeterProvider},
+};
+use opentelemetry_otlp::{MetricExporter, Protocol, WithExportConfig, WithHttpConfig, WithTonicConfig};
 use opentelemetry_sdk::Resource;
 use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
-use std::{env, sync::OnceLock, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+
+/// Transport used to send the metrics to the collector.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportProtocol {
+    /// OTLP over gRPC, usually on port 4317.
+    #[default]
+    Grpc,
+    /// OTLP over HTTP with binary protobuf, usually on port 4318.
+    Http,
+}
+
+/// Settings of the OTLP exporter.
+pub struct ExportSettings {
+    pub protocol: ExportProtocol,
+    pub collector_host: String,
+    pub headers: HashMap<String, String>,
+    pub timeout: Duration,
+    /// The measurements are aggregated and sent in one batch at this interval.
+    pub push_interval: Duration,
+    /// Attributes of the OpenTelemetry resource, which describes the entity that produces the metrics.
+    pub resource_attributes: Vec<KeyValue>,
+}
 
-#[derive(Clone)]
 pub struct OpenTelemetryOutput {
     use_unit_display_name: bool,
     add_attributes_to_labels: bool,
     prefix: String,
     suffix: String,
-    initialized: bool,
-    collector_host: String,
-    push_interval_seconds: u64,
+    export: ExportSettings,
+    /// Created on the first write, see [`OpenTelemetryOutput::meter`].
+    /// Shared with the plugin, which shuts it down when the pipeline stops.
+    provider: Arc<OnceLock<SdkMeterProvider>>,
+    meter: Option<Meter>,
+    gauges: HashMap<RawMetricId, Gauge<f64>>,
 }
 
 impl OpenTelemetryOutput {
@@ -27,47 +61,84 @@ impl OpenTelemetryOutput {
         add_attributes_to_labels: bool,
         prefix: String,
         suffix: String,
-        collector_host: String,
-        push_interval_seconds: u64,
+        export: ExportSettings,
     ) -> anyhow::Result<OpenTelemetryOutput> {
         Ok(Self {
             use_unit_display_name,
             add_attributes_to_labels,
             prefix,
             suffix,
-            initialized: false,
-            collector_host: format!("{}{}", collector_host, "/v1/metrics"),
-            push_interval_seconds,
+            export,
+            provider: Arc::new(OnceLock::new()),
+            meter: None,
+            gauges: HashMap::new(),
         })
     }
-    pub fn initialize(&mut self) {
-        // Needs to be created inside the tokio thread
-        // TODO: rework after https://github.com/alumet-dev/alumet/issues/119 is implemented
-        let meter_provider = self.init_metrics();
-        global::set_meter_provider(meter_provider.clone());
+
+    /// Returns the meter provider, which is set on the first write.
+    pub fn provider(&self) -> Arc<OnceLock<SdkMeterProvider>> {
+        self.provider.clone()
     }
-    fn get_resource(&mut self) -> Resource {
-        static RESOURCE: OnceLock<Resource> = OnceLock::new();
-        RESOURCE
-            .get_or_init(|| Resource::builder().with_service_name("alumet-otlp-grpc").build())
-            .clone()
+
+    /// Returns the meter, after having initialized the exporter if needed.
+    ///
+    /// The gRPC exporter needs to be created inside the tokio runtime, hence it cannot be created in `new`.
+    // TODO: rework after https://github.com/alumet-dev/alumet/issues/119 is implemented
+    fn meter(&mut self) -> anyhow::Result<&Meter> {
+        if self.meter.is_none() {
+            let provider = self.init_metrics()?;
+            let scope = InstrumentationScope::builder("alumet")
+                .with_version(env!("CARGO_PKG_VERSION"))
+                .with_attributes(vec![KeyValue::new("tool", "alumet")])
+                .build();
+            self.meter = Some(provider.meter_with_scope(scope));
+            let _ = self.provider.set(provider);
+        }
+        Ok(self.meter.as_ref().unwrap())
     }
 
-    fn init_metrics(&mut self) -> SdkMeterProvider {
-        let exporter = MetricExporter::builder()
-            .with_tonic()
-            .with_endpoint(self.collector_host.clone())
-            .build()
-            .expect("Failed to create metric exporter");
+    fn init_metrics(&self) -> anyhow::Result<SdkMeterProvider> {
+        let settings = &self.export;
+        let exporter = match settings.protocol {
+            ExportProtocol::Grpc => {
+                let mut metadata = MetadataMap::new();
+                for (key, value) in &settings.headers {
+                    let key =
+                        MetadataKey::from_bytes(key.as_bytes()).with_context(|| format!("invalid header {key}"))?;
+                    let value = MetadataValue::try_from(value).with_context(|| format!("invalid value for {key}"))?;
+                    metadata.insert(key, value);
+                }
+                MetricExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(settings.collector_host.clone())
+                    .with_timeout(settings.timeout)
+                    .with_metadata(metadata)
+                    .build()
+            }
+            ExportProtocol::Http => MetricExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .with_endpoint(format!("{}/v1/metrics", settings.collector_host.trim_end_matches('/')))
+                .with_timeout(settings.timeout)
+                .with_headers(settings.headers.clone())
+                .build(),
+        }
+        .context("failed to create the OTLP metric exporter")?;
+
         let reader = PeriodicReader::builder(exporter)
-            .with_interval(Duration::new(self.push_interval_seconds, 0))
+            .with_interval(settings.push_interval)
+            .build();
+
+        // Resource::builder() also reads the OTEL_SERVICE_NAME and OTEL_RESOURCE_ATTRIBUTES environment variables.
+        let resource = Resource::builder()
+            .with_service_name("alumet")
+            .with_attributes(settings.resource_attributes.clone())
             .build();
 
-        SdkMeterProvider::builder()
+        Ok(SdkMeterProvider::builder()
             .with_reader(reader)
-            // .with_periodic_exporter(exporter)
-            .with_resource(self.get_resource())
-            .build()
+            .with_resource(resource)
+            .build())
     }
 }
 
@@ -76,37 +147,19 @@ impl alumet::pipeline::Output for OpenTelemetryOutput {
         if measurements.is_empty() {
             return Ok(());
         }
-        if !self.initialized {
-            self.initialize();
-            self.initialized = true;
-        }
-        let common_scope_attributes = vec![KeyValue::new("tool", "alumet")];
-        let scope = InstrumentationScope::builder("alumet")
-            .with_version(env!("CARGO_PKG_VERSION"))
-            .with_attributes(common_scope_attributes)
-            .build();
+        let meter = self.meter()?.clone();
         for m in measurements {
-            // Configure the name of the metric
-            let full_metric = ctx
-                .metrics
-                .by_id(&m.metric)
-                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
-            let metric_name = format!("{}{}{}", self.prefix, full_metric.name.clone(), self.suffix);
-
             // Create the default labels for all metrics and optionally add attributes
             let mut labels = vec![
-                KeyValue::new("resource_kind".to_string(), m.resource.kind().to_string()),
-                KeyValue::new("resource_id".to_string(), m.resource.id_string().unwrap_or_default()),
-                KeyValue::new("resource_consumer_kind".to_string(), m.consumer.kind().to_string()),
-                KeyValue::new(
-                    "resource_consumer_id".to_string(),
-                    m.consumer.id_string().unwrap_or_default(),
-                ),
+                KeyValue::new("resource_kind", m.resource.kind().to_string()),
+                KeyValue::new("resource_id", m.resource.id_string().unwrap_or_default()),
+                KeyValue::new("resource_consumer_kind", m.consumer.kind().to_string()),
+                KeyValue::new("resource_consumer_id", m.consumer.id_string().unwrap_or_default()),
             ];
             if self.add_attributes_to_labels {
                 // Add attributes as labels
                 for (key, value) in m.attributes() {
-                    labels.push(KeyValue::new(key.to_owned(), value.to_string()));
+                    labels.push(KeyValue::new(key.to_owned(), convert_attribute(value)));
                 }
             }
             // OpenTelemetry does not accept empty label
@@ -117,16 +170,25 @@ impl alumet::pipeline::Output for OpenTelemetryOutput {
             }
             labels.sort_by(|a, b| a.key.cmp(&b.key));
 
-            // Prepare the meter provider
-            let meter = global::meter_with_scope(scope.clone());
-            let metric = ctx.metrics.by_id(&m.metric).unwrap().clone();
-            let gauge = meter
-                .f64_gauge(metric_name)
-                .with_description(metric.description.to_string())
-                .with_unit(get_unit_string(full_metric, self.use_unit_display_name))
-                .build();
+            // One gauge per metric, with the unit and description of the metric
+            let gauge = match self.gauges.get(&m.metric) {
+                Some(gauge) => gauge,
+                None => {
+                    let full_metric = ctx
+                        .metrics
+                        .by_id(&m.metric)
+                        .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+                    let metric_name = format!("{}{}{}", self.prefix, full_metric.name, self.suffix);
+                    let gauge = meter
+                        .f64_gauge(metric_name)
+                        .with_description(full_metric.description.to_string())
+                        .with_unit(get_unit_string(full_metric, self.use_unit_display_name))
+                        .build();
+                    self.gauges.entry(m.metric).or_insert(gauge)
+                }
+            };
             match m.value {
-                WrappedMeasurementValue::F64(v) => gauge.record(v as f64, &labels),
+                WrappedMeasurementValue::F64(v) => gauge.record(v, &labels),
                 WrappedMeasurementValue::U64(v) => gauge.record(v as f64, &labels),
             };
         }
@@ -135,6 +197,21 @@ impl alumet::pipeline::Output for OpenTelemetryOutput {
     }
 }
 
+/// Converts an Alumet attribute to an OpenTelemetry value, preserving its type when possible.
+fn convert_attribute(value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::F64(v) => Value::F64(*v),
+        AttributeValue::U64(v) => match i64::try_from(*v) {
+            Ok(v) => Value::I64(v),
+            Err(_) => Value::String(v.to_string().into()),
+        },
+        AttributeValue::Bool(v) => Value::Bool(*v),
+        AttributeValue::Str(v) => Value::String((*v).into()),
+        AttributeValue::String(v) => Value::String(v.clone().into()),
+        AttributeValue::ListU64(_) => Value::String(value.to_string().into()),
+    }
+}
+
 fn get_unit_string(full_metric: &Metric, use_unit_display_name: bool) -> String {
     if use_unit_display_name {
         full_metric.unit.display_name()
@@ -142,3 +219,27 @@ fn get_unit_string(full_metric: &Metric, use_unit_display_name: bool) -> String
         full_metric.unit.unique_name()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alumet::measurement::AttributeValue;
+    use opentelemetry::Value;
+    use pretty_assertions::assert_eq;
+
+    use super::convert_attribute;
+
+    #[test]
+    fn attribute_conversion() {
+        assert_eq!(convert_attribute(&AttributeValue::U64(12)), Value::I64(12));
+        assert_eq!(
+            convert_attribute(&AttributeValue::U64(u64::MAX)),
+            Value::String(u64::MAX.to_string().into())
+        );
+        assert_eq!(convert_attribute(&AttributeValue::F64(1.5)), Value::F64(1.5));
+        assert_eq!(convert_attribute(&AttributeValue::Bool(true)), Value::Bool(true));
+        assert_eq!(
+            convert_attribute(&AttributeValue::Str("cpu")),
+            Value::String("cpu".into())
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::time::{Duration, Instant};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    pipeline::naming::OutputName,
+    plugin::{AlumetPluginStart, ConfigTable, PluginMetadata, rust::AlumetPlugin},
+    resources::{Resource, ResourceConsumer},
+    test::{RuntimeExpectations, runtime::OutputCheckInputContext},
+    units::Unit,
+};
+use mockito::Server;
+use opentelemetry_proto::tonic::{
+    collector::metrics::v1::ExportMetricsServiceRequest,
+    common::v1::{AnyValue, KeyValue, any_value},
+    metrics::v1::metric,
+};
+use plugin_opentelemetry::{Config, ExportProtocol, OpenTelemetryPlugin};
+use prost::Message;
+
+/// Registers the metric used by the test.
+struct MetricPlugin;
+
+impl AlumetPlugin for MetricPlugin {
+    fn name() -> &'static str {
+        "metrics"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(MetricPlugin))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.create_metric::<u64>("consumed_energy", Unit::Joule, "energy consumed")?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+fn string_attr(attrs: &[KeyValue], key: &str) -> Option<String> {
+    attrs.iter().find(|kv| kv.key == key).and_then(|kv| match &kv.value {
+        Some(AnyValue {
+            value: Some(any_value::Value::StringValue(s)),
+        }) => Some(s.clone()),
+        _ => None,
+    })
+}
+
+/// Checks the content of an OTLP/HTTP request.
+fn is_expected(body: &[u8]) -> bool {
+    let request = ExportMetricsServiceRequest::decode(body).expect("body should be an ExportMetricsServiceRequest");
+    let [resource_metrics] = &request.resource_metrics[..] else {
+        return false;
+    };
+    let resource = resource_metrics.resource.as_ref().unwrap();
+    if string_attr(&resource.attributes, "service.name").as_deref() != Some("alumet")
+        || string_attr(&resource.attributes, "host.name").as_deref() != Some("node-1")
+    {
+        return false;
+    }
+    let metrics: Vec<_> = resource_metrics.scope_metrics.iter().flat_map(|s| &s.metrics).collect();
+    let [metric] = &metrics[..] else {
+        return false;
+    };
+    let Some(metric::Data::Gauge(gauge)) = &metric.data else {
+        return false;
+    };
+    metric.name == "consumed_energy_alumet"
+        && metric.unit == "J"
+        && gauge.data_points.len() == 1
+        && string_attr(&gauge.data_points[0].attributes, "resource_kind").as_deref() == Some("cpu_package")
+}
+
+/// Starts the opentelemetry plugin with the given config, and checks it with one measurement.
+fn run_agent(config: Config, check_output: impl Fn() + Send + 'static) {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<OpenTelemetryPlugin>(),
+        enabled: true,
+        config: Some(toml::Value::try_from(&config).unwrap().as_table().unwrap().clone()),
+    });
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<MetricPlugin>(),
+        enabled: true,
+        config: None,
+    });
+
+    let make_input = |ctx: &mut OutputCheckInputContext| -> MeasurementBuffer {
+        let metric = ctx.metrics().by_name("consumed_energy").expect("metric should exist").0;
+        let mut m = MeasurementBuffer::new();
+        m.push(MeasurementPoint::new_untyped(
+            Timestamp::now(),
+            metric,
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::U64(42),
+        ));
+        m
+    };
+    let expectations =
+        RuntimeExpectations::new().test_output(OutputName::from_str("opentelemetry", "out"), make_input, check_output);
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(expectations)
+        .build_and_start()
+        .unwrap();
+    agent.wait_for_shutdown(Duration::from_secs(10)).unwrap();
+}
+
+#[test]
+fn export_over_http() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/v1/metrics")
+        .match_header("Content-Type", "application/x-protobuf")
+        .match_header("Authorization", "Bearer token")
+        .match_request(|req| is_expected(req.body().unwrap()))
+        .with_status(200)
+        .expect_at_least(1)
+        .create();
+
+    let config = Config {
+        protocol: ExportProtocol::Http,
+        collector_host: server.url(),
+        push_interval_seconds: 1,
+        headers: [(String::from("Authorization"), String::from("Bearer token"))].into(),
+        resource_attributes: [(String::from("host.name"), String::from("node-1"))].into(),
+        ..Default::default()
+    };
+    let check_output = move || {
+        // the measurements are sent in the next batch
+        let start = Instant::now();
+        while !mock.matched() && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        mock.assert();
+    };
+    run_agent(config, check_output);
+}
+
+#[test]
+fn export_on_shutdown() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/v1/metrics")
+        .match_request(|req| is_expected(req.body().unwrap()))
+        .with_status(200)
+        .expect(1)
+        .create();
+
+    let config = Config {
+        protocol: ExportProtocol::Http,
+        collector_host: server.url(),
+        // the next batch would only be sent in an hour
+        push_interval_seconds: 3600,
+        resource_attributes: [(String::from("host.name"), String::from("node-1"))].into(),
+        ..Default::default()
+    };
+    run_agent(config, || ());
+    // the shutdown hook has exported the aggregated measurements
+    mock.assert();
+}
@@ -0,0 +1,463 @@
+//! Conversion of OTLP metrics to Alumet measurements.
+//!
+//! The values of the gauges and sums are reported as is, except for the monotonic cumulative sums
+//! (counters), which are converted to the increase since the previous data point of the same series.
+//! The histograms are reported by their count and sum. The summaries are not supported.
+
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{AttributeValue, Timestamp},
+    resources::ResourceConsumer,
+    units::{PrefixedUnit, Unit},
+};
+use opentelemetry_proto::tonic::{
+    collector::metrics::v1::ExportMetricsServiceRequest,
+    common::v1::{KeyValue, any_value},
+    metrics::v1::{AggregationTemporality, Metric, metric::Data, number_data_point},
+};
+
+/// Definition of the metric of a [`Point`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDef {
+    pub name: String,
+    pub description: String,
+    pub unit: PrefixedUnit,
+}
+
+/// A data point of an OTLP metric, converted to Alumet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub metric: MetricDef,
+    pub timestamp: Timestamp,
+    pub consumer: ResourceConsumer,
+    pub value: f64,
+    /// Attributes of the data point and of its resource, sorted by key.
+    pub attributes: Vec<(String, AttributeValue)>,
+}
+
+pub struct Converter {
+    /// Attributes of the OTLP resources to copy to the measurements.
+    resource_attributes: Vec<String>,
+    /// Last value of each cumulative series, with the start time of the series.
+    cumulative: HashMap<String, (u64, f64)>,
+}
+
+/// What the data points of a metric have in common.
+struct Series<'a> {
+    consumer: &'a ResourceConsumer,
+    attributes: &'a [(String, AttributeValue)],
+    /// Values must be converted to deltas.
+    cumulative: bool,
+}
+
+/// What the values of an OTLP data point have in common (a histogram point has several values).
+struct Sample<'a> {
+    attributes: &'a [KeyValue],
+    start_time: u64,
+    time: u64,
+}
+
+impl<'a> Sample<'a> {
+    fn new(attributes: &'a [KeyValue], start_time: u64, time: u64) -> Self {
+        Self {
+            attributes,
+            start_time,
+            time,
+        }
+    }
+}
+
+impl Converter {
+    pub fn new(resource_attributes: Vec<String>) -> Self {
+        Self {
+            resource_attributes,
+            cumulative: HashMap::new(),
+        }
+    }
+
+    pub fn convert(&mut self, request: ExportMetricsServiceRequest) -> Vec<Point> {
+        let mut points = Vec::new();
+        for resource_metrics in request.resource_metrics {
+            let resource = resource_metrics.resource.map(|r| r.attributes).unwrap_or_default();
+            let consumer = consumer(&resource);
+            let attributes: Vec<_> = resource
+                .iter()
+                .filter(|kv| self.resource_attributes.contains(&kv.key))
+                .filter_map(attribute)
+                .collect();
+            for metric in resource_metrics.scope_metrics.into_iter().flat_map(|s| s.metrics) {
+                self.convert_metric(metric, &consumer, &attributes, &mut points);
+            }
+        }
+        points
+    }
+
+    fn convert_metric(
+        &mut self,
+        metric: Metric,
+        consumer: &ResourceConsumer,
+        attributes: &[(String, AttributeValue)],
+        points: &mut Vec<Point>,
+    ) {
+        let def = MetricDef {
+            unit: parse_unit(&metric.unit),
+            name: metric.name,
+            description: metric.description,
+        };
+        let is_cumulative = |temporality: i32| temporality == AggregationTemporality::Cumulative as i32;
+        match metric.data {
+            Some(Data::Gauge(gauge)) => {
+                let series = Series {
+                    consumer,
+                    attributes,
+                    cumulative: false,
+                };
+                for p in gauge.data_points {
+                    let sample = Sample::new(&p.attributes, p.start_time_unix_nano, p.time_unix_nano);
+                    self.push(points, &def, &series, &sample, number(p.value));
+                }
+            }
+            Some(Data::Sum(sum)) => {
+                let series = Series {
+                    consumer,
+                    attributes,
+                    cumulative: sum.is_monotonic && is_cumulative(sum.aggregation_temporality),
+                };
+                for p in sum.data_points {
+                    let sample = Sample::new(&p.attributes, p.start_time_unix_nano, p.time_unix_nano);
+                    self.push(points, &def, &series, &sample, number(p.value));
+                }
+            }
+            Some(Data::Histogram(histogram)) => {
+                let series = Series {
+                    consumer,
+                    attributes,
+                    cumulative: is_cumulative(histogram.aggregation_temporality),
+                };
+                let (count, sum) = histogram_defs(&def);
+                for p in histogram.data_points {
+                    let sample = Sample::new(&p.attributes, p.start_time_unix_nano, p.time_unix_nano);
+                    self.push(points, &count, &series, &sample, Some(p.count as f64));
+                    self.push(points, &sum, &series, &sample, p.sum);
+                }
+            }
+            Some(Data::ExponentialHistogram(histogram)) => {
+                let series = Series {
+                    consumer,
+                    attributes,
+                    cumulative: is_cumulative(histogram.aggregation_temporality),
+                };
+                let (count, sum) = histogram_defs(&def);
+                for p in histogram.data_points {
+                    let sample = Sample::new(&p.attributes, p.start_time_unix_nano, p.time_unix_nano);
+                    self.push(points, &count, &series, &sample, Some(p.count as f64));
+                    self.push(points, &sum, &series, &sample, p.sum);
+                }
+            }
+            Some(Data::Summary(_)) => log::debug!("Ignoring OTLP metric {}: summaries are not supported.", def.name),
+            None => log::debug!("Ignoring OTLP metric {}: no data.", def.name),
+        }
+    }
+
+    fn push(&mut self, points: &mut Vec<Point>, def: &MetricDef, series: &Series, sample: &Sample, value: Option<f64>) {
+        let Some(value) = value else {
+            return;
+        };
+        let mut attributes: Vec<_> = series.attributes.to_vec();
+        attributes.extend(sample.attributes.iter().filter_map(attribute));
+        attributes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let value = if series.cumulative {
+            let key = format!("{}\0{:?}\0{:?}", def.name, series.consumer, attributes);
+            match self.delta(key, sample.start_time, value) {
+                Some(delta) => delta,
+                None => return,
+            }
+        } else {
+            value
+        };
+        let timestamp = if sample.time == 0 {
+            Timestamp::now()
+        } else {
+            Timestamp::from(UNIX_EPOCH + Duration::from_nanos(sample.time))
+        };
+        points.push(Point {
+            metric: def.clone(),
+            timestamp,
+            consumer: series.consumer.clone(),
+            value,
+            attributes,
+        });
+    }
+
+    /// Returns the increase of a cumulative series since its previous value.
+    ///
+    /// Returns `None` for the first value of the series, and when the series has been reset.
+    fn delta(&mut self, key: String, start_time: u64, value: f64) -> Option<f64> {
+        match self.cumulative.insert(key, (start_time, value)) {
+            Some((previous_start, previous)) if previous_start == start_time && value >= previous => {
+                Some(value - previous)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn histogram_defs(def: &MetricDef) -> (MetricDef, MetricDef) {
+    let count = MetricDef {
+        name: format!("{}_count", def.name),
+        description: format!("number of values in the histogram {}", def.name),
+        unit: Unit::Unity.into(),
+    };
+    let sum = MetricDef {
+        name: format!("{}_sum", def.name),
+        description: format!("sum of the values in the histogram {}", def.name),
+        unit: def.unit.clone(),
+    };
+    (count, sum)
+}
+
+fn number(value: Option<number_data_point::Value>) -> Option<f64> {
+    match value? {
+        number_data_point::Value::AsDouble(v) => Some(v),
+        number_data_point::Value::AsInt(v) => Some(v as f64),
+    }
+}
+
+/// Converts an OTLP attribute. Arrays, maps and bytes are not supported.
+fn attribute(kv: &KeyValue) -> Option<(String, AttributeValue)> {
+    let value = match kv.value.as_ref()?.value.as_ref()? {
+        any_value::Value::StringValue(s) => AttributeValue::String(s.clone()),
+        any_value::Value::BoolValue(b) => AttributeValue::Bool(*b),
+        any_value::Value::IntValue(i) => match u64::try_from(*i) {
+            Ok(u) => AttributeValue::U64(u),
+            Err(_) => AttributeValue::F64(*i as f64),
+        },
+        any_value::Value::DoubleValue(d) => AttributeValue::F64(*d),
+        _ => return None,
+    };
+    Some((kv.key.clone(), value))
+}
+
+/// The consumer is the process of the application if it reports its `process.pid`, the local machine otherwise.
+fn consumer(resource: &[KeyValue]) -> ResourceConsumer {
+    let pid =
+        resource
+            .iter()
+            .find(|kv| kv.key == "process.pid")
+            .and_then(|kv| match kv.value.as_ref()?.value.as_ref()? {
+                any_value::Value::IntValue(pid) => u32::try_from(*pid).ok(),
+                any_value::Value::StringValue(pid) => pid.parse().ok(),
+                _ => None,
+            });
+    match pid {
+        Some(pid) => ResourceConsumer::Process { pid },
+        None => ResourceConsumer::LocalMachine,
+    }
+}
+
+/// Parses an UCUM unit of OTLP. The annotations like `{request}` are dimensionless.
+fn parse_unit(unit: &str) -> PrefixedUnit {
+    match unit {
+        "" | "1" => Unit::Unity.into(),
+        u if u.starts_with('{') && u.ends_with('}') => Unit::Unity.into(),
+        u => PrefixedUnit::from_str(u).unwrap_or_else(|_| {
+            Unit::Custom {
+                unique_name: u.to_owned(),
+                display_name: u.to_owned(),
+            }
+            .into()
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::units::UnitPrefix;
+    use opentelemetry_proto::tonic::{
+        common::v1::AnyValue,
+        metrics::v1::{Gauge, HistogramDataPoint, NumberDataPoint, ResourceMetrics, ScopeMetrics, Sum},
+        resource::v1::Resource,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn kv(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn number_point(start: u64, time: u64, value: f64) -> NumberDataPoint {
+        NumberDataPoint {
+            attributes: vec![kv("route", any_value::Value::StringValue(String::from("/api")))],
+            start_time_unix_nano: start,
+            time_unix_nano: time,
+            value: Some(number_data_point::Value::AsDouble(value)),
+            ..Default::default()
+        }
+    }
+
+    fn request(metrics: Vec<Metric>) -> ExportMetricsServiceRequest {
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource {
+                    attributes: vec![
+                        kv("service.name", any_value::Value::StringValue(String::from("shop"))),
+                        kv("process.pid", any_value::Value::IntValue(1234)),
+                        kv(
+                            "telemetry.sdk.language",
+                            any_value::Value::StringValue(String::from("rust")),
+                        ),
+                    ],
+                    ..Default::default()
+                }),
+                scope_metrics: vec![ScopeMetrics {
+                    metrics,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn converter() -> Converter {
+        Converter::new(vec![String::from("service.name")])
+    }
+
+    #[test]
+    fn gauge() {
+        let metric = Metric {
+            name: String::from("queue.size"),
+            unit: String::from("{item}"),
+            data: Some(Data::Gauge(Gauge {
+                data_points: vec![number_point(0, 1_700_000_000_000_000_000, 12.0)],
+            })),
+            ..Default::default()
+        };
+        let points = converter().convert(request(vec![metric]));
+        assert_eq!(
+            points,
+            vec![Point {
+                metric: MetricDef {
+                    name: String::from("queue.size"),
+                    description: String::new(),
+                    unit: Unit::Unity.into(),
+                },
+                timestamp: Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+                consumer: ResourceConsumer::Process { pid: 1234 },
+                value: 12.0,
+                attributes: vec![
+                    (String::from("route"), AttributeValue::String(String::from("/api"))),
+                    (
+                        String::from("service.name"),
+                        AttributeValue::String(String::from("shop"))
+                    ),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn cumulative_sum() {
+        let sum = |points: Vec<NumberDataPoint>| Metric {
+            name: String::from("requests"),
+            data: Some(Data::Sum(Sum {
+                data_points: points,
+                aggregation_temporality: AggregationTemporality::Cumulative as i32,
+                is_monotonic: true,
+            })),
+            ..Default::default()
+        };
+        let mut converter = converter();
+        let mut values = |points| -> Vec<f64> {
+            converter
+                .convert(request(vec![sum(points)]))
+                .into_iter()
+                .map(|p| p.value)
+                .collect()
+        };
+        // no value for the first data point of the series
+        assert_eq!(values(vec![number_point(1, 10, 5.0)]), Vec::<f64>::new());
+        assert_eq!(values(vec![number_point(1, 20, 8.0)]), vec![3.0]);
+        assert_eq!(values(vec![number_point(1, 30, 8.0)]), vec![0.0]);
+        // the application has restarted
+        assert_eq!(values(vec![number_point(25, 40, 2.0)]), Vec::<f64>::new());
+        assert_eq!(values(vec![number_point(25, 50, 6.0)]), vec![4.0]);
+    }
+
+    #[test]
+    fn delta_sum() {
+        let metric = Metric {
+            name: String::from("requests"),
+            data: Some(Data::Sum(Sum {
+                data_points: vec![number_point(1, 10, 5.0)],
+                aggregation_temporality: AggregationTemporality::Delta as i32,
+                is_monotonic: true,
+            })),
+            ..Default::default()
+        };
+        let points = converter().convert(request(vec![metric]));
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].value, 5.0);
+    }
+
+    #[test]
+    fn delta_histogram() {
+        let metric = Metric {
+            name: String::from("http.server.request.duration"),
+            unit: String::from("s"),
+            data: Some(Data::Histogram(opentelemetry_proto::tonic::metrics::v1::Histogram {
+                data_points: vec![HistogramDataPoint {
+                    time_unix_nano: 10,
+                    count: 4,
+                    sum: Some(0.5),
+                    ..Default::default()
+                }],
+                aggregation_temporality: AggregationTemporality::Delta as i32,
+            })),
+            ..Default::default()
+        };
+        let points = converter().convert(request(vec![metric]));
+        let summary: Vec<_> = points
+            .iter()
+            .map(|p| (p.metric.name.as_str(), p.metric.unit.clone(), p.value))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("http.server.request.duration_count", Unit::Unity.into(), 4.0),
+                ("http.server.request.duration_sum", Unit::Second.into(), 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(parse_unit(""), Unit::Unity.into());
+        assert_eq!(parse_unit("{packet}"), Unit::Unity.into());
+        assert_eq!(parse_unit("By"), Unit::Byte.into());
+        assert_eq!(
+            parse_unit("ms"),
+            PrefixedUnit {
+                base_unit: Unit::Second,
+                prefix: UnitPrefix::Milli
+            }
+        );
+        assert_eq!(
+            parse_unit("min"),
+            Unit::Custom {
+                unique_name: String::from("min"),
+                display_name: String::from("min")
+            }
+            .into()
+        );
+    }
+}
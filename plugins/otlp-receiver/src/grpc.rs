@@ -0,0 +1,52 @@
+//! OTLP/gRPC transport.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+    metrics_service_server::{MetricsService, MetricsServiceServer},
+};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_util::sync::CancellationToken;
+use tonic::{Request, Response, Status};
+
+use crate::receiver::Receiver;
+
+struct Service {
+    receiver: Arc<Receiver>,
+}
+
+#[tonic::async_trait]
+impl MetricsService for Service {
+    async fn export(
+        &self,
+        request: Request<ExportMetricsServiceRequest>,
+    ) -> Result<Response<ExportMetricsServiceResponse>, Status> {
+        match self.receiver.export(request.into_inner()).await {
+            Ok(()) => Ok(Response::new(ExportMetricsServiceResponse { partial_success: None })),
+            Err(e) => {
+                log::error!("Failed to process an OTLP/gRPC request: {e:#}");
+                Err(Status::unavailable(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Serves OTLP/gRPC requests until the token is cancelled.
+///
+/// The messages that are larger than `max_request_size` bytes are rejected.
+pub async fn serve(
+    listener: TcpListener,
+    receiver: Arc<Receiver>,
+    max_request_size: usize,
+    cancel_token: CancellationToken,
+) -> anyhow::Result<()> {
+    let service = MetricsServiceServer::new(Service { receiver }).max_decoding_message_size(max_request_size);
+    tonic::transport::Server::builder()
+        .add_service(service)
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), cancel_token.cancelled_owned())
+        .await
+        .context("OTLP/gRPC server failed")
+}
@@ -0,0 +1,125 @@
+//! OTLP/HTTP transport, with binary protobuf payloads.
+//!
+//! The metrics are received on `POST /v1/metrics`. JSON payloads and compressed bodies are not supported.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use hyper::{
+    Body, Method, Request, Response, Server, StatusCode,
+    body::{Bytes, HttpBody},
+    header,
+    service::{make_service_fn, service_fn},
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{ExportMetricsServiceRequest, ExportMetricsServiceResponse};
+use prost::Message;
+use tokio_util::sync::CancellationToken;
+
+use crate::receiver::Receiver;
+
+const METRICS_PATH: &str = "/v1/metrics";
+const PROTOBUF: &str = "application/x-protobuf";
+
+/// Serves OTLP/HTTP requests until the token is cancelled.
+///
+/// The requests whose body is larger than `max_request_size` bytes are rejected.
+pub async fn serve(
+    listener: std::net::TcpListener,
+    receiver: Arc<Receiver>,
+    max_request_size: usize,
+    cancel_token: CancellationToken,
+) -> anyhow::Result<()> {
+    let make_svc = make_service_fn(move |_conn| {
+        let receiver = receiver.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                let receiver = receiver.clone();
+                async move { Ok::<_, hyper::Error>(handle(req, &receiver, max_request_size).await) }
+            }))
+        }
+    });
+    Server::from_tcp(listener)?
+        .serve(make_svc)
+        .with_graceful_shutdown(cancel_token.cancelled_owned())
+        .await
+        .context("OTLP/HTTP server failed")
+}
+
+async fn handle(req: Request<Body>, receiver: &Receiver, max_request_size: usize) -> Response<Body> {
+    if req.uri().path() != METRICS_PATH {
+        return error(StatusCode::NOT_FOUND, "not found");
+    }
+    if req.method() != Method::POST {
+        return error(StatusCode::METHOD_NOT_ALLOWED, "only POST is allowed");
+    }
+    let content_type = req.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    if content_type != Some(PROTOBUF) {
+        return error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "only application/x-protobuf is supported",
+        );
+    }
+    if req.headers().contains_key(header::CONTENT_ENCODING) {
+        return error(StatusCode::UNSUPPORTED_MEDIA_TYPE, "compression is not supported");
+    }
+
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len > max_request_size as u64) {
+        return too_large(max_request_size);
+    }
+
+    let body = match read_body(req.into_body(), max_request_size).await {
+        Ok(Some(body)) => body,
+        Ok(None) => return too_large(max_request_size),
+        Err(e) => return error(StatusCode::BAD_REQUEST, &format!("failed to read the body: {e}")),
+    };
+    let request = match ExportMetricsServiceRequest::decode(body) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &format!("invalid OTLP request: {e}")),
+    };
+    if let Err(e) = receiver.export(request).await {
+        log::error!("Failed to process an OTLP/HTTP request: {e:#}");
+        return error(StatusCode::SERVICE_UNAVAILABLE, &e.to_string());
+    }
+
+    let response = ExportMetricsServiceResponse { partial_success: None };
+    Response::builder()
+        .header(header::CONTENT_TYPE, PROTOBUF)
+        .body(Body::from(response.encode_to_vec()))
+        .unwrap()
+}
+
+/// Reads the whole body, or returns `None` as soon as it exceeds `max_size` bytes.
+///
+/// The `content-length` header cannot be trusted (chunked requests don't have one), hence the check on the data.
+async fn read_body(mut body: Body, max_size: usize) -> Result<Option<Bytes>, hyper::Error> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if data.len() + chunk.len() > max_size {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(Bytes::from(data)))
+}
+
+fn too_large(max_request_size: usize) -> Response<Body> {
+    error(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        &format!("the request is larger than {max_request_size} bytes"),
+    )
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    log::debug!("Rejecting OTLP/HTTP request: {message}");
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Body::from(message.to_owned()))
+        .unwrap()
+}
@@ -0,0 +1,146 @@
+mod convert;
+mod grpc;
+mod http;
+mod receiver;
+
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+};
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+
+use crate::{convert::Converter, receiver::Receiver};
+
+pub struct OtlpReceiverPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for OtlpReceiverPlugin {
+    fn name() -> &'static str {
+        "otlp-receiver"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(config.max_request_size > 0, "max_request_size must be greater than zero");
+        if config.grpc_address.is_none() && config.http_address.is_none() {
+            return Err(anyhow!(
+                "Both grpc_address and http_address are disabled, at least one of them must be set."
+            ));
+        }
+        Ok(Box::new(OtlpReceiverPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        // Resolve the addresses right now (fail fast).
+        let grpc_addr = self.config.grpc_address.as_deref().map(resolve).transpose()?;
+        let http_addr = self.config.http_address.as_deref().map(resolve).transpose()?;
+        let resource_attributes = self.config.resource_attributes.clone();
+        let max_request_size = self.config.max_request_size;
+
+        alumet.add_autonomous_source_builder("otlp", move |ctx, cancel_token, out_tx| {
+            let metrics_tx = ctx.metrics_sender();
+            let receiver = Arc::new(Receiver::new(Converter::new(resource_attributes), metrics_tx, out_tx));
+            let source = Box::pin(async move {
+                let grpc = match grpc_addr {
+                    Some(addr) => {
+                        let listener = TcpListener::bind(addr.as_slice())
+                            .await
+                            .context("failed to bind the OTLP/gRPC server")?;
+                        log::info!("Listening for OTLP/gRPC on {}", listener.local_addr()?);
+                        Some(tokio::spawn(grpc::serve(
+                            listener,
+                            receiver.clone(),
+                            max_request_size,
+                            cancel_token.clone(),
+                        )))
+                    }
+                    None => None,
+                };
+                let http = match http_addr {
+                    Some(addr) => {
+                        let listener = std::net::TcpListener::bind(addr.as_slice())
+                            .context("failed to bind the OTLP/HTTP server")?;
+                        log::info!("Listening for OTLP/HTTP on {}", listener.local_addr()?);
+                        Some(tokio::spawn(http::serve(
+                            listener,
+                            receiver,
+                            max_request_size,
+                            cancel_token,
+                        )))
+                    }
+                    None => None,
+                };
+                for server in [grpc, http].into_iter().flatten() {
+                    server.await??;
+                }
+                Ok(())
+            });
+            Ok(source)
+        })?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        // The autonomous source has already been stopped at this point.
+        Ok(())
+    }
+}
+
+fn resolve(address: &str) -> anyhow::Result<Vec<SocketAddr>> {
+    let addrs: Vec<_> = address
+        .to_socket_addrs()
+        .with_context(|| format!("invalid socket address: {address}"))?
+        .collect();
+    Ok(addrs)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the OTLP/gRPC server. Remove it to disable the gRPC transport.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_address: Option<String>,
+    /// Address of the OTLP/HTTP server (binary protobuf on `/v1/metrics`). Remove it to disable the HTTP transport.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_address: Option<String>,
+    /// Attributes of the OTLP resource (the application) that are copied to the measurements.
+    pub resource_attributes: Vec<String>,
+    /// Maximum size of a request, in bytes. Larger requests are rejected.
+    pub max_request_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            grpc_address: Some(String::from("127.0.0.1:4317")),
+            http_address: Some(String::from("127.0.0.1:4318")),
+            resource_attributes: vec![
+                String::from("service.name"),
+                String::from("service.instance.id"),
+                String::from("host.name"),
+            ],
+            max_request_size: 4 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Registration of the OTLP metrics and injection of their measurements into the pipeline.
+
+use std::collections::HashMap;
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementType, WrappedMeasurementValue},
+    metrics::{Metric, RawMetricId, duplicate::DuplicateReaction, online::MetricSender},
+    resources::Resource,
+};
+use anyhow::anyhow;
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+use tokio::sync::{Mutex, mpsc};
+
+use crate::convert::{Converter, MetricDef};
+
+/// Receives the requests of the gRPC and HTTP servers.
+pub struct Receiver {
+    state: Mutex<State>,
+    metrics_tx: MetricSender,
+    out_tx: mpsc::Sender<MeasurementBuffer>,
+}
+
+struct State {
+    converter: Converter,
+    /// Id of each OTLP metric, or `None` if it could not be registered.
+    metric_ids: HashMap<String, Option<RawMetricId>>,
+}
+
+impl Receiver {
+    pub fn new(converter: Converter, metrics_tx: MetricSender, out_tx: mpsc::Sender<MeasurementBuffer>) -> Self {
+        Self {
+            state: Mutex::new(State {
+                converter,
+                metric_ids: HashMap::new(),
+            }),
+            metrics_tx,
+            out_tx,
+        }
+    }
+
+    /// Converts the metrics of an OTLP request and sends them to the pipeline.
+    pub async fn export(&self, request: ExportMetricsServiceRequest) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+        let points = state.converter.convert(request);
+        if points.is_empty() {
+            return Ok(());
+        }
+
+        // register the new metrics
+        let mut new_metrics: Vec<MetricDef> = Vec::new();
+        for p in &points {
+            if !state.metric_ids.contains_key(&p.metric.name) && !new_metrics.iter().any(|m| m.name == p.metric.name) {
+                new_metrics.push(p.metric.clone());
+            }
+        }
+        if !new_metrics.is_empty() {
+            self.register(&mut state, new_metrics).await?;
+        }
+
+        let mut buffer = MeasurementBuffer::with_capacity(points.len());
+        for p in points {
+            let Some(Some(id)) = state.metric_ids.get(&p.metric.name) else {
+                continue;
+            };
+            let point = MeasurementPoint::new_untyped(
+                p.timestamp,
+                *id,
+                Resource::LocalMachine,
+                p.consumer,
+                WrappedMeasurementValue::F64(p.value),
+            )
+            .with_attr_vec(p.attributes);
+            buffer.push(point);
+        }
+        drop(state);
+
+        if !buffer.is_empty() {
+            self.out_tx.send(buffer).await?;
+        }
+        Ok(())
+    }
+
+    async fn register(&self, state: &mut State, defs: Vec<MetricDef>) -> anyhow::Result<()> {
+        let names: Vec<String> = defs.iter().map(|m| m.name.clone()).collect();
+        let metrics = defs
+            .into_iter()
+            .map(|m| Metric {
+                name: m.name,
+                description: m.description,
+                value_type: WrappedMeasurementType::F64,
+                unit: m.unit,
+                tags: Vec::new(),
+            })
+            .collect();
+        let results = self
+            .metrics_tx
+            .create_metrics(metrics, DuplicateReaction::Error)
+            .await
+            .map_err(|e| anyhow!("create_metrics returned an error: {e:?}"))?;
+        for (name, res) in names.into_iter().zip(results) {
+            let id = match res {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    log::error!("The OTLP metric {name} cannot be registered, its data points will be ignored: {e}");
+                    None
+                }
+            };
+            state.metric_ids.insert(name, id);
+        }
+        Ok(())
+    }
+}
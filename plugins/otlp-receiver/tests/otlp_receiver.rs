@@ -0,0 +1,151 @@
+use std::{net::TcpListener, time::Duration};
+
+use alumet::{
+    measurement::{AttributeValue, WrappedMeasurementValue},
+    plugin::rust::serialize_config,
+    resources::ResourceConsumer,
+    test::PluginHarness,
+};
+use opentelemetry_proto::tonic::{
+    collector::metrics::v1::{ExportMetricsServiceRequest, metrics_service_client::MetricsServiceClient},
+    common::v1::{AnyValue, KeyValue, any_value},
+    metrics::v1::{Gauge, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics, metric::Data, number_data_point},
+    resource::v1::Resource,
+};
+use plugin_otlp_receiver::{Config, OtlpReceiverPlugin};
+use pretty_assertions::assert_eq;
+use prost::Message;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn free_address() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+fn request(value: f64) -> ExportMetricsServiceRequest {
+    ExportMetricsServiceRequest {
+        resource_metrics: vec![ResourceMetrics {
+            resource: Some(Resource {
+                attributes: vec![
+                    KeyValue {
+                        key: String::from("service.name"),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::StringValue(String::from("shop"))),
+                        }),
+                    },
+                    KeyValue {
+                        key: String::from("process.pid"),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::IntValue(4321)),
+                        }),
+                    },
+                ],
+                ..Default::default()
+            }),
+            scope_metrics: vec![ScopeMetrics {
+                metrics: vec![Metric {
+                    name: String::from("cart.items"),
+                    unit: String::from("{item}"),
+                    data: Some(Data::Gauge(Gauge {
+                        data_points: vec![NumberDataPoint {
+                            time_unix_nano: 1_700_000_000_000_000_000,
+                            value: Some(number_data_point::Value::AsInt(value as i64)),
+                            ..Default::default()
+                        }],
+                    })),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+    }
+}
+
+#[test]
+fn grpc_and_http() -> anyhow::Result<()> {
+    let grpc_address = free_address();
+    let http_address = free_address();
+    let config = Config {
+        grpc_address: Some(grpc_address.clone()),
+        http_address: Some(http_address.clone()),
+        max_request_size: 1024,
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<OtlpReceiverPlugin>::start(serialize_config(config)?)?;
+    let mut source = harness.autonomous_source("otlp")?;
+    let client = tokio::runtime::Runtime::new()?;
+
+    // gRPC
+    client.block_on(async {
+        let mut grpc = loop {
+            match MetricsServiceClient::connect(format!("http://{grpc_address}")).await {
+                Ok(client) => break client,
+                Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        };
+        grpc.export(request(3.0)).await
+    })?;
+    let buffer = source.recv_timeout(TIMEOUT).expect("measurements should be received");
+    assert_eq!(
+        harness.values(&buffer, "cart.items"),
+        vec![WrappedMeasurementValue::F64(3.0)]
+    );
+    let point = &harness.points(&buffer, "cart.items")[0];
+    assert_eq!(point.consumer, ResourceConsumer::Process { pid: 4321 });
+    let attributes: Vec<_> = point.attributes().map(|(k, v)| (k.to_owned(), v.clone())).collect();
+    assert_eq!(
+        attributes,
+        vec![(
+            String::from("service.name"),
+            AttributeValue::String(String::from("shop"))
+        )]
+    );
+
+    // HTTP
+    let response = client.block_on(async {
+        let request = hyper::Request::post(format!("http://{http_address}/v1/metrics"))
+            .header("Content-Type", "application/x-protobuf")
+            .body(hyper::Body::from(request(5.0).encode_to_vec()))?;
+        anyhow::Ok(hyper::Client::new().request(request).await?)
+    })?;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let buffer = source.recv_timeout(TIMEOUT).expect("measurements should be received");
+    assert_eq!(
+        harness.values(&buffer, "cart.items"),
+        vec![WrappedMeasurementValue::F64(5.0)]
+    );
+
+    // JSON is not supported
+    let response = client.block_on(async {
+        let request = hyper::Request::post(format!("http://{http_address}/v1/metrics"))
+            .header("Content-Type", "application/json")
+            .body(hyper::Body::from("{}"))?;
+        anyhow::Ok(hyper::Client::new().request(request).await?)
+    })?;
+    assert_eq!(response.status(), hyper::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    // the body is too large, with or without a content-length
+    for chunked in [false, true] {
+        let response = client.block_on(async {
+            let payload = vec![0u8; 2048];
+            let body = if chunked {
+                let (mut sender, body) = hyper::Body::channel();
+                tokio::spawn(async move { sender.send_data(payload.into()).await });
+                body
+            } else {
+                hyper::Body::from(payload)
+            };
+            let request = hyper::Request::post(format!("http://{http_address}/v1/metrics"))
+                .header("Content-Type", "application/x-protobuf")
+                .body(body)?;
+            anyhow::Ok(hyper::Client::new().request(request).await?)
+        })?;
+        assert_eq!(response.status(), hyper::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    source.stop()?;
+    harness.stop()?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! File formats that store Arrow record batches.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use arrow_array::RecordBatch;
+use arrow_ipc::writer::{FileWriter, IpcWriteOptions};
+use arrow_schema::SchemaRef;
+use parquet::{arrow::ArrowWriter, file::properties::WriterProperties};
+
+pub trait FileFormat: Send {
+    type Writer: Send;
+
+    /// Extension of the complete files.
+    const EXTENSION: &'static str;
+
+    /// Starts writing a new file, with the given schema.
+    fn create(&self, file: File, schema: SchemaRef) -> anyhow::Result<Self::Writer>;
+
+    fn write(writer: &mut Self::Writer, batch: &RecordBatch) -> anyhow::Result<()>;
+
+    /// Writes the end of the file, which is required to read it.
+    fn finish(writer: Self::Writer) -> anyhow::Result<()>;
+}
+
+/// Apache Parquet files.
+pub struct Parquet {
+    pub properties: WriterProperties,
+}
+
+impl FileFormat for Parquet {
+    type Writer = ArrowWriter<File>;
+
+    const EXTENSION: &'static str = "parquet";
+
+    fn create(&self, file: File, schema: SchemaRef) -> anyhow::Result<Self::Writer> {
+        Ok(ArrowWriter::try_new(file, schema, Some(self.properties.clone()))?)
+    }
+
+    fn write(writer: &mut Self::Writer, batch: &RecordBatch) -> anyhow::Result<()> {
+        Ok(writer.write(batch)?)
+    }
+
+    fn finish(writer: Self::Writer) -> anyhow::Result<()> {
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// Arrow IPC files, also known as Feather V2.
+pub struct ArrowIpc {
+    pub options: IpcWriteOptions,
+}
+
+impl FileFormat for ArrowIpc {
+    type Writer = FileWriter<BufWriter<File>>;
+
+    const EXTENSION: &'static str = "arrow";
+
+    fn create(&self, file: File, schema: SchemaRef) -> anyhow::Result<Self::Writer> {
+        Ok(FileWriter::try_new_with_options(
+            BufWriter::new(file),
+            &schema,
+            self.options.clone(),
+        )?)
+    }
+
+    fn write(writer: &mut Self::Writer, batch: &RecordBatch) -> anyhow::Result<()> {
+        Ok(writer.write(batch)?)
+    }
+
+    fn finish(writer: Self::Writer) -> anyhow::Result<()> {
+        writer.into_inner()?.flush()?;
+        Ok(())
+    }
+}
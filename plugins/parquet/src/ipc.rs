@@ -0,0 +1,99 @@
+//! Output to Arrow IPC files, that analysis tools can map in memory without copying the data.
+
+use std::{path::PathBuf, time::Duration};
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use arrow_ipc::{CompressionType, writer::IpcWriteOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::{format::ArrowIpc, output::PartitionedOutput};
+
+pub struct ArrowIpcPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for ArrowIpcPlugin {
+    fn name() -> &'static str {
+        "arrow-ipc"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(config.directory)]))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(ArrowIpcPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let compression = match self.config.compression {
+            Compression::None => None,
+            Compression::Lz4 => Some(CompressionType::LZ4_FRAME),
+            Compression::Zstd => Some(CompressionType::ZSTD),
+        };
+        let options = IpcWriteOptions::default().try_with_compression(compression)?;
+        let output = PartitionedOutput::new(
+            self.config.directory.clone(),
+            ArrowIpc { options },
+            self.config.max_rows_per_file,
+            Some(self.config.max_file_age),
+        )?;
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Root directory of the files, created if needed.
+    ///
+    /// The files are partitioned by day and metric: `{directory}/date=YYYY-MM-DD/metric={name}/part-*.arrow`.
+    pub directory: PathBuf,
+    /// Compression of the record batches: `"none"`, `"lz4"` or `"zstd"`.
+    ///
+    /// Compressed files cannot be mapped in memory without copying the data.
+    pub compression: Compression,
+    /// Maximum number of rows in a file. When a file is full, it is closed and a new file is created.
+    pub max_rows_per_file: usize,
+    /// Maximum time during which a file is written, before it is closed and can be read.
+    #[serde(with = "humantime_serde")]
+    pub max_file_age: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("alumet-arrow"),
+            compression: Compression::None,
+            max_rows_per_file: 100_000,
+            max_file_age: Duration::from_secs(60),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+mod format;
+pub mod ipc;
+mod output;
+mod schema;
+
+use std::path::PathBuf;
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use parquet::{basic::ZstdLevel, file::properties::WriterProperties};
+use serde::{Deserialize, Serialize};
+
+use crate::{format::Parquet, output::PartitionedOutput};
+
+pub struct ParquetPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for ParquetPlugin {
+    fn name() -> &'static str {
+        "parquet"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(config.directory)]))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(ParquetPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let properties = WriterProperties::builder()
+            .set_compression(self.config.compression.into())
+            .set_max_row_group_row_count(Some(self.config.row_group_size))
+            .build();
+        let output = PartitionedOutput::new(
+            self.config.directory.clone(),
+            Parquet { properties },
+            self.config.max_rows_per_file,
+            None,
+        )?;
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Root directory of the dataset, created if needed.
+    ///
+    /// The files are partitioned by day and metric: `{directory}/date=YYYY-MM-DD/metric={name}/part-*.parquet`.
+    pub directory: PathBuf,
+    /// Compression of the data pages: `"none"`, `"snappy"` or `"zstd"`.
+    pub compression: Compression,
+    /// Maximum number of rows in a row group, which is kept in memory until it is full.
+    pub row_group_size: usize,
+    /// Maximum number of rows in a file. When a file is full, it is closed and a new file is created.
+    pub max_rows_per_file: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Snappy,
+    Zstd,
+}
+
+impl From<Compression> for parquet::basic::Compression {
+    fn from(value: Compression) -> Self {
+        match value {
+            Compression::None => parquet::basic::Compression::UNCOMPRESSED,
+            Compression::Snappy => parquet::basic::Compression::SNAPPY,
+            Compression::Zstd => parquet::basic::Compression::ZSTD(ZstdLevel::default()),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("alumet-parquet"),
+            compression: Compression::Snappy,
+            row_group_size: 100_000,
+            max_rows_per_file: 1_000_000,
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint},
+    metrics::{Metric, RawMetricId},
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use anyhow::Context;
+use arrow_schema::SchemaRef;
+use time::{Date, OffsetDateTime};
+
+use crate::{
+    format::FileFormat,
+    schema::{FileSchema, attribute_types},
+};
+
+/// Suffix of the files that are being written.
+///
+/// They are renamed when they are complete, so that the readers only see valid files.
+const IN_PROGRESS_SUFFIX: &str = "inprogress";
+
+/// Writes the measurements to files partitioned by day and metric.
+pub struct PartitionedOutput<F: FileFormat> {
+    directory: PathBuf,
+    /// Identifies the files of this run, so that they don't replace the files of a previous run.
+    run_id: String,
+    format: F,
+    max_rows_per_file: usize,
+    /// If set, the files are completed after this duration, even if they are not full.
+    max_file_age: Option<Duration>,
+    /// The files that are open, at most one per partition.
+    files: HashMap<Partition, PartitionFile<F>>,
+    /// Number of files created in each partition, used to name the next one.
+    file_counts: HashMap<Partition, usize>,
+}
+
+/// The measurements of a metric on a given day (UTC).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Partition {
+    date: Date,
+    metric: RawMetricId,
+}
+
+struct PartitionFile<F: FileFormat> {
+    writer: F::Writer,
+    schema: FileSchema,
+    arrow_schema: SchemaRef,
+    rows: usize,
+    created: Instant,
+    path: PathBuf,
+}
+
+impl<F: FileFormat> PartitionedOutput<F> {
+    pub fn new(
+        directory: PathBuf,
+        format: F,
+        max_rows_per_file: usize,
+        max_file_age: Option<Duration>,
+    ) -> anyhow::Result<Self> {
+        fs::create_dir_all(&directory).with_context(|| format!("failed to create directory {directory:?}"))?;
+        let now = OffsetDateTime::now_utc();
+        let run_id = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}",
+            now.year(),
+            u8::from(now.month()),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        Ok(Self {
+            directory,
+            run_id,
+            format,
+            max_rows_per_file,
+            max_file_age,
+            files: HashMap::new(),
+            file_counts: HashMap::new(),
+        })
+    }
+
+    fn write_partition(
+        &mut self,
+        partition: Partition,
+        metric: &Metric,
+        points: &[&MeasurementPoint],
+    ) -> anyhow::Result<()> {
+        let attributes = attribute_types(points.iter().copied());
+        let can_append = self
+            .files
+            .get(&partition)
+            .is_some_and(|f| f.schema.accepts(&attributes));
+        if !can_append {
+            // The schema of a Parquet file cannot change: start a new file with more columns.
+            let schema = match self.files.remove(&partition) {
+                Some(file) => {
+                    let mut schema = file.schema.clone();
+                    file.close()?;
+                    schema.merge(attributes);
+                    schema
+                }
+                None => FileSchema::new(metric.value_type.clone(), attributes),
+            };
+            let file = self.create_file(partition, metric, schema)?;
+            self.files.insert(partition, file);
+        }
+
+        let file = self.files.get_mut(&partition).unwrap();
+        let batch = file
+            .schema
+            .record_batch(file.arrow_schema.clone(), points)
+            .context("failed to convert the measurements to Arrow")?;
+        F::write(&mut file.writer, &batch).with_context(|| format!("failed to write to {:?}", file.path))?;
+        file.rows += points.len();
+        if file.rows >= self.max_rows_per_file {
+            self.files.remove(&partition).unwrap().close()?;
+        }
+        Ok(())
+    }
+
+    fn create_file(
+        &mut self,
+        partition: Partition,
+        metric: &Metric,
+        schema: FileSchema,
+    ) -> anyhow::Result<PartitionFile<F>> {
+        let dir = self
+            .directory
+            .join(format!("date={}", partition.date))
+            .join(format!("metric={}", metric.name.replace(['/', '\\'], "_")));
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create directory {dir:?}"))?;
+
+        let count = self.file_counts.entry(partition).or_default();
+        let path = dir.join(format!(
+            "part-{}-{count:04}.{}.{IN_PROGRESS_SUFFIX}",
+            self.run_id,
+            F::EXTENSION
+        ));
+        *count += 1;
+
+        let arrow_schema = schema.to_arrow(metric);
+        let f = File::create(&path).with_context(|| format!("failed to create {path:?}"))?;
+        let writer = self.format.create(f, arrow_schema.clone())?;
+        log::debug!("Writing measurements to {path:?}");
+        Ok(PartitionFile {
+            writer,
+            schema,
+            arrow_schema,
+            rows: 0,
+            created: Instant::now(),
+            path,
+        })
+    }
+
+    /// Closes the files of the days before `date`, and the files that are older than `max_file_age`.
+    fn close_old_files(&mut self, date: Date) -> anyhow::Result<()> {
+        let old: Vec<Partition> = self
+            .files
+            .iter()
+            .filter(|(p, f)| p.date < date || self.max_file_age.is_some_and(|age| f.created.elapsed() >= age))
+            .map(|(p, _)| *p)
+            .collect();
+        for partition in old {
+            self.files.remove(&partition).unwrap().close()?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: FileFormat> PartitionFile<F> {
+    /// Writes the end of the file, and gives it its final name.
+    fn close(self) -> anyhow::Result<()> {
+        F::finish(self.writer).with_context(|| format!("failed to finish {:?}", self.path))?;
+        let final_path = self.path.with_extension("");
+        fs::rename(&self.path, &final_path).with_context(|| format!("failed to rename {:?}", self.path))?;
+        Ok(())
+    }
+}
+
+fn utc_date(m: &MeasurementPoint) -> Date {
+    OffsetDateTime::from(SystemTime::from(m.timestamp)).date()
+}
+
+impl<F: FileFormat> alumet::pipeline::Output for PartitionedOutput<F> {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        // Group the measurements by partition, keeping their order.
+        let mut groups: Vec<(Partition, Vec<&MeasurementPoint>)> = Vec::new();
+        let mut group_index: HashMap<Partition, usize> = HashMap::new();
+        for m in measurements {
+            let partition = Partition {
+                date: utc_date(m),
+                metric: m.metric,
+            };
+            let i = *group_index.entry(partition).or_insert_with(|| {
+                groups.push((partition, Vec::new()));
+                groups.len() - 1
+            });
+            groups[i].1.push(m);
+        }
+
+        for (partition, points) in &groups {
+            let metric = ctx
+                .metrics
+                .by_id(&partition.metric)
+                .with_context(|| format!("Unknown metric {:?}", partition.metric))?;
+            self.write_partition(*partition, metric, points)?;
+        }
+
+        // The measurements of the previous days are complete, unless they arrive late.
+        if let Some(latest) = groups.iter().map(|(p, _)| p.date).max() {
+            self.close_old_files(latest)?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: FileFormat> Drop for PartitionedOutput<F> {
+    fn drop(&mut self) {
+        for (_, file) in self.files.drain() {
+            if let Err(e) = file.close() {
+                log::error!("{e:#}");
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Schema of the Parquet files, derived from the metric and from the attributes of the measurements.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use alumet::{
+    measurement::{AttributeValue, MeasurementPoint, WrappedMeasurementType},
+    metrics::Metric,
+};
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, UInt64Array,
+    builder::{ListBuilder, StringBuilder, TimestampNanosecondBuilder, UInt64Builder},
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+
+/// Columns that are present in every file, before the attributes.
+const FIXED_COLUMNS: [&str; 6] = [
+    "timestamp",
+    "value",
+    "resource_kind",
+    "resource_id",
+    "consumer_kind",
+    "consumer_id",
+];
+
+/// Type of an attribute column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeType {
+    F64,
+    U64,
+    Bool,
+    String,
+    ListU64,
+}
+
+impl AttributeType {
+    pub fn of(value: &AttributeValue) -> Self {
+        match value {
+            AttributeValue::F64(_) => AttributeType::F64,
+            AttributeValue::U64(_) => AttributeType::U64,
+            AttributeValue::Bool(_) => AttributeType::Bool,
+            AttributeValue::Str(_) | AttributeValue::String(_) => AttributeType::String,
+            AttributeValue::ListU64(_) => AttributeType::ListU64,
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            AttributeType::F64 => DataType::Float64,
+            AttributeType::U64 => DataType::UInt64,
+            AttributeType::Bool => DataType::Boolean,
+            AttributeType::String => DataType::Utf8,
+            AttributeType::ListU64 => DataType::List(Arc::new(Field::new_list_field(DataType::UInt64, true))),
+        }
+    }
+
+    /// Returns the type of a column that contains the values of both types.
+    ///
+    /// Any value can be stored in a string column, see [`AttributeValue`]'s `Display`.
+    fn union(self, other: AttributeType) -> AttributeType {
+        if self == other { self } else { AttributeType::String }
+    }
+}
+
+/// Returns the types of the attributes of some measurements, sorted by key.
+pub fn attribute_types<'a>(points: impl IntoIterator<Item = &'a MeasurementPoint>) -> BTreeMap<String, AttributeType> {
+    let mut res = BTreeMap::new();
+    for p in points {
+        for (key, value) in p.attributes() {
+            let t = AttributeType::of(value);
+            res.entry(key.to_owned())
+                .and_modify(|existing: &mut AttributeType| *existing = existing.union(t))
+                .or_insert(t);
+        }
+    }
+    res
+}
+
+/// The columns of a Parquet file.
+///
+/// All the measurements of a file belong to the same metric, hence the value has a single type.
+/// There is one nullable column per attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSchema {
+    value_type: WrappedMeasurementType,
+    attributes: BTreeMap<String, AttributeType>,
+}
+
+impl FileSchema {
+    pub fn new(value_type: WrappedMeasurementType, attributes: BTreeMap<String, AttributeType>) -> Self {
+        Self { value_type, attributes }
+    }
+
+    /// Returns `true` if the attributes can be stored in the columns of this schema.
+    pub fn accepts(&self, attributes: &BTreeMap<String, AttributeType>) -> bool {
+        attributes.iter().all(|(key, t)| {
+            self.attributes
+                .get(key)
+                .is_some_and(|existing| existing == t || *existing == AttributeType::String)
+        })
+    }
+
+    /// Adds the columns that are required to store the given attributes.
+    pub fn merge(&mut self, attributes: BTreeMap<String, AttributeType>) {
+        for (key, t) in attributes {
+            self.attributes
+                .entry(key)
+                .and_modify(|existing| *existing = existing.union(t))
+                .or_insert(t);
+        }
+    }
+
+    /// Returns the Arrow schema of the file.
+    ///
+    /// The name, unit and description of the metric are stored in the metadata of the schema.
+    pub fn to_arrow(&self, metric: &Metric) -> SchemaRef {
+        let value_type = match self.value_type {
+            WrappedMeasurementType::F64 => DataType::Float64,
+            WrappedMeasurementType::U64 => DataType::UInt64,
+        };
+        let mut fields = vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("value", value_type, false),
+            Field::new("resource_kind", DataType::Utf8, false),
+            Field::new("resource_id", DataType::Utf8, false),
+            Field::new("consumer_kind", DataType::Utf8, false),
+            Field::new("consumer_id", DataType::Utf8, false),
+        ];
+        fields.extend(
+            self.attributes
+                .iter()
+                .map(|(key, t)| Field::new(column_name(key), t.data_type(), true)),
+        );
+        let metadata: HashMap<String, String> = [
+            ("alumet.metric.name", metric.name.clone()),
+            ("alumet.metric.unit", metric.unit.unique_name()),
+            ("alumet.metric.description", metric.description.clone()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v))
+        .collect();
+        Arc::new(Schema::new_with_metadata(fields, metadata))
+    }
+
+    /// Converts measurements to a batch of rows, with the columns of `schema`.
+    ///
+    /// `schema` must have been returned by [`FileSchema::to_arrow`].
+    pub fn record_batch(&self, schema: SchemaRef, points: &[&MeasurementPoint]) -> Result<RecordBatch, ArrowError> {
+        let n = points.len();
+        let mut timestamps = TimestampNanosecondBuilder::with_capacity(n).with_timezone("UTC");
+        let mut resource_kinds = StringBuilder::with_capacity(n, n * 8);
+        let mut resource_ids = StringBuilder::with_capacity(n, n * 2);
+        let mut consumer_kinds = StringBuilder::with_capacity(n, n * 8);
+        let mut consumer_ids = StringBuilder::with_capacity(n, n * 2);
+        for p in points {
+            timestamps.append_value(unix_nanos(p));
+            resource_kinds.append_value(p.resource.kind());
+            resource_ids.append_value(p.resource.id_display().to_string());
+            consumer_kinds.append_value(p.consumer.kind());
+            consumer_ids.append_value(p.consumer.id_display().to_string());
+        }
+
+        let values: ArrayRef = match self.value_type {
+            WrappedMeasurementType::F64 => {
+                Arc::new(Float64Array::from_iter_values(points.iter().map(|p| p.value.as_f64())))
+            }
+            WrappedMeasurementType::U64 => {
+                Arc::new(UInt64Array::from_iter_values(points.iter().map(|p| p.value.as_u64())))
+            }
+        };
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(timestamps.finish()),
+            values,
+            Arc::new(resource_kinds.finish()),
+            Arc::new(resource_ids.finish()),
+            Arc::new(consumer_kinds.finish()),
+            Arc::new(consumer_ids.finish()),
+        ];
+        for (key, t) in &self.attributes {
+            columns.push(attribute_column(key, *t, points));
+        }
+        RecordBatch::try_new(schema, columns)
+    }
+}
+
+/// Returns the name of the column of an attribute.
+///
+/// The attributes that have the same name as a fixed column are prefixed with `attr_`.
+fn column_name(key: &str) -> String {
+    if FIXED_COLUMNS.contains(&key) {
+        format!("attr_{key}")
+    } else {
+        key.to_owned()
+    }
+}
+
+fn unix_nanos(p: &MeasurementPoint) -> i64 {
+    let t = SystemTime::from(p.timestamp)
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    i64::try_from(t.as_nanos()).unwrap_or(i64::MAX)
+}
+
+fn attribute<'a>(p: &'a MeasurementPoint, key: &str) -> Option<&'a AttributeValue> {
+    p.attributes().find_map(|(k, v)| (k == key).then_some(v))
+}
+
+/// Builds the column of an attribute. Missing values are null.
+fn attribute_column(key: &str, t: AttributeType, points: &[&MeasurementPoint]) -> ArrayRef {
+    let values = points.iter().map(|p| attribute(p, key));
+    match t {
+        AttributeType::F64 => Arc::new(
+            values
+                .map(|v| match v {
+                    Some(AttributeValue::F64(x)) => Some(*x),
+                    _ => None,
+                })
+                .collect::<Float64Array>(),
+        ),
+        AttributeType::U64 => Arc::new(
+            values
+                .map(|v| match v {
+                    Some(AttributeValue::U64(x)) => Some(*x),
+                    _ => None,
+                })
+                .collect::<UInt64Array>(),
+        ),
+        AttributeType::Bool => Arc::new(
+            values
+                .map(|v| match v {
+                    Some(AttributeValue::Bool(x)) => Some(*x),
+                    _ => None,
+                })
+                .collect::<BooleanArray>(),
+        ),
+        AttributeType::String => Arc::new(values.map(|v| v.map(|v| v.to_string())).collect::<StringArray>()),
+        AttributeType::ListU64 => {
+            let mut list = ListBuilder::new(UInt64Builder::new());
+            for v in values {
+                match v {
+                    Some(AttributeValue::ListU64(items)) => {
+                        list.values().append_slice(items);
+                        list.append(true);
+                    }
+                    _ => list.append_null(),
+                }
+            }
+            Arc::new(list.finish())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue},
+        metrics::{Metric, RawMetricId},
+        resources::{Resource, ResourceConsumer},
+        units::{PrefixedUnit, Unit},
+    };
+    use arrow_array::{Array, StringArray, UInt64Array};
+    use pretty_assertions::assert_eq;
+
+    use super::{AttributeType, FileSchema, attribute_types};
+
+    fn point() -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            Timestamp::from(UNIX_EPOCH + Duration::new(1_700_000_000, 5)),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::U64(3),
+        )
+    }
+
+    #[test]
+    fn conflicting_attributes() {
+        let points = [
+            point().with_attr("cpu", 1_u64).with_attr("value", "x"),
+            point().with_attr("cpu", "all"),
+            point(),
+        ];
+        let types = attribute_types(&points);
+        assert_eq!(types["cpu"], AttributeType::String);
+
+        let schema = FileSchema::new(WrappedMeasurementType::U64, types);
+        let metric = Metric {
+            name: String::from("counter"),
+            description: String::new(),
+            value_type: WrappedMeasurementType::U64,
+            unit: PrefixedUnit::from(Unit::Unity),
+            tags: Vec::new(),
+        };
+        let arrow_schema = schema.to_arrow(&metric);
+        let batch = schema
+            .record_batch(arrow_schema, &points.iter().collect::<Vec<_>>())
+            .unwrap();
+
+        let cpu = batch.column_by_name("cpu").unwrap();
+        let cpu = cpu.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(cpu.iter().collect::<Vec<_>>(), vec![Some("1"), Some("all"), None]);
+        assert_eq!(batch.column_by_name("attr_value").unwrap().null_count(), 2);
+        let value = batch.column_by_name("value").unwrap();
+        let value = value.as_any().downcast_ref::<UInt64Array>().unwrap();
+        assert_eq!(value.values().to_vec(), vec![3, 3, 3]);
+    }
+
+    #[test]
+    fn accepts() {
+        let mut schema = FileSchema::new(
+            WrappedMeasurementType::F64,
+            attribute_types(&[point().with_attr("state", "idle")]),
+        );
+        assert!(schema.accepts(&attribute_types(&[point()])));
+        assert!(schema.accepts(&attribute_types(&[point().with_attr("state", 1_u64)])));
+        assert!(!schema.accepts(&attribute_types(&[point().with_attr("cpu", 1_u64)])));
+
+        schema.merge(attribute_types(&[point().with_attr("cpu", 1_u64)]));
+        assert!(schema.accepts(&attribute_types(&[point().with_attr("cpu", 2_u64)])));
+        assert!(!schema.accepts(&attribute_types(&[point().with_attr("cpu", true)])));
+    }
+}
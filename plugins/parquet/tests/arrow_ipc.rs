@@ -0,0 +1,55 @@
+use std::{fs::File, path::Path, time::Duration};
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{PluginHarness, golden::SampleMeasurements},
+};
+use arrow_array::{Array, Float64Array, RecordBatch};
+use arrow_ipc::reader::FileReader;
+use plugin_parquet::ipc::{ArrowIpcPlugin, Config};
+use pretty_assertions::assert_eq;
+
+/// Returns the names of the files of a partition.
+fn list_files(dir: &Path) -> Vec<String> {
+    let mut res: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    res.sort();
+    res
+}
+
+#[test]
+fn files_complete_after_max_age() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = Config {
+        directory: dir.path().to_owned(),
+        max_file_age: Duration::ZERO,
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<ArrowIpcPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let energy_dir = dir.path().join("date=2023-11-14/metric=sample_energy");
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    // the files can be read while the agent is running
+    let files = list_files(&energy_dir);
+    assert_eq!(files.len(), 1);
+    assert!(files[0].starts_with("part-") && files[0].ends_with("-0000.arrow"));
+
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+    assert_eq!(list_files(&energy_dir).len(), 2);
+
+    let reader = FileReader::try_new(File::open(energy_dir.join(&files[0]))?, None)?;
+    assert_eq!(reader.schema().metadata()["alumet.metric.unit"], "J");
+    let batches: Vec<RecordBatch> = reader.collect::<Result<_, _>>()?;
+    assert_eq!(batches.len(), 1);
+    let values = batches[0].column_by_name("value").unwrap();
+    let values = values.as_any().downcast_ref::<Float64Array>().unwrap();
+    assert_eq!(values.values().to_vec(), vec![12.5, 3.25, 150.0]);
+    assert_eq!(batches[0].column_by_name("model").unwrap().null_count(), 2);
+    Ok(())
+}
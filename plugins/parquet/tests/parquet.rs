@@ -0,0 +1,192 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    resources::{Resource, ResourceConsumer},
+    test::golden::SampleMeasurements,
+};
+use arrow_array::{Array, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampNanosecondArray, UInt64Array};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use plugin_parquet::{Config, ParquetPlugin};
+use pretty_assertions::assert_eq;
+
+/// Returns the files of the dataset, relative to its root, sorted.
+fn list_files(root: &Path) -> Vec<String> {
+    fn visit(dir: &Path, root: &Path, res: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                visit(&path, root, res);
+            } else {
+                res.push(path.strip_prefix(root).unwrap().to_string_lossy().into_owned());
+            }
+        }
+    }
+    let mut res = Vec::new();
+    visit(root, root, &mut res);
+    res.sort();
+    res
+}
+
+/// Removes the identifier of the run from the name of a file.
+fn without_run_id(path: &str) -> String {
+    let (dir, file) = path.rsplit_once('/').unwrap();
+    let (_, suffix) = file.rsplit_once('-').unwrap();
+    format!("{dir}/part-{suffix}")
+}
+
+/// Reads the rows of a file, with the schema that is stored in the file (including its metadata).
+fn read(path: PathBuf) -> RecordBatch {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+    let schema = builder.schema().clone();
+    let batches: Vec<RecordBatch> = builder.build().unwrap().map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 1);
+    batches.into_iter().next().unwrap().with_schema(schema).unwrap()
+}
+
+fn config(dir: &Path) -> Config {
+    Config {
+        directory: dir.to_owned(),
+        ..Config::default()
+    }
+}
+
+#[test]
+fn partitioned_files() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (mut harness, sample) = SampleMeasurements::start::<ParquetPlugin>(config(dir.path()))?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let files = list_files(dir.path());
+    assert_eq!(
+        files.iter().map(|f| without_run_id(f)).collect::<Vec<_>>(),
+        vec![
+            "date=2023-11-14/metric=sample_count/part-0000.parquet",
+            "date=2023-11-14/metric=sample_energy/part-0000.parquet",
+        ]
+    );
+
+    let count = read(dir.path().join(&files[0]));
+    let columns: Vec<&str> = count.schema_ref().fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(
+        columns,
+        vec![
+            "timestamp",
+            "value",
+            "resource_kind",
+            "resource_id",
+            "consumer_kind",
+            "consumer_id",
+            "cpu",
+            "state",
+            "throttled",
+        ]
+    );
+    assert_eq!(count.schema_ref().metadata()["alumet.metric.name"], "sample_count");
+    assert_eq!(
+        count.schema_ref().metadata()["alumet.metric.description"],
+        "number of events"
+    );
+
+    let column = |name: &str| count.column_by_name(name).unwrap().clone();
+    let start_nanos = SampleMeasurements::START.as_nanos() as i64;
+    assert_eq!(
+        column("timestamp")
+            .as_any()
+            .downcast_ref::<TimestampNanosecondArray>()
+            .unwrap()
+            .values()
+            .to_vec(),
+        vec![start_nanos + 500_000_000, start_nanos + 1_000_000_000]
+    );
+    let values = column("value");
+    let values = values.as_any().downcast_ref::<UInt64Array>().unwrap();
+    assert_eq!(values.values().to_vec(), vec![42, 7]);
+    let consumer_ids = column("consumer_id");
+    let consumer_ids = consumer_ids.as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(
+        consumer_ids.iter().collect::<Vec<_>>(),
+        vec![Some("1234"), Some("/system.slice/test.service")]
+    );
+    let cpu = column("cpu");
+    let cpu = cpu.as_any().downcast_ref::<UInt64Array>().unwrap();
+    assert_eq!(cpu.iter().collect::<Vec<_>>(), vec![Some(3), None]);
+    let throttled = column("throttled");
+    let throttled = throttled.as_any().downcast_ref::<BooleanArray>().unwrap();
+    assert_eq!(throttled.iter().collect::<Vec<_>>(), vec![None, Some(true)]);
+
+    let energy = read(dir.path().join(&files[1]));
+    assert_eq!(energy.schema_ref().metadata()["alumet.metric.unit"], "J");
+    let values = energy.column_by_name("value").unwrap();
+    let values = values.as_any().downcast_ref::<Float64Array>().unwrap();
+    assert_eq!(values.values().to_vec(), vec![12.5, 3.25, 150.0]);
+    assert_eq!(energy.column_by_name("model").unwrap().null_count(), 2);
+    Ok(())
+}
+
+#[test]
+fn new_files_for_new_attributes_and_days() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let (mut harness, sample) = SampleMeasurements::start::<ParquetPlugin>(config(dir.path()))?;
+    let day1 = Timestamp::from(UNIX_EPOCH + SampleMeasurements::START);
+    let day2 = day1 + Duration::from_secs(86400);
+    let point = |t| {
+        MeasurementPoint::new(
+            t,
+            sample.energy,
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            1.0,
+        )
+    };
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    // an attribute that is not in the schema of the current file
+    output
+        .write(&MeasurementBuffer::from(vec![
+            point(day1).with_attr("domain", "package"),
+        ]))
+        .unwrap();
+    let files: Vec<String> = list_files(dir.path()).iter().map(|f| without_run_id(f)).collect();
+    assert_eq!(
+        files,
+        vec![
+            "date=2023-11-14/metric=sample_count/part-0000.parquet.inprogress",
+            "date=2023-11-14/metric=sample_energy/part-0000.parquet",
+            "date=2023-11-14/metric=sample_energy/part-0001.parquet.inprogress",
+        ]
+    );
+
+    // the next day: the files of the previous day are complete
+    output.write(&MeasurementBuffer::from(vec![point(day2)])).unwrap();
+    let files: Vec<String> = list_files(dir.path()).iter().map(|f| without_run_id(f)).collect();
+    assert_eq!(
+        files,
+        vec![
+            "date=2023-11-14/metric=sample_count/part-0000.parquet",
+            "date=2023-11-14/metric=sample_energy/part-0000.parquet",
+            "date=2023-11-14/metric=sample_energy/part-0001.parquet",
+            "date=2023-11-15/metric=sample_energy/part-0000.parquet.inprogress",
+        ]
+    );
+
+    output.finish()?;
+    harness.stop()?;
+
+    let files = list_files(dir.path());
+    assert!(files.iter().all(|f| f.ends_with(".parquet")));
+    let merged = read(dir.path().join(&files[2]));
+    assert_eq!(merged.num_rows(), 1);
+    let columns: Vec<&str> = merged.schema_ref().fields().iter().map(|f| f.name().as_str()).collect();
+    assert_eq!(columns[6..], ["domain", "model"]);
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! Readings of a PDU, common to the different APIs.
+
+use std::time::Duration;
+
+use alumet::pipeline::elements::source::blocking::BlockingDrop;
+
+use reqwest::blocking::{Client, RequestBuilder};
+
+/// API of a PDU, which gives the readings of its outlets and of its phases.
+pub trait PduApi: Send {
+    /// Reads the outlets and the phases of the PDU.
+    fn read(&mut self) -> anyhow::Result<Readings>;
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Readings {
+    pub outlets: Vec<OutletReading>,
+    pub phases: Vec<PhaseReading>,
+}
+
+/// Readings of an outlet.
+#[derive(Debug, Default, PartialEq)]
+pub struct OutletReading {
+    /// Id of the outlet, as displayed by the PDU (ex. `1` or `A1`).
+    pub id: String,
+    /// Active power, in Watts.
+    pub power: Option<f64>,
+    /// Total energy consumed since the counter of the PDU has been reset, in Joules.
+    pub energy: Option<f64>,
+}
+
+/// Readings of a phase of an inlet.
+#[derive(Debug, Default, PartialEq)]
+pub struct PhaseReading {
+    /// Id of the inlet (ex. `I1` or `AC1`).
+    pub inlet: String,
+    /// Name of the phase (ex. `L1`).
+    pub phase: String,
+    /// Active power, in Watts.
+    pub power: Option<f64>,
+    /// Current, in Amperes.
+    pub current: Option<f64>,
+    /// Voltage between the phase and the neutral, in Volts.
+    pub voltage: Option<f64>,
+}
+
+/// Connection settings of the HTTP API of a PDU.
+pub struct HttpSettings {
+    /// Base URL of the PDU, such as `https://192.168.1.60`.
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub timeout: Duration,
+    /// Accept the self-signed certificates that most PDUs use by default.
+    pub accept_invalid_certs: bool,
+}
+
+/// An HTTP client for the API of a PDU, which authenticates with HTTP Basic.
+pub struct HttpClient {
+    client: BlockingDrop<Client>,
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl HttpClient {
+    pub fn new(settings: HttpSettings) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .timeout(settings.timeout)
+            .danger_accept_invalid_certs(settings.accept_invalid_certs)
+            .build()?;
+        Ok(Self {
+            client: BlockingDrop::new(client),
+            url: settings.url.trim_end_matches('/').to_owned(),
+            username: settings.username,
+            password: settings.password,
+        })
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.authenticate(self.client.get(self.url(path)))
+    }
+
+    pub fn post(&self, path: &str) -> RequestBuilder {
+        self.authenticate(self.client.post(self.url(path)))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.url)
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_ref()),
+            None => request,
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use api::{HttpClient, HttpSettings, PduApi};
+use source::PduSource;
+
+mod api;
+mod metrics;
+mod raritan;
+mod redfish;
+mod source;
+
+pub struct PduPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for PduPlugin {
+    fn name() -> &'static str {
+        "pdu"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(PduPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        if self.config.pdus.is_empty() {
+            return Err(anyhow!("No PDU configured, please add some to the configuration."));
+        }
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        for pdu in &self.config.pdus {
+            let client = HttpClient::new(HttpSettings {
+                url: pdu.url.clone(),
+                username: pdu.username.clone(),
+                password: pdu.password.clone(),
+                timeout: self.config.timeout,
+                accept_invalid_certs: pdu.accept_invalid_certs,
+            })
+            .with_context(|| format!("failed to create the HTTP client of PDU {}", pdu.name))?;
+            // The PDU is contacted on the first poll, so that an unreachable PDU does not prevent the agent from starting.
+            let api: Box<dyn PduApi> = match pdu.api {
+                Api::Redfish => Box::new(redfish::Redfish::new(client)),
+                Api::Raritan => Box::new(raritan::Raritan::new(client)),
+            };
+
+            let source = PduSource::new(api, pdu.name.clone(), pdu.outlets.clone(), metrics);
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&format!("pdu_{}", pdu.name), Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// How long to wait for the response of a PDU.
+    #[serde(with = "humantime_serde")]
+    timeout: Duration,
+
+    pdus: Vec<Pdu>,
+}
+
+/// A PDU, reachable through its HTTP API.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Pdu {
+    /// Name of the PDU, used as the id of the resource of its measurements.
+    name: String,
+
+    /// API of the PDU.
+    api: Api,
+
+    /// Base URL of the PDU, such as `https://192.168.1.60`.
+    url: String,
+
+    /// Credentials of the PDU (HTTP Basic authentication).
+    username: Option<String>,
+    password: Option<String>,
+
+    /// Accept invalid TLS certificates, such as the self-signed certificates of the PDUs.
+    #[serde(default)]
+    accept_invalid_certs: bool,
+
+    /// Node that is plugged in each outlet, by outlet id.
+    #[serde(default)]
+    outlets: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Api {
+    /// DMTF Redfish (`PowerEquipment`), available on recent firmwares of APC, Eaton and Raritan PDUs.
+    Redfish,
+    /// JSON-RPC API of the Raritan PDUs.
+    Raritan,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(10),
+            flush_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+            pdus: vec![Pdu {
+                name: String::from("rack1-a"),
+                api: Api::Redfish,
+                url: String::from("https://192.168.1.60"),
+                username: Some(String::from("admin")),
+                password: Some(String::from("admin")),
+                accept_invalid_certs: false,
+                outlets: BTreeMap::from([(String::from("1"), String::from("node-1"))]),
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::Unit,
+    };
+    use mockito::Server;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<PduPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    fn mock_get(server: &mut Server, path: &str, body: serde_json::Value) -> mockito::Mock {
+        server
+            .mock("GET", path)
+            .match_header("authorization", "Basic YWRtaW46YWRtaW4=")
+            .with_body(body.to_string())
+            .create()
+    }
+
+    #[test]
+    fn redfish_pdu() {
+        let mut server = Server::new();
+        let pdu = "/redfish/v1/PowerEquipment/RackPDUs/1";
+        mock_get(
+            &mut server,
+            "/redfish/v1/PowerEquipment/RackPDUs",
+            json!({ "Members": [{ "@odata.id": pdu }] }),
+        );
+        mock_get(
+            &mut server,
+            &format!("{pdu}/Outlets"),
+            json!({ "Members": [{ "@odata.id": format!("{pdu}/Outlets/1") }, { "@odata.id": format!("{pdu}/Outlets/2") }] }),
+        );
+        mock_get(
+            &mut server,
+            &format!("{pdu}/Mains"),
+            json!({ "Members": [{ "@odata.id": format!("{pdu}/Mains/AC1") }] }),
+        );
+        // energy counter of the outlet 1, in kWh
+        let energy = Arc::new(Mutex::new(10.0));
+        let outlet_energy = energy.clone();
+        server
+            .mock("GET", format!("{pdu}/Outlets/1").as_str())
+            .with_body_from_request(move |_| {
+                let kwh = *outlet_energy.lock().unwrap();
+                json!({ "Id": "1", "PowerWatts": { "Reading": 150.0 }, "EnergykWh": { "Reading": kwh } })
+                    .to_string()
+                    .into_bytes()
+            })
+            .create();
+        mock_get(
+            &mut server,
+            &format!("{pdu}/Outlets/2"),
+            json!({ "Id": "2", "PowerWatts": { "Reading": 0.0 } }),
+        );
+        mock_get(
+            &mut server,
+            &format!("{pdu}/Mains/AC1"),
+            json!({ "Id": "AC1", "PowerWatts": { "Reading": 151.0 }, "Voltage": { "Reading": 230.0 } }),
+        );
+
+        let mut config = Config {
+            poll_interval: Duration::from_millis(100),
+            ..Config::default()
+        };
+        config.pdus[0].url = server.url();
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<f64>("pdu_outlet_power", Unit::Watt)
+            .expect_metric::<f64>("pdu_outlet_energy", Unit::Joule)
+            .expect_metric::<f64>("pdu_phase_power", Unit::Watt)
+            .expect_metric::<f64>("pdu_phase_current", Unit::Ampere)
+            .expect_metric::<f64>("pdu_phase_voltage", Unit::Volt)
+            .expect_source("pdu", "pdu_rack1-a");
+
+        let source = SourceName::from_str("pdu", "pdu_rack1-a");
+        let runtime = RuntimeExpectations::new()
+            .test_source(
+                source.clone(),
+                || (),
+                |ctx| {
+                    // no energy on the first measurement
+                    let m = ctx.measurements();
+                    let values: Vec<_> = m.iter().map(|m| m.value.clone()).collect();
+                    assert_eq!(
+                        values,
+                        vec![
+                            WrappedMeasurementValue::F64(150.0),
+                            WrappedMeasurementValue::F64(0.0),
+                            WrappedMeasurementValue::F64(151.0),
+                            WrappedMeasurementValue::F64(230.0),
+                        ]
+                    );
+                    let outlets: Vec<_> = m
+                        .iter()
+                        .take(2)
+                        .map(|m| (m.consumer.id_display().to_string(), m.resource.id_display().to_string()))
+                        .collect();
+                    assert_eq!(
+                        outlets,
+                        vec![
+                            (String::from("node-1"), String::from("rack1-a")),
+                            (String::from(""), String::from("rack1-a"))
+                        ]
+                    );
+                },
+            )
+            .test_source(
+                source,
+                move || *energy.lock().unwrap() = 10.5,
+                |ctx| {
+                    let energy = ctx.metrics().by_name("pdu_outlet_energy").unwrap().0;
+                    let values: Vec<_> = ctx
+                        .measurements()
+                        .iter()
+                        .filter(|m| m.metric == energy)
+                        .map(|m| m.value.clone())
+                        .collect();
+                    assert_eq!(values, vec![WrappedMeasurementValue::F64(1_800_000.0)]);
+                },
+            );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+
+    #[test]
+    fn no_pdu() {
+        let mut config = Config::default();
+        config.pdus.clear();
+        let agent = agent::Builder::new(plugins(&config)).build_and_start();
+        assert!(agent.is_err(), "plugin should not start");
+    }
+}
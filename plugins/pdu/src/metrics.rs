@@ -0,0 +1,43 @@
+use alumet::{metrics::TypedMetricId, plugin::AlumetPluginStart, units::Unit};
+
+/// Metrics of the PDUs, shared by all their sources.
+#[derive(Clone, Copy)]
+pub struct Metrics {
+    pub outlet_power: TypedMetricId<f64>,
+    pub outlet_energy: TypedMetricId<f64>,
+    pub phase_power: TypedMetricId<f64>,
+    pub phase_current: TypedMetricId<f64>,
+    pub phase_voltage: TypedMetricId<f64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            outlet_power: alumet.create_metric(
+                "pdu_outlet_power",
+                Unit::Watt,
+                "Active power of an outlet of the PDU",
+            )?,
+            outlet_energy: alumet.create_metric(
+                "pdu_outlet_energy",
+                Unit::Joule,
+                "Energy consumed on an outlet of the PDU since the previous measurement",
+            )?,
+            phase_power: alumet.create_metric(
+                "pdu_phase_power",
+                Unit::Watt,
+                "Active power of a phase of an inlet of the PDU",
+            )?,
+            phase_current: alumet.create_metric(
+                "pdu_phase_current",
+                Unit::Ampere,
+                "Current of a phase of an inlet of the PDU",
+            )?,
+            phase_voltage: alumet.create_metric(
+                "pdu_phase_voltage",
+                Unit::Volt,
+                "Voltage between a phase of an inlet of the PDU and the neutral",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,348 @@
+//! Readings of a Raritan PDU (PX2, PX3) through its JSON-RPC API, for the firmwares without Redfish.
+//!
+//! The objects of the PDU are identified by resource ids (`rid`), which are also the paths of their endpoints.
+//! The sensors of the outlets and of the poles (phases) of the inlets are discovered on the first read,
+//! then all their readings are obtained with one bulk request.
+
+use anyhow::{Context, anyhow};
+use serde_json::{Value, json};
+
+use crate::api::{HttpClient, OutletReading, PduApi, PhaseReading, Readings};
+
+const PDU_RID: &str = "/model/pdu/0";
+const BULK_RID: &str = "/bulk";
+
+pub struct Raritan {
+    client: HttpClient,
+    sensors: Option<Vec<Sensor>>,
+}
+
+/// A numeric sensor of the PDU.
+#[derive(Debug, Clone, PartialEq)]
+struct Sensor {
+    rid: String,
+    location: Location,
+    quantity: Quantity,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Location {
+    Outlet(String),
+    Pole { inlet: String, phase: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quantity {
+    /// Active power, in Watts.
+    Power,
+    /// Active energy, in Watt-hours.
+    Energy,
+    /// Current, in Amperes.
+    Current,
+    /// Voltage, in Volts.
+    Voltage,
+}
+
+/// Sensors of an outlet or pole, by name in the JSON-RPC API.
+const SENSORS: [(&str, Quantity); 4] = [
+    ("activePower", Quantity::Power),
+    ("activeEnergy", Quantity::Energy),
+    ("current", Quantity::Current),
+    ("voltage", Quantity::Voltage),
+];
+
+impl Raritan {
+    pub fn new(client: HttpClient) -> Self {
+        Self { client, sensors: None }
+    }
+
+    /// Calls a method of an object and returns its result.
+    fn call(&self, rid: &str, method: &str, params: Value) -> anyhow::Result<Value> {
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+        let response: Value = self
+            .client
+            .post(rid)
+            .json(&request)
+            .send()
+            .with_context(|| format!("request to {rid} failed"))?
+            .error_for_status()?
+            .json()?;
+        rpc_result(response).with_context(|| format!("{method} on {rid} failed"))
+    }
+
+    fn discover(&self) -> anyhow::Result<Vec<Sensor>> {
+        let mut sensors = Vec::new();
+
+        let outlets = self.call(PDU_RID, "getOutlets", json!({}))?;
+        for outlet in objects(&outlets)? {
+            let label = self.label(outlet)?;
+            let outlet_sensors = self.call(outlet, "getSensors", json!({}))?;
+            sensors.extend(sensors_of(&outlet_sensors, Location::Outlet(label)));
+        }
+
+        let inlets = self.call(PDU_RID, "getInlets", json!({}))?;
+        for inlet in objects(&inlets)? {
+            let inlet_label = self.label(inlet)?;
+            let poles = self.call(inlet, "getPoles", json!({}))?;
+            for pole in poles.as_array().into_iter().flatten() {
+                let location = Location::Pole {
+                    inlet: inlet_label.clone(),
+                    phase: pole["label"].as_str().unwrap_or_default().to_owned(),
+                };
+                sensors.extend(sensors_of(pole, location));
+            }
+        }
+        log::debug!("Found {} sensors in the Raritan PDU.", sensors.len());
+        Ok(sensors)
+    }
+
+    fn label(&self, rid: &str) -> anyhow::Result<String> {
+        let metadata = self.call(rid, "getMetaData", json!({}))?;
+        metadata["label"]
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("no label in the metadata of {rid}"))
+    }
+
+    /// Reads all the sensors with one bulk request.
+    fn read_sensors(&self, sensors: &[Sensor]) -> anyhow::Result<Readings> {
+        let requests: Vec<Value> = sensors
+            .iter()
+            .map(|s| json!({ "rid": s.rid, "json": { "jsonrpc": "2.0", "method": "getReading", "id": 1 } }))
+            .collect();
+        let bulk = self.call(BULK_RID, "performBulk", json!({ "requests": requests }))?;
+        let responses = bulk["responses"]
+            .as_array()
+            .ok_or_else(|| anyhow!("invalid bulk response: no responses"))?;
+        if responses.len() != sensors.len() {
+            return Err(anyhow!(
+                "invalid bulk response: {} responses for {} requests",
+                responses.len(),
+                sensors.len()
+            ));
+        }
+
+        let mut readings = Readings::default();
+        for (sensor, response) in sensors.iter().zip(responses) {
+            let reading =
+                rpc_result(response["json"].clone()).with_context(|| format!("getReading on {} failed", sensor.rid))?;
+            if !reading["valid"].as_bool().unwrap_or(false) {
+                continue;
+            }
+            let Some(value) = reading["value"].as_f64() else {
+                continue;
+            };
+            readings.set(&sensor.location, sensor.quantity, value);
+        }
+        Ok(readings)
+    }
+}
+
+impl PduApi for Raritan {
+    fn read(&mut self) -> anyhow::Result<Readings> {
+        let sensors = match self.sensors.take() {
+            Some(sensors) => sensors,
+            None => self.discover()?,
+        };
+        let readings = self.read_sensors(&sensors)?;
+        // on error, the sensors may have changed: discover them again on the next read
+        self.sensors = Some(sensors);
+        Ok(readings)
+    }
+}
+
+impl Readings {
+    /// Sets a value of an outlet or phase, which is added if it does not exist yet.
+    fn set(&mut self, location: &Location, quantity: Quantity, value: f64) {
+        match location {
+            Location::Outlet(id) => {
+                let outlet = match self.outlets.iter().position(|o| &o.id == id) {
+                    Some(i) => &mut self.outlets[i],
+                    None => {
+                        self.outlets.push(OutletReading {
+                            id: id.clone(),
+                            ..Default::default()
+                        });
+                        self.outlets.last_mut().unwrap()
+                    }
+                };
+                match quantity {
+                    Quantity::Power => outlet.power = Some(value),
+                    Quantity::Energy => outlet.energy = Some(value * 3600.0),
+                    Quantity::Current | Quantity::Voltage => (),
+                }
+            }
+            Location::Pole { inlet, phase } => {
+                let pole = match self.phases.iter().position(|p| &p.inlet == inlet && &p.phase == phase) {
+                    Some(i) => &mut self.phases[i],
+                    None => {
+                        self.phases.push(PhaseReading {
+                            inlet: inlet.clone(),
+                            phase: phase.clone(),
+                            ..Default::default()
+                        });
+                        self.phases.last_mut().unwrap()
+                    }
+                };
+                match quantity {
+                    Quantity::Power => pole.power = Some(value),
+                    Quantity::Current => pole.current = Some(value),
+                    Quantity::Voltage => pole.voltage = Some(value),
+                    Quantity::Energy => (),
+                }
+            }
+        }
+    }
+}
+
+/// Extracts the return value of a JSON-RPC response.
+fn rpc_result(response: Value) -> anyhow::Result<Value> {
+    if let Some(error) = response.get("error") {
+        let message = error["message"].as_str().unwrap_or("unknown error");
+        return Err(anyhow!("JSON-RPC error: {message}"));
+    }
+    let mut result = response
+        .get("result")
+        .cloned()
+        .ok_or_else(|| anyhow!("invalid JSON-RPC response: no result"))?;
+    Ok(result.get_mut("_ret_").map(Value::take).unwrap_or(result))
+}
+
+/// Returns the resource ids of a list of object references.
+fn objects(references: &Value) -> anyhow::Result<Vec<&str>> {
+    references
+        .as_array()
+        .ok_or_else(|| anyhow!("invalid list of objects: {references}"))?
+        .iter()
+        .map(|r| {
+            r["rid"]
+                .as_str()
+                .ok_or_else(|| anyhow!("invalid object reference: {r}"))
+        })
+        .collect()
+}
+
+/// Returns the sensors of an outlet or pole, whose references are in the fields of `object`.
+fn sensors_of(object: &Value, location: Location) -> Vec<Sensor> {
+    SENSORS
+        .iter()
+        .filter_map(|(name, quantity)| {
+            let rid = object[*name]["rid"].as_str()?;
+            Some(Sensor {
+                rid: rid.to_owned(),
+                location: location.clone(),
+                quantity: *quantity,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mockito::{Matcher, Server};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::api::HttpSettings;
+
+    fn rpc(server: &mut Server, rid: &str, method: &str, ret: Value) -> mockito::Mock {
+        server
+            .mock("POST", rid)
+            .match_body(Matcher::PartialJson(json!({ "method": method })))
+            .with_body(json!({ "jsonrpc": "2.0", "result": { "_ret_": ret }, "id": 1 }).to_string())
+            .create()
+    }
+
+    #[test]
+    fn outlets_and_poles() {
+        let mut server = Server::new();
+        rpc(
+            &mut server,
+            PDU_RID,
+            "getOutlets",
+            json!([{ "rid": "/tfwopaque/outlet.0", "type": "pdumodel.Outlet_2_1_5" }]),
+        );
+        rpc(
+            &mut server,
+            "/tfwopaque/outlet.0",
+            "getMetaData",
+            json!({ "label": "1" }),
+        );
+        rpc(
+            &mut server,
+            "/tfwopaque/outlet.0",
+            "getSensors",
+            json!({
+                "activePower": { "rid": "/tfwopaque/outlet.0.power" },
+                "activeEnergy": { "rid": "/tfwopaque/outlet.0.energy" },
+                "current": null
+            }),
+        );
+        rpc(
+            &mut server,
+            PDU_RID,
+            "getInlets",
+            json!([{ "rid": "/tfwopaque/inlet.0", "type": "pdumodel.Inlet_2_0_5" }]),
+        );
+        rpc(
+            &mut server,
+            "/tfwopaque/inlet.0",
+            "getMetaData",
+            json!({ "label": "I1" }),
+        );
+        rpc(
+            &mut server,
+            "/tfwopaque/inlet.0",
+            "getPoles",
+            json!([{
+                "label": "L1",
+                "current": { "rid": "/tfwopaque/pole.0.current" },
+                "voltage": { "rid": "/tfwopaque/pole.0.voltage" }
+            }]),
+        );
+        let reading = |valid: bool, value: f64| json!({ "json": { "jsonrpc": "2.0", "result": { "_ret_": { "valid": valid, "value": value } }, "id": 1 } });
+        rpc(
+            &mut server,
+            BULK_RID,
+            "performBulk",
+            json!({ "responses": [reading(true, 85.0), reading(true, 2.0), reading(true, 0.4), reading(false, 0.0)] }),
+        );
+
+        let client = HttpClient::new(HttpSettings {
+            url: server.url(),
+            username: Some(String::from("admin")),
+            password: Some(String::from("secret")),
+            timeout: Duration::from_secs(1),
+            accept_invalid_certs: false,
+        })
+        .unwrap();
+        let mut raritan = Raritan::new(client);
+        let readings = raritan.read().unwrap();
+        assert_eq!(
+            readings,
+            Readings {
+                outlets: vec![OutletReading {
+                    id: String::from("1"),
+                    power: Some(85.0),
+                    energy: Some(7200.0),
+                }],
+                phases: vec![PhaseReading {
+                    inlet: String::from("I1"),
+                    phase: String::from("L1"),
+                    power: None,
+                    current: Some(0.4),
+                    voltage: None,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn rpc_error() {
+        let response = json!({ "jsonrpc": "2.0", "error": { "code": -32601, "message": "Method not found" }, "id": 1 });
+        let err = rpc_result(response).unwrap_err();
+        assert_eq!(err.to_string(), "JSON-RPC error: Method not found");
+    }
+}
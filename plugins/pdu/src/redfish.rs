@@ -0,0 +1,235 @@
+//! Readings of a rack PDU through the Redfish API (DMTF `PowerDistribution` schema).
+//!
+//! Redfish is available on recent firmwares of Raritan (Xerus), Eaton (ePDU G4) and APC (NMC3) PDUs,
+//! among others. The PDU, its outlets and its mains circuits are discovered on the first read:
+//!
+//! - `/redfish/v1/PowerEquipment/RackPDUs/<id>/Outlets/<id>`
+//! - `/redfish/v1/PowerEquipment/RackPDUs/<id>/Mains/<id>`
+
+use anyhow::{Context, anyhow};
+use serde_json::Value;
+
+use crate::api::{HttpClient, OutletReading, PduApi, PhaseReading, Readings};
+
+const RACK_PDUS: &str = "/redfish/v1/PowerEquipment/RackPDUs";
+
+/// Lines of a polyphase circuit, and the name of their phase.
+const LINES: [(&str, &str); 3] = [("Line1", "L1"), ("Line2", "L2"), ("Line3", "L3")];
+
+pub struct Redfish {
+    client: HttpClient,
+    /// Links to the outlets and to the mains circuits of the PDU.
+    links: Option<Links>,
+}
+
+struct Links {
+    outlets: Vec<String>,
+    mains: Vec<String>,
+}
+
+impl Redfish {
+    pub fn new(client: HttpClient) -> Self {
+        Self { client, links: None }
+    }
+
+    fn get(&self, path: &str) -> anyhow::Result<Value> {
+        let response = self
+            .client
+            .get(path)
+            .header("Accept", "application/json")
+            .send()
+            .with_context(|| format!("request to {path} failed"))?
+            .error_for_status()?;
+        Ok(response.json()?)
+    }
+
+    /// Returns the links of the members of a collection.
+    fn members(&self, collection: &str) -> anyhow::Result<Vec<String>> {
+        let collection = self.get(collection)?;
+        let members = collection["Members"]
+            .as_array()
+            .ok_or_else(|| anyhow!("invalid Redfish collection: no Members"))?;
+        Ok(members
+            .iter()
+            .filter_map(|m| m["@odata.id"].as_str().map(str::to_owned))
+            .collect())
+    }
+
+    fn discover(&self) -> anyhow::Result<Links> {
+        let pdus = self.members(RACK_PDUS)?;
+        let pdu = match pdus.as_slice() {
+            [] => return Err(anyhow!("no rack PDU in {RACK_PDUS}")),
+            [pdu] => pdu,
+            [pdu, ..] => {
+                log::warn!(
+                    "Found {} rack PDUs behind the same address, only {pdu} is measured.",
+                    pdus.len()
+                );
+                pdu
+            }
+        };
+        let outlets = self.members(&format!("{pdu}/Outlets"))?;
+        // some PDUs do not report their inlets
+        let mains = self.members(&format!("{pdu}/Mains")).unwrap_or_else(|e| {
+            log::warn!("The mains of {pdu} are not available, the phases will not be measured: {e:#}");
+            Vec::new()
+        });
+        log::debug!(
+            "Found {} outlets and {} mains circuits in {pdu}.",
+            outlets.len(),
+            mains.len()
+        );
+        Ok(Links { outlets, mains })
+    }
+
+    fn read_links(&self, links: &Links) -> anyhow::Result<Readings> {
+        let mut readings = Readings::default();
+        for outlet in &links.outlets {
+            readings.outlets.push(parse_outlet(&self.get(outlet)?)?);
+        }
+        for circuit in &links.mains {
+            readings.phases.extend(parse_circuit(&self.get(circuit)?)?);
+        }
+        Ok(readings)
+    }
+}
+
+impl PduApi for Redfish {
+    fn read(&mut self) -> anyhow::Result<Readings> {
+        let links = match self.links.take() {
+            Some(links) => links,
+            None => self.discover()?,
+        };
+        let readings = self.read_links(&links)?;
+        // on error, the outlets or circuits may have changed: discover them again on the next read
+        self.links = Some(links);
+        Ok(readings)
+    }
+}
+
+/// Returns the `Reading` of a sensor excerpt.
+fn reading(resource: &Value, path: &str) -> Option<f64> {
+    resource.pointer(path)?.get("Reading")?.as_f64()
+}
+
+fn id(resource: &Value) -> anyhow::Result<String> {
+    resource["Id"]
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("invalid Redfish resource: no Id"))
+}
+
+fn parse_outlet(outlet: &Value) -> anyhow::Result<OutletReading> {
+    Ok(OutletReading {
+        id: id(outlet)?,
+        power: reading(outlet, "/PowerWatts"),
+        energy: reading(outlet, "/EnergykWh").map(|kwh| kwh * 3_600_000.0),
+    })
+}
+
+fn parse_circuit(circuit: &Value) -> anyhow::Result<Vec<PhaseReading>> {
+    let inlet = id(circuit)?;
+    let mut phases: Vec<PhaseReading> = LINES
+        .iter()
+        .map(|(line, phase)| PhaseReading {
+            inlet: inlet.clone(),
+            phase: String::from(*phase),
+            power: reading(circuit, &format!("/PolyPhasePowerWatts/{line}ToNeutral")),
+            current: reading(circuit, &format!("/PolyPhaseCurrentAmps/{line}")),
+            voltage: reading(circuit, &format!("/PolyPhaseVoltage/{line}ToNeutral")),
+        })
+        .filter(|p| p.power.is_some() || p.current.is_some() || p.voltage.is_some())
+        .collect();
+    if phases.is_empty() {
+        // single-phase circuit
+        phases.push(PhaseReading {
+            inlet,
+            phase: String::from("L1"),
+            power: reading(circuit, "/PowerWatts"),
+            current: reading(circuit, "/CurrentAmps"),
+            voltage: reading(circuit, "/Voltage"),
+        });
+    }
+    Ok(phases)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn outlet() {
+        let outlet = json!({
+            "@odata.id": "/redfish/v1/PowerEquipment/RackPDUs/1/Outlets/A1",
+            "Id": "A1",
+            "PowerWatts": { "DataSourceUri": "/redfish/v1/PowerEquipment/RackPDUs/1/Sensors/PowerA1", "Reading": 120.5 },
+            "EnergykWh": { "Reading": 1.5 },
+            "Voltage": { "Reading": 230.0 }
+        });
+        assert_eq!(
+            parse_outlet(&outlet).unwrap(),
+            OutletReading {
+                id: String::from("A1"),
+                power: Some(120.5),
+                energy: Some(5_400_000.0),
+            }
+        );
+    }
+
+    #[test]
+    fn three_phase_circuit() {
+        let circuit = json!({
+            "Id": "AC1",
+            "PolyPhaseCurrentAmps": {
+                "Line1": { "Reading": 1.5 },
+                "Line2": { "Reading": 2.0 },
+                "Line3": { "Reading": 0.5 }
+            },
+            "PolyPhaseVoltage": {
+                "Line1ToNeutral": { "Reading": 230.0 },
+                "Line2ToNeutral": { "Reading": 231.0 },
+                "Line3ToNeutral": { "Reading": 229.0 }
+            },
+            "PolyPhasePowerWatts": {
+                "Line1ToNeutral": { "Reading": 340.0 }
+            }
+        });
+        let phases = parse_circuit(&circuit).unwrap();
+        assert_eq!(phases.len(), 3);
+        assert_eq!(
+            phases[0],
+            PhaseReading {
+                inlet: String::from("AC1"),
+                phase: String::from("L1"),
+                power: Some(340.0),
+                current: Some(1.5),
+                voltage: Some(230.0),
+            }
+        );
+        assert_eq!(phases[2].power, None);
+        assert_eq!(phases[2].voltage, Some(229.0));
+    }
+
+    #[test]
+    fn single_phase_circuit() {
+        let circuit = json!({
+            "Id": "AC1",
+            "PowerWatts": { "Reading": 500.0 },
+            "CurrentAmps": { "Reading": 2.2 },
+            "Voltage": { "Reading": 228.0 }
+        });
+        assert_eq!(
+            parse_circuit(&circuit).unwrap(),
+            vec![PhaseReading {
+                inlet: String::from("AC1"),
+                phase: String::from("L1"),
+                power: Some(500.0),
+                current: Some(2.2),
+                voltage: Some(228.0),
+            }]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::{BTreeMap, HashMap};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{api::PduApi, metrics::Metrics};
+
+/// Measurement source that reads the outlets and the phases of a PDU.
+pub struct PduSource {
+    api: Box<dyn PduApi>,
+    resource: Resource,
+    /// Node that is plugged in each outlet.
+    nodes: BTreeMap<String, String>,
+    metrics: Metrics,
+    /// Previous value of the energy counter of each outlet.
+    last_energy: HashMap<String, f64>,
+}
+
+impl PduSource {
+    pub fn new(api: Box<dyn PduApi>, pdu: String, nodes: BTreeMap<String, String>, metrics: Metrics) -> Self {
+        Self {
+            api,
+            resource: Resource::custom("pdu", pdu),
+            nodes,
+            metrics,
+            last_energy: HashMap::new(),
+        }
+    }
+}
+
+impl Source for PduSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // The HTTP requests are blocking, and the PDU can be unreachable for a while (the next poll may work).
+        let readings = tokio::task::block_in_place(|| self.api.read())
+            .context("failed to read the PDU")
+            .map_err(PollError::CanRetry)?;
+
+        for outlet in readings.outlets {
+            let consumer = match self.nodes.get(&outlet.id) {
+                Some(node) => ResourceConsumer::custom("node", node.clone()),
+                None => ResourceConsumer::LocalMachine,
+            };
+            let point = |metric: TypedMetricId<f64>, value: f64| {
+                MeasurementPoint::new(t, metric, self.resource.clone(), consumer.clone(), value)
+                    .with_attr("outlet", outlet.id.clone())
+            };
+            if let Some(power) = outlet.power {
+                measurements.push(point(self.metrics.outlet_power, power));
+            }
+            if let Some(energy) = outlet.energy {
+                match self.last_energy.insert(outlet.id.clone(), energy) {
+                    Some(previous) if energy >= previous => {
+                        measurements.push(point(self.metrics.outlet_energy, energy - previous));
+                    }
+                    Some(_) => log::debug!("The energy counter of outlet {} has been reset.", outlet.id),
+                    None => (),
+                }
+            }
+        }
+
+        for phase in readings.phases {
+            let point = |metric: TypedMetricId<f64>, value: f64| {
+                MeasurementPoint::new(t, metric, self.resource.clone(), ResourceConsumer::LocalMachine, value)
+                    .with_attr("inlet", phase.inlet.clone())
+                    .with_attr("phase", phase.phase.clone())
+            };
+            let values = [
+                (self.metrics.phase_power, phase.power),
+                (self.metrics.phase_current, phase.current),
+                (self.metrics.phase_voltage, phase.voltage),
+            ];
+            for (metric, value) in values {
+                if let Some(value) = value {
+                    measurements.push(point(metric, value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -8,7 +8,9 @@ use alumet::{
     metrics::TypedMetricId,
     pipeline::{control::request, elements::source::trigger::TriggerSpec},
     plugin::{
-        AlumetPostStart, event,
+        AlumetPostStart,
+        capability::Capability,
+        event,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
     units::Unit,
@@ -19,12 +21,11 @@ use itertools::Itertools;
 use perf_event::events::{Cache, Hardware, Software};
 use serde::{Deserialize, Serialize};
 
-use crate::source::{Observable, PerfEventSourceBuilder};
+use crate::source::{Observable, PerfEventSource, PerfEventSourceBuilder};
 
 #[cfg(not(target_os = "linux"))]
 compile_error!("This plugin only works on Linux.");
 
-mod cpu;
 mod events;
 mod source;
 
@@ -41,6 +42,10 @@ impl AlumetPlugin for PerfPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::PerfEvents])
+    }
+
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
@@ -51,6 +56,7 @@ impl AlumetPlugin for PerfPlugin {
             // Store the source settings.
             poll_interval: config.poll_interval,
             flush_interval: config.flush_interval,
+            system_wide: config.system_wide,
             // Parse the perf events.
             hardware_events: config
                 .hardware_events
@@ -105,6 +111,15 @@ impl AlumetPlugin for PerfPlugin {
         config.hardware_metrics = hardware_metrics;
         config.software_metrics = software_metrics;
         config.cache_metrics = cache_metrics;
+
+        if config.system_wide {
+            let source = build_source(&config, Observable::System)
+                .context("could not observe the whole system, check perf_event_paranoid")?;
+            let trigger = TriggerSpec::builder(config.poll_interval)
+                .flush_interval(config.flush_interval)
+                .build()?;
+            alumet.add_source("system", Box::new(source), trigger)?;
+        }
         Ok(())
     }
 
@@ -136,33 +151,11 @@ impl AlumetPlugin for PerfPlugin {
                 if let Some((o, source_name)) = observable {
                     log::info!("Starting to observe {o:?}...");
                     let config = config_cloned.lock().unwrap();
-                    let mut builder = PerfEventSourceBuilder::observe(o)?;
-                    for (event, metric) in config.hardware_events.iter().zip(&config.hardware_metrics) {
-                        builder.add(event.event, *metric).with_context(|| {
-                            format!(
-                                "could not configure hardware event {} (code {})",
-                                event.name, event.event.0
-                            )
-                        })?;
-                    }
-                    for (event, metric) in config.software_events.iter().zip(&config.software_metrics) {
-                        builder.add(event.event, *metric).with_context(|| {
-                            format!(
-                                "could not configure software event {} (code {})",
-                                event.name, event.event.0
-                            )
-                        })?;
-                    }
-                    for (event, metric) in config.cache_events.iter().zip(&config.cache_metrics) {
-                        builder
-                            .add(event.event.clone(), *metric)
-                            .with_context(|| format!("could not configure cache event {}", event.name))?;
-                    }
+                    let source = build_source(&config, o)?;
                     let poll_interval = config.poll_interval;
                     let flush_interval = config.flush_interval;
                     drop(config);
 
-                    let source = builder.build()?;
                     let trigger = TriggerSpec::builder(poll_interval)
                         .flush_interval(flush_interval)
                         .build()?;
@@ -182,6 +175,33 @@ impl AlumetPlugin for PerfPlugin {
     }
 }
 
+/// Creates a source that measures the configured events on the `observable`.
+fn build_source(config: &ParsedConfig, observable: Observable) -> anyhow::Result<PerfEventSource> {
+    let mut builder = PerfEventSourceBuilder::observe(observable)?;
+    for (event, metric) in config.hardware_events.iter().zip(&config.hardware_metrics) {
+        builder.add(event.event, *metric).with_context(|| {
+            format!(
+                "could not configure hardware event {} (code {})",
+                event.name, event.event.0
+            )
+        })?;
+    }
+    for (event, metric) in config.software_events.iter().zip(&config.software_metrics) {
+        builder.add(event.event, *metric).with_context(|| {
+            format!(
+                "could not configure software event {} (code {})",
+                event.name, event.event.0
+            )
+        })?;
+    }
+    for (event, metric) in config.cache_events.iter().zip(&config.cache_metrics) {
+        builder
+            .add(event.event.clone(), *metric)
+            .with_context(|| format!("could not configure cache event {}", event.name))?;
+    }
+    Ok(builder.build()?)
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 struct Config {
@@ -190,6 +210,11 @@ struct Config {
     #[serde(with = "humantime_serde")]
     flush_interval: Duration,
 
+    /// If true, measure the events of all the processes, on each CPU.
+    /// Otherwise, only the processes and cgroups that Alumet is asked to monitor are measured.
+    #[serde(default)]
+    system_wide: bool,
+
     hardware_events: Vec<String>,
     software_events: Vec<String>,
     cache_events: Vec<String>,
@@ -200,6 +225,7 @@ impl Default for Config {
         Self {
             poll_interval: Duration::from_secs(1), // 1Hz
             flush_interval: Duration::from_secs(5),
+            system_wide: false,
 
             hardware_events: vec![
                 "REF_CPU_CYCLES".to_owned(),
@@ -216,6 +242,7 @@ impl Default for Config {
 struct ParsedConfig {
     poll_interval: Duration,
     flush_interval: Duration,
+    system_wide: bool,
 
     hardware_events: Vec<NamedPerfEvent<Hardware>>,
     software_events: Vec<NamedPerfEvent<Software>>,
@@ -1,17 +1,16 @@
 //! Source of measurements based on Linux perf_events.
-use std::{fs::File, io};
+use std::{fs::File, io, time::Duration};
 
 use alumet::{
     measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
     metrics::TypedMetricId,
     pipeline::{Source, elements::error::PollError},
+    plugin::util,
     resources::{Resource, ResourceConsumer},
 };
 use anyhow::Context;
 use itertools::Itertools;
 
-use crate::cpu;
-
 #[derive(Debug)]
 pub enum Observable {
     /// Observe a process.
@@ -23,6 +22,10 @@ pub enum Observable {
     /// Unlike processes, cgroups cannot be monitored with `cpu = -1`, a specific cpu id is required
     /// for `perf_event_open` (see https://github.com/torvalds/linux/blob/2c8159388952f530bd260e097293ccc0209240be/kernel/events/core.c#L12487)
     Cgroup { path: String, fd: File },
+    /// Observe all the processes, on each cpu separately.
+    ///
+    /// This requires `perf_event_paranoid <= 0` or the `CAP_PERFMON` capability.
+    System,
 }
 
 pub struct PerfEventSource {
@@ -47,7 +50,6 @@ impl Source for PerfEventSource {
             let resource = &group.observed_resource;
             let consumer = &group.observed_consumer;
 
-            // TODO: check time_enabled and time_running to detect issues
             log::trace!(
                 "Got perf_events measurements: time_enabled={:?}, time_running={:?}",
                 counts.time_enabled(),
@@ -56,7 +58,10 @@ impl Source for PerfEventSource {
 
             // for each counter, push its value
             for (perf_counter, alumet_metric) in &group.counters {
-                let value = counts[perf_counter];
+                let Some(value) = scale(counts[perf_counter], counts.time_enabled(), counts.time_running()) else {
+                    // the group has not been scheduled on the PMU yet, there is no meaningful value
+                    continue;
+                };
                 measurements.push(MeasurementPoint::new(
                     timestamp,
                     *alumet_metric,
@@ -70,6 +75,28 @@ impl Source for PerfEventSource {
     }
 }
 
+/// Scales the value of a counter to compensate for the multiplexing of the PMU.
+///
+/// When there are more events than hardware counters, the kernel shares the counters between
+/// the groups of events. The value is then estimated as `value * time_enabled / time_running`,
+/// like `perf stat` does. Returns `None` if the group has never been running.
+fn scale(value: u64, time_enabled: Option<Duration>, time_running: Option<Duration>) -> Option<u64> {
+    match (time_enabled, time_running) {
+        (Some(enabled), Some(running)) if running.is_zero() => {
+            if enabled.is_zero() {
+                Some(value)
+            } else {
+                None
+            }
+        }
+        (Some(enabled), Some(running)) if running < enabled => {
+            let scaled = u128::from(value) * enabled.as_nanos() / running.as_nanos();
+            Some(u64::try_from(scaled).unwrap_or(u64::MAX))
+        }
+        _ => Some(value),
+    }
+}
+
 /// Builder for the perf [`Source`].
 pub struct PerfEventSourceBuilder {
     /// Something to observe.
@@ -85,7 +112,7 @@ impl PerfEventSourceBuilder {
         Ok(Self {
             observable,
             groups: Vec::new(),
-            online_cpus: cpu::online_cpus().context("could not detect online CPUs")?,
+            online_cpus: util::online_cpus().context("could not detect online CPUs")?,
         })
     }
 
@@ -168,7 +195,42 @@ impl PerfEventSourceBuilder {
                             observed_consumer: ResourceConsumer::ControlGroup {
                                 path: path.to_owned().into(),
                             },
-                            cpu_id: None,
+                            cpu_id: Some(cpu_id as u32),
+                            counters: vec![(counter, alumet_metric)],
+                        };
+                        groups.push(group_with_info);
+                    }
+                    self.groups = groups;
+                }
+                Observable::System => {
+                    // Observe every process, on each cpu separately.
+
+                    // build one group per cpu
+                    let mut groups = Vec::new();
+                    for cpu_id in &self.online_cpus {
+                        let cpu_id = *cpu_id;
+
+                        // build group
+                        let mut perf_group = new_group_builder()
+                            .any_pid()
+                            .one_cpu(cpu_id as usize)
+                            .build_group()
+                            .with_context(|| format!("build_group with any_pid().one_cpu({cpu_id})"))?;
+
+                        // add event (the params must be the same)
+                        let counter = perf_group
+                            .add(
+                                perf_event::Builder::new(event.clone())
+                                    .any_pid()
+                                    .one_cpu(cpu_id as usize),
+                            )
+                            .with_context(|| format!("perf_group.add with any_pid().one_cpu({cpu_id})"))?;
+
+                        let group_with_info = EventGroup {
+                            perf_group,
+                            observed_resource: Resource::CpuCore { id: cpu_id },
+                            observed_consumer: ResourceConsumer::LocalMachine,
+                            cpu_id: Some(cpu_id),
                             counters: vec![(counter, alumet_metric)],
                         };
                         groups.push(group_with_info);
@@ -189,6 +251,9 @@ impl PerfEventSourceBuilder {
                     Observable::Cgroup { path: _, fd } => {
                         event_builder.observe_cgroup(fd).one_cpu(group.cpu_id.unwrap() as usize);
                     }
+                    Observable::System => {
+                        event_builder.any_pid().one_cpu(group.cpu_id.unwrap() as usize);
+                    }
                 }
 
                 let counter = group.perf_group.add(&event_builder).with_context(|| {
@@ -223,3 +288,27 @@ impl PerfEventSourceBuilder {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::scale;
+
+    #[test]
+    fn multiplexing() {
+        let ms = Duration::from_millis;
+        // not multiplexed
+        assert_eq!(scale(1000, Some(ms(10)), Some(ms(10))), Some(1000));
+        assert_eq!(scale(1000, None, None), Some(1000));
+        // running half of the time
+        assert_eq!(scale(1000, Some(ms(10)), Some(ms(5))), Some(2000));
+        assert_eq!(scale(3, Some(ms(4)), Some(ms(3))), Some(4));
+        // never running
+        assert_eq!(scale(0, Some(ms(10)), Some(ms(0))), None);
+        // just enabled
+        assert_eq!(scale(0, Some(ms(0)), Some(ms(0))), Some(0));
+        // saturation
+        assert_eq!(scale(u64::MAX, Some(ms(10)), Some(ms(1))), Some(u64::MAX));
+    }
+}
@@ -0,0 +1,95 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+mod metrics;
+mod source;
+mod supply;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct PowerSupplyPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for PowerSupplyPlugin {
+    fn name() -> &'static str {
+        "power-supply"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Filesystem(PathBuf::from("/sys/class/power_supply"))])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(PowerSupplyPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let supplies = supply::explore(&self.config.root_path).context("could not find the power supplies")?;
+        if supplies.is_empty() {
+            return Err(anyhow!(
+                "no battery or AC adapter found in {}",
+                self.config.root_path.display()
+            ));
+        }
+        log::debug!("Power supplies found: {supplies:?}");
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::PowerSupplySource::new(supplies, metrics);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("power_supply", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+
+    /// Path to the power supplies in sysfs.
+    pub root_path: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            flush_interval: Duration::from_secs(15),
+            root_path: PathBuf::from("/sys/class/power_supply"),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use alumet::{metrics::TypedMetricId, plugin::AlumetPluginStart, units::Unit};
+
+pub struct Metrics {
+    /// Power flowing in or out of the battery.
+    pub battery_power: TypedMetricId<f64>,
+    /// Energy drawn from the battery since the previous measurement.
+    pub battery_energy: TypedMetricId<f64>,
+    /// Remaining charge of the battery.
+    pub battery_charge: TypedMetricId<f64>,
+    /// Remaining energy in the battery.
+    pub battery_energy_remaining: TypedMetricId<f64>,
+    /// State of the AC adapter.
+    pub online: TypedMetricId<u64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            battery_power: alumet.create_metric(
+                "battery_power",
+                Unit::Watt,
+                "Power flowing in (charging) or out (discharging) of the battery",
+            )?,
+            battery_energy: alumet.create_metric(
+                "battery_energy_consumption",
+                Unit::Joule,
+                "Energy drawn from the battery since the previous measurement (computed from the power)",
+            )?,
+            battery_charge: alumet.create_metric(
+                "battery_charge",
+                Unit::Percent,
+                "Remaining charge of the battery, relative to its full charge",
+            )?,
+            battery_energy_remaining: alumet.create_metric(
+                "battery_energy_remaining",
+                Unit::Joule,
+                "Remaining energy in the battery",
+            )?,
+            online: alumet.create_metric(
+                "power_supply_online",
+                Unit::Unity,
+                "1 if the AC adapter is plugged in, 0 otherwise",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, MeasurementType, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+
+use crate::{
+    metrics::Metrics,
+    supply::{BatteryStatus, PowerSupply, SupplyKind},
+};
+
+/// Measurement source that reads the state of the batteries and AC adapters.
+pub struct PowerSupplySource {
+    probes: Vec<Probe>,
+    metrics: Metrics,
+}
+
+struct Probe {
+    supply: PowerSupply,
+    resource: Resource,
+    /// The previous power of the battery, to compute the energy.
+    prev_power: Option<(Timestamp, f64)>,
+}
+
+impl PowerSupplySource {
+    pub fn new(supplies: Vec<PowerSupply>, metrics: Metrics) -> Self {
+        let probes = supplies
+            .into_iter()
+            .map(|supply| Probe {
+                resource: Resource::Custom {
+                    kind: Cow::Borrowed("power_supply"),
+                    id: Cow::Owned(supply.name.clone()),
+                },
+                supply,
+                prev_power: None,
+            })
+            .collect();
+        Self { probes, metrics }
+    }
+}
+
+impl Probe {
+    fn point<T: MeasurementType>(&self, t: Timestamp, metric: TypedMetricId<T>, value: T::T) -> MeasurementPoint {
+        MeasurementPoint::new(t, metric, self.resource.clone(), ResourceConsumer::LocalMachine, value)
+    }
+
+    fn measure(
+        &mut self,
+        metrics: &Metrics,
+        measurements: &mut MeasurementAccumulator,
+        t: Timestamp,
+    ) -> anyhow::Result<()> {
+        match self.supply.kind {
+            SupplyKind::Mains => {
+                let online = self.supply.read_online()?;
+                measurements.push(self.point(t, metrics.online, online as u64));
+            }
+            SupplyKind::Battery => {
+                let state = self.supply.read_battery()?;
+                if let Some(charge) = state.charge {
+                    measurements.push(self.point(t, metrics.battery_charge, charge));
+                }
+                if let Some(energy) = state.energy {
+                    measurements.push(self.point(t, metrics.battery_energy_remaining, energy));
+                }
+                if let Some(power) = state.power {
+                    let status = state.status.as_str();
+                    measurements.push(self.point(t, metrics.battery_power, power).with_attr("status", status));
+
+                    // Only the energy that powers the machine is reported, not the energy that charges the battery.
+                    let prev = self.prev_power.replace((t, power));
+                    if let (Some((prev_t, prev_power)), BatteryStatus::Discharging) = (prev, state.status) {
+                        let energy = compute_energy(prev_t, prev_power, t, power)?;
+                        measurements.push(self.point(t, metrics.battery_energy, energy));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source for PowerSupplySource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        for probe in &mut self.probes {
+            if let Err(e) = probe.measure(&self.metrics, measurements, t) {
+                log::warn!("Failed to read the state of {}: {e:#}", probe.supply.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Computes the energy, in Joules, consumed between two power measurements, with a discrete integral.
+fn compute_energy(prev_t: Timestamp, prev_power: f64, t: Timestamp, power: f64) -> anyhow::Result<f64> {
+    let elapsed = t.duration_since(prev_t)?.as_secs_f64();
+    Ok((prev_power + power) / 2.0 * elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn energy() {
+        let t0 = Timestamp::now();
+        assert_eq!(compute_energy(t0, 10.0, t0, 12.0).unwrap(), 0.0);
+        assert_eq!(
+            compute_energy(t0, 10.0, t0 + Duration::from_secs(5), 12.0).unwrap(),
+            55.0
+        );
+        assert_eq!(
+            compute_energy(t0, 8.0, t0 + Duration::from_millis(500), 8.0).unwrap(),
+            4.0
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Batteries and AC adapters of `/sys/class/power_supply`.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+/// Error returned by some drivers when a value is temporarily unavailable.
+const ENODATA: i32 = 61;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SupplyKind {
+    Battery,
+    /// AC adapter, or USB power delivery.
+    Mains,
+}
+
+/// A power supply, such as `BAT0` or `AC`.
+#[derive(Debug, PartialEq)]
+pub struct PowerSupply {
+    pub name: String,
+    pub kind: SupplyKind,
+    path: PathBuf,
+}
+
+/// Status of a battery, as reported by the kernel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatteryStatus {
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+    Unknown,
+}
+
+/// State of a battery.
+#[derive(Debug, PartialEq)]
+pub struct BatteryState {
+    pub status: BatteryStatus,
+    /// Power flowing in or out of the battery, in Watts.
+    pub power: Option<f64>,
+    /// Remaining charge, in percent of the full charge.
+    pub charge: Option<f64>,
+    /// Remaining energy, in Joules.
+    pub energy: Option<f64>,
+}
+
+impl BatteryStatus {
+    fn parse(s: &str) -> Self {
+        match s {
+            "Charging" => Self::Charging,
+            "Discharging" => Self::Discharging,
+            "Not charging" => Self::NotCharging,
+            "Full" => Self::Full,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Charging => "charging",
+            Self::Discharging => "discharging",
+            Self::NotCharging => "not_charging",
+            Self::Full => "full",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Finds the batteries and the AC adapters of the machine.
+///
+/// The batteries of the peripherals, such as a wireless mouse, are ignored.
+pub fn explore(root: &Path) -> anyhow::Result<Vec<PowerSupply>> {
+    let mut supplies = Vec::new();
+    for entry in fs::read_dir(root).with_context(|| format!("could not list {root:?}"))? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()).map(str::to_owned) else {
+            continue;
+        };
+        let kind = match read_string(&path.join("type"))?.as_deref() {
+            Some("Battery") => SupplyKind::Battery,
+            Some("Mains" | "USB") => SupplyKind::Mains,
+            _ => continue,
+        };
+        if read_string(&path.join("scope"))?.as_deref() == Some("Device") {
+            log::debug!("Ignoring {name}, which powers a peripheral.");
+            continue;
+        }
+        supplies.push(PowerSupply { name, kind, path });
+    }
+    supplies.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(supplies)
+}
+
+impl PowerSupply {
+    /// Returns `true` if the AC adapter is plugged in.
+    pub fn read_online(&self) -> anyhow::Result<bool> {
+        Ok(self.read_u64("online")?.context("missing value: online")? != 0)
+    }
+
+    pub fn read_battery(&self) -> anyhow::Result<BatteryState> {
+        let status = read_string(&self.path.join("status"))?
+            .map(|s| BatteryStatus::parse(&s))
+            .unwrap_or(BatteryStatus::Unknown);
+
+        // Depending on the driver, the battery reports its power and energy (µW, µWh),
+        // or its current and charge (µA, µAh), which must be multiplied by the voltage (µV).
+        let voltage = self.read_u64("voltage_now")?.map(|uv| uv as f64 / 1e6);
+        let power = match self.read_i64("power_now")? {
+            Some(uw) => Some(uw.unsigned_abs() as f64 / 1e6),
+            None => match (self.read_i64("current_now")?, voltage) {
+                (Some(ua), Some(v)) => Some(ua.unsigned_abs() as f64 / 1e6 * v),
+                _ => None,
+            },
+        };
+        let energy = match self.read_u64("energy_now")? {
+            Some(uwh) => Some(uwh as f64 / 1e6 * 3600.0),
+            None => match (self.read_u64("charge_now")?, voltage) {
+                (Some(uah), Some(v)) => Some(uah as f64 / 1e6 * v * 3600.0),
+                _ => None,
+            },
+        };
+        let charge = match self.read_u64("capacity")? {
+            Some(percent) => Some(percent as f64),
+            None => {
+                let ratio = |now: Option<u64>, full: Option<u64>| match (now, full) {
+                    (Some(now), Some(full)) if full > 0 => Some(now as f64 * 100.0 / full as f64),
+                    _ => None,
+                };
+                ratio(self.read_u64("energy_now")?, self.read_u64("energy_full")?)
+                    .or(ratio(self.read_u64("charge_now")?, self.read_u64("charge_full")?))
+            }
+        };
+        Ok(BatteryState {
+            status,
+            power,
+            charge,
+            energy,
+        })
+    }
+
+    fn read_u64(&self, file: &str) -> anyhow::Result<Option<u64>> {
+        self.read_parsed(file)
+    }
+
+    fn read_i64(&self, file: &str) -> anyhow::Result<Option<i64>> {
+        self.read_parsed(file)
+    }
+
+    fn read_parsed<T: std::str::FromStr>(&self, file: &str) -> anyhow::Result<Option<T>> {
+        let path = self.path.join(file);
+        match read_string(&path)? {
+            Some(s) => {
+                let value = s
+                    .parse()
+                    .ok()
+                    .with_context(|| format!("invalid value in {path:?}: {s}"))?;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Reads a sysfs attribute, or returns `None` if the driver does not provide it.
+fn read_string(path: &Path) -> anyhow::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(s) => Ok(Some(s.trim().to_owned())),
+        Err(e) if e.kind() == ErrorKind::NotFound || e.raw_os_error() == Some(ENODATA) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("could not read {path:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn write_supply(root: &Path, name: &str, files: &[(&str, &str)]) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (file, content) in files {
+            fs::write(dir.join(file), format!("{content}\n")).unwrap();
+        }
+    }
+
+    #[test]
+    fn explore_and_read() {
+        let tmp = tempdir().unwrap();
+        let root = tmp.path();
+        write_supply(root, "AC", &[("type", "Mains"), ("online", "0")]);
+        write_supply(
+            root,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("power_now", "12500000"),
+                ("energy_now", "40000000"),
+                ("energy_full", "50000000"),
+            ],
+        );
+        write_supply(
+            root,
+            "BAT1",
+            &[
+                ("type", "Battery"),
+                ("status", "Not charging"),
+                ("current_now", "-500000"),
+                ("voltage_now", "12000000"),
+                ("charge_now", "1000000"),
+                ("capacity", "25"),
+            ],
+        );
+        write_supply(
+            root,
+            "hidpp_battery_0",
+            &[("type", "Battery"), ("scope", "Device"), ("capacity", "80")],
+        );
+        write_supply(root, "ucsi-source-psy-USBC000:001", &[("type", "USB"), ("online", "1")]);
+        write_supply(root, "unknown", &[("type", "Wireless")]);
+
+        let supplies = explore(root).unwrap();
+        let names: Vec<_> = supplies.iter().map(|s| (s.name.as_str(), s.kind)).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("AC", SupplyKind::Mains),
+                ("BAT0", SupplyKind::Battery),
+                ("BAT1", SupplyKind::Battery),
+                ("ucsi-source-psy-USBC000:001", SupplyKind::Mains),
+            ]
+        );
+
+        assert!(!supplies[0].read_online().unwrap());
+        assert!(supplies[3].read_online().unwrap());
+        assert_eq!(
+            supplies[1].read_battery().unwrap(),
+            BatteryState {
+                status: BatteryStatus::Discharging,
+                power: Some(12.5),
+                charge: Some(80.0),
+                energy: Some(144000.0),
+            }
+        );
+        assert_eq!(
+            supplies[2].read_battery().unwrap(),
+            BatteryState {
+                status: BatteryStatus::NotCharging,
+                power: Some(6.0),
+                charge: Some(25.0),
+                energy: Some(43200.0),
+            }
+        );
+
+        fs::write(root.join("BAT0/power_now"), "n/a").unwrap();
+        assert!(supplies[1].read_battery().is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fs, path::Path, time::Duration};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::WrappedMeasurementValue,
+    pipeline::naming::SourceName,
+    plugin::PluginMetadata,
+    test::{RuntimeExpectations, StartupExpectations},
+    units::Unit,
+};
+use plugin_power_supply::{Config, PowerSupplyPlugin};
+use pretty_assertions::assert_eq;
+use tempfile::tempdir;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn plugins(config: &Config) -> PluginSet {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<PowerSupplyPlugin>(),
+        enabled: true,
+        config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+    });
+    plugins
+}
+
+fn write_supply(root: &Path, name: &str, files: &[(&str, &str)]) {
+    let dir = root.join(name);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, content) in files {
+        fs::write(dir.join(file), format!("{content}\n")).unwrap();
+    }
+}
+
+/// Returns the values of the points of a metric, by name.
+fn values(ctx: &alumet::test::runtime::SourceCheckOutputContext, metric: &str) -> Vec<WrappedMeasurementValue> {
+    let metric = ctx.metrics().by_name(metric).unwrap().0;
+    ctx.measurements()
+        .iter()
+        .filter(|m| m.metric == metric)
+        .map(|m| m.value.clone())
+        .collect()
+}
+
+#[test]
+fn no_power_supply() {
+    let root = tempdir().unwrap();
+    write_supply(
+        root.path(),
+        "hidpp_battery_0",
+        &[("type", "Battery"), ("scope", "Device")],
+    );
+    let config = Config {
+        root_path: root.path().to_owned(),
+        ..Config::default()
+    };
+    let agent = agent::Builder::new(plugins(&config)).build_and_start();
+    assert!(agent.is_err(), "the plugin should fail to start (no power supply)");
+}
+
+#[test]
+fn laptop_on_battery() {
+    let root = tempdir().unwrap();
+    write_supply(root.path(), "AC", &[("type", "Mains"), ("online", "0")]);
+    write_supply(
+        root.path(),
+        "BAT0",
+        &[
+            ("type", "Battery"),
+            ("status", "Discharging"),
+            ("power_now", "10000000"),
+            ("energy_now", "40000000"),
+            ("energy_full", "50000000"),
+            ("capacity", "80"),
+        ],
+    );
+    let config = Config {
+        poll_interval: Duration::from_millis(100),
+        root_path: root.path().to_owned(),
+        ..Config::default()
+    };
+
+    let startup = StartupExpectations::new()
+        .expect_metric::<f64>("battery_power", Unit::Watt)
+        .expect_metric::<f64>("battery_energy_consumption", Unit::Joule)
+        .expect_metric::<f64>("battery_charge", Unit::Percent)
+        .expect_metric::<f64>("battery_energy_remaining", Unit::Joule)
+        .expect_metric::<u64>("power_supply_online", Unit::Unity)
+        .expect_source("power-supply", "power_supply");
+
+    let source = SourceName::from_str("power-supply", "power_supply");
+    let runtime = RuntimeExpectations::new()
+        .test_source(
+            source.clone(),
+            || (),
+            |ctx| {
+                // first measurement: no energy yet
+                assert_eq!(
+                    values(ctx, "power_supply_online"),
+                    vec![WrappedMeasurementValue::U64(0)]
+                );
+                assert_eq!(values(ctx, "battery_charge"), vec![WrappedMeasurementValue::F64(80.0)]);
+                assert_eq!(
+                    values(ctx, "battery_energy_remaining"),
+                    vec![WrappedMeasurementValue::F64(144000.0)]
+                );
+                assert_eq!(values(ctx, "battery_power"), vec![WrappedMeasurementValue::F64(10.0)]);
+                assert_eq!(values(ctx, "battery_energy_consumption"), vec![]);
+                let power = ctx
+                    .measurements()
+                    .iter()
+                    .find(|m| m.value == WrappedMeasurementValue::F64(10.0));
+                let status = power.unwrap().attributes().find(|(k, _)| *k == "status").unwrap().1;
+                assert_eq!(status.to_string(), "discharging");
+            },
+        )
+        .test_source(
+            source,
+            || (),
+            |ctx| {
+                let energy = values(ctx, "battery_energy_consumption");
+                match energy.as_slice() {
+                    [WrappedMeasurementValue::F64(joules)] => {
+                        // 10 W during the poll interval
+                        assert!(*joules > 0.0 && *joules < 10.0, "unexpected energy: {joules}");
+                    }
+                    other => panic!("unexpected energy: {other:?}"),
+                }
+            },
+        );
+
+    let agent = agent::Builder::new(plugins(&config))
+        .with_expectations(startup)
+        .with_expectations(runtime)
+        .build_and_start()
+        .expect("agent should start");
+    agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+}
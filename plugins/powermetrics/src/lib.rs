@@ -0,0 +1,198 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use powermetrics::Powermetrics;
+
+mod metrics;
+mod powermetrics;
+mod source;
+
+pub struct PowermetricsPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for PowermetricsPlugin {
+    fn name() -> &'static str {
+        "powermetrics"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::ProcessSpawn])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(PowermetricsPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let powermetrics = Powermetrics::spawn(
+            &self.config.powermetrics,
+            &self.config.samplers,
+            self.config.poll_interval,
+        )?;
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::PowermetricsSource::new(powermetrics, metrics);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("power", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements, which is also the sampling interval of powermetrics.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// Path to the `powermetrics` executable.
+    powermetrics: PathBuf,
+
+    /// Samplers of powermetrics to enable.
+    samplers: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(5),
+            powermetrics: PathBuf::from("/usr/bin/powermetrics"),
+            samplers: vec![
+                String::from("cpu_power"),
+                String::from("gpu_power"),
+                String::from("ane_power"),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path, thread};
+
+    use alumet::{
+        agent::{
+            self,
+            plugin::{PluginInfo, PluginSet},
+        },
+        measurement::WrappedMeasurementValue,
+        pipeline::naming::SourceName,
+        plugin::PluginMetadata,
+        test::{RuntimeExpectations, StartupExpectations},
+        units::Unit,
+    };
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A fake powermetrics that prints a complete sample, begins the next one and waits.
+    const FAKE_POWERMETRICS: &str = r#"#!/bin/sh
+if [ "$2" != "cpu_power,gpu_power,ane_power" ] || [ "$4" != "100" ]; then
+    echo "unexpected arguments: $*" >&2
+    exit 1
+fi
+echo "*** Sampled system activity (Fri Oct 16 10:00:00 2026 +0200) (500.00ms elapsed) ***"
+echo "CPU Power: 2000 mW"
+echo "GPU Power: 1000 mW"
+echo "*** Sampled system activity (Fri Oct 16 10:00:01 2026 +0200) (500.00ms elapsed) ***"
+exec sleep 10
+"#;
+
+    fn fake_powermetrics(dir: &Path) -> PathBuf {
+        let path = dir.join("powermetrics");
+        fs::write(&path, FAKE_POWERMETRICS).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn plugins(config: &Config) -> PluginSet {
+        let mut plugins = PluginSet::new();
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<PowermetricsPlugin>(),
+            enabled: true,
+            config: Some(toml::Value::try_from(config).unwrap().as_table().unwrap().clone()),
+        });
+        plugins
+    }
+
+    #[test]
+    fn power_samples() {
+        let tmp = tempdir().unwrap();
+        let config = Config {
+            poll_interval: Duration::from_millis(100),
+            powermetrics: fake_powermetrics(tmp.path()),
+            ..Config::default()
+        };
+
+        let startup = StartupExpectations::new()
+            .expect_metric::<f64>("powermetrics_power", Unit::Watt)
+            .expect_metric::<f64>("powermetrics_consumed_energy", Unit::Joule)
+            .expect_source("powermetrics", "power");
+
+        let runtime = RuntimeExpectations::new().test_source(
+            SourceName::from_str("powermetrics", "power"),
+            // let powermetrics print its output
+            || thread::sleep(Duration::from_millis(500)),
+            |ctx| {
+                let m = ctx.measurements();
+                let values: Vec<_> = m
+                    .iter()
+                    .map(|m| {
+                        let domain = m.attributes().find(|(k, _)| *k == "domain").unwrap().1;
+                        (domain.to_string(), m.value.clone())
+                    })
+                    .collect();
+                assert_eq!(
+                    values,
+                    vec![
+                        (String::from("cpu"), WrappedMeasurementValue::F64(2.0)),
+                        (String::from("cpu"), WrappedMeasurementValue::F64(1.0)),
+                        (String::from("gpu"), WrappedMeasurementValue::F64(1.0)),
+                        (String::from("gpu"), WrappedMeasurementValue::F64(0.5)),
+                    ]
+                );
+            },
+        );
+
+        let agent = agent::Builder::new(plugins(&config))
+            .with_expectations(startup)
+            .with_expectations(runtime)
+            .build_and_start()
+            .expect("agent should start");
+        agent.wait_for_shutdown(TIMEOUT).expect("pipeline should run fine");
+    }
+}
@@ -0,0 +1,25 @@
+use alumet::{metrics::TypedMetricId, plugin::AlumetPluginStart, units::Unit};
+
+pub struct Metrics {
+    /// Average power during the sampling interval.
+    pub power: TypedMetricId<f64>,
+    /// Energy consumed during the sampling interval.
+    pub consumed_energy: TypedMetricId<f64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            power: alumet.create_metric(
+                "powermetrics_power",
+                Unit::Watt,
+                "Average power since the previous measurement, as reported by powermetrics",
+            )?,
+            consumed_energy: alumet.create_metric(
+                "powermetrics_consumed_energy",
+                Unit::Joule,
+                "Energy consumed since the previous measurement, as reported by powermetrics",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,278 @@
+//! Power samples of macOS machines, obtained with `powermetrics`.
+
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use alumet::measurement::Timestamp;
+use anyhow::{Context, anyhow};
+
+/// Part of the machine whose power is reported by `powermetrics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Domain {
+    /// Whole SoC (CPU, GPU and Neural Engine), or whole package on Intel.
+    Package,
+    Cpu,
+    Gpu,
+    /// Apple Neural Engine.
+    Ane,
+}
+
+impl Domain {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Domain::Package => "package",
+            Domain::Cpu => "cpu",
+            Domain::Gpu => "gpu",
+            Domain::Ane => "ane",
+        }
+    }
+}
+
+/// A sample of `powermetrics`: the average power of each domain during a sampling interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// When the sample has been received.
+    pub timestamp: Timestamp,
+    /// Duration of the sampling interval.
+    pub elapsed: Duration,
+    /// Average power of each domain, in Watts.
+    pub power: Vec<(Domain, f64)>,
+}
+
+/// A `powermetrics` process that runs in the background and periodically prints a sample.
+pub struct Powermetrics {
+    child: Child,
+    samples: Receiver<Sample>,
+}
+
+impl Powermetrics {
+    /// Starts `powermetrics`, which takes a sample every `interval`.
+    pub fn spawn(powermetrics: &Path, samplers: &[String], interval: Duration) -> anyhow::Result<Self> {
+        let mut child = Command::new(powermetrics)
+            .args(["--samplers", &samplers.join(",")])
+            .args(["-i", &interval.as_millis().to_string()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to execute {}", powermetrics.display()))?;
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let (tx, samples) = mpsc::channel();
+        thread::Builder::new()
+            .name(String::from("powermetrics-reader"))
+            .spawn(move || read_samples(stdout, |sample| tx.send(sample).is_ok()))
+            .context("failed to spawn the thread that reads the output of powermetrics")?;
+        Ok(Self { child, samples })
+    }
+
+    /// Returns the samples that have been received since the last call.
+    ///
+    /// Fails if `powermetrics` has exited.
+    pub fn samples(&mut self) -> anyhow::Result<Vec<Sample>> {
+        let mut samples = Vec::new();
+        loop {
+            match self.samples.try_recv() {
+                Ok(sample) => samples.push(sample),
+                Err(TryRecvError::Empty) => return Ok(samples),
+                Err(TryRecvError::Disconnected) if !samples.is_empty() => return Ok(samples),
+                Err(TryRecvError::Disconnected) => return Err(self.exit_error()),
+            }
+        }
+    }
+
+    fn exit_error(&mut self) -> anyhow::Error {
+        let status = match self.child.wait() {
+            Ok(status) => status.to_string(),
+            Err(e) => return anyhow!("failed to wait for powermetrics: {e}"),
+        };
+        let mut stderr = String::new();
+        if let Some(mut pipe) = self.child.stderr.take() {
+            let _ = pipe.read_to_string(&mut stderr);
+        }
+        anyhow!("powermetrics has exited ({status}): {}", stderr.trim())
+    }
+}
+
+impl Drop for Powermetrics {
+    fn drop(&mut self) {
+        // the reader thread stops when stdout is closed
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Reads the samples printed by `powermetrics`, until its output is closed or `on_sample` returns `false`.
+///
+/// A sample is complete when the next one begins, thus it is only reported one interval later.
+fn read_samples(stdout: ChildStdout, mut on_sample: impl FnMut(Sample) -> bool) {
+    let mut parser = SampleParser::default();
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if let Some(sample) = parser.push_line(&line)
+            && !on_sample(sample)
+        {
+            return;
+        }
+    }
+    if let Some(sample) = parser.finish() {
+        on_sample(sample);
+    }
+}
+
+/// Parses the default (text) output of `powermetrics`, which looks like:
+///
+/// ```text
+/// *** Sampled system activity (Fri Oct 16 10:00:00 2026 +0200) (1004.12ms elapsed) ***
+///
+/// **** Processor usage ****
+/// ...
+/// CPU Power: 1234 mW
+/// GPU Power: 56 mW
+/// ANE Power: 0 mW
+/// Combined Power (CPU + GPU + ANE): 1290 mW
+/// ```
+///
+/// On Intel machines, the power of the package is reported by the line
+/// `Intel energy model derived package power (CPUs+GT+SA): 3.21W`.
+#[derive(Default)]
+struct SampleParser {
+    current: Option<Sample>,
+}
+
+impl SampleParser {
+    /// Parses a line, and returns the previous sample if the line begins a new one.
+    fn push_line(&mut self, line: &str) -> Option<Sample> {
+        let line = line.trim();
+        if line.starts_with("*** Sampled system activity") {
+            let elapsed = parse_elapsed(line);
+            let previous = self.finish();
+            self.current = elapsed.map(|elapsed| Sample {
+                timestamp: Timestamp::now(),
+                elapsed,
+                power: Vec::new(),
+            });
+            return previous;
+        }
+        if let Some(sample) = &mut self.current
+            && let Some((domain, watts)) = parse_power(line)
+        {
+            sample.power.push((domain, watts));
+        }
+        None
+    }
+
+    /// Returns the sample that is being parsed, if it contains some power values.
+    fn finish(&mut self) -> Option<Sample> {
+        self.current.take().filter(|s| !s.power.is_empty())
+    }
+}
+
+/// Parses the elapsed time of the header of a sample, like `(1004.12ms elapsed)`.
+fn parse_elapsed(header: &str) -> Option<Duration> {
+    let end = header.find("ms elapsed)")?;
+    let start = header[..end].rfind('(')? + 1;
+    let millis: f64 = header[start..end].trim().parse().ok()?;
+    Duration::try_from_secs_f64(millis / 1000.0).ok()
+}
+
+/// Parses a line that reports a power, like `CPU Power: 1234 mW`.
+fn parse_power(line: &str) -> Option<(Domain, f64)> {
+    let (label, value) = line.split_once(':')?;
+    let domain = match label.trim() {
+        "CPU Power" => Domain::Cpu,
+        "GPU Power" => Domain::Gpu,
+        "ANE Power" => Domain::Ane,
+        label if label.starts_with("Combined Power") => Domain::Package,
+        label if label.starts_with("Intel energy model derived package power") => Domain::Package,
+        _ => return None,
+    };
+    let value = value.trim();
+    let watts = if let Some(milliwatts) = value.strip_suffix("mW") {
+        milliwatts.trim().parse::<f64>().ok()? / 1000.0
+    } else {
+        value.strip_suffix('W')?.trim().parse::<f64>().ok()?
+    };
+    Some((domain, watts))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const APPLE_SILICON: &str = "Machine model: Mac14,2
+OS version: 23F79
+
+*** Sampled system activity (Fri Oct 16 10:00:00 2026 +0200) (1004.12ms elapsed) ***
+
+**** Processor usage ****
+
+E-Cluster HW active frequency: 1020 MHz
+CPU 0 frequency: 1118 MHz
+
+CPU Power: 1234 mW
+GPU Power: 56 mW
+ANE Power: 0 mW
+Combined Power (CPU + GPU + ANE): 1290 mW
+
+**** GPU usage ****
+
+GPU HW active frequency: 389 MHz
+
+*** Sampled system activity (Fri Oct 16 10:00:01 2026 +0200) (999.50ms elapsed) ***
+
+CPU Power: 2000 mW
+";
+
+    fn parse(output: &str) -> Vec<Sample> {
+        let mut parser = SampleParser::default();
+        let mut samples: Vec<Sample> = output.lines().filter_map(|l| parser.push_line(l)).collect();
+        samples.extend(parser.finish());
+        samples
+    }
+
+    #[test]
+    fn apple_silicon() {
+        let samples = parse(APPLE_SILICON);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].elapsed, Duration::from_micros(1_004_120));
+        assert_eq!(
+            samples[0].power,
+            vec![
+                (Domain::Cpu, 1.234),
+                (Domain::Gpu, 0.056),
+                (Domain::Ane, 0.0),
+                (Domain::Package, 1.29)
+            ]
+        );
+        assert_eq!(samples[1].elapsed, Duration::from_micros(999_500));
+        assert_eq!(samples[1].power, vec![(Domain::Cpu, 2.0)]);
+    }
+
+    #[test]
+    fn intel() {
+        let output = "*** Sampled system activity (Fri Oct 16 10:00:00 2026 +0200) (1000.00ms elapsed) ***
+Intel energy model derived package power (CPUs+GT+SA): 3.21W
+";
+        let samples = parse(output);
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].power, vec![(Domain::Package, 3.21)]);
+    }
+
+    #[test]
+    fn no_power() {
+        let output = "*** Sampled system activity (Fri Oct 16 10:00:00 2026 +0200) (1000.00ms elapsed) ***
+**** Battery and backlight usage ****
+";
+        assert_eq!(parse(output), vec![]);
+    }
+}
@@ -0,0 +1,44 @@
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+
+use crate::{metrics::Metrics, powermetrics::Powermetrics};
+
+/// Measurement source that reports the samples of a `powermetrics` process.
+pub struct PowermetricsSource {
+    powermetrics: Powermetrics,
+    metrics: Metrics,
+}
+
+impl PowermetricsSource {
+    pub fn new(powermetrics: Powermetrics, metrics: Metrics) -> Self {
+        Self { powermetrics, metrics }
+    }
+}
+
+impl Source for PowermetricsSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, _t: Timestamp) -> Result<(), PollError> {
+        // powermetrics cannot be restarted without losing the energy in-between
+        let samples = self.powermetrics.samples().map_err(PollError::Fatal)?;
+        for sample in samples {
+            let elapsed = sample.elapsed.as_secs_f64();
+            for (domain, watts) in sample.power {
+                let point = |metric, value| {
+                    MeasurementPoint::new(
+                        sample.timestamp,
+                        metric,
+                        Resource::LocalMachine,
+                        ResourceConsumer::LocalMachine,
+                        value,
+                    )
+                    .with_attr("domain", domain.as_str())
+                };
+                measurements.push(point(self.metrics.power, watts));
+                measurements.push(point(self.metrics.consumed_energy, watts * elapsed));
+            }
+        }
+        Ok(())
+    }
+}
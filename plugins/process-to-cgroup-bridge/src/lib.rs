@@ -1,7 +1,10 @@
+use std::path::PathBuf;
+
 use alumet::{
     metrics::RawMetricId,
     plugin::{
         ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
 };
@@ -9,9 +12,6 @@ use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use transform::ProcessToCgroupBridgeTransform;
 
-#[cfg(test)]
-use std::path::PathBuf;
-
 #[cfg(test)]
 mod tests;
 
@@ -30,6 +30,10 @@ impl AlumetPlugin for ProcessToCgroupBridgePlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Filesystem(PathBuf::from("/proc"))])
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
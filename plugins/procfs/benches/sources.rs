@@ -0,0 +1,30 @@
+use alumet::test::{
+    PluginHarness,
+    bench::{CountingAllocator, report_allocations},
+};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use plugin_procfs::ProcfsPlugin;
+
+#[global_allocator]
+static ALLOC: CountingAllocator = CountingAllocator;
+
+fn bench_poll(c: &mut Criterion) {
+    let mut harness = PluginHarness::<ProcfsPlugin>::start_with_default_config().unwrap();
+    let mut group = c.benchmark_group("procfs_poll");
+    for name in ["kernel", "memory", "network", "disks"] {
+        let mut source = harness.source(name).unwrap();
+        // the first poll initializes the deltas
+        source.poll().unwrap();
+        let points = source.poll().unwrap().len();
+        report_allocations(&format!("procfs_poll/{name}"), 100, || {
+            source.poll().unwrap();
+        });
+        group.throughput(Throughput::Elements(points as u64));
+        group.bench_function(name, |b| b.iter(|| source.poll().unwrap()));
+    }
+    group.finish();
+    harness.stop().unwrap();
+}
+
+criterion_group!(benches, bench_poll);
+criterion_main!(benches);
@@ -0,0 +1,215 @@
+//! I/O statistics of the block devices.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Seek},
+    path::Path,
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::{TypedMetricId, error::MetricCreationError},
+    pipeline::{Source, elements::error::PollError},
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::Context;
+use procfs::{DiskStat, DiskStats, FromBufRead};
+
+/// Directory of the block devices in sysfs, used to recognize the partitions.
+const SYSFS_BLOCK: &str = "/sys/class/block";
+
+/// The sectors of /proc/diskstats are always 512 bytes long, whatever the device.
+const SECTOR_SIZE: u64 = 512;
+
+/// Reads the I/O statistics of the block devices from /proc/diskstats.
+pub struct DiskStatsProbe {
+    /// A reader opened to /proc/diskstats.
+    reader: BufReader<File>,
+    /// Devices to measure, all the disks if empty.
+    devices: Vec<String>,
+
+    /// The previously measured stats, to compute the difference.
+    previous: HashMap<String, DiskStat>,
+
+    // ids of metrics
+    metrics: DiskMetrics,
+}
+
+pub struct DiskMetrics {
+    operations: TypedMetricId<u64>,
+    bytes: TypedMetricId<u64>,
+    operation_time: TypedMetricId<u64>,
+    busy_time: TypedMetricId<u64>,
+    in_flight: TypedMetricId<u64>,
+}
+
+impl DiskMetrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            operations: alumet.create_metric(
+                "disk_operations_delta",
+                Unit::Unity,
+                "number of completed read or write operations",
+            )?,
+            bytes: alumet.create_metric("disk_bytes_delta", Unit::Byte, "bytes read or written")?,
+            operation_time: alumet.create_metric(
+                "disk_operation_time_delta",
+                PrefixedUnit::milli(Unit::Second),
+                "time spent by the read or write operations, from their submission to their completion",
+            )?,
+            busy_time: alumet.create_metric(
+                "disk_busy_time_delta",
+                PrefixedUnit::milli(Unit::Second),
+                "time during which the device had operations in progress",
+            )?,
+            in_flight: alumet.create_metric("disk_in_flight", Unit::Unity, "number of operations in progress")?,
+        })
+    }
+}
+
+impl DiskStatsProbe {
+    pub fn new(metrics: DiskMetrics, proc_diskstats_path: &str, devices: Vec<String>) -> anyhow::Result<Self> {
+        let file = File::open(proc_diskstats_path).with_context(|| format!("could not open {proc_diskstats_path}"))?;
+        Ok(Self {
+            reader: BufReader::new(file),
+            devices,
+            previous: HashMap::new(),
+            metrics,
+        })
+    }
+
+    fn is_monitored(&self, device: &str) -> bool {
+        if self.devices.is_empty() {
+            is_disk(device, Path::new(SYSFS_BLOCK))
+        } else {
+            self.devices.iter().any(|d| d == device)
+        }
+    }
+}
+
+impl Source for DiskStatsProbe {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        self.reader.rewind()?;
+        let stats = DiskStats::from_buf_read(&mut self.reader)?;
+        let mut current = HashMap::new();
+        for now in stats.0 {
+            if !self.is_monitored(&now.name) {
+                continue;
+            }
+            let point = |metric, value| {
+                MeasurementPoint::new(
+                    timestamp,
+                    metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    value,
+                )
+                .with_attr("device", now.name.clone())
+            };
+
+            acc.push(point(self.metrics.in_flight, now.in_progress));
+            if let Some(delta) = self
+                .previous
+                .get(&now.name)
+                .and_then(|prev| DeltaDiskStat::compute_diff(prev, &now))
+            {
+                let m = &self.metrics;
+                for (metric, kind, value) in [
+                    (m.operations, "read", delta.reads),
+                    (m.operations, "write", delta.writes),
+                    (m.bytes, "read", delta.bytes_read),
+                    (m.bytes, "write", delta.bytes_written),
+                    (m.operation_time, "read", delta.time_reading),
+                    (m.operation_time, "write", delta.time_writing),
+                ] {
+                    acc.push(point(metric, value).with_attr("kind", kind));
+                }
+                acc.push(point(m.busy_time, delta.busy_time));
+            }
+            current.insert(now.name.clone(), now);
+        }
+        // forget the devices that have been removed
+        self.previous = current;
+        Ok(())
+    }
+}
+
+struct DeltaDiskStat {
+    reads: u64,
+    writes: u64,
+    bytes_read: u64,
+    bytes_written: u64,
+    time_reading: u64,
+    time_writing: u64,
+    busy_time: u64,
+}
+
+impl DeltaDiskStat {
+    /// Computes the difference between two measurements, or returns `None` if the counters have been reset.
+    fn compute_diff(prev: &DiskStat, now: &DiskStat) -> Option<Self> {
+        Some(Self {
+            reads: now.reads.checked_sub(prev.reads)?,
+            writes: now.writes.checked_sub(prev.writes)?,
+            bytes_read: now.sectors_read.checked_sub(prev.sectors_read)? * SECTOR_SIZE,
+            bytes_written: now.sectors_written.checked_sub(prev.sectors_written)? * SECTOR_SIZE,
+            time_reading: now.time_reading.checked_sub(prev.time_reading)?,
+            time_writing: now.time_writing.checked_sub(prev.time_writing)?,
+            busy_time: now.time_in_progress.checked_sub(prev.time_in_progress)?,
+        })
+    }
+}
+
+/// Returns `true` if the device is a disk, and not a partition or a virtual device without storage.
+fn is_disk(device: &str, sysfs_block: &Path) -> bool {
+    let virtual_device = ["loop", "ram", "zram"].iter().any(|prefix| device.starts_with(prefix));
+    let partition = sysfs_block.join(device).join("partition").exists();
+    !virtual_device && !partition
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn disk_selection() {
+        let tmp = tempdir().unwrap();
+        let sysfs = tmp.path();
+        fs::create_dir_all(sysfs.join("nvme0n1")).unwrap();
+        fs::create_dir_all(sysfs.join("nvme0n1p1")).unwrap();
+        fs::write(sysfs.join("nvme0n1p1/partition"), "1\n").unwrap();
+
+        assert!(is_disk("nvme0n1", sysfs));
+        assert!(is_disk("sda", sysfs));
+        assert!(!is_disk("nvme0n1p1", sysfs));
+        assert!(!is_disk("loop0", sysfs));
+        assert!(!is_disk("zram0", sysfs));
+    }
+
+    #[test]
+    fn deltas() {
+        let prev = DiskStat::from_line("259 0 nvme0n1 100 5 8000 40 50 2 4000 60 0 90 100 0 0 0 0").unwrap();
+        let now = DiskStat::from_line("259 0 nvme0n1 110 5 8800 44 52 2 4100 66 3 99 110 0 0 0 0").unwrap();
+        let delta = DeltaDiskStat::compute_diff(&prev, &now).unwrap();
+        assert_eq!(
+            [
+                delta.reads,
+                delta.writes,
+                delta.bytes_read,
+                delta.bytes_written,
+                delta.time_reading,
+                delta.time_writing,
+                delta.busy_time
+            ],
+            [10, 2, 800 * 512, 100 * 512, 4, 6, 9]
+        );
+        assert!(DeltaDiskStat::compute_diff(&now, &prev).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+//! Driver-specific statistics of the network interface cards, obtained with the ethtool ioctl (like `ethtool -S`).
+
+use std::{
+    collections::HashMap,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+const ETHTOOL_GDRVINFO: u32 = 0x03;
+const ETHTOOL_GSTRINGS: u32 = 0x1b;
+const ETHTOOL_GSTATS: u32 = 0x1d;
+const ETH_SS_STATS: u32 = 1;
+const ETH_GSTRING_LEN: usize = 32;
+
+/// Size of `struct ethtool_drvinfo`, in u32.
+const DRVINFO_LEN: usize = 49;
+/// Index of `n_stats` in `struct ethtool_drvinfo`.
+const DRVINFO_N_STATS: usize = 45;
+
+pub struct Ethtool {
+    /// Any socket can be used to send the ioctl.
+    socket: OwnedFd,
+    /// Names of the statistics of each interface, which do not change unless the driver is reloaded.
+    names: HashMap<String, Vec<String>>,
+}
+
+impl Ethtool {
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: socket has no memory-related precondition
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            // SAFETY: the fd has just been opened and is owned by nobody else
+            socket: unsafe { OwnedFd::from_raw_fd(fd) },
+            names: HashMap::new(),
+        })
+    }
+
+    /// Returns the statistics of the NIC, as `(name, value)` pairs.
+    ///
+    /// The interfaces that do not support ethtool, such as `lo`, have no statistic.
+    pub fn stats(&mut self, interface: &str) -> io::Result<Vec<(&str, u64)>> {
+        let mut drvinfo = [0u32; DRVINFO_LEN];
+        drvinfo[0] = ETHTOOL_GDRVINFO;
+        match self.ioctl(interface, drvinfo.as_mut_ptr().cast()) {
+            Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => return Ok(Vec::new()),
+            res => res?,
+        };
+        let n_stats = drvinfo[DRVINFO_N_STATS] as usize;
+        if n_stats == 0 {
+            return Ok(Vec::new());
+        }
+
+        if self.names.get(interface).is_none_or(|names| names.len() != n_stats) {
+            // struct ethtool_gstrings: cmd, string_set, len, then the strings
+            let mut gstrings = vec![0u32; 3 + n_stats * ETH_GSTRING_LEN / 4];
+            gstrings[..3].copy_from_slice(&[ETHTOOL_GSTRINGS, ETH_SS_STATS, n_stats as u32]);
+            self.ioctl(interface, gstrings.as_mut_ptr().cast())?;
+            let bytes: Vec<u8> = gstrings[3..].iter().flat_map(|w| w.to_ne_bytes()).collect();
+            self.names.insert(interface.to_owned(), parse_names(&bytes));
+        }
+
+        // struct ethtool_stats: cmd, n_stats, then the values
+        let mut gstats = vec![0u64; 1 + n_stats];
+        let header: [u32; 2] = [ETHTOOL_GSTATS, n_stats as u32];
+        // SAFETY: the first u64 is large enough for the two u32 of the header
+        unsafe { gstats.as_mut_ptr().cast::<[u32; 2]>().write(header) };
+        self.ioctl(interface, gstats.as_mut_ptr().cast())?;
+
+        let names = &self.names[interface];
+        Ok(names
+            .iter()
+            .map(String::as_str)
+            .zip(gstats[1..].iter().copied())
+            .collect())
+    }
+
+    /// Sends an ethtool command, whose structure is pointed to by `data`.
+    fn ioctl(&self, interface: &str, data: *mut libc::c_char) -> io::Result<()> {
+        if interface.len() >= libc::IFNAMSIZ {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "interface name too long"));
+        }
+        // SAFETY: ifreq is a plain C struct, for which zero is a valid value
+        let mut ifr: libc::ifreq = unsafe { std::mem::zeroed() };
+        for (dst, src) in ifr.ifr_name.iter_mut().zip(interface.bytes()) {
+            *dst = src as libc::c_char;
+        }
+        ifr.ifr_ifru.ifru_data = data;
+        // SAFETY: `data` points to a command structure that is large enough for the answer of the kernel
+        let res = unsafe { libc::ioctl(self.socket.as_raw_fd(), libc::SIOCETHTOOL as _, &mut ifr) };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Parses the names returned by ETHTOOL_GSTRINGS, which are nul-padded strings of `ETH_GSTRING_LEN` bytes.
+fn parse_names(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks_exact(ETH_GSTRING_LEN)
+        .map(|chunk| {
+            let len = chunk.iter().position(|b| *b == 0).unwrap_or(chunk.len());
+            String::from_utf8_lossy(&chunk[..len]).into_owned()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let mut bytes = vec![0u8; 2 * ETH_GSTRING_LEN];
+        bytes[..10].copy_from_slice(b"rx_packets");
+        bytes[ETH_GSTRING_LEN..].copy_from_slice(b"tx_queue_0_bytes_with_a_long_nam");
+        assert_eq!(
+            parse_names(&bytes),
+            vec!["rx_packets", "tx_queue_0_bytes_with_a_long_nam"]
+        );
+        assert!(parse_names(&[]).is_empty());
+    }
+}
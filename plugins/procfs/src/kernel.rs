@@ -33,6 +33,7 @@ pub struct KernelStatsProbe {
 
 pub struct KernelMetrics {
     cpu_time: TypedMetricId<u64>,
+    cpu_utilization: TypedMetricId<f64>,
     context_switches: TypedMetricId<u64>,
     new_forks: TypedMetricId<u64>,
     n_procs_running: TypedMetricId<u64>,
@@ -53,6 +54,11 @@ impl KernelMetrics {
     pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
         Ok(Self {
             cpu_time: alumet.create_metric("kernel_cpu_time", PrefixedUnit::milli(Unit::Second), "busy CPU time")?,
+            cpu_utilization: alumet.create_metric(
+                "kernel_cpu_utilization",
+                Unit::Percent,
+                "percentage of CPU time spent outside of the idle state",
+            )?,
             context_switches: alumet.create_metric(
                 "kernel_context_switches",
                 Unit::Unity,
@@ -104,14 +110,9 @@ impl Source for KernelStatsProbe {
             let n_procs_blocked = now.procs_blocked;
 
             // Push measurement points
-            cpu_time_total.push_measurements(self.metrics.cpu_time, Resource::LocalMachine, acc, timestamp);
+            cpu_time_total.push_measurements(&self.metrics, Resource::LocalMachine, acc, timestamp);
             for (i, cpu_time) in cpu_time_per_cpu.into_iter().enumerate() {
-                cpu_time.push_measurements(
-                    self.metrics.cpu_time,
-                    Resource::CpuCore { id: i as u32 },
-                    acc,
-                    timestamp,
-                )
+                cpu_time.push_measurements(&self.metrics, Resource::CpuCore { id: i as u32 }, acc, timestamp)
             }
             acc.push(MeasurementPoint::new(
                 timestamp,
@@ -156,7 +157,8 @@ struct DeltaCpuTime {
     pub nice: u64,
     pub system: u64,
     pub idle: u64,
-    // iowait skipped because it's documented as unreliable
+    // iowait not reported because it's documented as unreliable, but it is not busy time
+    pub iowait: Option<u64>,
     pub irq: Option<u64>,
     pub softirq: Option<u64>,
     pub steal: Option<u64>,
@@ -171,6 +173,7 @@ impl DeltaCpuTime {
             nice: now.nice_ms() - prev.nice_ms(),
             system: now.system_ms() - prev.system_ms(),
             idle: now.idle_ms() - prev.idle_ms(),
+            iowait: now.iowait_ms().map(|x| x.saturating_sub(prev.iowait_ms().unwrap())),
             irq: now.irq_ms().map(|x| x - prev.irq_ms().unwrap()),
             softirq: now.softirq_ms().map(|x| x - prev.softirq_ms().unwrap()),
             steal: now.steal_ms().map(|x| x - prev.steal_ms().unwrap()),
@@ -179,17 +182,39 @@ impl DeltaCpuTime {
         }
     }
 
-    /// Push measurement points with the delta values to `acc`.
+    /// Returns the percentage of time spent in a busy state, or `None` if no time has elapsed.
     ///
-    /// The `metric` must have the `millisecond` unit.
+    /// The guest time is not added because it is already included in the user time.
+    pub fn utilization(&self) -> Option<f64> {
+        let busy = self.user
+            + self.nice
+            + self.system
+            + self.irq.unwrap_or(0)
+            + self.softirq.unwrap_or(0)
+            + self.steal.unwrap_or(0);
+        let total = busy + self.idle + self.iowait.unwrap_or(0);
+        (total > 0).then(|| busy as f64 * 100.0 / total as f64)
+    }
+
+    /// Push measurement points with the delta values and the utilization to `acc`.
     pub fn push_measurements(
         &self,
-        metric: TypedMetricId<u64>,
+        metrics: &KernelMetrics,
         res: Resource,
         acc: &mut MeasurementAccumulator,
         timestamp: Timestamp,
     ) {
         let consumer = ResourceConsumer::LocalMachine;
+        if let Some(utilization) = self.utilization() {
+            acc.push(MeasurementPoint::new(
+                timestamp,
+                metrics.cpu_utilization,
+                res.clone(),
+                consumer.clone(),
+                utilization,
+            ));
+        }
+        let metric = metrics.cpu_time;
         acc.push(
             MeasurementPoint::new(timestamp, metric, res.clone(), consumer.clone(), self.user)
                 .with_attr("cpu_state", "user"),
@@ -238,3 +263,37 @@ impl DeltaCpuTime {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DeltaCpuTime;
+
+    fn delta(user: u64, system: u64, idle: u64, iowait: Option<u64>) -> DeltaCpuTime {
+        DeltaCpuTime {
+            user,
+            nice: 0,
+            system,
+            idle,
+            iowait,
+            irq: Some(5),
+            softirq: Some(5),
+            steal: None,
+            guest: Some(user),
+            guest_nice: None,
+        }
+    }
+
+    #[test]
+    fn cpu_utilization() {
+        assert_eq!(delta(30, 10, 50, None).utilization(), Some(50.0));
+        assert_eq!(delta(20, 10, 40, Some(20)).utilization(), Some(40.0));
+        assert_eq!(delta(0, 0, 90, Some(0)).utilization(), Some(10.0));
+
+        let nothing = DeltaCpuTime {
+            irq: None,
+            softirq: None,
+            ..delta(0, 0, 0, None)
+        };
+        assert_eq!(nothing.utilization(), None);
+    }
+}
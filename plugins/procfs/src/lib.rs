@@ -1,6 +1,9 @@
+use std::path::PathBuf;
+
 use alumet::{
     pipeline::{control::request, elements::source::trigger::TriggerSpec},
     plugin::{
+        capability::Capability,
         event,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
@@ -11,8 +14,12 @@ use anyhow::Context;
 use procfs::{Current, CurrentSI};
 use rlimit::{Resource, getrlimit, setrlimit};
 
+mod disk;
+mod ethtool;
 mod kernel;
+mod load;
 mod memory;
+mod network;
 mod process;
 mod serde_regex;
 
@@ -29,6 +36,13 @@ impl AlumetPlugin for ProcfsPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::Filesystem(PathBuf::from("/proc")),
+            Capability::Filesystem(PathBuf::from(network::SYSFS_NET)),
+        ])
+    }
+
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
         Ok(Some(serialize_config(config::Config::default())?))
     }
@@ -50,6 +64,15 @@ impl AlumetPlugin for ProcfsPlugin {
         if config.memory.enabled {
             start_memory_probe(config.memory, alumet)?;
         }
+        if config.load.enabled {
+            start_load_probe(config.load, alumet)?;
+        }
+        if config.network.enabled {
+            start_network_probe(config.network, alumet)?;
+        }
+        if config.disks.enabled {
+            start_disk_probe(config.disks, alumet)?;
+        }
         if config.processes.enabled {
             let metrics = process::ProcessMetrics {
                 metric_cpu_time_delta: alumet
@@ -115,6 +138,52 @@ fn start_memory_probe(
     Ok(())
 }
 
+fn start_load_probe(
+    config_load: config::LoadAvgMonitoring,
+    alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
+) -> Result<(), anyhow::Error> {
+    let trigger = TriggerSpec::at_interval(config_load.poll_interval);
+    let metric = alumet
+        .create_metric(
+            "kernel_load_average",
+            Unit::Unity,
+            "average number of runnable or uninterruptible tasks",
+        )
+        .context("unable to register metric kernel_load_average for load probe")?;
+    let source = load::LoadAvgProbe::new(metric, procfs::LoadAverage::PATH).context("unable to create load probe")?;
+    alumet.add_source("load", Box::new(source), trigger)?;
+    Ok(())
+}
+
+fn start_network_probe(
+    config_network: config::NetworkMonitoring,
+    alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
+) -> Result<(), anyhow::Error> {
+    let trigger = TriggerSpec::at_interval(config_network.poll_interval);
+    let metrics = network::NetworkMetrics::new(alumet).context("unable to register metrics for network probe")?;
+    let source = network::NetworkProbe::new(
+        metrics,
+        network::SYSFS_NET,
+        config_network.interfaces,
+        config_network.ethtool,
+    )
+    .context("unable to create network probe")?;
+    alumet.add_source("network", Box::new(source), trigger)?;
+    Ok(())
+}
+
+fn start_disk_probe(
+    config_disks: config::DiskStatsMonitoring,
+    alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
+) -> Result<(), anyhow::Error> {
+    let trigger = TriggerSpec::at_interval(config_disks.poll_interval);
+    let metrics = disk::DiskMetrics::new(alumet).context("unable to register metrics for disk probe")?;
+    let source = disk::DiskStatsProbe::new(metrics, procfs::DiskStats::PATH, config_disks.devices)
+        .context("unable to create disk probe")?;
+    alumet.add_source("disks", Box::new(source), trigger)?;
+    Ok(())
+}
+
 fn start_process_watcher(
     config_processes: config::ProcessMonitoring,
     alumet: &mut alumet::plugin::AlumetPluginStart<'_>,
@@ -204,6 +273,15 @@ mod config {
     pub struct Config {
         pub kernel: KernelStatsMonitoring,
         pub memory: MeminfoMonitoring,
+        /// Optional, for compatibility with the configurations that predate the load probe.
+        #[serde(default)]
+        pub load: LoadAvgMonitoring,
+        /// Optional, for compatibility with the configurations that predate the network probe.
+        #[serde(default)]
+        pub network: NetworkMonitoring,
+        /// Optional, for compatibility with the configurations that predate the disk probe.
+        #[serde(default)]
+        pub disks: DiskStatsMonitoring,
         pub processes: ProcessMonitoring,
     }
 
@@ -225,6 +303,39 @@ mod config {
         pub metrics: Vec<String>,
     }
 
+    #[derive(Serialize, Deserialize)]
+    pub struct LoadAvgMonitoring {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(with = "humantime_serde")]
+        pub poll_interval: Duration,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct NetworkMonitoring {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(with = "humantime_serde")]
+        pub poll_interval: Duration,
+        /// The interfaces to monitor, all the interfaces except the loopback if empty.
+        #[serde(default)]
+        pub interfaces: Vec<String>,
+        /// `true` to also collect the statistics of the NICs, like `ethtool -S`.
+        #[serde(default)]
+        pub ethtool: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct DiskStatsMonitoring {
+        #[serde(default = "default_enabled")]
+        pub enabled: bool,
+        #[serde(with = "humantime_serde")]
+        pub poll_interval: Duration,
+        /// The block devices to monitor, all the disks (without the partitions) if empty.
+        #[serde(default)]
+        pub devices: Vec<String>,
+    }
+
     #[derive(Serialize, Deserialize)]
     pub struct ProcessMonitoring {
         /// `true` to enable the monitoring of processes.
@@ -312,6 +423,36 @@ mod config {
         }
     }
 
+    impl Default for LoadAvgMonitoring {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                poll_interval: Duration::from_secs(5),
+            }
+        }
+    }
+
+    impl Default for NetworkMonitoring {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                poll_interval: Duration::from_secs(5),
+                interfaces: Vec::new(),
+                ethtool: false,
+            }
+        }
+    }
+
+    impl Default for DiskStatsMonitoring {
+        fn default() -> Self {
+            Self {
+                enabled: true,
+                poll_interval: Duration::from_secs(5),
+                devices: Vec::new(),
+            }
+        }
+    }
+
     impl Default for ProcessMonitoring {
         fn default() -> Self {
             Self {
@@ -0,0 +1,53 @@
+//! Load average of the system.
+
+use std::{
+    fs::File,
+    io::{BufReader, Seek},
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+use procfs::{FromRead, LoadAverage};
+
+/// Reads the load average from /proc/loadavg.
+pub struct LoadAvgProbe {
+    /// A reader opened to /proc/loadavg.
+    reader: BufReader<File>,
+    /// Metric of the load average, with the `Unity` unit.
+    metric: TypedMetricId<f64>,
+}
+
+impl LoadAvgProbe {
+    pub fn new(metric: TypedMetricId<f64>, proc_loadavg_path: &str) -> anyhow::Result<Self> {
+        let file = File::open(proc_loadavg_path).with_context(|| format!("could not open {proc_loadavg_path}"))?;
+        Ok(Self {
+            reader: BufReader::new(file),
+            metric,
+        })
+    }
+}
+
+impl Source for LoadAvgProbe {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        self.reader.rewind()?;
+        let load = LoadAverage::from_read(&mut self.reader)?;
+        for (window, value) in [("1m", load.one), ("5m", load.five), ("15m", load.fifteen)] {
+            acc.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    f64::from(value),
+                )
+                .with_attr("window", window),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+//! Network activity of the interfaces.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::{TypedMetricId, error::MetricCreationError},
+    pipeline::{Source, elements::error::PollError},
+    plugin::AlumetPluginStart,
+    resources::{Resource, ResourceConsumer},
+    units::Unit,
+};
+use anyhow::Context;
+
+use crate::ethtool::Ethtool;
+
+/// Directory of the network interfaces in sysfs.
+pub const SYSFS_NET: &str = "/sys/class/net";
+
+/// Reads the counters of the network interfaces from /sys/class/net.
+pub struct NetworkProbe {
+    /// Path to the directory that contains the interfaces, usually /sys/class/net.
+    sysfs_net: PathBuf,
+    /// Interfaces to measure, all the interfaces except the loopback if empty.
+    interfaces: Vec<String>,
+    /// Reads the statistics of the NICs, if enabled.
+    ethtool: Option<Ethtool>,
+
+    /// The previously measured counters, to compute the difference.
+    previous: HashMap<String, InterfaceCounters>,
+
+    // ids of metrics
+    metrics: NetworkMetrics,
+}
+
+pub struct NetworkMetrics {
+    bytes: TypedMetricId<u64>,
+    packets: TypedMetricId<u64>,
+    errors: TypedMetricId<u64>,
+    nic_stat: TypedMetricId<u64>,
+}
+
+/// Cumulative counters of an interface, in the order of [`InterfaceCounters::FILES`].
+#[derive(Debug, PartialEq)]
+struct InterfaceCounters([u64; 6]);
+
+impl InterfaceCounters {
+    const FILES: [&str; 6] = [
+        "rx_bytes",
+        "tx_bytes",
+        "rx_packets",
+        "tx_packets",
+        "rx_errors",
+        "tx_errors",
+    ];
+
+    fn read(interface_dir: &Path) -> anyhow::Result<Self> {
+        let mut counters = [0; 6];
+        for (value, file) in counters.iter_mut().zip(Self::FILES) {
+            let path = interface_dir.join("statistics").join(file);
+            let content = fs::read_to_string(&path).with_context(|| format!("could not read {path:?}"))?;
+            *value = content
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid counter in {path:?}: {content}"))?;
+        }
+        Ok(Self(counters))
+    }
+}
+
+impl NetworkMetrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> Result<Self, MetricCreationError> {
+        Ok(Self {
+            bytes: alumet.create_metric(
+                "network_bytes_delta",
+                Unit::Byte,
+                "bytes received or sent by the interface",
+            )?,
+            packets: alumet.create_metric(
+                "network_packets_delta",
+                Unit::Unity,
+                "packets received or sent by the interface",
+            )?,
+            errors: alumet.create_metric(
+                "network_errors_delta",
+                Unit::Unity,
+                "receive or transmit errors of the interface",
+            )?,
+            nic_stat: alumet.create_metric(
+                "network_nic_stat",
+                Unit::Unity,
+                "driver-specific statistic of the network interface card, as reported by `ethtool -S`",
+            )?,
+        })
+    }
+}
+
+impl NetworkProbe {
+    pub fn new(
+        metrics: NetworkMetrics,
+        sysfs_net: impl Into<PathBuf>,
+        interfaces: Vec<String>,
+        ethtool: bool,
+    ) -> anyhow::Result<Self> {
+        let sysfs_net = sysfs_net.into();
+        fs::read_dir(&sysfs_net).with_context(|| format!("could not list {sysfs_net:?}"))?;
+        let ethtool = if ethtool {
+            Some(Ethtool::new().context("could not open a socket for ethtool")?)
+        } else {
+            None
+        };
+        Ok(Self {
+            sysfs_net,
+            interfaces,
+            ethtool,
+            previous: HashMap::new(),
+            metrics,
+        })
+    }
+
+    /// Lists the interfaces to measure.
+    fn list_interfaces(&self) -> anyhow::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.sysfs_net)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let selected = if self.interfaces.is_empty() {
+                name != "lo"
+            } else {
+                self.interfaces.contains(&name)
+            };
+            if selected {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+impl Source for NetworkProbe {
+    fn poll(&mut self, acc: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        let mut current = HashMap::new();
+        for interface in self.list_interfaces()? {
+            // The interface can disappear between the listing and the reading.
+            let Ok(now) = InterfaceCounters::read(&self.sysfs_net.join(&interface)) else {
+                continue;
+            };
+            if let Some(prev) = self.previous.get(&interface) {
+                let m = &self.metrics;
+                let deltas = [
+                    (m.bytes, "rx"),
+                    (m.bytes, "tx"),
+                    (m.packets, "rx"),
+                    (m.packets, "tx"),
+                    (m.errors, "rx"),
+                    (m.errors, "tx"),
+                ];
+                for (i, (metric, kind)) in deltas.into_iter().enumerate() {
+                    // The counters are reset when the interface is recreated, skip the deltas in this case.
+                    if let Some(delta) = now.0[i].checked_sub(prev.0[i]) {
+                        acc.push(
+                            MeasurementPoint::new(
+                                timestamp,
+                                metric,
+                                Resource::LocalMachine,
+                                ResourceConsumer::LocalMachine,
+                                delta,
+                            )
+                            .with_attr("interface", interface.clone())
+                            .with_attr("kind", kind),
+                        );
+                    }
+                }
+            }
+
+            if let Some(ethtool) = &mut self.ethtool {
+                match ethtool.stats(&interface) {
+                    Ok(stats) => {
+                        for (name, value) in stats {
+                            acc.push(
+                                MeasurementPoint::new(
+                                    timestamp,
+                                    self.metrics.nic_stat,
+                                    Resource::LocalMachine,
+                                    ResourceConsumer::LocalMachine,
+                                    value,
+                                )
+                                .with_attr("interface", interface.clone())
+                                .with_attr("stat", name.to_owned()),
+                            );
+                        }
+                    }
+                    Err(e) => log::debug!("could not get the ethtool statistics of {interface}: {e}"),
+                }
+            }
+            current.insert(interface, now);
+        }
+        // forget the interfaces that have been removed
+        self.previous = current;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn interface_counters() {
+        let tmp = tempdir().unwrap();
+        let stats = tmp.path().join("eth0/statistics");
+        fs::create_dir_all(&stats).unwrap();
+        for (i, file) in InterfaceCounters::FILES.iter().enumerate() {
+            fs::write(stats.join(file), format!("{}\n", i * 100)).unwrap();
+        }
+        let counters = InterfaceCounters::read(&tmp.path().join("eth0")).unwrap();
+        assert_eq!(counters, InterfaceCounters([0, 100, 200, 300, 400, 500]));
+
+        fs::write(stats.join("tx_errors"), "n/a\n").unwrap();
+        assert!(InterfaceCounters::read(&tmp.path().join("eth0")).is_err());
+        assert!(InterfaceCounters::read(&tmp.path().join("eth1")).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 mod output;
 
+use alumet::plugin::capability::Capability;
 use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
 use hyper::http::StatusCode;
 use hyper::{
@@ -7,8 +8,8 @@ use hyper::{
     service::{make_service_fn, service_fn},
 };
 use output::PrometheusOutput;
-use prometheus_client::encoding::text::encode;
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 use tokio::runtime::Builder;
 use tokio::sync::oneshot;
 
@@ -26,6 +27,10 @@ impl AlumetPlugin for PrometheusPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn default_config() -> anyhow::Result<Option<alumet::plugin::ConfigTable>> {
         Ok(Some(serialize_config(Config::default())?))
     }
@@ -47,6 +52,7 @@ impl AlumetPlugin for PrometheusPlugin {
             self.config.host.clone(),
             self.config.prefix.clone(),
             self.config.suffix.clone(),
+            self.config.stale_after,
         )?);
 
         // Create shutdown channel to close the server thread
@@ -63,7 +69,7 @@ impl AlumetPlugin for PrometheusPlugin {
             // Execute the server inside the current thread runtime
             rt.block_on(async {
                 let state_clone = output_clone.state.clone();
-                let addr_clone = output_clone.addr.clone();
+                let addr_clone = output_clone.addr;
                 let make_svc = make_service_fn(move |_conn| {
                     let state = state_clone.clone();
                     async move {
@@ -78,19 +84,9 @@ impl AlumetPlugin for PrometheusPlugin {
                                             .unwrap(),
                                     );
                                 }
-                                let mut buf = String::new();
-                                if let Err(e) = encode(&mut buf, &*state.registry.read().await) {
-                                    log::error!("Failed to encode metrics: {}", e);
-                                    return Ok(Response::builder()
-                                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                                        .body(Body::from("Internal Server Error"))
-                                        .unwrap());
-                                }
+                                let buf = state.encode(Instant::now());
                                 Ok(Response::builder()
-                                    .header(
-                                        "Content-Type",
-                                        "application/openmetrics-text; version=1.0.0; charset=utf-8",
-                                    )
+                                    .header("Content-Type", output::CONTENT_TYPE)
                                     .body(Body::from(buf))
                                     .unwrap())
                             }
@@ -157,6 +153,9 @@ struct Config {
     port: u16,
     use_unit_display_name: bool,
     add_attributes_to_labels: bool,
+    /// Series that have not been updated for this duration are no longer exposed.
+    #[serde(with = "humantime_serde")]
+    stale_after: Duration,
 }
 
 impl Default for Config {
@@ -168,6 +167,7 @@ impl Default for Config {
             port: 9091,
             use_unit_display_name: true,
             add_attributes_to_labels: true,
+            stale_after: Duration::from_secs(300),
         }
     }
 }
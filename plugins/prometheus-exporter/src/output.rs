@@ -3,22 +3,37 @@ use alumet::{
     metrics::Metric,
     pipeline::elements::{error::WriteError, output::OutputContext},
 };
+use alumet_prometheus::{sanitize_label_name, sanitize_metric_name};
 use anyhow::Context;
-use prometheus_client::{
-    metrics::{family::Family, gauge::Gauge},
-    registry::Registry,
-};
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
+    fmt::Write,
     net::SocketAddr,
-    sync::{Arc, atomic::AtomicU64},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use tokio::sync::RwLock;
 
+/// Content type of the Prometheus text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Latest value of each series, shared between the output and the HTTP server.
 #[derive(Clone)]
 pub struct MetricState {
-    pub registry: Arc<RwLock<Registry>>,
-    metrics: Arc<RwLock<HashMap<String, Family<Vec<(String, String)>, Gauge<f64, AtomicU64>>>>>,
+    families: Arc<Mutex<BTreeMap<String, MetricFamily>>>,
+    /// Series that have not been updated for this duration are no longer exposed.
+    stale_after: Duration,
+}
+
+/// A Prometheus metric, with all its series.
+struct MetricFamily {
+    help: String,
+    /// Series, by sorted labels.
+    series: BTreeMap<Vec<(String, String)>, Sample>,
+}
+
+struct Sample {
+    value: f64,
+    updated: Instant,
 }
 
 #[derive(Clone)]
@@ -31,6 +46,44 @@ pub struct PrometheusOutput {
     pub addr: SocketAddr,
 }
 
+impl MetricState {
+    pub fn new(stale_after: Duration) -> Self {
+        Self {
+            families: Arc::new(Mutex::new(BTreeMap::new())),
+            stale_after,
+        }
+    }
+
+    /// Removes the stale series and encodes the others in the Prometheus text exposition format.
+    pub fn encode(&self, now: Instant) -> String {
+        let mut families = self.families.lock().unwrap();
+        families.retain(|_, family| {
+            family
+                .series
+                .retain(|_, sample| now.saturating_duration_since(sample.updated) < self.stale_after);
+            !family.series.is_empty()
+        });
+
+        let mut buf = String::new();
+        for (name, family) in families.iter() {
+            writeln!(buf, "# HELP {name} {}", escape_help(&family.help)).unwrap();
+            writeln!(buf, "# TYPE {name} gauge").unwrap();
+            for (labels, sample) in &family.series {
+                buf.push_str(name);
+                if !labels.is_empty() {
+                    let labels: Vec<String> = labels
+                        .iter()
+                        .map(|(k, v)| format!("{k}=\"{}\"", escape_label_value(v)))
+                        .collect();
+                    write!(buf, "{{{}}}", labels.join(",")).unwrap();
+                }
+                writeln!(buf, " {}", format_value(sample.value)).unwrap();
+            }
+        }
+        buf
+    }
+}
+
 impl PrometheusOutput {
     pub fn new(
         use_unit_display_name: bool,
@@ -39,19 +92,15 @@ impl PrometheusOutput {
         host: String,
         prefix: String,
         suffix: String,
+        stale_after: Duration,
     ) -> anyhow::Result<PrometheusOutput> {
-        // Create metric state
-        let registry = Arc::new(RwLock::new(Registry::default()));
-        let metrics = Arc::new(RwLock::new(HashMap::new()));
-        let state = MetricState { registry, metrics };
-
         // Configure the HTTP server to expose the metrics
         let addr: SocketAddr = format!("{}:{}", host, port)
             .parse()
             .context("Invalid host:port configuration")?;
 
         Ok(Self {
-            state,
+            state: MetricState::new(stale_after),
             use_unit_display_name,
             add_attributes_to_labels,
             prefix,
@@ -67,24 +116,16 @@ impl alumet::pipeline::Output for PrometheusOutput {
             return Ok(());
         }
 
-        // Ensure threads reading and writing are handled correctly
-        let mut metrics = self.state.metrics.blocking_write();
-        let mut registry = self.state.registry.blocking_write();
+        let now = Instant::now();
+        let mut families = self.state.families.lock().unwrap();
 
         for m in measurements {
-            let metric = ctx.metrics.by_id(&m.metric).unwrap();
-
             // Configure the name of the metric
             let full_metric = ctx
                 .metrics
                 .by_id(&m.metric)
                 .with_context(|| format!("Unknown metric {:?}", m.metric))?;
-            let metric_name = format!(
-                "{}{}{}",
-                self.prefix,
-                sanitize_name(full_metric.name.clone()),
-                self.suffix
-            );
+            let metric_name = sanitize_metric_name(&format!("{}{}{}", self.prefix, full_metric.name, self.suffix));
 
             // Create the default labels for all metrics and optionally add attributes
             let mut labels = vec![
@@ -99,38 +140,32 @@ impl alumet::pipeline::Output for PrometheusOutput {
             if self.add_attributes_to_labels {
                 // Add attributes as labels
                 for (key, value) in m.attributes() {
-                    let key = sanitize_name(key.to_owned());
-                    labels.push((key, value.to_string()));
+                    labels.push((sanitize_label_name(key), value.to_string()));
                 }
             }
             labels.sort_by(|a, b| a.0.cmp(&b.0));
+            labels.dedup_by(|a, b| a.0 == b.0);
 
-            // Each family vector contains a metric with all associated metrics and differentiated by the labels
-            let family = if let Some(family) = metrics.get(&metric_name) {
-                family
-            } else {
-                let unit_string = get_unit_string(full_metric, self.use_unit_display_name);
-                let family = Family::<Vec<(String, String)>, Gauge<f64, AtomicU64>>::default();
-                registry.register_with_unit(
-                    metric_name.clone(),
-                    &metric.description,
-                    prometheus_client::registry::Unit::Other(unit_string),
-                    family.clone(),
-                );
-
-                metrics.insert(metric_name.clone(), family.clone());
-                // Check that it was correctly registered
-                metrics
-                    .get(&metric_name)
-                    .ok_or_else(|| WriteError::Fatal(anyhow::anyhow!("Failed to retrieve metric after registration")))?
-            };
+            // Each family contains the series of a metric, differentiated by their labels
+            let family = families.entry(metric_name).or_insert_with(|| {
+                let unit = get_unit_string(full_metric, self.use_unit_display_name);
+                let help = if unit.is_empty() {
+                    full_metric.description.clone()
+                } else {
+                    format!("{} ({unit})", full_metric.description)
+                };
+                MetricFamily {
+                    help,
+                    series: BTreeMap::new(),
+                }
+            });
 
-            // Update metric value
-            let gauge = family.get_or_create(&labels);
-            match m.value {
-                WrappedMeasurementValue::F64(v) => gauge.set(v as f64),
-                WrappedMeasurementValue::U64(v) => gauge.set(v as f64),
+            // Update the latest value
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
             };
+            family.series.insert(labels, Sample { value, updated: now });
         }
 
         Ok(())
@@ -138,10 +173,22 @@ impl alumet::pipeline::Output for PrometheusOutput {
 }
 
 // Helper functions to ensure metric/label names follow Prometheus naming rules
-fn sanitize_name(name: String) -> String {
-    name.chars()
-        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
-        .collect()
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        String::from("NaN")
+    } else if value.is_infinite() {
+        String::from(if value > 0.0 { "+Inf" } else { "-Inf" })
+    } else {
+        value.to_string()
+    }
 }
 
 fn get_unit_string(full_metric: &Metric, use_unit_display_name: bool) -> String {
@@ -151,3 +198,78 @@ fn get_unit_string(full_metric: &Metric, use_unit_display_name: bool) -> String
         full_metric.unit.unique_name()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape_label_value(r#"a "quoted" \ value"#), r#"a \"quoted\" \\ value"#);
+        assert_eq!(escape_label_value("two\nlines"), "two\\nlines");
+        assert_eq!(escape_help("with \"quotes\"\n"), "with \"quotes\"\\n");
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(12.5), "12.5");
+    }
+
+    #[test]
+    fn encode_and_staleness() {
+        let state = MetricState::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        {
+            let mut families = state.families.lock().unwrap();
+            let mut series = BTreeMap::new();
+            let labels = |id: &str| {
+                vec![
+                    (String::from("resource_id"), id.to_owned()),
+                    (String::from("resource_kind"), String::from("cpu_package")),
+                ]
+            };
+            series.insert(
+                labels("0"),
+                Sample {
+                    value: 12.5,
+                    updated: t0,
+                },
+            );
+            series.insert(
+                labels("1"),
+                Sample {
+                    value: 3.0,
+                    updated: t0 + Duration::from_secs(30),
+                },
+            );
+            families.insert(
+                String::from("energy_alumet"),
+                MetricFamily {
+                    help: String::from("energy consumed (J)"),
+                    series,
+                },
+            );
+        }
+
+        let expected = "\
+# HELP energy_alumet energy consumed (J)
+# TYPE energy_alumet gauge
+energy_alumet{resource_id=\"0\",resource_kind=\"cpu_package\"} 12.5
+energy_alumet{resource_id=\"1\",resource_kind=\"cpu_package\"} 3
+";
+        assert_eq!(state.encode(t0 + Duration::from_secs(10)), expected);
+
+        // the first series is stale
+        let expected = "\
+# HELP energy_alumet energy consumed (J)
+# TYPE energy_alumet gauge
+energy_alumet{resource_id=\"1\",resource_kind=\"cpu_package\"} 3
+";
+        assert_eq!(state.encode(t0 + Duration::from_secs(60)), expected);
+
+        // every series is stale, the family is removed
+        assert_eq!(state.encode(t0 + Duration::from_secs(90)), "");
+    }
+}
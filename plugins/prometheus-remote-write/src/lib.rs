@@ -0,0 +1,125 @@
+mod output;
+pub mod proto;
+mod relabel;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::Context;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+use crate::output::RemoteWriteOutput;
+pub use crate::relabel::{RelabelAction, RelabelConfig};
+
+pub struct RemoteWritePlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for RemoteWritePlugin {
+    fn name() -> &'static str {
+        "prometheus-remote-write"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(RemoteWritePlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::try_from(name).with_context(|| format!("invalid header name {name}"))?;
+            let value = HeaderValue::try_from(value).with_context(|| format!("invalid value for header {name}"))?;
+            headers.insert(name, value);
+        }
+        let auth = match (&config.login, &config.password) {
+            (Some(user), Some(pass)) => Some((user.to_owned(), pass.to_owned())),
+            _ => None,
+        };
+        let relabel = config
+            .relabel
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, r)| r.try_into().with_context(|| format!("invalid relabel rule {i}")))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let output = RemoteWriteOutput::new(
+            config.url.to_owned(),
+            headers,
+            auth,
+            config.timeout,
+            config.external_labels.clone(),
+            config.add_attributes_to_labels,
+            relabel,
+            config.max_retries,
+            config.retry_delay,
+        )?;
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// URL of the remote-write endpoint, ex. `http://localhost:9090/api/v1/write`.
+    pub url: String,
+    /// Login and password for HTTP basic authentication, both optional.
+    pub login: Option<String>,
+    pub password: Option<String>,
+    /// Additional HTTP headers, ex. `X-Scope-OrgID` for Mimir.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    /// How many times a batch is sent again when the endpoint is unavailable.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after each attempt.
+    #[serde(with = "humantime_serde")]
+    pub retry_delay: Duration,
+    /// Labels added to every series, ex. `instance`.
+    #[serde(default)]
+    pub external_labels: BTreeMap<String, String>,
+    pub add_attributes_to_labels: bool,
+    /// Relabeling rules, applied in order to each series.
+    #[serde(default)]
+    pub relabel: Vec<RelabelConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: String::from("http://localhost:9090/api/v1/write"),
+            login: None,
+            password: None,
+            headers: BTreeMap::new(),
+            timeout: Duration::from_secs(10),
+            max_retries: 3,
+            retry_delay: Duration::from_millis(500),
+            external_labels: BTreeMap::new(),
+            add_attributes_to_labels: true,
+            relabel: Vec::new(),
+        }
+    }
+}
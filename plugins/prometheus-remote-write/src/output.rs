@@ -0,0 +1,185 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use alumet_prometheus::{sanitize_label_name, sanitize_metric_name};
+use anyhow::{Context, anyhow};
+use prost::Message;
+use reqwest::{StatusCode, blocking::Client, header::HeaderMap};
+
+use crate::{
+    proto::{Label, Sample, TimeSeries, WriteRequest},
+    relabel::{self, RelabelRule},
+};
+
+pub struct RemoteWriteOutput {
+    client: Client,
+    url: String,
+    auth: Option<(String, String)>,
+    /// Labels added to every series.
+    external_labels: BTreeMap<String, String>,
+    add_attributes_to_labels: bool,
+    relabel: Vec<RelabelRule>,
+    /// How many times a request is sent again after a recoverable failure.
+    max_retries: u32,
+    /// Delay before the first retry, doubled after each attempt.
+    retry_delay: Duration,
+}
+
+/// The outcome of a request that failed.
+enum SendError {
+    /// The request can be sent again, e.g. because the endpoint is temporarily unavailable.
+    Recoverable(anyhow::Error),
+    /// The data has been rejected, sending it again would not help.
+    Rejected(anyhow::Error),
+}
+
+impl RemoteWriteOutput {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        url: String,
+        headers: HeaderMap,
+        auth: Option<(String, String)>,
+        timeout: Duration,
+        external_labels: BTreeMap<String, String>,
+        add_attributes_to_labels: bool,
+        relabel: Vec<RelabelRule>,
+        max_retries: u32,
+        retry_delay: Duration,
+    ) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(timeout)
+            .build()
+            .context("failed to build the HTTP client")?;
+        Ok(Self {
+            client,
+            url,
+            auth,
+            external_labels,
+            add_attributes_to_labels,
+            relabel,
+            max_retries,
+            retry_delay,
+        })
+    }
+
+    /// Converts the measurements to a remote-write request, one series per set of labels.
+    fn to_write_request(&self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> anyhow::Result<WriteRequest> {
+        let mut series: BTreeMap<BTreeMap<String, String>, Vec<Sample>> = BTreeMap::new();
+        for m in measurements {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+
+            let mut labels = self.external_labels.clone();
+            if self.add_attributes_to_labels {
+                for (key, value) in m.attributes() {
+                    labels.insert(sanitize_label_name(key), value.to_string());
+                }
+            }
+            labels.insert(String::from("resource_kind"), m.resource.kind().to_owned());
+            labels.insert(String::from("resource_id"), m.resource.id_display().to_string());
+            labels.insert(String::from("resource_consumer_kind"), m.consumer.kind().to_owned());
+            labels.insert(
+                String::from("resource_consumer_id"),
+                m.consumer.id_display().to_string(),
+            );
+            labels.insert(String::from("__name__"), sanitize_metric_name(&metric.name));
+            labels.retain(|_, v| !v.is_empty());
+
+            if !relabel::apply(&self.relabel, &mut labels) {
+                continue;
+            }
+
+            let (secs, nanos) = m.timestamp.to_unix_timestamp();
+            let sample = Sample {
+                value: match m.value {
+                    WrappedMeasurementValue::F64(v) => v,
+                    WrappedMeasurementValue::U64(v) => v as f64,
+                },
+                timestamp: secs as i64 * 1000 + nanos as i64 / 1_000_000,
+            };
+            series.entry(labels).or_default().push(sample);
+        }
+
+        let timeseries = series
+            .into_iter()
+            .map(|(labels, mut samples)| {
+                samples.sort_by_key(|s| s.timestamp);
+                TimeSeries {
+                    labels: labels.into_iter().map(|(name, value)| Label { name, value }).collect(),
+                    samples,
+                }
+            })
+            .collect();
+        Ok(WriteRequest { timeseries })
+    }
+
+    /// Sends a compressed request to the remote-write endpoint, once.
+    fn send(&self, body: &[u8]) -> Result<(), SendError> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Content-Encoding", "snappy")
+            .header("Content-Type", "application/x-protobuf")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+            .body(body.to_vec());
+        if let Some((user, pass)) = &self.auth {
+            request = request.basic_auth(user, Some(pass));
+        }
+        let res = request.send().map_err(|e| SendError::Recoverable(e.into()))?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = res.text().unwrap_or_default();
+        let error = anyhow!("remote-write endpoint returned {status}: {body}");
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            Err(SendError::Recoverable(error))
+        } else {
+            Err(SendError::Rejected(error))
+        }
+    }
+}
+
+impl alumet::pipeline::Output for RemoteWriteOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let request = self.to_write_request(measurements, ctx)?;
+        if request.timeseries.is_empty() {
+            return Ok(());
+        }
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .context("snappy compression failed")?;
+
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.send(&body) {
+                Ok(()) => return Ok(()),
+                Err(SendError::Rejected(e)) => {
+                    // Sending the measurements again would fail in the same way: drop them, the next ones may
+                    // be accepted. Returning an error would keep them in the gap of a failover rule forever.
+                    log::error!("The measurements have been rejected and are dropped: {e:#}");
+                    return Ok(());
+                }
+                Err(SendError::Recoverable(e)) if attempt < self.max_retries => {
+                    log::warn!("Failed to send the measurements, retrying in {delay:?}: {e:#}");
+                    std::thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(SendError::Recoverable(e)) => {
+                    return Err(WriteError::CanRetry(
+                        e.context(format!("failed to send the measurements after {attempt} retries")),
+                    ));
+                }
+            }
+        }
+    }
+}
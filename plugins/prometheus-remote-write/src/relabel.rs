@@ -0,0 +1,232 @@
+//! Relabeling of the series before they are sent, like `write_relabel_configs` in Prometheus.
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A relabeling rule, as written in the configuration.
+///
+/// The fields have the same meaning as in the Prometheus configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelabelConfig {
+    /// Labels whose values are concatenated (with `separator`) and matched against `regex`.
+    #[serde(default)]
+    pub source_labels: Vec<String>,
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    /// Regular expression, anchored at both ends.
+    #[serde(default = "default_regex")]
+    pub regex: String,
+    /// Label to write, for the `replace` action.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_label: Option<String>,
+    /// Value of the target label, for the `replace` action. Can refer to the capture groups of `regex`.
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+    #[serde(default)]
+    pub action: RelabelAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelabelAction {
+    /// Writes `replacement` to `target_label` if `regex` matches.
+    #[default]
+    Replace,
+    /// Drops the series if `regex` does not match.
+    Keep,
+    /// Drops the series if `regex` matches.
+    Drop,
+    /// Removes the labels whose name matches `regex`.
+    LabelDrop,
+    /// Removes the labels whose name does not match `regex`.
+    LabelKeep,
+}
+
+fn default_separator() -> String {
+    String::from(";")
+}
+
+fn default_regex() -> String {
+    String::from("(.*)")
+}
+
+fn default_replacement() -> String {
+    String::from("$1")
+}
+
+/// A relabeling rule, ready to be applied.
+pub struct RelabelRule {
+    source_labels: Vec<String>,
+    separator: String,
+    regex: Regex,
+    target_label: Option<String>,
+    replacement: String,
+    action: RelabelAction,
+}
+
+impl TryFrom<RelabelConfig> for RelabelRule {
+    type Error = anyhow::Error;
+
+    fn try_from(config: RelabelConfig) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&format!("^(?:{})$", config.regex))?;
+        if config.action == RelabelAction::Replace && config.target_label.is_none() {
+            return Err(anyhow::anyhow!("the replace action requires a target_label"));
+        }
+        Ok(Self {
+            source_labels: config.source_labels,
+            separator: config.separator,
+            regex,
+            target_label: config.target_label,
+            replacement: config.replacement,
+            action: config.action,
+        })
+    }
+}
+
+/// Applies the rules, in order, to the labels of a series.
+///
+/// Returns `false` if the series must be dropped.
+pub fn apply(rules: &[RelabelRule], labels: &mut BTreeMap<String, String>) -> bool {
+    for rule in rules {
+        let value = rule
+            .source_labels
+            .iter()
+            .map(|l| labels.get(l).map(String::as_str).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(&rule.separator);
+        match rule.action {
+            RelabelAction::Replace => {
+                if let Some(captures) = rule.regex.captures(&value) {
+                    let mut res = String::new();
+                    captures.expand(&rule.replacement, &mut res);
+                    let target = rule.target_label.clone().unwrap();
+                    if res.is_empty() {
+                        labels.remove(&target);
+                    } else {
+                        labels.insert(target, res);
+                    }
+                }
+            }
+            RelabelAction::Keep => {
+                if !rule.regex.is_match(&value) {
+                    return false;
+                }
+            }
+            RelabelAction::Drop => {
+                if rule.regex.is_match(&value) {
+                    return false;
+                }
+            }
+            RelabelAction::LabelDrop => labels.retain(|name, _| !rule.regex.is_match(name)),
+            RelabelAction::LabelKeep => labels.retain(|name, _| rule.regex.is_match(name)),
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use pretty_assertions::assert_eq;
+
+    use super::{RelabelConfig, RelabelRule, apply};
+
+    fn rules(toml: &str) -> Vec<RelabelRule> {
+        #[derive(serde::Deserialize)]
+        struct Rules {
+            relabel: Vec<RelabelConfig>,
+        }
+        let rules: Rules = toml::from_str(toml).unwrap();
+        rules.relabel.into_iter().map(|r| r.try_into().unwrap()).collect()
+    }
+
+    fn labels() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            (String::from("__name__"), String::from("rapl_consumed_energy")),
+            (String::from("domain"), String::from("package")),
+            (String::from("resource_id"), String::from("0")),
+        ])
+    }
+
+    #[test]
+    fn replace() {
+        let rules = rules(
+            r#"
+            [[relabel]]
+            source_labels = ["domain", "resource_id"]
+            regex = "(.+);(.+)"
+            target_label = "zone"
+            replacement = "$1-$2"
+
+            [[relabel]]
+            source_labels = ["__name__"]
+            regex = "consumed_(.*)"
+            target_label = "__name__"
+
+            [[relabel]]
+            source_labels = ["__name__"]
+            regex = "rapl_(.*)"
+            target_label = "energy_kind"
+            "#,
+        );
+        let mut labels = labels();
+        assert!(apply(&rules, &mut labels));
+        assert_eq!(labels["zone"], "package-0");
+        assert_eq!(labels["__name__"], "rapl_consumed_energy", "the regex is anchored");
+        assert_eq!(labels["energy_kind"], "consumed_energy");
+    }
+
+    #[test]
+    fn keep_and_drop() {
+        let keep = rules(
+            r#"
+            [[relabel]]
+            source_labels = ["domain"]
+            regex = "package|dram"
+            action = "keep"
+            "#,
+        );
+        assert!(apply(&keep, &mut labels()));
+
+        let drop = rules(
+            r#"
+            [[relabel]]
+            source_labels = ["domain"]
+            regex = "pack.*"
+            action = "drop"
+            "#,
+        );
+        assert!(!apply(&drop, &mut labels()));
+    }
+
+    #[test]
+    fn label_drop() {
+        let rules = rules(
+            r#"
+            [[relabel]]
+            regex = "resource_.*"
+            action = "labeldrop"
+            "#,
+        );
+        let mut labels = labels();
+        assert!(apply(&rules, &mut labels));
+        assert_eq!(labels.keys().collect::<Vec<_>>(), vec!["__name__", "domain"]);
+    }
+
+    #[test]
+    fn invalid() {
+        let config = RelabelConfig {
+            source_labels: vec![],
+            separator: String::from(";"),
+            regex: String::from("(.*)"),
+            target_label: None,
+            replacement: String::from("$1"),
+            action: super::RelabelAction::Replace,
+        };
+        assert!(RelabelRule::try_from(config).is_err());
+    }
+}
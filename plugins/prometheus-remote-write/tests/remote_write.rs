@@ -0,0 +1,214 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use alumet::{
+    agent::{
+        self,
+        plugin::{PluginInfo, PluginSet},
+    },
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    pipeline::naming::OutputName,
+    plugin::{AlumetPluginStart, ConfigTable, PluginMetadata, rust::AlumetPlugin},
+    resources::{Resource, ResourceConsumer},
+    test::{RuntimeExpectations, runtime::OutputCheckInputContext},
+    units::Unit,
+};
+use mockito::{Matcher, Server};
+use plugin_prometheus_remote_write::{
+    Config, RelabelAction, RelabelConfig, RemoteWritePlugin,
+    proto::{Label, WriteRequest},
+};
+use prost::Message;
+
+/// Registers the metric used by the tests.
+struct MetricPlugin;
+
+impl AlumetPlugin for MetricPlugin {
+    fn name() -> &'static str {
+        "metrics"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(None)
+    }
+
+    fn init(_config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        Ok(Box::new(MetricPlugin))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        alumet.create_metric::<u64>("consumed.energy", Unit::Joule, "energy consumed")?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+fn decode(body: &[u8]) -> WriteRequest {
+    let body = snap::raw::Decoder::new()
+        .decompress_vec(body)
+        .expect("body should be compressed");
+    WriteRequest::decode(&body[..]).expect("body should be a WriteRequest")
+}
+
+fn label(name: &str, value: &str) -> Label {
+    Label {
+        name: name.to_owned(),
+        value: value.to_owned(),
+    }
+}
+
+fn run_agent(config: Config, check_output: impl Fn() + Send + 'static) {
+    let mut plugins = PluginSet::new();
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<RemoteWritePlugin>(),
+        enabled: true,
+        config: Some(toml::Value::try_from(&config).unwrap().as_table().unwrap().clone()),
+    });
+    plugins.add_plugin(PluginInfo {
+        metadata: PluginMetadata::from_static::<MetricPlugin>(),
+        enabled: true,
+        config: None,
+    });
+
+    let make_input = |ctx: &mut OutputCheckInputContext| -> MeasurementBuffer {
+        let metric = ctx.metrics().by_name("consumed.energy").expect("metric should exist").0;
+        let mut m = MeasurementBuffer::new();
+        for (pkg, value) in [(0, 10), (1, 20)] {
+            m.push(
+                MeasurementPoint::new_untyped(
+                    Timestamp::now(),
+                    metric,
+                    Resource::CpuPackage { id: pkg },
+                    ResourceConsumer::LocalMachine,
+                    WrappedMeasurementValue::U64(value),
+                )
+                .with_attr("domain", "package"),
+            );
+        }
+        m
+    };
+    let expectations = RuntimeExpectations::new().test_output(
+        OutputName::from_str("prometheus-remote-write", "out"),
+        make_input,
+        check_output,
+    );
+
+    let agent = agent::Builder::new(plugins)
+        .with_expectations(expectations)
+        .build_and_start()
+        .unwrap();
+    agent.wait_for_shutdown(Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn write_with_relabeling() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/api/v1/write")
+        .match_header("Content-Encoding", "snappy")
+        .match_header("Content-Type", "application/x-protobuf")
+        .match_header("X-Scope-OrgID", "alumet")
+        .match_request(|req| {
+            let request = decode(req.body().unwrap());
+            // the series of package 1 has been dropped
+            let [series] = &request.timeseries[..] else {
+                return false;
+            };
+            series.samples.len() == 1
+                && series.samples[0].value == 10.0
+                && series.labels
+                    == vec![
+                        label("__name__", "consumed_energy"),
+                        label("instance", "node-1"),
+                        label("resource_consumer_kind", "local_machine"),
+                        label("resource_id", "0"),
+                        label("resource_kind", "cpu_package"),
+                    ]
+        })
+        .with_status(204)
+        .create();
+
+    let config = Config {
+        url: format!("{}/api/v1/write", server.url()),
+        headers: [(String::from("X-Scope-OrgID"), String::from("alumet"))].into(),
+        external_labels: [(String::from("instance"), String::from("node-1"))].into(),
+        relabel: vec![
+            RelabelConfig {
+                source_labels: vec![String::from("resource_id")],
+                separator: String::from(";"),
+                regex: String::from("1"),
+                target_label: None,
+                replacement: String::from("$1"),
+                action: RelabelAction::Drop,
+            },
+            RelabelConfig {
+                source_labels: vec![],
+                separator: String::from(";"),
+                regex: String::from("domain"),
+                target_label: None,
+                replacement: String::from("$1"),
+                action: RelabelAction::LabelDrop,
+            },
+        ],
+        ..Default::default()
+    };
+    run_agent(config, move || mock.assert());
+}
+
+#[test]
+fn retry_when_unavailable() {
+    let mut server = Server::new();
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let attempts_clone = attempts.clone();
+    let unavailable = server
+        .mock("POST", "/")
+        .match_request(move |_| attempts_clone.fetch_add(1, Ordering::Relaxed) == 0)
+        .with_status(503)
+        .create();
+    let ok = server
+        .mock("POST", "/")
+        .match_body(Matcher::Any)
+        .with_status(200)
+        .create();
+
+    let config = Config {
+        url: server.url(),
+        retry_delay: Duration::from_millis(10),
+        ..Default::default()
+    };
+    run_agent(config, move || {
+        unavailable.assert();
+        ok.assert();
+    });
+}
+
+#[test]
+fn drop_when_rejected() {
+    let mut server = Server::new();
+    // the request is not sent again
+    let rejected = server
+        .mock("POST", "/")
+        .match_body(Matcher::Any)
+        .with_status(400)
+        .expect(1)
+        .create();
+
+    let config = Config {
+        url: server.url(),
+        retry_delay: Duration::from_millis(10),
+        ..Default::default()
+    };
+    run_agent(config, move || rejected.assert());
+}
@@ -0,0 +1,742 @@
+//! Energy counters of AMD Zen processors.
+//!
+//! They are read from the hwmon drivers `amd_energy`, `zenergy` or `zenpower`, or directly from the MSRs.
+//! They measure the packages, like RAPL, but also the cores, which are summed per CCD (core complex die).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::elements::error::PollError,
+    plugin::util::{CounterDiff, CounterDiffUpdate},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cpus::{self, CPU_SYSFS_PATH, CpuTopology},
+    domains::RaplDomainType,
+    msr::{self, MSR_PATH},
+    total::DomainTotals,
+};
+
+pub const HWMON_PATH: &str = "/sys/class/hwmon";
+
+/// Unit of the energy and power counters of hwmon: microjoules and microwatts.
+const HWMON_UNIT: f64 = 0.000_001;
+
+const MSR_RAPL_POWER_UNIT: u64 = 0xC001_0299;
+const MSR_CORE_ENERGY_STATUS: u64 = 0xC001_029A;
+const MSR_PKG_ENERGY_STATUS: u64 = 0xC001_029B;
+
+/// An interface that gives access to the energy counters of AMD processors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AmdInterface {
+    /// The hwmon drivers `amd_energy`, `zenergy` and `zenpower`.
+    Hwmon,
+    /// The MSRs, through `/dev/cpu/*/msr`.
+    Msr,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmdConfig {
+    /// Interfaces to use on AMD processors, in order of preference. If empty, the AMD counters are not used.
+    pub interfaces: Vec<AmdInterface>,
+    /// Measure the energy of the cores of each CCD, in addition to RAPL.
+    pub per_ccd: bool,
+}
+
+impl Default for AmdConfig {
+    fn default() -> Self {
+        Self {
+            interfaces: vec![AmdInterface::Hwmon, AmdInterface::Msr],
+            per_ccd: true,
+        }
+    }
+}
+
+/// Location of the interfaces, which can be changed for the tests.
+#[derive(Debug, Clone)]
+pub struct AmdPaths {
+    pub hwmon: PathBuf,
+    pub cpus: PathBuf,
+    pub msr: PathBuf,
+}
+
+impl Default for AmdPaths {
+    fn default() -> Self {
+        Self {
+            hwmon: PathBuf::from(HWMON_PATH),
+            cpus: PathBuf::from(CPU_SYSFS_PATH),
+            msr: PathBuf::from(MSR_PATH),
+        }
+    }
+}
+
+/// What to measure.
+#[derive(Debug, Clone, Copy)]
+pub struct Selection {
+    /// The packages, like the `package` domain of RAPL.
+    pub packages: bool,
+    /// The cores, summed per CCD.
+    pub cores: bool,
+}
+
+/// Measured part of the processor, which receives the energy of one or several counters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Target {
+    domain: RaplDomainType,
+    socket: u32,
+    ccd: Option<u32>,
+}
+
+impl Target {
+    fn resource(&self) -> Resource {
+        match self.ccd {
+            Some(ccd) => Resource::custom("cpu_ccd", ccd.to_string()),
+            None => self.domain.to_resource(self.socket),
+        }
+    }
+}
+
+enum Reader {
+    /// A file that contains an energy counter in microjoules (`amd_energy` and `zenergy`).
+    Energy(File),
+    /// Files that contain powers in microwatts (`zenpower`), which are summed and integrated over time.
+    Power(Vec<File>),
+    /// An energy MSR, with the energy unit of its package in joules.
+    Msr { file: File, register: u64, unit: f64 },
+}
+
+struct Counter {
+    reader: Reader,
+    target: Target,
+    diff: CounterDiff,
+    /// Power and timestamp of the previous measurement, for the power counters.
+    last_power: Option<(f64, Timestamp)>,
+}
+
+impl Counter {
+    fn new(reader: Reader, target: Target) -> Self {
+        let max_value = match reader {
+            // The MSRs have 32 bits, the drivers accumulate them on 64 bits.
+            Reader::Msr { .. } => u32::MAX as u64,
+            _ => u64::MAX,
+        };
+        Self {
+            reader,
+            target,
+            diff: CounterDiff::with_max_value(max_value),
+            last_power: None,
+        }
+    }
+
+    /// Returns the energy consumed since the previous call, in joules, or `None` on the first call.
+    fn read_joules(&mut self, timestamp: Timestamp, buf: &mut Vec<u8>) -> anyhow::Result<Option<f64>> {
+        let (value, unit) = match &mut self.reader {
+            Reader::Energy(file) => (read_integer(file, buf)?, HWMON_UNIT),
+            Reader::Msr { file, register, unit } => (msr::read_msr(file, *register)? & u32::MAX as u64, *unit),
+            Reader::Power(files) => {
+                let mut power = 0.0;
+                for file in files {
+                    power += read_integer(file, buf)? as f64 * HWMON_UNIT;
+                }
+                // Trapezoidal integration of the power between the two measurements.
+                let energy = match self.last_power {
+                    Some((last, t)) => timestamp
+                        .duration_since(t)
+                        .ok()
+                        .map(|elapsed| (power + last) / 2.0 * elapsed.as_secs_f64()),
+                    None => None,
+                };
+                self.last_power = Some((power, timestamp));
+                return Ok(energy);
+            }
+        };
+        Ok(match self.diff.update(value) {
+            CounterDiffUpdate::FirstTime => None,
+            CounterDiffUpdate::Difference(diff) => Some(diff as f64 * unit),
+            CounterDiffUpdate::CorrectedDifference(diff) => {
+                log::debug!("Overflow on AMD energy counter for {:?}", self.target);
+                Some(diff as f64 * unit)
+            }
+        })
+    }
+}
+
+/// Reads a file that contains an integer.
+fn read_integer(file: &mut File, buf: &mut Vec<u8>) -> anyhow::Result<u64> {
+    buf.clear();
+    file.rewind().with_context(|| format!("failed to rewind {file:?}"))?;
+    file.read_to_end(buf)
+        .with_context(|| format!("failed to read {file:?}"))?;
+    let content = std::str::from_utf8(buf)?;
+    content
+        .trim_end()
+        .parse()
+        .with_context(|| format!("failed to parse {file:?}: '{content}'"))
+}
+
+/// AMD probe collects the energy of the packages and CCDs of AMD Zen processors.
+pub struct AmdProbe {
+    metric: TypedMetricId<f64>,
+    counters: Vec<Counter>,
+    interface: AmdInterface,
+}
+
+impl AmdProbe {
+    /// Creates a probe with the first interface that works.
+    pub fn new(
+        metric: TypedMetricId<f64>,
+        interfaces: &[AmdInterface],
+        paths: &AmdPaths,
+        selection: Selection,
+    ) -> anyhow::Result<AmdProbe> {
+        let (interface, counters) = discover(interfaces, paths, selection)?;
+        Ok(AmdProbe {
+            metric,
+            counters,
+            interface,
+        })
+    }
+
+    /// The interface that the probe uses.
+    pub fn interface(&self) -> AmdInterface {
+        self.interface
+    }
+
+    /// The measured domains, without duplicates.
+    pub fn domains(&self) -> Vec<RaplDomainType> {
+        domains(&self.counters)
+    }
+}
+
+/// Returns the counters of the first interface that works.
+fn discover(
+    interfaces: &[AmdInterface],
+    paths: &AmdPaths,
+    selection: Selection,
+) -> anyhow::Result<(AmdInterface, Vec<Counter>)> {
+    let cpus = cpus::read_topology(&paths.cpus)?;
+    let mut errors = Vec::new();
+    for interface in interfaces {
+        let counters = match interface {
+            AmdInterface::Hwmon => hwmon_counters(&paths.hwmon, &cpus, selection),
+            AmdInterface::Msr => msr_counters(&paths.msr, &cpus, selection),
+        };
+        match counters {
+            Ok(counters) if !counters.is_empty() => return Ok((*interface, counters)),
+            Ok(_) => errors.push(format!("{interface:?}: no energy counter found")),
+            Err(e) => errors.push(format!("{interface:?}: {e:#}")),
+        }
+    }
+    Err(anyhow!("no AMD energy counter is available.\n{}", errors.join("\n")))
+}
+
+fn domains(counters: &[Counter]) -> Vec<RaplDomainType> {
+    let domains: BTreeSet<_> = counters.iter().map(|c| c.target.domain).collect();
+    domains.into_iter().collect()
+}
+
+impl alumet::pipeline::Source for AmdProbe {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        let mut buf = Vec::with_capacity(24);
+        let mut per_target: BTreeMap<Target, f64> = BTreeMap::new();
+        for counter in &mut self.counters {
+            if let Some(joules) = counter.read_joules(timestamp, &mut buf)? {
+                *per_target.entry(counter.target).or_default() += joules;
+            }
+        }
+
+        let mut totals = DomainTotals::new();
+        for (target, joules) in per_target {
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metric,
+                    target.resource(),
+                    ResourceConsumer::LocalMachine,
+                    joules,
+                )
+                .with_attr("domain", target.domain.as_str()),
+            );
+            totals.push(target.domain, joules);
+        }
+        for (domain, total) in totals.iter() {
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    total,
+                )
+                .with_attr("domain", domain.as_str_total()),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the target of the energy of a core.
+fn core_target(cpu: &CpuTopology) -> Target {
+    Target {
+        domain: RaplDomainType::PP0,
+        socket: cpu.socket,
+        ccd: cpu.l3_cache,
+    }
+}
+
+fn package_target(socket: u32) -> Target {
+    Target {
+        domain: RaplDomainType::Package,
+        socket,
+        ccd: None,
+    }
+}
+
+/// Finds the energy counters of the AMD hwmon drivers.
+fn hwmon_counters(hwmon_dir: &Path, cpus: &[CpuTopology], selection: Selection) -> anyhow::Result<Vec<Counter>> {
+    let mut devices = Vec::new();
+    let entries = fs::read_dir(hwmon_dir).with_context(|| format!("failed to list {hwmon_dir:?}"))?;
+    for entry in entries {
+        let path = entry?.path();
+        let Ok(name) = fs::read_to_string(path.join("name")) else {
+            continue;
+        };
+        let name = name.trim().to_owned();
+        if matches!(name.as_str(), "amd_energy" | "zenergy" | "zenpower") {
+            // zenpower has one device per socket: sort them by the path of their PCI device.
+            let device = fs::canonicalize(path.join("device")).unwrap_or_else(|_| path.clone());
+            devices.push((device, name, path));
+        }
+    }
+    devices.sort();
+
+    let mut counters = Vec::new();
+    let mut zenpower_socket = 0;
+    for (_, name, path) in devices {
+        if name == "zenpower" {
+            counters.extend(zenpower_counters(&path, zenpower_socket, selection)?);
+            zenpower_socket += 1;
+            continue;
+        }
+        for (label, input) in labelled_files(&path, "energy")? {
+            let target = if let Some(socket) = label.strip_prefix("Esocket") {
+                if !selection.packages {
+                    continue;
+                }
+                package_target(socket.parse().with_context(|| format!("invalid label {label}"))?)
+            } else if let Some(core) = label.strip_prefix("Ecore") {
+                if !selection.cores {
+                    continue;
+                }
+                // The number of a core counter is the id of its first CPU.
+                let id: u32 = core.parse().with_context(|| format!("invalid label {label}"))?;
+                match cpus.iter().find(|c| c.id == id) {
+                    Some(cpu) => core_target(cpu),
+                    None => continue,
+                }
+            } else {
+                continue;
+            };
+            let file = File::open(&input).with_context(|| format!("failed to open {input:?}"))?;
+            counters.push(Counter::new(Reader::Energy(file), target));
+        }
+    }
+    Ok(counters)
+}
+
+/// Returns the counters of a zenpower device, which measures the power of the cores and of the SoC of a socket.
+fn zenpower_counters(path: &Path, socket: u32, selection: Selection) -> anyhow::Result<Vec<Counter>> {
+    let mut core = None;
+    let mut soc = None;
+    for (label, input) in labelled_files(path, "power")? {
+        match label.as_str() {
+            "SVI2_P_Core" => core = Some(input),
+            "SVI2_P_SoC" => soc = Some(input),
+            _ => (),
+        }
+    }
+    let open = |path: &PathBuf| File::open(path).with_context(|| format!("failed to open {path:?}"));
+
+    let mut counters = Vec::new();
+    if let (true, Some(core), Some(soc)) = (selection.packages, &core, &soc) {
+        let reader = Reader::Power(vec![open(core)?, open(soc)?]);
+        counters.push(Counter::new(reader, package_target(socket)));
+    }
+    // zenpower does not measure the CCDs: the cores are measured as a whole.
+    if let (true, Some(core)) = (selection.cores, &core) {
+        let target = Target {
+            domain: RaplDomainType::PP0,
+            socket,
+            ccd: None,
+        };
+        counters.push(Counter::new(Reader::Power(vec![open(core)?]), target));
+    }
+    Ok(counters)
+}
+
+/// Returns the label and the path of the `{prefix}N_input` files of a hwmon device, sorted by path.
+fn labelled_files(device: &Path, prefix: &str) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(device).with_context(|| format!("failed to list {device:?}"))? {
+        let path = entry?.path();
+        let Some(channel) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix(prefix))
+            .and_then(|n| n.strip_suffix("_label"))
+        else {
+            continue;
+        };
+        let label = fs::read_to_string(&path).with_context(|| format!("failed to read {path:?}"))?;
+        files.push((label.trim().to_owned(), device.join(format!("{prefix}{channel}_input"))));
+    }
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(files)
+}
+
+/// Opens the energy MSRs of the packages and of the cores.
+fn msr_counters(msr_dir: &Path, cpus: &[CpuTopology], selection: Selection) -> anyhow::Result<Vec<Counter>> {
+    let open = |cpu: u32| {
+        let path = msr_dir.join(cpu.to_string()).join("msr");
+        File::open(&path).with_context(|| {
+            format!("failed to open {path:?}. Is the msr module loaded, and does the agent run as root?")
+        })
+    };
+
+    // The first CPU of each socket and of each core.
+    let mut sockets: BTreeMap<u32, &CpuTopology> = BTreeMap::new();
+    let mut cores: BTreeMap<(u32, u32), &CpuTopology> = BTreeMap::new();
+    for cpu in cpus {
+        sockets.entry(cpu.socket).or_insert(cpu);
+        cores.entry((cpu.socket, cpu.core)).or_insert(cpu);
+    }
+
+    let mut units = BTreeMap::new();
+    for (socket, cpu) in &sockets {
+        let power_unit = msr::read_msr(&open(cpu.id)?, MSR_RAPL_POWER_UNIT)
+            .with_context(|| format!("failed to read the energy unit of socket {socket}"))?;
+        units.insert(*socket, msr::energy_unit(power_unit));
+    }
+
+    let mut counters = Vec::new();
+    if selection.packages {
+        for (socket, cpu) in &sockets {
+            let reader = Reader::Msr {
+                file: open(cpu.id)?,
+                register: MSR_PKG_ENERGY_STATUS,
+                unit: units[socket],
+            };
+            counters.push(Counter::new(reader, package_target(*socket)));
+        }
+    }
+    if selection.cores {
+        for cpu in cores.values() {
+            let reader = Reader::Msr {
+                file: open(cpu.id)?,
+                register: MSR_CORE_ENERGY_STATUS,
+                unit: units[&cpu.socket],
+            };
+            counters.push(Counter::new(reader, core_target(cpu)));
+        }
+    }
+    Ok(counters)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        os::unix::fs::FileExt,
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use alumet::measurement::Timestamp;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::tests_mock::{Entry, EntryType::*, create_mock_layout};
+
+    /// Two sockets of two cores of two threads each, one CCD per socket.
+    fn create_topology(base: &Path) -> anyhow::Result<()> {
+        let mut entries = Vec::new();
+        let mut files = Vec::new();
+        for cpu in 0..8u32 {
+            let socket = cpu / 4;
+            let core = cpu % 2;
+            files.push((format!("cpu{cpu}/topology/physical_package_id"), socket.to_string()));
+            files.push((format!("cpu{cpu}/topology/core_id"), core.to_string()));
+            files.push((format!("cpu{cpu}/cache/index3/id"), (socket * 8).to_string()));
+        }
+        for (path, content) in &files {
+            entries.push(Entry {
+                path,
+                entry_type: File(content),
+            });
+        }
+        create_mock_layout(&base.join("cpus"), &entries)?;
+        Ok(())
+    }
+
+    fn paths(base: &Path) -> AmdPaths {
+        AmdPaths {
+            hwmon: base.join("hwmon"),
+            cpus: base.join("cpus"),
+            msr: base.join("msr"),
+        }
+    }
+
+    fn at(secs: u64) -> Timestamp {
+        Timestamp::from(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    fn poll(counters: &mut [Counter], timestamp: Timestamp) -> BTreeMap<Target, f64> {
+        let mut buf = Vec::new();
+        let mut result = BTreeMap::new();
+        for counter in counters {
+            if let Some(joules) = counter.read_joules(timestamp, &mut buf).unwrap() {
+                *result.entry(counter.target).or_default() += joules;
+            }
+        }
+        result
+    }
+
+    const ALL: Selection = Selection {
+        packages: true,
+        cores: true,
+    };
+
+    #[test]
+    fn test_topology() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        create_topology(tmp.path())?;
+        // an offline cpu and another file
+        fs::create_dir_all(tmp.path().join("cpus/cpu8"))?;
+        fs::write(tmp.path().join("cpus/online"), "0-7")?;
+
+        let cpus = cpus::read_topology(&tmp.path().join("cpus"))?;
+        assert_eq!(cpus.len(), 8);
+        assert_eq!(
+            cpus[5],
+            CpuTopology {
+                id: 5,
+                socket: 1,
+                core: 1,
+                l3_cache: Some(8)
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_amd_energy() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        create_topology(tmp.path())?;
+        let dir = "hwmon/hwmon3";
+        let files = [
+            ("name", "amd_energy\n"),
+            ("energy1_label", "Ecore000\n"),
+            ("energy1_input", "1000000\n"),
+            ("energy2_label", "Ecore001\n"),
+            ("energy2_input", "2000000\n"),
+            ("energy3_label", "Ecore004\n"),
+            ("energy3_input", "3000000\n"),
+            ("energy4_label", "Esocket0\n"),
+            ("energy4_input", "10000000\n"),
+            ("energy5_label", "Esocket1\n"),
+            ("energy5_input", "20000000\n"),
+        ];
+        let paths_and_contents: Vec<_> = files.iter().map(|(f, c)| (format!("{dir}/{f}"), *c)).collect();
+        let entries: Vec<_> = paths_and_contents
+            .iter()
+            .map(|(path, content)| Entry {
+                path,
+                entry_type: File(content),
+            })
+            .collect();
+        create_mock_layout(tmp.path(), &entries)?;
+        // another hwmon device
+        create_mock_layout(
+            tmp.path(),
+            &[Entry {
+                path: "hwmon/hwmon0/name",
+                entry_type: File("k10temp"),
+            }],
+        )?;
+
+        let (interface, mut counters) = discover(&[AmdInterface::Hwmon], &paths(tmp.path()), ALL)?;
+        assert_eq!(interface, AmdInterface::Hwmon);
+        assert_eq!(domains(&counters), vec![RaplDomainType::Package, RaplDomainType::PP0]);
+        assert!(poll(&mut counters, at(0)).is_empty());
+
+        let hwmon = tmp.path().join(dir);
+        fs::write(hwmon.join("energy1_input"), "1500000")?;
+        fs::write(hwmon.join("energy2_input"), "2500000")?;
+        fs::write(hwmon.join("energy3_input"), "3250000")?;
+        fs::write(hwmon.join("energy4_input"), "12000000")?;
+        let energy = poll(&mut counters, at(1));
+        let expected = BTreeMap::from([
+            (package_target(0), 2.0),
+            (package_target(1), 0.0),
+            (
+                Target {
+                    domain: RaplDomainType::PP0,
+                    socket: 0,
+                    ccd: Some(0),
+                },
+                1.0,
+            ),
+            (
+                Target {
+                    domain: RaplDomainType::PP0,
+                    socket: 1,
+                    ccd: Some(8),
+                },
+                0.25,
+            ),
+        ]);
+        assert_eq!(energy, expected);
+
+        // without the cores
+        let selection = Selection {
+            packages: true,
+            cores: false,
+        };
+        let (_, counters) = discover(&[AmdInterface::Hwmon], &paths(tmp.path()), selection)?;
+        assert_eq!(domains(&counters), vec![RaplDomainType::Package]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zenpower() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        create_topology(tmp.path())?;
+        create_mock_layout(
+            tmp.path(),
+            &[
+                Entry {
+                    path: "hwmon/hwmon2/name",
+                    entry_type: File("zenpower"),
+                },
+                Entry {
+                    path: "hwmon/hwmon2/power1_label",
+                    entry_type: File("SVI2_P_Core"),
+                },
+                Entry {
+                    path: "hwmon/hwmon2/power1_input",
+                    entry_type: File("40000000"),
+                },
+                Entry {
+                    path: "hwmon/hwmon2/power2_label",
+                    entry_type: File("SVI2_P_SoC"),
+                },
+                Entry {
+                    path: "hwmon/hwmon2/power2_input",
+                    entry_type: File("10000000"),
+                },
+            ],
+        )?;
+
+        let (_, mut counters) = discover(&[AmdInterface::Hwmon], &paths(tmp.path()), ALL)?;
+        assert!(poll(&mut counters, at(0)).is_empty());
+        fs::write(tmp.path().join("hwmon/hwmon2/power1_input"), "60000000")?;
+        let energy = poll(&mut counters, at(2));
+        let cores = Target {
+            domain: RaplDomainType::PP0,
+            socket: 0,
+            ccd: None,
+        };
+        assert_eq!(energy, BTreeMap::from([(package_target(0), 120.0), (cores, 100.0)]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_msr() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        create_topology(tmp.path())?;
+        // In the real files, the offset is the number of the register:
+        // the registers of a mock file overlap, only the unit can be written.
+        for cpu in 0..8 {
+            let dir = tmp.path().join("msr").join(cpu.to_string());
+            fs::create_dir_all(&dir)?;
+            // ESU = 16: the unit is 2^-16 J
+            File::create(dir.join("msr"))?.write_all_at(&0x000A_1003u64.to_le_bytes(), MSR_RAPL_POWER_UNIT)?;
+        }
+
+        // hwmon is not available: fall back to the MSRs
+        let (interface, counters) = discover(&[AmdInterface::Hwmon, AmdInterface::Msr], &paths(tmp.path()), ALL)?;
+        assert_eq!(interface, AmdInterface::Msr);
+        let registers: Vec<_> = counters
+            .iter()
+            .map(|c| match c.reader {
+                Reader::Msr { register, unit, .. } => (register, unit, c.target),
+                _ => panic!("unexpected reader"),
+            })
+            .collect();
+        let unit = 1.0 / 65536.0;
+        let core = |socket, ccd| Target {
+            domain: RaplDomainType::PP0,
+            socket,
+            ccd: Some(ccd),
+        };
+        // 2 packages and 4 cores
+        assert_eq!(
+            registers,
+            vec![
+                (MSR_PKG_ENERGY_STATUS, unit, package_target(0)),
+                (MSR_PKG_ENERGY_STATUS, unit, package_target(1)),
+                (MSR_CORE_ENERGY_STATUS, unit, core(0, 0)),
+                (MSR_CORE_ENERGY_STATUS, unit, core(0, 0)),
+                (MSR_CORE_ENERGY_STATUS, unit, core(1, 8)),
+                (MSR_CORE_ENERGY_STATUS, unit, core(1, 8)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_msr_counter() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path().join("msr");
+        let file = File::create(&path)?;
+        file.write_all_at(&(u32::MAX as u64 - 65535).to_le_bytes(), 0)?;
+        let reader = Reader::Msr {
+            file: File::open(&path)?,
+            register: 0,
+            unit: 1.0 / 65536.0,
+        };
+        let mut counters = vec![Counter::new(reader, package_target(0))];
+        assert!(poll(&mut counters, at(0)).is_empty());
+
+        // the counter has 32 bits and wraps around, the upper bits are reserved
+        file.write_all_at(&(0xFFFF_0000_0001_0000u64).to_le_bytes(), 0)?;
+        assert_eq!(poll(&mut counters, at(1)), BTreeMap::from([(package_target(0), 2.0)]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_interface() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        create_topology(tmp.path())?;
+        fs::create_dir_all(tmp.path().join("hwmon"))?;
+        let err = discover(&[AmdInterface::Hwmon, AmdInterface::Msr], &paths(tmp.path()), ALL)
+            .err()
+            .expect("no interface should be available");
+        let msg = format!("{err:#}");
+        assert!(msg.contains("Hwmon: no energy counter found"), "{msg}");
+        assert!(msg.contains("Msr: failed to open"), "{msg}");
+        Ok(())
+    }
+}
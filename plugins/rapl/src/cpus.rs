@@ -1,10 +1,13 @@
+use alumet::plugin::util::parse_cpu_list;
 use anyhow::{Context, anyhow};
 use std::{
     fs,
-    num::ParseIntError,
+    path::Path,
     process::{Command, Stdio},
 };
 
+pub const CPU_SYSFS_PATH: &str = "/sys/devices/system/cpu";
+
 /// Cpu id and socket (package) id.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CpuId {
@@ -12,6 +15,16 @@ pub struct CpuId {
     pub socket: u32,
 }
 
+/// Topology of a CPU (hardware thread).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTopology {
+    pub id: u32,
+    pub socket: u32,
+    pub core: u32,
+    /// Id of the L3 cache, which is shared by the cores of a CCD on AMD processors.
+    pub l3_cache: Option<u32>,
+}
+
 /// Cpu vendor that supports RAPL energy counters.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum CpuVendor {
@@ -41,40 +54,44 @@ fn parse_cpu_and_socket_list(cpulist: &str) -> anyhow::Result<Vec<CpuId>> {
     Ok(cpus_and_sockets)
 }
 
-fn parse_cpu_list(cpulist: &str) -> anyhow::Result<Vec<u32>> {
-    // handles "n" or "start-end"
-    fn parse_cpulist_item(item: &str) -> anyhow::Result<Vec<u32>> {
-        let bounds: Vec<u32> = item
-            .split('-')
-            .map(str::parse)
-            .collect::<Result<Vec<u32>, ParseIntError>>()?;
-
-        match *bounds.as_slice() {
-            [start, end] => Ok((start..=end).collect()),
-            [n] => Ok(vec![n]),
-            _ => Err(anyhow::anyhow!("invalid cpu_list: {}", item)),
-        }
+/// Reads the topology of the online CPUs, sorted by id.
+pub fn read_topology(cpus_dir: &Path) -> anyhow::Result<Vec<CpuTopology>> {
+    fn read_u32(path: &Path) -> anyhow::Result<u32> {
+        let content = fs::read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+        content
+            .trim()
+            .parse()
+            .with_context(|| format!("failed to parse {path:?}: '{content}'"))
     }
 
-    // this can be "0,64" or "0-1" or maybe "0-1,64-66"
-    let cpus: Vec<u32> = cpulist
-        .trim_end()
-        .split(',')
-        .map(parse_cpulist_item)
-        .collect::<anyhow::Result<Vec<Vec<u32>>>>()?
-        .into_iter() // not the same as iter() !
-        .flatten()
-        .collect();
-
+    let mut cpus = Vec::new();
+    let entries = fs::read_dir(cpus_dir).with_context(|| format!("failed to list {cpus_dir:?}"))?;
+    for entry in entries {
+        let path = entry?.path();
+        let Some(id) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("cpu"))
+            .and_then(|n| n.parse().ok())
+        else {
+            continue;
+        };
+        // The offline CPUs have no topology.
+        let topology = path.join("topology");
+        if !topology.exists() {
+            continue;
+        }
+        cpus.push(CpuTopology {
+            id,
+            socket: read_u32(&topology.join("physical_package_id"))?,
+            core: read_u32(&topology.join("core_id"))?,
+            l3_cache: read_u32(&path.join("cache/index3/id")).ok(),
+        });
+    }
+    cpus.sort_by_key(|c| c.id);
     Ok(cpus)
 }
 
-pub fn online_cpus() -> anyhow::Result<Vec<u32>> {
-    let path = "/sys/devices/system/cpu/online";
-    let list = std::fs::read_to_string(path).with_context(|| format!("failed to parse {path}"))?;
-    parse_cpu_list(&list)
-}
-
 fn run_lscpu() -> anyhow::Result<String> {
     // run: LC_ALL=C lscpu
     let child = Command::new("lscpu")
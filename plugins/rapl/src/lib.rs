@@ -1,28 +1,32 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use alumet::{
+    metrics::{TypedMetricId, def::tag},
     pipeline::elements::source::{Source, trigger},
     plugin::{
         ConfigTable,
+        capability::Capability,
         rust::{AlumetPlugin, deserialize_config, serialize_config},
     },
     units::Unit,
 };
-use anyhow::{Context, anyhow};
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    consistency::{SafeSubset, get_available_domains},
+    amd::{AmdConfig, AmdPaths, AmdProbe, Selection},
+    consistency::get_available_domains,
+    cpus::CpuVendor,
+    msr::{MsrPaths, MsrProbe},
     perf_event::{PerfEventProbe, PowerEvent},
     powercap::{PowerZone, PowercapProbe},
 };
 
-#[cfg(test)]
-use std::path::PathBuf;
-
+pub mod amd;
 mod consistency;
 mod cpus;
 mod domains;
+mod msr;
 pub mod perf_event;
 mod powercap;
 mod total;
@@ -54,28 +58,52 @@ impl RaplPlugin {
     fn get_all_power_zones(&self) -> anyhow::Result<Vec<PowerZone>> {
         Ok(powercap::all_power_zones_from_path(&self.config.powercap_test_path)?.flat)
     }
-}
 
-impl AlumetPlugin for RaplPlugin {
-    fn name() -> &'static str {
-        "rapl"
+    #[cfg(not(test))]
+    fn get_cpu_vendor(&self) -> anyhow::Result<CpuVendor> {
+        cpus::cpu_vendor()
     }
 
-    fn version() -> &'static str {
-        env!("CARGO_PKG_VERSION")
+    #[cfg(test)]
+    fn get_cpu_vendor(&self) -> anyhow::Result<CpuVendor> {
+        Ok(CpuVendor::Amd)
     }
 
-    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
-        let config = serialize_config(Config::default())?;
-        Ok(Some(config))
+    #[cfg(not(test))]
+    fn get_msr_paths(&self) -> MsrPaths {
+        MsrPaths::default()
     }
 
-    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
-        let config = deserialize_config(config)?;
-        Ok(Box::new(RaplPlugin { config }))
+    #[cfg(test)]
+    fn get_msr_paths(&self) -> MsrPaths {
+        let base = &self.config.msr_test_path;
+        MsrPaths {
+            msr: base.join("msr"),
+            cpus: base.join("cpus"),
+            cpuinfo: base.join("cpuinfo"),
+        }
     }
 
-    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
+    #[cfg(not(test))]
+    fn get_amd_paths(&self) -> AmdPaths {
+        AmdPaths::default()
+    }
+
+    #[cfg(test)]
+    fn get_amd_paths(&self) -> AmdPaths {
+        let base = &self.config.amd_test_path;
+        AmdPaths {
+            hwmon: base.join("hwmon"),
+            cpus: base.join("cpus"),
+            msr: base.join("msr"),
+        }
+    }
+
+    /// Creates the source that reads the RAPL counters.
+    ///
+    /// The interfaces are tried in order: perf_events, powercap and the MSRs.
+    /// An interface that cannot be used, for instance because the agent lacks the required privileges, is skipped.
+    fn create_rapl_source(&self, metric: TypedMetricId<f64>) -> anyhow::Result<Box<dyn Source>> {
         let mut use_perf = !self.config.no_perf_events;
         let mut use_powercap = true;
         let mut check_consistency = true;
@@ -103,54 +131,179 @@ impl AlumetPlugin for RaplPlugin {
         let try_perf_events = self.get_all_power_events();
         let try_power_zones = self.get_all_power_zones();
 
-        let (available_domains, subset_indicator) = get_available_domains(
+        // Why each interface cannot be used.
+        let mut errors = Vec::new();
+        let mut domains = Vec::new();
+        match get_available_domains(
             try_perf_events,
             try_power_zones,
             check_consistency,
             &mut use_perf,
             &mut use_powercap,
-        )?;
+        ) {
+            Ok((available_domains, subset_indicator)) => {
+                // We have found a set of RAPL domains that we agree on (in the best case, perf_events and powercap both work, are accessible by the agent and report the same list of domains).
+                log::info!(
+                    "Available RAPL domains{subset_indicator}: {}",
+                    consistency::mkstring(&available_domains.domains, ", ")
+                );
+
+                // Create the measurement source, prefer perf_events.
+                if use_perf {
+                    match PerfEventProbe::new(metric, &available_domains.perf_events) {
+                        Ok(probe) => return Ok(Box::new(probe)),
+                        Err(e) => errors.push(format!("perf_events: {e:#}")),
+                    }
+                }
+                if use_powercap {
+                    if use_perf {
+                        log::warn!(
+                            "I will fallback to the powercap sysfs, but perf_events is more efficient (see https://hal.science/hal-04420527)."
+                        );
+                    }
+                    match PowercapProbe::new(metric, &available_domains.power_zones) {
+                        Ok(probe) => return Ok(Box::new(probe)),
+                        Err(e) => errors.push(format!("powercap: {e:#}")),
+                    }
+                }
+                domains = available_domains.domains;
+            }
+            Err(e) => errors.push(format!("{e:#}")),
+        }
 
-        // We have found a set of RAPL domains that we agree on (in the best case, perf_events and powercap both work, are accessible by the agent and report the same list of domains).
-        log::info!(
-            "Available RAPL domains{subset_indicator}: {}",
-            consistency::mkstring(&available_domains.domains, ", ")
-        );
+        // Last resort: read the MSRs, which does not depend on the permissions of perf_events and powercap.
+        if !self.config.no_msr {
+            log::warn!("I will fall back to reading the RAPL MSRs directly.");
+            match MsrProbe::new(metric, &domains, &self.get_msr_paths()) {
+                Ok(probe) => {
+                    log::info!(
+                        "RAPL domains read from the MSRs: {}",
+                        consistency::mkstring(&probe.domains(), ", ")
+                    );
+                    return Ok(Box::new(probe));
+                }
+                Err(e) => errors.push(format!("msr: {e:#}")),
+            }
+        }
 
+        Err(anyhow!(
+            "I can use neither perf_events, powercap nor the MSRs: impossible to measure RAPL counters.\n{}",
+            errors.join("\n")
+        ))
+    }
+
+    /// Creates the probe that reads the energy counters of AMD processors, if they are needed and available.
+    ///
+    /// When RAPL works, the AMD counters only measure the CCDs.
+    fn create_amd_probe(&self, metric: TypedMetricId<f64>, rapl_works: bool) -> Option<AmdProbe> {
+        let config = &self.config.amd;
+        let selection = Selection {
+            packages: !rapl_works,
+            cores: config.per_ccd,
+        };
+        if config.interfaces.is_empty() || !(selection.packages || selection.cores) {
+            return None;
+        }
+        match self.get_cpu_vendor() {
+            Ok(CpuVendor::Amd) => (),
+            Ok(_) => return None,
+            Err(e) => {
+                log::warn!("Failed to detect the cpu vendor, the AMD energy counters will not be used. {e:#}");
+                return None;
+            }
+        }
+        match AmdProbe::new(metric, &config.interfaces, &self.get_amd_paths(), selection) {
+            Ok(probe) => {
+                log::info!(
+                    "Available AMD energy domains (from {:?}): {}",
+                    probe.interface(),
+                    consistency::mkstring(&probe.domains(), ", ")
+                );
+                Some(probe)
+            }
+            Err(e) => {
+                log::warn!("I cannot read the energy counters of the AMD processor: {e:#}");
+                None
+            }
+        }
+    }
+}
+
+impl AlumetPlugin for RaplPlugin {
+    fn name() -> &'static str {
+        "rapl"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![
+            Capability::PerfEvents,
+            Capability::Filesystem(PathBuf::from("/sys/bus/event_source/devices/power")),
+            Capability::Filesystem(PathBuf::from("/sys/devices/virtual/powercap")),
+            // energy counters of AMD processors
+            Capability::Filesystem(PathBuf::from(amd::HWMON_PATH)),
+            Capability::Filesystem(PathBuf::from(cpus::CPU_SYSFS_PATH)),
+            // energy counters of Intel and AMD processors
+            Capability::Msr,
+        ])
+    }
+
+    fn config_comments() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("poll_interval", "Interval between two RAPL measurements."),
+            (
+                "flush_interval",
+                "Interval between two flushes of the measurements to the pipeline.",
+            ),
+            (
+                "no_perf_events",
+                "Set to true to disable perf_events and always use the powercap sysfs.",
+            ),
+            (
+                "no_msr",
+                "Set to true to never read the RAPL MSRs, which are the last resort\n\
+                 when perf_events and powercap cannot be used.",
+            ),
+            (
+                "amd",
+                "Energy counters of AMD Zen processors, used when RAPL is not available and to measure the CCDs.",
+            ),
+            (
+                "amd.interfaces",
+                "Interfaces to use, in order of preference: hwmon (amd_energy, zenergy and zenpower drivers)\n\
+                 and msr. If empty, the AMD counters are not used.",
+            ),
+            (
+                "amd.per_ccd",
+                "Measure the energy of the cores of each CCD, in addition to RAPL.",
+            ),
+        ]
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        let config = serialize_config(Config::default())?;
+        Ok(Some(config))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(RaplPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut alumet::plugin::AlumetPluginStart) -> anyhow::Result<()> {
         // Create the metric.
-        let metric = alumet.create_metric::<f64>(
+        let metric = alumet.create_metric_with_tags::<f64>(
             "rapl_consumed_energy",
             Unit::Joule,
             "Energy consumed since the previous measurement, as reported by RAPL.",
+            &[tag::ENERGY],
         )?;
 
-        // Create the measurement source.
-        let source = match (use_perf, use_powercap) {
-            (true, true) => {
-                // prefer perf_events, fallback to powercap if it fails
-                setup_perf_events_probe_or_fallback(metric, &available_domains)?
-            }
-            (true, false) => {
-                // only use perf
-                Box::new(
-                    PerfEventProbe::new(metric, &available_domains.perf_events)
-                        .context("Failed to create RAPL probe based on perf_events")?,
-                )
-            }
-            (false, true) => {
-                // only use powercap
-                Box::new(
-                    PowercapProbe::new(metric, &available_domains.power_zones)
-                        .context("Failed to create RAPL probe based on powercap")?,
-                )
-            }
-            (false, false) => {
-                // error: no available interface!
-                return Err(anyhow!(
-                    "I can use neither perf_events nor powercap: impossible to measure RAPL counters."
-                ));
-            }
-        };
+        let rapl_source = self.create_rapl_source(metric);
+        let amd_probe = self.create_amd_probe(metric, rapl_source.is_ok());
 
         // Configure the source and add it to Alumet
         let trigger = trigger::builder::time_interval(self.config.poll_interval)
@@ -158,7 +311,19 @@ impl AlumetPlugin for RaplPlugin {
             .update_interval(self.config.flush_interval)
             .build()
             .unwrap();
-        alumet.add_source("in", source, trigger)?;
+        match (rapl_source, amd_probe) {
+            (Ok(source), amd_probe) => {
+                alumet.add_source("in", source, trigger.clone())?;
+                if let Some(probe) = amd_probe {
+                    alumet.add_source("amd", Box::new(probe), trigger)?;
+                }
+            }
+            (Err(e), Some(probe)) => {
+                log::warn!("I will use the energy counters of the AMD processor instead of RAPL. {e:#}");
+                alumet.add_source("in", Box::new(probe), trigger)?;
+            }
+            (Err(e), None) => return Err(e),
+        }
         Ok(())
     }
 
@@ -167,22 +332,6 @@ impl AlumetPlugin for RaplPlugin {
     }
 }
 
-fn setup_perf_events_probe_or_fallback(
-    metric: alumet::metrics::TypedMetricId<f64>,
-    available_domains: &SafeSubset,
-) -> anyhow::Result<Box<dyn Source>> {
-    match PerfEventProbe::new(metric, &available_domains.perf_events) {
-        Ok(probe) => Ok(Box::new(probe)),
-        Err(_) => {
-            log::warn!(
-                "I will fallback to the powercap sysfs, but perf_events is more efficient (see https://hal.science/hal-04420527)."
-            );
-            let fallback = PowercapProbe::new(metric, &available_domains.power_zones)?;
-            Ok(Box::new(fallback))
-        }
-    }
-}
-
 #[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
@@ -197,10 +346,20 @@ pub struct Config {
     /// Set to true to disable perf_events and always use the powercap sysfs.
     pub no_perf_events: bool,
 
+    /// Set to true to never read the RAPL MSRs, which are the last resort when perf_events and powercap cannot be used.
+    pub no_msr: bool,
+
+    /// Energy counters of AMD Zen processors, used when RAPL is not available and to measure the CCDs.
+    pub amd: AmdConfig,
+
     #[cfg(test)]
     pub perf_event_test_path: PathBuf,
     #[cfg(test)]
     pub powercap_test_path: PathBuf,
+    #[cfg(test)]
+    pub amd_test_path: PathBuf,
+    #[cfg(test)]
+    pub msr_test_path: PathBuf,
 }
 
 impl Default for Config {
@@ -209,20 +368,28 @@ impl Default for Config {
             poll_interval: Duration::from_secs(1), // 1Hz
             flush_interval: Duration::from_secs(5),
             no_perf_events: false, // prefer perf_events
+            no_msr: false,
+            amd: AmdConfig::default(),
 
             #[cfg(test)]
             perf_event_test_path: PathBuf::from(""),
             #[cfg(test)]
             powercap_test_path: PathBuf::from(""),
+            #[cfg(test)]
+            amd_test_path: PathBuf::from(""),
+            #[cfg(test)]
+            msr_test_path: PathBuf::from(""),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::Path;
+    use std::os::unix::fs::FileExt;
+    use std::path::{Path, PathBuf};
     use std::time::Duration;
 
+    use crate::amd::AmdConfig;
     use crate::tests_mock::{Entry, EntryType, create_mock_layout, create_valid_powercap_mock};
     use crate::{Config, RaplPlugin};
     use alumet::{
@@ -253,6 +420,10 @@ mod tests {
             no_perf_events: true,
             perf_event_test_path: Path::new("").to_path_buf(),
             powercap_test_path: base_path,
+            amd: AmdConfig::default(),
+            amd_test_path: PathBuf::new(),
+            no_msr: false,
+            msr_test_path: PathBuf::new(),
         };
         plugins.add_plugin(PluginInfo {
             metadata: PluginMetadata::from_static::<RaplPlugin>(),
@@ -316,6 +487,10 @@ mod tests {
             no_perf_events: true,
             perf_event_test_path: Path::new("").to_path_buf(),
             powercap_test_path: base_path.clone(),
+            amd: AmdConfig::default(),
+            amd_test_path: PathBuf::new(),
+            no_msr: false,
+            msr_test_path: PathBuf::new(),
         };
         plugins.add_plugin(PluginInfo {
             metadata: PluginMetadata::from_static::<RaplPlugin>(),
@@ -412,6 +587,253 @@ mod tests {
         Ok(())
     }
 
+    /// Without RAPL, the AMD counters measure the packages, in addition to the CCDs.
+    #[test]
+    fn test_runtime_with_amd_fallback() -> anyhow::Result<()> {
+        let mut plugins = PluginSet::new();
+
+        let tmp = tempdir()?;
+        let base_path = tmp.path().to_owned();
+        std::fs::create_dir(base_path.join("powercap"))?;
+
+        use EntryType::*;
+
+        let amd_entries = |core: &'static str, socket: &'static str| {
+            [
+                Entry {
+                    path: "cpus/cpu0/topology/physical_package_id",
+                    entry_type: File("0"),
+                },
+                Entry {
+                    path: "cpus/cpu0/topology/core_id",
+                    entry_type: File("0"),
+                },
+                Entry {
+                    path: "cpus/cpu0/cache/index3/id",
+                    entry_type: File("0"),
+                },
+                Entry {
+                    path: "hwmon/hwmon1/name",
+                    entry_type: File("amd_energy"),
+                },
+                Entry {
+                    path: "hwmon/hwmon1/energy1_label",
+                    entry_type: File("Ecore000"),
+                },
+                Entry {
+                    path: "hwmon/hwmon1/energy1_input",
+                    entry_type: File(core),
+                },
+                Entry {
+                    path: "hwmon/hwmon1/energy2_label",
+                    entry_type: File("Esocket0"),
+                },
+                Entry {
+                    path: "hwmon/hwmon1/energy2_input",
+                    entry_type: File(socket),
+                },
+            ]
+        };
+        create_mock_layout(&base_path, &amd_entries("1000000", "5000000"))?;
+
+        let source_config = Config {
+            poll_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(1),
+            no_perf_events: true,
+            perf_event_test_path: Path::new("").to_path_buf(),
+            powercap_test_path: base_path.join("powercap"),
+            amd: AmdConfig::default(),
+            amd_test_path: base_path.clone(),
+            no_msr: false,
+            msr_test_path: PathBuf::new(),
+        };
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<RaplPlugin>(),
+            enabled: true,
+            config: Some(config_to_toml_table(&source_config)),
+        });
+
+        let runtime_expectations = RuntimeExpectations::new()
+            .test_source(
+                SourceName::from_str("rapl", "in"),
+                || (),
+                |ctx| {
+                    assert_eq!(ctx.measurements().len(), 0);
+                },
+            )
+            .test_source(
+                SourceName::from_str("rapl", "in"),
+                move || {
+                    create_mock_layout(&base_path, &amd_entries("1500000", "7000000")).unwrap();
+                },
+                |ctx| {
+                    let mut actual: Vec<_> = ctx
+                        .measurements()
+                        .iter()
+                        .map(|m| {
+                            let domain = m.attributes().next().unwrap().1.to_string();
+                            (domain, m.resource.kind().to_owned(), m.value.clone())
+                        })
+                        .collect();
+                    actual.sort_by(|a, b| a.0.cmp(&b.0));
+                    let expected = vec![
+                        (
+                            String::from("package"),
+                            String::from("cpu_package"),
+                            WrappedMeasurementValue::F64(2.0),
+                        ),
+                        (
+                            String::from("package_total"),
+                            String::from("local_machine"),
+                            WrappedMeasurementValue::F64(2.0),
+                        ),
+                        (
+                            String::from("pp0"),
+                            String::from("cpu_ccd"),
+                            WrappedMeasurementValue::F64(0.5),
+                        ),
+                        (
+                            String::from("pp0_total"),
+                            String::from("local_machine"),
+                            WrappedMeasurementValue::F64(0.5),
+                        ),
+                    ];
+                    assert_eq!(actual, expected);
+                },
+            );
+
+        let agent = agent::Builder::new(plugins)
+            .with_expectations(runtime_expectations)
+            .build_and_start()
+            .unwrap();
+
+        agent.wait_for_shutdown(Duration::from_secs(10)).unwrap();
+
+        Ok(())
+    }
+
+    /// When the energy counters of powercap are not readable, the MSRs of the discovered domains are read.
+    #[test]
+    fn test_runtime_with_msr_fallback() -> anyhow::Result<()> {
+        let mut plugins = PluginSet::new();
+
+        let tmp = tempdir()?;
+        let base_path = tmp.path().to_owned();
+
+        use EntryType::*;
+
+        // the zone is listed, but energy_uj cannot be opened
+        let entries = [
+            Entry {
+                path: "powercap/enabled",
+                entry_type: File("1"),
+            },
+            Entry {
+                path: "powercap/intel-rapl:0",
+                entry_type: Dir,
+            },
+            Entry {
+                path: "powercap/intel-rapl:0/name",
+                entry_type: File("package-0"),
+            },
+            Entry {
+                path: "cpus/cpu0/topology/physical_package_id",
+                entry_type: File("0"),
+            },
+            Entry {
+                path: "cpus/cpu0/topology/core_id",
+                entry_type: File("0"),
+            },
+            Entry {
+                path: "cpuinfo",
+                entry_type: File("cpu family\t: 6\nmodel\t\t: 142\n"),
+            },
+        ];
+        create_mock_layout(&base_path, &entries)?;
+        let write_msr = move |register: u64, value: u64| -> std::io::Result<()> {
+            let dir = base_path.join("msr/0");
+            std::fs::create_dir_all(&dir)?;
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(dir.join("msr"))?;
+            file.write_all_at(&value.to_le_bytes(), register)
+        };
+        // energy unit of 1/16384 J, and a package counter close to its overflow
+        write_msr(0x606, 0x000A_0E03)?;
+        write_msr(0x611, 0xFFFF_C000)?;
+
+        let source_config = Config {
+            poll_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(1),
+            no_perf_events: true,
+            perf_event_test_path: Path::new("").to_path_buf(),
+            powercap_test_path: tmp.path().join("powercap"),
+            amd: AmdConfig {
+                interfaces: Vec::new(),
+                per_ccd: false,
+            },
+            amd_test_path: PathBuf::new(),
+            no_msr: false,
+            msr_test_path: tmp.path().to_owned(),
+        };
+        plugins.add_plugin(PluginInfo {
+            metadata: PluginMetadata::from_static::<RaplPlugin>(),
+            enabled: true,
+            config: Some(config_to_toml_table(&source_config)),
+        });
+
+        let runtime_expectations = RuntimeExpectations::new()
+            .test_source(
+                SourceName::from_str("rapl", "in"),
+                || (),
+                |ctx| {
+                    assert_eq!(ctx.measurements().len(), 0);
+                },
+            )
+            .test_source(
+                SourceName::from_str("rapl", "in"),
+                move || {
+                    write_msr(0x611, 0x8000).unwrap();
+                },
+                |ctx| {
+                    let mut actual: Vec<_> = ctx
+                        .measurements()
+                        .iter()
+                        .map(|m| {
+                            let domain = m.attributes().next().unwrap().1.to_string();
+                            (domain, m.resource.kind().to_owned(), m.value.clone())
+                        })
+                        .collect();
+                    actual.sort_by(|a, b| a.0.cmp(&b.0));
+                    // the counter has wrapped around: 0x4000 + 0x8000 units
+                    let expected = vec![
+                        (
+                            String::from("package"),
+                            String::from("cpu_package"),
+                            WrappedMeasurementValue::F64(3.0),
+                        ),
+                        (
+                            String::from("package_total"),
+                            String::from("local_machine"),
+                            WrappedMeasurementValue::F64(3.0),
+                        ),
+                    ];
+                    assert_eq!(actual, expected);
+                },
+            );
+
+        let agent = agent::Builder::new(plugins)
+            .with_expectations(runtime_expectations)
+            .build_and_start()
+            .unwrap();
+
+        agent.wait_for_shutdown(Duration::from_secs(10)).unwrap();
+
+        Ok(())
+    }
+
     fn config_to_toml_table(config: &Config) -> toml::Table {
         toml::Value::try_from(config).unwrap().as_table().unwrap().clone()
     }
@@ -0,0 +1,435 @@
+//! RAPL energy counters of Intel processors, read directly from the MSRs.
+//!
+//! This is the last resort, when neither perf_events nor powercap can be used:
+//! it requires the `msr` kernel module and the `CAP_SYS_RAWIO` capability.
+//! The MSRs are 32-bit counters, which overflow after a few minutes under load.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::elements::error::PollError,
+    plugin::util::{CounterDiff, CounterDiffUpdate},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+
+use crate::{
+    cpus::{self, CPU_SYSFS_PATH},
+    domains::RaplDomainType,
+    total::DomainTotals,
+};
+
+pub const MSR_PATH: &str = "/dev/cpu";
+pub const CPUINFO_PATH: &str = "/proc/cpuinfo";
+
+const MSR_RAPL_POWER_UNIT: u64 = 0x606;
+const MSR_PKG_ENERGY_STATUS: u64 = 0x611;
+const MSR_DRAM_ENERGY_STATUS: u64 = 0x619;
+const MSR_PP0_ENERGY_STATUS: u64 = 0x639;
+const MSR_PP1_ENERGY_STATUS: u64 = 0x641;
+const MSR_PLATFORM_ENERGY_STATUS: u64 = 0x64D;
+
+/// Energy unit of the DRAM domain of some server processors, which ignore `MSR_RAPL_POWER_UNIT`: 15.3 µJ.
+const FIXED_DRAM_UNIT: f64 = 1.0 / 65536.0;
+
+/// Intel processors (family 6) whose DRAM domain has a fixed energy unit, as in the `intel_rapl` driver of Linux:
+/// Haswell-X, Broadwell-X and -D, Xeon Phi, Skylake-X, Ice Lake-X and -D, Sapphire and Emerald Rapids,
+/// Granite Rapids.
+const FIXED_DRAM_UNIT_MODELS: &[u32] = &[0x3F, 0x4F, 0x56, 0x57, 0x85, 0x55, 0x6A, 0x6C, 0x8F, 0xCF, 0xAD, 0xAE];
+
+/// Location of the interfaces, which can be changed for the tests.
+#[derive(Debug, Clone)]
+pub struct MsrPaths {
+    pub msr: PathBuf,
+    pub cpus: PathBuf,
+    pub cpuinfo: PathBuf,
+}
+
+impl Default for MsrPaths {
+    fn default() -> Self {
+        Self {
+            msr: PathBuf::from(MSR_PATH),
+            cpus: PathBuf::from(CPU_SYSFS_PATH),
+            cpuinfo: PathBuf::from(CPUINFO_PATH),
+        }
+    }
+}
+
+/// Reads a 64-bit MSR from an opened `/dev/cpu/*/msr` file.
+pub fn read_msr(file: &File, register: u64) -> anyhow::Result<u64> {
+    let mut bytes = [0u8; 8];
+    file.read_exact_at(&mut bytes, register)
+        .with_context(|| format!("failed to read MSR {register:#x} from {file:?}"))?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Returns the energy unit, in joules, encoded in the value of a `RAPL_POWER_UNIT` MSR.
+///
+/// The energy unit is 1/2^ESU joules, where ESU is in bits 12:8. Intel and AMD use the same layout.
+pub fn energy_unit(power_unit: u64) -> f64 {
+    let esu = (power_unit >> 8) & 0x1f;
+    0.5f64.powi(esu as i32)
+}
+
+fn energy_register(domain: RaplDomainType) -> u64 {
+    match domain {
+        RaplDomainType::Package => MSR_PKG_ENERGY_STATUS,
+        RaplDomainType::PP0 => MSR_PP0_ENERGY_STATUS,
+        RaplDomainType::PP1 => MSR_PP1_ENERGY_STATUS,
+        RaplDomainType::Dram => MSR_DRAM_ENERGY_STATUS,
+        RaplDomainType::Platform => MSR_PLATFORM_ENERGY_STATUS,
+    }
+}
+
+/// Returns the family and model of the processor, from the content of `/proc/cpuinfo`.
+fn parse_cpu_model(cpuinfo: &str) -> Option<(u32, u32)> {
+    let field = |name: &str| {
+        cpuinfo.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if key.trim() == name {
+                value.trim().parse().ok()
+            } else {
+                None
+            }
+        })
+    };
+    Some((field("cpu family")?, field("model")?))
+}
+
+/// Returns true if the DRAM domain of the processor has a fixed energy unit.
+fn has_fixed_dram_unit(cpuinfo: &Path) -> bool {
+    let model = fs::read_to_string(cpuinfo).ok().and_then(|s| parse_cpu_model(&s));
+    match model {
+        Some((family, model)) => family == 6 && FIXED_DRAM_UNIT_MODELS.contains(&model),
+        None => {
+            log::warn!("Failed to read the cpu model from {cpuinfo:?}, the DRAM energy may be wrong.");
+            false
+        }
+    }
+}
+
+/// An energy MSR of a socket.
+struct MsrCounter {
+    file: File,
+    register: u64,
+    domain: RaplDomainType,
+    socket: u32,
+    /// Energy unit, in joules.
+    unit: f64,
+    diff: CounterDiff,
+}
+
+impl MsrCounter {
+    fn new(file: File, domain: RaplDomainType, socket: u32, unit: f64) -> Self {
+        Self {
+            file,
+            register: energy_register(domain),
+            domain,
+            socket,
+            unit,
+            diff: CounterDiff::with_max_value(u32::MAX as u64),
+        }
+    }
+
+    /// Returns the energy consumed since the previous call, in joules, or `None` on the first call.
+    fn read_joules(&mut self) -> anyhow::Result<Option<f64>> {
+        // The energy is in the lower 32 bits, the upper ones are reserved.
+        let value = read_msr(&self.file, self.register)? & u32::MAX as u64;
+        Ok(match self.diff.update(value) {
+            CounterDiffUpdate::FirstTime => None,
+            CounterDiffUpdate::Difference(diff) => Some(diff as f64 * self.unit),
+            CounterDiffUpdate::CorrectedDifference(diff) => {
+                log::debug!("Overflow on RAPL MSR {:#x} of socket {}", self.register, self.socket);
+                Some(diff as f64 * self.unit)
+            }
+        })
+    }
+}
+
+/// Opens the energy MSRs of the given domains, or of every domain that the processor supports if `domains` is empty.
+fn discover(domains: &[RaplDomainType], paths: &MsrPaths) -> anyhow::Result<Vec<MsrCounter>> {
+    let cpus = cpus::read_topology(&paths.cpus)?;
+    // The first CPU of each socket.
+    let mut sockets = BTreeMap::new();
+    for cpu in &cpus {
+        sockets.entry(cpu.socket).or_insert(cpu.id);
+    }
+
+    let candidates = if domains.is_empty() {
+        vec![
+            RaplDomainType::Package,
+            RaplDomainType::PP0,
+            RaplDomainType::PP1,
+            RaplDomainType::Dram,
+            RaplDomainType::Platform,
+        ]
+    } else {
+        domains.to_vec()
+    };
+    let fixed_dram_unit = candidates.contains(&RaplDomainType::Dram) && has_fixed_dram_unit(&paths.cpuinfo);
+
+    let mut counters = Vec::new();
+    for (i, (socket, cpu)) in sockets.into_iter().enumerate() {
+        let path = paths.msr.join(cpu.to_string()).join("msr");
+        let open = || {
+            File::open(&path).with_context(|| {
+                format!("failed to open {path:?}. Is the msr module loaded, and does the agent have the CAP_SYS_RAWIO capability?")
+            })
+        };
+        let power_unit = read_msr(&open()?, MSR_RAPL_POWER_UNIT).with_context(|| {
+            format!("failed to read the RAPL energy unit of socket {socket}. Is it an Intel processor?")
+        })?;
+        let unit = energy_unit(power_unit);
+
+        for domain in &candidates {
+            // The platform domain covers the whole machine.
+            if *domain == RaplDomainType::Platform && i > 0 {
+                continue;
+            }
+            let file = open()?;
+            if domains.is_empty() {
+                // The counters of the unsupported domains cannot be read, or stay at zero.
+                match read_msr(&file, energy_register(*domain)) {
+                    Ok(value) if value & u32::MAX as u64 != 0 => (),
+                    _ => continue,
+                }
+            }
+            let unit = match domain {
+                RaplDomainType::Dram if fixed_dram_unit => FIXED_DRAM_UNIT,
+                _ => unit,
+            };
+            counters.push(MsrCounter::new(file, *domain, socket, unit));
+        }
+    }
+    if counters.is_empty() {
+        return Err(anyhow!("no RAPL energy MSR found in {:?}", paths.msr));
+    }
+    Ok(counters)
+}
+
+/// MSR probe collects RAPL measurements by reading the MSRs of the processor.
+pub struct MsrProbe {
+    metric: TypedMetricId<f64>,
+    counters: Vec<MsrCounter>,
+}
+
+impl MsrProbe {
+    /// Creates a probe that reads the given domains, or every domain that the processor supports if `domains` is empty.
+    pub fn new(metric: TypedMetricId<f64>, domains: &[RaplDomainType], paths: &MsrPaths) -> anyhow::Result<MsrProbe> {
+        let counters = discover(domains, paths)?;
+        Ok(MsrProbe { metric, counters })
+    }
+
+    /// The measured domains, without duplicates.
+    pub fn domains(&self) -> Vec<RaplDomainType> {
+        let domains: BTreeSet<_> = self.counters.iter().map(|c| c.domain).collect();
+        domains.into_iter().collect()
+    }
+}
+
+impl alumet::pipeline::Source for MsrProbe {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, timestamp: Timestamp) -> Result<(), PollError> {
+        let mut totals = DomainTotals::new();
+        for counter in &mut self.counters {
+            if let Some(joules) = counter.read_joules()? {
+                measurements.push(
+                    MeasurementPoint::new(
+                        timestamp,
+                        self.metric,
+                        counter.domain.to_resource(counter.socket),
+                        ResourceConsumer::LocalMachine,
+                        joules,
+                    )
+                    .with_attr("domain", counter.domain.as_str()),
+                );
+                totals.push(counter.domain, joules);
+            }
+        }
+        for (domain, total) in totals.iter() {
+            measurements.push(
+                MeasurementPoint::new(
+                    timestamp,
+                    self.metric,
+                    Resource::LocalMachine,
+                    ResourceConsumer::LocalMachine,
+                    total,
+                )
+                .with_attr("domain", domain.as_str_total()),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::tests_mock::{Entry, EntryType::*, create_mock_layout};
+
+    /// Two sockets of two threads each.
+    fn create_topology(base: &Path) -> anyhow::Result<()> {
+        let files: Vec<_> = (0..4u32)
+            .flat_map(|cpu| {
+                [
+                    (format!("cpu{cpu}/topology/physical_package_id"), (cpu / 2).to_string()),
+                    (format!("cpu{cpu}/topology/core_id"), "0".to_owned()),
+                ]
+            })
+            .collect();
+        let entries: Vec<_> = files
+            .iter()
+            .map(|(path, content)| Entry {
+                path,
+                entry_type: File(content),
+            })
+            .collect();
+        create_mock_layout(&base.join("cpus"), &entries)?;
+        Ok(())
+    }
+
+    /// Writes the MSRs of a cpu. The registers are 8 bytes apart at least, hence they don't overlap in a regular file.
+    fn write_msrs(base: &Path, cpu: u32, registers: &[(u64, u64)]) -> anyhow::Result<()> {
+        let dir = base.join("msr").join(cpu.to_string());
+        fs::create_dir_all(&dir)?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join("msr"))?;
+        for (register, value) in registers {
+            file.write_all_at(&value.to_le_bytes(), *register)?;
+        }
+        Ok(())
+    }
+
+    fn paths(base: &Path) -> MsrPaths {
+        MsrPaths {
+            msr: base.join("msr"),
+            cpus: base.join("cpus"),
+            cpuinfo: base.join("cpuinfo"),
+        }
+    }
+
+    fn summary(counters: &[MsrCounter]) -> Vec<(RaplDomainType, u32, f64)> {
+        counters.iter().map(|c| (c.domain, c.socket, c.unit)).collect()
+    }
+
+    #[test]
+    fn test_energy_unit() {
+        // default value on Intel processors: 61 µJ
+        assert_eq!(energy_unit(0x000A_0E03), 1.0 / 16384.0);
+        assert_eq!(energy_unit(0x000A_1003), 1.0 / 65536.0);
+    }
+
+    #[test]
+    fn test_parse_cpu_model() {
+        let cpuinfo = "processor\t: 0\nvendor_id\t: GenuineIntel\ncpu family\t: 6\nmodel\t\t: 85\nmodel name\t: Intel(R) Xeon(R) Gold 6130 CPU @ 2.10GHz\n";
+        assert_eq!(parse_cpu_model(cpuinfo), Some((6, 85)));
+        assert_eq!(parse_cpu_model("processor\t: 0\n"), None);
+    }
+
+    #[test]
+    fn test_discover_supported_domains() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let base = tmp.path();
+        create_topology(base)?;
+        // Skylake-X: the DRAM domain has a fixed unit
+        fs::write(base.join("cpuinfo"), "cpu family\t: 6\nmodel\t\t: 85\n")?;
+        for cpu in [0, 2] {
+            // PP0 stays at zero, PP1 and platform are past the end of the file
+            write_msrs(
+                base,
+                cpu,
+                &[
+                    (MSR_RAPL_POWER_UNIT, 0x000A_0E03),
+                    (MSR_PKG_ENERGY_STATUS, 1000),
+                    (MSR_DRAM_ENERGY_STATUS, 500),
+                    (MSR_PP0_ENERGY_STATUS, 0),
+                ],
+            )?;
+        }
+
+        let counters = discover(&[], &paths(base))?;
+        let pkg_unit = 1.0 / 16384.0;
+        assert_eq!(
+            summary(&counters),
+            vec![
+                (RaplDomainType::Package, 0, pkg_unit),
+                (RaplDomainType::Dram, 0, FIXED_DRAM_UNIT),
+                (RaplDomainType::Package, 1, pkg_unit),
+                (RaplDomainType::Dram, 1, FIXED_DRAM_UNIT),
+            ]
+        );
+
+        // the domains found by the other interfaces are used as is
+        let counters = discover(&[RaplDomainType::Package, RaplDomainType::PP0], &paths(base))?;
+        assert_eq!(
+            summary(&counters),
+            vec![
+                (RaplDomainType::Package, 0, pkg_unit),
+                (RaplDomainType::PP0, 0, pkg_unit),
+                (RaplDomainType::Package, 1, pkg_unit),
+                (RaplDomainType::PP0, 1, pkg_unit),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_counter_overflow() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let base = tmp.path();
+        create_topology(base)?;
+        fs::write(base.join("cpuinfo"), "cpu family\t: 6\nmodel\t\t: 142\n")?;
+        for cpu in [0, 2] {
+            write_msrs(
+                base,
+                cpu,
+                &[(MSR_RAPL_POWER_UNIT, 0x000A_0E03), (MSR_PKG_ENERGY_STATUS, 0xFFFF_FF00)],
+            )?;
+        }
+
+        let mut counters = discover(&[RaplDomainType::Package], &paths(base))?;
+        assert_eq!(counters[0].read_joules()?, None);
+        // the upper bits are reserved
+        write_msrs(base, 0, &[(MSR_PKG_ENERGY_STATUS, 0x1_FFFF_FFFF)])?;
+        assert_eq!(counters[0].read_joules()?, Some(255.0 / 16384.0));
+        // the counter wraps around
+        write_msrs(base, 0, &[(MSR_PKG_ENERGY_STATUS, 0x3FFF)])?;
+        assert_eq!(counters[0].read_joules()?, Some(1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_intel() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let base = tmp.path();
+        create_topology(base)?;
+        // the Intel registers do not exist
+        write_msrs(base, 0, &[(0, 0)])?;
+        let err = discover(&[], &paths(base))
+            .err()
+            .expect("the MSRs should not be readable");
+        assert!(
+            format!("{err:#}").contains("failed to read the RAPL energy unit of socket 0"),
+            "{err:#}"
+        );
+
+        // without the msr module
+        fs::remove_dir_all(base.join("msr"))?;
+        let err = discover(&[], &paths(base)).err().expect("the MSRs should not exist");
+        assert!(format!("{err:#}").contains("Is the msr module loaded"), "{err:#}");
+        Ok(())
+    }
+}
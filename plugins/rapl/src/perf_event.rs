@@ -2,7 +2,7 @@ use alumet::{
     measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
     metrics::TypedMetricId,
     pipeline::elements::error::PollError,
-    plugin::util::{CounterDiff, CounterDiffUpdate},
+    plugin::util::{self, CounterDiff, CounterDiffUpdate},
     resources::{Resource, ResourceConsumer},
 };
 use anyhow::{Context, Result};
@@ -253,7 +253,7 @@ impl OpenedPowerEvent {
 impl PerfEventProbe {
     /// creates a new PerfEventProbe by passing an Alumet metric ID for energy measurement and related power events
     pub fn new(metric: TypedMetricId<f64>, power_events: &Vec<PowerEvent>) -> anyhow::Result<PerfEventProbe> {
-        let all_cpus = cpus::online_cpus()?;
+        let all_cpus = util::online_cpus()?;
         let socket_cpus = cpus::cpus_to_monitor_with_perf()
         .context("I could not determine how to use perf_events to read RAPL energy counters. The Intel RAPL PMU module may not be enabled, is your Linux kernel too old?")?;
 
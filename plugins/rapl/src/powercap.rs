@@ -317,7 +317,7 @@ impl PowercapProbe {
         
             A solution could be:
                 sudo chmod a+r -R {POWERCAP_RAPL_PATH}"};
-        log::error!("{msg}");
+        log::warn!("{msg}");
     }
 }
 
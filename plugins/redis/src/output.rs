@@ -0,0 +1,167 @@
+use std::time::{Duration, SystemTime};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use anyhow::Context;
+use redis::{Commands, Connection, RedisResult, streams::StreamMaxlen};
+
+pub struct RedisStreamOutput {
+    client: redis::Client,
+    /// Opened by [`RedisStreamOutput::connect`], closed after a connection error.
+    connection: Option<Connection>,
+    stream: String,
+    maxlen: Option<StreamMaxlen>,
+    timeout: Duration,
+}
+
+impl RedisStreamOutput {
+    pub fn new(client: redis::Client, stream: String, maxlen: Option<StreamMaxlen>, timeout: Duration) -> Self {
+        Self {
+            client,
+            connection: None,
+            stream,
+            maxlen,
+            timeout,
+        }
+    }
+
+    /// Returns the connection to Redis, after having (re)connected if needed.
+    pub fn connect(&mut self) -> RedisResult<&mut Connection> {
+        if self.connection.is_none() {
+            let connection = self.client.get_connection_with_timeout(self.timeout)?;
+            connection.set_read_timeout(Some(self.timeout))?;
+            connection.set_write_timeout(Some(self.timeout))?;
+            self.connection = Some(connection);
+        }
+        Ok(self.connection.as_mut().unwrap())
+    }
+
+    /// Creates a consumer group that reads the new entries of the stream, and the stream itself if needed.
+    pub fn create_group(&mut self, group: &str) -> RedisResult<()> {
+        let stream = self.stream.clone();
+        let res: RedisResult<()> = self.connect()?.xgroup_create_mkstream(stream, group, "$");
+        match res {
+            // the group already exists, for instance because the agent has been restarted
+            Err(e) if e.code() == Some("BUSYGROUP") => Ok(()),
+            res => res,
+        }
+    }
+}
+
+/// Returns the fields of the stream entry of a measurement.
+///
+/// Each measurement is a separate entry, so that the consumers of a group can process them independently.
+/// The attributes are prefixed by `attr.`.
+fn entry_fields(m: &MeasurementPoint, metric: &Metric) -> Vec<(String, String)> {
+    let (value, value_type) = match m.value {
+        WrappedMeasurementValue::F64(v) => (v.to_string(), "f64"),
+        WrappedMeasurementValue::U64(v) => (v.to_string(), "u64"),
+    };
+    let mut fields = vec![
+        (String::from("metric"), metric.name.clone()),
+        (
+            String::from("timestamp"),
+            humantime::format_rfc3339_nanos(SystemTime::from(m.timestamp)).to_string(),
+        ),
+        (String::from("value"), value),
+        (String::from("value_type"), value_type.to_owned()),
+        (String::from("unit"), metric.unit.unique_name()),
+        (String::from("resource_kind"), m.resource.kind().to_owned()),
+        (String::from("resource_id"), m.resource.id_display().to_string()),
+        (String::from("consumer_kind"), m.consumer.kind().to_owned()),
+        (String::from("consumer_id"), m.consumer.id_display().to_string()),
+    ];
+    for (key, value) in m.attributes() {
+        fields.push((format!("attr.{key}"), value.to_string()));
+    }
+    fields
+}
+
+impl alumet::pipeline::Output for RedisStreamOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        if measurements.is_empty() {
+            return Ok(());
+        }
+
+        // Add all the entries in one transaction, so that a batch is either written entirely or not at all.
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for m in measurements {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            let fields = entry_fields(m, metric);
+            match self.maxlen {
+                Some(maxlen) => pipe.xadd_maxlen(&self.stream, maxlen, "*", &fields),
+                None => pipe.xadd(&self.stream, "*", &fields),
+            }
+            .ignore();
+        }
+
+        let res = self
+            .connect()
+            .and_then(|connection| pipe.query::<()>(connection))
+            .context("failed to add the measurements to the Redis stream");
+        if res.is_err() {
+            // the connection may be broken, open a new one on the next write
+            self.connection = None;
+        }
+        res.map_err(WriteError::CanRetry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue},
+        metrics::{Metric, RawMetricId},
+        resources::{Resource, ResourceConsumer},
+        units::{PrefixedUnit, Unit},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::entry_fields;
+
+    #[test]
+    fn fields() {
+        let metric = Metric {
+            name: String::from("rapl_consumed_energy"),
+            description: String::new(),
+            value_type: WrappedMeasurementType::F64,
+            unit: PrefixedUnit::from(Unit::Joule),
+            tags: Vec::new(),
+        };
+        let point = MeasurementPoint::new_untyped(
+            Timestamp::from(UNIX_EPOCH + Duration::from_millis(1_700_000_000_500)),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::F64(12.5),
+        )
+        .with_attr("domain", "package");
+
+        let fields = entry_fields(&point, &metric);
+        let fields: Vec<(&str, &str)> = fields.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("metric", "rapl_consumed_energy"),
+                ("timestamp", "2023-11-14T22:13:20.500000000Z"),
+                ("value", "12.5"),
+                ("value_type", "f64"),
+                ("unit", "J"),
+                ("resource_kind", "cpu_package"),
+                ("resource_id", "0"),
+                ("consumer_kind", "local_machine"),
+                ("consumer_id", ""),
+                ("attr.domain", "package"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+};
+
+/// A command received by [`FakeRedis`], as a list of arguments.
+pub type Command = Vec<String>;
+
+/// A minimal Redis server that records the commands it receives.
+///
+/// It understands just enough of the protocol (RESP2) to answer the client of the plugin:
+/// `XADD` returns an entry id, `MULTI`/`EXEC` are supported, everything else returns `OK`.
+pub struct FakeRedis {
+    port: u16,
+    commands: Arc<Mutex<Vec<Command>>>,
+}
+
+impl FakeRedis {
+    pub fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let commands_clone = commands.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let commands = commands_clone.clone();
+                std::thread::spawn(move || handle_client(stream.unwrap(), commands));
+            }
+        });
+        Ok(Self { port, commands })
+    }
+
+    pub fn url(&self) -> String {
+        format!("redis://127.0.0.1:{}", self.port)
+    }
+
+    /// Returns the commands received so far, with the given name.
+    pub fn commands(&self, name: &str) -> Vec<Command> {
+        let commands = self.commands.lock().unwrap();
+        commands
+            .iter()
+            .filter(|c| c[0].eq_ignore_ascii_case(name))
+            .cloned()
+            .collect()
+    }
+}
+
+fn handle_client(stream: TcpStream, commands: Arc<Mutex<Vec<Command>>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut queued: Option<usize> = None;
+    let mut next_id = 0;
+    while let Some(command) = read_command(&mut reader) {
+        let name = command[0].to_ascii_uppercase();
+        commands.lock().unwrap().push(command);
+        let reply = match (name.as_str(), queued.as_mut()) {
+            ("MULTI", _) => {
+                queued = Some(0);
+                String::from("+OK\r\n")
+            }
+            ("EXEC", Some(n)) => {
+                let mut reply = format!("*{n}\r\n");
+                for _ in 0..*n {
+                    let id = format!("1700000000000-{next_id}");
+                    next_id += 1;
+                    reply.push_str(&format!("${}\r\n{id}\r\n", id.len()));
+                }
+                queued = None;
+                reply
+            }
+            (_, Some(n)) => {
+                *n += 1;
+                String::from("+QUEUED\r\n")
+            }
+            ("XADD", None) => {
+                let id = format!("1700000000000-{next_id}");
+                next_id += 1;
+                format!("${}\r\n{id}\r\n", id.len())
+            }
+            _ => String::from("+OK\r\n"),
+        };
+        writer.write_all(reply.as_bytes()).unwrap();
+    }
+}
+
+/// Reads a command, which is an array of bulk strings. Returns `None` at the end of the stream.
+fn read_command(reader: &mut impl BufRead) -> Option<Command> {
+    let len: usize = read_line(reader)?.strip_prefix('*')?.parse().ok()?;
+    let mut command = Vec::with_capacity(len);
+    for _ in 0..len {
+        let arg_len: usize = read_line(reader)?.strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0u8; arg_len + 2];
+        reader.read_exact(&mut arg).ok()?;
+        arg.truncate(arg_len);
+        command.push(String::from_utf8(arg).ok()?);
+    }
+    Some(command)
+}
+
+fn read_line(reader: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end().to_owned()),
+    }
+}
@@ -0,0 +1,93 @@
+mod fake_redis;
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{PluginHarness, golden::SampleMeasurements},
+};
+use plugin_redis::{Config, RedisPlugin};
+use pretty_assertions::assert_eq;
+
+use crate::fake_redis::FakeRedis;
+
+#[test]
+fn add_entries_with_trimming() -> anyhow::Result<()> {
+    let redis = FakeRedis::start()?;
+    let config = Config {
+        url: redis.url(),
+        stream: String::from("lab:energy"),
+        maxlen: 1000,
+        consumer_group: Some(String::from("analysis")),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<RedisPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    assert_eq!(
+        redis.commands("XGROUP"),
+        vec![vec!["XGROUP", "CREATE", "lab:energy", "analysis", "$", "MKSTREAM"]]
+    );
+    assert_eq!(redis.commands("MULTI").len(), 1);
+    assert_eq!(redis.commands("EXEC").len(), 1);
+
+    let entries = redis.commands("XADD");
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[0][..5], ["XADD", "lab:energy", "MAXLEN", "~", "1000"]);
+    assert_eq!(entries[0][5], "*");
+    let fields: Vec<&str> = entries[2][6..].iter().map(String::as_str).collect();
+    assert_eq!(
+        fields,
+        vec![
+            "metric",
+            "sample_count",
+            "timestamp",
+            "2023-11-14T22:13:20.500000000Z",
+            "value",
+            "42",
+            "value_type",
+            "u64",
+            "unit",
+            "1",
+            "resource_kind",
+            "local_machine",
+            "resource_id",
+            "",
+            "consumer_kind",
+            "process",
+            "consumer_id",
+            "1234",
+            "attr.state",
+            "running",
+            "attr.cpu",
+            "3",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn no_trimming() -> anyhow::Result<()> {
+    let redis = FakeRedis::start()?;
+    let config = Config {
+        url: redis.url(),
+        maxlen: 0,
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<RedisPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    assert!(redis.commands("XGROUP").is_empty());
+    for entry in redis.commands("XADD") {
+        assert_eq!(entry[..3], ["XADD", "alumet:measurements", "*"]);
+    }
+    Ok(())
+}
@@ -0,0 +1,285 @@
+//! Local storage of the batches that could not be sent to the server.
+
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::protocol::SendBatch;
+
+/// Extension of the files that contain a batch.
+const BATCH_EXTENSION: &str = "batch";
+
+/// A bounded FIFO queue of batches, stored in memory or on disk.
+///
+/// When the queue is full, the oldest batches are dropped to make room for the new ones.
+///
+/// The batches that have been sent stay at the front of the queue, "in flight", until the server
+/// acknowledges them. If the connection is lost, they are sent again (see [`Backlog::resend_all`]).
+pub struct Backlog {
+    storage: Storage,
+    /// Maximum number of measurements in the queue.
+    max_measurements: usize,
+    /// Current number of measurements in the queue.
+    n_measurements: usize,
+    /// Number of batches, at the front of the queue, that have been sent but not acknowledged.
+    n_in_flight: usize,
+    /// Number of in-flight batches that have been dropped to make room for new batches,
+    /// and whose acknowledgement is still expected.
+    n_dropped_in_flight: usize,
+}
+
+enum Storage {
+    Memory(VecDeque<SendBatch>),
+    Disk {
+        dir: PathBuf,
+        /// Sequence number and number of measurements of each file, from the oldest to the newest.
+        files: VecDeque<(u64, usize)>,
+        next_seq: u64,
+    },
+}
+
+impl Backlog {
+    /// Creates a backlog that keeps the batches in memory.
+    pub fn in_memory(max_measurements: usize) -> Self {
+        Self {
+            storage: Storage::Memory(VecDeque::new()),
+            max_measurements,
+            n_measurements: 0,
+            n_in_flight: 0,
+            n_dropped_in_flight: 0,
+        }
+    }
+
+    /// Creates a backlog that writes the batches to files in `dir`.
+    ///
+    /// The batches that are left in `dir` by a previous run are deleted: their metric ids
+    /// refer to the metric registry of the previous run, which no longer exists.
+    pub fn on_disk(dir: PathBuf, max_measurements: usize) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let mut n_deleted = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == BATCH_EXTENSION) {
+                fs::remove_file(path)?;
+                n_deleted += 1;
+            }
+        }
+        if n_deleted > 0 {
+            log::warn!(
+                "Deleted {n_deleted} batches left by a previous run in {}: they cannot be sent.",
+                dir.display()
+            );
+        }
+        Ok(Self {
+            storage: Storage::Disk {
+                dir,
+                files: VecDeque::new(),
+                next_seq: 0,
+            },
+            max_measurements,
+            n_measurements: 0,
+            n_in_flight: 0,
+            n_dropped_in_flight: 0,
+        })
+    }
+
+    /// Returns `true` if the backlog contains no batch.
+    pub fn is_empty(&self) -> bool {
+        match &self.storage {
+            Storage::Memory(batches) => batches.is_empty(),
+            Storage::Disk { files, .. } => files.is_empty(),
+        }
+    }
+
+    /// Returns the number of measurements in the backlog.
+    pub fn n_measurements(&self) -> usize {
+        self.n_measurements
+    }
+
+    /// Adds a batch at the end of the queue.
+    ///
+    /// Returns the number of measurements that have been dropped to make room for it.
+    pub fn push(&mut self, batch: SendBatch) -> io::Result<usize> {
+        let len = batch.len as usize;
+        let mut n_dropped = 0;
+        while !self.is_empty() && self.n_measurements + len > self.max_measurements {
+            n_dropped += self.drop_oldest()?;
+        }
+        match &mut self.storage {
+            Storage::Memory(batches) => batches.push_back(batch),
+            Storage::Disk { dir, files, next_seq } => {
+                let seq = *next_seq;
+                let bytes = postcard::to_allocvec(&batch).map_err(io::Error::other)?;
+                fs::write(batch_path(dir, seq), bytes)?;
+                files.push_back((seq, len));
+                *next_seq += 1;
+            }
+        }
+        self.n_measurements += len;
+        Ok(n_dropped)
+    }
+
+    /// Returns the oldest batch that has not been sent yet, and marks it as in flight.
+    ///
+    /// The batch stays in the queue until it is acknowledged.
+    pub fn next_to_send(&mut self) -> io::Result<Option<SendBatch>> {
+        let batch = match &self.storage {
+            Storage::Memory(batches) => batches.get(self.n_in_flight).cloned(),
+            Storage::Disk { dir, files, .. } => match files.get(self.n_in_flight) {
+                Some((seq, _)) => {
+                    let bytes = fs::read(batch_path(dir, *seq))?;
+                    let batch =
+                        postcard::from_bytes(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                    Some(batch)
+                }
+                None => None,
+            },
+        };
+        if batch.is_some() {
+            self.n_in_flight += 1;
+        }
+        Ok(batch)
+    }
+
+    /// Removes the `n` oldest in-flight batches, which have been received by the server.
+    pub fn acknowledge(&mut self, n: usize) -> io::Result<()> {
+        // the batches that have been dropped are acknowledged first, since they have been sent first
+        let already_dropped = n.min(self.n_dropped_in_flight);
+        self.n_dropped_in_flight -= already_dropped;
+        for _ in 0..(n - already_dropped).min(self.n_in_flight) {
+            self.remove_oldest()?;
+            self.n_in_flight -= 1;
+        }
+        Ok(())
+    }
+
+    /// Marks the in-flight batches as not sent, so that they are sent again, from the oldest one.
+    ///
+    /// This is used when the connection is lost: the server may not have received them.
+    pub fn resend_all(&mut self) {
+        self.n_in_flight = 0;
+        self.n_dropped_in_flight = 0;
+    }
+
+    /// Drops the oldest batch to make room for a new one, and returns its number of measurements.
+    fn drop_oldest(&mut self) -> io::Result<usize> {
+        if self.n_in_flight > 0 {
+            self.n_in_flight -= 1;
+            self.n_dropped_in_flight += 1;
+        }
+        self.remove_oldest()
+    }
+
+    /// Removes the oldest batch and returns its number of measurements.
+    fn remove_oldest(&mut self) -> io::Result<usize> {
+        let len = match &mut self.storage {
+            Storage::Memory(batches) => batches.pop_front().map_or(0, |b| b.len as usize),
+            Storage::Disk { dir, files, .. } => match files.pop_front() {
+                Some((seq, len)) => {
+                    fs::remove_file(batch_path(dir, seq))?;
+                    len
+                }
+                None => 0,
+            },
+        };
+        self.n_measurements -= len;
+        Ok(len)
+    }
+}
+
+fn batch_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{seq:020}.{BATCH_EXTENSION}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compression::Compression, protocol::SendBatch};
+
+    use super::Backlog;
+
+    fn batch(len: u32) -> SendBatch {
+        SendBatch {
+            compression: Compression::None,
+            len,
+            payload: vec![len as u8; 8],
+        }
+    }
+
+    fn check_fifo(mut backlog: Backlog) {
+        assert!(backlog.is_empty());
+        assert_eq!(backlog.push(batch(4)).unwrap(), 0);
+        assert_eq!(backlog.push(batch(3)).unwrap(), 0);
+        assert_eq!(backlog.n_measurements(), 7);
+
+        // full: the oldest batch is dropped
+        assert_eq!(backlog.push(batch(2)).unwrap(), 4);
+        assert_eq!(backlog.n_measurements(), 5);
+
+        let first = backlog.next_to_send().unwrap().unwrap();
+        assert_eq!((first.len, first.payload), (3, vec![3; 8]));
+        let second = backlog.next_to_send().unwrap().unwrap();
+        assert_eq!((second.len, second.payload), (2, vec![2; 8]));
+        assert!(backlog.next_to_send().unwrap().is_none());
+
+        // the batches are kept until they are acknowledged
+        assert_eq!(backlog.n_measurements(), 5);
+        backlog.acknowledge(2).unwrap();
+        assert!(backlog.is_empty());
+        assert_eq!(backlog.n_measurements(), 0);
+
+        // a batch that is bigger than the limit is kept, alone
+        assert_eq!(backlog.push(batch(1)).unwrap(), 0);
+        assert_eq!(backlog.push(batch(100)).unwrap(), 1);
+        assert_eq!(backlog.next_to_send().unwrap().unwrap().len, 100);
+        backlog.acknowledge(1).unwrap();
+        assert!(backlog.is_empty());
+    }
+
+    fn check_resend(mut backlog: Backlog) {
+        backlog.push(batch(1)).unwrap();
+        backlog.push(batch(2)).unwrap();
+        assert_eq!(backlog.next_to_send().unwrap().unwrap().len, 1);
+        assert_eq!(backlog.next_to_send().unwrap().unwrap().len, 2);
+
+        // connection lost: the batches are sent again, from the oldest
+        backlog.resend_all();
+        assert_eq!(backlog.next_to_send().unwrap().unwrap().len, 1);
+        backlog.acknowledge(1).unwrap();
+        assert_eq!(backlog.n_measurements(), 2);
+
+        // an in-flight batch is dropped: its acknowledgement does not remove another batch
+        assert_eq!(backlog.next_to_send().unwrap().unwrap().len, 2);
+        backlog.push(batch(3)).unwrap();
+        assert_eq!(backlog.push(batch(4)).unwrap(), 2);
+        assert_eq!(backlog.n_measurements(), 7);
+        backlog.acknowledge(1).unwrap();
+        assert_eq!(backlog.n_measurements(), 7);
+        assert_eq!(backlog.next_to_send().unwrap().unwrap().len, 3);
+    }
+
+    #[test]
+    fn memory() {
+        check_fifo(Backlog::in_memory(8));
+        check_resend(Backlog::in_memory(8));
+    }
+
+    #[test]
+    fn disk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("backlog");
+        check_fifo(Backlog::on_disk(dir.clone(), 8).unwrap());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        check_resend(Backlog::on_disk(dir.clone(), 8).unwrap());
+
+        // batches left by a previous run are deleted
+        let mut backlog = Backlog::on_disk(dir.clone(), 8).unwrap();
+        backlog.push(batch(1)).unwrap();
+        std::fs::write(dir.join("unrelated.txt"), "keep me").unwrap();
+        drop(backlog);
+        let backlog = Backlog::on_disk(dir.clone(), 8).unwrap();
+        assert!(backlog.is_empty());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+}
@@ -1,3 +1,4 @@
+mod backlog;
 mod output;
 mod plugin;
 mod retry;
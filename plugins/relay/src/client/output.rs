@@ -12,17 +12,45 @@ use alumet::{
 use futures::StreamExt;
 use tokio::{net::TcpStream, sync::mpsc};
 
-use crate::{client::retry::RetryState, protocol, serde_impl};
+use crate::{client::retry::RetryState, compression::Compression, protocol, tls::ClientTls, transport::Transport};
 
-use super::retry::ExponentialRetryPolicy;
+use super::{backlog::Backlog, retry::ExponentialRetryPolicy};
 
 /// Exports Alumet measurements to a relay server via TCP.
 pub struct TcpOutput {
     settings: Settings,
     alumet: AlumetLink,
-    out_relay: protocol::MessageStream<TcpStream>,
+    /// Connection to the server, `None` if it has been lost.
+    out_relay: Option<Connection>,
+    /// Set when the connection has been lost, to reconnect with an exponential backoff.
+    reconnection: Option<Reconnection>,
     buffer: MeasurementBuffer,
     buffer_last_send: Instant,
+    /// Batches that have not been sent yet, because the connection has been lost.
+    backlog: Backlog,
+    /// Compression that has been used for the last batch.
+    last_compression: Compression,
+}
+
+/// How long to wait for the acknowledgement of the last batches, when the output stops.
+const SHUTDOWN_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An open connection to the server.
+struct Connection {
+    stream: protocol::MessageStream<Transport>,
+    /// Compression of the batches, negotiated with the server.
+    compression: Compression,
+    /// Set if the server acknowledges the batches.
+    acknowledges_batches: bool,
+    /// Number of batches acknowledged by the server since the beginning of the connection.
+    n_acknowledged: u64,
+    /// Set if the server accepts the tags of the metrics.
+    metric_tags: bool,
+}
+
+struct Reconnection {
+    retry: RetryState,
+    next_attempt: tokio::time::Instant,
 }
 
 /// Links between the Alumet pipeline and the relay output.
@@ -39,14 +67,23 @@ pub struct AlumetLink {
 pub struct Settings {
     pub client_name: String,
     pub server_address: String,
+    /// If set, the connection is encrypted with TLS.
+    pub tls: Option<ClientTls>,
     pub buffer: BufferSettings,
-    pub msg_retry: ExponentialRetryPolicy,
+    /// Compression of the batches of measurements.
+    pub compression: Compression,
+    /// Retry policy of the reconnection, when the connection is lost.
+    /// There is no limit on the number of retries.
+    pub reconnect_retry: ExponentialRetryPolicy,
+    /// Retry policy of the first connection.
     pub init_retry: ExponentialRetryPolicy,
 }
 
 pub struct BufferSettings {
     pub initial_capacity: usize,
+    /// Maximum number of measurements in a batch.
     pub max_length: usize,
+    /// Maximum amount of time between two batches.
     pub timeout: Duration,
 }
 
@@ -61,12 +98,16 @@ pub enum RetryAction {
 
 impl TcpOutput {
     /// Opens a connection to a remote relay server.
-    pub async fn connect(alumet: AlumetLink, settings: Settings) -> Result<TcpOutput, protocol::Error> {
+    pub async fn connect(
+        alumet: AlumetLink,
+        settings: Settings,
+        backlog: Backlog,
+    ) -> Result<TcpOutput, protocol::Error> {
         log::info!("Connecting to relay server {}...", settings.server_address);
 
         // --- connecting
         let mut retry_state = RetryState::new(&settings.init_retry);
-        let mut res = connect_to_server(&settings.server_address, &settings.client_name, &alumet.metrics_reader).await;
+        let mut res = connect_to_server(&settings, &alumet.metrics_reader).await;
         while let Err(e) = res {
             if !retry_state.can_retry() {
                 return Err(e);
@@ -76,8 +117,7 @@ impl TcpOutput {
             match retry_action(&e) {
                 RetryAction::Fail => return Err(e),
                 RetryAction::RetryOp | RetryAction::Reconnect => {
-                    res = connect_to_server(&settings.server_address, &settings.client_name, &alumet.metrics_reader)
-                        .await;
+                    res = connect_to_server(&settings, &alumet.metrics_reader).await;
                 }
             }
         }
@@ -92,63 +132,108 @@ impl TcpOutput {
         Ok(TcpOutput {
             settings,
             alumet,
-            out_relay,
+            last_compression: out_relay.compression,
+            out_relay: Some(out_relay),
+            reconnection: None,
             buffer,
             buffer_last_send: Instant::now(),
+            backlog,
         })
     }
 
-    /// Serialize the measurements and send the result via TCP.
+    /// Buffers the measurements, and sends the buffer if it is full or if the flush interval has expired.
     async fn send_measurements(&mut self, mut measurements: MeasurementBuffer) -> Result<(), protocol::Error> {
-        let now = Instant::now();
-        let size_limit_reached = self.buffer.len() + measurements.len() > self.settings.buffer.max_length;
-        let timeout_expired = (now - self.buffer_last_send) > self.settings.buffer.timeout;
+        self.buffer.merge(&mut measurements);
+        let size_limit_reached = self.buffer.len() >= self.settings.buffer.max_length;
+        let timeout_expired = self.buffer_last_send.elapsed() >= self.settings.buffer.timeout;
+        log::trace!("size_limit_reached={size_limit_reached}, timeout_expired={timeout_expired}");
 
-        log::trace!("size_limit_reached={size_limit_reached}, timeout_expired={timeout_expired}, now={now:?}");
+        if size_limit_reached || timeout_expired {
+            self.flush().await?;
+        }
+        Ok(())
+    }
 
-        if !size_limit_reached {
-            self.buffer.merge(&mut measurements);
+    /// Compresses the buffered measurements into a batch, and sends all the pending batches.
+    async fn flush(&mut self) -> Result<(), protocol::Error> {
+        self.buffer_last_send = Instant::now();
+        if self.buffer.is_empty() {
+            return Ok(());
         }
-        // TODO it would be better to use a transform step for the buffering, wouldn't it?
+        // If the connection is lost, use the compression of the last connection: it is probably the same server.
+        let compression = self.out_relay.as_ref().map_or(self.last_compression, |c| c.compression);
+        self.last_compression = compression;
+        let batch = protocol::SendBatch::encode(&self.buffer, compression)?;
+        self.buffer.clear();
+        let n_dropped = self.backlog.push(batch)?;
+        if n_dropped > 0 {
+            log::warn!("The relay backlog is full: {n_dropped} measurements have been dropped.");
+        }
+        self.send_backlog().await
+    }
 
-        if size_limit_reached || timeout_expired {
-            self.buffer_last_send = now;
+    /// Sends the pending batches, from the oldest to the newest, until they are all sent or the connection is lost.
+    ///
+    /// The batches stay in the backlog until the server acknowledges them (see [`Self::handle_server_message`]).
+    /// If the connection is lost, they are sent again after the reconnection, from the oldest one.
+    async fn send_backlog(&mut self) -> Result<(), protocol::Error> {
+        while self.out_relay.is_some() {
+            let Some(batch) = self.backlog.next_to_send()? else {
+                break;
+            };
+            log::trace!("sending {batch:?}");
             let msg = protocol::MessageBody {
                 sender: self.settings.client_name.clone(),
-                content: protocol::MessageEnum::SendMeasurements(protocol::SendMeasurements {
-                    buf: serde_impl::SerdeMeasurementBuffer::Borrowed(&self.buffer),
-                }),
+                content: protocol::MessageEnum::SendBatch(batch),
             };
-            // --- writing
-            let mut retry_state = RetryState::new(&self.settings.msg_retry);
-            let mut res = self.out_relay.write_message(&msg).await;
-            while let Err(e) = res {
-                if !retry_state.can_retry() {
-                    return Err(e);
-                }
-                log::error!("Sending measurements failed: {e:?} - retrying...");
-                retry_state.after_attempt().await;
-                match retry_action(&e) {
-                    RetryAction::Fail => return Err(e),
-                    RetryAction::RetryOp => res = self.out_relay.write_message(&msg).await,
-                    RetryAction::Reconnect => {
-                        res = async {
-                            self.out_relay = connect_to_server(
-                                &self.settings.server_address,
-                                &self.settings.client_name,
-                                &self.alumet.metrics_reader,
-                            )
-                            .await?;
-                            self.out_relay.write_message(&msg).await
-                        }
-                        .await;
-                    }
+            let written = self.write_message(&msg).await?;
+            if written && self.out_relay.as_ref().is_some_and(|c| !c.acknowledges_batches) {
+                // The server is too old to acknowledge the batches, consider that it has received it.
+                self.backlog.acknowledge(1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a message from the server, or the error that occurred while reading it.
+    async fn handle_server_message(
+        &mut self,
+        msg: Result<protocol::MessageBody<'static>, protocol::Error>,
+    ) -> Result<(), protocol::Error> {
+        match msg {
+            Ok(protocol::MessageBody {
+                content: protocol::MessageEnum::BatchAck(ack),
+                ..
+            }) => {
+                if let Some(out_relay) = &mut self.out_relay {
+                    let n = ack.count.saturating_sub(out_relay.n_acknowledged);
+                    out_relay.n_acknowledged = ack.count;
+                    self.backlog.acknowledge(n as usize)?;
                 }
+                Ok(())
+            }
+            Ok(msg) => {
+                log::warn!("Ignoring unexpected message from the relay server: {msg:?}");
+                Ok(())
             }
-            // ---
-            self.buffer.clear();
-            if size_limit_reached {
-                self.buffer.merge(&mut measurements);
+            Err(e) => match retry_action(&e) {
+                RetryAction::Fail => Err(e),
+                RetryAction::RetryOp => Ok(()),
+                RetryAction::Reconnect => {
+                    self.connection_lost(&e);
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Waits for the acknowledgement of the batches that have been sent, or until the timeout expires.
+    async fn wait_for_acks(&mut self, timeout: Duration) -> Result<(), protocol::Error> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.out_relay.is_some() && !self.backlog.is_empty() {
+            match tokio::time::timeout_at(deadline, read_from_server(&mut self.out_relay)).await {
+                Ok(msg) => self.handle_server_message(msg).await?,
+                Err(_) => break,
             }
         }
         Ok(())
@@ -156,42 +241,76 @@ impl TcpOutput {
 
     /// Sends metric definitions via TCP.
     async fn send_metrics(&mut self, metrics_buf: &mut Vec<Vec<(RawMetricId, Metric)>>) -> Result<(), protocol::Error> {
-        let iterable = metrics_buf.drain(..).flatten();
-        let to_send: Vec<_> = iterable.into_iter().map(protocol::Metric::from).collect();
-
+        let with_tags = self.out_relay.as_ref().is_some_and(|c| c.metric_tags);
         let msg = protocol::MessageBody {
             sender: self.settings.client_name.clone(),
-            content: protocol::MessageEnum::RegisterMetrics(protocol::RegisterMetrics { metrics: to_send }),
+            content: protocol::MessageEnum::register_metrics(metrics_buf.drain(..).flatten(), with_tags),
         };
+        // If the connection is lost, there is nothing to do: all the metrics will be sent after the reconnection.
+        self.write_message(&msg).await?;
+        Ok(())
+    }
 
-        // NOTE: To make this code generic on the operation, we need either a macro,
-        // or the upcoming async closures (https://github.com/rust-lang/rust/pull/132706).
-        let mut retry_state = RetryState::new(&self.settings.msg_retry);
-        let mut res = self.out_relay.write_message(&msg).await;
-        while let Err(e) = res {
-            if !retry_state.can_retry() {
-                return Err(e);
+    /// Writes a message to the server.
+    ///
+    /// Returns `false` if the message has not been written because the connection has been lost.
+    /// In that case, the reconnection is scheduled.
+    async fn write_message(&mut self, msg: &protocol::MessageBody<'_>) -> Result<bool, protocol::Error> {
+        while let Some(out_relay) = &mut self.out_relay {
+            match out_relay.stream.write_message(msg).await {
+                Ok(()) => return Ok(true),
+                Err(e) => match retry_action(&e) {
+                    RetryAction::Fail => return Err(e),
+                    RetryAction::RetryOp => continue,
+                    RetryAction::Reconnect => self.connection_lost(&e),
+                },
             }
-            log::error!("Sending metrics failed: {e:?} - retrying...");
-            retry_state.after_attempt().await;
-            match retry_action(&e) {
-                RetryAction::Fail => return Err(e),
-                RetryAction::RetryOp => res = self.out_relay.write_message(&msg).await,
-                RetryAction::Reconnect => {
-                    res = async {
-                        self.out_relay = connect_to_server(
-                            &self.settings.server_address,
-                            &self.settings.client_name,
-                            &self.alumet.metrics_reader,
-                        )
-                        .await?;
-                        self.out_relay.write_message(&msg).await
-                    }
-                    .await;
-                }
+        }
+        Ok(false)
+    }
+
+    /// Closes the connection and schedules the reconnection.
+    ///
+    /// The batches that have not been acknowledged will be sent again.
+    fn connection_lost(&mut self, e: &protocol::Error) {
+        log::error!(
+            "Connection to relay server lost: {e:?} - the measurements will be kept in the backlog until the connection is back."
+        );
+        self.out_relay = None;
+        self.backlog.resend_all();
+        let mut retry = RetryState::new(&self.settings.reconnect_retry);
+        let next_attempt = tokio::time::Instant::now() + retry.next_delay();
+        self.reconnection = Some(Reconnection { retry, next_attempt });
+    }
+
+    /// Tries to reconnect to the server, and sends the pending batches if it succeeds.
+    async fn reconnect(&mut self) -> Result<(), protocol::Error> {
+        let Some(reconnection) = &mut self.reconnection else {
+            return Ok(());
+        };
+        match connect_to_server(&self.settings, &self.alumet.metrics_reader).await {
+            Ok(out_relay) => {
+                log::info!(
+                    "Reconnected to relay server, sending {} pending measurements.",
+                    self.backlog.n_measurements()
+                );
+                self.out_relay = Some(out_relay);
+                self.reconnection = None;
+                self.send_backlog().await
             }
+            Err(e) => match retry_action(&e) {
+                RetryAction::Fail => Err(e),
+                RetryAction::RetryOp | RetryAction::Reconnect => {
+                    let delay = reconnection.retry.next_delay();
+                    log::warn!(
+                        "Reconnection to relay server failed: {e:?} - retrying in {delay:?} ({} measurements pending)",
+                        self.backlog.n_measurements()
+                    );
+                    reconnection.next_attempt = tokio::time::Instant::now() + delay;
+                    Ok(())
+                }
+            },
         }
-        Ok(())
     }
 
     /// Continuously polls new measurements and metrics, and sends them via TCP.
@@ -204,6 +323,9 @@ impl TcpOutput {
                 // used to produce the measurements (= the registry known by the server is up to date for
                 // this measurement buffer).
                 let mut metrics_buf = Vec::with_capacity(8);
+                let flush_deadline =
+                    tokio::time::Instant::from_std(self.buffer_last_send + self.settings.buffer.timeout);
+                let reconnect_deadline = self.reconnection.as_ref().map(|r| r.next_attempt);
                 tokio::select! {
                     biased;
                     n_metrics = self.alumet.in_metrics.recv_many(&mut metrics_buf, 8) => {
@@ -213,6 +335,12 @@ impl TcpOutput {
                         }
                         self.send_metrics(&mut metrics_buf).await?;
                     }
+                    msg = read_from_server(&mut self.out_relay) => {
+                        self.handle_server_message(msg).await?;
+                    }
+                    _ = tokio::time::sleep_until(reconnect_deadline.unwrap_or(flush_deadline)), if reconnect_deadline.is_some() => {
+                        self.reconnect().await?;
+                    }
                     measurements = self.alumet.in_measurements.0.next() => {
                         match measurements {
                             Some(Ok(buf)) => self.send_measurements(buf).await?,
@@ -229,13 +357,39 @@ impl TcpOutput {
                             }
                         };
                     },
+                    _ = tokio::time::sleep_until(flush_deadline), if !self.buffer.is_empty() => {
+                        // no measurement for a while, send what we have
+                        self.flush().await?;
+                    }
                 };
             }
+            // send the last measurements, with a last attempt to reconnect if needed
+            self.flush().await?;
+            self.reconnect().await?;
+            self.wait_for_acks(SHUTDOWN_ACK_TIMEOUT).await?;
+            if !self.backlog.is_empty() {
+                log::error!(
+                    "{} measurements could not be sent to the relay server before the shutdown.",
+                    self.backlog.n_measurements()
+                );
+            }
             Ok(())
         }
     }
 }
 
+/// Reads the next message from the server, or waits forever if the connection is lost.
+///
+/// This function is cancel-safe.
+async fn read_from_server(
+    out_relay: &mut Option<Connection>,
+) -> Result<protocol::MessageBody<'static>, protocol::Error> {
+    match out_relay {
+        Some(connection) => connection.stream.read_message().await,
+        None => std::future::pending().await,
+    }
+}
+
 fn retry_action(err: &protocol::Error) -> RetryAction {
     match err {
         protocol::Error::Io(error) => {
@@ -257,51 +411,79 @@ fn retry_action(err: &protocol::Error) -> RetryAction {
                 }
             }
         }
+        // the server has closed the connection, for instance because it restarts
+        protocol::Error::Disconnected => RetryAction::Reconnect,
         _ => RetryAction::Fail,
     }
 }
 
-#[must_use]
-async fn connect_to_server(
-    server_addr: &str,
-    client_name: &str,
-    metrics_reader: &MetricReader,
-) -> Result<protocol::MessageStream<TcpStream>, protocol::Error> {
+async fn connect_to_server(settings: &Settings, metrics_reader: &MetricReader) -> Result<Connection, protocol::Error> {
+    let client_name = &settings.client_name;
+
     // open the TCP connection
     log::debug!("Opening TCP connection...");
-    let stream = TcpStream::connect(server_addr).await?;
+    let stream = TcpStream::connect(&settings.server_address).await?;
+
+    // encrypt it
+    let stream = match &settings.tls {
+        Some(tls) => {
+            log::debug!("Doing TLS handshake...");
+            tls.connect(stream).await?
+        }
+        None => Transport::from(stream),
+    };
 
     // do the protocol handshake
     log::debug!("Doing protocol handshake...");
-    let mut stream = handshake_client2server(client_name.to_owned(), stream).await?;
+    let (mut stream, server_features) = handshake_client2server(client_name.to_owned(), stream).await?;
 
     // send the metric definitions (for metrics that are known at this point)
     log::debug!("Sending initial metrics...");
     let metrics = metrics_reader.read().await;
-    let to_send = metrics
-        .iter()
-        .map(|(id, def)| protocol::Metric::from((*id, def.to_owned())))
-        .collect();
+    let metric_tags = server_features.supports(protocol::feature::METRIC_TAGS);
+    let to_send = metrics.iter().map(|(id, def)| (*id, def.to_owned()));
     let msg = protocol::MessageBody {
         sender: client_name.to_owned(),
-        content: protocol::MessageEnum::RegisterMetrics(protocol::RegisterMetrics { metrics: to_send }),
+        content: protocol::MessageEnum::register_metrics(to_send, metric_tags),
     };
     stream.write_message(&msg).await?;
 
     // done
-    Ok(stream)
+    let compression = negotiate_compression(settings.compression, &server_features);
+    Ok(Connection {
+        stream,
+        compression,
+        acknowledges_batches: server_features.supports(protocol::feature::BATCH_ACK),
+        n_acknowledged: 0,
+        metric_tags,
+    })
 }
 
+/// Returns the compression to use with the server: the requested one if the server supports it, no compression otherwise.
+fn negotiate_compression(requested: Compression, server_features: &protocol::Features) -> Compression {
+    if server_features.supports_compression(requested) {
+        requested
+    } else {
+        log::warn!(
+            "The relay server does not support the compression {requested:?}, the measurements will not be compressed."
+        );
+        Compression::None
+    }
+}
+
+/// Greets the server and exchanges the features supported by the client and the server.
+///
+/// Returns the features of the server.
 async fn handshake_client2server(
     client_name: String,
-    stream: TcpStream,
-) -> Result<protocol::MessageStream<TcpStream>, protocol::Error> {
+    stream: Transport,
+) -> Result<(protocol::MessageStream<Transport>, protocol::Features), protocol::Error> {
     let mut out_relay = protocol::MessageStream::new(stream);
 
     // send greeting
     out_relay
         .write_message(&protocol::MessageBody {
-            sender: client_name,
+            sender: client_name.clone(),
             content: protocol::MessageEnum::Greet(protocol::Greet {
                 alumet_core_version: String::from(alumet::VERSION),
                 relay_plugin_version: String::from(crate::PLUGIN_VERSION),
@@ -322,7 +504,24 @@ async fn handshake_client2server(
                 response.server_relay_plugin_version,
                 response.protocol_version
             );
-            Ok(out_relay)
+            let server_features = if response.protocol_version >= protocol::FEATURES_VERSION {
+                let msg = out_relay.read_message().await?;
+                let protocol::MessageEnum::Features(server_features) = msg.content else {
+                    log::error!("Cannot connect: expected the features of the server, received {msg:?}");
+                    return Err(protocol::Error::Unexpected);
+                };
+                out_relay
+                    .write_message(&protocol::MessageBody {
+                        sender: client_name,
+                        content: protocol::MessageEnum::Features(protocol::Features::local()),
+                    })
+                    .await?;
+                server_features
+            } else {
+                protocol::Features::implied_by(response.protocol_version)
+            };
+            log::debug!("The server supports {server_features:?}");
+            Ok((out_relay, server_features))
         } else {
             log::error!(
                 "Cannot connect: client and server are incompatible.
@@ -345,3 +544,19 @@ async fn handshake_client2server(
         Err(protocol::Error::Unexpected)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::negotiate_compression;
+    use crate::{compression::Compression, protocol::Features};
+
+    #[test]
+    fn compression() {
+        let local = Features::local();
+        assert_eq!(negotiate_compression(Compression::Zstd, &local), Compression::Zstd);
+        assert_eq!(negotiate_compression(Compression::None, &local), Compression::None);
+        // the server is too old
+        let v2 = Features::implied_by(2);
+        assert_eq!(negotiate_compression(Compression::Lz4, &v2), Compression::None);
+    }
+}
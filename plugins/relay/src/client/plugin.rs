@@ -2,12 +2,14 @@ use alumet::metrics::{Metric, RawMetricId};
 use alumet::pipeline::elements::output::BoxedAsyncOutput;
 use alumet::plugin::{
     AlumetPluginStart, ConfigTable,
+    capability::Capability,
     rust::{AlumetPlugin, deserialize_config, serialize_config},
 };
 use anyhow::Context;
 use tokio::sync::mpsc;
 
-use crate::client::output;
+use crate::client::{backlog::Backlog, output};
+use crate::tls::ClientTls;
 
 use super::retry::ExponentialRetryPolicy;
 
@@ -16,10 +18,12 @@ pub struct RelayClientPlugin {
 }
 
 mod config {
-    use std::time::Duration;
+    use std::{path::PathBuf, time::Duration};
 
     use serde::{Deserialize, Serialize};
 
+    use crate::{compression::Compression, tls::ClientTlsConfig};
+
     #[derive(Serialize, Deserialize)]
     #[serde(deny_unknown_fields)]
     pub struct Config {
@@ -32,17 +36,32 @@ mod config {
         #[serde(default = "default_relay_server_address")]
         pub relay_server: String,
 
-        /// Maximum number of elements to keep in the output buffer before sending it.
+        /// Maximum number of measurements to send in one batch.
+        #[serde(alias = "batch_size")]
         pub buffer_max_length: usize,
 
-        /// Maximum amount of time to wait before sending the measurements to the server.
-        #[serde(with = "humantime_serde")]
+        /// Maximum amount of time to wait before sending a batch of measurements to the server.
+        #[serde(alias = "flush_interval", with = "humantime_serde")]
         pub buffer_timeout: Duration,
 
+        /// Compression of the batches of measurements: `none`, `lz4` or `zstd`.
+        #[serde(default)]
+        pub compression: Compression,
+
         /// Parameter of the exponential backoff strategy that is applied when a network operation fails.
         ///
         /// The delay is multiplied by two after each attempt.
+        /// `max_times` only applies to the first connection: when the connection is lost,
+        /// the client tries to reconnect until it succeeds.
         pub retry: RetryConfig,
+
+        /// Storage of the measurements while the connection to the server is lost.
+        #[serde(default)]
+        pub backlog: BacklogConfig,
+
+        /// If set, the connection to the server is encrypted with TLS.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tls: Option<ClientTlsConfig>,
     }
 
     #[derive(Serialize, Deserialize)]
@@ -60,6 +79,26 @@ mod config {
         pub max_delay: Duration,
     }
 
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct BacklogConfig {
+        /// Maximum number of measurements to keep. When the limit is reached, the oldest measurements are dropped.
+        pub max_measurements: usize,
+
+        /// If set, the measurements are stored in this directory instead of in memory.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub directory: Option<PathBuf>,
+    }
+
+    impl Default for BacklogConfig {
+        fn default() -> Self {
+            Self {
+                max_measurements: 1_000_000,
+                directory: None,
+            }
+        }
+    }
+
     impl Default for Config {
         fn default() -> Self {
             Self {
@@ -67,7 +106,10 @@ mod config {
                 relay_server: default_relay_server_address(),
                 buffer_max_length: 4096,
                 buffer_timeout: Duration::from_secs(30),
+                compression: Compression::None,
                 retry: RetryConfig::default(),
+                backlog: BacklogConfig::default(),
+                tls: None,
             }
         }
     }
@@ -102,6 +144,10 @@ impl AlumetPlugin for RelayClientPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         Ok(Some(serialize_config(config::Config::default())?))
     }
@@ -117,15 +163,27 @@ impl AlumetPlugin for RelayClientPlugin {
     fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
         // Prepare the values that will be moved to the closure.
         let config = self.config.take().unwrap();
+        let tls = config
+            .tls
+            .map(|tls| ClientTls::new(tls, &config.relay_server))
+            .transpose()
+            .context("invalid TLS configuration")?;
+        let backlog = match config.backlog.directory {
+            Some(dir) => Backlog::on_disk(dir, config.backlog.max_measurements)
+                .context("failed to prepare the backlog directory")?,
+            None => Backlog::in_memory(config.backlog.max_measurements),
+        };
         let client_settings = output::Settings {
             client_name: config.client_name,
             server_address: config.relay_server,
+            tls,
             buffer: output::BufferSettings {
                 initial_capacity: 512,
                 max_length: config.buffer_max_length,
                 timeout: config.buffer_timeout,
             },
-            msg_retry: ExponentialRetryPolicy {
+            compression: config.compression,
+            reconnect_retry: ExponentialRetryPolicy {
                 max_retrys: config.retry.max_times,
                 initial_delay: config.retry.initial_delay,
                 max_delay: config.retry.max_delay,
@@ -153,7 +211,7 @@ impl AlumetPlugin for RelayClientPlugin {
 
             let tcp = ctx
                 .async_runtime()
-                .block_on(super::output::TcpOutput::connect(alumet_link, client_settings))
+                .block_on(super::output::TcpOutput::connect(alumet_link, client_settings, backlog))
                 .context("relay connection error")?;
 
             let output: BoxedAsyncOutput = Box::pin(tcp.send_loop());
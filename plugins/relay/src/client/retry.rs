@@ -33,6 +33,13 @@ impl RetryState {
         self.delay = (self.delay * self.policy.multiplier.into()).min(self.policy.max_delay);
     }
 
+    /// Returns the delay to wait before the next attempt, and counts the attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.count_and_increase_delay();
+        delay
+    }
+
     pub async fn after_attempt(&mut self) {
         tokio::time::sleep(self.delay).await;
         self.count_and_increase_delay();
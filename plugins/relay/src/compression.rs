@@ -0,0 +1,84 @@
+//! Compression of the measurement batches.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+/// Compression level of zstd, from 1 (fastest) to 22 (smallest output).
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compression algorithm applied to the batches of measurements.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// No compression.
+    #[default]
+    None,
+    /// LZ4: fast, with a moderate compression ratio.
+    Lz4,
+    /// Zstandard: slower than LZ4, with a better compression ratio.
+    Zstd,
+}
+
+impl Compression {
+    /// Compresses `data`.
+    pub fn compress(self, data: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data),
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(&data)),
+            Compression::Zstd => zstd::bulk::compress(&data, ZSTD_LEVEL),
+        }
+    }
+
+    /// Decompresses `data`.
+    ///
+    /// Returns an error if the decompressed data would be larger than `max_size` bytes,
+    /// which protects the server against "compression bombs".
+    pub fn decompress(self, data: Vec<u8>, max_size: usize) -> io::Result<Vec<u8>> {
+        let too_big = || io::Error::new(io::ErrorKind::InvalidData, "decompressed batch is too big");
+        match self {
+            Compression::None => Ok(data),
+            Compression::Lz4 => {
+                // the uncompressed size is prepended, as a little-endian u32
+                let size_bytes: [u8; 4] = data
+                    .get(..4)
+                    .and_then(|b| b.try_into().ok())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated lz4 batch"))?;
+                if u32::from_le_bytes(size_bytes) as usize > max_size {
+                    return Err(too_big());
+                }
+                lz4_flex::decompress_size_prepended(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            Compression::Zstd => zstd::bulk::decompress(&data, max_size)
+                .map_err(|e| if e.kind() == io::ErrorKind::Other { too_big() } else { e }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compression;
+
+    #[test]
+    fn roundtrip() {
+        let data: Vec<u8> = (0..10_000u32).flat_map(|i| (i % 100).to_le_bytes()).collect();
+        for c in [Compression::None, Compression::Lz4, Compression::Zstd] {
+            let compressed = c.compress(data.clone()).unwrap();
+            if c != Compression::None {
+                assert!(compressed.len() < data.len() / 4, "{c:?} should compress the data");
+            }
+            let decompressed = c.decompress(compressed, data.len()).unwrap();
+            assert_eq!(decompressed, data, "{c:?} roundtrip failed");
+        }
+    }
+
+    #[test]
+    fn size_limit() {
+        let data = vec![0u8; 100_000];
+        for c in [Compression::Lz4, Compression::Zstd] {
+            let compressed = c.compress(data.clone()).unwrap();
+            c.decompress(compressed, 1000)
+                .expect_err("the decompressed size should exceed the limit");
+        }
+    }
+}
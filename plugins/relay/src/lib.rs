@@ -3,7 +3,10 @@ pub mod client;
 #[cfg(feature = "server")]
 pub mod server;
 
+pub mod compression;
 mod protocol;
 mod serde_impl;
+pub mod tls;
+mod transport;
 
 pub const PLUGIN_VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -2,22 +2,42 @@
 
 use std::{io, time::Duration};
 
-use alumet::{measurement::WrappedMeasurementType, metrics::RawMetricId, units::PrefixedUnit};
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementType},
+    metrics::RawMetricId,
+    units::PrefixedUnit,
+};
 use anyhow::Context;
 use bytes::BytesMut;
 use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
-    net::TcpStream,
     time::error::Elapsed,
 };
 
+use crate::compression::Compression;
 use crate::serde_impl;
+use crate::transport::Transport;
 
 /// Version number of the current protocol.
 ///
 /// IMPORTANT: you must increase this number when the protocol changes.
-pub const PROTOCOL_VERSION: u32 = 2;
+/// To keep the compatibility with older peers, only add new variants at the end of [`MessageEnum`],
+/// and never change the [`Greet`] and [`GreetResponse`] messages.
+pub const PROTOCOL_VERSION: u32 = 6;
+
+/// Oldest protocol version that the server accepts.
+///
+/// This allows to upgrade the server before the clients.
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+/// First protocol version with the [`Features`] message.
+pub const FEATURES_VERSION: u32 = 4;
+
+/// Returns `true` if a peer that uses the given protocol version can connect to us.
+pub fn is_supported(protocol_version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&protocol_version)
+}
 
 /// Maximum size (in bytes) of a message body.
 ///
@@ -71,6 +91,10 @@ pub enum MessageEnum<'s> {
     GreetResponse(GreetResponse),
     RegisterMetrics(RegisterMetrics),
     SendMeasurements(SendMeasurements<'s>),
+    SendBatch(SendBatch),
+    Features(Features),
+    BatchAck(BatchAck),
+    RegisterTaggedMetrics(RegisterTaggedMetrics),
 }
 
 /// Sent by the client at the beginning of the connection.
@@ -94,6 +118,20 @@ pub struct RegisterMetrics {
     pub metrics: Vec<Metric>,
 }
 
+/// Like [`RegisterMetrics`], with the tags of the metrics.
+///
+/// Sent instead of [`RegisterMetrics`] if the server supports [`feature::METRIC_TAGS`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterTaggedMetrics {
+    pub metrics: Vec<TaggedMetric>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaggedMetric {
+    pub metric: Metric,
+    pub tags: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Metric {
     pub id: u64,
@@ -119,6 +157,125 @@ pub struct SendMeasurements<'s> {
     pub buf: serde_impl::SerdeMeasurementBuffer<'s>,
 }
 
+/// Sent by the server when it has received some batches, if the client supports [`feature::BATCH_ACK`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchAck {
+    /// Number of batches received since the beginning of the connection.
+    pub count: u64,
+}
+
+/// Optional features of the protocol, sent by each peer after the greeting.
+///
+/// Features are identified by strings, so that a peer can ignore the features that it does not know.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Features {
+    pub features: Vec<String>,
+}
+
+/// Names of the features.
+pub mod feature {
+    /// Batches of measurements ([`SendBatch`](super::SendBatch)).
+    pub const BATCH: &str = "batch";
+    pub const COMPRESSION_LZ4: &str = "compression:lz4";
+    pub const COMPRESSION_ZSTD: &str = "compression:zstd";
+    pub const VALUE_F64: &str = "value:f64";
+    pub const VALUE_U64: &str = "value:u64";
+    /// The metrics can be registered at any time, and are registered again after a reconnection.
+    pub const METRIC_SYNC: &str = "metric-sync";
+    /// The server acknowledges the batches ([`BatchAck`](super::BatchAck)).
+    pub const BATCH_ACK: &str = "batch-ack";
+    /// The metrics are registered with their tags ([`RegisterTaggedMetrics`](super::RegisterTaggedMetrics)).
+    pub const METRIC_TAGS: &str = "metric-tags";
+}
+
+impl Features {
+    /// The features supported by this version of the relay plugin.
+    pub fn local() -> Self {
+        Self::implied_by(PROTOCOL_VERSION)
+    }
+
+    /// The features supported by a peer that uses the given protocol version.
+    ///
+    /// This is useful for peers that are too old to send their features.
+    pub fn implied_by(protocol_version: u32) -> Self {
+        let mut features = vec![feature::VALUE_F64, feature::VALUE_U64, feature::METRIC_SYNC];
+        if protocol_version >= 3 {
+            features.extend([feature::BATCH, feature::COMPRESSION_LZ4, feature::COMPRESSION_ZSTD]);
+        }
+        if protocol_version >= 5 {
+            features.push(feature::BATCH_ACK);
+        }
+        if protocol_version >= 6 {
+            features.push(feature::METRIC_TAGS);
+        }
+        Self {
+            features: features.into_iter().map(String::from).collect(),
+        }
+    }
+
+    /// Returns `true` if the feature is supported.
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Returns `true` if the compression algorithm is supported.
+    pub fn supports_compression(&self, compression: Compression) -> bool {
+        match compression {
+            Compression::None => true,
+            Compression::Lz4 => self.supports(feature::BATCH) && self.supports(feature::COMPRESSION_LZ4),
+            Compression::Zstd => self.supports(feature::BATCH) && self.supports(feature::COMPRESSION_ZSTD),
+        }
+    }
+}
+
+/// A batch of measurements, serialized and compressed.
+///
+/// Unlike [`SendMeasurements`], the measurements are serialized separately from the message,
+/// so that they can be compressed as a whole.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SendBatch {
+    /// The algorithm that has been used to compress the payload.
+    pub compression: Compression,
+    /// Number of measurements in the batch.
+    pub len: u32,
+    /// The compressed measurements.
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+}
+
+impl SendBatch {
+    /// Serializes and compresses the measurements.
+    pub fn encode(measurements: &MeasurementBuffer, compression: Compression) -> Result<Self, Error> {
+        let serialized = postcard::to_allocvec(&serde_impl::SerdeMeasurementBuffer::Borrowed(measurements))?;
+        let payload = compression.compress(serialized)?;
+        Ok(Self {
+            compression,
+            len: measurements.len() as u32,
+            payload,
+        })
+    }
+
+    /// Decompresses and deserializes the measurements.
+    pub fn decode(self) -> Result<MeasurementBuffer, Error> {
+        let serialized = self
+            .compression
+            .decompress(self.payload, MAX_MESSAGE_BODY_SIZE as usize)?;
+        let buf: serde_impl::SerdeMeasurementBuffer = postcard::from_bytes(&serialized)?;
+        Ok(buf.owned())
+    }
+}
+
+impl std::fmt::Debug for SendBatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // don't print the payload, it is not human-readable
+        f.debug_struct("SendBatch")
+            .field("compression", &self.compression)
+            .field("len", &self.len)
+            .field("payload_size", &self.payload.len())
+            .finish()
+    }
+}
+
 /// Allows to read/write protocol messages from/to an asynchronous IO stream.
 ///
 /// # Coherency
@@ -166,6 +323,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
 
         // write to the underlying data stream (tcp socket)
         self.stream.write_all(&self.serializer.output.bytes).await?;
+        self.stream.flush().await?; // required by TLS, no-op on TCP
         self.serializer.output.bytes.clear();
         Ok(())
     }
@@ -179,13 +337,14 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
         // First, deserialize the next message header. We need 4 bytes.
         // Then, deserialize the message body.
 
-        // Read from the tcp socket until we get 4 bytes
-        let mut header_read = 0;
-        while header_read < 4 {
+        // Read from the tcp socket until we get 4 bytes.
+        // The buffer may already contain the beginning of the message, or the whole message, if it
+        // has been received with the previous one. Since every byte that is read is kept in the buffer,
+        // this function is cancel-safe: it can be used in `select!`.
+        while self.deserialization_buffer.len() < 4 {
             let n = self.stream.read_buf(&mut self.deserialization_buffer).await?;
-            header_read += n;
             if n == 0 {
-                if header_read == 0 {
+                if self.deserialization_buffer.is_empty() {
                     return Err(Error::Disconnected);
                 } else {
                     return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
@@ -216,7 +375,10 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
 
         // Read more data if required.
         while self.deserialization_buffer.len() < message_len {
-            self.stream.read_buf(&mut self.deserialization_buffer).await?;
+            let n = self.stream.read_buf(&mut self.deserialization_buffer).await?;
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
         }
 
         // Take the data
@@ -263,7 +425,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> MessageStream<S> {
     }
 }
 
-impl MessageStream<TcpStream> {
+impl MessageStream<Transport> {
     pub fn peer_addr(&self) -> Result<std::net::SocketAddr, std::io::Error> {
         self.stream.peer_addr()
     }
@@ -352,6 +514,34 @@ impl From<MetricType> for WrappedMeasurementType {
     }
 }
 
+impl MessageEnum<'_> {
+    /// Returns the message that registers the given metrics on the server.
+    ///
+    /// The tags of the metrics are only sent if the server supports [`feature::METRIC_TAGS`].
+    pub fn register_metrics(
+        metrics: impl IntoIterator<Item = (RawMetricId, alumet::metrics::Metric)>,
+        with_tags: bool,
+    ) -> Self {
+        let metrics = metrics.into_iter();
+        if with_tags {
+            let metrics = metrics
+                .map(|(id, mut def)| {
+                    let tags = std::mem::take(&mut def.tags);
+                    TaggedMetric {
+                        metric: Metric::from((id, def)),
+                        tags,
+                    }
+                })
+                .collect();
+            MessageEnum::RegisterTaggedMetrics(RegisterTaggedMetrics { metrics })
+        } else {
+            MessageEnum::RegisterMetrics(RegisterMetrics {
+                metrics: metrics.map(Metric::from).collect(),
+            })
+        }
+    }
+}
+
 impl From<(RawMetricId, alumet::metrics::Metric)> for Metric {
     fn from(value: (RawMetricId, alumet::metrics::Metric)) -> Self {
         let (id, def) = value;
@@ -366,10 +556,137 @@ impl From<(RawMetricId, alumet::metrics::Metric)> for Metric {
 
 #[cfg(test)]
 mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use alumet::{
+        measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+    };
+
+    use super::{
+        BatchAck, Features, MessageBody, MessageEnum, MessageStream, PROTOCOL_VERSION, SendBatch, feature, is_supported,
+    };
+    use crate::compression::Compression;
 
     #[test]
     fn test_message_rw_simple() -> anyhow::Result<()> {
         // TODO
         Ok(())
     }
+
+    #[tokio::test]
+    async fn read_messages_received_together() -> anyhow::Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        let mut client = MessageStream::new(client);
+        let mut server = MessageStream::new(server);
+        // the messages arrive in the same read
+        let mut bytes = Vec::new();
+        for count in [1, 2, 3] {
+            server.serialize_full_message(&MessageBody {
+                sender: String::new(),
+                content: MessageEnum::BatchAck(BatchAck { count }),
+            })?;
+            bytes.extend(&server.serializer.output.bytes);
+        }
+        tokio::io::AsyncWriteExt::write_all(&mut server.stream, &bytes).await?;
+
+        for expected in [1, 2, 3] {
+            let msg = client.read_timeout(std::time::Duration::from_secs(1)).await??;
+            let MessageEnum::BatchAck(ack) = msg.content else {
+                panic!("unexpected message {msg:?}");
+            };
+            assert_eq!(ack.count, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn versions() {
+        assert!(is_supported(PROTOCOL_VERSION));
+        assert!(is_supported(2));
+        assert!(!is_supported(1));
+        assert!(!is_supported(PROTOCOL_VERSION + 1));
+    }
+
+    #[test]
+    fn features() {
+        let v2 = Features::implied_by(2);
+        assert!(v2.supports(feature::VALUE_U64));
+        assert!(!v2.supports(feature::BATCH));
+        assert!(!Features::implied_by(4).supports(feature::BATCH_ACK));
+        assert!(!Features::implied_by(5).supports(feature::METRIC_TAGS));
+        assert!(v2.supports_compression(Compression::None));
+        assert!(!v2.supports_compression(Compression::Zstd));
+
+        let local = Features::local();
+        assert!(local.supports(feature::BATCH));
+        assert!(local.supports(feature::BATCH_ACK));
+        assert!(local.supports(feature::METRIC_TAGS));
+        assert!(local.supports_compression(Compression::Lz4));
+        assert!(local.supports_compression(Compression::Zstd));
+
+        // unknown features are ignored
+        let future = Features {
+            features: vec![String::from("compression:brotli"), String::from(feature::BATCH)],
+        };
+        assert!(future.supports(feature::BATCH));
+        assert!(!future.supports_compression(Compression::Zstd));
+    }
+
+    #[test]
+    fn register_metrics_tags() {
+        let metric = || alumet::metrics::Metric {
+            name: String::from("energy"),
+            description: String::new(),
+            value_type: alumet::measurement::WrappedMeasurementType::F64,
+            unit: alumet::units::Unit::Joule.into(),
+            tags: vec![String::from("hardware")],
+        };
+        let id = RawMetricId::from_u64(1);
+
+        let MessageEnum::RegisterTaggedMetrics(tagged) = MessageEnum::register_metrics([(id, metric())], true) else {
+            panic!("the tags should be sent");
+        };
+        assert_eq!(tagged.metrics[0].metric.name, "energy");
+        assert_eq!(tagged.metrics[0].tags, vec![String::from("hardware")]);
+
+        let MessageEnum::RegisterMetrics(plain) = MessageEnum::register_metrics([(id, metric())], false) else {
+            panic!("the tags should not be sent");
+        };
+        assert_eq!(plain.metrics[0].name, "energy");
+    }
+
+    #[test]
+    fn batch_roundtrip() -> anyhow::Result<()> {
+        let points = (0..1000u64).map(|i| {
+            MeasurementPoint::new_untyped(
+                Timestamp::from(UNIX_EPOCH),
+                RawMetricId::from_u64(i % 4),
+                Resource::CpuPackage { id: (i % 2) as u32 },
+                ResourceConsumer::LocalMachine,
+                WrappedMeasurementValue::U64(i),
+            )
+            .with_attr("node", String::from("node-1"))
+        });
+        let buf = MeasurementBuffer::from_iter(points);
+        let uncompressed_size = SendBatch::encode(&buf, Compression::None)?.payload.len();
+
+        for compression in [Compression::None, Compression::Lz4, Compression::Zstd] {
+            let batch = SendBatch::encode(&buf, compression)?;
+            assert_eq!(batch.len, 1000);
+            if compression != Compression::None {
+                assert!(batch.payload.len() < uncompressed_size);
+            }
+            let decoded = batch.decode()?;
+            assert_eq!(decoded.len(), buf.len());
+            for (a, b) in decoded.iter().zip(buf.iter()) {
+                assert_eq!(a.metric, b.metric);
+                assert_eq!(a.value, b.value);
+                assert_eq!(a.resource, b.resource);
+                assert_eq!(a.attributes().collect::<Vec<_>>(), b.attributes().collect::<Vec<_>>());
+            }
+        }
+        Ok(())
+    }
 }
@@ -1,5 +1,6 @@
 use std::net::ToSocketAddrs;
 
+use alumet::plugin::capability::Capability;
 use alumet::plugin::{
     AlumetPluginStart, ConfigTable,
     rust::{AlumetPlugin, deserialize_config, serialize_config},
@@ -9,6 +10,7 @@ use serde::{Deserialize, Serialize};
 use tokio::net::TcpListener;
 
 use crate::server::source;
+use crate::tls::{ServerTls, ServerTlsConfig};
 
 pub struct RelayServerPlugin {
     config: Config,
@@ -23,12 +25,17 @@ struct Config {
     /// For information, ip6-localhost is `::1`.
     /// To listen to all your network interfaces please use `0.0.0.0` or `::`.
     address: String,
+
+    /// If set, the connections are encrypted with TLS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tls: Option<ServerTlsConfig>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             address: String::from("[::]:50051"), // "any" on ipv6
+            tls: None,
         }
     }
 }
@@ -42,6 +49,10 @@ impl AlumetPlugin for RelayServerPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         let config = serialize_config(Config::default())?;
         Ok(Some(config))
@@ -60,6 +71,13 @@ impl AlumetPlugin for RelayServerPlugin {
             .to_socket_addrs()
             .with_context(|| format!("invalid socket address: {addr}"))?
             .collect();
+        let tls = self
+            .config
+            .tls
+            .take()
+            .map(ServerTls::new)
+            .transpose()
+            .context("invalid TLS configuration")?;
 
         // Register the source builder.
         alumet.add_autonomous_source_builder("tcp_server", move |ctx, cancel_token, out_tx| {
@@ -68,7 +86,7 @@ impl AlumetPlugin for RelayServerPlugin {
             let source = Box::pin(async move {
                 // `bind` loops through all the addresses that correspond to the string
                 let listener = TcpListener::bind(addr.as_slice()).await.context("tcp binding failed")?;
-                let server = source::TcpServer::new(cancel_token, listener, out_tx, metrics_tx);
+                let server = source::TcpServer::new(cancel_token, listener, tls, out_tx, metrics_tx);
                 server.accept_loop().await
             });
             Ok(source)
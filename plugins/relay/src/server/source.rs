@@ -7,20 +7,32 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::protocol::{self, GreetResponse, MessageBody, MessageEnum, MessageStream, PROTOCOL_VERSION};
+use crate::{
+    protocol::{
+        self, BatchAck, FEATURES_VERSION, Features, GreetResponse, MIN_PROTOCOL_VERSION, MessageBody, MessageEnum,
+        MessageStream, PROTOCOL_VERSION, feature,
+    },
+    tls::{self, ServerTls},
+    transport::Transport,
+};
 
 use super::metrics::MetricConverter;
 
 pub struct TcpSource {
     cancel_token: CancellationToken,
-    tcp: MessageStream<TcpStream>,
+    tcp: MessageStream<Transport>,
     out_tx: mpsc::Sender<MeasurementBuffer>,
     metrics: MetricConverter,
+    /// Set if the client supports [`feature::BATCH_ACK`].
+    acknowledge_batches: bool,
+    /// Number of batches received since the beginning of the connection.
+    n_batches: u64,
 }
 
 pub struct TcpServer {
     cancel_token: CancellationToken,
     listener: TcpListener,
+    tls: Option<ServerTls>,
     measurement_tx: mpsc::Sender<MeasurementBuffer>,
     metrics_tx: MetricSender,
 }
@@ -32,7 +44,7 @@ impl TcpSource {
             MessageEnum::Greet(greet) => {
                 // Ensure that the client and server are compatible and respond.
                 log::debug!("Received {greet:?}");
-                let accept = greet.protocol_version == PROTOCOL_VERSION; // TODO check alumet and plugin are compatible?
+                let accept = protocol::is_supported(greet.protocol_version); // TODO check alumet and plugin are compatible?
                 let remote_addr = self
                     .tcp
                     .peer_addr()
@@ -46,11 +58,9 @@ impl TcpSource {
                     );
                 } else {
                     log::warn!(
-                        "Client {remote_name} ({remote_addr}) is NOT compatible: it uses protocol version {}, which is not compatible with our protocol version {}. Rejecting.",
+                        "Client {remote_name} ({remote_addr}) is NOT compatible: it uses protocol version {}, but we only support versions {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}. Rejecting.",
                         greet.protocol_version,
-                        PROTOCOL_VERSION
                     );
-                    return Ok(());
                 }
                 self.tcp
                     .write_message(&MessageBody {
@@ -65,24 +75,24 @@ impl TcpSource {
                     .await?;
                 if !accept {
                     self.tcp.shutdown().await?;
+                } else if greet.protocol_version >= FEATURES_VERSION {
+                    // Older clients don't know this message.
+                    self.tcp
+                        .write_message(&MessageBody {
+                            sender: String::from(""),
+                            content: MessageEnum::Features(Features::local()),
+                        })
+                        .await?;
                 }
             }
             MessageEnum::RegisterMetrics(register_metrics) => {
-                let mut metric_ids = Vec::with_capacity(register_metrics.metrics.len());
-                let mut metric_defs = Vec::with_capacity(register_metrics.metrics.len());
-                for protocol_metric in register_metrics.metrics {
-                    let alumet_metric = Metric {
-                        name: protocol_metric.name,
-                        description: String::from("remote metric via plugin_relay"),
-                        value_type: protocol_metric.value_type.try_into()?,
-                        unit: protocol_metric.unit.try_into()?,
-                    };
-                    metric_defs.push(alumet_metric);
-                    metric_ids.push(protocol_metric.id);
-                }
-                self.metrics
-                    .register_from_client(&remote_name, metric_ids, metric_defs)
-                    .await?;
+                // older clients don't send the tags
+                let metrics = register_metrics.metrics.into_iter().map(|m| (m, Vec::new()));
+                self.register_metrics(&remote_name, metrics).await?;
+            }
+            MessageEnum::RegisterTaggedMetrics(register_metrics) => {
+                let metrics = register_metrics.metrics.into_iter().map(|m| (m.metric, m.tags));
+                self.register_metrics(&remote_name, metrics).await?;
             }
             MessageEnum::SendMeasurements(send_measurements) => {
                 let mut alumet_measurements = send_measurements.buf.owned();
@@ -91,11 +101,55 @@ impl TcpSource {
                 // send them
                 self.out_tx.send(alumet_measurements).await?;
             }
+            MessageEnum::SendBatch(batch) => {
+                let mut alumet_measurements = batch.decode()?;
+                self.metrics.convert_all(&remote_name, &mut alumet_measurements)?;
+                self.out_tx.send(alumet_measurements).await?;
+                // The batch is in the pipeline: the client can forget it.
+                self.n_batches += 1;
+                if self.acknowledge_batches {
+                    self.tcp
+                        .write_message(&MessageBody {
+                            sender: String::from(""),
+                            content: MessageEnum::BatchAck(BatchAck { count: self.n_batches }),
+                        })
+                        .await?;
+                }
+            }
+            MessageEnum::Features(features) => {
+                log::debug!("Client {remote_name} supports {features:?}");
+                self.acknowledge_batches = features.supports(feature::BATCH_ACK);
+            }
             _ => unreachable!(),
         }
         Ok(())
     }
 
+    /// Registers the metrics of a client, with their tags.
+    async fn register_metrics(
+        &mut self,
+        remote_name: &str,
+        metrics: impl ExactSizeIterator<Item = (protocol::Metric, Vec<String>)>,
+    ) -> anyhow::Result<()> {
+        let mut metric_ids = Vec::with_capacity(metrics.len());
+        let mut metric_defs = Vec::with_capacity(metrics.len());
+        for (protocol_metric, tags) in metrics {
+            let alumet_metric = Metric {
+                name: protocol_metric.name,
+                description: String::from("remote metric via plugin_relay"),
+                value_type: protocol_metric.value_type.into(),
+                unit: protocol_metric.unit.try_into()?,
+                tags,
+            };
+            metric_defs.push(alumet_metric);
+            metric_ids.push(protocol_metric.id);
+        }
+        self.metrics
+            .register_from_client(remote_name, metric_ids, metric_defs)
+            .await?;
+        Ok(())
+    }
+
     pub fn receive_loop(mut self) -> impl Future<Output = anyhow::Result<()>> + Send {
         fn is_fatal_error(err: &protocol::Error) -> bool {
             match err {
@@ -152,12 +206,14 @@ impl TcpServer {
     pub fn new(
         cancel_token: CancellationToken,
         listener: TcpListener,
+        tls: Option<ServerTls>,
         measurement_tx: mpsc::Sender<MeasurementBuffer>,
         metrics_tx: MetricSender,
     ) -> Self {
         Self {
             cancel_token,
             listener,
+            tls,
             measurement_tx,
             metrics_tx,
         }
@@ -165,13 +221,30 @@ impl TcpServer {
 
     fn start_receiving(&mut self, tcp_stream: TcpStream, remote_addr: SocketAddr) {
         log::info!("New incoming connection from {remote_addr}");
-        let source = TcpSource {
-            cancel_token: self.cancel_token.child_token(),
-            tcp: MessageStream::new(tcp_stream),
-            out_tx: self.measurement_tx.clone(),
-            metrics: MetricConverter::new(self.metrics_tx.clone()),
-        };
+        let cancel_token = self.cancel_token.child_token();
+        let out_tx = self.measurement_tx.clone();
+        let metrics = MetricConverter::new(self.metrics_tx.clone());
+        let acceptor = self.tls.as_ref().map(|tls| tls.acceptor());
         tokio::spawn(async move {
+            // the TLS handshake is done in the new task, in order not to block the accept loop
+            let stream = match acceptor {
+                Some(acceptor) => match tls::accept(acceptor, tcp_stream).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::error!("TLS handshake with client {remote_addr} failed: {e}");
+                        return;
+                    }
+                },
+                None => Transport::from(tcp_stream),
+            };
+            let source = TcpSource {
+                cancel_token,
+                tcp: MessageStream::new(stream),
+                out_tx,
+                metrics,
+                acknowledge_batches: false,
+                n_batches: 0,
+            };
             if let Err(e) = source.receive_loop().await {
                 log::error!("Error in relay source connected to client {remote_addr}: {e:?}");
             }
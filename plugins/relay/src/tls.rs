@@ -0,0 +1,414 @@
+//! TLS and mutual TLS for the relay connection.
+//!
+//! The certificates and keys are read from PEM files. When one of these files changes (for instance,
+//! because the certificates have been renewed), the TLS configuration is rebuilt before the next
+//! connection. If the new files are invalid, the previous configuration is kept.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use rustls::{
+    ClientConfig, RootCertStore, ServerConfig,
+    crypto::{CryptoProvider, ring},
+    server::WebPkiClientVerifier,
+};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use crate::transport::Transport;
+
+/// TLS settings of the relay client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientTlsConfig {
+    /// Certificate(s) of the authority that has signed the certificate of the server.
+    pub ca_cert: PathBuf,
+    /// Certificate of the client, for mutual TLS.
+    pub client_cert: Option<PathBuf>,
+    /// Private key of the client, for mutual TLS.
+    pub client_key: Option<PathBuf>,
+    /// Name to check against the certificate of the server.
+    /// Defaults to the host of the server address.
+    pub server_name: Option<String>,
+}
+
+/// TLS settings of the relay server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerTlsConfig {
+    /// Certificate of the server, followed by the intermediate certificates (if any).
+    pub cert: PathBuf,
+    /// Private key of the server.
+    pub key: PathBuf,
+    /// Certificate(s) of the authority that has signed the certificates of the clients.
+    /// If set, the clients must authenticate with a certificate (mutual TLS).
+    pub client_ca_cert: Option<PathBuf>,
+}
+
+/// Establishes TLS connections to a relay server.
+pub struct ClientTls {
+    config: ClientTlsConfig,
+    server_name: ServerName<'static>,
+    connector: Mutex<Reloading<TlsConnector>>,
+}
+
+/// Accepts TLS connections from relay clients.
+pub struct ServerTls {
+    config: ServerTlsConfig,
+    acceptor: Mutex<Reloading<TlsAcceptor>>,
+}
+
+/// A value built from some files, that is rebuilt when the files change.
+struct Reloading<T> {
+    files: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    current: T,
+}
+
+impl ClientTls {
+    /// Loads the TLS configuration of a client that connects to `server_address`.
+    pub fn new(config: ClientTlsConfig, server_address: &str) -> anyhow::Result<Self> {
+        if config.client_cert.is_some() != config.client_key.is_some() {
+            return Err(anyhow::anyhow!(
+                "client_cert and client_key must be set together, to enable mutual TLS"
+            ));
+        }
+        let server_name = config.server_name.as_deref().unwrap_or_else(|| host(server_address));
+        let server_name = ServerName::try_from(server_name.to_owned())
+            .with_context(|| format!("invalid TLS server name: {server_name}"))?;
+        let files = [
+            Some(&config.ca_cert),
+            config.client_cert.as_ref(),
+            config.client_key.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+        let connector = Reloading::new(files, || client_connector(&config))?;
+        Ok(Self {
+            config,
+            server_name,
+            connector: Mutex::new(connector),
+        })
+    }
+
+    /// Performs the TLS handshake on a new TCP connection.
+    pub async fn connect(&self, stream: TcpStream) -> io::Result<Transport> {
+        let connector = self.connector.lock().unwrap().get(|| client_connector(&self.config));
+        let stream = connector.connect(self.server_name.clone(), stream).await?;
+        Ok(Transport::from(tokio_rustls::TlsStream::from(stream)))
+    }
+}
+
+impl ServerTls {
+    /// Loads the TLS configuration of a server.
+    pub fn new(config: ServerTlsConfig) -> anyhow::Result<Self> {
+        let files = [Some(&config.cert), Some(&config.key), config.client_ca_cert.as_ref()]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        let acceptor = Reloading::new(files, || server_acceptor(&config))?;
+        Ok(Self {
+            config,
+            acceptor: Mutex::new(acceptor),
+        })
+    }
+
+    /// Returns the acceptor to use for the next connection.
+    pub fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.lock().unwrap().get(|| server_acceptor(&self.config))
+    }
+}
+
+/// Performs the TLS handshake of the server on a new TCP connection.
+pub async fn accept(acceptor: TlsAcceptor, stream: TcpStream) -> io::Result<Transport> {
+    let stream = acceptor.accept(stream).await?;
+    Ok(Transport::from(tokio_rustls::TlsStream::from(stream)))
+}
+
+impl<T: Clone> Reloading<T> {
+    fn new(files: Vec<PathBuf>, build: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<Self> {
+        let modified = modification_times(&files);
+        let current = build()?;
+        Ok(Self {
+            files,
+            modified,
+            current,
+        })
+    }
+
+    /// Returns the current value, after rebuilding it if the files have changed.
+    fn get(&mut self, build: impl FnOnce() -> anyhow::Result<T>) -> T {
+        let modified = modification_times(&self.files);
+        if modified != self.modified {
+            match build() {
+                Ok(new) => {
+                    log::info!("TLS certificates reloaded from {:?}", self.files);
+                    self.current = new;
+                    self.modified = modified;
+                }
+                Err(e) => {
+                    log::error!("Failed to reload the TLS certificates, the previous ones are kept: {e:#}");
+                }
+            }
+        }
+        self.current.clone()
+    }
+}
+
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|f| std::fs::metadata(f).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn client_connector(config: &ClientTlsConfig) -> anyhow::Result<TlsConnector> {
+    let roots = load_roots(&config.ca_cert)?;
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+    let tls_config = match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+            .context("invalid client certificate or key")?,
+        _ => builder.with_no_client_auth(),
+    };
+    Ok(TlsConnector::from(Arc::new(tls_config)))
+}
+
+fn server_acceptor(config: &ServerTlsConfig) -> anyhow::Result<TlsAcceptor> {
+    let builder = ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+    let builder = match &config.client_ca_cert {
+        Some(ca) => {
+            let roots = load_roots(ca)?;
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider()).build()?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let tls_config = builder
+        .with_single_cert(load_certs(&config.cert)?, load_key(&config.key)?)
+        .context("invalid server certificate or key")?;
+    Ok(TlsAcceptor::from(Arc::new(tls_config)))
+}
+
+fn load_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("could not read certificates from {path:?}"))?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("no certificate found in {path:?}"));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).with_context(|| format!("could not read private key from {path:?}"))
+}
+
+fn load_roots(path: &Path) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots
+            .add(cert)
+            .with_context(|| format!("invalid CA certificate in {path:?}"))?;
+    }
+    Ok(roots)
+}
+
+/// Returns the host part of an address of the form `host:port`.
+fn host(address: &str) -> &str {
+    let host = match address.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => address,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::{ClientTls, ClientTlsConfig, ServerTls, ServerTlsConfig, accept, host};
+
+    struct Pki {
+        ca_cert: PathBuf,
+        server_cert: PathBuf,
+        server_key: PathBuf,
+        client_cert: PathBuf,
+        client_key: PathBuf,
+    }
+
+    /// Generates a CA, a certificate for the server (`localhost`) and a certificate for the client.
+    fn generate_pki(dir: &Path, prefix: &str) -> Pki {
+        let write = |name: &str, content: String| {
+            let path = dir.join(format!("{prefix}-{name}.pem"));
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+        let sign = |name: &str, purpose| {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec![name.to_owned()]).unwrap();
+            params.extended_key_usages = vec![purpose];
+            let cert = params.signed_by(&key, &ca_cert, &ca_key).unwrap();
+            (cert.pem(), key.serialize_pem())
+        };
+        let (server_cert, server_key) = sign("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let (client_cert, client_key) = sign("client", ExtendedKeyUsagePurpose::ClientAuth);
+
+        Pki {
+            ca_cert: write("ca", ca_cert.pem()),
+            server_cert: write("server-cert", server_cert),
+            server_key: write("server-key", server_key),
+            client_cert: write("client-cert", client_cert),
+            client_key: write("client-key", client_key),
+        }
+    }
+
+    /// Connects the client to the server, and checks that data can be exchanged.
+    async fn exchange(client: &ClientTls, server: &ServerTls) -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let acceptor = server.acceptor();
+        let server_task = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await?;
+            let mut stream = accept(acceptor, tcp).await?;
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await?;
+            anyhow::Ok(buf)
+        });
+
+        let tcp = TcpStream::connect(addr).await?;
+        let client_res = async {
+            let mut stream = client.connect(tcp).await?;
+            stream.write_all(b"hello").await?;
+            stream.flush().await?;
+            anyhow::Ok(())
+        }
+        .await;
+        let server_res = server_task.await?;
+        client_res?;
+        assert_eq!(&server_res?, b"hello");
+        Ok(())
+    }
+
+    fn client_config(pki: &Pki, mutual: bool) -> ClientTlsConfig {
+        ClientTlsConfig {
+            ca_cert: pki.ca_cert.clone(),
+            client_cert: mutual.then(|| pki.client_cert.clone()),
+            client_key: mutual.then(|| pki.client_key.clone()),
+            server_name: None,
+        }
+    }
+
+    fn server_config(pki: &Pki, mutual: bool) -> ServerTlsConfig {
+        ServerTlsConfig {
+            cert: pki.server_cert.clone(),
+            key: pki.server_key.clone(),
+            client_ca_cert: mutual.then(|| pki.ca_cert.clone()),
+        }
+    }
+
+    #[tokio::test]
+    async fn tls() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pki = generate_pki(tmp.path(), "a");
+        let server = ServerTls::new(server_config(&pki, false)).unwrap();
+        let client = ClientTls::new(client_config(&pki, false), "localhost:50051").unwrap();
+        exchange(&client, &server).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn mutual_tls() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pki = generate_pki(tmp.path(), "a");
+        let server = ServerTls::new(server_config(&pki, true)).unwrap();
+
+        let client = ClientTls::new(client_config(&pki, true), "localhost:50051").unwrap();
+        exchange(&client, &server).await.unwrap();
+
+        // without a client certificate, the connection is refused
+        let client = ClientTls::new(client_config(&pki, false), "localhost:50051").unwrap();
+        exchange(&client, &server).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn verification() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pki = generate_pki(tmp.path(), "a");
+        let other_pki = generate_pki(tmp.path(), "b");
+        let server = ServerTls::new(server_config(&pki, false)).unwrap();
+
+        // wrong hostname
+        let mut config = client_config(&pki, false);
+        config.server_name = Some(String::from("collector.example.com"));
+        let client = ClientTls::new(config, "localhost:50051").unwrap();
+        exchange(&client, &server).await.unwrap_err();
+
+        // unknown CA
+        let client = ClientTls::new(client_config(&other_pki, false), "localhost:50051").unwrap();
+        exchange(&client, &server).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn rotation() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pki = generate_pki(tmp.path(), "a");
+        let server = ServerTls::new(server_config(&pki, false)).unwrap();
+        let client = ClientTls::new(client_config(&pki, false), "localhost:50051").unwrap();
+        exchange(&client, &server).await.unwrap();
+
+        // Renew every certificate, including the CA: the old CA cannot verify the new server certificate.
+        // Set an explicit modification time, because the filesystem timestamps may be too coarse.
+        let new_pki = generate_pki(tmp.path(), "b");
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        for (new, old) in [
+            (&new_pki.ca_cert, &pki.ca_cert),
+            (&new_pki.server_cert, &pki.server_cert),
+            (&new_pki.server_key, &pki.server_key),
+        ] {
+            std::fs::rename(new, old).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(old)
+                .unwrap()
+                .set_modified(later)
+                .unwrap();
+        }
+        exchange(&client, &server).await.unwrap();
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(host("localhost:50051"), "localhost");
+        assert_eq!(host("[::1]:50051"), "::1");
+        assert_eq!(host("192.168.1.10:50051"), "192.168.1.10");
+        assert_eq!(host("collector"), "collector");
+    }
+}
@@ -0,0 +1,298 @@
+//! Parsing of the files of the `csv` plugin.
+//!
+//! The first line is a header that gives the columns of the file, so that all the layouts of the
+//! `csv` plugin can be read. The columns that are not known are attributes.
+
+use alumet::measurement::AttributeValue;
+use anyhow::{Context, anyhow};
+use serde_json::Map;
+
+use crate::{
+    jsonl::attributes_from_json,
+    record::{Record, Value, parse_attribute, parse_consumer, parse_resource, parse_timestamp},
+};
+
+/// A column of the CSV file.
+#[derive(Debug, Clone, PartialEq)]
+enum Column {
+    Metric,
+    Timestamp,
+    Value,
+    Unit,
+    ResourceKind,
+    ResourceId,
+    ConsumerKind,
+    ConsumerId,
+    /// All the attributes, in a JSON object.
+    AttributesJson,
+    /// The attributes that are not in the header, like `key=value, key2=value2`.
+    LateAttributes,
+    /// One attribute.
+    Attribute(String),
+}
+
+pub struct CsvParser {
+    delimiter: char,
+    escaped_quote: String,
+    /// The columns of the file, `None` until the header has been read.
+    columns: Option<Vec<Column>>,
+    /// Beginning of a record that contains a line break, in a quoted value.
+    pending: String,
+}
+
+impl CsvParser {
+    pub fn new(delimiter: char, escaped_quote: String) -> Self {
+        Self {
+            delimiter,
+            escaped_quote,
+            columns: None,
+            pending: String::new(),
+        }
+    }
+
+    /// Parses a line of the file.
+    ///
+    /// Returns `Ok(None)` for the header, the empty lines, and the lines that are not the end of a record.
+    pub fn parse_line(&mut self, line: &str) -> anyhow::Result<Option<Record>> {
+        let line = if self.pending.is_empty() {
+            if line.is_empty() {
+                return Ok(None);
+            }
+            line.to_owned()
+        } else {
+            let mut pending = std::mem::take(&mut self.pending);
+            pending.push('\n');
+            pending.push_str(line);
+            pending
+        };
+        let Some(fields) = self.split(&line) else {
+            self.pending = line;
+            return Ok(None);
+        };
+        match &self.columns {
+            None => {
+                self.columns = Some(parse_header(fields)?);
+                Ok(None)
+            }
+            Some(columns) => parse_record(columns, fields).map(Some),
+        }
+    }
+
+    /// Splits a line into unescaped fields, or returns `None` if a quoted value is not terminated.
+    fn split(&self, line: &str) -> Option<Vec<String>> {
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            if quoted && rest.starts_with(self.escaped_quote.as_str()) {
+                field.push('"');
+                rest = &rest[self.escaped_quote.len()..];
+                continue;
+            }
+            if quoted && c == '"' {
+                quoted = false;
+            } else if !quoted && c == '"' && field.is_empty() {
+                quoted = true;
+            } else if !quoted && c == self.delimiter {
+                fields.push(std::mem::take(&mut field));
+            } else {
+                field.push(c);
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+        if quoted {
+            return None;
+        }
+        fields.push(field);
+        Some(fields)
+    }
+}
+
+fn parse_header(fields: Vec<String>) -> anyhow::Result<Vec<Column>> {
+    let columns: Vec<Column> = fields
+        .into_iter()
+        .map(|name| match name.as_str() {
+            "metric" => Column::Metric,
+            "timestamp" => Column::Timestamp,
+            "value" => Column::Value,
+            "unit" => Column::Unit,
+            "resource_kind" => Column::ResourceKind,
+            "resource_id" => Column::ResourceId,
+            "consumer_kind" => Column::ConsumerKind,
+            "consumer_id" => Column::ConsumerId,
+            "attributes" => Column::AttributesJson,
+            "__late_attributes" => Column::LateAttributes,
+            _ => Column::Attribute(name),
+        })
+        .collect();
+    for required in [Column::Metric, Column::Timestamp, Column::Value] {
+        if !columns.contains(&required) {
+            return Err(anyhow!("the header has no column {required:?}"));
+        }
+    }
+    Ok(columns)
+}
+
+fn parse_record(columns: &[Column], fields: Vec<String>) -> anyhow::Result<Record> {
+    if fields.len() != columns.len() {
+        return Err(anyhow!(
+            "the record has {} values but the header has {} columns",
+            fields.len(),
+            columns.len()
+        ));
+    }
+    let mut metric = String::new();
+    let mut timestamp = None;
+    let mut value = None;
+    let mut unit = None;
+    let (mut resource_kind, mut resource_id) = (String::from("local_machine"), String::new());
+    let (mut consumer_kind, mut consumer_id) = (String::from("local_machine"), String::new());
+    let mut attributes = Vec::new();
+
+    for (column, field) in columns.iter().zip(fields) {
+        match column {
+            Column::Metric => metric = field,
+            Column::Timestamp => timestamp = Some(parse_timestamp(&field)?),
+            Column::Value => value = Some(Value::parse(&field)?),
+            Column::Unit => unit = Some(field).filter(|u| !u.is_empty()),
+            Column::ResourceKind => resource_kind = field,
+            Column::ResourceId => resource_id = field,
+            Column::ConsumerKind => consumer_kind = field,
+            Column::ConsumerId => consumer_id = field,
+            Column::AttributesJson => {
+                let object: Map<String, serde_json::Value> =
+                    serde_json::from_str(&field).context("invalid attributes")?;
+                attributes.extend(attributes_from_json(object));
+            }
+            Column::LateAttributes => attributes.extend(parse_late_attributes(&field)),
+            Column::Attribute(key) if !field.is_empty() => attributes.push((key.clone(), parse_attribute(&field))),
+            Column::Attribute(_) => (),
+        }
+    }
+    Ok(Record {
+        metric,
+        timestamp: timestamp.expect("the header should have a timestamp column"),
+        value: value.expect("the header should have a value column"),
+        unit,
+        resource: parse_resource(&resource_kind, &resource_id)?,
+        consumer: parse_consumer(&consumer_kind, &consumer_id)?,
+        attributes,
+    })
+}
+
+/// Parses the attributes of the column `__late_attributes`, in which `=` is escaped as `\=`.
+fn parse_late_attributes(field: &str) -> Vec<(String, AttributeValue)> {
+    let unescape = |s: &str| s.replace("\\=", "=");
+    field
+        .split(", ")
+        .filter_map(|pair| {
+            let separator = pair
+                .match_indices('=')
+                .map(|(i, _)| i)
+                .find(|&i| !pair[..i].ends_with('\\'))?;
+            let key = unescape(&pair[..separator]);
+            let value = unescape(&pair[separator + 1..]);
+            Some((key, parse_attribute(&value)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::{
+        measurement::{AttributeValue, Timestamp},
+        resources::{Resource, ResourceConsumer},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse_all(parser: &mut CsvParser, content: &str) -> Vec<Record> {
+        content
+            .lines()
+            .filter_map(|line| parser.parse_line(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn default_layout() {
+        let content = "metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;domain;__late_attributes\n\
+                       rapl_consumed_energy_J;2025-01-01T12:00:00Z;12.5;cpu_package;0;local_machine;;package;\n\
+                       cpu_time_delta_ns;2025-01-01T12:00:01Z;300;local_machine;;process;15;;kind=user, a\\=b=1\n";
+        let records = parse_all(&mut CsvParser::new(';', String::from("\"\"")), content);
+        let t0 = Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_735_732_800));
+        assert_eq!(
+            records,
+            vec![
+                Record {
+                    metric: String::from("rapl_consumed_energy_J"),
+                    timestamp: t0,
+                    value: Value::Float(12.5),
+                    unit: None,
+                    resource: Resource::CpuPackage { id: 0 },
+                    consumer: ResourceConsumer::LocalMachine,
+                    attributes: vec![(String::from("domain"), AttributeValue::String(String::from("package")))],
+                },
+                Record {
+                    metric: String::from("cpu_time_delta_ns"),
+                    timestamp: Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_735_732_801)),
+                    value: Value::Integer(300),
+                    unit: None,
+                    resource: Resource::LocalMachine,
+                    consumer: ResourceConsumer::Process { pid: 15 },
+                    attributes: vec![
+                        (String::from("kind"), AttributeValue::String(String::from("user"))),
+                        (String::from("a=b"), AttributeValue::U64(1)),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_layout() {
+        let content = "timestamp,metric,unit,value,note,attributes\n\
+                       1735732800500,power,W,\"3\",\"a,\n\
+                       b\",\"{\"\"core\"\":2}\"\n";
+        let records = parse_all(&mut CsvParser::new(',', String::from("\"\"")), content);
+        assert_eq!(
+            records,
+            vec![Record {
+                metric: String::from("power"),
+                timestamp: Timestamp::from(UNIX_EPOCH + Duration::from_millis(1_735_732_800_500)),
+                value: Value::Integer(3),
+                unit: Some(String::from("W")),
+                resource: Resource::LocalMachine,
+                consumer: ResourceConsumer::LocalMachine,
+                attributes: vec![
+                    (String::from("note"), AttributeValue::String(String::from("a,\nb"))),
+                    (String::from("core"), AttributeValue::U64(2)),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn escaped_quotes() {
+        let parser = CsvParser::new(';', String::from("\\\""));
+        assert_eq!(
+            parser.split("a;\"b\\\"c;d\";e").unwrap(),
+            vec![String::from("a"), String::from("b\"c;d"), String::from("e")]
+        );
+        assert_eq!(parser.split("a;\"b"), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let mut parser = CsvParser::new(';', String::from("\"\""));
+        assert!(parser.parse_line("metric;value").is_err());
+
+        let mut parser = CsvParser::new(';', String::from("\"\""));
+        parser.parse_line("metric;timestamp;value").unwrap();
+        assert!(parser.parse_line("power;1735732800").is_err());
+        assert!(parser.parse_line("power;1735732800;high").is_err());
+    }
+}
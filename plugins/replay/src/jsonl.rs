@@ -0,0 +1,124 @@
+//! Parsing of the files of the `jsonl` plugin: one JSON object per measurement, one object per line.
+
+use alumet::measurement::AttributeValue;
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+use serde_json::{Map, Number};
+
+use crate::record::{Record, Value, parse_consumer, parse_resource, parse_timestamp};
+
+#[derive(Deserialize)]
+struct JsonRecord {
+    metric: String,
+    timestamp: String,
+    value: Number,
+    unit: Option<String>,
+    #[serde(default = "local_machine")]
+    resource_kind: String,
+    #[serde(default)]
+    resource_id: String,
+    #[serde(default = "local_machine")]
+    consumer_kind: String,
+    #[serde(default)]
+    consumer_id: String,
+    #[serde(default)]
+    attributes: Map<String, serde_json::Value>,
+}
+
+fn local_machine() -> String {
+    String::from("local_machine")
+}
+
+pub fn parse_line(line: &str) -> anyhow::Result<Record> {
+    let record: JsonRecord = serde_json::from_str(line).context("invalid JSON record")?;
+    let value = match record.value.as_u64() {
+        Some(v) => Value::Integer(v),
+        None => Value::Float(
+            record
+                .value
+                .as_f64()
+                .ok_or_else(|| anyhow!("invalid value {}", record.value))?,
+        ),
+    };
+    Ok(Record {
+        metric: record.metric,
+        timestamp: parse_timestamp(&record.timestamp)?,
+        value,
+        unit: record.unit,
+        resource: parse_resource(&record.resource_kind, &record.resource_id)?,
+        consumer: parse_consumer(&record.consumer_kind, &record.consumer_id)?,
+        attributes: attributes_from_json(record.attributes),
+    })
+}
+
+/// Converts a JSON object to attributes.
+///
+/// The values that have no equivalent attribute type, such as objects, are kept as JSON strings.
+pub fn attributes_from_json(attributes: Map<String, serde_json::Value>) -> Vec<(String, AttributeValue)> {
+    attributes
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::Null => return None,
+                serde_json::Value::Bool(v) => AttributeValue::Bool(v),
+                serde_json::Value::Number(n) => match n.as_u64() {
+                    Some(v) => AttributeValue::U64(v),
+                    None => AttributeValue::F64(n.as_f64()?),
+                },
+                serde_json::Value::String(v) => AttributeValue::String(v),
+                serde_json::Value::Array(ref items) => match items.iter().map(|v| v.as_u64()).collect() {
+                    Some(list) => AttributeValue::ListU64(list),
+                    None => AttributeValue::String(value.to_string()),
+                },
+                serde_json::Value::Object(_) => AttributeValue::String(value.to_string()),
+            };
+            Some((key, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::{
+        measurement::{AttributeValue, Timestamp},
+        resources::{Resource, ResourceConsumer},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn output_of_the_jsonl_plugin() {
+        let line = r#"{"metric":"cpu_time_delta","timestamp":"2025-01-01T12:00:00.000000000Z","value":1720000000,"unit":"ns","resource_kind":"cpu_package","resource_id":"0","consumer_kind":"process","consumer_id":"15","attributes":{"cpus":[0,1],"kind":"user","ratio":0.5}}"#;
+        assert_eq!(
+            parse_line(line).unwrap(),
+            Record {
+                metric: String::from("cpu_time_delta"),
+                timestamp: Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_735_732_800)),
+                value: Value::Integer(1_720_000_000),
+                unit: Some(String::from("ns")),
+                resource: Resource::CpuPackage { id: 0 },
+                consumer: ResourceConsumer::Process { pid: 15 },
+                attributes: vec![
+                    (String::from("cpus"), AttributeValue::ListU64(vec![0, 1])),
+                    (String::from("kind"), AttributeValue::String(String::from("user"))),
+                    (String::from("ratio"), AttributeValue::F64(0.5)),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn minimal_record() {
+        let record = parse_line(r#"{"metric":"power","timestamp":"1735732800","value":12.0}"#).unwrap();
+        assert_eq!(record.value, Value::Float(12.0));
+        assert_eq!(record.unit, None);
+        assert_eq!(record.resource, Resource::LocalMachine);
+        assert_eq!(record.consumer, ResourceConsumer::LocalMachine);
+
+        assert!(parse_line(r#"{"metric":"power","value":12.0}"#).is_err());
+        assert!(parse_line(r#"{"metric":"power","timestamp":"1735732800","value":1,"#).is_err());
+    }
+}
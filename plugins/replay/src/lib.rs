@@ -0,0 +1,196 @@
+mod csv;
+mod jsonl;
+mod record;
+mod source;
+
+use std::{collections::HashMap, path::PathBuf};
+
+use alumet::{
+    measurement::WrappedMeasurementType,
+    plugin::{
+        AlumetPluginStart, AlumetPostStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::source::{ReplayFile, ReplaySource};
+
+pub struct ReplayPlugin {
+    config: Config,
+    /// Notified when all the files have been replayed.
+    done_rx: Option<oneshot::Receiver<()>>,
+}
+
+impl AlumetPlugin for ReplayPlugin {
+    fn name() -> &'static str {
+        "replay"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(config.files.into_iter().map(Capability::Filesystem).collect()))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.files.is_empty() {
+            return Err(anyhow!("no file to replay, please set files in the configuration."));
+        }
+        if !(config.speed >= 0.0 && config.speed.is_finite()) {
+            return Err(anyhow!("invalid speed {}, it must be a positive number", config.speed));
+        }
+        for path in &config.files {
+            config.format.resolve(path)?;
+        }
+        Ok(Box::new(ReplayPlugin { config, done_rx: None }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let files: Vec<ReplayFile> = self
+            .config
+            .files
+            .iter()
+            .map(|path| {
+                Ok(ReplayFile {
+                    path: path.clone(),
+                    format: self.config.format.resolve(path)?,
+                    csv_delimiter: self.config.csv_delimiter,
+                    csv_escaped_quote: self.config.csv_escaped_quote.clone(),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        // Read the files a first time to register their metrics, and to fail fast if they are invalid.
+        let mut metrics = HashMap::new();
+        let mut n_measurements = 0;
+        for (name, info) in source::scan(&files)? {
+            let value_type = if info.integer {
+                WrappedMeasurementType::U64
+            } else {
+                WrappedMeasurementType::F64
+            };
+            let unit = info.unit.as_deref().map(parse_unit).unwrap_or(Unit::Unity.into());
+            let id = alumet
+                .create_metric_untyped(&name, value_type.clone(), unit, "metric replayed from a file")
+                .with_context(|| format!("failed to create the metric {name}"))?;
+            metrics.insert(name, (id, value_type));
+            n_measurements += info.count;
+        }
+        log::info!(
+            "Found {n_measurements} measurements of {} metrics in {} files.",
+            metrics.len(),
+            files.len()
+        );
+
+        let (done_tx, done_rx) = oneshot::channel();
+        self.done_rx = Some(done_rx);
+        let speed = self.config.speed;
+        alumet.add_autonomous_source_builder("files", move |_ctx, cancel_token, out_tx| {
+            let source = ReplaySource::new(files, speed, metrics, out_tx, done_tx);
+            Ok(Box::pin(source.run(cancel_token)))
+        })?;
+        Ok(())
+    }
+
+    fn post_pipeline_start(&mut self, alumet: &mut AlumetPostStart) -> anyhow::Result<()> {
+        if let (true, Some(done_rx)) = (self.config.shutdown_when_done, self.done_rx.take()) {
+            let control_handle = alumet.pipeline_control();
+            alumet.async_runtime().spawn(async move {
+                if done_rx.await.is_ok() {
+                    log::info!("The replay is done, stopping the pipeline.");
+                    control_handle.shutdown();
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Parses the unit of a file, which is the unique name or the display name of the unit.
+fn parse_unit(unit: &str) -> PrefixedUnit {
+    unit.parse().unwrap_or_else(|_| {
+        Unit::Custom {
+            unique_name: unit.to_owned(),
+            display_name: unit.to_owned(),
+        }
+        .into()
+    })
+}
+
+/// Format of the files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// Guessed from the extension of each file.
+    #[default]
+    Auto,
+    /// Files of the `csv` plugin.
+    Csv,
+    /// Files of the `jsonl` plugin.
+    Jsonl,
+}
+
+impl Format {
+    /// Returns the format of a file, guessing it from its extension if needed.
+    fn resolve(self, path: &std::path::Path) -> anyhow::Result<Format> {
+        if self != Format::Auto {
+            return Ok(self);
+        }
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Ok(Format::Csv),
+            Some("jsonl" | "ndjson" | "json") => Ok(Format::Jsonl),
+            _ => Err(anyhow!(
+                "cannot guess the format of {}, please set format in the configuration.",
+                path.display()
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The files to replay, one after the other.
+    pub files: Vec<PathBuf>,
+    /// Format of the files: `"csv"`, `"jsonl"`, or `"auto"` to guess it from their extension.
+    pub format: Format,
+    /// The CSV delimiter, such as `;`
+    pub csv_delimiter: char,
+    /// How the quotes are escaped in the quoted CSV values.
+    pub csv_escaped_quote: String,
+    /// Replay speed: `1` to replay the measurements at the pace at which they have been measured,
+    /// `2` to replay them twice as fast, `0` to replay them without delay.
+    pub speed: f64,
+    /// Do we stop Alumet when all the files have been replayed?
+    pub shutdown_when_done: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            files: vec![PathBuf::from("alumet-output.csv")],
+            format: Format::Auto,
+            csv_delimiter: ';',
+            csv_escaped_quote: String::from("\"\""),
+            speed: 1.0,
+            shutdown_when_done: false,
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! A measurement read from a file, and parsing of the fields common to all the formats.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use alumet::{
+    measurement::{AttributeValue, Timestamp},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+
+/// One measurement of a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub metric: String,
+    pub timestamp: Timestamp,
+    pub value: Value,
+    /// The unit of the metric, if the file contains it.
+    pub unit: Option<String>,
+    pub resource: Resource,
+    pub consumer: ResourceConsumer,
+    pub attributes: Vec<(String, AttributeValue)>,
+}
+
+/// A value, as written in the file.
+///
+/// The files do not give the type of the metrics: a metric is considered to be an integer
+/// if all its values are integers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Integer(u64),
+    Float(f64),
+}
+
+impl Value {
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        if let Ok(v) = s.parse() {
+            return Ok(Value::Integer(v));
+        }
+        let v = s.parse().with_context(|| format!("invalid value {s:?}"))?;
+        Ok(Value::Float(v))
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            Value::Integer(v) => v as f64,
+            Value::Float(v) => v,
+        }
+    }
+}
+
+/// Parses a timestamp written by an Alumet output.
+///
+/// Accepts RFC 3339 dates in UTC, and numbers of seconds, milliseconds or nanoseconds since the Unix epoch.
+/// The unit of the numbers is guessed from their magnitude.
+pub fn parse_timestamp(s: &str) -> anyhow::Result<Timestamp> {
+    if s.contains('T') {
+        let t = humantime::parse_rfc3339(s).with_context(|| format!("invalid timestamp {s:?}"))?;
+        return Ok(Timestamp::from(t));
+    }
+    let since_epoch = if let Ok(n) = s.parse::<u128>() {
+        if n < 100_000_000_000 {
+            // until the year 5138
+            Duration::from_secs(n as u64)
+        } else if n < 100_000_000_000_000 {
+            Duration::from_millis(n as u64)
+        } else {
+            Duration::from_nanos(u64::try_from(n).map_err(|_| anyhow!("timestamp {s:?} is too big"))?)
+        }
+    } else {
+        let secs: f64 = s.parse().map_err(|_| anyhow!("invalid timestamp {s:?}"))?;
+        Duration::try_from_secs_f64(secs).map_err(|_| anyhow!("invalid timestamp {s:?}"))?
+    };
+    let t = UNIX_EPOCH
+        .checked_add(since_epoch)
+        .ok_or_else(|| anyhow!("timestamp {s:?} is too big"))?;
+    Ok(Timestamp::from(t))
+}
+
+pub fn parse_resource(kind: &str, id: &str) -> anyhow::Result<Resource> {
+    Resource::parse(kind.to_owned(), id.to_owned()).map_err(|e| anyhow!("invalid resource {kind}:{id}: {e}"))
+}
+
+pub fn parse_consumer(kind: &str, id: &str) -> anyhow::Result<ResourceConsumer> {
+    ResourceConsumer::parse(kind.to_owned(), id.to_owned()).map_err(|e| anyhow!("invalid consumer {kind}:{id}: {e}"))
+}
+
+/// Guesses the type of an attribute that has been written as text.
+pub fn parse_attribute(s: &str) -> AttributeValue {
+    if let Ok(v) = s.parse() {
+        AttributeValue::U64(v)
+    } else if let Ok(v) = s.parse() {
+        AttributeValue::Bool(v)
+    } else if let Ok(v) = s.parse::<f64>()
+        && v.is_finite()
+    {
+        AttributeValue::F64(v)
+    } else {
+        AttributeValue::String(s.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::measurement::{AttributeValue, Timestamp};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        let t = Timestamp::from(UNIX_EPOCH + Duration::new(1_700_000_000, 500_000_000));
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20.5Z").unwrap(), t);
+        assert_eq!(parse_timestamp("2023-11-14T22:13:20.500000000Z").unwrap(), t);
+        assert_eq!(parse_timestamp("1700000000.5").unwrap(), t);
+        assert_eq!(parse_timestamp("1700000000500").unwrap(), t);
+        assert_eq!(parse_timestamp("1700000000500000000").unwrap(), t);
+        assert_eq!(
+            parse_timestamp("1700000000").unwrap(),
+            Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert!(parse_timestamp("yesterday").is_err());
+        assert!(parse_timestamp("-1.5").is_err());
+        // too big for the system clock
+        assert!(parse_timestamp("1e19").is_err());
+        assert!(parse_timestamp("18446744073709551615000000000").is_err());
+    }
+
+    #[test]
+    fn values_and_attributes() {
+        assert_eq!(Value::parse("42").unwrap(), Value::Integer(42));
+        assert_eq!(Value::parse("-3").unwrap(), Value::Float(-3.0));
+        assert_eq!(Value::parse("1.5e3").unwrap(), Value::Float(1500.0));
+        assert!(Value::parse("").is_err());
+
+        assert_eq!(parse_attribute("12"), AttributeValue::U64(12));
+        assert_eq!(parse_attribute("0.25"), AttributeValue::F64(0.25));
+        assert_eq!(parse_attribute("true"), AttributeValue::Bool(true));
+        assert_eq!(parse_attribute("nan"), AttributeValue::String(String::from("nan")));
+        assert_eq!(parse_attribute("user"), AttributeValue::String(String::from("user")));
+    }
+}
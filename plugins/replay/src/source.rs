@@ -0,0 +1,207 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue},
+    metrics::RawMetricId,
+};
+use anyhow::Context;
+use tokio::{
+    io::AsyncBufReadExt,
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    Format,
+    csv::CsvParser,
+    jsonl,
+    record::{Record, Value},
+};
+
+/// Maximum number of measurements in a buffer, when the measurements are replayed without delay.
+const MAX_BUFFER_SIZE: usize = 8192;
+
+/// A file to replay, with the options of its format.
+#[derive(Clone)]
+pub struct ReplayFile {
+    pub path: PathBuf,
+    pub format: Format,
+    pub csv_delimiter: char,
+    pub csv_escaped_quote: String,
+}
+
+/// Reads the records of a file, whatever its format.
+enum Parser {
+    Csv(CsvParser),
+    Jsonl,
+}
+
+impl Parser {
+    fn new(file: &ReplayFile) -> Self {
+        match file.format {
+            Format::Csv => Parser::Csv(CsvParser::new(file.csv_delimiter, file.csv_escaped_quote.clone())),
+            Format::Jsonl => Parser::Jsonl,
+            Format::Auto => unreachable!("the format of {} should have been resolved", file.path.display()),
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> anyhow::Result<Option<Record>> {
+        match self {
+            Parser::Csv(parser) => parser.parse_line(line),
+            Parser::Jsonl if line.trim().is_empty() => Ok(None),
+            Parser::Jsonl => jsonl::parse_line(line).map(Some),
+        }
+    }
+}
+
+/// What the files tell about a metric.
+#[derive(Debug, Default)]
+pub struct MetricInfo {
+    /// The first unit found in the files.
+    pub unit: Option<String>,
+    /// `true` if all the values are integers.
+    pub integer: bool,
+    pub count: usize,
+}
+
+/// Reads all the files to find the metrics that they contain, and checks that they can be parsed.
+pub fn scan(files: &[ReplayFile]) -> anyhow::Result<HashMap<String, MetricInfo>> {
+    let mut metrics: HashMap<String, MetricInfo> = HashMap::new();
+    for file in files {
+        let path = file.path.display();
+        let reader = BufReader::new(File::open(&file.path).with_context(|| format!("failed to open {path}"))?);
+        let mut parser = Parser::new(file);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.with_context(|| format!("failed to read {path}"))?;
+            let Some(record) = parser.parse_line(&line).with_context(|| format!("{path}:{}", i + 1))? else {
+                continue;
+            };
+            let info = metrics.entry(record.metric).or_insert_with(|| MetricInfo {
+                integer: true,
+                ..Default::default()
+            });
+            info.integer &= matches!(record.value, Value::Integer(_));
+            if info.unit.is_none() {
+                info.unit = record.unit;
+            }
+            info.count += 1;
+        }
+    }
+    Ok(metrics)
+}
+
+/// Sends the records of the files to the pipeline, at the pace at which they have been measured.
+pub struct ReplaySource {
+    files: Vec<ReplayFile>,
+    /// `0` to send the measurements without delay.
+    speed: f64,
+    metrics: HashMap<String, (RawMetricId, WrappedMeasurementType)>,
+    out_tx: mpsc::Sender<MeasurementBuffer>,
+    /// Notified when all the files have been replayed.
+    done_tx: oneshot::Sender<()>,
+    buffer: MeasurementBuffer,
+}
+
+impl ReplaySource {
+    pub fn new(
+        files: Vec<ReplayFile>,
+        speed: f64,
+        metrics: HashMap<String, (RawMetricId, WrappedMeasurementType)>,
+        out_tx: mpsc::Sender<MeasurementBuffer>,
+        done_tx: oneshot::Sender<()>,
+    ) -> Self {
+        Self {
+            files,
+            speed,
+            metrics,
+            out_tx,
+            done_tx,
+            buffer: MeasurementBuffer::new(),
+        }
+    }
+
+    pub async fn run(mut self, cancel_token: CancellationToken) -> anyhow::Result<()> {
+        let started = Instant::now();
+        // Timestamp of the first measurement, which is sent when the source starts.
+        let mut origin: Option<Timestamp> = None;
+
+        for file in std::mem::take(&mut self.files) {
+            let path = file.path.display();
+            log::info!("Replaying {path}");
+            let f = tokio::fs::File::open(&file.path)
+                .await
+                .with_context(|| format!("failed to open {path}"))?;
+            let mut lines = tokio::io::BufReader::new(f).lines();
+            let mut parser = Parser::new(&file);
+            let mut line_number = 0;
+            while let Some(line) = lines
+                .next_line()
+                .await
+                .with_context(|| format!("failed to read {path}"))?
+            {
+                line_number += 1;
+                let Some(record) = parser
+                    .parse_line(&line)
+                    .with_context(|| format!("{path}:{line_number}"))?
+                else {
+                    continue;
+                };
+
+                if self.speed > 0.0 {
+                    let origin = *origin.get_or_insert(record.timestamp);
+                    // measurements that are older than the first one are sent immediately
+                    if let Ok(elapsed) = record.timestamp.duration_since(origin) {
+                        let deadline = started + elapsed.div_f64(self.speed);
+                        if deadline > Instant::now() {
+                            self.flush().await?;
+                            tokio::select! {
+                                _ = cancel_token.cancelled() => return Ok(()),
+                                _ = tokio::time::sleep_until(deadline) => (),
+                            }
+                        }
+                    }
+                } else if cancel_token.is_cancelled() {
+                    return Ok(());
+                }
+
+                self.push(record);
+                if self.buffer.len() >= MAX_BUFFER_SIZE {
+                    self.flush().await?;
+                }
+            }
+        }
+        self.flush().await?;
+        log::info!("All the files have been replayed.");
+        let _ = self.done_tx.send(());
+        Ok(())
+    }
+
+    fn push(&mut self, record: Record) {
+        // The metric is unknown if the file has been modified since the start of the plugin.
+        let Some((metric, value_type)) = self.metrics.get(&record.metric) else {
+            return;
+        };
+        let value = match (value_type, record.value) {
+            (WrappedMeasurementType::U64, Value::Integer(v)) => WrappedMeasurementValue::U64(v),
+            (WrappedMeasurementType::U64, Value::Float(_)) => return,
+            (WrappedMeasurementType::F64, v) => WrappedMeasurementValue::F64(v.as_f64()),
+        };
+        let point = MeasurementPoint::new_untyped(record.timestamp, *metric, record.resource, record.consumer, value)
+            .with_attr_vec(record.attributes);
+        self.buffer.push(point);
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.out_tx.send(buffer).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use std::{fs, time::Duration};
+
+use alumet::{
+    measurement::{AttributeValue, WrappedMeasurementType, WrappedMeasurementValue},
+    plugin::rust::serialize_config,
+    resources::{Resource, ResourceConsumer},
+    test::PluginHarness,
+    units::{PrefixedUnit, Unit},
+};
+use plugin_replay::{Config, ReplayPlugin};
+use pretty_assertions::assert_eq;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn replay_without_delay() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let jsonl = dir.path().join("capture.jsonl");
+    fs::write(
+        &jsonl,
+        concat!(
+            r#"{"metric":"rapl_consumed_energy","timestamp":"2025-01-01T12:00:00.000000000Z","value":12.5,"unit":"J","resource_kind":"cpu_package","resource_id":"0","consumer_kind":"local_machine","consumer_id":"","attributes":{"domain":"package"}}"#,
+            "\n",
+            r#"{"metric":"cpu_time_delta","timestamp":"2025-01-01T12:00:00.000000000Z","value":1720000000,"unit":"nanos","resource_kind":"local_machine","resource_id":"","consumer_kind":"process","consumer_id":"15","attributes":{}}"#,
+            "\n",
+        ),
+    )?;
+    let csv = dir.path().join("capture.csv");
+    fs::write(
+        &csv,
+        "metric;timestamp;value;unit;resource_kind;resource_id;consumer_kind;consumer_id;__late_attributes\n\
+         rapl_consumed_energy;2025-01-01T12:00:01Z;13;J;cpu_package;0;local_machine;;domain=package\n",
+    )?;
+
+    let config = Config {
+        files: vec![jsonl, csv],
+        speed: 0.0,
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<ReplayPlugin>::start(serialize_config(config)?)?;
+
+    // the types and units of the metrics are found by reading all the files
+    let metrics = harness.metrics();
+    let energy = metrics.by_name("rapl_consumed_energy").unwrap().1;
+    assert_eq!(energy.value_type, WrappedMeasurementType::F64);
+    assert_eq!(energy.unit, PrefixedUnit::from(Unit::Joule));
+    let cpu_time = metrics.by_name("cpu_time_delta").unwrap().1;
+    assert_eq!(cpu_time.value_type, WrappedMeasurementType::U64);
+    assert_eq!(cpu_time.unit, PrefixedUnit::nano(Unit::Second));
+
+    let mut source = harness.autonomous_source("files")?;
+    let buffer = source.recv_timeout(TIMEOUT).expect("measurements should be replayed");
+    assert_eq!(
+        harness.values(&buffer, "rapl_consumed_energy"),
+        vec![WrappedMeasurementValue::F64(12.5), WrappedMeasurementValue::F64(13.0)]
+    );
+    for point in harness.points(&buffer, "rapl_consumed_energy") {
+        assert_eq!(point.resource, Resource::CpuPackage { id: 0 });
+        let attributes: Vec<_> = point.attributes().map(|(k, v)| (k.to_owned(), v.clone())).collect();
+        assert_eq!(
+            attributes,
+            vec![(String::from("domain"), AttributeValue::String(String::from("package")))]
+        );
+    }
+    let cpu_time_points = harness.points(&buffer, "cpu_time_delta");
+    assert_eq!(cpu_time_points[0].value, WrappedMeasurementValue::U64(1_720_000_000));
+    assert_eq!(cpu_time_points[0].consumer, ResourceConsumer::Process { pid: 15 });
+
+    source.stop()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn replay_at_the_original_pace() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let csv = dir.path().join("capture.csv");
+    fs::write(
+        &csv,
+        "metric;timestamp;value\n\
+         power;1735732800;10\n\
+         power;1735732800;20\n\
+         power;1735732802;30\n",
+    )?;
+
+    // 2 seconds of measurements, replayed in 200 milliseconds
+    let config = Config {
+        files: vec![csv],
+        speed: 10.0,
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<ReplayPlugin>::start(serialize_config(config)?)?;
+    let mut source = harness.autonomous_source("files")?;
+
+    let first = source.recv_timeout(TIMEOUT).expect("measurements should be replayed");
+    assert_eq!(
+        harness.values(&first, "power"),
+        vec![WrappedMeasurementValue::U64(10), WrappedMeasurementValue::U64(20)]
+    );
+    let second = source.recv_timeout(TIMEOUT).expect("measurements should be replayed");
+    assert_eq!(harness.values(&second, "power"), vec![WrappedMeasurementValue::U64(30)]);
+    // the original timestamps are kept
+    let elapsed = second
+        .iter()
+        .next()
+        .unwrap()
+        .timestamp
+        .duration_since(first.iter().next().unwrap().timestamp)?;
+    assert_eq!(elapsed, Duration::from_secs(2));
+
+    source.stop()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn invalid_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let csv = dir.path().join("capture.csv");
+    fs::write(&csv, "metric;timestamp;value\npower;yesterday;10\n").unwrap();
+    let config = |files| Config {
+        files,
+        ..Config::default()
+    };
+
+    let error = PluginHarness::<ReplayPlugin>::start(serialize_config(config(vec![csv])).unwrap())
+        .err()
+        .expect("the timestamp is invalid");
+    assert!(format!("{error:#}").contains("capture.csv:2"), "{error:#}");
+
+    let missing = dir.path().join("missing.jsonl");
+    assert!(PluginHarness::<ReplayPlugin>::start(serialize_config(config(vec![missing])).unwrap()).is_err());
+
+    let unknown_format = dir.path().join("capture.txt");
+    assert!(PluginHarness::<ReplayPlugin>::start(serialize_config(config(vec![unknown_format])).unwrap()).is_err());
+}
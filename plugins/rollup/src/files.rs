@@ -0,0 +1,122 @@
+//! Files that each cover a period of time, such as one file per hour or per day.
+
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use time::OffsetDateTime;
+
+pub struct PeriodicFiles {
+    dir: PathBuf,
+    /// Extension of the files, such as `csv`.
+    extension: &'static str,
+    period: Duration,
+}
+
+impl PeriodicFiles {
+    pub fn new(dir: PathBuf, extension: &'static str, period: Duration) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, extension, period })
+    }
+
+    /// Returns the index of the period that contains `time`, since the Unix epoch.
+    pub fn period_of(&self, time: SystemTime) -> u64 {
+        let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        (elapsed.as_millis() / self.period.as_millis()) as u64
+    }
+
+    /// Returns the path of the file of a period, named after the beginning of the period.
+    ///
+    /// If `unique` is true and the file already exists, for instance because Alumet has been restarted,
+    /// a number is added to the name.
+    pub fn path(&self, period: u64, unique: bool) -> PathBuf {
+        let start = UNIX_EPOCH + Duration::from_millis(period * self.period.as_millis() as u64);
+        let start = OffsetDateTime::from(start);
+        let name = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}",
+            start.year(),
+            u8::from(start.month()),
+            start.day(),
+            start.hour(),
+            start.minute(),
+            start.second()
+        );
+        let mut path = self.dir.join(format!("{name}.{}", self.extension));
+        let mut n = 1;
+        while unique && path.exists() {
+            path = self.dir.join(format!("{name}-{n}.{}", self.extension));
+            n += 1;
+        }
+        path
+    }
+
+    /// Deletes the files that have not been modified for `retention`.
+    pub fn delete_older_than(&self, retention: Duration) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != self.extension) {
+                continue;
+            }
+            let expired = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| t.elapsed().unwrap_or_default() >= retention);
+            if expired {
+                fs::remove_file(&path)?;
+                log::debug!("Deleted old file {path:?}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use pretty_assertions::assert_eq;
+
+    use super::PeriodicFiles;
+
+    #[test]
+    fn names() {
+        let tmp = tempfile::tempdir().unwrap();
+        let files = PeriodicFiles::new(tmp.path().to_owned(), "csv", Duration::from_secs(3600)).unwrap();
+        // 2025-01-02T03:04:05Z
+        let period = files.period_of(UNIX_EPOCH + Duration::from_secs(1_735_787_045));
+        let path = files.path(period, true);
+        assert_eq!(path, tmp.path().join("20250102T030000.csv"));
+        fs::write(&path, "").unwrap();
+        assert_eq!(files.path(period, true), tmp.path().join("20250102T030000-1.csv"));
+        assert_eq!(files.path(period, false), path);
+    }
+
+    #[test]
+    fn retention() {
+        let tmp = tempfile::tempdir().unwrap();
+        let files = PeriodicFiles::new(tmp.path().to_owned(), "csv", Duration::from_secs(3600)).unwrap();
+        let old = tmp.path().join("20250102T030000.csv");
+        let recent = tmp.path().join("20250102T040000.csv");
+        let other = tmp.path().join("notes.txt");
+        for path in [&old, &recent, &other] {
+            fs::write(path, "").unwrap();
+        }
+        let two_days_ago = SystemTime::now() - Duration::from_secs(2 * 86400);
+        for path in [&old, &other] {
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(two_days_ago)
+                .unwrap();
+        }
+        files.delete_older_than(Duration::from_secs(86400)).unwrap();
+        assert!(!old.exists());
+        assert!(recent.exists());
+        assert!(other.exists());
+    }
+}
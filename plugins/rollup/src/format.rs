@@ -0,0 +1,319 @@
+//! Binary format of the rollup files.
+//!
+//! A file starts with a header:
+//! - the magic bytes `ALUMROLL`;
+//! - the version of the format, the resolution of the rollups in milliseconds and the precision
+//!   (number of decimal digits of the averages), as varints.
+//!
+//! Then comes a sequence of records, each starting with a tag byte:
+//! - `1`, a series: its id (varint), then its metric, resource kind, resource id, consumer kind and consumer id,
+//!   as strings (varint length followed by UTF-8 bytes);
+//! - `2`, a rollup of a series: the id of the series, the window (number of resolutions since the Unix epoch)
+//!   and the average multiplied by `10^precision` and rounded, both as the zigzag-encoded difference with
+//!   the previous rollup of the same series (or with zero), then the number of averaged samples.
+//!
+//! Since the consecutive windows of a series differ by one and the averages change slowly,
+//! most rollups take a few bytes.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &[u8; 8] = b"ALUMROLL";
+const VERSION: u64 = 1;
+const TAG_SERIES: u8 = 1;
+const TAG_ROLLUP: u8 = 2;
+
+/// Identifies a series of measurements.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SeriesKey {
+    pub metric: String,
+    pub resource_kind: String,
+    pub resource_id: String,
+    pub consumer_kind: String,
+    pub consumer_id: String,
+}
+
+/// The average of a series over a window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rollup {
+    pub series: Arc<SeriesKey>,
+    /// Start of the window.
+    pub start: SystemTime,
+    pub mean: f64,
+    /// Number of samples in the window.
+    pub count: u64,
+}
+
+/// State of a series in a file, needed to compute the differences.
+struct SeriesState {
+    id: u64,
+    window: i64,
+    value: i64,
+}
+
+/// Writes rollups to a file.
+pub struct Encoder<W: Write> {
+    writer: W,
+    scale: f64,
+    series: HashMap<Arc<SeriesKey>, SeriesState>,
+}
+
+impl<W: Write> Encoder<W> {
+    /// Writes the header of a new file.
+    pub fn new(mut writer: W, resolution: Duration, precision: u32) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        write_varint(&mut writer, VERSION)?;
+        write_varint(&mut writer, resolution.as_millis() as u64)?;
+        write_varint(&mut writer, precision as u64)?;
+        Ok(Self {
+            writer,
+            scale: 10f64.powi(precision as i32),
+            series: HashMap::new(),
+        })
+    }
+
+    /// Writes the average of `series` over the `window`-th window since the Unix epoch.
+    pub fn write(&mut self, series: &Arc<SeriesKey>, window: u64, mean: f64, count: u64) -> io::Result<()> {
+        if !self.series.contains_key(series) {
+            let id = self.series.len() as u64;
+            self.writer.write_all(&[TAG_SERIES])?;
+            write_varint(&mut self.writer, id)?;
+            for s in [
+                &series.metric,
+                &series.resource_kind,
+                &series.resource_id,
+                &series.consumer_kind,
+                &series.consumer_id,
+            ] {
+                write_varint(&mut self.writer, s.len() as u64)?;
+                self.writer.write_all(s.as_bytes())?;
+            }
+            let state = SeriesState {
+                id,
+                window: 0,
+                value: 0,
+            };
+            self.series.insert(series.clone(), state);
+        }
+        let state = self.series.get_mut(series).unwrap();
+        let window = window as i64;
+        let value = (mean * self.scale).round() as i64;
+        self.writer.write_all(&[TAG_ROLLUP])?;
+        write_varint(&mut self.writer, state.id)?;
+        write_varint(&mut self.writer, zigzag(window.wrapping_sub(state.window)))?;
+        write_varint(&mut self.writer, zigzag(value.wrapping_sub(state.value)))?;
+        write_varint(&mut self.writer, count)?;
+        state.window = window;
+        state.value = value;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads the rollups of a file.
+pub struct Reader<R: Read> {
+    reader: R,
+    /// Resolution of the rollups, in milliseconds.
+    resolution: u64,
+    scale: f64,
+    /// The series of the file, indexed by id, and their state.
+    series: Vec<(Arc<SeriesKey>, i64, i64)>,
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads the header of the file.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a rollup file"));
+        }
+        let version = read_varint(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("unsupported version {version}")));
+        }
+        let resolution = read_varint(&mut reader)?;
+        let precision = read_varint(&mut reader)?;
+        Ok(Self {
+            reader,
+            resolution,
+            scale: 10f64.powi(precision as i32),
+            series: Vec::new(),
+        })
+    }
+
+    /// Resolution of the rollups of the file.
+    pub fn resolution(&self) -> Duration {
+        Duration::from_millis(self.resolution)
+    }
+
+    fn read_string(&mut self) -> io::Result<String> {
+        let len = read_varint(&mut self.reader)? as usize;
+        let mut buf = vec![0; len];
+        self.reader.read_exact(&mut buf)?;
+        String::from_utf8(buf).map_err(|_| invalid_data("invalid UTF-8 string"))
+    }
+
+    /// Reads the next rollup, or returns `None` at the end of the file.
+    fn read_rollup(&mut self) -> io::Result<Option<Rollup>> {
+        loop {
+            let mut tag = [0];
+            if self.reader.read(&mut tag)? == 0 {
+                return Ok(None);
+            }
+            match tag[0] {
+                TAG_SERIES => {
+                    let id = read_varint(&mut self.reader)?;
+                    if id != self.series.len() as u64 {
+                        return Err(invalid_data(&format!("unexpected series id {id}")));
+                    }
+                    let key = SeriesKey {
+                        metric: self.read_string()?,
+                        resource_kind: self.read_string()?,
+                        resource_id: self.read_string()?,
+                        consumer_kind: self.read_string()?,
+                        consumer_id: self.read_string()?,
+                    };
+                    self.series.push((Arc::new(key), 0, 0));
+                }
+                TAG_ROLLUP => {
+                    let id = read_varint(&mut self.reader)? as usize;
+                    let window_delta = unzigzag(read_varint(&mut self.reader)?);
+                    let value_delta = unzigzag(read_varint(&mut self.reader)?);
+                    let count = read_varint(&mut self.reader)?;
+                    let (series, window, value) = self
+                        .series
+                        .get_mut(id)
+                        .ok_or_else(|| invalid_data(&format!("unknown series id {id}")))?;
+                    *window = window.wrapping_add(window_delta);
+                    *value = value.wrapping_add(value_delta);
+                    return Ok(Some(Rollup {
+                        series: series.clone(),
+                        start: UNIX_EPOCH + Duration::from_millis(self.resolution.saturating_mul(*window as u64)),
+                        mean: *value as f64 / self.scale,
+                        count,
+                    }));
+                }
+                tag => return Err(invalid_data(&format!("unknown record {tag}"))),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = io::Result<Rollup>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_rollup().transpose()
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_owned())
+}
+
+/// Maps the signed integers to unsigned integers, so that the small negative values stay small.
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+/// Writes an unsigned LEB128 integer: 7 bits per byte, the high bit is set on all the bytes but the last.
+fn write_varint(w: &mut impl Write, mut n: u64) -> io::Result<()> {
+    let mut buf = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    w.write_all(&buf[..len])
+}
+
+fn read_varint(r: &mut impl Read) -> io::Result<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        r.read_exact(&mut byte)?;
+        n |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(invalid_data("varint is too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use pretty_assertions::assert_eq;
+
+    use super::{Encoder, Reader, SeriesKey, read_varint, unzigzag, write_varint, zigzag};
+
+    #[test]
+    fn integers() {
+        for n in [0, 1, -1, 63, -64, 1_700_000_000_000, i64::MAX, i64::MIN] {
+            assert_eq!(unzigzag(zigzag(n)), n);
+        }
+        assert_eq!(zigzag(-1), 1);
+        assert_eq!(zigzag(1), 2);
+
+        let mut buf = Vec::new();
+        for n in [0, 127, 128, 300, u64::MAX] {
+            write_varint(&mut buf, n).unwrap();
+        }
+        assert_eq!(&buf[..4], &[0, 127, 0x80, 1]);
+        let mut r = &buf[..];
+        for n in [0, 127, 128, 300, u64::MAX] {
+            assert_eq!(read_varint(&mut r).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let series = Arc::new(SeriesKey {
+            metric: String::from("rapl_consumed_energy"),
+            resource_kind: String::from("cpu_package"),
+            resource_id: String::from("0"),
+            consumer_kind: String::from("local_machine"),
+            consumer_id: String::new(),
+        });
+        let mut buf = Vec::new();
+        let mut encoder = Encoder::new(&mut buf, Duration::from_secs(1), 3).unwrap();
+        encoder.write(&series, 1_700_000_000, 12.5, 10).unwrap();
+        let first = encoder.writer.len();
+        encoder.write(&series, 1_700_000_001, 12.625, 10).unwrap();
+        let second = encoder.writer.len();
+        encoder.write(&series, 1_700_000_002, 12.6251, 9).unwrap();
+        // the next rollups of the series are small
+        assert_eq!(second - first, 6);
+        assert_eq!(encoder.writer.len() - second, 5);
+
+        let rollups: Vec<_> = Reader::new(&buf[..]).unwrap().map(Result::unwrap).collect();
+        let means: Vec<f64> = rollups.iter().map(|r| r.mean).collect();
+        assert_eq!(means, vec![12.5, 12.625, 12.625]);
+        assert_eq!(rollups[2].start, UNIX_EPOCH + Duration::from_secs(1_700_000_002));
+        assert_eq!(rollups[2].count, 9);
+        assert_eq!(rollups[2].series, series);
+    }
+}
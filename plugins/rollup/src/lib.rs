@@ -0,0 +1,124 @@
+mod files;
+pub mod format;
+mod output;
+
+use std::{path::PathBuf, time::Duration};
+
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, capability::Capability, rust::AlumetPlugin};
+use anyhow::Context;
+use humantime_serde::Serde;
+use serde::{Deserialize, Serialize};
+
+use crate::files::PeriodicFiles;
+use crate::output::{Aggregator, RawWriter, RollupOutput};
+
+/// Maximum number of decimal digits of the averages, to avoid overflows.
+const MAX_PRECISION: u32 = 9;
+
+pub struct RollupPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for RollupPlugin {
+    fn name() -> &'static str {
+        "rollup"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(config.directory)]))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        for resolution in &config.resolutions {
+            anyhow::ensure!(
+                resolution.as_millis() > 0 && resolution.subsec_nanos() % 1_000_000 == 0,
+                "invalid resolution {}: it must be a whole number of milliseconds",
+                humantime::format_duration(**resolution)
+            );
+        }
+        anyhow::ensure!(
+            config.precision <= MAX_PRECISION,
+            "precision must be at most {MAX_PRECISION}"
+        );
+        anyhow::ensure!(
+            config.raw_file_duration.as_millis() > 0 && config.rollup_file_duration.as_millis() > 0,
+            "the durations of the files must be at least one millisecond"
+        );
+        Ok(Box::new(RollupPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let raw = if config.raw_retention.is_zero() {
+            None
+        } else {
+            let dir = config.directory.join("raw");
+            let files = PeriodicFiles::new(dir.clone(), "csv", config.raw_file_duration)
+                .with_context(|| format!("failed to create the directory {dir:?}"))?;
+            Some(RawWriter::new(files, config.raw_retention))
+        };
+        let mut aggregators = Vec::with_capacity(config.resolutions.len());
+        for resolution in &config.resolutions {
+            let dir = config
+                .directory
+                .join(humantime::format_duration(**resolution).to_string().replace(' ', ""));
+            let files = PeriodicFiles::new(dir.clone(), "rollup", config.rollup_file_duration)
+                .with_context(|| format!("failed to create the directory {dir:?}"))?;
+            aggregators.push(Aggregator::new(**resolution, config.precision, files));
+        }
+        let output = RollupOutput::new(raw, aggregators);
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Directory of the files. The raw samples are in `raw/`, the rollups in a directory per resolution, such as `1m/`.
+    pub directory: PathBuf,
+    /// How long the raw samples are kept. `0s` disables the raw files.
+    #[serde(with = "humantime_serde")]
+    pub raw_retention: Duration,
+    /// Period covered by each raw file.
+    #[serde(with = "humantime_serde")]
+    pub raw_file_duration: Duration,
+    /// Resolutions of the rollups: the samples of each series are averaged over windows of these durations.
+    pub resolutions: Vec<Serde<Duration>>,
+    /// Number of decimal digits of the averages.
+    pub precision: u32,
+    /// Period covered by each rollup file.
+    #[serde(with = "humantime_serde")]
+    pub rollup_file_duration: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("alumet-rollup"),
+            raw_retention: Duration::from_secs(24 * 3600),
+            raw_file_duration: Duration::from_secs(3600),
+            resolutions: vec![
+                Serde::from(Duration::from_secs(1)),
+                Serde::from(Duration::from_secs(60)),
+            ],
+            precision: 3,
+            rollup_file_duration: Duration::from_secs(24 * 3600),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+    pipeline::elements::{error::WriteError, output::OutputContext},
+};
+use anyhow::Context;
+
+use crate::{
+    files::PeriodicFiles,
+    format::{Encoder, SeriesKey},
+};
+
+pub struct RollupOutput {
+    /// Writer of the raw samples, if they are kept.
+    raw: Option<RawWriter>,
+    aggregators: Vec<Aggregator>,
+}
+
+/// Writes the raw samples to CSV files, which are deleted after some time.
+pub struct RawWriter {
+    files: PeriodicFiles,
+    retention: Duration,
+    /// The current period and its file.
+    current: Option<(u64, BufWriter<File>)>,
+}
+
+/// Averages the samples over windows of a given resolution, and writes the averages to rollup files.
+pub struct Aggregator {
+    /// Resolution of the rollups, in milliseconds.
+    resolution: u64,
+    precision: u32,
+    files: PeriodicFiles,
+    /// The window that is being filled, for each series.
+    windows: BTreeMap<Arc<SeriesKey>, Window>,
+    /// The current period and its file.
+    current: Option<(u64, Encoder<BufWriter<File>>)>,
+}
+
+struct Window {
+    index: u64,
+    sum: f64,
+    count: u64,
+}
+
+impl RollupOutput {
+    pub fn new(raw: Option<RawWriter>, aggregators: Vec<Aggregator>) -> Self {
+        Self { raw, aggregators }
+    }
+}
+
+impl alumet::pipeline::Output for RollupOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
+            };
+            if let Some(raw) = &mut self.raw {
+                raw.write(m, &metric.name, value)
+                    .context("failed to write the raw samples")?;
+            }
+            // An average with a NaN or an infinite value would be meaningless.
+            if self.aggregators.is_empty() || !value.is_finite() {
+                continue;
+            }
+            let series = Arc::new(SeriesKey {
+                metric: metric.name.clone(),
+                resource_kind: m.resource.kind().to_owned(),
+                resource_id: m.resource.id_display().to_string(),
+                consumer_kind: m.consumer.kind().to_owned(),
+                consumer_id: m.consumer.id_display().to_string(),
+            });
+            let (secs, nanos) = m.timestamp.to_unix_timestamp();
+            let millis = secs * 1000 + nanos as u64 / 1_000_000;
+            for aggregator in &mut self.aggregators {
+                aggregator
+                    .add(&series, millis, value)
+                    .context("failed to write the rollups")?;
+            }
+        }
+
+        if let Some(raw) = &mut self.raw {
+            raw.flush().context("failed to write the raw samples")?;
+        }
+        for aggregator in &mut self.aggregators {
+            aggregator.flush().context("failed to write the rollups")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RollupOutput {
+    fn drop(&mut self) {
+        // Write the windows that are still open.
+        for aggregator in &mut self.aggregators {
+            if let Err(e) = aggregator.close() {
+                log::error!("Failed to write the last rollups: {e}");
+            }
+        }
+    }
+}
+
+impl RawWriter {
+    pub fn new(files: PeriodicFiles, retention: Duration) -> Self {
+        Self {
+            files,
+            retention,
+            current: None,
+        }
+    }
+
+    fn write(&mut self, m: &MeasurementPoint, metric: &str, value: f64) -> io::Result<()> {
+        let time = SystemTime::from(m.timestamp);
+        let period = self.files.period_of(time);
+        // The files are only opened forward in time: late samples go to the current file.
+        if self.current.as_ref().is_none_or(|(p, _)| period > *p) {
+            if let Some((_, mut previous)) = self.current.take() {
+                previous.flush()?;
+            }
+            self.files.delete_older_than(self.retention)?;
+            let path = self.files.path(period, false);
+            let is_new = !path.exists();
+            let mut file = BufWriter::new(File::options().create(true).append(true).open(&path)?);
+            if is_new {
+                writeln!(
+                    file,
+                    "metric;timestamp;value;resource_kind;resource_id;consumer_kind;consumer_id;attributes"
+                )?;
+            }
+            self.current = Some((period, file));
+        }
+
+        let (_, file) = self.current.as_mut().unwrap();
+        let attributes: Vec<String> = m.attributes().map(|(k, v)| escape(&format!("{k}={v}"))).collect();
+        writeln!(
+            file,
+            "{};{};{value};{};{};{};{};{}",
+            escape(metric),
+            humantime::format_rfc3339(time),
+            m.resource.kind(),
+            escape(&m.resource.id_display().to_string()),
+            m.consumer.kind(),
+            escape(&m.consumer.id_display().to_string()),
+            attributes.join(",")
+        )
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((_, file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Replaces the separators of the CSV fields and of the attributes.
+fn escape(s: &str) -> String {
+    s.replace([';', ',', '\n'], "_")
+}
+
+impl Aggregator {
+    pub fn new(resolution: Duration, precision: u32, files: PeriodicFiles) -> Self {
+        Self {
+            resolution: resolution.as_millis() as u64,
+            precision,
+            files,
+            windows: BTreeMap::new(),
+            current: None,
+        }
+    }
+
+    /// Adds a sample taken at `millis` milliseconds since the Unix epoch.
+    fn add(&mut self, series: &Arc<SeriesKey>, millis: u64, value: f64) -> io::Result<()> {
+        let index = millis / self.resolution;
+        let window = Window {
+            index,
+            sum: value,
+            count: 1,
+        };
+        match self.windows.get_mut(series) {
+            Some(w) if w.index == index => {
+                w.sum += value;
+                w.count += 1;
+            }
+            Some(w) if w.index < index => {
+                // The previous window of the series is complete.
+                let complete = std::mem::replace(w, window);
+                self.emit(series, complete)?;
+            }
+            Some(_) => {
+                // The window of this late sample has already been written: write it on its own.
+                self.emit(series, window)?;
+            }
+            None => {
+                self.windows.insert(series.clone(), window);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the average of a window.
+    fn emit(&mut self, series: &Arc<SeriesKey>, window: Window) -> io::Result<()> {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_millis(window.index * self.resolution);
+        let period = self.files.period_of(start);
+        // Like the raw files, the rollup files are only opened forward in time.
+        if self.current.as_ref().is_none_or(|(p, _)| period > *p) {
+            if let Some((_, mut previous)) = self.current.take() {
+                previous.flush()?;
+            }
+            // A rollup file cannot be extended after a restart: the series are declared at the beginning.
+            let path = self.files.path(period, true);
+            let file = BufWriter::new(File::create(&path)?);
+            let encoder = Encoder::new(file, Duration::from_millis(self.resolution), self.precision)?;
+            self.current = Some((period, encoder));
+        }
+        let (_, encoder) = self.current.as_mut().unwrap();
+        encoder.write(series, window.index, window.sum / window.count as f64, window.count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((_, encoder)) => encoder.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes all the open windows, in chronological order.
+    fn close(&mut self) -> io::Result<()> {
+        let mut windows: Vec<_> = std::mem::take(&mut self.windows).into_iter().collect();
+        windows.sort_by_key(|(_, w)| w.index);
+        for (series, window) in windows {
+            self.emit(&series, window)?;
+        }
+        self.flush()
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    plugin::rust::serialize_config,
+    resources::{Resource, ResourceConsumer},
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use humantime_serde::Serde;
+use plugin_rollup::{Config, RollupPlugin, format::Reader};
+use pretty_assertions::assert_eq;
+
+fn config(dir: &Path) -> Config {
+    Config {
+        directory: dir.to_owned(),
+        ..Config::default()
+    }
+}
+
+/// Writes the sample measurements, plus a few values of the first series in the next windows.
+fn write(config: Config) -> anyhow::Result<()> {
+    let mut harness = PluginHarness::<RollupPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("out")?;
+
+    let mut buffer = sample.measurements();
+    let push = |buffer: &mut MeasurementBuffer, offset: Duration, value: f64| {
+        let t = Timestamp::from(UNIX_EPOCH + SampleMeasurements::START + offset);
+        buffer.push(MeasurementPoint::new(
+            t,
+            sample.energy,
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            value,
+        ));
+    };
+    push(&mut buffer, Duration::from_millis(500), 13.5);
+    push(&mut buffer, Duration::from_millis(2000), 14.0);
+    push(&mut buffer, Duration::from_millis(2250), f64::NAN);
+    push(&mut buffer, Duration::from_millis(2500), 15.0);
+    output.write(&buffer).unwrap();
+    // the output writes the open windows when it stops
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+/// Decodes a rollup file to text.
+fn decode(path: &Path) -> String {
+    let reader = Reader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+    let mut res = format!("resolution: {}\n", humantime::format_duration(reader.resolution()));
+    for rollup in reader {
+        let rollup = rollup.unwrap();
+        let s = &rollup.series;
+        res.push_str(&format!(
+            "{};{};{};{};{};{};{};{}\n",
+            s.metric,
+            humantime::format_rfc3339(rollup.start),
+            rollup.mean,
+            rollup.count,
+            s.resource_kind,
+            s.resource_id,
+            s.consumer_kind,
+            s.consumer_id
+        ));
+    }
+    res
+}
+
+#[test]
+fn files() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    write(config(tmp.path()))?;
+
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/raw.csv"),
+        fs::read_to_string(tmp.path().join("raw/20231114T220000.csv"))?,
+    );
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/rollup_1s.txt"),
+        decode(&tmp.path().join("1s/20231114T000000.rollup")),
+    );
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/rollup_1m.txt"),
+        decode(&tmp.path().join("1m/20231114T000000.rollup")),
+    );
+    Ok(())
+}
+
+#[test]
+fn restart() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    write(config(tmp.path()))?;
+    write(config(tmp.path()))?;
+
+    // the raw samples are appended, but the rollups go to a new file
+    let raw = fs::read_to_string(tmp.path().join("raw/20231114T220000.csv"))?;
+    assert_eq!(raw.lines().count(), 1 + 2 * 9);
+    let mut rollups: Vec<_> = fs::read_dir(tmp.path().join("1m"))?
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    rollups.sort();
+    assert_eq!(rollups, vec!["20231114T000000-1.rollup", "20231114T000000.rollup"]);
+    Ok(())
+}
+
+#[test]
+fn without_raw_samples() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let config = Config {
+        raw_retention: Duration::ZERO,
+        resolutions: vec![Serde::from(Duration::from_millis(250))],
+        ..config(tmp.path())
+    };
+    write(config)?;
+
+    assert!(!tmp.path().join("raw").exists());
+    let rollups = decode(&tmp.path().join("250ms/20231114T000000.rollup"));
+    assert!(rollups.starts_with("resolution: 250ms\n"));
+    // 5 sample windows, 3 more windows of the first series
+    assert_eq!(rollups.lines().count(), 1 + 8);
+    Ok(())
+}
+
+#[test]
+fn invalid_config() {
+    let tmp = tempfile::tempdir().unwrap();
+    let config = Config {
+        resolutions: vec![Serde::from(Duration::from_micros(500))],
+        ..config(tmp.path())
+    };
+    let res = PluginHarness::<RollupPlugin>::start(serialize_config(config).unwrap());
+    assert!(res.is_err());
+}
@@ -0,0 +1,197 @@
+mod output;
+mod path;
+mod rrdtool;
+mod schema;
+
+use std::{path::PathBuf, time::Duration};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::output::RrdOutput;
+use crate::path::PathTemplate;
+use crate::schema::Schema;
+
+pub struct RrdPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for RrdPlugin {
+    fn name() -> &'static str {
+        "rrd"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::ProcessSpawn])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        Schema::new(&config)?;
+        Ok(Box::new(RrdPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = hostname::get()
+            .context("unable to retrieve the hostname of the machine")?
+            .to_string_lossy()
+            .to_string();
+        let path = PathTemplate::parse(&config.file, &hostname)?;
+        let output = RrdOutput::new(
+            config.rrdtool.clone(),
+            config.directory.clone(),
+            path,
+            Schema::new(config)?,
+            config.metrics.iter().cloned().collect(),
+        )?;
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Directory of the RRD files.
+    pub directory: PathBuf,
+    /// Path of the RRD file of each series, relative to `directory`.
+    ///
+    /// See [`MeasurementPlaceholder`](alumet::template::MeasurementPlaceholder) for the available placeholders,
+    /// in addition to `{hostname}`.
+    pub file: String,
+    /// The metrics to write. If empty, every metric is written.
+    pub metrics: Vec<String>,
+    /// Interval between two primary data points of the files.
+    #[serde(with = "humantime_serde")]
+    pub step: Duration,
+    /// Maximum time between two updates before the value becomes unknown.
+    #[serde(with = "humantime_serde")]
+    pub heartbeat: Duration,
+    /// How rrdtool interprets the values.
+    pub data_source_type: DataSourceType,
+    /// The values below this minimum are stored as unknown.
+    pub min: Option<f64>,
+    /// The values above this maximum are stored as unknown.
+    pub max: Option<f64>,
+    /// The round-robin archives of each file.
+    pub archives: Vec<Archive>,
+    /// Path to the rrdtool executable.
+    pub rrdtool: PathBuf,
+}
+
+/// A round-robin archive, which consolidates the values with a given resolution, for a given time.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Archive {
+    pub function: ConsolidationFunction,
+    /// Duration of each row of the archive. It must be a multiple of the step.
+    #[serde(with = "humantime_serde")]
+    pub resolution: Duration,
+    /// Duration covered by the archive.
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+    /// Fraction of a row that can be unknown, while the consolidated value is still known.
+    #[serde(default = "default_xff")]
+    pub xff: f64,
+}
+
+/// Type of the data source, see `rrdcreate(1)`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DataSourceType {
+    /// The value is stored as is.
+    Gauge,
+    /// The value is a counter that only increases: the rate of change is stored.
+    Counter,
+    /// The value is a counter that can decrease: the rate of change is stored.
+    Derive,
+    /// The value is reset on each update: the value divided by the interval is stored.
+    /// With the energy measurements of Alumet, this stores the power.
+    Absolute,
+}
+
+/// Consolidation function of an archive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ConsolidationFunction {
+    Average,
+    Min,
+    Max,
+    Last,
+}
+
+impl DataSourceType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DataSourceType::Gauge => "GAUGE",
+            DataSourceType::Counter => "COUNTER",
+            DataSourceType::Derive => "DERIVE",
+            DataSourceType::Absolute => "ABSOLUTE",
+        }
+    }
+}
+
+impl ConsolidationFunction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConsolidationFunction::Average => "AVERAGE",
+            ConsolidationFunction::Min => "MIN",
+            ConsolidationFunction::Max => "MAX",
+            ConsolidationFunction::Last => "LAST",
+        }
+    }
+}
+
+fn default_xff() -> f64 {
+    0.5
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        const DAY: Duration = Duration::from_secs(24 * 3600);
+        let archive = |function, resolution, retention| Archive {
+            function,
+            resolution,
+            retention,
+            xff: default_xff(),
+        };
+        Self {
+            directory: PathBuf::from("alumet-rrd"),
+            file: String::from("{metric}/{resource_kind}_{resource_id}-{consumer_kind}_{consumer_id}.rrd"),
+            metrics: Vec::new(),
+            step: Duration::from_secs(10),
+            heartbeat: Duration::from_secs(30),
+            data_source_type: DataSourceType::Gauge,
+            min: None,
+            max: None,
+            archives: vec![
+                // 10 seconds for a day, 1 minute for a week, 1 hour for a year (365.25 days)
+                archive(ConsolidationFunction::Average, Duration::from_secs(10), DAY),
+                archive(ConsolidationFunction::Average, Duration::from_secs(60), 7 * DAY),
+                archive(
+                    ConsolidationFunction::Average,
+                    Duration::from_secs(3600),
+                    DAY * 36525 / 100,
+                ),
+                archive(ConsolidationFunction::Max, Duration::from_secs(3600), DAY * 36525 / 100),
+            ],
+            rrdtool: PathBuf::from("rrdtool"),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::{
+        Output,
+        elements::{
+            error::WriteError,
+            output::{OutputContext, error::WriteRetry},
+        },
+    },
+};
+use anyhow::Context;
+
+use crate::{
+    path::PathTemplate,
+    rrdtool::{Reply, Rrdtool},
+    schema::Schema,
+};
+
+pub struct RrdOutput {
+    /// The rrdtool executable.
+    executable: PathBuf,
+    /// The directory of the files.
+    directory: PathBuf,
+    path: PathTemplate,
+    schema: Schema,
+    /// The metrics to write, or an empty set for all the metrics.
+    metrics: HashSet<String>,
+    /// `None` if the process has failed and must be restarted.
+    rrdtool: Option<Rrdtool>,
+    /// The files that have been used since the start, by path.
+    files: HashMap<String, FileState>,
+}
+
+enum FileState {
+    /// The file exists, and has been updated at this time (in seconds since the Unix epoch).
+    Ready { last_update: u64 },
+    /// rrdtool refused to create or to update the file.
+    Ignored,
+}
+
+impl RrdOutput {
+    pub fn new(
+        executable: PathBuf,
+        directory: PathBuf,
+        path: PathTemplate,
+        schema: Schema,
+        metrics: HashSet<String>,
+    ) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&directory)
+            .with_context(|| format!("failed to create the directory {}", directory.display()))?;
+        // Start rrdtool now to report errors early.
+        let rrdtool = Rrdtool::spawn(&executable, &directory)?;
+        Ok(Self {
+            executable,
+            directory,
+            path,
+            schema,
+            metrics,
+            rrdtool: Some(rrdtool),
+            files: HashMap::new(),
+        })
+    }
+
+    /// Writes the points of a file, and creates the file if needed.
+    ///
+    /// `points` contains the timestamp (in seconds) and the value of each point.
+    /// An error is returned if rrdtool failed and must be restarted.
+    fn write_file(&mut self, path: &str, mut points: Vec<(u64, String)>) -> anyhow::Result<()> {
+        let rrdtool = match &mut self.rrdtool {
+            Some(rrdtool) => rrdtool,
+            None => self.rrdtool.insert(Rrdtool::spawn(&self.executable, &self.directory)?),
+        };
+        // Stable sort: the points of the same second stay in the order of the measurements.
+        points.sort_by_key(|(t, _)| *t);
+
+        let last_update = match self.files.get(path) {
+            Some(FileState::Ready { last_update }) => *last_update,
+            Some(FileState::Ignored) => return Ok(()),
+            None => {
+                let res = if self.directory.join(path).exists() {
+                    // The file has been created by a previous run of the agent.
+                    rrdtool
+                        .execute(&[String::from("last"), path.to_owned()])?
+                        .into_result()
+                        .and_then(|lines| {
+                            lines
+                                .first()
+                                .and_then(|l| l.trim().parse().ok())
+                                .ok_or_else(|| format!("unexpected output of rrdtool last: {lines:?}"))
+                        })
+                } else {
+                    let first = points[0].0;
+                    match create_parent(&self.directory, path) {
+                        Ok(()) => rrdtool
+                            .execute(&self.schema.create_command(path, first))?
+                            .into_result()
+                            .map(|_| first - 1),
+                        Err(e) => Err(format!("{e:#}")),
+                    }
+                };
+                match res {
+                    Ok(last_update) => last_update,
+                    Err(msg) => {
+                        log::warn!(
+                            "Unable to use the RRD file {path}: {msg}. This file is ignored until Alumet restarts."
+                        );
+                        self.files.insert(path.to_owned(), FileState::Ignored);
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        // rrdtool accepts at most one value per second, and only after the last update.
+        let mut command = vec![String::from("update"), path.to_owned()];
+        let mut new_last_update = last_update;
+        for (i, (t, value)) in points.iter().enumerate() {
+            let overwritten = points.get(i + 1).is_some_and(|(next, _)| next == t);
+            if *t > last_update && !overwritten {
+                command.push(format!("{t}:{value}"));
+                new_last_update = *t;
+            }
+        }
+        if command.len() == 2 {
+            log::debug!("No new point for the RRD file {path}.");
+            self.files.insert(path.to_owned(), FileState::Ready { last_update });
+            return Ok(());
+        }
+        let state = match rrdtool.execute(&command)? {
+            Reply::Ok(_) => FileState::Ready {
+                last_update: new_last_update,
+            },
+            Reply::Error(msg) => {
+                log::warn!("rrdtool refused to update {path}: {msg}. This file is ignored until Alumet restarts.");
+                FileState::Ignored
+            }
+        };
+        self.files.insert(path.to_owned(), state);
+        Ok(())
+    }
+}
+
+fn create_parent(directory: &Path, path: &str) -> anyhow::Result<()> {
+    if let Some(parent) = directory.join(path).parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+impl Output for RrdOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        // Group the points by file, sorted by path to have the same order on each run.
+        let mut files: BTreeMap<String, Vec<(u64, String)>> = BTreeMap::new();
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            if !self.metrics.is_empty() && !self.metrics.contains(&metric.name) {
+                continue;
+            }
+            let (t, _) = m.timestamp.to_unix_timestamp();
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) if v.is_finite() => v.to_string(),
+                // unknown value
+                WrappedMeasurementValue::F64(_) => String::from("U"),
+                WrappedMeasurementValue::U64(v) => v.to_string(),
+            };
+            files.entry(self.path.render(m, metric)).or_default().push((t, value));
+        }
+
+        for (path, points) in files {
+            if let Err(e) = self.write_file(&path, points) {
+                // Restart rrdtool on the next write. The files that have already been updated will skip the points
+                // that they already have.
+                self.rrdtool = None;
+                return Err(e).retry_write();
+            }
+        }
+        Ok(())
+    }
+}
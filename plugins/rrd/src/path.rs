@@ -0,0 +1,102 @@
+//! Templates of the paths of the RRD files, such as `{metric}/{resource_kind}_{resource_id}.rrd`.
+
+use alumet::{measurement::MeasurementPoint, metrics::Metric, template::MeasurementTemplate};
+use anyhow::{Context, anyhow};
+
+/// A parsed path template.
+#[derive(Debug, PartialEq)]
+pub struct PathTemplate(MeasurementTemplate);
+
+impl PathTemplate {
+    /// Parses a template.
+    ///
+    /// The placeholders are replaced by the corresponding values for each measurement.
+    /// The hostname is resolved here, since it does not change.
+    pub fn parse(template: &str, hostname: &str) -> anyhow::Result<Self> {
+        let parsed = MeasurementTemplate::parse_measurement(template, &sanitize(hostname))
+            .with_context(|| format!("invalid path template {template:?}"))?;
+        if parsed.is_empty() {
+            return Err(anyhow!("the path template is empty"));
+        }
+        // The commands sent to rrdtool are split on whitespace, and quotes are interpreted.
+        if parsed
+            .literal()
+            .contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\')
+        {
+            return Err(anyhow!(
+                "spaces, quotes and backslashes are not allowed in path template {template:?}"
+            ));
+        }
+        // The files must stay in the directory of the plugin.
+        if template.starts_with('/') || template.split('/').any(|c| c == "..") {
+            return Err(anyhow!(
+                "the path template {template:?} must be relative and must not contain .."
+            ));
+        }
+        Ok(Self(parsed))
+    }
+
+    /// Returns the path of the file of a measurement, relative to the directory of the plugin.
+    pub fn render(&self, m: &MeasurementPoint, metric: &Metric) -> String {
+        self.0.render_measurement(m, metric, sanitize)
+    }
+}
+
+/// Replaces the characters that are not safe in file names, or in the commands sent to rrdtool.
+///
+/// A value must not create new directories (`/`, `..`), hence the replacement of the dots.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        resources::ResourceConsumer,
+        test::fixture::{metric, point},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::PathTemplate;
+
+    #[test]
+    fn render() {
+        let template = PathTemplate::parse("{hostname}/{metric}.rrd", "node-1.example.com").unwrap();
+        assert_eq!(
+            template.render(&point(ResourceConsumer::LocalMachine), &metric()),
+            "node-1_example_com/rapl_consumed_energy.rrd"
+        );
+
+        let template = PathTemplate::parse(
+            "{resource_kind}_{resource_id}-{consumer_kind}_{consumer_id}.rrd",
+            "node",
+        )
+        .unwrap();
+        let consumer = ResourceConsumer::ControlGroup {
+            path: "/system.slice/test.service".into(),
+        };
+        assert_eq!(
+            template.render(&point(consumer), &metric()),
+            "cpu_package_1-cgroup__system_slice_test_service.rrd"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(PathTemplate::parse("{host}.rrd", "node").is_err());
+        assert!(PathTemplate::parse("{metric.rrd", "node").is_err());
+        assert!(PathTemplate::parse("my {metric}.rrd", "node").is_err());
+        assert!(PathTemplate::parse("/var/lib/{metric}.rrd", "node").is_err());
+        assert!(PathTemplate::parse("../{metric}.rrd", "node").is_err());
+        assert!(PathTemplate::parse("", "node").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! A long-running `rrdtool` process in pipe mode (`rrdtool -`), which executes one command per line.
+//!
+//! This avoids starting a new process for each update.
+//! See <https://oss.oetiker.ch/rrdtool/doc/rrdtool.en.html#IPIPE_MODE>.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use anyhow::{Context, anyhow};
+
+pub struct Rrdtool {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+/// The result of a command.
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    /// The command succeeded, with these lines of output.
+    Ok(Vec<String>),
+    /// rrdtool refused the command, with this message.
+    Error(String),
+}
+
+impl Reply {
+    pub fn into_result(self) -> Result<Vec<String>, String> {
+        match self {
+            Reply::Ok(lines) => Ok(lines),
+            Reply::Error(msg) => Err(msg),
+        }
+    }
+}
+
+impl Rrdtool {
+    /// Starts rrdtool in pipe mode.
+    ///
+    /// The relative paths of the commands are relative to `directory`.
+    pub fn spawn(executable: &Path, directory: &Path) -> anyhow::Result<Self> {
+        let mut child = Command::new(executable)
+            .arg("-")
+            .current_dir(directory)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to start {}", executable.display()))?;
+        let stdin = child.stdin.take().expect("stdin should be piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout should be piped"));
+        Ok(Self { child, stdin, stdout })
+    }
+
+    /// Executes a command and waits for its result.
+    ///
+    /// An error is returned if the process cannot execute commands anymore: it must be restarted.
+    pub fn execute(&mut self, args: &[String]) -> anyhow::Result<Reply> {
+        let command = args.join(" ");
+        writeln!(self.stdin, "{command}").context("failed to send a command to rrdtool")?;
+        self.stdin.flush().context("failed to send a command to rrdtool")?;
+
+        let mut output = Vec::new();
+        loop {
+            let mut line = String::new();
+            let n = self
+                .stdout
+                .read_line(&mut line)
+                .context("failed to read the output of rrdtool")?;
+            if n == 0 {
+                return Err(anyhow!("rrdtool exited unexpectedly"));
+            }
+            let line = line.trim_end();
+            // The last line is "OK u:<user time> s:<system time> r:<real time>", or an error.
+            if line == "OK" || line.starts_with("OK ") {
+                return Ok(Reply::Ok(output));
+            }
+            if let Some(message) = line.strip_prefix("ERROR:") {
+                return Ok(Reply::Error(message.trim().to_owned()));
+            }
+            output.push(line.to_owned());
+        }
+    }
+}
+
+impl Drop for Rrdtool {
+    fn drop(&mut self) {
+        // rrdtool exits at the end of its input, or with `quit`.
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.stdin.flush();
+        if let Err(e) = self.child.wait() {
+            log::warn!("Failed to wait for rrdtool to exit: {e}");
+        }
+    }
+}
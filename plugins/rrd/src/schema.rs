@@ -0,0 +1,183 @@
+//! Structure of the RRD files: the data source and the round-robin archives.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, ensure};
+
+use crate::{Archive, Config};
+
+/// Name of the only data source of each file.
+pub const DATA_SOURCE: &str = "value";
+
+/// The arguments of `rrdtool create`, except the path and the start time.
+#[derive(Debug, PartialEq)]
+pub struct Schema {
+    /// Interval between two primary data points, in seconds.
+    step: u64,
+    /// Definition of the data source, such as `DS:value:GAUGE:30:U:U`.
+    data_source: String,
+    /// Definitions of the archives, such as `RRA:AVERAGE:0.5:6:10080`.
+    archives: Vec<String>,
+}
+
+impl Schema {
+    /// Checks the configuration of the files, and converts the durations into steps and rows.
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let step = whole_seconds(config.step, "step")?;
+        let heartbeat = whole_seconds(config.heartbeat, "heartbeat")?;
+        ensure!(
+            heartbeat >= step,
+            "the heartbeat must be greater than or equal to the step"
+        );
+        ensure!(!config.archives.is_empty(), "at least one archive is required");
+        let bound = |b: Option<f64>| b.map(|b| b.to_string()).unwrap_or_else(|| String::from("U"));
+        let data_source = format!(
+            "DS:{DATA_SOURCE}:{}:{heartbeat}:{}:{}",
+            config.data_source_type.as_str(),
+            bound(config.min),
+            bound(config.max)
+        );
+        let archives = config
+            .archives
+            .iter()
+            .map(|a| archive(a, step))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            step,
+            data_source,
+            archives,
+        })
+    }
+
+    /// Returns the arguments of the command that creates the file at `path`,
+    /// whose first update will be at `first_update` (in seconds since the Unix epoch).
+    pub fn create_command(&self, path: &str, first_update: u64) -> Vec<String> {
+        let mut args = vec![
+            String::from("create"),
+            path.to_owned(),
+            String::from("--start"),
+            // The start is the time of the last update: the first update must come after it.
+            (first_update - 1).to_string(),
+            String::from("--step"),
+            self.step.to_string(),
+            self.data_source.clone(),
+        ];
+        args.extend(self.archives.iter().cloned());
+        args
+    }
+}
+
+fn archive(archive: &Archive, step: u64) -> anyhow::Result<String> {
+    let resolution = whole_seconds(archive.resolution, "resolution of the archive")?;
+    let retention = whole_seconds(archive.retention, "retention of the archive")?;
+    ensure!(
+        resolution % step == 0,
+        "the resolution of the archive ({resolution}s) must be a multiple of the step ({step}s)"
+    );
+    ensure!(
+        retention >= resolution,
+        "the retention of the archive must be greater than or equal to its resolution"
+    );
+    ensure!(
+        (0.0..1.0).contains(&archive.xff),
+        "the xff of the archive must be in the interval [0, 1)"
+    );
+    let steps = resolution / step;
+    let rows = retention.div_ceil(resolution);
+    Ok(format!(
+        "RRA:{}:{}:{steps}:{rows}",
+        archive.function.as_str(),
+        archive.xff
+    ))
+}
+
+fn whole_seconds(d: Duration, what: &str) -> anyhow::Result<u64> {
+    if d.subsec_nanos() != 0 || d.is_zero() {
+        return Err(anyhow!("the {what} must be a non-zero number of seconds"));
+    }
+    Ok(d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::Schema;
+    use crate::{Archive, Config, ConsolidationFunction};
+
+    #[test]
+    fn default() {
+        let schema = Schema::new(&Config::default()).unwrap();
+        assert_eq!(
+            schema.create_command("a/b.rrd", 1_700_000_000),
+            vec![
+                "create",
+                "a/b.rrd",
+                "--start",
+                "1699999999",
+                "--step",
+                "10",
+                "DS:value:GAUGE:30:U:U",
+                "RRA:AVERAGE:0.5:1:8640",
+                "RRA:AVERAGE:0.5:6:10080",
+                "RRA:AVERAGE:0.5:360:8766",
+                "RRA:MAX:0.5:360:8766",
+            ]
+        );
+    }
+
+    #[test]
+    fn bounds() {
+        let config = Config {
+            min: Some(0.0),
+            max: Some(1e6),
+            ..Config::default()
+        };
+        let schema = Schema::new(&config).unwrap();
+        assert_eq!(schema.data_source, "DS:value:GAUGE:30:0:1000000");
+    }
+
+    #[test]
+    fn invalid() {
+        let archive = |resolution: u64, retention: u64| Archive {
+            function: ConsolidationFunction::Average,
+            resolution: Duration::from_secs(resolution),
+            retention: Duration::from_secs(retention),
+            xff: 0.5,
+        };
+        let invalid = [
+            Config {
+                step: Duration::from_millis(1500),
+                ..Config::default()
+            },
+            Config {
+                heartbeat: Duration::from_secs(5),
+                ..Config::default()
+            },
+            Config {
+                archives: Vec::new(),
+                ..Config::default()
+            },
+            Config {
+                archives: vec![archive(15, 3600)],
+                ..Config::default()
+            },
+            Config {
+                archives: vec![archive(60, 30)],
+                ..Config::default()
+            },
+            Config {
+                archives: vec![Archive {
+                    xff: 1.0,
+                    ..archive(60, 3600)
+                }],
+                ..Config::default()
+            },
+        ];
+        for config in invalid {
+            assert!(Schema::new(&config).is_err());
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp},
+    pipeline::elements::error::WriteError,
+    plugin::rust::serialize_config,
+    resources::{Resource, ResourceConsumer},
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_rrd::{Config, RrdPlugin};
+use pretty_assertions::assert_eq;
+
+/// A fake rrdtool in pipe mode, which records the commands in `commands.log`.
+///
+/// `last` returns the time of the sample, and the updates are refused if the file `refuse` exists,
+/// or make the fake crash if the file `crash` exists.
+const FAKE_RRDTOOL: &str = r#"#!/bin/sh
+while read -r cmd file rest; do
+    echo "$cmd $file $rest" >> commands.log
+    case "$cmd" in
+        create) touch "$file" ;;
+        last) echo 1700000000 ;;
+        update)
+            if [ -e crash ]; then exit 1; fi
+            if [ -e refuse ]; then echo "ERROR: refused"; continue; fi
+            ;;
+        quit) exit 0 ;;
+    esac
+    echo "OK u:0.00 s:0.00 r:0.00"
+done
+"#;
+
+/// Installs the fake rrdtool in `dir`, and returns the config that uses it.
+fn config(dir: &Path) -> anyhow::Result<Config> {
+    let rrdtool = dir.join("fake-rrdtool");
+    fs::write(&rrdtool, FAKE_RRDTOOL)?;
+    fs::set_permissions(&rrdtool, fs::Permissions::from_mode(0o755))?;
+    let directory = dir.join("rrd");
+    fs::create_dir_all(&directory)?;
+    Ok(Config {
+        directory,
+        rrdtool,
+        ..Config::default()
+    })
+}
+
+/// Returns the commands received by the fake rrdtool.
+fn commands(directory: &Path) -> String {
+    fs::read_to_string(directory.join("commands.log")).unwrap_or_default()
+}
+
+/// The energy of the first CPU package, at `START + secs`.
+fn energy(sample: &SampleMeasurements, secs: f64, value: f64) -> MeasurementBuffer {
+    let t = Timestamp::from(UNIX_EPOCH + SampleMeasurements::START + Duration::from_secs_f64(secs));
+    MeasurementBuffer::from(vec![MeasurementPoint::new(
+        t,
+        sample.energy,
+        Resource::CpuPackage { id: 0 },
+        ResourceConsumer::LocalMachine,
+        value,
+    )])
+}
+
+#[test]
+fn create_and_update() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = config(dir.path())?;
+    let directory = config.directory.clone();
+    let (mut harness, sample) = SampleMeasurements::start::<RrdPlugin>(config)?;
+    let mut output = harness.output("out")?;
+
+    output.write(&sample.measurements()).unwrap();
+    // already written
+    output.write(&sample.measurements()).unwrap();
+    // several points in the same second: only the last one is kept
+    let mut buffer = energy(&sample, 2.0, 1.0);
+    buffer.merge(&mut energy(&sample, 3.0, 2.0));
+    buffer.merge(&mut energy(&sample, 3.5, 3.0));
+    buffer.merge(&mut energy(&sample, 4.0, f64::NAN));
+    output.write(&buffer).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/commands.log"),
+        commands(&directory),
+    );
+    assert!(
+        directory
+            .join("sample_energy/cpu_package_0-local_machine_.rrd")
+            .exists()
+    );
+    Ok(())
+}
+
+#[test]
+fn existing_file() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = Config {
+        metrics: vec![String::from("sample_energy")],
+        ..config(dir.path())?
+    };
+    let directory = config.directory.clone();
+    let file = directory.join("sample_energy/cpu_package_0-local_machine_.rrd");
+    fs::create_dir_all(file.parent().unwrap())?;
+    fs::write(&file, "")?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<RrdPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    // not after the last update of the file
+    output.write(&energy(&sample, 0.0, 1.0)).unwrap();
+    output.write(&energy(&sample, 1.0, 2.0)).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    assert_eq!(
+        commands(&directory),
+        "last sample_energy/cpu_package_0-local_machine_.rrd \n\
+         update sample_energy/cpu_package_0-local_machine_.rrd 1700000001:2\n\
+         quit  \n"
+    );
+    Ok(())
+}
+
+#[test]
+fn refused() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = config(dir.path())?;
+    let directory = config.directory.clone();
+    fs::write(directory.join("refuse"), "")?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<RrdPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&energy(&sample, 0.0, 1.0)).unwrap();
+    // the file is ignored after the refusal
+    output.write(&energy(&sample, 1.0, 2.0)).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let updates = commands(&directory).lines().filter(|l| l.starts_with("update")).count();
+    assert_eq!(updates, 1);
+    Ok(())
+}
+
+#[test]
+fn restart_after_crash() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = config(dir.path())?;
+    let directory = config.directory.clone();
+    fs::write(directory.join("crash"), "")?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<RrdPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&energy(&sample, 5.0, 1.0));
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    // the next process does not crash, and starts from the last update of the file
+    fs::remove_file(directory.join("crash"))?;
+    output.write(&energy(&sample, 5.0, 1.0)).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let commands = commands(&directory);
+    let commands: Vec<&str> = commands.lines().map(|l| l.split(' ').next().unwrap()).collect();
+    assert_eq!(commands, vec!["create", "update", "last", "update", "quit"]);
+    Ok(())
+}
+
+#[test]
+fn missing_rrdtool() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config = Config {
+        directory: dir.path().join("rrd"),
+        rrdtool: PathBuf::from("/nonexistent/rrdtool"),
+        ..Config::default()
+    };
+    assert!(PluginHarness::<RrdPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
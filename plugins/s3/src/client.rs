@@ -0,0 +1,217 @@
+//! Minimal client of the S3 API: upload of objects, with multipart upload for the big files.
+
+use std::{fs::File, io::Read, path::Path, time::Duration};
+
+use anyhow::{Context, anyhow};
+use reqwest::{
+    Method, Url,
+    blocking::{Client, Response},
+};
+use time::OffsetDateTime;
+
+use alumet_sigv4::{self as sigv4, Credentials, EMPTY_SHA256};
+
+/// Minimum size of a part of a multipart upload, except the last one.
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Maximum number of parts of a multipart upload.
+const MAX_PARTS: u64 = 10_000;
+
+pub struct S3Client {
+    http: Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    /// Use `endpoint/bucket/key` instead of `bucket.endpoint/key`.
+    path_style: bool,
+    credentials: Credentials,
+}
+
+impl S3Client {
+    pub fn new(
+        endpoint: &str,
+        bucket: String,
+        region: String,
+        path_style: bool,
+        credentials: Credentials,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let endpoint = Url::parse(endpoint).with_context(|| format!("invalid endpoint {endpoint:?}"))?;
+        if endpoint.host_str().is_none() {
+            return Err(anyhow!("invalid endpoint {endpoint}: missing host"));
+        }
+        let http = Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("failed to create the HTTP client")?;
+        Ok(Self {
+            http,
+            endpoint,
+            bucket,
+            region,
+            path_style,
+            credentials,
+        })
+    }
+
+    /// Uploads a file to `key`, in several parts if it is bigger than `multipart_threshold`.
+    pub fn upload_file(&self, path: &Path, key: &str, multipart_threshold: u64, part_size: u64) -> anyhow::Result<()> {
+        let mut file = File::open(path).with_context(|| format!("failed to open {path:?}"))?;
+        let size = file.metadata()?.len();
+        if size <= multipart_threshold {
+            let mut data = Vec::with_capacity(size as usize);
+            file.read_to_end(&mut data)?;
+            self.request(Method::PUT, key, &[], data)?;
+        } else {
+            // Use bigger parts if the file would need too many parts.
+            let part_size = part_size.max(size.div_ceil(MAX_PARTS));
+            self.multipart_upload(file, key, part_size)?;
+        }
+        Ok(())
+    }
+
+    fn multipart_upload(&self, mut file: File, key: &str, part_size: u64) -> anyhow::Result<()> {
+        let res = self.request(Method::POST, key, &[("uploads", "")], Vec::new())?;
+        let body = res.text()?;
+        let upload_id = xml_element(&body, "UploadId")
+            .ok_or_else(|| anyhow!("invalid response to CreateMultipartUpload: {body}"))?
+            .to_owned();
+
+        let res = self.upload_parts(&mut file, key, &upload_id, part_size);
+        if res.is_err() {
+            // Delete the parts that have already been uploaded, they are billed until the upload is aborted.
+            if let Err(e) = self.request(Method::DELETE, key, &[("uploadId", &upload_id)], Vec::new()) {
+                log::warn!("Failed to abort the multipart upload of {key}: {e:#}");
+            }
+        }
+        res
+    }
+
+    fn upload_parts(&self, file: &mut File, key: &str, upload_id: &str, part_size: u64) -> anyhow::Result<()> {
+        let mut etags = Vec::new();
+        loop {
+            let mut part = Vec::with_capacity(part_size as usize);
+            file.by_ref().take(part_size).read_to_end(&mut part)?;
+            if part.is_empty() {
+                break;
+            }
+            let part_number = (etags.len() + 1).to_string();
+            let res = self.request(
+                Method::PUT,
+                key,
+                &[("partNumber", &part_number), ("uploadId", upload_id)],
+                part,
+            )?;
+            let etag = res
+                .headers()
+                .get("etag")
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| anyhow!("missing ETag in the response to UploadPart {part_number}"))?
+                .to_owned();
+            etags.push(etag);
+        }
+
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for (i, etag) in etags.iter().enumerate() {
+            body.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                i + 1,
+                escape_xml(etag)
+            ));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+        let res = self.request(Method::POST, key, &[("uploadId", upload_id)], body.into_bytes())?;
+
+        // CompleteMultipartUpload can fail after the status 200 has been sent.
+        let body = res.text()?;
+        if body.contains("<Error>") {
+            return Err(anyhow!("CompleteMultipartUpload failed: {body}"));
+        }
+        Ok(())
+    }
+
+    /// Sends a signed request about an object, and checks its status.
+    fn request(&self, method: Method, key: &str, query: &[(&str, &str)], body: Vec<u8>) -> anyhow::Result<Response> {
+        let mut url = self.endpoint.clone();
+        let encoded_key = sigv4::uri_encode(key, true);
+        let path = if self.path_style {
+            format!("/{}/{encoded_key}", sigv4::uri_encode(&self.bucket, false))
+        } else {
+            let host = format!("{}.{}", self.bucket, self.endpoint.host_str().unwrap_or_default());
+            url.set_host(Some(&host))
+                .with_context(|| format!("invalid bucket name {:?}", self.bucket))?;
+            format!("/{encoded_key}")
+        };
+        url.set_path(&path);
+        let query_string = sigv4::canonical_query(query);
+        url.set_query((!query_string.is_empty()).then_some(query_string.as_str()));
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+        let payload_sha256 = if body.is_empty() {
+            EMPTY_SHA256.to_owned()
+        } else {
+            sigv4::sha256_hex(&body)
+        };
+        let signed = sigv4::Request {
+            method: method.as_str(),
+            host: &host,
+            path: &path,
+            query,
+            headers: &[("x-amz-content-sha256", &payload_sha256)],
+            payload_sha256: &payload_sha256,
+        };
+        let headers = sigv4::sign(
+            &signed,
+            &self.credentials,
+            &self.region,
+            "s3",
+            OffsetDateTime::now_utc(),
+        );
+
+        let mut request = self.http.request(method.clone(), url).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let res = request
+            .send()
+            .with_context(|| format!("failed to send the request {method} {key}"))?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().unwrap_or_default();
+            return Err(anyhow!("request {method} {key} failed with status {status}: {body}"));
+        }
+        Ok(res)
+    }
+}
+
+/// Returns the text of the first element `<name>` of an XML document.
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = xml[start..].find(&format!("</{name}>"))? + start;
+    Some(&xml[start..end])
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::xml_element;
+
+    #[test]
+    fn parse_upload_id() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<InitiateMultipartUploadResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Bucket>bucket</Bucket><Key>a/b.csv</Key><UploadId>VXBsb2FkIElE</UploadId>
+</InitiateMultipartUploadResult>"#;
+        assert_eq!(xml_element(xml, "UploadId"), Some("VXBsb2FkIElE"));
+        assert_eq!(xml_element(xml, "Missing"), None);
+    }
+}
@@ -0,0 +1,121 @@
+//! Templates of the object keys, such as `alumet/{hostname}/{path}`.
+
+use std::time::SystemTime;
+
+use alumet::template::{Part, Template};
+use anyhow::{Context, anyhow};
+use time::OffsetDateTime;
+
+/// A parsed key template.
+#[derive(Debug, PartialEq)]
+pub struct KeyTemplate(Template<Placeholder>);
+
+#[derive(Debug, PartialEq)]
+enum Placeholder {
+    /// Path of the file, relative to the watched directory.
+    Path,
+    /// Name of the file.
+    Filename,
+    Year,
+    Month,
+    Day,
+    /// `YYYY-MM-DD`
+    Date,
+}
+
+/// A file to upload.
+pub struct FileInfo<'a> {
+    /// Path relative to the watched directory, with `/` as separator.
+    pub relative_path: &'a str,
+    /// Last modification of the file, which gives the date in the key.
+    pub modified: SystemTime,
+}
+
+impl KeyTemplate {
+    /// Parses a template.
+    ///
+    /// The placeholders are replaced by the corresponding values for each file.
+    /// The hostname is resolved here, since it does not change.
+    pub fn parse(template: &str, hostname: &str) -> anyhow::Result<Self> {
+        let parsed = Template::parse(template, |name| {
+            let placeholder = match name {
+                "hostname" => return Some(Part::Text(hostname.replace('/', "_"))),
+                "path" => Placeholder::Path,
+                "filename" => Placeholder::Filename,
+                "year" => Placeholder::Year,
+                "month" => Placeholder::Month,
+                "day" => Placeholder::Day,
+                "date" => Placeholder::Date,
+                _ => return None,
+            };
+            Some(Part::Placeholder(placeholder))
+        })
+        .with_context(|| format!("invalid key template {template:?}"))?;
+
+        let has_name = parsed
+            .parts()
+            .iter()
+            .any(|p| matches!(p, Part::Placeholder(Placeholder::Path | Placeholder::Filename)));
+        if !has_name {
+            return Err(anyhow!(
+                "the key template {template:?} must contain {{path}} or {{filename}}, otherwise all the files would have the same key"
+            ));
+        }
+        if template.starts_with('/') {
+            return Err(anyhow!("the key template {template:?} must not start with '/'"));
+        }
+        Ok(Self(parsed))
+    }
+
+    /// Returns the key of a file.
+    pub fn render(&self, file: &FileInfo) -> String {
+        let date = OffsetDateTime::from(file.modified);
+        self.0.render(|placeholder, res| match placeholder {
+            Placeholder::Path => res.push_str(file.relative_path),
+            Placeholder::Filename => res.push_str(file.relative_path.rsplit('/').next().unwrap_or_default()),
+            Placeholder::Year => res.push_str(&format!("{:04}", date.year())),
+            Placeholder::Month => res.push_str(&format!("{:02}", u8::from(date.month()))),
+            Placeholder::Day => res.push_str(&format!("{:02}", date.day())),
+            Placeholder::Date => res.push_str(&format!(
+                "{:04}-{:02}-{:02}",
+                date.year(),
+                u8::from(date.month()),
+                date.day()
+            )),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use pretty_assertions::assert_eq;
+
+    use super::{FileInfo, KeyTemplate};
+
+    #[test]
+    fn render() {
+        let file = FileInfo {
+            relative_path: "date=2025-01-02/metric=energy/part-1.parquet",
+            modified: UNIX_EPOCH + Duration::from_secs(1_735_787_045), // 2025-01-02T03:04:05Z
+        };
+        let template = KeyTemplate::parse("alumet/{hostname}/{path}", "node-1").unwrap();
+        assert_eq!(
+            template.render(&file),
+            "alumet/node-1/date=2025-01-02/metric=energy/part-1.parquet"
+        );
+        let template = KeyTemplate::parse("{year}/{month}/{day}/{hostname}-{filename}", "node-1").unwrap();
+        assert_eq!(template.render(&file), "2025/01/02/node-1-part-1.parquet");
+        let template = KeyTemplate::parse("{date}/{filename}", "node-1").unwrap();
+        assert_eq!(template.render(&file), "2025-01-02/part-1.parquet");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(KeyTemplate::parse("alumet/{host}/{path}", "node").is_err());
+        assert!(KeyTemplate::parse("alumet/{path", "node").is_err());
+        assert!(KeyTemplate::parse("alumet/{hostname}", "node").is_err());
+        assert!(KeyTemplate::parse("/alumet/{path}", "node").is_err());
+    }
+}
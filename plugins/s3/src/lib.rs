@@ -0,0 +1,226 @@
+mod client;
+mod key;
+mod uploader;
+
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{config_or_env, deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::client::{MIN_PART_SIZE, S3Client};
+use crate::key::KeyTemplate;
+use crate::uploader::Uploader;
+use alumet_sigv4::Credentials;
+
+pub struct S3Plugin {
+    config: Config,
+    uploader: Option<UploaderThread>,
+}
+
+struct UploaderThread {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl AlumetPlugin for S3Plugin {
+    fn name() -> &'static str {
+        "s3"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.part_size < MIN_PART_SIZE {
+            return Err(anyhow!(
+                "part_size must be at least {MIN_PART_SIZE} bytes (5 MiB), got {}",
+                config.part_size
+            ));
+        }
+        if config.watch.is_empty() {
+            log::warn!("No directory to watch, the s3 plugin will not upload anything.");
+        }
+        Ok(Box::new(S3Plugin { config, uploader: None }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let key = KeyTemplate::parse(&config.key, &hostname)?;
+
+        let credentials = Credentials {
+            access_key_id: config_or_env(&config.access_key_id, "AWS_ACCESS_KEY_ID")
+                .context("missing access_key_id")?,
+            secret_access_key: config_or_env(&config.secret_access_key, "AWS_SECRET_ACCESS_KEY")
+                .context("missing secret_access_key")?,
+            session_token: config_or_env(&config.session_token, "AWS_SESSION_TOKEN"),
+        };
+        let client = S3Client::new(
+            &config.endpoint,
+            config.bucket.clone(),
+            config.region.clone(),
+            config.path_style,
+            credentials,
+            config.timeout,
+        )?;
+        let state = if config.delete_after_upload {
+            None
+        } else {
+            Some(alumet.state_dir()?)
+        };
+
+        let uploader = Uploader {
+            client,
+            key,
+            watch: config.watch.clone(),
+            min_age: config.min_age,
+            delete_after_upload: config.delete_after_upload,
+            multipart_threshold: config.multipart_threshold,
+            part_size: config.part_size,
+            state,
+        };
+        let interval = config.upload_interval;
+        let (stop, stop_rx) = mpsc::channel();
+        let handle = std::thread::Builder::new()
+            .name(String::from("s3-uploader"))
+            .spawn(move || {
+                loop {
+                    match stop_rx.recv_timeout(interval) {
+                        Err(RecvTimeoutError::Timeout) => {
+                            uploader.sweep(false);
+                        }
+                        Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                            // The outputs have been stopped: upload what remains.
+                            let n = uploader.sweep(true);
+                            log::debug!("Final upload to S3: {n} file(s).");
+                            break;
+                        }
+                    }
+                }
+            })
+            .context("failed to spawn the uploader thread")?;
+        self.uploader = Some(UploaderThread { stop, handle });
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        if let Some(uploader) = self.uploader.take() {
+            let _ = uploader.stop.send(());
+            uploader
+                .handle
+                .join()
+                .map_err(|_| anyhow!("the uploader thread panicked"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// URL of the S3 API, for instance `https://s3.eu-west-3.amazonaws.com` or `http://minio:9000`.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Put the bucket in the path of the URL (`endpoint/bucket/key`) instead of the host name
+    /// (`bucket.endpoint/key`). Most S3-compatible servers require it.
+    pub path_style: bool,
+    /// Credentials. Default to the environment variables `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`.
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub session_token: Option<String>,
+    /// Name of the machine in the keys. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Template of the key of each object.
+    ///
+    /// Available placeholders: `{hostname}`, `{path}` (relative to the watched directory), `{filename}`,
+    /// `{year}`, `{month}`, `{day}` and `{date}` (from the modification time of the file, in UTC).
+    pub key: String,
+    /// The directories that contain the files to upload.
+    pub watch: Vec<WatchConfig>,
+    /// Time between two scans of the watched directories.
+    #[serde(with = "humantime_serde")]
+    pub upload_interval: Duration,
+    /// A file is only uploaded once it has not been modified for this duration.
+    /// All the remaining files are uploaded when Alumet stops.
+    #[serde(with = "humantime_serde")]
+    pub min_age: Duration,
+    /// Delete the files once uploaded. Otherwise, the uploaded files are remembered in the state directory.
+    pub delete_after_upload: bool,
+    /// Files bigger than this size, in bytes, are uploaded in several parts.
+    pub multipart_threshold: u64,
+    /// Size of each part of a multipart upload, in bytes. At least 5 MiB.
+    pub part_size: u64,
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WatchConfig {
+    pub directory: PathBuf,
+    /// Pattern of the names of the files to upload, where `*` matches any sequence of characters
+    /// and `?` matches one character.
+    #[serde(default = "default_pattern")]
+    pub pattern: String,
+    /// Also upload the files of the subdirectories, for instance the partitions of the parquet plugin.
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+fn default_pattern() -> String {
+    String::from("*")
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            endpoint: String::from("http://localhost:9000"),
+            region: String::from("us-east-1"),
+            bucket: String::from("alumet"),
+            path_style: true,
+            access_key_id: None,
+            secret_access_key: None,
+            session_token: None,
+            hostname: None,
+            key: String::from("alumet/{hostname}/{path}"),
+            watch: vec![WatchConfig {
+                directory: PathBuf::from("."),
+                pattern: String::from("alumet-output-*.csv*"),
+                recursive: false,
+            }],
+            upload_interval: Duration::from_secs(60),
+            min_age: Duration::from_secs(30),
+            delete_after_upload: true,
+            multipart_threshold: 64 * 1024 * 1024,
+            part_size: 16 * 1024 * 1024,
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
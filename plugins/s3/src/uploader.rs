@@ -0,0 +1,231 @@
+//! Discovery and upload of the files to ship.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alumet::plugin::state::StateDir;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    WatchConfig,
+    client::S3Client,
+    key::{FileInfo, KeyTemplate},
+};
+
+/// Name of the state file that lists the uploaded files, when they are not deleted.
+const STATE_FILE: &str = "uploaded.toml";
+
+pub struct Uploader {
+    pub client: S3Client,
+    pub key: KeyTemplate,
+    pub watch: Vec<WatchConfig>,
+    /// Time since the last modification after which a file is considered complete.
+    pub min_age: Duration,
+    pub delete_after_upload: bool,
+    pub multipart_threshold: u64,
+    pub part_size: u64,
+    /// Where to remember the uploaded files, used when they are not deleted.
+    pub state: Option<StateDir>,
+}
+
+/// A file that has been uploaded, identified by its size and modification time
+/// so that it is uploaded again if it changes.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Uploaded {
+    size: u64,
+    modified: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct UploadedFiles {
+    files: BTreeMap<String, Uploaded>,
+}
+
+struct Candidate {
+    path: PathBuf,
+    relative_path: String,
+    size: u64,
+    modified: SystemTime,
+}
+
+impl Uploader {
+    /// Uploads the files that are ready, and returns the number of uploaded files.
+    ///
+    /// On the last sweep, `min_age` is ignored: the outputs have been stopped, so the files are complete.
+    /// Failures are logged, the file will be uploaded by a later sweep.
+    pub fn sweep(&self, last: bool) -> usize {
+        let mut uploaded = match self.load_state() {
+            Ok(uploaded) => uploaded,
+            Err(e) => {
+                log::error!("Failed to load the list of uploaded files, they may be uploaded again: {e:#}");
+                UploadedFiles::default()
+            }
+        };
+
+        let now = SystemTime::now();
+        let mut count = 0;
+        let mut seen = Vec::new();
+        for watch in &self.watch {
+            let candidates = match find_files(watch) {
+                Ok(files) => files,
+                Err(e) => {
+                    log::error!("Failed to list the files of {:?}: {e:#}", watch.directory);
+                    continue;
+                }
+            };
+            for file in candidates {
+                let id = file.path.to_string_lossy().into_owned();
+                let fingerprint = Uploaded {
+                    size: file.size,
+                    modified: file.modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+                };
+                seen.push(id.clone());
+                if uploaded.files.get(&id) == Some(&fingerprint) {
+                    continue;
+                }
+                let age = now.duration_since(file.modified).unwrap_or_default();
+                if !last && age < self.min_age {
+                    continue;
+                }
+
+                let key = self.key.render(&FileInfo {
+                    relative_path: &file.relative_path,
+                    modified: file.modified,
+                });
+                log::debug!("Uploading {:?} to {key}", file.path);
+                if let Err(e) = self
+                    .client
+                    .upload_file(&file.path, &key, self.multipart_threshold, self.part_size)
+                {
+                    log::error!("Failed to upload {:?} to {key}, will retry later: {e:#}", file.path);
+                    continue;
+                }
+                count += 1;
+                if self.delete_after_upload {
+                    if let Err(e) = fs::remove_file(&file.path) {
+                        log::error!("Failed to delete {:?} after its upload: {e}", file.path);
+                    }
+                } else {
+                    uploaded.files.insert(id, fingerprint);
+                }
+            }
+        }
+
+        if self.state.is_some() {
+            // Forget the files that no longer exist.
+            uploaded.files.retain(|id, _| seen.contains(id));
+            if let Err(e) = self.save_state(&uploaded) {
+                log::error!("Failed to save the list of uploaded files: {e:#}");
+            }
+        }
+        count
+    }
+
+    fn load_state(&self) -> anyhow::Result<UploadedFiles> {
+        match &self.state {
+            Some(state) => Ok(state.load(STATE_FILE)?.unwrap_or_default()),
+            None => Ok(UploadedFiles::default()),
+        }
+    }
+
+    fn save_state(&self, uploaded: &UploadedFiles) -> anyhow::Result<()> {
+        match &self.state {
+            Some(state) => state.save(STATE_FILE, uploaded),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Lists the files of a watched directory that match its pattern, sorted by path.
+///
+/// Hidden files and unfinished files (`.inprogress`, `.tmp`) are ignored.
+fn find_files(watch: &WatchConfig) -> anyhow::Result<Vec<Candidate>> {
+    let mut res = Vec::new();
+    let mut dirs = vec![watch.directory.clone()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if watch.recursive {
+                    dirs.push(entry.path());
+                }
+                continue;
+            }
+            if !file_type.is_file()
+                || name.ends_with(".inprogress")
+                || name.ends_with(".tmp")
+                || !matches_pattern(&watch.pattern, &name)
+            {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let path = entry.path();
+            res.push(Candidate {
+                relative_path: relative_path(&watch.directory, &path),
+                path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+    res.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(res)
+}
+
+fn relative_path(base: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    let parts: Vec<_> = relative.iter().map(|p| p.to_string_lossy()).collect();
+    parts.join("/")
+}
+
+/// Matches a file name against a glob pattern, where `*` matches any sequence of characters
+/// and `?` matches exactly one character.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and of the name when it was encountered, for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches_pattern;
+
+    #[test]
+    fn pattern() {
+        assert!(matches_pattern("*", "alumet-output.csv"));
+        assert!(matches_pattern("*.csv.gz", "alumet-output-20250102T030405.csv.gz"));
+        assert!(matches_pattern("part-?.parquet", "part-1.parquet"));
+        assert!(matches_pattern("a*b*c", "axxbyyc"));
+        assert!(!matches_pattern("*.csv.gz", "alumet-output.csv"));
+        assert!(!matches_pattern("part-?.parquet", "part-12.parquet"));
+        assert!(!matches_pattern("a*b", "ac"));
+    }
+}
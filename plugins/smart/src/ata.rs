@@ -0,0 +1,247 @@
+//! SMART attributes of the SATA drives, obtained with ATA PASS-THROUGH commands (SCSI/ATA Translation).
+
+use std::{
+    fs::{File, OpenOptions},
+    os::{
+        fd::AsRawFd,
+        raw::{c_int, c_uchar, c_uint, c_ushort, c_void},
+        unix::fs::OpenOptionsExt,
+    },
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+
+// The structure of `scsi/sg.h`, used with the SG_IO ioctl.
+
+#[repr(C)]
+struct SgIoHdr {
+    interface_id: c_int,
+    dxfer_direction: c_int,
+    cmd_len: c_uchar,
+    mx_sb_len: c_uchar,
+    iovec_count: c_ushort,
+    dxfer_len: c_uint,
+    dxferp: *mut c_void,
+    cmdp: *mut c_uchar,
+    sbp: *mut c_uchar,
+    timeout: c_uint,
+    flags: c_uint,
+    pack_id: c_int,
+    usr_ptr: *mut c_void,
+    status: c_uchar,
+    masked_status: c_uchar,
+    msg_status: c_uchar,
+    sb_len_wr: c_uchar,
+    host_status: c_ushort,
+    driver_status: c_ushort,
+    resid: c_int,
+    duration: c_uint,
+    info: c_uint,
+}
+
+const SG_IO: u32 = 0x2285;
+nix::ioctl_readwrite_bad!(sg_io, SG_IO, SgIoHdr);
+
+const SG_DXFER_NONE: c_int = -1;
+const SG_DXFER_FROM_DEV: c_int = -3;
+const TIMEOUT_MS: c_uint = 5000;
+
+const ATA_PASS_THROUGH_16: u8 = 0x85;
+const ATA_CHECK_POWER_MODE: u8 = 0xe5;
+const ATA_SMART: u8 = 0xb0;
+const SMART_READ_DATA: u8 = 0xd0;
+
+/// A SATA drive, such as `/dev/sda`.
+pub struct AtaDrive {
+    path: PathBuf,
+    file: File,
+}
+
+/// A SMART attribute.
+#[derive(Debug, PartialEq)]
+pub struct SmartAttribute {
+    pub id: u8,
+    /// Normalized value, from 1 to 253 (higher is better).
+    pub value: u8,
+    /// Raw value, whose meaning depends on the attribute and on the vendor.
+    pub raw: u64,
+}
+
+/// Identifiers of the attributes that are interpreted by the plugin.
+pub mod attribute {
+    pub const TEMPERATURE: u8 = 194;
+    pub const AIRFLOW_TEMPERATURE: u8 = 190;
+    pub const TOTAL_LBAS_WRITTEN: u8 = 241;
+    pub const TOTAL_LBAS_READ: u8 = 242;
+}
+
+impl AtaDrive {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .with_context(|| format!("failed to open {path:?}"))?;
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+        })
+    }
+
+    /// Returns `true` if the drive is in standby mode, in which case reading its attributes would spin it up.
+    pub fn is_standby(&self) -> anyhow::Result<bool> {
+        let mut cdb = [0u8; 16];
+        cdb[0] = ATA_PASS_THROUGH_16;
+        // protocol: non-data
+        cdb[1] = 3 << 1;
+        // CK_COND: return the registers of the device in the sense data
+        cdb[2] = 0x20;
+        cdb[14] = ATA_CHECK_POWER_MODE;
+        let sense = self.pass_through(&cdb, &mut [])?;
+        // the power mode is returned in the count register, 0x00 means standby
+        Ok(sense_count_register(&sense) == Some(0x00))
+    }
+
+    /// Reads the SMART attributes of the drive.
+    pub fn smart_attributes(&self) -> anyhow::Result<Vec<SmartAttribute>> {
+        let mut cdb = [0u8; 16];
+        cdb[0] = ATA_PASS_THROUGH_16;
+        // protocol: PIO data-in
+        cdb[1] = 4 << 1;
+        // T_DIR = from device, BYT_BLOK = blocks, T_LENGTH = in the count register
+        cdb[2] = 0x0e;
+        cdb[4] = SMART_READ_DATA;
+        cdb[6] = 1;
+        // SMART signature in the LBA mid and high registers
+        cdb[10] = 0x4f;
+        cdb[12] = 0xc2;
+        cdb[14] = ATA_SMART;
+        let mut data = [0u8; 512];
+        self.pass_through(&cdb, &mut data)?;
+        Ok(parse_smart_data(&data))
+    }
+
+    /// Sends an ATA PASS-THROUGH command, and returns the sense data.
+    fn pass_through(&self, cdb: &[u8; 16], data: &mut [u8]) -> anyhow::Result<Vec<u8>> {
+        let mut cdb = *cdb;
+        let mut sense = vec![0u8; 32];
+        let mut hdr = SgIoHdr {
+            interface_id: b'S' as c_int,
+            dxfer_direction: if data.is_empty() {
+                SG_DXFER_NONE
+            } else {
+                SG_DXFER_FROM_DEV
+            },
+            cmd_len: cdb.len() as c_uchar,
+            mx_sb_len: sense.len() as c_uchar,
+            iovec_count: 0,
+            dxfer_len: data.len() as c_uint,
+            dxferp: data.as_mut_ptr().cast(),
+            cmdp: cdb.as_mut_ptr(),
+            sbp: sense.as_mut_ptr(),
+            timeout: TIMEOUT_MS,
+            flags: 0,
+            pack_id: 0,
+            usr_ptr: std::ptr::null_mut(),
+            status: 0,
+            masked_status: 0,
+            msg_status: 0,
+            sb_len_wr: 0,
+            host_status: 0,
+            driver_status: 0,
+            resid: 0,
+            duration: 0,
+            info: 0,
+        };
+        // SAFETY: the pointers of `hdr` are valid until the end of the call, and the buffers have the announced lengths
+        unsafe { sg_io(self.file.as_raw_fd(), &mut hdr) }
+            .with_context(|| format!("SG_IO failed on {:?}", self.path))?;
+        if hdr.host_status != 0 {
+            return Err(anyhow!(
+                "ATA command {:#x} failed on {:?}: host status {:#x}",
+                cdb[14],
+                self.path,
+                hdr.host_status
+            ));
+        }
+        sense.truncate(hdr.sb_len_wr as usize);
+        Ok(sense)
+    }
+}
+
+/// Finds the count register of the device in the sense data of an ATA PASS-THROUGH command.
+fn sense_count_register(sense: &[u8]) -> Option<u8> {
+    match sense.first()? & 0x7f {
+        // descriptor format, with an ATA Status Return descriptor
+        0x72 => {
+            let descriptor = sense.get(8..)?;
+            (descriptor.first() == Some(&0x09)).then(|| descriptor.get(5).copied())?
+        }
+        // fixed format
+        0x70 => sense.get(6).copied(),
+        _ => None,
+    }
+}
+
+/// Parses the SMART data structure returned by SMART READ DATA.
+fn parse_smart_data(data: &[u8; 512]) -> Vec<SmartAttribute> {
+    // 30 attributes of 12 bytes, after the revision number
+    data[2..2 + 30 * 12]
+        .chunks_exact(12)
+        .filter(|entry| entry[0] != 0)
+        .map(|entry| {
+            let mut raw = [0u8; 8];
+            raw[..6].copy_from_slice(&entry[5..11]);
+            SmartAttribute {
+                id: entry[0],
+                value: entry[3],
+                raw: u64::from_le_bytes(raw),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn smart_data() {
+        let mut data = [0u8; 512];
+        data[0] = 0x10;
+        // Power_On_Hours
+        data[2..14].copy_from_slice(&[9, 0x32, 0, 98, 98, 0x10, 0x27, 0, 0, 0, 0, 0]);
+        // Temperature_Celsius: 35°C, min 20, max 45
+        data[14..26].copy_from_slice(&[194, 0x22, 0, 65, 55, 35, 0, 20, 0, 45, 0, 0]);
+        let attributes = parse_smart_data(&data);
+        assert_eq!(
+            attributes,
+            vec![
+                SmartAttribute {
+                    id: 9,
+                    value: 98,
+                    raw: 10000
+                },
+                SmartAttribute {
+                    id: 194,
+                    value: 65,
+                    raw: 0x2d_0014_0023
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn count_register() {
+        let descriptor = [
+            0x72, 0x01, 0x00, 0x1d, 0, 0, 0, 0x0e, 0x09, 0x0c, 0, 0, 0, 0xff, 0, 0, 0, 0, 0, 0, 0, 0x50,
+        ];
+        assert_eq!(sense_count_register(&descriptor), Some(0xff));
+        let fixed = [0x70, 0, 0x01, 0, 0x50, 0x40, 0x00, 0, 0, 0, 0, 0, 0, 0x1d];
+        assert_eq!(sense_count_register(&fixed), Some(0x00));
+        assert_eq!(sense_count_register(&[]), None);
+    }
+}
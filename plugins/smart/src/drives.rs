@@ -0,0 +1,155 @@
+//! Discovery of the NVMe and SATA drives.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriveKind {
+    Nvme,
+    Ata,
+}
+
+/// A drive to measure.
+#[derive(Debug, PartialEq)]
+pub struct Drive {
+    /// Name of the drive in the kernel, such as `nvme0` or `sda`.
+    pub name: String,
+    /// Path to the device file.
+    pub path: PathBuf,
+    pub kind: DriveKind,
+    /// Model of the drive, if reported by sysfs.
+    pub model: Option<String>,
+}
+
+impl Drive {
+    /// Describes the device at the given path, which has been chosen by the user.
+    pub fn from_path(sysfs: &Path, path: PathBuf) -> anyhow::Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .with_context(|| format!("invalid device path {path:?}"))?
+            .to_owned();
+        let (kind, sysfs_dir) = if name.starts_with("nvme") {
+            (DriveKind::Nvme, sysfs.join("class/nvme").join(&name))
+        } else {
+            (DriveKind::Ata, sysfs.join("block").join(&name).join("device"))
+        };
+        Ok(Self {
+            model: read_trimmed(&sysfs_dir.join("model")),
+            name,
+            path,
+            kind,
+        })
+    }
+}
+
+/// Finds the NVMe controllers and the SATA drives of the machine.
+///
+/// `sysfs` is the mount point of sysfs, usually `/sys`.
+pub fn discover(sysfs: &Path) -> anyhow::Result<Vec<Drive>> {
+    let mut drives = Vec::new();
+
+    // NVMe controllers: /sys/class/nvme/nvmeN
+    let nvme_dir = sysfs.join("class/nvme");
+    if nvme_dir.exists() {
+        for name in sorted_entries(&nvme_dir)? {
+            drives.push(Drive {
+                path: Path::new("/dev").join(&name),
+                model: read_trimmed(&nvme_dir.join(&name).join("model")),
+                name,
+                kind: DriveKind::Nvme,
+            });
+        }
+    }
+
+    // SATA drives: /sys/block/sdX, whose vendor is "ATA" (SCSI/ATA Translation)
+    let block_dir = sysfs.join("block");
+    for name in sorted_entries(&block_dir)? {
+        if !name.starts_with("sd") {
+            continue;
+        }
+        let device = block_dir.join(&name).join("device");
+        if read_trimmed(&device.join("vendor")).as_deref() == Some("ATA") {
+            drives.push(Drive {
+                path: Path::new("/dev").join(&name),
+                model: read_trimmed(&device.join("model")),
+                name,
+                kind: DriveKind::Ata,
+            });
+        }
+    }
+    Ok(drives)
+}
+
+fn sorted_entries(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to list {dir:?}"))? {
+        let entry = entry?;
+        names.extend(entry.file_name().to_str().map(str::to_owned));
+    }
+    names.sort();
+    Ok(names)
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    Some(content.trim().to_owned()).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn write(path: PathBuf, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn discovery() {
+        let tmp = tempdir().unwrap();
+        let sysfs = tmp.path();
+        write(
+            sysfs.join("class/nvme/nvme0/model"),
+            "Samsung SSD 980 PRO 1TB                 \n",
+        );
+        write(sysfs.join("block/sda/device/vendor"), "ATA     \n");
+        write(sysfs.join("block/sda/device/model"), "WDC WD40EFRX-68N\n");
+        write(sysfs.join("block/sdb/device/vendor"), "Generic \n");
+        write(sysfs.join("block/loop0/size"), "0\n");
+        write(sysfs.join("block/nvme0n1/size"), "0\n");
+
+        let drives = discover(sysfs).unwrap();
+        assert_eq!(
+            drives,
+            vec![
+                Drive {
+                    name: String::from("nvme0"),
+                    path: PathBuf::from("/dev/nvme0"),
+                    kind: DriveKind::Nvme,
+                    model: Some(String::from("Samsung SSD 980 PRO 1TB")),
+                },
+                Drive {
+                    name: String::from("sda"),
+                    path: PathBuf::from("/dev/sda"),
+                    kind: DriveKind::Ata,
+                    model: Some(String::from("WDC WD40EFRX-68N")),
+                },
+            ]
+        );
+
+        let drive = Drive::from_path(sysfs, PathBuf::from("/dev/sdc")).unwrap();
+        assert_eq!(drive.kind, DriveKind::Ata);
+        assert_eq!(drive.model, None);
+        let drive = Drive::from_path(sysfs, PathBuf::from("/dev/nvme0")).unwrap();
+        assert_eq!(drive.kind, DriveKind::Nvme);
+        assert_eq!(drive.model.as_deref(), Some("Samsung SSD 980 PRO 1TB"));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use drives::Drive;
+use source::MeasuredDrive;
+
+mod ata;
+mod drives;
+mod metrics;
+mod nvme;
+mod source;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+const SYSFS: &str = "/sys";
+
+pub struct SmartPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for SmartPlugin {
+    fn name() -> &'static str {
+        "smart"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Filesystem(PathBuf::from("/dev"))])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(SmartPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let sysfs = Path::new(SYSFS);
+        let drives = if self.config.devices.is_empty() {
+            drives::discover(sysfs).context("failed to discover the drives")?
+        } else {
+            self.config
+                .devices
+                .iter()
+                .map(|path| Drive::from_path(sysfs, path.clone()))
+                .collect::<anyhow::Result<_>>()?
+        };
+
+        let mut measured = Vec::with_capacity(drives.len());
+        for drive in drives {
+            let path = drive.path.clone();
+            match MeasuredDrive::open(drive) {
+                Ok(drive) => measured.push(drive),
+                Err(e) => log::warn!("Skipping {}: {e:#}", path.display()),
+            }
+        }
+        if measured.is_empty() {
+            return Err(anyhow!("no NVMe or SATA drive can be measured"));
+        }
+        log::info!("Measuring the SMART data of {} drives.", measured.len());
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::SmartSource::new(measured, metrics);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("drives", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// Drives to measure, such as `/dev/nvme0` or `/dev/sda`.
+    /// If empty, all the NVMe controllers and SATA drives are measured.
+    devices: Vec<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+            flush_interval: Duration::from_secs(60),
+            devices: Vec::new(),
+        }
+    }
+}
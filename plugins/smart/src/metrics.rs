@@ -0,0 +1,49 @@
+use alumet::{metrics::TypedMetricId, plugin::AlumetPluginStart, units::Unit};
+
+pub struct Metrics {
+    /// Temperature of the drive (composite temperature for NVMe).
+    pub temperature: TypedMetricId<f64>,
+    /// Bytes read or written since the previous measurement.
+    pub bytes_delta: TypedMetricId<u64>,
+    /// Estimated percentage of the life of an NVMe drive that has been used.
+    pub nvme_percentage_used: TypedMetricId<u64>,
+    /// Current power state of an NVMe controller.
+    pub nvme_power_state: TypedMetricId<u64>,
+    /// Maximum power of the current power state of an NVMe controller.
+    pub nvme_power_state_max_power: TypedMetricId<f64>,
+    /// Raw value of a SMART attribute of a SATA drive.
+    pub sata_smart_raw_value: TypedMetricId<u64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            temperature: alumet.create_metric("drive_temperature", Unit::DegreeCelsius, "Temperature of the drive")?,
+            bytes_delta: alumet.create_metric(
+                "drive_bytes_delta",
+                Unit::Byte,
+                "Bytes read or written by the drive since the previous measurement",
+            )?,
+            nvme_percentage_used: alumet.create_metric(
+                "nvme_percentage_used",
+                Unit::Percent,
+                "Estimated percentage of the life of the NVMe drive that has been used (can exceed 100)",
+            )?,
+            nvme_power_state: alumet.create_metric(
+                "nvme_power_state",
+                Unit::Unity,
+                "Current power state of the NVMe controller (0 is the most powerful)",
+            )?,
+            nvme_power_state_max_power: alumet.create_metric(
+                "nvme_power_state_max_power",
+                Unit::Watt,
+                "Maximum power that the NVMe controller can draw in its current power state",
+            )?,
+            sata_smart_raw_value: alumet.create_metric(
+                "sata_smart_raw_value",
+                Unit::Unity,
+                "Raw value of a SMART attribute of the SATA drive",
+            )?,
+        })
+    }
+}
@@ -0,0 +1,197 @@
+//! Health information and power states of the NVMe controllers, obtained with admin commands.
+
+use std::{
+    fs::File,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+
+// The structure of `linux/nvme_ioctl.h`, used with NVME_IOCTL_ADMIN_CMD.
+
+#[repr(C)]
+#[derive(Default)]
+struct NvmeAdminCmd {
+    opcode: u8,
+    flags: u8,
+    rsvd1: u16,
+    nsid: u32,
+    cdw2: u32,
+    cdw3: u32,
+    metadata: u64,
+    addr: u64,
+    metadata_len: u32,
+    data_len: u32,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+    timeout_ms: u32,
+    result: u32,
+}
+
+nix::ioctl_readwrite!(nvme_admin_cmd, b'N', 0x41, NvmeAdminCmd);
+
+const OPCODE_GET_LOG_PAGE: u8 = 0x02;
+const OPCODE_IDENTIFY: u8 = 0x06;
+const OPCODE_GET_FEATURES: u8 = 0x0a;
+
+const LOG_SMART_HEALTH: u32 = 0x02;
+const IDENTIFY_CONTROLLER: u32 = 0x01;
+const FEATURE_POWER_MANAGEMENT: u32 = 0x02;
+
+/// All the namespaces.
+const NSID_ALL: u32 = 0xffff_ffff;
+const TIMEOUT_MS: u32 = 5000;
+
+/// An NVMe controller, such as `/dev/nvme0`.
+pub struct NvmeController {
+    path: PathBuf,
+    file: File,
+    /// Maximum power of each power state, in Watts.
+    power_states: Vec<f64>,
+}
+
+/// Content of the SMART / Health Information log page.
+#[derive(Debug, PartialEq)]
+pub struct NvmeHealth {
+    /// Composite temperature, in degrees Celsius.
+    pub temperature: f64,
+    /// Estimate of the life of the drive that has been used, can exceed 100.
+    pub percentage_used: u8,
+    /// Bytes read since the manufacture of the drive.
+    pub bytes_read: u128,
+    /// Bytes written since the manufacture of the drive.
+    pub bytes_written: u128,
+}
+
+impl NvmeController {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("failed to open {path:?}"))?;
+        let mut controller = Self {
+            path: path.to_owned(),
+            file,
+            power_states: Vec::new(),
+        };
+        let mut identify = vec![0u8; 4096];
+        controller
+            .admin(OPCODE_IDENTIFY, 0, IDENTIFY_CONTROLLER, &mut identify)
+            .context("identify controller failed")?;
+        controller.power_states = parse_power_states(&identify);
+        Ok(controller)
+    }
+
+    /// Reads the SMART / Health Information log page.
+    pub fn health(&self) -> anyhow::Result<NvmeHealth> {
+        let mut log = [0u8; 512];
+        // number of dwords to read (0-based) and log identifier
+        let cdw10 = ((log.len() as u32 / 4 - 1) << 16) | LOG_SMART_HEALTH;
+        self.admin(OPCODE_GET_LOG_PAGE, NSID_ALL, cdw10, &mut log)
+            .context("get log page failed")?;
+        Ok(parse_health(&log))
+    }
+
+    /// Returns the current power state and its maximum power, in Watts.
+    pub fn power_state(&self) -> anyhow::Result<(u8, Option<f64>)> {
+        let result = self
+            .admin(OPCODE_GET_FEATURES, 0, FEATURE_POWER_MANAGEMENT, &mut [])
+            .context("get features failed")?;
+        let state = (result & 0x1f) as u8;
+        Ok((state, self.power_states.get(state as usize).copied()))
+    }
+
+    /// Sends an admin command and returns the result dword.
+    fn admin(&self, opcode: u8, nsid: u32, cdw10: u32, data: &mut [u8]) -> anyhow::Result<u32> {
+        let mut cmd = NvmeAdminCmd {
+            opcode,
+            nsid,
+            addr: data.as_mut_ptr() as u64,
+            data_len: data.len() as u32,
+            cdw10,
+            timeout_ms: TIMEOUT_MS,
+            ..Default::default()
+        };
+        // SAFETY: the buffer is valid and large enough for the data of the command
+        let status = unsafe { nvme_admin_cmd(self.file.as_raw_fd(), &mut cmd) }
+            .with_context(|| format!("ioctl failed on {:?}", self.path))?;
+        if status != 0 {
+            return Err(anyhow!(
+                "the controller {:?} returned the status {status:#x}",
+                self.path
+            ));
+        }
+        Ok(cmd.result)
+    }
+}
+
+fn parse_health(log: &[u8; 512]) -> NvmeHealth {
+    // a data unit is 1000 blocks of 512 bytes
+    const DATA_UNIT: u128 = 512_000;
+    let u128_at = |offset: usize| u128::from_le_bytes(log[offset..offset + 16].try_into().unwrap());
+    let kelvin = u16::from_le_bytes([log[1], log[2]]);
+    NvmeHealth {
+        temperature: kelvin as f64 - 273.15,
+        percentage_used: log[5],
+        bytes_read: u128_at(32).saturating_mul(DATA_UNIT),
+        bytes_written: u128_at(48).saturating_mul(DATA_UNIT),
+    }
+}
+
+/// Parses the power state descriptors of the identify controller data structure.
+fn parse_power_states(identify: &[u8]) -> Vec<f64> {
+    // number of power states, 0-based
+    let npss = identify[263] as usize;
+    (0..=npss)
+        .map(|i| {
+            let psd = &identify[2048 + 32 * i..2048 + 32 * (i + 1)];
+            let max_power = u16::from_le_bytes([psd[0], psd[1]]) as f64;
+            // the scale depends on the MXPS bit
+            if psd[3] & 1 == 0 {
+                max_power / 100.0
+            } else {
+                max_power / 10_000.0
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health() {
+        let mut log = [0u8; 512];
+        log[1..3].copy_from_slice(&310u16.to_le_bytes());
+        log[5] = 3;
+        log[32] = 10;
+        log[48..50].copy_from_slice(&1000u16.to_le_bytes());
+        let health = parse_health(&log);
+        assert_eq!(
+            health,
+            NvmeHealth {
+                temperature: 310.0 - 273.15,
+                percentage_used: 3,
+                bytes_read: 5_120_000,
+                bytes_written: 512_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn power_states() {
+        let mut identify = vec![0u8; 4096];
+        identify[263] = 2;
+        // 8.25 W
+        identify[2048..2050].copy_from_slice(&825u16.to_le_bytes());
+        // 3.5 W, in units of 0.0001 W
+        identify[2080..2082].copy_from_slice(&35000u16.to_le_bytes());
+        identify[2083] = 1;
+        // 0.05 W
+        identify[2112] = 5;
+        assert_eq!(parse_power_states(&identify), vec![8.25, 3.5, 0.05]);
+    }
+}
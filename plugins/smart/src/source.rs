@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, MeasurementType, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+
+use crate::{
+    ata::{AtaDrive, SmartAttribute, attribute},
+    drives::{Drive, DriveKind},
+    metrics::Metrics,
+    nvme::NvmeController,
+};
+
+/// Size of a logical block, in the SMART attributes of the SATA drives.
+const ATA_LBA_SIZE: u128 = 512;
+
+/// Measurement source that reads the SMART data of NVMe and SATA drives.
+pub struct SmartSource {
+    drives: Vec<MeasuredDrive>,
+    metrics: Metrics,
+}
+
+pub struct MeasuredDrive {
+    device: Device,
+    resource: Resource,
+    model: Option<String>,
+    /// Bytes read and written at the previous measurement.
+    previous_io: Option<(u128, u128)>,
+}
+
+enum Device {
+    Nvme(NvmeController),
+    Ata(AtaDrive),
+}
+
+impl MeasuredDrive {
+    pub fn open(drive: Drive) -> anyhow::Result<Self> {
+        let device = match drive.kind {
+            DriveKind::Nvme => Device::Nvme(NvmeController::open(&drive.path)?),
+            DriveKind::Ata => Device::Ata(AtaDrive::open(&drive.path)?),
+        };
+        Ok(Self {
+            device,
+            resource: Resource::Custom {
+                kind: Cow::Borrowed("drive"),
+                id: Cow::Owned(drive.name),
+            },
+            model: drive.model,
+            previous_io: None,
+        })
+    }
+
+    fn point<T: MeasurementType>(&self, t: Timestamp, metric: TypedMetricId<T>, value: T::T) -> MeasurementPoint {
+        let point = MeasurementPoint::new(t, metric, self.resource.clone(), ResourceConsumer::LocalMachine, value);
+        match &self.model {
+            Some(model) => point.with_attr("model", model.clone()),
+            None => point,
+        }
+    }
+
+    fn poll(
+        &mut self,
+        metrics: &Metrics,
+        measurements: &mut MeasurementAccumulator,
+        t: Timestamp,
+    ) -> anyhow::Result<()> {
+        let io = match &self.device {
+            Device::Nvme(controller) => {
+                let health = controller.health()?;
+                measurements.push(self.point(t, metrics.temperature, health.temperature));
+                measurements.push(self.point(t, metrics.nvme_percentage_used, health.percentage_used as u64));
+
+                let (state, max_power) = controller.power_state()?;
+                measurements.push(self.point(t, metrics.nvme_power_state, state as u64));
+                if let Some(max_power) = max_power {
+                    measurements.push(self.point(t, metrics.nvme_power_state_max_power, max_power));
+                }
+                Some((health.bytes_read, health.bytes_written))
+            }
+            Device::Ata(drive) => {
+                // Reading the attributes of a drive in standby would spin it up, and waste energy.
+                if drive.is_standby()? {
+                    log::debug!("Skipping {:?}, which is in standby.", self.resource);
+                    return Ok(());
+                }
+                let attributes = drive.smart_attributes()?;
+                for attr in &attributes {
+                    measurements.push(
+                        self.point(t, metrics.sata_smart_raw_value, attr.raw)
+                            .with_attr("attribute", attr.id as u64),
+                    );
+                }
+                if let Some(temperature) = ata_temperature(&attributes) {
+                    measurements.push(self.point(t, metrics.temperature, temperature));
+                }
+                ata_io(&attributes)
+            }
+        };
+
+        if let Some((read, written)) = io {
+            if let Some((prev_read, prev_written)) = self.previous_io {
+                for (kind, value, prev) in [("read", read, prev_read), ("write", written, prev_written)] {
+                    if let Some(delta) = value.checked_sub(prev) {
+                        let delta = u64::try_from(delta).unwrap_or(u64::MAX);
+                        measurements.push(self.point(t, metrics.bytes_delta, delta).with_attr("kind", kind));
+                    }
+                }
+            }
+            self.previous_io = Some((read, written));
+        }
+        Ok(())
+    }
+}
+
+impl SmartSource {
+    pub fn new(drives: Vec<MeasuredDrive>, metrics: Metrics) -> Self {
+        Self { drives, metrics }
+    }
+}
+
+impl Source for SmartSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        for drive in &mut self.drives {
+            // One failing drive must not prevent the others from being measured.
+            if let Err(e) = drive.poll(&self.metrics, measurements, t) {
+                log::warn!("Failed to read the SMART data of {:?}: {e:#}", drive.resource);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the temperature of a SATA drive, in degrees Celsius.
+fn ata_temperature(attributes: &[SmartAttribute]) -> Option<f64> {
+    [attribute::TEMPERATURE, attribute::AIRFLOW_TEMPERATURE]
+        .iter()
+        .find_map(|id| attributes.iter().find(|a| a.id == *id))
+        // the other bytes can contain the min and max temperatures
+        .map(|a| (a.raw & 0xff) as f64)
+}
+
+/// Returns the bytes read and written by a SATA drive, if it reports them.
+fn ata_io(attributes: &[SmartAttribute]) -> Option<(u128, u128)> {
+    let lbas = |id| attributes.iter().find(|a| a.id == id).map(|a| a.raw);
+    let read = lbas(attribute::TOTAL_LBAS_READ)?;
+    let written = lbas(attribute::TOTAL_LBAS_WRITTEN)?;
+    Some((read as u128 * ATA_LBA_SIZE, written as u128 * ATA_LBA_SIZE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(id: u8, raw: u64) -> SmartAttribute {
+        SmartAttribute { id, value: 100, raw }
+    }
+
+    #[test]
+    fn ata_interpretation() {
+        let attributes = vec![
+            attr(9, 10000),
+            attr(190, 40),
+            attr(194, 0x2d_0014_0023),
+            attr(241, 2),
+            attr(242, 8),
+        ];
+        assert_eq!(ata_temperature(&attributes), Some(35.0));
+        assert_eq!(ata_temperature(&attributes[..2]), Some(40.0));
+        assert_eq!(ata_temperature(&attributes[..1]), None);
+        assert_eq!(ata_io(&attributes), Some((4096, 1024)));
+        assert_eq!(ata_io(&attributes[..4]), None);
+    }
+}
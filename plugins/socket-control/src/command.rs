@@ -4,8 +4,8 @@ use std::str::FromStr;
 use std::time::Duration;
 
 use alumet::pipeline::control::AnonymousControlHandle;
-use alumet::pipeline::control::handle::DispatchError;
-use alumet::pipeline::control::request::{self, any::AnyAnonymousControlRequest};
+use alumet::pipeline::control::handle::SendWaitError;
+use alumet::pipeline::control::request::{self, ElementListFilter, any::AnyAnonymousControlRequest};
 use alumet::pipeline::elements::source::trigger::TriggerSpec;
 use alumet::pipeline::matching::{
     ElementNamePattern, OutputNamePattern, SourceNamePattern, StringPattern, TransformNamePattern,
@@ -21,21 +21,36 @@ const COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
 #[derive(Debug)]
 pub enum Command {
     Control(Vec<AnyAnonymousControlRequest>),
+    List(ElementNamePattern),
     Shutdown,
 }
 
 impl Command {
-    pub async fn run(self, handle: &AnonymousControlHandle) -> Result<(), DispatchError> {
+    /// Runs the command and returns the lines of its output (which can be empty).
+    pub async fn run(self, handle: &AnonymousControlHandle) -> Result<Vec<String>, SendWaitError> {
         match self {
             Command::Control(messages) => {
                 for msg in messages {
-                    handle.dispatch(msg, COMMAND_TIMEOUT).await?;
+                    handle.send_wait(msg, COMMAND_TIMEOUT).await?;
                 }
-                Ok(())
+                Ok(Vec::new())
+            }
+            Command::List(pattern) => {
+                let filter = match pattern.kind {
+                    Some(kind) => ElementListFilter::kind(kind),
+                    None => ElementListFilter::kind_any(),
+                };
+                let filter = filter.plugin_pat(pattern.plugin).name_pat(pattern.element);
+                let elements = handle
+                    .send_wait(request::list_elements(filter), COMMAND_TIMEOUT)
+                    .await?;
+                let mut lines: Vec<String> = elements.into_iter().map(|e| e.to_string()).collect();
+                lines.sort();
+                Ok(lines)
             }
             Command::Shutdown => {
                 handle.shutdown();
-                Ok(())
+                Ok(Vec::new())
             }
         }
     }
@@ -47,6 +62,7 @@ impl Command {
 ///
 /// - `shutdown` or `stop`: shutdowns the measurement pipeline
 /// - `control <PATTERN> [ARGS...]`: reconfigures a part of the pipeline (see below)
+/// - `list [PATTERN]`: lists the elements of the pipeline that match the pattern (all elements by default)
 ///
 /// ### Control arguments
 ///
@@ -167,8 +183,16 @@ pub fn parse(command: &str) -> anyhow::Result<Command> {
     }
 
     let parts: Vec<&str> = command.split_ascii_whitespace().collect();
-    match parts[0] {
+    match parts.first().copied().unwrap_or_default() {
         "shutdown" | "stop" => Ok(Command::Shutdown),
+        "list" => {
+            let pattern = match parts[1..] {
+                [] => parse_pattern("*")?,
+                [pat] => parse_pattern(pat)?,
+                _ => return Err(anyhow!("invalid command '{command}': too many arguments")),
+            };
+            Ok(Command::List(pattern))
+        }
         "control" => {
             let pat = parts
                 .get(1)
@@ -179,7 +203,7 @@ pub fn parse(command: &str) -> anyhow::Result<Command> {
             Ok(Command::Control(messages))
         }
         _ => Err(anyhow!(
-            "unknown command '{command}'; available commands are 'shutdown' or 'control'"
+            "unknown command '{command}'; available commands are 'shutdown', 'control' or 'list'"
         )),
     }
 }
@@ -373,6 +397,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn list() {
+        let all = |cmd| match parse(cmd).unwrap() {
+            Command::List(pat) => pat,
+            cmd => panic!("wrong command {cmd:?}, expected List"),
+        };
+        assert_eq!(all("list"), parse_pattern("*").unwrap());
+        assert_eq!(all("list sources"), parse_pattern("sources").unwrap());
+        assert_eq!(all("list out/csv/*"), parse_pattern("out/csv/*").unwrap());
+        assert!(parse("list sources outputs").is_err());
+    }
+
     #[test]
     fn parse_pattern_wrong_pattern() {
         assert_eq!(
@@ -1,8 +1,10 @@
-mod command;
+pub mod command;
 mod socket;
 
+use std::path::PathBuf;
+
 use alumet::plugin::rust::{AlumetPlugin, deserialize_config, serialize_config};
-use alumet::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable};
+use alumet::plugin::{AlumetPluginStart, AlumetPostStart, ConfigTable, capability::Capability};
 use serde::{Deserialize, Serialize};
 use socket::SocketControl;
 
@@ -26,6 +28,11 @@ impl AlumetPlugin for SocketControlPlugin {
         env!("CARGO_PKG_VERSION")
     }
 
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(PathBuf::from(config.socket_path))]))
+    }
+
     fn default_config() -> anyhow::Result<Option<ConfigTable>> {
         let config = serialize_config(Config::default())?;
         Ok(Some(config))
@@ -88,21 +88,57 @@ impl SocketControl {
     }
 }
 
+/// Handles a client connection.
+///
+/// # Protocol
+/// The client sends one command per line. For each command, the server replies with
+/// zero or more lines of output, followed by a status line that terminates the response:
+/// - `ok` if the command succeeded
+/// - `error: <message>` if it failed
+///
+/// An error does not close the connection: the client can send another command.
 async fn handle_socket_connection(
     stream: UnixStream,
     _addr: SocketAddr,
     alumet_handle: &AnonymousControlHandle,
 ) -> anyhow::Result<()> {
-    use anyhow::anyhow;
-    use tokio::io::{AsyncBufReadExt, BufStream};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufStream};
 
     let buf = BufStream::new(stream);
     let mut lines = buf.lines();
     while let Some(line) = lines.next_line().await? {
-        let cmd = command::parse(&line)?;
-        cmd.run(alumet_handle)
-            .await
-            .map_err(|e| anyhow!("failed to run command {line}: {e}"))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut response = String::new();
+        match run_command(line, alumet_handle).await {
+            Ok(output) => {
+                for l in output {
+                    response.push_str(&l);
+                    response.push('\n');
+                }
+                response.push_str("ok\n");
+            }
+            Err(e) => {
+                log::warn!("Command '{line}' received on the unix socket failed: {e:#}");
+                // keep the response on a single line
+                let msg = format!("{e:#}").replace('\n', " ");
+                response.push_str(&format!("error: {msg}\n"));
+            }
+        }
+        let stream = lines.get_mut();
+        stream.write_all(response.as_bytes()).await?;
+        stream.flush().await?;
     }
     Ok(())
 }
+
+async fn run_command(line: &str, alumet_handle: &AnonymousControlHandle) -> anyhow::Result<Vec<String>> {
+    let cmd = command::parse(line)?;
+    let output = cmd
+        .run(alumet_handle)
+        .await
+        .with_context(|| format!("failed to run command {line}"))?;
+    Ok(output)
+}
@@ -1,4 +1,8 @@
-use std::{io::Write, os::unix::net::UnixStream, time::Duration};
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    time::Duration,
+};
 
 use alumet::{
     agent::{
@@ -40,7 +44,19 @@ fn shutdown() {
 
     // send a command to the socket
     let mut stream = UnixStream::connect(socket_file).expect("I should be able to connect to the socket");
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    socket_write_line(&mut stream, "list");
+    // no plugin element in the pipeline, only the dummy output that Alumet adds when there is no output
+    assert_eq!(socket_read_response(&mut reader), vec!["outputs/alumet/dummy", "ok"]);
     socket_write_line(&mut stream, "control source stop"); // just to check the "hard" path of command execution
+    assert_eq!(socket_read_response(&mut reader), vec!["ok"]);
+    socket_write_line(&mut stream, "bad command");
+    let response = socket_read_response(&mut reader);
+    assert_eq!(response.len(), 1);
+    assert!(
+        response[0].starts_with("error: unknown command"),
+        "unexpected response {response:?}"
+    );
     socket_write_line(&mut stream, "shutdown");
 
     // check that alumet has stopped
@@ -55,3 +71,20 @@ fn socket_write_line(stream: &mut UnixStream, line: &str) {
     stream.write_all(&buf).expect("I should be able to write to the socket");
     stream.flush().unwrap();
 }
+
+fn socket_read_response(reader: &mut impl BufRead) -> Vec<String> {
+    // the response ends with a status line: "ok" or "error: ..."
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .expect("I should be able to read from the socket");
+        let line = line.trim_end().to_owned();
+        let end = line == "ok" || line.starts_with("error:");
+        lines.push(line);
+        if end {
+            return lines;
+        }
+    }
+}
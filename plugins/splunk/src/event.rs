@@ -0,0 +1,126 @@
+//! Conversion of the measurements to HEC events.
+//!
+//! See <https://docs.splunk.com/Documentation/Splunk/latest/Data/FormateventsforHTTPEventCollector>.
+
+use std::collections::BTreeMap;
+
+use alumet::{
+    json,
+    measurement::{MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// How the measurements are represented in Splunk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventFormat {
+    /// One JSON event per measurement, for an event index.
+    #[default]
+    Event,
+    /// One metric data point per measurement, for a metrics index.
+    Metric,
+}
+
+/// Metadata shared by all the events.
+pub struct Metadata {
+    pub host: String,
+    pub source: String,
+    pub sourcetype: String,
+    pub index: Option<String>,
+}
+
+/// An event of the HEC protocol.
+///
+/// The fields are serialized in this order, and the maps are sorted by key.
+#[derive(Serialize)]
+struct HecEvent<'a> {
+    time: f64,
+    host: &'a str,
+    source: &'a str,
+    sourcetype: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    index: Option<&'a str>,
+    event: EventBody<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<BTreeMap<String, Value>>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum EventBody<'a> {
+    Measurement(MeasurementEvent<'a>),
+    /// The constant `"metric"`, which marks a metric data point.
+    Metric(&'static str),
+}
+
+/// The `event` of [`EventFormat::Event`].
+#[derive(Serialize)]
+struct MeasurementEvent<'a> {
+    metric: &'a str,
+    value: Value,
+    unit: String,
+    resource_kind: &'a str,
+    resource_id: String,
+    consumer_kind: &'a str,
+    consumer_id: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    attributes: BTreeMap<&'a str, Value>,
+}
+
+/// Appends the HEC event of `m` to `buf`.
+///
+/// HEC accepts several events in the same request, simply concatenated.
+/// A newline is added after each event to make the payload easier to read.
+pub fn write_event(buf: &mut Vec<u8>, m: &MeasurementPoint, metric: &Metric, format: EventFormat, meta: &Metadata) {
+    let (secs, nanos) = m.timestamp.to_unix_timestamp();
+    // Splunk keeps at most microseconds.
+    let time = secs as f64 + f64::from(nanos / 1000) / 1e6;
+    let value = match m.value {
+        WrappedMeasurementValue::F64(v) => json!(v),
+        WrappedMeasurementValue::U64(v) => json!(v),
+    };
+
+    let (event, fields) = match format {
+        EventFormat::Event => {
+            let event = MeasurementEvent {
+                metric: &metric.name,
+                value,
+                unit: metric.unit.unique_name(),
+                resource_kind: m.resource.kind(),
+                resource_id: m.resource.id_display().to_string(),
+                consumer_kind: m.consumer.kind(),
+                consumer_id: m.consumer.id_display().to_string(),
+                attributes: json::attributes(m),
+            };
+            (EventBody::Measurement(event), None)
+        }
+        EventFormat::Metric => {
+            // The dimensions of a metric data point are strings.
+            let mut fields: BTreeMap<String, Value> = m
+                .attributes()
+                .map(|(key, value)| (key.to_owned(), json!(value.to_string())))
+                .collect();
+            fields.insert(format!("metric_name:{}", metric.name), value);
+            fields.insert(String::from("unit"), json!(metric.unit.unique_name()));
+            fields.insert(String::from("resource_kind"), json!(m.resource.kind()));
+            fields.insert(String::from("resource_id"), json!(m.resource.id_display().to_string()));
+            fields.insert(String::from("consumer_kind"), json!(m.consumer.kind()));
+            fields.insert(String::from("consumer_id"), json!(m.consumer.id_display().to_string()));
+            (EventBody::Metric("metric"), Some(fields))
+        }
+    };
+
+    let event = HecEvent {
+        time,
+        host: &meta.host,
+        source: &meta.source,
+        sourcetype: &meta.sourcetype,
+        index: meta.index.as_deref(),
+        event,
+        fields,
+    };
+    serde_json::to_writer(&mut *buf, &event).expect("an event should always be serializable");
+    buf.push(b'\n');
+}
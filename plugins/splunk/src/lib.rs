@@ -0,0 +1,128 @@
+mod event;
+mod output;
+
+use std::time::Duration;
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::Context;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+pub use crate::event::EventFormat;
+use crate::event::Metadata;
+use crate::output::SplunkOutput;
+
+pub struct SplunkPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for SplunkPlugin {
+    fn name() -> &'static str {
+        "splunk"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(
+            !config.token.is_empty(),
+            "the token of the HTTP Event Collector is missing"
+        );
+        anyhow::ensure!(config.batch_size > 0, "batch_size must be greater than zero");
+        Ok(Box::new(SplunkPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let host = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .danger_accept_invalid_certs(config.accept_invalid_certs)
+            .build()
+            .context("failed to create the HTTP client")?;
+        let meta = Metadata {
+            host,
+            source: config.source.clone(),
+            sourcetype: config.sourcetype.clone(),
+            index: config.index.clone(),
+        };
+        let output = SplunkOutput::new(
+            client,
+            &config.url,
+            &config.token,
+            config.channel.clone(),
+            config.format,
+            meta,
+            config.batch_size,
+        );
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// URL of the HTTP Event Collector, without the path of the endpoint.
+    pub url: String,
+    /// Token of the HTTP Event Collector.
+    pub token: String,
+    /// Index in which the events are stored. Defaults to the index of the token.
+    pub index: Option<String>,
+    pub source: String,
+    pub sourcetype: String,
+    /// Value of the `host` field of the events. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Send the measurements as JSON events (`"event"`) or as metric data points (`"metric"`).
+    pub format: EventFormat,
+    /// Channel of the requests, required when the indexer acknowledgement is enabled on the token.
+    pub channel: Option<String>,
+    /// Maximum number of events per request.
+    pub batch_size: usize,
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Accept the invalid TLS certificates, such as the self-signed certificate of a default Splunk installation.
+    pub accept_invalid_certs: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: String::from("https://localhost:8088"),
+            token: String::from("FILL ME"),
+            index: None,
+            source: String::from("alumet"),
+            sourcetype: String::from("alumet"),
+            hostname: None,
+            format: EventFormat::Event,
+            channel: None,
+            batch_size: 500,
+            timeout: Duration::from_secs(10),
+            accept_invalid_certs: false,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::elements::{
+        error::WriteError,
+        output::{OutputContext, error::WriteRetry},
+    },
+};
+use anyhow::{Context, anyhow};
+use itertools::Itertools;
+use reqwest::{StatusCode, blocking::Client};
+
+use crate::event::{EventFormat, Metadata, write_event};
+
+/// Path of the endpoint that accepts JSON events, relative to the URL of the collector.
+const EVENT_ENDPOINT: &str = "/services/collector/event";
+
+pub struct SplunkOutput {
+    client: Client,
+    endpoint: String,
+    /// Value of the `Authorization` header.
+    authorization: String,
+    /// Required by HEC when the indexer acknowledgement is enabled.
+    channel: Option<String>,
+    format: EventFormat,
+    meta: Metadata,
+    /// Maximum number of events per request.
+    batch_size: usize,
+}
+
+impl SplunkOutput {
+    pub fn new(
+        client: Client,
+        url: &str,
+        token: &str,
+        channel: Option<String>,
+        format: EventFormat,
+        meta: Metadata,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            client,
+            endpoint: format!("{}{EVENT_ENDPOINT}", url.trim_end_matches('/')),
+            authorization: format!("Splunk {token}"),
+            channel,
+            format,
+            meta,
+            batch_size,
+        }
+    }
+
+    /// Sends a batch of events to the collector.
+    fn send(&self, body: Vec<u8>) -> Result<(), WriteError> {
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .header("Authorization", &self.authorization)
+            .header("Content-Type", "application/json")
+            .body(body);
+        if let Some(channel) = &self.channel {
+            request = request.header("X-Splunk-Request-Channel", channel);
+        }
+        let res = request
+            .send()
+            .context("failed to send the events to Splunk")
+            .retry_write()?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = res.text().unwrap_or_default();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            // The collector is busy or unavailable, try again later.
+            return Err(WriteError::CanRetry(anyhow!("Splunk error {status}: {body}")));
+        }
+        // The events have been rejected (invalid token, disabled collector, invalid data...),
+        // sending them again would not help.
+        log::error!("Splunk rejected the events with status {status}: {body}");
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Output for SplunkOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        for batch in &measurements.iter().chunks(self.batch_size) {
+            let mut body = Vec::new();
+            for m in batch {
+                let metric = ctx
+                    .metrics
+                    .by_id(&m.metric)
+                    .with_context(|| format!("unknown metric {:?}", m.metric))?;
+                write_event(&mut body, m, metric, self.format, &self.meta);
+            }
+            if !body.is_empty() {
+                self.send(body)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{HttpCapture, SampleMeasurements, assert_golden},
+    },
+};
+use plugin_splunk::{Config, EventFormat, SplunkPlugin};
+use pretty_assertions::assert_eq;
+
+fn config(server: &HttpCapture) -> Config {
+    Config {
+        url: server.url(),
+        token: String::from("00000000-1111-2222-3333-444444444444"),
+        hostname: Some(String::from("node-1")),
+        ..Config::default()
+    }
+}
+
+fn send_sample(config: Config) -> anyhow::Result<()> {
+    let mut harness = PluginHarness::<SplunkPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()
+}
+
+#[test]
+fn events() -> anyhow::Result<()> {
+    let server = HttpCapture::start()?;
+    send_sample(Config {
+        index: Some(String::from("energy")),
+        ..config(&server)
+    })?;
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    let req = &requests[0];
+    assert_eq!(
+        (req.method.as_str(), req.path.as_str()),
+        ("POST", "/services/collector/event")
+    );
+    assert_eq!(
+        req.header("authorization"),
+        Some("Splunk 00000000-1111-2222-3333-444444444444")
+    );
+    assert_eq!(req.header("content-type"), Some("application/json"));
+    assert_eq!(req.header("x-splunk-request-channel"), None);
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/events.txt"),
+        req.body_str(),
+    );
+    Ok(())
+}
+
+#[test]
+fn metrics_in_batches() -> anyhow::Result<()> {
+    let server = HttpCapture::start()?;
+    send_sample(Config {
+        format: EventFormat::Metric,
+        batch_size: 2,
+        channel: Some(String::from("0aecf2c6-2b7a-4d1e-8f04-1d7e2c3f4a5b")),
+        ..config(&server)
+    })?;
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    for req in &requests {
+        assert_eq!(
+            req.header("x-splunk-request-channel"),
+            Some("0aecf2c6-2b7a-4d1e-8f04-1d7e2c3f4a5b")
+        );
+    }
+    let body: String = requests.iter().map(|r| r.body_str()).collect();
+    assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/metrics.txt"), body);
+    Ok(())
+}
+
+#[test]
+fn retry_when_busy() -> anyhow::Result<()> {
+    let server = HttpCapture::with_status(503)?;
+    let mut harness = PluginHarness::<SplunkPlugin>::start(serialize_config(config(&server))?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    assert!(matches!(
+        res,
+        Err(alumet::pipeline::elements::error::WriteError::CanRetry(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn drop_rejected_events() -> anyhow::Result<()> {
+    let server = HttpCapture::with_status(403)?;
+    let mut harness = PluginHarness::<SplunkPlugin>::start(serialize_config(config(&server))?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("out")?;
+    assert!(output.write(&sample.measurements()).is_ok());
+    assert_eq!(server.requests().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn invalid_config() -> anyhow::Result<()> {
+    let server = HttpCapture::start()?;
+    let config = Config {
+        token: String::new(),
+        ..config(&server)
+    };
+    assert!(PluginHarness::<SplunkPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
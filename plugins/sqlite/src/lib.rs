@@ -0,0 +1,84 @@
+mod output;
+mod schema;
+
+use std::{path::PathBuf, time::Duration};
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::anyhow;
+use output::SqliteOutput;
+pub use schema::Synchronous;
+use serde::{Deserialize, Serialize};
+
+pub struct SqlitePlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for SqlitePlugin {
+    fn name() -> &'static str {
+        "sqlite"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities_with_config(config: &ConfigTable) -> anyhow::Result<Option<Vec<Capability>>> {
+        let config: Config = deserialize_config(config.clone())?;
+        Ok(Some(vec![Capability::Filesystem(config.path)]))
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.batch_size == 0 {
+            return Err(anyhow!("batch_size must be greater than zero"));
+        }
+        Ok(Box::new(SqlitePlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        // Open the database now, to report a wrong path or an incompatible file as early as possible.
+        let conn = schema::open(&self.config.path, self.config.synchronous, self.config.busy_timeout)?;
+        log::info!("Writing the measurements to SQLite database {:?}", self.config.path);
+        let output = Box::new(SqliteOutput::new(conn, self.config.batch_size));
+        alumet.add_blocking_output("out", output)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Absolute or relative path to the database file, created if it does not exist.
+    pub path: PathBuf,
+    /// Maximum number of measurements inserted in one transaction.
+    pub batch_size: usize,
+    /// How often SQLite waits for the data to be written to the disk.
+    #[serde(default)]
+    pub synchronous: Synchronous,
+    /// How long to wait for the database to be unlocked, when another process writes to it.
+    #[serde(with = "humantime_serde")]
+    pub busy_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("alumet-data.sqlite"),
+            batch_size: 10_000,
+            synchronous: Synchronous::default(),
+            busy_timeout: Duration::from_secs(5),
+        }
+    }
+}
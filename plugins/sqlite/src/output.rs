@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use alumet::{
+    json,
+    measurement::{MeasurementBuffer, MeasurementPoint, WrappedMeasurementValue},
+    metrics::{Metric, RawMetricId},
+    pipeline::{
+        Output,
+        elements::{
+            error::WriteError,
+            output::{OutputContext, error::WriteRetry},
+        },
+    },
+};
+use anyhow::Context;
+use itertools::Itertools;
+use rusqlite::{Connection, ErrorCode, Transaction, params, types::Value};
+
+const INSERT_METRIC: &str = "
+INSERT INTO metrics (name, unit, value_type, description) VALUES (?1, ?2, ?3, ?4)
+ON CONFLICT (name) DO UPDATE SET unit = excluded.unit, value_type = excluded.value_type, description = excluded.description
+RETURNING id";
+
+const INSERT_MEASUREMENT: &str = "
+INSERT INTO measurements (timestamp, metric_id, value, resource_kind, resource_id, consumer_kind, consumer_id, attributes)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+
+pub struct SqliteOutput {
+    conn: Connection,
+    batch_size: usize,
+    /// Id of each metric in the table `metrics`.
+    metric_ids: HashMap<RawMetricId, i64>,
+}
+
+impl SqliteOutput {
+    pub fn new(conn: Connection, batch_size: usize) -> Self {
+        Self {
+            conn,
+            batch_size,
+            metric_ids: HashMap::new(),
+        }
+    }
+}
+
+impl Output for SqliteOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        // Each batch is inserted in its own transaction, which is much faster than one transaction per row.
+        for batch in &measurements.iter().chunks(self.batch_size) {
+            let tx = self.conn.transaction()?;
+            // The ids of the new metrics are only valid if the transaction is committed.
+            let mut new_metrics = HashMap::new();
+            let res = insert_batch(&tx, batch, ctx, &self.metric_ids, &mut new_metrics).and_then(|_| tx.commit());
+            match res {
+                Ok(()) => self.metric_ids.extend(new_metrics),
+                Err(e) if is_busy(&e) => {
+                    return Err(e).context("the SQLite database is locked").retry_write();
+                }
+                Err(e) => return Err(e).context("failed to insert the measurements into SQLite")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn insert_batch<'a>(
+    tx: &Transaction,
+    batch: impl Iterator<Item = &'a MeasurementPoint>,
+    ctx: &OutputContext,
+    metric_ids: &HashMap<RawMetricId, i64>,
+    new_metrics: &mut HashMap<RawMetricId, i64>,
+) -> rusqlite::Result<()> {
+    let mut insert_metric = tx.prepare_cached(INSERT_METRIC)?;
+    let mut insert = tx.prepare_cached(INSERT_MEASUREMENT)?;
+    for m in batch {
+        let metric_id = match metric_ids.get(&m.metric).or_else(|| new_metrics.get(&m.metric)) {
+            Some(id) => *id,
+            None => {
+                let Some(metric) = ctx.metrics.by_id(&m.metric) else {
+                    log::warn!("Unknown metric {:?}, the measurement is ignored.", m.metric);
+                    continue;
+                };
+                let id = insert_metric.query_row(metric_params(metric), |row| row.get(0))?;
+                new_metrics.insert(m.metric, id);
+                id
+            }
+        };
+        let (secs, nanos) = m.timestamp.to_unix_timestamp();
+        let timestamp = secs as i64 * 1_000_000_000 + i64::from(nanos);
+        insert.execute(params![
+            timestamp,
+            metric_id,
+            convert_value(&m.value),
+            m.resource.kind(),
+            m.resource.id_display().to_string(),
+            m.consumer.kind(),
+            m.consumer.id_display().to_string(),
+            attributes_json(m),
+        ])?;
+    }
+    Ok(())
+}
+
+fn metric_params(metric: &Metric) -> [String; 4] {
+    [
+        metric.name.clone(),
+        metric.unit.unique_name(),
+        metric.value_type.to_string(),
+        metric.description.clone(),
+    ]
+}
+
+/// Converts a value to an integer or a real number.
+///
+/// SQLite integers are signed: the unsigned values that are too big are stored as real numbers.
+fn convert_value(value: &WrappedMeasurementValue) -> Value {
+    match value {
+        WrappedMeasurementValue::F64(v) => Value::Real(*v),
+        WrappedMeasurementValue::U64(v) => match i64::try_from(*v) {
+            Ok(v) => Value::Integer(v),
+            Err(_) => Value::Real(*v as f64),
+        },
+    }
+}
+
+/// Returns the attributes as a JSON object sorted by key, or `None` if there is no attribute.
+fn attributes_json(m: &MeasurementPoint) -> Option<String> {
+    if m.attributes_len() == 0 {
+        return None;
+    }
+    Some(serde_json::to_string(&json::attributes(m)).expect("the attributes should always be serializable"))
+}
+
+/// Returns `true` if the error is caused by another connection that holds a lock on the database.
+fn is_busy(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::measurement::WrappedMeasurementValue;
+    use rusqlite::types::Value;
+
+    use super::convert_value;
+
+    #[test]
+    fn values() {
+        assert_eq!(convert_value(&WrappedMeasurementValue::F64(1.5)), Value::Real(1.5));
+        assert_eq!(convert_value(&WrappedMeasurementValue::U64(42)), Value::Integer(42));
+        assert_eq!(
+            convert_value(&WrappedMeasurementValue::U64(u64::MAX)),
+            Value::Real(u64::MAX as f64)
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Tables of the database, and configuration of the connection.
+
+use std::{path::Path, time::Duration};
+
+use anyhow::{Context, anyhow};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// Version of the schema, stored in `PRAGMA user_version`.
+pub const SCHEMA_VERSION: i32 = 1;
+
+/// The tables of the database.
+///
+/// The timestamps are stored in nanoseconds since the Unix epoch, and the attributes
+/// in a JSON object. The column `value` has no type, so that it keeps the integers and the
+/// floating-point numbers as they are. The view `measurements_view` joins the metrics and
+/// converts the timestamps to text (with a precision of one millisecond), for convenience.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS metrics (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    unit TEXT NOT NULL,
+    value_type TEXT NOT NULL,
+    description TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS measurements (
+    timestamp INTEGER NOT NULL,
+    metric_id INTEGER NOT NULL REFERENCES metrics(id),
+    value NOT NULL,
+    resource_kind TEXT NOT NULL,
+    resource_id TEXT NOT NULL,
+    consumer_kind TEXT NOT NULL,
+    consumer_id TEXT NOT NULL,
+    attributes TEXT
+);
+
+CREATE INDEX IF NOT EXISTS measurements_by_metric ON measurements(metric_id, timestamp);
+
+CREATE VIEW IF NOT EXISTS measurements_view AS
+SELECT
+    strftime('%Y-%m-%dT%H:%M:%fZ', m.timestamp / 1e9, 'unixepoch') AS time,
+    metrics.name AS metric,
+    m.value,
+    metrics.unit,
+    m.resource_kind,
+    m.resource_id,
+    m.consumer_kind,
+    m.consumer_id,
+    m.attributes
+FROM measurements AS m JOIN metrics ON metrics.id = m.metric_id;
+";
+
+/// Value of `PRAGMA synchronous`, see <https://www.sqlite.org/pragma.html#pragma_synchronous>.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    /// Never wait for the disk. The last transactions can be lost if the machine crashes.
+    Off,
+    /// Wait for the disk at checkpoints. Safe from corruption in WAL mode, and fast.
+    #[default]
+    Normal,
+    /// Wait for the disk after each transaction.
+    Full,
+}
+
+impl Synchronous {
+    fn as_pragma(&self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+        }
+    }
+}
+
+/// Opens the database in WAL mode and creates the tables if needed.
+pub fn open(path: &Path, synchronous: Synchronous, busy_timeout: Duration) -> anyhow::Result<Connection> {
+    let conn = Connection::open(path).with_context(|| format!("failed to open SQLite database {path:?}"))?;
+    conn.busy_timeout(busy_timeout)?;
+
+    // WAL allows other processes to read the database while Alumet writes to it.
+    let mode: String = conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    if !mode.eq_ignore_ascii_case("wal") {
+        log::warn!("SQLite database {path:?} does not support the WAL mode, using journal mode {mode} instead.");
+    }
+    conn.pragma_update(None, "synchronous", synchronous.as_pragma())?;
+
+    let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "SQLite database {path:?} has schema version {version}, which is newer than the supported version {SCHEMA_VERSION}"
+        ));
+    }
+    conn.execute_batch(SCHEMA)
+        .with_context(|| format!("failed to create the tables of {path:?}"))?;
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    Ok(conn)
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_sqlite::{Config, SqlitePlugin};
+use pretty_assertions::assert_eq;
+use rusqlite::{Connection, types::Value};
+
+/// Writes the sample measurements to the database at `path`.
+fn write_sample(path: &Path) -> anyhow::Result<()> {
+    let config = Config {
+        path: path.to_owned(),
+        ..Config::default()
+    };
+    let mut harness = PluginHarness::<SqlitePlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()
+}
+
+/// Returns the result of a query, one line per row, with the columns separated by `|`.
+fn dump(conn: &Connection, query: &str) -> anyhow::Result<String> {
+    let mut stmt = conn.prepare(query)?;
+    let n_columns = stmt.column_count();
+    let mut rows = stmt.query([])?;
+    let mut res = String::new();
+    while let Some(row) = rows.next()? {
+        let values: Vec<String> = (0..n_columns)
+            .map(|i| match row.get::<_, Value>(i) {
+                Ok(Value::Null) => String::new(),
+                Ok(Value::Integer(v)) => v.to_string(),
+                Ok(Value::Real(v)) => format!("{v:?}"),
+                Ok(Value::Text(v)) => v,
+                Ok(Value::Blob(v)) => format!("{v:?}"),
+                Err(e) => panic!("invalid value in column {i}: {e}"),
+            })
+            .collect();
+        res.push_str(&values.join("|"));
+        res.push('\n');
+    }
+    Ok(res)
+}
+
+#[test]
+fn sqlite_format() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("alumet-data.sqlite");
+    write_sample(&path)?;
+
+    let conn = Connection::open(&path)?;
+    let journal_mode: String = conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+    assert_eq!(journal_mode, "wal");
+    let content = format!(
+        "-- metrics\n{}-- measurements\n{}-- measurements_view\n{}",
+        dump(&conn, "SELECT * FROM metrics ORDER BY id")?,
+        dump(&conn, "SELECT * FROM measurements ORDER BY rowid")?,
+        dump(&conn, "SELECT * FROM measurements_view ORDER BY time, metric, value")?
+    );
+    assert_golden(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.txt"), content);
+    Ok(())
+}
+
+#[test]
+fn append_to_existing_database() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("alumet-data.sqlite");
+    write_sample(&path)?;
+    write_sample(&path)?;
+
+    let conn = Connection::open(&path)?;
+    let n_metrics: i64 = conn.query_row("SELECT count(*) FROM metrics", [], |row| row.get(0))?;
+    let n_measurements: i64 = conn.query_row("SELECT count(*) FROM measurements", [], |row| row.get(0))?;
+    assert_eq!(n_metrics, 2);
+    assert_eq!(n_measurements, 10);
+    Ok(())
+}
+
+#[test]
+fn newer_schema_is_rejected() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("alumet-data.sqlite");
+    Connection::open(&path)?.pragma_update(None, "user_version", 99)?;
+
+    let config = Config {
+        path,
+        ..Config::default()
+    };
+    assert!(PluginHarness::<SqlitePlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}
@@ -0,0 +1,104 @@
+mod parse;
+mod source;
+
+use std::time::Duration;
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::source::StatsdSource;
+
+pub struct StatsdInputPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for StatsdInputPlugin {
+    fn name() -> &'static str {
+        "statsd-input"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(StatsdInputPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        // Bind the socket right now (fail fast).
+        let address = &self.config.address;
+        let socket =
+            std::net::UdpSocket::bind(address.as_str()).with_context(|| format!("failed to listen on {address}"))?;
+        socket.set_nonblocking(true)?;
+        log::info!("Listening for StatsD on {}", socket.local_addr()?);
+
+        let flush_interval = self.config.flush_interval;
+        let prefix = self.config.prefix.clone();
+        let (max_metrics, max_gauges) = (self.config.max_metrics, self.config.max_gauges);
+        alumet.add_autonomous_source_builder("udp", move |ctx, cancel_token, out_tx| {
+            let metrics_tx = ctx.metrics_sender();
+            let source = Box::pin(async move {
+                let socket = tokio::net::UdpSocket::from_std(socket)?;
+                let source = StatsdSource::new(
+                    socket,
+                    flush_interval,
+                    prefix,
+                    max_metrics,
+                    max_gauges,
+                    metrics_tx,
+                    out_tx,
+                );
+                source.run(cancel_token).await
+            });
+            Ok(source)
+        })?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the UDP socket.
+    pub address: String,
+    /// Interval between two sendings of the received measurements to the pipeline.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+    /// Prepended to the name of the metrics, e.g. `app_`.
+    pub prefix: String,
+    /// Maximum number of metrics that the clients can create, the new metrics beyond it are ignored.
+    pub max_metrics: usize,
+    /// Maximum number of gauges (by name and tags) that are tracked, the new gauges beyond it are ignored.
+    pub max_gauges: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: String::from("127.0.0.1:8125"),
+            flush_interval: Duration::from_secs(1),
+            prefix: String::new(),
+            max_metrics: 1000,
+            max_gauges: 10_000,
+        }
+    }
+}
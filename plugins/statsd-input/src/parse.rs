@@ -0,0 +1,263 @@
+//! Parsing of the StatsD protocol and of its extensions.
+//!
+//! A packet contains one or more lines like `name:value|type|@sample_rate`. The tags can be
+//! given in the format of DogStatsD (`|#key:value,key2:value2` at the end of the line) or in the
+//! format of Telegraf (`name,key=value,key2=value2:value|type`).
+
+use std::{
+    fmt,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::measurement::Timestamp;
+
+/// Type of a StatsD metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// `c`: a number of events since the last sample.
+    Counter,
+    /// `g`: the current value, or a change of the current value if it is signed (`+3`, `-2`).
+    Gauge,
+    /// `ms`: a duration, in milliseconds.
+    Timer,
+    /// `h` and `d`: a value of a histogram or distribution (DogStatsD).
+    Histogram,
+}
+
+/// A line of StatsD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub kind: Kind,
+    /// The values of the line (DogStatsD accepts several values separated by `:`).
+    pub values: Vec<Value>,
+    /// Fraction of the events that have been sent, between 0 and 1.
+    pub sample_rate: f64,
+    /// Tags, with an empty value for the tags without value.
+    pub tags: Vec<(String, String)>,
+    /// Timestamp of the sample, if it is given (DogStatsD `|T<unix seconds>`).
+    pub timestamp: Option<Timestamp>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Absolute(f64),
+    /// A signed gauge value, which is added to the current value.
+    Relative(f64),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    line: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid StatsD line {:?}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a line of StatsD.
+///
+/// Returns `Ok(None)` for the lines that are valid but not supported: sets, events and service checks.
+pub fn parse_line(line: &str) -> Result<Option<Sample>, ParseError> {
+    let error = |reason| ParseError {
+        line: line.to_owned(),
+        reason,
+    };
+    if line.starts_with("_e{") || line.starts_with("_sc|") {
+        return Ok(None);
+    }
+
+    let mut fields = line.split('|');
+    let metric = fields.next().unwrap_or_default();
+    let kind = match fields.next() {
+        Some("c") => Kind::Counter,
+        Some("g") => Kind::Gauge,
+        Some("ms") => Kind::Timer,
+        Some("h" | "d") => Kind::Histogram,
+        Some("s") => return Ok(None),
+        Some(_) => return Err(error("unknown metric type")),
+        None => return Err(error("no metric type")),
+    };
+
+    let (name, raw_values) = metric.split_once(':').ok_or_else(|| error("no value"))?;
+    let mut tags = Vec::new();
+    // Telegraf tags
+    let mut name_and_tags = name.split(',');
+    let name = name_and_tags.next().unwrap_or_default();
+    if name.is_empty() {
+        return Err(error("empty name"));
+    }
+    for tag in name_and_tags {
+        let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+        tags.push((key.to_owned(), value.to_owned()));
+    }
+
+    let values = raw_values
+        .split(':')
+        .map(|v| parse_value(v, kind))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| error("invalid value"))?;
+
+    let mut sample_rate = 1.0;
+    let mut timestamp = None;
+    for field in fields {
+        if let Some(rate) = field.strip_prefix('@') {
+            sample_rate = rate.parse().map_err(|_| error("invalid sample rate"))?;
+            if !(sample_rate > 0.0 && sample_rate <= 1.0) {
+                return Err(error("invalid sample rate"));
+            }
+        } else if let Some(dogstatsd_tags) = field.strip_prefix('#') {
+            for tag in dogstatsd_tags.split(',').filter(|t| !t.is_empty()) {
+                let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+                tags.push((key.to_owned(), value.to_owned()));
+            }
+        } else if let Some(seconds) = field.strip_prefix('T') {
+            let seconds: u64 = seconds.parse().map_err(|_| error("invalid timestamp"))?;
+            let time = UNIX_EPOCH
+                .checked_add(Duration::from_secs(seconds))
+                .ok_or_else(|| error("invalid timestamp"))?;
+            timestamp = Some(Timestamp::from(time));
+        }
+        // other extensions, such as the container id of DogStatsD (`|c:<id>`), are ignored
+    }
+
+    Ok(Some(Sample {
+        name: name.to_owned(),
+        kind,
+        values,
+        sample_rate,
+        tags,
+        timestamp,
+    }))
+}
+
+fn parse_value(value: &str, kind: Kind) -> Option<Value> {
+    let number: f64 = value.parse().ok()?;
+    if !number.is_finite() {
+        return None;
+    }
+    if kind == Kind::Gauge && (value.starts_with('+') || value.starts_with('-')) {
+        Some(Value::Relative(number))
+    } else {
+        Some(Value::Absolute(number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn sample(name: &str, kind: Kind, values: Vec<Value>) -> Sample {
+        Sample {
+            name: name.to_owned(),
+            kind,
+            values,
+            sample_rate: 1.0,
+            tags: Vec::new(),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn plain_statsd() {
+        assert_eq!(
+            parse_line("page.views:1|c").unwrap(),
+            Some(sample("page.views", Kind::Counter, vec![Value::Absolute(1.0)]))
+        );
+        assert_eq!(
+            parse_line("request.time:320.5|ms|@0.1").unwrap(),
+            Some(Sample {
+                sample_rate: 0.1,
+                ..sample("request.time", Kind::Timer, vec![Value::Absolute(320.5)])
+            })
+        );
+        assert_eq!(
+            parse_line("queue.size:-3|g").unwrap(),
+            Some(sample("queue.size", Kind::Gauge, vec![Value::Relative(-3.0)]))
+        );
+        // only the gauges have relative values
+        assert_eq!(
+            parse_line("temperature.delta:-3|h").unwrap(),
+            Some(sample(
+                "temperature.delta",
+                Kind::Histogram,
+                vec![Value::Absolute(-3.0)]
+            ))
+        );
+    }
+
+    #[test]
+    fn dogstatsd_tags() {
+        assert_eq!(
+            parse_line("cart.items:2:5|d|#env:prod,shop,region:eu-west|c:83a9f1|T1700000000").unwrap(),
+            Some(Sample {
+                tags: vec![
+                    (String::from("env"), String::from("prod")),
+                    (String::from("shop"), String::new()),
+                    (String::from("region"), String::from("eu-west")),
+                ],
+                timestamp: Some(Timestamp::from(UNIX_EPOCH + Duration::from_secs(1_700_000_000))),
+                ..sample(
+                    "cart.items",
+                    Kind::Histogram,
+                    vec![Value::Absolute(2.0), Value::Absolute(5.0)]
+                )
+            })
+        );
+    }
+
+    #[test]
+    fn telegraf_tags() {
+        assert_eq!(
+            parse_line("users.online,env=prod,region=eu:42|g").unwrap(),
+            Some(Sample {
+                tags: vec![
+                    (String::from("env"), String::from("prod")),
+                    (String::from("region"), String::from("eu")),
+                ],
+                ..sample("users.online", Kind::Gauge, vec![Value::Absolute(42.0)])
+            })
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        assert_eq!(parse_line("users.unique:alice|s").unwrap(), None);
+        assert_eq!(parse_line("_e{5,4}:title|text").unwrap(), None);
+        assert_eq!(parse_line("_sc|Redis connection|2").unwrap(), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let err = |line| parse_line(line).unwrap_err().to_string();
+        assert_eq!(
+            err("page.views:1"),
+            r#"invalid StatsD line "page.views:1": no metric type"#
+        );
+        assert_eq!(err("page.views|c"), r#"invalid StatsD line "page.views|c": no value"#);
+        assert_eq!(
+            err("page.views:x|c"),
+            r#"invalid StatsD line "page.views:x|c": invalid value"#
+        );
+        assert_eq!(
+            err("page.views:1|x"),
+            r#"invalid StatsD line "page.views:1|x": unknown metric type"#
+        );
+        assert_eq!(
+            err("page.views:1|c|@2"),
+            r#"invalid StatsD line "page.views:1|c|@2": invalid sample rate"#
+        );
+        assert_eq!(err(":1|c"), r#"invalid StatsD line ":1|c": empty name"#);
+        assert_eq!(
+            err("page.views:1|c|T18446744073709551615"),
+            r#"invalid StatsD line "page.views:1|c|T18446744073709551615": invalid timestamp"#
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, time::Duration};
+
+use alumet::{
+    measurement::{
+        AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue,
+    },
+    metrics::{Metric, RawMetricId, duplicate::DuplicateReaction, online::MetricSender},
+    resources::{Resource, ResourceConsumer},
+    units::{PrefixedUnit, Unit},
+};
+use anyhow::anyhow;
+use tokio::{net::UdpSocket, sync::mpsc};
+use tokio_util::sync::CancellationToken;
+
+use crate::parse::{self, Kind, Sample, Value};
+
+/// Maximum size of a UDP datagram.
+const MAX_PACKET_SIZE: usize = 65535;
+
+/// Receives the StatsD packets and sends their measurements to the pipeline at regular intervals.
+pub struct StatsdSource {
+    socket: UdpSocket,
+    flush_interval: Duration,
+    /// Prepended to the name of the metrics.
+    prefix: String,
+    metrics_tx: MetricSender,
+    out_tx: mpsc::Sender<MeasurementBuffer>,
+    /// Id of each metric, or `None` if it could not be registered.
+    metric_ids: HashMap<String, Option<RawMetricId>>,
+    /// Current value of the gauges, by name and tags, to apply the relative values.
+    gauges: HashMap<String, f64>,
+    /// Maximum number of metrics, the new metrics beyond it are ignored.
+    max_metrics: usize,
+    /// Maximum number of gauges (by name and tags), the new gauges beyond it are ignored.
+    max_gauges: usize,
+    /// Set when a limit has been reached and logged, to log it only once.
+    limit_reached: bool,
+    buffer: MeasurementBuffer,
+}
+
+impl StatsdSource {
+    pub fn new(
+        socket: UdpSocket,
+        flush_interval: Duration,
+        prefix: String,
+        max_metrics: usize,
+        max_gauges: usize,
+        metrics_tx: MetricSender,
+        out_tx: mpsc::Sender<MeasurementBuffer>,
+    ) -> Self {
+        Self {
+            socket,
+            flush_interval,
+            prefix,
+            metrics_tx,
+            out_tx,
+            metric_ids: HashMap::new(),
+            gauges: HashMap::new(),
+            max_metrics,
+            max_gauges,
+            limit_reached: false,
+            buffer: MeasurementBuffer::new(),
+        }
+    }
+
+    pub async fn run(mut self, cancel_token: CancellationToken) -> anyhow::Result<()> {
+        let mut packet = vec![0; MAX_PACKET_SIZE];
+        let mut flush = tokio::time::interval(self.flush_interval);
+        loop {
+            tokio::select! {
+                biased;
+                _ = cancel_token.cancelled() => {
+                    break;
+                }
+                _ = flush.tick() => {
+                    self.flush().await?;
+                }
+                received = self.socket.recv_from(&mut packet) => {
+                    match received {
+                        Ok((n, _)) => self.handle_packet(&packet[..n]).await?,
+                        Err(e) => log::error!("Failed to receive a StatsD packet: {e}"),
+                    }
+                }
+            }
+        }
+        self.flush().await
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::take(&mut self.buffer);
+            self.out_tx.send(buffer).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_packet(&mut self, packet: &[u8]) -> anyhow::Result<()> {
+        let now = Timestamp::now();
+        let packet = String::from_utf8_lossy(packet);
+        for line in packet.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            match parse::parse_line(line) {
+                Ok(Some(sample)) => self.push(sample, now).await?,
+                Ok(None) => log::trace!("Ignoring unsupported StatsD line {line:?}"),
+                Err(e) => log::debug!("{e}"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn push(&mut self, sample: Sample, now: Timestamp) -> anyhow::Result<()> {
+        let name = format!("{}{}", self.prefix, sample.name);
+        let Some(metric) = self.metric_id(&name, sample.kind).await? else {
+            return Ok(());
+        };
+        let attributes: Vec<_> = sample
+            .tags
+            .iter()
+            .map(|(k, v)| (k.clone(), AttributeValue::String(v.clone())))
+            .collect();
+        let timestamp = sample.timestamp.unwrap_or(now);
+        for value in &sample.values {
+            let value = match (sample.kind, *value) {
+                (Kind::Counter, Value::Absolute(v)) => v / sample.sample_rate,
+                (Kind::Gauge, value) => match self.gauge(&name, &sample.tags, value) {
+                    Some(value) => value,
+                    None => continue,
+                },
+                (_, Value::Absolute(v) | Value::Relative(v)) => v,
+            };
+            let point = MeasurementPoint::new_untyped(
+                timestamp,
+                metric,
+                Resource::LocalMachine,
+                ResourceConsumer::LocalMachine,
+                WrappedMeasurementValue::F64(value),
+            )
+            .with_attr_vec(attributes.clone());
+            self.buffer.push(point);
+        }
+        Ok(())
+    }
+
+    /// Updates the current value of a gauge, and returns it.
+    ///
+    /// Returns `None` if the gauge is new and the maximum number of gauges has been reached.
+    fn gauge(&mut self, name: &str, tags: &[(String, String)], value: Value) -> Option<f64> {
+        let mut tags = tags.to_vec();
+        tags.sort();
+        let key = format!("{name}\0{tags:?}");
+        if !self.gauges.contains_key(&key) && self.gauges.len() >= self.max_gauges {
+            self.log_limit("gauges", self.max_gauges);
+            return None;
+        }
+        let current = self.gauges.entry(key).or_default();
+        match value {
+            Value::Absolute(v) => *current = v,
+            Value::Relative(delta) => *current += delta,
+        }
+        Some(*current)
+    }
+
+    fn log_limit(&mut self, what: &str, limit: usize) {
+        if !self.limit_reached {
+            self.limit_reached = true;
+            log::warn!("The maximum number of StatsD {what} ({limit}) has been reached, the new ones will be ignored.");
+        }
+    }
+
+    /// Returns the id of a metric, registered on its first use.
+    async fn metric_id(&mut self, name: &str, kind: Kind) -> anyhow::Result<Option<RawMetricId>> {
+        if let Some(id) = self.metric_ids.get(name) {
+            return Ok(*id);
+        }
+        if self.metric_ids.len() >= self.max_metrics {
+            self.log_limit("metrics", self.max_metrics);
+            return Ok(None);
+        }
+        let unit = match kind {
+            Kind::Timer => PrefixedUnit::milli(Unit::Second),
+            Kind::Counter | Kind::Gauge | Kind::Histogram => Unit::Unity.into(),
+        };
+        let metric = Metric {
+            name: name.to_owned(),
+            description: String::from("metric received from a StatsD client"),
+            value_type: WrappedMeasurementType::F64,
+            unit,
+            tags: Vec::new(),
+        };
+        let result = self
+            .metrics_tx
+            .create_metrics(vec![metric], DuplicateReaction::Error)
+            .await
+            .map_err(|e| anyhow!("create_metrics returned an error: {e:?}"))?
+            .pop()
+            .expect("there should be one result per metric");
+        let id = match result {
+            Ok(id) => Some(id),
+            Err(e) => {
+                log::error!("The StatsD metric {name} cannot be registered, its values will be ignored: {e}");
+                None
+            }
+        };
+        self.metric_ids.insert(name.to_owned(), id);
+        Ok(id)
+    }
+}
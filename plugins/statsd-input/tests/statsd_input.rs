@@ -0,0 +1,104 @@
+use std::{net::UdpSocket, time::Duration};
+
+use alumet::{
+    measurement::{AttributeValue, WrappedMeasurementValue},
+    plugin::rust::serialize_config,
+    test::PluginHarness,
+    units::{PrefixedUnit, Unit},
+};
+use plugin_statsd_input::{Config, StatsdInputPlugin};
+use pretty_assertions::assert_eq;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn free_address() -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.local_addr().unwrap().to_string()
+}
+
+#[test]
+fn receive_packets() -> anyhow::Result<()> {
+    let address = free_address();
+    let config = Config {
+        address: address.clone(),
+        flush_interval: Duration::from_millis(100),
+        prefix: String::from("app_"),
+        ..Default::default()
+    };
+    let mut harness = PluginHarness::<StatsdInputPlugin>::start(serialize_config(config)?)?;
+    let mut source = harness.autonomous_source("udp")?;
+
+    let client = UdpSocket::bind("127.0.0.1:0")?;
+    client.send_to(
+        b"requests:2|c|@0.5|#route:/api\nqueue:10|g\nqueue:-3|g\nlatency:12.5|ms\nusers:alice|s\ninvalid line",
+        &address,
+    )?;
+
+    let buffer = source.recv_timeout(TIMEOUT).expect("measurements should be received");
+    assert_eq!(
+        harness.values(&buffer, "app_requests"),
+        vec![WrappedMeasurementValue::F64(4.0)]
+    );
+    let requests = harness.points(&buffer, "app_requests");
+    let attributes: Vec<_> = requests[0]
+        .attributes()
+        .map(|(k, v)| (k.to_owned(), v.clone()))
+        .collect();
+    assert_eq!(
+        attributes,
+        vec![(String::from("route"), AttributeValue::String(String::from("/api")))]
+    );
+    assert_eq!(
+        harness.values(&buffer, "app_queue"),
+        vec![WrappedMeasurementValue::F64(10.0), WrappedMeasurementValue::F64(7.0)]
+    );
+    assert_eq!(
+        harness.values(&buffer, "app_latency"),
+        vec![WrappedMeasurementValue::F64(12.5)]
+    );
+    assert_eq!(buffer.len(), 4);
+
+    let metrics = harness.metrics();
+    let latency = metrics.by_name("app_latency").unwrap().1;
+    assert_eq!(latency.unit, PrefixedUnit::milli(Unit::Second));
+
+    source.stop()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn limits() -> anyhow::Result<()> {
+    let address = free_address();
+    let config = Config {
+        address: address.clone(),
+        flush_interval: Duration::from_millis(100),
+        prefix: String::new(),
+        max_metrics: 2,
+        max_gauges: 1,
+    };
+    let mut harness = PluginHarness::<StatsdInputPlugin>::start(serialize_config(config)?)?;
+    let mut source = harness.autonomous_source("udp")?;
+
+    let client = UdpSocket::bind("127.0.0.1:0")?;
+    client.send_to(
+        b"queue:10|g|#shop:eu\nqueue:5|g|#shop:us\nrequests:1|c\nerrors:1|c",
+        &address,
+    )?;
+
+    let buffer = source.recv_timeout(TIMEOUT).expect("measurements should be received");
+    assert_eq!(
+        harness.values(&buffer, "queue"),
+        vec![WrappedMeasurementValue::F64(10.0)]
+    );
+    assert_eq!(
+        harness.values(&buffer, "requests"),
+        vec![WrappedMeasurementValue::F64(1.0)]
+    );
+    assert_eq!(buffer.len(), 2);
+    assert!(harness.metrics().by_name("errors").is_none());
+
+    source.stop()?;
+    harness.stop()?;
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! Encoding of the measurements in the StatsD protocol and its extensions.
+
+use std::collections::HashSet;
+
+use alumet::{
+    measurement::{MeasurementPoint, WrappedMeasurementValue},
+    metrics::Metric,
+};
+use serde::{Deserialize, Serialize};
+
+/// Variant of the StatsD protocol, which determines how the tags are sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    /// The original protocol, without tags: `name:value|g`.
+    Statsd,
+    /// The protocol of Datadog, with the tags at the end: `name:value|g|#key:value,key2:value2`.
+    #[default]
+    Dogstatsd,
+    /// The protocol of Telegraf, with the tags after the name: `name,key=value,key2=value2:value|g`.
+    Telegraf,
+}
+
+/// Encodes the measurements as StatsD lines.
+pub struct Encoder {
+    pub flavor: Flavor,
+    /// Prepended to the name of the metrics, e.g. `alumet.`.
+    pub prefix: String,
+    /// Metrics sent as counters, the other metrics are sent as gauges.
+    pub counters: HashSet<String>,
+    /// Optional tag `host`, added to all the measurements.
+    pub host: Option<String>,
+}
+
+impl Encoder {
+    /// Appends the line of a measurement to `buf`, without the newline.
+    pub fn encode(&self, buf: &mut String, m: &MeasurementPoint, metric: &Metric) {
+        let value = match m.value {
+            WrappedMeasurementValue::F64(v) => v.to_string(),
+            WrappedMeasurementValue::U64(v) => v.to_string(),
+        };
+        let kind = if self.counters.contains(&metric.name) { "c" } else { "g" };
+        let name = format!("{}{}", self.prefix, sanitize(&metric.name));
+
+        match self.flavor {
+            Flavor::Statsd => {
+                buf.push_str(&format!("{name}:{value}|{kind}"));
+            }
+            Flavor::Dogstatsd => {
+                buf.push_str(&format!("{name}:{value}|{kind}"));
+                let tags: Vec<String> = self.tags(m).map(|(k, v)| format!("{k}:{v}")).collect();
+                if !tags.is_empty() {
+                    buf.push_str("|#");
+                    buf.push_str(&tags.join(","));
+                }
+            }
+            Flavor::Telegraf => {
+                buf.push_str(&name);
+                for (k, v) in self.tags(m) {
+                    buf.push_str(&format!(",{k}={v}"));
+                }
+                buf.push_str(&format!(":{value}|{kind}"));
+            }
+        }
+    }
+
+    /// Returns the sanitized tags of a measurement, sorted by key, without the empty values.
+    fn tags(&self, m: &MeasurementPoint) -> impl Iterator<Item = (String, String)> {
+        let mut tags = vec![
+            (String::from("resource_kind"), m.resource.kind().to_owned()),
+            (String::from("resource_id"), m.resource.id_display().to_string()),
+            (String::from("consumer_kind"), m.consumer.kind().to_owned()),
+            (String::from("consumer_id"), m.consumer.id_display().to_string()),
+        ];
+        if let Some(host) = &self.host {
+            tags.push((String::from("host"), host.clone()));
+        }
+        tags.extend(m.attributes().map(|(k, v)| (k.to_owned(), v.to_string())));
+        tags.sort();
+        tags.into_iter()
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| (sanitize(&k), sanitize(&v)))
+    }
+}
+
+/// Replaces the characters that separate the fields of a StatsD line.
+fn sanitize(value: &str) -> String {
+    value.replace(
+        |c: char| matches!(c, ':' | '|' | '@' | '#' | ',' | '=') || c.is_whitespace(),
+        "_",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::SystemTime};
+
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::RawMetricId,
+        resources::{Resource, ResourceConsumer},
+        test::fixture::metric,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{Encoder, Flavor};
+
+    fn encode(flavor: Flavor, counters: &[&str]) -> String {
+        let encoder = Encoder {
+            flavor,
+            prefix: String::from("alumet."),
+            counters: counters.iter().map(|c| c.to_string()).collect::<HashSet<_>>(),
+            host: Some(String::from("node-1")),
+        };
+        let m = MeasurementPoint::new_untyped(
+            Timestamp::from(SystemTime::UNIX_EPOCH),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::F64(12.5),
+        )
+        .with_attr("domain", "package:0");
+        let mut buf = String::new();
+        encoder.encode(&mut buf, &m, &metric());
+        buf
+    }
+
+    #[test]
+    fn flavors() {
+        assert_eq!(encode(Flavor::Statsd, &[]), "alumet.rapl_consumed_energy:12.5|g");
+        assert_eq!(
+            encode(Flavor::Dogstatsd, &["rapl_consumed_energy"]),
+            "alumet.rapl_consumed_energy:12.5|c|#consumer_kind:local_machine,domain:package_0,host:node-1,resource_id:0,resource_kind:cpu_package"
+        );
+        assert_eq!(
+            encode(Flavor::Telegraf, &[]),
+            "alumet.rapl_consumed_energy,consumer_kind=local_machine,domain=package_0,host=node-1,resource_id=0,resource_kind=cpu_package:12.5|g"
+        );
+    }
+}
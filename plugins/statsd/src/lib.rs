@@ -0,0 +1,124 @@
+mod format;
+mod output;
+
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::format::Encoder;
+pub use crate::format::Flavor;
+use crate::output::StatsdOutput;
+
+pub struct StatsdPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for StatsdPlugin {
+    fn name() -> &'static str {
+        "statsd"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.max_packet_size == 0 {
+            return Err(anyhow!("max_packet_size must be greater than zero"));
+        }
+        Ok(Box::new(StatsdPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let host = if config.host_tag {
+            let hostname = match &config.hostname {
+                Some(hostname) => hostname.to_owned(),
+                None => hostname::get()
+                    .context(
+                        "No hostname specified in the config, and unable to retrieve the hostname of the machine.",
+                    )?
+                    .to_string_lossy()
+                    .to_string(),
+            };
+            Some(hostname)
+        } else {
+            None
+        };
+
+        // The address is resolved once: the UDP socket is connected to it.
+        let address = (config.host.as_str(), config.port)
+            .to_socket_addrs()
+            .with_context(|| format!("failed to resolve the address of the StatsD server {}", config.host))?
+            .next()
+            .ok_or_else(|| anyhow!("no address found for the StatsD server {}", config.host))?;
+        let bind_address = if address.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+        let socket = UdpSocket::bind(bind_address).context("failed to create the UDP socket")?;
+        socket.connect(address)?;
+
+        let encoder = Encoder {
+            flavor: config.flavor,
+            prefix: config.prefix.clone(),
+            counters: config.counters.iter().cloned().collect(),
+            host,
+        };
+        let output = StatsdOutput::new(socket, encoder, config.max_packet_size);
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the StatsD server.
+    pub host: String,
+    pub port: u16,
+    /// Variant of the protocol: `"statsd"` (no tags), `"dogstatsd"` or `"telegraf"`.
+    pub flavor: Flavor,
+    /// Prepended to the name of the metrics.
+    pub prefix: String,
+    /// Metrics sent as counters, such as the energy consumed since the last measurement.
+    /// The other metrics are sent as gauges.
+    pub counters: Vec<String>,
+    /// Add the tag `host` to all the measurements?
+    pub host_tag: bool,
+    /// Value of the tag `host`. Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Maximum size of a UDP packet, in bytes.
+    pub max_packet_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: 8125,
+            flavor: Flavor::Dogstatsd,
+            prefix: String::from("alumet."),
+            counters: Vec::new(),
+            host_tag: true,
+            hostname: None,
+            max_packet_size: 1432,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::net::UdpSocket;
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::{
+        Output,
+        elements::{
+            error::WriteError,
+            output::{OutputContext, error::WriteRetry},
+        },
+    },
+};
+use anyhow::Context;
+
+use crate::format::Encoder;
+
+pub struct StatsdOutput {
+    /// UDP socket, connected to the StatsD server.
+    socket: UdpSocket,
+    encoder: Encoder,
+    /// Maximum size of a UDP packet. The lines are grouped in packets of this size.
+    max_packet_size: usize,
+}
+
+impl StatsdOutput {
+    pub fn new(socket: UdpSocket, encoder: Encoder, max_packet_size: usize) -> Self {
+        Self {
+            socket,
+            encoder,
+            max_packet_size,
+        }
+    }
+
+    fn send(&self, packet: &str) -> anyhow::Result<()> {
+        self.socket
+            .send(packet.as_bytes())
+            .context("failed to send the measurements to StatsD")?;
+        Ok(())
+    }
+}
+
+impl Output for StatsdOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut packet = String::with_capacity(self.max_packet_size);
+        let mut line = String::new();
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            line.clear();
+            self.encoder.encode(&mut line, m, metric);
+
+            // Send the packet when the next line does not fit in it.
+            // A line that is bigger than max_packet_size is sent alone.
+            if !packet.is_empty() && packet.len() + 1 + line.len() > self.max_packet_size {
+                self.send(&packet).retry_write()?;
+                packet.clear();
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(&line);
+        }
+        if !packet.is_empty() {
+            self.send(&packet).retry_write()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::{net::UdpSocket, time::Duration};
+
+use alumet::{
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_statsd::{Config, Flavor, StatsdPlugin};
+use pretty_assertions::assert_eq;
+
+/// Writes the sample measurements with the given config, and returns the packets received by the server.
+fn write_sample(server: &UdpSocket, config: Config) -> anyhow::Result<Vec<String>> {
+    let config = Config {
+        host: String::from("127.0.0.1"),
+        port: server.local_addr()?.port(),
+        hostname: Some(String::from("node-1")),
+        ..config
+    };
+    let mut harness = PluginHarness::<StatsdPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    server.set_read_timeout(Some(Duration::from_millis(200)))?;
+    let mut packets = Vec::new();
+    let mut buf = [0; 2048];
+    while let Ok(n) = server.recv(&mut buf) {
+        packets.push(String::from_utf8(buf[..n].to_vec())?);
+    }
+    Ok(packets)
+}
+
+#[test]
+fn dogstatsd() -> anyhow::Result<()> {
+    let server = UdpSocket::bind("127.0.0.1:0")?;
+    let config = Config {
+        counters: vec![String::from("sample_energy")],
+        ..Config::default()
+    };
+    let packets = write_sample(&server, config)?;
+    assert_eq!(packets.len(), 1, "unexpected packets {packets:?}");
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/dogstatsd.txt"),
+        format!("{}\n", packets[0]),
+    );
+    Ok(())
+}
+
+#[test]
+fn small_packets() -> anyhow::Result<()> {
+    let server = UdpSocket::bind("127.0.0.1:0")?;
+    let config = Config {
+        flavor: Flavor::Statsd,
+        prefix: String::new(),
+        max_packet_size: 41,
+        ..Config::default()
+    };
+    let packets = write_sample(&server, config)?;
+    assert_eq!(
+        packets,
+        [
+            "sample_energy:12.5|g\nsample_energy:3.25|g",
+            "sample_count:42|g\nsample_energy:150|g",
+            "sample_count:7|g",
+        ]
+    );
+    Ok(())
+}
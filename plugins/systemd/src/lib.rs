@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+use systemd::Systemd;
+
+mod metrics;
+mod source;
+mod systemd;
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("This plugin only works on Linux.");
+
+pub struct SystemdPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for SystemdPlugin {
+    fn name() -> &'static str {
+        "systemd"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        // the units are read from systemd on its bus
+        Some(vec![Capability::Dbus])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.units.is_empty() {
+            return Err(anyhow!(
+                "No unit pattern configured, please add some to the configuration."
+            ));
+        }
+        Ok(Box::new(SystemdPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        // Connect now to report errors early.
+        let systemd = Systemd::connect(&self.config.bus)?;
+        let units = systemd
+            .units(&self.config.units)
+            .context("failed to list the units, is systemd running?")?;
+        log::info!("Found {} active units that match {:?}.", units.len(), self.config.units);
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::SystemdSource::new(systemd, self.config.units.clone(), metrics);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("units", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+
+    /// The bus to connect to: `system`, `session` or the address of a bus, such as `unix:path=/run/dbus/system_bus_socket`.
+    pub bus: String,
+
+    /// Patterns of the units to measure, such as `*.service` or `postgresql*`.
+    pub units: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            flush_interval: Duration::from_secs(15),
+            bus: String::from("system"),
+            units: vec![String::from("*.service")],
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use alumet::{
+    metrics::TypedMetricId,
+    plugin::AlumetPluginStart,
+    units::{PrefixedUnit, Unit},
+};
+
+pub struct Metrics {
+    /// CPU time since the previous measurement.
+    pub cpu_time_delta: TypedMetricId<u64>,
+    /// CPU time divided by the time elapsed since the previous measurement.
+    pub cpu_percent: TypedMetricId<f64>,
+    /// Memory currently used.
+    pub memory_usage: TypedMetricId<u64>,
+    /// Bytes read or written since the previous measurement.
+    pub io_bytes_delta: TypedMetricId<u64>,
+    /// Read or write operations since the previous measurement.
+    pub io_operations_delta: TypedMetricId<u64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            cpu_time_delta: alumet.create_metric(
+                "systemd_cpu_time_delta",
+                PrefixedUnit::nano(Unit::Second),
+                "CPU time used by the unit since the previous measurement",
+            )?,
+            cpu_percent: alumet.create_metric(
+                "systemd_cpu_percent",
+                Unit::Percent,
+                "Part of the CPU used by the unit since the previous measurement (1 core fully used = 100%)",
+            )?,
+            memory_usage: alumet.create_metric(
+                "systemd_memory_usage",
+                Unit::Byte,
+                "Memory used by the unit, including the page cache",
+            )?,
+            io_bytes_delta: alumet.create_metric(
+                "systemd_io_bytes_delta",
+                Unit::Byte,
+                "Bytes read or written by the unit since the previous measurement",
+            )?,
+            io_operations_delta: alumet.create_metric(
+                "systemd_io_operations_delta",
+                Unit::Unity,
+                "Read or write operations of the unit since the previous measurement",
+            )?,
+        })
+    }
+}
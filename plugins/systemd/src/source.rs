@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::{
+    metrics::Metrics,
+    systemd::{Accounting, Systemd, is_unknown_unit},
+};
+
+/// Measurement source that gets the resource accounting of the active units from systemd.
+pub struct SystemdSource {
+    systemd: Systemd,
+    /// Patterns of the units to measure, such as `*.service`.
+    patterns: Vec<String>,
+    metrics: Metrics,
+    /// Accounting of the units, at the previous measurement.
+    previous: HashMap<String, (Timestamp, Accounting)>,
+}
+
+impl SystemdSource {
+    pub fn new(systemd: Systemd, patterns: Vec<String>, metrics: Metrics) -> Self {
+        Self {
+            systemd,
+            patterns,
+            metrics,
+            previous: HashMap::new(),
+        }
+    }
+}
+
+impl Source for SystemdSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // systemd can be reexecuted, the next poll may work.
+        let units = self
+            .systemd
+            .units(&self.patterns)
+            .context("could not list the units")
+            .map_err(PollError::CanRetry)?;
+
+        let mut current = HashMap::with_capacity(units.len());
+        for unit in units {
+            let accounting = match self.systemd.accounting(&unit) {
+                Ok(Some(accounting)) => accounting,
+                Ok(None) => {
+                    log::debug!("Unit {} has no control group.", unit.name);
+                    continue;
+                }
+                Err(e) if is_unknown_unit(&e) => {
+                    log::debug!("Unit {} has stopped before its measurement.", unit.name);
+                    continue;
+                }
+                Err(e) => {
+                    return Err(PollError::CanRetry(
+                        anyhow::Error::from(e).context(format!("could not get the accounting of unit {}", unit.name)),
+                    ));
+                }
+            };
+
+            let consumer = ResourceConsumer::ControlGroup {
+                path: accounting.control_group.clone().into(),
+            };
+            let point = |metric, value| {
+                MeasurementPoint::new(t, metric, Resource::LocalMachine, consumer.clone(), value)
+                    .with_attr("unit", unit.name.clone())
+            };
+
+            if let Some(memory) = accounting.memory_current {
+                measurements.push(point(self.metrics.memory_usage, memory));
+            }
+
+            if let Some((timestamp, previous)) = self.previous.get(&unit.name) {
+                if let (Some(cpu_usage), Some(prev_cpu_usage)) = (accounting.cpu_usage, previous.cpu_usage) {
+                    if let Some(cpu_time) = cpu_usage.checked_sub(prev_cpu_usage) {
+                        measurements.push(point(self.metrics.cpu_time_delta, cpu_time));
+                        let elapsed = t.duration_since(*timestamp)?.as_nanos() as f64;
+                        if elapsed > 0.0 {
+                            let percent = cpu_time as f64 / elapsed * 100.0;
+                            measurements.push(
+                                MeasurementPoint::new(
+                                    t,
+                                    self.metrics.cpu_percent,
+                                    Resource::LocalMachine,
+                                    consumer.clone(),
+                                    percent,
+                                )
+                                .with_attr("unit", unit.name.clone()),
+                            );
+                        }
+                    } else {
+                        log::debug!("The CPU usage of unit {} has been reset.", unit.name);
+                    }
+                }
+
+                let io_counters = [
+                    (
+                        self.metrics.io_bytes_delta,
+                        "read",
+                        accounting.io_read_bytes,
+                        previous.io_read_bytes,
+                    ),
+                    (
+                        self.metrics.io_bytes_delta,
+                        "write",
+                        accounting.io_write_bytes,
+                        previous.io_write_bytes,
+                    ),
+                    (
+                        self.metrics.io_operations_delta,
+                        "read",
+                        accounting.io_read_operations,
+                        previous.io_read_operations,
+                    ),
+                    (
+                        self.metrics.io_operations_delta,
+                        "write",
+                        accounting.io_write_operations,
+                        previous.io_write_operations,
+                    ),
+                ];
+                for (metric, kind, value, prev) in io_counters {
+                    if let Some(delta) = value.zip(prev).and_then(|(value, prev)| value.checked_sub(prev)) {
+                        measurements.push(point(metric, delta).with_attr("kind", kind));
+                    }
+                }
+            }
+            current.insert(unit.name, (t, accounting));
+        }
+        // forget the units that have stopped
+        self.previous = current;
+        Ok(())
+    }
+}
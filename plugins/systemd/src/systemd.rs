@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use zbus::{
+    blocking::{Connection, connection::Builder},
+    zvariant::{OwnedObjectPath, OwnedValue},
+};
+
+const DESTINATION: &str = "org.freedesktop.systemd1";
+const MANAGER_PATH: &str = "/org/freedesktop/systemd1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// Value of the accounting properties when the accounting is disabled for the unit.
+const NOT_AVAILABLE: u64 = u64::MAX;
+
+/// A unit, as returned by `ListUnitsByPatterns`: name, description, load state, active state, sub state,
+/// followed unit, object path, queued job id, job type and job path.
+type ListedUnit = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    OwnedObjectPath,
+    u32,
+    String,
+    OwnedObjectPath,
+);
+
+/// Client of the systemd manager, on D-Bus.
+pub struct Systemd {
+    connection: Connection,
+}
+
+/// A unit that runs in its own control group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    pub path: OwnedObjectPath,
+    /// D-Bus interface that holds the accounting properties, which depends on the type of the unit.
+    interface: &'static str,
+}
+
+/// The resources used by a unit, as counted by systemd.
+///
+/// The properties are `None` when the corresponding accounting (`CPUAccounting`, `MemoryAccounting`
+/// or `IOAccounting`) is disabled for the unit.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Accounting {
+    /// Path of the unit's cgroup, such as `/system.slice/nginx.service`.
+    pub control_group: String,
+    /// CPU time used by the unit since it has started, in nanoseconds.
+    pub cpu_usage: Option<u64>,
+    /// Memory currently used by the unit, in bytes.
+    pub memory_current: Option<u64>,
+    pub io_read_bytes: Option<u64>,
+    pub io_write_bytes: Option<u64>,
+    pub io_read_operations: Option<u64>,
+    pub io_write_operations: Option<u64>,
+}
+
+impl Systemd {
+    /// Connects to systemd on the given bus: `system`, `session` or the address of a bus.
+    pub fn connect(bus: &str) -> anyhow::Result<Self> {
+        let builder = match bus {
+            "system" => Builder::system(),
+            "session" => Builder::session(),
+            address => Builder::address(address),
+        }
+        .with_context(|| format!("invalid D-Bus bus {bus:?}"))?;
+        let connection = builder
+            .build()
+            .with_context(|| format!("failed to connect to the {bus} bus"))?;
+        Ok(Self { connection })
+    }
+
+    /// Lists the active units whose name matches one of the patterns, such as `*.service`.
+    ///
+    /// Only the units that have a control group are returned: services, scopes, slices, sockets, mounts and swaps.
+    pub fn units(&self, patterns: &[String]) -> zbus::Result<Vec<Unit>> {
+        let reply = self.connection.call_method(
+            Some(DESTINATION),
+            MANAGER_PATH,
+            Some(MANAGER_INTERFACE),
+            "ListUnitsByPatterns",
+            &(vec!["active"], patterns),
+        )?;
+        let units: Vec<ListedUnit> = reply.body().deserialize()?;
+        Ok(units
+            .into_iter()
+            .filter_map(|(name, _, _, _, _, _, path, _, _, _)| {
+                let interface = accounting_interface(&name)?;
+                Some(Unit { name, path, interface })
+            })
+            .collect())
+    }
+
+    /// Reads the accounting properties of a unit.
+    ///
+    /// Returns `None` if the unit has no control group, for instance because it has stopped.
+    pub fn accounting(&self, unit: &Unit) -> zbus::Result<Option<Accounting>> {
+        let reply = self.connection.call_method(
+            Some(DESTINATION),
+            &unit.path,
+            Some(PROPERTIES_INTERFACE),
+            "GetAll",
+            &(unit.interface,),
+        )?;
+        let properties: HashMap<String, OwnedValue> = reply.body().deserialize()?;
+        Ok(parse_accounting(&properties))
+    }
+}
+
+/// Returns true if the error means that the unit does not exist anymore.
+pub fn is_unknown_unit(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::MethodError(name, _, _) => matches!(
+            name.as_str(),
+            "org.freedesktop.systemd1.NoSuchUnit" | "org.freedesktop.DBus.Error.UnknownObject"
+        ),
+        _ => false,
+    }
+}
+
+/// Returns the D-Bus interface that holds the accounting properties of the unit, if the unit has a control group.
+fn accounting_interface(unit_name: &str) -> Option<&'static str> {
+    let (_, unit_type) = unit_name.rsplit_once('.')?;
+    match unit_type {
+        "service" => Some("org.freedesktop.systemd1.Service"),
+        "scope" => Some("org.freedesktop.systemd1.Scope"),
+        "slice" => Some("org.freedesktop.systemd1.Slice"),
+        "socket" => Some("org.freedesktop.systemd1.Socket"),
+        "mount" => Some("org.freedesktop.systemd1.Mount"),
+        "swap" => Some("org.freedesktop.systemd1.Swap"),
+        _ => None,
+    }
+}
+
+fn parse_accounting(properties: &HashMap<String, OwnedValue>) -> Option<Accounting> {
+    let control_group = properties
+        .get("ControlGroup")
+        .and_then(|v| String::try_from(v.clone()).ok())
+        .filter(|cgroup| !cgroup.is_empty())?;
+    let counter = |name: &str| {
+        properties
+            .get(name)
+            .and_then(|v| u64::try_from(v).ok())
+            .filter(|v| *v != NOT_AVAILABLE)
+    };
+    Some(Accounting {
+        control_group,
+        cpu_usage: counter("CPUUsageNSec"),
+        memory_current: counter("MemoryCurrent"),
+        io_read_bytes: counter("IOReadBytes"),
+        io_write_bytes: counter("IOWriteBytes"),
+        io_read_operations: counter("IOReadOperations"),
+        io_write_operations: counter("IOWriteOperations"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use zbus::zvariant::Value;
+
+    use super::*;
+
+    fn properties(values: Vec<(&str, Value<'static>)>) -> HashMap<String, OwnedValue> {
+        values
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), OwnedValue::try_from(v).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn interfaces() {
+        assert_eq!(
+            accounting_interface("nginx.service"),
+            Some("org.freedesktop.systemd1.Service")
+        );
+        assert_eq!(
+            accounting_interface("system.slice"),
+            Some("org.freedesktop.systemd1.Slice")
+        );
+        assert_eq!(
+            accounting_interface("session-2.scope"),
+            Some("org.freedesktop.systemd1.Scope")
+        );
+        assert_eq!(accounting_interface("multi-user.target"), None);
+        assert_eq!(accounting_interface("logrotate.timer"), None);
+        assert_eq!(accounting_interface("noextension"), None);
+    }
+
+    #[test]
+    fn accounting_properties() {
+        let props = properties(vec![
+            ("ControlGroup", Value::from("/system.slice/postgresql.service")),
+            ("CPUUsageNSec", Value::from(1_500_000_000_u64)),
+            ("MemoryCurrent", Value::from(u64::MAX)),
+            ("IOReadBytes", Value::from(4096_u64)),
+            ("IOWriteBytes", Value::from(8192_u64)),
+            ("MainPID", Value::from(1234_u32)),
+        ]);
+        assert_eq!(
+            parse_accounting(&props),
+            Some(Accounting {
+                control_group: String::from("/system.slice/postgresql.service"),
+                cpu_usage: Some(1_500_000_000),
+                memory_current: None,
+                io_read_bytes: Some(4096),
+                io_write_bytes: Some(8192),
+                io_read_operations: None,
+                io_write_operations: None,
+            })
+        );
+
+        // a unit that has stopped has no cgroup
+        let props = properties(vec![
+            ("ControlGroup", Value::from("")),
+            ("CPUUsageNSec", Value::from(u64::MAX)),
+        ]);
+        assert_eq!(parse_accounting(&props), None);
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use alumet::{
+    measurement::WrappedMeasurementValue, pipeline::elements::error::PollError, plugin::rust::serialize_config,
+    resources::ResourceConsumer, test::PluginHarness,
+};
+use plugin_systemd::{Config, SystemdPlugin};
+use pretty_assertions::assert_eq;
+use zbus::{blocking::Connection, interface, zvariant::OwnedObjectPath};
+
+const NGINX: &str = "/org/freedesktop/systemd1/unit/nginx_2eservice";
+const POSTGRESQL: &str = "/org/freedesktop/systemd1/unit/postgresql_2eservice";
+
+/// A private session bus, stopped on drop.
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    /// Starts a bus, or returns `None` if `dbus-daemon` is not installed.
+    fn start() -> anyhow::Result<Option<Self>> {
+        let daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut daemon = match daemon {
+            Ok(daemon) => daemon,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("dbus-daemon is not installed, the test is skipped");
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address)?;
+        Ok(Some(Self {
+            daemon,
+            address: address.trim().to_owned(),
+        }))
+    }
+
+    fn config(&self) -> Config {
+        Config {
+            bus: self.address.clone(),
+            ..Config::default()
+        }
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// The manager of a fake systemd, which lists the units without applying the patterns.
+struct FakeManager {
+    units: Vec<(&'static str, &'static str)>,
+}
+
+#[interface(name = "org.freedesktop.systemd1.Manager")]
+impl FakeManager {
+    #[allow(clippy::type_complexity)]
+    fn list_units_by_patterns(
+        &self,
+        _states: Vec<String>,
+        _patterns: Vec<String>,
+    ) -> Vec<(
+        String,
+        String,
+        String,
+        String,
+        String,
+        String,
+        OwnedObjectPath,
+        u32,
+        String,
+        OwnedObjectPath,
+    )> {
+        self.units
+            .iter()
+            .map(|(name, path)| {
+                (
+                    name.to_string(),
+                    String::new(),
+                    String::from("loaded"),
+                    String::from("active"),
+                    String::from("running"),
+                    String::new(),
+                    OwnedObjectPath::try_from(*path).unwrap(),
+                    0,
+                    String::new(),
+                    OwnedObjectPath::try_from("/").unwrap(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The accounting properties of a fake service. `u64::MAX` means that the accounting is disabled.
+#[derive(Clone)]
+struct FakeService {
+    control_group: String,
+    cpu_usage: u64,
+    memory_current: u64,
+    io_read_bytes: u64,
+    io_write_bytes: u64,
+}
+
+#[interface(name = "org.freedesktop.systemd1.Service")]
+impl FakeService {
+    #[zbus(property, name = "ControlGroup")]
+    fn control_group(&self) -> String {
+        self.control_group.clone()
+    }
+
+    #[zbus(property, name = "CPUUsageNSec")]
+    fn cpu_usage_nsec(&self) -> u64 {
+        self.cpu_usage
+    }
+
+    #[zbus(property, name = "MemoryCurrent")]
+    fn memory_current(&self) -> u64 {
+        self.memory_current
+    }
+
+    #[zbus(property, name = "IOReadBytes")]
+    fn io_read_bytes(&self) -> u64 {
+        self.io_read_bytes
+    }
+
+    #[zbus(property, name = "IOWriteBytes")]
+    fn io_write_bytes(&self) -> u64 {
+        self.io_write_bytes
+    }
+
+    #[zbus(property, name = "IOReadOperations")]
+    fn io_read_operations(&self) -> u64 {
+        u64::MAX
+    }
+
+    #[zbus(property, name = "IOWriteOperations")]
+    fn io_write_operations(&self) -> u64 {
+        u64::MAX
+    }
+}
+
+/// Serves a fake systemd with two services, a target and a unit that has disappeared.
+fn fake_systemd(bus: &Bus) -> anyhow::Result<Connection> {
+    let manager = FakeManager {
+        units: vec![
+            ("nginx.service", NGINX),
+            ("postgresql.service", POSTGRESQL),
+            (
+                "multi-user.target",
+                "/org/freedesktop/systemd1/unit/multi_2duser_2etarget",
+            ),
+            ("gone.service", "/org/freedesktop/systemd1/unit/gone_2eservice"),
+        ],
+    };
+    let nginx = FakeService {
+        control_group: String::from("/system.slice/nginx.service"),
+        cpu_usage: 2_000_000_000,
+        memory_current: 50_000_000,
+        io_read_bytes: 1000,
+        io_write_bytes: 0,
+    };
+    // MemoryAccounting and IOAccounting are disabled for this one
+    let postgresql = FakeService {
+        control_group: String::from("/system.slice/postgresql.service"),
+        cpu_usage: 10_000_000_000,
+        memory_current: u64::MAX,
+        io_read_bytes: u64::MAX,
+        io_write_bytes: u64::MAX,
+    };
+    let connection = zbus::blocking::connection::Builder::address(bus.address.as_str())?
+        .name("org.freedesktop.systemd1")?
+        .serve_at("/org/freedesktop/systemd1", manager)?
+        .serve_at(NGINX, nginx)?
+        .serve_at(POSTGRESQL, postgresql)?
+        .build()?;
+    Ok(connection)
+}
+
+fn update(connection: &Connection, path: &str, f: impl FnOnce(&mut FakeService)) -> anyhow::Result<()> {
+    let iface = connection.object_server().interface::<_, FakeService>(path)?;
+    f(&mut iface.get_mut());
+    Ok(())
+}
+
+#[test]
+fn unit_accounting() -> anyhow::Result<()> {
+    let Some(bus) = Bus::start()? else { return Ok(()) };
+    let systemd = fake_systemd(&bus)?;
+    let mut harness = PluginHarness::<SystemdPlugin>::start(serialize_config(bus.config())?)?;
+    let mut source = harness.source("units")?;
+
+    // first measurement: no delta yet
+    let measurements = source.poll().unwrap();
+    assert_eq!(measurements.len(), 1);
+    let point = harness.points(&measurements, "systemd_memory_usage")[0];
+    assert_eq!(point.value, WrappedMeasurementValue::U64(50_000_000));
+    assert_eq!(
+        point.consumer,
+        ResourceConsumer::ControlGroup {
+            path: "/system.slice/nginx.service".into()
+        }
+    );
+    let attrs: Vec<_> = point.attributes().map(|(k, v)| (k, v.to_string())).collect();
+    assert_eq!(attrs, vec![("unit", String::from("nginx.service"))]);
+
+    update(&systemd, NGINX, |s| {
+        s.cpu_usage += 1_000_000_000;
+        s.io_read_bytes += 4096;
+        s.io_write_bytes += 512;
+    })?;
+    update(&systemd, POSTGRESQL, |s| s.cpu_usage += 2_500_000_000)?;
+    source.advance(Duration::from_secs(5));
+
+    let measurements = source.poll().unwrap();
+    let cpu_time: Vec<_> = harness
+        .points(&measurements, "systemd_cpu_time_delta")
+        .into_iter()
+        .map(|p| (p.consumer.id_display().to_string(), p.value.clone()))
+        .collect();
+    assert_eq!(
+        cpu_time,
+        vec![
+            (
+                String::from("/system.slice/nginx.service"),
+                WrappedMeasurementValue::U64(1_000_000_000)
+            ),
+            (
+                String::from("/system.slice/postgresql.service"),
+                WrappedMeasurementValue::U64(2_500_000_000)
+            ),
+        ]
+    );
+    assert_eq!(
+        harness.values(&measurements, "systemd_cpu_percent"),
+        vec![WrappedMeasurementValue::F64(20.0), WrappedMeasurementValue::F64(50.0)]
+    );
+    let io: Vec<_> = harness
+        .points(&measurements, "systemd_io_bytes_delta")
+        .into_iter()
+        .map(|p| (p.attributes().last().unwrap().1.to_string(), p.value.clone()))
+        .collect();
+    assert_eq!(
+        io,
+        vec![
+            (String::from("read"), WrappedMeasurementValue::U64(4096)),
+            (String::from("write"), WrappedMeasurementValue::U64(512)),
+        ]
+    );
+    assert!(harness.values(&measurements, "systemd_io_operations_delta").is_empty());
+
+    // systemd is stopped
+    drop(systemd);
+    assert!(matches!(source.poll(), Err(PollError::CanRetry(_))));
+
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn no_systemd() -> anyhow::Result<()> {
+    let Some(bus) = Bus::start()? else { return Ok(()) };
+    let err = PluginHarness::<SystemdPlugin>::start(serialize_config(bus.config())?)
+        .err()
+        .expect("the plugin should not start without systemd");
+    assert!(format!("{err:#}").contains("failed to list the units"), "{err:#}");
+    Ok(())
+}
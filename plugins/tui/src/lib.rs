@@ -0,0 +1,81 @@
+mod output;
+mod render;
+mod state;
+
+use std::time::Duration;
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    capability::Capability,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use anyhow::anyhow;
+use output::TuiOutput;
+use serde::{Deserialize, Serialize};
+use state::Dashboard;
+
+pub struct TuiPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for TuiPlugin {
+    fn name() -> &'static str {
+        "tui"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(Vec::new())
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        if config.history_len == 0 {
+            return Err(anyhow!("history_len must be greater than zero"));
+        }
+        Ok(Box::new(TuiPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let dashboard = Dashboard::new(self.config.metrics.clone(), self.config.history_len);
+        let output = TuiOutput::new(dashboard, self.config.refresh_interval, self.config.print_summary)?;
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Names of the metrics to display. If empty, all the metrics are displayed.
+    pub metrics: Vec<String>,
+    /// Minimum delay between two refreshes of the dashboard.
+    #[serde(with = "humantime_serde")]
+    pub refresh_interval: Duration,
+    /// Number of values in the history of each line.
+    pub history_len: usize,
+    /// Print the last state of the dashboard when Alumet stops?
+    pub print_summary: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            metrics: Vec::new(),
+            refresh_interval: Duration::from_millis(500),
+            history_len: 60,
+            print_summary: true,
+        }
+    }
+}
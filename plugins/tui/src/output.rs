@@ -0,0 +1,91 @@
+use std::{
+    io::{self, IsTerminal, Stdout},
+    time::{Duration, Instant},
+};
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::{
+        Output,
+        elements::{error::WriteError, output::OutputContext},
+    },
+};
+use anyhow::Context;
+use ratatui::{
+    Terminal,
+    backend::CrosstermBackend,
+    crossterm::{
+        execute,
+        terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+    },
+};
+
+use crate::{render, state::Dashboard};
+
+/// Draws the dashboard in the terminal.
+pub struct TuiOutput {
+    /// `None` if the standard output is not a terminal.
+    terminal: Option<Terminal<CrosstermBackend<Stdout>>>,
+    dashboard: Dashboard,
+    refresh_interval: Duration,
+    last_draw: Option<Instant>,
+    print_summary: bool,
+}
+
+impl TuiOutput {
+    pub fn new(dashboard: Dashboard, refresh_interval: Duration, print_summary: bool) -> anyhow::Result<Self> {
+        let terminal = if io::stdout().is_terminal() {
+            // The raw mode is not enabled: Ctrl+C must still stop Alumet.
+            execute!(io::stdout(), EnterAlternateScreen).context("failed to switch to the alternate screen")?;
+            let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+            terminal.hide_cursor()?;
+            terminal.clear()?;
+            Some(terminal)
+        } else {
+            log::warn!("The standard output is not a terminal, the dashboard will not be displayed.");
+            None
+        };
+        Ok(Self {
+            terminal,
+            dashboard,
+            refresh_interval,
+            last_draw: None,
+            print_summary,
+        })
+    }
+}
+
+impl Output for TuiOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        self.dashboard.update(measurements, ctx);
+
+        // Limit the refresh rate, drawing is much slower than updating the state.
+        let Some(terminal) = &mut self.terminal else {
+            return Ok(());
+        };
+        if self.last_draw.is_some_and(|t| t.elapsed() < self.refresh_interval) {
+            return Ok(());
+        }
+        terminal
+            .draw(|frame| render::draw(frame, &self.dashboard))
+            .context("failed to draw the dashboard")?;
+        self.last_draw = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl Drop for TuiOutput {
+    fn drop(&mut self) {
+        if let Some(mut terminal) = self.terminal.take() {
+            let res = terminal
+                .show_cursor()
+                .and_then(|_| execute!(terminal.backend_mut(), LeaveAlternateScreen));
+            if let Err(e) = res {
+                log::error!("Failed to restore the terminal: {e}");
+            }
+        }
+        if self.print_summary && !self.dashboard.series.is_empty() {
+            print!("{}", render::summary(&self.dashboard));
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Drawing of the dashboard.
+
+use std::time::Duration;
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, Cell, Paragraph, Row, Table},
+};
+
+use crate::state::{Dashboard, Series, SeriesKey};
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Titles of the columns, except the history which is only drawn in the terminal.
+const HEADER: [&str; 6] = ["Metric", "Resource", "Consumer", "Last", "Power", "Total"];
+
+/// Draws the dashboard on the whole frame.
+pub fn draw(frame: &mut Frame, dashboard: &Dashboard) {
+    let [title_area, table_area] = Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+
+    let title = Line::from(vec![
+        " Alumet ".bold().reversed(),
+        format!(
+            " {} elapsed, {} measurements",
+            format_elapsed(dashboard.elapsed()),
+            dashboard.measurement_count
+        )
+        .into(),
+    ]);
+    frame.render_widget(Paragraph::new(title), title_area);
+
+    if dashboard.series.is_empty() {
+        let waiting = Paragraph::new("Waiting for measurements...").block(Block::bordered());
+        frame.render_widget(waiting, table_area);
+        return;
+    }
+
+    // The text columns are as wide as their content, the sparklines use the space that is left.
+    let text_width = |f: fn(&SeriesKey) -> &str, title: &str| {
+        let content = dashboard.series.keys().map(|k| f(k).chars().count());
+        Constraint::Length(content.chain([title.len()]).max().unwrap_or_default() as u16)
+    };
+    let widths = [
+        text_width(|k| &k.metric, HEADER[0]),
+        text_width(|k| &k.resource, HEADER[1]),
+        text_width(|k| &k.consumer, HEADER[2]),
+        Constraint::Length(12),
+        Constraint::Length(12),
+        Constraint::Length(12),
+        Constraint::Fill(1),
+    ];
+    let rows = dashboard.series.iter().map(|(key, series)| {
+        let [metric, resource, consumer, last, power, total] = cells(key, series);
+        Row::new([
+            Cell::from(metric),
+            Cell::from(resource),
+            Cell::from(consumer),
+            Cell::from(Line::from(last).right_aligned()),
+            Cell::from(Line::from(power).right_aligned()),
+            Cell::from(Line::from(total).right_aligned()),
+            Cell::from(sparkline(series.history.iter())).cyan(),
+        ])
+    });
+    let table = Table::new(rows, widths)
+        .header(Row::new(HEADER.into_iter().chain(["History"])).style(Style::new().add_modifier(Modifier::BOLD)))
+        .column_spacing(2)
+        .block(Block::bordered());
+    frame.render_widget(table, table_area);
+}
+
+/// Returns the dashboard as plain text, to print it when Alumet stops.
+pub fn summary(dashboard: &Dashboard) -> String {
+    let mut lines = vec![HEADER.map(String::from)];
+    lines.extend(dashboard.series.iter().map(|(key, series)| cells(key, series)));
+
+    let mut widths = [0; 6];
+    for line in &lines {
+        for (w, cell) in widths.iter_mut().zip(line) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let mut res = format!(
+        "Alumet: {} elapsed, {} measurements\n",
+        format_elapsed(dashboard.elapsed()),
+        dashboard.measurement_count
+    );
+    for line in lines {
+        let mut text = String::new();
+        for (i, (cell, w)) in line.iter().zip(widths).enumerate() {
+            if i < 3 {
+                text.push_str(&format!("{cell:<w$}  "));
+            } else {
+                text.push_str(&format!("{cell:>w$}  "));
+            }
+        }
+        res.push_str(text.trim_end());
+        res.push('\n');
+    }
+    res
+}
+
+/// Returns the text of the columns of a series, except the history.
+fn cells(key: &SeriesKey, series: &Series) -> [String; 6] {
+    [
+        key.metric.clone(),
+        key.resource.clone(),
+        key.consumer.clone(),
+        format_value(series.last_value, &series.unit),
+        series
+            .power
+            .map(|p| format_value(p, &series.power_unit()))
+            .unwrap_or_default(),
+        format_value(series.total, &series.unit),
+    ]
+}
+
+fn format_value(value: f64, unit: &str) -> String {
+    let value = if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{value}")
+    } else if value.abs() >= 100.0 {
+        format!("{value:.1}")
+    } else {
+        format!("{value:.3}")
+    };
+    if unit.is_empty() {
+        value
+    } else {
+        format!("{value} {unit}")
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> humantime::FormattedDuration {
+    humantime::format_duration(Duration::from_secs(elapsed.as_secs()))
+}
+
+/// Returns a line of block characters whose height is proportional to the values.
+fn sparkline<'a>(values: impl Iterator<Item = &'a f64> + Clone) -> String {
+    let (min, max) = values
+        .clone()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(*v), max.max(*v))
+        });
+    values
+        .map(|v| {
+            let level = if max > min {
+                ((v - min) / (max - min) * (SPARK_CHARS.len() - 1) as f64).round() as usize
+            } else {
+                0
+            };
+            SPARK_CHARS[level]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use ratatui::{Terminal, backend::TestBackend};
+
+    use super::{draw, format_value, sparkline, summary};
+    use crate::state::tests::sample_dashboard;
+
+    #[test]
+    fn sparklines() {
+        assert_eq!(sparkline([0.0, 1.0, 7.0, 3.5].iter()), "▁▂█▅");
+        assert_eq!(sparkline([2.0, 2.0].iter()), "▁▁");
+        assert_eq!(sparkline([].iter()), "");
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(format_value(42.0, "W"), "42 W");
+        assert_eq!(format_value(12.3456, "J"), "12.346 J");
+        assert_eq!(format_value(1234.56, ""), "1234.6");
+    }
+
+    #[test]
+    fn dashboard_table() {
+        let dashboard = sample_dashboard();
+        let mut terminal = Terminal::new(TestBackend::new(120, 6)).unwrap();
+        terminal.draw(|frame| draw(frame, &dashboard)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let lines: Vec<String> = (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect();
+        assert!(
+            lines[0].starts_with(" Alumet  0s elapsed, 4 measurements"),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[2].contains("Metric") && lines[2].contains("History"),
+            "{}",
+            lines[2]
+        );
+        let row = lines[3].trim_end_matches([' ', '│']);
+        assert!(row.contains("energy") && row.contains("1000 mW"), "{row}");
+        assert!(row.ends_with("▅█▁"), "{row}");
+    }
+
+    #[test]
+    fn summary_text() {
+        let dashboard = sample_dashboard();
+        let expected = "\
+Alumet: 0s elapsed, 4 measurements
+Metric  Resource       Consumer          Last    Power    Total
+energy  cpu_package/0  local_machine  1000 mJ  1000 mW  6500 mJ
+";
+        assert_eq!(summary(&dashboard), expected);
+    }
+}
@@ -0,0 +1,218 @@
+//! Data displayed by the dashboard.
+
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    time::Instant,
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementValue},
+    metrics::Metric,
+    pipeline::elements::output::OutputContext,
+    units::Unit,
+};
+
+/// Identifies a line of the dashboard: the metric, the resource and the consumer.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SeriesKey {
+    pub metric: String,
+    pub resource: String,
+    pub consumer: String,
+}
+
+/// The last values of a metric for a resource and a consumer.
+#[derive(Debug)]
+pub struct Series {
+    /// Display name of the unit of the metric.
+    pub unit: String,
+    is_energy: bool,
+    pub last_value: f64,
+    last_timestamp: Timestamp,
+    /// Sum of all the values, which is the total energy for an energy metric.
+    pub total: f64,
+    /// If the metric is an energy, the power computed from the last two measurements.
+    /// The unit has the same prefix as the energy, e.g. mW for mJ.
+    pub power: Option<f64>,
+    /// The last values, or the last powers for an energy metric, from the oldest to the newest.
+    pub history: VecDeque<f64>,
+}
+
+impl Series {
+    /// Returns the display name of the unit of the power.
+    pub fn power_unit(&self) -> String {
+        format!("{}W", self.unit.trim_end_matches('J'))
+    }
+}
+
+/// State of the dashboard, updated by the output.
+pub struct Dashboard {
+    /// Metrics to display, all metrics if empty.
+    metrics: HashSet<String>,
+    history_len: usize,
+    started_at: Instant,
+    /// Number of measurements received, including the ones that are not displayed.
+    pub measurement_count: u64,
+    pub series: BTreeMap<SeriesKey, Series>,
+}
+
+impl Dashboard {
+    pub fn new(metrics: Vec<String>, history_len: usize) -> Self {
+        Self {
+            metrics: metrics.into_iter().collect(),
+            history_len,
+            started_at: Instant::now(),
+            measurement_count: 0,
+            series: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the time elapsed since the creation of the dashboard.
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn update(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) {
+        for m in measurements.iter() {
+            self.measurement_count += 1;
+            let Some(metric) = ctx.metrics.by_id(&m.metric) else {
+                log::warn!("Unknown metric {:?}, the measurement is ignored.", m.metric);
+                continue;
+            };
+            if !self.metrics.is_empty() && !self.metrics.contains(&metric.name) {
+                continue;
+            }
+            self.push(m, metric);
+        }
+    }
+
+    fn push(&mut self, m: &MeasurementPoint, metric: &Metric) {
+        let value = match m.value {
+            WrappedMeasurementValue::F64(v) => v,
+            WrappedMeasurementValue::U64(v) => v as f64,
+        };
+        let key = SeriesKey {
+            metric: metric.name.clone(),
+            resource: format_kind_id(m.resource.kind(), &m.resource.id_display().to_string()),
+            consumer: format_kind_id(m.consumer.kind(), &m.consumer.id_display().to_string()),
+        };
+        let series = self.series.entry(key).or_insert_with(|| Series {
+            unit: if metric.unit.base_unit == Unit::Unity {
+                String::new()
+            } else {
+                metric.unit.display_name()
+            },
+            is_energy: metric.unit.base_unit == Unit::Joule,
+            last_value: value,
+            last_timestamp: m.timestamp,
+            total: 0.0,
+            power: None,
+            history: VecDeque::with_capacity(self.history_len),
+        });
+
+        if series.is_energy {
+            // The first measurement gives no power, because its duration is unknown.
+            let dt = m.timestamp.duration_since(series.last_timestamp).unwrap_or_default();
+            if !dt.is_zero() {
+                let power = value / dt.as_secs_f64();
+                series.power = Some(power);
+                push_bounded(&mut series.history, power, self.history_len);
+            }
+        } else {
+            push_bounded(&mut series.history, value, self.history_len);
+        }
+        series.last_value = value;
+        series.last_timestamp = m.timestamp;
+        series.total += value;
+    }
+}
+
+fn push_bounded(history: &mut VecDeque<f64>, value: f64, max_len: usize) {
+    if history.len() == max_len {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
+
+fn format_kind_id(kind: &str, id: &str) -> String {
+    if id.is_empty() {
+        kind.to_owned()
+    } else {
+        format!("{kind}/{id}")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use alumet::{
+        measurement::{MeasurementPoint, Timestamp, WrappedMeasurementValue},
+        metrics::{Metric, RawMetricId},
+        resources::{Resource, ResourceConsumer},
+        units::{PrefixedUnit, Unit},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{Dashboard, SeriesKey};
+
+    fn point(secs: u64, value: f64) -> MeasurementPoint {
+        MeasurementPoint::new_untyped(
+            Timestamp::from(UNIX_EPOCH + Duration::from_secs(secs)),
+            RawMetricId::from_u64(0),
+            Resource::CpuPackage { id: 0 },
+            ResourceConsumer::LocalMachine,
+            WrappedMeasurementValue::F64(value),
+        )
+    }
+
+    fn metric(unit: PrefixedUnit) -> Metric {
+        Metric {
+            name: String::from("energy"),
+            description: String::new(),
+            value_type: alumet::measurement::WrappedMeasurementType::F64,
+            unit,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Returns a dashboard with one energy series, in mJ.
+    pub(crate) fn sample_dashboard() -> Dashboard {
+        let mut dashboard = Dashboard::new(vec![], 3);
+        let metric = metric(PrefixedUnit::milli(Unit::Joule));
+        for (t, value) in [(10, 500.0), (12, 3000.0), (13, 2000.0), (14, 1000.0)] {
+            dashboard.measurement_count += 1;
+            dashboard.push(&point(t, value), &metric);
+        }
+        dashboard
+    }
+
+    #[test]
+    fn energy_to_power() {
+        let dashboard = sample_dashboard();
+
+        let key = SeriesKey {
+            metric: String::from("energy"),
+            resource: String::from("cpu_package/0"),
+            consumer: String::from("local_machine"),
+        };
+        let series = &dashboard.series[&key];
+        assert_eq!(series.unit, "mJ");
+        assert_eq!(series.power_unit(), "mW");
+        assert_eq!(series.last_value, 1000.0);
+        assert_eq!(series.total, 6500.0);
+        assert_eq!(series.power, Some(1000.0));
+        assert_eq!(series.history, [1500.0, 2000.0, 1000.0]);
+    }
+
+    #[test]
+    fn other_values() {
+        let mut dashboard = Dashboard::new(vec![], 10);
+        let metric = metric(Unit::Percent.into());
+        dashboard.push(&point(10, 5.0), &metric);
+        dashboard.push(&point(11, 7.0), &metric);
+
+        let series = dashboard.series.values().next().unwrap();
+        assert_eq!(series.power, None);
+        assert_eq!(series.history, [5.0, 7.0]);
+    }
+}
@@ -0,0 +1,85 @@
+//! Encoding of the measurements in the JSON line format of VictoriaMetrics.
+//!
+//! Each line contains all the values of a series, which is much more compact than one line per value
+//! when the measurements are frequent.
+//! See <https://docs.victoriametrics.com/#how-to-import-data-in-json-line-format>.
+
+use std::collections::BTreeMap;
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::elements::output::OutputContext,
+};
+use alumet_prometheus::{sanitize_label_name, sanitize_metric_name};
+use anyhow::Context;
+use serde::Serialize;
+
+/// A line of the import format.
+#[derive(Serialize)]
+pub struct Series {
+    pub metric: BTreeMap<String, String>,
+    pub values: Vec<f64>,
+    /// Timestamps in milliseconds since the Unix epoch.
+    pub timestamps: Vec<i64>,
+}
+
+/// Converts the measurements into series.
+pub struct Encoder {
+    /// Labels added to every series.
+    pub extra_labels: BTreeMap<String, String>,
+    pub add_attributes_to_labels: bool,
+}
+
+impl Encoder {
+    /// Groups the measurements by series, sorted by labels, with their values in chronological order.
+    pub fn series(&self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> anyhow::Result<Vec<Series>> {
+        let mut series: BTreeMap<BTreeMap<String, String>, Vec<(i64, f64)>> = BTreeMap::new();
+        for m in measurements {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("unknown metric {:?}", m.metric))?;
+            let value = match m.value {
+                WrappedMeasurementValue::F64(v) => v,
+                WrappedMeasurementValue::U64(v) => v as f64,
+            };
+            // JSON cannot represent NaN and infinite values.
+            if !value.is_finite() {
+                continue;
+            }
+
+            let mut labels = self.extra_labels.clone();
+            if self.add_attributes_to_labels {
+                for (key, value) in m.attributes() {
+                    labels.insert(sanitize_label_name(key), value.to_string());
+                }
+            }
+            labels.insert(String::from("resource_kind"), m.resource.kind().to_owned());
+            labels.insert(String::from("resource_id"), m.resource.id_display().to_string());
+            labels.insert(String::from("resource_consumer_kind"), m.consumer.kind().to_owned());
+            labels.insert(
+                String::from("resource_consumer_id"),
+                m.consumer.id_display().to_string(),
+            );
+            labels.insert(String::from("__name__"), sanitize_metric_name(&metric.name));
+            labels.retain(|_, v| !v.is_empty());
+
+            let (secs, nanos) = m.timestamp.to_unix_timestamp();
+            let timestamp = secs as i64 * 1000 + nanos as i64 / 1_000_000;
+            series.entry(labels).or_default().push((timestamp, value));
+        }
+
+        Ok(series
+            .into_iter()
+            .map(|(metric, mut points)| {
+                points.sort_by_key(|(t, _)| *t);
+                let (timestamps, values) = points.into_iter().unzip();
+                Series {
+                    metric,
+                    values,
+                    timestamps,
+                }
+            })
+            .collect())
+    }
+}
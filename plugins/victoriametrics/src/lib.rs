@@ -0,0 +1,123 @@
+mod import;
+mod output;
+
+use std::{collections::BTreeMap, time::Duration};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::Context;
+use reqwest::{
+    blocking::Client,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::import::Encoder;
+use crate::output::ImportOutput;
+
+pub struct VictoriaMetricsPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for VictoriaMetricsPlugin {
+    fn name() -> &'static str {
+        "victoriametrics"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(config.batch_size > 0, "batch_size must be greater than zero");
+        Ok(Box::new(VictoriaMetricsPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::try_from(name).with_context(|| format!("invalid header name {name}"))?;
+            let value = HeaderValue::try_from(value).with_context(|| format!("invalid value for header {name}"))?;
+            headers.insert(name, value);
+        }
+        let auth = match (&config.login, &config.password) {
+            (Some(user), Some(pass)) => Some((user.to_owned(), pass.to_owned())),
+            _ => None,
+        };
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(config.timeout)
+            .build()
+            .context("failed to create the HTTP client")?;
+        let encoder = Encoder {
+            extra_labels: config.extra_labels.clone(),
+            add_attributes_to_labels: config.add_attributes_to_labels,
+        };
+        let output = ImportOutput::new(
+            client,
+            config.url.clone(),
+            auth,
+            encoder,
+            config.gzip,
+            config.batch_size,
+        );
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// URL of the JSON line import endpoint, ex. `http://localhost:8428/api/v1/import`
+    /// or `http://vminsert:8480/insert/0/prometheus/api/v1/import` for a cluster.
+    pub url: String,
+    /// Login and password for HTTP basic authentication, both optional.
+    pub login: Option<String>,
+    pub password: Option<String>,
+    /// Additional HTTP headers, ex. `Authorization` for a bearer token.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Compress the requests with gzip.
+    pub gzip: bool,
+    /// Maximum number of series per request.
+    pub batch_size: usize,
+    /// Timeout of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Labels added to every series, ex. `instance`.
+    #[serde(default)]
+    pub extra_labels: BTreeMap<String, String>,
+    pub add_attributes_to_labels: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            url: String::from("http://localhost:8428/api/v1/import"),
+            login: None,
+            password: None,
+            headers: BTreeMap::new(),
+            gzip: true,
+            batch_size: 1000,
+            timeout: Duration::from_secs(10),
+            extra_labels: BTreeMap::new(),
+            add_attributes_to_labels: true,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::io::Write;
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::elements::{
+        error::WriteError,
+        output::{OutputContext, error::WriteRetry},
+    },
+};
+use anyhow::{Context, anyhow};
+use flate2::{Compression, write::GzEncoder};
+use reqwest::{StatusCode, blocking::Client};
+
+use crate::import::{Encoder, Series};
+
+pub struct ImportOutput {
+    client: Client,
+    /// URL of the import endpoint, such as `http://localhost:8428/api/v1/import`.
+    url: String,
+    auth: Option<(String, String)>,
+    encoder: Encoder,
+    /// Compress the requests with gzip?
+    gzip: bool,
+    /// Maximum number of series per request.
+    batch_size: usize,
+}
+
+impl ImportOutput {
+    pub fn new(
+        client: Client,
+        url: String,
+        auth: Option<(String, String)>,
+        encoder: Encoder,
+        gzip: bool,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            client,
+            url,
+            auth,
+            encoder,
+            gzip,
+            batch_size,
+        }
+    }
+
+    /// Sends a batch of series, one JSON line per series.
+    fn send(&self, series: &[Series]) -> Result<(), WriteError> {
+        let mut body = Vec::new();
+        for s in series {
+            serde_json::to_writer(&mut body, s).context("failed to serialize the series")?;
+            body.push(b'\n');
+        }
+        let mut request = self.client.post(&self.url).header("Content-Type", "application/json");
+        if self.gzip {
+            let mut encoder = GzEncoder::new(Vec::with_capacity(body.len() / 4), Compression::default());
+            encoder.write_all(&body).context("gzip compression failed")?;
+            body = encoder.finish().context("gzip compression failed")?;
+            request = request.header("Content-Encoding", "gzip");
+        }
+        if let Some((user, pass)) = &self.auth {
+            request = request.basic_auth(user, Some(pass));
+        }
+        let res = request
+            .body(body)
+            .send()
+            .context("failed to send the series to VictoriaMetrics")
+            .retry_write()?;
+
+        let status = res.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = res.text().unwrap_or_default();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            // VictoriaMetrics is busy or unavailable, try again later.
+            return Err(WriteError::CanRetry(anyhow!("VictoriaMetrics error {status}: {body}")));
+        }
+        // Invalid data, invalid credentials... sending the series again would not help.
+        log::error!("VictoriaMetrics rejected the series with status {status}: {body}");
+        Ok(())
+    }
+}
+
+impl alumet::pipeline::Output for ImportOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let series = self.encoder.series(measurements, ctx)?;
+        for batch in series.chunks(self.batch_size) {
+            self.send(batch)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use alumet::{
+    measurement::{MeasurementPoint, Timestamp},
+    pipeline::elements::error::WriteError,
+    resources::{Resource, ResourceConsumer},
+    test::golden::{HttpCapture, SampleMeasurements, assert_golden},
+};
+use flate2::read::GzDecoder;
+use plugin_victoriametrics::{Config, VictoriaMetricsPlugin};
+use pretty_assertions::assert_eq;
+
+fn config(server: &HttpCapture) -> Config {
+    Config {
+        url: format!("{}/api/v1/import", server.url()),
+        ..Config::default()
+    }
+}
+
+fn gunzip(body: &[u8]) -> String {
+    let mut res = String::new();
+    GzDecoder::new(body)
+        .read_to_string(&mut res)
+        .expect("body should be compressed with gzip");
+    res
+}
+
+#[test]
+fn import() -> anyhow::Result<()> {
+    let server = HttpCapture::with_status(204)?;
+
+    let config = Config {
+        extra_labels: BTreeMap::from([(String::from("instance"), String::from("node-1"))]),
+        ..config(&server)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<VictoriaMetricsPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    // a second value of the first series, which is sent on the same line
+    let mut buffer = sample.measurements();
+    let t = Timestamp::from(UNIX_EPOCH + SampleMeasurements::START + Duration::from_secs(2));
+    buffer.push(MeasurementPoint::new(
+        t,
+        sample.energy,
+        Resource::CpuPackage { id: 0 },
+        ResourceConsumer::LocalMachine,
+        13.75,
+    ));
+    output.write(&buffer).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        (requests[0].method.as_str(), requests[0].path.as_str()),
+        ("POST", "/api/v1/import")
+    );
+    assert_eq!(requests[0].header("content-type"), Some("application/json"));
+    assert_eq!(requests[0].header("content-encoding"), Some("gzip"));
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/import.jsonl"),
+        gunzip(&requests[0].body),
+    );
+    Ok(())
+}
+
+#[test]
+fn uncompressed_batches() -> anyhow::Result<()> {
+    let server = HttpCapture::with_status(204)?;
+
+    let config = Config {
+        gzip: false,
+        batch_size: 2,
+        ..config(&server)
+    };
+    let (mut harness, sample) = SampleMeasurements::start::<VictoriaMetricsPlugin>(config)?;
+    let mut output = harness.output("out")?;
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+
+    // 5 series, at most 2 per request
+    let requests = server.requests();
+    assert!(requests.iter().all(|r| r.header("content-encoding").is_none()));
+    let lines: Vec<usize> = requests.iter().map(|r| r.body_str().lines().count()).collect();
+    assert_eq!(lines, vec![2, 2, 1]);
+    Ok(())
+}
+
+#[test]
+fn server_error() -> anyhow::Result<()> {
+    let server = HttpCapture::with_status(503)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<VictoriaMetricsPlugin>(config(&server))?;
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn rejected() -> anyhow::Result<()> {
+    let server = HttpCapture::with_status(400)?;
+
+    let (mut harness, sample) = SampleMeasurements::start::<VictoriaMetricsPlugin>(config(&server))?;
+    let mut output = harness.output("out")?;
+    // invalid data are dropped instead of being sent again
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()?;
+    assert_eq!(server.requests().len(), 1);
+    Ok(())
+}
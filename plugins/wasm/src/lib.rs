@@ -0,0 +1,122 @@
+//! Runs transforms and outputs compiled to WebAssembly, in a sandbox.
+//!
+//! See the README for the interface that the WASM modules must implement.
+
+mod output;
+mod points;
+mod runtime;
+mod transform;
+mod wasi;
+
+use std::path::PathBuf;
+
+use alumet::plugin::{
+    AlumetPluginStart, ConfigTable,
+    rust::{AlumetPlugin, deserialize_config, serialize_config},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    output::WasmOutput,
+    runtime::{Guest, Limits},
+    transform::WasmTransform,
+};
+
+pub use points::{Entity, Point};
+
+pub struct WasmPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for WasmPlugin {
+    fn name() -> &'static str {
+        "wasm"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(WasmPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        if self.config.transforms.is_empty() && self.config.outputs.is_empty() {
+            log::warn!("No WASM module configured, the wasm plugin will do nothing.");
+            return Ok(());
+        }
+        let engine = runtime::engine()?;
+        for module in &self.config.transforms {
+            let guest = Guest::load::<i64>(
+                &engine,
+                &module.name,
+                &module.path,
+                module.limits(),
+                transform::ENTRY_POINT,
+            )?;
+            alumet.add_transform(&module.name, Box::new(WasmTransform::new(guest)))?;
+        }
+        for module in &self.config.outputs {
+            let guest = Guest::load::<i32>(
+                &engine,
+                &module.name,
+                &module.path,
+                module.limits(),
+                output::ENTRY_POINT,
+            )?;
+            alumet.add_blocking_output(&module.name, Box::new(WasmOutput::new(guest)))?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Config {
+    /// Transforms implemented by WASM modules.
+    #[serde(default)]
+    pub transforms: Vec<ModuleConfig>,
+    /// Outputs implemented by WASM modules.
+    #[serde(default)]
+    pub outputs: Vec<ModuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ModuleConfig {
+    /// Name of the pipeline element.
+    pub name: String,
+    /// Path to the `.wasm` file.
+    pub path: PathBuf,
+    /// Maximum size of the memory of the module.
+    #[serde(default = "default_max_memory_bytes")]
+    pub max_memory_bytes: usize,
+    /// Maximum number of instructions (approximately) that the module can execute per batch of measurements.
+    #[serde(default = "default_fuel_per_batch")]
+    pub fuel_per_batch: u64,
+}
+
+impl ModuleConfig {
+    fn limits(&self) -> Limits {
+        Limits {
+            max_memory: self.max_memory_bytes,
+            fuel_per_batch: self.fuel_per_batch,
+        }
+    }
+}
+
+fn default_max_memory_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_fuel_per_batch() -> u64 {
+    1_000_000_000
+}
@@ -0,0 +1,44 @@
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::elements::{
+        error::WriteError,
+        output::{Output, OutputContext},
+    },
+};
+use anyhow::anyhow;
+
+use crate::{points, runtime::Guest};
+
+/// Name of the function exported by the output modules.
+pub const ENTRY_POINT: &str = "alumet_write";
+
+/// An output implemented by a WASM module.
+///
+/// The module receives the measurements in JSON and returns 0 on success.
+/// Since the module has no access to the network nor to the filesystem, it can only
+/// use `alumet.log`, for instance to report some statistics.
+pub struct WasmOutput {
+    guest: Guest,
+}
+
+impl WasmOutput {
+    pub fn new(guest: Guest) -> Self {
+        Self { guest }
+    }
+}
+
+impl Output for WasmOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let input = points::encode(measurements, ctx.metrics)?;
+        let res = match self.guest.call::<i32>(ENTRY_POINT, &input) {
+            Ok((0, _)) => Ok(()),
+            Ok((status, _)) => Err(anyhow!("{ENTRY_POINT} returned an error ({status})")),
+            Err(e) => Err(e),
+        };
+        if res.is_err() {
+            self.guest.reset();
+        }
+        // Don't stop the output: the next batch may work.
+        res.map_err(WriteError::CanRetry)
+    }
+}
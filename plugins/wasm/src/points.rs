@@ -0,0 +1,140 @@
+//! Conversion of the measurements to and from the JSON format given to the WASM modules.
+
+use alumet::{
+    measurement::{
+        AttributeValue, MeasurementBuffer, MeasurementPoint, Timestamp, WrappedMeasurementType, WrappedMeasurementValue,
+    },
+    metrics::registry::MetricRegistry,
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A measurement point, as seen by the WASM modules.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Point {
+    /// Name of the metric.
+    pub metric: String,
+    /// Number of nanoseconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Measured value, `null` if it is not a finite number.
+    pub value: Value,
+    pub resource: Entity,
+    pub consumer: Entity,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub attributes: Map<String, Value>,
+}
+
+/// A resource or a resource consumer.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Entity {
+    pub kind: String,
+    /// The id of the entity, empty for `local_machine`.
+    #[serde(default)]
+    pub id: String,
+}
+
+/// Serializes the measurements to a JSON array of [`Point`].
+pub fn encode(measurements: &MeasurementBuffer, metrics: &MetricRegistry) -> anyhow::Result<Vec<u8>> {
+    let points = measurements
+        .iter()
+        .map(|m| to_point(m, metrics))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(serde_json::to_vec(&points)?)
+}
+
+/// Deserializes a JSON array of [`Point`] to measurements.
+///
+/// The metrics must exist in the registry, and the values must match their type.
+pub fn decode(json: &[u8], metrics: &MetricRegistry) -> anyhow::Result<MeasurementBuffer> {
+    let points: Vec<Point> = serde_json::from_slice(json).context("invalid list of measurement points")?;
+    let mut res = MeasurementBuffer::with_capacity(points.len());
+    for p in points {
+        let m = from_point(p, metrics)?;
+        res.push(m);
+    }
+    Ok(res)
+}
+
+fn to_point(m: &MeasurementPoint, metrics: &MetricRegistry) -> anyhow::Result<Point> {
+    let metric = metrics
+        .by_id(&m.metric)
+        .with_context(|| format!("unknown metric {:?}", m.metric))?;
+    let (secs, nanos) = m.timestamp.to_unix_timestamp();
+    let value = match m.value {
+        WrappedMeasurementValue::F64(v) => Value::from(v),
+        WrappedMeasurementValue::U64(v) => Value::from(v),
+    };
+    let attributes = m
+        .attributes()
+        .map(|(k, v)| {
+            let v = match v {
+                AttributeValue::F64(v) => Value::from(*v),
+                AttributeValue::U64(v) => Value::from(*v),
+                AttributeValue::Bool(v) => Value::from(*v),
+                AttributeValue::Str(v) => Value::from(*v),
+                AttributeValue::String(v) => Value::from(v.as_str()),
+                AttributeValue::ListU64(v) => Value::from(v.as_slice()),
+            };
+            (k.to_owned(), v)
+        })
+        .collect();
+    Ok(Point {
+        metric: metric.name.clone(),
+        timestamp: secs * 1_000_000_000 + u64::from(nanos),
+        value,
+        resource: Entity {
+            kind: m.resource.kind().to_owned(),
+            id: m.resource.id_display().to_string(),
+        },
+        consumer: Entity {
+            kind: m.consumer.kind().to_owned(),
+            id: m.consumer.id_display().to_string(),
+        },
+        attributes,
+    })
+}
+
+fn from_point(p: Point, metrics: &MetricRegistry) -> anyhow::Result<MeasurementPoint> {
+    let (metric_id, metric) = metrics
+        .by_name(&p.metric)
+        .with_context(|| format!("unknown metric '{}'", p.metric))?;
+    let value = match (&metric.value_type, &p.value) {
+        (WrappedMeasurementType::F64, Value::Null) => WrappedMeasurementValue::F64(f64::NAN),
+        (WrappedMeasurementType::F64, Value::Number(n)) => WrappedMeasurementValue::F64(n.as_f64().unwrap()),
+        (WrappedMeasurementType::U64, Value::Number(n)) if n.is_u64() => {
+            WrappedMeasurementValue::U64(n.as_u64().unwrap())
+        }
+        (t, v) => return Err(anyhow!("invalid value {v} for metric '{}' of type {t}", p.metric)),
+    };
+    let timestamp = Timestamp::from_unix_timestamp(p.timestamp / 1_000_000_000, (p.timestamp % 1_000_000_000) as u32);
+    let resource = Resource::parse(p.resource.kind, p.resource.id)?;
+    let consumer = ResourceConsumer::parse(p.consumer.kind, p.consumer.id)?;
+    let attributes = p
+        .attributes
+        .into_iter()
+        .map(|(k, v)| {
+            let v = attribute_value(v).with_context(|| format!("invalid value for attribute '{k}'"))?;
+            Ok((k, v))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(MeasurementPoint::new_untyped(timestamp, metric_id, resource, consumer, value).with_attr_vec(attributes))
+}
+
+fn attribute_value(v: Value) -> anyhow::Result<AttributeValue> {
+    match v {
+        Value::Bool(b) => Ok(AttributeValue::Bool(b)),
+        Value::Number(n) => match n.as_u64() {
+            Some(u) => Ok(AttributeValue::U64(u)),
+            None => Ok(AttributeValue::F64(n.as_f64().unwrap())),
+        },
+        Value::String(s) => Ok(AttributeValue::String(s)),
+        Value::Array(list) => list
+            .into_iter()
+            .map(|v| v.as_u64().context("expected a list of unsigned integers"))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(AttributeValue::ListU64),
+        Value::Null | Value::Object(_) => Err(anyhow!("expected a boolean, a number, a string or a list")),
+    }
+}
@@ -0,0 +1,210 @@
+//! Sandboxed execution of the WASM modules.
+//!
+//! Each module runs in its own store, with a memory limit and a "fuel" limit that bounds the number
+//! of instructions executed per batch of measurements. The module can only access its own memory,
+//! the minimal WASI functions of [`crate::wasi`] and the functions of the `alumet` host module:
+//!
+//! - `log(level: i32, ptr: i32, len: i32)` writes a UTF-8 message to the logs of the agent,
+//!   with `level` between 1 (error) and 5 (trace).
+//!
+//! If a call fails (trap, fuel exhausted, memory limit reached, invalid result), the instance is
+//! discarded and a fresh one is created for the next batch, see [`Guest::reset`].
+
+use std::{path::Path, time::Instant};
+
+use anyhow::{Context, anyhow};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Instance, InstancePre, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc, WasmParams, WasmResults,
+};
+
+use crate::wasi;
+
+/// Resource limits of a module.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum size of the memory of the module, in bytes.
+    pub max_memory: usize,
+    /// Fuel given to the module for each batch of measurements (roughly one unit per instruction).
+    pub fuel_per_batch: u64,
+}
+
+/// Data available to the host functions.
+pub struct HostState {
+    pub name: String,
+    pub start: Instant,
+    limits: StoreLimits,
+}
+
+/// Creates an engine that supports the limits of [`Guest`].
+pub fn engine() -> anyhow::Result<Engine> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    Engine::new(&config)
+}
+
+/// A WASM module loaded in the sandbox.
+pub struct Guest {
+    name: String,
+    pre: InstancePre<HostState>,
+    limits: Limits,
+    /// `None` when the previous call has failed.
+    live: Option<Live>,
+}
+
+/// An instance of the module.
+struct Live {
+    store: Store<HostState>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    free: Option<TypedFunc<(i32, i32), ()>>,
+}
+
+impl Guest {
+    /// Compiles and instantiates the module at `path`.
+    ///
+    /// The module must export its `memory`, `alumet_alloc(len: i32) -> i32` and the function `entry_point`.
+    pub fn load<R: WasmResults>(
+        engine: &Engine,
+        name: &str,
+        path: &Path,
+        limits: Limits,
+        entry_point: &str,
+    ) -> anyhow::Result<Self> {
+        let module = Module::from_file(engine, path)
+            .with_context(|| format!("could not load WASM module {}", path.display()))?;
+        let mut linker = Linker::new(engine);
+        wasi::add_to_linker(&mut linker)?;
+        wasi::define_unsupported(&mut linker, &module)?;
+        linker.func_wrap("alumet", "log", host_log)?;
+        let pre = linker
+            .instantiate_pre(&module)
+            .with_context(|| format!("could not link WASM module {}", path.display()))?;
+
+        let mut guest = Self {
+            name: name.to_owned(),
+            pre,
+            limits,
+            live: None,
+        };
+        // Instantiate now to report the errors early.
+        let mut live = guest.instantiate()?;
+        live.instance_func::<(i32, i32), R>(entry_point)?;
+        guest.live = Some(live);
+        Ok(guest)
+    }
+
+    fn instantiate(&mut self) -> anyhow::Result<Live> {
+        let state = HostState {
+            name: self.name.clone(),
+            start: Instant::now(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.limits.max_memory)
+                .instances(1)
+                .build(),
+        };
+        let mut store = Store::new(self.pre.module().engine(), state);
+        store.limiter(|s| &mut s.limits);
+        store.set_fuel(self.limits.fuel_per_batch)?;
+        let instance = self.pre.instantiate(&mut store)?;
+        // WASI "reactors" must be initialized before anything else.
+        if let Ok(init) = instance.get_typed_func::<(), ()>(&mut store, "_initialize") {
+            init.call(&mut store, ()).context("_initialize failed")?;
+        }
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("the module must export its memory")?;
+        let alloc = instance.get_typed_func(&mut store, "alumet_alloc")?;
+        let free = instance.get_typed_func(&mut store, "alumet_free").ok();
+        Ok(Live {
+            store,
+            instance,
+            memory,
+            alloc,
+            free,
+        })
+    }
+
+    /// Copies `input` to the memory of the module and calls `func(ptr, len)`.
+    ///
+    /// The module takes the ownership of the input.
+    /// If the call fails, or if the result is invalid, [`reset`](Self::reset) must be called.
+    pub fn call<R: WasmResults>(&mut self, func: &str, input: &[u8]) -> anyhow::Result<(R, GuestMemory<'_>)> {
+        if self.live.is_none() {
+            self.live = Some(self.instantiate()?);
+        }
+        let live = self.live.as_mut().unwrap();
+        live.store.set_fuel(self.limits.fuel_per_batch)?;
+        let len = i32::try_from(input.len()).context("input too large")?;
+        let ptr = live.alloc.call(&mut live.store, len).context("alumet_alloc failed")?;
+        live.memory
+            .write(&mut live.store, ptr as u32 as usize, input)
+            .context("alumet_alloc returned an invalid pointer")?;
+        let r = live
+            .instance_func::<(i32, i32), R>(func)?
+            .call(&mut live.store, (ptr, len))
+            .with_context(|| format!("{func} failed"))?;
+        Ok((r, GuestMemory(live)))
+    }
+
+    /// Discards the current instance, a new one will be created on the next call.
+    pub fn reset(&mut self) {
+        self.live = None;
+    }
+}
+
+/// Access to the memory of a module after a call.
+pub struct GuestMemory<'a>(&'a mut Live);
+
+impl GuestMemory<'_> {
+    /// Copies the bytes at `[ptr, ptr+len)` out of the memory of the module, and frees them with
+    /// `alumet_free(ptr, len)` if the module exports it.
+    pub fn take(self, ptr: i32, len: i32) -> anyhow::Result<Vec<u8>> {
+        let Live {
+            store, memory, free, ..
+        } = self.0;
+        let begin = ptr as u32 as usize;
+        let end = begin + len as u32 as usize;
+        let bytes = memory
+            .data(&*store)
+            .get(begin..end)
+            .context("the module returned an invalid pointer")?
+            .to_vec();
+        if let Some(free) = free {
+            free.call(&mut *store, (ptr, len)).context("alumet_free failed")?;
+        }
+        Ok(bytes)
+    }
+}
+
+impl Live {
+    fn instance_func<P: WasmParams, R: WasmResults>(&mut self, name: &str) -> anyhow::Result<TypedFunc<P, R>> {
+        self.instance
+            .get_typed_func(&mut self.store, name)
+            .with_context(|| format!("the module must export {name} with the right signature"))
+    }
+}
+
+/// Implements `alumet.log`.
+fn host_log(mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32) -> anyhow::Result<()> {
+    let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+        return Err(anyhow!("the module does not export its memory"));
+    };
+    let begin = ptr as u32 as usize;
+    let end = begin + len as u32 as usize;
+    let msg = memory
+        .data(&caller)
+        .get(begin..end)
+        .context("invalid pointer given to alumet.log")?;
+    let msg = String::from_utf8_lossy(msg);
+    let level = match level {
+        1 => log::Level::Error,
+        2 => log::Level::Warn,
+        3 => log::Level::Info,
+        4 => log::Level::Debug,
+        _ => log::Level::Trace,
+    };
+    log::log!(level, "{}: {msg}", caller.data().name);
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use alumet::{
+    measurement::MeasurementBuffer,
+    metrics::registry::MetricRegistry,
+    pipeline::elements::{
+        error::TransformError,
+        transform::{Transform, TransformContext},
+    },
+};
+use anyhow::anyhow;
+
+use crate::{points, runtime::Guest};
+
+/// Name of the function exported by the transform modules.
+pub const ENTRY_POINT: &str = "alumet_transform";
+
+/// A transform implemented by a WASM module.
+///
+/// The module receives the measurements in JSON and returns the transformed measurements in JSON,
+/// as a pointer and a length packed in an `i64`: `(ptr << 32) | len`. A negative value means that the
+/// transform has failed.
+///
+/// When the module fails, the batch is dropped: we don't want to let measurements go through a transform
+/// that is supposed to filter them.
+pub struct WasmTransform {
+    guest: Guest,
+}
+
+impl WasmTransform {
+    pub fn new(guest: Guest) -> Self {
+        Self { guest }
+    }
+
+    fn run(&mut self, measurements: &MeasurementBuffer, metrics: &MetricRegistry) -> anyhow::Result<MeasurementBuffer> {
+        let input = points::encode(measurements, metrics)?;
+        let (packed, memory) = self.guest.call::<i64>(ENTRY_POINT, &input)?;
+        if packed < 0 {
+            return Err(anyhow!("{ENTRY_POINT} returned an error ({packed})"));
+        }
+        let output = memory.take((packed >> 32) as i32, packed as i32)?;
+        points::decode(&output, metrics)
+    }
+}
+
+impl Transform for WasmTransform {
+    fn apply(&mut self, measurements: &mut MeasurementBuffer, ctx: &TransformContext) -> Result<(), TransformError> {
+        match self.run(measurements, ctx.metrics) {
+            Ok(transformed) => *measurements = transformed,
+            Err(e) => {
+                log::error!(
+                    "WASM transform failed, {} measurements dropped: {e:#}",
+                    measurements.len()
+                );
+                self.guest.reset();
+                measurements.clear();
+            }
+        }
+        Ok(())
+    }
+}
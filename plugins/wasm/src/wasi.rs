@@ -0,0 +1,168 @@
+//! Minimal implementation of WASI preview 1 (`wasi_snapshot_preview1`).
+//!
+//! Modules compiled for `wasm32-wasip1` import some WASI functions, even if they don't use them.
+//! We only provide what a processing module needs: the clocks, random numbers, and stdout/stderr,
+//! which are redirected to the logs of the agent. There are no arguments, no environment variables,
+//! no preopened directories and no sockets: the other functions return `ERRNO_NOTCAPABLE`.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use wasmtime::{Caller, Extern, Linker, Memory, Module, Val, ValType};
+
+use crate::runtime::HostState;
+
+const MODULE: &str = "wasi_snapshot_preview1";
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+const ERRNO_FAULT: i32 = 21;
+const ERRNO_INVAL: i32 = 28;
+const ERRNO_NOTCAPABLE: i32 = 76;
+
+const CLOCK_REALTIME: i32 = 0;
+const CLOCK_MONOTONIC: i32 = 1;
+
+const STDOUT: i32 = 1;
+const STDERR: i32 = 2;
+
+/// The functions defined by [`add_to_linker`].
+const SUPPORTED: [&str; 10] = [
+    "args_sizes_get",
+    "args_get",
+    "environ_sizes_get",
+    "environ_get",
+    "clock_time_get",
+    "random_get",
+    "fd_write",
+    "fd_prestat_get",
+    "fd_prestat_dir_name",
+    "proc_exit",
+];
+
+/// Adds the supported WASI functions to the linker.
+pub fn add_to_linker(linker: &mut Linker<HostState>) -> anyhow::Result<()> {
+    linker.func_wrap(MODULE, "args_sizes_get", write_zero_sizes)?;
+    linker.func_wrap(MODULE, "args_get", |_: i32, _: i32| ERRNO_SUCCESS)?;
+    linker.func_wrap(MODULE, "environ_sizes_get", write_zero_sizes)?;
+    linker.func_wrap(MODULE, "environ_get", |_: i32, _: i32| ERRNO_SUCCESS)?;
+    linker.func_wrap(MODULE, "clock_time_get", clock_time_get)?;
+    linker.func_wrap(MODULE, "random_get", random_get)?;
+    linker.func_wrap(MODULE, "fd_write", fd_write)?;
+    linker.func_wrap(MODULE, "fd_prestat_get", |_: i32, _: i32| ERRNO_BADF)?;
+    linker.func_wrap(MODULE, "fd_prestat_dir_name", |_: i32, _: i32, _: i32| ERRNO_BADF)?;
+    linker.func_wrap(MODULE, "proc_exit", |code: i32| -> anyhow::Result<()> {
+        Err(anyhow!("the module exited with code {code}"))
+    })?;
+    Ok(())
+}
+
+/// Defines the other WASI functions imported by the module, so that they return `ERRNO_NOTCAPABLE`.
+pub fn define_unsupported(linker: &mut Linker<HostState>, module: &Module) -> anyhow::Result<()> {
+    for import in module.imports() {
+        if import.module() != MODULE {
+            continue;
+        }
+        let Some(ty) = import.ty().func().cloned() else {
+            continue;
+        };
+        if SUPPORTED.contains(&import.name()) {
+            continue;
+        }
+        if !ty.results().map(|t| matches!(t, ValType::I32)).eq([true]) {
+            return Err(anyhow!("unsupported WASI import {}", import.name()));
+        }
+        linker.func_new(MODULE, import.name(), ty, |_, _, results| {
+            results[0] = Val::I32(ERRNO_NOTCAPABLE);
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> anyhow::Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(m)) => Ok(m),
+        _ => Err(anyhow!("the module does not export its memory")),
+    }
+}
+
+fn write_bytes(caller: &mut Caller<'_, HostState>, ptr: i32, bytes: &[u8]) -> anyhow::Result<i32> {
+    let memory = memory(caller)?;
+    Ok(match memory.write(caller, ptr as u32 as usize, bytes) {
+        Ok(()) => ERRNO_SUCCESS,
+        Err(_) => ERRNO_FAULT,
+    })
+}
+
+fn write_zero_sizes(mut caller: Caller<'_, HostState>, count_ptr: i32, size_ptr: i32) -> anyhow::Result<i32> {
+    let res = write_bytes(&mut caller, count_ptr, &0u32.to_le_bytes())?;
+    if res != ERRNO_SUCCESS {
+        return Ok(res);
+    }
+    write_bytes(&mut caller, size_ptr, &0u32.to_le_bytes())
+}
+
+fn clock_time_get(mut caller: Caller<'_, HostState>, id: i32, _precision: i64, time_ptr: i32) -> anyhow::Result<i32> {
+    let t = match id {
+        CLOCK_REALTIME => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+        CLOCK_MONOTONIC => Instant::now().duration_since(caller.data().start),
+        _ => return Ok(ERRNO_INVAL),
+    };
+    write_bytes(&mut caller, time_ptr, &(t.as_nanos() as u64).to_le_bytes())
+}
+
+/// Fills the buffer with random bytes (not suitable for cryptography).
+fn random_get(mut caller: Caller<'_, HostState>, buf: i32, len: i32) -> anyhow::Result<i32> {
+    let state = RandomState::new();
+    let mut bytes = Vec::with_capacity(len as u32 as usize);
+    let mut i = 0u64;
+    while bytes.len() < len as u32 as usize {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(i);
+        bytes.extend_from_slice(&hasher.finish().to_le_bytes());
+        i += 1;
+    }
+    bytes.truncate(len as u32 as usize);
+    write_bytes(&mut caller, buf, &bytes)
+}
+
+fn fd_write(
+    mut caller: Caller<'_, HostState>,
+    fd: i32,
+    iovs: i32,
+    iovs_len: i32,
+    nwritten_ptr: i32,
+) -> anyhow::Result<i32> {
+    if fd != STDOUT && fd != STDERR {
+        return Ok(ERRNO_BADF);
+    }
+    let memory = memory(&mut caller)?;
+    let data = memory.data(&caller);
+    let mut text = Vec::new();
+    for i in 0..iovs_len as u32 as usize {
+        let iov = iovs as u32 as usize + i * 8;
+        let Some(iov) = data.get(iov..iov + 8) else {
+            return Ok(ERRNO_FAULT);
+        };
+        let buf = u32::from_le_bytes(iov[..4].try_into().unwrap()) as usize;
+        let buf_len = u32::from_le_bytes(iov[4..].try_into().unwrap()) as usize;
+        let Some(buf) = data.get(buf..buf + buf_len) else {
+            return Ok(ERRNO_FAULT);
+        };
+        text.extend_from_slice(buf);
+    }
+    let name = &caller.data().name;
+    for line in String::from_utf8_lossy(&text).lines().filter(|l| !l.is_empty()) {
+        if fd == STDOUT {
+            log::info!("{name}: {line}");
+        } else {
+            log::warn!("{name}: {line}");
+        }
+    }
+    write_bytes(&mut caller, nwritten_ptr, &(text.len() as u32).to_le_bytes())
+}
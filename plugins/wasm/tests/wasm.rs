@@ -0,0 +1,219 @@
+use std::path::{Path, PathBuf};
+
+use alumet::{
+    measurement::{MeasurementBuffer, MeasurementPoint},
+    plugin::rust::serialize_config,
+    test::{PluginHarness, golden::SampleMeasurements},
+};
+use plugin_wasm::{Config, ModuleConfig, WasmPlugin};
+
+/// Bump allocator that never frees, and grows the memory when needed.
+const ALLOC: &str = r#"
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "alumet_alloc") (param $len i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr (global.get $heap))
+        (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+        (if (i32.gt_u (global.get $heap) (i32.mul (memory.size) (i32.const 65536)))
+            (then (drop (memory.grow (i32.add (i32.div_u (local.get $len) (i32.const 65536)) (i32.const 1))))))
+        (local.get $ptr))
+"#;
+
+/// Returns the input unchanged.
+const IDENTITY: &str = r#"
+    (func (export "alumet_transform") (param $ptr i32) (param $len i32) (result i64)
+        (i64.or
+            (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+            (i64.extend_i32_u (local.get $len))))
+"#;
+
+fn write_module(dir: &Path, name: &str, body: &str) -> PathBuf {
+    let wasm = wat::parse_str(format!("(module {body})")).unwrap();
+    let path = dir.join(format!("{name}.wasm"));
+    std::fs::write(&path, wasm).unwrap();
+    path
+}
+
+fn module(name: &str, path: PathBuf) -> ModuleConfig {
+    ModuleConfig {
+        name: name.to_owned(),
+        path,
+        max_memory_bytes: 1024 * 1024,
+        fuel_per_batch: 1_000_000,
+    }
+}
+
+fn start(config: Config) -> anyhow::Result<PluginHarness<WasmPlugin>> {
+    PluginHarness::<WasmPlugin>::start(serialize_config(config)?)
+}
+
+/// The order of the attributes is not kept, and `Str` becomes `String`.
+fn sorted_attributes(p: &MeasurementPoint) -> Vec<(String, String)> {
+    let mut attributes: Vec<_> = p.attributes().map(|(k, v)| (k.to_owned(), v.to_string())).collect();
+    attributes.sort();
+    attributes
+}
+
+fn assert_same(a: &MeasurementBuffer, b: &MeasurementBuffer) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b.iter()) {
+        assert_eq!(a.metric, b.metric);
+        assert_eq!(a.timestamp, b.timestamp);
+        assert_eq!(a.value, b.value);
+        assert_eq!(a.resource, b.resource);
+        assert_eq!(a.consumer, b.consumer);
+        assert_eq!(sorted_attributes(a), sorted_attributes(b));
+    }
+}
+
+#[test]
+fn transform_roundtrip() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let path = write_module(tmp.path(), "identity", &format!("{ALLOC}{IDENTITY}"));
+    let mut harness = start(Config {
+        transforms: vec![module("identity", path)],
+        ..Default::default()
+    })?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut transform = harness.transform("identity")?;
+    let output = transform.apply(sample.measurements()).unwrap();
+    assert_same(&output, &sample.measurements());
+    harness.stop()
+}
+
+#[test]
+fn transform_filter() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let drop_all = r#"
+        (data (i32.const 16) "[]")
+        (func (export "alumet_transform") (param i32 i32) (result i64)
+            (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 2)))
+    "#;
+    let path = write_module(tmp.path(), "drop", &format!("{drop_all}{ALLOC}"));
+    let mut harness = start(Config {
+        transforms: vec![module("drop", path)],
+        ..Default::default()
+    })?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut transform = harness.transform("drop")?;
+    assert!(transform.apply(sample.measurements()).unwrap().is_empty());
+    harness.stop()
+}
+
+#[test]
+fn limits() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let infinite_loop = r#"
+        (func (export "alumet_transform") (param i32 i32) (result i64)
+            (loop $l (br $l))
+            (i64.const -1))
+    "#;
+    let looping = write_module(tmp.path(), "loop", &format!("{infinite_loop}{ALLOC}"));
+    let identity = write_module(tmp.path(), "identity", &format!("{ALLOC}{IDENTITY}"));
+    let mut harness = start(Config {
+        transforms: vec![
+            module("loop", looping),
+            ModuleConfig {
+                max_memory_bytes: 65536,
+                ..module("small", identity)
+            },
+        ],
+        ..Default::default()
+    })?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    // The fuel is exhausted: the batch is dropped and the instance is replaced by a new one.
+    let mut transform = harness.transform("loop")?;
+    assert!(transform.apply(sample.measurements()).unwrap().is_empty());
+    assert!(transform.apply(sample.measurements()).unwrap().is_empty());
+
+    // The input does not fit in the memory of the module.
+    let mut transform = harness.transform("small")?;
+    assert_same(&transform.apply(sample.measurements()).unwrap(), &sample.measurements());
+    let mut big = MeasurementBuffer::new();
+    for _ in 0..200 {
+        big.merge(&mut sample.measurements());
+    }
+    assert!(transform.apply(big).unwrap().is_empty());
+    harness.stop()
+}
+
+#[test]
+fn wasi_is_restricted() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    // Traps if path_open does not return ERRNO_NOTCAPABLE (76).
+    let open_file = r#"
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (func (export "alumet_transform") (param $ptr i32) (param $len i32) (result i64)
+            (if (i32.ne
+                    (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 0)
+                        (i32.const 0) (i64.const 0) (i64.const 0) (i32.const 0) (i32.const 0))
+                    (i32.const 76))
+                (then unreachable))
+            (i64.or
+                (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                (i64.extend_i32_u (local.get $len))))
+    "#;
+    let path = write_module(tmp.path(), "open", &format!("{open_file}{ALLOC}"));
+    let mut harness = start(Config {
+        transforms: vec![module("open", path)],
+        ..Default::default()
+    })?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut transform = harness.transform("open")?;
+    assert_same(&transform.apply(sample.measurements()).unwrap(), &sample.measurements());
+    harness.stop()
+}
+
+#[test]
+fn output() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    // Logs the measurements, and fails on the second call.
+    let log_and_fail = r#"
+        (import "alumet" "log" (func $log (param i32 i32 i32)))
+        (global $calls (mut i32) (i32.const 0))
+        (func (export "alumet_write") (param $ptr i32) (param $len i32) (result i32)
+            (call $log (i32.const 3) (local.get $ptr) (local.get $len))
+            (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+            (i32.eq (global.get $calls) (i32.const 2)))
+    "#;
+    let path = write_module(tmp.path(), "log", &format!("{log_and_fail}{ALLOC}"));
+    let mut harness = start(Config {
+        outputs: vec![module("log", path)],
+        ..Default::default()
+    })?;
+    let sample = SampleMeasurements::register(&harness)?;
+    let mut output = harness.output("log")?;
+    output.write(&sample.measurements()).unwrap();
+    output.write(&sample.measurements()).unwrap_err();
+    // the instance has been reset
+    output.write(&sample.measurements()).unwrap();
+    output.finish()?;
+    harness.stop()
+}
+
+#[test]
+fn invalid_module() {
+    let tmp = tempfile::tempdir().unwrap();
+    // no alumet_alloc
+    let path = write_module(tmp.path(), "invalid", IDENTITY);
+    let res = start(Config {
+        transforms: vec![module("invalid", path)],
+        ..Default::default()
+    });
+    assert!(res.is_err());
+
+    // forbidden import
+    let path = write_module(
+        tmp.path(),
+        "import",
+        &format!(r#"(import "env" "system" (func (param i32))) {ALLOC}{IDENTITY}"#),
+    );
+    let res = start(Config {
+        transforms: vec![module("import", path)],
+        ..Default::default()
+    });
+    assert!(res.is_err());
+}
@@ -0,0 +1,53 @@
+//! Ambient conditions, common to the weather APIs and to the local sensors.
+
+use std::time::Duration;
+
+use alumet::pipeline::elements::source::blocking::BlockingDrop;
+
+use anyhow::{Context, anyhow};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+
+/// A weather API or a local sensor, which gives the ambient conditions of a location.
+pub trait Station: Send {
+    /// Reads the current conditions.
+    fn read(&mut self) -> anyhow::Result<Conditions>;
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Conditions {
+    /// Air temperature, in degrees Celsius.
+    pub temperature: Option<f64>,
+    /// Relative humidity of the air, in percents.
+    pub humidity: Option<f64>,
+}
+
+/// An HTTP client for a weather API.
+pub struct HttpClient {
+    client: BlockingDrop<Client>,
+    url: String,
+}
+
+impl HttpClient {
+    pub fn new(url: &str, timeout: Duration) -> anyhow::Result<Self> {
+        let client = Client::builder().timeout(timeout).build()?;
+        Ok(Self {
+            client: BlockingDrop::new(client),
+            url: url.trim_end_matches('/').to_owned(),
+        })
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.client.get(format!("{}{path}", self.url))
+    }
+}
+
+/// Deserializes the body of a response, or returns an error that contains the message of the API.
+pub fn json<T: DeserializeOwned>(res: Response) -> anyhow::Result<T> {
+    let status = res.status();
+    if !status.is_success() {
+        let body = res.text().unwrap_or_default();
+        return Err(anyhow!("the weather API returned {status}: {}", body.trim()));
+    }
+    res.json().context("invalid response")
+}
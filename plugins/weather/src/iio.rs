@@ -0,0 +1,128 @@
+//! Local sensors of temperature and humidity, such as the DHT22 or the SHT3x, through the Industrial I/O subsystem of Linux.
+//!
+//! See <https://www.kernel.org/doc/Documentation/ABI/testing/sysfs-bus-iio>.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::api::{Conditions, Station};
+
+/// Channel of the temperature, in milli degrees Celsius.
+const TEMPERATURE: &str = "in_temp";
+/// Channel of the relative humidity, in milli percents.
+const HUMIDITY: &str = "in_humidityrelative";
+
+pub struct Iio {
+    device: PathBuf,
+    temperature: bool,
+    humidity: bool,
+}
+
+impl Iio {
+    /// Opens an IIO device, such as `/sys/bus/iio/devices/iio:device0`.
+    pub fn new(device: PathBuf) -> anyhow::Result<Self> {
+        let has_channel = |channel: &str| {
+            device.join(format!("{channel}_input")).exists() || device.join(format!("{channel}_raw")).exists()
+        };
+        let temperature = has_channel(TEMPERATURE);
+        let humidity = has_channel(HUMIDITY);
+        if !temperature && !humidity {
+            return Err(anyhow!(
+                "{} has no temperature or humidity channel, is it an IIO device?",
+                device.display()
+            ));
+        }
+        Ok(Self {
+            device,
+            temperature,
+            humidity,
+        })
+    }
+}
+
+impl Station for Iio {
+    fn read(&mut self) -> anyhow::Result<Conditions> {
+        let read = |channel: &str| -> anyhow::Result<f64> { Ok(read_channel(&self.device, channel)? / 1000.0) };
+        Ok(Conditions {
+            temperature: self.temperature.then(|| read(TEMPERATURE)).transpose()?,
+            humidity: self.humidity.then(|| read(HUMIDITY)).transpose()?,
+        })
+    }
+}
+
+/// Reads the processed value of a channel, or computes it from the raw value, the offset and the scale.
+fn read_channel(device: &Path, channel: &str) -> anyhow::Result<f64> {
+    let input = device.join(format!("{channel}_input"));
+    if input.exists() {
+        return read_number(&input);
+    }
+    let raw = read_number(&device.join(format!("{channel}_raw")))?;
+    let offset = read_optional(&device.join(format!("{channel}_offset")))?.unwrap_or(0.0);
+    let scale = read_optional(&device.join(format!("{channel}_scale")))?.unwrap_or(1.0);
+    Ok((raw + offset) * scale)
+}
+
+fn read_number(path: &Path) -> anyhow::Result<f64> {
+    // Some sensors, like the DHT11, often fail to answer: the next poll may work.
+    let content = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    content
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid value {:?} in {}", content.trim(), path.display()))
+}
+
+fn read_optional(path: &Path) -> anyhow::Result<Option<f64>> {
+    if path.exists() {
+        read_number(path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn processed_values() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path().join("name"), "dht11\n").unwrap();
+        write(dir.path().join("in_temp_input"), "23500\n").unwrap();
+        write(dir.path().join("in_humidityrelative_input"), "41200\n").unwrap();
+        let mut station = Iio::new(dir.path().to_owned()).unwrap();
+        assert_eq!(
+            station.read().unwrap(),
+            Conditions {
+                temperature: Some(23.5),
+                humidity: Some(41.2),
+            }
+        );
+    }
+
+    #[test]
+    fn raw_values() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path().join("in_temp_raw"), "26000\n").unwrap();
+        write(dir.path().join("in_temp_offset"), "-16852\n").unwrap();
+        write(dir.path().join("in_temp_scale"), "2.670288\n").unwrap();
+        let mut station = Iio::new(dir.path().to_owned()).unwrap();
+        let conditions = station.read().unwrap();
+        assert!((conditions.temperature.unwrap() - 24.427794).abs() < 1e-6);
+        assert_eq!(conditions.humidity, None);
+    }
+
+    #[test]
+    fn not_a_sensor() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path().join("in_voltage0_raw"), "512\n").unwrap();
+        assert!(Iio::new(dir.path().to_owned()).is_err());
+    }
+}
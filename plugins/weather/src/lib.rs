@@ -0,0 +1,293 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+    units::Unit,
+};
+
+use api::{HttpClient, Station};
+use source::{Metrics, WeatherSource};
+
+mod api;
+mod iio;
+mod openmeteo;
+mod openweathermap;
+mod source;
+
+pub struct WeatherPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for WeatherPlugin {
+    fn name() -> &'static str {
+        "weather"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(WeatherPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        if self.config.stations.is_empty() {
+            return Err(anyhow!("No station configured, please add some to the configuration."));
+        }
+
+        let metrics = Metrics {
+            temperature: alumet.create_metric::<f64>(
+                "ambient_temperature",
+                Unit::DegreeCelsius,
+                "Temperature of the air",
+            )?,
+            humidity: alumet.create_metric::<f64>("ambient_humidity", Unit::Percent, "Relative humidity of the air")?,
+        };
+        for station in &self.config.stations {
+            // The APIs are contacted on the first poll, so that an unreachable API does not prevent the agent from starting.
+            let api = self
+                .open_station(station)
+                .with_context(|| format!("invalid configuration of station {}", station.name))?;
+            let source = WeatherSource::new(api, station.name.clone(), metrics);
+            let trigger = TriggerSpec::builder(self.config.poll_interval)
+                .flush_interval(self.config.flush_interval)
+                .build()?;
+            alumet.add_source(&format!("station_{}", station.name), Box::new(source), trigger)?;
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl WeatherPlugin {
+    fn open_station(&self, station: &StationConfig) -> anyhow::Result<Box<dyn Station>> {
+        let position = || match (station.latitude, station.longitude) {
+            (Some(latitude), Some(longitude)) => Ok((latitude, longitude)),
+            _ => Err(anyhow!("the latitude and the longitude are required")),
+        };
+        let client = |default: &str| {
+            let url = station.url.as_deref().unwrap_or(default);
+            HttpClient::new(url, self.config.timeout).context("failed to create the HTTP client")
+        };
+        let api: Box<dyn Station> = match station.kind {
+            Kind::OpenMeteo => {
+                let (latitude, longitude) = position()?;
+                Box::new(openmeteo::OpenMeteo::new(
+                    client(openmeteo::DEFAULT_URL)?,
+                    latitude,
+                    longitude,
+                ))
+            }
+            Kind::OpenWeatherMap => {
+                let (latitude, longitude) = position()?;
+                let api_key = station
+                    .api_key
+                    .clone()
+                    .or_else(|| std::env::var("OPENWEATHERMAP_API_KEY").ok())
+                    .ok_or_else(|| {
+                        anyhow!("No api_key specified in the config, and OPENWEATHERMAP_API_KEY is not set.")
+                    })?;
+                Box::new(openweathermap::OpenWeatherMap::new(
+                    client(openweathermap::DEFAULT_URL)?,
+                    latitude,
+                    longitude,
+                    api_key,
+                ))
+            }
+            Kind::Iio => {
+                let device = station
+                    .device
+                    .clone()
+                    .ok_or_else(|| anyhow!("the device is required"))?;
+                Box::new(iio::Iio::new(device)?)
+            }
+        };
+        Ok(api)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Initial interval between two readings of the stations.
+    #[serde(with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+
+    /// Timeout of each request to the weather APIs.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+
+    pub stations: Vec<StationConfig>,
+}
+
+/// A location whose ambient conditions are measured, by a weather API or by a local sensor.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StationConfig {
+    /// Name of the location, such as `outdoor` or `cold_aisle`.
+    pub name: String,
+    pub kind: Kind,
+    /// Position of the location (weather APIs).
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Key of the OpenWeatherMap API. Defaults to the environment variable `OPENWEATHERMAP_API_KEY`.
+    pub api_key: Option<String>,
+    /// URL of the weather API. Defaults to the public API.
+    pub url: Option<String>,
+    /// IIO device of the sensor, such as `/sys/bus/iio/devices/iio:device0`.
+    pub device: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// Open-Meteo API, which requires no key.
+    OpenMeteo,
+    /// OpenWeatherMap API.
+    OpenWeatherMap,
+    /// Local sensor, through the Industrial I/O subsystem of Linux.
+    Iio,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5 * 60),
+            flush_interval: Duration::from_secs(5 * 60),
+            timeout: Duration::from_secs(10),
+            stations: vec![StationConfig {
+                name: String::from("outdoor"),
+                kind: Kind::OpenMeteo,
+                latitude: Some(48.8566),
+                longitude: Some(2.3522),
+                api_key: None,
+                url: None,
+                device: None,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use alumet::{measurement::WrappedMeasurementValue, pipeline::elements::error::PollError, test::PluginHarness};
+    use mockito::{Matcher, Server};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn station(name: &str, kind: Kind) -> StationConfig {
+        StationConfig {
+            name: name.to_owned(),
+            kind,
+            latitude: None,
+            longitude: None,
+            api_key: None,
+            url: None,
+            device: None,
+        }
+    }
+
+    #[test]
+    fn api_and_sensor() -> anyhow::Result<()> {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/v1/forecast")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("latitude".into(), "45.19".into()),
+                Matcher::UrlEncoded("longitude".into(), "5.72".into()),
+                Matcher::UrlEncoded("current".into(), "temperature_2m,relative_humidity_2m".into()),
+            ]))
+            .with_body(
+                r#"{"latitude":45.18,"longitude":5.72,"current_units":{"temperature_2m":"°C","relative_humidity_2m":"%"},
+                    "current":{"time":"2025-07-01T14:00","interval":900,"temperature_2m":31.2,"relative_humidity_2m":35}}"#,
+            )
+            .create();
+        let sensor = tempfile::tempdir()?;
+        write(sensor.path().join("in_temp_input"), "24250\n")?;
+
+        let config = Config {
+            stations: vec![
+                StationConfig {
+                    latitude: Some(45.19),
+                    longitude: Some(5.72),
+                    url: Some(server.url()),
+                    ..station("outdoor", Kind::OpenMeteo)
+                },
+                StationConfig {
+                    device: Some(sensor.path().to_owned()),
+                    ..station("cold_aisle", Kind::Iio)
+                },
+            ],
+            ..Config::default()
+        };
+        let mut harness = PluginHarness::<WeatherPlugin>::start(serialize_config(config)?)?;
+
+        let measurements = harness.source("station_outdoor")?.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, "ambient_temperature"),
+            vec![WrappedMeasurementValue::F64(31.2)]
+        );
+        assert_eq!(
+            harness.values(&measurements, "ambient_humidity"),
+            vec![WrappedMeasurementValue::F64(35.0)]
+        );
+        let point = harness.points(&measurements, "ambient_temperature")[0];
+        assert_eq!(point.resource.kind(), "location");
+        assert_eq!(point.resource.id_display().to_string(), "outdoor");
+
+        // the sensor has no humidity channel
+        let mut sensor_source = harness.source("station_cold_aisle")?;
+        let measurements = sensor_source.poll().unwrap();
+        assert_eq!(
+            harness.values(&measurements, "ambient_temperature"),
+            vec![WrappedMeasurementValue::F64(24.25)]
+        );
+        assert!(harness.values(&measurements, "ambient_humidity").is_empty());
+
+        // the sensor does not answer
+        std::fs::remove_file(sensor.path().join("in_temp_input"))?;
+        write(sensor.path().join("in_temp_raw"), "")?;
+        assert!(matches!(sensor_source.poll(), Err(PollError::CanRetry(_))));
+
+        harness.stop()?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_position() -> anyhow::Result<()> {
+        let config = Config {
+            stations: vec![station("outdoor", Kind::OpenMeteo)],
+            ..Config::default()
+        };
+        assert!(PluginHarness::<WeatherPlugin>::start(serialize_config(config)?).is_err());
+        Ok(())
+    }
+}
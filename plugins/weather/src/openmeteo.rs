@@ -0,0 +1,52 @@
+//! Current weather of Open-Meteo, which requires no key.
+//!
+//! See <https://open-meteo.com/en/docs>.
+
+use serde::Deserialize;
+
+use crate::api::{Conditions, HttpClient, Station, json};
+
+pub const DEFAULT_URL: &str = "https://api.open-meteo.com";
+
+pub struct OpenMeteo {
+    client: HttpClient,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Deserialize)]
+struct Forecast {
+    current: Current,
+}
+
+#[derive(Deserialize)]
+struct Current {
+    temperature_2m: Option<f64>,
+    relative_humidity_2m: Option<f64>,
+}
+
+impl OpenMeteo {
+    pub fn new(client: HttpClient, latitude: f64, longitude: f64) -> Self {
+        Self {
+            client,
+            latitude,
+            longitude,
+        }
+    }
+}
+
+impl Station for OpenMeteo {
+    fn read(&mut self) -> anyhow::Result<Conditions> {
+        let res = self
+            .client
+            .get("/v1/forecast")
+            .query(&[("latitude", self.latitude), ("longitude", self.longitude)])
+            .query(&[("current", "temperature_2m,relative_humidity_2m")])
+            .send()?;
+        let forecast: Forecast = json(res)?;
+        Ok(Conditions {
+            temperature: forecast.current.temperature_2m,
+            humidity: forecast.current.relative_humidity_2m,
+        })
+    }
+}
@@ -0,0 +1,109 @@
+//! Current weather data of OpenWeatherMap.
+//!
+//! See <https://openweathermap.org/current>.
+
+use serde::Deserialize;
+
+use crate::api::{Conditions, HttpClient, Station, json};
+
+pub const DEFAULT_URL: &str = "https://api.openweathermap.org";
+
+pub struct OpenWeatherMap {
+    client: HttpClient,
+    latitude: f64,
+    longitude: f64,
+    api_key: String,
+}
+
+#[derive(Deserialize)]
+struct Weather {
+    main: Main,
+}
+
+#[derive(Deserialize)]
+struct Main {
+    temp: Option<f64>,
+    humidity: Option<f64>,
+}
+
+impl OpenWeatherMap {
+    pub fn new(client: HttpClient, latitude: f64, longitude: f64, api_key: String) -> Self {
+        Self {
+            client,
+            latitude,
+            longitude,
+            api_key,
+        }
+    }
+}
+
+impl Station for OpenWeatherMap {
+    fn read(&mut self) -> anyhow::Result<Conditions> {
+        let res = self
+            .client
+            .get("/data/2.5/weather")
+            .query(&[("lat", self.latitude), ("lon", self.longitude)])
+            .query(&[("units", "metric"), ("appid", &self.api_key)])
+            .send()?;
+        let weather: Weather = json(res)?;
+        Ok(Conditions {
+            temperature: weather.main.temp,
+            humidity: weather.main.humidity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mockito::{Matcher, Server};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn current_weather() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/data/2.5/weather")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("lat".into(), "45.19".into()),
+                Matcher::UrlEncoded("lon".into(), "5.72".into()),
+                Matcher::UrlEncoded("units".into(), "metric".into()),
+                Matcher::UrlEncoded("appid".into(), "secret".into()),
+            ]))
+            .with_body(
+                r#"{"coord":{"lon":5.72,"lat":45.19},"weather":[{"id":800,"main":"Clear"}],
+                    "main":{"temp":21.4,"feels_like":21.1,"pressure":1015,"humidity":48},"name":"Grenoble"}"#,
+            )
+            .create();
+        let client = HttpClient::new(&server.url(), Duration::from_secs(5)).unwrap();
+        let mut station = OpenWeatherMap::new(client, 45.19, 5.72, String::from("secret"));
+        assert_eq!(
+            station.read().unwrap(),
+            Conditions {
+                temperature: Some(21.4),
+                humidity: Some(48.0),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_key() {
+        let mut server = Server::new();
+        server
+            .mock("GET", "/data/2.5/weather")
+            .match_query(Matcher::Any)
+            .with_status(401)
+            .with_body(r#"{"cod":401,"message":"Invalid API key."}"#)
+            .create();
+        let client = HttpClient::new(&server.url(), Duration::from_secs(5)).unwrap();
+        let mut station = OpenWeatherMap::new(client, 45.19, 5.72, String::from("wrong"));
+        let error = station.read().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"the weather API returned 401 Unauthorized: {"cod":401,"message":"Invalid API key."}"#
+        );
+    }
+}
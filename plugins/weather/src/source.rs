@@ -0,0 +1,52 @@
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    metrics::TypedMetricId,
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+use anyhow::Context;
+
+use crate::api::Station;
+
+#[derive(Clone, Copy)]
+pub struct Metrics {
+    pub temperature: TypedMetricId<f64>,
+    pub humidity: TypedMetricId<f64>,
+}
+
+/// Measurement source that reads the ambient conditions of a location.
+pub struct WeatherSource {
+    station: Box<dyn Station>,
+    resource: Resource,
+    metrics: Metrics,
+}
+
+impl WeatherSource {
+    pub fn new(station: Box<dyn Station>, location: String, metrics: Metrics) -> Self {
+        Self {
+            station,
+            resource: Resource::custom("location", location),
+            metrics,
+        }
+    }
+}
+
+impl Source for WeatherSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        // The HTTP requests are blocking, and the API or the sensor can be unavailable for a while (the next poll may work).
+        let conditions = tokio::task::block_in_place(|| self.station.read())
+            .context("failed to read the ambient conditions")
+            .map_err(PollError::CanRetry)?;
+
+        let point = |metric: TypedMetricId<f64>, value: f64| {
+            MeasurementPoint::new(t, metric, self.resource.clone(), ResourceConsumer::LocalMachine, value)
+        };
+        if let Some(temperature) = conditions.temperature {
+            measurements.push(point(self.metrics.temperature, temperature));
+        }
+        if let Some(humidity) = conditions.humidity {
+            measurements.push(point(self.metrics.humidity, humidity));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+mod output;
+mod payload;
+mod server;
+
+use std::{
+    net::TcpListener,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+};
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::output::WebSocketOutput;
+use crate::server::Clients;
+
+pub struct WebSocketPlugin {
+    config: Config,
+    server: Option<(Arc<AtomicBool>, JoinHandle<anyhow::Result<()>>)>,
+}
+
+impl AlumetPlugin for WebSocketPlugin {
+    fn name() -> &'static str {
+        "websocket"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(config.max_clients > 0, "max_clients must be greater than zero");
+        anyhow::ensure!(config.queue_size > 0, "queue_size must be greater than zero");
+        Ok(Box::new(WebSocketPlugin { config, server: None }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+
+        // Bind now to report errors early.
+        let listener =
+            TcpListener::bind(&config.address).with_context(|| format!("failed to listen on {}", config.address))?;
+        log::info!("WebSocket server listening on ws://{}", listener.local_addr()?);
+
+        let clients = Arc::new(Clients::new(config.max_clients, config.queue_size));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let clients = clients.clone();
+            let stop = stop.clone();
+            std::thread::Builder::new()
+                .name(String::from("websocket-server"))
+                .spawn(move || server::run(listener, clients, stop))?
+        };
+        self.server = Some((stop, handle));
+
+        let metrics = config.metrics.iter().cloned().collect();
+        alumet.add_blocking_output("out", Box::new(WebSocketOutput::new(clients, metrics)))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        if let Some((stop, handle)) = self.server.take() {
+            stop.store(true, Ordering::Relaxed);
+            handle
+                .join()
+                .map_err(|_| anyhow!("the WebSocket server thread panicked"))?
+                .context("the WebSocket server failed")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address and port on which the WebSocket server listens.
+    pub address: String,
+    /// The metrics to stream. If empty, every metric is streamed.
+    ///
+    /// Each client can subscribe to some of these metrics with the query parameter `metrics`,
+    /// for instance `ws://localhost:8765/?metrics=rapl_consumed_energy`.
+    pub metrics: Vec<String>,
+    /// Maximum number of clients connected at the same time.
+    pub max_clients: usize,
+    /// Maximum number of messages waiting to be sent to each client.
+    /// When a client is too slow and its queue is full, the new messages are not sent to it.
+    pub queue_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: String::from("127.0.0.1:8765"),
+            metrics: Vec::new(),
+            max_clients: 32,
+            queue_size: 64,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::{collections::HashSet, sync::Arc};
+
+use alumet::{
+    measurement::MeasurementBuffer,
+    pipeline::{
+        Output,
+        elements::{error::WriteError, output::OutputContext},
+    },
+};
+use anyhow::Context;
+
+use crate::{
+    payload::{self, Record},
+    server::Clients,
+};
+
+pub struct WebSocketOutput {
+    clients: Arc<Clients>,
+    /// The metrics to stream, or an empty set for all the metrics.
+    metrics: HashSet<String>,
+}
+
+impl WebSocketOutput {
+    pub fn new(clients: Arc<Clients>, metrics: HashSet<String>) -> Self {
+        Self { clients, metrics }
+    }
+}
+
+impl Output for WebSocketOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        // Measurements that nobody receives are not kept: this output is for live data.
+        if self.clients.is_empty() {
+            return Ok(());
+        }
+        let mut records = Vec::with_capacity(measurements.len());
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            if !self.metrics.is_empty() && !self.metrics.contains(&metric.name) {
+                continue;
+            }
+            records.push((metric.name.as_str(), payload::encode(&Record::new(m, metric))));
+        }
+        if !records.is_empty() {
+            self.clients.broadcast(&records);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! WebSocket server: accepts the clients and streams the messages to them.
+
+use std::{
+    collections::HashSet,
+    io::ErrorKind,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use tungstenite::{
+    Message, Utf8Bytes,
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+};
+
+use crate::payload;
+
+/// How often the threads check whether the server must stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a client can block the sending of a message before being disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The clients that are connected to the server.
+pub struct Clients {
+    clients: Mutex<Vec<Client>>,
+    max_clients: usize,
+    queue_size: usize,
+}
+
+struct Client {
+    addr: SocketAddr,
+    /// The metrics that the client subscribed to, or `None` for all the metrics.
+    metrics: Option<HashSet<String>>,
+    tx: SyncSender<Utf8Bytes>,
+    /// Whether some messages have been dropped because the client is too slow.
+    lagging: bool,
+}
+
+impl Clients {
+    pub fn new(max_clients: usize, queue_size: usize) -> Self {
+        Self {
+            clients: Mutex::new(Vec::new()),
+            max_clients,
+            queue_size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.lock().unwrap().is_empty()
+    }
+
+    /// Registers a new client and returns the queue of its messages,
+    /// or `None` if there are already too many clients.
+    fn register(&self, addr: SocketAddr, metrics: Option<HashSet<String>>) -> Option<Receiver<Utf8Bytes>> {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= self.max_clients {
+            return None;
+        }
+        let (tx, rx) = std::sync::mpsc::sync_channel(self.queue_size);
+        clients.push(Client {
+            addr,
+            metrics,
+            tx,
+            lagging: false,
+        });
+        Some(rx)
+    }
+
+    fn unregister(&self, addr: SocketAddr) {
+        self.clients.lock().unwrap().retain(|c| c.addr != addr);
+    }
+
+    /// Sends the records to the clients that subscribed to their metric.
+    ///
+    /// `records` contains the name of the metric and the encoded record.
+    /// The clients that have no record to receive get no message.
+    pub fn broadcast(&self, records: &[(&str, String)]) {
+        // Most clients receive every record: build this message only once.
+        let mut everything: Option<Utf8Bytes> = None;
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain_mut(|client| {
+            let message = match &client.metrics {
+                None => everything
+                    .get_or_insert_with(|| payload::message(records.iter().map(|(_, r)| r.as_str())).into())
+                    .clone(),
+                Some(metrics) => {
+                    let mut selected = records
+                        .iter()
+                        .filter(|(metric, _)| metrics.contains(*metric))
+                        .map(|(_, r)| r.as_str())
+                        .peekable();
+                    if selected.peek().is_none() {
+                        return true;
+                    }
+                    payload::message(selected).into()
+                }
+            };
+            match client.tx.try_send(message) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    if !client.lagging {
+                        log::warn!(
+                            "WebSocket client {} is too slow, some measurements will not be sent to it.",
+                            client.addr
+                        );
+                        client.lagging = true;
+                    }
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+}
+
+/// Accepts the clients until `stop` is set, and then waits for the clients to be disconnected.
+pub fn run(listener: TcpListener, clients: Arc<Clients>, stop: Arc<AtomicBool>) -> anyhow::Result<()> {
+    listener.set_nonblocking(true)?;
+    let mut handles: Vec<JoinHandle<()>> = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                let clients = clients.clone();
+                let stop = stop.clone();
+                let handle = thread::Builder::new()
+                    .name(format!("websocket-client-{addr}"))
+                    .spawn(move || serve(stream, addr, &clients, &stop))?;
+                handles.retain(|h| !h.is_finished());
+                handles.push(handle);
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => log::warn!("Failed to accept a WebSocket client: {e}"),
+        }
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+    Ok(())
+}
+
+/// Performs the handshake with a client, and then sends it its messages until it disconnects.
+fn serve(stream: TcpStream, addr: SocketAddr, clients: &Clients, stop: &AtomicBool) {
+    if let Err(e) = prepare(&stream) {
+        log::warn!("Failed to configure the connection of WebSocket client {addr}: {e}");
+        return;
+    }
+
+    let mut rx = None;
+    // The type of the error is imposed by tungstenite.
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, response: Response| -> Result<Response, ErrorResponse> {
+        let metrics = parse_subscription(request.uri().query());
+        rx = clients.register(addr, metrics);
+        if rx.is_none() {
+            let mut error = ErrorResponse::new(Some(String::from("too many clients")));
+            *error.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            return Err(error);
+        }
+        Ok(response)
+    };
+    // The error keeps the callback, which borrows `rx`.
+    let socket = tungstenite::accept_hdr(stream, callback).map_err(|e| e.to_string());
+    let (mut socket, rx) = match (socket, rx) {
+        (Ok(socket), Some(rx)) => (socket, rx),
+        (Err(e), _) => {
+            log::debug!("WebSocket handshake with {addr} failed: {e}");
+            clients.unregister(addr);
+            return;
+        }
+        (Ok(_), None) => unreachable!("the handshake succeeds only if the client is registered"),
+    };
+    log::debug!("WebSocket client {addr} connected.");
+
+    // The reads time out almost immediately: they are only used to answer the pings
+    // and to detect the disconnection of the client.
+    if let Err(e) = socket.get_ref().set_read_timeout(Some(Duration::from_millis(1))) {
+        log::warn!("Failed to configure the connection of WebSocket client {addr}: {e}");
+        clients.unregister(addr);
+        return;
+    }
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            let _ = socket.close(None);
+            let _ = socket.flush();
+            break;
+        }
+        let res = match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(message) => socket.send(Message::Text(message)),
+            Err(RecvTimeoutError::Timeout) => Ok(()),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        // Read the incoming frames to answer the pings and to detect the disconnection of the client.
+        let res = match res {
+            Ok(()) => match socket.read() {
+                Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    Ok(())
+                }
+                res => res.map(|_| ()),
+            },
+            err => err,
+        };
+        match res {
+            Ok(()) => (),
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => break,
+            Err(e) => {
+                log::debug!("WebSocket connection with {addr} closed: {e}");
+                break;
+            }
+        }
+    }
+    clients.unregister(addr);
+    log::debug!("WebSocket client {addr} disconnected.");
+}
+
+fn prepare(stream: &TcpStream) -> std::io::Result<()> {
+    // The accepted stream inherits the non-blocking mode of the listener on some platforms.
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))
+}
+
+/// Parses the query of the request of a client, such as `metrics=a,b`,
+/// and returns the metrics that the client subscribed to, or `None` for all the metrics.
+fn parse_subscription(query: Option<&str>) -> Option<HashSet<String>> {
+    let mut metrics: Option<HashSet<String>> = None;
+    for pair in query?.split('&') {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if key == "metrics" {
+            let names = percent_decode(value);
+            metrics
+                .get_or_insert_default()
+                .extend(names.split(',').filter(|n| !n.is_empty()).map(String::from));
+        }
+    }
+    metrics
+}
+
+/// Decodes the `%XX` sequences of a query parameter, such as `%2C` for a comma.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{parse_subscription, percent_decode};
+
+    fn set(names: &[&str]) -> Option<HashSet<String>> {
+        Some(names.iter().map(|n| n.to_string()).collect())
+    }
+
+    #[test]
+    fn subscription() {
+        assert_eq!(parse_subscription(None), None);
+        assert_eq!(parse_subscription(Some("")), None);
+        assert_eq!(parse_subscription(Some("other=1")), None);
+        assert_eq!(parse_subscription(Some("metrics=a,b")), set(&["a", "b"]));
+        assert_eq!(parse_subscription(Some("metrics=a%2Cb&other=1")), set(&["a", "b"]));
+        assert_eq!(parse_subscription(Some("metrics=a&metrics=b,")), set(&["a", "b"]));
+        // subscribed to nothing
+        assert_eq!(parse_subscription(Some("metrics=")), set(&[]));
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("rapl_consumed_energy"), "rapl_consumed_energy");
+        assert_eq!(percent_decode("a%2cb%2C"), "a,b,");
+        assert_eq!(percent_decode("a+b%20c"), "a b c");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
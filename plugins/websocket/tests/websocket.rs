@@ -0,0 +1,160 @@
+use std::{
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use alumet::test::golden::{SampleMeasurements, assert_golden};
+use plugin_websocket::{Config, WebSocketPlugin};
+use pretty_assertions::assert_eq;
+use tungstenite::{Message, WebSocket};
+
+/// Returns an address on which the server can listen.
+fn free_address() -> anyhow::Result<String> {
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    Ok(format!("127.0.0.1:{port}"))
+}
+
+/// Connects to the server with the given query.
+fn connect(address: &str, query: &str) -> anyhow::Result<WebSocket<TcpStream>> {
+    let stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let url = format!("ws://{address}/{query}");
+    let (socket, _) = tungstenite::client(url, stream).map_err(|e| match e {
+        tungstenite::HandshakeError::Failure(e) => e,
+        tungstenite::HandshakeError::Interrupted(_) => panic!("the stream should be blocking"),
+    })?;
+    Ok(socket)
+}
+
+/// Reads the next message, which must be a text message, and returns the metrics of its records.
+fn read_metrics(socket: &mut WebSocket<TcpStream>) -> anyhow::Result<Vec<String>> {
+    let text = read_text(socket)?;
+    let records: Vec<serde_json::Value> = serde_json::from_str(&text)?;
+    Ok(records
+        .iter()
+        .map(|r| r["metric"].as_str().unwrap().to_owned())
+        .collect())
+}
+
+fn read_text(socket: &mut WebSocket<TcpStream>) -> anyhow::Result<String> {
+    match socket.read()? {
+        Message::Text(text) => Ok(text.to_string()),
+        other => Err(anyhow::anyhow!("unexpected message: {other:?}")),
+    }
+}
+
+#[test]
+fn stream() -> anyhow::Result<()> {
+    let address = free_address()?;
+    let (mut harness, sample) = SampleMeasurements::start::<WebSocketPlugin>(Config {
+        address: address.clone(),
+        ..Config::default()
+    })?;
+    let mut output = harness.output("out")?;
+
+    let mut all = connect(&address, "")?;
+    let mut counts = connect(&address, "?metrics=sample_count")?;
+    output.write(&sample.measurements()).unwrap();
+
+    // one record per line, to make the golden file readable
+    let message = read_text(&mut all)?.replace("},{", "},\n{") + "\n";
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sample.json"),
+        message,
+    );
+    assert_eq!(read_metrics(&mut counts)?, vec!["sample_count", "sample_count"]);
+
+    // a client that disconnects does not prevent the others from receiving the measurements
+    all.close(None)?;
+    output.write(&sample.measurements()).unwrap();
+    assert_eq!(read_metrics(&mut counts)?, vec!["sample_count", "sample_count"]);
+
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn selected_metrics() -> anyhow::Result<()> {
+    let address = free_address()?;
+    let (mut harness, sample) = SampleMeasurements::start::<WebSocketPlugin>(Config {
+        address: address.clone(),
+        metrics: vec![String::from("sample_energy")],
+        ..Config::default()
+    })?;
+    let mut output = harness.output("out")?;
+
+    // the subscription is restricted to the metrics of the config
+    let mut client = connect(&address, "?metrics=sample_count%2Csample_energy")?;
+    // a client that receives nothing
+    let mut nothing = connect(&address, "?metrics=sample_count")?;
+    nothing.get_ref().set_read_timeout(Some(Duration::from_millis(300)))?;
+
+    output.write(&sample.measurements()).unwrap();
+    assert_eq!(
+        read_metrics(&mut client)?,
+        vec!["sample_energy", "sample_energy", "sample_energy"]
+    );
+    assert!(matches!(nothing.read(), Err(tungstenite::Error::Io(_))));
+
+    output.finish()?;
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn too_many_clients() -> anyhow::Result<()> {
+    let address = free_address()?;
+    let (harness, _) = SampleMeasurements::start::<WebSocketPlugin>(Config {
+        address: address.clone(),
+        max_clients: 1,
+        ..Config::default()
+    })?;
+
+    let mut first = connect(&address, "")?;
+    let err = connect(&address, "").expect_err("the second client should be rejected");
+    match err.downcast_ref::<tungstenite::Error>() {
+        Some(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 503),
+        _ => panic!("unexpected error: {err}"),
+    }
+
+    // the place is available again once the first client is gone
+    first.close(None)?;
+    while !matches!(first.read(), Err(tungstenite::Error::ConnectionClosed)) {}
+    let mut retries = 0;
+    while connect(&address, "").is_err() {
+        retries += 1;
+        assert!(retries < 50, "the first client should have been forgotten");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    harness.stop()?;
+    Ok(())
+}
+
+#[test]
+fn close_on_stop() -> anyhow::Result<()> {
+    let address = free_address()?;
+    let (harness, _) = SampleMeasurements::start::<WebSocketPlugin>(Config {
+        address: address.clone(),
+        ..Config::default()
+    })?;
+
+    let mut client = connect(&address, "")?;
+    harness.stop()?;
+    assert!(matches!(client.read()?, Message::Close(_)));
+    // the server does not accept new clients anymore
+    assert!(connect(&address, "").is_err());
+    Ok(())
+}
+
+#[test]
+fn address_in_use() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let res = SampleMeasurements::start::<WebSocketPlugin>(Config {
+        address: listener.local_addr()?.to_string(),
+        ..Config::default()
+    });
+    assert!(res.is_err());
+    Ok(())
+}
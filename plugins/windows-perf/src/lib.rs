@@ -0,0 +1,109 @@
+//! The plugin is empty on the other platforms, so that the workspace can be built everywhere.
+#![cfg(target_os = "windows")]
+
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use alumet::{
+    pipeline::elements::source::trigger::TriggerSpec,
+    plugin::{
+        AlumetPluginStart, ConfigTable,
+        capability::Capability,
+        rust::{AlumetPlugin, deserialize_config, serialize_config},
+    },
+};
+
+mod metrics;
+mod pdh;
+mod source;
+
+const PROCESSOR_UTILITY: &str = r"\Processor Information(*)\% Processor Utility";
+const POWER_METER: &str = r"\Power Meter(*)\Power";
+
+pub struct WindowsPerfPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for WindowsPerfPlugin {
+    fn name() -> &'static str {
+        "windows-perf"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(Vec::new())
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config = deserialize_config(config)?;
+        Ok(Box::new(WindowsPerfPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let mut query = pdh::Query::open().context("failed to open a PDH query")?;
+        let processor = query
+            .add_counter(PROCESSOR_UTILITY)
+            .context("the processor counters are not available")?;
+        let power = if self.config.power_meter {
+            match query.add_counter(POWER_METER) {
+                Ok(counter) => Some(counter),
+                Err(e) => {
+                    log::warn!(
+                        "The Power Meter counters are not available on this machine, the power will not be measured: {e:#}"
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        // The processor utility is a rate, which is computed from two collections.
+        query.collect().context("failed to collect the performance counters")?;
+
+        let metrics = metrics::Metrics::new(alumet)?;
+        let source = source::PerfCounterSource::new(query, processor, power, metrics);
+        let trigger = TriggerSpec::builder(self.config.poll_interval)
+            .flush_interval(self.config.flush_interval)
+            .build()?;
+        alumet.add_source("counters", Box::new(source), trigger)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// Initial interval between two measurements.
+    #[serde(with = "humantime_serde")]
+    poll_interval: Duration,
+
+    /// Initial interval between two measurement flushes.
+    #[serde(with = "humantime_serde")]
+    flush_interval: Duration,
+
+    /// `true` to read the power from the Power Meter counters (Energy Estimation Engine).
+    power_meter: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            flush_interval: Duration::from_secs(5),
+            power_meter: true,
+        }
+    }
+}
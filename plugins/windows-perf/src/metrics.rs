@@ -0,0 +1,25 @@
+use alumet::{metrics::TypedMetricId, plugin::AlumetPluginStart, units::Unit};
+
+pub struct Metrics {
+    /// Processor utility, relative to the nominal frequency.
+    pub cpu_utility: TypedMetricId<f64>,
+    /// Power reported by the power meters, or estimated by the Energy Estimation Engine.
+    pub power: TypedMetricId<f64>,
+}
+
+impl Metrics {
+    pub fn new(alumet: &mut AlumetPluginStart) -> anyhow::Result<Self> {
+        Ok(Self {
+            cpu_utility: alumet.create_metric(
+                "windows_cpu_utility",
+                Unit::Percent,
+                "Amount of work done by the processor, relative to its nominal frequency (can exceed 100% when boosted)",
+            )?,
+            power: alumet.create_metric(
+                "windows_power",
+                Unit::Watt,
+                "Power reported by the Power Meter performance counters (Energy Estimation Engine)",
+            )?,
+        })
+    }
+}
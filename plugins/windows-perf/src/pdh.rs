@@ -0,0 +1,147 @@
+//! Safe wrapper around the Performance Data Helper (PDH) library.
+
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    ptr,
+};
+
+use anyhow::anyhow;
+use windows_sys::Win32::System::Performance::{
+    PDH_CSTATUS_NEW_DATA, PDH_CSTATUS_VALID_DATA, PDH_FMT_COUNTERVALUE_ITEM_W, PDH_FMT_DOUBLE, PDH_HCOUNTER,
+    PDH_HQUERY, PDH_MORE_DATA, PdhAddEnglishCounterW, PdhCloseQuery, PdhCollectQueryData, PdhGetFormattedCounterArrayW,
+    PdhOpenQueryW,
+};
+
+/// Do not cap the percentages to 100, the processor utility exceeds 100% when the frequency is boosted.
+const PDH_FMT_NOCAP100: u32 = 0x8000;
+const ERROR_SUCCESS: u32 = 0;
+
+/// A PDH query, which collects the values of several counters at once.
+pub struct Query {
+    handle: PDH_HQUERY,
+}
+
+/// A counter of a [`Query`], whose path can contain a wildcard instance, like `\Processor Information(*)\% Processor Utility`.
+pub struct Counter {
+    handle: PDH_HCOUNTER,
+    path: String,
+}
+
+// SAFETY: the PDH handles are not bound to the thread that created them.
+unsafe impl Send for Query {}
+unsafe impl Send for Counter {}
+
+impl Query {
+    /// Opens a query on the real-time data of the local machine.
+    pub fn open() -> anyhow::Result<Self> {
+        let mut handle = ptr::null_mut();
+        // SAFETY: a null data source means "real-time data"
+        check(unsafe { PdhOpenQueryW(ptr::null(), 0, &mut handle) }, "PdhOpenQueryW")?;
+        Ok(Self { handle })
+    }
+
+    /// Adds a counter to the query, with its English (locale-independent) path.
+    pub fn add_counter(&mut self, path: &str) -> anyhow::Result<Counter> {
+        let wide_path: Vec<u16> = OsStr::new(path).encode_wide().chain([0]).collect();
+        let mut handle = ptr::null_mut();
+        // SAFETY: the path is a nul-terminated wide string
+        let status = unsafe { PdhAddEnglishCounterW(self.handle, wide_path.as_ptr(), 0, &mut handle) };
+        check(status, path)?;
+        Ok(Counter {
+            handle,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Collects the current values of all the counters of the query.
+    ///
+    /// The counters that are rates, like the processor utility, need two collections to compute their value.
+    pub fn collect(&mut self) -> anyhow::Result<()> {
+        // SAFETY: the query handle is valid until drop
+        check(unsafe { PdhCollectQueryData(self.handle) }, "PdhCollectQueryData")
+    }
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        // SAFETY: the handle is valid, and closing the query also closes its counters
+        unsafe { PdhCloseQuery(self.handle) };
+    }
+}
+
+impl Counter {
+    /// Returns the value of each instance of the counter, computed from the last collections of its query.
+    pub fn values(&self) -> anyhow::Result<Vec<(String, f64)>> {
+        let format = PDH_FMT_DOUBLE | PDH_FMT_NOCAP100;
+        let mut buffer_size = 0u32;
+        let mut item_count = 0u32;
+        // SAFETY: a null buffer is allowed to query the required size
+        let status = unsafe {
+            PdhGetFormattedCounterArrayW(self.handle, format, &mut buffer_size, &mut item_count, ptr::null_mut())
+        };
+        if status != PDH_MORE_DATA {
+            check(status, &self.path)?;
+            return Ok(Vec::new());
+        }
+
+        // The buffer contains the items, followed by their names.
+        let item_size = size_of::<PDH_FMT_COUNTERVALUE_ITEM_W>();
+        let mut buffer: Vec<PDH_FMT_COUNTERVALUE_ITEM_W> =
+            Vec::with_capacity((buffer_size as usize).div_ceil(item_size));
+        // SAFETY: the buffer is large enough, as required by the previous call
+        let status = unsafe {
+            PdhGetFormattedCounterArrayW(
+                self.handle,
+                format,
+                &mut buffer_size,
+                &mut item_count,
+                buffer.as_mut_ptr(),
+            )
+        };
+        check(status, &self.path)?;
+        // SAFETY: PDH has initialized `item_count` items
+        unsafe { buffer.set_len(item_count as usize) };
+
+        let mut values = Vec::with_capacity(buffer.len());
+        for item in &buffer {
+            let status = item.FmtValue.CStatus;
+            if status != PDH_CSTATUS_VALID_DATA && status != PDH_CSTATUS_NEW_DATA {
+                // no value for this instance yet, or anymore
+                continue;
+            }
+            // SAFETY: the name is a nul-terminated wide string in the buffer
+            let name = unsafe { wide_to_string(item.szName) };
+            // SAFETY: the value has been formatted as a double
+            let value = unsafe { item.FmtValue.Anonymous.doubleValue };
+            values.push((name, value));
+        }
+        Ok(values)
+    }
+}
+
+/// Converts a nul-terminated wide string to a `String`.
+///
+/// # Safety
+/// `s` must be a valid, nul-terminated wide string.
+unsafe fn wide_to_string(s: *const u16) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    let mut len = 0;
+    // SAFETY: the string is nul-terminated
+    while unsafe { *s.add(len) } != 0 {
+        len += 1;
+    }
+    // SAFETY: the `len` first characters are initialized
+    let wide = unsafe { std::slice::from_raw_parts(s, len) };
+    OsString::from_wide(wide).to_string_lossy().into_owned()
+}
+
+fn check(status: u32, what: &str) -> anyhow::Result<()> {
+    if status == ERROR_SUCCESS {
+        Ok(())
+    } else {
+        Err(anyhow!("{what} failed with the PDH status {status:#010x}"))
+    }
+}
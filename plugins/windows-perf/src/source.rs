@@ -0,0 +1,101 @@
+use anyhow::Context;
+
+use alumet::{
+    measurement::{MeasurementAccumulator, MeasurementPoint, Timestamp},
+    pipeline::{Source, elements::error::PollError},
+    resources::{Resource, ResourceConsumer},
+};
+
+use crate::{
+    metrics::Metrics,
+    pdh::{Counter, Query},
+};
+
+/// Number of logical processors in a processor group.
+const GROUP_SIZE: u32 = 64;
+
+/// Measurement source that reads performance counters with PDH.
+pub struct PerfCounterSource {
+    query: Query,
+    processor: Counter,
+    power: Option<Counter>,
+    metrics: Metrics,
+}
+
+impl PerfCounterSource {
+    pub fn new(query: Query, processor: Counter, power: Option<Counter>, metrics: Metrics) -> Self {
+        Self {
+            query,
+            processor,
+            power,
+            metrics,
+        }
+    }
+}
+
+impl Source for PerfCounterSource {
+    fn poll(&mut self, measurements: &mut MeasurementAccumulator, t: Timestamp) -> Result<(), PollError> {
+        self.query
+            .collect()
+            .context("failed to collect the performance counters")
+            .map_err(PollError::CanRetry)?;
+
+        for (instance, utility) in self.processor.values()? {
+            if let Some(resource) = processor_resource(&instance) {
+                measurements.push(MeasurementPoint::new(
+                    t,
+                    self.metrics.cpu_utility,
+                    resource,
+                    ResourceConsumer::LocalMachine,
+                    utility,
+                ));
+            }
+        }
+
+        if let Some(power) = &self.power {
+            for (meter, milliwatts) in power.values()? {
+                measurements.push(
+                    MeasurementPoint::new(
+                        t,
+                        self.metrics.power,
+                        Resource::LocalMachine,
+                        ResourceConsumer::LocalMachine,
+                        milliwatts / 1000.0,
+                    )
+                    .with_attr("meter", meter),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the resource that corresponds to an instance of the "Processor Information" counter set.
+///
+/// The instances are `_Total`, `<group>,_Total` and `<group>,<processor>`.
+/// The totals of the groups are ignored, they are redundant with the global total on most machines.
+fn processor_resource(instance: &str) -> Option<Resource> {
+    if instance == "_Total" {
+        return Some(Resource::LocalMachine);
+    }
+    let (group, processor) = instance.split_once(',')?;
+    let group: u32 = group.parse().ok()?;
+    let processor: u32 = processor.parse().ok()?;
+    Some(Resource::CpuCore {
+        id: group * GROUP_SIZE + processor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn processor_instances() {
+        assert_eq!(processor_resource("_Total"), Some(Resource::LocalMachine));
+        assert_eq!(processor_resource("0,_Total"), None);
+        assert_eq!(processor_resource("0,3"), Some(Resource::CpuCore { id: 3 }));
+        assert_eq!(processor_resource("1,2"), Some(Resource::CpuCore { id: 66 }));
+        assert_eq!(processor_resource("invalid"), None);
+    }
+}
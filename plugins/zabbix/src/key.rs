@@ -0,0 +1,162 @@
+//! Templates of the item keys, such as `alumet.{metric}[{resource_kind},{resource_id}]`.
+
+use alumet::{
+    measurement::MeasurementPoint,
+    metrics::Metric,
+    template::{MeasurementPlaceholder, Part, Template},
+};
+use anyhow::{Context, anyhow};
+
+/// A parsed key template.
+///
+/// A Zabbix key is made of a name, optionally followed by parameters between brackets.
+#[derive(Debug, PartialEq)]
+pub struct KeyTemplate {
+    name: Template<Placeholder>,
+    params: Option<Vec<Template<Placeholder>>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Placeholder {
+    /// The hostname, which is not checked like the text of the template.
+    Hostname(String),
+    Measurement(MeasurementPlaceholder),
+}
+
+impl KeyTemplate {
+    /// Parses a template.
+    ///
+    /// The placeholders are replaced by the corresponding values for each measurement.
+    /// The hostname is resolved here, since it does not change.
+    pub fn parse(template: &str, hostname: &str) -> anyhow::Result<Self> {
+        let (name, params) = match template.find('[') {
+            Some(start) => {
+                let params = template[start + 1..]
+                    .strip_suffix(']')
+                    .ok_or_else(|| anyhow!("the parameters of key template {template:?} must end with ']'"))?;
+                let params = params
+                    .split(',')
+                    .map(|p| parse_parts(p, hostname, template))
+                    .collect::<anyhow::Result<_>>()?;
+                (&template[..start], Some(params))
+            }
+            None => (template, None),
+        };
+        let name = parse_parts(name, hostname, template)?;
+        if name.is_empty() {
+            return Err(anyhow!("the name of key template {template:?} is empty"));
+        }
+        if name.literal().chars().any(|c| !is_name_char(c)) {
+            return Err(anyhow!(
+                "invalid character in the name of key template {template:?}, only 0-9a-zA-Z_-. are allowed"
+            ));
+        }
+        Ok(Self { name, params })
+    }
+
+    /// Returns the key of a measurement.
+    pub fn render(&self, m: &MeasurementPoint, metric: &Metric) -> String {
+        let mut res = self
+            .name
+            .render(|p, res| res.push_str(&sanitize_name(&render_placeholder(p, m, metric))));
+        if let Some(params) = &self.params {
+            res.push('[');
+            for (i, param) in params.iter().enumerate() {
+                if i > 0 {
+                    res.push(',');
+                }
+                let value = param.render(|p, res| res.push_str(&render_placeholder(p, m, metric)));
+                push_param(&mut res, &value);
+            }
+            res.push(']');
+        }
+        res
+    }
+}
+
+fn parse_parts(s: &str, hostname: &str, template: &str) -> anyhow::Result<Template<Placeholder>> {
+    Template::parse(s, |name| match name {
+        "hostname" => Some(Part::Placeholder(Placeholder::Hostname(hostname.to_owned()))),
+        _ => MeasurementPlaceholder::from_name(name).map(|p| Part::Placeholder(Placeholder::Measurement(p))),
+    })
+    .with_context(|| format!("invalid key template {template:?}"))
+}
+
+fn render_placeholder(placeholder: &Placeholder, m: &MeasurementPoint, metric: &Metric) -> String {
+    match placeholder {
+        Placeholder::Hostname(hostname) => hostname.clone(),
+        Placeholder::Measurement(p) => p.value(m, metric),
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
+
+/// Replaces the characters that are not allowed in the name of a key.
+fn sanitize_name(value: &str) -> String {
+    value.chars().map(|c| if is_name_char(c) { c } else { '_' }).collect()
+}
+
+/// Appends a parameter of a key, quoted if it contains special characters.
+fn push_param(res: &mut String, value: &str) {
+    if value.contains([',', ']', '[', '"']) || value.starts_with(' ') {
+        res.push('"');
+        res.push_str(&value.replace('"', "\\\""));
+        res.push('"');
+    } else {
+        res.push_str(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alumet::{
+        resources::ResourceConsumer,
+        test::fixture::{metric, point},
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::KeyTemplate;
+
+    #[test]
+    fn render() {
+        let template = KeyTemplate::parse("alumet.{metric}", "node-1").unwrap();
+        assert_eq!(
+            template.render(&point(ResourceConsumer::LocalMachine), &metric()),
+            "alumet.rapl_consumed_energy"
+        );
+
+        let template = KeyTemplate::parse(
+            "alumet.{metric}[{resource_kind},cpu{resource_id},{consumer_kind},{consumer_id}]",
+            "node-1",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&point(ResourceConsumer::LocalMachine), &metric()),
+            "alumet.rapl_consumed_energy[cpu_package,cpu1,local_machine,]"
+        );
+        let consumer = ResourceConsumer::ControlGroup {
+            path: "/kubepods/pod[a,b]".into(),
+        };
+        assert_eq!(
+            template.render(&point(consumer), &metric()),
+            "alumet.rapl_consumed_energy[cpu_package,cpu1,cgroup,\"/kubepods/pod[a,b]\"]"
+        );
+
+        let template = KeyTemplate::parse("{hostname}.{consumer_id}", "node 1").unwrap();
+        let consumer = ResourceConsumer::ControlGroup {
+            path: "/system.slice".into(),
+        };
+        assert_eq!(template.render(&point(consumer), &metric()), "node_1._system.slice");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(KeyTemplate::parse("alumet.{host}", "node").is_err());
+        assert!(KeyTemplate::parse("alumet.{metric", "node").is_err());
+        assert!(KeyTemplate::parse("alumet.{metric}[{resource_id}", "node").is_err());
+        assert!(KeyTemplate::parse("alumet/{metric}", "node").is_err());
+        assert!(KeyTemplate::parse("[{metric}]", "node").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+mod key;
+mod output;
+mod protocol;
+
+use std::time::Duration;
+
+use alumet::plugin::capability::Capability;
+use alumet::plugin::rust::{deserialize_config, serialize_config};
+use alumet::plugin::{AlumetPluginStart, ConfigTable, rust::AlumetPlugin};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::key::KeyTemplate;
+use crate::output::ZabbixOutput;
+
+pub struct ZabbixPlugin {
+    config: Config,
+}
+
+impl AlumetPlugin for ZabbixPlugin {
+    fn name() -> &'static str {
+        "zabbix"
+    }
+
+    fn version() -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn capabilities() -> Option<Vec<Capability>> {
+        Some(vec![Capability::Network])
+    }
+
+    fn default_config() -> anyhow::Result<Option<ConfigTable>> {
+        Ok(Some(serialize_config(Config::default())?))
+    }
+
+    fn init(config: ConfigTable) -> anyhow::Result<Box<Self>> {
+        let config: Config = deserialize_config(config)?;
+        anyhow::ensure!(config.batch_size > 0, "batch_size must be greater than zero");
+        Ok(Box::new(ZabbixPlugin { config }))
+    }
+
+    fn start(&mut self, alumet: &mut AlumetPluginStart) -> anyhow::Result<()> {
+        let config = &self.config;
+        let hostname = match &config.hostname {
+            Some(hostname) => hostname.to_owned(),
+            None => hostname::get()
+                .context("No hostname specified in the config, and unable to retrieve the hostname of the machine.")?
+                .to_string_lossy()
+                .to_string(),
+        };
+        let key = KeyTemplate::parse(&config.key, &hostname)?;
+        let output = ZabbixOutput::new(
+            format!("{}:{}", config.server, config.port),
+            config.timeout,
+            hostname,
+            key,
+            config.batch_size,
+        );
+        alumet.add_blocking_output("out", Box::new(output))?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Address of the Zabbix server or proxy.
+    pub server: String,
+    pub port: u16,
+    /// Name of the host in Zabbix (the "host name", not the "visible name"). Defaults to the hostname.
+    pub hostname: Option<String>,
+    /// Template of the key of the item of each measurement.
+    ///
+    /// See [`MeasurementPlaceholder`](alumet::template::MeasurementPlaceholder) for the available placeholders,
+    /// in addition to `{hostname}`.
+    pub key: String,
+    /// Maximum number of values per request.
+    pub batch_size: usize,
+    /// Timeout of the connection and of each request.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: String::from("localhost"),
+            port: 10051,
+            hostname: None,
+            key: String::from("alumet.{metric}[{resource_kind},{resource_id},{consumer_kind},{consumer_id}]"),
+            batch_size: 250,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
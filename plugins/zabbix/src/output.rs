@@ -0,0 +1,108 @@
+use std::{
+    io::Write,
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use alumet::{
+    measurement::{MeasurementBuffer, WrappedMeasurementValue},
+    pipeline::{
+        Output,
+        elements::{
+            error::WriteError,
+            output::{OutputContext, error::WriteRetry},
+        },
+    },
+};
+use anyhow::{Context, anyhow};
+
+use crate::{
+    key::KeyTemplate,
+    protocol::{self, Summary, Value},
+};
+
+pub struct ZabbixOutput {
+    /// Address of the Zabbix server or proxy, such as `localhost:10051`.
+    address: String,
+    timeout: Duration,
+    /// Name of the host in Zabbix.
+    host: String,
+    key: KeyTemplate,
+    batch_size: usize,
+    /// Whether the server has already rejected some values.
+    warned_failed: bool,
+}
+
+impl ZabbixOutput {
+    pub fn new(address: String, timeout: Duration, host: String, key: KeyTemplate, batch_size: usize) -> Self {
+        Self {
+            address,
+            timeout,
+            host,
+            key,
+            batch_size,
+            warned_failed: false,
+        }
+    }
+
+    /// Sends a batch of values and returns the result reported by the server.
+    ///
+    /// The server closes the connection after each response, hence a new connection for each batch.
+    fn send(&self, values: &[Value]) -> anyhow::Result<Summary> {
+        let addr = self
+            .address
+            .to_socket_addrs()
+            .with_context(|| format!("failed to resolve {}", self.address))?
+            .next()
+            .ok_or_else(|| anyhow!("no address found for {}", self.address))?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.timeout)
+            .with_context(|| format!("failed to connect to Zabbix at {}", self.address))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream
+            .write_all(&protocol::encode_request(values))
+            .with_context(|| format!("failed to send the values to Zabbix at {}", self.address))?;
+        protocol::read_response(&mut stream)
+    }
+}
+
+impl Output for ZabbixOutput {
+    fn write(&mut self, measurements: &MeasurementBuffer, ctx: &OutputContext) -> Result<(), WriteError> {
+        let mut values = Vec::with_capacity(measurements.len());
+        for m in measurements.iter() {
+            let metric = ctx
+                .metrics
+                .by_id(&m.metric)
+                .with_context(|| format!("Unknown metric {:?}", m.metric))?;
+            let (clock, ns) = m.timestamp.to_unix_timestamp();
+            values.push(Value {
+                host: self.host.clone(),
+                key: self.key.render(m, metric),
+                value: match m.value {
+                    WrappedMeasurementValue::F64(v) => v.to_string(),
+                    WrappedMeasurementValue::U64(v) => v.to_string(),
+                },
+                clock,
+                ns,
+            });
+        }
+
+        for batch in values.chunks(self.batch_size) {
+            let summary = self.send(batch).retry_write()?;
+            if summary.failed > 0 {
+                // The values of the items that do not exist, or are not trapper items, are rejected.
+                let msg = format!(
+                    "Zabbix rejected {} of {} values, check that the items exist on host {:?} with the type \"Zabbix trapper\"",
+                    summary.failed, summary.total, self.host
+                );
+                if self.warned_failed {
+                    log::debug!("{msg}");
+                } else {
+                    log::warn!("{msg} (this warning is only shown once)");
+                    self.warned_failed = true;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! The Zabbix sender protocol, used by `zabbix_sender` to push values to trapper items.
+//!
+//! See <https://www.zabbix.com/documentation/current/en/manual/appendix/protocols/zabbix_sender>
+//! and <https://www.zabbix.com/documentation/current/en/manual/appendix/protocols/header_datalen>.
+
+use std::io::Read;
+
+use anyhow::{Context, anyhow};
+use serde::{Deserialize, Serialize};
+
+const MAGIC: &[u8; 4] = b"ZBXD";
+/// The packet uses the Zabbix protocol.
+const FLAG_PROTOCOL: u8 = 0x01;
+/// The packet is compressed with zlib.
+const FLAG_COMPRESSED: u8 = 0x02;
+/// The lengths are on 8 bytes instead of 4.
+const FLAG_LARGE: u8 = 0x04;
+
+/// Maximum size of a response, to protect against garbage.
+const MAX_RESPONSE_LEN: u64 = 1024 * 1024;
+
+/// A value of an item.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Value {
+    /// Name of the host in Zabbix.
+    pub host: String,
+    pub key: String,
+    pub value: String,
+    /// Timestamp of the value: seconds and nanoseconds.
+    pub clock: u64,
+    pub ns: u32,
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    request: &'static str,
+    data: &'a [Value],
+}
+
+#[derive(Deserialize)]
+struct Response {
+    response: String,
+    #[serde(default)]
+    info: String,
+}
+
+/// Result of a request, as reported by the server.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub processed: u64,
+    pub failed: u64,
+    pub total: u64,
+}
+
+/// Encodes a `sender data` request, header included.
+pub fn encode_request(values: &[Value]) -> Vec<u8> {
+    let body = serde_json::to_vec(&Request {
+        request: "sender data",
+        data: values,
+    })
+    .expect("a request should always be serializable");
+    let mut packet = Vec::with_capacity(13 + body.len());
+    packet.extend_from_slice(MAGIC);
+    packet.push(FLAG_PROTOCOL);
+    packet.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    // reserved, or uncompressed length
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.extend_from_slice(&body);
+    packet
+}
+
+/// Reads and decodes the response of the server.
+pub fn read_response(reader: &mut impl Read) -> anyhow::Result<Summary> {
+    let mut header = [0u8; 5];
+    reader
+        .read_exact(&mut header)
+        .context("failed to read the header of the response")?;
+    if &header[..4] != MAGIC {
+        return Err(anyhow!("invalid response: the header does not start with ZBXD"));
+    }
+    let flags = header[4];
+    if flags & FLAG_COMPRESSED != 0 {
+        return Err(anyhow!("compressed responses are not supported"));
+    }
+    let len = if flags & FLAG_LARGE != 0 {
+        let mut lengths = [0u8; 16];
+        reader.read_exact(&mut lengths)?;
+        u64::from_le_bytes(lengths[..8].try_into().unwrap())
+    } else {
+        let mut lengths = [0u8; 8];
+        reader.read_exact(&mut lengths)?;
+        u64::from(u32::from_le_bytes(lengths[..4].try_into().unwrap()))
+    };
+    if len > MAX_RESPONSE_LEN {
+        return Err(anyhow!("invalid response: too long ({len} bytes)"));
+    }
+    let mut body = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut body)
+        .context("failed to read the body of the response")?;
+
+    let response: Response = serde_json::from_slice(&body)
+        .with_context(|| format!("invalid response: {}", String::from_utf8_lossy(&body)))?;
+    if response.response != "success" {
+        return Err(anyhow!("the server refused the values: {}", response.info));
+    }
+    parse_info(&response.info).ok_or_else(|| anyhow!("invalid response info: {:?}", response.info))
+}
+
+/// Parses the `info` of a response, such as `processed: 3; failed: 2; total: 5; seconds spent: 0.000046`.
+fn parse_info(info: &str) -> Option<Summary> {
+    let mut processed = None;
+    let mut failed = None;
+    let mut total = None;
+    for field in info.split(';') {
+        let (name, value) = field.split_once(':')?;
+        let value = value.trim();
+        match name.trim() {
+            "processed" => processed = value.parse().ok(),
+            "failed" => failed = value.parse().ok(),
+            "total" => total = value.parse().ok(),
+            _ => (),
+        }
+    }
+    Some(Summary {
+        processed: processed?,
+        failed: failed?,
+        total: total?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{Summary, Value, encode_request, read_response};
+
+    #[test]
+    fn encode() {
+        let values = [Value {
+            host: String::from("node-1"),
+            key: String::from("alumet.energy"),
+            value: String::from("12.5"),
+            clock: 1_700_000_000,
+            ns: 500,
+        }];
+        let packet = encode_request(&values);
+        let body = r#"{"request":"sender data","data":[{"host":"node-1","key":"alumet.energy","value":"12.5","clock":1700000000,"ns":500}]}"#;
+        assert_eq!(&packet[..5], b"ZBXD\x01");
+        assert_eq!(&packet[5..9], (body.len() as u32).to_le_bytes());
+        assert_eq!(&packet[9..13], [0, 0, 0, 0]);
+        assert_eq!(std::str::from_utf8(&packet[13..]).unwrap(), body);
+    }
+
+    fn response(body: &str) -> Vec<u8> {
+        let mut packet = b"ZBXD\x01".to_vec();
+        packet.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0]);
+        packet.extend_from_slice(body.as_bytes());
+        packet
+    }
+
+    #[test]
+    fn decode() {
+        let packet =
+            response(r#"{"response":"success","info":"processed: 3; failed: 2; total: 5; seconds spent: 0.000046"}"#);
+        assert_eq!(
+            read_response(&mut packet.as_slice()).unwrap(),
+            Summary {
+                processed: 3,
+                failed: 2,
+                total: 5
+            }
+        );
+
+        let packet = response(r#"{"response":"failed","info":"host not allowed"}"#);
+        assert!(read_response(&mut packet.as_slice()).is_err());
+        assert!(read_response(&mut &b"HTTP/1.1 400 Bad Request\r\n"[..]).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    thread::{self, JoinHandle},
+};
+
+use alumet::{
+    pipeline::elements::error::WriteError,
+    plugin::rust::serialize_config,
+    test::{
+        PluginHarness,
+        golden::{SampleMeasurements, assert_golden},
+    },
+};
+use plugin_zabbix::{Config, ZabbixPlugin};
+use pretty_assertions::assert_eq;
+
+/// Accepts `connections` connections, answers each request with `response`,
+/// and returns the body of the requests.
+fn fake_zabbix(listener: TcpListener, connections: usize, response: &'static str) -> JoinHandle<Vec<String>> {
+    thread::spawn(move || {
+        let mut requests = Vec::new();
+        for _ in 0..connections {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut header = [0u8; 13];
+            stream.read_exact(&mut header).unwrap();
+            assert_eq!(&header[..5], b"ZBXD\x01");
+            let len = u32::from_le_bytes(header[5..9].try_into().unwrap());
+            let mut body = vec![0u8; len as usize];
+            stream.read_exact(&mut body).unwrap();
+            requests.push(String::from_utf8(body).unwrap());
+
+            let mut packet = b"ZBXD\x01".to_vec();
+            packet.extend_from_slice(&(response.len() as u32).to_le_bytes());
+            packet.extend_from_slice(&[0, 0, 0, 0]);
+            packet.extend_from_slice(response.as_bytes());
+            stream.write_all(&packet).unwrap();
+        }
+        requests
+    })
+}
+
+const SUCCESS: &str = r#"{"response":"success","info":"processed: 5; failed: 0; total: 5; seconds spent: 0.000046"}"#;
+
+fn config(port: u16) -> Config {
+    Config {
+        server: String::from("127.0.0.1"),
+        port,
+        hostname: Some(String::from("node-1")),
+        ..Config::default()
+    }
+}
+
+/// Writes the sample measurements with the given config.
+fn write_sample(config: Config) -> anyhow::Result<Result<(), WriteError>> {
+    let mut harness = PluginHarness::<ZabbixPlugin>::start(serialize_config(config)?)?;
+    let sample = SampleMeasurements::register(&harness)?;
+
+    let mut output = harness.output("out")?;
+    let res = output.write(&sample.measurements());
+    output.finish()?;
+    harness.stop()?;
+    Ok(res)
+}
+
+#[test]
+fn sender_data() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let config = config(listener.local_addr()?.port());
+    let zabbix = fake_zabbix(listener, 1, SUCCESS);
+    write_sample(config)?.unwrap();
+
+    let requests = zabbix.join().unwrap();
+    assert_eq!(requests.len(), 1);
+    // one value per line, to make the golden file readable
+    let values = requests[0].replace("},{", "},\n{") + "\n";
+    assert_golden(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/sender_data.txt"),
+        values,
+    );
+    Ok(())
+}
+
+#[test]
+fn batches() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let config = Config {
+        key: String::from("alumet.{metric}"),
+        batch_size: 2,
+        ..config(listener.local_addr()?.port())
+    };
+    // some values are rejected, which is not an error
+    let response = r#"{"response":"success","info":"processed: 1; failed: 1; total: 2; seconds spent: 0.000046"}"#;
+    let zabbix = fake_zabbix(listener, 3, response);
+    write_sample(config)?.unwrap();
+
+    let requests = zabbix.join().unwrap();
+    let counts: Vec<usize> = requests
+        .iter()
+        .map(|r| {
+            serde_json::from_str::<serde_json::Value>(r).unwrap()["data"]
+                .as_array()
+                .unwrap()
+                .len()
+        })
+        .collect();
+    assert_eq!(counts, vec![2, 2, 1]);
+    Ok(())
+}
+
+#[test]
+fn refused() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let config = config(listener.local_addr()?.port());
+    let zabbix = fake_zabbix(listener, 1, r#"{"response":"failed","info":"host [node-1] not found"}"#);
+    let res = write_sample(config)?;
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    zabbix.join().unwrap();
+    Ok(())
+}
+
+#[test]
+fn server_down() -> anyhow::Result<()> {
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let res = write_sample(config(port))?;
+    assert!(matches!(res, Err(WriteError::CanRetry(_))));
+    Ok(())
+}
+
+#[test]
+fn invalid_key() -> anyhow::Result<()> {
+    let config = Config {
+        key: String::from("alumet/{metric}"),
+        ..config(10051)
+    };
+    assert!(PluginHarness::<ZabbixPlugin>::start(serialize_config(config)?).is_err());
+    Ok(())
+}